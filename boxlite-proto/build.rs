@@ -1,9 +1,14 @@
 //! Build script to compile Protocol Buffer definitions.
 
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+
     tonic_build::configure()
         .build_server(true)
         .build_client(true)
+        .file_descriptor_set_path(out_dir.join("boxlite_v1_descriptor.bin"))
         .compile_protos(&["proto/boxlite/v1/service.proto"], &["proto"])?;
 
     println!("cargo:rerun-if-changed=proto/");
@@ -0,0 +1,49 @@
+//! BoxLite Proto - The guest agent's gRPC protocol
+//!
+//! This crate ships the `.proto` definition of the protocol spoken between
+//! the host runtime and the guest agent, its encoded descriptors, and the
+//! Rust code generated from it. Third parties can use the clients to drive
+//! a guest agent from their own controller, or the servers to stand in for
+//! one.
+//!
+//! # Compatibility
+//!
+//! Each protocol version is its own package and module (`boxlite.v1` is
+//! [`v1`]). Within a version, changes are wire compatible: fields, messages,
+//! enum values and RPCs are only added, never renumbered, retyped or
+//! removed, so old clients keep working against newer agents and the other
+//! way round. Anything else goes into a new version, published alongside
+//! the old one.
+//!
+//! The crate follows semver for the generated Rust API. Since prost
+//! structs are exhaustive, a new field changes their struct literals, so
+//! protocol additions bump the minor version while the crate is below 1.0.
+
+/// Version 1 of the protocol (`boxlite.v1`).
+pub mod v1 {
+    #![allow(clippy::all, unused_qualifications)]
+    tonic::include_proto!("boxlite.v1");
+
+    /// The `.proto` source of this version.
+    pub const PROTO: &str = include_str!("../proto/boxlite/v1/service.proto");
+
+    /// Encoded `FileDescriptorSet` of this version, for reflection and for
+    /// generating clients in other languages.
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/boxlite_v1_descriptor.bin"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::v1;
+
+    #[test]
+    fn test_descriptor_set_describes_services() {
+        let descriptors = String::from_utf8_lossy(v1::FILE_DESCRIPTOR_SET);
+        for service in ["Container", "Guest", "Execution"] {
+            assert!(descriptors.contains(service), "missing {}", service);
+            assert!(v1::PROTO.contains(&format!("service {} {{", service)));
+        }
+        assert!(descriptors.contains("boxlite.v1"));
+    }
+}
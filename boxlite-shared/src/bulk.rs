@@ -0,0 +1,149 @@
+//! Bulk transfer protocol
+//!
+//! File contents are too large to frame through gRPC, so they travel over a
+//! dedicated connection (vsock port [`GUEST_BULK_PORT`]), one transfer per
+//! connection:
+//!
+//! 1. Host sends a request header ([`BulkRequest`])
+//! 2. For `Write`, `Drop` and `StageAgent`, host sends exactly `size` raw
+//!    bytes
+//! 3. Guest replies with a response header ([`BulkResponse`])
+//! 4. For `Read`, `Archive` and `CrashDump` answered with `Ok`, guest
+//!    sends exactly `size` raw bytes
+//!
+//! Headers are a big-endian `u32` length followed by JSON. Payload bytes are
+//! never wrapped in messages, so both ends can move them with
+//! sendfile/splice instead of copying through userspace buffers.
+//!
+//! [`GUEST_BULK_PORT`]: crate::constants::network::GUEST_BULK_PORT
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// Largest accepted header, to bound allocation on a corrupt stream.
+const MAX_HEADER_LEN: u32 = 64 * 1024;
+
+/// Transfer requested by the host.
+///
+/// Paths are absolute paths inside the container's rootfs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkRequest {
+    /// Create or replace a file in the container with `size` bytes.
+    Write {
+        container_id: String,
+        path: String,
+        mode: u32,
+        size: u64,
+    },
+    /// Read a file from the container.
+    Read { container_id: String, path: String },
+    /// Read a zstd-compressed tar of files and directories in the
+    /// container; paths that do not exist are left out.
+    Archive {
+        container_id: String,
+        paths: Vec<String>,
+    },
+    /// Land a dropped file named `name` with `size` bytes in the
+    /// container's drop directory.
+    Drop {
+        container_id: String,
+        name: String,
+        mode: u32,
+        size: u64,
+    },
+    /// Remove a file, symlink or empty directory in the container; a path
+    /// that does not exist is not an error.
+    Remove { container_id: String, path: String },
+    /// Create a directory in the container, and any missing parents.
+    MakeDir {
+        container_id: String,
+        path: String,
+        mode: u32,
+    },
+    /// Stage a new guest agent binary of `size` bytes for
+    /// Guest.UpgradeAgent, replacing one staged before.
+    StageAgent { size: u64 },
+    /// Read a zstd-compressed tar of the core dump `name` and its metadata
+    /// from the guest's crash scratch volume (see Guest.WatchCrashes); the
+    /// guest removes the dump once it is sent.
+    CrashDump { name: String },
+}
+
+/// Guest reply to a [`BulkRequest`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BulkResponse {
+    /// Bytes written (`Write`, `Drop`, `StageAgent`) or about to be sent
+    /// (`Read`, `Archive`, `CrashDump`); 0 for `Remove` and `MakeDir`.
+    Ok {
+        size: u64,
+    },
+    Error {
+        reason: String,
+    },
+}
+
+/// Write a length-prefixed JSON header.
+pub fn write_header<T: Serialize>(writer: &mut impl Write, header: &T) -> io::Result<()> {
+    let json = serde_json::to_vec(header)?;
+    let len = u32::try_from(json.len())
+        .ok()
+        .filter(|&len| len <= MAX_HEADER_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "bulk header too large"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&json)?;
+    writer.flush()
+}
+
+/// Read a length-prefixed JSON header.
+pub fn read_header<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<T> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bulk header too large ({} bytes)", len),
+        ));
+    }
+
+    let mut json = vec![0u8; len as usize];
+    reader.read_exact(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let request = BulkRequest::Write {
+            container_id: "c1".to_string(),
+            path: "/tmp/data.bin".to_string(),
+            mode: 0o644,
+            size: 1 << 32,
+        };
+
+        let mut buf = Vec::new();
+        write_header(&mut buf, &request).unwrap();
+        buf.extend_from_slice(b"payload");
+
+        let mut reader = buf.as_slice();
+        let decoded: BulkRequest = read_header(&mut reader).unwrap();
+        assert_eq!(decoded, request);
+        // The payload is left untouched for the raw copy
+        assert_eq!(reader, b"payload");
+    }
+
+    #[test]
+    fn test_oversized_header_rejected() {
+        let mut buf = (MAX_HEADER_LEN + 1).to_be_bytes().to_vec();
+        buf.extend_from_slice(b"{}");
+
+        let err = read_header::<BulkResponse>(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
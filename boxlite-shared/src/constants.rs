@@ -24,6 +24,22 @@ pub mod network {
     /// Guest connects to this port to signal it's ready to serve
     /// Port 2696 = "BOXM" on phone keypad
     pub const GUEST_READY_PORT: u32 = 2696;
+
+    /// Vsock port for bulk file transfers (see `bulk` module)
+    /// Port 2697 = "BOXN" on phone keypad
+    pub const GUEST_BULK_PORT: u32 = 2697;
+
+    /// Vsock port the guest connects to for the host's SSH agent
+    pub const GUEST_SSH_AGENT_PORT: u32 = 2698;
+
+    /// Vsock port the guest connects to for host git credentials
+    pub const GUEST_GIT_CREDENTIAL_PORT: u32 = 2699;
+
+    /// Vsock port the guest connects to for the host's X server
+    pub const GUEST_X11_PORT: u32 = 2700;
+
+    /// Vsock port the guest connects to for brokered file locks
+    pub const GUEST_LOCK_PORT: u32 = 2701;
 }
 
 /// Executor environment variable
@@ -53,4 +69,33 @@ pub mod mount_tags {
 
     /// Tag for shared container directory (contains overlayfs/ and rootfs/)
     pub const SHARED: &str = "BoxLiteShared";
+
+    /// Tag for the host directory holding the x86_64 interpreter
+    pub const X86_EMULATOR: &str = "BoxLiteX86Emulator";
+}
+
+/// Boxless engine (agent on the host, no VM)
+pub mod boxless {
+    /// Environment variable mapping virtiofs tags to host directories, as a
+    /// JSON object. The agent bind-mounts these instead of mounting virtiofs.
+    pub const SHARES_ENV: &str = "BOXLITE_BOXLESS_SHARES";
+}
+
+/// Optional guest agent features
+///
+/// The agent can be built without some of them (cargo features of the same
+/// name); Guest.Capabilities reports the ones it has.
+pub mod agent_features {
+    /// Guest interface configuration and per-exec network namespaces
+    pub const NET: &str = "net";
+
+    /// Execs on a pseudo-terminal
+    pub const PTY: &str = "pty";
+
+    /// Test-only Container.InjectFault support
+    pub const FAULT_INJECTION: &str = "fault-injection";
+
+    /// How long the agent may take from its process starting to serving
+    /// gRPC; it warns when it is slower
+    pub const READY_BUDGET_MS: u64 = 150;
 }
@@ -66,6 +66,18 @@ pub enum BoxliteError {
     /// Invalid argument provided.
     #[error("invalid argument: {0}")]
     InvalidArgument(String),
+
+    /// Host resource or volume quota would be exceeded.
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// Box spec refused by an admission policy rule.
+    #[error("policy violation: {0}")]
+    PolicyViolation(String),
+
+    /// Operation was cancelled before it finished.
+    #[error("cancelled: {0}")]
+    Cancelled(String),
 }
 
 // Implement From for common error types to enable `?` operator
@@ -3,16 +3,14 @@
 //! This crate contains common types, protocols, and utilities
 //! used by both the host-side runtime (boxlite) and guest agent.
 
+pub mod bulk;
 pub mod constants;
 pub mod errors;
 pub mod layout;
 pub mod transport;
 
-// Generated protobuf types
-pub mod generated {
-    #![allow(clippy::all, unused_qualifications)]
-    tonic::include_proto!("boxlite.v1");
-}
+// Generated protobuf types, from the published protocol crate
+pub use boxlite_proto::v1 as generated;
 
 pub use errors::{BoxliteError, BoxliteResult};
 pub use transport::Transport;
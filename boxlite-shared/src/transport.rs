@@ -9,7 +9,13 @@ use std::path::PathBuf;
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Transport {
     /// TCP transport
-    Tcp { port: u16 },
+    Tcp {
+        /// Address to connect or bind to; loopback unless set otherwise,
+        /// e.g. `0.0.0.0` for an agent debugged from outside a nested VM
+        #[serde(default = "default_tcp_host")]
+        host: String,
+        port: u16,
+    },
 
     /// Unix socket transport
     Unix { socket_path: PathBuf },
@@ -18,10 +24,22 @@ pub enum Transport {
     Vsock { port: u32 },
 }
 
+fn default_tcp_host() -> String {
+    "127.0.0.1".to_string()
+}
+
 impl Transport {
-    /// Create a TCP transport.
+    /// Create a TCP transport on loopback.
     pub fn tcp(port: u16) -> Self {
-        Self::Tcp { port }
+        Self::tcp_on(default_tcp_host(), port)
+    }
+
+    /// Create a TCP transport on `host`.
+    pub fn tcp_on(host: impl Into<String>, port: u16) -> Self {
+        Self::Tcp {
+            host: host.into(),
+            port,
+        }
     }
 
     /// Create a Unix socket transport.
@@ -37,7 +55,7 @@ impl Transport {
     /// Get the URI representation of this transport.
     pub fn to_uri(&self) -> String {
         match self {
-            Transport::Tcp { port } => format!("tcp://127.0.0.1:{}", port),
+            Transport::Tcp { host, port } => format!("tcp://{}:{}", host, port),
             Transport::Unix { socket_path } => format!("unix://{}", socket_path.display()),
             Transport::Vsock { port } => format!("vsock://{}", port),
         }
@@ -46,13 +64,16 @@ impl Transport {
     /// Parse a transport from a URI string.
     pub fn from_uri(uri: &str) -> Result<Self, String> {
         if let Some(rest) = uri.strip_prefix("tcp://") {
-            let port = rest
-                .split(':')
-                .nth(1)
-                .ok_or_else(|| format!("invalid TCP URI '{}': missing port", uri))?
+            let (host, port) = rest
+                .rsplit_once(':')
+                .ok_or_else(|| format!("invalid TCP URI '{}': missing port", uri))?;
+            let port = port
                 .parse::<u16>()
                 .map_err(|e| format!("invalid TCP port in '{}': {}", uri, e))?;
-            Ok(Self::tcp(port))
+            if host.is_empty() {
+                return Err(format!("invalid TCP URI '{}': missing host", uri));
+            }
+            Ok(Self::tcp_on(host, port))
         } else if let Some(path) = uri.strip_prefix("unix://") {
             Ok(Self::unix(PathBuf::from(path)))
         } else if let Some(port_str) = uri.strip_prefix("vsock://") {
@@ -82,3 +103,28 @@ impl std::str::FromStr for Transport {
         Self::from_uri(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_round_trip() {
+        for transport in [
+            Transport::tcp(2695),
+            Transport::tcp_on("0.0.0.0", 8080),
+            Transport::unix(PathBuf::from("/run/boxlite.sock")),
+            Transport::vsock(2695),
+        ] {
+            assert_eq!(Transport::from_uri(&transport.to_uri()), Ok(transport));
+        }
+        assert!(Transport::from_uri("tcp://:8080").is_err());
+        assert!(Transport::from_uri("tcp://127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_tcp_host_defaults_to_loopback() {
+        let transport: Transport = serde_json::from_str(r#"{"Tcp":{"port":2695}}"#).unwrap();
+        assert_eq!(transport, Transport::tcp(2695));
+    }
+}
@@ -0,0 +1,230 @@
+package main
+
+import "C"
+import (
+	"encoding/binary"
+	"net"
+	"os"
+	"sync"
+	"time"
+
+	logrus "github.com/sirupsen/logrus"
+)
+
+// Live packet captures of the guest's link, written as pcap.
+//
+// The connection to the VM is wrapped so every Ethernet frame crossing it is
+// copied to the active taps. Each tap owns a descriptor handed over by
+// gvproxy_capture_start and drains a bounded queue, so a slow reader drops
+// frames instead of stalling the guest's network.
+
+const (
+	pcapMagic        = 0xa1b2c3d4
+	pcapLinkEthernet = 1
+)
+
+// Frames queued per tap before new ones are dropped
+const tapQueueLen = 1024
+
+type capturedFrame struct {
+	at   time.Time
+	data []byte
+}
+
+type pcapTap struct {
+	file    *os.File
+	snaplen int
+	queue   chan capturedFrame
+}
+
+// tapSet is the set of captures running on one instance.
+type tapSet struct {
+	mu   sync.RWMutex
+	taps map[*pcapTap]struct{}
+}
+
+func newTapSet() *tapSet {
+	return &tapSet{taps: make(map[*pcapTap]struct{})}
+}
+
+// add starts a capture writing to file, beginning with the pcap header.
+func (s *tapSet) add(file *os.File, snaplen int) error {
+	header := make([]byte, 24)
+	binary.LittleEndian.PutUint32(header[0:], pcapMagic)
+	binary.LittleEndian.PutUint16(header[4:], 2)
+	binary.LittleEndian.PutUint16(header[6:], 4)
+	binary.LittleEndian.PutUint32(header[16:], uint32(snaplen))
+	binary.LittleEndian.PutUint32(header[20:], pcapLinkEthernet)
+	if _, err := file.Write(header); err != nil {
+		return err
+	}
+
+	tap := &pcapTap{file: file, snaplen: snaplen, queue: make(chan capturedFrame, tapQueueLen)}
+	s.mu.Lock()
+	s.taps[tap] = struct{}{}
+	s.mu.Unlock()
+	go s.drain(tap)
+	return nil
+}
+
+// drain writes queued frames until the reader goes away or the set closes.
+func (s *tapSet) drain(tap *pcapTap) {
+	defer tap.file.Close()
+	record := make([]byte, 16+tap.snaplen)
+	for frame := range tap.queue {
+		n := copy(record[16:], frame.data)
+		binary.LittleEndian.PutUint32(record[0:], uint32(frame.at.Unix()))
+		binary.LittleEndian.PutUint32(record[4:], uint32(frame.at.Nanosecond()/1000))
+		binary.LittleEndian.PutUint32(record[8:], uint32(n))
+		binary.LittleEndian.PutUint32(record[12:], uint32(len(frame.data)))
+		if _, err := tap.file.Write(record[:16+n]); err != nil {
+			logrus.WithError(err).Debug("Packet capture reader went away")
+			s.remove(tap)
+			return
+		}
+	}
+}
+
+func (s *tapSet) remove(tap *pcapTap) {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	if _, ok := s.taps[tap]; ok {
+		delete(s.taps, tap)
+		close(tap.queue)
+	}
+}
+
+func (s *tapSet) active() bool {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	return len(s.taps) > 0
+}
+
+// frame queues a copy of one Ethernet frame on every tap.
+func (s *tapSet) frame(data []byte) {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	if len(s.taps) == 0 {
+		return
+	}
+	frame := capturedFrame{at: time.Now(), data: append([]byte(nil), data...)}
+	for tap := range s.taps {
+		select {
+		case tap.queue <- frame:
+		default:
+		}
+	}
+}
+
+// closeAll stops every capture; their readers see end of file.
+func (s *tapSet) closeAll() {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	for tap := range s.taps {
+		delete(s.taps, tap)
+		close(tap.queue)
+	}
+}
+
+// frameSplitter recovers frames from one direction of a Qemu stream, where
+// each frame is preceded by its length as a 4-byte big-endian integer.
+type frameSplitter struct {
+	header    [4]byte
+	headerLen int
+	remaining int
+	keep      bool
+	frame     []byte
+}
+
+func (f *frameSplitter) feed(data []byte, taps *tapSet) {
+	for len(data) > 0 {
+		if f.remaining == 0 {
+			n := copy(f.header[f.headerLen:], data)
+			f.headerLen += n
+			data = data[n:]
+			if f.headerLen < len(f.header) {
+				return
+			}
+			f.headerLen = 0
+			f.remaining = int(binary.BigEndian.Uint32(f.header[:]))
+			// Frames are only buffered while someone is capturing
+			f.keep = taps.active()
+			f.frame = f.frame[:0]
+			continue
+		}
+		n := min(f.remaining, len(data))
+		if f.keep {
+			f.frame = append(f.frame, data[:n]...)
+		}
+		f.remaining -= n
+		data = data[n:]
+		if f.remaining == 0 && f.keep {
+			taps.frame(f.frame)
+		}
+	}
+}
+
+// tappedConn copies the frames crossing the VM connection to taps. Qemu
+// streams need splitting; each VFKit datagram is one frame.
+type tappedConn struct {
+	net.Conn
+	taps   *tapSet
+	stream bool
+	rx, tx frameSplitter
+}
+
+func newTappedConn(conn net.Conn, taps *tapSet, stream bool) *tappedConn {
+	return &tappedConn{Conn: conn, taps: taps, stream: stream}
+}
+
+func (c *tappedConn) Read(b []byte) (int, error) {
+	n, err := c.Conn.Read(b)
+	if n > 0 {
+		c.observe(&c.rx, b[:n])
+	}
+	return n, err
+}
+
+func (c *tappedConn) Write(b []byte) (int, error) {
+	n, err := c.Conn.Write(b)
+	if n > 0 {
+		c.observe(&c.tx, b[:n])
+	}
+	return n, err
+}
+
+func (c *tappedConn) observe(splitter *frameSplitter, data []byte) {
+	if c.stream {
+		splitter.feed(data, c.taps)
+	} else {
+		c.taps.frame(data)
+	}
+}
+
+//export gvproxy_capture_start
+func gvproxy_capture_start(id C.longlong, fd C.int, snaplen C.int) C.int {
+	// The descriptor is ours from here on, whatever happens
+	file := os.NewFile(uintptr(fd), "pcap")
+	if file == nil {
+		return -1
+	}
+
+	instancesMu.RLock()
+	instance, ok := instances[int64(id)]
+	instancesMu.RUnlock()
+	if !ok {
+		file.Close()
+		return -1
+	}
+
+	if snaplen <= 0 || snaplen > 65535 {
+		snaplen = 65535
+	}
+	if err := instance.taps.add(file, int(snaplen)); err != nil {
+		logrus.WithFields(logrus.Fields{"error": err, "id": id}).Warn("Failed to start packet capture")
+		file.Close()
+		return -1
+	}
+	logrus.WithFields(logrus.Fields{"id": id, "snaplen": snaplen}).Info("Packet capture started")
+	return 0
+}
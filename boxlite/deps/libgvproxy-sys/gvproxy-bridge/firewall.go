@@ -0,0 +1,456 @@
+package main
+
+import "C"
+import (
+	"encoding/binary"
+	"encoding/json"
+	"fmt"
+	"net"
+	"sync"
+	"time"
+
+	logrus "github.com/sirupsen/logrus"
+)
+
+// Per-box firewall on the guest's link.
+//
+// The connection to the VM is wrapped so every Ethernet frame crossing it is
+// checked against the box's policy before it is delivered; denied frames are
+// dropped. Frames from the VM are egress, frames to it ingress. Rules see
+// the IPv4 header and the TCP/UDP ports: the other end's address and, for
+// egress, its port, for ingress, the box's port. Connections through
+// forwarded ports come from the gateway address.
+//
+// Flows a rule let through are tracked, so their replies pass whichever way
+// they go. ARP, DHCP and DNS with the gateway are never filtered, nor is
+// anything but IPv4.
+//
+// The host alias, when set, is NATed to the host's loopback, but the box
+// may only open connections through it to the host ports boxes forward:
+// the runtime keeps that set current. This holds whatever the policy says.
+
+const (
+	etherTypeIPv4 = 0x0800
+	protoICMP     = 1
+	protoTCP      = 6
+	protoUDP      = 17
+)
+
+// Tracked flows idle this long are forgotten
+const flowIdle = 5 * time.Minute
+
+// Tracked flows kept before idle ones are swept
+const flowSweepAt = 16384
+
+// firewallPolicy matches the Rust FirewallPolicy (must stay in sync!)
+type firewallPolicy struct {
+	Egress  string         `json:"egress"`
+	Ingress string         `json:"ingress"`
+	Rules   []firewallRule `json:"rules"`
+}
+
+type firewallRule struct {
+	Direction string     `json:"direction"`
+	Action    string     `json:"action"`
+	Protocol  string     `json:"protocol"`
+	Ports     *[2]uint16 `json:"ports"`
+	CIDR      *string    `json:"cidr"`
+}
+
+// compiledRule is a rule ready to match frames against.
+type compiledRule struct {
+	ingress bool
+	allow   bool
+	proto   uint8 // 0 matches every protocol
+	ports   *[2]uint16
+	block   *net.IPNet
+}
+
+// flowKey identifies a flow from the box's side; ports are zero for ICMP.
+type flowKey struct {
+	proto      uint8
+	remote     [4]byte
+	remotePort uint16
+	localPort  uint16
+}
+
+// firewall filters the frames of one instance; until it has a policy it
+// allows everything.
+type firewall struct {
+	gateway [4]byte
+	alias   [4]byte
+	aliased bool
+
+	mu           sync.RWMutex
+	hostPorts    map[uint16]bool
+	rules        []compiledRule
+	egressAllow  bool
+	ingressAllow bool
+	enabled      bool
+
+	flowsMu sync.Mutex
+	flows   map[flowKey]time.Time
+}
+
+func newFirewall(gatewayIP, hostIP string, hostPorts []uint16, policy *firewallPolicy) (*firewall, error) {
+	fw := &firewall{flows: make(map[flowKey]time.Time)}
+	if ip := net.ParseIP(gatewayIP).To4(); ip != nil {
+		copy(fw.gateway[:], ip)
+	}
+	if ip := net.ParseIP(hostIP).To4(); ip != nil {
+		copy(fw.alias[:], ip)
+		fw.aliased = true
+	}
+	fw.setHostPorts(hostPorts)
+	if policy != nil {
+		if err := fw.set(policy); err != nil {
+			return nil, err
+		}
+	}
+	return fw, nil
+}
+
+// set replaces the policy. Tracked flows are forgotten, so open connections
+// are judged by the new rules from their next frame on.
+func (fw *firewall) set(policy *firewallPolicy) error {
+	rules := make([]compiledRule, 0, len(policy.Rules))
+	for i, rule := range policy.Rules {
+		compiled, err := compileRule(rule)
+		if err != nil {
+			return fmt.Errorf("rule %d: %w", i+1, err)
+		}
+		rules = append(rules, compiled)
+	}
+	egressAllow, err := parseAction(policy.Egress)
+	if err != nil {
+		return err
+	}
+	ingressAllow, err := parseAction(policy.Ingress)
+	if err != nil {
+		return err
+	}
+
+	fw.mu.Lock()
+	fw.rules = rules
+	fw.egressAllow = egressAllow
+	fw.ingressAllow = ingressAllow
+	fw.enabled = len(rules) > 0 || !egressAllow || !ingressAllow
+	fw.mu.Unlock()
+
+	fw.flowsMu.Lock()
+	fw.flows = make(map[flowKey]time.Time)
+	fw.flowsMu.Unlock()
+	return nil
+}
+
+// setHostPorts replaces the host ports the box may reach through the host
+// alias. Connections to ports no longer in the set are cut off.
+func (fw *firewall) setHostPorts(ports []uint16) {
+	hostPorts := make(map[uint16]bool, len(ports))
+	for _, port := range ports {
+		hostPorts[port] = true
+	}
+	fw.mu.Lock()
+	fw.hostPorts = hostPorts
+	fw.mu.Unlock()
+}
+
+func parseAction(action string) (bool, error) {
+	switch action {
+	case "", "allow":
+		return true, nil
+	case "deny":
+		return false, nil
+	}
+	return false, fmt.Errorf("unknown action %q", action)
+}
+
+func compileRule(rule firewallRule) (compiledRule, error) {
+	var compiled compiledRule
+	switch rule.Direction {
+	case "", "egress":
+	case "ingress":
+		compiled.ingress = true
+	default:
+		return compiled, fmt.Errorf("unknown direction %q", rule.Direction)
+	}
+	allow, err := parseAction(rule.Action)
+	if err != nil {
+		return compiled, err
+	}
+	compiled.allow = allow
+	switch rule.Protocol {
+	case "", "any":
+	case "tcp":
+		compiled.proto = protoTCP
+	case "udp":
+		compiled.proto = protoUDP
+	case "icmp":
+		compiled.proto = protoICMP
+	default:
+		return compiled, fmt.Errorf("unknown protocol %q", rule.Protocol)
+	}
+	compiled.ports = rule.Ports
+	if rule.CIDR != nil {
+		cidr := *rule.CIDR
+		if ip := net.ParseIP(cidr); ip != nil {
+			cidr += "/32"
+		}
+		_, block, err := net.ParseCIDR(cidr)
+		if err != nil || block.IP.To4() == nil {
+			return compiled, fmt.Errorf("invalid IPv4 block %q", *rule.CIDR)
+		}
+		compiled.block = block
+	}
+	return compiled, nil
+}
+
+func (r *compiledRule) matches(ingress bool, proto uint8, remote net.IP, port uint16) bool {
+	if r.ingress != ingress {
+		return false
+	}
+	if r.proto != 0 && r.proto != proto {
+		return false
+	}
+	if r.ports != nil && (port < r.ports[0] || port > r.ports[1]) {
+		return false
+	}
+	return r.block == nil || r.block.Contains(remote)
+}
+
+// allow decides whether an Ethernet frame may cross the link.
+func (fw *firewall) allow(ingress bool, frame []byte) bool {
+	fw.mu.RLock()
+	defer fw.mu.RUnlock()
+	if !fw.enabled && !fw.aliased {
+		return true
+	}
+
+	if len(frame) < 14+20 || binary.BigEndian.Uint16(frame[12:]) != etherTypeIPv4 {
+		return true
+	}
+	ip := frame[14:]
+	headerLen := int(ip[0]&0x0f) * 4
+	if headerLen < 20 || len(ip) < headerLen {
+		return false
+	}
+	// Later fragments carry no ports; they are useless without the first
+	if binary.BigEndian.Uint16(ip[6:])&0x1fff != 0 {
+		return true
+	}
+
+	proto := ip[9]
+	var src, dst [4]byte
+	copy(src[:], ip[12:16])
+	copy(dst[:], ip[16:20])
+	var srcPort, dstPort uint16
+	if proto == protoTCP || proto == protoUDP {
+		if len(ip) < headerLen+4 {
+			return false
+		}
+		srcPort = binary.BigEndian.Uint16(ip[headerLen:])
+		dstPort = binary.BigEndian.Uint16(ip[headerLen+2:])
+	}
+
+	key := flowKey{proto: proto, remote: dst, remotePort: dstPort, localPort: srcPort}
+	port := dstPort
+	if ingress {
+		key = flowKey{proto: proto, remote: src, remotePort: srcPort, localPort: dstPort}
+	}
+	if key.remote == fw.gateway && isInfrastructure(proto, key.remotePort, key.localPort) {
+		return true
+	}
+	if proto == protoUDP && isDHCP(key.remotePort, key.localPort) {
+		return true
+	}
+	if fw.aliased && !ingress && key.remote == fw.alias &&
+		!((proto == protoTCP || proto == protoUDP) && fw.hostPorts[dstPort]) {
+		return false
+	}
+	if !fw.enabled {
+		return true
+	}
+	if fw.tracked(key) {
+		return true
+	}
+
+	allowed := fw.egressAllow
+	if ingress {
+		allowed = fw.ingressAllow
+	}
+	remote := net.IP(key.remote[:])
+	for i := range fw.rules {
+		if fw.rules[i].matches(ingress, proto, remote, port) {
+			allowed = fw.rules[i].allow
+			break
+		}
+	}
+	if allowed {
+		fw.track(key)
+	}
+	return allowed
+}
+
+// isInfrastructure reports DNS with the gateway.
+func isInfrastructure(proto uint8, remotePort, localPort uint16) bool {
+	return (proto == protoUDP || proto == protoTCP) && (remotePort == 53 || localPort == 53)
+}
+
+func isDHCP(remotePort, localPort uint16) bool {
+	return (remotePort == 67 && localPort == 68) || (remotePort == 68 && localPort == 67)
+}
+
+func (fw *firewall) tracked(key flowKey) bool {
+	fw.flowsMu.Lock()
+	defer fw.flowsMu.Unlock()
+	seen, ok := fw.flows[key]
+	if !ok || time.Since(seen) > flowIdle {
+		return false
+	}
+	fw.flows[key] = time.Now()
+	return true
+}
+
+func (fw *firewall) track(key flowKey) {
+	fw.flowsMu.Lock()
+	defer fw.flowsMu.Unlock()
+	now := time.Now()
+	if len(fw.flows) >= flowSweepAt {
+		for flow, seen := range fw.flows {
+			if now.Sub(seen) > flowIdle {
+				delete(fw.flows, flow)
+			}
+		}
+	}
+	fw.flows[key] = now
+}
+
+// frameJoiner recovers frames from one direction of a Qemu stream, where
+// each frame is preceded by its length as a 4-byte big-endian integer, and
+// re-emits the ones the firewall allows in the same framing.
+type frameJoiner struct {
+	buf []byte
+}
+
+// feed takes stream bytes and appends the allowed complete frames, with
+// their length prefix, to out.
+func (j *frameJoiner) feed(data []byte, out []byte, allow func([]byte) bool) []byte {
+	j.buf = append(j.buf, data...)
+	for len(j.buf) >= 4 {
+		size := int(binary.BigEndian.Uint32(j.buf))
+		if len(j.buf) < 4+size {
+			break
+		}
+		if allow(j.buf[4 : 4+size]) {
+			out = append(out, j.buf[:4+size]...)
+		}
+		j.buf = j.buf[4+size:]
+	}
+	// Keep the partial frame at the start of the buffer
+	j.buf = append(j.buf[:0:0], j.buf...)
+	return out
+}
+
+// filteredConn drops the frames crossing the VM connection that the
+// firewall denies. Qemu streams need splitting; each VFKit datagram is one
+// frame.
+type filteredConn struct {
+	net.Conn
+	fw      *firewall
+	stream  bool
+	rx, tx  frameJoiner
+	pending []byte
+	scratch []byte
+}
+
+func newFilteredConn(conn net.Conn, fw *firewall, stream bool) *filteredConn {
+	return &filteredConn{Conn: conn, fw: fw, stream: stream}
+}
+
+func (c *filteredConn) egress(frame []byte) bool  { return c.fw.allow(false, frame) }
+func (c *filteredConn) ingress(frame []byte) bool { return c.fw.allow(true, frame) }
+
+func (c *filteredConn) Read(b []byte) (int, error) {
+	if !c.stream {
+		for {
+			n, err := c.Conn.Read(b)
+			if err != nil || n == 0 || c.egress(b[:n]) {
+				return n, err
+			}
+		}
+	}
+
+	for len(c.pending) == 0 {
+		if c.scratch == nil {
+			c.scratch = make([]byte, 64*1024)
+		}
+		n, err := c.Conn.Read(c.scratch)
+		if n > 0 {
+			c.pending = c.rx.feed(c.scratch[:n], c.pending, c.egress)
+		}
+		if err != nil && len(c.pending) == 0 {
+			return 0, err
+		}
+	}
+	n := copy(b, c.pending)
+	c.pending = c.pending[n:]
+	return n, nil
+}
+
+func (c *filteredConn) Write(b []byte) (int, error) {
+	if !c.stream {
+		if !c.ingress(b) {
+			return len(b), nil
+		}
+		return c.Conn.Write(b)
+	}
+
+	out := c.tx.feed(b, nil, c.ingress)
+	if len(out) > 0 {
+		if _, err := c.Conn.Write(out); err != nil {
+			return 0, err
+		}
+	}
+	return len(b), nil
+}
+
+//export gvproxy_set_firewall
+func gvproxy_set_firewall(id C.longlong, policyJSON *C.char) C.int {
+	instancesMu.RLock()
+	instance, ok := instances[int64(id)]
+	instancesMu.RUnlock()
+	if !ok {
+		return -1
+	}
+
+	var policy firewallPolicy
+	if err := json.Unmarshal([]byte(C.GoString(policyJSON)), &policy); err != nil {
+		logrus.WithFields(logrus.Fields{"error": err, "id": id}).Warn("Failed to parse firewall policy")
+		return -1
+	}
+	if err := instance.fw.set(&policy); err != nil {
+		logrus.WithFields(logrus.Fields{"error": err, "id": id}).Warn("Invalid firewall policy")
+		return -1
+	}
+	logrus.WithFields(logrus.Fields{"id": id, "rules": len(policy.Rules)}).Info("Firewall updated")
+	return 0
+}
+
+//export gvproxy_set_host_ports
+func gvproxy_set_host_ports(id C.longlong, portsJSON *C.char) C.int {
+	instancesMu.RLock()
+	instance, ok := instances[int64(id)]
+	instancesMu.RUnlock()
+	if !ok {
+		return -1
+	}
+
+	var ports []uint16
+	if err := json.Unmarshal([]byte(C.GoString(portsJSON)), &ports); err != nil {
+		logrus.WithFields(logrus.Fields{"error": err, "id": id}).Warn("Failed to parse host ports")
+		return -1
+	}
+	instance.fw.setHostPorts(ports)
+	logrus.WithFields(logrus.Fields{"id": id, "ports": len(ports)}).Info("Host ports updated")
+	return 0
+}
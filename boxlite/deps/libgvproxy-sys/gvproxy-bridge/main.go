@@ -175,17 +175,20 @@ type DNSZone struct {
 
 // GvproxyConfig matches the Rust structure (must stay in sync!)
 type GvproxyConfig struct {
-	Subnet           string        `json:"subnet"`
-	GatewayIP        string        `json:"gateway_ip"`
-	GatewayMac       string        `json:"gateway_mac"`
-	GuestIP          string        `json:"guest_ip"`
-	GuestMac         string        `json:"guest_mac"`
-	MTU              uint16        `json:"mtu"`
-	PortMappings     []PortMapping `json:"port_mappings"`
-	DNSZones         []DNSZone     `json:"dns_zones"`
-	DNSSearchDomains []string      `json:"dns_search_domains"`
-	Debug            bool          `json:"debug"`
-	CaptureFile      *string       `json:"capture_file,omitempty"`
+	Subnet           string          `json:"subnet"`
+	GatewayIP        string          `json:"gateway_ip"`
+	GatewayMac       string          `json:"gateway_mac"`
+	GuestIP          string          `json:"guest_ip"`
+	GuestMac         string          `json:"guest_mac"`
+	HostIP           string          `json:"host_ip"`
+	HostPorts        []uint16        `json:"host_ports"`
+	MTU              uint16          `json:"mtu"`
+	PortMappings     []PortMapping   `json:"port_mappings"`
+	DNSZones         []DNSZone       `json:"dns_zones"`
+	DNSSearchDomains []string        `json:"dns_search_domains"`
+	Debug            bool            `json:"debug"`
+	CaptureFile      *string         `json:"capture_file,omitempty"`
+	Firewall         *firewallPolicy `json:"firewall,omitempty"`
 }
 
 // GvproxyInstance tracks a running gvisor-tap-vsock instance
@@ -198,6 +201,8 @@ type GvproxyInstance struct {
 	listener   net.Listener                   // For Linux UnixStream (Qemu)
 	vn         *virtualnetwork.VirtualNetwork // Virtual network for stats collection
 	vnMu       sync.RWMutex                   // Protects vn field
+	taps       *tapSet                        // Live packet captures (capture.go)
+	fw         *firewall                      // Frame filter (firewall.go)
 }
 
 var (
@@ -267,6 +272,14 @@ func gvproxy_create(configJSON *C.char) C.longlong {
 		DNSSearchDomains:  config.DNSSearchDomains,
 	}
 
+	// The host alias reaches the host's loopback, where forwarded ports
+	// listen. The firewall only lets connections through it to the
+	// forwarded ports in HostPorts, never to other loopback services.
+	if config.HostIP != "" {
+		tapConfig.NAT[config.HostIP] = "127.0.0.1"
+		tapConfig.GatewayVirtualIPs = append(tapConfig.GatewayVirtualIPs, config.HostIP)
+	}
+
 	// Set CaptureFile if provided
 	if config.CaptureFile != nil && *config.CaptureFile != "" {
 		tapConfig.CaptureFile = *config.CaptureFile
@@ -285,10 +298,15 @@ func gvproxy_create(configJSON *C.char) C.longlong {
 		logrus.WithFields(logrus.Fields{"host": forwardKey, "guest": forwardVal}).Info("Added TCP port forward")
 	}
 
+	fw, err := newFirewall(config.GatewayIP, config.HostIP, config.HostPorts, config.Firewall)
+	if err != nil {
+		logrus.WithError(err).Error("Invalid firewall policy")
+		return -1
+	}
+
 	// Platform-specific socket creation
 	var conn net.Conn
 	var listener net.Listener
-	var err error
 
 	if runtime.GOOS == "darwin" {
 		// macOS: Use UnixDgram with VFKit protocol (SOCK_DGRAM)
@@ -319,6 +337,8 @@ func gvproxy_create(configJSON *C.char) C.longlong {
 		Cancel:     cancel,
 		conn:       conn,
 		listener:   listener,
+		taps:       newTapSet(),
+		fw:         fw,
 	}
 
 	instancesMu.Lock()
@@ -384,7 +404,7 @@ func gvproxy_create(configJSON *C.char) C.longlong {
 				logrus.WithFields(logrus.Fields{"id": id, "remote": wrappedConn.RemoteAddr().String()}).Info("VFKit connection accepted")
 
 				// Handle the VFKit protocol with the wrapped connection
-				if err := vn.AcceptVfkit(ctx, wrappedConn); err != nil {
+				if err := vn.AcceptVfkit(ctx, newTappedConn(newFilteredConn(wrappedConn, instance.fw, false), instance.taps, false)); err != nil {
 					if ctx.Err() == nil {
 						logrus.WithFields(logrus.Fields{"error": err, "id": id}).Error("AcceptVfkit error")
 					}
@@ -410,7 +430,7 @@ func gvproxy_create(configJSON *C.char) C.longlong {
 				listener.Close()
 
 				// Handle the Qemu protocol
-				if err := vn.AcceptQemu(ctx, acceptedConn); err != nil {
+				if err := vn.AcceptQemu(ctx, newTappedConn(newFilteredConn(acceptedConn, instance.fw, true), instance.taps, true)); err != nil {
 					if ctx.Err() == nil {
 						logrus.WithFields(logrus.Fields{"error": err, "id": id}).Error("AcceptQemu error")
 					}
@@ -422,6 +442,7 @@ func gvproxy_create(configJSON *C.char) C.longlong {
 		<-ctx.Done()
 
 		// Cleanup
+		instance.taps.closeAll()
 		if runtime.GOOS == "darwin" && conn != nil {
 			conn.Close()
 		} else if listener != nil {
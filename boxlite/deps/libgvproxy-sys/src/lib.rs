@@ -73,6 +73,40 @@ extern "C" {
     /// - Do not use pointer after calling gvproxy_free_string
     pub fn gvproxy_get_stats(id: c_longlong) -> *mut c_char;
 
+    /// Start a pcap capture of the guest's link
+    ///
+    /// Writes a pcap header and then every Ethernet frame crossing the VM
+    /// connection to `fd`, until the instance is destroyed or a write fails.
+    ///
+    /// # Arguments
+    /// * `id` - Instance ID returned from gvproxy_create
+    /// * `fd` - Descriptor to write to; owned by libgvproxy even on error
+    /// * `snaplen` - Bytes kept per frame (65535 if not positive)
+    ///
+    /// # Returns
+    /// 0 on success, -1 if the instance doesn't exist or the header write failed
+    pub fn gvproxy_capture_start(id: c_longlong, fd: c_int, snaplen: c_int) -> c_int;
+
+    /// Replace the firewall filtering the guest's link
+    ///
+    /// # Arguments
+    /// * `id` - Instance ID returned from gvproxy_create
+    /// * `policyJSON` - JSON-encoded firewall policy (null-terminated C string)
+    ///
+    /// # Returns
+    /// 0 on success, -1 if the instance doesn't exist or the policy is invalid
+    pub fn gvproxy_set_firewall(id: c_longlong, policyJSON: *const c_char) -> c_int;
+
+    /// Replace the host ports the guest may reach through the host alias
+    ///
+    /// # Arguments
+    /// * `id` - Instance ID returned from gvproxy_create
+    /// * `portsJSON` - JSON array of host ports (null-terminated C string)
+    ///
+    /// # Returns
+    /// 0 on success, -1 if the instance doesn't exist or the ports are invalid
+    pub fn gvproxy_set_host_ports(id: c_longlong, portsJSON: *const c_char) -> c_int;
+
     /// Get the libgvproxy version string
     ///
     /// # Returns
@@ -25,6 +25,10 @@ pub const KRUN_LOG_STYLE_NEVER: u32 = 2;
 pub const KRUN_DISK_FORMAT_RAW: u32 = 0;
 pub const KRUN_DISK_FORMAT_QCOW2: u32 = 1;
 
+// Transparent socket impersonation (TSI) features for krun_add_vsock
+pub const KRUN_TSI_HIJACK_INET: u32 = 1 << 0;
+pub const KRUN_TSI_HIJACK_UNIX: u32 = 1 << 1;
+
 extern "C" {
     pub fn krun_init_log(target: i32, level: u32, style: u32, flags: u32) -> i32;
     pub fn krun_set_log_level(level: u32) -> i32;
@@ -37,6 +41,12 @@ extern "C" {
         mount_tag: *const c_char,
         host_path: *const c_char,
     ) -> i32;
+    pub fn krun_add_virtiofs2(
+        ctx_id: u32,
+        mount_tag: *const c_char,
+        host_path: *const c_char,
+        shm_size: u64,
+    ) -> i32;
     pub fn krun_set_kernel(
         ctx_id: u32,
         kernel_path: *const c_char,
@@ -63,6 +73,14 @@ extern "C" {
         filepath: *const c_char,
         listen: bool,
     ) -> i32;
+    /// Do not attach the virtio-console device libkrun adds by default.
+    pub fn krun_disable_implicit_console(ctx_id: u32) -> i32;
+    /// Do not attach the vsock device (with TSI) libkrun adds by default.
+    ///
+    /// Pair with `krun_add_vsock` to keep vsock without TSI networking.
+    pub fn krun_disable_implicit_vsock(ctx_id: u32) -> i32;
+    /// Attach a vsock device; `tsi_features` is a mask of `KRUN_TSI_*`.
+    pub fn krun_add_vsock(ctx_id: u32, tsi_features: u32) -> i32;
     pub fn krun_add_disk(
         ctx_id: u32,
         block_id: *const c_char,
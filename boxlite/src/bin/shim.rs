@@ -11,21 +11,30 @@
 //! The shim creates the network backend (gvproxy) from network_config if present.
 //! This ensures networking survives detach operations - the gvproxy lives in the
 //! shim subprocess, not the main boxlite process.
+//!
+//! When the config names a capture socket, each connection to it receives a
+//! pcap stream of the guest's link, written by gvproxy directly.
 
 use std::path::Path;
+use std::time::Duration;
 
 use boxlite::{
+    GuestSession,
     runtime::layout,
     util,
     vmm::{self, InstanceSpec, VmmConfig, VmmKind},
 };
+use boxlite_shared::Transport;
 use boxlite_shared::errors::BoxliteResult;
 use clap::Parser;
 #[allow(unused_imports)]
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
 #[cfg(feature = "gvproxy-backend")]
-use boxlite::net::{ConnectionType, NetworkBackendEndpoint, gvproxy::GvproxyInstance};
+use boxlite::net::{
+    ConnectionType, NetworkBackendEndpoint,
+    gvproxy::{GvproxyConfig, GvproxyInstance},
+};
 
 /// Universal Box runner binary - subprocess that executes isolated Boxes
 #[derive(Parser, Debug)]
@@ -37,7 +46,7 @@ use boxlite::net::{ConnectionType, NetworkBackendEndpoint, gvproxy::GvproxyInsta
 struct ShimArgs {
     /// Engine type to use for Box execution
     ///
-    /// Supported engines: libkrun, firecracker
+    /// Supported engines: libkrun, firecracker, boxless, whpx
     #[arg(long)]
     engine: VmmKind,
 
@@ -76,6 +85,148 @@ fn init_logging(home_dir: &Path) -> tracing_appender::non_blocking::WorkerGuard
     guard
 }
 
+/// How long an expired box's guest gets to shut down before the VM is killed.
+const TTL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shut the guest down the way a normal stop does, before the shim exits.
+fn shut_down_guest(transport: Transport) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            tracing::warn!("Failed to start runtime for guest shutdown: {}", e);
+            return;
+        }
+    };
+    let shutdown = async {
+        let mut guest = GuestSession::new(transport).guest().await?;
+        guest.shutdown().await
+    };
+    match runtime.block_on(tokio::time::timeout(TTL_SHUTDOWN_TIMEOUT, shutdown)) {
+        Ok(Ok(())) => tracing::info!("Guest shut down"),
+        Ok(Err(e)) => tracing::warn!("Guest shutdown failed: {}", e),
+        Err(_) => tracing::warn!("Guest did not shut down within {:?}", TTL_SHUTDOWN_TIMEOUT),
+    }
+}
+
+/// Hand every connection to `path` to gvproxy as a packet capture.
+#[cfg(feature = "gvproxy-backend")]
+fn serve_captures(gvproxy: &'static GvproxyInstance, path: &Path) -> BoxliteResult<()> {
+    use boxlite_shared::errors::BoxliteError;
+    use std::os::unix::net::UnixListener;
+
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(|e| {
+        BoxliteError::Network(format!(
+            "Failed to bind capture socket {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(|e| BoxliteError::Network(e.to_string()))
+                .and_then(|stream| gvproxy.start_capture(stream.into(), u16::MAX.into()));
+            if let Err(e) = result {
+                tracing::warn!("Failed to start packet capture: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Take firewall policies for the gvproxy link on `path`, one JSON line
+/// per connection, replying `ok` or the error.
+#[cfg(feature = "gvproxy-backend")]
+fn serve_firewall(gvproxy: &'static GvproxyInstance, path: &Path) -> BoxliteResult<()> {
+    use boxlite::net::firewall::{APPLIED, FirewallPolicy};
+    use boxlite_shared::errors::BoxliteError;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(|e| {
+        BoxliteError::Network(format!(
+            "Failed to bind firewall socket {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let mut request = String::new();
+            let result = BufReader::new(&stream)
+                .read_line(&mut request)
+                .map_err(|e| BoxliteError::Network(e.to_string()))
+                .and_then(|_| {
+                    serde_json::from_str::<FirewallPolicy>(&request)
+                        .map_err(|e| BoxliteError::InvalidArgument(e.to_string()))
+                })
+                .and_then(|policy| gvproxy.set_firewall(&policy));
+            let reply = match result {
+                Ok(()) => APPLIED.to_string(),
+                Err(e) => {
+                    tracing::warn!("Failed to apply firewall: {}", e);
+                    e.to_string()
+                }
+            };
+            let _ = writeln!(stream, "{}", reply);
+        }
+    });
+    Ok(())
+}
+
+/// Take the host ports the guest may reach through the host alias on
+/// `path`, as a JSON array, one line per connection, replying `ok` or the
+/// error.
+#[cfg(feature = "gvproxy-backend")]
+fn serve_host_ports(gvproxy: &'static GvproxyInstance, path: &Path) -> BoxliteResult<()> {
+    use boxlite::net::firewall::APPLIED;
+    use boxlite_shared::errors::BoxliteError;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(|e| {
+        BoxliteError::Network(format!(
+            "Failed to bind host ports socket {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let mut request = String::new();
+            let result = BufReader::new(&stream)
+                .read_line(&mut request)
+                .map_err(|e| BoxliteError::Network(e.to_string()))
+                .and_then(|_| {
+                    serde_json::from_str::<Vec<u16>>(&request)
+                        .map_err(|e| BoxliteError::InvalidArgument(e.to_string()))
+                })
+                .and_then(|ports| gvproxy.set_host_ports(&ports));
+            let reply = match result {
+                Ok(()) => APPLIED.to_string(),
+                Err(e) => {
+                    tracing::warn!("Failed to apply host ports: {}", e);
+                    e.to_string()
+                }
+            };
+            let _ = writeln!(stream, "{}", reply);
+        }
+    });
+    Ok(())
+}
+
 fn main() -> BoxliteResult<()> {
     // Parse command line arguments with clap
     // VmmKind parsed via FromStr trait automatically
@@ -112,7 +263,7 @@ fn main() -> BoxliteResult<()> {
         );
 
         // Create gvproxy instance
-        let gvproxy = GvproxyInstance::new(&net_config.port_mappings)?;
+        let gvproxy = GvproxyInstance::with_config(GvproxyConfig::from_backend(net_config))?;
         let socket_path = gvproxy.get_socket_path()?;
 
         tracing::info!(
@@ -142,8 +293,34 @@ fn main() -> BoxliteResult<()> {
         // Leak the gvproxy instance to keep it alive for VM lifetime.
         // This is intentional - the VM needs networking for its entire life,
         // and OS cleanup handles resources when process exits.
-        let _gvproxy_leaked = Box::leak(Box::new(gvproxy));
+        let gvproxy_leaked: &'static GvproxyInstance = Box::leak(Box::new(gvproxy));
         tracing::debug!("Leaked gvproxy instance for VM lifetime");
+
+        if let Some(capture_socket) = &net_config.capture_socket {
+            serve_captures(gvproxy_leaked, capture_socket)?;
+        }
+        if let Some(firewall_socket) = &net_config.firewall_socket {
+            serve_firewall(gvproxy_leaked, firewall_socket)?;
+        }
+        if let Some(host_ports_socket) = &net_config.host_ports_socket {
+            serve_host_ports(gvproxy_leaked, host_ports_socket)?;
+        }
+    }
+
+    // Enforce the box TTL from inside the shim so it holds even if the
+    // process that created the box is gone. The guest is shut down as on a
+    // normal stop; exiting then tears down the VM.
+    if let Some(expires_at) = config.expires_at {
+        let remaining = (expires_at - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        let transport = config.transport.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(remaining);
+            tracing::info!(%expires_at, "Box TTL expired, shutting down");
+            shut_down_guest(transport);
+            std::process::exit(0);
+        });
     }
 
     // Initialize engine options with defaults
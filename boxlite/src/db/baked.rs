@@ -0,0 +1,112 @@
+//! Baked image storage operations.
+//!
+//! One row per baked tag. JSON blob contains the full BakedImage struct.
+
+use rusqlite::{OptionalExtension, params};
+
+use crate::runtime::bake::BakedImage;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use super::{Database, db_err};
+
+/// Baked image storage wrapping Database.
+#[derive(Clone)]
+pub struct BakedImageStore {
+    db: Database,
+}
+
+impl BakedImageStore {
+    /// Create a new BakedImageStore from a Database.
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Get the image baked under `tag`, if any.
+    pub fn get(&self, tag: &str) -> BoxliteResult<Option<BakedImage>> {
+        let conn = self.db.conn();
+        let json: Option<String> = db_err!(
+            conn.query_row(
+                "SELECT json FROM baked_image WHERE tag = ?1",
+                params![tag],
+                |row| row.get(0),
+            )
+            .optional()
+        )?;
+        json.map(|json| deserialize(&json)).transpose()
+    }
+
+    /// Add or replace the image baked under its tag.
+    pub fn upsert(&self, image: &BakedImage) -> BoxliteResult<()> {
+        let conn = self.db.conn();
+        let json = serde_json::to_string(image).map_err(|e| {
+            BoxliteError::Database(format!("Failed to serialize baked image: {}", e))
+        })?;
+        db_err!(conn.execute(
+            r#"
+            INSERT INTO baked_image (tag, created_at, json) VALUES (?1, ?2, ?3)
+            ON CONFLICT(tag) DO UPDATE SET
+                created_at = excluded.created_at,
+                json = excluded.json
+            "#,
+            params![image.tag, image.created_at.timestamp_millis(), json],
+        ))?;
+        Ok(())
+    }
+
+    /// List every baked image, by tag.
+    pub fn list(&self) -> BoxliteResult<Vec<BakedImage>> {
+        let conn = self.db.conn();
+        let mut stmt = db_err!(conn.prepare("SELECT json FROM baked_image ORDER BY tag"))?;
+        let rows = db_err!(stmt.query_map([], |row| row.get::<_, String>(0)))?;
+
+        let mut images = Vec::new();
+        for row in rows {
+            images.push(deserialize(&db_err!(row)?)?);
+        }
+        Ok(images)
+    }
+
+    /// Forget the image baked under `tag`.
+    pub fn remove(&self, tag: &str) -> BoxliteResult<bool> {
+        let conn = self.db.conn();
+        let rows_affected =
+            db_err!(conn.execute("DELETE FROM baked_image WHERE tag = ?1", params![tag]))?;
+        Ok(rows_affected > 0)
+    }
+}
+
+fn deserialize(json: &str) -> BoxliteResult<BakedImage> {
+    serde_json::from_str(json)
+        .map_err(|e| BoxliteError::Database(format!("Failed to deserialize baked image: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_upsert_get_remove() {
+        let dir = TempDir::new().unwrap();
+        let store = BakedImageStore::new(Database::open(&dir.path().join("test.db")).unwrap());
+
+        let mut image = BakedImage {
+            tag: "myorg/pytorch-sandbox".to_string(),
+            base_image: "python:3.12-slim".to_string(),
+            env: vec![("PIP_NO_CACHE_DIR".to_string(), "1".to_string())],
+            labels: [("team".to_string(), "ml".to_string())].into(),
+            created_at: Utc::now(),
+        };
+        store.upsert(&image).unwrap();
+        assert_eq!(store.get(&image.tag).unwrap(), Some(image.clone()));
+
+        image.base_image = "python:3.13-slim".to_string();
+        store.upsert(&image).unwrap();
+        assert_eq!(store.list().unwrap(), [image.clone()]);
+
+        assert!(store.remove(&image.tag).unwrap());
+        assert_eq!(store.get(&image.tag).unwrap(), None);
+        assert!(!store.remove(&image.tag).unwrap());
+    }
+}
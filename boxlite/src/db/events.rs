@@ -0,0 +1,101 @@
+//! Event log storage operations.
+//!
+//! Append-only log of host-side lifecycle events, capped at a fixed number
+//! of rows so it cannot grow without bound.
+
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+
+use crate::events::BoxEvent;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use super::{Database, db_err};
+
+/// Number of most recent events kept in the log.
+const MAX_EVENTS: i64 = 10_000;
+
+/// Event storage wrapping Database.
+#[derive(Clone)]
+pub struct EventStore {
+    db: Database,
+}
+
+impl EventStore {
+    /// Create a new EventStore from a Database.
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Append an event, returning its sequence number.
+    ///
+    /// Drops the oldest events once the log exceeds its cap.
+    pub fn append(&self, event: &BoxEvent) -> BoxliteResult<i64> {
+        let conn = self.db.conn();
+
+        let json = serde_json::to_string(event)
+            .map_err(|e| BoxliteError::Database(format!("Failed to serialize event: {}", e)))?;
+
+        db_err!(conn.execute(
+            "INSERT INTO event (box_id, kind, timestamp, json) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                event.box_id.as_str(),
+                event.kind.as_str(),
+                event.timestamp.timestamp_millis(),
+                json
+            ],
+        ))?;
+        let seq = conn.last_insert_rowid();
+
+        db_err!(conn.execute(
+            "DELETE FROM event WHERE seq <= ?1",
+            params![seq - MAX_EVENTS]
+        ))?;
+
+        Ok(seq)
+    }
+
+    /// List events at or after `since` (all events if None), oldest first.
+    pub fn list(&self, since: Option<DateTime<Utc>>) -> BoxliteResult<Vec<BoxEvent>> {
+        let since = since.map(|t| t.timestamp_millis()).unwrap_or(i64::MIN);
+        self.query(
+            "SELECT seq, json FROM event WHERE timestamp >= ?1 ORDER BY seq ASC",
+            since,
+        )
+    }
+
+    /// List events with a sequence number above `seq`, oldest first.
+    pub fn list_after(&self, seq: i64) -> BoxliteResult<Vec<BoxEvent>> {
+        self.query(
+            "SELECT seq, json FROM event WHERE seq > ?1 ORDER BY seq ASC",
+            seq,
+        )
+    }
+
+    /// Sequence number of the newest event (0 if there are none).
+    pub fn last_seq(&self) -> BoxliteResult<i64> {
+        let conn = self.db.conn();
+        db_err!(
+            conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM event", [], |row| row
+                .get(0))
+        )
+    }
+
+    fn query(&self, sql: &str, param: i64) -> BoxliteResult<Vec<BoxEvent>> {
+        let conn = self.db.conn();
+        let mut stmt = db_err!(conn.prepare(sql))?;
+        let rows = db_err!(stmt.query_map(params![param], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        }))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (seq, json) = db_err!(row)?;
+            let mut event: BoxEvent = serde_json::from_str(&json).map_err(|e| {
+                BoxliteError::Database(format!("Failed to deserialize event: {}", e))
+            })?;
+            event.seq = seq;
+            result.push(event);
+        }
+        Ok(result)
+    }
+}
@@ -106,8 +106,46 @@ impl ImageIndexStore {
         Ok(())
     }
 
+    /// List every cached image with its reference.
+    pub fn list(&self) -> BoxliteResult<Vec<(String, CachedImage)>> {
+        let conn = self.db.conn();
+
+        let mut stmt = db_err!(conn.prepare(
+            "SELECT reference, manifest_digest, config_digest, layers, cached_at, complete FROM image_index ORDER BY reference"
+        ))?;
+        let rows = db_err!(stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, i32>(5)?,
+            ))
+        }))?;
+
+        let mut images = Vec::new();
+        for row in rows {
+            let (reference, manifest_digest, config_digest, layers_json, cached_at, complete) =
+                db_err!(row)?;
+            let layers: Vec<String> = serde_json::from_str(&layers_json).map_err(|e| {
+                BoxliteError::Database(format!("Failed to deserialize layers: {}", e))
+            })?;
+            images.push((
+                reference,
+                CachedImage {
+                    manifest_digest,
+                    config_digest,
+                    layers,
+                    cached_at,
+                    complete: complete != 0,
+                },
+            ));
+        }
+        Ok(images)
+    }
+
     /// Remove cached image from index.
-    #[allow(dead_code)]
     pub fn remove(&self, reference: &str) -> BoxliteResult<bool> {
         let conn = self.db.conn();
         let rows_affected = db_err!(conn.execute(
@@ -167,6 +205,28 @@ mod tests {
         assert!(loaded.complete);
     }
 
+    #[test]
+    fn test_list_and_remove() {
+        let (store, _dir) = create_test_db();
+
+        let image = CachedImage {
+            manifest_digest: "sha256:abc123".to_string(),
+            config_digest: "sha256:config123".to_string(),
+            layers: vec!["sha256:layer1".to_string()],
+            cached_at: "2025-10-24T12:00:00Z".to_string(),
+            complete: true,
+        };
+        store.upsert("python:alpine", &image).unwrap();
+        store.upsert("alpine:latest", &image).unwrap();
+
+        let references: Vec<String> = store.list().unwrap().into_iter().map(|(r, _)| r).collect();
+        assert_eq!(references, ["alpine:latest", "python:alpine"]);
+
+        assert!(store.remove("alpine:latest").unwrap());
+        assert!(!store.remove("alpine:latest").unwrap());
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_upsert_updates_existing() {
         let (store, _dir) = create_test_db();
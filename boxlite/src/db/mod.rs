@@ -6,9 +6,13 @@
 //!
 //! Uses JSON blob pattern for flexibility with queryable columns for performance.
 
+mod baked;
 mod boxes;
+mod events;
 mod images;
 mod schema;
+mod snapshots;
+mod usage;
 
 use std::path::Path;
 use std::sync::Arc;
@@ -19,8 +23,12 @@ use rusqlite::{Connection, OptionalExtension};
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 
+pub use baked::BakedImageStore;
 pub use boxes::BoxStore;
+pub use events::EventStore;
 pub use images::{CachedImage, ImageIndexStore};
+pub use snapshots::SnapshotStore;
+pub use usage::UsageStore;
 
 /// Helper macro to convert rusqlite errors to BoxliteError.
 macro_rules! db_err {
@@ -175,6 +183,42 @@ impl Database {
             current = 4;
         }
 
+        // Migration 4 -> 5: Add snapshot table
+        if current == 4 {
+            tracing::info!("Running migration 4 -> 5: Adding snapshot table");
+
+            db_err!(conn.execute_batch(schema::SNAPSHOT_TABLE))?;
+
+            current = 5;
+        }
+
+        // Migration 5 -> 6: Add event table
+        if current == 5 {
+            tracing::info!("Running migration 5 -> 6: Adding event table");
+
+            db_err!(conn.execute_batch(schema::EVENT_TABLE))?;
+
+            current = 6;
+        }
+
+        // Migration 6 -> 7: Add exec_usage table
+        if current == 6 {
+            tracing::info!("Running migration 6 -> 7: Adding exec_usage table");
+
+            db_err!(conn.execute_batch(schema::EXEC_USAGE_TABLE))?;
+
+            current = 7;
+        }
+
+        // Migration 7 -> 8: Add baked_image table
+        if current == 7 {
+            tracing::info!("Running migration 7 -> 8: Adding baked_image table");
+
+            db_err!(conn.execute_batch(schema::BAKED_IMAGE_TABLE))?;
+
+            current = 8;
+        }
+
         // Update schema version
         let now = Utc::now().to_rfc3339();
         db_err!(conn.execute(
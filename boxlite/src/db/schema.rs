@@ -7,7 +7,7 @@
 //! Each table has queryable columns for efficient filtering + JSON blob for full data.
 
 /// Current schema version.
-pub const SCHEMA_VERSION: i32 = 4;
+pub const SCHEMA_VERSION: i32 = 8;
 
 /// Schema version tracking table.
 pub const SCHEMA_VERSION_TABLE: &str = r#"
@@ -80,6 +80,77 @@ CREATE TABLE IF NOT EXISTS image_index (
 CREATE INDEX IF NOT EXISTS idx_image_index_manifest_digest ON image_index(manifest_digest);
 "#;
 
+/// Snapshot table schema.
+///
+/// Stores box disk snapshots. JSON blob contains full SnapshotInfo struct.
+/// Queryable columns: box_id, name (unique per box), parent_id (for chain
+/// checks), and layer digests (for layer garbage collection).
+pub const SNAPSHOT_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS snapshot (
+    id TEXT PRIMARY KEY NOT NULL,
+    box_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    parent_id TEXT,
+    disk_layer TEXT NOT NULL,
+    guest_disk_layer TEXT,
+    created_at INTEGER NOT NULL,
+    json TEXT NOT NULL,
+    UNIQUE (box_id, name),
+    FOREIGN KEY (box_id) REFERENCES box_config(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_snapshot_box_id ON snapshot(box_id);
+CREATE INDEX IF NOT EXISTS idx_snapshot_parent_id ON snapshot(parent_id);
+"#;
+
+/// Event log table schema.
+///
+/// Stores host-side lifecycle events. JSON blob contains full BoxEvent struct.
+/// Queryable columns: seq (monotonic order), box_id, timestamp (for `since`).
+/// No foreign key: events outlive the boxes they describe.
+pub const EVENT_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS event (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    box_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    json TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_timestamp ON event(timestamp);
+CREATE INDEX IF NOT EXISTS idx_event_box_id ON event(box_id);
+"#;
+
+/// Execution usage table schema.
+///
+/// One row per finished execution. JSON blob contains full ExecUsage struct.
+/// Queryable columns: seq (monotonic order), box_id, finished_at (for `since`).
+/// No foreign key: usage outlives the boxes it was accrued in.
+pub const EXEC_USAGE_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS exec_usage (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    box_id TEXT NOT NULL,
+    execution_id TEXT NOT NULL,
+    finished_at INTEGER NOT NULL,
+    json TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_exec_usage_finished_at ON exec_usage(finished_at);
+CREATE INDEX IF NOT EXISTS idx_exec_usage_box_id ON exec_usage(box_id);
+"#;
+
+/// Baked image table schema.
+///
+/// One row per baked tag. JSON blob contains full BakedImage struct.
+/// Queryable columns: tag, created_at.
+pub const BAKED_IMAGE_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS baked_image (
+    tag TEXT PRIMARY KEY NOT NULL,
+    created_at INTEGER NOT NULL,
+    json TEXT NOT NULL
+);
+"#;
+
 /// Get all schema creation statements.
 pub fn all_schemas() -> Vec<&'static str> {
     vec![
@@ -88,5 +159,9 @@ pub fn all_schemas() -> Vec<&'static str> {
         BOX_STATE_TABLE,
         ALIVE_TABLE,
         IMAGE_INDEX_TABLE,
+        SNAPSHOT_TABLE,
+        EVENT_TABLE,
+        EXEC_USAGE_TABLE,
+        BAKED_IMAGE_TABLE,
     ]
 }
@@ -0,0 +1,132 @@
+//! Snapshot storage operations.
+//!
+//! Each row describes one snapshot of a box: the content-addressed disk layers
+//! it froze and its parent in the box's snapshot chain.
+
+use std::collections::HashSet;
+
+use rusqlite::{OptionalExtension, params};
+
+use crate::snapshots::SnapshotInfo;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use super::{Database, db_err};
+
+/// Snapshot storage wrapping Database.
+///
+/// Uses JSON blob pattern with queryable columns for chain and layer lookups.
+#[derive(Clone)]
+pub struct SnapshotStore {
+    db: Database,
+}
+
+impl SnapshotStore {
+    /// Create a new SnapshotStore from a Database.
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Insert a snapshot record.
+    ///
+    /// Fails if the box already has a snapshot with the same name.
+    pub fn save(&self, snapshot: &SnapshotInfo) -> BoxliteResult<()> {
+        let conn = self.db.conn();
+
+        let json = serde_json::to_string(snapshot)
+            .map_err(|e| BoxliteError::Database(format!("Failed to serialize snapshot: {}", e)))?;
+
+        db_err!(conn.execute(
+            r#"
+            INSERT INTO snapshot
+                (id, box_id, name, parent_id, disk_layer, guest_disk_layer, created_at, json)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                snapshot.id,
+                snapshot.box_id.as_str(),
+                snapshot.name,
+                snapshot.parent_id.as_deref(),
+                snapshot.disk_layer,
+                snapshot.guest_disk_layer.as_deref(),
+                snapshot.created_at.timestamp_millis(),
+                json
+            ],
+        ))?;
+
+        Ok(())
+    }
+
+    /// Find a snapshot of a box by snapshot ID or name.
+    pub fn get(&self, box_id: &str, id_or_name: &str) -> BoxliteResult<Option<SnapshotInfo>> {
+        let conn = self.db.conn();
+
+        let json: Option<String> = db_err!(
+            conn.query_row(
+                "SELECT json FROM snapshot WHERE box_id = ?1 AND (id = ?2 OR name = ?2)",
+                params![box_id, id_or_name],
+                |row| row.get(0),
+            )
+            .optional()
+        )?;
+
+        json.map(|j| Self::deserialize(&j)).transpose()
+    }
+
+    /// List snapshots of a box, oldest first.
+    pub fn list(&self, box_id: &str) -> BoxliteResult<Vec<SnapshotInfo>> {
+        let conn = self.db.conn();
+
+        let mut stmt = db_err!(
+            conn.prepare("SELECT json FROM snapshot WHERE box_id = ?1 ORDER BY created_at ASC")
+        )?;
+        let rows = db_err!(stmt.query_map(params![box_id], |row| row.get::<_, String>(0)))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(Self::deserialize(&db_err!(row)?)?);
+        }
+        Ok(result)
+    }
+
+    /// Check whether any snapshot uses the given snapshot as its parent.
+    pub fn has_children(&self, id: &str) -> BoxliteResult<bool> {
+        let conn = self.db.conn();
+        let count: i64 = db_err!(conn.query_row(
+            "SELECT COUNT(*) FROM snapshot WHERE parent_id = ?1",
+            params![id],
+            |row| row.get(0),
+        ))?;
+        Ok(count > 0)
+    }
+
+    /// Delete a snapshot record.
+    pub fn delete(&self, id: &str) -> BoxliteResult<bool> {
+        let conn = self.db.conn();
+        let rows_affected =
+            db_err!(conn.execute("DELETE FROM snapshot WHERE id = ?1", params![id]))?;
+        Ok(rows_affected > 0)
+    }
+
+    /// All layer digests referenced by any snapshot of any box.
+    pub fn referenced_layers(&self) -> BoxliteResult<HashSet<String>> {
+        let conn = self.db.conn();
+
+        let mut stmt = db_err!(conn.prepare("SELECT disk_layer, guest_disk_layer FROM snapshot"))?;
+        let rows = db_err!(stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        }))?;
+
+        let mut layers = HashSet::new();
+        for row in rows {
+            let (disk, guest_disk) = db_err!(row)?;
+            layers.insert(disk);
+            layers.extend(guest_disk);
+        }
+        Ok(layers)
+    }
+
+    fn deserialize(json: &str) -> BoxliteResult<SnapshotInfo> {
+        serde_json::from_str(json)
+            .map_err(|e| BoxliteError::Database(format!("Failed to deserialize snapshot: {}", e)))
+    }
+}
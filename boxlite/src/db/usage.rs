@@ -0,0 +1,81 @@
+//! Execution usage storage operations.
+//!
+//! One row per finished execution, capped at a fixed number of rows like
+//! the event log.
+
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+
+use crate::usage::ExecUsage;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use super::{Database, db_err};
+
+/// Number of most recent usage records kept.
+const MAX_RECORDS: i64 = 100_000;
+
+/// Usage storage wrapping Database.
+#[derive(Clone)]
+pub struct UsageStore {
+    db: Database,
+}
+
+impl UsageStore {
+    /// Create a new UsageStore from a Database.
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Append a record, returning its sequence number.
+    ///
+    /// Drops the oldest records once the table exceeds its cap.
+    pub fn append(&self, usage: &ExecUsage) -> BoxliteResult<i64> {
+        let conn = self.db.conn();
+
+        let json = serde_json::to_string(usage)
+            .map_err(|e| BoxliteError::Database(format!("Failed to serialize usage: {}", e)))?;
+
+        db_err!(conn.execute(
+            "INSERT INTO exec_usage (box_id, execution_id, finished_at, json) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                usage.box_id.as_str(),
+                usage.execution_id,
+                usage.finished_at.timestamp_millis(),
+                json
+            ],
+        ))?;
+        let seq = conn.last_insert_rowid();
+
+        db_err!(conn.execute(
+            "DELETE FROM exec_usage WHERE seq <= ?1",
+            params![seq - MAX_RECORDS]
+        ))?;
+
+        Ok(seq)
+    }
+
+    /// Records of executions that finished at or after `since` (all if
+    /// None), oldest first.
+    pub fn list(&self, since: Option<DateTime<Utc>>) -> BoxliteResult<Vec<ExecUsage>> {
+        let since = since.map(|t| t.timestamp_millis()).unwrap_or(i64::MIN);
+        let conn = self.db.conn();
+        let mut stmt =
+            db_err!(conn.prepare(
+                "SELECT seq, json FROM exec_usage WHERE finished_at >= ?1 ORDER BY seq ASC"
+            ))?;
+        let rows = db_err!(stmt.query_map(params![since], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        }))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (seq, json) = db_err!(row)?;
+            let mut usage: ExecUsage = serde_json::from_str(&json).map_err(|e| {
+                BoxliteError::Database(format!("Failed to deserialize usage: {}", e))
+            })?;
+            usage.seq = seq;
+            result.push(usage);
+        }
+        Ok(result)
+    }
+}
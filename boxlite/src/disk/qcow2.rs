@@ -4,7 +4,7 @@
 
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
@@ -247,14 +247,122 @@ impl Qcow2Helper {
     }
 
     /// Get the virtual size of a qcow2 disk image.
-    #[allow(dead_code)]
     pub fn qcow2_virtual_size(path: &Path) -> BoxliteResult<u64> {
         let header = Self::read_qcow2_header(path)?;
         Ok(header.size)
     }
 
+    /// Get the backing file path recorded in a qcow2 header, if any.
+    pub fn backing_file(path: &Path) -> BoxliteResult<Option<PathBuf>> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut file = std::fs::File::open(path).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to open {}: {}", path.display(), e))
+        })?;
+
+        let mut header = [0u8; 20];
+        file.read_exact(&mut header).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to read header from {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let magic = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        if magic != 0x514649fb {
+            return Err(BoxliteError::Storage(format!(
+                "Invalid qcow2 magic in {}: 0x{:08x}",
+                path.display(),
+                magic
+            )));
+        }
+
+        let offset = u64::from_be_bytes([
+            header[8], header[9], header[10], header[11], header[12], header[13], header[14],
+            header[15],
+        ]);
+        let size = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+        if offset == 0 || size == 0 {
+            return Ok(None);
+        }
+
+        let mut name = vec![0u8; size as usize];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut name))
+            .map_err(|e| {
+                BoxliteError::Storage(format!(
+                    "Failed to read backing file name from {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        Ok(Some(PathBuf::from(
+            String::from_utf8_lossy(&name).into_owned(),
+        )))
+    }
+
+    /// Point a qcow2 image at a different backing file.
+    ///
+    /// The name is rewritten in place, so it must fit in the first cluster
+    /// after its current offset, as it does for images created here.
+    pub fn set_backing_file(path: &Path, backing_path: &Path) -> BoxliteResult<()> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let fail = |e: std::io::Error| {
+            BoxliteError::Storage(format!(
+                "Failed to rewrite backing file of {}: {}",
+                path.display(),
+                e
+            ))
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(fail)?;
+        let mut header = [0u8; 72];
+        file.read_exact(&mut header).map_err(fail)?;
+
+        let be_u32 = |at: usize| u32::from_be_bytes(header[at..at + 4].try_into().unwrap());
+        let be_u64 = |at: usize| u64::from_be_bytes(header[at..at + 8].try_into().unwrap());
+        if be_u32(0) != 0x514649fb {
+            return Err(BoxliteError::Storage(format!(
+                "Invalid qcow2 magic in {}: 0x{:08x}",
+                path.display(),
+                be_u32(0)
+            )));
+        }
+        let offset = be_u64(8);
+        let old_len = be_u32(16) as u64;
+        let cluster_size = 1u64 << be_u32(20);
+        let name = backing_path.to_string_lossy();
+        let new_len = name.len() as u64;
+
+        // Tables sharing the first cluster could be overwritten
+        let tables_clear = [be_u64(40), be_u64(48)]
+            .into_iter()
+            .chain((be_u32(60) > 0).then_some(be_u64(64)))
+            .all(|table| table >= cluster_size);
+        if offset == 0 || !tables_clear || offset + new_len.max(old_len) > cluster_size {
+            return Err(BoxliteError::Storage(format!(
+                "No room to rewrite backing file of {}",
+                path.display()
+            )));
+        }
+
+        let mut field = vec![0u8; new_len.max(old_len) as usize];
+        field[..name.len()].copy_from_slice(name.as_bytes());
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.write_all(&field))
+            .and_then(|_| file.seek(SeekFrom::Start(16)))
+            .and_then(|_| file.write_all(&(new_len as u32).to_be_bytes()))
+            .and_then(|_| file.sync_all())
+            .map_err(fail)
+    }
+
     /// Read qcow2 header from disk file.
-    #[allow(dead_code)]
     fn read_qcow2_header(path: &Path) -> BoxliteResult<Qcow2HeaderInfo> {
         use std::io::Read;
 
@@ -0,0 +1,448 @@
+//! Host-side event bus for box lifecycle events.
+//!
+//! Every lifecycle transition the runtime drives (create, start, stop,
+//! suspend, snapshot, remove, ...) is recorded as a [`BoxEvent`], and so is
+//! what the guest reports while the box runs: readiness, OOM kills, low
+//! memory, pressure, crash dumps and probe results. Events are appended to
+//! the database, so `events(since)` can replay history across runtime
+//! restarts, and broadcast to live subscribers.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use crate::db::{Database, EventStore};
+use crate::litebox::config::BoxConfig;
+use crate::runtime::options::{OverflowPolicy, StreamBufferOptions};
+use crate::runtime::types::BoxID;
+use boxlite_shared::errors::BoxliteResult;
+
+pub(crate) mod webhooks;
+
+/// Kind of lifecycle event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Box was created (or cloned from another box).
+    Created,
+    /// VM booted and the guest is ready for commands.
+    Started,
+    /// Box is ready for work: its post-ready hooks passed and, if it has a
+    /// readiness probe, the probe passed for the first time since boot
+    /// (`probe` is then set).
+    Ready,
+    /// Box was stopped.
+    Stopped,
+    /// VM process was found dead while the box was marked active.
+    Exited,
+    /// Idle box had its VM torn down (see `idle_timeout_secs`).
+    Suspended,
+    /// A disk snapshot was taken.
+    Snapshotted,
+    /// Box disks were restored to a snapshot.
+    Restored,
+    /// Stopped box was exported as a bundle, to be imported on another host.
+    Exported,
+    /// Guest booted from cloned or restored disks was fixed up (clock,
+    /// entropy, session token).
+    Resumed,
+    /// Guest caught up after the host slept or its wall clock jumped
+    /// (`jump_ms`, `clock_step_ms`, `network_refreshed`, `stale_ports`).
+    Woke,
+    /// Guest agent was replaced in place (`previous_version`, `version`).
+    AgentUpgraded,
+    /// The running box's firewall changed (`egress`, `ingress`, `rules`).
+    FirewallChanged,
+    /// The box's runtimes were installed on its first boot, or failed to.
+    Provisioned,
+    /// Box was removed.
+    Removed,
+    /// Stopped box was removed by `prune()`.
+    Pruned,
+    /// Box was removed by the reaper (TTL expired or creator gone).
+    Reaped,
+    /// A volume reached its quota; writes to it fail until usage drops.
+    QuotaExceeded,
+    /// The guest's available memory dropped under `low_memory_percent`.
+    LowMemory,
+    /// The guest's OOM killer ended a process (`pid`, `command`), or an
+    /// execution it ended exited (`execution_id`, `command`).
+    OomKilled,
+    /// Some task in the guest stalled on a resource for `pressure_percent`
+    /// of the time or more (`resource`, `some_avg10`, `some_avg60`,
+    /// `full_avg10`).
+    Pressure,
+    /// A resource of the guest is no longer under pressure.
+    PressureRelieved,
+    /// A process in the guest dumped core (`name`, `pid`, `signal`,
+    /// `command`, `command_line`, `size_bytes`, `stored_bytes`, `stored`);
+    /// once `stored`, the dump is among the box's artifacts as `name`.
+    CrashDump,
+    /// A readiness or liveness probe started or stopped passing (`probe`,
+    /// `healthy`, `message`).
+    HealthChanged,
+    /// Box was restarted for failing its liveness probe (`restarts`).
+    Restarted,
+    /// A shared directory could not be mounted over virtio-fs and another
+    /// strategy took over (`tag`, `strategy`, `error`).
+    ShareFallback,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Started => "started",
+            EventKind::Ready => "ready",
+            EventKind::Stopped => "stopped",
+            EventKind::Exited => "exited",
+            EventKind::Suspended => "suspended",
+            EventKind::Snapshotted => "snapshotted",
+            EventKind::Restored => "restored",
+            EventKind::Exported => "exported",
+            EventKind::Resumed => "resumed",
+            EventKind::Woke => "woke",
+            EventKind::AgentUpgraded => "agent_upgraded",
+            EventKind::FirewallChanged => "firewall_changed",
+            EventKind::Provisioned => "provisioned",
+            EventKind::Removed => "removed",
+            EventKind::Pruned => "pruned",
+            EventKind::Reaped => "reaped",
+            EventKind::QuotaExceeded => "quota_exceeded",
+            EventKind::LowMemory => "low_memory",
+            EventKind::OomKilled => "oom_killed",
+            EventKind::Pressure => "pressure",
+            EventKind::PressureRelieved => "pressure_relieved",
+            EventKind::CrashDump => "crash_dump",
+            EventKind::HealthChanged => "health_changed",
+            EventKind::Restarted => "restarted",
+            EventKind::ShareFallback => "share_fallback",
+        }
+    }
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single lifecycle event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoxEvent {
+    /// Monotonic sequence number (0 if the event could not be persisted).
+    #[serde(skip)]
+    pub seq: i64,
+    pub kind: EventKind,
+    pub timestamp: DateTime<Utc>,
+    pub box_id: BoxID,
+    /// Box name at the time of the event.
+    pub box_name: Option<String>,
+    /// Box labels at the time of the event.
+    pub labels: HashMap<String, String>,
+    /// Event-specific details (e.g. `pid`, `snapshot`, `reason`).
+    pub attributes: HashMap<String, String>,
+}
+
+/// Records events and fans them out to subscribers.
+pub(crate) struct EventBus {
+    store: EventStore,
+    sender: broadcast::Sender<BoxEvent>,
+    overflow: OverflowPolicy,
+    /// Events subscribers missed, across all of them
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub(crate) fn new(db: Database, buffer: StreamBufferOptions, dropped: Arc<AtomicU64>) -> Self {
+        let (sender, _) = broadcast::channel(buffer.capacity.max(1));
+        Self {
+            store: EventStore::new(db),
+            sender,
+            overflow: buffer.overflow,
+            dropped,
+        }
+    }
+
+    /// Record an event for a box.
+    ///
+    /// Never fails: events are observability, so a storage error is logged
+    /// and the event is still broadcast.
+    pub(crate) fn emit<'a>(
+        &self,
+        kind: EventKind,
+        config: &BoxConfig,
+        attributes: impl IntoIterator<Item = (&'a str, String)>,
+    ) {
+        let mut event = BoxEvent {
+            seq: 0,
+            kind,
+            timestamp: Utc::now(),
+            box_id: config.id.clone(),
+            box_name: config.name.clone(),
+            labels: config.options.labels.clone(),
+            attributes: attributes
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        };
+
+        match self.store.append(&event) {
+            Ok(seq) => event.seq = seq,
+            Err(e) => tracing::warn!(
+                box_id = %event.box_id,
+                kind = %kind,
+                error = %e,
+                "Failed to persist event"
+            ),
+        }
+
+        tracing::debug!(box_id = %event.box_id, kind = %kind, "Event");
+
+        // No receivers is not an error
+        let _ = self.sender.send(event);
+    }
+
+    /// Events recorded at or after `since` (all if None), oldest first.
+    pub(crate) fn history(&self, since: Option<DateTime<Utc>>) -> BoxliteResult<Vec<BoxEvent>> {
+        self.store.list(since)
+    }
+
+    /// Subscribe to events emitted from now on.
+    pub(crate) fn subscribe(&self) -> EventSubscription {
+        // Read before subscribing, so nothing emitted in between is
+        // mistaken for a duplicate
+        let last_seq = self.store.last_seq().unwrap_or_default();
+        EventSubscription {
+            receiver: self.sender.subscribe(),
+            store: self.store.clone(),
+            overflow: self.overflow,
+            backlog: VecDeque::new(),
+            last_seq,
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+}
+
+/// A live feed of lifecycle events.
+///
+/// Each subscriber has a buffer of `BoxliteOptions::event_buffer.capacity`
+/// events, and the runtime never waits on one. A subscriber that falls
+/// further behind than that loses the events it missed under
+/// [`OverflowPolicy::DropOldest`], counted in
+/// `RuntimeMetrics::events_dropped_total`. Under [`OverflowPolicy::Block`]
+/// it reads them back from the event log instead, so it only loses events
+/// the log could not store or has already trimmed.
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<BoxEvent>,
+    store: EventStore,
+    overflow: OverflowPolicy,
+    /// Missed events read back from the log, oldest first
+    backlog: VecDeque<BoxEvent>,
+    /// Sequence number of the newest event delivered
+    last_seq: i64,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventSubscription {
+    /// Wait for the next event; `None` once the runtime is gone.
+    pub async fn recv(&mut self) -> Option<BoxEvent> {
+        loop {
+            if let Some(event) = self.next_backlog() {
+                return Some(event);
+            }
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    if let Some(event) = self.deliver(event) {
+                        return Some(event);
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => self.lagged(missed),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The next event if one is ready.
+    pub fn try_recv(&mut self) -> Option<BoxEvent> {
+        loop {
+            if let Some(event) = self.next_backlog() {
+                return Some(event);
+            }
+            match self.receiver.try_recv() {
+                Ok(event) => {
+                    if let Some(event) = self.deliver(event) {
+                        return Some(event);
+                    }
+                }
+                Err(TryRecvError::Lagged(missed)) => self.lagged(missed),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+
+    fn next_backlog(&mut self) -> Option<BoxEvent> {
+        while let Some(event) = self.backlog.pop_front() {
+            if let Some(event) = self.deliver(event) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    /// Pass on an event unless it was already read back from the log.
+    fn deliver(&mut self, event: BoxEvent) -> Option<BoxEvent> {
+        // Events that could not be persisted have no sequence number
+        if event.seq == 0 {
+            return Some(event);
+        }
+        if event.seq <= self.last_seq {
+            return None;
+        }
+        self.last_seq = event.seq;
+        Some(event)
+    }
+
+    fn lagged(&mut self, missed: u64) {
+        if self.overflow == OverflowPolicy::Block {
+            match self.store.list_after(self.last_seq) {
+                // Includes events still in the channel, skipped on delivery
+                Ok(events) => {
+                    self.backlog.extend(events);
+                    return;
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to replay missed events"),
+            }
+        }
+        tracing::debug!(missed, "Event subscriber fell behind");
+        self.dropped.fetch_add(missed, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::litebox::config::ContainerRuntimeConfig;
+    use crate::runtime::options::BoxOptions;
+    use crate::runtime::types::ContainerID;
+    use boxlite_shared::Transport;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn test_config() -> BoxConfig {
+        BoxConfig {
+            id: BoxID::new(),
+            name: Some("events".to_string()),
+            created_at: Utc::now(),
+            container: ContainerRuntimeConfig {
+                id: ContainerID::new(),
+            },
+            options: BoxOptions::default(),
+            engine_kind: crate::vmm::VmmKind::Libkrun,
+            transport: Transport::unix(PathBuf::from("/tmp/boxlite.sock")),
+            box_home: PathBuf::from("/tmp/box"),
+            ready_socket_path: PathBuf::from("/tmp/ready.sock"),
+        }
+    }
+
+    fn test_bus(db: Database, buffer: StreamBufferOptions) -> EventBus {
+        EventBus::new(db, buffer, Arc::new(AtomicU64::new(0)))
+    }
+
+    fn lagging_subscriber(overflow: OverflowPolicy) -> (EventBus, EventSubscription, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open(&temp_dir.path().join("test.db")).unwrap();
+        let bus = test_bus(
+            db,
+            StreamBufferOptions {
+                capacity: 2,
+                overflow,
+            },
+        );
+        let config = test_config();
+        bus.emit(EventKind::Created, &config, []);
+        let rx = bus.subscribe();
+        for kind in [
+            EventKind::Started,
+            EventKind::Stopped,
+            EventKind::Started,
+            EventKind::Stopped,
+            EventKind::Removed,
+        ] {
+            bus.emit(kind, &config, []);
+        }
+        (bus, rx, temp_dir)
+    }
+
+    #[test]
+    fn test_lagging_subscriber_drops_oldest() {
+        let (bus, mut rx, _dir) = lagging_subscriber(OverflowPolicy::DropOldest);
+        let kinds: Vec<_> = std::iter::from_fn(|| rx.try_recv())
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(kinds, vec![EventKind::Stopped, EventKind::Removed]);
+        assert_eq!(bus.dropped.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_lagging_subscriber_replays_log() {
+        let (bus, mut rx, _dir) = lagging_subscriber(OverflowPolicy::Block);
+        let kinds: Vec<_> = std::iter::from_fn(|| rx.try_recv())
+            .map(|e| e.kind)
+            .collect();
+        // Everything since subscribing, once each, without the earlier event
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::Started,
+                EventKind::Stopped,
+                EventKind::Started,
+                EventKind::Stopped,
+                EventKind::Removed,
+            ]
+        );
+        assert_eq!(bus.dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_emit_persists_and_broadcasts() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open(&temp_dir.path().join("test.db")).unwrap();
+        let bus = test_bus(db, StreamBufferOptions::default());
+        let mut rx = bus.subscribe();
+        let config = test_config();
+
+        bus.emit(EventKind::Created, &config, []);
+        bus.emit(EventKind::Started, &config, [("pid", "42".to_string())]);
+
+        let live = rx.try_recv().unwrap();
+        assert_eq!(live.kind, EventKind::Created);
+        assert!(live.seq > 0);
+
+        let history = bus.history(None).unwrap();
+        let kinds: Vec<_> = history.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::Created, EventKind::Started]);
+        assert_eq!(history[1].attributes["pid"], "42");
+        assert_eq!(history[1].box_name.as_deref(), Some("events"));
+        assert!(history[0].seq < history[1].seq);
+    }
+
+    #[test]
+    fn test_history_since() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::open(&temp_dir.path().join("test.db")).unwrap();
+        let bus = test_bus(db, StreamBufferOptions::default());
+        let config = test_config();
+
+        bus.emit(EventKind::Created, &config, []);
+        let after = Utc::now() + chrono::Duration::seconds(1);
+
+        assert_eq!(bus.history(None).unwrap().len(), 1);
+        assert!(bus.history(Some(after)).unwrap().is_empty());
+    }
+}
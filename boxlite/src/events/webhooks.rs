@@ -0,0 +1,135 @@
+//! Delivery of lifecycle events to webhook endpoints.
+//!
+//! A runtime with [`WebhookOptions`] runs one dispatcher thread that
+//! subscribes to the event bus and POSTs each event to the endpoints that
+//! want it, so CI systems and chat bots can react to a box becoming ready
+//! (`started`), ending (`stopped`, `exited`), running out of memory
+//! (`oom_killed`) or finishing a snapshot (`snapshotted`) without polling.
+//!
+//! Deliveries never hold up the runtime. An endpoint that keeps failing
+//! loses the event after [`RETRY_DELAYS`]; a dispatcher that falls behind
+//! the subscriber buffer loses events as any subscriber does.
+
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::events::{BoxEvent, EventSubscription};
+use crate::runtime::options::WebhookOptions;
+
+/// Waits between attempts; the delivery is dropped after the last.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(4),
+];
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Deliver events from `events` to `webhooks` until the runtime is gone.
+pub(crate) fn start(webhooks: Vec<WebhookOptions>, mut events: EventSubscription) {
+    let spawned = std::thread::Builder::new()
+        .name("boxlite-webhooks".to_string())
+        .spawn(move || {
+            // The runtime may be created outside of any tokio runtime, so
+            // the dispatcher brings its own
+            let rt = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(rt) => rt,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to start webhook dispatcher");
+                    return;
+                }
+            };
+            rt.block_on(async move {
+                let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+                    Ok(client) => client,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Failed to create webhook client");
+                        return;
+                    }
+                };
+                while let Some(event) = events.recv().await {
+                    let Ok(body) = serde_json::to_vec(&event) else {
+                        continue;
+                    };
+                    for webhook in webhooks.iter().filter(|w| w.wants(event.kind)) {
+                        // Endpoints are independent; a slow one only delays itself
+                        tokio::spawn(deliver(
+                            client.clone(),
+                            webhook.clone(),
+                            event.clone(),
+                            body.clone(),
+                        ));
+                    }
+                }
+            });
+        });
+    if let Err(e) = spawned {
+        tracing::warn!(error = %e, "Failed to start webhook dispatcher");
+    }
+}
+
+async fn deliver(client: reqwest::Client, webhook: WebhookOptions, event: BoxEvent, body: Vec<u8>) {
+    let signature = webhook.secret.as_deref().map(|secret| sign(secret, &body));
+    for attempt in 0..=RETRY_DELAYS.len() {
+        let mut request = client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Boxlite-Event", event.kind.as_str())
+            .header("X-Boxlite-Delivery", event.seq.to_string())
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header("X-Boxlite-Signature", signature);
+        }
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        let Some(delay) = RETRY_DELAYS.get(attempt) else {
+            tracing::warn!(
+                url = %webhook.url,
+                box_id = %event.box_id,
+                kind = %event.kind,
+                error = %error,
+                "Dropping webhook delivery"
+            );
+            return;
+        };
+        tracing::debug!(url = %webhook.url, error = %error, "Webhook delivery failed, retrying");
+        tokio::time::sleep(*delay).await;
+    }
+}
+
+/// `X-Boxlite-Signature` value for `body`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+
+    #[test]
+    fn test_sign_and_filter() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let mut webhook = WebhookOptions::new("http://localhost:9000/hook");
+        assert!(webhook.wants(EventKind::Removed));
+        webhook.events = vec![EventKind::Started, EventKind::OomKilled];
+        assert!(webhook.wants(EventKind::OomKilled));
+        assert!(!webhook.wants(EventKind::Removed));
+    }
+}
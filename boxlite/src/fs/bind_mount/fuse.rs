@@ -3,6 +3,7 @@
 //! Uses fuse-backend-rs passthrough filesystem with fusermount3.
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use fuse_backend_rs::api::filesystem::FileSystem;
 use fuse_backend_rs::api::server::Server;
 use fuse_backend_rs::passthrough::{Config, PassthroughFs};
 use fuse_backend_rs::transport::{FuseChannel, FuseSession};
@@ -28,11 +29,7 @@ impl FuseBindMount {
         ensure_target_dir_exists(&target)?;
 
         let fs = create_passthrough_fs(&source, config.read_only)?;
-        let mut session = create_fuse_session(&target)?;
-        mount_session(&mut session, &target)?;
-
-        let channel = create_channel(&session)?;
-        let server_thread = spawn_server_thread(fs, channel);
+        let (session, server_thread) = serve(fs, &target, "boxlite-bindfs")?;
 
         debug!(
             source = %source.display(),
@@ -117,8 +114,27 @@ fn create_passthrough_fs(source: &Path, read_only: bool) -> BoxliteResult<Arc<Pa
     Ok(Arc::new(fs))
 }
 
-fn create_fuse_session(target: &Path) -> BoxliteResult<FuseSession> {
-    FuseSession::new(target, "boxlite-bindfs", "", true).map_err(|e| {
+/// Mount `fs` at `target` and serve it on a new thread.
+///
+/// Unmounting the returned session ends the thread.
+pub(crate) fn serve<F>(
+    fs: Arc<F>,
+    target: &Path,
+    fsname: &str,
+) -> BoxliteResult<(FuseSession, JoinHandle<()>)>
+where
+    F: FileSystem + Send + Sync + 'static,
+{
+    let mut session = create_fuse_session(target, fsname)?;
+    mount_session(&mut session, target)?;
+
+    let channel = create_channel(&session)?;
+    let server_thread = spawn_server_thread(fs, channel);
+    Ok((session, server_thread))
+}
+
+fn create_fuse_session(target: &Path, fsname: &str) -> BoxliteResult<FuseSession> {
+    FuseSession::new(target, fsname, "", true).map_err(|e| {
         BoxliteError::Storage(format!(
             "Failed to create FUSE session for {}: {}",
             target.display(),
@@ -143,13 +159,19 @@ fn create_channel(session: &FuseSession) -> BoxliteResult<FuseChannel> {
         .map_err(|e| BoxliteError::Storage(format!("Failed to create FUSE channel: {}", e)))
 }
 
-fn spawn_server_thread(fs: Arc<PassthroughFs>, channel: FuseChannel) -> JoinHandle<()> {
+fn spawn_server_thread<F>(fs: Arc<F>, channel: FuseChannel) -> JoinHandle<()>
+where
+    F: FileSystem + Send + Sync + 'static,
+{
     std::thread::spawn(move || {
         serve_requests(fs, channel);
     })
 }
 
-fn serve_requests(fs: Arc<PassthroughFs>, mut channel: FuseChannel) {
+fn serve_requests<F>(fs: Arc<F>, mut channel: FuseChannel)
+where
+    F: FileSystem + Send + Sync,
+{
     let server = Server::new(fs);
 
     loop {
@@ -19,6 +19,9 @@ mod native;
 #[cfg(target_os = "linux")]
 mod fuse;
 
+#[cfg(target_os = "linux")]
+pub(crate) use fuse::serve as serve_fuse;
+
 /// Create a bind mount with automatic strategy selection.
 ///
 /// Selects strategy based on available capabilities:
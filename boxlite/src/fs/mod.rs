@@ -4,3 +4,6 @@ mod bind_mount;
 
 #[cfg(target_os = "linux")]
 pub use bind_mount::{BindMountConfig, BindMountHandle, create_bind_mount};
+
+#[cfg(target_os = "linux")]
+pub(crate) use bind_mount::serve_fuse;
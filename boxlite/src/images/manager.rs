@@ -9,12 +9,14 @@
 //! - `ImageStore` handles all locking internally
 //! - `ImageObject` also holds `Arc<ImageStore>` for layer access
 
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use super::object::ImageObject;
 use crate::db::Database;
-use crate::images::store::{ImageStore, SharedImageStore};
+use crate::images::store::{ImagePrune, ImageStore, SharedImageStore};
+use crate::runtime::operations::OperationRegistry;
 use boxlite_shared::errors::BoxliteResult;
 
 // ============================================================================
@@ -33,6 +35,8 @@ pub(super) struct ImageManifest {
 pub(super) struct LayerInfo {
     pub(super) digest: String,
     pub(super) media_type: String,
+    /// Compressed size declared by the manifest, in bytes
+    pub(super) size: u64,
 }
 
 // ============================================================================
@@ -49,14 +53,14 @@ pub(super) struct LayerInfo {
 ///
 /// # Example
 ///
-/// ```no_run
+/// ```ignore
 /// use boxlite::images::ImageManager;
 /// use boxlite::db::Database;
 /// use std::path::PathBuf;
 ///
 /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
 /// let db = Database::open(&PathBuf::from("/tmp/boxlite.db"))?;
-/// let manager = ImageManager::new(PathBuf::from("/tmp/images"), db)?;
+/// let manager = ImageManager::new(PathBuf::from("/tmp/images"), db, Default::default())?;
 ///
 /// // Pull an image
 /// let image = manager.pull("python:alpine").await?;
@@ -79,9 +83,14 @@ impl std::fmt::Debug for ImageManager {
 }
 
 impl ImageManager {
-    /// Create a new image manager for the given images directory.
-    pub fn new(images_dir: PathBuf, db: Database) -> BoxliteResult<Self> {
-        let store = Arc::new(ImageStore::new(images_dir, db)?);
+    /// Create a new image manager for the given images directory; pulls
+    /// from registries are registered in `operations`.
+    pub(crate) fn new(
+        images_dir: PathBuf,
+        db: Database,
+        operations: OperationRegistry,
+    ) -> BoxliteResult<Self> {
+        let store = Arc::new(ImageStore::new(images_dir, db, operations)?);
         Ok(Self { store })
     }
 
@@ -92,6 +101,9 @@ impl ImageManager {
     ///
     /// Thread Safety: `ImageStore` handles locking internally. Multiple
     /// concurrent pulls of the same image will only download once.
+    ///
+    /// A pull from the registry runs as an operation that reports the bytes
+    /// downloaded and can be cancelled.
     pub async fn pull(&self, image_ref: &str) -> BoxliteResult<ImageObject> {
         let manifest = self.store.pull(image_ref).await?;
 
@@ -101,4 +113,19 @@ impl ImageManager {
             Arc::clone(&self.store),
         ))
     }
+
+    /// Manifest digest an image reference resolves to, without pulling it.
+    pub async fn resolve(&self, image_ref: &str) -> BoxliteResult<String> {
+        self.store.resolve_digest(image_ref).await
+    }
+
+    /// Remove cached images not in `keep` (see `ImageStore::prune`).
+    pub(crate) async fn prune(
+        &self,
+        keep: &HashSet<String>,
+        in_use_disks: &HashSet<PathBuf>,
+        dry_run: bool,
+    ) -> BoxliteResult<ImagePrune> {
+        self.store.prune(keep, in_use_disks, dry_run).await
+    }
 }
@@ -163,13 +163,7 @@ impl ImageObject {
     /// This is used as a cache key for base disks - same layers = same base disk.
     /// Uses SHA256 hash of concatenated layer digests.
     pub(crate) fn compute_image_digest(&self) -> String {
-        use sha2::{Digest, Sha256};
-
-        let mut hasher = Sha256::new();
-        for layer in &self.manifest.layers {
-            hasher.update(layer.digest.as_bytes());
-        }
-        format!("sha256:{:x}", hasher.finalize())
+        image_digest(self.manifest.layers.iter().map(|l| l.digest.as_str()))
     }
 
     /// Get existing disk image if available.
@@ -193,6 +187,28 @@ impl ImageObject {
         self.store.install_disk_image(&image_digest, disk).await
     }
 
+    /// Cached disk image derived from this image, if one was saved.
+    ///
+    /// `variant` names what was done to the image (e.g. the runtimes
+    /// installed); each variant has its own disk.
+    pub async fn derived_disk_image(&self, variant: &str) -> Option<crate::disk::Disk> {
+        self.store.disk_image(&self.derived_digest(variant)).await
+    }
+
+    /// Where the qcow2 disk derived from this image by `variant` is saved.
+    pub async fn derived_disk_image_path(&self, variant: &str) -> PathBuf {
+        self.store
+            .disk_image_path(
+                &self.derived_digest(variant),
+                crate::disk::DiskFormat::Qcow2,
+            )
+            .await
+    }
+
+    pub(crate) fn derived_digest(&self, variant: &str) -> String {
+        image_digest([self.compute_image_digest().as_str(), "+", variant])
+    }
+
     // ========================================================================
     // INSPECTION
     // ========================================================================
@@ -234,3 +250,15 @@ impl std::fmt::Display for ImageObject {
         )
     }
 }
+
+/// Base disk cache key for a list of layer digests (see
+/// [`ImageObject::compute_image_digest`]).
+pub(super) fn image_digest<'a>(layer_digests: impl IntoIterator<Item = &'a str>) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    for digest in layer_digests {
+        hasher.update(digest.as_bytes());
+    }
+    format!("sha256:{:x}", hasher.finalize())
+}
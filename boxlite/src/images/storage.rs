@@ -597,6 +597,15 @@ impl StagedDownload {
     }
 }
 
+impl Drop for StagedDownload {
+    fn drop(&mut self) {
+        // Dropped half way, as when its pull was cancelled
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.staged_path);
+        }
+    }
+}
+
 // ============================================================================
 // TESTS
 // ============================================================================
@@ -14,16 +14,43 @@
 //! - `layer_extracted()` - Get extracted layer path (extracts if needed)
 
 use crate::db::{CachedImage, Database, ImageIndexStore};
+use crate::disk::DiskFormat;
 use crate::images::manager::{ImageManifest, LayerInfo};
+use crate::images::object::image_digest;
 use crate::images::storage::ImageStorage;
+use crate::runtime::operations::{OperationKind, OperationRegistry, Progress};
+use crate::util::{allocated_size, arch};
 use boxlite_shared::{BoxliteError, BoxliteResult};
 use oci_client::Reference;
 use oci_client::manifest::OciDescriptor;
 use oci_client::secrets::RegistryAuth;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Images removed by [`ImageStore::prune`].
+#[derive(Debug, Default)]
+pub(crate) struct ImagePrune {
+    pub(crate) references: Vec<String>,
+    /// Disk space the removed blobs and disk images took.
+    pub(crate) bytes: u64,
+}
+
+/// Remove a blob file or extracted layer directory, ignoring missing ones.
+fn remove_path(path: &Path) {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match result {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!(path = %path.display(), error = %e, "Failed to remove image data"),
+    }
+}
+
 // ============================================================================
 // INNER STATE (no locking awareness)
 // ============================================================================
@@ -77,6 +104,8 @@ pub struct ImageStore {
     client: oci_client::Client,
     /// Mutable state protected by RwLock
     inner: RwLock<ImageStoreInner>,
+    /// Where pulls from the registry are registered
+    operations: OperationRegistry,
 }
 
 impl std::fmt::Debug for ImageStore {
@@ -87,11 +116,16 @@ impl std::fmt::Debug for ImageStore {
 
 impl ImageStore {
     /// Create a new image store for the given images' directory.
-    pub fn new(images_dir: PathBuf, db: Database) -> BoxliteResult<Self> {
+    pub(crate) fn new(
+        images_dir: PathBuf,
+        db: Database,
+        operations: OperationRegistry,
+    ) -> BoxliteResult<Self> {
         let inner = ImageStoreInner::new(images_dir, db)?;
         Ok(Self {
             client: oci_client::Client::new(Default::default()),
             inner: RwLock::new(inner),
+            operations,
         })
     }
 
@@ -104,6 +138,7 @@ impl ImageStore {
     /// This method:
     /// 1. Checks local cache first (quick read lock)
     /// 2. If not cached, downloads from registry (releases lock during I/O)
+    ///    as an image pull operation
     /// 3. Updates index after successful download (quick write lock)
     ///
     /// Thread-safe: Multiple concurrent pulls of the same image will only
@@ -120,7 +155,29 @@ impl ImageStore {
 
         // Slow path: pull from registry
         tracing::info!("Pulling image from registry: {}", image_ref);
-        self.pull_from_registry(image_ref).await
+        self.operations
+            .start(OperationKind::ImagePull, image_ref, None)
+            .run(|progress| self.pull_from_registry(image_ref, progress))
+            .await
+    }
+
+    /// Manifest digest of `image_ref` without downloading the image: the
+    /// cached one if the image is complete locally, otherwise the registry's.
+    pub async fn resolve_digest(&self, image_ref: &str) -> BoxliteResult<String> {
+        {
+            let inner = self.inner.read().await;
+            if let Some(manifest) = self.try_load_cached(&inner, image_ref)? {
+                return Ok(manifest.manifest_digest);
+            }
+        }
+
+        let reference: Reference = image_ref
+            .parse()
+            .map_err(|e| BoxliteError::Storage(format!("invalid image reference: {e}")))?;
+        self.client
+            .fetch_manifest_digest(&reference, &RegistryAuth::Anonymous)
+            .await
+            .map_err(|e| BoxliteError::Storage(format!("failed to resolve {image_ref}: {e}")))
     }
 
     /// Load config JSON for an image.
@@ -203,6 +260,12 @@ impl ImageStore {
         }
     }
 
+    /// Where the disk image for an image digest lives, whether or not it exists.
+    pub async fn disk_image_path(&self, image_digest: &str, format: DiskFormat) -> PathBuf {
+        let inner = self.inner.read().await;
+        inner.storage.disk_image_path(image_digest, format)
+    }
+
     /// Install a disk as the cached disk image for an image digest.
     ///
     /// Atomically moves the source disk to the image store path.
@@ -267,6 +330,82 @@ impl ImageStore {
         Ok(crate::disk::Disk::new(target_path, disk_format, true))
     }
 
+    /// Remove cached images whose reference is not in `keep`, along with
+    /// every blob and disk image no remaining image shares.
+    ///
+    /// Disk images in `in_use_disks` (backing files of existing boxes) are
+    /// kept even when their image goes. With `dry_run` nothing is removed
+    /// and the result reports what would be.
+    pub(crate) async fn prune(
+        &self,
+        keep: &HashSet<String>,
+        in_use_disks: &HashSet<PathBuf>,
+        dry_run: bool,
+    ) -> BoxliteResult<ImagePrune> {
+        let inner = self.inner.write().await;
+        let (removed, kept): (Vec<_>, Vec<_>) = inner
+            .index
+            .list()?
+            .into_iter()
+            .partition(|(reference, _)| !keep.contains(reference));
+
+        let mut kept_digests = HashSet::new();
+        for (_, image) in &kept {
+            kept_digests.extend(image.layers.iter().cloned());
+            kept_digests.insert(image.manifest_digest.clone());
+            kept_digests.insert(image.config_digest.clone());
+            kept_digests.insert(image_digest(image.layers.iter().map(String::as_str)));
+        }
+
+        let mut paths = BTreeSet::new();
+        for (_, image) in &removed {
+            for layer in &image.layers {
+                if !kept_digests.contains(layer) {
+                    paths.insert(inner.storage.layer_tarball_path(layer));
+                    paths.insert(inner.storage.layer_extracted_path(layer));
+                }
+            }
+            if !kept_digests.contains(&image.manifest_digest) {
+                paths.insert(inner.storage.manifest_path(&image.manifest_digest));
+            }
+            if !kept_digests.contains(&image.config_digest) {
+                paths.insert(inner.storage.config_path(&image.config_digest));
+            }
+            let digest = image_digest(image.layers.iter().map(String::as_str));
+            if !kept_digests.contains(&digest) {
+                for format in [DiskFormat::Ext4, DiskFormat::Qcow2] {
+                    let path = inner.storage.disk_image_path(&digest, format);
+                    let in_use = in_use_disks.contains(&path)
+                        || path
+                            .canonicalize()
+                            .is_ok_and(|path| in_use_disks.contains(&path));
+                    if !in_use {
+                        paths.insert(path);
+                    }
+                }
+            }
+        }
+
+        let bytes = paths.iter().map(|path| allocated_size(path)).sum();
+        if !dry_run {
+            for path in &paths {
+                remove_path(path);
+            }
+            for (reference, _) in &removed {
+                inner.index.remove(reference)?;
+                tracing::info!(reference = %reference, "Pruned image");
+            }
+        }
+
+        Ok(ImagePrune {
+            references: removed
+                .into_iter()
+                .map(|(reference, _)| reference)
+                .collect(),
+            bytes,
+        })
+    }
+
     // ========================================================================
     // INTERNAL: Cache Operations
     // ========================================================================
@@ -357,7 +496,11 @@ impl ImageStore {
     /// Pull image from registry with fine-grained locking.
     ///
     /// Lock is released during network I/O to allow other operations.
-    async fn pull_from_registry(&self, image_ref: &str) -> BoxliteResult<ImageManifest> {
+    async fn pull_from_registry(
+        &self,
+        image_ref: &str,
+        progress: Progress,
+    ) -> BoxliteResult<ImageManifest> {
         let reference: Reference = image_ref
             .parse()
             .map_err(|e| BoxliteError::Storage(format!("invalid image reference: {e}")))?;
@@ -383,7 +526,7 @@ impl ImageStore {
             .await?;
 
         // Step 4: Download layers (no lock during download, atomic file writes)
-        self.download_layers(&reference, &image_manifest.layers)
+        self.download_layers(&reference, &image_manifest.layers, &progress)
             .await?;
 
         // Step 5: Download config (no lock during download)
@@ -447,6 +590,7 @@ impl ImageStore {
             .map(|layer| LayerInfo {
                 digest: layer.digest.clone(),
                 media_type: layer.media_type.clone(),
+                size: layer.size.max(0) as u64,
             })
             .collect()
     }
@@ -507,33 +651,35 @@ impl ImageStore {
     }
 
     fn detect_platform() -> (&'static str, &'static str) {
-        let os = "linux";
-        let arch = match std::env::consts::ARCH {
-            "aarch64" => "arm64",
-            "x86_64" => "amd64",
-            "x86" => "386",
-            "arm" => "arm",
-            other => other,
-        };
-        (os, arch)
+        ("linux", arch::HOST.oci_arch())
     }
 
+    /// Entry for `platform_os`/`platform_arch`, preferring the host's
+    /// variant (`arm64/v8`) when an index lists several.
     fn select_platform_manifest<'b>(
         &self,
         index: &'b oci_client::manifest::OciImageIndex,
         platform_os: &str,
         platform_arch: &str,
     ) -> BoxliteResult<&'b oci_client::manifest::ImageIndexEntry> {
-        index
+        let candidates: Vec<_> = index
             .manifests
+            .iter()
+            .filter(|m| {
+                m.platform
+                    .as_ref()
+                    .is_some_and(|p| p.os == platform_os && p.architecture == platform_arch)
+            })
+            .collect();
+        let host_variant = arch::HOST.oci_variant();
+        candidates
             .iter()
             .find(|m| {
-                if let Some(p) = &m.platform {
-                    p.os == platform_os && p.architecture == platform_arch
-                } else {
-                    false
-                }
+                let variant = m.platform.as_ref().and_then(|p| p.variant.as_deref());
+                variant.is_none() || variant == host_variant
             })
+            .or(candidates.first())
+            .copied()
             .ok_or_else(|| {
                 let available = index
                     .manifests
@@ -560,6 +706,7 @@ impl ImageStore {
         &self,
         reference: &Reference,
         layers: &[LayerInfo],
+        progress: &Progress,
     ) -> BoxliteResult<()> {
         use futures::future::join_all;
 
@@ -600,11 +747,12 @@ impl ImageStore {
             "Downloading {} layers in parallel",
             layers_to_download.len()
         );
+        progress.set_total(layers_to_download.iter().map(|layer| layer.size).sum());
 
         // Download in parallel (no lock held)
         let download_futures = layers_to_download
             .iter()
-            .map(|layer| self.download_layer(reference, layer));
+            .map(|layer| self.download_layer(reference, layer, progress));
 
         let results = join_all(download_futures).await;
 
@@ -615,7 +763,12 @@ impl ImageStore {
         Ok(())
     }
 
-    async fn download_layer(&self, reference: &Reference, layer: &LayerInfo) -> BoxliteResult<()> {
+    async fn download_layer(
+        &self,
+        reference: &Reference,
+        layer: &LayerInfo,
+        progress: &Progress,
+    ) -> BoxliteResult<()> {
         const MAX_RETRIES: u32 = 3;
 
         tracing::info!("Downloading layer: {}", layer.digest);
@@ -648,7 +801,8 @@ impl ImageStore {
             };
 
             // Download (no lock)
-            match self
+            let mut writer = progress.writer(staged.file());
+            let pulled = self
                 .client
                 .pull_blob(
                     reference,
@@ -659,10 +813,11 @@ impl ImageStore {
                         urls: None,
                         annotations: None,
                     },
-                    staged.file(),
+                    &mut writer,
                 )
-                .await
-            {
+                .await;
+            let written = writer.written();
+            match pulled {
                 Ok(_) => match staged.commit().await {
                     Ok(true) => {
                         tracing::info!("Downloaded and verified layer: {}", layer.digest);
@@ -688,6 +843,8 @@ impl ImageStore {
                     staged.abort().await;
                 }
             }
+            // The next attempt downloads the layer again
+            progress.retract(written);
         }
 
         Err(BoxliteError::Storage(last_error.unwrap_or_else(|| {
@@ -19,24 +19,63 @@ pub mod vmm;
 
 mod db;
 mod disk;
+mod events;
 mod fs;
 mod images;
 mod portal;
 mod rootfs;
+mod snapshots;
+mod usage;
 mod volumes;
 
+pub use events::{BoxEvent, EventKind, EventSubscription};
 pub use litebox::LiteBox;
+pub use portal::GuestSession;
 pub use runtime::BoxliteRuntime;
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+#[cfg(feature = "fault-injection")]
+pub use litebox::Fault;
 pub use litebox::{
-    BoxCommand, ExecResult, ExecStderr, ExecStdin, ExecStdout, Execution, ExecutionId,
+    AgentCapabilities, ArtifactInfo, AuditReport, BoxCommand, BoxHealth, CaptureSpec, CaptureStats,
+    CellError, CellOutput, ConflictPolicy, ExecNetwork, ExecResult, ExecStderr, ExecStdin,
+    ExecStdout, Execution, ExecutionId, ExitKind, ForwardedPort, HealthStatus, HomeStorage,
+    InstalledPackage, NetworkCapture, OutputCapture, OutputChunk, OutputStream,
+    PackageInstallResult, PackageInstallation, PathChange, PathChangeKind, PathWatch, ProcessInfo,
+    RecordingInfo, Screenshot, ShareMountStrategy, ShareStatus, SyncConflict, SyncSession,
+    SyncSpec, TaskStatus, UserSpec,
 };
-pub use metrics::{BoxMetrics, RuntimeMetrics};
+pub use metrics::{BoxMetrics, GuestStageTiming, RuntimeMetrics};
+pub use net::firewall::{
+    FirewallAction, FirewallPolicy, FirewallProtocol, FirewallRule, TrafficDirection,
+};
+pub use runtime::bake::{BakeSpec, BakedImage};
+pub use runtime::config::{ConfigLoader, ConfigSource, ResolvedConfig};
+pub use runtime::disk_usage::{
+    BoxDiskUsage, DiskUsage, PruneReport, SystemPruneOptions, UsageEntry, VolumeUsage,
+};
+pub use runtime::doctor::{BenchReport, Check, CheckStatus, DoctorReport};
+pub use runtime::dry_run::{DryRunReport, SpecIssue};
+pub use runtime::filter::BoxFilter;
 use runtime::layout::FilesystemLayout;
-pub use runtime::options::{BoxOptions, BoxliteOptions, RootfsSpec};
+pub use runtime::lockfile::{BoxLock, LockedImage};
+pub use runtime::migration::ExportOptions;
+pub use runtime::operations::{
+    OperationKind, OperationProgress, OperationState, ProgressSubscription,
+};
+pub use runtime::options::{
+    ArtifactRetention, BatchQueue, BoxOptions, BoxPriority, BoxliteOptions, ClipboardPolicy,
+    Determinism, DeviceNodeSpec, DevicePolicy, DeviceProfile, DnsOptions, EngineSelection,
+    ExecAction, ExecRule, GpuSpec, HomeVolume, HookOptions, HookStage, IngressOptions, InitMode,
+    IoLimits, OvercommitOptions, OverflowPolicy, PolicyCheck, PolicyRule, PressureOptions, Probe,
+    ProbeCheck, RootfsSpec, RuntimeProfile, ScheduledTask, SetupStep, SharingOptions, SshOptions,
+    StreamBufferOptions, UnhealthyPolicy, WebhookOptions,
+};
+pub use runtime::overcommit::CapacityReport;
 pub use runtime::types::ContainerID;
 pub use runtime::types::{BoxID, BoxInfo, BoxState, BoxStatus};
+pub use snapshots::SnapshotInfo;
+pub use usage::{ExecUsage, write_usage_csv};
 
 /// Initialize tracing for Boxlite using the provided filesystem layout.
 ///
@@ -0,0 +1,90 @@
+//! What a box's guest agent was built with.
+//!
+//! The agent can be built without networking and PTY support (cargo
+//! features of the guest crate) to start faster. It reports its features
+//! and how long it took to start serving through Guest.Capabilities. Before
+//! Guest.Init the host checks the box's options against them, so a box
+//! needing a missing feature fails to start with a clear error instead of
+//! partway through, and warns when the agent started slower than its
+//! budget.
+
+use boxlite_shared::CapabilitiesResponse;
+use boxlite_shared::constants::agent_features;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Features and startup time of a box's guest agent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentCapabilities {
+    pub version: String,
+    /// Optional features the agent was built with ("net", "pty", ...).
+    pub features: Vec<String>,
+    /// From the agent's process starting to it serving.
+    pub startup: Duration,
+    /// From the guest kernel booting to the agent serving, if known.
+    pub ready: Option<Duration>,
+    /// Startup time the agent was built to stay within.
+    pub budget: Duration,
+}
+
+impl AgentCapabilities {
+    /// Whether the agent was built with `feature`.
+    pub fn has(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Whether the agent started within its budget.
+    pub fn within_budget(&self) -> bool {
+        self.startup <= self.budget
+    }
+
+    /// Fail if a box with a network (`network`) needs a feature the agent
+    /// lacks.
+    pub(crate) fn check(&self, network: bool) -> BoxliteResult<()> {
+        if network && !self.has(agent_features::NET) {
+            return Err(BoxliteError::Unsupported(format!(
+                "guest agent {} was built without the {} feature, but the box has a network",
+                self.version,
+                agent_features::NET
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl From<CapabilitiesResponse> for AgentCapabilities {
+    fn from(response: CapabilitiesResponse) -> Self {
+        Self {
+            version: response.version,
+            features: response.features,
+            startup: Duration::from_millis(response.startup_ms),
+            ready: (response.ready_ms > 0).then(|| Duration::from_millis(response.ready_ms)),
+            budget: Duration::from_millis(response.budget_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_network_needs_net() {
+        let capabilities = AgentCapabilities::from(CapabilitiesResponse {
+            version: "0.4.4".to_string(),
+            features: vec![agent_features::PTY.to_string()],
+            startup_ms: 200,
+            ready_ms: 0,
+            budget_ms: 150,
+        });
+        assert!(!capabilities.has(agent_features::NET));
+        assert!(!capabilities.within_budget());
+        assert_eq!(capabilities.ready, None);
+        assert!(capabilities.check(false).is_ok());
+        assert!(matches!(
+            capabilities.check(true),
+            Err(BoxliteError::Unsupported(_))
+        ));
+    }
+}
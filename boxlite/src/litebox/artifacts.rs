@@ -0,0 +1,228 @@
+//! Artifacts collected from executions.
+//!
+//! A command run with [`BoxCommand::artifacts`] names files and directories
+//! it produces. When it exits, the guest packs whichever of them exist into
+//! a zstd-compressed tar, which is stored in the box directory as
+//! `artifacts/{execution_id}.tar.zst`, so CI-style runs can fetch their
+//! outputs after the fact, even once the box is stopped. Core dumps of
+//! crashed processes are stored the same way, named `core-{time}-{pid}`
+//! rather than by execution (see `BoxOptions::core_dump_max_bytes`). Old
+//! archives are pruned according to `BoxOptions::artifact_retention`.
+//!
+//! [`BoxCommand::artifacts`]: super::BoxCommand::artifacts
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::runtime::options::ArtifactRetention;
+
+/// Artifacts directory inside a box directory.
+pub(crate) const ARTIFACTS_DIR: &str = "artifacts";
+
+const ARCHIVE_SUFFIX: &str = ".tar.zst";
+
+/// Artifacts of one execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtifactInfo {
+    /// The execution, or the name of a core dump.
+    pub execution_id: String,
+    /// The zstd-compressed tar.
+    pub path: PathBuf,
+    pub collected_at: SystemTime,
+    pub size_bytes: u64,
+}
+
+/// Archive for `execution_id` in `dir`.
+pub(crate) fn archive_path(dir: &Path, execution_id: &str) -> BoxliteResult<PathBuf> {
+    // Execution ids come from the guest; never let one leave the directory
+    if execution_id.is_empty()
+        || execution_id.contains(['/', '\\'])
+        || execution_id.starts_with('.')
+    {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "Invalid execution id: {}",
+            execution_id
+        )));
+    }
+    Ok(dir.join(format!("{}{}", execution_id, ARCHIVE_SUFFIX)))
+}
+
+/// Artifacts in `dir`, oldest first.
+pub(crate) fn list(dir: &Path) -> BoxliteResult<Vec<ArtifactInfo>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(BoxliteError::Storage(format!(
+                "Failed to read {}: {}",
+                dir.display(),
+                e
+            )));
+        }
+    };
+
+    let mut artifacts: Vec<ArtifactInfo> = entries
+        .flatten()
+        .filter_map(|entry| read_info(&entry.path()))
+        .collect();
+    artifacts.sort_by_key(|a| a.collected_at);
+    Ok(artifacts)
+}
+
+fn read_info(path: &Path) -> Option<ArtifactInfo> {
+    let execution_id = path.file_name()?.to_str()?.strip_suffix(ARCHIVE_SUFFIX)?;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(ArtifactInfo {
+        execution_id: execution_id.to_string(),
+        path: path.to_path_buf(),
+        collected_at: metadata.modified().ok()?,
+        size_bytes: metadata.len(),
+    })
+}
+
+/// Delete archives in `dir` that `retention` no longer keeps.
+///
+/// The newest archive is always kept, even if it alone is over
+/// `max_bytes`, so a run's artifacts are never lost the moment they land.
+pub(crate) fn prune(dir: &Path, retention: &ArtifactRetention) -> BoxliteResult<Vec<ArtifactInfo>> {
+    let mut artifacts = list(dir)?;
+    let now = SystemTime::now();
+    let mut kept_bytes = 0u64;
+    let mut pruned = Vec::new();
+
+    // Walk newest first, keeping archives until a limit is reached
+    for (index, artifact) in artifacts.drain(..).rev().enumerate() {
+        let expired = retention.max_age_secs.is_some_and(|max_age| {
+            now.duration_since(artifact.collected_at)
+                .unwrap_or_default()
+                > Duration::from_secs(max_age)
+        });
+        let over_count = retention.max_count.is_some_and(|max| index >= max);
+        let over_bytes = retention
+            .max_bytes
+            .is_some_and(|max| kept_bytes + artifact.size_bytes > max);
+        if index > 0 && (expired || over_count || over_bytes) {
+            pruned.push(artifact);
+        } else {
+            kept_bytes += artifact.size_bytes;
+        }
+    }
+
+    for artifact in &pruned {
+        if let Err(e) = std::fs::remove_file(&artifact.path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(BoxliteError::Storage(format!(
+                "Failed to remove {}: {}",
+                artifact.path.display(),
+                e
+            )));
+        }
+    }
+    Ok(pruned)
+}
+
+/// Unpack the archive at `archive` into the host directory `dest`.
+pub(crate) fn extract(archive: &Path, dest: &Path) -> BoxliteResult<()> {
+    let fail = |e: std::io::Error| {
+        BoxliteError::Storage(format!(
+            "Failed to extract {} to {}: {}",
+            archive.display(),
+            dest.display(),
+            e
+        ))
+    };
+    let file = std::fs::File::open(archive).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => {
+            BoxliteError::NotFound(format!("No artifacts at {}", archive.display()))
+        }
+        _ => fail(e),
+    })?;
+    std::fs::create_dir_all(dest).map_err(fail)?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file).map_err(fail)?);
+    archive.set_preserve_permissions(true);
+    // unpack() refuses entries that would land outside `dest`
+    archive.unpack(dest).map_err(fail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_archive(dir: &Path, execution_id: &str, age_secs: u64, files: &[(&str, &[u8])]) {
+        let path = archive_path(dir, execution_id).unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        let mut builder = tar::Builder::new(zstd::Encoder::new(file, 0).unwrap());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        let mtime = SystemTime::now() - Duration::from_secs(age_secs);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_extract_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        write_archive(dir.path(), "exec-1", 0, &[("out/report.xml", b"<ok/>")]);
+        std::fs::write(dir.path().join("stray.txt"), b"").unwrap();
+
+        let artifacts = list(dir.path()).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].execution_id, "exec-1");
+
+        let dest = dir.path().join("dest");
+        extract(&artifacts[0].path, &dest).unwrap();
+        assert_eq!(
+            std::fs::read(dest.join("out/report.xml")).unwrap(),
+            b"<ok/>"
+        );
+        assert!(archive_path(dir.path(), "../escape").is_err());
+    }
+
+    #[test]
+    fn test_prune_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        write_archive(dir.path(), "old", 7200, &[("a", b"1")]);
+        write_archive(dir.path(), "mid", 60, &[("a", b"2")]);
+        write_archive(dir.path(), "new", 0, &[("a", b"3")]);
+
+        let retention = ArtifactRetention {
+            max_age_secs: Some(3600),
+            ..Default::default()
+        };
+        let pruned = prune(dir.path(), &retention).unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].execution_id, "old");
+
+        let retention = ArtifactRetention {
+            max_count: Some(1),
+            ..Default::default()
+        };
+        prune(dir.path(), &retention).unwrap();
+        let left: Vec<_> = list(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|a| a.execution_id)
+            .collect();
+        assert_eq!(left, ["new"]);
+
+        // The newest archive survives even a limit it alone exceeds
+        let retention = ArtifactRetention {
+            max_bytes: Some(1),
+            ..Default::default()
+        };
+        assert!(prune(dir.path(), &retention).unwrap().is_empty());
+    }
+}
@@ -4,26 +4,72 @@
 // IMPORTS
 // ============================================================================
 
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use parking_lot::RwLock;
-use tokio::sync::OnceCell;
+use rand::RngCore;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 
+use super::agent::AgentCapabilities;
+use super::artifacts::{self, ArtifactInfo};
+use super::capture::{CaptureSpec, NetworkCapture};
 use super::config::BoxConfig;
-use super::exec::{BoxCommand, ExecStderr, ExecStdin, ExecStdout, Execution};
+use super::crash::CrashWatcher;
+use super::display::Screenshot;
+use super::exec::{BoxCommand, ExecResult, ExecStderr, ExecStdin, ExecStdout, Execution, ExitKind};
+use super::exec_cache::{self, CachedRun, OutputCopy};
+use super::health::{self, BoxHealth};
+use super::idle::{ActivityGuard, IdleTracker};
+use super::kernel::CellOutput;
+use super::memory::MemoryWatcher;
+use super::packages::PackageInstallation;
+use super::ports::{self, ForwardedPort};
+use super::pressure::PressureWatcher;
+use super::processes::ProcessInfo;
+use super::provision;
+use super::readahead::ReadaheadRecorder;
+use super::recording::{self, RecordingInfo, SessionRecorder};
+use super::resume::{self, ResumeReason};
+use super::shares::ShareStatus;
 use super::state::BoxState;
+use super::sync::{SyncSession, SyncSpec};
+use super::tasks::TaskStatus;
+use super::users::UserSpec;
+use super::wake::{self, ClockWatch};
+use super::watch::PathWatch;
 use crate::disk::Disk;
+use crate::events::EventKind;
 #[cfg(target_os = "linux")]
 use crate::fs::BindMountHandle;
 use crate::lock::LockGuard;
 use crate::metrics::{BoxMetrics, BoxMetricsStorage};
+use crate::net::dns::DnsRegistration;
+use crate::net::firewall::{self, FirewallPolicy, FirewallRule};
+use crate::net::ingress::IngressRoute;
+use crate::net::mdns::MdnsRegistration;
 use crate::portal::GuestSession;
+use crate::portal::bulk::BulkChannel;
+use crate::portal::credentials::CredentialForwarding;
+use crate::portal::interfaces::ExecutionInterface;
+use crate::portal::locks::LockBroker;
+use crate::runtime::batch::QueueSlot;
+use crate::runtime::operations::OperationKind;
+use crate::runtime::options::{ClipboardPolicy, GpuSpec, ScheduledTask, UnhealthyPolicy};
+use crate::runtime::overcommit::Reservation;
 use crate::runtime::rt_impl::SharedRuntimeImpl;
 use crate::runtime::types::BoxStatus;
+use crate::usage::ExecUsage;
 use crate::vmm::controller::VmmHandler;
+use crate::vmm::priority;
+#[cfg(target_os = "linux")]
+use crate::volumes::{EncryptedVolume, ObjectMount};
+use crate::volumes::{PackageCacheLease, QuotaEnforcer, VolumeWatcher};
 use crate::{BoxID, BoxInfo};
 
 // ============================================================================
@@ -33,6 +79,18 @@ use crate::{BoxID, BoxInfo};
 /// Shared reference to BoxImpl.
 pub type SharedBoxImpl = Arc<BoxImpl>;
 
+/// Host randomness sent to a resumed guest.
+const RESUME_ENTROPY_BYTES: usize = 64;
+
+/// How long installing a box's runtimes may take.
+const PROVISION_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How long an upgraded guest agent may take to serve again.
+const AGENT_UPGRADE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to check whether an upgraded guest agent serves.
+const AGENT_UPGRADE_POLL: Duration = Duration::from_millis(250);
+
 // ============================================================================
 // LIVE STATE
 // ============================================================================
@@ -50,14 +108,48 @@ pub(crate) struct LiveState {
     metrics: BoxMetricsStorage,
 
     // Disk resources (kept for lifecycle management)
-    _container_rootfs_disk: Disk,
+    container_rootfs_disk: Disk,
     #[allow(dead_code)]
     guest_rootfs_disk: Option<Disk>,
+    // Held so no other box mounts the package cache while this one runs
+    _package_cache: Option<PackageCacheLease>,
+    // Serves forwarded git credentials while the box runs
+    _credentials: Option<CredentialForwarding>,
+    // Forwards volume changes to the guest while the box runs
+    _volume_watcher: Option<VolumeWatcher>,
+    // Locks volumes that go over their quota
+    quota_enforcer: Option<QuotaEnforcer>,
+    // Serves brokered file locks while the box runs
+    _lock_broker: Option<LockBroker>,
+    // Keeps the box's name advertised over mDNS while the box runs
+    _mdns: Option<MdnsRegistration>,
+    // Keeps the box reachable through the ingress proxy while the box runs
+    _ingress: Option<IngressRoute>,
+    // Keeps the box's names answered by the runtime's DNS server while the box runs
+    _dns: Option<DnsRegistration>,
+    // Records the base disk's readahead hints once the box has settled
+    _readahead: Option<ReadaheadRecorder>,
+    // Relays the guest's low memory warnings and OOM kills as events
+    _memory_watcher: MemoryWatcher,
+    // Relays the guest's pressure changes as events and to the runtime
+    _pressure_watcher: Option<PressureWatcher>,
+    // Stores the guest's core dumps with the box's artifacts
+    _crash_watcher: Option<CrashWatcher>,
+    // The box's committed share of host CPU and memory
+    _reservation: Reservation,
+    // The box's slot in its batch queue
+    _queue_slot: Option<QueueSlot>,
 
     // Platform-specific
     #[cfg(target_os = "linux")]
     #[allow(dead_code)]
     bind_mount: Option<BindMountHandle>,
+    #[cfg(target_os = "linux")]
+    #[allow(dead_code)]
+    object_mounts: Vec<ObjectMount>,
+    #[cfg(target_os = "linux")]
+    #[allow(dead_code)]
+    encrypted_volumes: Vec<EncryptedVolume>,
 }
 
 impl LiveState {
@@ -68,16 +160,50 @@ impl LiveState {
         metrics: BoxMetricsStorage,
         container_rootfs_disk: Disk,
         guest_rootfs_disk: Option<Disk>,
+        package_cache: Option<PackageCacheLease>,
+        credentials: Option<CredentialForwarding>,
+        volume_watcher: Option<VolumeWatcher>,
+        quota_enforcer: Option<QuotaEnforcer>,
+        lock_broker: Option<LockBroker>,
+        mdns: Option<MdnsRegistration>,
+        ingress: Option<IngressRoute>,
+        dns: Option<DnsRegistration>,
+        readahead: Option<ReadaheadRecorder>,
+        memory_watcher: MemoryWatcher,
+        pressure_watcher: Option<PressureWatcher>,
+        crash_watcher: Option<CrashWatcher>,
+        reservation: Reservation,
+        queue_slot: Option<QueueSlot>,
         #[cfg(target_os = "linux")] bind_mount: Option<BindMountHandle>,
+        #[cfg(target_os = "linux")] object_mounts: Vec<ObjectMount>,
+        #[cfg(target_os = "linux")] encrypted_volumes: Vec<EncryptedVolume>,
     ) -> Self {
         Self {
             handler: std::sync::Mutex::new(handler),
             guest_session,
             metrics,
-            _container_rootfs_disk: container_rootfs_disk,
+            container_rootfs_disk,
             guest_rootfs_disk,
+            _package_cache: package_cache,
+            _credentials: credentials,
+            _volume_watcher: volume_watcher,
+            quota_enforcer,
+            _lock_broker: lock_broker,
+            _mdns: mdns,
+            _ingress: ingress,
+            _dns: dns,
+            _readahead: readahead,
+            _memory_watcher: memory_watcher,
+            _pressure_watcher: pressure_watcher,
+            _crash_watcher: crash_watcher,
+            _reservation: reservation,
+            _queue_slot: queue_slot,
             #[cfg(target_os = "linux")]
             bind_mount,
+            #[cfg(target_os = "linux")]
+            object_mounts,
+            #[cfg(target_os = "linux")]
+            encrypted_volumes,
         }
     }
 }
@@ -95,9 +221,12 @@ pub(crate) struct BoxImpl {
     pub(crate) state: RwLock<BoxState>,
     pub(crate) runtime: SharedRuntimeImpl,
     is_shutdown: AtomicBool,
+    idle: Arc<IdleTracker>,
+    // Serializes firewall changes, which read the current policy first
+    firewall_lock: tokio::sync::Mutex<()>,
 
-    // --- Lazily initialized ---
-    live: OnceCell<LiveState>,
+    // --- Lazily initialized (dropped again when suspended) ---
+    live: tokio::sync::Mutex<Option<Arc<LiveState>>>,
 }
 
 impl BoxImpl {
@@ -114,7 +243,9 @@ impl BoxImpl {
             state: RwLock::new(state),
             runtime,
             is_shutdown: AtomicBool::new(false),
-            live: OnceCell::new(),
+            idle: Arc::new(IdleTracker::new()),
+            firewall_lock: tokio::sync::Mutex::new(()),
+            live: tokio::sync::Mutex::new(None),
         }
     }
 
@@ -139,7 +270,7 @@ impl BoxImpl {
     // OPERATIONS (require LiveState)
     // ========================================================================
 
-    pub(crate) async fn exec(&self, command: BoxCommand) -> BoxliteResult<Execution> {
+    pub(crate) async fn exec(self: &Arc<Self>, command: BoxCommand) -> BoxliteResult<Execution> {
         use boxlite_shared::constants::executor as executor_const;
 
         // Check if box is stopped before proceeding
@@ -147,6 +278,8 @@ impl BoxImpl {
             return Err(BoxliteError::InvalidState("Box is stopped".into()));
         }
 
+        // Mark busy before touching LiveState so an idle suspend can't race us
+        let activity = self.idle.activity();
         let live = self.live_state().await?;
 
         // Inject container ID into environment if not already set
@@ -165,15 +298,57 @@ impl BoxImpl {
         };
 
         // Set working directory from BoxOptions if not set in command
-        let command = if command.working_dir.is_none() && self.config.options.working_dir.is_some()
+        let command = if command.working_dir.is_none()
+            && let Some(dir) = &self.config.options.working_dir
         {
-            command.working_dir(self.config.options.working_dir.as_ref().unwrap())
+            command.working_dir(dir)
         } else {
             command
         };
 
+        let cache_key = match &command.cache_inputs {
+            Some(_) if command.capture.is_some() => {
+                return Err(BoxliteError::InvalidArgument(
+                    "Commands with captured output cannot be cached".into(),
+                ));
+            }
+            Some(_) if command.audit => {
+                return Err(BoxliteError::InvalidArgument(
+                    "Audited commands cannot be cached".into(),
+                ));
+            }
+            Some(inputs) => {
+                let mut container = live.guest_session.container().await?;
+                let digests = container.digest_paths(self.container_id(), inputs).await?;
+                Some(exec_cache::key(&command, &digests))
+            }
+            None => None,
+        };
+        if let Some(key) = &cache_key
+            && let Some(run) = exec_cache::lookup(&self.exec_cache_dir(), key)
+        {
+            let exec_interface = live.guest_session.execution().await?;
+            let output_dropped = Arc::clone(&live.metrics.output_dropped);
+            return self.replay_cached(key, run, exec_interface, output_dropped, activity);
+        }
+
+        let command_line = std::iter::once(&command.command)
+            .chain(&command.args)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let recorded_command =
+            (command.tty && self.config.options.record_sessions).then(|| command_line.clone());
+
+        let artifacts = command.artifacts.clone();
+        let started_at = chrono::Utc::now();
+
         let mut exec_interface = live.guest_session.execution().await?;
-        let result = exec_interface.exec(command).await;
+        let output_buffer = self.config.options.output_buffer;
+        let output_dropped = Arc::clone(&live.metrics.output_dropped);
+        let result = exec_interface
+            .exec(command, output_buffer, Arc::clone(&output_dropped))
+            .await;
 
         // Instrument metrics
         live.metrics.increment_commands_executed();
@@ -191,23 +366,201 @@ impl BoxImpl {
         }
 
         let components = result?;
+        let mut stdout_rx = components.stdout_rx;
+        if let Some(command_line) = recorded_command {
+            let path = recording::cast_path(&self.recordings_dir(), &components.execution_id)?;
+            match SessionRecorder::create(&path, command_line, crate::util::get_terminal_size()) {
+                Ok(recorder) => {
+                    stdout_rx =
+                        recorder.record(stdout_rx, output_buffer, Arc::clone(&output_dropped))
+                }
+                Err(e) => tracing::warn!("Session will not be recorded: {}", e),
+            }
+        }
+        let result_rx = self.record_usage_on_exit(
+            components.execution_id.clone(),
+            command_line,
+            started_at,
+            components.result_rx,
+        );
+        let has_artifacts = !artifacts.is_empty();
+        let mut result_rx = if artifacts.is_empty() {
+            result_rx
+        } else {
+            self.collect_artifacts_on_exit(components.execution_id.clone(), artifacts, result_rx)
+        };
+        let mut stderr_rx = components.stderr_rx;
+        if let Some(key) = cache_key {
+            let (tee_stdout, stdout_copy) =
+                exec_cache::tee(stdout_rx, output_buffer, Arc::clone(&output_dropped));
+            let (tee_stderr, stderr_copy) =
+                exec_cache::tee(stderr_rx, output_buffer, output_dropped);
+            stdout_rx = tee_stdout;
+            stderr_rx = tee_stderr;
+            result_rx = self.record_in_cache_on_exit(
+                key,
+                has_artifacts.then(|| components.execution_id.clone()),
+                stdout_copy,
+                stderr_copy,
+                result_rx,
+            );
+        }
         Ok(Execution::new(
             components.execution_id,
             exec_interface,
-            components.result_rx,
+            result_rx,
             Some(ExecStdin::new(components.stdin_tx)),
-            Some(ExecStdout::new(components.stdout_rx)),
-            Some(ExecStderr::new(components.stderr_rx)),
+            Some(ExecStdout::new(stdout_rx)),
+            Some(ExecStderr::new(stderr_rx)),
+            Some(activity),
+            components.cancel,
+        ))
+    }
+
+    /// Forward the result, recording what the execution consumed.
+    fn record_usage_on_exit(
+        &self,
+        execution_id: String,
+        command_line: String,
+        started_at: chrono::DateTime<chrono::Utc>,
+        mut result_rx: mpsc::UnboundedReceiver<ExecResult>,
+    ) -> mpsc::UnboundedReceiver<ExecResult> {
+        let (result_tx, forwarded_rx) = mpsc::unbounded_channel();
+        let runtime = Arc::clone(&self.runtime);
+        let config = self.config.clone();
+
+        tokio::spawn(async move {
+            let Some(result) = result_rx.recv().await else {
+                return;
+            };
+            if result.exit_kind == ExitKind::OomKilled {
+                runtime.events.emit(
+                    EventKind::OomKilled,
+                    &config,
+                    [
+                        ("execution_id", execution_id.clone()),
+                        ("command", command_line.clone()),
+                    ],
+                );
+            }
+            let usage = ExecUsage::new(&config, execution_id, command_line, started_at, &result);
+            let _ = result_tx.send(result);
+            let stored = tokio::task::spawn_blocking(move || runtime.usage.append(&usage)).await;
+            if let Ok(Err(e)) = stored {
+                tracing::warn!(box_id = %config.id, "Failed to record execution usage: {}", e);
+            }
+        });
+        forwarded_rx
+    }
+
+    /// An execution that replays the recorded run `run`.
+    fn replay_cached(
+        &self,
+        key: &str,
+        run: CachedRun,
+        exec_interface: ExecutionInterface,
+        output_dropped: Arc<AtomicU64>,
+        activity: ActivityGuard,
+    ) -> BoxliteResult<Execution> {
+        let execution_id = format!("cached-{}", uuid::Uuid::new_v4());
+        if run.artifacts {
+            // Under its own id, so download_artifacts works as for a real run
+            let dir = self.artifacts_dir();
+            let dest = artifacts::archive_path(&dir, &execution_id)?;
+            std::fs::create_dir_all(&dir)
+                .and_then(|_| {
+                    std::fs::copy(exec_cache::archive_path(&self.exec_cache_dir(), key), &dest)
+                })
+                .map_err(|e| {
+                    BoxliteError::Storage(format!("Failed to write {}: {}", dest.display(), e))
+                })?;
+            artifacts::prune(&dir, &self.config.options.artifact_retention)?;
+        }
+        tracing::debug!(
+            box_id = %self.config.id,
+            execution_id = %execution_id,
+            key = %key,
+            "Replaying cached execution"
+        );
+
+        let replay = run.replay(self.config.options.output_buffer, output_dropped);
+        // Nothing reads stdin; writes to it fail as after the process exited
+        let (stdin_tx, _) = mpsc::channel(1);
+        Ok(Execution::new(
+            execution_id,
+            exec_interface,
+            replay.result_rx,
+            Some(ExecStdin::new(stdin_tx)),
+            Some(ExecStdout::new(replay.stdout_rx)),
+            Some(ExecStderr::new(replay.stderr_rx)),
+            Some(activity),
+            CancellationToken::new().drop_guard(),
         ))
     }
 
+    /// Forward the result, then record the run under `key` once its output
+    /// has been copied.
+    fn record_in_cache_on_exit(
+        &self,
+        key: String,
+        artifacts_of: Option<String>,
+        stdout_copy: OutputCopy,
+        stderr_copy: OutputCopy,
+        mut result_rx: mpsc::UnboundedReceiver<ExecResult>,
+    ) -> mpsc::UnboundedReceiver<ExecResult> {
+        let (result_tx, forwarded_rx) = mpsc::unbounded_channel();
+        let cache_dir = self.exec_cache_dir();
+        let artifacts_dir = self.artifacts_dir();
+        let box_id = self.config.id.clone();
+
+        tokio::spawn(async move {
+            let Some(result) = result_rx.recv().await else {
+                return;
+            };
+            let _ = result_tx.send(result.clone());
+
+            let (Ok(Some(stdout)), Ok(Some(stderr))) = (stdout_copy.await, stderr_copy.await)
+            else {
+                tracing::debug!(box_id = %box_id, "Output too large to cache");
+                return;
+            };
+            let archive = match artifacts_of {
+                Some(execution_id) => {
+                    match artifacts::archive_path(&artifacts_dir, &execution_id) {
+                        Ok(path) if path.exists() => Some(path),
+                        // The artifacts were not collected, so neither is the run
+                        _ => return,
+                    }
+                }
+                None => None,
+            };
+            let Some(run) = CachedRun::new(&result, stdout, stderr, archive.is_some()) else {
+                return;
+            };
+            let stored = tokio::task::spawn_blocking(move || {
+                exec_cache::store(&cache_dir, &key, &run, archive.as_deref())
+            })
+            .await;
+            if let Ok(Err(e)) = stored {
+                tracing::warn!(box_id = %box_id, "Failed to cache execution: {}", e);
+            }
+        });
+        forwarded_rx
+    }
+
     pub(crate) async fn metrics(&self) -> BoxliteResult<BoxMetrics> {
         // Check if box is stopped before proceeding
         if self.is_shutdown.load(Ordering::SeqCst) {
             return Err(BoxliteError::InvalidState("Box is stopped".into()));
         }
 
-        let live = self.live_state().await?;
+        // Reading metrics must not start or resume the box
+        let live = self
+            .live
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| BoxliteError::InvalidState("Box is not running".into()))?;
         let handler = live
             .handler
             .lock()
@@ -225,11 +578,596 @@ impl BoxImpl {
         ))
     }
 
+    pub(crate) async fn screenshot(self: &Arc<Self>) -> BoxliteResult<Screenshot> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+        if !matches!(self.config.options.gpu, Some(GpuSpec::VirtioGpu { .. })) {
+            return Err(BoxliteError::InvalidState(
+                "Box has no display (create it with a virtio-gpu GPU)".into(),
+            ));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut guest = live.guest_session.guest().await?;
+        guest.screenshot().await
+    }
+
+    pub(crate) async fn shares(self: &Arc<Self>) -> BoxliteResult<Vec<ShareStatus>> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        let mut guest = live.guest_session.guest().await?;
+        guest.ping().await
+    }
+
+    pub(crate) async fn agent_capabilities(self: &Arc<Self>) -> BoxliteResult<AgentCapabilities> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        let mut guest = live.guest_session.guest().await?;
+        guest.capabilities().await
+    }
+
+    pub(crate) async fn upgrade_agent(
+        self: &Arc<Self>,
+        binary: &Path,
+        signature: &[u8],
+    ) -> BoxliteResult<String> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let (_, upgrades) = live.guest_session.guest().await?.agent_version().await?;
+        self.bulk_channel().stage_agent(binary).await?;
+        let previous_version = live
+            .guest_session
+            .guest()
+            .await?
+            .upgrade_agent(signature.to_vec())
+            .await?;
+
+        // The agent replies before it execs the new binary; the new one
+        // counts one more upgrade once it serves
+        let deadline = tokio::time::Instant::now() + AGENT_UPGRADE_TIMEOUT;
+        let version = loop {
+            tokio::time::sleep(AGENT_UPGRADE_POLL).await;
+            let answer = match live.guest_session.guest().await {
+                Ok(mut guest) => guest.agent_version().await,
+                Err(e) => Err(e),
+            };
+            match answer {
+                Ok((version, now)) if now > upgrades => break version,
+                _ if tokio::time::Instant::now() >= deadline => {
+                    return Err(BoxliteError::Internal(format!(
+                        "Upgraded guest agent did not come up within {}s",
+                        AGENT_UPGRADE_TIMEOUT.as_secs()
+                    )));
+                }
+                _ => {}
+            }
+        };
+
+        tracing::info!(
+            box_id = %self.config.id,
+            previous_version = %previous_version,
+            version = %version,
+            "Upgraded guest agent"
+        );
+        self.runtime.events.emit(
+            EventKind::AgentUpgraded,
+            &self.config,
+            [
+                ("previous_version", previous_version),
+                ("version", version.clone()),
+            ],
+        );
+        Ok(version)
+    }
+
+    pub(crate) async fn copy_in(
+        self: &Arc<Self>,
+        host_path: &Path,
+        box_path: &str,
+    ) -> BoxliteResult<u64> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        if let Some(quota) = &live.quota_enforcer {
+            quota.check_writable(box_path)?;
+        }
+        let bulk = self.bulk_channel();
+        self.runtime
+            .operations
+            .start(
+                OperationKind::CopyIn,
+                box_path,
+                Some(self.config.id.clone()),
+            )
+            .run(|progress| bulk.upload(self.container_id(), host_path, box_path, Some(progress)))
+            .await
+    }
+
+    pub(crate) async fn copy_out(
+        self: &Arc<Self>,
+        box_path: &str,
+        host_path: &Path,
+    ) -> BoxliteResult<u64> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        self.live_state().await?;
+        let bulk = self.bulk_channel();
+        self.runtime
+            .operations
+            .start(
+                OperationKind::CopyOut,
+                box_path,
+                Some(self.config.id.clone()),
+            )
+            .run(|progress| bulk.download(self.container_id(), box_path, host_path, Some(progress)))
+            .await
+    }
+
+    pub(crate) async fn set_clipboard(self: &Arc<Self>, content: &[u8]) -> BoxliteResult<()> {
+        if !self.clipboard_policy().allows_host_to_box() {
+            return Err(BoxliteError::InvalidState(
+                "Clipboard sharing to the box is disabled for this box".into(),
+            ));
+        }
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.set_clipboard(self.container_id(), content).await
+    }
+
+    pub(crate) async fn clipboard(self: &Arc<Self>) -> BoxliteResult<Vec<u8>> {
+        if !self.clipboard_policy().allows_box_to_host() {
+            return Err(BoxliteError::InvalidState(
+                "Clipboard sharing from the box is disabled for this box".into(),
+            ));
+        }
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.get_clipboard(self.container_id()).await
+    }
+
+    pub(crate) async fn drop_file(self: &Arc<Self>, host_path: &Path) -> BoxliteResult<String> {
+        let sharing = self.config.options.sharing.as_ref();
+        let drop_dir = sharing
+            .and_then(|s| s.drop_dir.as_deref())
+            .ok_or_else(|| BoxliteError::InvalidState("Drops are disabled for this box".into()))?;
+        let name = host_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| {
+                BoxliteError::InvalidArgument(format!(
+                    "{} has no usable file name",
+                    host_path.display()
+                ))
+            })?;
+        if let Some(limit) = sharing.and_then(|s| s.max_drop_bytes) {
+            let size = std::fs::metadata(host_path)
+                .map_err(|e| {
+                    BoxliteError::Storage(format!("Failed to stat {}: {}", host_path.display(), e))
+                })?
+                .len();
+            if size > limit {
+                return Err(BoxliteError::InvalidArgument(format!(
+                    "{} is {} bytes, over the box's {} byte drop limit",
+                    host_path.display(),
+                    size,
+                    limit
+                )));
+            }
+        }
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        if let Some(quota) = &live.quota_enforcer {
+            quota.check_writable(drop_dir)?;
+        }
+        self.bulk_channel()
+            .drop_file(self.container_id(), host_path, name)
+            .await?;
+        Ok(format!("{}/{}", drop_dir.trim_end_matches('/'), name))
+    }
+
+    fn clipboard_policy(&self) -> ClipboardPolicy {
+        self.config
+            .options
+            .sharing
+            .as_ref()
+            .map(|s| s.clipboard)
+            .unwrap_or_default()
+    }
+
+    pub(crate) async fn create_user(self: &Arc<Self>, user: UserSpec) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.create_user(self.container_id(), &user).await
+    }
+
+    pub(crate) async fn delete_user(
+        self: &Arc<Self>,
+        name: &str,
+        remove_home: bool,
+    ) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container
+            .delete_user(self.container_id(), name, remove_home)
+            .await
+    }
+
+    pub(crate) async fn register_task(self: &Arc<Self>, task: ScheduledTask) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.register_task(self.container_id(), &task).await
+    }
+
+    pub(crate) async fn unregister_task(self: &Arc<Self>, name: &str) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.unregister_task(name).await
+    }
+
+    pub(crate) async fn install_packages(
+        self: &Arc<Self>,
+        packages: &[String],
+        update_index: bool,
+    ) -> BoxliteResult<PackageInstallation> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        let events = container
+            .install_packages(self.container_id(), packages, update_index)
+            .await?;
+        Ok(PackageInstallation::new(events, activity))
+    }
+
+    pub(crate) async fn execute_cell(
+        self: &Arc<Self>,
+        kernel: &str,
+        code: &str,
+        timeout: Option<Duration>,
+    ) -> BoxliteResult<CellOutput> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container
+            .execute_cell(self.container_id(), kernel, code, timeout)
+            .await
+    }
+
+    pub(crate) async fn shutdown_kernel(self: &Arc<Self>, kernel: &str) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.shutdown_kernel(self.container_id(), kernel).await
+    }
+
+    #[cfg(feature = "fault-injection")]
+    pub(crate) async fn inject_fault(self: &Arc<Self>, fault: super::Fault) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        tracing::warn!(box_id = %self.config.id, ?fault, "Injecting fault");
+        match fault.into_proto() {
+            Some(fault) => {
+                let mut container = live.guest_session.container().await?;
+                container.inject_fault(self.container_id(), fault).await
+            }
+            None => {
+                let severed = live.guest_session.sever();
+                tracing::debug!(box_id = %self.config.id, severed, "Severed guest connection");
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) async fn list_tasks(self: &Arc<Self>) -> BoxliteResult<Vec<TaskStatus>> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.list_tasks().await
+    }
+
+    pub(crate) async fn watch(
+        self: &Arc<Self>,
+        path: &str,
+        recursive: bool,
+        debounce: Option<Duration>,
+    ) -> BoxliteResult<PathWatch> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        let events = container
+            .watch_path(self.container_id(), path, recursive, debounce)
+            .await?;
+        Ok(PathWatch::new(events))
+    }
+
+    pub(crate) async fn sync(self: &Arc<Self>, spec: SyncSpec) -> BoxliteResult<SyncSession> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        if let Some(quota) = &live.quota_enforcer {
+            quota.check_writable(&spec.box_path)?;
+        }
+        SyncSession::start(
+            self.bulk_channel(),
+            live.guest_session.clone(),
+            self.container_id().to_string(),
+            spec,
+        )
+        .await
+    }
+
+    pub(crate) async fn processes(self: &Arc<Self>) -> BoxliteResult<Vec<ProcessInfo>> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+
+        let live = self.live_state().await?;
+        let mut container = live.guest_session.container().await?;
+        container.list_processes(self.container_id()).await
+    }
+
+    pub(crate) async fn capture_network(&self, spec: CaptureSpec) -> BoxliteResult<NetworkCapture> {
+        if !self.state.read().status.is_running() {
+            return Err(BoxliteError::InvalidState("Box is not running".into()));
+        }
+        // The shim only serves captures for boxes with a gvproxy network
+        let socket = self.config.box_home.join("sockets").join("capture.sock");
+        if !socket.exists() {
+            return Err(BoxliteError::Unsupported(
+                "Packet capture needs a box with networking on the gvproxy backend".into(),
+            ));
+        }
+        NetworkCapture::start(&socket, spec).await
+    }
+
+    pub(crate) async fn reclaim_memory(self: &Arc<Self>) -> BoxliteResult<u64> {
+        if !self.state.read().status.is_running() {
+            return Err(BoxliteError::InvalidState("Box is not running".into()));
+        }
+        let live = self.live_state().await?;
+        live.guest_session.guest().await?.reclaim_memory().await
+    }
+
+    pub(crate) fn firewall(&self) -> BoxliteResult<FirewallPolicy> {
+        self.firewall_socket()?;
+        Ok(firewall::read(&self.config.box_home.join("firewall.json")))
+    }
+
+    pub(crate) async fn set_firewall(&self, policy: FirewallPolicy) -> BoxliteResult<()> {
+        self.update_firewall(|current| *current = policy).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add_firewall_rules(
+        &self,
+        rules: Vec<FirewallRule>,
+    ) -> BoxliteResult<FirewallPolicy> {
+        self.update_firewall(|policy| policy.rules.extend(rules))
+            .await
+    }
+
+    /// Apply `change` to the running box's policy, then record it.
+    async fn update_firewall(
+        &self,
+        change: impl FnOnce(&mut FirewallPolicy),
+    ) -> BoxliteResult<FirewallPolicy> {
+        let socket = self.firewall_socket()?;
+        let _guard = self.firewall_lock.lock().await;
+        let path = self.config.box_home.join("firewall.json");
+        let mut policy = firewall::read(&path);
+        change(&mut policy);
+        policy.validate()?;
+
+        firewall::apply(&socket, &policy).await?;
+        firewall::record(&path, &policy)?;
+        self.runtime.events.emit(
+            EventKind::FirewallChanged,
+            &self.config,
+            [
+                ("egress", policy.egress.as_str().to_string()),
+                ("ingress", policy.ingress.as_str().to_string()),
+                ("rules", policy.rules.len().to_string()),
+            ],
+        );
+        Ok(policy)
+    }
+
+    /// The socket the box's shim takes firewall policies on.
+    fn firewall_socket(&self) -> BoxliteResult<std::path::PathBuf> {
+        if !self.state.read().status.is_running() {
+            return Err(BoxliteError::InvalidState("Box is not running".into()));
+        }
+        // The shim only filters boxes with a gvproxy network
+        let socket = self.config.box_home.join("sockets").join("firewall.sock");
+        if !socket.exists() {
+            return Err(BoxliteError::Unsupported(
+                "Firewall rules need a box with networking on the gvproxy backend".into(),
+            ));
+        }
+        Ok(socket)
+    }
+
+    pub(crate) fn ports(&self) -> Vec<ForwardedPort> {
+        if !self.state.read().status.is_running() {
+            return Vec::new();
+        }
+        ports::read(&self.config.box_home.join("ports.json"))
+    }
+
+    pub(crate) fn recordings(&self) -> BoxliteResult<Vec<RecordingInfo>> {
+        recording::list(&self.recordings_dir())
+    }
+
+    pub(crate) fn read_recording(&self, execution_id: &str) -> BoxliteResult<String> {
+        let path = recording::cast_path(&self.recordings_dir(), execution_id)?;
+        std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                BoxliteError::NotFound(format!("No recording for execution {}", execution_id))
+            }
+            _ => BoxliteError::Storage(format!("Failed to read {}: {}", path.display(), e)),
+        })
+    }
+
+    /// Collect `paths` once the execution exits, passing its result on
+    /// after the artifacts are stored.
+    ///
+    /// Artifacts that cannot be collected are logged; the execution's
+    /// result is passed on regardless.
+    fn collect_artifacts_on_exit(
+        &self,
+        execution_id: String,
+        paths: Vec<String>,
+        mut result_rx: mpsc::UnboundedReceiver<ExecResult>,
+    ) -> mpsc::UnboundedReceiver<ExecResult> {
+        let (result_tx, forwarded_rx) = mpsc::unbounded_channel();
+        let bulk = self.bulk_channel();
+        let container_id = self.container_id().to_string();
+        let dir = self.artifacts_dir();
+        let retention = self.config.options.artifact_retention.clone();
+        let box_id = self.config.id.clone();
+
+        tokio::spawn(async move {
+            let Some(result) = result_rx.recv().await else {
+                return;
+            };
+            let stored = async {
+                let path = artifacts::archive_path(&dir, &execution_id)?;
+                std::fs::create_dir_all(&dir).map_err(|e| {
+                    BoxliteError::Storage(format!("Failed to create {}: {}", dir.display(), e))
+                })?;
+                bulk.archive(&container_id, &paths, &path).await?;
+                artifacts::prune(&dir, &retention)
+            }
+            .await;
+            match stored {
+                Ok(pruned) => tracing::debug!(
+                    box_id = %box_id,
+                    execution_id = %execution_id,
+                    pruned = pruned.len(),
+                    "Stored artifacts"
+                ),
+                Err(e) => tracing::warn!(
+                    box_id = %box_id,
+                    execution_id = %execution_id,
+                    "Failed to collect artifacts: {}",
+                    e
+                ),
+            }
+            let _ = result_tx.send(result);
+        });
+        forwarded_rx
+    }
+
+    pub(crate) fn artifacts(&self) -> BoxliteResult<Vec<ArtifactInfo>> {
+        artifacts::list(&self.artifacts_dir())
+    }
+
+    pub(crate) async fn download_artifacts(
+        &self,
+        execution_id: &str,
+        dest: &Path,
+    ) -> BoxliteResult<()> {
+        let archive = artifacts::archive_path(&self.artifacts_dir(), execution_id)?;
+        let dest = dest.to_path_buf();
+        tokio::task::spawn_blocking(move || artifacts::extract(&archive, &dest))
+            .await
+            .map_err(|e| BoxliteError::Internal(format!("Extract task failed: {}", e)))?
+    }
+
+    fn artifacts_dir(&self) -> std::path::PathBuf {
+        self.config.box_home.join(artifacts::ARTIFACTS_DIR)
+    }
+
+    fn exec_cache_dir(&self) -> std::path::PathBuf {
+        self.config.box_home.join(exec_cache::EXEC_CACHE_DIR)
+    }
+
+    fn recordings_dir(&self) -> std::path::PathBuf {
+        self.config.box_home.join(recording::RECORDINGS_DIR)
+    }
+
+    fn bulk_channel(&self) -> BulkChannel {
+        BulkChannel::new(self.config.box_home.join("sockets").join("bulk.sock"))
+    }
+
     pub(crate) async fn stop(&self) -> BoxliteResult<()> {
         self.is_shutdown.store(true, Ordering::SeqCst);
 
         // Only try to stop VM if LiveState exists
-        if let Some(live) = self.live.get() {
+        if let Some(live) = self.live.lock().await.as_ref() {
             // Gracefully shut down guest
             if let Ok(mut guest) = live.guest_session.guest().await {
                 let _ = guest.shutdown().await;
@@ -239,6 +1177,8 @@ impl BoxImpl {
             if let Ok(mut handler) = live.handler.lock() {
                 handler.stop()?;
             }
+            priority::release(self.runtime.cgroup_parent.as_deref(), self.id());
+            self.runtime.pressure.forget(self.id());
         }
 
         // Check if box was persisted
@@ -263,6 +1203,10 @@ impl BoxImpl {
         self.runtime
             .invalidate_box_impl(self.id(), self.config.name.as_deref());
 
+        self.runtime
+            .events
+            .emit(EventKind::Stopped, &self.config, []);
+
         tracing::info!("Stopped box {}", self.id());
 
         if self.config.options.auto_remove {
@@ -276,9 +1220,639 @@ impl BoxImpl {
     // LIVE STATE INITIALIZATION (internal)
     // ========================================================================
 
-    /// Get LiveState, lazily initializing it if needed.
-    async fn live_state(&self) -> BoxliteResult<&LiveState> {
-        self.live.get_or_try_init(|| self.init_live_state()).await
+    /// Get LiveState, lazily initializing (or resuming) it if needed.
+    async fn live_state(self: &Arc<Self>) -> BoxliteResult<Arc<LiveState>> {
+        let mut live = self.live.lock().await;
+        if let Some(live_state) = live.as_ref() {
+            return Ok(Arc::clone(live_state));
+        }
+
+        let live_state = Arc::new(self.init_live_state().await?);
+        *live = Some(Arc::clone(&live_state));
+
+        let pid = self.state.read().pid;
+        self.runtime.events.emit(
+            EventKind::Started,
+            &self.config,
+            pid.map(|pid| ("pid", pid.to_string())),
+        );
+
+        self.report_share_fallbacks(&live_state).await;
+        if let Some(reason) = resume::pending(&self.config.box_home) {
+            self.resume_guest(&live_state, reason).await;
+        }
+        if let Some(derived_disk) = provision::pending(&self.config.box_home) {
+            self.provision_runtimes(&live_state, &derived_disk).await;
+        }
+        if !self.runtime.hooks.is_empty() {
+            let hooks = self.runtime.hooks.clone();
+            let config = self.config.clone();
+            let ready = tokio::task::spawn_blocking(move || hooks.post_ready(&config))
+                .await
+                .map_err(|e| BoxliteError::Internal(format!("Hook task failed: {}", e)))
+                .and_then(|result| result);
+            if let Err(e) = ready {
+                tracing::warn!(box_id = %self.id(), error = %e, "Hook vetoed box, stopping it");
+                drop(live);
+                if let Err(stop_err) = self.stop().await {
+                    tracing::warn!(box_id = %self.id(), error = %stop_err, "Failed to stop box");
+                }
+                return Err(e);
+            }
+        }
+
+        if let Some(timeout) = self.idle_timeout() {
+            self.idle.touch();
+            tokio::spawn(Self::watch_idle(
+                Arc::downgrade(self),
+                Arc::downgrade(&live_state),
+                timeout,
+            ));
+        }
+        if let Some(expires_at) = self.config.expires_at() {
+            tokio::spawn(Self::watch_ttl(Arc::downgrade(self), expires_at));
+        }
+        // A deterministic box keeps its own clock
+        if self.config.options.deterministic.is_none() {
+            tokio::spawn(Self::watch_clock(
+                Arc::downgrade(self),
+                Arc::downgrade(&live_state),
+            ));
+        }
+        self.start_health_watch(&live_state);
+        // With a readiness probe, the health watch reports readiness
+        if self.config.options.readiness_probe.is_none() {
+            self.runtime.events.emit(EventKind::Ready, &self.config, []);
+        }
+
+        Ok(live_state)
+    }
+
+    /// Emit a `share_fallback` event for each share the guest could not
+    /// mount over virtio-fs.
+    async fn report_share_fallbacks(&self, live: &LiveState) {
+        let shares = match live.guest_session.guest().await {
+            Ok(mut guest) => guest.ping().await,
+            Err(e) => Err(e),
+        };
+        let shares = match shares {
+            Ok(shares) => shares,
+            Err(e) => {
+                tracing::warn!(box_id = %self.config.id, "Failed to query guest shares: {}", e);
+                return;
+            }
+        };
+        for share in shares.into_iter().filter(|s| s.strategy.is_fallback()) {
+            tracing::warn!(
+                box_id = %self.config.id,
+                tag = %share.tag,
+                strategy = %share.strategy,
+                error = share.error.as_deref().unwrap_or_default(),
+                "Share not mounted over virtio-fs"
+            );
+            self.runtime.events.emit(
+                EventKind::ShareFallback,
+                &self.config,
+                [
+                    ("tag", share.tag),
+                    ("strategy", share.strategy.to_string()),
+                    ("error", share.error.unwrap_or_default()),
+                ],
+            );
+        }
+    }
+
+    /// Fix up a guest booted from cloned or restored disks.
+    ///
+    /// A guest that cannot be fixed up still runs; the marker stays so the
+    /// next start tries again.
+    async fn resume_guest(&self, live: &LiveState, reason: ResumeReason) {
+        let mut entropy = vec![0u8; RESUME_ENTROPY_BYTES];
+        rand::rng().fill_bytes(&mut entropy);
+
+        let result = match live.guest_session.guest().await {
+            Ok(mut guest) => guest.resume(reason.as_str(), entropy).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(clock_step_ms) => {
+                resume::clear(&self.config.box_home);
+                self.runtime.events.emit(
+                    EventKind::Resumed,
+                    &self.config,
+                    [
+                        ("reason", reason.as_str().to_string()),
+                        ("clock_step_ms", clock_step_ms.to_string()),
+                    ],
+                );
+            }
+            Err(e) => tracing::warn!(
+                box_id = %self.config.id,
+                reason = reason.as_str(),
+                "Failed to resume guest: {}",
+                e
+            ),
+        }
+    }
+
+    /// Install the box's runtimes and save its disk as the derived image.
+    ///
+    /// A box whose runtimes fail to install still runs; the marker stays so
+    /// the next start tries again.
+    async fn provision_runtimes(&self, live: &LiveState, derived_disk: &Path) {
+        use boxlite_shared::constants::executor as executor_const;
+
+        let runtimes = &self.config.options.runtimes;
+        let command = BoxCommand::new("sh")
+            .args(["-c", &provision::script(runtimes)])
+            .env(
+                executor_const::ENV_VAR,
+                format!("{}={}", executor_const::CONTAINER_KEY, self.container_id()),
+            )
+            .timeout(PROVISION_TIMEOUT);
+
+        let installed = async {
+            let mut exec_interface = live.guest_session.execution().await?;
+            let output_dropped = Arc::clone(&live.metrics.output_dropped);
+            let components = exec_interface
+                .exec(command, self.config.options.output_buffer, output_dropped)
+                .await?;
+            let mut execution = Execution::new(
+                components.execution_id,
+                exec_interface,
+                components.result_rx,
+                None,
+                None,
+                None,
+                None,
+                components.cancel,
+            );
+            execution.wait().await
+        }
+        .await;
+
+        let result = match installed {
+            Ok(result) if result.exit_code == 0 => self.save_derived_disk(live, derived_disk).await,
+            Ok(result) => Err(BoxliteError::Internal(format!(
+                "runtime install exited with {}",
+                result.exit_code
+            ))),
+            Err(e) => Err(e),
+        };
+        let names = runtimes
+            .iter()
+            .map(|r| r.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        match &result {
+            Ok(()) => provision::clear(&self.config.box_home),
+            Err(e) => tracing::warn!(
+                box_id = %self.config.id,
+                runtimes = %names,
+                "Failed to provision runtimes: {}",
+                e
+            ),
+        }
+        self.runtime.events.emit(
+            EventKind::Provisioned,
+            &self.config,
+            [
+                ("runtimes", names),
+                (
+                    "result",
+                    if result.is_ok() { "ok" } else { "failed" }.to_string(),
+                ),
+            ],
+        );
+    }
+
+    /// Boot the box if needed and save its container disk to `target`.
+    pub(crate) async fn save_disk(self: &Arc<Self>, target: &Path) -> BoxliteResult<()> {
+        if self.is_shutdown.load(Ordering::SeqCst) {
+            return Err(BoxliteError::InvalidState("Box is stopped".into()));
+        }
+        if !self.config.engine_kind.is_vm() {
+            return Err(BoxliteError::Unsupported(
+                "Boxless boxes have no disk to save".into(),
+            ));
+        }
+
+        let _activity = self.idle.activity();
+        let live = self.live_state().await?;
+        self.save_derived_disk(&live, target).await
+    }
+
+    /// Run `copy` while the running box's container rootfs is frozen, so
+    /// its disks can be copied without stopping it.
+    pub(crate) async fn with_frozen_rootfs<F>(self: &Arc<Self>, copy: F) -> BoxliteResult<()>
+    where
+        F: FnOnce() -> BoxliteResult<()> + Send + 'static,
+    {
+        if self.is_shutdown.load(Ordering::SeqCst) || self.state.read().status != BoxStatus::Running
+        {
+            return Err(BoxliteError::InvalidState("Box is not running".into()));
+        }
+        let live = self.live_state().await?;
+        self.frozen(&live, copy).await
+    }
+
+    /// Copy the box disk to `derived_disk` while the container rootfs is frozen.
+    async fn save_derived_disk(&self, live: &LiveState, derived_disk: &Path) -> BoxliteResult<()> {
+        let disk = live.container_rootfs_disk.path().to_path_buf();
+        let target = derived_disk.to_path_buf();
+        self.frozen(live, move || provision::save_derived(&disk, &target))
+            .await
+    }
+
+    /// Run `copy` on a blocking thread with the container rootfs frozen.
+    async fn frozen<F>(&self, live: &LiveState, copy: F) -> BoxliteResult<()>
+    where
+        F: FnOnce() -> BoxliteResult<()> + Send + 'static,
+    {
+        let mut container = live.guest_session.container().await?;
+        container.freeze_rootfs(self.container_id(), true).await?;
+
+        let copied = tokio::task::spawn_blocking(copy)
+            .await
+            .map_err(|e| BoxliteError::Internal(format!("Disk copy task failed: {}", e)))
+            .and_then(|r| r);
+
+        let thawed = container.freeze_rootfs(self.container_id(), false).await;
+        copied?;
+        thawed
+    }
+
+    // ========================================================================
+    // TTL (internal)
+    // ========================================================================
+
+    /// Stop and remove the box when its TTL runs out.
+    ///
+    /// The shim kills the VM itself at the deadline; this cleans up the box's
+    /// state and files while the owning runtime is still around.
+    async fn watch_ttl(this: Weak<Self>, expires_at: chrono::DateTime<chrono::Utc>) {
+        let remaining = (expires_at - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(remaining).await;
+
+        let Some(this) = this.upgrade() else {
+            return;
+        };
+        if this.is_shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+
+        tracing::info!(box_id = %this.id(), %expires_at, "Box TTL expired, removing");
+        if let Err(e) = this.stop().await {
+            tracing::warn!(box_id = %this.id(), error = %e, "Failed to stop expired box");
+        }
+        if !this.config.options.auto_remove
+            && let Err(e) = this.runtime.remove_box(this.id(), true)
+        {
+            tracing::warn!(box_id = %this.id(), error = %e, "Failed to remove expired box");
+        }
+    }
+
+    /// Wake the guest whenever the host wall clock jumps: after the host
+    /// slept, or its clock was set.
+    ///
+    /// Exits when the box is dropped or stopped, or the watched LiveState
+    /// is replaced (each start spawns its own watcher).
+    async fn watch_clock(this: Weak<Self>, live: Weak<LiveState>) {
+        let mut clock = ClockWatch::new();
+        loop {
+            tokio::time::sleep(wake::CHECK_INTERVAL).await;
+            let (Some(this), Some(live_state)) = (this.upgrade(), live.upgrade()) else {
+                return;
+            };
+            if this.is_shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+            if let Some(jump_ms) = clock.check() {
+                this.wake_guest(&live_state, jump_ms).await;
+            }
+        }
+    }
+
+    /// Have the guest catch up with a host clock jump of `jump_ms`, then
+    /// check the box's port forwards still listen.
+    async fn wake_guest(&self, live: &LiveState, jump_ms: i64) {
+        tracing::info!(box_id = %self.config.id, jump_ms, "Host clock jumped, waking guest");
+        let result = match live.guest_session.guest().await {
+            Ok(mut guest) => guest.wake(jump_ms).await,
+            Err(e) => Err(e),
+        };
+        let (clock_step_ms, network_refreshed) = match result {
+            Ok(woke) => woke,
+            Err(e) => {
+                tracing::warn!(box_id = %self.config.id, "Failed to wake guest: {}", e);
+                return;
+            }
+        };
+
+        // The forwards live in the shim's network backend; one that no
+        // longer listens only comes back when the box restarts
+        let ports = ports::read(&self.config.box_home.join("ports.json"));
+        let stale = tokio::task::spawn_blocking(move || wake::stale_ports(&ports))
+            .await
+            .unwrap_or_default();
+        if !stale.is_empty() {
+            tracing::warn!(
+                box_id = %self.config.id,
+                ports = ?stale.iter().map(|p| p.host_port).collect::<Vec<_>>(),
+                "Forwarded ports stopped listening after host clock jump"
+            );
+        }
+
+        self.runtime.events.emit(
+            EventKind::Woke,
+            &self.config,
+            [
+                ("jump_ms", jump_ms.to_string()),
+                ("clock_step_ms", clock_step_ms.to_string()),
+                ("network_refreshed", network_refreshed.to_string()),
+                (
+                    "stale_ports",
+                    stale
+                        .iter()
+                        .map(|p| p.host_port.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+            ],
+        );
+    }
+
+    // ========================================================================
+    // HEALTH PROBES (internal)
+    // ========================================================================
+
+    /// Reset the box's health and start following its probes, if it has any.
+    ///
+    /// Spawned from a plain fn so the watcher, which can restart the box,
+    /// does not make `live_state`'s future type recursive.
+    fn start_health_watch(self: &Arc<Self>, live: &Arc<LiveState>) {
+        let options = &self.config.options;
+        if options.readiness_probe.is_none() && options.liveness_probe.is_none() {
+            return;
+        }
+        {
+            let mut state = self.state.write();
+            let restarts = state.health.as_ref().map_or(0, |h| h.restarts);
+            state.health = Some(BoxHealth::new(
+                options.readiness_probe.is_some(),
+                options.liveness_probe.is_some(),
+                restarts,
+            ));
+            if let Err(e) = self.runtime.box_manager.save_box(&self.config.id, &state) {
+                tracing::warn!(box_id = %self.id(), error = %e, "Failed to save box health");
+            }
+        }
+        tokio::spawn(Self::watch_health(
+            Arc::downgrade(self),
+            Arc::downgrade(live),
+        ));
+    }
+
+    /// Follow the guest's probe results, restarting the box per its
+    /// `unhealthy_policy` when the liveness probe fails.
+    ///
+    /// Exits when the box is dropped or stopped, or the watched LiveState
+    /// is replaced (each start spawns its own watcher).
+    async fn watch_health(this: Weak<Self>, live: Weak<LiveState>) {
+        let stream = match (this.upgrade(), live.upgrade()) {
+            (Some(this), Some(live_state)) => match live_state.guest_session.container().await {
+                Ok(mut container) => {
+                    let options = &this.config.options;
+                    container
+                        .watch_health(
+                            this.container_id(),
+                            options.readiness_probe.as_ref(),
+                            options.liveness_probe.as_ref(),
+                        )
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            _ => return,
+        };
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to start health probes: {}", e);
+                return;
+            }
+        };
+
+        let mut ready = false;
+        loop {
+            let event = match stream.message().await {
+                Ok(Some(event)) => event,
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::debug!("Health watch ended: {}", e);
+                    return;
+                }
+            };
+            let Some(this) = this.upgrade() else {
+                return;
+            };
+            if live.strong_count() == 0 || this.is_shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let liveness_failed = {
+                let mut state = this.state.write();
+                let health = state
+                    .health
+                    .get_or_insert_with(|| BoxHealth::new(true, true, 0));
+                let failed = health.apply(&event);
+                if let Err(e) = this.runtime.box_manager.save_box(&this.config.id, &state) {
+                    tracing::warn!(box_id = %this.id(), error = %e, "Failed to save box health");
+                }
+                failed
+            };
+            this.runtime.events.emit(
+                EventKind::HealthChanged,
+                &this.config,
+                [
+                    ("probe", health::probe_name(&event).to_string()),
+                    ("healthy", event.healthy.to_string()),
+                    ("message", event.message.clone()),
+                ],
+            );
+            let probe = health::probe_name(&event);
+            if !ready && event.healthy && probe == "readiness" {
+                ready = true;
+                this.runtime.events.emit(
+                    EventKind::Ready,
+                    &this.config,
+                    [("probe", probe.to_string())],
+                );
+            }
+
+            if liveness_failed && this.should_restart() {
+                if let Err(e) = this.restart_unhealthy(&live).await {
+                    tracing::warn!(box_id = %this.id(), error = %e, "Failed to restart unhealthy box");
+                }
+                return;
+            }
+        }
+    }
+
+    fn should_restart(&self) -> bool {
+        let restarts = self.state.read().health.as_ref().map_or(0, |h| h.restarts);
+        match self.config.options.unhealthy_policy {
+            UnhealthyPolicy::Ignore => false,
+            UnhealthyPolicy::Restart { max_restarts } => {
+                max_restarts.is_none_or(|max| restarts < max)
+            }
+        }
+    }
+
+    /// Tear down the VM of a box whose liveness probe failed and boot it again.
+    async fn restart_unhealthy(self: &Arc<Self>, watched: &Weak<LiveState>) -> BoxliteResult<()> {
+        {
+            let mut live = self.live.lock().await;
+            let is_watched = match (live.as_ref(), watched.upgrade()) {
+                (Some(current), Some(watched)) => Arc::ptr_eq(current, &watched),
+                _ => false,
+            };
+            if !is_watched || self.is_shutdown.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let Some(live_state) = live.as_ref() else {
+                return Ok(());
+            };
+            // Keep the LiveState until its VM is stopped so stop() can retry
+            self.tear_down(live_state).await?;
+            live.take();
+        }
+
+        let restarts = {
+            let mut state = self.state.write();
+            let health = state
+                .health
+                .get_or_insert_with(|| BoxHealth::new(true, true, 0));
+            health.restarts += 1;
+            health.restarts
+        };
+        tracing::info!(box_id = %self.id(), restarts, "Restarting box after liveness probe failed");
+
+        self.live_state().await?;
+        self.runtime.events.emit(
+            EventKind::Restarted,
+            &self.config,
+            [("restarts", restarts.to_string())],
+        );
+        Ok(())
+    }
+
+    /// Shut down a LiveState's guest and VM, and record the box as stopped.
+    async fn tear_down(&self, live_state: &LiveState) -> BoxliteResult<()> {
+        if let Ok(mut guest) = live_state.guest_session.guest().await {
+            let _ = guest.shutdown().await;
+        }
+        if let Ok(mut handler) = live_state.handler.lock() {
+            handler.stop()?;
+        }
+        priority::release(self.runtime.cgroup_parent.as_deref(), self.id());
+        self.runtime.pressure.forget(self.id());
+
+        let mut state = self.state.write();
+        state.set_status(BoxStatus::Stopped);
+        state.set_pid(None);
+        self.runtime.box_manager.save_box(&self.config.id, &state)
+    }
+
+    // ========================================================================
+    // IDLE SUSPEND (internal)
+    // ========================================================================
+
+    fn idle_timeout(&self) -> Option<Duration> {
+        if !self.config.options.ports.is_empty() || self.config.options.ssh.is_some() {
+            return None;
+        }
+        self.config
+            .options
+            .idle_timeout_secs
+            .map(Duration::from_secs)
+    }
+
+    /// Suspend the box once it has been idle for `timeout`.
+    ///
+    /// Exits when the box is dropped, stopped, or the watched LiveState is
+    /// replaced (each resume spawns its own watcher).
+    async fn watch_idle(this: Weak<Self>, live: Weak<LiveState>, timeout: Duration) {
+        loop {
+            let remaining = match this.upgrade() {
+                Some(this) if !this.is_shutdown.load(Ordering::SeqCst) => {
+                    match this.idle.idle_for() {
+                        Some(idle) if idle >= timeout => match this.suspend(&live, timeout).await {
+                            Ok(true) => return,
+                            Ok(false) => timeout,
+                            Err(e) => {
+                                tracing::warn!(
+                                    box_id = %this.id(),
+                                    error = %e,
+                                    "Failed to suspend idle box"
+                                );
+                                return;
+                            }
+                        },
+                        Some(idle) => timeout - idle,
+                        // Busy: check again after a full timeout
+                        None => timeout,
+                    }
+                }
+                _ => return,
+            };
+
+            if live.strong_count() == 0 {
+                return;
+            }
+            tokio::time::sleep(remaining).await;
+        }
+    }
+
+    /// Tear down the VM of an idle box, keeping its disks for the next resume.
+    ///
+    /// Returns false if the box became busy or the LiveState was already replaced.
+    async fn suspend(&self, watched: &Weak<LiveState>, timeout: Duration) -> BoxliteResult<bool> {
+        let mut live = self.live.lock().await;
+
+        let is_watched = match (live.as_ref(), watched.upgrade()) {
+            (Some(current), Some(watched)) => Arc::ptr_eq(current, &watched),
+            _ => false,
+        };
+        if !is_watched || self.is_shutdown.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+        if self.idle.idle_for().is_none_or(|idle| idle < timeout) {
+            return Ok(false);
+        }
+
+        let Some(live_state) = live.as_ref() else {
+            return Ok(false);
+        };
+        // Keep the LiveState until its VM is stopped so stop() can retry
+        self.tear_down(live_state).await?;
+        live.take();
+
+        self.runtime.events.emit(
+            EventKind::Suspended,
+            &self.config,
+            [("idle_secs", timeout.as_secs().to_string())],
+        );
+
+        tracing::info!(
+            box_id = %self.id(),
+            idle_secs = timeout.as_secs(),
+            "Suspended idle box"
+        );
+
+        Ok(true)
     }
 
     /// Initialize LiveState via BoxBuilder.
@@ -292,7 +1866,15 @@ impl BoxImpl {
     /// after successful build.
     async fn init_live_state(&self) -> BoxliteResult<LiveState> {
         use super::BoxBuilder;
-        use std::sync::Arc;
+
+        if let Some(expires_at) = self.config.expires_at()
+            && expires_at <= chrono::Utc::now()
+        {
+            return Err(BoxliteError::InvalidState(format!(
+                "box {} expired at {}",
+                self.config.id, expires_at
+            )));
+        }
 
         let state = self.state.read().clone();
         let is_new_box = state.status == BoxStatus::Starting;
@@ -0,0 +1,553 @@
+//! Packet captures of a box's network.
+//!
+//! The shim serves a pcap stream of the guest's link on the box's capture
+//! socket, copied off the gvproxy connection (see `net::gvproxy`). Captures
+//! read that stream, keep the packets a filter selects, and write them to a
+//! pcap file on the host that tcpdump and Wireshark open as-is.
+//!
+//! Filters are a subset of the pcap-filter language, evaluated here rather
+//! than compiled to BPF: the protocols `arp`, `ip`, `ip6`, `tcp`, `udp`,
+//! `icmp` and `icmp6`, `[src|dst] host ADDR` and `[src|dst] port N`, combined
+//! with `and`, `or`, `not` and parentheses.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::net::UnixStream;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ICMPV6: u8 = 58;
+
+/// What to capture and where to write it.
+#[derive(Clone, Debug)]
+pub struct CaptureSpec {
+    /// The pcap file to write; replaced if it exists.
+    pub output: PathBuf,
+    /// A pcap-filter expression such as `tcp port 80`; everything if None.
+    pub filter: Option<String>,
+    /// Bytes kept of each packet.
+    pub snaplen: u32,
+}
+
+impl CaptureSpec {
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self {
+            output: output.into(),
+            filter: None,
+            snaplen: 65535,
+        }
+    }
+}
+
+/// Packets written by a capture so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    pub packets: u64,
+    /// Bytes of the captured packets on the wire, before truncation.
+    pub bytes: u64,
+}
+
+/// A running capture started by [`crate::LiteBox::capture_network`]; it
+/// stops when stopped, when dropped, or when the box stops.
+pub struct NetworkCapture {
+    task: JoinHandle<BoxliteResult<()>>,
+    stop: Option<oneshot::Sender<()>>,
+    stats: Arc<Mutex<CaptureStats>>,
+}
+
+impl NetworkCapture {
+    /// Start capturing the stream served on `socket` into `spec.output`.
+    pub(crate) async fn start(socket: &Path, spec: CaptureSpec) -> BoxliteResult<Self> {
+        let filter = spec.filter.as_deref().map(Filter::parse).transpose()?;
+        if spec.snaplen == 0 {
+            return Err(BoxliteError::InvalidArgument(
+                "Capture snaplen must be positive".to_string(),
+            ));
+        }
+
+        let mut stream = UnixStream::connect(socket).await.map_err(|e| {
+            BoxliteError::Network(format!(
+                "Failed to connect to capture socket {}: {}",
+                socket.display(),
+                e
+            ))
+        })?;
+        let mut header = [0u8; PCAP_HEADER_LEN];
+        stream
+            .read_exact(&mut header)
+            .await
+            .map_err(|e| BoxliteError::Network(format!("Failed to read capture header: {}", e)))?;
+        if u32::from_le_bytes(header[0..4].try_into().unwrap()) != PCAP_MAGIC {
+            return Err(BoxliteError::Network(
+                "Capture socket did not send a pcap stream".to_string(),
+            ));
+        }
+        header[16..20].copy_from_slice(&spec.snaplen.to_le_bytes());
+
+        let file = tokio::fs::File::create(&spec.output).await.map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", spec.output.display(), e))
+        })?;
+        let mut output = BufWriter::new(file);
+        output.write_all(&header).await?;
+
+        let (stop, stopped) = oneshot::channel();
+        let stats = Arc::new(Mutex::new(CaptureStats::default()));
+        let task = tokio::spawn(run(
+            stream,
+            output,
+            filter,
+            spec.snaplen,
+            stopped,
+            Arc::clone(&stats),
+        ));
+        Ok(Self {
+            task,
+            stop: Some(stop),
+            stats,
+        })
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stop capturing and flush the file.
+    pub async fn stop(mut self) -> BoxliteResult<CaptureStats> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        (&mut self.task)
+            .await
+            .map_err(|e| BoxliteError::Internal(format!("Capture task failed: {}", e)))??;
+        Ok(self.stats())
+    }
+}
+
+impl Drop for NetworkCapture {
+    fn drop(&mut self) {
+        // The task flushes the file on its way out
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+async fn run(
+    mut stream: UnixStream,
+    mut output: BufWriter<tokio::fs::File>,
+    filter: Option<Filter>,
+    snaplen: u32,
+    mut stopped: oneshot::Receiver<()>,
+    stats: Arc<Mutex<CaptureStats>>,
+) -> BoxliteResult<()> {
+    let mut record = [0u8; RECORD_HEADER_LEN];
+    let mut frame = Vec::new();
+    loop {
+        tokio::select! {
+            read = stream.read_exact(&mut record) => {
+                if read.is_err() {
+                    // The box stopped
+                    break;
+                }
+            }
+            _ = &mut stopped => break,
+        }
+        let captured = u32::from_le_bytes(record[8..12].try_into().unwrap());
+        let wire = u32::from_le_bytes(record[12..16].try_into().unwrap());
+        frame.resize(captured as usize, 0);
+        if stream.read_exact(&mut frame).await.is_err() {
+            break;
+        }
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches(&frame))
+        {
+            continue;
+        }
+
+        let kept = captured.min(snaplen);
+        record[8..12].copy_from_slice(&kept.to_le_bytes());
+        output.write_all(&record).await?;
+        output.write_all(&frame[..kept as usize]).await?;
+
+        let mut stats = stats.lock().unwrap();
+        stats.packets += 1;
+        stats.bytes += u64::from(wire);
+    }
+    output.flush().await?;
+    Ok(())
+}
+
+/// Which end of a packet a `host` or `port` primitive looks at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Src,
+    Dst,
+    Either,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Protocol {
+    Arp,
+    Ip,
+    Ip6,
+    Tcp,
+    Udp,
+    Icmp,
+    Icmp6,
+}
+
+/// A parsed capture filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Filter {
+    Protocol(Protocol),
+    Host(Direction, IpAddr),
+    Port(Direction, u16),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    fn parse(text: &str) -> BoxliteResult<Self> {
+        let invalid = |reason: String| {
+            BoxliteError::InvalidArgument(format!("Invalid capture filter '{}': {}", text, reason))
+        };
+        let tokens = tokenize(text);
+        if tokens.is_empty() {
+            return Err(invalid("the filter is empty".to_string()));
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let filter = parser.or().map_err(invalid)?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(invalid(format!("unexpected '{}'", token))),
+        }
+    }
+
+    fn matches(&self, frame: &[u8]) -> bool {
+        self.eval(&Packet::parse(frame))
+    }
+
+    fn eval(&self, packet: &Packet) -> bool {
+        let either = |direction: Direction, src: bool, dst: bool| match direction {
+            Direction::Src => src,
+            Direction::Dst => dst,
+            Direction::Either => src || dst,
+        };
+        match self {
+            Filter::Protocol(protocol) => match protocol {
+                Protocol::Arp => packet.ethertype == ETHERTYPE_ARP,
+                Protocol::Ip => packet.ethertype == ETHERTYPE_IPV4,
+                Protocol::Ip6 => packet.ethertype == ETHERTYPE_IPV6,
+                Protocol::Tcp => packet.transport == Some(IPPROTO_TCP),
+                Protocol::Udp => packet.transport == Some(IPPROTO_UDP),
+                Protocol::Icmp => {
+                    packet.ethertype == ETHERTYPE_IPV4 && packet.transport == Some(IPPROTO_ICMP)
+                }
+                Protocol::Icmp6 => {
+                    packet.ethertype == ETHERTYPE_IPV6 && packet.transport == Some(IPPROTO_ICMPV6)
+                }
+            },
+            Filter::Host(direction, addr) => either(
+                *direction,
+                packet.src == Some(*addr),
+                packet.dst == Some(*addr),
+            ),
+            Filter::Port(direction, port) => either(
+                *direction,
+                packet.src_port == Some(*port),
+                packet.dst_port == Some(*port),
+            ),
+            Filter::Not(inner) => !inner.eval(packet),
+            Filter::And(left, right) => left.eval(packet) && right.eval(packet),
+            Filter::Or(left, right) => left.eval(packet) || right.eval(packet),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let symbol = match c {
+            '(' | ')' | '!' => Some(c.to_string()),
+            '&' | '|' if chars.peek() == Some(&c) => {
+                chars.next();
+                Some(format!("{}{}", c, c))
+            }
+            _ => None,
+        };
+        if symbol.is_some() || c.is_whitespace() {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            tokens.extend(symbol);
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn take(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn accept(&mut self, words: &[&str]) -> bool {
+        let found = self.peek().is_some_and(|token| words.contains(&token));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.and()?;
+        while self.accept(&["or", "||"]) {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.not()?;
+        while self.accept(&["and", "&&"]) {
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter, String> {
+        if self.accept(&["not", "!"]) {
+            return Ok(Filter::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Filter, String> {
+        let Some(token) = self.take() else {
+            return Err("unexpected end of filter".to_string());
+        };
+        let protocol = match token.as_str() {
+            "(" => {
+                let filter = self.or()?;
+                return match self.take().as_deref() {
+                    Some(")") => Ok(filter),
+                    _ => Err("missing ')'".to_string()),
+                };
+            }
+            "src" | "dst" | "host" | "port" => {
+                self.next -= 1;
+                return self.address();
+            }
+            "arp" => Protocol::Arp,
+            "ip" => Protocol::Ip,
+            "ip6" => Protocol::Ip6,
+            "tcp" => Protocol::Tcp,
+            "udp" => Protocol::Udp,
+            "icmp" => Protocol::Icmp,
+            "icmp6" => Protocol::Icmp6,
+            other => return Err(format!("unknown primitive '{}'", other)),
+        };
+        let filter = Filter::Protocol(protocol);
+        // `tcp port 80` is shorthand for `tcp and port 80`
+        if matches!(protocol, Protocol::Tcp | Protocol::Udp)
+            && matches!(self.peek(), Some("src" | "dst" | "port"))
+        {
+            let port = self.address()?;
+            return Ok(Filter::And(Box::new(filter), Box::new(port)));
+        }
+        Ok(filter)
+    }
+
+    /// `[src|dst] host ADDR` or `[src|dst] port N`.
+    fn address(&mut self) -> Result<Filter, String> {
+        let direction = if self.accept(&["src"]) {
+            Direction::Src
+        } else if self.accept(&["dst"]) {
+            Direction::Dst
+        } else {
+            Direction::Either
+        };
+        let kind = self.take();
+        let Some(value) = self.take() else {
+            return Err("expected a host address or port number".to_string());
+        };
+        match kind.as_deref() {
+            Some("host") => value
+                .parse()
+                .map(|addr| Filter::Host(direction, addr))
+                .map_err(|_| format!("'{}' is not an IP address", value)),
+            Some("port") => value
+                .parse()
+                .map(|port| Filter::Port(direction, port))
+                .map_err(|_| format!("'{}' is not a port number", value)),
+            _ => Err("expected 'host' or 'port'".to_string()),
+        }
+    }
+}
+
+/// The fields of an Ethernet frame filters look at.
+#[derive(Debug, Default)]
+struct Packet {
+    ethertype: u16,
+    src: Option<IpAddr>,
+    dst: Option<IpAddr>,
+    transport: Option<u8>,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+}
+
+impl Packet {
+    fn parse(frame: &[u8]) -> Self {
+        let mut packet = Packet::default();
+        let Some(mut ethertype) = be16(frame, 12) else {
+            return packet;
+        };
+        let mut offset = 14;
+        if ethertype == ETHERTYPE_VLAN {
+            let Some(inner) = be16(frame, 16) else {
+                return packet;
+            };
+            ethertype = inner;
+            offset = 18;
+        }
+        packet.ethertype = ethertype;
+        let payload = &frame[offset.min(frame.len())..];
+
+        let transport_offset = match ethertype {
+            ETHERTYPE_IPV4 if payload.len() >= 20 => {
+                packet.src = Some(IpAddr::from(<[u8; 4]>::try_from(&payload[12..16]).unwrap()));
+                packet.dst = Some(IpAddr::from(<[u8; 4]>::try_from(&payload[16..20]).unwrap()));
+                packet.transport = Some(payload[9]);
+                // Only the first fragment carries the ports
+                let fragment_offset = be16(payload, 6).unwrap() & 0x1fff;
+                (fragment_offset == 0).then_some(usize::from(payload[0] & 0x0f) * 4)
+            }
+            ETHERTYPE_IPV6 if payload.len() >= 40 => {
+                packet.src = Some(IpAddr::from(<[u8; 16]>::try_from(&payload[8..24]).unwrap()));
+                packet.dst = Some(IpAddr::from(
+                    <[u8; 16]>::try_from(&payload[24..40]).unwrap(),
+                ));
+                packet.transport = Some(payload[6]);
+                Some(40)
+            }
+            ETHERTYPE_ARP if payload.len() >= 28 => {
+                packet.src = Some(IpAddr::from(<[u8; 4]>::try_from(&payload[14..18]).unwrap()));
+                packet.dst = Some(IpAddr::from(<[u8; 4]>::try_from(&payload[24..28]).unwrap()));
+                None
+            }
+            _ => None,
+        };
+        if let Some(at) = transport_offset
+            && matches!(packet.transport, Some(IPPROTO_TCP | IPPROTO_UDP))
+        {
+            packet.src_port = be16(payload, at);
+            packet.dst_port = be16(payload, at + 2);
+        }
+        packet
+    }
+}
+
+fn be16(bytes: &[u8], at: usize) -> Option<u16> {
+    bytes
+        .get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ipv4_frame(protocol: u8, src: [u8; 4], dst: [u8; 4], ports: (u16, u16)) -> Vec<u8> {
+        let mut frame = vec![0u8; 14 + 20 + 8];
+        frame[12..14].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame[14] = 0x45;
+        frame[14 + 9] = protocol;
+        frame[14 + 12..14 + 16].copy_from_slice(&src);
+        frame[14 + 16..14 + 20].copy_from_slice(&dst);
+        frame[34..36].copy_from_slice(&ports.0.to_be_bytes());
+        frame[36..38].copy_from_slice(&ports.1.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            Filter::parse("tcp port 80").unwrap(),
+            Filter::And(
+                Box::new(Filter::Protocol(Protocol::Tcp)),
+                Box::new(Filter::Port(Direction::Either, 80))
+            )
+        );
+        assert_eq!(
+            Filter::parse("not (arp || dst host 10.0.2.2)").unwrap(),
+            Filter::Not(Box::new(Filter::Or(
+                Box::new(Filter::Protocol(Protocol::Arp)),
+                Box::new(Filter::Host(Direction::Dst, "10.0.2.2".parse().unwrap()))
+            )))
+        );
+        for text in ["", "tcp and", "host", "port http", "(udp", "ether", "udp )"] {
+            assert!(Filter::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let guest = [192, 168, 127, 2];
+        let gateway = [192, 168, 127, 1];
+        let http = ipv4_frame(IPPROTO_TCP, guest, gateway, (40000, 80));
+        let dns = ipv4_frame(IPPROTO_UDP, guest, gateway, (40001, 53));
+
+        let filter = Filter::parse("tcp dst port 80").unwrap();
+        assert!(filter.matches(&http));
+        assert!(!filter.matches(&dns));
+
+        let filter = Filter::parse("udp and src host 192.168.127.2 and not port 80").unwrap();
+        assert!(filter.matches(&dns));
+        assert!(!filter.matches(&http));
+
+        assert!(Filter::parse("ip and !icmp").unwrap().matches(&http));
+        assert!(!Filter::parse("ip6 or arp").unwrap().matches(&http));
+        // Truncated frames match nothing but negations
+        assert!(!Filter::parse("tcp").unwrap().matches(&http[..20]));
+        assert!(Filter::parse("not port 80").unwrap().matches(&http[..20]));
+    }
+}
@@ -48,3 +48,17 @@ pub struct BoxConfig {
     /// Ready signal socket path.
     pub ready_socket_path: PathBuf,
 }
+
+impl BoxConfig {
+    /// When the box's TTL runs out, if it has one.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        let ttl = self.options.ttl_secs?;
+        let ttl = chrono::Duration::seconds(i64::try_from(ttl).unwrap_or(i64::MAX));
+        self.created_at.checked_add_signed(ttl)
+    }
+
+    /// Check whether the box's TTL has run out.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at().is_some_and(|at| at <= Utc::now())
+    }
+}
@@ -0,0 +1,132 @@
+//! Core dumps of workload crashes.
+//!
+//! While a box runs, its guest agent collects core dumps
+//! (Guest.WatchCrashes): the kernel pipes them to the agent, which keeps up
+//! to `BoxOptions::core_dump_max_bytes` of each on a scratch volume and
+//! streams one report per dump. Each dump is then taken off the volume over
+//! the bulk channel and stored with its metadata among the box's artifacts
+//! under the dump's name (`core-{time}-{pid}`), where it outlives the box
+//! stopping, and a `crash_dump` event is emitted. A stream that breaks is
+//! opened again; dumps still on the volume are reported again.
+
+use boxlite_shared::CrashDump;
+use boxlite_shared::errors::BoxliteError;
+use std::path::PathBuf;
+use tokio::task::JoinHandle;
+
+use crate::events::EventKind;
+use crate::litebox::artifacts;
+use crate::litebox::guest_stream::{GuestStream, Watched};
+use crate::portal::GuestSession;
+use crate::portal::bulk::BulkChannel;
+use crate::runtime::options::ArtifactRetention;
+
+/// Where dumps are stored.
+pub(crate) struct CrashStore {
+    pub(crate) bulk: BulkChannel,
+    pub(crate) artifacts_dir: PathBuf,
+    pub(crate) retention: ArtifactRetention,
+}
+
+impl CrashStore {
+    /// Take the dump off the guest into the artifacts directory; returns
+    /// whether it was stored.
+    async fn store(&self, dump: &CrashDump) -> bool {
+        let stored = async {
+            let path = artifacts::archive_path(&self.artifacts_dir, &dump.name)?;
+            std::fs::create_dir_all(&self.artifacts_dir).map_err(|e| {
+                BoxliteError::Storage(format!(
+                    "Failed to create {}: {}",
+                    self.artifacts_dir.display(),
+                    e
+                ))
+            })?;
+            self.bulk.crash_dump(&dump.name, &path).await?;
+            artifacts::prune(&self.artifacts_dir, &self.retention)
+        }
+        .await;
+        match stored {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::warn!(name = %dump.name, "Failed to store core dump: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// The event attributes a dump is emitted with.
+fn describe(dump: &CrashDump, stored: bool) -> Vec<(&'static str, String)> {
+    vec![
+        ("name", dump.name.clone()),
+        ("pid", dump.pid.to_string()),
+        ("signal", dump.signal.to_string()),
+        ("command", dump.command.clone()),
+        ("command_line", dump.command_line.clone()),
+        ("size_bytes", dump.size_bytes.to_string()),
+        ("stored_bytes", dump.stored_bytes.to_string()),
+        ("stored", stored.to_string()),
+    ]
+}
+
+/// Stores the guest's core dumps until dropped.
+pub(crate) struct CrashWatcher {
+    task: JoinHandle<()>,
+}
+
+impl CrashWatcher {
+    /// Start collecting dumps of up to `max_bytes` into `store`; `emit` is
+    /// called with the event for each one.
+    pub(crate) fn start<F>(
+        session: GuestSession,
+        max_bytes: u64,
+        store: CrashStore,
+        emit: F,
+    ) -> Self
+    where
+        F: Fn(EventKind, Vec<(&'static str, String)>) + Send + 'static,
+    {
+        let task = tokio::spawn(async move {
+            let mut stream = GuestStream::new(session, "crashes", move |mut guest| async move {
+                guest.watch_crashes(max_bytes).await
+            });
+            while let Some(watched) = stream.next().await {
+                if let Watched::Message(dump) = watched {
+                    let stored = store.store(&dump).await;
+                    emit(EventKind::CrashDump, describe(&dump, stored));
+                }
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for CrashWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let dump = CrashDump {
+            name: "core-1700000000-42".to_string(),
+            pid: 42,
+            signal: 11,
+            command: "worker".to_string(),
+            command_line: "worker --fast".to_string(),
+            time: 1_700_000_000,
+            size_bytes: 4096,
+            stored_bytes: 1024,
+        };
+        let attributes = describe(&dump, true);
+        assert_eq!(attributes[0], ("name", "core-1700000000-42".to_string()));
+        assert_eq!(attributes[2], ("signal", "11".to_string()));
+        assert_eq!(attributes[6], ("stored_bytes", "1024".to_string()));
+        assert_eq!(attributes[7], ("stored", "true".to_string()));
+    }
+}
@@ -0,0 +1,10 @@
+//! Display capture types.
+
+/// A PNG screenshot of a box's virtio-gpu display.
+#[derive(Clone, Debug)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    /// PNG-encoded RGB image.
+    pub png: Vec<u8>,
+}
@@ -0,0 +1,130 @@
+//! x86_64 emulation in arm64 boxes.
+//!
+//! The host's x86_64 interpreter is copied into the box directory and
+//! shared read-only with the guest, which registers it with binfmt_misc
+//! during Guest.Init. Copying keeps the rest of the interpreter's host
+//! directory (often `/usr/bin`) out of the box. macOS hosts use Rosetta;
+//! Linux hosts use a statically linked `qemu-x86_64`, which runs in the
+//! guest because both are arm64.
+
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Where the guest mounts the interpreter's host directory.
+pub(crate) const GUEST_DIR: &str = "/run/boxlite/x86";
+
+/// File name of the staged interpreter.
+const INTERPRETER: &str = "x86_64-interpreter";
+
+/// Rosetta for Linux, installed with `softwareupdate --install-rosetta`.
+const ROSETTA_DIR: &str = "/Library/Apple/usr/libexec/oah/RosettaLinux";
+
+/// Static qemu-user builds, as Debian, Fedora and Arch package them.
+const QEMU_CANDIDATES: &[&str] = &[
+    "/usr/bin/qemu-x86_64-static",
+    "/usr/libexec/qemu-binfmt/x86_64-binfmt-P",
+    "/usr/bin/qemu-x86_64",
+];
+
+/// An x86_64 interpreter on the host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct X86Emulator {
+    /// The interpreter binary.
+    pub(crate) path: PathBuf,
+    /// binfmt_misc flags to register it with.
+    pub(crate) flags: &'static str,
+}
+
+impl X86Emulator {
+    /// The interpreter for this host; None where boxes are x86_64 already.
+    pub(crate) fn for_host() -> BoxliteResult<Option<Self>> {
+        if !cfg!(target_arch = "aarch64") {
+            tracing::info!("x86_emulation has no effect on x86_64 hosts");
+            return Ok(None);
+        }
+        let found = if cfg!(target_os = "macos") {
+            Self::rosetta(Path::new(ROSETTA_DIR))
+        } else {
+            QEMU_CANDIDATES
+                .iter()
+                .find_map(|path| Self::qemu(Path::new(path)))
+        };
+        found.map(Some).ok_or_else(|| {
+            BoxliteError::Config(if cfg!(target_os = "macos") {
+                "x86_emulation needs Rosetta: run softwareupdate --install-rosetta".to_string()
+            } else {
+                "x86_emulation needs a static qemu-x86_64: install qemu-user-static".to_string()
+            })
+        })
+    }
+
+    fn rosetta(dir: &Path) -> Option<Self> {
+        let path = dir.join("rosetta");
+        path.is_file().then_some(Self {
+            path,
+            // Credentials from the binary, so setuid x86 programs work
+            flags: "CF",
+        })
+    }
+
+    fn qemu(path: &Path) -> Option<Self> {
+        path.is_file().then(|| Self {
+            path: path.to_path_buf(),
+            flags: "F",
+        })
+    }
+
+    /// Copy the interpreter into `dir`, the directory shared with the guest.
+    pub(crate) fn stage(&self, dir: &Path) -> BoxliteResult<()> {
+        std::fs::create_dir_all(dir)?;
+        let staged = dir.join(INTERPRETER);
+        if staged.exists() {
+            std::fs::remove_file(&staged)?;
+        }
+        if std::fs::hard_link(&self.path, &staged).is_err() {
+            std::fs::copy(&self.path, &staged).map_err(|e| {
+                BoxliteError::Storage(format!(
+                    "Failed to copy x86_64 interpreter {}: {}",
+                    self.path.display(),
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Interpreter path inside the guest.
+    pub(crate) fn guest_interpreter(&self) -> String {
+        format!("{}/{}", GUEST_DIR, INTERPRETER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(X86Emulator::rosetta(dir.path()), None);
+        std::fs::write(dir.path().join("rosetta"), b"").unwrap();
+        let rosetta = X86Emulator::rosetta(dir.path()).unwrap();
+        assert_eq!(rosetta.flags, "CF");
+
+        let qemu_path = dir.path().join("qemu-x86_64-static");
+        assert_eq!(X86Emulator::qemu(&qemu_path), None);
+        std::fs::write(&qemu_path, b"qemu").unwrap();
+        let qemu = X86Emulator::qemu(&qemu_path).unwrap();
+        assert_eq!(qemu.flags, "F");
+
+        let share = dir.path().join("share");
+        qemu.stage(&share).unwrap();
+        qemu.stage(&share).unwrap();
+        assert_eq!(std::fs::read(share.join(INTERPRETER)).unwrap(), b"qemu");
+        assert_eq!(
+            qemu.guest_interpreter(),
+            "/run/boxlite/x86/x86_64-interpreter"
+        );
+    }
+}
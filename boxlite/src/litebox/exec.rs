@@ -3,13 +3,18 @@
 //! Type definitions for executing commands in a box.
 //! The actual execution logic is in BoxImpl::exec().
 
+use super::idle::ActivityGuard;
 use crate::portal::interfaces::ExecutionInterface;
+use crate::util::stream_buffer::BufferReceiver;
 use boxlite_shared::errors::BoxliteResult;
 use futures::Stream;
+use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::sync::mpsc;
+use tokio_util::sync::DropGuard;
 
 /// Command builder for executing programs in a box.
 ///
@@ -34,6 +39,73 @@ pub struct BoxCommand {
     pub(crate) timeout: Option<Duration>,
     pub(crate) working_dir: Option<String>,
     pub(crate) tty: bool,
+    pub(crate) user: Option<(u32, u32)>,
+    pub(crate) network: ExecNetwork,
+    pub(crate) private_fs: Option<Vec<String>>,
+    pub(crate) capture: Option<OutputCapture>,
+    pub(crate) artifacts: Vec<String>,
+    pub(crate) cache_inputs: Option<Vec<String>>,
+    pub(crate) audit: bool,
+}
+
+/// Output written to files inside the box instead of streamed.
+///
+/// A captured stream yields nothing from [`Execution::stdout`] or
+/// [`Execution::stderr`]; read it back in ranges with
+/// [`Execution::fetch_output`]. The files stay in the box, so a build that
+/// writes gigabytes of logs costs the control channel only what is fetched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputCapture {
+    /// Absolute path, in the box, to write stdout to.
+    pub stdout_path: Option<String>,
+    /// Absolute path, in the box, to write stderr to.
+    pub stderr_path: Option<String>,
+    /// Rotate to `path.1`, `path.2`, ... once a file reaches this size;
+    /// 0 never rotates.
+    pub max_file_bytes: u64,
+    /// Files kept, including the one being written; older ones are
+    /// deleted. 0 keeps them all. Together with `max_file_bytes` this caps
+    /// the space a stream takes.
+    pub max_files: u32,
+}
+
+/// One of a command's output streams.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A range of captured output.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputChunk {
+    pub data: Vec<u8>,
+    /// Stream offset of `data`. Later than the one asked for when that
+    /// part was already rotated away.
+    pub offset: u64,
+    /// Bytes the stream has written so far, rotated ones included.
+    pub total_bytes: u64,
+    /// The process closed the stream, so `total_bytes` is final.
+    pub complete: bool,
+}
+
+/// Network namespace a command runs in.
+///
+/// Anything but [`ExecNetwork::Shared`] gives the command its own namespace,
+/// so commands in the same box cannot reach each other's local ports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecNetwork {
+    /// The box's network, shared with its other processes.
+    #[default]
+    Shared,
+    /// No network at all, not even loopback.
+    None,
+    /// Loopback only.
+    Loopback,
+    /// Loopback plus a point-to-point link to the box's namespace, so the
+    /// command reaches services listening in the box (at `10.254.x.1`) but
+    /// not the outside network or other isolated commands.
+    Veth,
 }
 
 impl BoxCommand {
@@ -46,6 +118,13 @@ impl BoxCommand {
             timeout: None,
             working_dir: None,
             tty: false,
+            user: None,
+            network: ExecNetwork::Shared,
+            private_fs: None,
+            capture: None,
+            artifacts: vec![],
+            cache_inputs: None,
+            audit: false,
         }
     }
 
@@ -85,6 +164,92 @@ impl BoxCommand {
         self
     }
 
+    /// Run as `uid` with primary group `gid` instead of root.
+    pub fn user(mut self, uid: u32, gid: u32) -> Self {
+        self.user = Some((uid, gid));
+        self
+    }
+
+    /// Run in its own network namespace (see [`ExecNetwork`]).
+    pub fn network(mut self, network: ExecNetwork) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Run in a private filesystem view: a fresh, empty `/tmp` of its own,
+    /// and a throwaway overlay over each of `overlay_paths`, directories
+    /// in the box.
+    ///
+    /// The command and everything it starts see the directories' contents
+    /// and can write to them, but the writes land in guest memory and no
+    /// other process sees them. The view is discarded once the last of
+    /// those processes exits, so concurrent commands in one box cannot
+    /// interfere through temporary files. Artifacts have to be written
+    /// outside the view to be collected.
+    pub fn private_fs<I, S>(mut self, overlay_paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.private_fs = Some(overlay_paths.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Write output to files in the box (see [`OutputCapture`]).
+    pub fn capture_output(mut self, capture: OutputCapture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Collect these box paths (files or directories) as artifacts when
+    /// the command exits.
+    ///
+    /// `Execution::wait` returns once they are stored; fetch them with
+    /// `LiteBox::download_artifacts`. Paths that do not exist are skipped.
+    pub fn artifacts<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.artifacts.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Reuse the result of an earlier identical run in this box.
+    ///
+    /// A run is identified by the command, arguments, environment, working
+    /// directory, user, artifact paths and the content of `inputs`, box
+    /// paths (files or directories) the command reads. When a matching run
+    /// was recorded, its output, exit code and artifacts come back without
+    /// the command running again. Only runs that exited on their own are
+    /// recorded; anything else the command depends on, stdin included, is
+    /// up to the caller to keep unchanged.
+    pub fn cache<I, S>(mut self, inputs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cache_inputs = Some(inputs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Record what the command and everything it starts do: programs
+    /// run, files opened and removed, addresses connected to and bound,
+    /// and a count of each traced syscall. The summary comes back as
+    /// [`ExecResult::audit`].
+    ///
+    /// The guest agent traces with a seccomp user-notification filter, so
+    /// each traced call waits on the agent; file-heavy commands run
+    /// noticeably slower. The report is for visibility, not enforcement: a
+    /// multi-threaded program can change a call's arguments after they were
+    /// recorded. Commands run as a non-root user cannot gain privileges
+    /// through setuid programs while audited. Audited commands are not
+    /// cached.
+    pub fn audit(mut self, enable: bool) -> Self {
+        self.audit = enable;
+        self
+    }
+
     /// Enable TTY (pseudo-terminal) for interactive sessions.
     ///
     /// Terminal size is auto-detected from the current terminal.
@@ -99,6 +264,13 @@ impl BoxCommand {
 /// Similar to `std::process::Child` but for remote execution in a guest.
 /// Provides access to stdin, stdout, stderr streams and control operations.
 ///
+/// Unlike a `Child`, dropping the handle before the process exits kills
+/// the process, as does the host losing its connection to the box: a
+/// caller that gives up on a command, a cancelled SDK call or a host
+/// interrupted with Ctrl-C does not leave it running in the guest. To keep
+/// something running past its handle, start it in the background from a
+/// shell (`nohup ... &`).
+///
 /// # Examples
 ///
 /// ```rust,no_run
@@ -109,7 +281,7 @@ impl BoxCommand {
 /// let mut execution = litebox.exec(BoxCommand::new("ls").arg("-la")).await?;
 ///
 /// // Read stdout
-/// let mut stdout = execution.stdout.take().unwrap();
+/// let mut stdout = execution.stdout().unwrap();
 /// while let Some(line) = stdout.next().await {
 ///     println!("{}", line);
 /// }
@@ -123,6 +295,8 @@ impl BoxCommand {
 pub struct Execution {
     id: ExecutionId,
     inner: std::sync::Arc<tokio::sync::Mutex<ExecutionInner>>,
+    /// For calls that must not wait behind [`Execution::wait`].
+    interface: ExecutionInterface,
 }
 
 pub(crate) struct ExecutionInner {
@@ -138,6 +312,12 @@ pub(crate) struct ExecutionInner {
 
     /// Standard error stream (read-only).
     stderr: Option<ExecStderr>,
+
+    /// Keeps the box from being suspended until the result is collected.
+    activity: Option<ActivityGuard>,
+
+    /// Kills the process if the handle is dropped before it exits.
+    _cancel: DropGuard,
 }
 
 /// Unique identifier for an execution.
@@ -152,19 +332,24 @@ impl Execution {
         stdin: Option<ExecStdin>,
         stdout: Option<ExecStdout>,
         stderr: Option<ExecStderr>,
+        activity: Option<ActivityGuard>,
+        cancel: DropGuard,
     ) -> Self {
         let inner = ExecutionInner {
-            interface,
+            interface: interface.clone(),
             result_rx,
             cached_result: None,
             stdin,
             stdout,
             stderr,
+            activity,
+            _cancel: cancel,
         };
 
         Self {
             id: execution_id,
             inner: std::sync::Arc::new(tokio::sync::Mutex::new(inner)),
+            interface,
         }
     }
 
@@ -212,6 +397,7 @@ impl Execution {
         // Try to receive from result channel (non-blocking)
         if let Ok(status) = inner.result_rx.try_recv() {
             inner.cached_result = Some(status.clone());
+            inner.activity = None;
             return Ok(status);
         }
 
@@ -220,6 +406,7 @@ impl Execution {
             boxlite_shared::BoxliteError::Internal("Result channel closed".into())
         })?;
         inner.cached_result = Some(status.clone());
+        inner.activity = None;
         Ok(status)
     }
 
@@ -241,6 +428,23 @@ impl Execution {
         let mut inner = self.inner.lock().await;
         inner.interface.resize_tty(&self.id, rows, cols, 0, 0).await
     }
+
+    /// Read up to `length` bytes of captured output from `offset`.
+    ///
+    /// `length` 0 asks for as much as one call returns. Results can come up
+    /// short of the end, so continue from `offset + data.len()` until it is
+    /// empty and `complete` is set. Safe to call while another task waits.
+    pub async fn fetch_output(
+        &self,
+        stream: OutputStream,
+        offset: u64,
+        length: u64,
+    ) -> BoxliteResult<OutputChunk> {
+        self.interface
+            .clone()
+            .fetch_output(&self.id, stream, offset, length)
+            .await
+    }
 }
 
 /// Exit status of a process.
@@ -248,9 +452,99 @@ impl Execution {
 pub struct ExecResult {
     /// Exit code (0 = success). If terminated by signal, code is negative signal number.
     pub exit_code: i32,
+    /// Why the process ended.
+    pub exit_kind: ExitKind,
+    /// Wall-clock time from spawn to exit.
+    pub duration: Duration,
+    /// Peak resident set size of the largest process in the tree.
+    pub peak_memory_bytes: u64,
+    /// CPU time spent in user mode, including reaped descendants.
+    pub user_time: Duration,
+    /// CPU time spent in the kernel, including reaped descendants.
+    pub system_time: Duration,
+    /// Bytes read from block devices, including reaped descendants.
+    pub read_bytes: u64,
+    /// Bytes written to block devices, including reaped descendants.
+    pub write_bytes: u64,
+    /// What the command did, when run with [`BoxCommand::audit`].
+    pub audit: Option<AuditReport>,
+}
+
+/// Summary of an audited command's activity (see [`BoxCommand::audit`]).
+///
+/// Lists hold distinct entries, sorted, with paths as the command saw
+/// them. Each list keeps a bounded number of entries; the rest are only
+/// counted in `dropped`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Calls per traced syscall, e.g. `openat` or `connect`.
+    pub syscalls: BTreeMap<String, u64>,
+    /// Programs run.
+    pub executed: Vec<String>,
+    /// Files opened read-only.
+    pub files_read: Vec<String>,
+    /// Files opened for writing, created, or renamed into place.
+    pub files_written: Vec<String>,
+    /// Files and directories removed or renamed away.
+    pub files_removed: Vec<String>,
+    /// Addresses connected to: `host:port`, or a Unix socket path.
+    pub connections: Vec<String>,
+    /// Addresses bound, in the same form.
+    pub listeners: Vec<String>,
+    /// Entries left out once their list was full.
+    pub dropped: u64,
+}
+
+/// Why a process ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExitKind {
+    /// Exited on its own with [`ExecResult::exit_code`].
+    Exited,
+    /// Terminated by a signal sent by something other than the runtime.
+    Signaled {
+        /// Linux signal number.
+        signal: i32,
+        /// Signal name, e.g. `SIGSEGV`.
+        name: String,
+    },
+    /// Killed by the kernel OOM killer.
+    OomKilled,
+    /// Killed after running past [`crate::BoxCommand::timeout`].
+    TimedOut,
+    /// The exit could not be observed, e.g. the guest connection was lost.
+    Unknown,
+}
+
+impl ExitKind {
+    /// Name without details: "exited", "signaled", "oom_killed",
+    /// "timed_out" or "unknown".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExitKind::Exited => "exited",
+            ExitKind::Signaled { .. } => "signaled",
+            ExitKind::OomKilled => "oom_killed",
+            ExitKind::TimedOut => "timed_out",
+            ExitKind::Unknown => "unknown",
+        }
+    }
 }
 
 impl ExecResult {
+    /// Result for a process whose exit could not be observed.
+    pub(crate) fn unknown() -> Self {
+        Self {
+            exit_code: -1,
+            exit_kind: ExitKind::Unknown,
+            duration: Duration::ZERO,
+            peak_memory_bytes: 0,
+            user_time: Duration::ZERO,
+            system_time: Duration::ZERO,
+            read_bytes: 0,
+            write_bytes: 0,
+            audit: None,
+        }
+    }
+
     /// Returns true if the exit code was 0.
     pub fn success(&self) -> bool {
         self.exit_code == 0
@@ -259,38 +553,78 @@ impl ExecResult {
     pub fn code(&self) -> i32 {
         self.exit_code
     }
+
+    /// Total CPU time, user plus kernel.
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
 }
 
+/// Largest piece of stdin sent to the guest in one message.
+const STDIN_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Standard input stream (write-only).
+///
+/// Only a few chunks are buffered on the way to the guest; past that,
+/// writes wait until the process reads, so large inputs stream through
+/// without being held in memory. Dropping the stream (or calling
+/// [`close`](Self::close)) sends EOF.
 pub struct ExecStdin {
-    sender: mpsc::UnboundedSender<Vec<u8>>,
+    sender: mpsc::Sender<Vec<u8>>,
 }
 
 impl ExecStdin {
-    pub(crate) fn new(sender: mpsc::UnboundedSender<Vec<u8>>) -> Self {
+    pub(crate) fn new(sender: mpsc::Sender<Vec<u8>>) -> Self {
         Self { sender }
     }
 
     /// Write data to stdin.
+    ///
+    /// Waits while the process is behind on reading.
     pub async fn write(&mut self, data: &[u8]) -> BoxliteResult<()> {
-        self.sender
-            .send(data.to_vec())
-            .map_err(|_| boxlite_shared::BoxliteError::Internal("stdin channel closed".to_string()))
+        for chunk in data.chunks(STDIN_CHUNK_SIZE) {
+            self.sender.send(chunk.to_vec()).await.map_err(|_| {
+                boxlite_shared::BoxliteError::Internal("stdin channel closed".to_string())
+            })?;
+        }
+        Ok(())
     }
 
     /// Write all data to stdin.
     pub async fn write_all(&mut self, data: &[u8]) -> BoxliteResult<()> {
         self.write(data).await
     }
+
+    /// Stream everything from `reader` to stdin, returning the byte count.
+    ///
+    /// Does not close stdin, so several sources can be concatenated.
+    pub async fn copy_from<R>(&mut self, reader: &mut R) -> BoxliteResult<u64>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buf = vec![0u8; STDIN_CHUNK_SIZE];
+        let mut total = 0;
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(total);
+            }
+            self.write(&buf[..n]).await?;
+            total += n as u64;
+        }
+    }
+
+    /// Close stdin, so the process reads EOF once it has drained the input.
+    pub fn close(self) {}
 }
 
 /// Standard output stream (read-only).
 pub struct ExecStdout {
-    receiver: mpsc::UnboundedReceiver<String>,
+    receiver: BufferReceiver<String>,
 }
 
 impl ExecStdout {
-    pub(crate) fn new(receiver: mpsc::UnboundedReceiver<String>) -> Self {
+    pub(crate) fn new(receiver: BufferReceiver<String>) -> Self {
         Self { receiver }
     }
 }
@@ -305,11 +639,11 @@ impl Stream for ExecStdout {
 
 /// Standard error stream (read-only).
 pub struct ExecStderr {
-    receiver: mpsc::UnboundedReceiver<String>,
+    receiver: BufferReceiver<String>,
 }
 
 impl ExecStderr {
-    pub(crate) fn new(receiver: mpsc::UnboundedReceiver<String>) -> Self {
+    pub(crate) fn new(receiver: BufferReceiver<String>) -> Self {
         Self { receiver }
     }
 }
@@ -321,3 +655,30 @@ impl Stream for ExecStderr {
         self.receiver.poll_recv(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stdin_writes_in_chunks_with_backpressure() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut stdin = ExecStdin::new(tx);
+        let data = vec![7u8; STDIN_CHUNK_SIZE * 2 + 10];
+
+        // With room for one chunk, the write cannot finish until read
+        let writer = tokio::spawn(async move {
+            stdin.write_all(&data).await.unwrap();
+            let mut reader: &[u8] = b"tail";
+            assert_eq!(stdin.copy_from(&mut reader).await.unwrap(), 4);
+            stdin.close();
+        });
+
+        let mut sizes = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            sizes.push(chunk.len());
+        }
+        writer.await.unwrap();
+        assert_eq!(sizes, vec![STDIN_CHUNK_SIZE, STDIN_CHUNK_SIZE, 10, 4]);
+    }
+}
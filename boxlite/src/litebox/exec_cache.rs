@@ -0,0 +1,337 @@
+//! Cached execution results.
+//!
+//! A command run with [`BoxCommand::cache`] is keyed on a SHA-256 over what
+//! identifies it: the command line, environment, working directory, user,
+//! artifact paths, and the guest's content digests of its declared inputs.
+//! A run that exits on its own is recorded in the box directory as
+//! `exec-cache/{key}.json`, with its artifacts next to it as
+//! `{key}.tar.zst`; the next run with the same key replays the record
+//! instead of running. Only the newest [`MAX_ENTRIES`] records are kept.
+//!
+//! [`BoxCommand::cache`]: super::BoxCommand::cache
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::exec::{BoxCommand, ExecResult, ExitKind};
+use crate::runtime::options::StreamBufferOptions;
+use crate::util::stream_buffer::{self, BufferReceiver};
+
+/// Cache directory inside a box directory.
+pub(crate) const EXEC_CACHE_DIR: &str = "exec-cache";
+
+/// Bumped whenever the key layout changes, so old records stop matching.
+const KEY_VERSION: u32 = 1;
+
+/// Records kept per box; older ones are deleted as new ones land.
+const MAX_ENTRIES: usize = 256;
+
+/// Runs with more output than this on a stream are not recorded.
+const MAX_OUTPUT_BYTES: usize = 4 * 1024 * 1024;
+
+const RECORD_SUFFIX: &str = ".json";
+const ARCHIVE_SUFFIX: &str = ".tar.zst";
+
+/// A recorded run.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CachedRun {
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub peak_memory_bytes: u64,
+    pub user_time_ms: u64,
+    pub system_time_ms: u64,
+    #[serde(default)]
+    pub read_bytes: u64,
+    #[serde(default)]
+    pub write_bytes: u64,
+    /// An artifacts archive is stored with the record.
+    pub artifacts: bool,
+}
+
+/// Output replayed from a [`CachedRun`].
+pub(crate) struct Replay {
+    pub stdout_rx: BufferReceiver<String>,
+    pub stderr_rx: BufferReceiver<String>,
+    pub result_rx: mpsc::UnboundedReceiver<ExecResult>,
+}
+
+impl CachedRun {
+    /// Record of a run, or `None` if it did not exit on its own.
+    pub(crate) fn new(
+        result: &ExecResult,
+        stdout: Vec<String>,
+        stderr: Vec<String>,
+        artifacts: bool,
+    ) -> Option<Self> {
+        (result.exit_kind == ExitKind::Exited).then_some(Self {
+            stdout,
+            stderr,
+            exit_code: result.exit_code,
+            duration_ms: result.duration.as_millis() as u64,
+            peak_memory_bytes: result.peak_memory_bytes,
+            user_time_ms: result.user_time.as_millis() as u64,
+            system_time_ms: result.system_time.as_millis() as u64,
+            read_bytes: result.read_bytes,
+            write_bytes: result.write_bytes,
+            artifacts,
+        })
+    }
+
+    fn result(&self) -> ExecResult {
+        ExecResult {
+            exit_code: self.exit_code,
+            exit_kind: ExitKind::Exited,
+            duration: Duration::from_millis(self.duration_ms),
+            peak_memory_bytes: self.peak_memory_bytes,
+            user_time: Duration::from_millis(self.user_time_ms),
+            system_time: Duration::from_millis(self.system_time_ms),
+            read_bytes: self.read_bytes,
+            write_bytes: self.write_bytes,
+            audit: None,
+        }
+    }
+
+    /// Streams that yield the recorded output and result.
+    pub(crate) fn replay(self, buffer: StreamBufferOptions, dropped: Arc<AtomicU64>) -> Replay {
+        let (result_tx, result_rx) = mpsc::unbounded_channel();
+        let _ = result_tx.send(self.result());
+        Replay {
+            stdout_rx: feed(self.stdout, buffer, Arc::clone(&dropped)),
+            stderr_rx: feed(self.stderr, buffer, dropped),
+            result_rx,
+        }
+    }
+}
+
+fn feed(
+    chunks: Vec<String>,
+    buffer: StreamBufferOptions,
+    dropped: Arc<AtomicU64>,
+) -> BufferReceiver<String> {
+    let (tx, rx) = stream_buffer::channel(buffer, dropped);
+    tokio::spawn(async move {
+        for chunk in chunks {
+            if tx.send(chunk).await.is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Copy of an output stream, or `None` if it was too large to keep.
+pub(crate) type OutputCopy = JoinHandle<Option<Vec<String>>>;
+
+/// Pass `output` through, keeping a copy for the record.
+///
+/// The copy is complete once the stream ends; output past
+/// [`MAX_OUTPUT_BYTES`] is not kept.
+pub(crate) fn tee(
+    mut output: BufferReceiver<String>,
+    buffer: StreamBufferOptions,
+    dropped: Arc<AtomicU64>,
+) -> (BufferReceiver<String>, OutputCopy) {
+    let (tx, rx) = stream_buffer::channel(buffer, dropped);
+    let copy = tokio::spawn(async move {
+        let mut chunks = Some(Vec::new());
+        let mut bytes = 0;
+        let mut forwarding = true;
+        while let Some(chunk) = output.recv().await {
+            bytes += chunk.len();
+            if bytes > MAX_OUTPUT_BYTES {
+                chunks = None;
+            }
+            if let Some(chunks) = &mut chunks {
+                chunks.push(chunk.clone());
+            }
+            if forwarding && tx.send(chunk).await.is_err() {
+                forwarding = false;
+            }
+        }
+        chunks
+    });
+    (rx, copy)
+}
+
+/// Cache key for `command` given the digests of its inputs.
+///
+/// `command` is what is sent to the guest, defaults applied.
+pub(crate) fn key(command: &BoxCommand, inputs: &[(String, Option<String>)]) -> String {
+    let mut env = command.env.clone().unwrap_or_default();
+    env.sort();
+    let mut identity = serde_json::json!({
+        "version": KEY_VERSION,
+        "command": command.command,
+        "args": command.args,
+        "env": env,
+        "working_dir": command.working_dir,
+        "user": command.user,
+        "tty": command.tty,
+        "network": format!("{:?}", command.network),
+        "artifacts": command.artifacts,
+        "inputs": inputs,
+    });
+    // Left out when unset, so keys recorded before it existed still match
+    if let Some(overlay_paths) = &command.private_fs {
+        identity["private_fs"] = serde_json::json!(overlay_paths);
+    }
+    hex::encode(Sha256::digest(identity.to_string().as_bytes()))
+}
+
+fn record_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}{}", key, RECORD_SUFFIX))
+}
+
+/// Artifacts archive stored with the record for `key`.
+pub(crate) fn archive_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}{}", key, ARCHIVE_SUFFIX))
+}
+
+/// The recorded run for `key`, if there is a complete one.
+pub(crate) fn lookup(dir: &Path, key: &str) -> Option<CachedRun> {
+    let content = std::fs::read(record_path(dir, key)).ok()?;
+    let run: CachedRun = serde_json::from_slice(&content).ok()?;
+    if run.artifacts && !archive_path(dir, key).exists() {
+        return None;
+    }
+    Some(run)
+}
+
+/// Record `run` under `key`, copying its artifacts from `archive`, then
+/// drop the oldest records past [`MAX_ENTRIES`].
+pub(crate) fn store(
+    dir: &Path,
+    key: &str,
+    run: &CachedRun,
+    archive: Option<&Path>,
+) -> BoxliteResult<()> {
+    let fail = |path: &Path, e: std::io::Error| {
+        BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e))
+    };
+    std::fs::create_dir_all(dir).map_err(|e| fail(dir, e))?;
+
+    // The record lands last, so a lookup never sees it without its archive
+    let staging = |path: &Path| path.with_extension(format!("tmp-{}", std::process::id()));
+    if let Some(archive) = archive {
+        let dest = archive_path(dir, key);
+        let staged = staging(&dest);
+        std::fs::copy(archive, &staged)
+            .and_then(|_| std::fs::rename(&staged, &dest))
+            .map_err(|e| fail(&dest, e))?;
+    }
+    let dest = record_path(dir, key);
+    let staged = staging(&dest);
+    let content = serde_json::to_vec(run)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode cached run: {}", e)))?;
+    std::fs::write(&staged, content)
+        .and_then(|_| std::fs::rename(&staged, &dest))
+        .map_err(|e| fail(&dest, e))?;
+
+    prune(dir, MAX_ENTRIES);
+    Ok(())
+}
+
+fn prune(dir: &Path, max_entries: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut records: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let key = path.file_name()?.to_str()?.strip_suffix(RECORD_SUFFIX)?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, key.to_string()))
+        })
+        .collect();
+    if records.len() <= max_entries {
+        return;
+    }
+    records.sort();
+    for (_, key) in &records[..records.len() - max_entries] {
+        let _ = std::fs::remove_file(record_path(dir, key));
+        let _ = std::fs::remove_file(archive_path(dir, key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_covers_command_and_inputs() {
+        let command = BoxCommand::new("pytest")
+            .arg("-q")
+            .env("A", "1")
+            .env("B", "2");
+        let inputs = [("/src".to_string(), Some("abc".to_string()))];
+        let base = key(&command, &inputs);
+
+        let reordered = BoxCommand::new("pytest")
+            .arg("-q")
+            .env("B", "2")
+            .env("A", "1");
+        assert_eq!(key(&reordered, &inputs), base);
+        assert_ne!(key(&command.clone().arg("-x"), &inputs), base);
+        assert_ne!(key(&command.clone().private_fs(["/work"]), &inputs), base);
+
+        let changed = [("/src".to_string(), Some("abd".to_string()))];
+        assert_ne!(key(&command, &changed), base);
+        let missing = [("/src".to_string(), None)];
+        assert_ne!(key(&command, &missing), base);
+    }
+
+    #[test]
+    fn test_store_lookup_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = ExecResult {
+            exit_code: 1,
+            exit_kind: ExitKind::Exited,
+            duration: Duration::from_millis(1500),
+            peak_memory_bytes: 4096,
+            user_time: Duration::ZERO,
+            system_time: Duration::ZERO,
+            read_bytes: 0,
+            write_bytes: 512,
+            audit: None,
+        };
+        let run = CachedRun::new(&result, vec!["out\n".into()], vec![], true).unwrap();
+        assert_eq!(run.result().duration, result.duration);
+
+        let archive = dir.path().join("artifacts.tar.zst");
+        std::fs::write(&archive, b"archive").unwrap();
+        let cache = dir.path().join("cache");
+        store(&cache, "k1", &run, Some(&archive)).unwrap();
+        assert_eq!(lookup(&cache, "k1"), Some(run.clone()));
+        assert_eq!(lookup(&cache, "k2"), None);
+
+        // A record whose archive is gone is not used
+        std::fs::remove_file(archive_path(&cache, "k1")).unwrap();
+        assert_eq!(lookup(&cache, "k1"), None);
+
+        let plain = CachedRun {
+            artifacts: false,
+            ..run
+        };
+        store(&cache, "k2", &plain, None).unwrap();
+        prune(&cache, 1);
+        assert_eq!(lookup(&cache, "k2"), Some(plain));
+        assert!(!record_path(&cache, "k1").exists());
+
+        let timed_out = ExecResult {
+            exit_kind: ExitKind::TimedOut,
+            ..result
+        };
+        assert_eq!(CachedRun::new(&timed_out, vec![], vec![], false), None);
+    }
+}
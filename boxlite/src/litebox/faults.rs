@@ -0,0 +1,66 @@
+//! Failure injection types, for testing how callers cope with a box going
+//! wrong.
+
+use std::time::Duration;
+
+use boxlite_shared::inject_fault_request;
+
+/// A failure to inject into a running box (see `LiteBox::inject_fault`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Detach the mount at `path` in the container, so its files vanish
+    /// and IO on open ones fails.
+    MountError { path: String },
+    /// Freeze the container rootfs for `duration` (60s at most), stalling
+    /// every write.
+    SlowIo { duration: Duration },
+    /// Step the guest clock by `seconds`, backwards if negative.
+    ClockJump { seconds: i64 },
+    /// Drop the host's connection to the guest agent. In-flight calls fail;
+    /// the next one reconnects.
+    VsockDrop,
+}
+
+impl Fault {
+    /// The guest agent's form of the fault; None for faults injected on the
+    /// host.
+    pub(crate) fn into_proto(self) -> Option<inject_fault_request::Fault> {
+        use boxlite_shared::{ClockJumpFault, IoStallFault, MountFault};
+
+        match self {
+            Fault::MountError { path } => {
+                Some(inject_fault_request::Fault::MountError(MountFault { path }))
+            }
+            Fault::SlowIo { duration } => {
+                Some(inject_fault_request::Fault::IoStall(IoStallFault {
+                    duration_ms: duration.as_millis() as u64,
+                }))
+            }
+            Fault::ClockJump { seconds } => {
+                Some(inject_fault_request::Fault::ClockJump(ClockJumpFault {
+                    seconds,
+                }))
+            }
+            Fault::VsockDrop => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_proto() {
+        let fault = Fault::SlowIo {
+            duration: Duration::from_secs(2),
+        };
+        assert_eq!(
+            fault.into_proto(),
+            Some(inject_fault_request::Fault::IoStall(
+                boxlite_shared::IoStallFault { duration_ms: 2000 }
+            ))
+        );
+        assert_eq!(Fault::VsockDrop.into_proto(), None);
+    }
+}
@@ -0,0 +1,86 @@
+//! Guest event streams that survive the agent going away.
+//!
+//! The guest agent streams memory, pressure and crash reports for as long
+//! as the box runs. Such a stream breaks when the agent is upgraded in
+//! place or the connection drops; [`GuestStream`] opens it again on the
+//! next read, a short delay after the break.
+
+use std::future::Future;
+use std::time::Duration;
+
+use boxlite_shared::errors::BoxliteResult;
+use tonic::Streaming;
+
+use crate::portal::GuestSession;
+use crate::portal::interfaces::GuestInterface;
+
+/// How long to wait before watching again after the stream broke.
+const REOPEN_DELAY: Duration = Duration::from_secs(2);
+
+/// What reading a [`GuestStream`] gave.
+pub(crate) enum Watched<T> {
+    Message(T),
+    /// The stream broke; the next read opens it again.
+    Broken,
+}
+
+/// A guest stream, opened with `open` and again each time it breaks.
+pub(crate) struct GuestStream<T, F> {
+    session: GuestSession,
+    /// What is watched, for logs.
+    name: &'static str,
+    open: F,
+    stream: Option<Streaming<T>>,
+    broken: bool,
+}
+
+impl<T, F, Fut> GuestStream<T, F>
+where
+    F: FnMut(GuestInterface) -> Fut,
+    Fut: Future<Output = BoxliteResult<Streaming<T>>>,
+{
+    pub(crate) fn new(session: GuestSession, name: &'static str, open: F) -> Self {
+        Self {
+            session,
+            name,
+            open,
+            stream: None,
+            broken: false,
+        }
+    }
+
+    /// Read the next message, opening the stream first if needed.
+    ///
+    /// Returns `None` once the guest ends the stream, or when it cannot be
+    /// opened: an agent that predates the watch, or one that went away.
+    pub(crate) async fn next(&mut self) -> Option<Watched<T>> {
+        if self.stream.is_none() {
+            if self.broken {
+                tokio::time::sleep(REOPEN_DELAY).await;
+            }
+            let opened = match self.session.guest().await {
+                Ok(guest) => (self.open)(guest).await,
+                Err(e) => Err(e),
+            };
+            match opened {
+                Ok(stream) => self.stream = Some(stream),
+                Err(e) => {
+                    tracing::debug!(watch = self.name, "Failed to open guest watch: {}", e);
+                    return None;
+                }
+            }
+        }
+
+        let stream = self.stream.as_mut()?;
+        match stream.message().await {
+            Ok(Some(message)) => Some(Watched::Message(message)),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::debug!(watch = self.name, "Guest watch ended: {}", e);
+                self.stream = None;
+                self.broken = true;
+                Some(Watched::Broken)
+            }
+        }
+    }
+}
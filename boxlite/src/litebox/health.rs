@@ -0,0 +1,146 @@
+//! Box health from readiness and liveness probes.
+//!
+//! The guest agent runs the box's probes (Container.WatchHealth) and streams
+//! each change in a probe's health. The host folds them into a
+//! [`BoxHealth`], persisted with the box state so `info()` shows it, and
+//! emits each change as a `health_changed` event.
+
+use boxlite_shared::{HealthEvent, ProbeKind};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Overall health of a box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// A probe has not reported yet.
+    Starting,
+    /// Every probe passes.
+    Healthy,
+    /// A probe fails.
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Starting => "starting",
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Health of a box with probes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoxHealth {
+    pub status: HealthStatus,
+    /// Whether the readiness probe passes; None until it first reports.
+    /// Boxes without one are always ready.
+    pub ready: Option<bool>,
+    /// Whether the liveness probe passes; None until it first reports.
+    /// Boxes without one are always live.
+    pub live: Option<bool>,
+    /// Why the last failing probe failed.
+    pub message: Option<String>,
+    /// Times the box was restarted for failing its liveness probe.
+    pub restarts: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BoxHealth {
+    /// Health of a box that just started.
+    pub(crate) fn new(readiness: bool, liveness: bool, restarts: u32) -> Self {
+        let mut health = Self {
+            status: HealthStatus::Starting,
+            ready: (!readiness).then_some(true),
+            live: (!liveness).then_some(true),
+            message: None,
+            restarts,
+            updated_at: Utc::now(),
+        };
+        health.status = health.compute_status();
+        health
+    }
+
+    /// Fold in one probe change; returns true if the liveness probe just
+    /// started failing.
+    pub(crate) fn apply(&mut self, event: &HealthEvent) -> bool {
+        let probe = match ProbeKind::try_from(event.kind) {
+            Ok(ProbeKind::ProbeLiveness) => &mut self.live,
+            _ => &mut self.ready,
+        };
+        let was = probe.replace(event.healthy);
+
+        if !event.healthy {
+            self.message = Some(event.message.clone());
+        } else if self.ready != Some(false) && self.live != Some(false) {
+            self.message = None;
+        }
+        self.status = self.compute_status();
+        self.updated_at = Utc::now();
+
+        event.kind == ProbeKind::ProbeLiveness as i32 && !event.healthy && was != Some(false)
+    }
+
+    fn compute_status(&self) -> HealthStatus {
+        match (self.ready, self.live) {
+            (Some(false), _) | (_, Some(false)) => HealthStatus::Unhealthy,
+            (Some(true), Some(true)) => HealthStatus::Healthy,
+            _ => HealthStatus::Starting,
+        }
+    }
+}
+
+/// Name of the probe a health event is about, as used in event attributes.
+pub(crate) fn probe_name(event: &HealthEvent) -> &'static str {
+    match ProbeKind::try_from(event.kind) {
+        Ok(ProbeKind::ProbeLiveness) => "liveness",
+        _ => "readiness",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: ProbeKind, healthy: bool) -> HealthEvent {
+        HealthEvent {
+            kind: kind as i32,
+            healthy,
+            message: if healthy { "" } else { "connection refused" }.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_health_transitions() {
+        let mut health = BoxHealth::new(true, true, 0);
+        assert_eq!(health.status, HealthStatus::Starting);
+
+        assert!(!health.apply(&event(ProbeKind::ProbeLiveness, true)));
+        assert_eq!(health.status, HealthStatus::Starting);
+        assert!(!health.apply(&event(ProbeKind::ProbeReadiness, true)));
+        assert_eq!(health.status, HealthStatus::Healthy);
+
+        assert!(health.apply(&event(ProbeKind::ProbeLiveness, false)));
+        assert_eq!(health.status, HealthStatus::Unhealthy);
+        assert_eq!(health.message.as_deref(), Some("connection refused"));
+        assert!(!health.apply(&event(ProbeKind::ProbeLiveness, false)));
+
+        assert!(!health.apply(&event(ProbeKind::ProbeLiveness, true)));
+        assert_eq!(health.status, HealthStatus::Healthy);
+        assert_eq!(health.message, None);
+
+        let mut health = BoxHealth::new(false, true, 2);
+        assert_eq!(health.ready, Some(true));
+        assert!(!health.apply(&event(ProbeKind::ProbeLiveness, true)));
+        assert_eq!(health.status, HealthStatus::Healthy);
+        assert_eq!(health.restarts, 2);
+    }
+}
@@ -0,0 +1,85 @@
+//! Idle tracking for boxes with an idle timeout.
+//!
+//! Every exec holds an [`ActivityGuard`] until its result is collected, so a
+//! box is only considered idle once no command is in flight and the last one
+//! finished at least `idle_timeout` ago.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Tracks in-flight executions and the time of the last activity.
+pub(crate) struct IdleTracker {
+    last_activity: Mutex<Instant>,
+    active: AtomicUsize,
+}
+
+impl IdleTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record activity without holding the box busy.
+    pub(crate) fn touch(&self) {
+        *self.last_activity.lock() = Instant::now();
+    }
+
+    /// Mark the start of an activity that lasts until the guard is dropped.
+    pub(crate) fn activity(self: &Arc<Self>) -> ActivityGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        self.touch();
+        ActivityGuard {
+            tracker: Arc::clone(self),
+        }
+    }
+
+    /// How long the box has been idle, or `None` while an activity is in flight.
+    pub(crate) fn idle_for(&self) -> Option<Duration> {
+        if self.active.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        Some(self.last_activity.lock().elapsed())
+    }
+}
+
+/// Keeps a box busy while alive; records activity when dropped.
+pub(crate) struct ActivityGuard {
+    tracker: Arc<IdleTracker>,
+}
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        self.tracker.touch();
+        self.tracker.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_idle_while_activity_in_flight() {
+        let tracker = Arc::new(IdleTracker::new());
+        let guard = tracker.activity();
+        assert!(tracker.idle_for().is_none());
+
+        drop(guard);
+        assert!(tracker.idle_for().is_some());
+    }
+
+    #[test]
+    fn test_activity_resets_idle_time() {
+        let tracker = Arc::new(IdleTracker::new());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(tracker.idle_for().unwrap() >= Duration::from_millis(20));
+
+        tracker.touch();
+        assert!(tracker.idle_for().unwrap() < Duration::from_millis(20));
+    }
+}
@@ -24,6 +24,10 @@
 //! Running (reattach):
 //!   1. VmmAttach            (attach to running VM)
 //!   2. GuestConnect         (reconnect to guest)
+//!
+//! Replay (`rpc_replay` set, any status):
+//!   1. Filesystem           (create or load layout)
+//!   2. GuestReplay          (answer RPCs from the recording, no VM)
 //! ```
 //!
 //! `CleanupGuard` provides RAII cleanup on failure.
@@ -33,21 +37,35 @@ mod types;
 
 pub(crate) use crate::litebox::box_impl::LiveState;
 
+use crate::events::EventKind;
 use crate::litebox::BoxStatus;
+use crate::litebox::artifacts;
 use crate::litebox::config::BoxConfig;
+use crate::litebox::crash::{CrashStore, CrashWatcher};
+use crate::litebox::memory::MemoryWatcher;
+use crate::litebox::ports;
+use crate::litebox::pressure::PressureWatcher;
+use crate::litebox::readahead::ReadaheadRecorder;
 use crate::metrics::BoxMetricsStorage;
+use crate::net::dns::box_names;
+use crate::net::ingress::{Ingress, IngressRoute};
 use crate::pipeline::{
     BoxedTask, ExecutionPlan, PipelineBuilder, PipelineExecutor, PipelineMetrics, Stage,
 };
+use crate::portal::bulk::BulkChannel;
+use crate::runtime::quota::ResourceUsage;
 use crate::runtime::rt_impl::SharedRuntimeImpl;
 use crate::runtime::types::BoxState;
+#[cfg(target_os = "linux")]
+use crate::volumes::QuotaEnforcer;
+use crate::volumes::{VolumeWatcher, WatchedVolume};
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use tasks::{
-    ContainerRootfsTask, FilesystemTask, GuestConnectTask, GuestInitTask, GuestRootfsTask, InitCtx,
-    VmmAttachTask, VmmSpawnTask,
+    ContainerRootfsTask, FilesystemTask, GuestConnectTask, GuestInitTask, GuestReplayTask,
+    GuestRootfsTask, InitCtx, VmmAttachTask, VmmSpawnTask,
 };
 use types::InitPipelineContext;
 
@@ -55,8 +73,14 @@ use types::InitPipelineContext;
 // EXECUTION PLAN
 // ============================================================================
 
-/// Get execution plan based on BoxStatus.
-fn get_execution_plan(status: BoxStatus) -> ExecutionPlan<InitCtx> {
+/// Get execution plan based on BoxStatus, or the replay plan.
+fn get_execution_plan(status: BoxStatus, replay: bool) -> ExecutionPlan<InitCtx> {
+    if replay {
+        return ExecutionPlan::new(vec![
+            Stage::sequential(vec![Box::new(FilesystemTask)]),
+            Stage::sequential(vec![Box::new(GuestReplayTask)]),
+        ]);
+    }
     let stages: Vec<Stage<BoxedTask<InitCtx>>> = match status {
         BoxStatus::Starting => vec![
             // Phase 1: Setup filesystem layout first
@@ -184,20 +208,44 @@ impl BoxBuilder {
         } = self;
 
         let status = state.status;
+        let replay = config.options.rpc_replay.is_some();
         let reuse_rootfs = status == BoxStatus::Stopped;
         let skip_guest_wait = status == BoxStatus::Running;
 
+        // A reattached VM is already running, so it neither waits in its
+        // queue nor is admitted, only tracked; a replay has no VM to admit
+        let queue_slot = match &config.options.queue {
+            Some(queue) if status == BoxStatus::Running => runtime.queues.try_enter(queue),
+            Some(queue) => Some(runtime.queues.enter(queue, &config.id).await),
+            None => None,
+        };
+        let requested = ResourceUsage::of(&config.options);
+        let reservation = if status == BoxStatus::Running || replay {
+            runtime.planner.track(&config.id, requested)
+        } else {
+            runtime.planner.admit(&config.id, requested).await?
+        };
+
         let ctx = InitPipelineContext::new(config, runtime.clone(), reuse_rootfs, skip_guest_wait);
         let ctx = Arc::new(Mutex::new(ctx));
 
-        if status != BoxStatus::Starting {
-            let mut ctx_guard = ctx.lock().await;
-            ctx_guard.guard.disarm();
+        match status {
+            BoxStatus::Starting => {}
+            BoxStatus::Stopped if !replay => ctx.lock().await.guard.keep_box(),
+            _ => ctx.lock().await.guard.disarm(),
         }
 
-        let plan = get_execution_plan(status);
+        let plan = get_execution_plan(status, replay);
         let pipeline = PipelineBuilder::from_plan(plan);
-        let pipeline_metrics = PipelineExecutor::execute(pipeline, Arc::clone(&ctx)).await?;
+        let pipeline_metrics = match PipelineExecutor::execute(pipeline, Arc::clone(&ctx)).await {
+            Ok(pipeline_metrics) => pipeline_metrics,
+            Err(e) => {
+                // Before the cleanup guard removes a new box's directory
+                let config = ctx.lock().await.config.clone();
+                runtime.save_diagnostics(&config, &state, &e.to_string());
+                return Err(e);
+            }
+        };
 
         let mut ctx = ctx.lock().await;
         let total_create_duration_ms = total_start.elapsed().as_millis();
@@ -208,6 +256,7 @@ impl BoxBuilder {
 
         let mut metrics = box_metrics_from_pipeline(&pipeline_metrics);
         metrics.set_total_create_duration(total_create_duration_ms);
+        metrics.set_guest_stages(std::mem::take(&mut ctx.guest_stages));
 
         metrics.log_init_stages();
 
@@ -220,8 +269,8 @@ impl BoxBuilder {
             .ok_or_else(|| BoxliteError::Internal("guest_connect task must run first".into()))?;
 
         // Get disks from context (for Running, create disk reference directly)
-        let (container_disk, guest_disk) = if status == BoxStatus::Running {
-            // Reattach: create disk reference to existing qcow2
+        let (container_disk, guest_disk) = if status == BoxStatus::Running || replay {
+            // Reattach or replay: create disk reference to existing qcow2
             use crate::disk::DiskFormat;
             let disk = crate::disk::Disk::new(
                 ctx.config.box_home.join("root.qcow2"),
@@ -238,8 +287,137 @@ impl BoxBuilder {
             (container_disk, ctx.guest_disk.take())
         };
 
+        let package_cache = ctx.package_cache.take();
+        let credentials = ctx.credentials.take();
+        let volume_watcher = if ctx.config.options.watch_volumes {
+            let volumes = types::resolve_user_volumes(&ctx.config.options.volumes)?
+                .into_iter()
+                .map(|vol| WatchedVolume {
+                    name: vol.tag,
+                    host_path: vol.host_path,
+                })
+                .collect();
+            Some(VolumeWatcher::start(
+                guest_session.clone(),
+                ctx.config.container.id.as_str().to_string(),
+                volumes,
+            ))
+        } else {
+            None
+        };
+        #[cfg(target_os = "linux")]
+        let quota_mounts = std::mem::take(&mut ctx.quota_mounts);
+        #[cfg(target_os = "linux")]
+        let quota_enforcer = if !quota_mounts.is_empty() {
+            let runtime = ctx.runtime.clone();
+            let config = ctx.config.clone();
+            Some(QuotaEnforcer::start(quota_mounts, move |volume, usage| {
+                runtime.events.emit(
+                    EventKind::QuotaExceeded,
+                    &config,
+                    [
+                        ("path", volume.guest_path.clone()),
+                        ("usage_bytes", usage.to_string()),
+                        ("quota_bytes", volume.quota_bytes.to_string()),
+                    ],
+                )
+            }))
+        } else {
+            None
+        };
+        // Refused by BoxOptions::sanitize elsewhere
+        #[cfg(not(target_os = "linux"))]
+        let quota_enforcer = None;
+        let lock_broker = ctx.lock_broker.take();
+        let mdns = match (&ctx.config.name, ctx.config.options.mdns) {
+            (Some(name), true) => Some(ctx.runtime.mdns.register(name)),
+            (None, true) => {
+                tracing::warn!(box_id = %ctx.config.id, "mdns needs a box name, not advertising");
+                None
+            }
+            _ => None,
+        };
+        let ingress = match (&ctx.runtime.ingress, ctx.config.options.ingress_port) {
+            (Some(ingress), Some(guest_port)) => ingress_route(&ctx.config, ingress, guest_port),
+            _ => None,
+        };
+        let dns = ctx.runtime.dns.as_ref().and_then(|dns| {
+            let options = &ctx.config.options;
+            let names = box_names(
+                ctx.config.name.as_deref(),
+                options.dns_network.as_deref(),
+                &options.dns_services,
+            );
+            // Only the ports of boxes answered by name are reachable by name
+            let host_ports = if names.is_empty() {
+                Vec::new()
+            } else {
+                ports::read(&ctx.config.box_home.join("ports.json"))
+                    .iter()
+                    .map(|port| port.host_port)
+                    .collect()
+            };
+            // The shim only routes the host alias for boxes with a gvproxy
+            // network
+            let link = Some(ctx.config.box_home.join("sockets").join("host-ports.sock"))
+                .filter(|socket| socket.exists());
+            (!names.is_empty() || link.is_some()).then(|| dns.register(names, host_ports, link))
+        });
+        // Only a box whose base disk has no hints yet records them
+        let readahead = ctx
+            .readahead
+            .take()
+            .filter(|path| !path.exists())
+            .map(|path| {
+                ReadaheadRecorder::start(
+                    guest_session.clone(),
+                    ctx.config.container.id.as_str().to_string(),
+                    path,
+                )
+            });
+        let memory_watcher = {
+            let runtime = ctx.runtime.clone();
+            let config = ctx.config.clone();
+            MemoryWatcher::start(
+                guest_session.clone(),
+                ctx.config.options.low_memory_percent,
+                move |kind, attributes| runtime.events.emit(kind, &config, attributes),
+            )
+        };
+        let pressure_watcher = (ctx.config.options.pressure_percent > 0).then(|| {
+            let runtime = ctx.runtime.clone();
+            let config = ctx.config.clone();
+            PressureWatcher::start(
+                guest_session.clone(),
+                ctx.config.options.pressure_percent,
+                move |change| {
+                    let (kind, attributes) = change.describe();
+                    runtime.events.emit(kind, &config, attributes);
+                    runtime.pressure.update(&runtime, &config, &change);
+                },
+            )
+        });
+        let crash_watcher = (ctx.config.options.core_dump_max_bytes > 0).then(|| {
+            let runtime = ctx.runtime.clone();
+            let config = ctx.config.clone();
+            let store = CrashStore {
+                bulk: BulkChannel::new(ctx.config.box_home.join("sockets").join("bulk.sock")),
+                artifacts_dir: ctx.config.box_home.join(artifacts::ARTIFACTS_DIR),
+                retention: ctx.config.options.artifact_retention.clone(),
+            };
+            CrashWatcher::start(
+                guest_session.clone(),
+                ctx.config.options.core_dump_max_bytes,
+                store,
+                move |kind, attributes| runtime.events.emit(kind, &config, attributes),
+            )
+        });
         #[cfg(target_os = "linux")]
         let bind_mount = ctx.bind_mount.take();
+        #[cfg(target_os = "linux")]
+        let object_mounts = std::mem::take(&mut ctx.object_mounts);
+        #[cfg(target_os = "linux")]
+        let encrypted_volumes = std::mem::take(&mut ctx.encrypted_volumes);
 
         // Build LiveState
         Ok(LiveState::new(
@@ -248,8 +426,46 @@ impl BoxBuilder {
             metrics,
             container_disk,
             guest_disk,
+            package_cache,
+            credentials,
+            volume_watcher,
+            quota_enforcer,
+            lock_broker,
+            mdns,
+            ingress,
+            dns,
+            readahead,
+            memory_watcher,
+            pressure_watcher,
+            crash_watcher,
+            reservation,
+            queue_slot,
             #[cfg(target_os = "linux")]
             bind_mount,
+            #[cfg(target_os = "linux")]
+            object_mounts,
+            #[cfg(target_os = "linux")]
+            encrypted_volumes,
         ))
     }
 }
+
+/// Route the box's `ingress_port` through the runtime's ingress proxy.
+fn ingress_route(config: &BoxConfig, ingress: &Ingress, guest_port: u16) -> Option<IngressRoute> {
+    let Some(name) = &config.name else {
+        tracing::warn!(box_id = %config.id, "ingress needs a box name, not routing");
+        return None;
+    };
+    let forwarded = ports::read(&config.box_home.join("ports.json"));
+    match forwarded.iter().find(|port| port.guest_port == guest_port) {
+        Some(port) => Some(ingress.route(name, port.host_port)),
+        None => {
+            tracing::warn!(
+                box_id = %config.id,
+                guest_port,
+                "ingress_port is not forwarded to the host, not routing"
+            );
+            None
+        }
+    }
+}
@@ -5,15 +5,28 @@
 //! - Overlayfs: Extracts layers for guest-side overlayfs (flexible)
 //!
 //! For restart (reuse_rootfs=true), opens existing COW disk instead of creating new.
+//!
+//! Boxes with runtimes start from the derived disk provisioned for them, if
+//! one is cached; otherwise they are marked to provision on first boot.
+//! Boxes whose image is a baked tag start from the disk baked for it.
+//!
+//! Disk-based boxes also learn where their base disk's readahead hints live.
+//!
+//! Boxless boxes cannot attach disks: their image is merged into the shared
+//! container directory on the host instead.
 
 use super::{InitCtx, log_task_error, task_start};
 use crate::disk::{BackingFormat, Disk, DiskFormat, Qcow2Helper, create_ext4_from_dir};
 use crate::images::ContainerImageConfig;
 use crate::litebox::init::types::{ContainerRootfsPrepResult, USE_DISK_ROOTFS, USE_OVERLAYFS};
+use crate::litebox::{provision, readahead};
 use crate::pipeline::PipelineTask;
+use crate::rootfs::RootfsBuilder;
+use crate::runtime::bake::BakedImage;
 use crate::runtime::layout::BoxFilesystemLayout;
-use crate::runtime::options::RootfsSpec;
+use crate::runtime::options::{RootfsSpec, RuntimeProfile};
 use crate::runtime::rt_impl::SharedRuntimeImpl;
+use crate::runtime::types::ContainerID;
 use async_trait::async_trait;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 
@@ -25,7 +38,7 @@ impl PipelineTask<InitCtx> for ContainerRootfsTask {
         let task_name = self.name();
         let box_id = task_start(&ctx, task_name).await;
 
-        let (rootfs_spec, env, runtime, layout, reuse_rootfs, disk_size_gb) = {
+        let (rootfs_spec, env, runtime, layout, reuse_rootfs, disk_size_gb, runtimes, engine_kind) = {
             let ctx = ctx.lock().await;
             let layout = ctx
                 .layout
@@ -33,21 +46,36 @@ impl PipelineTask<InitCtx> for ContainerRootfsTask {
                 .ok_or_else(|| BoxliteError::Internal("filesystem task must run first".into()))?;
             (
                 ctx.config.options.rootfs.clone(),
-                ctx.config.options.env.clone(),
+                ctx.config.options.resolved_env()?,
                 ctx.runtime.clone(),
                 layout,
                 ctx.reuse_rootfs,
                 ctx.config.options.disk_size_gb,
+                ctx.config.options.runtimes.clone(),
+                ctx.config.engine_kind,
             )
         };
 
-        let (container_image_config, disk) = run_container_rootfs(
+        if !engine_kind.is_vm() {
+            let container_id = ctx.lock().await.config.container.id.clone();
+            let (container_image_config, disk) =
+                run_merged_rootfs(&rootfs_spec, &env, &runtime, &layout, &container_id)
+                    .await
+                    .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+            let mut ctx = ctx.lock().await;
+            ctx.container_image_config = Some(container_image_config);
+            ctx.container_disk = Some(disk);
+            return Ok(());
+        }
+
+        let (container_image_config, disk, base_digest) = run_container_rootfs(
             &rootfs_spec,
             &env,
             &runtime,
             &layout,
             reuse_rootfs,
             disk_size_gb,
+            &runtimes,
         )
         .await
         .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
@@ -55,6 +83,12 @@ impl PipelineTask<InitCtx> for ContainerRootfsTask {
         let mut ctx = ctx.lock().await;
         ctx.container_image_config = Some(container_image_config);
         ctx.container_disk = Some(disk);
+        if USE_DISK_ROOTFS && ctx.config.options.readahead {
+            ctx.readahead = Some(readahead::hints_path(
+                &runtime.layout.readahead_dir(),
+                &base_digest,
+            ));
+        }
 
         Ok(())
     }
@@ -65,6 +99,8 @@ impl PipelineTask<InitCtx> for ContainerRootfsTask {
 }
 
 /// Pull image and prepare rootfs, then create or reuse COW disk.
+///
+/// Also returns the digest naming the base disk the box boots from.
 async fn run_container_rootfs(
     rootfs_spec: &RootfsSpec,
     env: &[(String, String)],
@@ -72,7 +108,8 @@ async fn run_container_rootfs(
     layout: &BoxFilesystemLayout,
     reuse_rootfs: bool,
     disk_size_gb: Option<u64>,
-) -> BoxliteResult<(ContainerImageConfig, Disk)> {
+    runtimes: &[RuntimeProfile],
+) -> BoxliteResult<(ContainerImageConfig, Disk, String)> {
     let disk_path = layout.disk_path();
 
     // For restart, reuse existing COW disk
@@ -99,14 +136,17 @@ async fn run_container_rootfs(
                 ));
             }
         };
-        let image = pull_image(runtime, image_ref).await?;
+        let (base_ref, baked) = resolve_baked(runtime, image_ref, runtimes)?;
+        let image = pull_image(runtime, &base_ref).await?;
         let image_config = image.load_config().await?;
-        let mut container_image_config = ContainerImageConfig::from_oci_config(&image_config)?;
-        if !env.is_empty() {
-            container_image_config.merge_env(env.to_vec());
-        }
+        let container_image_config =
+            container_config(&image_config, env, runtimes, baked.as_ref())?;
 
-        return Ok((container_image_config, disk));
+        return Ok((
+            container_image_config,
+            disk,
+            base_digest(&image, runtimes, baked.as_ref()),
+        ));
     }
 
     // Fresh start: pull image and prepare rootfs
@@ -119,7 +159,70 @@ async fn run_container_rootfs(
         }
     };
 
-    let image = pull_image(runtime, image_ref).await?;
+    let (base_ref, baked) = resolve_baked(runtime, image_ref, runtimes)?;
+    let image = pull_image(runtime, &base_ref).await?;
+    let image_config = image.load_config().await?;
+    let container_image_config = container_config(&image_config, env, runtimes, baked.as_ref())?;
+
+    if let Some(baked) = &baked {
+        let variant = baked.variant();
+        let derived = image.derived_disk_image(&variant).await.ok_or_else(|| {
+            BoxliteError::NotFound(format!(
+                "Disk of baked image {} is missing; bake it again",
+                baked.tag
+            ))
+        })?;
+        let base_disk_path = derived.path().to_path_buf();
+        let _ = derived.leak();
+        let base_disk_size = Qcow2Helper::qcow2_virtual_size(&base_disk_path)?;
+        tracing::info!(
+            baked_disk = %base_disk_path.display(),
+            tag = %baked.tag,
+            "Using baked disk image"
+        );
+        let disk = create_cow_overlay(
+            &base_disk_path,
+            BackingFormat::Qcow2,
+            base_disk_size,
+            layout,
+            disk_size_gb,
+        )?;
+        return Ok((
+            container_image_config,
+            disk,
+            base_digest(&image, runtimes, Some(baked)),
+        ));
+    }
+
+    if USE_DISK_ROOTFS && !runtimes.is_empty() {
+        let variant = provision::variant(runtimes);
+        if let Some(derived) = image.derived_disk_image(&variant).await {
+            let base_disk_path = derived.path().to_path_buf();
+            let _ = derived.leak();
+            let base_disk_size = Qcow2Helper::qcow2_virtual_size(&base_disk_path)?;
+            tracing::info!(
+                derived_disk = %base_disk_path.display(),
+                variant = %variant,
+                "Using provisioned disk image"
+            );
+            let disk = create_cow_overlay(
+                &base_disk_path,
+                BackingFormat::Qcow2,
+                base_disk_size,
+                layout,
+                disk_size_gb,
+            )?;
+            return Ok((
+                container_image_config,
+                disk,
+                base_digest(&image, runtimes, None),
+            ));
+        }
+        provision::mark(
+            layout.root(),
+            &image.derived_disk_image_path(&variant).await,
+        )?;
+    }
 
     let rootfs_result = if USE_DISK_ROOTFS {
         prepare_disk_rootfs(runtime, &image).await?
@@ -133,14 +236,109 @@ async fn run_container_rootfs(
 
     let disk = create_cow_disk(&rootfs_result, layout, disk_size_gb)?;
 
+    Ok((
+        container_image_config,
+        disk,
+        base_digest(&image, runtimes, None),
+    ))
+}
+
+/// Pull the image and merge it into the shared container rootfs directory,
+/// for boxes that cannot attach a disk.
+///
+/// The returned disk is a placeholder at the box's disk path; nothing is
+/// written there.
+async fn run_merged_rootfs(
+    rootfs_spec: &RootfsSpec,
+    env: &[(String, String)],
+    runtime: &SharedRuntimeImpl,
+    layout: &BoxFilesystemLayout,
+    container_id: &ContainerID,
+) -> BoxliteResult<(ContainerImageConfig, Disk)> {
+    let image_ref = match rootfs_spec {
+        RootfsSpec::Image(r) => r,
+        RootfsSpec::RootfsPath(_) => {
+            return Err(BoxliteError::Storage(
+                "Direct rootfs paths not yet supported".into(),
+            ));
+        }
+    };
+    if runtime.baked_images.get(image_ref)?.is_some() {
+        return Err(BoxliteError::Unsupported(format!(
+            "Boxless boxes cannot start from baked image {}",
+            image_ref
+        )));
+    }
+    let image = pull_image(runtime, image_ref).await?;
     let image_config = image.load_config().await?;
-    let mut container_image_config = ContainerImageConfig::from_oci_config(&image_config)?;
+    let container_image_config = container_config(&image_config, env, &[], None)?;
+
+    // Reuses the directory if a previous start already merged it
+    let rootfs_dir = layout
+        .shared_layout()
+        .container(container_id.as_str())
+        .rootfs_dir();
+    RootfsBuilder::new().prepare(rootfs_dir, &image).await?;
+
+    let disk = Disk::new(layout.disk_path(), DiskFormat::Qcow2, true);
+    Ok((container_image_config, disk))
+}
+
+/// The image `image_ref` starts from, and its bake if it is a baked tag.
+fn resolve_baked(
+    runtime: &SharedRuntimeImpl,
+    image_ref: &str,
+    runtimes: &[RuntimeProfile],
+) -> BoxliteResult<(String, Option<BakedImage>)> {
+    match runtime.baked_images.get(image_ref)? {
+        Some(_) if !runtimes.is_empty() => Err(BoxliteError::InvalidArgument(format!(
+            "runtimes cannot be provisioned on baked image {}; bake them in",
+            image_ref
+        ))),
+        Some(baked) => Ok((baked.base_image.clone(), Some(baked))),
+        None => Ok((image_ref.to_string(), None)),
+    }
+}
 
+/// The digest naming the disk the box boots from: the image's own, the
+/// baked one, or the one provisioned for its runtimes (which the first
+/// boot provisions).
+fn base_digest(
+    image: &crate::images::ImageObject,
+    runtimes: &[RuntimeProfile],
+    baked: Option<&BakedImage>,
+) -> String {
+    if let Some(baked) = baked {
+        image.derived_digest(&baked.variant())
+    } else if runtimes.is_empty() {
+        image.compute_image_digest()
+    } else {
+        image.derived_digest(&provision::variant(runtimes))
+    }
+}
+
+/// Container config from the image, with the baked env, the runtimes' env
+/// and then the box's own on top.
+fn container_config(
+    image_config: &oci_spec::image::ImageConfiguration,
+    env: &[(String, String)],
+    runtimes: &[RuntimeProfile],
+    baked: Option<&BakedImage>,
+) -> BoxliteResult<ContainerImageConfig> {
+    let mut container_image_config = ContainerImageConfig::from_oci_config(image_config)?;
+    if let Some(baked) = baked
+        && !baked.env.is_empty()
+    {
+        container_image_config.merge_env(baked.env.clone());
+    }
+    let runtime_env = provision::env(&container_image_config, runtimes);
+    if !runtime_env.is_empty() {
+        container_image_config.merge_env(runtime_env);
+    }
     if !env.is_empty() {
         container_image_config.merge_env(env.to_vec());
     }
-
-    Ok((container_image_config, disk))
+    Ok(container_image_config)
 }
 
 /// Create COW disk from base rootfs.
@@ -159,39 +357,13 @@ fn create_cow_disk(
         ContainerRootfsPrepResult::DiskImage {
             base_disk_path,
             disk_size: base_disk_size,
-        } => {
-            // Calculate target disk size: use max of user-specified size and base disk size
-            let target_disk_size = if let Some(size_gb) = disk_size_gb {
-                let user_size_bytes = size_gb * 1024 * 1024 * 1024;
-                std::cmp::max(user_size_bytes, *base_disk_size)
-            } else {
-                *base_disk_size
-            };
-
-            let qcow2_helper = Qcow2Helper::new();
-            let cow_disk_path = layout.disk_path();
-            let temp_disk = qcow2_helper.create_cow_child_disk(
-                base_disk_path,
-                BackingFormat::Raw,
-                &cow_disk_path,
-                target_disk_size,
-            )?;
-
-            // Make disk persistent so it survives stop/restart
-            // create_cow_child_disk returns non-persistent disk, but we want to preserve
-            // COW disks across box restarts (only delete on remove)
-            let disk_path = temp_disk.leak(); // Prevent cleanup
-            let disk = Disk::new(disk_path, DiskFormat::Qcow2, true); // persistent=true
-
-            tracing::info!(
-                cow_disk = %cow_disk_path.display(),
-                base_disk = %base_disk_path.display(),
-                virtual_size_mb = target_disk_size / (1024 * 1024),
-                "Created container rootfs COW overlay (persistent)"
-            );
-
-            Ok(disk)
-        }
+        } => create_cow_overlay(
+            base_disk_path,
+            BackingFormat::Raw,
+            *base_disk_size,
+            layout,
+            disk_size_gb,
+        ),
         ContainerRootfsPrepResult::Layers { .. } => Err(BoxliteError::Internal(
             "Layers mode requires overlayfs - disk creation not applicable".into(),
         )),
@@ -201,6 +373,47 @@ fn create_cow_disk(
     }
 }
 
+/// Create the box's persistent COW overlay on `base_disk_path`.
+fn create_cow_overlay(
+    base_disk_path: &std::path::Path,
+    backing_format: BackingFormat,
+    base_disk_size: u64,
+    layout: &BoxFilesystemLayout,
+    disk_size_gb: Option<u64>,
+) -> BoxliteResult<Disk> {
+    // Calculate target disk size: use max of user-specified size and base disk size
+    let target_disk_size = if let Some(size_gb) = disk_size_gb {
+        let user_size_bytes = size_gb * 1024 * 1024 * 1024;
+        std::cmp::max(user_size_bytes, base_disk_size)
+    } else {
+        base_disk_size
+    };
+
+    let qcow2_helper = Qcow2Helper::new();
+    let cow_disk_path = layout.disk_path();
+    let temp_disk = qcow2_helper.create_cow_child_disk(
+        base_disk_path,
+        backing_format,
+        &cow_disk_path,
+        target_disk_size,
+    )?;
+
+    // Make disk persistent so it survives stop/restart
+    // create_cow_child_disk returns non-persistent disk, but we want to preserve
+    // COW disks across box restarts (only delete on remove)
+    let disk_path = temp_disk.leak(); // Prevent cleanup
+    let disk = Disk::new(disk_path, DiskFormat::Qcow2, true); // persistent=true
+
+    tracing::info!(
+        cow_disk = %cow_disk_path.display(),
+        base_disk = %base_disk_path.display(),
+        virtual_size_mb = target_disk_size / (1024 * 1024),
+        "Created container rootfs COW overlay (persistent)"
+    );
+
+    Ok(disk)
+}
+
 async fn pull_image(
     runtime: &crate::runtime::SharedRuntimeImpl,
     image_ref: &str,
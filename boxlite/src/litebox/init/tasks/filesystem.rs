@@ -1,12 +1,22 @@
 //! Task: Filesystem setup.
 //!
-//! Creates box directory structure and optionally sets up the mounts/ → shared/ binding.
+//! Creates box directory structure, optionally sets up the mounts/ → shared/
+//! binding, and mounts the box's object, encrypted and quota volumes.
 
 use super::{InitCtx, log_task_error, task_start};
 use crate::pipeline::PipelineTask;
 use async_trait::async_trait;
 use boxlite_shared::errors::BoxliteResult;
 
+#[cfg(target_os = "linux")]
+use crate::litebox::init::types::resolve_user_volumes;
+#[cfg(target_os = "linux")]
+use crate::runtime::layout::BoxFilesystemLayout;
+#[cfg(target_os = "linux")]
+use crate::runtime::options::VolumeSpec;
+#[cfg(target_os = "linux")]
+use crate::volumes::{QuotaMount, QuotaVolume};
+
 pub struct FilesystemTask;
 
 #[async_trait]
@@ -15,9 +25,15 @@ impl PipelineTask<InitCtx> for FilesystemTask {
         let task_name = self.name();
         let box_id = task_start(&ctx, task_name).await;
 
-        let (runtime, isolate_mounts) = {
+        let (runtime, isolate_mounts, volumes, object_volumes, encrypted_volumes) = {
             let ctx = ctx.lock().await;
-            (ctx.runtime.clone(), ctx.config.options.isolate_mounts)
+            (
+                ctx.runtime.clone(),
+                ctx.config.options.isolate_mounts,
+                ctx.config.options.volumes.clone(),
+                ctx.config.options.object_volumes.clone(),
+                ctx.config.options.encrypted_volumes.clone(),
+            )
         };
 
         let layout = runtime
@@ -41,12 +57,42 @@ impl PipelineTask<InitCtx> for FilesystemTask {
             None
         };
 
+        #[cfg(target_os = "linux")]
+        let object_mounts = object_volumes
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| crate::volumes::ObjectMount::start(spec, &layout.object_volume_dir(i)))
+            .collect::<BoxliteResult<Vec<_>>>()
+            .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        #[cfg(target_os = "linux")]
+        let encrypted_volumes = encrypted_volumes
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                crate::volumes::EncryptedVolume::attach(
+                    spec,
+                    &runtime.layout.volumes_dir().join(&spec.name),
+                    &layout.encrypted_volume_dir(i),
+                )
+            })
+            .collect::<BoxliteResult<Vec<_>>>()
+            .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        #[cfg(target_os = "linux")]
+        let quota_mounts = start_quota_mounts(&volumes, &layout)
+            .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        // Refused by BoxOptions::sanitize elsewhere
+        #[cfg(not(target_os = "linux"))]
+        let _ = (volumes, object_volumes, encrypted_volumes);
+
         let mut ctx = ctx.lock().await;
         ctx.guard.set_layout(layout.clone());
         ctx.layout = Some(layout);
         #[cfg(target_os = "linux")]
         {
             ctx.bind_mount = bind_mount;
+            ctx.object_mounts = object_mounts;
+            ctx.encrypted_volumes = encrypted_volumes;
+            ctx.quota_mounts = quota_mounts;
         }
 
         Ok(())
@@ -56,3 +102,27 @@ impl PipelineTask<InitCtx> for FilesystemTask {
         "filesystem_setup"
     }
 }
+
+/// Mount each user volume with a quota where the VMM will share it from.
+#[cfg(target_os = "linux")]
+fn start_quota_mounts(
+    volumes: &[VolumeSpec],
+    layout: &BoxFilesystemLayout,
+) -> BoxliteResult<Vec<QuotaMount>> {
+    if volumes.iter().all(|volume| volume.quota_bytes.is_none()) {
+        return Ok(Vec::new());
+    }
+    resolve_user_volumes(volumes)?
+        .into_iter()
+        .zip(volumes)
+        .enumerate()
+        .filter_map(|(index, (vol, spec))| {
+            let volume = QuotaVolume {
+                host_path: vol.host_path,
+                guest_path: vol.guest_path,
+                quota_bytes: spec.quota_bytes?,
+            };
+            Some(QuotaMount::start(volume, &layout.quota_volume_dir(index)))
+        })
+        .collect()
+}
@@ -8,9 +8,12 @@
 use super::{InitCtx, log_task_error, task_start};
 use crate::pipeline::PipelineTask;
 use crate::portal::GuestSession;
+use crate::portal::connection::RpcMode;
+use crate::portal::replay::RpcRecorder;
 use async_trait::async_trait;
 use boxlite_shared::Transport;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct GuestConnectTask;
@@ -21,12 +24,14 @@ impl PipelineTask<InitCtx> for GuestConnectTask {
         let task_name = self.name();
         let box_id = task_start(&ctx, task_name).await;
 
-        let (transport, ready_transport, skip_guest_wait) = {
+        let (transport, ready_transport, skip_guest_wait, rpc_recording, exec_policy) = {
             let ctx = ctx.lock().await;
             (
                 ctx.config.transport.clone(),
                 Transport::unix(ctx.config.ready_socket_path.clone()),
                 ctx.skip_guest_wait,
+                ctx.config.options.rpc_recording.clone(),
+                ctx.runtime.exec_policy.clone(),
             )
         };
 
@@ -42,7 +47,15 @@ impl PipelineTask<InitCtx> for GuestConnectTask {
         }
 
         tracing::debug!(box_id = %box_id, "Guest is ready, creating session");
-        let guest_session = GuestSession::new(transport);
+        let guest_session = match rpc_recording {
+            Some(path) => {
+                let recorder = RpcRecorder::open(&path)
+                    .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+                GuestSession::with_mode(transport, RpcMode::Record(Arc::new(recorder)))
+            }
+            None => GuestSession::new(transport),
+        }
+        .with_exec_policy(exec_policy);
 
         let mut ctx = ctx.lock().await;
         ctx.guest_session = Some(guest_session);
@@ -5,11 +5,19 @@
 
 use super::{InitCtx, log_task_error, task_start};
 use crate::images::ContainerImageConfig;
+use crate::litebox::resume::{self, ResumeReason};
+use crate::metrics::GuestStageTiming;
+use crate::net::constants::BOX_DNS_ZONE;
 use crate::pipeline::PipelineTask;
 use crate::portal::GuestSession;
-use crate::portal::interfaces::{ContainerRootfsInitConfig, GuestInitConfig, NetworkInitConfig};
+use crate::portal::credentials::CredentialForwarding;
+use crate::portal::interfaces::{
+    ContainerRootfsInitConfig, GuestInitConfig, GuestInterface, NetworkInitConfig,
+    X86EmulationConfig,
+};
+use crate::runtime::options::{BoxOptions, GpuSpec, InitMode};
 use crate::runtime::types::ContainerID;
-use crate::volumes::{ContainerMount, GuestVolumeManager};
+use crate::volumes::{ContainerMount, GuestVolumeManager, PACKAGE_CACHE_PATH};
 use async_trait::async_trait;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 
@@ -28,6 +36,13 @@ impl PipelineTask<InitCtx> for GuestInitTask {
             volume_mgr,
             rootfs_init,
             container_mounts,
+            options,
+            package_cache,
+            credentials,
+            x11_forwarded,
+            x86_emulation,
+            box_dns,
+            cloned,
         ) =
             {
                 let mut ctx = ctx.lock().await;
@@ -48,6 +63,12 @@ impl PipelineTask<InitCtx> for GuestInitTask {
                 let container_mounts = ctx.container_mounts.take().ok_or_else(|| {
                     BoxliteError::Internal("vmm_spawn task must run first".into())
                 })?;
+                let mut options = ctx.config.options.clone();
+                // Boxless boxes share the host network: there is no eth0
+                if !ctx.config.engine_kind.is_vm() {
+                    options.devices.network = false;
+                }
+                let box_dns = ctx.runtime.dns.is_some() && options.devices.network;
                 (
                     guest_session,
                     container_image_config,
@@ -55,16 +76,35 @@ impl PipelineTask<InitCtx> for GuestInitTask {
                     volume_mgr,
                     rootfs_init,
                     container_mounts,
+                    options,
+                    ctx.package_cache.is_some(),
+                    ctx.credentials.take(),
+                    ctx.x11_forwarded,
+                    ctx.x86_emulator
+                        .as_ref()
+                        .map(|emulator| X86EmulationConfig {
+                            interpreter: emulator.guest_interpreter(),
+                            flags: emulator.flags.to_string(),
+                        }),
+                    box_dns,
+                    resume::pending(&ctx.config.box_home) == Some(ResumeReason::Clone),
                 )
             };
 
-        run_guest_init(
+        let guest_stages = run_guest_init(
             guest_session.clone(),
             &container_image_config,
             &container_id,
             &volume_mgr,
             &rootfs_init,
             &container_mounts,
+            &options,
+            package_cache,
+            credentials.as_ref(),
+            x11_forwarded,
+            x86_emulation,
+            box_dns,
+            cloned,
         )
         .await
         .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
@@ -74,6 +114,8 @@ impl PipelineTask<InitCtx> for GuestInitTask {
         ctx.volume_mgr = Some(volume_mgr);
         ctx.rootfs_init = Some(rootfs_init);
         ctx.container_mounts = Some(container_mounts);
+        ctx.guest_stages = guest_stages;
+        ctx.credentials = credentials;
 
         Ok(())
     }
@@ -84,6 +126,9 @@ impl PipelineTask<InitCtx> for GuestInitTask {
 }
 
 /// Initialize guest and start container.
+///
+/// Returns the boot stage timings reported by the guest.
+#[allow(clippy::too_many_arguments)]
 async fn run_guest_init(
     guest_session: GuestSession,
     container_image_config: &ContainerImageConfig,
@@ -91,7 +136,14 @@ async fn run_guest_init(
     volume_mgr: &GuestVolumeManager,
     rootfs_init: &ContainerRootfsInitConfig,
     container_mounts: &[ContainerMount],
-) -> BoxliteResult<()> {
+    options: &BoxOptions,
+    package_cache: bool,
+    credentials: Option<&CredentialForwarding>,
+    x11_forwarded: bool,
+    x86_emulation: Option<X86EmulationConfig>,
+    box_dns: bool,
+    cloned: bool,
+) -> BoxliteResult<Vec<GuestStageTiming>> {
     let container_id_str = container_id.as_str();
 
     // Build guest volumes from volume manager
@@ -99,20 +151,33 @@ async fn run_guest_init(
 
     let guest_init_config = GuestInitConfig {
         volumes: guest_volumes,
-        network: Some(NetworkInitConfig {
+        network: options.devices.network.then(|| NetworkInitConfig {
             interface: "eth0".to_string(),
             ip: Some("192.168.127.2/24".to_string()),
             gateway: Some("192.168.127.1".to_string()),
         }),
+        kernel_modules: options
+            .gpu
+            .as_ref()
+            .map(|gpu| gpu.driver_modules())
+            .unwrap_or_default(),
+        clock_start_secs: options
+            .deterministic
+            .map(|deterministic| deterministic.clock_start_secs),
+        x86_emulation,
     };
 
-    // Step 1: Guest Init (volumes + network)
+    // Step 1: Guest Init (volumes + network + kernel modules), once the
+    // agent is known to support the box
     tracing::info!("Sending guest initialization request");
     let mut guest_interface = guest_session.guest().await?;
-    guest_interface.init(guest_init_config).await?;
+    let agent_startup = check_agent(&mut guest_interface, options.devices.network).await?;
+    let mut guest_stages = guest_interface.init(guest_init_config).await?;
+    guest_stages.extend(agent_startup);
     tracing::info!("Guest initialized successfully");
 
-    // Step 2: Container Init (rootfs + container image config + user volume mounts)
+    // Step 2: Container Init (rootfs + container image config + user volume
+    // mounts), returning once the setup steps have run
     tracing::info!("Sending container configuration to guest");
     let mut container_interface = guest_session.container().await?;
     let returned_id = container_interface
@@ -121,9 +186,82 @@ async fn run_guest_init(
             container_image_config.clone(),
             rootfs_init.clone(),
             container_mounts.to_vec(),
+            container_devices(options),
+            package_cache.then_some(PACKAGE_CACHE_PATH),
+            options.ssh.as_ref(),
+            credentials,
+            x11_forwarded,
+            options.sharing.as_ref(),
+            options.lock_proxy,
+            options.etc_overlay,
+            &options.device_policy,
+            options.fuse,
+            options.nested_containers,
+            options.init_mode == InitMode::Systemd,
+            options.masked_paths.clone(),
+            options.readonly_paths.clone(),
+            &options.setup_steps,
+            box_dns
+                .then(|| BOX_DNS_ZONE.to_string())
+                .into_iter()
+                .collect(),
+            cloned,
         )
         .await?;
     tracing::info!(container_id = %returned_id, "Container initialized");
 
-    Ok(())
+    // Step 3: Periodic tasks from the box options
+    for task in &options.scheduled_tasks {
+        container_interface
+            .register_task(container_id_str, task)
+            .await?;
+    }
+
+    Ok(guest_stages)
+}
+
+/// Check the agent was built with what the box needs; returns the agent's
+/// startup as a stage timing.
+///
+/// Agents that predate Guest.Capabilities were built with every feature.
+async fn check_agent(
+    guest: &mut GuestInterface,
+    network: bool,
+) -> BoxliteResult<Option<GuestStageTiming>> {
+    let capabilities = match guest.capabilities().await {
+        Ok(capabilities) => capabilities,
+        Err(e) => {
+            tracing::debug!("Guest agent does not report capabilities: {}", e);
+            return Ok(None);
+        }
+    };
+    if !capabilities.within_budget() {
+        tracing::warn!(
+            startup_ms = capabilities.startup.as_millis(),
+            budget_ms = capabilities.budget.as_millis(),
+            "Guest agent started slower than its budget"
+        );
+    }
+    capabilities.check(network)?;
+    Ok(Some(GuestStageTiming {
+        name: "agent".to_string(),
+        duration_ms: capabilities.startup.as_millis(),
+    }))
+}
+
+/// Guest device nodes to expose in the container.
+fn container_devices(options: &BoxOptions) -> Vec<String> {
+    let mut devices = Vec::new();
+    if options.nested_virt {
+        devices.push("/dev/kvm".to_string());
+    }
+    if options.fuse {
+        devices.push("/dev/fuse".to_string());
+    }
+    if let Some(GpuSpec::VirtioGpu { .. }) = options.gpu {
+        // DRM nodes for a compositor (card0) and for rendering (renderD128)
+        devices.push("/dev/dri/card0".to_string());
+        devices.push("/dev/dri/renderD128".to_string());
+    }
+    devices
 }
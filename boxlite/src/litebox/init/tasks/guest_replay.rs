@@ -0,0 +1,83 @@
+//! Task: Guest Replay - Stand in for the VM with a recorded RPC session.
+//!
+//! Used instead of VmmSpawn, GuestConnect and GuestInit for boxes with
+//! `rpc_replay` set: no VM is started, and the session answers every call
+//! from the recording.
+
+use super::{InitCtx, log_task_error, task_start};
+use crate::litebox::BoxStatus;
+use crate::pipeline::PipelineTask;
+use crate::portal::GuestSession;
+use crate::portal::connection::RpcMode;
+use crate::portal::replay::Replayer;
+use crate::vmm::controller::{VmmHandler, VmmMetrics};
+use async_trait::async_trait;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::sync::Arc;
+
+pub struct GuestReplayTask;
+
+#[async_trait]
+impl PipelineTask<InitCtx> for GuestReplayTask {
+    async fn run(self: Box<Self>, ctx: InitCtx) -> BoxliteResult<()> {
+        let task_name = self.name();
+        let box_id = task_start(&ctx, task_name).await;
+
+        let (runtime, transport, path) = {
+            let ctx = ctx.lock().await;
+            let path = ctx.config.options.rpc_replay.clone().ok_or_else(|| {
+                BoxliteError::Internal("guest_replay needs rpc_replay set".into())
+            })?;
+            (ctx.runtime.clone(), ctx.config.transport.clone(), path)
+        };
+
+        let replayer =
+            Replayer::open(&path).inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        let guest_session = GuestSession::with_mode(transport, RpcMode::Replay(Arc::new(replayer)));
+        tracing::info!(box_id = %box_id, log = %path.display(), "Replaying recorded guest session");
+
+        // Running without a PID, so recovery sees it as stopped
+        {
+            let _guard_lock = runtime.acquire_write();
+            if let Ok(mut state) = runtime.box_manager.update_box(&box_id) {
+                state.set_pid(None);
+                state.set_status(BoxStatus::Running);
+                let _ = runtime.box_manager.save_box(&box_id, &state);
+            }
+        }
+
+        let mut ctx = ctx.lock().await;
+        ctx.guard
+            .set_handler(Box::new(ReplayHandler { running: true }));
+        ctx.guest_session = Some(guest_session);
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "guest_replay"
+    }
+}
+
+/// Handler of a box with no VM behind it.
+struct ReplayHandler {
+    running: bool,
+}
+
+impl VmmHandler for ReplayHandler {
+    fn stop(&mut self) -> BoxliteResult<()> {
+        self.running = false;
+        Ok(())
+    }
+
+    fn metrics(&self) -> BoxliteResult<VmmMetrics> {
+        Ok(VmmMetrics::default())
+    }
+
+    fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn pid(&self) -> u32 {
+        0
+    }
+}
@@ -21,12 +21,16 @@
 //!
 //! Running (reattach):
 //! - Stage 1 (sequential): [VmmAttach, GuestConnect]
+//!
+//! Replaying a recorded session (any status):
+//! - Stage 1 (sequential): [Filesystem, GuestReplay]
 //! ```
 
 mod container_rootfs;
 mod filesystem;
 mod guest_connect;
 mod guest_init;
+mod guest_replay;
 mod guest_rootfs;
 mod vmm_attach;
 mod vmm_spawn;
@@ -53,6 +57,7 @@ pub use container_rootfs::ContainerRootfsTask;
 pub use filesystem::FilesystemTask;
 pub use guest_connect::GuestConnectTask;
 pub use guest_init::GuestInitTask;
+pub use guest_replay::GuestReplayTask;
 pub use guest_rootfs::GuestRootfsTask;
 pub use vmm_attach::VmmAttachTask;
 pub use vmm_spawn::VmmSpawnTask;
@@ -6,24 +6,39 @@
 use super::{InitCtx, log_task_error, task_start};
 use crate::disk::DiskFormat;
 use crate::images::ContainerImageConfig;
+use crate::litebox::emulation::{self, X86Emulator};
 use crate::litebox::init::types::resolve_user_volumes;
+use crate::litebox::{ports, readahead};
 use crate::net::NetworkBackendConfig;
+use crate::net::firewall;
 use crate::pipeline::PipelineTask;
+use crate::portal::credentials::CredentialForwarding;
+use crate::portal::interfaces::NetworkCredentials;
+use crate::portal::locks::{LockBroker, LockVolume};
 use crate::runtime::constants::{guest_paths, mount_tags};
 use crate::runtime::guest_rootfs::{GuestRootfs, Strategy};
 use crate::runtime::layout::BoxFilesystemLayout;
-use crate::runtime::options::BoxOptions;
+use crate::runtime::options::{
+    BoxOptions, EncryptedVolumeSpec, HOME_VOLUME_TAG, MountCredentials, NetworkFilesystem,
+    NetworkMountSpec, SshOptions,
+};
 use crate::runtime::rt_impl::SharedRuntimeImpl;
 use crate::runtime::types::{BoxID, BoxStatus, ContainerID};
 use crate::util::find_binary;
 use crate::vmm::controller::{ShimController, VmmController, VmmHandler};
+use crate::vmm::priority::Placement;
 use crate::vmm::{Entrypoint, InstanceSpec, VmmKind};
-use crate::volumes::{ContainerMount, ContainerVolumeManager, GuestVolumeManager};
+use crate::volumes::{
+    ContainerMount, ContainerVolumeManager, GuestVolumeManager, NetworkMountEntry,
+    PACKAGE_CACHE_PATH, PACKAGE_CACHE_TAG, PackageCacheLease,
+};
 use async_trait::async_trait;
 use boxlite_shared::Transport;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use chrono::{DateTime, Utc};
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
 
 pub struct VmmSpawnTask;
 
@@ -42,7 +57,11 @@ impl PipelineTask<InitCtx> for VmmSpawnTask {
             guest_disk_path,
             home_dir,
             container_id,
+            expires_at,
             runtime,
+            readahead_path,
+            encrypted_disks,
+            engine_kind,
         ) = {
             let ctx = ctx.lock().await;
             let layout = ctx
@@ -60,6 +79,15 @@ impl PipelineTask<InitCtx> for VmmSpawnTask {
                 .path()
                 .to_path_buf();
             let guest_disk_path = ctx.guest_disk.as_ref().map(|d| d.path().to_path_buf());
+            // Unlocked by the filesystem task: (disk, needs format)
+            #[cfg(target_os = "linux")]
+            let encrypted_disks: Vec<(PathBuf, bool)> = ctx
+                .encrypted_volumes
+                .iter()
+                .map(|volume| (volume.disk_path(), volume.needs_format()))
+                .collect();
+            #[cfg(not(target_os = "linux"))]
+            let encrypted_disks: Vec<(PathBuf, bool)> = Vec::new();
             (
                 ctx.config.options.clone(),
                 layout,
@@ -68,12 +96,55 @@ impl PipelineTask<InitCtx> for VmmSpawnTask {
                 guest_disk_path,
                 ctx.config.box_home.clone(),
                 ctx.config.container.id.clone(),
+                ctx.config.expires_at(),
                 ctx.runtime.clone(),
+                ctx.readahead.clone(),
+                encrypted_disks,
+                ctx.config.engine_kind,
             )
         };
 
+        // Host services must be listening before the guest can connect
+        let credentials = CredentialForwarding::start(
+            options.forward_ssh_agent,
+            options.forward_git_credentials,
+            &options.git_credential_hosts,
+            layout.git_credential_socket_path(),
+        )
+        .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+
+        let x11_socket = if options.forward_x11 {
+            crate::portal::x11::host_socket()
+        } else {
+            None
+        };
+
+        let lock_broker = if options.lock_proxy {
+            let volumes = resolve_user_volumes(&options.volumes)
+                .inspect_err(|e| log_task_error(&box_id, task_name, e))?
+                .into_iter()
+                .map(|vol| LockVolume {
+                    guest_path: vol.guest_path,
+                    host_path: vol.host_path,
+                })
+                .collect();
+            Some(
+                LockBroker::bind(layout.lock_socket_path(), volumes)
+                    .inspect_err(|e| log_task_error(&box_id, task_name, e))?,
+            )
+        } else {
+            None
+        };
+
         // Build config and get outputs
-        let (instance_spec, volume_mgr, rootfs_init, container_mounts) = build_config(
+        let (
+            instance_spec,
+            volume_mgr,
+            mut rootfs_init,
+            container_mounts,
+            package_cache,
+            x86_emulator,
+        ) = build_config(
             &options,
             &layout,
             &container_image_config,
@@ -81,15 +152,61 @@ impl PipelineTask<InitCtx> for VmmSpawnTask {
             guest_disk_path.as_deref(),
             &home_dir,
             &container_id,
+            expires_at,
             &runtime,
+            &credentials,
+            x11_socket.clone(),
+            lock_broker
+                .as_ref()
+                .map(|broker| broker.socket_path().to_path_buf()),
+            &encrypted_disks,
+            engine_kind,
         )
         .await
         .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        if let (
+            Some(path),
+            crate::portal::interfaces::ContainerRootfsInitConfig::DiskImage { readahead, .. },
+        ) = (&readahead_path, &mut rootfs_init)
+        {
+            *readahead = readahead::load(path);
+        }
 
-        // Spawn VM
-        let handler = spawn_vm(&box_id, &instance_spec)
-            .await
+        let forwards = instance_spec
+            .network_config
+            .as_ref()
+            .map(|network| network.port_mappings.as_slice())
+            .unwrap_or_default();
+        ports::record(&layout.ports_path(), forwards)
             .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        if let Some(network) = &instance_spec.network_config {
+            let policy = network.firewall.clone().unwrap_or_default();
+            firewall::record(&layout.firewall_path(), &policy)
+                .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
+        }
+
+        // Spawn VM
+        let io_paths: Vec<PathBuf> = [layout.root().to_path_buf(), runtime.layout.images_dir()]
+            .into_iter()
+            .chain(options.volumes.iter().map(|v| PathBuf::from(&v.host_path)))
+            .collect();
+        let placement = Placement::prepare(
+            &box_id,
+            options.priority,
+            &options.io_limits,
+            &io_paths,
+            runtime.cgroup_parent.as_deref(),
+        );
+        let inherited_fds: Vec<_> = package_cache.iter().map(|lease| lease.lock_fd()).collect();
+        let handler = spawn_vm(
+            &box_id,
+            engine_kind,
+            &instance_spec,
+            placement,
+            &inherited_fds,
+        )
+        .await
+        .inspect_err(|e| log_task_error(&box_id, task_name, e))?;
 
         // Update PID and status in database
         let pid = handler.pid();
@@ -107,6 +224,11 @@ impl PipelineTask<InitCtx> for VmmSpawnTask {
         ctx.volume_mgr = Some(volume_mgr);
         ctx.rootfs_init = Some(rootfs_init);
         ctx.container_mounts = Some(container_mounts);
+        ctx.package_cache = package_cache;
+        ctx.x86_emulator = x86_emulator;
+        ctx.credentials = Some(credentials);
+        ctx.x11_forwarded = x11_socket.is_some();
+        ctx.lock_broker = lock_broker;
         Ok(())
     }
 
@@ -116,7 +238,7 @@ impl PipelineTask<InitCtx> for VmmSpawnTask {
 }
 
 /// Build VMM config from prepared rootfs outputs.
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 async fn build_config(
     options: &BoxOptions,
     layout: &BoxFilesystemLayout,
@@ -125,16 +247,25 @@ async fn build_config(
     guest_disk_path: Option<&Path>,
     home_dir: &Path,
     container_id: &ContainerID,
+    expires_at: Option<DateTime<Utc>>,
     runtime: &SharedRuntimeImpl,
+    credentials: &CredentialForwarding,
+    x11_socket: Option<PathBuf>,
+    lock_socket: Option<PathBuf>,
+    encrypted_disks: &[(PathBuf, bool)],
+    engine_kind: VmmKind,
 ) -> BoxliteResult<(
     InstanceSpec,
     GuestVolumeManager,
     crate::portal::interfaces::ContainerRootfsInitConfig,
     Vec<ContainerMount>,
+    Option<PackageCacheLease>,
+    Option<X86Emulator>,
 )> {
     // Transport setup
     let transport = Transport::unix(layout.socket_path());
     let ready_transport = Transport::unix(layout.ready_socket_path());
+    let bulk_transport = Transport::unix(layout.bulk_socket_path());
 
     let user_volumes = resolve_user_volumes(&options.volumes)?;
 
@@ -147,6 +278,8 @@ async fn build_config(
 
     // SHARED virtiofs - needed by all strategies
     volume_mgr.add_fs_share(mount_tags::SHARED, layout.shared_dir(), None, false, None);
+    // Image layers are read through SHARED; DAX keeps them out of guest page cache
+    volume_mgr.enable_dax(mount_tags::SHARED);
 
     // Add container rootfs disk (COW overlay workflow):
     // 1. Base disk: Pre-built ext4 image with container layers merged
@@ -154,37 +287,158 @@ async fn build_config(
     //    - Inherits formatted ext4 from base (need_format=false)
     //    - May have larger virtual size if disk_size_gb specified
     // 3. Guest mount: If disk was resized, expand ext4 to fill space (need_resize=true)
-    let need_resize = options.disk_size_gb.is_some();
-    let rootfs_device = volume_mgr.add_block_device(
-        container_disk_path,
-        DiskFormat::Qcow2,
-        false,
-        None,
-        false,       // need_format: COW child inherits formatted base
-        need_resize, // need_resize: expand ext4 if virtual size > base size
-    );
+    //
+    // Boxless boxes cannot attach disks; their rootfs was merged into the
+    // shared container directory on the host instead.
+    let rootfs_init = if engine_kind.is_vm() {
+        let need_resize = options.disk_size_gb.is_some();
+        let rootfs_device = volume_mgr.add_block_device(
+            container_disk_path,
+            DiskFormat::Qcow2,
+            false,
+            None,
+            false,       // need_format: COW child inherits formatted base
+            need_resize, // need_resize: expand ext4 if virtual size > base size
+        );
 
-    // Update rootfs_init with actual device path and resize flag
-    let rootfs_init = crate::portal::interfaces::ContainerRootfsInitConfig::DiskImage {
-        device: rootfs_device,
-        need_format: false, // COW child uses pre-formatted base
-        need_resize,        // Expand ext4 if disk_size_gb was specified
+        // Update rootfs_init with actual device path and resize flag
+        crate::portal::interfaces::ContainerRootfsInitConfig::DiskImage {
+            device: rootfs_device,
+            need_format: false, // COW child uses pre-formatted base
+            need_resize,        // Expand ext4 if disk_size_gb was specified
+            readahead: Vec::new(),
+        }
+    } else {
+        crate::portal::interfaces::ContainerRootfsInitConfig::Merged
     };
 
     // Add user volumes via ContainerVolumeManager
     let mut container_mgr = ContainerVolumeManager::new(&mut volume_mgr);
-    for vol in &user_volumes {
+    for (index, vol) in user_volumes.iter().enumerate() {
+        // Mounted by the filesystem task
+        let host_path = match options.volumes[index].quota_bytes {
+            Some(_) => layout.quota_volume_dir(index),
+            None => vol.host_path.clone(),
+        };
         container_mgr.add_volume(
             container_id.as_str(),
             &vol.tag,
             &vol.tag,
-            vol.host_path.clone(),
+            host_path,
             &vol.guest_path,
             vol.read_only,
         );
+        container_mgr.restrict_volume(&vol.tag, &vol.tag, vol.id_mapping, &vol.hidden);
+        if vol.optional {
+            container_mgr.make_optional(&vol.tag);
+        }
+    }
+    for (index, mount) in options.network_mounts.iter().enumerate() {
+        let credentials = mount
+            .credentials
+            .as_ref()
+            .map(resolve_mount_credentials)
+            .transpose()?;
+        let entry = NetworkMountEntry {
+            name: NetworkMountSpec::volume_name(index),
+            container_id: container_id.as_str().to_string(),
+            filesystem: match mount.filesystem {
+                NetworkFilesystem::Nfs => boxlite_shared::NetworkFilesystem::Nfs,
+                NetworkFilesystem::Cifs => boxlite_shared::NetworkFilesystem::Cifs,
+            },
+            source: mount.source.clone(),
+            read_only: mount.read_only,
+            options: mount.options.clone().unwrap_or_default(),
+            credentials,
+        };
+        container_mgr.add_network_volume(entry, &mount.guest_path);
+    }
+    for (index, (volume, (disk, needs_format))) in options
+        .encrypted_volumes
+        .iter()
+        .zip(encrypted_disks)
+        .enumerate()
+    {
+        container_mgr.add_block_volume(
+            container_id.as_str(),
+            &EncryptedVolumeSpec::volume_name(index),
+            disk,
+            &volume.guest_path,
+            volume.read_only,
+            *needs_format,
+        );
+    }
+    // Mounted by the filesystem task
+    #[cfg(target_os = "linux")]
+    for (index, volume) in options.object_volumes.iter().enumerate() {
+        let tag = crate::runtime::options::ObjectVolumeSpec::tag(index);
+        container_mgr.add_volume(
+            container_id.as_str(),
+            &tag,
+            &tag,
+            crate::volumes::ObjectMount::mount_point(&layout.object_volume_dir(index)),
+            &volume.guest_path,
+            !volume.write_back,
+        );
+    }
+    let package_cache = if options.package_cache {
+        acquire_package_cache(options, layout, runtime)?
+    } else {
+        None
+    };
+    if let Some(lease) = &package_cache {
+        container_mgr.add_volume(
+            container_id.as_str(),
+            PACKAGE_CACHE_TAG,
+            PACKAGE_CACHE_TAG,
+            lease.dir().to_path_buf(),
+            PACKAGE_CACHE_PATH,
+            false,
+        );
+        // Only a cache: installs still work without it, just slower
+        container_mgr.make_optional(PACKAGE_CACHE_TAG);
+    }
+    if let Some(home) = &options.home_volume {
+        let volume_dir = runtime.layout.volumes_dir().join(&home.name).join("home");
+        std::fs::create_dir_all(&volume_dir).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to create home volume {}: {}",
+                volume_dir.display(),
+                e
+            ))
+        })?;
+        container_mgr.add_volume(
+            container_id.as_str(),
+            HOME_VOLUME_TAG,
+            HOME_VOLUME_TAG,
+            volume_dir,
+            &home.path,
+            false,
+        );
+        container_mgr.populate_volume(HOME_VOLUME_TAG);
     }
     let container_mounts = container_mgr.build_container_mounts();
 
+    // x86_64 interpreter for arm64 boxes, registered by guest init
+    let x86_emulator = match (options.x86_emulation, engine_kind.is_vm()) {
+        (true, true) => X86Emulator::for_host()?,
+        (true, false) => {
+            tracing::warn!("x86_emulation needs a VM; ignored for boxless boxes");
+            None
+        }
+        (false, _) => None,
+    };
+    if let Some(emulator) = &x86_emulator {
+        emulator.stage(&layout.x86_emulator_dir())?;
+        volume_mgr.add_fs_share(
+            mount_tags::X86_EMULATOR,
+            layout.x86_emulator_dir(),
+            Some(emulation::GUEST_DIR),
+            true,
+            None,
+        );
+    }
+
     // Get guest rootfs from runtime cache and configure with disk
     let guest_rootfs = runtime
         .guest_rootfs
@@ -192,17 +446,49 @@ async fn build_config(
         .ok_or_else(|| BoxliteError::Internal("guest_rootfs not initialized".into()))?
         .clone();
 
-    let guest_rootfs = configure_guest_rootfs(guest_rootfs, guest_disk_path, &mut volume_mgr)?;
+    let guest_rootfs = if engine_kind.is_vm() {
+        configure_guest_rootfs(guest_rootfs, guest_disk_path, &mut volume_mgr)?
+    } else {
+        guest_rootfs
+    };
 
     // Build VMM config from volume manager
     let vmm_config = volume_mgr.build_vmm_config();
 
     // Guest entrypoint
-    let guest_entrypoint =
-        build_guest_entrypoint(&transport, &ready_transport, &guest_rootfs, options)?;
+    let mut guest_entrypoint = build_guest_entrypoint(
+        &transport,
+        &ready_transport,
+        &bulk_transport,
+        &guest_rootfs,
+        options,
+    )?;
+    if !engine_kind.is_vm() {
+        // No guest rootfs: the agent is the host's own build
+        guest_entrypoint.executable = find_binary("boxlite-guest")?.display().to_string();
+    }
 
-    // Network configuration
-    let network_config = build_network_config(container_image_config, options);
+    // Network configuration; none when the device profile has no network,
+    // and none for boxless boxes, which share the host network
+    let network_config = (engine_kind.is_vm() && options.devices.network)
+        .then(|| build_network_config(container_image_config, options))
+        .flatten()
+        .map(|config| NetworkBackendConfig {
+            capture_socket: Some(layout.capture_socket_path()),
+            box_dns: runtime.dns.is_some(),
+            host_ports: runtime
+                .dns
+                .as_ref()
+                .map(|dns| dns.host_ports())
+                .unwrap_or_default(),
+            host_ports_socket: runtime
+                .dns
+                .is_some()
+                .then(|| layout.host_ports_socket_path()),
+            firewall: options.firewall.clone(),
+            firewall_socket: Some(layout.firewall_socket_path()),
+            ..config
+        });
 
     // Assemble VMM instance spec
     let instance_spec = InstanceSpec {
@@ -213,14 +499,66 @@ async fn build_config(
         guest_entrypoint,
         transport: transport.clone(),
         ready_transport: ready_transport.clone(),
+        bulk_transport: Some(bulk_transport),
+        ssh_agent_socket: credentials.ssh_agent_socket().map(Path::to_path_buf),
+        git_credential_socket: credentials.git_socket().map(Path::to_path_buf),
+        x11_socket,
+        lock_socket,
         guest_rootfs,
         network_config,
         network_backend_endpoint: None,
         home_dir: home_dir.to_path_buf(),
         console_output: None,
+        expires_at,
+        gpu: options.gpu.clone(),
+        nested_virt: options.nested_virt,
+        memory_dedup: options.memory_dedup,
+        devices: options.devices,
     };
 
-    Ok((instance_spec, volume_mgr, rootfs_init, container_mounts))
+    Ok((
+        instance_spec,
+        volume_mgr,
+        rootfs_init,
+        container_mounts,
+        package_cache,
+        x86_emulator,
+    ))
+}
+
+/// Read a network mount's password from the host environment.
+fn resolve_mount_credentials(credentials: &MountCredentials) -> BoxliteResult<NetworkCredentials> {
+    let password = std::env::var(&credentials.password_env).map_err(|_| {
+        BoxliteError::Config(format!(
+            "Network mount password variable {} is not set",
+            credentials.password_env
+        ))
+    })?;
+    Ok(NetworkCredentials {
+        username: credentials.username.clone(),
+        password,
+        domain: credentials.domain.clone().unwrap_or_default(),
+    })
+}
+
+/// Take the box's package cache, or start without it if another box has it.
+///
+/// Boxes share a cache only within their `package_cache_domain`; without
+/// one the cache lives in the box directory.
+fn acquire_package_cache(
+    options: &BoxOptions,
+    layout: &BoxFilesystemLayout,
+    runtime: &SharedRuntimeImpl,
+) -> BoxliteResult<Option<PackageCacheLease>> {
+    let dir = match &options.package_cache_domain {
+        Some(domain) => runtime.layout.package_cache_dir().join(domain),
+        None => layout.package_cache_dir(),
+    };
+    let lease = PackageCacheLease::try_acquire(&dir)?;
+    if lease.is_none() {
+        tracing::warn!("Package cache is in use by another box; starting without it");
+    }
+    Ok(lease)
 }
 
 /// Configure guest rootfs with device path from volume manager.
@@ -255,6 +593,7 @@ fn configure_guest_rootfs(
 fn build_guest_entrypoint(
     transport: &Transport,
     ready_transport: &Transport,
+    bulk_transport: &Transport,
     guest_rootfs: &GuestRootfs,
     options: &crate::runtime::options::BoxOptions,
 ) -> BoxliteResult<Entrypoint> {
@@ -278,14 +617,22 @@ fn build_guest_entrypoint(
         env.push(("RUST_LOG".to_string(), rust_log));
     }
 
+    let mut args = vec![
+        "--listen".to_string(),
+        listen_uri,
+        "--notify".to_string(),
+        ready_notify_uri,
+        "--bulk".to_string(),
+        bulk_transport.to_uri(),
+    ];
+    if let Some(max_concurrency) = options.agent_max_concurrency {
+        args.push("--max-concurrency".to_string());
+        args.push(max_concurrency.to_string());
+    }
+
     Ok(Entrypoint {
         executable: format!("{}/boxlite-guest", guest_paths::BIN_DIR),
-        args: vec![
-            "--listen".to_string(),
-            listen_uri,
-            "--notify".to_string(),
-            ready_notify_uri,
-        ],
+        args,
         env,
     })
 }
@@ -298,7 +645,10 @@ fn build_network_config(
     let mut port_map: HashMap<u16, u16> = HashMap::new();
 
     // Step 1: Collect guest ports that user wants to customize
-    let user_guest_ports: HashSet<u16> = options.ports.iter().map(|p| p.guest_port).collect();
+    let mut user_guest_ports: HashSet<u16> = options.ports.iter().map(|p| p.guest_port).collect();
+    if options.ssh.is_some() {
+        user_guest_ports.insert(SshOptions::GUEST_PORT);
+    }
 
     // Step 2: Image exposed ports (only add default 1:1 mapping if user didn't override)
     for port in container_image_config.tcp_ports() {
@@ -312,6 +662,9 @@ fn build_network_config(
         let host_port = port.host_port.unwrap_or(port.guest_port);
         port_map.insert(host_port, port.guest_port);
     }
+    if let Some(ssh) = &options.ssh {
+        port_map.insert(ssh.host_port, SshOptions::GUEST_PORT);
+    }
 
     let final_mappings: Vec<(u16, u16)> = port_map.into_iter().collect();
 
@@ -330,12 +683,19 @@ fn build_network_config(
 }
 
 /// Spawn VM subprocess and return handler.
-async fn spawn_vm(box_id: &BoxID, config: &InstanceSpec) -> BoxliteResult<Box<dyn VmmHandler>> {
-    let mut controller = ShimController::new(
-        find_binary("boxlite-shim")?,
-        VmmKind::Libkrun,
-        box_id.clone(),
-    )?;
+async fn spawn_vm(
+    box_id: &BoxID,
+    engine_kind: VmmKind,
+    config: &InstanceSpec,
+    placement: Placement,
+    inherited_fds: &[RawFd],
+) -> BoxliteResult<Box<dyn VmmHandler>> {
+    let mut controller =
+        ShimController::new(find_binary("boxlite-shim")?, engine_kind, box_id.clone())?
+            .with_placement(placement);
+    for &fd in inherited_fds {
+        controller = controller.with_inherited_fd(fd);
+    }
 
     controller.start(config).await
 }
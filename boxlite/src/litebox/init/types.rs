@@ -6,13 +6,19 @@ use crate::disk::Disk;
 use crate::fs::BindMountHandle;
 use crate::images::ContainerImageConfig;
 use crate::litebox::config::BoxConfig;
+use crate::litebox::emulation::X86Emulator;
+use crate::metrics::GuestStageTiming;
 use crate::portal::GuestSession;
-use crate::portal::interfaces::ContainerRootfsInitConfig;
+use crate::portal::credentials::CredentialForwarding;
+use crate::portal::interfaces::{ContainerRootfsInitConfig, IdMapping};
+use crate::portal::locks::LockBroker;
 use crate::runtime::layout::BoxFilesystemLayout;
 use crate::runtime::options::VolumeSpec;
 use crate::runtime::rt_impl::SharedRuntimeImpl;
 use crate::vmm::controller::VmmHandler;
-use crate::volumes::{ContainerMount, GuestVolumeManager};
+use crate::volumes::{ContainerMount, GuestVolumeManager, PackageCacheLease};
+#[cfg(target_os = "linux")]
+use crate::volumes::{EncryptedVolume, ObjectMount, QuotaMount};
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
@@ -37,6 +43,12 @@ pub struct ResolvedVolume {
     pub host_path: PathBuf,
     pub guest_path: String,
     pub read_only: bool,
+    /// Directory owner and the box ids it appears as
+    pub id_mapping: Option<IdMapping>,
+    /// Paths inside the volume hidden from the box
+    pub hidden: Vec<String>,
+    /// Boot without the volume if it cannot be mounted
+    pub optional: bool,
 }
 
 pub fn resolve_user_volumes(volumes: &[VolumeSpec]) -> BoxliteResult<Vec<ResolvedVolume>> {
@@ -66,7 +78,26 @@ pub fn resolve_user_volumes(volumes: &[VolumeSpec]) -> BoxliteResult<Vec<Resolve
             )));
         }
 
-        let tag = format!("uservol{}", i);
+        let tag = vol.share_tag(i);
+
+        let id_mapping = match vol.owner {
+            Some(owner) => {
+                use std::os::unix::fs::MetadataExt;
+                let metadata = resolved_path.metadata().map_err(|e| {
+                    BoxliteError::Config(format!(
+                        "Failed to stat volume path '{}': {}",
+                        vol.host_path, e
+                    ))
+                })?;
+                Some(IdMapping {
+                    host_uid: metadata.uid(),
+                    host_gid: metadata.gid(),
+                    uid: owner.uid,
+                    gid: owner.gid,
+                })
+            }
+            None => None,
+        };
 
         tracing::debug!(
             tag = %tag,
@@ -81,6 +112,9 @@ pub fn resolve_user_volumes(volumes: &[VolumeSpec]) -> BoxliteResult<Vec<Resolve
             host_path: resolved_path,
             guest_path: vol.guest_path.clone(),
             read_only: vol.read_only,
+            id_mapping,
+            hidden: vol.hidden.clone(),
+            optional: vol.optional,
         });
     }
 
@@ -121,6 +155,7 @@ pub struct CleanupGuard {
     layout: Option<BoxFilesystemLayout>,
     handler: Option<Box<dyn VmmHandler>>,
     armed: bool,
+    keep_box: bool,
 }
 
 impl CleanupGuard {
@@ -131,6 +166,7 @@ impl CleanupGuard {
             layout: None,
             handler: None,
             armed: true,
+            keep_box: false,
         }
     }
 
@@ -155,6 +191,14 @@ impl CleanupGuard {
     pub fn disarm(&mut self) {
         self.armed = false;
     }
+
+    /// Only stop the VM on failure, keeping the box (for restarts).
+    ///
+    /// A restart that fails or is cancelled partway must not leave the VM
+    /// it spawned running, but the box and its disks are not its to remove.
+    pub fn keep_box(&mut self) {
+        self.keep_box = true;
+    }
 }
 
 impl Drop for CleanupGuard {
@@ -171,6 +215,9 @@ impl Drop for CleanupGuard {
         {
             tracing::warn!("Failed to stop handler during cleanup: {}", e);
         }
+        if self.keep_box {
+            return;
+        }
 
         // Cleanup filesystem
         if let Some(ref layout) = self.layout
@@ -218,9 +265,32 @@ pub struct InitPipelineContext {
     pub rootfs_init: Option<ContainerRootfsInitConfig>,
     pub container_mounts: Option<Vec<ContainerMount>>,
     pub guest_session: Option<GuestSession>,
+    /// Boot stage timings reported by the guest during GuestInit.
+    pub guest_stages: Vec<GuestStageTiming>,
+    /// Exclusive use of the shared package cache, if this box holds it.
+    pub package_cache: Option<PackageCacheLease>,
+    /// Host credential services forwarded into the box.
+    pub credentials: Option<CredentialForwarding>,
+    /// Whether the host X server is bridged to the guest.
+    pub x11_forwarded: bool,
+    /// x86_64 interpreter staged for the guest to register.
+    pub x86_emulator: Option<X86Emulator>,
+    /// Host lock broker serving the box's lock proxy.
+    pub lock_broker: Option<LockBroker>,
+    /// Readahead hint file of the base disk the rootfs boots from.
+    pub readahead: Option<std::path::PathBuf>,
 
     #[cfg(target_os = "linux")]
     pub bind_mount: Option<BindMountHandle>,
+    /// FUSE mounts serving `object_volumes`, in order.
+    #[cfg(target_os = "linux")]
+    pub object_mounts: Vec<ObjectMount>,
+    /// Unlocked `encrypted_volumes`, in order.
+    #[cfg(target_os = "linux")]
+    pub encrypted_volumes: Vec<EncryptedVolume>,
+    /// FUSE mounts serving user volumes with a quota.
+    #[cfg(target_os = "linux")]
+    pub quota_mounts: Vec<QuotaMount>,
 }
 
 impl InitPipelineContext {
@@ -245,8 +315,21 @@ impl InitPipelineContext {
             rootfs_init: None,
             container_mounts: None,
             guest_session: None,
+            guest_stages: Vec::new(),
+            package_cache: None,
+            credentials: None,
+            x11_forwarded: false,
+            x86_emulator: None,
+            lock_broker: None,
+            readahead: None,
             #[cfg(target_os = "linux")]
             bind_mount: None,
+            #[cfg(target_os = "linux")]
+            object_mounts: Vec::new(),
+            #[cfg(target_os = "linux")]
+            encrypted_volumes: Vec::new(),
+            #[cfg(target_os = "linux")]
+            quota_mounts: Vec::new(),
         }
     }
 }
@@ -0,0 +1,50 @@
+//! Persistent interpreter sessions hosted by the guest agent.
+
+use std::collections::HashMap;
+
+/// Exception raised by a cell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellError {
+    /// Exception class name, e.g. `ValueError`.
+    pub ename: String,
+    pub evalue: String,
+    /// Formatted traceback lines.
+    pub traceback: Vec<String>,
+}
+
+/// Outcome of [`crate::LiteBox::execute_cell`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellOutput {
+    /// Cells run so far in the kernel, this one included.
+    pub execution_count: u32,
+    pub stdout: String,
+    /// What the cell wrote to `sys.stderr`, then what child processes printed.
+    pub stderr: String,
+    /// Rich outputs, MIME type to data (binary types base64-encoded): each
+    /// `display()` call, then the value of a trailing expression.
+    pub display: Vec<HashMap<String, String>>,
+    /// Set if the cell raised, including when it was interrupted by its timeout.
+    pub error: Option<CellError>,
+}
+
+impl CellOutput {
+    pub fn success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl From<boxlite_shared::ExecuteCellResponse> for CellOutput {
+    fn from(response: boxlite_shared::ExecuteCellResponse) -> Self {
+        Self {
+            execution_count: response.execution_count,
+            stdout: response.stdout,
+            stderr: response.stderr,
+            display: response.display.into_iter().map(|d| d.data).collect(),
+            error: response.error.map(|e| CellError {
+                ename: e.ename,
+                evalue: e.evalue,
+                traceback: e.traceback,
+            }),
+        }
+    }
+}
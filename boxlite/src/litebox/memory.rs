@@ -0,0 +1,116 @@
+//! Guest memory events.
+//!
+//! While a box runs, its guest agent watches memory (Guest.WatchMemory) and
+//! streams a warning each time available memory drops under the box's
+//! `low_memory_percent`, and every OOM kill with its victim. They are
+//! emitted as `low_memory` and `oom_killed` events. A stream that breaks,
+//! as it does when the agent is upgraded in place, is opened again.
+
+use boxlite_shared::{MemoryEvent, MemoryProcess, memory_event};
+use tokio::task::JoinHandle;
+
+use crate::events::EventKind;
+use crate::litebox::guest_stream::{GuestStream, Watched};
+use crate::portal::GuestSession;
+
+/// Relays the guest's memory events until dropped.
+pub(crate) struct MemoryWatcher {
+    task: JoinHandle<()>,
+}
+
+impl MemoryWatcher {
+    /// Start watching; `on_event` is called with each event's kind and
+    /// attributes.
+    pub(crate) fn start<F>(session: GuestSession, low_percent: u8, on_event: F) -> Self
+    where
+        F: Fn(EventKind, Vec<(&'static str, String)>) + Send + 'static,
+    {
+        let task = tokio::spawn(async move {
+            let mut stream = GuestStream::new(session, "memory", move |mut guest| async move {
+                guest.watch_memory(u32::from(low_percent)).await
+            });
+            while let Some(watched) = stream.next().await {
+                if let Watched::Message(event) = watched
+                    && let Some((kind, attributes)) = describe(event)
+                {
+                    on_event(kind, attributes);
+                }
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for MemoryWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The event kind and attributes a guest memory event is emitted as.
+fn describe(event: MemoryEvent) -> Option<(EventKind, Vec<(&'static str, String)>)> {
+    let process = |process: Option<MemoryProcess>| {
+        process.into_iter().flat_map(|process| {
+            [
+                ("pid", process.pid.to_string()),
+                ("command", process.command),
+                ("rss_bytes", process.rss_bytes.to_string()),
+            ]
+        })
+    };
+    match event.event? {
+        memory_event::Event::LowMemory(low) => {
+            let mut attributes = vec![
+                ("available_bytes", low.available_bytes.to_string()),
+                ("total_bytes", low.total_bytes.to_string()),
+            ];
+            attributes.extend(process(low.top));
+            Some((EventKind::LowMemory, attributes))
+        }
+        memory_event::Event::OomKill(kill) => {
+            Some((EventKind::OomKilled, process(kill.victim).collect()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boxlite_shared::{LowMemory, OomKill};
+
+    #[test]
+    fn test_describe() {
+        let victim = MemoryProcess {
+            pid: 321,
+            command: "python3".to_string(),
+            rss_bytes: 4096,
+        };
+        let (kind, attributes) = describe(MemoryEvent {
+            event: Some(memory_event::Event::OomKill(OomKill {
+                victim: Some(victim.clone()),
+            })),
+        })
+        .unwrap();
+        assert_eq!(kind, EventKind::OomKilled);
+        assert_eq!(
+            attributes,
+            [
+                ("pid", "321".to_string()),
+                ("command", "python3".to_string()),
+                ("rss_bytes", "4096".to_string()),
+            ]
+        );
+
+        let (kind, attributes) = describe(MemoryEvent {
+            event: Some(memory_event::Event::LowMemory(LowMemory {
+                available_bytes: 10,
+                total_bytes: 100,
+                top: None,
+            })),
+        })
+        .unwrap();
+        assert_eq!(kind, EventKind::LowMemory);
+        assert_eq!(attributes.len(), 2);
+        assert!(describe(MemoryEvent { event: None }).is_none());
+    }
+}
@@ -2,24 +2,75 @@
 //!
 //! Provides lazy initialization and execution capabilities for isolated boxes.
 
+mod agent;
+mod artifacts;
 pub(crate) mod box_impl;
+mod capture;
 pub(crate) mod config;
+mod crash;
+mod display;
+pub(crate) mod emulation;
 mod exec;
+mod exec_cache;
+#[cfg(feature = "fault-injection")]
+mod faults;
+mod guest_stream;
+mod health;
+mod idle;
 mod init;
+mod kernel;
 mod manager;
+mod memory;
+mod packages;
+pub(crate) mod ports;
+pub(crate) mod pressure;
+mod processes;
+mod provision;
+mod readahead;
+mod recording;
+pub(crate) mod resume;
+mod shares;
 mod state;
+mod sync;
+mod tasks;
+mod users;
+mod wake;
+mod watch;
 
-pub use exec::{BoxCommand, ExecResult, ExecStderr, ExecStdin, ExecStdout, Execution, ExecutionId};
+pub use agent::AgentCapabilities;
+pub use artifacts::ArtifactInfo;
+pub use capture::{CaptureSpec, CaptureStats, NetworkCapture};
+pub use display::Screenshot;
+pub use exec::{
+    AuditReport, BoxCommand, ExecNetwork, ExecResult, ExecStderr, ExecStdin, ExecStdout, Execution,
+    ExecutionId, ExitKind, OutputCapture, OutputChunk, OutputStream,
+};
+#[cfg(feature = "fault-injection")]
+pub use faults::Fault;
+pub use health::{BoxHealth, HealthStatus};
+pub use kernel::{CellError, CellOutput};
 pub(crate) use manager::BoxManager;
+pub use packages::{InstalledPackage, PackageInstallResult, PackageInstallation};
+pub use ports::ForwardedPort;
+pub use processes::ProcessInfo;
+pub use recording::RecordingInfo;
+pub use shares::{ShareMountStrategy, ShareStatus};
 pub use state::{BoxState, BoxStatus};
+pub use sync::{ConflictPolicy, SyncConflict, SyncSession, SyncSpec};
+pub use tasks::TaskStatus;
+pub use users::{HomeStorage, UserSpec};
+pub use watch::{PathChange, PathChangeKind, PathWatch};
 
 pub(crate) use box_impl::SharedBoxImpl;
 pub(crate) use init::BoxBuilder;
 
 use crate::metrics::BoxMetrics;
+use crate::net::firewall::{FirewallPolicy, FirewallRule};
+use crate::runtime::options::ScheduledTask;
 use crate::{BoxID, BoxInfo};
 use boxlite_shared::errors::BoxliteResult;
 pub use config::BoxConfig;
+use std::path::Path;
 
 /// LiteBox - Handle to a box.
 ///
@@ -64,10 +115,256 @@ impl LiteBox {
         self.inner.exec(command).await
     }
 
+    /// Get metrics of the running VM.
+    ///
+    /// Fails with `InvalidState` if the box is not running; unlike `exec`,
+    /// this never starts the box or resumes a suspended one.
     pub async fn metrics(&self) -> BoxliteResult<BoxMetrics> {
         self.inner.metrics().await
     }
 
+    /// Capture the box's display as a PNG (requires a virtio-gpu GPU).
+    pub async fn screenshot(&self) -> BoxliteResult<Screenshot> {
+        self.inner.screenshot().await
+    }
+
+    /// How each shared directory is mounted in the box, including any
+    /// fallback the guest took when virtio-fs failed.
+    pub async fn shares(&self) -> BoxliteResult<Vec<ShareStatus>> {
+        self.inner.shares().await
+    }
+
+    /// Optional features the box's guest agent was built with, and how
+    /// fast it started.
+    pub async fn agent_capabilities(&self) -> BoxliteResult<AgentCapabilities> {
+        self.inner.agent_capabilities().await
+    }
+
+    /// Replace the box's guest agent with `binary` without a reboot.
+    ///
+    /// The agent checks `signature`, an Ed25519 signature of the binary,
+    /// against the key it was built with, then execs the new binary, which
+    /// takes over its containers, mounts and periodic tasks. Refused while
+    /// executions or interpreter sessions are running. Returns the new
+    /// agent's version.
+    pub async fn upgrade_agent(&self, binary: &Path, signature: &[u8]) -> BoxliteResult<String> {
+        self.inner.upgrade_agent(binary, signature).await
+    }
+
+    /// Copy a host file into the container at `box_path` (an absolute path).
+    ///
+    /// Runs as an operation that reports the bytes copied and can be
+    /// cancelled (see [`BoxliteRuntime::operations`](crate::BoxliteRuntime::operations)).
+    /// Returns the number of bytes copied.
+    pub async fn copy_in(&self, host_path: &Path, box_path: &str) -> BoxliteResult<u64> {
+        self.inner.copy_in(host_path, box_path).await
+    }
+
+    /// Copy a file out of the container to `host_path`.
+    ///
+    /// Runs as an operation, like [`copy_in`](Self::copy_in). Returns the
+    /// number of bytes copied.
+    pub async fn copy_out(&self, box_path: &str, host_path: &Path) -> BoxliteResult<u64> {
+        self.inner.copy_out(box_path, host_path).await
+    }
+
+    /// Replace the box's shared clipboard.
+    ///
+    /// Requires a sharing policy that allows host-to-box clipboard use.
+    pub async fn set_clipboard(&self, content: &[u8]) -> BoxliteResult<()> {
+        self.inner.set_clipboard(content).await
+    }
+
+    /// Read the box's shared clipboard.
+    ///
+    /// Requires a sharing policy that allows box-to-host clipboard use.
+    pub async fn clipboard(&self) -> BoxliteResult<Vec<u8>> {
+        self.inner.clipboard().await
+    }
+
+    /// Drop a host file into the box's drop directory, replacing a file
+    /// with the same name.
+    ///
+    /// Returns the file's path in the box.
+    pub async fn drop_file(&self, host_path: &Path) -> BoxliteResult<String> {
+        self.inner.drop_file(host_path).await
+    }
+
+    /// Create a user account in the container.
+    ///
+    /// Run commands as the user with [`BoxCommand::user`].
+    pub async fn create_user(&self, user: UserSpec) -> BoxliteResult<()> {
+        self.inner.create_user(user).await
+    }
+
+    /// Delete a user account, and its home directory if `remove_home`.
+    ///
+    /// Only a regular user's (UID 1000 and up) own home under `/home` is
+    /// removed; asking to remove any other fails before the account is
+    /// touched.
+    pub async fn delete_user(&self, name: &str, remove_home: bool) -> BoxliteResult<()> {
+        self.inner.delete_user(name, remove_home).await
+    }
+
+    /// Start running a task periodically, replacing any task with its name.
+    pub async fn register_task(&self, task: ScheduledTask) -> BoxliteResult<()> {
+        self.inner.register_task(task).await
+    }
+
+    /// Stop running a periodic task.
+    pub async fn unregister_task(&self, name: &str) -> BoxliteResult<()> {
+        self.inner.unregister_task(name).await
+    }
+
+    /// Install packages with the image's package manager (apt, apk or dnf).
+    ///
+    /// Package names may carry the manager's version syntax, for example
+    /// `curl=7.88.1-10` for apt. With `update_index` the package index is
+    /// refreshed first, which minimal images need before their first
+    /// install.
+    pub async fn install_packages(
+        &self,
+        packages: &[String],
+        update_index: bool,
+    ) -> BoxliteResult<PackageInstallation> {
+        self.inner.install_packages(packages, update_index).await
+    }
+
+    /// Run Python code in the persistent interpreter session `kernel`.
+    ///
+    /// The session starts on first use (the image needs `python3`) and
+    /// keeps variables and imports between cells, like a notebook. A cell
+    /// still running after `timeout` is interrupted with a
+    /// `KeyboardInterrupt`; the session survives that.
+    pub async fn execute_cell(
+        &self,
+        kernel: &str,
+        code: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> BoxliteResult<CellOutput> {
+        self.inner.execute_cell(kernel, code, timeout).await
+    }
+
+    /// Stop the interpreter session `kernel`, discarding its state.
+    pub async fn shutdown_kernel(&self, kernel: &str) -> BoxliteResult<()> {
+        self.inner.shutdown_kernel(kernel).await
+    }
+
+    /// Periodic tasks and the outcome of their last runs.
+    pub async fn list_tasks(&self) -> BoxliteResult<Vec<TaskStatus>> {
+        self.inner.list_tasks().await
+    }
+
+    /// Processes running in the box's container, by pid.
+    pub async fn processes(&self) -> BoxliteResult<Vec<ProcessInfo>> {
+        self.inner.processes().await
+    }
+
+    /// Watch `path` in the box for created, modified and deleted files, with
+    /// `recursive` in every directory below it too.
+    ///
+    /// Changes are batched until the path has been quiet for `debounce`
+    /// (100ms if None), so host tools can live-reload or sync without
+    /// reacting to every intermediate write.
+    pub async fn watch(
+        &self,
+        path: &str,
+        recursive: bool,
+        debounce: Option<std::time::Duration>,
+    ) -> BoxliteResult<PathWatch> {
+        self.inner.watch(path, recursive, debounce).await
+    }
+
+    /// Copy `spec.host_path` into the box at `spec.box_path`, then keep the
+    /// two in step both ways until the returned session is dropped.
+    ///
+    /// Files changed on both sides are settled by `spec.conflict`.
+    pub async fn sync(&self, spec: SyncSpec) -> BoxliteResult<SyncSession> {
+        self.inner.sync(spec).await
+    }
+
+    /// Capture the box's network traffic to a pcap file until the returned
+    /// capture is stopped.
+    ///
+    /// Frames are copied off the host side of the box's link, so traffic
+    /// between the box and the host shows up too. Needs a running box with
+    /// networking on the gvproxy backend.
+    pub async fn capture_network(&self, spec: CaptureSpec) -> BoxliteResult<NetworkCapture> {
+        self.inner.capture_network(spec).await
+    }
+
+    /// Have the running box's guest drop its clean page cache and compact
+    /// memory, handing the freed pages back to the host. Returns how many
+    /// bytes more the guest has available.
+    pub async fn reclaim_memory(&self) -> BoxliteResult<u64> {
+        self.inner.reclaim_memory().await
+    }
+
+    /// The running box's firewall policy.
+    ///
+    /// Needs a running box with networking on the gvproxy backend.
+    pub fn firewall(&self) -> BoxliteResult<FirewallPolicy> {
+        self.inner.firewall()
+    }
+
+    /// Filter the running box's traffic with `policy` from now on.
+    ///
+    /// The change applies to connections already open and lasts until the
+    /// box stops; it starts again with `BoxOptions::firewall`.
+    pub async fn set_firewall(&self, policy: FirewallPolicy) -> BoxliteResult<()> {
+        self.inner.set_firewall(policy).await
+    }
+
+    /// Add `rules` after the running box's current ones, returning the
+    /// resulting policy.
+    pub async fn add_firewall_rules(
+        &self,
+        rules: Vec<FirewallRule>,
+    ) -> BoxliteResult<FirewallPolicy> {
+        self.inner.add_firewall_rules(rules).await
+    }
+
+    /// Host ports forwarded into the box while it runs, by host port; empty
+    /// when it is not running.
+    pub fn ports(&self) -> Vec<ForwardedPort> {
+        self.inner.ports()
+    }
+
+    /// Artifacts collected from executions, oldest first (see
+    /// [`BoxCommand::artifacts`]). Available while the box is stopped.
+    pub fn artifacts(&self) -> BoxliteResult<Vec<ArtifactInfo>> {
+        self.inner.artifacts()
+    }
+
+    /// Unpack the artifacts of `execution_id` into the host directory `dest`.
+    pub async fn download_artifacts(&self, execution_id: &str, dest: &Path) -> BoxliteResult<()> {
+        self.inner.download_artifacts(execution_id, dest).await
+    }
+
+    /// Recorded TTY sessions, oldest first (see `BoxOptions::record_sessions`).
+    pub fn recordings(&self) -> BoxliteResult<Vec<RecordingInfo>> {
+        self.inner.recordings()
+    }
+
+    /// The asciinema cast of a recorded session.
+    pub fn read_recording(&self, execution_id: &str) -> BoxliteResult<String> {
+        self.inner.read_recording(execution_id)
+    }
+
+    /// Inject `fault` into the running box, to test how callers cope.
+    ///
+    /// Faults other than [`Fault::VsockDrop`] need a guest agent built with
+    /// its `fault-injection` feature.
+    #[cfg(feature = "fault-injection")]
+    pub async fn inject_fault(&self, fault: Fault) -> BoxliteResult<()> {
+        self.inner.inject_fault(fault).await
+    }
+
+    /// Save the box's container disk to `target`, booting it if needed.
+    pub(crate) async fn save_disk(&self, target: &Path) -> BoxliteResult<()> {
+        self.inner.save_disk(target).await
+    }
+
     pub async fn stop(&self) -> BoxliteResult<()> {
         self.inner.stop().await
     }
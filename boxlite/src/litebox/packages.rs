@@ -0,0 +1,101 @@
+//! Package installation through the guest agent.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::{InstallPackagesEvent, install_packages_event};
+
+use super::idle::ActivityGuard;
+
+/// A package installed by [`crate::LiteBox::install_packages`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+}
+
+/// Outcome of a package installation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageInstallResult {
+    /// `apt`, `apk` or `dnf`; `None` if the image has none of them.
+    pub package_manager: Option<String>,
+    /// Exit code of the step that failed, 0 on success.
+    pub exit_code: i32,
+    /// Requested packages now installed, with their versions.
+    pub installed: Vec<InstalledPackage>,
+    /// Why installation failed.
+    pub error: Option<String>,
+}
+
+impl PackageInstallResult {
+    pub fn success(&self) -> bool {
+        self.error.is_none() && self.exit_code == 0
+    }
+}
+
+impl From<boxlite_shared::InstallPackagesResult> for PackageInstallResult {
+    fn from(result: boxlite_shared::InstallPackagesResult) -> Self {
+        Self {
+            package_manager: (!result.package_manager.is_empty()).then_some(result.package_manager),
+            exit_code: result.exit_code,
+            installed: result
+                .installed
+                .into_iter()
+                .map(|p| InstalledPackage {
+                    name: p.name,
+                    version: p.version,
+                })
+                .collect(),
+            error: (!result.error.is_empty()).then_some(result.error),
+        }
+    }
+}
+
+/// A running package installation.
+///
+/// Read the package manager's output with [`Self::next_output`], or skip
+/// it and go straight to [`Self::wait`]. The box is kept from being
+/// suspended until the result is collected.
+pub struct PackageInstallation {
+    events: tonic::Streaming<InstallPackagesEvent>,
+    result: Option<PackageInstallResult>,
+    _activity: ActivityGuard,
+}
+
+impl PackageInstallation {
+    pub(crate) fn new(
+        events: tonic::Streaming<InstallPackagesEvent>,
+        activity: ActivityGuard,
+    ) -> Self {
+        Self {
+            events,
+            result: None,
+            _activity: activity,
+        }
+    }
+
+    /// The next chunk of combined stdout and stderr, or `None` once the
+    /// package manager has finished.
+    pub async fn next_output(&mut self) -> BoxliteResult<Option<String>> {
+        while self.result.is_none() {
+            match self.events.message().await?.and_then(|e| e.event) {
+                Some(install_packages_event::Event::Output(output)) => return Ok(Some(output)),
+                Some(install_packages_event::Event::Result(result)) => {
+                    self.result = Some(result.into());
+                }
+                None => {
+                    return Err(BoxliteError::Internal(
+                        "Package installation ended without a result".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Wait for installation to finish, discarding output not yet read.
+    pub async fn wait(mut self) -> BoxliteResult<PackageInstallResult> {
+        while self.next_output().await?.is_some() {}
+        self.result
+            .take()
+            .ok_or_else(|| BoxliteError::Internal("Package installation has no result".into()))
+    }
+}
@@ -0,0 +1,42 @@
+//! Host ports forwarded to a box.
+//!
+//! The forwards are only settled when the VM is spawned, after the image's
+//! exposed ports are merged with `BoxOptions::ports`, so they are recorded
+//! in the box directory then and read back by `LiteBox::ports`.
+
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+
+/// A host TCP port forwarded into the box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardedPort {
+    pub host_port: u16,
+    pub guest_port: u16,
+}
+
+/// Record the forwards of the VM being started.
+pub(crate) fn record(path: &Path, mappings: &[(u16, u16)]) -> BoxliteResult<()> {
+    let ports: Vec<ForwardedPort> = mappings
+        .iter()
+        .map(|&(host_port, guest_port)| ForwardedPort {
+            host_port,
+            guest_port,
+        })
+        .collect();
+    let content = serde_json::to_vec(&ports)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode port forwards: {}", e)))?;
+    std::fs::write(path, content)
+        .map_err(|e| BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// The recorded forwards, by host port; none if nothing was recorded.
+pub(crate) fn read(path: &Path) -> Vec<ForwardedPort> {
+    let mut ports: Vec<ForwardedPort> = std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default();
+    ports.sort_by_key(|port| port.host_port);
+    ports
+}
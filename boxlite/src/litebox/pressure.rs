@@ -0,0 +1,185 @@
+//! Guest pressure events.
+//!
+//! While a box runs, its guest agent watches the kernel's pressure stall
+//! information (Guest.WatchPressure) and streams a change each time CPU,
+//! memory or I/O comes under the box's `pressure_percent` of pressure or
+//! is relieved. Changes are emitted as `pressure` and `pressure_relieved`
+//! events, and passed to the runtime, which may lean on boxes of lower
+//! priority in response (see `BoxliteOptions::pressure`). A stream that
+//! breaks is opened again; pressure reported before the break counts as
+//! relieved until the guest reports it again.
+
+use boxlite_shared::PressureEvent;
+use std::collections::HashSet;
+use tokio::task::JoinHandle;
+
+use crate::events::EventKind;
+use crate::litebox::guest_stream::{GuestStream, Watched};
+use crate::portal::GuestSession;
+
+/// A resource the guest reports pressure on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum PressureResource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl PressureResource {
+    fn parse(resource: &str) -> Option<Self> {
+        match resource {
+            "cpu" => Some(PressureResource::Cpu),
+            "memory" => Some(PressureResource::Memory),
+            "io" => Some(PressureResource::Io),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            PressureResource::Cpu => "cpu",
+            PressureResource::Memory => "memory",
+            PressureResource::Io => "io",
+        }
+    }
+}
+
+/// A resource coming under pressure, or being relieved.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PressureChange {
+    pub(crate) resource: PressureResource,
+    pub(crate) under_pressure: bool,
+    /// Percent of time some tasks stalled, over 10 and 60 seconds
+    some_avg10: f32,
+    some_avg60: f32,
+    /// Percent of time all tasks stalled, over 10 seconds
+    full_avg10: f32,
+}
+
+impl PressureChange {
+    fn from_event(event: PressureEvent) -> Option<Self> {
+        Some(Self {
+            resource: PressureResource::parse(&event.resource)?,
+            under_pressure: event.under_pressure,
+            some_avg10: event.some_avg10,
+            some_avg60: event.some_avg60,
+            full_avg10: event.full_avg10,
+        })
+    }
+
+    fn relieved(resource: PressureResource) -> Self {
+        Self {
+            resource,
+            under_pressure: false,
+            some_avg10: 0.0,
+            some_avg60: 0.0,
+            full_avg10: 0.0,
+        }
+    }
+
+    /// The event kind and attributes the change is emitted as.
+    pub(crate) fn describe(&self) -> (EventKind, Vec<(&'static str, String)>) {
+        let kind = if self.under_pressure {
+            EventKind::Pressure
+        } else {
+            EventKind::PressureRelieved
+        };
+        (
+            kind,
+            vec![
+                ("resource", self.resource.as_str().to_string()),
+                ("some_avg10", format!("{:.2}", self.some_avg10)),
+                ("some_avg60", format!("{:.2}", self.some_avg60)),
+                ("full_avg10", format!("{:.2}", self.full_avg10)),
+            ],
+        )
+    }
+}
+
+/// Relays the guest's pressure changes until dropped.
+pub(crate) struct PressureWatcher {
+    task: JoinHandle<()>,
+}
+
+impl PressureWatcher {
+    /// Start watching for `threshold_percent` of pressure; `on_change` is
+    /// called with each change.
+    pub(crate) fn start<F>(session: GuestSession, threshold_percent: u8, on_change: F) -> Self
+    where
+        F: Fn(PressureChange) + Send + 'static,
+    {
+        let task = tokio::spawn(async move {
+            let mut under = HashSet::new();
+            // Also ends if the guest kernel has no PSI
+            let mut stream = GuestStream::new(session, "pressure", move |mut guest| async move {
+                guest.watch_pressure(u32::from(threshold_percent)).await
+            });
+            while let Some(watched) = stream.next().await {
+                match watched {
+                    Watched::Message(event) => {
+                        if let Some(change) = PressureChange::from_event(event) {
+                            if change.under_pressure {
+                                under.insert(change.resource);
+                            } else {
+                                under.remove(&change.resource);
+                            }
+                            on_change(change);
+                        }
+                    }
+                    // The new agent starts out relieved and reports again
+                    Watched::Broken => {
+                        for resource in under.drain() {
+                            on_change(PressureChange::relieved(resource));
+                        }
+                    }
+                }
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for PressureWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let change = PressureChange::from_event(PressureEvent {
+            resource: "memory".to_string(),
+            under_pressure: true,
+            some_avg10: 31.25,
+            some_avg60: 12.0,
+            full_avg10: 8.5,
+        })
+        .unwrap();
+        let (kind, attributes) = change.describe();
+        assert_eq!(kind, EventKind::Pressure);
+        assert_eq!(
+            attributes,
+            [
+                ("resource", "memory".to_string()),
+                ("some_avg10", "31.25".to_string()),
+                ("some_avg60", "12.00".to_string()),
+                ("full_avg10", "8.50".to_string()),
+            ]
+        );
+
+        let (kind, _) = PressureChange::relieved(PressureResource::Cpu).describe();
+        assert_eq!(kind, EventKind::PressureRelieved);
+
+        assert!(
+            PressureChange::from_event(PressureEvent {
+                resource: "irq".to_string(),
+                ..Default::default()
+            })
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Container process listing types.
+
+use std::time::Duration;
+
+/// A process running in a box's container, as reported by the guest agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessInfo {
+    /// Pid as seen inside the container.
+    pub pid: u32,
+    /// Parent pid inside the container; 0 if the parent is outside it.
+    pub ppid: u32,
+    pub uid: u32,
+    /// One-letter state, as in `ps` (R, S, D, Z, T, ...).
+    pub state: String,
+    /// Command line, or `[name]` for processes without one.
+    pub command: String,
+    pub rss_bytes: u64,
+    /// User plus system CPU time consumed so far.
+    pub cpu_time: Duration,
+}
+
+impl From<boxlite_shared::ProcessInfo> for ProcessInfo {
+    fn from(process: boxlite_shared::ProcessInfo) -> Self {
+        Self {
+            pid: process.pid,
+            ppid: process.ppid,
+            uid: process.uid,
+            state: process.state,
+            command: process.command,
+            rss_bytes: process.rss_bytes,
+            cpu_time: Duration::from_millis(process.cpu_time_ms),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Provisioning boxes with language runtimes.
+//!
+//! A box created with `runtimes` installs them on its first boot with a
+//! built-in recipe for the image's package manager (apt, apk or dnf). Once
+//! that succeeds the guest freezes the container rootfs, the host copies
+//! the box's disk overlay into the image store as a derived image, and the
+//! rootfs is thawed again. Later boxes with the same image and runtimes
+//! start from the derived image and skip the install.
+//!
+//! The derived image is the first box's disk right after provisioning, so
+//! it also holds what the agent wrote into the rootfs at container init.
+//! The recipes are versioned in the cache key; changing one makes every
+//! box install afresh once.
+//!
+//! Between the first boot and the copy, a `provision` marker in the box
+//! directory holds where the derived image goes. A box whose provisioning
+//! failed keeps the marker and tries again on its next start.
+
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::images::ContainerImageConfig;
+use crate::runtime::options::RuntimeProfile;
+
+/// Marker file inside a box directory.
+const PROVISION_MARKER: &str = "provision";
+
+/// Bumped whenever a recipe changes, so stale derived images are not used.
+const RECIPE_VERSION: u32 = 1;
+
+/// Virtualenv the Python profile creates.
+const PYTHON_VENV: &str = "/opt/venv";
+
+/// Sorted, deduplicated runtimes.
+fn normalized(runtimes: &[RuntimeProfile]) -> Vec<RuntimeProfile> {
+    let mut runtimes = runtimes.to_vec();
+    runtimes.sort();
+    runtimes.dedup();
+    runtimes
+}
+
+impl RuntimeProfile {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            RuntimeProfile::Python => "python",
+            RuntimeProfile::Node => "node",
+            RuntimeProfile::Go => "go",
+            RuntimeProfile::Rust => "rust",
+        }
+    }
+
+    /// Packages for apt, apk and dnf, in that order.
+    fn packages(self) -> [&'static str; 3] {
+        match self {
+            RuntimeProfile::Python => [
+                "python3 python3-venv python3-pip",
+                "python3 py3-pip",
+                "python3 python3-pip",
+            ],
+            RuntimeProfile::Node => ["nodejs npm", "nodejs npm", "nodejs npm"],
+            RuntimeProfile::Go => ["golang-go", "go", "golang"],
+            RuntimeProfile::Rust => ["rustc cargo", "rust cargo", "rust cargo"],
+        }
+    }
+}
+
+/// Derived image variant for `runtimes`; the same set always gives the same name.
+pub(crate) fn variant(runtimes: &[RuntimeProfile]) -> String {
+    let names: Vec<&str> = normalized(runtimes)
+        .into_iter()
+        .map(RuntimeProfile::as_str)
+        .collect();
+    format!("runtimes-v{}:{}", RECIPE_VERSION, names.join(","))
+}
+
+/// Shell script that installs `runtimes` in the container.
+pub(crate) fn script(runtimes: &[RuntimeProfile]) -> String {
+    let runtimes = normalized(runtimes);
+    let packages = |manager: usize| {
+        runtimes
+            .iter()
+            .map(|r| r.packages()[manager])
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut script = format!(
+        "set -e\n\
+         if command -v apt-get >/dev/null 2>&1; then\n\
+         \x20 export DEBIAN_FRONTEND=noninteractive\n\
+         \x20 apt-get update -q\n\
+         \x20 apt-get install -y -q --no-install-recommends ca-certificates {apt}\n\
+         \x20 rm -rf /var/lib/apt/lists/*\n\
+         elif command -v apk >/dev/null 2>&1; then\n\
+         \x20 apk add --no-progress --no-cache ca-certificates {apk}\n\
+         elif command -v dnf >/dev/null 2>&1; then\n\
+         \x20 dnf install -y ca-certificates {dnf}\n\
+         \x20 dnf clean all\n\
+         else\n\
+         \x20 echo 'boxlite: the image has no apt, apk or dnf' >&2\n\
+         \x20 exit 127\n\
+         fi\n",
+        apt = packages(0),
+        apk = packages(1),
+        dnf = packages(2),
+    );
+    if runtimes.contains(&RuntimeProfile::Python) {
+        script.push_str(&format!("python3 -m venv {}\n", PYTHON_VENV));
+    }
+    script
+}
+
+/// Environment the runtimes need, on top of the image's.
+pub(crate) fn env(
+    image_config: &ContainerImageConfig,
+    runtimes: &[RuntimeProfile],
+) -> Vec<(String, String)> {
+    if !runtimes.contains(&RuntimeProfile::Python) {
+        return Vec::new();
+    }
+    let path = image_config
+        .env
+        .iter()
+        .find_map(|e| e.strip_prefix("PATH="))
+        .unwrap_or("/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin");
+    vec![
+        ("VIRTUAL_ENV".to_string(), PYTHON_VENV.to_string()),
+        ("PATH".to_string(), format!("{}/bin:{}", PYTHON_VENV, path)),
+    ]
+}
+
+fn marker_path(box_home: &Path) -> PathBuf {
+    box_home.join(PROVISION_MARKER)
+}
+
+/// Record that the box in `box_home` provisions on its next boot and saves
+/// its disk to `derived_disk`.
+pub(crate) fn mark(box_home: &Path, derived_disk: &Path) -> BoxliteResult<()> {
+    let path = marker_path(box_home);
+    std::fs::write(&path, derived_disk.as_os_str().as_encoded_bytes())
+        .map_err(|e| BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Where the pending provisioning of the box in `box_home` saves its disk.
+pub(crate) fn pending(box_home: &Path) -> Option<PathBuf> {
+    let content = std::fs::read_to_string(marker_path(box_home)).ok()?;
+    let content = content.trim();
+    (!content.is_empty()).then(|| PathBuf::from(content))
+}
+
+/// Forget the pending provisioning once it is done.
+pub(crate) fn clear(box_home: &Path) {
+    let path = marker_path(box_home);
+    if let Err(e) = std::fs::remove_file(&path)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(path = %path.display(), "Failed to remove provision marker: {}", e);
+    }
+}
+
+/// Copy the box disk at `disk` to `derived_disk`, replacing it atomically.
+pub(crate) fn save_derived(disk: &Path, derived_disk: &Path) -> BoxliteResult<()> {
+    let fail = |e: std::io::Error| {
+        BoxliteError::Storage(format!(
+            "Failed to save derived image {}: {}",
+            derived_disk.display(),
+            e
+        ))
+    };
+    if let Some(parent) = derived_disk.parent() {
+        std::fs::create_dir_all(parent).map_err(fail)?;
+    }
+    let staging = derived_disk.with_extension(format!("tmp-{}", std::process::id()));
+    std::fs::copy(disk, &staging)
+        .and_then(|_| std::fs::rename(&staging, derived_disk))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&staging);
+        })
+        .map_err(fail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_ignores_order_and_repeats() {
+        let a = variant(&[RuntimeProfile::Node, RuntimeProfile::Python]);
+        let b = variant(&[
+            RuntimeProfile::Python,
+            RuntimeProfile::Node,
+            RuntimeProfile::Python,
+        ]);
+        assert_eq!(a, b);
+        assert_ne!(a, variant(&[RuntimeProfile::Python]));
+
+        let script = script(&[RuntimeProfile::Rust, RuntimeProfile::Python]);
+        assert!(script.contains(
+            "--no-install-recommends ca-certificates python3 python3-venv python3-pip rustc cargo\n"
+        ));
+        assert!(script.contains(
+            "apk add --no-progress --no-cache ca-certificates python3 py3-pip rust cargo\n"
+        ));
+        assert!(script.ends_with("python3 -m venv /opt/venv\n"));
+    }
+
+    #[test]
+    fn test_python_env_prepends_venv() {
+        let config = ContainerImageConfig {
+            env: vec!["PATH=/usr/bin:/bin".to_string()],
+            ..Default::default()
+        };
+        let env = env(&config, &[RuntimeProfile::Python]);
+        assert_eq!(env[1].1, "/opt/venv/bin:/usr/bin:/bin");
+        assert!(super::env(&config, &[RuntimeProfile::Go]).is_empty());
+    }
+
+    #[test]
+    fn test_mark_pending_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(pending(dir.path()), None);
+        let derived = dir.path().join("derived.qcow2");
+        mark(dir.path(), &derived).unwrap();
+        assert_eq!(pending(dir.path()), Some(derived.clone()));
+
+        std::fs::write(dir.path().join("disk.qcow2"), b"overlay").unwrap();
+        save_derived(&dir.path().join("disk.qcow2"), &derived).unwrap();
+        assert_eq!(std::fs::read(&derived).unwrap(), b"overlay");
+
+        clear(dir.path());
+        assert_eq!(pending(dir.path()), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! Rootfs readahead hints, kept per base disk.
+//!
+//! Boxes started from the same image fault in much the same binaries and
+//! libraries on every cold start. The first box booted from a base disk
+//! asks its guest, once the box has settled, which rootfs file ranges are in
+//! the page cache, and the answer is saved under the runtime's readahead
+//! cache. Later boots from the disk pass the ranges to the guest, which
+//! reads them ahead in the background while the workload starts.
+//!
+//! Hints are only recorded once per base disk: a boot that read hints ahead
+//! has them cached whether or not it used them. Delete the hint file to
+//! learn again.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use boxlite_shared::ReadaheadHint;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::portal::GuestSession;
+
+/// How long after the container starts its page cache is recorded.
+const RECORD_DELAY: Duration = Duration::from_secs(30);
+
+/// One hint as stored on disk.
+#[derive(Serialize, Deserialize)]
+struct Hint {
+    path: String,
+    offset: u64,
+    length: u64,
+}
+
+/// The hint file for the base disk identified by `digest`.
+pub(crate) fn hints_path(dir: &Path, digest: &str) -> PathBuf {
+    dir.join(format!("{}.json", digest.replace(':', "-")))
+}
+
+/// The saved hints; none if nothing was recorded or the file is unreadable.
+pub(crate) fn load(path: &Path) -> Vec<ReadaheadHint> {
+    let hints: Vec<Hint> = std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default();
+    hints
+        .into_iter()
+        .map(|hint| ReadaheadHint {
+            path: hint.path,
+            offset: hint.offset,
+            length: hint.length,
+        })
+        .collect()
+}
+
+pub(crate) fn save(path: &Path, hints: &[ReadaheadHint]) -> BoxliteResult<()> {
+    let hints: Vec<Hint> = hints
+        .iter()
+        .map(|hint| Hint {
+            path: hint.path.clone(),
+            offset: hint.offset,
+            length: hint.length,
+        })
+        .collect();
+    let content = serde_json::to_vec(&hints)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode readahead hints: {}", e)))?;
+    let storage = |e: std::io::Error| {
+        BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e))
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(storage)?;
+    }
+    // Boxes booting from the same disk may record at once; rename keeps
+    // every reader seeing one whole file
+    let temp = path.with_extension(format!("tmp-{}", std::process::id()));
+    std::fs::write(&temp, content).map_err(storage)?;
+    std::fs::rename(&temp, path).map_err(storage)
+}
+
+/// Records the hints of a box's first boot from its base disk, unless
+/// dropped first.
+pub(crate) struct ReadaheadRecorder {
+    task: JoinHandle<()>,
+}
+
+impl ReadaheadRecorder {
+    /// Record into `path` once the box has run for a while, if no other box
+    /// has by then.
+    pub(crate) fn start(session: GuestSession, container_id: String, path: PathBuf) -> Self {
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(RECORD_DELAY).await;
+            if path.exists() {
+                return;
+            }
+            let recorded = async {
+                let hints = session
+                    .container()
+                    .await?
+                    .record_readahead(&container_id)
+                    .await?;
+                save(&path, &hints)?;
+                Ok::<_, BoxliteError>(hints.len())
+            }
+            .await;
+            match recorded {
+                Ok(count) => tracing::debug!(
+                    path = %path.display(),
+                    count,
+                    "Recorded rootfs readahead hints"
+                ),
+                Err(e) => tracing::debug!("Failed to record readahead hints: {}", e),
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for ReadaheadRecorder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = hints_path(&dir.path().join("readahead"), "sha256:abc");
+        assert!(path.ends_with("readahead/sha256-abc.json"));
+        assert!(load(&path).is_empty());
+
+        let hints = vec![ReadaheadHint {
+            path: "usr/lib/libc.so.6".to_string(),
+            offset: 0,
+            length: 8192,
+        }];
+        save(&path, &hints).unwrap();
+        assert_eq!(load(&path), hints);
+    }
+}
@@ -0,0 +1,241 @@
+//! Recordings of interactive (PTY) sessions.
+//!
+//! With `BoxOptions::record_sessions`, the output of every TTY exec is
+//! written as it streams to an asciinema v2 cast in the box directory
+//! (`recordings/{execution_id}.cast`), so what happened in a sandbox can be
+//! audited or replayed with `asciinema play`. A PTY echoes what is typed,
+//! so input shows up in the output; it is not recorded separately.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+
+use crate::runtime::options::StreamBufferOptions;
+use crate::util::stream_buffer::{self, BufferReceiver};
+
+/// Recordings directory inside a box directory.
+pub(crate) const RECORDINGS_DIR: &str = "recordings";
+
+const CAST_EXTENSION: &str = "cast";
+
+/// A recorded session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordingInfo {
+    pub execution_id: String,
+    /// The asciinema cast file.
+    pub path: PathBuf,
+    /// Command line of the session.
+    pub command: String,
+    pub started_at: SystemTime,
+    pub size_bytes: u64,
+}
+
+/// First line of an asciinema v2 cast.
+#[derive(Debug, Serialize, Deserialize)]
+struct CastHeader {
+    version: u32,
+    width: u32,
+    height: u32,
+    timestamp: u64,
+    #[serde(default)]
+    command: String,
+}
+
+/// A session cast being written.
+pub(crate) struct SessionRecorder {
+    path: PathBuf,
+    file: File,
+}
+
+impl SessionRecorder {
+    /// Create the cast for a session of `command` on a `rows` x `cols` terminal.
+    pub(crate) fn create(
+        path: &Path,
+        command: String,
+        (rows, cols): (u32, u32),
+    ) -> BoxliteResult<Self> {
+        let header = CastHeader {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            command,
+        };
+        let file = create_cast(path, &header).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to create recording {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    /// Record `output` as it streams, returning the stream to hand on.
+    ///
+    /// Output is forwarded unchanged through a buffer like the one it came
+    /// from, and recording continues if the consumer goes away. A write
+    /// failure stops the recording, not the session.
+    pub(crate) fn record(
+        self,
+        mut output: BufferReceiver<String>,
+        buffer: StreamBufferOptions,
+        dropped: Arc<AtomicU64>,
+    ) -> BufferReceiver<String> {
+        let Self { path, mut file } = self;
+        let (tx, rx) = stream_buffer::channel(buffer, dropped);
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let mut recording = true;
+            while let Some(chunk) = output.recv().await {
+                if recording
+                    && let Err(e) = file.write_all(event_line(started.elapsed(), &chunk).as_bytes())
+                {
+                    tracing::warn!(path = %path.display(), "Stopped recording session: {}", e);
+                    recording = false;
+                }
+                if tx.send(chunk).await.is_err() && !recording {
+                    return;
+                }
+            }
+        });
+        rx
+    }
+}
+
+fn create_cast(path: &Path, header: &CastHeader) -> std::io::Result<File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = File::create(path)?;
+    let line = serde_json::to_string(header).map_err(std::io::Error::other)?;
+    writeln!(file, "{}", line)?;
+    Ok(file)
+}
+
+/// An output event: `[seconds, "o", data]`.
+fn event_line(elapsed: Duration, data: &str) -> String {
+    let event = serde_json::json!([elapsed.as_secs_f64(), "o", data]);
+    format!("{}\n", event)
+}
+
+/// Recordings in `dir`, oldest first.
+pub(crate) fn list(dir: &Path) -> BoxliteResult<Vec<RecordingInfo>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(BoxliteError::Storage(format!(
+                "Failed to read {}: {}",
+                dir.display(),
+                e
+            )));
+        }
+    };
+
+    let mut recordings: Vec<RecordingInfo> = entries
+        .flatten()
+        .filter_map(|entry| read_info(&entry.path()))
+        .collect();
+    recordings.sort_by_key(|r| r.started_at);
+    Ok(recordings)
+}
+
+fn read_info(path: &Path) -> Option<RecordingInfo> {
+    if path.extension()? != CAST_EXTENSION {
+        return None;
+    }
+    let execution_id = path.file_stem()?.to_str()?.to_string();
+    let file = File::open(path).ok()?;
+    let size_bytes = file.metadata().ok()?.len();
+    let mut line = String::new();
+    BufReader::new(file).read_line(&mut line).ok()?;
+    let header: CastHeader = serde_json::from_str(&line).ok()?;
+    Some(RecordingInfo {
+        execution_id,
+        path: path.to_path_buf(),
+        command: header.command,
+        started_at: UNIX_EPOCH + Duration::from_secs(header.timestamp),
+        size_bytes,
+    })
+}
+
+/// Cast file for `execution_id` in `dir`.
+pub(crate) fn cast_path(dir: &Path, execution_id: &str) -> BoxliteResult<PathBuf> {
+    // Execution ids come from the guest; never let one leave the directory
+    if execution_id.is_empty()
+        || execution_id.contains(['/', '\\'])
+        || execution_id.starts_with('.')
+    {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "Invalid execution id: {}",
+            execution_id
+        )));
+    }
+    Ok(dir.join(format!("{}.{}", execution_id, CAST_EXTENSION)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = cast_path(dir.path(), "exec-1").unwrap();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let buffer = StreamBufferOptions::default();
+        let (tx, rx) = stream_buffer::channel(buffer, Arc::clone(&dropped));
+        let recorder = SessionRecorder::create(&path, "bash -l".to_string(), (24, 80)).unwrap();
+        let mut forwarded = recorder.record(rx, buffer, dropped);
+
+        tx.send("$ ls\r\n".to_string()).await.unwrap();
+        tx.send("a\"b\r\n".to_string()).await.unwrap();
+        drop(tx);
+        assert_eq!(forwarded.recv().await.unwrap(), "$ ls\r\n");
+        assert_eq!(forwarded.recv().await.unwrap(), "a\"b\r\n");
+        assert!(forwarded.recv().await.is_none());
+
+        let cast = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = cast
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[2][2], "a\"b\r\n");
+
+        let recordings = list(dir.path()).unwrap();
+        assert_eq!(recordings.len(), 1);
+        assert_eq!(recordings[0].execution_id, "exec-1");
+        assert_eq!(recordings[0].command, "bash -l");
+        assert_eq!(recordings[0].size_bytes, cast.len() as u64);
+        assert!(list(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cast_path_stays_in_dir() {
+        let dir = Path::new("/box/recordings");
+        assert_eq!(
+            cast_path(dir, "abc").unwrap(),
+            PathBuf::from("/box/recordings/abc.cast")
+        );
+        assert!(cast_path(dir, "../x").is_err());
+        assert!(cast_path(dir, "").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Resuming boxes whose disks were cloned or restored.
+//!
+//! A clone, snapshot restore or import leaves a marker in the box directory naming
+//! what the disks came from. The next start sends the guest a Resume request
+//! once it is up, so it steps its clock to the host's, reseeds its entropy
+//! pool from host randomness and rotates its session token, and then emits
+//! a `Resumed` event. The marker is only removed once the guest has
+//! answered, so a start that fails before then tries again next time.
+
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Marker file inside a box directory.
+const RESUME_MARKER: &str = "resume";
+
+/// Why a box's next boot is a resume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResumeReason {
+    Clone,
+    Restore,
+    Import,
+}
+
+impl ResumeReason {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            ResumeReason::Clone => "clone",
+            ResumeReason::Restore => "restore",
+            ResumeReason::Import => "import",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "clone" => Some(ResumeReason::Clone),
+            "restore" => Some(ResumeReason::Restore),
+            "import" => Some(ResumeReason::Import),
+            _ => None,
+        }
+    }
+}
+
+fn marker_path(box_home: &Path) -> PathBuf {
+    box_home.join(RESUME_MARKER)
+}
+
+/// Record that the box in `box_home` resumes from copied or restored disks.
+pub(crate) fn mark(box_home: &Path, reason: ResumeReason) -> BoxliteResult<()> {
+    let path = marker_path(box_home);
+    std::fs::write(&path, reason.as_str())
+        .map_err(|e| BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// The pending resume for the box in `box_home`, if any.
+pub(crate) fn pending(box_home: &Path) -> Option<ResumeReason> {
+    let content = std::fs::read_to_string(marker_path(box_home)).ok()?;
+    ResumeReason::parse(&content)
+}
+
+/// Forget the pending resume once the guest has handled it.
+pub(crate) fn clear(box_home: &Path) {
+    let path = marker_path(box_home);
+    if let Err(e) = std::fs::remove_file(&path)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(path = %path.display(), "Failed to remove resume marker: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mark_pending_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(pending(dir.path()), None);
+
+        mark(dir.path(), ResumeReason::Restore).unwrap();
+        assert_eq!(pending(dir.path()), Some(ResumeReason::Restore));
+        mark(dir.path(), ResumeReason::Clone).unwrap();
+        assert_eq!(pending(dir.path()), Some(ResumeReason::Clone));
+
+        clear(dir.path());
+        assert_eq!(pending(dir.path()), None);
+        clear(dir.path());
+    }
+}
@@ -0,0 +1,112 @@
+//! How a box's shared directories are mounted in the guest.
+//!
+//! The guest mounts each host directory share over virtio-fs, retrying
+//! with backoff. A share that still fails is tried over 9p, and an
+//! optional share (the package cache, volumes marked `optional`) is
+//! finally replaced by an empty tmpfs so the box boots degraded instead of
+//! failing. The guest reports what it settled on through Guest.Ping; the
+//! host emits a `share_fallback` event for each share not on virtio-fs.
+
+use boxlite_shared::{ShareMount, ShareStrategy};
+use serde::{Deserialize, Serialize};
+
+/// How a share ended up mounted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareMountStrategy {
+    /// virtio-fs, as requested.
+    Virtiofs,
+    /// virtio-fs without the requested DAX window.
+    VirtiofsNoDax,
+    /// 9p over virtio.
+    #[serde(rename = "9p")]
+    NineP,
+    /// Not mounted: the box sees an empty tmpfs in its place.
+    Degraded,
+    /// Bind mount of the host directory (boxless engine).
+    Bind,
+}
+
+impl ShareMountStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareMountStrategy::Virtiofs => "virtiofs",
+            ShareMountStrategy::VirtiofsNoDax => "virtiofs_no_dax",
+            ShareMountStrategy::NineP => "9p",
+            ShareMountStrategy::Degraded => "degraded",
+            ShareMountStrategy::Bind => "bind",
+        }
+    }
+
+    /// Whether virtio-fs failed and another strategy took over.
+    pub fn is_fallback(&self) -> bool {
+        matches!(
+            self,
+            ShareMountStrategy::NineP | ShareMountStrategy::Degraded
+        )
+    }
+}
+
+impl std::fmt::Display for ShareMountStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// How one share of a running box is mounted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareStatus {
+    /// Virtio-fs tag of the share.
+    pub tag: String,
+    pub strategy: ShareMountStrategy,
+    /// Mount attempts the guest made, across all strategies.
+    pub attempts: u32,
+    /// Why virtio-fs failed, if another strategy is in effect.
+    pub error: Option<String>,
+}
+
+impl From<ShareMount> for ShareStatus {
+    fn from(share: ShareMount) -> Self {
+        let strategy = match share.strategy() {
+            ShareStrategy::ShareVirtiofs => ShareMountStrategy::Virtiofs,
+            ShareStrategy::ShareVirtiofsNoDax => ShareMountStrategy::VirtiofsNoDax,
+            ShareStrategy::Share9p => ShareMountStrategy::NineP,
+            ShareStrategy::ShareDegraded => ShareMountStrategy::Degraded,
+            ShareStrategy::ShareBind => ShareMountStrategy::Bind,
+        };
+        Self {
+            tag: share.tag,
+            strategy,
+            attempts: share.attempts,
+            error: (!share.error.is_empty()).then_some(share.error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_status_from_proto() {
+        let status = ShareStatus::from(ShareMount {
+            tag: "pkgcache".to_string(),
+            strategy: ShareStrategy::ShareDegraded as i32,
+            attempts: 4,
+            error: "No such device".to_string(),
+        });
+        assert_eq!(status.strategy, ShareMountStrategy::Degraded);
+        assert!(status.strategy.is_fallback());
+        assert_eq!(status.error.as_deref(), Some("No such device"));
+
+        let status = ShareStatus::from(ShareMount {
+            tag: "BoxLiteShared".to_string(),
+            strategy: ShareStrategy::ShareVirtiofs as i32,
+            attempts: 1,
+            error: String::new(),
+        });
+        assert_eq!(status.strategy, ShareMountStrategy::Virtiofs);
+        assert!(!status.strategy.is_fallback());
+        assert_eq!(status.error, None);
+    }
+}
@@ -3,6 +3,7 @@
 //! Defines the possible states of a box and valid transitions between them.
 
 use crate::ContainerID;
+use crate::litebox::BoxHealth;
 use crate::lock::LockId;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use chrono::{DateTime, Utc};
@@ -160,6 +161,9 @@ pub struct BoxState {
     /// Allocated when the box is first initialized (not at creation time).
     /// Used to retrieve the lock across process restarts.
     pub lock_id: Option<LockId>,
+    /// Probe health from the last run; None for boxes without probes.
+    #[serde(default)]
+    pub health: Option<BoxHealth>,
 }
 
 impl BoxState {
@@ -171,6 +175,7 @@ impl BoxState {
             container_id: None,
             last_updated: Utc::now(),
             lock_id: None,
+            health: None,
         }
     }
 
@@ -0,0 +1,843 @@
+//! Two-way directory sync between the host and a box.
+//!
+//! [`crate::LiteBox::sync`] keeps a host directory and a box directory in
+//! step while the box runs, for editing on one side and building on the
+//! other without a shared mount. Files travel over the bulk channel; the box
+//! side is watched through Container.WatchPath and the host side is polled,
+//! as user volumes are.
+//!
+//! For every synced file the digests both sides had when they last agreed
+//! are remembered, so a change is carried over from whichever side made it.
+//! A file changed on both sides is a conflict, settled by the
+//! [`ConflictPolicy`]; a file deleted on one side and changed on the other
+//! is kept. Symlinks are not synced, and what is already in the box
+//! directory when the sync starts is left alone until it changes.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+
+use super::watch::{PathChange, PathChangeKind, PathWatch};
+use crate::portal::GuestSession;
+use crate::portal::bulk::BulkChannel;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long box changes are collected before they are synced.
+const BOX_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Suffix of the box's copy of a file kept under [`ConflictPolicy::KeepBoth`].
+pub const CONFLICT_SUFFIX: &str = ".conflict-box";
+
+/// What to do with a file changed on both sides.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the host's version, and the box's as `<name>.conflict-box`
+    /// next to it on both sides.
+    #[default]
+    KeepBoth,
+    HostWins,
+    BoxWins,
+}
+
+/// A host directory to keep in step with a box directory.
+#[derive(Clone, Debug)]
+pub struct SyncSpec {
+    pub host_path: PathBuf,
+    pub box_path: String,
+    pub conflict: ConflictPolicy,
+    /// File and directory names never synced, on either side.
+    pub ignore: Vec<String>,
+}
+
+impl SyncSpec {
+    pub fn new(host_path: impl Into<PathBuf>, box_path: impl Into<String>) -> Self {
+        Self {
+            host_path: host_path.into(),
+            box_path: box_path.into(),
+            conflict: ConflictPolicy::default(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+/// A file that changed on both sides, and how it was settled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncConflict {
+    /// Relative to the synced directories.
+    pub path: String,
+    pub resolution: ConflictPolicy,
+}
+
+#[derive(Default)]
+struct SyncStatus {
+    conflicts: Vec<SyncConflict>,
+    error: Option<String>,
+}
+
+/// A running sync started by [`crate::LiteBox::sync`]; it stops when
+/// dropped, when the box stops, or when the box directory is removed.
+pub struct SyncSession {
+    task: JoinHandle<()>,
+    status: Arc<Mutex<SyncStatus>>,
+}
+
+impl SyncSession {
+    /// Copy the host directory into the box, then keep both in step.
+    pub(crate) async fn start(
+        bulk: BulkChannel,
+        session: GuestSession,
+        container_id: String,
+        spec: SyncSpec,
+    ) -> BoxliteResult<Self> {
+        if !spec.host_path.is_dir() {
+            return Err(BoxliteError::InvalidArgument(format!(
+                "{} is not a directory",
+                spec.host_path.display()
+            )));
+        }
+        let remote = BoxRemote {
+            bulk,
+            session,
+            container_id,
+            root: spec.box_path.trim_end_matches('/').to_string(),
+        };
+        remote.make_dir("", 0o755).await?;
+        // Watching first means nothing changed in the box during the
+        // initial copy is missed
+        let watch = PathWatch::new(
+            remote
+                .session
+                .container()
+                .await?
+                .watch_path(
+                    &remote.container_id,
+                    &spec.box_path,
+                    true,
+                    Some(BOX_DEBOUNCE),
+                )
+                .await?,
+        );
+
+        let mut engine = Engine::new(remote, spec);
+        engine.initial().await?;
+        let status = Arc::new(Mutex::new(SyncStatus::default()));
+        let task = tokio::spawn(run(engine, watch, Arc::clone(&status)));
+        Ok(Self { task, status })
+    }
+
+    /// Conflicts settled so far.
+    pub fn conflicts(&self) -> Vec<SyncConflict> {
+        self.status.lock().unwrap().conflicts.clone()
+    }
+
+    /// Why the sync stopped, once it has.
+    pub fn error(&self) -> Option<String> {
+        self.status.lock().unwrap().error.clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for SyncSession {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(mut engine: Engine<BoxRemote>, mut watch: PathWatch, status: Arc<Mutex<SyncStatus>>) {
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    let stopped = loop {
+        let dirty = tokio::select! {
+            batch = watch.next() => match batch {
+                Ok(Some(changes)) => engine.box_changes(changes),
+                Ok(None) => break "The box directory was removed or the box stopped".to_string(),
+                Err(e) => break e.to_string(),
+            },
+            _ = poll.tick() => match engine.host_changes().await {
+                Ok(dirty) => dirty,
+                Err(e) => break e.to_string(),
+            },
+        };
+        match engine.reconcile(dirty).await {
+            Ok(conflicts) => status.lock().unwrap().conflicts.extend(conflicts),
+            Err(e) => break e.to_string(),
+        }
+    };
+    tracing::warn!(root = %engine.root.display(), "Directory sync stopped: {}", stopped);
+    status.lock().unwrap().error = Some(stopped);
+}
+
+/// The box side of a sync; paths are relative to the synced directory.
+trait Remote {
+    /// Digests in the order given; `None` for a path that does not exist.
+    async fn digests(&self, paths: &[String]) -> BoxliteResult<Vec<Option<String>>>;
+    async fn upload(&self, src: &Path, dest: &str) -> BoxliteResult<()>;
+    async fn download(&self, src: &str, dest: &Path) -> BoxliteResult<()>;
+    /// Remove a file or empty directory.
+    async fn remove(&self, path: &str) -> BoxliteResult<()>;
+    async fn make_dir(&self, path: &str, mode: u32) -> BoxliteResult<()>;
+}
+
+struct BoxRemote {
+    bulk: BulkChannel,
+    session: GuestSession,
+    container_id: String,
+    root: String,
+}
+
+impl BoxRemote {
+    fn path(&self, relative: &str) -> String {
+        if relative.is_empty() {
+            self.root.clone()
+        } else {
+            format!("{}/{}", self.root, relative)
+        }
+    }
+}
+
+impl Remote for BoxRemote {
+    async fn digests(&self, paths: &[String]) -> BoxliteResult<Vec<Option<String>>> {
+        let paths: Vec<String> = paths.iter().map(|p| self.path(p)).collect();
+        let mut container = self.session.container().await?;
+        let digests = container.digest_paths(&self.container_id, &paths).await?;
+        Ok(digests.into_iter().map(|(_, digest)| digest).collect())
+    }
+
+    async fn upload(&self, src: &Path, dest: &str) -> BoxliteResult<()> {
+        self.bulk
+            .upload(&self.container_id, src, &self.path(dest), None)
+            .await
+            .map(drop)
+    }
+
+    async fn download(&self, src: &str, dest: &Path) -> BoxliteResult<()> {
+        self.bulk
+            .download(&self.container_id, &self.path(src), dest, None)
+            .await
+            .map(drop)
+    }
+
+    async fn remove(&self, path: &str) -> BoxliteResult<()> {
+        self.bulk.remove(&self.container_id, &self.path(path)).await
+    }
+
+    async fn make_dir(&self, path: &str, mode: u32) -> BoxliteResult<()> {
+        self.bulk
+            .make_dir(&self.container_id, &self.path(path), mode)
+            .await
+    }
+}
+
+/// Digests of a file on each side when they last agreed.
+struct Agreed {
+    host: String,
+    remote: String,
+}
+
+/// What the host poll compares between walks.
+#[derive(Clone, PartialEq, Eq)]
+struct Stamp {
+    dir: bool,
+    len: u64,
+    mode: u32,
+    mtime: Option<SystemTime>,
+}
+
+/// A path on the host.
+enum Local {
+    Missing,
+    Dir(u32),
+    /// Digest of the mode and content
+    File(String),
+    /// Symlinks and special files, which are not synced
+    Other,
+}
+
+/// Which side a directory was removed on.
+enum Removed {
+    Host,
+    Box,
+}
+
+struct Engine<R> {
+    remote: R,
+    root: PathBuf,
+    policy: ConflictPolicy,
+    ignore: Vec<String>,
+    files: HashMap<String, Agreed>,
+    /// Directories present on both sides
+    dirs: HashSet<String>,
+    /// The host tree at the last poll
+    snapshot: HashMap<String, Stamp>,
+}
+
+impl<R: Remote> Engine<R> {
+    fn new(remote: R, spec: SyncSpec) -> Self {
+        Self {
+            remote,
+            root: spec.host_path,
+            policy: spec.conflict,
+            ignore: spec.ignore,
+            files: HashMap::new(),
+            dirs: HashSet::new(),
+            snapshot: HashMap::new(),
+        }
+    }
+
+    /// Copy every host file into the box.
+    async fn initial(&mut self) -> BoxliteResult<()> {
+        let snapshot = self.walk().await?;
+        let mut entries: Vec<(&String, &Stamp)> = snapshot.iter().collect();
+        // Parents sort before their children
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let mut uploaded = Vec::new();
+        for (path, stamp) in entries {
+            if stamp.dir {
+                self.remote.make_dir(path, stamp.mode).await?;
+                self.dirs.insert(path.clone());
+                continue;
+            }
+            if let Local::File(host) = local(self.root.join(path)).await? {
+                self.remote.upload(&self.root.join(path), path).await?;
+                uploaded.push((path.clone(), host));
+            }
+        }
+        let paths: Vec<String> = uploaded.iter().map(|(path, _)| path.clone()).collect();
+        let digests = self.remote.digests(&paths).await?;
+        for ((path, host), remote) in uploaded.into_iter().zip(digests) {
+            if let Some(remote) = remote {
+                self.files.insert(path, Agreed { host, remote });
+            }
+        }
+        self.snapshot = snapshot;
+        Ok(())
+    }
+
+    /// Paths the box reported, and whether each is a directory there.
+    fn box_changes(&self, changes: Vec<PathChange>) -> BTreeMap<String, bool> {
+        let mut dirty = BTreeMap::new();
+        for change in changes {
+            if change.kind == PathChangeKind::Overflow {
+                // Everything known is rechecked; new box files that were
+                // missed wait for their next change
+                dirty.extend(self.files.keys().map(|path| (path.clone(), false)));
+                dirty.extend(self.dirs.iter().map(|path| (path.clone(), true)));
+            } else if !change.path.is_empty() {
+                dirty.insert(change.path, change.is_dir);
+            }
+        }
+        dirty
+    }
+
+    /// Paths that changed on the host since the last poll.
+    async fn host_changes(&mut self) -> BoxliteResult<BTreeMap<String, bool>> {
+        let current = self.walk().await?;
+        let mut dirty = BTreeMap::new();
+        for (path, stamp) in &current {
+            if self.snapshot.get(path) != Some(stamp) {
+                dirty.insert(path.clone(), false);
+            }
+        }
+        for path in self.snapshot.keys() {
+            if !current.contains_key(path) {
+                dirty.insert(path.clone(), false);
+            }
+        }
+        self.snapshot = current;
+        Ok(dirty)
+    }
+
+    /// Bring `dirty` paths in step, returning the conflicts settled.
+    async fn reconcile(
+        &mut self,
+        dirty: BTreeMap<String, bool>,
+    ) -> BoxliteResult<Vec<SyncConflict>> {
+        let dirty: Vec<(String, bool)> = dirty
+            .into_iter()
+            .filter(|(path, _)| !self.ignored(path))
+            .collect();
+        if dirty.is_empty() {
+            return Ok(Vec::new());
+        }
+        let paths: Vec<String> = dirty.iter().map(|(path, _)| path.clone()).collect();
+        let remote = self.remote.digests(&paths).await?;
+
+        let mut conflicts = Vec::new();
+        let mut removed = Vec::new();
+        for ((path, remote_dir), remote) in dirty.into_iter().zip(remote) {
+            let host = self.root.join(&path);
+            let local = local(host.clone()).await?;
+            let known_dir = self.dirs.contains(&path);
+            let dir = matches!(local, Local::Dir(_)) || remote_dir || known_dir;
+            match (local, remote) {
+                (Local::Other, _) => {}
+                (Local::Dir(mode), remote) if dir => match (remote.is_some(), known_dir) {
+                    (true, _) => {
+                        self.dirs.insert(path);
+                    }
+                    (false, true) => removed.push((path, Removed::Box)),
+                    (false, false) => {
+                        self.remote.make_dir(&path, mode).await?;
+                        self.dirs.insert(path);
+                    }
+                },
+                (Local::Missing, remote) if dir => match (remote.is_some(), known_dir) {
+                    (true, true) => removed.push((path, Removed::Host)),
+                    (true, false) => {
+                        create_dir(&host)?;
+                        self.touched(&path);
+                        self.dirs.insert(path);
+                    }
+                    (false, _) => {
+                        self.dirs.remove(&path);
+                    }
+                },
+                (Local::File(digest), remote) => {
+                    conflicts.extend(self.sync_file(&path, Some(digest), remote).await?)
+                }
+                (_, remote) => conflicts.extend(self.sync_file(&path, None, remote).await?),
+            }
+        }
+
+        // Children first; a directory that still holds something the other
+        // side has not seen is put back instead
+        for (path, side) in removed.into_iter().rev() {
+            let host = self.root.join(&path);
+            match side {
+                Removed::Box => match std::fs::remove_dir(&host) {
+                    Ok(()) => {
+                        self.touched(&path);
+                        self.dirs.remove(&path);
+                    }
+                    Err(_) => self.remote.make_dir(&path, mode_of(&host)).await?,
+                },
+                Removed::Host => {
+                    if self.remote.remove(&path).await.is_ok() {
+                        self.dirs.remove(&path);
+                    } else {
+                        create_dir(&host)?;
+                        self.touched(&path);
+                    }
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+
+    async fn sync_file(
+        &mut self,
+        path: &str,
+        host: Option<String>,
+        remote: Option<String>,
+    ) -> BoxliteResult<Option<SyncConflict>> {
+        let agreed = self.files.get(path);
+        let host_changed = host.as_ref() != agreed.map(|a| &a.host);
+        let remote_changed = remote.as_ref() != agreed.map(|a| &a.remote);
+        if !host_changed && !remote_changed {
+            return Ok(None);
+        }
+        match (host, remote) {
+            (None, None) => {
+                self.files.remove(path);
+            }
+            (host, _) if !remote_changed => self.push(path, host).await?,
+            (_, remote) if !host_changed => self.pull(path, remote).await?,
+            // A deletion loses to a change on the other side
+            (None, remote) => self.pull(path, remote).await?,
+            (host, None) => self.push(path, host).await?,
+            (Some(host), Some(remote)) => return self.resolve(path, host, remote).await,
+        }
+        Ok(None)
+    }
+
+    /// Carry the host's version of `path`, or its absence, to the box.
+    async fn push(&mut self, path: &str, host: Option<String>) -> BoxliteResult<()> {
+        let Some(host) = host else {
+            self.remote.remove(path).await?;
+            self.files.remove(path);
+            return Ok(());
+        };
+        self.remote.upload(&self.root.join(path), path).await?;
+        match self
+            .remote
+            .digests(&[path.to_string()])
+            .await?
+            .pop()
+            .flatten()
+        {
+            Some(remote) => {
+                self.files.insert(path.to_string(), Agreed { host, remote });
+            }
+            None => {
+                self.files.remove(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Carry the box's version of `path`, or its absence, to the host.
+    async fn pull(&mut self, path: &str, remote: Option<String>) -> BoxliteResult<()> {
+        let host = self.root.join(path);
+        let Some(remote) = remote else {
+            match std::fs::remove_file(&host) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(storage_error(&host, e));
+                }
+                _ => {}
+            }
+            self.touched(path);
+            self.files.remove(path);
+            return Ok(());
+        };
+        if let Some(parent) = host.parent() {
+            create_dir(parent)?;
+        }
+        let temp = temp_path(&host);
+        self.remote.download(path, &temp).await?;
+        std::fs::rename(&temp, &host).map_err(|e| storage_error(&host, e))?;
+        self.pulled(path, remote).await
+    }
+
+    /// Settle a file changed on both sides.
+    async fn resolve(
+        &mut self,
+        path: &str,
+        host: String,
+        remote: String,
+    ) -> BoxliteResult<Option<SyncConflict>> {
+        let local_path = self.root.join(path);
+        let temp = temp_path(&local_path);
+        self.remote.download(path, &temp).await?;
+        // Both sides making the same edit is no conflict
+        let same = std::fs::read(&temp).ok() == std::fs::read(&local_path).ok();
+        if same {
+            let _ = std::fs::remove_file(&temp);
+            self.files.insert(path.to_string(), Agreed { host, remote });
+            return Ok(None);
+        }
+
+        match self.policy {
+            ConflictPolicy::HostWins => {
+                let _ = std::fs::remove_file(&temp);
+                self.push(path, Some(host)).await?;
+            }
+            ConflictPolicy::BoxWins => {
+                std::fs::rename(&temp, &local_path).map_err(|e| storage_error(&local_path, e))?;
+                self.pulled(path, remote).await?;
+            }
+            ConflictPolicy::KeepBoth => {
+                // The copy is a new host file, so the next poll sends it to
+                // the box as well
+                let copy = format!("{}{}", local_path.display(), CONFLICT_SUFFIX);
+                std::fs::rename(&temp, &copy).map_err(|e| storage_error(Path::new(&copy), e))?;
+                self.push(path, Some(host)).await?;
+            }
+        }
+        tracing::info!(path, resolution = ?self.policy, "Settled sync conflict");
+        Ok(Some(SyncConflict {
+            path: path.to_string(),
+            resolution: self.policy,
+        }))
+    }
+
+    /// Record a file just written from the box's copy.
+    async fn pulled(&mut self, path: &str, remote: String) -> BoxliteResult<()> {
+        let host_path = self.root.join(path);
+        if let Local::File(host) = local(host_path.clone()).await? {
+            self.files.insert(path.to_string(), Agreed { host, remote });
+        }
+        self.touched(path);
+        Ok(())
+    }
+
+    /// Refresh the snapshot of a path we wrote on the host, so the next
+    /// poll does not take our own write for a host change.
+    fn touched(&mut self, path: &str) {
+        match std::fs::symlink_metadata(self.root.join(path)) {
+            Ok(metadata) => {
+                self.snapshot.insert(path.to_string(), stamp(&metadata));
+            }
+            Err(_) => {
+                self.snapshot.remove(path);
+            }
+        }
+    }
+
+    fn ignored(&self, path: &str) -> bool {
+        path.split('/')
+            .any(|name| self.ignore.iter().any(|ignored| ignored == name))
+    }
+
+    async fn walk(&self) -> BoxliteResult<HashMap<String, Stamp>> {
+        let root = self.root.clone();
+        let ignore = self.ignore.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut entries = HashMap::new();
+            walk(&root, "", &ignore, &mut entries).map(|()| entries)
+        })
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Sync walk task failed: {}", e)))?
+        .map_err(|e| storage_error(&self.root, e))
+    }
+}
+
+fn walk(
+    root: &Path,
+    relative: &str,
+    ignore: &[String],
+    entries: &mut HashMap<String, Stamp>,
+) -> io::Result<()> {
+    for entry in std::fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if ignore.contains(&name) {
+            continue;
+        }
+        let path = if relative.is_empty() {
+            name
+        } else {
+            format!("{}/{}", relative, name)
+        };
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if !metadata.is_dir() && !metadata.is_file() {
+            continue;
+        }
+        entries.insert(path.clone(), stamp(&metadata));
+        if metadata.is_dir() {
+            walk(root, &path, ignore, entries)?;
+        }
+    }
+    Ok(())
+}
+
+fn stamp(metadata: &std::fs::Metadata) -> Stamp {
+    let mode = metadata.permissions().mode() & 0o7777;
+    // A directory's mtime moves with every change to its entries, which
+    // are compared on their own
+    if metadata.is_dir() {
+        return Stamp {
+            dir: true,
+            len: 0,
+            mode,
+            mtime: None,
+        };
+    }
+    Stamp {
+        dir: false,
+        len: metadata.len(),
+        mode,
+        mtime: metadata.modified().ok(),
+    }
+}
+
+/// Look at `path` on the host, hashing it if it is a file.
+async fn local(path: PathBuf) -> BoxliteResult<Local> {
+    tokio::task::spawn_blocking(move || {
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Local::Missing),
+            Err(e) => return Err(storage_error(&path, e)),
+        };
+        let mode = metadata.permissions().mode() & 0o7777;
+        if metadata.is_dir() {
+            return Ok(Local::Dir(mode));
+        }
+        if !metadata.is_file() {
+            return Ok(Local::Other);
+        }
+        let mut file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Local::Missing),
+            Err(e) => return Err(storage_error(&path, e)),
+        };
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).map_err(|e| storage_error(&path, e))?;
+        Ok(Local::File(format!("{:o}:{:x}", mode, hasher.finalize())))
+    })
+    .await
+    .map_err(|e| BoxliteError::Internal(format!("Sync hash task failed: {}", e)))?
+}
+
+fn mode_of(path: &Path) -> u32 {
+    std::fs::metadata(path).map_or(0o755, |m| m.permissions().mode() & 0o7777)
+}
+
+fn create_dir(path: &Path) -> BoxliteResult<()> {
+    std::fs::create_dir_all(path).map_err(|e| storage_error(path, e))
+}
+
+/// Where a download lands before it replaces `path`; beside it, so the
+/// rename cannot cross filesystems.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.boxlite-sync", name))
+}
+
+fn storage_error(path: &Path, e: io::Error) -> BoxliteError {
+    BoxliteError::Storage(format!("Failed to sync {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A box directory that is a plain host directory.
+    struct FakeRemote {
+        root: PathBuf,
+    }
+
+    impl Remote for FakeRemote {
+        async fn digests(&self, paths: &[String]) -> BoxliteResult<Vec<Option<String>>> {
+            Ok(paths
+                .iter()
+                .map(|path| {
+                    let path = self.root.join(path);
+                    if path.is_dir() {
+                        return Some("dir".to_string());
+                    }
+                    std::fs::read(path)
+                        .ok()
+                        .map(|content| format!("{:x}", Sha256::digest(content)))
+                })
+                .collect())
+        }
+
+        async fn upload(&self, src: &Path, dest: &str) -> BoxliteResult<()> {
+            std::fs::copy(src, self.root.join(dest)).unwrap();
+            Ok(())
+        }
+
+        async fn download(&self, src: &str, dest: &Path) -> BoxliteResult<()> {
+            std::fs::copy(self.root.join(src), dest).unwrap();
+            Ok(())
+        }
+
+        async fn remove(&self, path: &str) -> BoxliteResult<()> {
+            let path = self.root.join(path);
+            let removed = if path.is_dir() {
+                std::fs::remove_dir(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            removed.map_err(|e| BoxliteError::Storage(e.to_string()))
+        }
+
+        async fn make_dir(&self, path: &str, _mode: u32) -> BoxliteResult<()> {
+            std::fs::create_dir_all(self.root.join(path)).unwrap();
+            Ok(())
+        }
+    }
+
+    fn engine(host: &Path, remote: &Path, policy: ConflictPolicy) -> Engine<FakeRemote> {
+        let mut spec = SyncSpec::new(host, "/work");
+        spec.conflict = policy;
+        spec.ignore = vec!["target".to_string()];
+        Engine::new(
+            FakeRemote {
+                root: remote.to_path_buf(),
+            },
+            spec,
+        )
+    }
+
+    fn dirty(paths: &[(&str, bool)]) -> BTreeMap<String, bool> {
+        paths.iter().map(|&(p, d)| (p.to_string(), d)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_changes_follow_the_side_that_made_them() {
+        let host = tempfile::tempdir().unwrap();
+        let remote = tempfile::tempdir().unwrap();
+        std::fs::create_dir(host.path().join("src")).unwrap();
+        std::fs::write(host.path().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::create_dir(host.path().join("target")).unwrap();
+        std::fs::write(host.path().join("README"), "hi").unwrap();
+        let mut engine = engine(host.path(), remote.path(), ConflictPolicy::KeepBoth);
+        engine.initial().await.unwrap();
+        assert!(remote.path().join("src/main.rs").is_file());
+        assert!(!remote.path().join("target").exists());
+
+        // Box edits and creates; the host deletes
+        std::fs::write(remote.path().join("src/main.rs"), "fn main() { run() }").unwrap();
+        std::fs::create_dir(remote.path().join("out")).unwrap();
+        std::fs::write(remote.path().join("out/log"), "built").unwrap();
+        std::fs::remove_file(host.path().join("README")).unwrap();
+        let mut changes = engine.host_changes().await.unwrap();
+        changes.extend(dirty(&[
+            ("src/main.rs", false),
+            ("out", true),
+            ("out/log", false),
+        ]));
+        let conflicts = engine.reconcile(changes).await.unwrap();
+
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(host.path().join("src/main.rs")).unwrap(),
+            "fn main() { run() }"
+        );
+        assert_eq!(
+            std::fs::read_to_string(host.path().join("out/log")).unwrap(),
+            "built"
+        );
+        assert!(!remote.path().join("README").exists());
+        // Our own writes are not mistaken for host edits
+        assert!(engine.host_changes().await.unwrap().is_empty());
+
+        // Removing a directory on the box removes it on the host
+        std::fs::remove_file(remote.path().join("out/log")).unwrap();
+        std::fs::remove_dir(remote.path().join("out")).unwrap();
+        engine
+            .reconcile(dirty(&[("out", true), ("out/log", false)]))
+            .await
+            .unwrap();
+        assert!(!host.path().join("out").exists());
+    }
+
+    #[tokio::test]
+    async fn test_conflicts_keep_both_versions() {
+        let host = tempfile::tempdir().unwrap();
+        let remote = tempfile::tempdir().unwrap();
+        std::fs::write(host.path().join("a.txt"), "base").unwrap();
+        std::fs::write(host.path().join("b.txt"), "base").unwrap();
+        let mut engine = engine(host.path(), remote.path(), ConflictPolicy::KeepBoth);
+        engine.initial().await.unwrap();
+
+        std::fs::write(host.path().join("a.txt"), "host edit").unwrap();
+        std::fs::write(remote.path().join("a.txt"), "box edit").unwrap();
+        // The same edit on both sides agrees
+        std::fs::write(host.path().join("b.txt"), "same").unwrap();
+        std::fs::write(remote.path().join("b.txt"), "same").unwrap();
+        let conflicts = engine
+            .reconcile(dirty(&[("a.txt", false), ("b.txt", false)]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            conflicts,
+            vec![SyncConflict {
+                path: "a.txt".to_string(),
+                resolution: ConflictPolicy::KeepBoth,
+            }]
+        );
+        let read = |dir: &Path, name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read(host.path(), "a.txt"), "host edit");
+        assert_eq!(read(remote.path(), "a.txt"), "host edit");
+        assert_eq!(read(host.path(), "a.txt.conflict-box"), "box edit");
+        // The kept copy reaches the box on the next poll
+        let next = engine.host_changes().await.unwrap();
+        engine.reconcile(next).await.unwrap();
+        assert_eq!(read(remote.path(), "a.txt.conflict-box"), "box edit");
+    }
+}
@@ -0,0 +1,41 @@
+//! Periodic task status types.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Outcome of a periodic task's runs, as reported by the guest agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskStatus {
+    pub name: String,
+    pub interval: Duration,
+    /// Completed runs, including failed ones.
+    pub runs: u64,
+    /// Runs that exited non-zero, timed out, or failed to start.
+    pub failures: u64,
+    /// Exit code of the last run (128 + signal if killed). `None` before
+    /// the first run or if the last run failed to start.
+    pub last_exit_code: Option<i32>,
+    /// When the last run started.
+    pub last_run: Option<SystemTime>,
+    pub last_duration: Duration,
+    /// Tail of the last run's combined stdout and stderr.
+    pub last_output: String,
+    /// Why the last run failed to start or was killed.
+    pub last_error: Option<String>,
+}
+
+impl From<boxlite_shared::TaskStatus> for TaskStatus {
+    fn from(status: boxlite_shared::TaskStatus) -> Self {
+        Self {
+            name: status.name,
+            interval: Duration::from_millis(status.interval_ms),
+            runs: status.runs,
+            failures: status.failures,
+            last_exit_code: status.last_exit_code,
+            last_run: (status.runs > 0)
+                .then(|| UNIX_EPOCH + Duration::from_millis(status.last_run_unix_ms)),
+            last_duration: Duration::from_millis(status.last_duration_ms),
+            last_output: status.last_output,
+            last_error: (!status.last_error.is_empty()).then_some(status.last_error),
+        }
+    }
+}
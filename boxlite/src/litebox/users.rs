@@ -0,0 +1,73 @@
+//! Container user account types.
+
+/// Where a user's home directory lives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HomeStorage {
+    /// Directory in the container rootfs; persists with the box's disk.
+    #[default]
+    Rootfs,
+    /// tmpfs mounted at the home path; discarded when the user is deleted
+    /// or the box restarts. Suited to per-session users.
+    Tmpfs,
+}
+
+/// User account to create in a box.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use boxlite::{HomeStorage, UserSpec};
+/// let user = UserSpec::new("session1", 2001)
+///     .shell("/bin/bash")
+///     .home_storage(HomeStorage::Tmpfs);
+/// ```
+#[derive(Clone, Debug)]
+pub struct UserSpec {
+    pub(crate) name: String,
+    pub(crate) uid: u32,
+    pub(crate) gid: Option<u32>,
+    pub(crate) shell: Option<String>,
+    pub(crate) home: Option<String>,
+    pub(crate) home_storage: HomeStorage,
+}
+
+impl UserSpec {
+    /// Create a spec for `name` with the given UID.
+    ///
+    /// Defaults: primary group = UID (a personal group is created if it
+    /// doesn't exist), shell `/bin/sh`, home `/home/{name}` in the rootfs.
+    pub fn new(name: impl Into<String>, uid: u32) -> Self {
+        Self {
+            name: name.into(),
+            uid,
+            gid: None,
+            shell: None,
+            home: None,
+            home_storage: HomeStorage::default(),
+        }
+    }
+
+    /// Set the primary group ID.
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Set the login shell.
+    pub fn shell(mut self, shell: impl Into<String>) -> Self {
+        self.shell = Some(shell.into());
+        self
+    }
+
+    /// Set the home directory path.
+    pub fn home(mut self, home: impl Into<String>) -> Self {
+        self.home = Some(home.into());
+        self
+    }
+
+    /// Set where the home directory is stored.
+    pub fn home_storage(mut self, storage: HomeStorage) -> Self {
+        self.home_storage = storage;
+        self
+    }
+}
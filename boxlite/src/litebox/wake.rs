@@ -0,0 +1,124 @@
+//! Host sleep and wall-clock jump detection.
+//!
+//! A guest's clock only advances while its vCPUs run, so after the host
+//! sleeps (a laptop lid closing) the guest wakes up behind, its network
+//! backend may have forgotten where it is, and forwarded ports stop
+//! reaching it. Each running box compares how far the host wall clock
+//! moved against the monotonic clock, which stands still while the host
+//! sleeps; when they disagree by more than [`JUMP_THRESHOLD`] the host
+//! slept or its clock was set, and the box sends the guest a Wake request.
+//! The guest steps its clock, re-applies its network lease and announces
+//! its address; the host then checks that each forwarded port still
+//! accepts connections and emits a `woke` event.
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::ports::ForwardedPort;
+
+/// How often a box checks for clock jumps.
+pub(crate) const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Smallest disagreement between the wall and monotonic clocks treated as
+/// a jump; ordinary NTP slewing stays well below it.
+const JUMP_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// How long a forwarded port gets to accept a connection.
+const PORT_CHECK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Wall and monotonic readings of the previous check.
+pub(crate) struct ClockWatch {
+    wall: SystemTime,
+    monotonic: Instant,
+}
+
+impl ClockWatch {
+    pub(crate) fn new() -> Self {
+        Self {
+            wall: SystemTime::now(),
+            monotonic: Instant::now(),
+        }
+    }
+
+    /// How far the wall clock jumped since the last check, in milliseconds
+    /// (negative if it went back), if it did.
+    pub(crate) fn check(&mut self) -> Option<i64> {
+        self.check_at(SystemTime::now(), Instant::now())
+    }
+
+    fn check_at(&mut self, wall: SystemTime, monotonic: Instant) -> Option<i64> {
+        let wall_ms = match wall.duration_since(self.wall) {
+            Ok(forward) => forward.as_millis() as i64,
+            Err(e) => -(e.duration().as_millis() as i64),
+        };
+        let monotonic_ms = monotonic.duration_since(self.monotonic).as_millis() as i64;
+        self.wall = wall;
+        self.monotonic = monotonic;
+
+        let jump_ms = wall_ms - monotonic_ms;
+        (jump_ms.unsigned_abs() >= JUMP_THRESHOLD.as_millis() as u64).then_some(jump_ms)
+    }
+}
+
+/// The forwarded ports whose host side no longer accepts connections.
+pub(crate) fn stale_ports(ports: &[ForwardedPort]) -> Vec<ForwardedPort> {
+    ports
+        .iter()
+        .filter(|port| {
+            let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port.host_port));
+            TcpStream::connect_timeout(&addr, PORT_CHECK_TIMEOUT).is_err()
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_jumps() {
+        let mut watch = ClockWatch::new();
+        let (wall, monotonic) = (watch.wall, watch.monotonic);
+
+        // Both clocks moved alike
+        let step = Duration::from_secs(5);
+        assert_eq!(watch.check_at(wall + step, monotonic + step), None);
+
+        // The host slept for an hour: only the wall clock moved
+        let slept = Duration::from_secs(3600);
+        assert_eq!(
+            watch.check_at(wall + step * 2 + slept, monotonic + step * 2),
+            Some(3_600_000)
+        );
+
+        // The wall clock was set back
+        assert_eq!(
+            watch.check_at(wall + step * 3, monotonic + step * 3),
+            Some(-3_600_000)
+        );
+    }
+
+    #[test]
+    fn test_stale_ports() {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let live = listener.local_addr().unwrap().port();
+        let closed = {
+            let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let ports = [
+            ForwardedPort {
+                host_port: live,
+                guest_port: 80,
+            },
+            ForwardedPort {
+                host_port: closed,
+                guest_port: 443,
+            },
+        ];
+        let stale = stale_ports(&ports);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].host_port, closed);
+    }
+}
@@ -0,0 +1,63 @@
+//! File watching through the guest agent.
+
+use boxlite_shared::errors::BoxliteResult;
+use boxlite_shared::{PathChangeKind as ProtoPathChangeKind, WatchPathEvent};
+
+/// What happened to a path, net over one batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    /// The guest kernel dropped events; rescan the watched path.
+    Overflow,
+}
+
+/// A change under a watched path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathChange {
+    /// Relative to the watched path; empty for the path itself.
+    pub path: String,
+    pub kind: PathChangeKind,
+    pub is_dir: bool,
+}
+
+impl From<boxlite_shared::PathChange> for PathChange {
+    fn from(change: boxlite_shared::PathChange) -> Self {
+        let kind = match ProtoPathChangeKind::try_from(change.kind) {
+            Ok(ProtoPathChangeKind::PathChangeCreated) => PathChangeKind::Created,
+            Ok(ProtoPathChangeKind::PathChangeDeleted) => PathChangeKind::Deleted,
+            Ok(ProtoPathChangeKind::PathChangeOverflow) => PathChangeKind::Overflow,
+            _ => PathChangeKind::Modified,
+        };
+        Self {
+            path: change.path,
+            kind,
+            is_dir: change.is_dir,
+        }
+    }
+}
+
+/// A watch started by [`crate::LiteBox::watch`].
+///
+/// Changes arrive in batches once the path has been quiet for the debounce
+/// period. The watch ends when it is dropped, the path is removed, or the
+/// box stops.
+pub struct PathWatch {
+    events: tonic::Streaming<WatchPathEvent>,
+}
+
+impl PathWatch {
+    pub(crate) fn new(events: tonic::Streaming<WatchPathEvent>) -> Self {
+        Self { events }
+    }
+
+    /// The next batch of changes, or `None` once the watch has ended.
+    pub async fn next(&mut self) -> BoxliteResult<Option<Vec<PathChange>>> {
+        Ok(self
+            .events
+            .message()
+            .await?
+            .map(|event| event.changes.into_iter().map(PathChange::from).collect()))
+    }
+}
@@ -19,7 +19,7 @@ use super::{lock_already_allocated, lock_exhausted, lock_invalid, lock_not_alloc
 /// # Example
 ///
 /// ```
-/// use boxlite::lock::InMemoryLockManager;
+/// use boxlite::lock::{InMemoryLockManager, LockManager};
 ///
 /// let manager = InMemoryLockManager::new(16);
 /// let lock_id = manager.allocate().expect("allocate lock");
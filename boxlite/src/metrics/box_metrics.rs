@@ -1,5 +1,6 @@
 //! Per-box metrics (individual LiteBox statistics).
 
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Storage for per-box metrics.
@@ -16,6 +17,8 @@ pub struct BoxMetricsStorage {
     pub(crate) bytes_sent: AtomicU64,
     /// Bytes received from this box (via stdout/stderr)
     pub(crate) bytes_received: AtomicU64,
+    /// Exec output chunks dropped for consumers that fell behind
+    pub(crate) output_dropped: Arc<AtomicU64>,
 
     // Timing metrics (set once, never change)
     /// Total time from create() call to LiteBox ready (includes all stages)
@@ -36,6 +39,21 @@ pub struct BoxMetricsStorage {
     pub(crate) stage_box_spawn_ms: Option<u128>,
     /// Time to initialize container inside guest (Stage 6)
     pub(crate) stage_container_init_ms: Option<u128>,
+    /// Guest boot stages reported by the guest agent
+    pub(crate) guest_stages: Vec<GuestStageTiming>,
+}
+
+/// Duration of one boot stage inside the guest.
+///
+/// Reported by the guest agent for its startup (e.g. `tmpfs`, `layout`,
+/// and `agent` from its process starting to serving) and Guest.Init (e.g. `volumes`, `network`, `kernel_modules`) stages.
+/// Independent stages run concurrently, so durations can overlap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuestStageTiming {
+    /// Stage name
+    pub name: String,
+    /// Time the stage took (milliseconds)
+    pub duration_ms: u128,
 }
 
 impl Clone for BoxMetricsStorage {
@@ -45,6 +63,7 @@ impl Clone for BoxMetricsStorage {
             exec_errors: AtomicU64::new(self.exec_errors.load(Ordering::Relaxed)),
             bytes_sent: AtomicU64::new(self.bytes_sent.load(Ordering::Relaxed)),
             bytes_received: AtomicU64::new(self.bytes_received.load(Ordering::Relaxed)),
+            output_dropped: Arc::new(AtomicU64::new(self.output_dropped.load(Ordering::Relaxed))),
             total_create_duration_ms: self.total_create_duration_ms,
             guest_boot_duration_ms: self.guest_boot_duration_ms,
             stage_filesystem_setup_ms: self.stage_filesystem_setup_ms,
@@ -53,6 +72,7 @@ impl Clone for BoxMetricsStorage {
             stage_box_config_ms: self.stage_box_config_ms,
             stage_box_spawn_ms: self.stage_box_spawn_ms,
             stage_container_init_ms: self.stage_container_init_ms,
+            guest_stages: self.guest_stages.clone(),
         }
     }
 }
@@ -105,6 +125,11 @@ impl BoxMetricsStorage {
         self.stage_container_init_ms = Some(duration_ms);
     }
 
+    /// Set guest boot stage timings.
+    pub(crate) fn set_guest_stages(&mut self, stages: Vec<GuestStageTiming>) {
+        self.guest_stages = stages;
+    }
+
     /// Log init stage durations for debugging.
     pub(crate) fn log_init_stages(&self) {
         tracing::debug!(
@@ -115,6 +140,7 @@ impl BoxMetricsStorage {
             stage_box_config_ms = self.stage_box_config_ms.unwrap_or(0),
             stage_box_spawn_ms = self.stage_box_spawn_ms.unwrap_or(0),
             stage_container_init_ms = self.stage_container_init_ms.unwrap_or(0),
+            guest_stages = ?self.guest_stages,
             "Box initialization stages completed"
         );
     }
@@ -156,6 +182,8 @@ pub struct BoxMetrics {
     pub bytes_sent_total: u64,
     /// Bytes received from this box (via stdout/stderr)
     pub bytes_received_total: u64,
+    /// Exec output chunks dropped for consumers that fell behind
+    pub output_chunks_dropped_total: u64,
     /// Total time from create() call to LiteBox ready (milliseconds)
     pub total_create_duration_ms: Option<u128>,
     /// Time from box subprocess spawn to guest agent ready (milliseconds)
@@ -186,6 +214,8 @@ pub struct BoxMetrics {
     pub stage_box_spawn_ms: Option<u128>,
     /// Time to initialize container inside guest (milliseconds)
     pub stage_container_init_ms: Option<u128>,
+    /// Guest boot stages, in completion order
+    pub guest_stages: Vec<GuestStageTiming>,
 }
 
 impl BoxMetrics {
@@ -204,6 +234,7 @@ impl BoxMetrics {
             exec_errors_total: storage.exec_errors.load(Ordering::Relaxed),
             bytes_sent_total: storage.bytes_sent.load(Ordering::Relaxed),
             bytes_received_total: storage.bytes_received.load(Ordering::Relaxed),
+            output_chunks_dropped_total: storage.output_dropped.load(Ordering::Relaxed),
             total_create_duration_ms: storage.total_create_duration_ms,
             guest_boot_duration_ms: storage.guest_boot_duration_ms,
             cpu_percent,
@@ -218,6 +249,7 @@ impl BoxMetrics {
             stage_box_config_ms: storage.stage_box_config_ms,
             stage_box_spawn_ms: storage.stage_box_spawn_ms,
             stage_container_init_ms: storage.stage_container_init_ms,
+            guest_stages: storage.guest_stages.clone(),
         }
     }
 
@@ -251,6 +283,14 @@ impl BoxMetrics {
         self.bytes_received_total
     }
 
+    /// Total stdout/stderr chunks dropped because the consumer fell behind.
+    ///
+    /// Counts evictions under `OverflowPolicy::DropOldest` (see
+    /// `BoxOptions::output_buffer`). Never decreases (monotonic counter).
+    pub fn output_chunks_dropped_total(&self) -> u64 {
+        self.output_chunks_dropped_total
+    }
+
     /// Total time from create() call to box ready (milliseconds).
     ///
     /// Includes all initialization stages: filesystem setup, image pull,
@@ -364,4 +404,12 @@ impl BoxMetrics {
     pub fn stage_container_init_ms(&self) -> Option<u128> {
         self.stage_container_init_ms
     }
+
+    /// Boot stages inside the guest, in completion order.
+    ///
+    /// Breaks down the guest side of initialization (agent startup,
+    /// volume mounts, network, drivers). Empty for reattached boxes.
+    pub fn guest_stages(&self) -> &[GuestStageTiming] {
+        &self.guest_stages
+    }
 }
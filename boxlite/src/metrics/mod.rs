@@ -12,7 +12,7 @@
 //! # Example
 //!
 //! ```rust,no_run
-//! use boxlite_runtime::BoxliteRuntime;
+//! use boxlite::BoxliteRuntime;
 //!
 //! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
 //! let runtime = BoxliteRuntime::new(Default::default())?;
@@ -23,7 +23,7 @@
 //! println!("Running boxes: {}", rt_metrics.num_running_boxes());
 //!
 //! // Level 2: Per-box metrics
-//! let (box_id, litebox) = runtime.create(Default::default()).await?;
+//! let litebox = runtime.create(Default::default(), None)?;
 //! let box_metrics = litebox.metrics().await?;
 //! println!("Box boot time: {}ms", box_metrics.guest_boot_duration_ms().unwrap_or(0));
 //! # Ok(())
@@ -33,5 +33,5 @@
 mod box_metrics;
 mod runtime_metrics;
 
-pub use box_metrics::{BoxMetrics, BoxMetricsStorage};
+pub use box_metrics::{BoxMetrics, BoxMetricsStorage, GuestStageTiming};
 pub use runtime_metrics::{RuntimeMetrics, RuntimeMetricsStorage};
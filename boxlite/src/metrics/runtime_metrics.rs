@@ -17,6 +17,8 @@ pub struct RuntimeMetricsStorage {
     pub(crate) total_commands: Arc<AtomicU64>,
     /// Total command execution errors across all boxes
     pub(crate) total_exec_errors: Arc<AtomicU64>,
+    /// Lifecycle events live subscribers missed by falling behind
+    pub(crate) events_dropped: Arc<AtomicU64>,
 }
 
 impl RuntimeMetricsStorage {
@@ -84,4 +86,12 @@ impl RuntimeMetrics {
     pub fn total_exec_errors(&self) -> u64 {
         self.storage.total_exec_errors.load(Ordering::Relaxed)
     }
+
+    /// Total lifecycle events dropped for subscribers that fell behind.
+    ///
+    /// Counts events skipped under `OverflowPolicy::DropOldest`, summed
+    /// over subscribers. Never decreases (monotonic counter).
+    pub fn events_dropped_total(&self) -> u64 {
+        self.storage.events_dropped.load(Ordering::Relaxed)
+    }
 }
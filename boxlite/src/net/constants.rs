@@ -13,6 +13,13 @@ pub const GATEWAY_IP: &str = "192.168.127.1";
 /// Guest IP address (assigned via DHCP static lease)
 pub const GUEST_IP: &str = "192.168.127.2";
 
+/// Host alias address
+///
+/// Connections from the guest to this address reach the host's loopback,
+/// where forwarded ports of all boxes listen. Only routed for boxes of a
+/// runtime with box DNS on, and only to the ports boxes in the zone forward.
+pub const HOST_IP: &str = "192.168.127.254";
+
 /// Gateway MAC address
 ///
 /// This MAC is used by gvproxy's virtual network interface.
@@ -42,6 +49,9 @@ pub const DNS_SERVER_IP: &str = GATEWAY_IP;
 /// DNS search domains
 pub const DNS_SEARCH_DOMAINS: &[&str] = &["local"];
 
+/// Zone box names are resolved in (see `BoxliteOptions::dns`)
+pub const BOX_DNS_ZONE: &str = "box";
+
 /// Helper function to format MAC address as string
 pub fn mac_to_string(mac: &[u8; 6]) -> String {
     format!(
@@ -0,0 +1,441 @@
+//! DNS names for boxes in the `box.` zone.
+//!
+//! With `BoxliteOptions::dns` set, the runtime answers unicast DNS on its
+//! `listen` address for `<name>.box`, for each running named box, and
+//! `<service>.<network>.box`, for the services a box offers in the network
+//! it joined with `BoxOptions::dns_network`. Names resolve to the host's
+//! loopback address, where boxes' forwarded ports listen; other names in
+//! the zone get NXDOMAIN, and names outside it are refused. Point the host
+//! resolver at it for the zone (`/etc/resolver/box` on macOS, a
+//! systemd-resolved `Domains=~box` drop-in on Linux).
+//!
+//! Guests search `box` and resolve the zone through their network backend,
+//! which answers every name in it with the host alias address; connections
+//! to that address reach the host's loopback, so `web.box:8080` in one box
+//! reaches the port another box `web` forwards to 8080 on the host. The
+//! backend's zone is fixed when a box starts, so in guests a name with no
+//! box behind it resolves too, and the connection is refused instead.
+//!
+//! Only the host ports forwarded by boxes answered in the zone can be
+//! reached through the alias; other services on the host's loopback stay
+//! out of reach. The runtime pushes that set to the shim of every box with
+//! a network each time a box comes or goes.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use super::constants::BOX_DNS_ZONE;
+use super::firewall::APPLIED;
+use super::mdns::{Query, parse_query, write_name};
+use crate::runtime::options::DnsOptions;
+
+/// Boxes come and go, so answers are not cached for long.
+const TTL_SECS: u32 = 5;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const RCODE_FORMERR: u16 = 1;
+const RCODE_NXDOMAIN: u16 = 3;
+const RCODE_REFUSED: u16 = 5;
+
+/// Answered names, lowercased with the zone suffix, and how many boxes
+/// hold each.
+type Names = Arc<Mutex<HashMap<String, usize>>>;
+
+/// New host ports for the shims listening on each socket.
+type Update = (Vec<PathBuf>, Vec<u16>);
+
+/// Host ports forwarded by boxes answered in the zone, and the shims
+/// letting their guests reach them through the host alias, by
+/// registration.
+#[derive(Default)]
+struct Routes {
+    next_id: u64,
+    ports: HashMap<u64, Vec<u16>>,
+    links: HashMap<u64, PathBuf>,
+    updates: Option<Sender<Update>>,
+}
+
+impl Routes {
+    fn host_ports(&self) -> Vec<u16> {
+        let mut ports: Vec<u16> = self.ports.values().flatten().copied().collect();
+        ports.sort_unstable();
+        ports.dedup();
+        ports
+    }
+
+    /// Queue the current host ports for every shim.
+    fn publish(&self) {
+        if let Some(updates) = &self.updates
+            && !self.links.is_empty()
+        {
+            let _ = updates.send((self.links.values().cloned().collect(), self.host_ports()));
+        }
+    }
+}
+
+/// The running DNS server of a runtime.
+pub(crate) struct BoxDns {
+    names: Names,
+    routes: Arc<Mutex<Routes>>,
+}
+
+/// Keeps a box's names answered, and its ports reachable, until dropped.
+pub(crate) struct DnsRegistration {
+    names: Names,
+    held: Vec<String>,
+    routes: Arc<Mutex<Routes>>,
+    id: u64,
+}
+
+impl BoxDns {
+    /// Serve the zone on `options.listen`.
+    pub(crate) fn start(options: &DnsOptions) -> BoxliteResult<Self> {
+        let socket = UdpSocket::bind(options.listen)
+            // Wake up now and then to notice the runtime is gone
+            .and_then(|socket| {
+                socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+                Ok(socket)
+            })
+            .map_err(|e| {
+                BoxliteError::Network(format!(
+                    "Failed to serve DNS on {}: {}",
+                    options.listen, e
+                ))
+            })?;
+
+        let names = Names::default();
+        let weak = Arc::downgrade(&names);
+        std::thread::Builder::new()
+            .name("boxlite-dns".to_string())
+            .spawn(move || serve(socket, weak))
+            .map_err(|e| BoxliteError::Internal(format!("Failed to start DNS server: {}", e)))?;
+
+        let (updates, pending) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("boxlite-dns-routes".to_string())
+            .spawn(move || push_updates(pending))
+            .map_err(|e| BoxliteError::Internal(format!("Failed to start DNS server: {}", e)))?;
+        let routes = Routes {
+            updates: Some(updates),
+            ..Default::default()
+        };
+
+        tracing::info!(listen = %options.listen, "DNS server started");
+        Ok(Self {
+            names,
+            routes: Arc::new(Mutex::new(routes)),
+        })
+    }
+
+    /// Host ports guests may reach through the host alias right now.
+    pub(crate) fn host_ports(&self) -> Vec<u16> {
+        self.routes.lock().unwrap().host_ports()
+    }
+
+    /// Answer for `names` (see [`box_names`]) and let guests reach
+    /// `host_ports` until the registration is dropped. The shim taking host
+    /// port changes on `link`, if any, is kept up to date meanwhile.
+    pub(crate) fn register(
+        &self,
+        names: Vec<String>,
+        host_ports: Vec<u16>,
+        link: Option<PathBuf>,
+    ) -> DnsRegistration {
+        let mut known = self.names.lock().unwrap();
+        for name in &names {
+            *known.entry(name.clone()).or_default() += 1;
+        }
+        tracing::debug!(names = ?names, ports = ?host_ports, "Answering box names over DNS");
+
+        let mut routes = self.routes.lock().unwrap();
+        let id = routes.next_id;
+        routes.next_id += 1;
+        let changed = !host_ports.is_empty() || link.is_some();
+        if !host_ports.is_empty() {
+            routes.ports.insert(id, host_ports);
+        }
+        if let Some(link) = link {
+            routes.links.insert(id, link);
+        }
+        if changed {
+            routes.publish();
+        }
+        DnsRegistration {
+            names: Arc::clone(&self.names),
+            held: names,
+            routes: Arc::clone(&self.routes),
+            id,
+        }
+    }
+}
+
+impl Drop for DnsRegistration {
+    fn drop(&mut self) {
+        let mut names = self.names.lock().unwrap();
+        for name in &self.held {
+            if let Some(count) = names.get_mut(name) {
+                *count -= 1;
+                if *count == 0 {
+                    names.remove(name);
+                }
+            }
+        }
+
+        let mut routes = self.routes.lock().unwrap();
+        routes.links.remove(&self.id);
+        if routes.ports.remove(&self.id).is_some() {
+            routes.publish();
+        }
+    }
+}
+
+/// Hand queued host ports to the shims, in order, until the runtime is
+/// gone.
+fn push_updates(pending: Receiver<Update>) {
+    for (links, ports) in pending {
+        for link in links {
+            if let Err(e) = push(&link, &ports) {
+                tracing::warn!(
+                    socket = %link.display(),
+                    error = %e,
+                    "Failed to update the host ports of a box"
+                );
+            }
+        }
+    }
+}
+
+/// Have the shim serving `link` let its guest reach only `ports`.
+fn push(link: &Path, ports: &[u16]) -> std::io::Result<()> {
+    let stream = UnixStream::connect(link)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request = serde_json::to_string(ports)?;
+    request.push('\n');
+    (&stream).write_all(request.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    match reply.trim() {
+        APPLIED => Ok(()),
+        error => Err(std::io::Error::other(error.to_string())),
+    }
+}
+
+/// The names a box is answerable as: `<name>.box`, and
+/// `<service>.<network>.box` for each of `services` (the box name if there
+/// are none) once it joined `network`.
+pub(crate) fn box_names(
+    name: Option<&str>,
+    network: Option<&str>,
+    services: &[String],
+) -> Vec<String> {
+    let mut names: Vec<String> = name
+        .map(|name| format!("{}.{}", name, BOX_DNS_ZONE))
+        .into_iter()
+        .collect();
+    if let Some(network) = network {
+        let services = if services.is_empty() {
+            name.into_iter().collect()
+        } else {
+            services.iter().map(String::as_str).collect::<Vec<_>>()
+        };
+        for service in services {
+            names.push(format!("{}.{}.{}", service, network, BOX_DNS_ZONE));
+        }
+    }
+    for name in &mut names {
+        name.make_ascii_lowercase();
+    }
+    names
+}
+
+/// Whether `label` can be one label of a box name: letters, digits and
+/// inner hyphens, at most 63 of them.
+pub(crate) fn is_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+}
+
+fn serve(socket: UdpSocket, names: Weak<Mutex<HashMap<String, usize>>>) {
+    let mut buf = [0u8; 512];
+    loop {
+        let received = socket.recv_from(&mut buf);
+        let Some(names) = names.upgrade() else {
+            return;
+        };
+        let Ok((len, from)) = received else {
+            continue;
+        };
+        let Some(query) = parse_query(&buf[..len]) else {
+            continue;
+        };
+        let reply = respond(&query, &names.lock().unwrap());
+        if let Err(e) = socket.send_to(&reply, from) {
+            tracing::debug!(error = %e, "Failed to send DNS answer");
+        }
+    }
+}
+
+/// The reply to `query`, answering its first question from `known`.
+fn respond(query: &Query, known: &HashMap<String, usize>) -> Vec<u8> {
+    let Some(question) = query.questions.first() else {
+        return header(query.id, RCODE_FORMERR, 0, 0);
+    };
+    let name = question.name.trim_end_matches('.').to_ascii_lowercase();
+    let in_zone = name == BOX_DNS_ZONE || name.ends_with(&format!(".{}", BOX_DNS_ZONE));
+    let (rcode, answers) = if !in_zone {
+        (RCODE_REFUSED, 0)
+    } else if known.contains_key(&name) {
+        (0, matches!(question.qtype, TYPE_A | TYPE_ANY).into())
+    } else if name == BOX_DNS_ZONE {
+        (0, 0)
+    } else {
+        (RCODE_NXDOMAIN, 0)
+    };
+
+    let mut packet = header(query.id, rcode, 1, answers);
+    write_name(&mut packet, &question.name);
+    packet.extend_from_slice(&question.qtype.to_be_bytes());
+    packet.extend_from_slice(&question.qclass.to_be_bytes());
+    if answers > 0 {
+        write_name(&mut packet, &question.name);
+        packet.extend_from_slice(&TYPE_A.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&TTL_SECS.to_be_bytes());
+        packet.extend_from_slice(&4u16.to_be_bytes());
+        packet.extend_from_slice(&Ipv4Addr::LOCALHOST.octets());
+    }
+    packet
+}
+
+/// An authoritative response header.
+fn header(id: u16, rcode: u16, questions: u16, answers: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&(0x8400 | rcode).to_be_bytes());
+    for count in [questions, answers, 0, 0] {
+        packet.extend_from_slice(&count.to_be_bytes());
+    }
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, qtype: u16) -> Query {
+        let mut packet = vec![0xab, 0xcd, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        write_name(&mut packet, name);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        parse_query(&packet).unwrap()
+    }
+
+    #[test]
+    fn test_respond() {
+        let known = HashMap::from([("web.box".to_string(), 1)]);
+
+        let reply = respond(&query("Web.box.", TYPE_A), &known);
+        assert_eq!(&reply[..2], &[0xab, 0xcd]);
+        assert_eq!(&reply[2..4], &[0x84, 0]);
+        assert_eq!(&reply[6..8], &[0, 1]);
+        assert_eq!(&reply[reply.len() - 4..], &[127, 0, 0, 1]);
+
+        // Known name, but no IPv6 address
+        let reply = respond(&query("web.box", 28), &known);
+        assert_eq!(&reply[2..4], &[0x84, 0]);
+        assert_eq!(&reply[6..8], &[0, 0]);
+
+        let reply = respond(&query("db.box", TYPE_A), &known);
+        assert_eq!(&reply[2..4], &[0x84, RCODE_NXDOMAIN as u8]);
+
+        let reply = respond(&query("example.com", TYPE_A), &known);
+        assert_eq!(&reply[2..4], &[0x84, RCODE_REFUSED as u8]);
+    }
+
+    #[test]
+    fn test_box_names() {
+        assert_eq!(box_names(Some("Web"), None, &[]), ["web.box"]);
+        assert_eq!(
+            box_names(Some("web-1"), Some("shop"), &[]),
+            ["web-1.box", "web-1.shop.box"]
+        );
+        assert_eq!(
+            box_names(None, Some("shop"), &["api".to_string(), "www".to_string()]),
+            ["api.shop.box", "www.shop.box"]
+        );
+        assert!(box_names(None, None, &[]).is_empty());
+
+        assert!(is_label("db-primary"));
+        assert!(!is_label("-db"));
+        assert!(!is_label("db.primary"));
+        assert!(!is_label(""));
+    }
+
+    #[test]
+    fn test_registration_refcount() {
+        let dns = BoxDns {
+            names: Names::default(),
+            routes: Default::default(),
+        };
+        let first = dns.register(box_names(Some("web"), Some("shop"), &[]), vec![], None);
+        let second = dns.register(
+            box_names(None, Some("shop"), &["web".to_string()]),
+            vec![],
+            None,
+        );
+        drop(first);
+        let names = dns.names.lock().unwrap().clone();
+        assert_eq!(names.len(), 1);
+        assert!(names.contains_key("web.shop.box"));
+        drop(second);
+        assert!(dns.names.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_host_ports_follow_registrations() {
+        let (updates, pending) = std::sync::mpsc::channel();
+        let dns = BoxDns {
+            names: Names::default(),
+            routes: Arc::new(Mutex::new(Routes {
+                updates: Some(updates),
+                ..Default::default()
+            })),
+        };
+        let link = PathBuf::from("/boxes/a/sockets/host-ports.sock");
+
+        // A box without names or ports only listens for changes
+        let client = dns.register(vec![], vec![], Some(link.clone()));
+        assert_eq!(pending.try_recv().unwrap(), (vec![link.clone()], vec![]));
+
+        let web = dns.register(box_names(Some("web"), None, &[]), vec![8080, 8443], None);
+        assert_eq!(dns.host_ports(), [8080, 8443]);
+        assert_eq!(
+            pending.try_recv().unwrap(),
+            (vec![link.clone()], vec![8080, 8443])
+        );
+
+        drop(web);
+        assert!(dns.host_ports().is_empty());
+        assert_eq!(pending.try_recv().unwrap(), (vec![link], vec![]));
+
+        // Nobody is left to tell
+        drop(client);
+        let db = dns.register(box_names(Some("db"), None, &[]), vec![5432], None);
+        assert!(pending.try_recv().is_err());
+        drop(db);
+    }
+}
@@ -0,0 +1,345 @@
+//! Per-box firewall rules.
+//!
+//! A box's firewall filters the Ethernet frames crossing its gvproxy link,
+//! inside the shim, so nothing in the guest can lift it. Rules are matched
+//! in order against egress traffic (from the box, by where it goes) and
+//! ingress traffic (to the box, by the box's port and who sent it, which is
+//! the gateway for forwarded ports); the first match decides, and traffic
+//! no rule matches gets the direction's default. Replies on connections a rule let through are let back, so
+//! allowing `tcp:443` with egress denied by default still completes
+//! handshakes. ARP, DHCP and DNS to the gateway are never filtered.
+//!
+//! `BoxOptions::firewall` is the policy a box starts with; the running
+//! box's policy can be replaced or extended at any time, and the change
+//! lasts until the box stops.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+/// Reply of the shim to a policy it applied.
+pub(crate) const APPLIED: &str = "ok";
+
+/// What happens to matching traffic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallAction {
+    #[default]
+    Allow,
+    Deny,
+}
+
+impl FirewallAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirewallAction::Allow => "allow",
+            FirewallAction::Deny => "deny",
+        }
+    }
+}
+
+/// Which way traffic flows, seen from the box.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficDirection {
+    /// Connections the box opens.
+    #[default]
+    Egress,
+    /// Connections to the box, through forwarded ports.
+    Ingress,
+}
+
+impl TrafficDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrafficDirection::Egress => "egress",
+            TrafficDirection::Ingress => "ingress",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallProtocol {
+    #[default]
+    Any,
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+impl FirewallProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirewallProtocol::Any => "any",
+            FirewallProtocol::Tcp => "tcp",
+            FirewallProtocol::Udp => "udp",
+            FirewallProtocol::Icmp => "icmp",
+        }
+    }
+
+    fn has_ports(&self) -> bool {
+        matches!(self, FirewallProtocol::Tcp | FirewallProtocol::Udp)
+    }
+}
+
+/// One firewall rule.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallRule {
+    #[serde(default)]
+    pub direction: TrafficDirection,
+    pub action: FirewallAction,
+    #[serde(default)]
+    pub protocol: FirewallProtocol,
+    /// Inclusive port range: where egress traffic goes, or the box's port
+    /// for ingress traffic. None matches every port.
+    #[serde(default)]
+    pub ports: Option<(u16, u16)>,
+    /// IPv4 block of the other end, such as `10.0.0.0/8`. None matches
+    /// every address.
+    #[serde(default)]
+    pub cidr: Option<String>,
+}
+
+impl FirewallRule {
+    /// Parse `PROTO[:PORT[-PORT]][@CIDR]`, such as `tcp:443`,
+    /// `udp:5000-5100` or `any@10.0.0.0/8`.
+    pub fn parse(
+        direction: TrafficDirection,
+        action: FirewallAction,
+        spec: &str,
+    ) -> BoxliteResult<Self> {
+        let invalid =
+            || BoxliteError::InvalidArgument(format!("Invalid firewall rule: {:?}", spec));
+        let (rest, cidr) = match spec.split_once('@') {
+            Some((rest, cidr)) => (rest, Some(cidr.to_string())),
+            None => (spec, None),
+        };
+        let (protocol, ports) = match rest.split_once(':') {
+            Some((protocol, ports)) => (protocol, Some(ports)),
+            None => (rest, None),
+        };
+        let protocol = match protocol.to_ascii_lowercase().as_str() {
+            "any" | "all" => FirewallProtocol::Any,
+            "tcp" => FirewallProtocol::Tcp,
+            "udp" => FirewallProtocol::Udp,
+            "icmp" => FirewallProtocol::Icmp,
+            _ => return Err(invalid()),
+        };
+        let ports = ports
+            .map(|ports| {
+                let (low, high) = ports.split_once('-').unwrap_or((ports, ports));
+                Ok::<_, BoxliteError>((
+                    low.parse().map_err(|_| invalid())?,
+                    high.parse().map_err(|_| invalid())?,
+                ))
+            })
+            .transpose()?;
+
+        let rule = Self {
+            direction,
+            action,
+            protocol,
+            ports,
+            cidr,
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        if let Some((low, high)) = self.ports {
+            if !self.protocol.has_ports() {
+                return Err(BoxliteError::InvalidArgument(format!(
+                    "Firewall rule '{}' has ports but no tcp or udp protocol",
+                    self
+                )));
+            }
+            if low == 0 || low > high {
+                return Err(BoxliteError::InvalidArgument(format!(
+                    "Invalid port range in firewall rule '{}'",
+                    self
+                )));
+            }
+        }
+        if let Some(cidr) = &self.cidr
+            && parse_cidr(cidr).is_none()
+        {
+            return Err(BoxliteError::InvalidArgument(format!(
+                "Invalid IPv4 block in firewall rule '{}'",
+                self
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FirewallRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.action.as_str(),
+            self.direction.as_str(),
+            self.protocol.as_str()
+        )?;
+        match self.ports {
+            Some((low, high)) if low == high => write!(f, ":{}", low)?,
+            Some((low, high)) => write!(f, ":{}-{}", low, high)?,
+            None => {}
+        }
+        if let Some(cidr) = &self.cidr {
+            write!(f, "@{}", cidr)?;
+        }
+        Ok(())
+    }
+}
+
+/// A box's firewall: rules matched in order, then a default per direction.
+///
+/// The default policy allows everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallPolicy {
+    /// What happens to egress traffic no rule matches.
+    #[serde(default)]
+    pub egress: FirewallAction,
+    /// What happens to ingress traffic no rule matches.
+    #[serde(default)]
+    pub ingress: FirewallAction,
+    #[serde(default)]
+    pub rules: Vec<FirewallRule>,
+}
+
+impl FirewallPolicy {
+    pub fn validate(&self) -> BoxliteResult<()> {
+        for rule in &self.rules {
+            rule.validate()?;
+        }
+        Ok(())
+    }
+
+    /// The default for `direction`.
+    pub fn default_action(&self, direction: TrafficDirection) -> FirewallAction {
+        match direction {
+            TrafficDirection::Egress => self.egress,
+            TrafficDirection::Ingress => self.ingress,
+        }
+    }
+}
+
+/// `a.b.c.d/n` as an address and prefix length.
+fn parse_cidr(cidr: &str) -> Option<(Ipv4Addr, u8)> {
+    let (addr, prefix) = cidr.split_once('/').unwrap_or((cidr, "32"));
+    let prefix: u8 = prefix.parse().ok()?;
+    (prefix <= 32).then_some((addr.parse().ok()?, prefix))
+}
+
+/// Record the policy the box's link is filtered with.
+pub(crate) fn record(path: &Path, policy: &FirewallPolicy) -> BoxliteResult<()> {
+    let content = serde_json::to_vec(policy)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode firewall: {}", e)))?;
+    std::fs::write(path, content)
+        .map_err(|e| BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// The recorded policy; the default if nothing was recorded.
+pub(crate) fn read(path: &Path) -> FirewallPolicy {
+    std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Have the shim serving `socket` filter the box's link with `policy`.
+pub(crate) async fn apply(socket: &Path, policy: &FirewallPolicy) -> BoxliteResult<()> {
+    let stream = UnixStream::connect(socket).await.map_err(|e| {
+        BoxliteError::Network(format!(
+            "Failed to connect to firewall socket {}: {}",
+            socket.display(),
+            e
+        ))
+    })?;
+    let mut stream = BufReader::new(stream);
+    let mut request = serde_json::to_string(policy)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode firewall: {}", e)))?;
+    request.push('\n');
+    stream.get_mut().write_all(request.as_bytes()).await?;
+
+    let mut reply = String::new();
+    stream.read_line(&mut reply).await?;
+    match reply.trim() {
+        APPLIED => Ok(()),
+        "" => Err(BoxliteError::Network(
+            "Shim closed the firewall socket without a reply".to_string(),
+        )),
+        error => Err(BoxliteError::Network(format!(
+            "Failed to apply firewall: {}",
+            error
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = FirewallRule::parse(TrafficDirection::Egress, FirewallAction::Allow, "tcp:443")
+            .unwrap();
+        assert_eq!(rule.protocol, FirewallProtocol::Tcp);
+        assert_eq!(rule.ports, Some((443, 443)));
+        assert_eq!(rule.to_string(), "allow egress tcp:443");
+
+        let rule = FirewallRule::parse(
+            TrafficDirection::Ingress,
+            FirewallAction::Deny,
+            "UDP:5000-5100@10.0.0.0/8",
+        )
+        .unwrap();
+        assert_eq!(rule.ports, Some((5000, 5100)));
+        assert_eq!(rule.cidr.as_deref(), Some("10.0.0.0/8"));
+        assert_eq!(rule.to_string(), "deny ingress udp:5000-5100@10.0.0.0/8");
+
+        let rule = FirewallRule::parse(
+            TrafficDirection::Egress,
+            FirewallAction::Deny,
+            "any@1.2.3.4",
+        )
+        .unwrap();
+        assert_eq!(rule.ports, None);
+
+        for spec in [
+            "sctp:80",
+            "tcp:0",
+            "tcp:90-80",
+            "icmp:8",
+            "tcp:443@10.0.0.0/40",
+            "tcp:x",
+        ] {
+            assert!(
+                FirewallRule::parse(TrafficDirection::Egress, FirewallAction::Allow, spec).is_err(),
+                "{} accepted",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_json() {
+        let policy: FirewallPolicy = serde_json::from_str(
+            r#"{"egress": "deny", "rules": [{"action": "allow", "protocol": "tcp", "ports": [443, 443]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(policy.egress, FirewallAction::Deny);
+        assert_eq!(policy.ingress, FirewallAction::Allow);
+        assert_eq!(policy.rules[0].direction, TrafficDirection::Egress);
+        assert!(policy.validate().is_ok());
+        assert_eq!(FirewallPolicy::default().rules, Vec::new());
+    }
+}
@@ -2,6 +2,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::net::NetworkBackendConfig;
+use crate::net::firewall::FirewallPolicy;
+
 /// Local DNS zone configuration
 ///
 /// Defines local DNS records served by the gateway's embedded DNS server.
@@ -44,6 +47,14 @@ pub struct GvproxyConfig {
     /// Guest MAC address
     pub guest_mac: String,
 
+    /// Address NATed to the host's loopback; empty (the default) leaves
+    /// the host's loopback unreachable from the guest
+    pub host_ip: String,
+
+    /// Host ports the guest may connect to through `host_ip`; every other
+    /// loopback service stays unreachable
+    pub host_ports: Vec<u16>,
+
     /// MTU for the virtual network
     pub mtu: u16,
 
@@ -64,6 +75,10 @@ pub struct GvproxyConfig {
     /// Set via config or BOXLITE_NET_CAPTURE_FILE environment variable
     #[serde(skip_serializing_if = "Option::is_none")]
     pub capture_file: Option<String>,
+
+    /// Firewall filtering the guest's link from the start
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firewall: Option<FirewallPolicy>,
 }
 
 impl Default for GvproxyConfig {
@@ -76,12 +91,15 @@ impl Default for GvproxyConfig {
             gateway_mac: GATEWAY_MAC_STRING.to_string(),
             guest_ip: GUEST_IP.to_string(),
             guest_mac: GUEST_MAC_STRING.to_string(),
+            host_ip: String::new(),
+            host_ports: Vec::new(),
             mtu: DEFAULT_MTU,
             port_mappings: Vec::new(),
             dns_zones: Vec::new(),
             dns_search_domains: DNS_SEARCH_DOMAINS.iter().map(|s| s.to_string()).collect(),
             debug: false,
             capture_file: None,
+            firewall: None,
         }
     }
 }
@@ -134,6 +152,19 @@ impl GvproxyConfig {
         config
     }
 
+    /// Create the configuration for a backend's [`NetworkBackendConfig`]
+    pub fn from_backend(backend: &NetworkBackendConfig) -> Self {
+        let config = Self {
+            firewall: backend.firewall.clone(),
+            ..Self::new(backend.port_mappings.clone())
+        };
+        if backend.box_dns {
+            config.with_box_zone(backend.host_ports.clone())
+        } else {
+            config
+        }
+    }
+
     /// Enable debug logging
     pub fn with_debug(mut self, debug: bool) -> Self {
         self.debug = debug;
@@ -146,6 +177,21 @@ impl GvproxyConfig {
         self
     }
 
+    /// Route the host alias to `host_ports` on the host's loopback, resolve
+    /// every name in the box zone to it, and search the zone
+    pub fn with_box_zone(mut self, host_ports: Vec<u16>) -> Self {
+        use crate::net::constants::{BOX_DNS_ZONE, HOST_IP};
+
+        self.host_ip = HOST_IP.to_string();
+        self.host_ports = host_ports;
+        self.dns_zones.push(DnsZone {
+            name: format!("{}.", BOX_DNS_ZONE),
+            default_ip: self.host_ip.clone(),
+        });
+        self.dns_search_domains.push(BOX_DNS_ZONE.to_string());
+        self
+    }
+
     /// Set custom MTU
     pub fn with_mtu(mut self, mtu: u16) -> Self {
         self.mtu = mtu;
@@ -204,6 +250,18 @@ mod tests {
         assert_eq!(config.mtu, 9000);
     }
 
+    #[test]
+    fn test_box_zone() {
+        assert!(GvproxyConfig::new(vec![]).host_ip.is_empty());
+        let config = GvproxyConfig::new(vec![]).with_box_zone(vec![8080]);
+        assert_eq!(config.host_ip, "192.168.127.254");
+        assert_eq!(config.host_ports, [8080]);
+        assert_eq!(config.dns_zones.len(), 1);
+        assert_eq!(config.dns_zones[0].name, "box.");
+        assert_eq!(config.dns_zones[0].default_ip, "192.168.127.254");
+        assert_eq!(config.dns_search_domains, ["local", "box"]);
+    }
+
     #[test]
     fn test_serialization() {
         let config = GvproxyConfig::new(vec![(8080, 80)]);
@@ -4,14 +4,17 @@
 //! functions from libgvproxy-sys. All unsafe operations are encapsulated here.
 
 use std::ffi::{CStr, CString};
+use std::os::fd::{IntoRawFd, OwnedFd};
 use std::path::PathBuf;
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 
 use super::config::GvproxyConfig;
+use crate::net::firewall::FirewallPolicy;
 use libgvproxy_sys::{
-    gvproxy_create, gvproxy_destroy, gvproxy_free_string, gvproxy_get_socket_path,
-    gvproxy_get_stats, gvproxy_get_version,
+    gvproxy_capture_start, gvproxy_create, gvproxy_destroy, gvproxy_free_string,
+    gvproxy_get_socket_path, gvproxy_get_stats, gvproxy_get_version, gvproxy_set_firewall,
+    gvproxy_set_host_ports,
 };
 
 /// Create a new gvproxy instance with full configuration
@@ -143,6 +146,83 @@ pub fn get_stats_json(id: i64) -> BoxliteResult<String> {
     Ok(json_str)
 }
 
+/// Start a pcap capture of a gvproxy instance's guest link
+///
+/// # Arguments
+/// * `id` - Instance ID returned from `create_instance`
+/// * `fd` - Where the pcap stream is written; handed over to gvproxy
+/// * `snaplen` - Bytes kept per frame
+///
+/// # Returns
+/// Ok(()) once the pcap header is written, error otherwise
+pub fn start_capture(id: i64, fd: OwnedFd, snaplen: u32) -> BoxliteResult<()> {
+    let snaplen = snaplen.min(i32::MAX as u32) as i32;
+    // gvproxy owns the descriptor from here on, and closes it on error
+    let result = unsafe { gvproxy_capture_start(id, fd.into_raw_fd(), snaplen) };
+
+    if result != 0 {
+        return Err(BoxliteError::Network(format!(
+            "gvproxy_capture_start failed for instance {}",
+            id
+        )));
+    }
+
+    Ok(())
+}
+
+/// Replace the firewall filtering an instance's guest link
+///
+/// # Arguments
+/// * `id` - Instance ID
+/// * `policy` - Firewall policy to filter with from now on
+///
+/// # Returns
+/// Ok(()) once frames are filtered with `policy`, error otherwise
+pub fn set_firewall(id: i64, policy: &FirewallPolicy) -> BoxliteResult<()> {
+    let json = serde_json::to_string(policy)
+        .map_err(|e| BoxliteError::Network(format!("Failed to serialize firewall: {}", e)))?;
+    let c_json = CString::new(json)
+        .map_err(|e| BoxliteError::Network(format!("Invalid JSON string: {}", e)))?;
+
+    let result = unsafe { gvproxy_set_firewall(id, c_json.as_ptr()) };
+
+    if result != 0 {
+        return Err(BoxliteError::Network(format!(
+            "gvproxy_set_firewall failed for instance {}",
+            id
+        )));
+    }
+
+    Ok(())
+}
+
+/// Replace the host ports an instance's guest may reach through the host
+/// alias
+///
+/// # Arguments
+/// * `id` - Instance ID
+/// * `ports` - Forwarded host ports to let connections through to
+///
+/// # Returns
+/// Ok(()) once only `ports` are reachable, error otherwise
+pub fn set_host_ports(id: i64, ports: &[u16]) -> BoxliteResult<()> {
+    let json = serde_json::to_string(ports)
+        .map_err(|e| BoxliteError::Network(format!("Failed to serialize host ports: {}", e)))?;
+    let c_json = CString::new(json)
+        .map_err(|e| BoxliteError::Network(format!("Invalid JSON string: {}", e)))?;
+
+    let result = unsafe { gvproxy_set_host_ports(id, c_json.as_ptr()) };
+
+    if result != 0 {
+        return Err(BoxliteError::Network(format!(
+            "gvproxy_set_host_ports failed for instance {}",
+            id
+        )));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
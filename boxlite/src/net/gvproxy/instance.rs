@@ -3,6 +3,7 @@
 //! This module provides a safe, RAII-style wrapper around gvproxy instances.
 //! Instances are automatically cleaned up when dropped.
 
+use std::os::fd::OwnedFd;
 use std::path::PathBuf;
 use std::sync::Weak;
 
@@ -11,6 +12,7 @@ use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use super::ffi;
 use super::logging;
 use super::stats::NetworkStats;
+use crate::net::firewall::FirewallPolicy;
 
 /// Safe wrapper for gvproxy library with automatic resource management
 ///
@@ -78,13 +80,16 @@ impl GvproxyInstance {
     /// # Ok::<(), boxlite_shared::errors::BoxliteError>(())
     /// ```
     pub fn new(port_mappings: &[(u16, u16)]) -> BoxliteResult<Self> {
+        // Create config with defaults + port mappings
+        Self::with_config(super::config::GvproxyConfig::new(port_mappings.to_vec()))
+    }
+
+    /// Create a new gvproxy instance from a full configuration
+    pub fn with_config(config: super::config::GvproxyConfig) -> BoxliteResult<Self> {
         // Initialize logging callback (one-time setup)
         // This ensures all gvproxy logs are routed to Rust's tracing system
         logging::init_logging();
 
-        // Create config with defaults + port mappings
-        let config = super::config::GvproxyConfig::new(port_mappings.to_vec());
-
         // Create instance via FFI with full config
         let id = ffi::create_instance(&config)?;
 
@@ -162,6 +167,31 @@ impl GvproxyInstance {
         })
     }
 
+    /// Write a pcap capture of the guest's link to `fd`
+    ///
+    /// Every Ethernet frame crossing the VM connection, in both directions,
+    /// is written with at most `snaplen` bytes kept. The capture runs until
+    /// the reader closes `fd` or the instance is destroyed; frames are
+    /// dropped rather than stalling the guest when the reader falls behind.
+    pub fn start_capture(&self, fd: OwnedFd, snaplen: u32) -> BoxliteResult<()> {
+        ffi::start_capture(self.id, fd, snaplen)
+    }
+
+    /// Filter the guest's link with `policy` from now on
+    ///
+    /// Connections already let through keep their replies flowing until
+    /// they go idle.
+    pub fn set_firewall(&self, policy: &FirewallPolicy) -> BoxliteResult<()> {
+        ffi::set_firewall(self.id, policy)
+    }
+
+    /// Let the guest reach only `ports` on the host through the host alias
+    ///
+    /// Connections to ports dropped from the set are cut off.
+    pub fn set_host_ports(&self, ports: &[u16]) -> BoxliteResult<()> {
+        ffi::set_host_ports(self.id, ports)
+    }
+
     /// Get the gvproxy version string
     ///
     /// Returns the version of the gvproxy-bridge library.
@@ -65,7 +65,7 @@ extern "C" fn gvproxy_log_callback(level: c_int, message: *const c_char) {
 /// # Example
 ///
 /// ```no_run
-/// use boxlite::net::gvproxy::logging::init_logging;
+/// use boxlite::net::gvproxy::init_logging;
 ///
 /// // Initialize logging bridge (idempotent)
 /// init_logging();
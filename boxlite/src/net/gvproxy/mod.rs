@@ -58,6 +58,12 @@
 //!
 //! let config = NetworkBackendConfig {
 //!     port_mappings: vec![(8080, 80), (8443, 443)],
+//!     capture_socket: None,
+//!     box_dns: false,
+//!     host_ports: Vec::new(),
+//!     host_ports_socket: None,
+//!     firewall: None,
+//!     firewall_socket: None,
 //! };
 //!
 //! // Create backend - logs from gvproxy will appear in tracing
@@ -129,6 +135,12 @@ impl GvisorTapBackend {
     ///
     /// let config = NetworkBackendConfig {
     ///     port_mappings: vec![(8080, 80), (8443, 443)],
+    ///     capture_socket: None,
+    ///     box_dns: false,
+    ///     host_ports: Vec::new(),
+    ///     host_ports_socket: None,
+    ///     firewall: None,
+    ///     firewall_socket: None,
     /// };
     ///
     /// let backend = GvisorTapBackend::new(config)?;
@@ -140,8 +152,9 @@ impl GvisorTapBackend {
             config.port_mappings
         );
 
-        // Create gvproxy instance with port mappings
-        let instance = Arc::new(GvproxyInstance::new(&config.port_mappings)?);
+        // Create gvproxy instance with port mappings and DNS zones
+        let gvproxy_config = GvproxyConfig::from_backend(&config);
+        let instance = Arc::new(GvproxyInstance::with_config(gvproxy_config)?);
 
         // Start background stats logging thread
         instance::start_stats_logging(Arc::downgrade(&instance));
@@ -177,6 +190,12 @@ impl GvisorTapBackend {
     ///
     /// let config = NetworkBackendConfig {
     ///     port_mappings: vec![(8080, 80)],
+    ///     capture_socket: None,
+    ///     box_dns: false,
+    ///     host_ports: Vec::new(),
+    ///     host_ports_socket: None,
+    ///     firewall: None,
+    ///     firewall_socket: None,
     /// };
     /// let backend = GvisorTapBackend::new(config)?;
     ///
@@ -16,7 +16,7 @@ use serde::{Deserialize, Serialize};
 /// # Example
 ///
 /// ```no_run
-/// # use boxlite::net::gvproxy::stats::NetworkStats;
+/// # use boxlite::net::gvproxy::NetworkStats;
 /// let stats_json = r#"{"BytesSent":1024,"BytesReceived":2048,"TCP":{"ForwardMaxInFlightDrop":0,"CurrentEstablished":1,"FailedConnectionAttempts":0,"Retransmits":0,"Timeouts":0}}"#;
 /// let stats = NetworkStats::from_json_str(stats_json)?;
 /// if stats.tcp.forward_max_inflight_drop > 0 {
@@ -0,0 +1,394 @@
+//! HTTPS ingress for box web services.
+//!
+//! With `BoxliteOptions::ingress` set, the runtime listens for TLS and
+//! routes each connection by its SNI host: `<name>.localhost` goes to the
+//! running box `<name>`, at the host port forwarded to its `ingress_port`.
+//! TLS ends here and the plain stream is spliced through, so anything that
+//! speaks HTTP/1.1 (including WebSocket upgrades) works unchanged. Browsers
+//! resolve `*.localhost` to loopback themselves, so no DNS setup is needed.
+//!
+//! Certificates come from a local CA kept in the ingress directory and are
+//! issued per host name on first use. The CA is name-constrained to
+//! `localhost` and its subdomains, so trusting it cannot let its key vouch
+//! for any other site.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose,
+    GeneralSubtree, IsCa, KeyPair, KeyUsagePurpose, NameConstraints,
+};
+use rustls::ServerConfig;
+use rustls::crypto::ring::sign::any_supported_type;
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio::io::AsyncWriteExt;
+use tokio_rustls::TlsAcceptor;
+
+use crate::runtime::options::IngressOptions;
+
+const HOST_SUFFIX: &str = ".localhost";
+/// Leaf lifetime; macOS rejects server certificates valid for longer than
+/// 825 days even from a locally trusted CA.
+const LEAF_VALIDITY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+/// Longest pause after failed accepts, e.g. while out of descriptors.
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Box names and the host ports their ingress traffic goes to.
+type Routes = Arc<Mutex<HashMap<String, u16>>>;
+
+/// The running ingress proxy of a runtime.
+pub(crate) struct Ingress {
+    routes: Routes,
+    ca_path: PathBuf,
+}
+
+/// Keeps a box reachable through the ingress until dropped.
+pub(crate) struct IngressRoute {
+    routes: Routes,
+    name: String,
+    host_port: u16,
+}
+
+impl Ingress {
+    /// Listen on `options.listen`, with the CA in `dir` (created if missing).
+    pub(crate) fn start(options: &IngressOptions, dir: &Path) -> BoxliteResult<Self> {
+        let ca = LocalCa::load_or_create(dir)?;
+        let ca_path = dir.join("ca.pem");
+        let mut config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(|e| BoxliteError::Internal(format!("Failed to configure TLS: {}", e)))?
+                .with_no_client_auth()
+                .with_cert_resolver(Arc::new(ca));
+        // Backends get the decrypted stream as is, and dev servers rarely
+        // speak HTTP/2 without TLS
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        let listener = TcpListener::bind(options.listen)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|e| {
+                BoxliteError::Network(format!(
+                    "Failed to listen for ingress on {}: {}",
+                    options.listen, e
+                ))
+            })?;
+
+        let routes = Routes::default();
+        let weak = Arc::downgrade(&routes);
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        std::thread::Builder::new()
+            .name("boxlite-ingress".to_string())
+            .spawn(move || serve(listener, acceptor, weak))
+            .map_err(|e| BoxliteError::Internal(format!("Failed to start ingress: {}", e)))?;
+
+        tracing::info!(listen = %options.listen, ca = %ca_path.display(), "Ingress proxy started");
+        Ok(Self { routes, ca_path })
+    }
+
+    /// The CA certificate clients must trust.
+    pub(crate) fn ca_path(&self) -> &Path {
+        &self.ca_path
+    }
+
+    /// Route `https://<box_name>.localhost` to `host_port` on the host.
+    pub(crate) fn route(&self, box_name: &str, host_port: u16) -> IngressRoute {
+        let name = box_name.to_ascii_lowercase();
+        self.routes.lock().unwrap().insert(name.clone(), host_port);
+        tracing::debug!(name = %name, host_port, "Routing box through ingress");
+        IngressRoute {
+            routes: Arc::clone(&self.routes),
+            name,
+            host_port,
+        }
+    }
+}
+
+impl Drop for IngressRoute {
+    fn drop(&mut self) {
+        let mut routes = self.routes.lock().unwrap();
+        // A box started since under the same name keeps its route
+        if routes.get(&self.name) == Some(&self.host_port) {
+            routes.remove(&self.name);
+        }
+    }
+}
+
+fn serve(listener: TcpListener, acceptor: TlsAcceptor, routes: Weak<Mutex<HashMap<String, u16>>>) {
+    // The runtime may be created outside of any tokio runtime, so the
+    // proxy brings its own
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to start ingress proxy");
+            return;
+        }
+    };
+    rt.block_on(async move {
+        let listener = match tokio::net::TcpListener::from_std(listener) {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to start ingress proxy");
+                return;
+            }
+        };
+        let mut backoff = Duration::from_millis(10);
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::debug!(error = %e, "Ingress accept failed");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                    continue;
+                }
+            };
+            backoff = Duration::from_millis(10);
+            let Some(routes) = routes.upgrade() else {
+                return;
+            };
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                if let Err(e) = proxy(stream, acceptor, routes).await {
+                    tracing::debug!(error = %e, "Ingress connection failed");
+                }
+            });
+        }
+    });
+}
+
+async fn proxy(
+    stream: tokio::net::TcpStream,
+    acceptor: TlsAcceptor,
+    routes: Routes,
+) -> std::io::Result<()> {
+    let mut tls = acceptor.accept(stream).await?;
+    let host = tls.get_ref().1.server_name().map(str::to_ascii_lowercase);
+    let name = host.as_deref().and_then(box_name);
+    let port = name.and_then(|name| routes.lock().unwrap().get(name).copied());
+    let Some(port) = port else {
+        let body = format!("no running box is served as {}\n", host.unwrap_or_default());
+        let response = format!(
+            "HTTP/1.1 502 Bad Gateway\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        tls.write_all(response.as_bytes()).await?;
+        return tls.shutdown().await;
+    };
+    let mut backend = tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await?;
+    tokio::io::copy_bidirectional(&mut tls, &mut backend).await?;
+    Ok(())
+}
+
+/// The box a host name addresses, if it is one of ours.
+fn box_name(host: &str) -> Option<&str> {
+    host.strip_suffix(HOST_SUFFIX)
+        .filter(|name| !name.is_empty() && !name.contains('.'))
+}
+
+/// The CA signing ingress certificates, and the leaves issued so far.
+struct LocalCa {
+    cert: Certificate,
+    key: KeyPair,
+    issued: Mutex<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl fmt::Debug for LocalCa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalCa").finish_non_exhaustive()
+    }
+}
+
+impl LocalCa {
+    fn load_or_create(dir: &Path) -> BoxliteResult<Self> {
+        let cert_path = dir.join("ca.pem");
+        let key_path = dir.join("ca-key.pem");
+        let invalid = |e: rcgen::Error| {
+            BoxliteError::Config(format!("Invalid ingress CA in {}: {}", dir.display(), e))
+        };
+        let storage = |path: &Path, e: std::io::Error| {
+            BoxliteError::Storage(format!("Failed to access {}: {}", path.display(), e))
+        };
+
+        if cert_path.exists() && key_path.exists() {
+            let cert_pem =
+                std::fs::read_to_string(&cert_path).map_err(|e| storage(&cert_path, e))?;
+            let key_pem = std::fs::read_to_string(&key_path).map_err(|e| storage(&key_path, e))?;
+            let key = KeyPair::from_pem(&key_pem).map_err(invalid)?;
+            let params = CertificateParams::from_ca_cert_pem(&cert_pem).map_err(invalid)?;
+            if params.name_constraints.is_some() {
+                // Re-signing the parsed certificate keeps its name and key,
+                // which is all a leaf is checked against
+                let cert = params.self_signed(&key).map_err(invalid)?;
+                return Ok(Self::new(cert, key));
+            }
+            tracing::warn!(
+                path = %cert_path.display(),
+                "Replacing ingress CA without name constraints; trust the new one instead"
+            );
+        }
+
+        let key = KeyPair::generate().map_err(invalid)?;
+        let mut params = CertificateParams::default();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "BoxLite local CA");
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        // A DNS constraint also permits every subdomain of the name
+        params.name_constraints = Some(NameConstraints {
+            permitted_subtrees: vec![GeneralSubtree::DnsName("localhost".to_string())],
+            excluded_subtrees: Vec::new(),
+        });
+        let cert = params.self_signed(&key).map_err(invalid)?;
+
+        std::fs::create_dir_all(dir).map_err(|e| storage(dir, e))?;
+        write_private(&key_path, &key.serialize_pem()).map_err(|e| storage(&key_path, e))?;
+        std::fs::write(&cert_path, cert.pem()).map_err(|e| storage(&cert_path, e))?;
+        tracing::info!(path = %cert_path.display(), "Created ingress CA");
+        Ok(Self::new(cert, key))
+    }
+
+    fn new(cert: Certificate, key: KeyPair) -> Self {
+        Self {
+            cert,
+            key,
+            issued: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A certificate for `host`, issued on first request.
+    fn certificate(&self, host: &str) -> Result<Arc<CertifiedKey>, String> {
+        let mut issued = self.issued.lock().unwrap();
+        if let Some(certified) = issued.get(host) {
+            return Ok(Arc::clone(certified));
+        }
+
+        let key = KeyPair::generate().map_err(|e| e.to_string())?;
+        let mut params =
+            CertificateParams::new(vec![host.to_string()]).map_err(|e| e.to_string())?;
+        params.distinguished_name.push(DnType::CommonName, host);
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        let now = time::OffsetDateTime::now_utc();
+        params.not_before = now - Duration::from_secs(60 * 60);
+        params.not_after = now + LEAF_VALIDITY;
+        let cert = params
+            .signed_by(&key, &self.cert, &self.key)
+            .map_err(|e| e.to_string())?;
+
+        let der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+        let signing_key = any_supported_type(&der).map_err(|e| e.to_string())?;
+        let certified = Arc::new(CertifiedKey::new(
+            vec![cert.der().clone(), self.cert.der().clone()],
+            signing_key,
+        ));
+        issued.insert(host.to_string(), Arc::clone(&certified));
+        Ok(certified)
+    }
+}
+
+impl ResolvesServerCert for LocalCa {
+    fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let host = hello.server_name()?.to_ascii_lowercase();
+        // Only names the proxy can route get a certificate
+        box_name(&host)?;
+        match self.certificate(&host) {
+            Ok(certified) => Some(certified),
+            Err(e) => {
+                tracing::warn!(host = %host, error = %e, "Failed to issue ingress certificate");
+                None
+            }
+        }
+    }
+}
+
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_names_and_routes() {
+        assert_eq!(box_name("web.localhost"), Some("web"));
+        assert_eq!(box_name("a.web.localhost"), None);
+        assert_eq!(box_name(".localhost"), None);
+        assert_eq!(box_name("web.local"), None);
+
+        let routes = Routes::default();
+        let route = |name: &str, host_port| IngressRoute {
+            routes: Arc::clone(&routes),
+            name: name.to_string(),
+            host_port,
+        };
+        routes.lock().unwrap().insert("web".to_string(), 8080);
+        let old = route("web", 8080);
+        // The box was restarted and got another port
+        routes.lock().unwrap().insert("web".to_string(), 9090);
+        drop(old);
+        assert_eq!(routes.lock().unwrap().get("web"), Some(&9090));
+    }
+
+    #[test]
+    fn test_ca_is_reused_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = LocalCa::load_or_create(dir.path()).unwrap();
+        first.certificate("web.localhost").unwrap();
+        let pem = std::fs::read_to_string(dir.path().join("ca.pem")).unwrap();
+
+        let second = LocalCa::load_or_create(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("ca.pem")).unwrap(),
+            pem
+        );
+        assert_eq!(second.key.public_key_der(), first.key.public_key_der());
+        let leaf = second.certificate("web.localhost").unwrap();
+        assert_eq!(leaf.cert.len(), 2);
+    }
+
+    #[test]
+    fn test_ca_is_limited_to_localhost() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = LocalCa::load_or_create(dir.path()).unwrap();
+        let pem = std::fs::read_to_string(dir.path().join("ca.pem")).unwrap();
+        let params = CertificateParams::from_ca_cert_pem(&pem).unwrap();
+        let constraints = params.name_constraints.unwrap();
+        assert_eq!(
+            constraints.permitted_subtrees,
+            vec![GeneralSubtree::DnsName("localhost".to_string())]
+        );
+
+        // A CA from before the constraints is replaced
+        let mut old = CertificateParams::default();
+        old.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        std::fs::write(
+            dir.path().join("ca.pem"),
+            old.self_signed(&ca.key).unwrap().pem(),
+        )
+        .unwrap();
+        let replaced = LocalCa::load_or_create(dir.path()).unwrap();
+        assert_ne!(replaced.key.public_key_der(), ca.key.public_key_der());
+    }
+}
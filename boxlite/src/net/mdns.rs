@@ -0,0 +1,301 @@
+//! mDNS names for boxes on the host LAN.
+//!
+//! A box with `BoxOptions::mdns` is answerable as `<name>.local` while it
+//! runs, resolving to the host's LAN address, so its forwarded ports can be
+//! opened from a browser or another device without looking up the host IP.
+//! The runtime answers A queries for those names itself and leaves every
+//! other name to the host's own responder (avahi, mDNSResponder), which it
+//! shares port 5353 with.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const TTL_SECS: u32 = 120;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Set on a question's class when the asker wants a unicast reply.
+const UNICAST_RESPONSE: u16 = 0x8000;
+/// Set on an answer's class: this record replaces cached ones.
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// Advertised names, lowercased with the `.local` suffix, and how many
+/// boxes hold each.
+type Names = Arc<Mutex<HashMap<String, usize>>>;
+
+/// Answers mDNS queries for the names of registered boxes.
+#[derive(Default)]
+pub(crate) struct MdnsResponder {
+    names: Names,
+    started: Mutex<bool>,
+}
+
+/// Keeps a name advertised until dropped.
+pub(crate) struct MdnsRegistration {
+    names: Names,
+    name: String,
+}
+
+impl MdnsResponder {
+    /// Advertise `<box_name>.local`, starting the responder on first use.
+    pub(crate) fn register(&self, box_name: &str) -> MdnsRegistration {
+        let name = format!("{}.local", box_name.to_ascii_lowercase());
+        *self.names.lock().unwrap().entry(name.clone()).or_default() += 1;
+
+        let mut started = self.started.lock().unwrap();
+        if !*started {
+            match bind() {
+                Ok(socket) => {
+                    let names = Arc::downgrade(&self.names);
+                    let spawned = std::thread::Builder::new()
+                        .name("boxlite-mdns".to_string())
+                        .spawn(move || serve(socket, names));
+                    *started = spawned.is_ok();
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to start mDNS responder"),
+            }
+        }
+        tracing::debug!(name = %name, "Advertising box over mDNS");
+        MdnsRegistration {
+            names: Arc::clone(&self.names),
+            name,
+        }
+    }
+}
+
+impl Drop for MdnsRegistration {
+    fn drop(&mut self) {
+        let mut names = self.names.lock().unwrap();
+        if let Some(count) = names.get_mut(&self.name) {
+            *count -= 1;
+            if *count == 0 {
+                names.remove(&self.name);
+            }
+        }
+    }
+}
+
+fn bind() -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    // The host's own responder usually holds the port already
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+    socket.join_multicast_v4(&MDNS_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_loop_v4(true)?;
+    // Wake up now and then to notice the runtime is gone
+    socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    Ok(socket.into())
+}
+
+fn serve(socket: UdpSocket, names: Weak<Mutex<HashMap<String, usize>>>) {
+    let mut buf = [0u8; 1500];
+    loop {
+        let received = socket.recv_from(&mut buf);
+        let Some(names) = names.upgrade() else {
+            return;
+        };
+        let Ok((len, from)) = received else {
+            continue;
+        };
+        let Some(query) = parse_query(&buf[..len]) else {
+            continue;
+        };
+        let known = names.lock().unwrap();
+        for question in &query.questions {
+            if !matches!(question.qtype, TYPE_A | TYPE_ANY)
+                || !known.contains_key(&question.name.to_ascii_lowercase())
+            {
+                continue;
+            }
+            let Some(ip) = lan_address() else {
+                continue;
+            };
+            // Legacy resolvers query from an ephemeral port and expect a
+            // conventional unicast DNS reply
+            let legacy = from.port() != MDNS_PORT;
+            let unicast = legacy || question.qclass & UNICAST_RESPONSE != 0;
+            let reply = answer(legacy.then_some(query.id), &question.name, ip);
+            let dest = if unicast {
+                from
+            } else {
+                SocketAddrV4::new(MDNS_ADDR, MDNS_PORT).into()
+            };
+            if let Err(e) = socket.send_to(&reply, dest) {
+                tracing::debug!(error = %e, "Failed to send mDNS answer");
+            }
+        }
+    }
+}
+
+/// The address other LAN hosts reach this host at.
+fn lan_address() -> Option<Ipv4Addr> {
+    // Connecting a UDP socket sends nothing; it only picks the route
+    let probe = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    probe.connect((MDNS_ADDR, MDNS_PORT)).ok()?;
+    match probe.local_addr().ok()? {
+        SocketAddr::V4(addr) if !addr.ip().is_unspecified() => Some(*addr.ip()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Question {
+    pub(super) name: String,
+    pub(super) qtype: u16,
+    pub(super) qclass: u16,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Query {
+    pub(super) id: u16,
+    pub(super) questions: Vec<Question>,
+}
+
+pub(super) fn parse_query(packet: &[u8]) -> Option<Query> {
+    let word = |at: usize| Some(u16::from_be_bytes([*packet.get(at)?, *packet.get(at + 1)?]));
+    let flags = word(2)?;
+    // Responses from other hosts are not questions
+    if flags & 0x8000 != 0 {
+        return None;
+    }
+    let mut offset = 12;
+    let mut questions = Vec::new();
+    for _ in 0..word(4)? {
+        let (name, next) = read_name(packet, offset)?;
+        questions.push(Question {
+            name,
+            qtype: word(next)?,
+            qclass: word(next + 2)?,
+        });
+        offset = next + 4;
+    }
+    Some(Query {
+        id: word(0)?,
+        questions,
+    })
+}
+
+/// The name at `offset`, and the offset just past it.
+fn read_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds the pointers a malformed packet can chain
+    for _ in 0..128 {
+        let len = *packet.get(offset)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(offset + 1)));
+        }
+        if len & 0xc0 == 0xc0 {
+            let pointer = ((len & 0x3f) << 8) | *packet.get(offset + 1)? as usize;
+            end.get_or_insert(offset + 2);
+            offset = pointer;
+            continue;
+        }
+        let label = packet.get(offset + 1..offset + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += 1 + len;
+    }
+    None
+}
+
+/// A response carrying one A record for `name`.
+fn answer(id: Option<u16>, name: &str, ip: Ipv4Addr) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    packet.extend_from_slice(&id.unwrap_or(0).to_be_bytes());
+    // Response, authoritative
+    packet.extend_from_slice(&0x8400u16.to_be_bytes());
+    // Legacy unicast replies repeat the question
+    let questions: u16 = id.is_some().into();
+    for count in [questions, 1, 0, 0] {
+        packet.extend_from_slice(&count.to_be_bytes());
+    }
+    if id.is_some() {
+        write_name(&mut packet, name);
+        packet.extend_from_slice(&TYPE_A.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    }
+    write_name(&mut packet, name);
+    packet.extend_from_slice(&TYPE_A.to_be_bytes());
+    let class = if id.is_some() {
+        CLASS_IN
+    } else {
+        CLASS_IN | CACHE_FLUSH
+    };
+    packet.extend_from_slice(&class.to_be_bytes());
+    packet.extend_from_slice(&TTL_SECS.to_be_bytes());
+    packet.extend_from_slice(&4u16.to_be_bytes());
+    packet.extend_from_slice(&ip.octets());
+    packet
+}
+
+pub(super) fn write_name(packet: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label);
+    }
+    packet.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_and_answer() {
+        // A query for web.local, then a second question pointing back at it
+        let mut query = vec![0x12, 0x34, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+        write_name(&mut query, "web.local");
+        query.extend_from_slice(&[0, 1, 0x80, 1]);
+        query.extend_from_slice(&[0xc0, 12, 0, 28, 0, 1]);
+
+        let parsed = parse_query(&query).unwrap();
+        assert_eq!(parsed.id, 0x1234);
+        assert_eq!(
+            parsed.questions,
+            vec![
+                Question {
+                    name: "web.local".to_string(),
+                    qtype: TYPE_A,
+                    qclass: CLASS_IN | UNICAST_RESPONSE,
+                },
+                Question {
+                    name: "web.local".to_string(),
+                    qtype: 28,
+                    qclass: CLASS_IN,
+                },
+            ]
+        );
+
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        let reply = answer(None, "web.local", ip);
+        assert_eq!(&reply[2..4], &[0x84, 0]);
+        assert!(parse_query(&reply).is_none());
+        assert_eq!(&reply[reply.len() - 4..], &ip.octets());
+
+        let legacy = answer(Some(0x1234), "web.local", ip);
+        assert_eq!(&legacy[..2], &[0x12, 0x34]);
+        assert_eq!(legacy.len(), reply.len() + 15);
+    }
+
+    #[test]
+    fn test_registration_refcount() {
+        let responder = MdnsResponder::default();
+        // Stand in for a started responder, so the test binds nothing
+        *responder.started.lock().unwrap() = true;
+        let first = responder.register("Web");
+        let second = responder.register("web");
+        drop(first);
+        assert!(responder.names.lock().unwrap().contains_key("web.local"));
+        drop(second);
+        assert!(responder.names.lock().unwrap().is_empty());
+    }
+}
@@ -11,6 +11,10 @@ use boxlite_shared::errors::BoxliteResult;
 use std::path::PathBuf;
 
 pub mod constants;
+pub(crate) mod dns;
+pub mod firewall;
+pub(crate) mod ingress;
+pub(crate) mod mdns;
 
 #[cfg(feature = "libslirp-backend")]
 mod libslirp;
@@ -50,11 +54,39 @@ pub enum NetworkBackendEndpoint {
 pub struct NetworkBackendConfig {
     /// Port mappings: (host_port, guest_port)
     pub port_mappings: Vec<(u16, u16)>,
+    /// Unix socket the shim serves pcap captures of the guest link on.
+    #[serde(default)]
+    pub capture_socket: Option<PathBuf>,
+    /// Resolve the box zone to the host in the guest (see
+    /// `BoxliteOptions::dns`).
+    #[serde(default)]
+    pub box_dns: bool,
+    /// Host ports the guest may reach through the host alias when
+    /// `box_dns` is on; updated on `host_ports_socket` as boxes come and go.
+    #[serde(default)]
+    pub host_ports: Vec<u16>,
+    /// Unix socket the shim takes host port changes on.
+    #[serde(default)]
+    pub host_ports_socket: Option<PathBuf>,
+    /// Firewall the backend starts filtering the guest's link with.
+    #[serde(default)]
+    pub firewall: Option<firewall::FirewallPolicy>,
+    /// Unix socket the shim takes firewall changes on.
+    #[serde(default)]
+    pub firewall_socket: Option<PathBuf>,
 }
 
 impl NetworkBackendConfig {
     pub fn new(port_mappings: Vec<(u16, u16)>) -> Self {
-        Self { port_mappings }
+        Self {
+            port_mappings,
+            capture_socket: None,
+            box_dns: false,
+            host_ports: Vec::new(),
+            host_ports_socket: None,
+            firewall: None,
+            firewall_socket: None,
+        }
     }
 }
 
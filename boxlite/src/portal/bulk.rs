@@ -0,0 +1,418 @@
+//! Host side of the bulk transfer channel.
+//!
+//! Moves file contents over a raw socket (bridged to the guest's bulk vsock
+//! port) instead of gRPC. See `boxlite_shared::bulk` for the protocol.
+//! Payloads are moved with `std::io::copy`, which uses sendfile (file to
+//! socket) and splice (socket to file) on Linux. Copies that report
+//! progress do so in steps of [`PROGRESS_CHUNK`] bytes, and stop at the next
+//! step once their operation is cancelled.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::bulk::{BulkRequest, BulkResponse, read_header, write_header};
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::runtime::operations::Progress;
+
+/// Bytes copied between two progress reports.
+const PROGRESS_CHUNK: u64 = 4 << 20;
+
+/// Client for the bulk transfer channel of one box.
+#[derive(Clone, Debug)]
+pub struct BulkChannel {
+    socket_path: PathBuf,
+}
+
+impl BulkChannel {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Copy a host file to `dest` in the container, keeping its permissions,
+    /// reporting the bytes sent to `progress`.
+    ///
+    /// Returns the number of bytes copied.
+    pub(crate) async fn upload(
+        &self,
+        container_id: &str,
+        src: &Path,
+        dest: &str,
+        progress: Option<Progress>,
+    ) -> BoxliteResult<u64> {
+        let socket_path = self.socket_path.clone();
+        let container_id = container_id.to_string();
+        let src = src.to_path_buf();
+        let dest = dest.to_string();
+        run_blocking(move || upload(&socket_path, container_id, &src, dest, progress.as_ref()))
+            .await
+    }
+
+    /// Copy a host file into the container's drop directory as `name`.
+    ///
+    /// Returns the number of bytes copied.
+    pub async fn drop_file(
+        &self,
+        container_id: &str,
+        src: &Path,
+        name: &str,
+    ) -> BoxliteResult<u64> {
+        let socket_path = self.socket_path.clone();
+        let container_id = container_id.to_string();
+        let src = src.to_path_buf();
+        let name = name.to_string();
+        run_blocking(move || {
+            send_file(
+                &socket_path,
+                &src,
+                |mode, size| BulkRequest::Drop {
+                    container_id,
+                    name,
+                    mode,
+                    size,
+                },
+                None,
+            )
+        })
+        .await
+    }
+
+    /// Stage a guest agent binary for `GuestInterface::upgrade_agent`.
+    ///
+    /// Returns the number of bytes copied.
+    pub async fn stage_agent(&self, src: &Path) -> BoxliteResult<u64> {
+        let socket_path = self.socket_path.clone();
+        let src = src.to_path_buf();
+        run_blocking(move || {
+            send_file(
+                &socket_path,
+                &src,
+                |_, size| BulkRequest::StageAgent { size },
+                None,
+            )
+        })
+        .await
+    }
+
+    /// Copy `src` from the container to a host file, reporting the bytes
+    /// received to `progress`.
+    ///
+    /// Returns the number of bytes copied.
+    pub(crate) async fn download(
+        &self,
+        container_id: &str,
+        src: &str,
+        dest: &Path,
+        progress: Option<Progress>,
+    ) -> BoxliteResult<u64> {
+        let socket_path = self.socket_path.clone();
+        let request = BulkRequest::Read {
+            container_id: container_id.to_string(),
+            path: src.to_string(),
+        };
+        let dest = dest.to_path_buf();
+        run_blocking(move || receive_file(&socket_path, &request, &dest, progress.as_ref())).await
+    }
+
+    /// Remove a file or empty directory in the container, if it exists.
+    pub async fn remove(&self, container_id: &str, path: &str) -> BoxliteResult<()> {
+        let socket_path = self.socket_path.clone();
+        let request = BulkRequest::Remove {
+            container_id: container_id.to_string(),
+            path: path.to_string(),
+        };
+        run_blocking(move || send_request(&socket_path, &request)).await?;
+        Ok(())
+    }
+
+    /// Create a directory in the container, with any missing parents.
+    pub async fn make_dir(&self, container_id: &str, path: &str, mode: u32) -> BoxliteResult<()> {
+        let socket_path = self.socket_path.clone();
+        let request = BulkRequest::MakeDir {
+            container_id: container_id.to_string(),
+            path: path.to_string(),
+            mode,
+        };
+        run_blocking(move || send_request(&socket_path, &request)).await?;
+        Ok(())
+    }
+
+    /// Write a zstd-compressed tar of container `paths` to a host file.
+    ///
+    /// Returns the size of the archive.
+    pub async fn archive(
+        &self,
+        container_id: &str,
+        paths: &[String],
+        dest: &Path,
+    ) -> BoxliteResult<u64> {
+        let socket_path = self.socket_path.clone();
+        let request = BulkRequest::Archive {
+            container_id: container_id.to_string(),
+            paths: paths.to_vec(),
+        };
+        let dest = dest.to_path_buf();
+        run_blocking(move || receive_file(&socket_path, &request, &dest, None)).await
+    }
+
+    /// Take the core dump `name` off the guest's crash scratch volume,
+    /// writing it with its metadata as a zstd-compressed tar to a host file.
+    ///
+    /// Returns the size of the archive.
+    pub async fn crash_dump(&self, name: &str, dest: &Path) -> BoxliteResult<u64> {
+        let socket_path = self.socket_path.clone();
+        let request = BulkRequest::CrashDump {
+            name: name.to_string(),
+        };
+        let dest = dest.to_path_buf();
+        run_blocking(move || receive_file(&socket_path, &request, &dest, None)).await
+    }
+}
+
+async fn run_blocking<F>(f: F) -> BoxliteResult<u64>
+where
+    F: FnOnce() -> BoxliteResult<u64> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Bulk transfer task failed: {}", e)))?
+}
+
+fn connect(socket_path: &Path) -> BoxliteResult<UnixStream> {
+    UnixStream::connect(socket_path).map_err(|e| {
+        BoxliteError::Portal(format!(
+            "Failed to connect to bulk channel {}: {}",
+            socket_path.display(),
+            e
+        ))
+    })
+}
+
+fn upload(
+    socket_path: &Path,
+    container_id: String,
+    src: &Path,
+    dest: String,
+    progress: Option<&Progress>,
+) -> BoxliteResult<u64> {
+    send_file(
+        socket_path,
+        src,
+        |mode, size| BulkRequest::Write {
+            container_id,
+            path: dest,
+            mode,
+            size,
+        },
+        progress,
+    )
+}
+
+/// Send a regular file with the request built from its mode and size.
+fn send_file(
+    socket_path: &Path,
+    src: &Path,
+    request: impl FnOnce(u32, u64) -> BulkRequest,
+    progress: Option<&Progress>,
+) -> BoxliteResult<u64> {
+    let mut file = File::open(src)
+        .map_err(|e| BoxliteError::Storage(format!("Failed to open {}: {}", src.display(), e)))?;
+    let metadata = file
+        .metadata()
+        .map_err(|e| BoxliteError::Storage(format!("Failed to stat {}: {}", src.display(), e)))?;
+    if !metadata.is_file() {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "{} is not a regular file",
+            src.display()
+        )));
+    }
+    let size = metadata.len();
+
+    let mut conn = connect(socket_path)?;
+    let request = request(metadata.permissions().mode() & 0o7777, size);
+    let sent =
+        write_header(&mut conn, &request).and_then(|_| copy(&mut file, &mut conn, size, progress));
+
+    // The guest may reject the write and close early; its reason is more
+    // useful than the resulting broken pipe.
+    match (sent, read_response(&mut conn)) {
+        (_, Ok(BulkResponse::Error { reason })) => Err(BoxliteError::Storage(reason)),
+        (Err(e), _) => Err(transfer_error(e)),
+        (Ok(_), Ok(BulkResponse::Ok { size: written })) if written == size => Ok(written),
+        (Ok(_), Ok(BulkResponse::Ok { size: written })) => Err(BoxliteError::Storage(format!(
+            "Source changed during copy ({} of {} bytes written)",
+            written, size
+        ))),
+        (Ok(_), Err(e)) => Err(e),
+    }
+}
+
+/// Send `request` and write the payload the guest answers with to `dest`.
+fn receive_file(
+    socket_path: &Path,
+    request: &BulkRequest,
+    dest: &Path,
+    progress: Option<&Progress>,
+) -> BoxliteResult<u64> {
+    let mut conn = connect(socket_path)?;
+    write_header(&mut conn, request).map_err(transfer_error)?;
+
+    let size = match read_response(&mut conn)? {
+        BulkResponse::Ok { size } => size,
+        BulkResponse::Error { reason } => return Err(BoxliteError::Storage(reason)),
+    };
+
+    let mut file = File::create(dest).map_err(|e| {
+        BoxliteError::Storage(format!("Failed to create {}: {}", dest.display(), e))
+    })?;
+    let mut payload = io::Read::take(&mut conn, size);
+    let received = copy(&mut payload, &mut file, size, progress).map_err(transfer_error)?;
+    if received < size {
+        return Err(BoxliteError::Portal(format!(
+            "Bulk channel closed after {} of {} bytes",
+            received, size
+        )));
+    }
+    Ok(received)
+}
+
+/// `io::copy` that reports to `progress`, if any, expecting `size` bytes.
+fn copy(
+    reader: &mut impl io::Read,
+    writer: &mut impl io::Write,
+    size: u64,
+    progress: Option<&Progress>,
+) -> io::Result<u64> {
+    let Some(progress) = progress else {
+        return io::copy(reader, writer);
+    };
+    progress.set_total(size);
+    let mut copied = 0;
+    loop {
+        if progress.is_cancelled() {
+            return Err(io::Error::other("operation cancelled"));
+        }
+        let n = io::copy(&mut reader.by_ref().take(PROGRESS_CHUNK), writer)?;
+        if n == 0 {
+            return Ok(copied);
+        }
+        copied += n;
+        progress.advance(n);
+    }
+}
+
+/// Send a request that carries no payload either way.
+fn send_request(socket_path: &Path, request: &BulkRequest) -> BoxliteResult<u64> {
+    let mut conn = connect(socket_path)?;
+    write_header(&mut conn, request).map_err(transfer_error)?;
+    match read_response(&mut conn)? {
+        BulkResponse::Ok { size } => Ok(size),
+        BulkResponse::Error { reason } => Err(BoxliteError::Storage(reason)),
+    }
+}
+
+fn read_response(conn: &mut UnixStream) -> BoxliteResult<BulkResponse> {
+    read_header(conn).map_err(transfer_error)
+}
+
+fn transfer_error(e: io::Error) -> BoxliteError {
+    BoxliteError::Portal(format!("Bulk transfer failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::operations::{OperationKind, OperationRegistry};
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+
+    /// Serve one transfer like the guest does, storing written files in memory.
+    fn fake_guest(
+        listener: UnixListener,
+        contents: &'static [u8],
+    ) -> std::thread::JoinHandle<Vec<u8>> {
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            match read_header::<BulkRequest>(&mut conn).unwrap() {
+                BulkRequest::Write { size, .. }
+                | BulkRequest::Drop { size, .. }
+                | BulkRequest::StageAgent { size } => {
+                    let mut data = Vec::new();
+                    (&mut conn).take(size).read_to_end(&mut data).unwrap();
+                    write_header(&mut conn, &BulkResponse::Ok { size }).unwrap();
+                    data
+                }
+                BulkRequest::Read { path, .. } if path == "/missing" => {
+                    let reason = "Failed to read /missing: not found".to_string();
+                    write_header(&mut conn, &BulkResponse::Error { reason }).unwrap();
+                    Vec::new()
+                }
+                BulkRequest::Read { .. }
+                | BulkRequest::Archive { .. }
+                | BulkRequest::CrashDump { .. } => {
+                    let size = contents.len() as u64;
+                    write_header(&mut conn, &BulkResponse::Ok { size }).unwrap();
+                    conn.write_all(contents).unwrap();
+                    Vec::new()
+                }
+                BulkRequest::Remove { .. } | BulkRequest::MakeDir { .. } => {
+                    write_header(&mut conn, &BulkResponse::Ok { size: 0 }).unwrap();
+                    Vec::new()
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("bulk.sock");
+        let channel = BulkChannel::new(socket.clone());
+
+        let src = dir.path().join("src.bin");
+        std::fs::write(&src, b"uploaded bytes").unwrap();
+        let guest = fake_guest(UnixListener::bind(&socket).unwrap(), b"");
+        assert_eq!(
+            channel.upload("c1", &src, "/data.bin", None).await.unwrap(),
+            14
+        );
+        assert_eq!(guest.join().unwrap(), b"uploaded bytes");
+
+        std::fs::remove_file(&socket).unwrap();
+        let dest = dir.path().join("dest.bin");
+        let guest = fake_guest(UnixListener::bind(&socket).unwrap(), b"downloaded");
+        let operations = OperationRegistry::default();
+        let operation = operations.start(OperationKind::CopyOut, "/data.bin", None);
+        let progress = Some(operation.progress().clone());
+        assert_eq!(
+            channel
+                .download("c1", "/data.bin", &dest, progress)
+                .await
+                .unwrap(),
+            10
+        );
+        guest.join().unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"downloaded");
+        let reported = &operations.list()[0];
+        assert_eq!((reported.done, reported.total), (10, Some(10)));
+    }
+
+    #[tokio::test]
+    async fn test_guest_error_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("bulk.sock");
+        let guest = fake_guest(UnixListener::bind(&socket).unwrap(), b"");
+
+        let err = BulkChannel::new(socket)
+            .download("c1", "/missing", &dir.path().join("out"), None)
+            .await
+            .unwrap_err();
+        guest.join().unwrap();
+        assert!(matches!(err, BoxliteError::Storage(ref reason) if reason.contains("not found")));
+        // Nothing is created when the guest refuses
+        assert!(!dir.path().join("out").exists());
+    }
+}
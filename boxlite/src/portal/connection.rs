@@ -1,7 +1,11 @@
 //! Connection management.
 //!
 //! Converts Transport to tonic Channel with lazy initialization.
+//!
+//! The channel can be wrapped to record the RPCs it carries, or stood in
+//! for by a replayed recording (see [`replay`](crate::portal::replay)).
 
+use crate::portal::replay::{AgentChannel, Replayer, RpcRecorder};
 use boxlite_shared::{BoxliteError, BoxliteResult, Transport};
 use hyper_util::rt::TokioIo;
 use std::sync::Arc;
@@ -16,38 +20,104 @@ use tower::service_fn;
 pub struct Connection {
     transport: Transport,
     channel: Arc<OnceCell<Channel>>,
+    mode: RpcMode,
+    /// Duplicates of the channel's Unix sockets, for severing them
+    #[cfg(feature = "fault-injection")]
+    sockets: Sockets,
+}
+
+/// What happens to the RPCs of a connection.
+#[derive(Clone, Default)]
+pub enum RpcMode {
+    /// Sent to the agent.
+    #[default]
+    Live,
+    /// Sent to the agent and recorded.
+    Record(Arc<RpcRecorder>),
+    /// Answered from a recording; the transport is never connected.
+    Replay(Arc<Replayer>),
 }
 
+#[cfg(feature = "fault-injection")]
+type Sockets = Arc<std::sync::Mutex<Vec<std::os::fd::OwnedFd>>>;
+
 impl Connection {
     /// Create a lazy connection (does not connect immediately).
     pub fn new(transport: Transport) -> Self {
         Self {
             transport,
             channel: Arc::new(OnceCell::new()),
+            mode: RpcMode::Live,
+            #[cfg(feature = "fault-injection")]
+            sockets: Sockets::default(),
         }
     }
 
+    /// Record or replay the connection's RPCs.
+    pub fn with_mode(mut self, mode: RpcMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     /// Get or establish the channel.
-    pub async fn channel(&self) -> BoxliteResult<Channel> {
+    pub async fn channel(&self) -> BoxliteResult<AgentChannel> {
+        if let RpcMode::Replay(replayer) = &self.mode {
+            return Ok(AgentChannel::Replay(Arc::clone(replayer)));
+        }
         let channel = self
             .channel
-            .get_or_try_init(|| async { connect_transport(&self.transport).await })
+            .get_or_try_init(|| async {
+                connect_transport(
+                    &self.transport,
+                    #[cfg(feature = "fault-injection")]
+                    &self.sockets,
+                )
+                .await
+            })
             .await?;
 
-        Ok(channel.clone())
+        Ok(match &self.mode {
+            RpcMode::Record(recorder) => {
+                AgentChannel::Recording(channel.clone(), Arc::clone(recorder))
+            }
+            _ => AgentChannel::Live(channel.clone()),
+        })
+    }
+
+    /// Shut down the sockets under the channel, as if the vsock connection
+    /// to the guest dropped. In-flight calls fail; the channel reconnects
+    /// on the next one.
+    #[cfg(feature = "fault-injection")]
+    pub fn sever(&self) -> usize {
+        use std::os::fd::AsRawFd;
+
+        let sockets = std::mem::take(&mut *self.sockets.lock().unwrap());
+        for socket in &sockets {
+            // SAFETY: socket is an open descriptor owned here
+            unsafe { libc::shutdown(socket.as_raw_fd(), libc::SHUT_RDWR) };
+        }
+        sockets.len()
     }
 }
 
 /// Connect to a transport.
-async fn connect_transport(transport: &Transport) -> BoxliteResult<Channel> {
+async fn connect_transport(
+    transport: &Transport,
+    #[cfg(feature = "fault-injection")] sockets: &Sockets,
+) -> BoxliteResult<Channel> {
     match transport {
         Transport::Unix { socket_path } => {
             tracing::debug!("Connecting via Unix: {}", socket_path.display());
-            connect_unix(socket_path).await
+            connect_unix(
+                socket_path,
+                #[cfg(feature = "fault-injection")]
+                sockets.clone(),
+            )
+            .await
         }
-        Transport::Tcp { port } => {
-            tracing::debug!("Connecting via TCP: 127.0.0.1:{}", port);
-            connect_tcp(*port).await
+        Transport::Tcp { host, port } => {
+            tracing::debug!("Connecting via TCP: {}:{}", host, port);
+            connect_tcp(host, *port).await
         }
         Transport::Vsock { port } => Err(BoxliteError::Internal(format!(
             "Vsock client not yet implemented (port: {})",
@@ -56,14 +126,25 @@ async fn connect_transport(transport: &Transport) -> BoxliteResult<Channel> {
     }
 }
 
-async fn connect_unix(socket_path: &std::path::Path) -> BoxliteResult<Channel> {
+async fn connect_unix(
+    socket_path: &std::path::Path,
+    #[cfg(feature = "fault-injection")] sockets: Sockets,
+) -> BoxliteResult<Channel> {
     let socket_path = socket_path.to_path_buf();
 
     let channel = Endpoint::try_from("http://[::]:50051")?
         .connect_with_connector(service_fn(move |_: Uri| {
             let socket_path = socket_path.clone();
+            #[cfg(feature = "fault-injection")]
+            let sockets = sockets.clone();
             async move {
                 let stream = tokio::net::UnixStream::connect(socket_path).await?;
+                #[cfg(feature = "fault-injection")]
+                {
+                    use std::os::fd::AsFd;
+                    let socket = stream.as_fd().try_clone_to_owned()?;
+                    sockets.lock().unwrap().push(socket);
+                }
                 Ok::<_, std::io::Error>(TokioIo::new(stream))
             }
         }))
@@ -73,8 +154,8 @@ async fn connect_unix(socket_path: &std::path::Path) -> BoxliteResult<Channel> {
     Ok(channel)
 }
 
-async fn connect_tcp(port: u16) -> BoxliteResult<Channel> {
-    let addr = format!("http://127.0.0.1:{}", port);
+async fn connect_tcp(host: &str, port: u16) -> BoxliteResult<Channel> {
+    let addr = format!("http://{}:{}", host, port);
     let channel = Endpoint::try_from(addr)?.connect().await?;
 
     tracing::debug!("Connected via TCP");
@@ -0,0 +1,318 @@
+//! Host side of credential forwarding.
+//!
+//! The guest relays two services to the host over vsock (see the guest's
+//! `container::credentials`):
+//!
+//! - SSH agent: libkrun connects the guest port straight to the host's
+//!   `$SSH_AUTH_SOCK`, so agent messages never pass through boxlite.
+//! - Git credentials: [`GitCredentialServer`] answers the guest-side
+//!   credential helper by running `git credential fill` on the host. Each
+//!   connection carries the helper operation on its first line, then git's
+//!   `key=value` attributes until EOF; the reply is git's output.
+//!
+//! Only `get` is answered. `store` and `erase` are ignored so that a box
+//! cannot write to or wipe the host's credential store. A `get` is only
+//! passed on for the box's allowed hosts, and only with the attributes
+//! naming the credential, so a `url` attribute cannot redirect it.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+
+/// Largest accepted request, to bound what a box can make the host buffer.
+const MAX_REQUEST_LEN: u64 = 64 * 1024;
+
+/// Host credentials forwarded into one box.
+#[derive(Debug, Default)]
+pub struct CredentialForwarding {
+    ssh_agent_socket: Option<PathBuf>,
+    git_server: Option<GitCredentialServer>,
+}
+
+impl CredentialForwarding {
+    /// Set up the requested forwarding.
+    ///
+    /// SSH agent forwarding is skipped with a warning when the host has no
+    /// agent (`SSH_AUTH_SOCK` unset). Git credentials are answered for
+    /// `git_hosts`, or with none given, the hosts of the remotes of the git
+    /// repository in the current directory.
+    pub fn start(
+        ssh_agent: bool,
+        git_credentials: bool,
+        git_hosts: &[String],
+        git_socket_path: PathBuf,
+    ) -> BoxliteResult<Self> {
+        let ssh_agent_socket = if ssh_agent {
+            let socket = std::env::var_os("SSH_AUTH_SOCK").map(PathBuf::from);
+            if socket.is_none() {
+                tracing::warn!("SSH agent forwarding requested but SSH_AUTH_SOCK is not set");
+            }
+            socket
+        } else {
+            None
+        };
+        let git_server = if git_credentials {
+            let hosts = if git_hosts.is_empty() {
+                remote_hosts()
+            } else {
+                git_hosts.to_vec()
+            };
+            if hosts.is_empty() {
+                tracing::warn!(
+                    "Git credential forwarding requested but no hosts are allowed; \
+                     set git_credential_hosts"
+                );
+            }
+            Some(GitCredentialServer::bind(git_socket_path, hosts)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            ssh_agent_socket,
+            git_server,
+        })
+    }
+
+    /// Host agent socket to bridge to the guest, if forwarded.
+    pub fn ssh_agent_socket(&self) -> Option<&Path> {
+        self.ssh_agent_socket.as_deref()
+    }
+
+    /// Socket of the git credential server, if forwarded.
+    pub fn git_socket(&self) -> Option<&Path> {
+        self.git_server
+            .as_ref()
+            .map(|server| server.socket_path.as_path())
+    }
+}
+
+/// Answers git credential requests from a box.
+///
+/// Serves until dropped. Runs in the runtime process, so a detached box
+/// loses git credentials once that process exits.
+#[derive(Debug)]
+pub struct GitCredentialServer {
+    socket_path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl GitCredentialServer {
+    /// Listen on `socket_path`, replacing a stale socket, and answer for
+    /// `hosts` only.
+    pub fn bind(socket_path: PathBuf, hosts: Vec<String>) -> BoxliteResult<Self> {
+        if socket_path.exists() {
+            std::fs::remove_file(&socket_path)?;
+        }
+        let listener = UnixListener::bind(&socket_path).map_err(|e| {
+            BoxliteError::Portal(format!(
+                "Failed to bind git credential socket {}: {}",
+                socket_path.display(),
+                e
+            ))
+        })?;
+
+        let hosts: Arc<[String]> = hosts.into();
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((conn, _)) => {
+                        let hosts = Arc::clone(&hosts);
+                        tokio::spawn(async move {
+                            if let Err(e) = serve(conn, &hosts).await {
+                                tracing::warn!("Git credential request failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        tracing::warn!("Git credential server stopped: {}", e);
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Self { socket_path, task })
+    }
+}
+
+impl Drop for GitCredentialServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+async fn serve(mut conn: UnixStream, hosts: &[String]) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    (&mut conn)
+        .take(MAX_REQUEST_LEN)
+        .read_to_end(&mut request)
+        .await?;
+
+    let reply = match parse_request(&request) {
+        Some(("get", attributes)) => match allowed_request(attributes, hosts) {
+            Some(attributes) => git_credential_fill(&attributes).await?,
+            None => {
+                tracing::warn!("Refused git credentials for a host the box may not use");
+                Vec::new()
+            }
+        },
+        _ => Vec::new(),
+    };
+    conn.write_all(&reply).await?;
+    conn.shutdown().await
+}
+
+/// Split a request into the helper operation and git's attributes.
+fn parse_request(request: &[u8]) -> Option<(&str, &[u8])> {
+    let newline = request.iter().position(|&b| b == b'\n')?;
+    let operation = std::str::from_utf8(&request[..newline]).ok()?;
+    Some((operation.trim(), &request[newline + 1..]))
+}
+
+/// The attributes to pass to git for a `get`, or `None` when its host is
+/// not in `hosts`.
+fn allowed_request(attributes: &[u8], hosts: &[String]) -> Option<Vec<u8>> {
+    let attributes = std::str::from_utf8(attributes).ok()?;
+    let mut host = None;
+    let mut forwarded = String::new();
+    for line in attributes.lines().filter(|line| !line.is_empty()) {
+        let (key, value) = line.split_once('=')?;
+        match key {
+            // Git would use the last one; be sure which one that is
+            "host" if host.replace(value).is_some() => return None,
+            "host" | "protocol" | "path" | "username" => {}
+            // Anything else, notably `url`, could point git elsewhere
+            _ => continue,
+        }
+        forwarded.push_str(line);
+        forwarded.push('\n');
+    }
+    let host = host?;
+    hosts
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        .then(|| forwarded.into_bytes())
+}
+
+/// Hosts of the remotes of the git repository in the current directory.
+fn remote_hosts() -> Vec<String> {
+    let output = std::process::Command::new("git")
+        .args(["config", "--get-regexp", r"^remote\..*\.(push)?url$"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    let mut hosts: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| url_host(line.split_once(' ')?.1))
+        .map(str::to_string)
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
+/// Host of a remote URL, with its port as git's `host` attribute has it.
+fn url_host(url: &str) -> Option<&str> {
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split('/').next()?,
+        // scp-like `user@host:path`
+        None => url.split_once(':')?.0,
+    };
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    (!host.is_empty()).then_some(host)
+}
+
+async fn git_credential_fill(attributes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut child = tokio::process::Command::new("git")
+        .args(["credential", "fill"])
+        // Never block a box on an interactive prompt on the host
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env_remove("GIT_ASKPASS")
+        .env_remove("SSH_ASKPASS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(attributes).await?;
+    }
+    let output = child.wait_with_output().await?;
+    // No credential available: git exits non-zero, the helper replies nothing
+    Ok(if output.status.success() {
+        output.stdout
+    } else {
+        Vec::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let (operation, attributes) =
+            parse_request(b"get\nprotocol=https\nhost=github.com\n").unwrap();
+        assert_eq!(operation, "get");
+        assert_eq!(attributes, b"protocol=https\nhost=github.com\n");
+
+        assert!(parse_request(b"get").is_none());
+    }
+
+    #[test]
+    fn test_only_allowed_hosts_are_asked() {
+        let hosts = ["github.com".to_string()];
+        assert_eq!(
+            allowed_request(b"protocol=https\nhost=GitHub.com\n", &hosts).unwrap(),
+            b"protocol=https\nhost=GitHub.com\n"
+        );
+        assert!(allowed_request(b"protocol=https\nhost=evil.com\n", &hosts).is_none());
+        assert!(allowed_request(b"protocol=https\n", &hosts).is_none());
+        assert!(allowed_request(b"host=github.com\nhost=evil.com\n", &hosts).is_none());
+        // A url would override the allowed host
+        assert_eq!(
+            allowed_request(b"host=github.com\nurl=https://evil.com/\n", &hosts).unwrap(),
+            b"host=github.com\n"
+        );
+
+        assert_eq!(
+            url_host("https://git@github.com/a/b.git"),
+            Some("github.com")
+        );
+        assert_eq!(
+            url_host("https://git.example:8443/a"),
+            Some("git.example:8443")
+        );
+        assert_eq!(url_host("git@github.com:a/b.git"), Some("github.com"));
+        assert_eq!(url_host("/srv/repo.git"), None);
+    }
+
+    #[tokio::test]
+    async fn test_store_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let server =
+            GitCredentialServer::bind(dir.path().join("git.sock"), vec!["example.com".to_string()])
+                .unwrap();
+
+        let mut conn = UnixStream::connect(&server.socket_path).await.unwrap();
+        conn.write_all(b"store\nprotocol=https\nhost=example.com\npassword=x\n")
+            .await
+            .unwrap();
+        conn.shutdown().await.unwrap();
+        let mut reply = Vec::new();
+        conn.read_to_end(&mut reply).await.unwrap();
+        assert!(reply.is_empty());
+    }
+}
@@ -1,20 +1,36 @@
 //! Container service interface.
 
+use crate::portal::replay::AgentChannel;
 use boxlite_shared::{
-    BindMount, BoxliteError, BoxliteResult, ContainerClient,
-    ContainerConfig as ProtoContainerConfig, ContainerInitRequest, DiskRootfs, MergedRootfs,
-    OverlayRootfs, RootfsInit, container_init_response,
+    BindMount, BoxliteError, BoxliteResult, ClipboardResponse, CommandCheck, ContainerClient,
+    ContainerConfig as ProtoContainerConfig, ContainerInitRequest, CreateUserRequest,
+    CredentialForwarding as ProtoCredentialForwarding, DeleteUserRequest,
+    DevicePolicy as ProtoDevicePolicy, DeviceRule, DigestPathsRequest, DiskRootfs,
+    ExecuteCellRequest, FileChange, FreezeRootfsRequest, GetClipboardRequest, HealthEvent,
+    HealthProbe, HomeStorage as ProtoHomeStorage, HttpCheck, InstallPackagesEvent,
+    InstallPackagesRequest, ListProcessesRequest, ListTasksRequest, MergedRootfs,
+    NotifyChangesRequest, OverlayRootfs, PeriodicTask, ProbeKind, ReadaheadHint,
+    RecordReadaheadRequest, RegisterTaskRequest, RootfsInit, SetClipboardRequest,
+    SetupStep as ProtoSetupStep, SharingConfig, ShutdownKernelRequest, SshConfig, TaskResponse,
+    UnregisterTaskRequest, UserResponse, WatchHealthRequest, WatchPathEvent, WatchPathRequest,
+    clipboard_response, container_init_response, health_probe, task_response, user_response,
 };
-use tonic::transport::Channel;
 
+use crate::litebox::{CellOutput, HomeStorage, ProcessInfo, TaskStatus, UserSpec};
+use crate::portal::credentials::CredentialForwarding;
+use crate::runtime::exec_policy::ExecPolicy;
+use crate::runtime::options::{
+    ClipboardPolicy, DevicePolicy, Probe, ProbeCheck, ScheduledTask, SetupStep, SharingOptions,
+    SshOptions,
+};
 use crate::volumes::ContainerMount;
+use std::sync::Arc;
 
 /// Container rootfs initialization strategy.
 /// Guest constructs paths from container_id using its own layout knowledge.
 #[derive(Debug, Clone)]
 pub enum ContainerRootfsInitConfig {
     /// Single merged rootfs - guest constructs path from container_id
-    #[allow(dead_code)] // Reserved for future merged rootfs mode
     Merged,
     /// Overlayfs from multiple layers - guest constructs paths from container_id and layer_names
     #[allow(dead_code)] // Reserved for future overlayfs mode
@@ -32,6 +48,8 @@ pub enum ContainerRootfsInitConfig {
         need_format: bool,
         /// Whether to resize filesystem after mounting to fill disk
         need_resize: bool,
+        /// Rootfs ranges to read ahead once the disk is mounted
+        readahead: Vec<ReadaheadHint>,
     },
 }
 
@@ -58,11 +76,13 @@ impl ContainerRootfsInitConfig {
                 device,
                 need_format,
                 need_resize,
+                readahead,
             } => RootfsInit {
                 strategy: Some(boxlite_shared::rootfs_init::Strategy::Disk(DiskRootfs {
                     device,
                     need_format,
                     need_resize,
+                    readahead,
                 })),
             },
         }
@@ -71,17 +91,25 @@ impl ContainerRootfsInitConfig {
 
 /// Container service interface.
 pub struct ContainerInterface {
-    client: ContainerClient<Channel>,
+    client: ContainerClient<AgentChannel>,
+    policy: Arc<ExecPolicy>,
 }
 
 impl ContainerInterface {
     /// Create from a channel.
-    pub fn new(channel: Channel) -> Self {
+    pub fn new(channel: AgentChannel) -> Self {
         Self {
             client: ContainerClient::new(channel),
+            policy: Arc::default(),
         }
     }
 
+    /// Hold the commands of tasks, setup steps, probes and cells to `policy`.
+    pub(crate) fn with_exec_policy(mut self, policy: Arc<ExecPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Initialize container with configuration.
     ///
     /// # Arguments
@@ -89,15 +117,49 @@ impl ContainerInterface {
     /// * `image_config` - Image-derived container config (entrypoint, env, workdir)
     /// * `rootfs` - Rootfs initialization strategy
     /// * `mounts` - Bind mounts from guest VM paths into container
+    /// * `devices` - Guest device nodes to expose in the container
+    /// * `package_cache` - Container path of the package cache, if mounted
+    /// * `ssh` - SSH server to start in the container, if enabled
+    /// * `credentials` - Host credentials forwarded into the container
+    /// * `x11` - Whether to serve an X11 display relayed to the host
+    /// * `sharing` - Clipboard and drop directory shared with the host
+    /// * `lock_proxy` - Whether to serve `boxlite-lock` for shared volumes
+    /// * `etc_overlay` - Whether to give the container a per-box writable `/etc`
+    /// * `device_policy` - Extra `/dev` nodes and their permissions
+    /// * `fuse` - Whether FUSE mounts are allowed (`/dev/fuse` must be in `devices`)
+    /// * `nested_containers` - Whether to prepare the container for container engines
+    /// * `systemd` - Whether to boot the image's systemd instead of its entrypoint
+    /// * `masked_paths` - Container paths hidden from the workload
+    /// * `readonly_paths` - Container paths remounted read-only
+    /// * `dns_search` - Search domains added to the container's resolv.conf
+    /// * `new_machine_id` - Whether to replace the machine ID of a cloned rootfs
     ///
     /// # Returns
     /// Container ID on success
+    #[allow(clippy::too_many_arguments)]
     pub async fn init(
         &mut self,
         container_id: &str,
         image_config: crate::images::ContainerImageConfig,
         rootfs: ContainerRootfsInitConfig,
         mounts: Vec<ContainerMount>,
+        devices: Vec<String>,
+        package_cache: Option<&str>,
+        ssh: Option<&SshOptions>,
+        credentials: Option<&CredentialForwarding>,
+        x11: bool,
+        sharing: Option<&SharingOptions>,
+        lock_proxy: bool,
+        etc_overlay: bool,
+        device_policy: &DevicePolicy,
+        fuse: bool,
+        nested_containers: bool,
+        systemd: bool,
+        masked_paths: Vec<String>,
+        readonly_paths: Vec<String>,
+        setup_steps: &[SetupStep],
+        dns_search: Vec<String>,
+        new_machine_id: bool,
     ) -> BoxliteResult<String> {
         let proto_config = ProtoContainerConfig {
             entrypoint: image_config.cmd.clone(),
@@ -113,6 +175,8 @@ impl ContainerInterface {
                 volume_name: m.volume_name,
                 destination: m.destination,
                 read_only: m.read_only,
+                hidden: m.hidden,
+                populate: m.populate,
             })
             .collect();
 
@@ -132,6 +196,56 @@ impl ContainerInterface {
             container_config: Some(proto_config),
             rootfs: Some(rootfs.into_proto()),
             mounts: proto_mounts,
+            devices,
+            package_cache: package_cache.unwrap_or_default().to_string(),
+            ssh: ssh.map(|ssh| SshConfig {
+                authorized_keys: ssh.authorized_keys.clone(),
+                port: SshOptions::GUEST_PORT.into(),
+            }),
+            credentials: credentials.map(|c| ProtoCredentialForwarding {
+                ssh_agent: c.ssh_agent_socket().is_some(),
+                git_credentials: c.git_socket().is_some(),
+            }),
+            x11,
+            sharing: sharing.map(|sharing| SharingConfig {
+                clipboard: sharing.clipboard != ClipboardPolicy::Disabled,
+                drop_dir: sharing.drop_dir.clone().unwrap_or_default(),
+            }),
+            lock_proxy,
+            etc_overlay,
+            device_policy: Some(ProtoDevicePolicy {
+                devtmpfs: device_policy.devtmpfs,
+                nodes: device_policy
+                    .nodes
+                    .iter()
+                    .map(|node| DeviceRule {
+                        path: node.path.clone(),
+                        mode: node.mode,
+                        uid: node.uid,
+                        gid: node.gid,
+                    })
+                    .collect(),
+            }),
+            fuse,
+            nested_containers,
+            systemd,
+            masked_paths,
+            readonly_paths,
+            setup_steps: setup_steps
+                .iter()
+                .map(|step| {
+                    let (program, args) = self.policy.apply_program(&step.command, &step.args)?;
+                    Ok(ProtoSetupStep {
+                        name: step.name.clone(),
+                        program,
+                        args,
+                        env: step.env.iter().cloned().collect(),
+                        timeout_ms: step.timeout_secs.unwrap_or(0).saturating_mul(1000),
+                    })
+                })
+                .collect::<BoxliteResult<_>>()?,
+            dns_search,
+            new_machine_id,
         };
 
         let response = self.client.init(request).await?.into_inner();
@@ -153,4 +267,360 @@ impl ContainerInterface {
             )),
         }
     }
+
+    /// Create a user account in the container.
+    pub async fn create_user(&mut self, container_id: &str, user: &UserSpec) -> BoxliteResult<()> {
+        let home_storage = match user.home_storage {
+            HomeStorage::Rootfs => ProtoHomeStorage::Rootfs,
+            HomeStorage::Tmpfs => ProtoHomeStorage::Tmpfs,
+        };
+        let request = CreateUserRequest {
+            container_id: container_id.to_string(),
+            name: user.name.clone(),
+            uid: user.uid,
+            gid: user.gid.unwrap_or(user.uid),
+            shell: user.shell.clone().unwrap_or_else(|| "/bin/sh".to_string()),
+            home: user
+                .home
+                .clone()
+                .unwrap_or_else(|| format!("/home/{}", user.name)),
+            home_storage: home_storage.into(),
+        };
+
+        let response = self.client.create_user(request).await?.into_inner();
+        Self::map_user_response(response, "Create user")
+    }
+
+    /// Delete a user account from the container.
+    pub async fn delete_user(
+        &mut self,
+        container_id: &str,
+        name: &str,
+        remove_home: bool,
+    ) -> BoxliteResult<()> {
+        let request = DeleteUserRequest {
+            container_id: container_id.to_string(),
+            name: name.to_string(),
+            remove_home,
+        };
+
+        let response = self.client.delete_user(request).await?.into_inner();
+        Self::map_user_response(response, "Delete user")
+    }
+
+    /// Register a periodic task in the container.
+    pub async fn register_task(
+        &mut self,
+        container_id: &str,
+        task: &ScheduledTask,
+    ) -> BoxliteResult<()> {
+        let (program, args) = self.policy.apply_program(&task.command, &task.args)?;
+        let request = RegisterTaskRequest {
+            container_id: container_id.to_string(),
+            task: Some(PeriodicTask {
+                name: task.name.clone(),
+                program,
+                args,
+                env: task.env.iter().cloned().collect(),
+                interval_ms: task.interval_secs.saturating_mul(1000),
+                timeout_ms: task.timeout_secs.unwrap_or(0).saturating_mul(1000),
+            }),
+        };
+
+        let response = self.client.register_task(request).await?.into_inner();
+        Self::map_task_response(response, "Register task")
+    }
+
+    /// Remove a periodic task.
+    pub async fn unregister_task(&mut self, name: &str) -> BoxliteResult<()> {
+        let request = UnregisterTaskRequest {
+            name: name.to_string(),
+        };
+
+        let response = self.client.unregister_task(request).await?.into_inner();
+        Self::map_task_response(response, "Unregister task")
+    }
+
+    /// List periodic tasks and their last runs.
+    pub async fn list_tasks(&mut self) -> BoxliteResult<Vec<TaskStatus>> {
+        let response = self
+            .client
+            .list_tasks(ListTasksRequest {})
+            .await?
+            .into_inner();
+        Ok(response.tasks.into_iter().map(TaskStatus::from).collect())
+    }
+
+    fn map_task_response(response: TaskResponse, op: &str) -> BoxliteResult<()> {
+        match response.result {
+            Some(task_response::Result::Success(_)) => Ok(()),
+            Some(task_response::Result::Error(err)) => Err(BoxliteError::InvalidArgument(format!(
+                "{} failed: {}",
+                op, err.reason
+            ))),
+            None => Err(BoxliteError::Internal(format!(
+                "{} response missing result",
+                op
+            ))),
+        }
+    }
+
+    /// Replace the container's shared clipboard.
+    pub async fn set_clipboard(&mut self, container_id: &str, content: &[u8]) -> BoxliteResult<()> {
+        let request = SetClipboardRequest {
+            container_id: container_id.to_string(),
+            content: content.to_vec(),
+        };
+
+        let response = self.client.set_clipboard(request).await?.into_inner();
+        Self::map_clipboard_response(response).map(|_| ())
+    }
+
+    /// Read the container's shared clipboard.
+    pub async fn get_clipboard(&mut self, container_id: &str) -> BoxliteResult<Vec<u8>> {
+        let request = GetClipboardRequest {
+            container_id: container_id.to_string(),
+        };
+
+        let response = self.client.get_clipboard(request).await?.into_inner();
+        Self::map_clipboard_response(response)
+    }
+
+    /// Replay host-side volume changes in the container.
+    ///
+    /// Returns how many changes the guest replayed.
+    pub async fn notify_changes(
+        &mut self,
+        container_id: &str,
+        changes: Vec<FileChange>,
+    ) -> BoxliteResult<u32> {
+        let request = NotifyChangesRequest {
+            container_id: container_id.to_string(),
+            changes,
+        };
+
+        let response = self.client.notify_changes(request).await?.into_inner();
+        Ok(response.replayed)
+    }
+
+    /// Start installing packages; the stream ends with the result.
+    pub async fn install_packages(
+        &mut self,
+        container_id: &str,
+        packages: &[String],
+        update_index: bool,
+    ) -> BoxliteResult<tonic::Streaming<InstallPackagesEvent>> {
+        let request = InstallPackagesRequest {
+            container_id: container_id.to_string(),
+            packages: packages.to_vec(),
+            update_index,
+        };
+
+        Ok(self.client.install_packages(request).await?.into_inner())
+    }
+
+    /// Run code in a guest interpreter session.
+    pub async fn execute_cell(
+        &mut self,
+        container_id: &str,
+        kernel_id: &str,
+        code: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> BoxliteResult<CellOutput> {
+        // The guest runs cells in python3; rules can refuse but not rewrite them
+        self.policy
+            .apply_program("python3", &["-c".to_string(), code.to_string()])?;
+        let request = ExecuteCellRequest {
+            container_id: container_id.to_string(),
+            kernel_id: kernel_id.to_string(),
+            language: "python".to_string(),
+            code: code.to_string(),
+            timeout_ms: timeout.map_or(0, |t| t.as_millis() as u64),
+        };
+
+        Ok(self.client.execute_cell(request).await?.into_inner().into())
+    }
+
+    /// Stop a guest interpreter session.
+    pub async fn shutdown_kernel(
+        &mut self,
+        container_id: &str,
+        kernel_id: &str,
+    ) -> BoxliteResult<()> {
+        let request = ShutdownKernelRequest {
+            container_id: container_id.to_string(),
+            kernel_id: kernel_id.to_string(),
+        };
+
+        self.client.shutdown_kernel(request).await?;
+        Ok(())
+    }
+
+    /// Content digests of paths in the container, in the order given;
+    /// `None` for a path that does not exist.
+    pub async fn digest_paths(
+        &mut self,
+        container_id: &str,
+        paths: &[String],
+    ) -> BoxliteResult<Vec<(String, Option<String>)>> {
+        let request = DigestPathsRequest {
+            container_id: container_id.to_string(),
+            paths: paths.to_vec(),
+        };
+
+        let response = self.client.digest_paths(request).await?.into_inner();
+        Ok(response
+            .digests
+            .into_iter()
+            .map(|d| (d.path, Some(d.digest).filter(|digest| !digest.is_empty())))
+            .collect())
+    }
+
+    /// Processes running in the container, by pid.
+    pub async fn list_processes(&mut self, container_id: &str) -> BoxliteResult<Vec<ProcessInfo>> {
+        let request = ListProcessesRequest {
+            container_id: container_id.to_string(),
+        };
+
+        let response = self.client.list_processes(request).await?.into_inner();
+        Ok(response
+            .processes
+            .into_iter()
+            .map(ProcessInfo::from)
+            .collect())
+    }
+
+    /// Start watching a path in the container; the stream ends when the
+    /// path is removed.
+    pub async fn watch_path(
+        &mut self,
+        container_id: &str,
+        path: &str,
+        recursive: bool,
+        debounce: Option<std::time::Duration>,
+    ) -> BoxliteResult<tonic::Streaming<WatchPathEvent>> {
+        let request = WatchPathRequest {
+            container_id: container_id.to_string(),
+            path: path.to_string(),
+            recursive,
+            debounce_ms: debounce.map_or(0, |d| d.as_millis() as u64),
+        };
+
+        Ok(self.client.watch_path(request).await?.into_inner())
+    }
+
+    /// Start running health probes in the container; the stream carries
+    /// each change in a probe's health.
+    pub async fn watch_health(
+        &mut self,
+        container_id: &str,
+        readiness: Option<&Probe>,
+        liveness: Option<&Probe>,
+    ) -> BoxliteResult<tonic::Streaming<HealthEvent>> {
+        let probes = [
+            (ProbeKind::ProbeReadiness, readiness),
+            (ProbeKind::ProbeLiveness, liveness),
+        ];
+        let request = WatchHealthRequest {
+            container_id: container_id.to_string(),
+            probes: probes
+                .into_iter()
+                .filter_map(|(kind, probe)| probe.map(|probe| self.to_health_probe(kind, probe)))
+                .collect::<BoxliteResult<_>>()?,
+        };
+
+        Ok(self.client.watch_health(request).await?.into_inner())
+    }
+
+    fn to_health_probe(&self, kind: ProbeKind, probe: &Probe) -> BoxliteResult<HealthProbe> {
+        let check = match &probe.check {
+            ProbeCheck::Command { command, args } => {
+                let (program, args) = self.policy.apply_program(command, args)?;
+                health_probe::Check::Command(CommandCheck { program, args })
+            }
+            ProbeCheck::Tcp { port } => health_probe::Check::Tcp(TcpCheck {
+                port: u32::from(*port),
+            }),
+            ProbeCheck::Http { port, path } => health_probe::Check::Http(HttpCheck {
+                port: u32::from(*port),
+                path: path.clone(),
+            }),
+        };
+        Ok(HealthProbe {
+            kind: kind as i32,
+            check: Some(check),
+            interval_ms: probe.interval_secs.saturating_mul(1000),
+            timeout_ms: probe.timeout_secs.saturating_mul(1000),
+            initial_delay_ms: probe.initial_delay_secs.saturating_mul(1000),
+            failure_threshold: probe.failure_threshold,
+        })
+    }
+
+    /// Rootfs file ranges now in the guest page cache.
+    pub async fn record_readahead(
+        &mut self,
+        container_id: &str,
+    ) -> BoxliteResult<Vec<ReadaheadHint>> {
+        let request = RecordReadaheadRequest {
+            container_id: container_id.to_string(),
+        };
+
+        let response = self.client.record_readahead(request).await?.into_inner();
+        Ok(response.hints)
+    }
+
+    /// Inject a failure into the container (guest agents built with
+    /// fault-injection only).
+    #[cfg(feature = "fault-injection")]
+    pub async fn inject_fault(
+        &mut self,
+        container_id: &str,
+        fault: boxlite_shared::inject_fault_request::Fault,
+    ) -> BoxliteResult<()> {
+        let request = boxlite_shared::InjectFaultRequest {
+            container_id: container_id.to_string(),
+            fault: Some(fault),
+        };
+
+        self.client.inject_fault(request).await?;
+        Ok(())
+    }
+
+    /// Freeze or thaw the container rootfs filesystem.
+    pub async fn freeze_rootfs(&mut self, container_id: &str, frozen: bool) -> BoxliteResult<()> {
+        let request = FreezeRootfsRequest {
+            container_id: container_id.to_string(),
+            frozen,
+        };
+
+        self.client.freeze_rootfs(request).await?;
+        Ok(())
+    }
+
+    fn map_clipboard_response(response: ClipboardResponse) -> BoxliteResult<Vec<u8>> {
+        match response.result {
+            Some(clipboard_response::Result::Content(content)) => Ok(content),
+            Some(clipboard_response::Result::Error(err)) => Err(BoxliteError::Internal(format!(
+                "Clipboard operation failed: {}",
+                err.reason
+            ))),
+            None => Err(BoxliteError::Internal(
+                "Clipboard response missing result".to_string(),
+            )),
+        }
+    }
+
+    fn map_user_response(response: UserResponse, op: &str) -> BoxliteResult<()> {
+        match response.result {
+            Some(user_response::Result::Success(_)) => Ok(()),
+            Some(user_response::Result::Error(err)) => Err(BoxliteError::Internal(format!(
+                "{} failed: {}",
+                op, err.reason
+            ))),
+            None => Err(BoxliteError::Internal(format!(
+                "{} response missing result",
+                op
+            ))),
+        }
+    }
 }
@@ -3,44 +3,79 @@
 //! High-level API for execution operations (unary Exec + output-only Attach +
 //! blocking Wait).
 
-use crate::litebox::{BoxCommand, ExecResult};
+use crate::litebox::{
+    AuditReport, BoxCommand, ExecNetwork, ExecResult, ExitKind, OutputCapture, OutputChunk,
+    OutputStream,
+};
+use crate::portal::replay::AgentChannel;
+use crate::runtime::exec_policy::ExecPolicy;
+use crate::runtime::options::StreamBufferOptions;
+use crate::util::stream_buffer::{self, BufferReceiver, BufferSender};
 use boxlite_shared::{
-    AttachRequest, BoxliteError, BoxliteResult, ExecOutput, ExecRequest, ExecStdin,
-    ExecutionClient, KillRequest, WaitRequest, WaitResponse, exec_output,
+    AttachRequest, AuditReport as ProtoAuditReport, BoxliteError, BoxliteResult,
+    ExecNetwork as ProtoExecNetwork, ExecOutput, ExecRequest, ExecStdin, ExecutionClient,
+    FetchOutputRequest, KillRequest, OutputCapture as ProtoOutputCapture,
+    OutputStream as ProtoOutputStream, PrivateFilesystem, WaitRequest, WaitResponse, exec_output,
 };
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::transport::Channel;
+use tokio_util::sync::{CancellationToken, DropGuard};
 
 /// Execution service interface.
 #[derive(Clone)]
 pub struct ExecutionInterface {
-    client: ExecutionClient<Channel>,
+    client: ExecutionClient<AgentChannel>,
+    policy: Arc<ExecPolicy>,
 }
 
+/// Stdin chunks buffered on the host before writers wait on the guest.
+const STDIN_CHANNEL_CAPACITY: usize = 16;
+
 /// Components for building an Execution.
 pub struct ExecComponents {
     pub execution_id: String,
-    pub stdin_tx: mpsc::UnboundedSender<Vec<u8>>,
-    pub stdout_rx: mpsc::UnboundedReceiver<String>,
-    pub stderr_rx: mpsc::UnboundedReceiver<String>,
+    pub stdin_tx: mpsc::Sender<Vec<u8>>,
+    pub stdout_rx: BufferReceiver<String>,
+    pub stderr_rx: BufferReceiver<String>,
     pub result_rx: mpsc::UnboundedReceiver<ExecResult>,
+    /// Kills the process if dropped before it exits.
+    pub cancel: DropGuard,
 }
 
 impl ExecutionInterface {
     /// Create from a channel.
-    pub fn new(channel: Channel) -> Self {
+    pub fn new(channel: AgentChannel) -> Self {
         Self {
             client: ExecutionClient::new(channel),
+            policy: Arc::default(),
         }
     }
 
+    /// Hold executed commands to `policy`.
+    pub(crate) fn with_exec_policy(mut self, policy: Arc<ExecPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Execute a command and return execution components.
-    pub async fn exec(&mut self, command: BoxCommand) -> BoxliteResult<ExecComponents> {
+    ///
+    /// Stdout and stderr are each buffered per `output`, with chunks
+    /// evicted for a slow consumer added to `dropped`.
+    pub async fn exec(
+        &mut self,
+        command: BoxCommand,
+        output: StreamBufferOptions,
+        dropped: Arc<AtomicU64>,
+    ) -> BoxliteResult<ExecComponents> {
+        let command = self.policy.apply(command)?;
+
         // Create channels
-        let (stdin_tx, stdin_rx) = mpsc::unbounded_channel::<Vec<u8>>();
-        let (stdout_tx, stdout_rx) = mpsc::unbounded_channel::<String>();
-        let (stderr_tx, stderr_rx) = mpsc::unbounded_channel::<String>();
+        let (stdin_tx, stdin_rx) = mpsc::channel::<Vec<u8>>(STDIN_CHANNEL_CAPACITY);
+        let (stdout_tx, stdout_rx) = stream_buffer::channel(output, Arc::clone(&dropped));
+        let (stderr_tx, stderr_rx) = stream_buffer::channel(output, dropped);
         let (result_tx, result_rx) = mpsc::unbounded_channel();
 
         // Build request
@@ -71,7 +106,13 @@ impl ExecutionInterface {
         );
 
         // Spawn wait task for terminal status
-        ExecProtocol::spawn_wait(self.client.clone(), execution_id.clone(), result_tx);
+        let cancel = CancellationToken::new();
+        ExecProtocol::spawn_wait(
+            self.client.clone(),
+            execution_id.clone(),
+            result_tx,
+            cancel.clone(),
+        );
 
         Ok(ExecComponents {
             execution_id,
@@ -79,6 +120,7 @@ impl ExecutionInterface {
             stdout_rx,
             stderr_rx,
             result_rx,
+            cancel: cancel.drop_guard(),
         })
     }
 
@@ -87,6 +129,7 @@ impl ExecutionInterface {
     pub async fn wait(&mut self, execution_id: &str) -> BoxliteResult<ExecResult> {
         let request = WaitRequest {
             execution_id: execution_id.to_string(),
+            kill_on_cancel: false,
         };
 
         let response = self.client.wait(request).await?.into_inner();
@@ -111,6 +154,33 @@ impl ExecutionInterface {
         }
     }
 
+    /// Read a range of captured output.
+    pub async fn fetch_output(
+        &mut self,
+        execution_id: &str,
+        stream: OutputStream,
+        offset: u64,
+        length: u64,
+    ) -> BoxliteResult<OutputChunk> {
+        let request = FetchOutputRequest {
+            execution_id: execution_id.to_string(),
+            stream: match stream {
+                OutputStream::Stdout => ProtoOutputStream::Stdout,
+                OutputStream::Stderr => ProtoOutputStream::Stderr,
+            } as i32,
+            offset,
+            length,
+        };
+
+        let response = self.client.fetch_output(request).await?.into_inner();
+        Ok(OutputChunk {
+            data: response.data,
+            offset: response.offset,
+            total_bytes: response.total_bytes,
+            complete: response.complete,
+        })
+    }
+
     /// Resize PTY terminal window.
     pub async fn resize_tty(
         &mut self,
@@ -177,6 +247,31 @@ impl ExecProtocol {
             } else {
                 None
             },
+            uid: command.user.map(|(uid, _)| uid),
+            gid: command.user.map(|(_, gid)| gid),
+            network: match command.network {
+                ExecNetwork::Shared => ProtoExecNetwork::Shared,
+                ExecNetwork::None => ProtoExecNetwork::None,
+                ExecNetwork::Loopback => ProtoExecNetwork::Loopback,
+                ExecNetwork::Veth => ProtoExecNetwork::Veth,
+            } as i32,
+            capture: command.capture.as_ref().map(Self::build_capture),
+            audit: command.audit,
+            private_fs: command
+                .private_fs
+                .as_ref()
+                .map(|overlay_paths| PrivateFilesystem {
+                    overlay_paths: overlay_paths.clone(),
+                }),
+        }
+    }
+
+    fn build_capture(capture: &OutputCapture) -> ProtoOutputCapture {
+        ProtoOutputCapture {
+            stdout_path: capture.stdout_path.clone().unwrap_or_default(),
+            stderr_path: capture.stderr_path.clone().unwrap_or_default(),
+            max_file_bytes: capture.max_file_bytes,
+            max_files: capture.max_files,
         }
     }
 
@@ -186,14 +281,53 @@ impl ExecProtocol {
         } else {
             resp.exit_code
         };
-        ExecResult { exit_code: code }
+        let exit_kind = if resp.signal == 0 {
+            ExitKind::Exited
+        } else if resp.timed_out {
+            ExitKind::TimedOut
+        } else if resp.oom_killed {
+            ExitKind::OomKilled
+        } else {
+            ExitKind::Signaled {
+                signal: resp.signal,
+                name: if resp.signal_name.is_empty() {
+                    resp.signal.to_string()
+                } else {
+                    resp.signal_name
+                },
+            }
+        };
+        ExecResult {
+            exit_code: code,
+            exit_kind,
+            duration: Duration::from_millis(resp.duration_ms),
+            peak_memory_bytes: resp.peak_memory_bytes,
+            user_time: Duration::from_micros(resp.user_time_us),
+            system_time: Duration::from_micros(resp.system_time_us),
+            read_bytes: resp.read_bytes,
+            write_bytes: resp.write_bytes,
+            audit: resp.audit.map(Self::map_audit_report),
+        }
+    }
+
+    fn map_audit_report(report: ProtoAuditReport) -> AuditReport {
+        AuditReport {
+            syscalls: report.syscalls.into_iter().collect(),
+            executed: report.executed,
+            files_read: report.files_read,
+            files_written: report.files_written,
+            files_removed: report.files_removed,
+            connections: report.connections,
+            listeners: report.listeners,
+            dropped: report.dropped,
+        }
     }
 
     fn spawn_attach(
-        mut client: ExecutionClient<Channel>,
+        mut client: ExecutionClient<AgentChannel>,
         execution_id: String,
-        stdout_tx: mpsc::UnboundedSender<String>,
-        stderr_tx: mpsc::UnboundedSender<String>,
+        stdout_tx: BufferSender<String>,
+        stderr_tx: BufferSender<String>,
     ) {
         tokio::spawn(async move {
             let request = AttachRequest {
@@ -209,7 +343,7 @@ impl ExecProtocol {
                         match output {
                             Ok(output) => {
                                 message_count += 1;
-                                Self::route_output(output, &stdout_tx, &stderr_tx);
+                                Self::route_output(output, &stdout_tx, &stderr_tx).await;
                             }
                             Err(e) => {
                                 tracing::debug!(
@@ -218,7 +352,7 @@ impl ExecProtocol {
                                     message_count,
                                     "Attach stream error, breaking"
                                 );
-                                let _ = stderr_tx.send(format!("Attach stream error: {}", e));
+                                let _ = stderr_tx.send(format!("Attach stream error: {}", e)).await;
                                 break;
                             }
                         }
@@ -231,43 +365,60 @@ impl ExecProtocol {
                 }
                 Err(e) => {
                     tracing::debug!(execution_id = %execution_id, error = %e, "Attach failed");
-                    let _ = stderr_tx.send(format!("Attach failed: {}", e));
+                    let _ = stderr_tx.send(format!("Attach failed: {}", e)).await;
                 }
             }
         });
     }
 
-    fn route_output(
+    /// Queue an output message for its stream, waiting for room under the
+    /// `Block` policy, which stops reading the attach stream and so holds
+    /// back the guest.
+    async fn route_output(
         output: ExecOutput,
-        stdout_tx: &mpsc::UnboundedSender<String>,
-        stderr_tx: &mpsc::UnboundedSender<String>,
+        stdout_tx: &BufferSender<String>,
+        stderr_tx: &BufferSender<String>,
     ) {
         match output.event {
             Some(exec_output::Event::Stdout(chunk)) => {
                 let stdout_data = String::from_utf8_lossy(&chunk.data).to_string();
                 tracing::trace!(?stdout_data, "Received exec stdout");
-                let _ = stdout_tx.send(stdout_data);
+                let _ = stdout_tx.send(stdout_data).await;
             }
             Some(exec_output::Event::Stderr(chunk)) => {
                 let stderr_data = String::from_utf8_lossy(&chunk.data).to_string();
                 tracing::trace!(?stderr_data, "Received exec stderr");
-                let _ = stderr_tx.send(stderr_data);
+                let _ = stderr_tx.send(stderr_data).await;
             }
             None => {}
         }
     }
 
+    /// Wait for the result, or for `cancel`.
+    ///
+    /// The wait asks the guest to kill the process if the call is cancelled,
+    /// so abandoning it (or the connection dropping) stops the process
+    /// rather than leaving it running unobserved.
     fn spawn_wait(
-        mut client: ExecutionClient<Channel>,
+        mut client: ExecutionClient<AgentChannel>,
         execution_id: String,
         result_tx: mpsc::UnboundedSender<ExecResult>,
+        cancel: CancellationToken,
     ) {
         tokio::spawn(async move {
             let request = WaitRequest {
                 execution_id: execution_id.clone(),
+                kill_on_cancel: true,
             };
 
-            match client.wait(request).await {
+            let response = tokio::select! {
+                response = client.wait(request) => response,
+                () = cancel.cancelled() => {
+                    tracing::debug!(execution_id = %execution_id, "Wait cancelled");
+                    return;
+                }
+            };
+            match response {
                 Ok(resp) => {
                     let mapped = Self::map_wait_response(resp.into_inner());
                     let _ = result_tx.send(mapped);
@@ -278,45 +429,39 @@ impl ExecProtocol {
                         error = %e,
                         "Wait failed"
                     );
-                    let _ = result_tx.send(ExecResult { exit_code: -1 });
+                    let _ = result_tx.send(ExecResult::unknown());
                 }
             }
         });
     }
 
     fn spawn_stdin(
-        mut client: ExecutionClient<Channel>,
+        mut client: ExecutionClient<AgentChannel>,
         execution_id: String,
-        mut stdin_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+        stdin_rx: mpsc::Receiver<Vec<u8>>,
     ) {
         tokio::spawn(async move {
-            let (tx, rx) = mpsc::channel::<ExecStdin>(8);
-
-            // Producer: forward stdin channel into tonic stream
-            let exec_id_clone = execution_id.clone();
-            tokio::spawn(async move {
-                while let Some(data) = stdin_rx.recv().await {
-                    let msg = ExecStdin {
-                        execution_id: exec_id_clone.clone(),
-                        data,
-                        close: false,
-                    };
-                    if tx.send(msg).await.is_err() {
-                        return;
-                    }
-                }
-
-                // Signal stdin close
-                let _ = tx
-                    .send(ExecStdin {
-                        execution_id: exec_id_clone,
+            use futures::StreamExt;
+
+            // Pull from the stdin channel only as fast as the gRPC stream
+            // sends, which the guest paces by how fast the process reads.
+            // Once the writer is dropped, a last message closes stdin.
+            let data_id = execution_id.clone();
+            let close_id = execution_id.clone();
+            let stream = ReceiverStream::new(stdin_rx)
+                .map(move |data| ExecStdin {
+                    execution_id: data_id.clone(),
+                    data,
+                    close: false,
+                })
+                .chain(futures::stream::once(async move {
+                    ExecStdin {
+                        execution_id: close_id,
                         data: Vec::new(),
                         close: true,
-                    })
-                    .await;
-            });
+                    }
+                }));
 
-            let stream = ReceiverStream::new(rx);
             if let Err(e) = client.send_input(stream).await {
                 tracing::warn!(
                     execution_id = %execution_id,
@@ -327,3 +472,70 @@ impl ExecProtocol {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn killed(signal: i32) -> WaitResponse {
+        WaitResponse {
+            signal,
+            signal_name: "SIGKILL".to_string(),
+            duration_ms: 1500,
+            peak_memory_bytes: 64 << 20,
+            user_time_us: 250_000,
+            system_time_us: 50_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_map_wait_response_classifies_exit() {
+        let exited = ExecProtocol::map_wait_response(WaitResponse {
+            exit_code: 2,
+            ..Default::default()
+        });
+        assert_eq!(exited.exit_code, 2);
+        assert_eq!(exited.exit_kind, ExitKind::Exited);
+
+        let signaled = ExecProtocol::map_wait_response(killed(9));
+        assert_eq!(signaled.exit_code, -9);
+        assert_eq!(
+            signaled.exit_kind,
+            ExitKind::Signaled {
+                signal: 9,
+                name: "SIGKILL".to_string()
+            }
+        );
+        assert_eq!(signaled.duration, Duration::from_millis(1500));
+        assert_eq!(signaled.peak_memory_bytes, 64 << 20);
+        assert_eq!(signaled.cpu_time(), Duration::from_millis(300));
+
+        let oom = ExecProtocol::map_wait_response(WaitResponse {
+            oom_killed: true,
+            ..killed(9)
+        });
+        assert_eq!(oom.exit_kind, ExitKind::OomKilled);
+
+        let timed_out = ExecProtocol::map_wait_response(WaitResponse {
+            timed_out: true,
+            ..killed(14)
+        });
+        assert_eq!(timed_out.exit_kind, ExitKind::TimedOut);
+        assert_eq!(timed_out.audit, None);
+
+        let audited = ExecProtocol::map_wait_response(WaitResponse {
+            audit: Some(ProtoAuditReport {
+                syscalls: [("openat".to_string(), 3)].into(),
+                executed: vec!["/usr/bin/curl".to_string()],
+                connections: vec!["10.0.2.2:443".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let audit = audited.audit.unwrap();
+        assert_eq!(audit.syscalls.get("openat"), Some(&3));
+        assert_eq!(audit.executed, ["/usr/bin/curl"]);
+        assert_eq!(audit.connections, ["10.0.2.2:443"]);
+    }
+}
@@ -1,19 +1,26 @@
 //! Guest service interface.
 
+use crate::portal::replay::AgentChannel;
 use boxlite_shared::{
-    BlockDeviceSource, BoxliteError, BoxliteResult, Filesystem, GuestClient, GuestInitRequest,
-    NetworkInit, PingRequest, ShutdownRequest, VirtiofsSource, Volume, guest_init_response,
+    BlockDeviceSource, BoxliteError, BoxliteResult, CapabilitiesRequest, CrashDump,
+    DeterministicInit, Filesystem, GuestClient, GuestInitRequest, MemoryEvent, NetworkFilesystem,
+    NetworkInit, NetworkSource, PingRequest, PressureEvent, ReclaimMemoryRequest, ResumeRequest,
+    ScreenshotRequest, ShutdownRequest, UpgradeAgentRequest, VirtiofsSource, Volume,
+    VolumeIdMapping, WakeRequest, WatchCrashesRequest, WatchMemoryRequest, WatchPressureRequest,
+    X86Emulation, guest_init_response, screenshot_response, upgrade_agent_response,
 };
-use tonic::transport::Channel;
+
+use crate::litebox::{AgentCapabilities, Screenshot, ShareStatus};
+use crate::metrics::GuestStageTiming;
 
 /// Guest service interface.
 pub struct GuestInterface {
-    client: GuestClient<Channel>,
+    client: GuestClient<AgentChannel>,
 }
 
 impl GuestInterface {
     /// Create from a channel.
-    pub fn new(channel: Channel) -> Self {
+    pub fn new(channel: AgentChannel) -> Self {
         Self {
             client: GuestClient::new(channel),
         }
@@ -23,7 +30,9 @@ impl GuestInterface {
     ///
     /// This must be called first after connection, before Container.Init.
     /// Sets up volumes (virtiofs + block devices) and network.
-    pub async fn init(&mut self, config: GuestInitConfig) -> BoxliteResult<()> {
+    ///
+    /// Returns the guest's boot stage timings.
+    pub async fn init(&mut self, config: GuestInitConfig) -> BoxliteResult<Vec<GuestStageTiming>> {
         tracing::debug!("Sending GuestInit request");
         tracing::trace!(
             volumes = config.volumes.len(),
@@ -38,14 +47,29 @@ impl GuestInterface {
                 ip: n.ip,
                 gateway: n.gateway,
             }),
+            kernel_modules: config.kernel_modules,
+            deterministic: config
+                .clock_start_secs
+                .map(|clock_start_secs| DeterministicInit { clock_start_secs }),
+            x86_emulation: config.x86_emulation.map(|x86| X86Emulation {
+                interpreter: x86.interpreter,
+                flags: x86.flags,
+            }),
         };
 
         let response = self.client.init(request).await?.into_inner();
 
         match response.result {
-            Some(guest_init_response::Result::Success(_)) => {
+            Some(guest_init_response::Result::Success(success)) => {
                 tracing::debug!("Guest initialized");
-                Ok(())
+                Ok(success
+                    .stages
+                    .into_iter()
+                    .map(|stage| GuestStageTiming {
+                        name: stage.name,
+                        duration_ms: stage.duration_ms as u128,
+                    })
+                    .collect())
             }
             Some(guest_init_response::Result::Error(err)) => {
                 tracing::error!("Guest init failed: {}", err.reason);
@@ -61,10 +85,167 @@ impl GuestInterface {
     }
 
     /// Ping the guest (health check).
-    #[allow(dead_code)] // API method for future health checks
-    pub async fn ping(&mut self) -> BoxliteResult<()> {
-        let _response = self.client.ping(PingRequest {}).await?;
-        Ok(())
+    ///
+    /// Returns how the guest mounted each virtiofs share.
+    pub async fn ping(&mut self) -> BoxliteResult<Vec<ShareStatus>> {
+        let response = self.client.ping(PingRequest {}).await?.into_inner();
+        Ok(response.shares.into_iter().map(ShareStatus::from).collect())
+    }
+
+    /// Version of the running agent, and how many times it was upgraded in
+    /// place since the guest booted.
+    pub async fn agent_version(&mut self) -> BoxliteResult<(String, u32)> {
+        let response = self.client.ping(PingRequest {}).await?.into_inner();
+        Ok((response.version, response.upgrades))
+    }
+
+    /// Optional features the agent was built with, and how fast it
+    /// started.
+    pub async fn capabilities(&mut self) -> BoxliteResult<AgentCapabilities> {
+        let response = self
+            .client
+            .capabilities(CapabilitiesRequest {})
+            .await?
+            .into_inner();
+        Ok(response.into())
+    }
+
+    /// Replace the agent with the binary staged over the bulk channel.
+    ///
+    /// Returns the version of the agent being replaced. The agent execs the
+    /// new binary after replying, so calls right after this one may fail
+    /// until it serves again.
+    pub async fn upgrade_agent(&mut self, signature: Vec<u8>) -> BoxliteResult<String> {
+        let response = self
+            .client
+            .upgrade_agent(UpgradeAgentRequest { signature })
+            .await?
+            .into_inner();
+
+        match response.result {
+            Some(upgrade_agent_response::Result::Success(success)) => Ok(success.previous_version),
+            Some(upgrade_agent_response::Result::Error(err)) => Err(BoxliteError::InvalidState(
+                format!("Agent upgrade refused: {}", err.reason),
+            )),
+            None => Err(BoxliteError::Internal(
+                "UpgradeAgent response missing result".to_string(),
+            )),
+        }
+    }
+
+    /// Capture the guest display.
+    pub async fn screenshot(&mut self) -> BoxliteResult<Screenshot> {
+        let response = self
+            .client
+            .screenshot(ScreenshotRequest {})
+            .await?
+            .into_inner();
+
+        match response.result {
+            Some(screenshot_response::Result::Image(image)) => Ok(Screenshot {
+                width: image.width,
+                height: image.height,
+                png: image.png,
+            }),
+            Some(screenshot_response::Result::Error(err)) => Err(BoxliteError::Internal(format!(
+                "Screenshot failed: {}",
+                err.reason
+            ))),
+            None => Err(BoxliteError::Internal(
+                "Screenshot response missing result".to_string(),
+            )),
+        }
+    }
+
+    /// Tell the guest it booted from cloned or restored disks.
+    ///
+    /// Sends the host clock and `entropy`; returns how far the guest
+    /// stepped its clock, in milliseconds.
+    pub async fn resume(&mut self, reason: &str, entropy: Vec<u8>) -> BoxliteResult<i64> {
+        let host_time_unix_nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as i64;
+        let response = self
+            .client
+            .resume(ResumeRequest {
+                host_time_unix_nanos,
+                entropy,
+                reason: reason.to_string(),
+            })
+            .await?
+            .into_inner();
+        Ok(response.clock_step_ms)
+    }
+
+    /// Tell the guest the host slept or its wall clock jumped by `jump_ms`.
+    ///
+    /// Sends the host clock; returns how far the guest stepped its clock,
+    /// in milliseconds, and whether it refreshed its network.
+    pub async fn wake(&mut self, jump_ms: i64) -> BoxliteResult<(i64, bool)> {
+        let host_time_unix_nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as i64;
+        let response = self
+            .client
+            .wake(WakeRequest {
+                host_time_unix_nanos,
+                jump_ms,
+            })
+            .await?
+            .into_inner();
+        Ok((response.clock_step_ms, response.network_refreshed))
+    }
+
+    /// Stream low memory events (under `low_percent` of the total, none
+    /// with 0) and OOM kills from the guest.
+    pub async fn watch_memory(
+        &mut self,
+        low_percent: u32,
+    ) -> BoxliteResult<tonic::Streaming<MemoryEvent>> {
+        Ok(self
+            .client
+            .watch_memory(WatchMemoryRequest { low_percent })
+            .await?
+            .into_inner())
+    }
+
+    /// Stream pressure changes from the guest: a resource coming under
+    /// `threshold_percent` of pressure, and being relieved.
+    pub async fn watch_pressure(
+        &mut self,
+        threshold_percent: u32,
+    ) -> BoxliteResult<tonic::Streaming<PressureEvent>> {
+        Ok(self
+            .client
+            .watch_pressure(WatchPressureRequest { threshold_percent })
+            .await?
+            .into_inner())
+    }
+
+    /// Have the guest drop clean page cache and compact memory; returns how
+    /// many bytes more are available.
+    pub async fn reclaim_memory(&mut self) -> BoxliteResult<u64> {
+        let response = self
+            .client
+            .reclaim_memory(ReclaimMemoryRequest {})
+            .await?
+            .into_inner();
+        Ok(response.freed_bytes)
+    }
+
+    /// Have the guest collect core dumps, keeping up to `max_bytes` of
+    /// each, and stream each one it stores.
+    pub async fn watch_crashes(
+        &mut self,
+        max_bytes: u64,
+    ) -> BoxliteResult<tonic::Streaming<CrashDump>> {
+        Ok(self
+            .client
+            .watch_crashes(WatchCrashesRequest { max_bytes })
+            .await?
+            .into_inner())
     }
 
     /// Shutdown the guest agent.
@@ -81,6 +262,21 @@ pub struct GuestInitConfig {
     pub volumes: Vec<VolumeConfig>,
     /// Network configuration (optional)
     pub network: Option<NetworkInitConfig>,
+    /// Kernel modules to load (e.g. GPU drivers)
+    pub kernel_modules: Vec<String>,
+    /// Deterministic mode: wall clock to start at, in seconds since the epoch
+    pub clock_start_secs: Option<u64>,
+    /// x86_64 interpreter to register with binfmt_misc
+    pub x86_emulation: Option<X86EmulationConfig>,
+}
+
+/// An x86_64 interpreter on a guest volume.
+#[derive(Debug, Clone)]
+pub struct X86EmulationConfig {
+    /// Interpreter path in the guest
+    pub interpreter: String,
+    /// binfmt_misc flags
+    pub flags: String,
 }
 
 /// Volume configuration.
@@ -93,8 +289,14 @@ pub enum VolumeConfig {
         /// Mount point in guest
         mount_point: String,
         read_only: bool,
+        /// Mount with DAX (share has a DAX window)
+        dax: bool,
         /// Optional container_id for convention-based paths
         container_id: Option<String>,
+        /// Present the share's owner as another user in the guest
+        id_mapping: Option<IdMapping>,
+        /// Boot without the share if it cannot be mounted
+        optional: bool,
     },
     /// Block device mount
     BlockDevice {
@@ -109,6 +311,19 @@ pub enum VolumeConfig {
         /// If true, resize filesystem after mounting to fill disk
         need_resize: bool,
     },
+    /// Remote filesystem, mounted once the guest network is up
+    Network {
+        filesystem: NetworkFilesystem,
+        /// `server:/export` (NFS) or `//server/share` (CIFS)
+        source: String,
+        /// Volume name for the convention-based path
+        name: String,
+        container_id: String,
+        read_only: bool,
+        /// Extra mount options, comma-separated
+        options: String,
+        credentials: Option<NetworkCredentials>,
+    },
 }
 
 impl VolumeConfig {
@@ -117,14 +332,39 @@ impl VolumeConfig {
         tag: impl Into<String>,
         mount_point: impl Into<String>,
         read_only: bool,
+        dax: bool,
         container_id: Option<String>,
     ) -> Self {
         Self::Virtiofs {
             tag: tag.into(),
             mount_point: mount_point.into(),
             read_only,
+            dax,
             container_id,
+            id_mapping: None,
+            optional: false,
+        }
+    }
+
+    /// Present files owned by the host owner as the mapping's guest ids.
+    ///
+    /// Only applies to virtiofs volumes.
+    pub fn with_id_mapping(mut self, mapping: Option<IdMapping>) -> Self {
+        if let Self::Virtiofs { id_mapping, .. } = &mut self {
+            *id_mapping = mapping;
         }
+        self
+    }
+
+    /// Let the guest boot without the share, degraded, if every way of
+    /// mounting it fails.
+    ///
+    /// Only applies to virtiofs volumes.
+    pub fn with_optional(mut self, optional: bool) -> Self {
+        if let Self::Virtiofs { optional: o, .. } = &mut self {
+            *o = optional;
+        }
+        self
     }
 
     /// Create block device volume config.
@@ -150,12 +390,23 @@ impl VolumeConfig {
                 tag,
                 mount_point,
                 read_only,
+                dax,
                 container_id,
+                id_mapping,
+                optional,
             } => Volume {
                 mount_point,
                 source: Some(boxlite_shared::volume::Source::Virtiofs(VirtiofsSource {
                     tag,
                     read_only,
+                    dax,
+                    id_mapping: id_mapping.map(|m| VolumeIdMapping {
+                        host_uid: m.host_uid,
+                        host_gid: m.host_gid,
+                        uid: m.uid,
+                        gid: m.gid,
+                    }),
+                    optional,
                 })),
                 container_id: container_id.unwrap_or_default(),
             },
@@ -177,10 +428,62 @@ impl VolumeConfig {
                 )),
                 container_id: String::new(),
             },
+            VolumeConfig::Network {
+                filesystem,
+                source,
+                name,
+                container_id,
+                read_only,
+                options,
+                credentials,
+            } => {
+                let credentials = credentials.unwrap_or_default();
+                Volume {
+                    mount_point: String::new(),
+                    source: Some(boxlite_shared::volume::Source::Network(NetworkSource {
+                        filesystem: filesystem.into(),
+                        source,
+                        name,
+                        read_only,
+                        options,
+                        username: credentials.username,
+                        password: credentials.password,
+                        domain: credentials.domain,
+                    })),
+                    container_id,
+                }
+            }
         }
     }
 }
 
+/// Login for a CIFS network volume.
+#[derive(Clone, Default)]
+pub struct NetworkCredentials {
+    pub username: String,
+    pub password: String,
+    pub domain: String,
+}
+
+// Guest init config is traced; the password must not be
+impl std::fmt::Debug for NetworkCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetworkCredentials")
+            .field("username", &self.username)
+            .field("domain", &self.domain)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Host owner of a share and the guest ids it appears as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdMapping {
+    pub host_uid: u32,
+    pub host_gid: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
 /// Network initialization configuration.
 #[derive(Debug)]
 pub struct NetworkInitConfig {
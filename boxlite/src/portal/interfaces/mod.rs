@@ -8,4 +8,7 @@ pub mod guest;
 
 pub use container::{ContainerInterface, ContainerRootfsInitConfig};
 pub use exec::ExecutionInterface;
-pub use guest::{GuestInitConfig, GuestInterface, NetworkInitConfig, VolumeConfig};
+pub use guest::{
+    GuestInitConfig, GuestInterface, IdMapping, NetworkCredentials, NetworkInitConfig,
+    VolumeConfig, X86EmulationConfig,
+};
@@ -0,0 +1,300 @@
+//! Host side of the lock proxy.
+//!
+//! Locks taken in a box on virtio-fs stay inside the guest kernel. With
+//! `lock_proxy`, the guest's `boxlite-lock` helper sends lock requests here
+//! instead (see the guest's `container::locks`) and [`LockBroker`] locks
+//! the volume's host file, so the box, host tools and other boxes exclude
+//! each other.
+//!
+//! Each connection carries one request line, `exclusive <path>` or
+//! `shared <path>` with a container path on a user volume. The reply is
+//! `ok` once the lock is held or `error <reason>`. The lock is released
+//! when the connection closes.
+//!
+//! On Linux these are open file description locks (`F_OFD_SETLK`), which
+//! conflict with the `fcntl` locks used by sqlite and dpkg on the host. On
+//! macOS they are `flock` locks.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+
+/// Longest accepted request line.
+const MAX_REQUEST_LEN: u64 = 4096;
+
+/// How often a contended lock is retried.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A user volume as seen from both sides.
+#[derive(Clone, Debug)]
+pub struct LockVolume {
+    pub guest_path: String,
+    pub host_path: PathBuf,
+}
+
+/// Serves lock requests from one box until dropped.
+#[derive(Debug)]
+pub struct LockBroker {
+    socket_path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl LockBroker {
+    /// Listen on `socket_path`, replacing a stale socket.
+    pub fn bind(socket_path: PathBuf, volumes: Vec<LockVolume>) -> BoxliteResult<Self> {
+        if socket_path.exists() {
+            std::fs::remove_file(&socket_path)?;
+        }
+        let listener = UnixListener::bind(&socket_path).map_err(|e| {
+            BoxliteError::Portal(format!(
+                "Failed to bind lock socket {}: {}",
+                socket_path.display(),
+                e
+            ))
+        })?;
+
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((conn, _)) => {
+                        let volumes = volumes.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve(conn, &volumes).await {
+                                tracing::debug!("Lock request failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        tracing::warn!("Lock broker stopped: {}", e);
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Self { socket_path, task })
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+}
+
+impl Drop for LockBroker {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+async fn serve(conn: UnixStream, volumes: &[LockVolume]) -> io::Result<()> {
+    let mut conn = BufReader::new(conn);
+    let mut line = String::new();
+    (&mut conn)
+        .take(MAX_REQUEST_LEN)
+        .read_line(&mut line)
+        .await?;
+
+    let _file = match lock(&mut conn, line.trim_end(), volumes).await {
+        Ok(Some(file)) => file,
+        // The box gave up while waiting
+        Ok(None) => return Ok(()),
+        Err(reason) => {
+            let reply = format!("error {}\n", reason);
+            return conn.get_mut().write_all(reply.as_bytes()).await;
+        }
+    };
+    conn.get_mut().write_all(b"ok\n").await?;
+
+    // Held until the box closes the connection
+    let mut buf = [0u8; 64];
+    while conn.read(&mut buf).await? > 0 {}
+    Ok(())
+}
+
+/// Lock the file named by `request`, waiting while it is contended.
+///
+/// Returns None if the connection closed before the lock was acquired.
+async fn lock(
+    conn: &mut BufReader<UnixStream>,
+    request: &str,
+    volumes: &[LockVolume],
+) -> Result<Option<File>, String> {
+    let (exclusive, path) = match request.split_once(' ') {
+        Some(("exclusive", path)) => (true, path),
+        Some(("shared", path)) => (false, path),
+        _ => return Err(format!("malformed request '{}'", request)),
+    };
+    let host_path = resolve(volumes, path)?;
+    // Write locks need a writable descriptor; create the file like flock(1)
+    let file = OpenOptions::new()
+        .read(true)
+        .write(exclusive)
+        .create(exclusive)
+        .truncate(false)
+        .open(&host_path)
+        .map_err(|e| format!("failed to open {}: {}", path, e))?;
+
+    let mut buf = [0u8; 64];
+    loop {
+        match try_lock(&file, exclusive) {
+            Ok(true) => return Ok(Some(file)),
+            Ok(false) => {}
+            Err(e) => return Err(format!("failed to lock {}: {}", path, e)),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+            read = conn.read(&mut buf) => {
+                if matches!(read, Ok(0) | Err(_)) {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Map a container path on a user volume to the host file.
+fn resolve(volumes: &[LockVolume], path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    volumes
+        .iter()
+        .filter_map(|volume| {
+            let relative = path.strip_prefix(&volume.guest_path).ok()?;
+            Some((volume, relative))
+        })
+        // Innermost volume wins when mounts are nested
+        .max_by_key(|(volume, _)| volume.guest_path.len())
+        .ok_or_else(|| format!("{} is not on a shared volume", path.display()))
+        .and_then(|(volume, relative)| {
+            let mut host_path = volume.host_path.clone();
+            for component in relative.components() {
+                match component {
+                    Component::Normal(name) => host_path.push(name),
+                    Component::CurDir => {}
+                    _ => return Err(format!("{} must not contain '..'", path.display())),
+                }
+            }
+            Ok(host_path)
+        })
+}
+
+/// Take the lock without blocking; false if another holder conflicts.
+#[cfg(target_os = "linux")]
+fn try_lock(file: &File, exclusive: bool) -> io::Result<bool> {
+    // SAFETY: flock is a plain C struct; all-zero is a valid value
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = if exclusive {
+        libc::F_WRLCK
+    } else {
+        libc::F_RDLCK
+    } as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    // l_start = 0, l_len = 0: the whole file
+
+    // SAFETY: the descriptor is open for the duration of the call
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_OFD_SETLK, &lock) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EAGAIN) | Some(libc::EACCES) => Ok(false),
+        _ => Err(err),
+    }
+}
+
+/// Take the lock without blocking; false if another holder conflicts.
+#[cfg(not(target_os = "linux"))]
+fn try_lock(file: &File, exclusive: bool) -> io::Result<bool> {
+    let operation = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    // SAFETY: the descriptor is open for the duration of the call
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let volumes = vec![
+            LockVolume {
+                guest_path: "/data".to_string(),
+                host_path: PathBuf::from("/srv/data"),
+            },
+            LockVolume {
+                guest_path: "/data/cache".to_string(),
+                host_path: PathBuf::from("/srv/cache"),
+            },
+        ];
+
+        assert_eq!(
+            resolve(&volumes, "/data/./app.db").unwrap(),
+            PathBuf::from("/srv/data/app.db")
+        );
+        assert_eq!(
+            resolve(&volumes, "/data/cache/x").unwrap(),
+            PathBuf::from("/srv/cache/x")
+        );
+        assert!(resolve(&volumes, "/etc/passwd").is_err());
+        assert!(resolve(&volumes, "/database").is_err());
+        assert!(resolve(&volumes, "/data/../etc/passwd").is_err());
+    }
+
+    async fn request(socket: &Path, line: &str) -> (BufReader<UnixStream>, String) {
+        let mut conn = BufReader::new(UnixStream::connect(socket).await.unwrap());
+        conn.get_mut().write_all(line.as_bytes()).await.unwrap();
+        let mut reply = String::new();
+        conn.read_line(&mut reply).await.unwrap();
+        (conn, reply)
+    }
+
+    #[tokio::test]
+    async fn test_lock_held_until_disconnect() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("lock.sock");
+        let broker = LockBroker::bind(
+            socket.clone(),
+            vec![LockVolume {
+                guest_path: "/data".to_string(),
+                host_path: dir.path().to_path_buf(),
+            }],
+        )
+        .unwrap();
+
+        let (first, reply) = request(&socket, "exclusive /data/app.db\n").await;
+        assert_eq!(reply, "ok\n");
+        assert!(dir.path().join("app.db").exists());
+
+        // A second holder waits until the first disconnects
+        let second = tokio::spawn({
+            let socket = socket.clone();
+            async move { request(&socket, "shared /data/app.db\n").await.1 }
+        });
+        tokio::time::sleep(RETRY_INTERVAL * 3).await;
+        assert!(!second.is_finished());
+        drop(first);
+        assert_eq!(second.await.unwrap(), "ok\n");
+
+        let (_, reply) = request(&socket, "exclusive /etc/passwd\n").await;
+        assert!(reply.starts_with("error "));
+        drop(broker);
+        assert!(!socket.exists());
+    }
+}
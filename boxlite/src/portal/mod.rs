@@ -1,7 +1,12 @@
 //! Host-side portal for communicating with guests via tonic/gRPC.
 
+pub mod bulk;
 pub mod connection;
+pub mod credentials;
 pub mod interfaces;
+pub mod locks;
+pub mod replay;
 pub mod session;
+pub mod x11;
 
 pub use session::GuestSession;
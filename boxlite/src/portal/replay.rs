@@ -0,0 +1,415 @@
+//! Recording and replay of guest agent RPC sessions.
+//!
+//! A recording box logs every gRPC exchange with its agent, as one JSON line
+//! per call: the method, the request and response bodies as sent on the
+//! wire, and the final status. A replaying box has no VM at all; its calls
+//! are answered from such a log, so tests of code built on the SDK run
+//! without booting anything.
+//!
+//! Replay answers each call with the first unused exchange of the same
+//! method, preferring one whose request is byte-for-byte the same. Calls
+//! with nothing left to replay fail with `Unimplemented`. Streams that were
+//! still open when the recording stopped stay open.
+
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use http_body::{Body, Frame};
+use http_body_util::BodyExt;
+use tonic::body::BoxBody;
+use tonic::codegen::Bytes;
+use tonic::codegen::http::{self, HeaderMap, HeaderValue};
+use tonic::transport::Channel;
+use tower::Service;
+
+type StdError = Box<dyn std::error::Error + Send + Sync>;
+
+/// One call to the agent as recorded.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Exchange {
+    /// gRPC path, e.g. `/boxlite.v1.Execution/Exec`.
+    method: String,
+    #[serde(with = "base64_bytes")]
+    request: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    response: Vec<u8>,
+    /// `grpc-status`; None for a stream still open when recording stopped.
+    status: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+mod base64_bytes {
+    use super::{Engine, STANDARD};
+
+    pub(super) fn serialize<S: serde::Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub(super) fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let encoded: String = serde::Deserialize::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Appends finished exchanges to a log file.
+pub struct RpcRecorder {
+    file: Mutex<File>,
+}
+
+impl RpcRecorder {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> BoxliteResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                BoxliteError::Storage(format!("Failed to open {}: {}", path.display(), e))
+            })?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    fn record(&self, exchange: &Exchange) {
+        let Ok(mut line) = serde_json::to_vec(exchange) else {
+            return;
+        };
+        line.push(b'\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(&line) {
+            tracing::warn!(method = %exchange.method, "Failed to record RPC: {}", e);
+        }
+    }
+}
+
+/// Answers calls from a recorded log.
+pub struct Replayer {
+    /// Exchanges not yet replayed, in log order.
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl Replayer {
+    /// Load the log at `path`.
+    pub fn open(path: &Path) -> BoxliteResult<Self> {
+        let fail = |e: String| {
+            BoxliteError::InvalidArgument(format!("Bad RPC log {}: {}", path.display(), e))
+        };
+        let file = File::open(path).map_err(|e| fail(e.to_string()))?;
+        let mut exchanges = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| fail(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            exchanges.push(serde_json::from_str(&line).map_err(|e| fail(e.to_string()))?);
+        }
+        Ok(Self {
+            exchanges: Mutex::new(exchanges),
+        })
+    }
+
+    /// Take the recorded exchange answering `request` to `method`.
+    fn take(&self, method: &str, request: &[u8]) -> Option<Exchange> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let index = exchanges
+            .iter()
+            .position(|e| e.method == method && e.request == request)
+            .or_else(|| exchanges.iter().position(|e| e.method == method))?;
+        Some(exchanges.remove(index))
+    }
+
+    fn respond(&self, method: &str, request: &[u8]) -> http::Response<BoxBody> {
+        let exchange = self.take(method, request).unwrap_or_else(|| Exchange {
+            method: method.to_string(),
+            request: Vec::new(),
+            response: Vec::new(),
+            status: Some(tonic::Code::Unimplemented as i32),
+            message: Some(format!("no recorded response for {}", method)),
+        });
+        let trailers = exchange.status.map(|status| {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", HeaderValue::from(status));
+            if let Some(value) = exchange
+                .message
+                .and_then(|message| HeaderValue::from_str(&message).ok())
+            {
+                trailers.insert("grpc-message", value);
+            }
+            trailers
+        });
+        let body = ReplayBody {
+            data: Some(Bytes::from(exchange.response)).filter(|data| !data.is_empty()),
+            trailers,
+        };
+        let mut response = http::Response::new(tonic::body::boxed(body));
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/grpc"),
+        );
+        response
+    }
+}
+
+/// The channel interfaces send their calls on.
+#[derive(Clone)]
+pub enum AgentChannel {
+    /// Straight to the agent.
+    Live(Channel),
+    /// To the agent, logging each exchange.
+    Recording(Channel, Arc<RpcRecorder>),
+    /// Answered from a log, with no agent.
+    Replay(Arc<Replayer>),
+}
+
+impl Service<http::Request<BoxBody>> for AgentChannel {
+    type Response = http::Response<BoxBody>;
+    type Error = StdError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            AgentChannel::Live(channel) | AgentChannel::Recording(channel, _) => {
+                channel.poll_ready(cx).map_err(Into::into)
+            }
+            AgentChannel::Replay(_) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        match self {
+            AgentChannel::Live(channel) => {
+                let response = channel.call(request);
+                Box::pin(async move { response.await.map_err(Into::into) })
+            }
+            AgentChannel::Recording(channel, recorder) => {
+                let exchange = Arc::new(Mutex::new(Exchange {
+                    method: request.uri().path().to_string(),
+                    request: Vec::new(),
+                    response: Vec::new(),
+                    status: None,
+                    message: None,
+                }));
+                let request = request.map(|body| {
+                    tonic::body::boxed(TeeBody {
+                        inner: body,
+                        exchange: Arc::clone(&exchange),
+                        recorder: None,
+                    })
+                });
+                let recorder = Arc::clone(recorder);
+                let response = channel.call(request);
+                Box::pin(async move {
+                    let response = response.await?;
+                    // Trailers-only responses carry the status in the headers
+                    read_status(&mut exchange.lock().unwrap(), response.headers());
+                    Ok(response.map(|body| {
+                        tonic::body::boxed(TeeBody {
+                            inner: body,
+                            exchange,
+                            recorder: Some(recorder),
+                        })
+                    }))
+                })
+            }
+            AgentChannel::Replay(replayer) => {
+                let replayer = Arc::clone(replayer);
+                Box::pin(async move {
+                    let method = request.uri().path().to_string();
+                    // Client streams are answered once they end, as the
+                    // agent would answer them
+                    let request = request
+                        .into_body()
+                        .collect()
+                        .await
+                        .map(|body| body.to_bytes())
+                        .unwrap_or_default();
+                    Ok(replayer.respond(&method, &request))
+                })
+            }
+        }
+    }
+}
+
+fn read_status(exchange: &mut Exchange, headers: &HeaderMap) {
+    if let Some(status) = headers
+        .get("grpc-status")
+        .and_then(|value| value.to_str().ok()?.parse().ok())
+    {
+        exchange.status = Some(status);
+        exchange.message = headers
+            .get("grpc-message")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+    }
+}
+
+/// A request or response body copied into its exchange as it passes.
+///
+/// The response side holds the recorder and logs the exchange once, when
+/// the body ends or is dropped.
+struct TeeBody {
+    inner: BoxBody,
+    exchange: Arc<Mutex<Exchange>>,
+    recorder: Option<Arc<RpcRecorder>>,
+}
+
+impl TeeBody {
+    fn finish(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.record(&self.exchange.lock().unwrap());
+        }
+    }
+}
+
+impl Body for TeeBody {
+    type Data = Bytes;
+    type Error = tonic::Status;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, tonic::Status>>> {
+        let this = self.get_mut();
+        let frame = std::task::ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        let is_response = this.recorder.is_some();
+        match &frame {
+            Some(Ok(frame)) => {
+                let mut exchange = this.exchange.lock().unwrap();
+                if let Some(data) = frame.data_ref() {
+                    if is_response {
+                        exchange.response.extend_from_slice(data);
+                    } else {
+                        exchange.request.extend_from_slice(data);
+                    }
+                } else if let Some(trailers) = frame.trailers_ref() {
+                    read_status(&mut exchange, trailers);
+                }
+            }
+            Some(Err(status)) if is_response => {
+                let mut exchange = this.exchange.lock().unwrap();
+                exchange.status = Some(status.code() as i32);
+                exchange.message = Some(status.message().to_string());
+                drop(exchange);
+                this.finish();
+            }
+            Some(Err(_)) => {}
+            None => this.finish(),
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+impl Drop for TeeBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// A recorded response body.
+struct ReplayBody {
+    data: Option<Bytes>,
+    /// None keeps the stream open after the data.
+    trailers: Option<HeaderMap>,
+}
+
+impl Body for ReplayBody {
+    type Data = Bytes;
+    type Error = tonic::Status;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, tonic::Status>>> {
+        let this = self.get_mut();
+        if let Some(data) = this.data.take() {
+            return Poll::Ready(Some(Ok(Frame::data(data))));
+        }
+        match this.trailers.take() {
+            Some(trailers) => Poll::Ready(Some(Ok(Frame::trailers(trailers)))),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(method: &str, request: &[u8], response: &[u8]) -> Exchange {
+        Exchange {
+            method: method.to_string(),
+            request: request.to_vec(),
+            response: response.to_vec(),
+            status: Some(0),
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_replayer_prefers_matching_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rpcs.jsonl");
+        let recorder = RpcRecorder::open(&path).unwrap();
+        recorder.record(&exchange("/v1.Execution/Wait", b"a", b"first"));
+        recorder.record(&exchange("/v1.Execution/Wait", b"b", b"second"));
+        recorder.record(&exchange("/v1.Guest/Ping", b"", b"pong"));
+
+        let replayer = Replayer::open(&path).unwrap();
+        let take = |method, request: &[u8]| replayer.take(method, request).map(|e| e.response);
+        assert_eq!(take("/v1.Execution/Wait", b"b"), Some(b"second".to_vec()));
+        assert_eq!(take("/v1.Execution/Wait", b"c"), Some(b"first".to_vec()));
+        assert_eq!(take("/v1.Execution/Wait", b"a"), None);
+        assert_eq!(take("/v1.Guest/Ping", b"x"), Some(b"pong".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_client_calls_are_replayed() {
+        use boxlite_shared::{GuestClient, PingRequest};
+
+        // A gRPC frame holding PingResponse { version: "1.2" }
+        let response = [0, 0, 0, 0, 5, 0x0a, 3, b'1', b'.', b'2'];
+        let replayer = Replayer {
+            exchanges: Mutex::new(vec![exchange("/boxlite.v1.Guest/Ping", b"", &response)]),
+        };
+        let mut client = GuestClient::new(AgentChannel::Replay(Arc::new(replayer)));
+        let pong = client.ping(PingRequest {}).await.unwrap().into_inner();
+        assert_eq!(pong.version, "1.2");
+        let err = client.ping(PingRequest {}).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_unrecorded_calls_are_unimplemented() {
+        let replayer = Replayer {
+            exchanges: Mutex::new(Vec::new()),
+        };
+        let response = replayer.respond("/v1.Guest/Ping", b"");
+        let trailers = response
+            .into_body()
+            .collect()
+            .await
+            .unwrap()
+            .trailers()
+            .cloned()
+            .unwrap();
+        assert_eq!(trailers["grpc-status"], "12");
+    }
+}
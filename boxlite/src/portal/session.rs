@@ -2,9 +2,11 @@
 //!
 //! Thin facade over service interfaces.
 
-use crate::portal::connection::Connection;
+use crate::portal::connection::{Connection, RpcMode};
 use crate::portal::interfaces::{ContainerInterface, ExecutionInterface, GuestInterface};
+use crate::runtime::exec_policy::ExecPolicy;
 use boxlite_shared::{BoxliteResult, Transport};
+use std::sync::Arc;
 
 /// High-level guest session.
 ///
@@ -12,6 +14,8 @@ use boxlite_shared::{BoxliteResult, Transport};
 #[derive(Clone)]
 pub struct GuestSession {
     connection: Connection,
+    /// Applied to every command sent to the guest.
+    exec_policy: Arc<ExecPolicy>,
 }
 
 impl GuestSession {
@@ -19,19 +23,34 @@ impl GuestSession {
     pub fn new(transport: Transport) -> Self {
         Self {
             connection: Connection::new(transport),
+            exec_policy: Arc::default(),
         }
     }
 
+    /// Create a session whose RPCs are recorded or replayed.
+    pub fn with_mode(transport: Transport, mode: RpcMode) -> Self {
+        Self {
+            connection: Connection::new(transport).with_mode(mode),
+            exec_policy: Arc::default(),
+        }
+    }
+
+    /// Hold every command sent through this session to `policy`.
+    pub(crate) fn with_exec_policy(mut self, policy: ExecPolicy) -> Self {
+        self.exec_policy = Arc::new(policy);
+        self
+    }
+
     /// Get execution interface.
     pub async fn execution(&self) -> BoxliteResult<ExecutionInterface> {
         let channel = self.connection.channel().await?;
-        Ok(ExecutionInterface::new(channel))
+        Ok(ExecutionInterface::new(channel).with_exec_policy(Arc::clone(&self.exec_policy)))
     }
 
     /// Get container interface.
     pub async fn container(&self) -> BoxliteResult<ContainerInterface> {
         let channel = self.connection.channel().await?;
-        Ok(ContainerInterface::new(channel))
+        Ok(ContainerInterface::new(channel).with_exec_policy(Arc::clone(&self.exec_policy)))
     }
 
     /// Get guest interface.
@@ -39,6 +58,13 @@ impl GuestSession {
         let channel = self.connection.channel().await?;
         Ok(GuestInterface::new(channel))
     }
+
+    /// Drop the connection to the guest (see [`Connection::sever`]),
+    /// returning how many sockets were shut down.
+    #[cfg(feature = "fault-injection")]
+    pub fn sever(&self) -> usize {
+        self.connection.sever()
+    }
 }
 
 // ============================================================================
@@ -0,0 +1,63 @@
+//! Host side of X11 forwarding.
+//!
+//! libkrun connects the guest's X11 port straight to the host X server
+//! socket named by `$DISPLAY`. The server sees connections from the shim
+//! process, so it must accept local clients of the current user (the
+//! default for Xorg and XWayland with `xhost +SI:localuser:$USER`).
+
+use std::path::PathBuf;
+
+/// Socket of the host X server, if `$DISPLAY` names a local one.
+pub fn host_socket() -> Option<PathBuf> {
+    let value = std::env::var("DISPLAY").ok()?;
+    let socket = socket_for_display(&value);
+    if socket.is_none() {
+        tracing::warn!(
+            "X11 forwarding needs a local X display, DISPLAY is {}",
+            value
+        );
+    }
+    socket
+}
+
+/// Map a `DISPLAY` value to the server's Unix socket.
+///
+/// Accepts `:N`, `:N.S`, `unix:N` and socket paths (XQuartz uses a
+/// launchd socket path). TCP displays (`host:N`) are not supported.
+fn socket_for_display(display: &str) -> Option<PathBuf> {
+    if display.starts_with('/') {
+        return Some(PathBuf::from(display));
+    }
+    let number = display
+        .strip_prefix(':')
+        .or_else(|| display.strip_prefix("unix:"))?;
+    let number = number.split('.').next()?;
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(PathBuf::from(format!("/tmp/.X11-unix/X{}", number)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_for_display() {
+        assert_eq!(
+            socket_for_display(":1"),
+            Some(PathBuf::from("/tmp/.X11-unix/X1"))
+        );
+        assert_eq!(
+            socket_for_display(":0.0"),
+            Some(PathBuf::from("/tmp/.X11-unix/X0"))
+        );
+        assert_eq!(
+            socket_for_display("/private/tmp/com.apple.launchd.x/org.xquartz:0"),
+            Some(PathBuf::from(
+                "/private/tmp/com.apple.launchd.x/org.xquartz:0"
+            ))
+        );
+        assert_eq!(socket_for_display("remote:0"), None);
+    }
+}
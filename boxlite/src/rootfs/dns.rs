@@ -24,7 +24,7 @@ use std::path::Path;
 /// * `Err` if directory creation or file writing fails
 ///
 /// # Example
-/// ```no_run
+/// ```ignore
 /// use std::path::Path;
 /// use boxlite::rootfs::configure_container_dns;
 ///
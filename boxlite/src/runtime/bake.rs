@@ -0,0 +1,225 @@
+//! Baking provisioned images.
+//!
+//! Baking boots a box from a base image, runs the spec's steps in it as
+//! setup steps, and saves the box's disk into the image store as a disk
+//! derived from the base image, recorded under a local tag. A box whose
+//! image is that tag starts from the saved disk with the spec's env, so
+//! slow provisioning is paid once instead of on every box. Baked tags
+//! shadow registry images of the same name.
+//!
+//! ```toml
+//! image = "python:3.12-slim"
+//! memory_mib = 4096
+//! env = [["PIP_NO_CACHE_DIR", "1"]]
+//!
+//! [[step]]
+//! name = "torch"
+//! command = "pip"
+//! args = ["install", "torch"]
+//! ```
+//!
+//! Only the disk is baked: the VMM cannot snapshot guest memory, so boxes
+//! from a baked image still boot cold.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::runtime::BoxliteRuntime;
+use crate::runtime::options::{BoxOptions, RootfsSpec, SetupStep};
+use crate::runtime::rt_impl::RuntimeImpl;
+
+/// Derived image variant of baked images, followed by their tag.
+const BAKE_VARIANT_PREFIX: &str = "bake:";
+
+/// What to bake: a base image and the steps that provision it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BakeSpec {
+    /// Base image reference.
+    pub image: String,
+    /// Steps run in order; baking fails if one does.
+    #[serde(rename = "step")]
+    pub steps: Vec<SetupStep>,
+    /// Environment for the steps, kept for boxes run from the baked image.
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    #[serde(default)]
+    pub cpus: Option<u8>,
+    #[serde(default)]
+    pub memory_mib: Option<u32>,
+    /// Disk size of the baking box, and so of the baked image.
+    #[serde(default)]
+    pub disk_size_gb: Option<u64>,
+    /// Labels recorded on the baked image, for filtering.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+impl BakeSpec {
+    /// Read a spec from a TOML file.
+    pub fn from_file(path: &Path) -> BoxliteResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            BoxliteError::Config(format!(
+                "Failed to read bake spec {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        toml::from_str(&content).map_err(|e| {
+            BoxliteError::Config(format!("Invalid bake spec {}: {}", path.display(), e))
+        })
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        if self.image.is_empty() {
+            return Err(BoxliteError::InvalidArgument(
+                "bake spec needs an image".to_string(),
+            ));
+        }
+        if self.steps.is_empty() {
+            return Err(BoxliteError::InvalidArgument(
+                "bake spec needs at least one step".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// An image baked under a local tag.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BakedImage {
+    pub tag: String,
+    /// Image the bake started from.
+    pub base_image: String,
+    /// Environment boxes from this image get, under their own.
+    pub env: Vec<(String, String)>,
+    /// User-defined key/value labels, from the bake spec.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BakedImage {
+    /// Variant naming the baked disk among those derived from the base image.
+    pub(crate) fn variant(&self) -> String {
+        format!("{}{}", BAKE_VARIANT_PREFIX, self.tag)
+    }
+}
+
+/// Bake `spec` and record it as `tag`, replacing an earlier bake of it.
+///
+/// The baking box is removed afterwards, whether or not baking succeeded.
+pub(crate) async fn bake(
+    runtime: &BoxliteRuntime,
+    rt_impl: &RuntimeImpl,
+    spec: &BakeSpec,
+    tag: &str,
+) -> BoxliteResult<BakedImage> {
+    validate_tag(tag)?;
+    spec.validate()?;
+
+    let baked = BakedImage {
+        tag: tag.to_string(),
+        base_image: spec.image.clone(),
+        env: spec.env.clone(),
+        labels: spec.labels.clone(),
+        created_at: Utc::now(),
+    };
+    let image = rt_impl.image_manager.pull(&spec.image).await?;
+    let target = image.derived_disk_image_path(&baked.variant()).await;
+
+    let options = BoxOptions {
+        rootfs: RootfsSpec::Image(spec.image.clone()),
+        env: spec.env.clone(),
+        setup_steps: spec.steps.clone(),
+        cpus: spec.cpus,
+        memory_mib: spec.memory_mib,
+        disk_size_gb: spec.disk_size_gb,
+        ..Default::default()
+    };
+    let litebox = runtime.create(options, None)?;
+    // Booting runs the steps; a failing one fails the boot
+    let saved = litebox.save_disk(&target).await;
+    if let Err(e) = litebox.stop().await {
+        tracing::warn!(box_id = %litebox.id(), "Failed to stop baking box: {}", e);
+    }
+    let _ = runtime.remove(litebox.id().as_str(), true).await;
+    saved?;
+
+    rt_impl.baked_images.upsert(&baked)?;
+    tracing::info!(tag, base_image = %spec.image, "Baked image");
+    Ok(baked)
+}
+
+/// Accept tags shaped like image references: lowercase letters, digits,
+/// and `.`, `_`, `-`, `/` or `:` between them.
+fn validate_tag(tag: &str) -> BoxliteResult<()> {
+    let valid = tag.len() <= 255
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-/:".contains(c))
+        && tag.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && tag.ends_with(|c: char| c.is_ascii_alphanumeric());
+    if valid {
+        Ok(())
+    } else {
+        Err(BoxliteError::InvalidArgument(format!(
+            "Invalid image tag: {:?}",
+            tag
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.toml");
+        std::fs::write(
+            &path,
+            r#"
+image = "python:3.12-slim"
+memory_mib = 4096
+env = [["PIP_NO_CACHE_DIR", "1"]]
+
+[labels]
+team = "ml"
+
+[[step]]
+name = "torch"
+command = "pip"
+args = ["install", "torch"]
+timeout_secs = 1800
+"#,
+        )
+        .unwrap();
+        let spec = BakeSpec::from_file(&path).unwrap();
+        assert_eq!(spec.image, "python:3.12-slim");
+        assert_eq!(spec.memory_mib, Some(4096));
+        assert_eq!(spec.labels["team"], "ml");
+        assert_eq!(spec.steps[0].args, ["install", "torch"]);
+        assert_eq!(spec.steps[0].timeout_secs, Some(1800));
+        assert!(spec.validate().is_ok());
+
+        let empty = BakeSpec {
+            steps: Vec::new(),
+            ..spec
+        };
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        assert!(validate_tag("myorg/pytorch-sandbox").is_ok());
+        assert!(validate_tag("pytorch-sandbox:2024.1").is_ok());
+        assert!(validate_tag("MyOrg/sandbox").is_err());
+        assert!(validate_tag("sandbox:").is_err());
+        assert!(validate_tag("").is_err());
+    }
+}
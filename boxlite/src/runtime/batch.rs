@@ -0,0 +1,114 @@
+//! Named queues limiting how many boxes run at once.
+//!
+//! A box with a [`BatchQueue`] takes one of its queue's slots before it
+//! starts and holds it while it runs. Slots are handed out first come,
+//! first served, so a batch submitted at once starts in submission order.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::runtime::options::BatchQueue;
+use crate::runtime::types::BoxID;
+
+/// The runtime's queues, created on first use and dropped once idle.
+#[derive(Default)]
+pub(crate) struct BatchQueues {
+    queues: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl BatchQueues {
+    /// Wait for a slot in `queue`.
+    pub(crate) async fn enter(self: &Arc<Self>, queue: &BatchQueue, box_id: &BoxID) -> QueueSlot {
+        let semaphore = self.semaphore(queue);
+        let permit = match Arc::clone(&semaphore).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                tracing::info!(box_id = %box_id, queue = %queue.name, "Box waiting in queue");
+                semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("queue semaphores are never closed")
+            }
+        };
+        self.slot(queue, permit)
+    }
+
+    /// Take a slot in `queue` if one is free, for a box that is already
+    /// running.
+    pub(crate) fn try_enter(self: &Arc<Self>, queue: &BatchQueue) -> Option<QueueSlot> {
+        let permit = self.semaphore(queue).try_acquire_owned().ok()?;
+        Some(self.slot(queue, permit))
+    }
+
+    fn semaphore(&self, queue: &BatchQueue) -> Arc<Semaphore> {
+        let mut queues = self.queues.lock().unwrap();
+        let semaphore = queues
+            .entry(queue.name.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(queue.max_parallel)));
+        Arc::clone(semaphore)
+    }
+
+    fn slot(self: &Arc<Self>, queue: &BatchQueue, permit: OwnedSemaphorePermit) -> QueueSlot {
+        QueueSlot {
+            queues: Arc::clone(self),
+            name: queue.name.clone(),
+            permit: Some(permit),
+        }
+    }
+}
+
+/// A running box's slot in its queue, given back on drop.
+pub(crate) struct QueueSlot {
+    queues: Arc<BatchQueues>,
+    name: String,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        // Forget the queue once no box holds or waits for a slot, so the
+        // next box to use the name sets its limit afresh
+        let mut queues = self.queues.queues.lock().unwrap();
+        if queues
+            .get(&self.name)
+            .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+        {
+            queues.remove(&self.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_queue_runs_max_parallel_at_once() {
+        let queues = Arc::new(BatchQueues::default());
+        let queue = BatchQueue::new("matrix", 2);
+        let first = queues.enter(&queue, &BoxID::new()).await;
+        let second = queues.enter(&queue, &BoxID::new()).await;
+        assert!(queues.try_enter(&queue).is_none());
+
+        let waiting = {
+            let queues = Arc::clone(&queues);
+            let queue = queue.clone();
+            tokio::spawn(async move { queues.enter(&queue, &BoxID::new()).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(third);
+        drop(second);
+        assert!(queues.queues.lock().unwrap().is_empty());
+    }
+}
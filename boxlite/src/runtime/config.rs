@@ -0,0 +1,547 @@
+//! Layered runtime configuration.
+//!
+//! [`BoxliteOptions`] can be resolved from several sources, each overriding
+//! the ones before it:
+//!
+//! 1. built-in defaults
+//! 2. the system config file, `/etc/boxlite/config.toml`
+//! 3. the user config file, `~/.config/boxlite/config.toml` (the platform
+//!    config directory)
+//! 4. `BOXLITE_*` environment variables
+//! 5. explicit overrides, as a front-end passes them from its flags
+//!
+//! Files are TOML with the same shape as the keys below; sections become
+//! dotted keys:
+//!
+//! ```toml
+//! home_dir = "/srv/boxlite"
+//!
+//! [quota]
+//! max_boxes = 20
+//! ```
+//!
+//! Every value is parsed to its type once, after the layers are merged, and
+//! [`ResolvedConfig`] keeps where each one came from so a wrong setting can
+//! be traced to its source.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::runtime::constants::envs as const_envs;
+use crate::runtime::options::{BoxliteOptions, EngineSelection, OverflowPolicy, default_home_dir};
+
+/// Config file read for every user.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/boxlite/config.toml";
+
+/// Every configurable key and the environment variable that sets it.
+const KEYS: &[Key] = &[
+    Key::new("home_dir", const_envs::BOXLITE_HOME, Kind::Path),
+    Key::new("quota.max_boxes", "BOXLITE_QUOTA_MAX_BOXES", Kind::Count),
+    Key::new("quota.max_cpus", "BOXLITE_QUOTA_MAX_CPUS", Kind::Count),
+    Key::new(
+        "quota.max_memory_mib",
+        "BOXLITE_QUOTA_MAX_MEMORY_MIB",
+        Kind::Count,
+    ),
+    Key::new(
+        "quota.max_disk_gb",
+        "BOXLITE_QUOTA_MAX_DISK_GB",
+        Kind::Count,
+    ),
+    Key::new(
+        "event_buffer.capacity",
+        "BOXLITE_EVENT_BUFFER_CAPACITY",
+        Kind::Count,
+    ),
+    Key::new(
+        "event_buffer.overflow",
+        "BOXLITE_EVENT_BUFFER_OVERFLOW",
+        Kind::Overflow,
+    ),
+    Key::new("cgroup_parent", "BOXLITE_CGROUP_PARENT", Kind::Path),
+    Key::new("policy_file", "BOXLITE_POLICY_FILE", Kind::Path),
+    Key::new("engine", "BOXLITE_ENGINE", Kind::Engine),
+];
+
+struct Key {
+    name: &'static str,
+    env: &'static str,
+    kind: Kind,
+}
+
+impl Key {
+    const fn new(name: &'static str, env: &'static str, kind: Kind) -> Self {
+        Self { name, env, kind }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Path,
+    Count,
+    Overflow,
+    Engine,
+}
+
+/// Where a configuration value came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    SystemFile(PathBuf),
+    UserFile(PathBuf),
+    Env(&'static str),
+    Override,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => f.write_str("default"),
+            ConfigSource::SystemFile(path) => write!(f, "system config {}", path.display()),
+            ConfigSource::UserFile(path) => write!(f, "user config {}", path.display()),
+            ConfigSource::Env(var) => write!(f, "env {}", var),
+            ConfigSource::Override => f.write_str("override"),
+        }
+    }
+}
+
+/// One resolved key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    /// Dotted key, e.g. `quota.max_boxes`.
+    pub key: &'static str,
+    /// The effective value; None when unset (unlimited quotas).
+    pub value: Option<String>,
+    pub source: ConfigSource,
+}
+
+/// Options resolved from every layer, with the origin of each value.
+#[derive(Clone, Debug)]
+pub struct ResolvedConfig {
+    options: BoxliteOptions,
+    entries: Vec<ConfigEntry>,
+}
+
+impl ResolvedConfig {
+    pub fn options(&self) -> &BoxliteOptions {
+        &self.options
+    }
+
+    pub fn into_options(self) -> BoxliteOptions {
+        self.options
+    }
+
+    /// Every key in a fixed order, set or not.
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+}
+
+/// One `key = value  # source` line per key.
+impl fmt::Display for ResolvedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let value = entry.value.as_deref().unwrap_or("(unset)");
+            writeln!(f, "{} = {}  # {}", entry.key, value, entry.source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`ResolvedConfig`] from the configuration layers.
+#[derive(Clone, Debug)]
+pub struct ConfigLoader {
+    system_file: Option<PathBuf>,
+    user_file: Option<PathBuf>,
+    env: Option<HashMap<String, String>>,
+    overrides: Vec<(String, String)>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigLoader {
+    /// Loader for the standard files and the process environment.
+    pub fn new() -> Self {
+        Self {
+            system_file: Some(PathBuf::from(SYSTEM_CONFIG_PATH)),
+            user_file: dirs::config_dir().map(|dir| dir.join("boxlite").join("config.toml")),
+            env: None,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Read the system layer from `path` instead (None to skip it).
+    pub fn system_file(mut self, path: Option<PathBuf>) -> Self {
+        self.system_file = path;
+        self
+    }
+
+    /// Read the user layer from `path` instead (None to skip it).
+    pub fn user_file(mut self, path: Option<PathBuf>) -> Self {
+        self.user_file = path;
+        self
+    }
+
+    /// Take environment variables from `vars` instead of the process.
+    pub fn env<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env = Some(
+            vars.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Override a key, as a command-line flag would.
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    /// Override a key from a `key=value` string.
+    pub fn set_pair(self, pair: &str) -> BoxliteResult<Self> {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| BoxliteError::Config(format!("Expected key=value, got '{}'", pair)))?;
+        Ok(self.set(key.trim(), value.trim()))
+    }
+
+    /// Merge the layers and parse the result.
+    pub fn resolve(&self) -> BoxliteResult<ResolvedConfig> {
+        let mut layers: HashMap<&'static str, (String, ConfigSource)> = HashMap::new();
+
+        if let Some(path) = &self.system_file {
+            for (key, value) in read_file(path)? {
+                layers.insert(key, (value, ConfigSource::SystemFile(path.clone())));
+            }
+        }
+        if let Some(path) = &self.user_file {
+            for (key, value) in read_file(path)? {
+                layers.insert(key, (value, ConfigSource::UserFile(path.clone())));
+            }
+        }
+        for key in KEYS {
+            let value = match &self.env {
+                Some(vars) => vars.get(key.env).cloned(),
+                None => std::env::var(key.env).ok(),
+            };
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                layers.insert(key.name, (value, ConfigSource::Env(key.env)));
+            }
+        }
+        for (name, value) in &self.overrides {
+            let key = lookup(name).ok_or_else(|| unknown_key(name, "overrides"))?;
+            layers.insert(key.name, (value.clone(), ConfigSource::Override));
+        }
+
+        build(layers)
+    }
+}
+
+/// Apply the layered values to the defaults.
+fn build(
+    mut layers: HashMap<&'static str, (String, ConfigSource)>,
+) -> BoxliteResult<ResolvedConfig> {
+    let mut options = BoxliteOptions {
+        home_dir: default_home_dir(),
+        ..BoxliteOptions::default()
+    };
+    let mut entries = Vec::with_capacity(KEYS.len());
+
+    for key in KEYS {
+        let Some((raw, source)) = layers.remove(key.name) else {
+            entries.push(ConfigEntry {
+                key: key.name,
+                value: current(&options, key.name),
+                source: ConfigSource::Default,
+            });
+            continue;
+        };
+        let invalid = |reason: String| {
+            BoxliteError::Config(format!(
+                "Invalid {} '{}' from {}: {}",
+                key.name, raw, source, reason
+            ))
+        };
+        match key.kind {
+            Kind::Path => {
+                if raw.is_empty() {
+                    return Err(invalid("must not be empty".to_string()));
+                }
+                match key.name {
+                    "home_dir" => options.home_dir = PathBuf::from(&raw),
+                    "cgroup_parent" => options.cgroup_parent = Some(PathBuf::from(&raw)),
+                    "policy_file" => options.policy_file = Some(PathBuf::from(&raw)),
+                    _ => unreachable!("not a path key: {}", key.name),
+                }
+            }
+            Kind::Count => {
+                let n: u64 = raw
+                    .parse()
+                    .map_err(|e: std::num::ParseIntError| invalid(e.to_string()))?;
+                set_count(&mut options, key.name, n).map_err(invalid)?;
+            }
+            Kind::Overflow => {
+                options.event_buffer.overflow = match raw.as_str() {
+                    "drop_oldest" => OverflowPolicy::DropOldest,
+                    "block" => OverflowPolicy::Block,
+                    _ => return Err(invalid("expected drop_oldest or block".to_string())),
+                };
+            }
+            Kind::Engine => {
+                options.engine = match raw.as_str() {
+                    "vm" => EngineSelection::Vm,
+                    "auto" => EngineSelection::Auto,
+                    "boxless" => EngineSelection::Boxless,
+                    _ => return Err(invalid("expected vm, auto or boxless".to_string())),
+                };
+            }
+        }
+        entries.push(ConfigEntry {
+            key: key.name,
+            value: current(&options, key.name),
+            source,
+        });
+    }
+
+    Ok(ResolvedConfig { options, entries })
+}
+
+fn set_count(options: &mut BoxliteOptions, key: &str, n: u64) -> Result<(), String> {
+    let too_large = |_| format!("{} is too large", n);
+    match key {
+        "quota.max_boxes" => options.quota.max_boxes = Some(n.try_into().map_err(too_large)?),
+        "quota.max_cpus" => options.quota.max_cpus = Some(n.try_into().map_err(too_large)?),
+        "quota.max_memory_mib" => options.quota.max_memory_mib = Some(n),
+        "quota.max_disk_gb" => options.quota.max_disk_gb = Some(n),
+        "event_buffer.capacity" => {
+            if n == 0 {
+                return Err("must be at least 1".to_string());
+            }
+            options.event_buffer.capacity = n.try_into().map_err(too_large)?;
+        }
+        _ => unreachable!("not a count key: {}", key),
+    }
+    Ok(())
+}
+
+/// The effective value of `key` in `options`.
+fn current(options: &BoxliteOptions, key: &str) -> Option<String> {
+    match key {
+        "home_dir" => Some(options.home_dir.display().to_string()),
+        "cgroup_parent" => options
+            .cgroup_parent
+            .as_ref()
+            .map(|path| path.display().to_string()),
+        "policy_file" => options
+            .policy_file
+            .as_ref()
+            .map(|path| path.display().to_string()),
+        "quota.max_boxes" => options.quota.max_boxes.map(|n| n.to_string()),
+        "quota.max_cpus" => options.quota.max_cpus.map(|n| n.to_string()),
+        "quota.max_memory_mib" => options.quota.max_memory_mib.map(|n| n.to_string()),
+        "quota.max_disk_gb" => options.quota.max_disk_gb.map(|n| n.to_string()),
+        "event_buffer.capacity" => Some(options.event_buffer.capacity.to_string()),
+        "event_buffer.overflow" => Some(
+            match options.event_buffer.overflow {
+                OverflowPolicy::DropOldest => "drop_oldest",
+                OverflowPolicy::Block => "block",
+            }
+            .to_string(),
+        ),
+        "engine" => Some(options.engine.as_str().to_string()),
+        _ => None,
+    }
+}
+
+fn lookup(name: &str) -> Option<&'static Key> {
+    KEYS.iter().find(|key| key.name == name)
+}
+
+fn unknown_key(name: &str, origin: &str) -> BoxliteError {
+    let known: Vec<_> = KEYS.iter().map(|key| key.name).collect();
+    BoxliteError::Config(format!(
+        "Unknown config key '{}' in {} (known keys: {})",
+        name,
+        origin,
+        known.join(", ")
+    ))
+}
+
+/// Keys and raw values set in a config file; a missing file sets nothing.
+fn read_file(path: &Path) -> BoxliteResult<Vec<(&'static str, String)>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(BoxliteError::Config(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            )));
+        }
+    };
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e| BoxliteError::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+
+    let mut values = Vec::new();
+    flatten(&table, "", &mut |name, value| {
+        let key = lookup(name).ok_or_else(|| unknown_key(name, &path.display().to_string()))?;
+        let raw = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(_) | toml::Value::Boolean(_) | toml::Value::Float(_) => {
+                value.to_string()
+            }
+            _ => {
+                return Err(BoxliteError::Config(format!(
+                    "{} in {} must be a string or number",
+                    name,
+                    path.display()
+                )));
+            }
+        };
+        values.push((key.name, raw));
+        Ok(())
+    })?;
+    Ok(values)
+}
+
+/// Visit the leaves of `table` by dotted key.
+fn flatten(
+    table: &toml::Table,
+    prefix: &str,
+    visit: &mut impl FnMut(&str, &toml::Value) -> BoxliteResult<()>,
+) -> BoxliteResult<()> {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value {
+            toml::Value::Table(inner) => flatten(inner, &key, visit)?,
+            _ => visit(&key, value)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader(dir: &Path) -> ConfigLoader {
+        ConfigLoader::new()
+            .system_file(Some(dir.join("system.toml")))
+            .user_file(Some(dir.join("user.toml")))
+            .env(Vec::<(String, String)>::new())
+    }
+
+    fn source_of<'a>(config: &'a ResolvedConfig, key: &str) -> &'a ConfigSource {
+        &config
+            .entries()
+            .iter()
+            .find(|e| e.key == key)
+            .unwrap()
+            .source
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("system.toml"),
+            "home_dir = \"/srv/boxlite\"\n[quota]\nmax_boxes = 10\nmax_cpus = 8\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("user.toml"),
+            "[quota]\nmax_boxes = 5\n[event_buffer]\noverflow = \"block\"\n",
+        )
+        .unwrap();
+
+        let config = loader(dir.path())
+            .env([("BOXLITE_QUOTA_MAX_CPUS", "4"), ("BOXLITE_ENGINE", "auto")])
+            .set("quota.max_boxes", "2")
+            .resolve()
+            .unwrap();
+        let options = config.options();
+        assert_eq!(options.home_dir, PathBuf::from("/srv/boxlite"));
+        assert_eq!(options.quota.max_boxes, Some(2));
+        assert_eq!(options.quota.max_cpus, Some(4));
+        assert_eq!(options.event_buffer.overflow, OverflowPolicy::Block);
+        assert_eq!(options.quota.max_disk_gb, None);
+        assert_eq!(options.engine, EngineSelection::Auto);
+
+        assert!(matches!(
+            source_of(&config, "home_dir"),
+            ConfigSource::SystemFile(_)
+        ));
+        assert_eq!(
+            source_of(&config, "quota.max_boxes"),
+            &ConfigSource::Override
+        );
+        assert_eq!(
+            source_of(&config, "quota.max_cpus"),
+            &ConfigSource::Env("BOXLITE_QUOTA_MAX_CPUS")
+        );
+        assert!(matches!(
+            source_of(&config, "event_buffer.overflow"),
+            ConfigSource::UserFile(_)
+        ));
+        assert_eq!(
+            source_of(&config, "quota.max_disk_gb"),
+            &ConfigSource::Default
+        );
+
+        let shown = config.to_string();
+        assert!(shown.contains("quota.max_boxes = 2  # override\n"));
+        assert!(shown.contains("quota.max_disk_gb = (unset)  # default\n"));
+    }
+
+    #[test]
+    fn test_rejects_bad_values() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("user.toml"), "[quota]\nmax_gpus = 1\n").unwrap();
+        let err = loader(dir.path()).resolve().unwrap_err().to_string();
+        assert!(
+            err.contains("Unknown config key 'quota.max_gpus'"),
+            "{}",
+            err
+        );
+
+        let err = loader(&dir.path().join("none"))
+            .env([("BOXLITE_QUOTA_MAX_BOXES", "many")])
+            .resolve()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("BOXLITE_QUOTA_MAX_BOXES"), "{}", err);
+
+        assert!(
+            loader(&dir.path().join("none"))
+                .set("event_buffer.overflow", "wait")
+                .resolve()
+                .is_err()
+        );
+        assert!(
+            loader(&dir.path().join("none"))
+                .set("engine", "container")
+                .resolve()
+                .is_err()
+        );
+        assert!(ConfigLoader::new().set_pair("quota.max_boxes").is_err());
+    }
+}
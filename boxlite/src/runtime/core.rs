@@ -1,13 +1,27 @@
 //! High-level sandbox runtime structures.
 
+use std::path::PathBuf;
 use std::sync::OnceLock;
 
+use crate::events::{BoxEvent, EventSubscription};
 use crate::litebox::LiteBox;
 use crate::metrics::RuntimeMetrics;
+use crate::runtime::bake::{self, BakeSpec, BakedImage};
+use crate::runtime::disk_usage::{DiskUsage, PruneReport, SystemPruneOptions};
+use crate::runtime::doctor::{self, BenchReport, DoctorReport};
+use crate::runtime::dry_run::DryRunReport;
+use crate::runtime::filter::BoxFilter;
+use crate::runtime::lockfile::BoxLock;
+use crate::runtime::migration::ExportOptions;
+use crate::runtime::operations::{OperationProgress, ProgressSubscription};
 use crate::runtime::options::{BoxOptions, BoxliteOptions};
+use crate::runtime::overcommit::CapacityReport;
 use crate::runtime::rt_impl::{RuntimeImpl, SharedRuntimeImpl};
-use crate::runtime::types::BoxInfo;
+use crate::runtime::types::{BoxID, BoxInfo};
+use crate::snapshots::SnapshotInfo;
+use crate::usage::ExecUsage;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use chrono::{DateTime, Utc};
 // ============================================================================
 // GLOBAL DEFAULT RUNTIME
 // ============================================================================
@@ -59,8 +73,8 @@ impl BoxliteRuntime {
 
     /// Create a new runtime with default options.
     ///
-    /// This is equivalent to `BoxliteRuntime::new(BoxliteOptions::default())`
-    /// but returns a `Result` instead of panicking.
+    /// This is equivalent to `BoxliteRuntime::new(BoxliteOptions::load()?)`:
+    /// the defaults with config files and `BOXLITE_*` variables applied.
     ///
     /// Prefer `default_runtime()` for most use cases (shares global instance).
     /// Use this when you need an owned, non-global runtime with default config.
@@ -74,12 +88,12 @@ impl BoxliteRuntime {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn with_defaults() -> BoxliteResult<Self> {
-        Self::new(BoxliteOptions::default())
+        Self::new(BoxliteOptions::load()?)
     }
 
     /// Get or initialize the default global runtime.
     ///
-    /// This runtime uses `BoxliteOptions::default()` for configuration.
+    /// This runtime uses `BoxliteOptions::load()` for configuration.
     /// The runtime is created lazily on first access and reused for all
     /// subsequent calls.
     ///
@@ -137,7 +151,7 @@ impl BoxliteRuntime {
     /// # Example
     ///
     /// ```no_run
-    /// use boxlite::runtime::{BoxliteRuntime, BoxliteOptions};
+    /// use boxlite::{BoxliteOptions, BoxliteRuntime};
     /// use std::path::PathBuf;
     ///
     /// fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -191,6 +205,11 @@ impl BoxliteRuntime {
         self.rt_impl.list_info()
     }
 
+    /// List boxes matching a filter, sorted by creation time (newest first).
+    pub fn list_info_filtered(&self, filter: &BoxFilter) -> BoxliteResult<Vec<BoxInfo>> {
+        self.rt_impl.list_info_filtered(filter)
+    }
+
     /// Check if a box with the given ID or name exists.
     pub fn exists(&self, id_or_name: &str) -> BoxliteResult<bool> {
         self.rt_impl.exists(id_or_name)
@@ -201,10 +220,293 @@ impl BoxliteRuntime {
         self.rt_impl.metrics()
     }
 
+    /// Host CPUs and memory against what running boxes have committed, and
+    /// the limits [`OvercommitOptions`](crate::OvercommitOptions) admit
+    /// boxes up to.
+    pub fn capacity(&self) -> CapacityReport {
+        self.rt_impl.capacity()
+    }
+
+    /// Check that the host can run boxes (hypervisor access, runtime
+    /// binaries, disk space, socket paths) without starting one.
+    pub fn doctor(&self) -> DoctorReport {
+        doctor::check_host(&self.rt_impl.layout)
+    }
+
+    /// Boot a throwaway box from `image` and time boot, exec round trips,
+    /// and virtio-fs throughput. The box is removed afterwards.
+    pub async fn bench(&self, image: &str) -> BoxliteResult<BenchReport> {
+        doctor::bench(self, &self.rt_impl.layout, image).await
+    }
+
+    /// Write a diagnostics bundle (gzipped tar) for a box: its spec, state
+    /// and events, the tail of its kernel console and of the runtime and
+    /// shim logs, and host information. One is also saved to
+    /// `~/.boxlite/logs/diagnostics` whenever a box fails to boot or its VM
+    /// is found dead.
+    pub fn diagnose(&self, id_or_name: &str, writer: impl std::io::Write) -> BoxliteResult<()> {
+        self.rt_impl.diagnose(id_or_name, writer)
+    }
+
     /// Remove a box completely by ID or name.
     pub async fn remove(&self, id_or_name: &str, force: bool) -> BoxliteResult<()> {
         self.rt_impl.remove(id_or_name, force)
     }
+
+    /// Lifecycle events recorded at or after `since` (all retained events if
+    /// None) for boxes matching `filter`, oldest first.
+    ///
+    /// Events are persisted, so history survives runtime restarts.
+    pub fn events(
+        &self,
+        since: Option<DateTime<Utc>>,
+        filter: &BoxFilter,
+    ) -> BoxliteResult<Vec<BoxEvent>> {
+        self.rt_impl.events(since, filter)
+    }
+
+    /// Resource usage of executions that finished at or after `since` (all
+    /// retained records if None) in boxes matching `filter`, oldest first.
+    ///
+    /// Records are persisted and outlive their boxes; export them with
+    /// [`write_usage_csv`](crate::write_usage_csv).
+    pub fn usage(
+        &self,
+        since: Option<DateTime<Utc>>,
+        filter: &BoxFilter,
+    ) -> BoxliteResult<Vec<ExecUsage>> {
+        self.rt_impl.usage(since, filter)
+    }
+
+    /// Subscribe to lifecycle events emitted by this runtime from now on.
+    ///
+    /// What a subscriber that falls behind misses depends on
+    /// `BoxliteOptions::event_buffer`; see [`EventSubscription`].
+    pub fn subscribe_events(&self) -> EventSubscription {
+        self.rt_impl.subscribe_events()
+    }
+
+    /// Long operations in progress (image pulls, snapshot restores, copies
+    /// into or out of boxes), oldest first.
+    pub fn operations(&self) -> Vec<OperationProgress> {
+        self.rt_impl.operations()
+    }
+
+    /// Subscribe to progress reports of operations from now on: one when an
+    /// operation starts, periodic ones while it advances, and one when it
+    /// ends.
+    ///
+    /// A subscriber that falls behind skips to the newest reports; see
+    /// [`operations`](crate::runtime::operations).
+    pub fn subscribe_progress(&self) -> ProgressSubscription {
+        self.rt_impl.subscribe_progress()
+    }
+
+    /// Cancel a running operation by ID; the call running it fails with
+    /// `BoxliteError::Cancelled`.
+    ///
+    /// Snapshot restores cannot be cancelled once started.
+    pub fn cancel_operation(&self, id: &str) -> BoxliteResult<()> {
+        self.rt_impl.cancel_operation(id)
+    }
+
+    /// The CA certificate of the ingress proxy, if `BoxliteOptions::ingress`
+    /// is set. Trust it to browse boxes at `https://<name>.localhost`.
+    pub fn ingress_ca(&self) -> Option<PathBuf> {
+        self.rt_impl.ingress_ca()
+    }
+
+    /// Remove every box matching a filter (e.g. `label=team=ml`).
+    ///
+    /// Returns the IDs of the removed boxes.
+    pub async fn remove_matching(
+        &self,
+        filter: &BoxFilter,
+        force: bool,
+    ) -> BoxliteResult<Vec<BoxID>> {
+        self.rt_impl.remove_matching(filter, force)
+    }
+
+    /// Remove every stopped box matching a filter.
+    ///
+    /// Returns the IDs of the removed boxes.
+    pub async fn prune(&self, filter: &BoxFilter) -> BoxliteResult<Vec<BoxID>> {
+        self.rt_impl.prune(filter)
+    }
+
+    /// Report disk space used by images, snapshots, the package cache and
+    /// each box, with what a prune could reclaim.
+    ///
+    /// Walks the store, so it can take a while on large homes.
+    pub async fn disk_usage(&self) -> BoxliteResult<DiskUsage> {
+        self.rt_impl.disk_usage().await
+    }
+
+    /// Boot a box from `spec.image`, run the spec's steps in it, and save
+    /// its disk as an image tagged `tag` that boxes can then be created
+    /// from, starting already provisioned. Baking a tag again replaces it.
+    pub async fn bake(&self, spec: &BakeSpec, tag: &str) -> BoxliteResult<BakedImage> {
+        bake::bake(self, &self.rt_impl, spec, tag).await
+    }
+
+    /// Images baked with [`bake`](Self::bake), by tag.
+    pub fn baked_images(&self) -> BoxliteResult<Vec<BakedImage>> {
+        self.rt_impl.baked_images.list()
+    }
+
+    /// Baked images matching a filter (see [`BoxFilter::matches_image`]),
+    /// by tag.
+    pub fn baked_images_filtered(&self, filter: &BoxFilter) -> BoxliteResult<Vec<BakedImage>> {
+        let mut images = self.rt_impl.baked_images.list()?;
+        images.retain(|image| filter.matches_image(image));
+        Ok(images)
+    }
+
+    /// Forget the image baked under `tag`; its disk goes with its base
+    /// image once that is pruned. Returns whether there was one.
+    pub fn remove_baked_image(&self, tag: &str) -> BoxliteResult<bool> {
+        self.rt_impl.baked_images.remove(tag)
+    }
+
+    /// Resolve and validate a box spec without creating or booting anything.
+    ///
+    /// Checks the options, the name, that the image resolves (from the cache
+    /// or the registry, without pulling it), that volume sources exist, that
+    /// fixed host ports are free, and the quota. Problems are collected in
+    /// the report rather than returned as errors.
+    pub async fn dry_run(
+        &self,
+        options: &BoxOptions,
+        name: Option<&str>,
+    ) -> BoxliteResult<DryRunReport> {
+        self.rt_impl.dry_run(options, name).await
+    }
+
+    /// Resolve a box spec and pin what it resolved to, for `create_locked`.
+    ///
+    /// The image reference and the guest rootfs image are resolved to
+    /// manifest digests (from the cache or the registry, without pulling),
+    /// and the resolved spec is digested so later edits are caught. Write
+    /// the lock with `BoxLock::write`, next to the spec at
+    /// `BoxLock::path_for`.
+    pub async fn lock(&self, options: &BoxOptions) -> BoxliteResult<BoxLock> {
+        self.rt_impl.lock(options).await
+    }
+
+    /// Create a box from a spec, refusing it if it drifted from `lock`.
+    ///
+    /// The box is created from the locked image digest, whatever the tag
+    /// points at now. The spec, the engine version and the guest rootfs
+    /// image must all still match the lock; every difference is reported
+    /// in the error.
+    pub async fn create_locked(
+        &self,
+        options: BoxOptions,
+        lock: &BoxLock,
+        name: Option<String>,
+    ) -> BoxliteResult<LiteBox> {
+        self.rt_impl.create_locked(options, lock, name).await
+    }
+
+    /// Remove stopped boxes matching the options' filter and, with
+    /// `images`, cached images no remaining box uses.
+    ///
+    /// With `dry_run` nothing is removed and the report says what would be.
+    pub async fn system_prune(&self, options: &SystemPruneOptions) -> BoxliteResult<PruneReport> {
+        self.rt_impl.system_prune(options).await
+    }
+
+    /// Remove boxes whose TTL has expired and stopped `auto_remove` boxes left
+    /// behind by a process that exited without removing them.
+    ///
+    /// Runs automatically when the runtime starts; long-lived processes can
+    /// call it periodically. Returns the IDs of the removed boxes.
+    pub fn reap(&self) -> BoxliteResult<Vec<BoxID>> {
+        self.rt_impl.reap()
+    }
+
+    /// Clone a box into a new, independent box.
+    ///
+    /// The clone starts from a copy of the source's disks with a fresh box ID,
+    /// container ID, hostname and machine ID, and no host port mappings. A
+    /// running source keeps running: its rootfs is frozen while the disks are
+    /// copied. Memory is not copied, so the clone boots afresh.
+    pub async fn clone_box(
+        &self,
+        id_or_name: &str,
+        name: Option<String>,
+    ) -> BoxliteResult<LiteBox> {
+        self.rt_impl.clone_box(id_or_name, name).await
+    }
+
+    /// Write a stopped box as a bundle, to move it to another host.
+    ///
+    /// The bundle is a zstd-compressed tar holding the box's options and
+    /// its disks with everything they are backed by, so it is
+    /// self-contained: pipe it over ssh and `import_box` it on the other
+    /// side. `options` can also bundle the box's volume directories, or
+    /// export the box as of one of its snapshots. Only disk state is moved;
+    /// the box must be stopped, and its snapshot history stays behind.
+    pub fn export_box(
+        &self,
+        id_or_name: &str,
+        options: &ExportOptions,
+        writer: impl std::io::Write,
+    ) -> BoxliteResult<()> {
+        self.rt_impl.export_box(id_or_name, options, writer)
+    }
+
+    /// Create a stopped box from a bundle written by `export_box`.
+    ///
+    /// The box gets a fresh box ID and container ID and keeps the exported
+    /// name unless `name` is given. Its first start resumes the guest as
+    /// after a clone (clock, entropy, session token).
+    ///
+    /// Bundles are untrusted: only options that stay inside the box are
+    /// kept, so port forwards, DNS names, host forwarding and the like are
+    /// turned off, and a bundle whose box needs host paths, keys, devices
+    /// or volumes it does not carry is refused.
+    pub fn import_box(
+        &self,
+        reader: impl std::io::Read,
+        name: Option<String>,
+    ) -> BoxliteResult<LiteBox> {
+        self.rt_impl.import_box(reader, name)
+    }
+
+    // ========================================================================
+    // SNAPSHOT OPERATIONS (delegate to RuntimeInnerImpl)
+    // ========================================================================
+
+    /// Snapshot a stopped box's disks.
+    ///
+    /// Snapshots are layered: each one stores only the blocks written since
+    /// the previous snapshot, and layers are shared by content digest.
+    pub fn snapshot(&self, id_or_name: &str, name: &str) -> BoxliteResult<SnapshotInfo> {
+        self.rt_impl.snapshot(id_or_name, name)
+    }
+
+    /// List a box's snapshots, oldest first.
+    pub fn list_snapshots(&self, id_or_name: &str) -> BoxliteResult<Vec<SnapshotInfo>> {
+        self.rt_impl.list_snapshots(id_or_name)
+    }
+
+    /// Restore a stopped box to a snapshot (by snapshot ID or name).
+    ///
+    /// Any point in the snapshot chain can be restored. Changes made since
+    /// the box's current snapshot are discarded, including the guest rootfs
+    /// overlay, which goes back to its base if the snapshot has none.
+    pub fn restore_snapshot(&self, id_or_name: &str, snapshot: &str) -> BoxliteResult<()> {
+        self.rt_impl.restore_snapshot(id_or_name, snapshot)
+    }
+
+    /// Remove a snapshot of a stopped box (by snapshot ID or name).
+    ///
+    /// Fails if other snapshots were taken on top of it or the box is
+    /// currently based on it.
+    pub fn remove_snapshot(&self, id_or_name: &str, snapshot: &str) -> BoxliteResult<()> {
+        self.rt_impl.remove_snapshot(id_or_name, snapshot)
+    }
 }
 
 // ============================================================================
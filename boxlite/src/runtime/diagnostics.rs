@@ -0,0 +1,185 @@
+//! Diagnostics bundles for bug reports.
+//!
+//! A bundle is a gzipped tarball of everything needed to tell why a box
+//! failed: its spec and state, the failure if one was seen, the tail of
+//! the kernel console (which carries the guest agent's traces), the tails
+//! of the runtime and shim logs, the box's events, and the host (OS,
+//! resources, [`DoctorReport`](crate::runtime::doctor::DoctorReport)).
+//!
+//! One is saved to `~/.boxlite/logs/diagnostics` whenever a box fails to
+//! boot or its VM is found dead, and `BoxliteRuntime::diagnose` writes one
+//! on demand.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use sysinfo::System;
+
+use crate::events::BoxEvent;
+use crate::litebox::config::BoxConfig;
+use crate::runtime::doctor;
+use crate::runtime::layout::FilesystemLayout;
+use crate::runtime::types::BoxState;
+
+/// How much of the end of each log is kept.
+const LOG_TAIL_BYTES: u64 = 512 * 1024;
+/// Log files in `~/.boxlite/logs`, each rotated daily.
+const LOGS: &[&str] = &["boxlite.log", "boxlite-shim.log"];
+
+/// What a bundle is made of.
+pub(crate) struct Diagnostics<'a> {
+    pub(crate) layout: &'a FilesystemLayout,
+    pub(crate) config: &'a BoxConfig,
+    pub(crate) state: &'a BoxState,
+    pub(crate) events: Vec<BoxEvent>,
+    /// The error the box failed with, if one was seen.
+    pub(crate) failure: Option<String>,
+}
+
+impl Diagnostics<'_> {
+    /// Write the bundle as a gzipped tarball.
+    pub(crate) fn write<W: Write>(&self, writer: W) -> BoxliteResult<()> {
+        let fail = |e: std::io::Error| {
+            BoxliteError::Storage(format!("Failed to write diagnostics: {}", e))
+        };
+        let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+
+        let mut entries = vec![
+            ("spec.json", json(self.config)?),
+            ("state.json", json(self.state)?),
+            ("events.json", json(&self.events)?),
+            ("host.txt", host_info(self.layout).into_bytes()),
+        ];
+        if let Some(failure) = &self.failure {
+            entries.push(("failure.txt", format!("{}\n", failure).into_bytes()));
+        }
+        let console = self.config.box_home.join("console.log");
+        if let Some(tail) = tail(&console) {
+            entries.push(("console.log", tail));
+        }
+        for name in LOGS {
+            if let Some(tail) = latest_log(&self.layout.logs_dir(), name).and_then(|p| tail(&p)) {
+                entries.push((name, tail));
+            }
+        }
+
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_slice())
+                .map_err(fail)?;
+        }
+        builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .and_then(|mut writer| writer.flush())
+            .map_err(fail)
+    }
+
+    /// Save the bundle under `~/.boxlite/logs/diagnostics`, returning its
+    /// path.
+    pub(crate) fn save(&self) -> BoxliteResult<PathBuf> {
+        let dir = self.layout.diagnostics_dir();
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", dir.display(), e))
+        })?;
+        let path = dir.join(format!(
+            "{}-{}.tar.gz",
+            self.config.id,
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+        ));
+        let file = std::fs::File::create(&path).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", path.display(), e))
+        })?;
+        self.write(std::io::BufWriter::new(file))?;
+        Ok(path)
+    }
+}
+
+fn json<T: serde::Serialize>(value: &T) -> BoxliteResult<Vec<u8>> {
+    serde_json::to_vec_pretty(value)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode diagnostics: {}", e)))
+}
+
+/// The engine, OS and resources of the host, then its doctor report.
+fn host_info(layout: &FilesystemLayout) -> String {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    format!(
+        "engine: {}\nos: {}\nkernel: {}\narch: {}\ncpus: {}\nmemory: {} MiB ({} MiB available)\n\n{}",
+        env!("CARGO_PKG_VERSION"),
+        System::long_os_version().unwrap_or_else(|| "unknown".to_string()),
+        System::kernel_version().unwrap_or_else(|| "unknown".to_string()),
+        std::env::consts::ARCH,
+        std::thread::available_parallelism().map_or(0, |n| n.get()),
+        sys.total_memory() >> 20,
+        sys.available_memory() >> 20,
+        doctor::check_host(layout)
+    )
+}
+
+/// The newest of the daily rotated files of log `name` in `dir`.
+fn latest_log(dir: &Path, name: &str) -> Option<PathBuf> {
+    // Rotated files are suffixed with their date, which sorts by age
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|file| file.to_str())
+                .is_some_and(|file| file.starts_with(name))
+        })
+        .max()
+}
+
+/// The last [`LOG_TAIL_BYTES`] of a file.
+fn tail(path: &Path) -> Option<Vec<u8>> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL_BYTES)))
+        .ok()?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).ok()?;
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::layout::FsLayoutConfig;
+
+    #[test]
+    fn test_tail_and_latest_log() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("boxlite.log.2026-01-01"), "old").unwrap();
+        std::fs::write(dir.path().join("boxlite.log.2026-01-02"), "new").unwrap();
+        std::fs::write(dir.path().join("boxlite-shim.log.2026-01-03"), "shim").unwrap();
+        let latest = latest_log(dir.path(), "boxlite.log").unwrap();
+        assert_eq!(tail(&latest).unwrap(), b"new");
+
+        let big = dir.path().join("big");
+        let content = vec![b'x'; LOG_TAIL_BYTES as usize + 10];
+        std::fs::write(&big, &content).unwrap();
+        assert_eq!(tail(&big).unwrap().len(), LOG_TAIL_BYTES as usize);
+    }
+
+    #[test]
+    fn test_host_info_includes_doctor_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let layout = FilesystemLayout::new(
+            dir.path().to_path_buf(),
+            FsLayoutConfig::without_bind_mount(),
+        );
+        let info = host_info(&layout);
+        assert!(info.starts_with("engine: "), "{}", info);
+        assert!(info.contains("] hypervisor: "), "{}", info);
+    }
+}
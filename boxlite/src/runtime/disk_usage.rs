@@ -0,0 +1,211 @@
+//! Disk usage reporting and store-wide pruning.
+//!
+//! [`DiskUsage`] breaks the runtime home down by what holds the space:
+//! cached images, snapshot layers, the package cache, and each box's own
+//! directory (disk overlays, logs, sockets). Sizes are allocated bytes, so
+//! sparse qcow2 overlays count for what they occupy rather than their
+//! virtual size, and shared snapshot layers count once.
+//!
+//! Reclaimable space is what [`SystemPruneOptions::all`] would free:
+//! stopped boxes with their snapshots, and images no remaining box uses.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::disk::Qcow2Helper;
+use crate::litebox::config::BoxConfig;
+use crate::runtime::constants::images;
+use crate::runtime::filter::BoxFilter;
+use crate::runtime::options::RootfsSpec;
+use crate::runtime::types::{BoxID, BoxStatus};
+
+/// Space used by the runtime's store.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// Cached images: layer blobs, extracted layers and base disks.
+    pub images: UsageEntry,
+    /// Snapshot layers.
+    pub snapshots: UsageEntry,
+    /// Shared package manager cache.
+    pub package_cache: UsageEntry,
+    /// Every box, newest first.
+    pub boxes: Vec<BoxDiskUsage>,
+}
+
+impl DiskUsage {
+    /// Space used inside the runtime home.
+    ///
+    /// Volume directories live outside it and are not included.
+    pub fn total_bytes(&self) -> u64 {
+        self.images.bytes
+            + self.snapshots.bytes
+            + self.package_cache.bytes
+            + self.boxes.iter().map(|b| b.scratch_bytes).sum::<u64>()
+    }
+
+    /// Space a full prune would free.
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.images.reclaimable_bytes
+            + self.snapshots.reclaimable_bytes
+            + self.package_cache.reclaimable_bytes
+            + self
+                .boxes
+                .iter()
+                .filter(|b| !b.status.is_active())
+                .map(|b| b.scratch_bytes)
+                .sum::<u64>()
+    }
+}
+
+/// Size of one part of the store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub bytes: u64,
+    /// Part of `bytes` a full prune would free.
+    pub reclaimable_bytes: u64,
+}
+
+/// Space held by one box.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoxDiskUsage {
+    pub id: BoxID,
+    pub name: Option<String>,
+    pub status: BoxStatus,
+    /// The box directory: disk overlays written since the image, logs and
+    /// sockets. Freed when the box is removed.
+    pub scratch_bytes: u64,
+    /// Host directories mounted as volumes. Never freed by pruning.
+    pub volumes: Vec<VolumeUsage>,
+}
+
+/// Space used by a volume's host directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeUsage {
+    pub host_path: String,
+    pub guest_path: String,
+    pub bytes: u64,
+}
+
+/// What [`crate::BoxliteRuntime::system_prune`] removes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemPruneOptions {
+    /// Stopped boxes to remove (with their snapshots). An empty filter
+    /// matches every stopped box; active boxes are never removed.
+    pub filter: BoxFilter,
+    /// Also remove cached images that no remaining box uses.
+    pub images: bool,
+    /// Report what would be removed without removing anything.
+    pub dry_run: bool,
+}
+
+impl SystemPruneOptions {
+    /// Every stopped box and every unused image.
+    pub fn all() -> Self {
+        Self {
+            images: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// What a prune removed (or, for a dry run, would remove).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub boxes: Vec<BoxID>,
+    /// References of the removed images.
+    pub images: Vec<String>,
+    /// Space freed by the removed boxes, their snapshot layers and images.
+    pub reclaimed_bytes: u64,
+}
+
+/// Image references the boxes were created from, plus the guest rootfs
+/// image every box boots from, which is never pruned.
+pub(crate) fn image_references(configs: &[&BoxConfig]) -> HashSet<String> {
+    configs
+        .iter()
+        .filter_map(|config| match &config.options.rootfs {
+            RootfsSpec::Image(reference) => Some(reference.clone()),
+            RootfsSpec::RootfsPath(_) => None,
+        })
+        .chain([images::INIT_ROOTFS.to_string()])
+        .collect()
+}
+
+/// Every file in a qcow2 backing chain, starting with `disk` itself.
+///
+/// Paths are recorded both as written and canonicalized, so callers can
+/// match them against store paths either way.
+pub(crate) fn backing_chain(disk: &Path, chain: &mut HashSet<PathBuf>) {
+    let mut current = disk.to_path_buf();
+    while !chain.contains(&current) {
+        if let Ok(canonical) = current.canonicalize() {
+            chain.insert(canonical);
+        }
+        chain.insert(current.clone());
+        // Base images are raw ext4, which ends the chain
+        let Ok(Some(backing)) = Qcow2Helper::backing_file(&current) else {
+            return;
+        };
+        current = match current.parent() {
+            Some(dir) if backing.is_relative() => dir.join(backing),
+            _ => backing,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn box_usage(status: BoxStatus, scratch_bytes: u64) -> BoxDiskUsage {
+        BoxDiskUsage {
+            id: BoxID::new(),
+            name: None,
+            status,
+            scratch_bytes,
+            volumes: vec![VolumeUsage {
+                host_path: "/srv/data".to_string(),
+                guest_path: "/data".to_string(),
+                bytes: 1 << 30,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_totals() {
+        let usage = DiskUsage {
+            images: UsageEntry {
+                bytes: 1000,
+                reclaimable_bytes: 400,
+            },
+            snapshots: UsageEntry {
+                bytes: 100,
+                reclaimable_bytes: 10,
+            },
+            package_cache: UsageEntry {
+                bytes: 50,
+                reclaimable_bytes: 0,
+            },
+            boxes: vec![
+                box_usage(BoxStatus::Running, 20),
+                box_usage(BoxStatus::Stopped, 30),
+            ],
+        };
+        assert_eq!(usage.total_bytes(), 1200);
+        assert_eq!(usage.reclaimable_bytes(), 440);
+    }
+
+    #[test]
+    fn test_backing_chain_of_raw_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("base.ext4");
+        std::fs::write(&disk, b"not a qcow2").unwrap();
+
+        let mut chain = HashSet::new();
+        backing_chain(&disk, &mut chain);
+        assert!(chain.contains(&disk));
+        assert!(chain.contains(&disk.canonicalize().unwrap()));
+    }
+}
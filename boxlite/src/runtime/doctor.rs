@@ -0,0 +1,412 @@
+//! Host self-test and benchmarks.
+//!
+//! [`DoctorReport`] checks what a box needs from the host without starting
+//! one: hypervisor access, the shim and guest agent binaries, a writable
+//! home with free space, and socket paths short enough for the vsock
+//! bridge. [`BenchReport`] boots a throwaway box and times boot, exec
+//! round trips and virtio-fs reads and writes. Both print as a plain text
+//! report to paste into a bug report.
+
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::litebox::{BoxCommand, LiteBox};
+use crate::runtime::BoxliteRuntime;
+use crate::runtime::layout::FilesystemLayout;
+use crate::runtime::options::{BoxOptions, RootfsSpec, VolumeSpec};
+use crate::runtime::types::BoxID;
+use crate::util::find_binary;
+
+/// Warn when the home directory has less free space than this.
+const MIN_FREE_BYTES: u64 = 5 << 30;
+/// Longest Unix socket path the platform accepts (`sun_path`, less the
+/// terminating NUL).
+#[cfg(target_os = "macos")]
+const MAX_SOCKET_PATH: usize = 103;
+#[cfg(not(target_os = "macos"))]
+const MAX_SOCKET_PATH: usize = 107;
+/// Exec round trips timed after boot.
+const EXEC_ROUNDS: usize = 10;
+/// MiB written and read back through virtio-fs.
+const FS_BENCH_MIB: u64 = 64;
+
+/// Outcome of one check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Boxes work, but something will hurt (e.g. low disk space).
+    Warn,
+    /// Boxes cannot start.
+    Fail,
+}
+
+/// One host check and what it found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Host checks, in the order they ran.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Whether no check failed.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Pass => "ok",
+                CheckStatus::Warn => "warn",
+                CheckStatus::Fail => "FAIL",
+            };
+            writeln!(f, "[{:>4}] {}: {}", status, check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Timings from a throwaway box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchReport {
+    /// From creating the box until its first exec returns.
+    pub boot: Duration,
+    /// Median host-side time to run `true` in the booted box.
+    pub exec_round_trip: Duration,
+    /// Sequential write throughput to a virtio-fs volume, in MiB/s.
+    pub fs_write_mib_per_sec: f64,
+    /// Sequential read throughput from a virtio-fs volume, in MiB/s.
+    pub fs_read_mib_per_sec: f64,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "boot:             {} ms", self.boot.as_millis())?;
+        writeln!(
+            f,
+            "exec round trip:  {:.1} ms (median of {})",
+            self.exec_round_trip.as_secs_f64() * 1000.0,
+            EXEC_ROUNDS
+        )?;
+        writeln!(
+            f,
+            "virtio-fs write:  {:.0} MiB/s",
+            self.fs_write_mib_per_sec
+        )?;
+        writeln!(f, "virtio-fs read:   {:.0} MiB/s", self.fs_read_mib_per_sec)
+    }
+}
+
+/// Run every host check.
+pub(crate) fn check_host(layout: &FilesystemLayout) -> DoctorReport {
+    DoctorReport {
+        checks: vec![
+            hypervisor(),
+            binary("shim", "boxlite-shim"),
+            binary("guest agent", "boxlite-guest"),
+            home_dir(layout),
+            socket_paths(layout),
+        ],
+    }
+}
+
+/// Whether this host can run VM boxes, for picking an engine.
+pub(crate) fn hypervisor_available() -> bool {
+    hypervisor().status == CheckStatus::Pass
+}
+
+#[cfg(target_os = "linux")]
+fn hypervisor() -> Check {
+    const NAME: &str = "hypervisor";
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/kvm")
+    {
+        Ok(_) => Check::new(NAME, CheckStatus::Pass, "/dev/kvm is accessible"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "/dev/kvm is missing: enable virtualization in firmware and load the kvm module",
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "no access to /dev/kvm: add the user to the kvm group",
+        ),
+        Err(e) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("failed to open /dev/kvm: {}", e),
+        ),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn hypervisor() -> Check {
+    const NAME: &str = "hypervisor";
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", "kern.hv_support"])
+        .output();
+    match output {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).trim() == "1" => {
+            Check::new(NAME, CheckStatus::Pass, "Hypervisor.framework is supported")
+        }
+        Ok(_) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "Hypervisor.framework is not supported on this Mac",
+        ),
+        Err(e) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("failed to run sysctl: {}", e),
+        ),
+    }
+}
+
+#[cfg(windows)]
+fn hypervisor() -> Check {
+    const NAME: &str = "hypervisor";
+    if crate::vmm::whpx::hypervisor_present() {
+        Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "Windows Hypervisor Platform is enabled, but the WHPX engine cannot boot guests yet; use WSL2",
+        )
+    } else {
+        Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "Windows Hypervisor Platform is not enabled: turn on the HypervisorPlatform optional feature",
+        )
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn hypervisor() -> Check {
+    Check::new(
+        "hypervisor",
+        CheckStatus::Fail,
+        "boxes need Linux (KVM), macOS (Hypervisor.framework) or Windows (WHPX)",
+    )
+}
+
+fn binary(name: &'static str, binary: &str) -> Check {
+    match find_binary(binary) {
+        Ok(path) => Check::new(name, CheckStatus::Pass, path.display().to_string()),
+        Err(_) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("{} not found; set BOXLITE_RUNTIME_DIR", binary),
+        ),
+    }
+}
+
+fn home_dir(layout: &FilesystemLayout) -> Check {
+    const NAME: &str = "home directory";
+    let home = layout.home_dir();
+    let probe = layout.temp_dir().join(format!("doctor-{}", BoxID::new()));
+    if let Err(e) = std::fs::write(&probe, b"") {
+        return Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not writable: {}", home.display(), e),
+        );
+    }
+    let _ = std::fs::remove_file(&probe);
+    match free_bytes(home) {
+        Some(free) if free < MIN_FREE_BYTES => Check::new(
+            NAME,
+            CheckStatus::Warn,
+            format!(
+                "{} has only {} MiB free; images and disks may not fit",
+                home.display(),
+                free >> 20
+            ),
+        ),
+        Some(free) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("{} ({} GiB free)", home.display(), free >> 30),
+        ),
+        None => Check::new(NAME, CheckStatus::Pass, home.display().to_string()),
+    }
+}
+
+/// Free bytes on the filesystem holding `path`.
+fn free_bytes(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is NUL-terminated and stat is written before it is read
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: statvfs succeeded
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)] // the field types differ by platform
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// The guest's vsock ports are bridged to Unix sockets in each box
+/// directory, which fail to bind once their path is too long.
+fn socket_paths(layout: &FilesystemLayout) -> Check {
+    const NAME: &str = "socket paths";
+    let sample = layout
+        .boxes_dir()
+        .join(BoxID::new().as_str())
+        .join("sockets")
+        .join("git-credential.sock");
+    let len = sample.as_os_str().len();
+    if len > MAX_SOCKET_PATH {
+        Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "box socket paths are {} bytes, over the {} byte limit; use a shorter home_dir",
+                len, MAX_SOCKET_PATH
+            ),
+        )
+    } else {
+        Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("{} of {} bytes", len, MAX_SOCKET_PATH),
+        )
+    }
+}
+
+/// Boot a box from `image` and time it; the box is removed afterwards.
+pub(crate) async fn bench(
+    runtime: &BoxliteRuntime,
+    layout: &FilesystemLayout,
+    image: &str,
+) -> BoxliteResult<BenchReport> {
+    let volume = tempfile::tempdir_in(layout.temp_dir())
+        .map_err(|e| BoxliteError::Storage(format!("Failed to create bench volume: {}", e)))?;
+    let options = BoxOptions {
+        rootfs: RootfsSpec::Image(image.to_string()),
+        volumes: vec![VolumeSpec {
+            host_path: volume.path().display().to_string(),
+            guest_path: "/bench".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let litebox = runtime.create(options, None)?;
+    let result = run_bench(&litebox, started).await;
+    if let Err(e) = litebox.stop().await {
+        tracing::warn!(box_id = %litebox.id(), "Failed to stop bench box: {}", e);
+    }
+    // Also covers a box that failed before auto_remove could apply
+    let _ = runtime.remove(litebox.id().as_str(), true).await;
+    result
+}
+
+async fn run_bench(litebox: &LiteBox, started: Instant) -> BoxliteResult<BenchReport> {
+    run_script(litebox, "true").await?;
+    let boot = started.elapsed();
+
+    let mut rounds = Vec::with_capacity(EXEC_ROUNDS);
+    for _ in 0..EXEC_ROUNDS {
+        let start = Instant::now();
+        run_script(litebox, "true").await?;
+        rounds.push(start.elapsed());
+    }
+    rounds.sort();
+
+    let write = run_script(
+        litebox,
+        &format!(
+            "dd if=/dev/zero of=/bench/data bs=1M count={} conv=fsync 2>/dev/null",
+            FS_BENCH_MIB
+        ),
+    )
+    .await?;
+    // Drop the guest's page cache so the read goes through virtio-fs
+    let read = run_script(
+        litebox,
+        "sync; echo 3 > /proc/sys/vm/drop_caches 2>/dev/null; \
+         dd if=/bench/data of=/dev/null bs=1M 2>/dev/null",
+    )
+    .await?;
+
+    Ok(BenchReport {
+        boot,
+        exec_round_trip: rounds[EXEC_ROUNDS / 2],
+        fs_write_mib_per_sec: FS_BENCH_MIB as f64 / write.as_secs_f64(),
+        fs_read_mib_per_sec: FS_BENCH_MIB as f64 / read.as_secs_f64(),
+    })
+}
+
+/// Run `script` with `sh -c`, returning how long it took in the guest.
+async fn run_script(litebox: &LiteBox, script: &str) -> BoxliteResult<Duration> {
+    let mut execution = litebox
+        .exec(BoxCommand::new("sh").args(["-c", script]))
+        .await?;
+    let result = execution.wait().await?;
+    if result.exit_code != 0 {
+        return Err(BoxliteError::Internal(format!(
+            "bench command `{}` exited with {}",
+            script, result.exit_code
+        )));
+    }
+    Ok(result.duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::layout::FsLayoutConfig;
+
+    #[test]
+    fn test_report_flags_long_socket_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let layout = FilesystemLayout::new(
+            dir.path().to_path_buf(),
+            FsLayoutConfig::without_bind_mount(),
+        );
+        layout.prepare().unwrap();
+        let report = check_host(&layout);
+        let home = report
+            .checks
+            .iter()
+            .find(|check| check.name == "home directory")
+            .unwrap();
+        assert_ne!(home.status, CheckStatus::Fail, "{}", home.detail);
+
+        let long = FilesystemLayout::new(
+            dir.path().join("x".repeat(100)),
+            FsLayoutConfig::without_bind_mount(),
+        );
+        assert_eq!(socket_paths(&long).status, CheckStatus::Fail);
+        assert!(report.to_string().contains("] socket paths: "));
+    }
+}
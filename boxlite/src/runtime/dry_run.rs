@@ -0,0 +1,252 @@
+//! Checking a box spec without creating the box.
+//!
+//! A dry run resolves a spec the way `create` and the first start would,
+//! with env files folded into `env` and the image reference pinned to a
+//! digest, then checks everything that can be checked from the host: the
+//! options themselves, the box name, the image, volume sources, host ports
+//! and the quota. Every problem is reported, not just the first, and
+//! nothing is created, pulled or booted.
+//!
+//! Ports are checked by binding them briefly, so one that is free during
+//! the dry run can still be taken before the box starts.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, TcpListener, UdpSocket};
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::runtime::options::{BoxOptions, PortProtocol, RootfsSpec};
+
+/// Outcome of a dry run.
+#[derive(Clone, Debug)]
+pub struct DryRunReport {
+    /// The spec as the box would get it.
+    pub spec: BoxOptions,
+    /// Manifest digest the image reference resolves to, when it does.
+    pub image_digest: Option<String>,
+    pub issues: Vec<SpecIssue>,
+}
+
+impl DryRunReport {
+    /// Whether the box would be created and started.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// The resolved spec as TOML, in the format `BoxOptions::from_spec_file`
+    /// reads.
+    pub fn spec_toml(&self) -> BoxliteResult<String> {
+        toml::to_string(&self.spec)
+            .map_err(|e| BoxliteError::Internal(format!("Failed to serialize spec: {}", e)))
+    }
+}
+
+/// The resolved spec followed by the outcome, as TOML comments.
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.spec_toml() {
+            Ok(spec) => f.write_str(&spec)?,
+            Err(e) => writeln!(f, "# {}", e)?,
+        }
+        if let Some(digest) = &self.image_digest {
+            writeln!(f, "# image digest: {}", digest)?;
+        }
+        if self.issues.is_empty() {
+            return writeln!(f, "# spec is valid");
+        }
+        for issue in &self.issues {
+            writeln!(f, "# error: {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// A problem found in a spec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecIssue {
+    /// Part of the spec at fault, e.g. `volumes[0]` or `quota`.
+    pub field: String,
+    pub message: String,
+}
+
+impl SpecIssue {
+    pub(crate) fn new(field: impl Into<String>, message: impl fmt::Display) -> Self {
+        Self {
+            field: field.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SpecIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl BoxOptions {
+    /// Read a box spec from a TOML file; missing keys take their defaults.
+    pub fn from_spec_file(path: &Path) -> BoxliteResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            BoxliteError::InvalidArgument(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        toml::from_str(&content).map_err(|e| {
+            BoxliteError::InvalidArgument(format!("Invalid spec {}: {}", path.display(), e))
+        })
+    }
+}
+
+/// Resolve what the box would be created with; problems go to `issues`.
+pub(crate) fn resolve(options: &BoxOptions, issues: &mut Vec<SpecIssue>) -> BoxOptions {
+    if let Err(e) = options.sanitize() {
+        issues.push(SpecIssue::new("options", e));
+    }
+    let mut spec = options.clone();
+    // An unreadable env file was reported by sanitize
+    if let Ok(env) = options.resolved_env() {
+        spec.env = env;
+        spec.env_files.clear();
+    }
+    spec
+}
+
+/// Check the parts of `spec` that live on the host.
+pub(crate) fn check_host(spec: &BoxOptions, issues: &mut Vec<SpecIssue>) {
+    if let RootfsSpec::RootfsPath(path) = &spec.rootfs
+        && !Path::new(path).is_dir()
+    {
+        issues.push(SpecIssue::new(
+            "rootfs",
+            format!("rootfs directory {} does not exist", path),
+        ));
+    }
+
+    for (i, volume) in spec.volumes.iter().enumerate() {
+        let field = format!("volumes[{}]", i);
+        if let Err(e) = std::fs::metadata(&volume.host_path) {
+            issues.push(SpecIssue::new(
+                &field,
+                format!("host path {}: {}", volume.host_path, e),
+            ));
+        }
+        if !volume.guest_path.starts_with('/') {
+            issues.push(SpecIssue::new(
+                field,
+                format!("guest path must be absolute, got '{}'", volume.guest_path),
+            ));
+        }
+    }
+
+    let mut claimed = HashSet::new();
+    for (i, port) in spec.ports.iter().enumerate() {
+        let field = format!("ports[{}]", i);
+        // 0 or unset is assigned when the box starts
+        let Some(host_port) = port.host_port.filter(|&p| p != 0) else {
+            continue;
+        };
+        let ip = match port.host_ip.as_deref().map(str::parse::<IpAddr>) {
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Some(Ok(ip)) => ip,
+            Some(Err(e)) => {
+                issues.push(SpecIssue::new(field, format!("host_ip: {}", e)));
+                continue;
+            }
+        };
+        if !claimed.insert((host_port, port.protocol)) {
+            issues.push(SpecIssue::new(
+                field,
+                format!("host port {} is mapped more than once", host_port),
+            ));
+            continue;
+        }
+        let bound = match port.protocol {
+            PortProtocol::Tcp => TcpListener::bind((ip, host_port)).map(drop),
+            PortProtocol::Udp => UdpSocket::bind((ip, host_port)).map(drop),
+        };
+        if let Err(e) = bound {
+            issues.push(SpecIssue::new(
+                field,
+                format!("host port {}:{} is not available: {}", ip, host_port, e),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::options::{PortSpec, VolumeSpec};
+
+    fn port(host_port: u16) -> PortSpec {
+        PortSpec {
+            host_port: Some(host_port),
+            guest_port: 80,
+            protocol: PortProtocol::Tcp,
+            host_ip: Some("127.0.0.1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_check_host_reports_every_problem() {
+        let dir = tempfile::tempdir().unwrap();
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+        let free_port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let options = BoxOptions {
+            volumes: vec![
+                VolumeSpec {
+                    host_path: dir.path().display().to_string(),
+                    guest_path: "/data".to_string(),
+                    ..Default::default()
+                },
+                VolumeSpec {
+                    host_path: dir.path().join("missing").display().to_string(),
+                    guest_path: "data".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ports: vec![port(taken_port), port(free_port), port(free_port)],
+            ..Default::default()
+        };
+        let mut issues = Vec::new();
+        check_host(&options, &mut issues);
+
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(fields, ["volumes[1]", "volumes[1]", "ports[0]", "ports[2]"]);
+        assert!(issues[2].message.contains("not available"));
+        assert!(issues[3].message.contains("more than once"));
+    }
+
+    #[test]
+    fn test_spec_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.toml");
+        std::fs::write(&path, "cpus = 2\nenv = [[\"A\", \"1\"]]\n").unwrap();
+        let options = BoxOptions::from_spec_file(&path).unwrap();
+        assert_eq!(options.cpus, Some(2));
+        assert_eq!(options.env, [("A".to_string(), "1".to_string())]);
+        assert!(!options.auto_remove);
+        assert!(options.volumes.is_empty());
+
+        let report = DryRunReport {
+            spec: options,
+            image_digest: None,
+            issues: Vec::new(),
+        };
+        std::fs::write(&path, report.spec_toml().unwrap()).unwrap();
+        let reread = BoxOptions::from_spec_file(&path).unwrap();
+        assert_eq!(reread.cpus, Some(2));
+        assert_eq!(reread.env, report.spec.env);
+        assert!(report.to_string().ends_with("# spec is valid\n"));
+
+        std::fs::write(&path, "cpus = \"two\"\n").unwrap();
+        assert!(BoxOptions::from_spec_file(&path).is_err());
+    }
+}
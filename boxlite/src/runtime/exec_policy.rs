@@ -0,0 +1,264 @@
+//! Exec rules for commands run in boxes.
+//!
+//! Operators set [`ExecRule`]s in `BoxliteOptions::exec_rules` or as
+//! `[[exec_rule]]` entries in the policy file. Every command a box is asked
+//! to run passes through them in order before it reaches the guest: deny
+//! rules refuse it, wrap rules prefix it (`nice`, `timeout`), and scrub rules
+//! rewrite its arguments. Clients need no changes to be held to them.
+//!
+//! The guest session applies them, so execs, scheduled tasks, setup steps,
+//! command probes and runtime provisioning are all covered; notebook cells
+//! are checked as `python3 -c <code>`. SSH sessions are not: sshd runs
+//! whatever its clients ask for.
+//!
+//! Rules match the command as given, not what it does. `sh -c 'pip ...'`,
+//! a copied or renamed binary, or a script passes a `deny_commands` rule
+//! for `pip`, so they steer cooperating clients and are no security
+//! boundary against hostile ones.
+
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::litebox::BoxCommand;
+use crate::runtime::options::{ExecAction, ExecRule};
+
+/// The exec rules of a runtime, with their patterns compiled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExecPolicy {
+    rules: Vec<(String, Action)>,
+}
+
+#[derive(Clone, Debug)]
+enum Action {
+    DenyCommands(Vec<String>),
+    DenyPattern(Regex),
+    Wrap(Vec<String>),
+    ScrubArgs(Regex, String),
+}
+
+/// The part of a policy file holding exec rules.
+#[derive(Deserialize)]
+struct ExecPolicyFile {
+    #[serde(default)]
+    exec_rule: Vec<ExecRule>,
+}
+
+impl ExecPolicy {
+    /// `rules` followed by those in `file`, if any.
+    pub(crate) fn load(mut rules: Vec<ExecRule>, file: Option<&Path>) -> BoxliteResult<Self> {
+        if let Some(path) = file {
+            let content = std::fs::read_to_string(path).map_err(|e| {
+                BoxliteError::Config(format!(
+                    "Failed to read policy file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let parsed: ExecPolicyFile = toml::from_str(&content).map_err(|e| {
+                BoxliteError::Config(format!("Invalid policy file {}: {}", path.display(), e))
+            })?;
+            rules.extend(parsed.exec_rule);
+        }
+        let rules = rules
+            .into_iter()
+            .map(|rule| Ok((rule.name.clone(), Action::compile(&rule)?)))
+            .collect::<BoxliteResult<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// `program` and `args` as the rules let them run, or the first rule
+    /// refusing them.
+    pub(crate) fn apply_program(
+        &self,
+        program: &str,
+        args: &[String],
+    ) -> BoxliteResult<(String, Vec<String>)> {
+        let command = self.apply(BoxCommand::new(program).args(args))?;
+        Ok((command.command, command.args))
+    }
+
+    /// `command` as the rules let it run, or the first rule refusing it.
+    pub(crate) fn apply(&self, mut command: BoxCommand) -> BoxliteResult<BoxCommand> {
+        for (name, action) in &self.rules {
+            let refuse = |reason: String| {
+                Err(BoxliteError::PolicyViolation(format!(
+                    "exec rule '{}': {}",
+                    name, reason
+                )))
+            };
+            match action {
+                Action::DenyCommands(commands) => {
+                    let program = program_name(&command.command);
+                    if commands.iter().any(|c| c == program) {
+                        return refuse(format!("command {} is not allowed", program));
+                    }
+                }
+                Action::DenyPattern(pattern) => {
+                    if pattern.is_match(&command_line(&command)) {
+                        return refuse(format!("command matches {}", pattern));
+                    }
+                }
+                Action::Wrap(prefix) => {
+                    let mut args = prefix[1..].to_vec();
+                    args.push(std::mem::replace(&mut command.command, prefix[0].clone()));
+                    args.append(&mut command.args);
+                    command.args = args;
+                }
+                Action::ScrubArgs(pattern, replacement) => {
+                    for arg in &mut command.args {
+                        if pattern.is_match(arg) {
+                            *arg = pattern.replace_all(arg, replacement.as_str()).into_owned();
+                        }
+                    }
+                }
+            }
+        }
+        Ok(command)
+    }
+}
+
+impl Action {
+    fn compile(rule: &ExecRule) -> BoxliteResult<Self> {
+        let regex = |pattern: &str| {
+            Regex::new(pattern).map_err(|e| {
+                BoxliteError::Config(format!(
+                    "exec rule '{}': invalid pattern {}: {}",
+                    rule.name, pattern, e
+                ))
+            })
+        };
+        Ok(match &rule.action {
+            ExecAction::DenyCommands { commands } => Action::DenyCommands(commands.clone()),
+            ExecAction::DenyPattern { pattern } => Action::DenyPattern(regex(pattern)?),
+            ExecAction::Wrap { prefix } if prefix.is_empty() => {
+                return Err(BoxliteError::Config(format!(
+                    "exec rule '{}': wrap prefix must not be empty",
+                    rule.name
+                )));
+            }
+            ExecAction::Wrap { prefix } => Action::Wrap(prefix.clone()),
+            ExecAction::ScrubArgs {
+                pattern,
+                replacement,
+            } => Action::ScrubArgs(regex(pattern)?, replacement.clone()),
+        })
+    }
+}
+
+/// File name of a program path: `/usr/bin/pip` is `pip`.
+fn program_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+fn command_line(command: &BoxCommand) -> String {
+    std::iter::once(command.command.as_str())
+        .chain(command.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, action: ExecAction) -> ExecRule {
+        ExecRule {
+            name: name.to_string(),
+            action,
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let policy = ExecPolicy::load(
+            vec![
+                rule(
+                    "no-pip",
+                    ExecAction::DenyCommands {
+                        commands: vec!["pip".to_string()],
+                    },
+                ),
+                rule(
+                    "no-rm-rf",
+                    ExecAction::DenyPattern {
+                        pattern: r"rm\s+-rf\s+/".to_string(),
+                    },
+                ),
+                rule(
+                    "tokens",
+                    ExecAction::ScrubArgs {
+                        pattern: "ghp_[A-Za-z0-9]+".to_string(),
+                        replacement: "***".to_string(),
+                    },
+                ),
+                rule(
+                    "nice",
+                    ExecAction::Wrap {
+                        prefix: vec!["nice".to_string(), "-n".to_string(), "10".to_string()],
+                    },
+                ),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let err = policy
+            .apply(BoxCommand::new("/usr/local/bin/pip").arg("install"))
+            .unwrap_err();
+        assert!(err.to_string().contains("exec rule 'no-pip'"));
+        let err = policy
+            .apply(BoxCommand::new("sh").args(["-c", "rm  -rf /"]))
+            .unwrap_err();
+        assert!(err.to_string().contains("exec rule 'no-rm-rf'"));
+
+        let command = policy
+            .apply(BoxCommand::new("git").args(["clone", "https://ghp_abc123@github.com/x"]))
+            .unwrap();
+        assert_eq!(command.command, "nice");
+        assert_eq!(
+            command.args,
+            ["-n", "10", "git", "clone", "https://***@github.com/x"]
+        );
+
+        // Task, setup step and probe commands pass through the same rules
+        let (program, args) = policy.apply_program("make", &["test".to_string()]).unwrap();
+        assert_eq!(program, "nice");
+        assert_eq!(args, ["-n", "10", "make", "test"]);
+        assert!(policy.apply_program("pip", &[]).is_err());
+    }
+
+    #[test]
+    fn test_policy_file_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("policy.toml");
+        std::fs::write(
+            &file,
+            r#"
+[[rule]]
+name = "no-devices"
+check = "no_devices"
+
+[[exec_rule]]
+name = "deadline"
+action = "wrap"
+prefix = ["timeout", "300"]
+"#,
+        )
+        .unwrap();
+        let policy = ExecPolicy::load(Vec::new(), Some(&file)).unwrap();
+        let command = policy.apply(BoxCommand::new("make")).unwrap();
+        assert_eq!(command.command, "timeout");
+        assert_eq!(command.args, ["300", "make"]);
+
+        let invalid = rule(
+            "bad",
+            ExecAction::DenyPattern {
+                pattern: "(".to_string(),
+            },
+        );
+        assert!(ExecPolicy::load(vec![invalid], None).is_err());
+    }
+}
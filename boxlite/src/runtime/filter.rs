@@ -0,0 +1,292 @@
+//! Box filters for listing and bulk operations.
+//!
+//! Labels are set on boxes (`BoxOptions::labels`) and on baked images
+//! (`BakeSpec::labels`). Volumes are host directories named in box options
+//! and registry images are shared by every box that pulls them, so neither
+//! carries labels of its own.
+//!
+//! Filters use Docker's `key=value` syntax:
+//! - `label=team` - box has label `team`
+//! - `label=team=ml` - box has label `team` with value `ml`
+//! - `name=web` - box is named `web`
+//! - `id=01HJ...` - box ID starts with the given prefix
+//! - `status=running` - box is in the given status
+//!
+//! Label filters must all match. Repeating any other key matches any of its
+//! values (`status=running status=stopped` matches both).
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::events::BoxEvent;
+use crate::runtime::bake::BakedImage;
+use crate::runtime::types::{BoxID, BoxInfo, BoxStatus};
+use crate::usage::ExecUsage;
+
+/// A set of conditions a box must satisfy.
+///
+/// An empty filter matches every box.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoxFilter {
+    labels: Vec<(String, Option<String>)>,
+    names: Vec<String>,
+    id_prefixes: Vec<String>,
+    statuses: Vec<BoxStatus>,
+}
+
+impl BoxFilter {
+    /// Create an empty filter that matches every box.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a list of `key=value` filter expressions.
+    pub fn parse<I, S>(filters: I) -> BoxliteResult<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut filter = Self::new();
+        for expr in filters {
+            let expr = expr.as_ref();
+            let (key, value) = expr.split_once('=').ok_or_else(|| {
+                BoxliteError::InvalidArgument(format!(
+                    "invalid filter '{}': expected key=value",
+                    expr
+                ))
+            })?;
+
+            filter = match key {
+                "label" => match value.split_once('=') {
+                    Some((k, v)) => filter.label(k, v),
+                    None => filter.label_exists(value),
+                },
+                "name" => filter.name(value),
+                "id" => filter.id_prefix(value),
+                "status" => filter.status(BoxStatus::from_str(value).map_err(|_| {
+                    BoxliteError::InvalidArgument(format!(
+                        "invalid filter '{}': unknown status '{}'",
+                        expr, value
+                    ))
+                })?),
+                _ => {
+                    return Err(BoxliteError::InvalidArgument(format!(
+                        "invalid filter '{}': unknown key '{}'",
+                        expr, key
+                    )));
+                }
+            };
+        }
+        Ok(filter)
+    }
+
+    /// Require a label with the given value.
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Require a label to be present, with any value.
+    pub fn label_exists(mut self, key: impl Into<String>) -> Self {
+        self.labels.push((key.into(), None));
+        self
+    }
+
+    /// Match boxes with the given name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.names.push(name.into());
+        self
+    }
+
+    /// Match boxes whose ID starts with the given prefix.
+    pub fn id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.id_prefixes.push(prefix.into());
+        self
+    }
+
+    /// Match boxes in the given status.
+    pub fn status(mut self, status: BoxStatus) -> Self {
+        self.statuses.push(status);
+        self
+    }
+
+    /// Check whether a box satisfies every condition.
+    pub fn matches(&self, info: &BoxInfo) -> bool {
+        self.matches_fields(
+            &info.id,
+            info.name.as_deref(),
+            &info.labels,
+            Some(info.status),
+        )
+    }
+
+    /// Check whether an event's box satisfies every condition.
+    ///
+    /// Events carry no status, so a filter with `status=` matches no events.
+    pub fn matches_event(&self, event: &BoxEvent) -> bool {
+        self.matches_fields(
+            &event.box_id,
+            event.box_name.as_deref(),
+            &event.labels,
+            None,
+        )
+    }
+
+    /// Check whether the box an execution ran in satisfies every condition.
+    ///
+    /// Like events, usage records carry no status.
+    pub fn matches_usage(&self, usage: &ExecUsage) -> bool {
+        self.matches_fields(
+            &usage.box_id,
+            usage.box_name.as_deref(),
+            &usage.labels,
+            None,
+        )
+    }
+
+    /// Check whether a baked image satisfies every condition.
+    ///
+    /// `name=` matches the image's tag. Images have no ID or status, so a
+    /// filter with `id=` or `status=` matches no images.
+    pub fn matches_image(&self, image: &BakedImage) -> bool {
+        self.labels_match(&image.labels)
+            && (self.names.is_empty() || self.names.contains(&image.tag))
+            && self.id_prefixes.is_empty()
+            && self.statuses.is_empty()
+    }
+
+    fn labels_match(&self, labels: &HashMap<String, String>) -> bool {
+        self.labels
+            .iter()
+            .all(|(key, expected)| match (labels.get(key), expected) {
+                (Some(actual), Some(expected)) => actual == expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+    }
+
+    fn matches_fields(
+        &self,
+        id: &BoxID,
+        name: Option<&str>,
+        labels: &HashMap<String, String>,
+        status: Option<BoxStatus>,
+    ) -> bool {
+        let labels_match = self.labels_match(labels);
+        let name_match =
+            self.names.is_empty() || name.is_some_and(|name| self.names.iter().any(|n| n == name));
+        let id_match = self.id_prefixes.is_empty()
+            || self
+                .id_prefixes
+                .iter()
+                .any(|prefix| id.as_str().starts_with(prefix.as_str()));
+        let status_match = self.statuses.is_empty()
+            || status.is_some_and(|status| self.statuses.contains(&status));
+
+        labels_match && name_match && id_match && status_match
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::litebox::config::{BoxConfig, ContainerRuntimeConfig};
+    use crate::runtime::options::BoxOptions;
+    use crate::runtime::types::{BoxID, BoxState, ContainerID};
+    use boxlite_shared::Transport;
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    fn info(name: &str, status: BoxStatus, labels: &[(&str, &str)]) -> BoxInfo {
+        let config = BoxConfig {
+            id: BoxID::new(),
+            name: Some(name.to_string()),
+            created_at: Utc::now(),
+            container: ContainerRuntimeConfig {
+                id: ContainerID::new(),
+            },
+            options: BoxOptions {
+                labels: labels
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<_, _>>(),
+                ..Default::default()
+            },
+            engine_kind: crate::vmm::VmmKind::Libkrun,
+            transport: Transport::unix(PathBuf::from("/tmp/boxlite.sock")),
+            box_home: PathBuf::from("/tmp/box"),
+            ready_socket_path: PathBuf::from("/tmp/ready.sock"),
+        };
+        let mut state = BoxState::new();
+        state.set_status(status);
+        BoxInfo::new(&config, &state)
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = BoxFilter::parse(Vec::<String>::new()).unwrap();
+        assert!(filter.matches(&info("a", BoxStatus::Running, &[])));
+    }
+
+    #[test]
+    fn test_label_filters_must_all_match() {
+        let filter = BoxFilter::parse(["label=team=ml", "label=env"]).unwrap();
+
+        assert!(filter.matches(&info(
+            "a",
+            BoxStatus::Running,
+            &[("team", "ml"), ("env", "ci")]
+        )));
+        assert!(!filter.matches(&info("b", BoxStatus::Running, &[("team", "ml")])));
+        assert!(!filter.matches(&info(
+            "c",
+            BoxStatus::Running,
+            &[("team", "web"), ("env", "ci")]
+        )));
+    }
+
+    #[test]
+    fn test_repeated_keys_match_any_value() {
+        let filter = BoxFilter::parse(["status=running", "status=stopped", "name=a"]).unwrap();
+
+        assert!(filter.matches(&info("a", BoxStatus::Stopped, &[])));
+        assert!(!filter.matches(&info("a", BoxStatus::Starting, &[])));
+        assert!(!filter.matches(&info("b", BoxStatus::Running, &[])));
+    }
+
+    #[test]
+    fn test_id_prefix() {
+        let target = info("a", BoxStatus::Running, &[]);
+        let prefix = &target.id.as_str()[..8];
+
+        assert!(BoxFilter::new().id_prefix(prefix).matches(&target));
+        assert!(!BoxFilter::new().id_prefix("zzzz").matches(&target));
+    }
+
+    #[test]
+    fn test_image_filters() {
+        let image = BakedImage {
+            tag: "myorg/sandbox".to_string(),
+            base_image: "python:3.12-slim".to_string(),
+            env: Vec::new(),
+            labels: [("team".to_string(), "ml".to_string())].into(),
+            created_at: Utc::now(),
+        };
+
+        let matches = |expr: &str| BoxFilter::parse([expr]).unwrap().matches_image(&image);
+
+        assert!(matches("label=team=ml"));
+        assert!(matches("name=myorg/sandbox"));
+        assert!(!matches("label=team=web"));
+        assert!(!matches("status=running"));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_filters() {
+        assert!(BoxFilter::parse(["label"]).is_err());
+        assert!(BoxFilter::parse(["color=red"]).is_err());
+        assert!(BoxFilter::parse(["status=sleeping"]).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Host hooks run at box lifecycle points.
+//!
+//! Hooks registered in [`BoxliteOptions::hooks`](crate::BoxliteOptions)
+//! run in order, each to completion before the next, so a `pre_create`
+//! hook sees the options as rewritten by the ones before it. They are for
+//! site-specific setup the runtime has no opinion on: fetching secrets into
+//! the box environment, wiring host networking once a box is up, refusing
+//! specs that break local policy. See [`HookOptions`] for the protocol.
+//!
+//! Hooks are plain executables. A WASM plugin runs as one by registering
+//! its runtime (e.g. `wasmtime`) as the path and the module as an argument.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::Serialize;
+
+use crate::events::EventKind;
+use crate::litebox::config::BoxConfig;
+use crate::runtime::options::{BoxOptions, HookOptions, HookStage};
+use crate::runtime::types::BoxID;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The document a hook reads from stdin.
+#[derive(Serialize)]
+struct HookInput<'a> {
+    stage: HookStage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    box_id: Option<&'a BoxID>,
+    name: Option<&'a str>,
+    options: &'a BoxOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+}
+
+/// The hooks of a runtime.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Hooks {
+    hooks: Vec<HookOptions>,
+}
+
+impl Hooks {
+    pub(crate) fn new(hooks: Vec<HookOptions>) -> Self {
+        Self { hooks }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Options for a new box named `name`, as the hooks rewrote them.
+    pub(crate) fn pre_create(
+        &self,
+        mut options: BoxOptions,
+        name: Option<&str>,
+    ) -> BoxliteResult<BoxOptions> {
+        for hook in self.at(HookStage::PreCreate) {
+            let input = HookInput {
+                stage: HookStage::PreCreate,
+                box_id: None,
+                name,
+                options: &options,
+                reason: None,
+            };
+            let output = run(hook, &input)?;
+            if output.iter().any(|b| !b.is_ascii_whitespace()) {
+                options = serde_json::from_slice(&output).map_err(|e| {
+                    BoxliteError::InvalidArgument(format!(
+                        "hook {} returned invalid box options: {}",
+                        hook.path.display(),
+                        e
+                    ))
+                })?;
+            }
+        }
+        Ok(options)
+    }
+
+    /// Run the `post_ready` hooks for a box that just booted.
+    pub(crate) fn post_ready(&self, config: &BoxConfig) -> BoxliteResult<()> {
+        self.notify(HookStage::PostReady, config, None)
+    }
+
+    /// Run the `pre_destroy` hooks for a box about to be removed as `kind`.
+    pub(crate) fn pre_destroy(&self, config: &BoxConfig, kind: EventKind) -> BoxliteResult<()> {
+        self.notify(HookStage::PreDestroy, config, Some(kind.as_str()))
+    }
+
+    fn notify(
+        &self,
+        stage: HookStage,
+        config: &BoxConfig,
+        reason: Option<&'static str>,
+    ) -> BoxliteResult<()> {
+        for hook in self.at(stage) {
+            let input = HookInput {
+                stage,
+                box_id: Some(&config.id),
+                name: config.name.as_deref(),
+                options: &config.options,
+                reason,
+            };
+            run(hook, &input)?;
+        }
+        Ok(())
+    }
+
+    fn at(&self, stage: HookStage) -> impl Iterator<Item = &HookOptions> {
+        self.hooks.iter().filter(move |hook| hook.runs_at(stage))
+    }
+}
+
+/// Run `hook` with `input` on stdin, returning its stdout.
+fn run(hook: &HookOptions, input: &HookInput) -> BoxliteResult<Vec<u8>> {
+    let stage = input.stage.as_str();
+    let failed = |reason: String| {
+        BoxliteError::InvalidState(format!(
+            "{} hook {} failed: {}",
+            stage,
+            hook.path.display(),
+            reason
+        ))
+    };
+    let document = serde_json::to_vec(input)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode hook input: {}", e)))?;
+
+    let mut child = Command::new(&hook.path)
+        .args(&hook.args)
+        .env("BOXLITE_HOOK_STAGE", stage)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+
+    // Pipes are drained on threads so a chatty hook cannot block on a full one
+    let mut stdin = child.stdin.take();
+    let writer = thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            // A hook that ignores its input may close stdin early
+            let _ = stdin.write_all(&document);
+        }
+    });
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = Instant::now() + Duration::from_secs(hook.timeout_secs);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break Err(format!("timed out after {}s", hook.timeout_secs));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => break Err(e.to_string()),
+        }
+    };
+    // Processes the hook left behind may hold the pipes open; past the
+    // timeout nothing is waited on any more
+    let status = status.map_err(failed)?;
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        let reason = match stderr.trim() {
+            "" => status.to_string(),
+            message => message.to_string(),
+        };
+        return Err(failed(reason));
+    }
+    tracing::debug!(hook = %hook.path.display(), stage, "Hook succeeded");
+    Ok(stdout)
+}
+
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn script(dir: &std::path::Path, name: &str, body: &str) -> HookOptions {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        HookOptions::new(path)
+    }
+
+    #[test]
+    fn test_pre_create_rewrites_and_vetoes() {
+        let dir = tempfile::tempdir().unwrap();
+        // Sees the stage and the spec, and caps the memory
+        let cap = script(
+            dir.path(),
+            "cap.sh",
+            r#"grep -q '"stage":"pre_create"' || exit 1
+[ "$BOXLITE_HOOK_STAGE" = pre_create ] || exit 1
+echo '{"memory_mib": 512}'"#,
+        );
+        let mut later = script(dir.path(), "later.sh", "echo 'not now' >&2; exit 3");
+        later.stages = vec![HookStage::PreDestroy];
+
+        let hooks = Hooks::new(vec![cap.clone(), later]);
+        let options = BoxOptions {
+            memory_mib: Some(4096),
+            ..Default::default()
+        };
+        let rewritten = hooks.pre_create(options, Some("web")).unwrap();
+        assert_eq!(rewritten.memory_mib, Some(512));
+
+        let deny = script(
+            dir.path(),
+            "deny.sh",
+            "echo 'registry not allowed' >&2; exit 1",
+        );
+        let hooks = Hooks::new(vec![cap, deny]);
+        let err = hooks.pre_create(BoxOptions::default(), None).unwrap_err();
+        assert!(err.to_string().contains("registry not allowed"));
+    }
+
+    #[test]
+    fn test_hook_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut slow = script(dir.path(), "slow.sh", "sleep 5");
+        slow.timeout_secs = 0;
+        let err = Hooks::new(vec![slow])
+            .pre_create(BoxOptions::default(), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+}
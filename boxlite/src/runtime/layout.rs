@@ -41,6 +41,15 @@ pub mod dirs {
 
     /// Subdirectory for per-entity locks
     pub const LOCKS_DIR: &str = "locks";
+
+    /// Subdirectory for content-addressed snapshot layers
+    pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+    /// Subdirectory for host-managed caches
+    pub const CACHE_DIR: &str = "cache";
+
+    /// Subdirectory for named volumes
+    pub const VOLUMES_DIR: &str = "volumes";
 }
 
 /// Configuration for filesystem layout behavior.
@@ -107,6 +116,11 @@ impl FilesystemLayout {
         self.home_dir.join(dirs::LOGS_DIR)
     }
 
+    /// Diagnostics bundles of failed boxes: ~/.boxlite/logs/diagnostics
+    pub fn diagnostics_dir(&self) -> PathBuf {
+        self.logs_dir().join("diagnostics")
+    }
+
     /// OCI images layers storage: ~/.boxlite/images/layers
     pub fn image_layers_dir(&self) -> PathBuf {
         self.images_dir().join(dirs::LAYERS_DIR)
@@ -131,6 +145,45 @@ impl FilesystemLayout {
         self.home_dir.join(dirs::LOCKS_DIR)
     }
 
+    /// Snapshot layer storage: ~/.boxlite/snapshots
+    ///
+    /// Holds frozen qcow2 layers named by content digest. Layers are shared
+    /// by every snapshot (and clone) that references them.
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.home_dir.join(dirs::SNAPSHOTS_DIR)
+    }
+
+    /// Shared package manager caches: ~/.boxlite/cache/packages/{domain}
+    ///
+    /// One cache per `package_cache_domain`, mounted into one box of the
+    /// domain at a time.
+    pub fn package_cache_dir(&self) -> PathBuf {
+        self.home_dir.join(dirs::CACHE_DIR).join("packages")
+    }
+
+    /// Rootfs readahead hints: ~/.boxlite/cache/readahead
+    ///
+    /// One file per base disk, recorded on the first boot from it.
+    pub fn readahead_dir(&self) -> PathBuf {
+        self.home_dir.join(dirs::CACHE_DIR).join("readahead")
+    }
+
+    /// Named volumes: ~/.boxlite/volumes
+    ///
+    /// One directory per volume, holding its encrypted disk image. Volumes
+    /// outlive the boxes they are attached to.
+    pub fn volumes_dir(&self) -> PathBuf {
+        self.home_dir.join(dirs::VOLUMES_DIR)
+    }
+
+    /// Ingress proxy state: ~/.boxlite/ingress
+    ///
+    /// Holds the local CA (`ca.pem`, `ca-key.pem`) that ingress
+    /// certificates are issued from.
+    pub fn ingress_dir(&self) -> PathBuf {
+        self.home_dir.join("ingress")
+    }
+
     /// Temporary directory for transient files: ~/.boxlite/tmp
     /// Used for disk image creation and other operations that need
     /// temp files on the same filesystem as the final destination.
@@ -151,6 +204,9 @@ impl FilesystemLayout {
         std::fs::create_dir_all(self.temp_dir())
             .map_err(|e| BoxliteError::Storage(format!("failed to create temp dir: {e}")))?;
 
+        std::fs::create_dir_all(self.snapshots_dir())
+            .map_err(|e| BoxliteError::Storage(format!("failed to create snapshots dir: {e}")))?;
+
         std::fs::create_dir_all(self.image_layers_dir())
             .map_err(|e| BoxliteError::Storage(format!("failed to create layers dir: {e}")))?;
 
@@ -274,6 +330,49 @@ impl BoxFilesystemLayout {
         self.sockets_dir().join("ready.sock")
     }
 
+    /// Unix socket bridged to the guest's bulk transfer port.
+    ///
+    /// Path: ~/.boxlite/boxes/{box_id}/sockets/bulk.sock
+    pub fn bulk_socket_path(&self) -> PathBuf {
+        self.sockets_dir().join("bulk.sock")
+    }
+
+    /// Unix socket the guest's git credential helper reaches the host on.
+    ///
+    /// Path: ~/.boxlite/boxes/{box_id}/sockets/git-credential.sock
+    pub fn git_credential_socket_path(&self) -> PathBuf {
+        self.sockets_dir().join("git-credential.sock")
+    }
+
+    /// Unix socket the guest's lock helper reaches the host lock broker on.
+    ///
+    /// Path: ~/.boxlite/boxes/{box_id}/sockets/lock.sock
+    pub fn lock_socket_path(&self) -> PathBuf {
+        self.sockets_dir().join("lock.sock")
+    }
+
+    /// Unix socket the shim serves packet captures of the box network on.
+    ///
+    /// Path: ~/.boxlite/boxes/{box_id}/sockets/capture.sock
+    pub fn capture_socket_path(&self) -> PathBuf {
+        self.sockets_dir().join("capture.sock")
+    }
+
+    /// Unix socket the shim takes firewall changes for the box network on.
+    ///
+    /// Path: ~/.boxlite/boxes/{box_id}/sockets/firewall.sock
+    pub fn firewall_socket_path(&self) -> PathBuf {
+        self.sockets_dir().join("firewall.sock")
+    }
+
+    /// Unix socket the shim takes the host ports the box may reach through
+    /// the host alias on.
+    ///
+    /// Path: ~/.boxlite/boxes/{box_id}/sockets/host-ports.sock
+    pub fn host_ports_socket_path(&self) -> PathBuf {
+        self.sockets_dir().join("host-ports.sock")
+    }
+
     // ========================================================================
     // MOUNTS AND SHARED
     // ========================================================================
@@ -316,6 +415,47 @@ impl BoxFilesystemLayout {
         self.box_dir.join(shared_dirs::SHARED)
     }
 
+    /// Object volume directory: ~/.boxlite/boxes/{box_id}/objects/{index}
+    ///
+    /// Holds the volume's read-through cache and the FUSE mount shared
+    /// with the guest.
+    pub fn object_volume_dir(&self, index: usize) -> PathBuf {
+        self.box_dir.join("objects").join(index.to_string())
+    }
+
+    /// x86_64 interpreter shared with the guest: ~/.boxlite/boxes/{box_id}/x86
+    pub fn x86_emulator_dir(&self) -> PathBuf {
+        self.box_dir.join("x86")
+    }
+
+    /// Encrypted volume mount point: ~/.boxlite/boxes/{box_id}/encrypted/{index}
+    ///
+    /// The FUSE mount presenting the decrypted disk image to the VMM.
+    pub fn encrypted_volume_dir(&self, index: usize) -> PathBuf {
+        self.box_dir.join("encrypted").join(index.to_string())
+    }
+
+    /// Quota volume mount point: ~/.boxlite/boxes/{box_id}/quota/{index}
+    ///
+    /// The FUSE mount over a user volume with a quota, shared with the
+    /// guest in place of the volume's directory.
+    pub fn quota_volume_dir(&self, index: usize) -> PathBuf {
+        self.box_dir.join("quota").join(index.to_string())
+    }
+
+    /// Package cache of a box without a domain: ~/.boxlite/boxes/{box_id}/packages
+    pub fn package_cache_dir(&self) -> PathBuf {
+        self.box_dir.join("packages")
+    }
+
+    /// Imported volume contents: ~/.boxlite/boxes/{box_id}/volumes/{index}
+    ///
+    /// Where an imported bundle's volume directories are unpacked; removed
+    /// with the box.
+    pub fn imported_volume_dir(&self, index: usize) -> PathBuf {
+        self.box_dir.join(dirs::VOLUMES_DIR).join(index.to_string())
+    }
+
     // ========================================================================
     // DISK AND CONSOLE
     // ========================================================================
@@ -325,6 +465,21 @@ impl BoxFilesystemLayout {
         self.box_dir.join("disk.qcow2")
     }
 
+    /// Guest rootfs COW disk path: ~/.boxlite/boxes/{box_id}/guest-rootfs.qcow2
+    pub fn guest_rootfs_disk_path(&self) -> PathBuf {
+        self.box_dir.join("guest-rootfs.qcow2")
+    }
+
+    /// Forwarded ports of the running VM: ~/.boxlite/boxes/{box_id}/ports.json
+    pub fn ports_path(&self) -> PathBuf {
+        self.box_dir.join("ports.json")
+    }
+
+    /// Firewall of the running VM: ~/.boxlite/boxes/{box_id}/firewall.json
+    pub fn firewall_path(&self) -> PathBuf {
+        self.box_dir.join("firewall.json")
+    }
+
     /// Console output path: ~/.boxlite/boxes/{box_id}/console.log
     ///
     /// Captures kernel and init output for debugging.
@@ -30,13 +30,13 @@ impl RuntimeLock {
     /// * `Err(...)` - Another runtime is already using this directory
     ///
     /// # Example
-    /// ```rust,no_run
-    /// use boxlite_runtime::lock::RuntimeLock;
+    /// ```ignore
+    /// use boxlite::runtime::lock::RuntimeLock;
     /// use std::path::PathBuf;
     ///
     /// let lock = RuntimeLock::acquire(&PathBuf::from("/tmp/test"))?;
     /// // Lock is held until `lock` is dropped
-    /// # Ok::<(), boxlite_runtime::errors::BoxliteError>(())
+    /// # Ok::<(), boxlite_shared::errors::BoxliteError>(())
     /// ```
     pub fn acquire(home_dir: &Path) -> BoxliteResult<Self> {
         // Ensure the directory exists
@@ -0,0 +1,204 @@
+//! Lockfiles pinning a box spec for reproducible runs.
+//!
+//! A [`BoxLock`] records what a spec resolved to when it was locked: the
+//! manifest digest of its image, the digest of the image the guest rootfs
+//! is built from, the engine version (which fixes the guest kernel and
+//! agent), and a digest of the resolved spec itself. The spec digest covers
+//! the provisioning steps — runtimes, scheduled tasks, env and env files —
+//! so any change to them shows up as drift.
+//!
+//! A box created from a lock starts from the locked image digest whatever
+//! its tag points at now, and is refused if anything else has drifted.
+
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::runtime::options::{BoxOptions, RootfsSpec, RuntimeProfile};
+
+/// Bumped whenever the lockfile format changes.
+const LOCK_VERSION: u32 = 1;
+
+/// What a box spec resolved to when it was locked.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoxLock {
+    pub version: u32,
+    /// Version of the engine that locked the spec, which ships the guest
+    /// kernel and agent.
+    pub engine: String,
+    /// Digest of the resolved spec, env files folded in.
+    pub spec_digest: String,
+    /// Runtimes the box is provisioned with on first boot.
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeProfile>,
+    /// The box image, when the rootfs is an image reference.
+    #[serde(default)]
+    pub image: Option<LockedImage>,
+    /// The image the guest rootfs is built from.
+    pub guest_image: LockedImage,
+}
+
+/// An image reference and the manifest digest it resolved to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedImage {
+    pub reference: String,
+    pub digest: String,
+}
+
+impl BoxLock {
+    pub(crate) fn new(
+        spec: &BoxOptions,
+        image: Option<LockedImage>,
+        guest_image: LockedImage,
+    ) -> BoxliteResult<Self> {
+        Ok(Self {
+            version: LOCK_VERSION,
+            engine: env!("CARGO_PKG_VERSION").to_string(),
+            spec_digest: spec_digest(spec)?,
+            runtimes: spec.runtimes.clone(),
+            image,
+            guest_image,
+        })
+    }
+
+    /// Where the lock for the spec file `spec` is kept: next to it, with a
+    /// `.lock` extension.
+    pub fn path_for(spec: &Path) -> PathBuf {
+        spec.with_extension("lock")
+    }
+
+    /// Read a lock written by [`BoxLock::write`].
+    pub fn read(path: &Path) -> BoxliteResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            BoxliteError::InvalidArgument(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let lock: Self = toml::from_str(&content).map_err(|e| {
+            BoxliteError::InvalidArgument(format!("Invalid lockfile {}: {}", path.display(), e))
+        })?;
+        if lock.version == 0 || lock.version > LOCK_VERSION {
+            return Err(BoxliteError::InvalidArgument(format!(
+                "Unsupported lockfile version {} (this build reads up to {})",
+                lock.version, LOCK_VERSION
+            )));
+        }
+        Ok(lock)
+    }
+
+    /// Write the lock as TOML.
+    pub fn write(&self, path: &Path) -> BoxliteResult<()> {
+        let content = toml::to_string(self)
+            .map_err(|e| BoxliteError::Internal(format!("Failed to serialize lock: {}", e)))?;
+        std::fs::write(path, content).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to write {}: {}", path.display(), e))
+        })
+    }
+
+    /// Refuse a spec that no longer matches the lock.
+    ///
+    /// `spec` is the resolved spec and `guest_digest` what the guest image
+    /// resolves to now. Every difference is reported.
+    pub(crate) fn check(&self, spec: &BoxOptions, guest_digest: &str) -> BoxliteResult<()> {
+        let mut drift = Vec::new();
+        let engine = env!("CARGO_PKG_VERSION");
+        if self.engine != engine {
+            drift.push(format!("engine is {}, locked at {}", engine, self.engine));
+        }
+        if self.spec_digest != spec_digest(spec)? {
+            drift.push("spec changed since it was locked".to_string());
+        }
+        if self.guest_image.digest != guest_digest {
+            drift.push(format!(
+                "guest image {} is {}, locked at {}",
+                self.guest_image.reference, guest_digest, self.guest_image.digest
+            ));
+        }
+        if drift.is_empty() {
+            return Ok(());
+        }
+        Err(BoxliteError::InvalidArgument(format!(
+            "Box spec drifted from its lock: {}",
+            drift.join("; ")
+        )))
+    }
+
+    /// `options` with its image reference pinned to the locked digest.
+    pub(crate) fn pin(&self, mut options: BoxOptions) -> BoxOptions {
+        if let (RootfsSpec::Image(reference), Some(image)) = (&mut options.rootfs, &self.image)
+            && !reference.contains('@')
+        {
+            *reference = format!("{}@{}", reference, image.digest);
+        }
+        options
+    }
+}
+
+/// Digest of a resolved spec; JSON objects keep their keys sorted, so this
+/// does not depend on the order of map entries.
+fn spec_digest(spec: &BoxOptions) -> BoxliteResult<String> {
+    let value = serde_json::to_value(spec)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to serialize spec: {}", e)))?;
+    Ok(format!(
+        "sha256:{}",
+        hex::encode(Sha256::digest(value.to_string().as_bytes()))
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked(spec: &BoxOptions) -> BoxLock {
+        BoxLock::new(
+            spec,
+            Some(LockedImage {
+                reference: "python:3.12".to_string(),
+                digest: "sha256:aaaa".to_string(),
+            }),
+            LockedImage {
+                reference: "debian:bookworm-slim".to_string(),
+                digest: "sha256:bbbb".to_string(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_lock_round_trip_and_drift() {
+        let mut spec = BoxOptions {
+            rootfs: RootfsSpec::Image("python:3.12".to_string()),
+            runtimes: vec![RuntimeProfile::Node],
+            ..Default::default()
+        };
+        spec.labels.insert("team".to_string(), "ml".to_string());
+        spec.labels.insert("ci".to_string(), "yes".to_string());
+        let lock = locked(&spec);
+
+        let dir = tempfile::tempdir().unwrap();
+        let spec_path = dir.path().join("spec.toml");
+        let path = BoxLock::path_for(&spec_path);
+        assert_eq!(path, dir.path().join("spec.lock"));
+        lock.write(&path).unwrap();
+        assert_eq!(BoxLock::read(&path).unwrap(), lock);
+
+        lock.check(&spec, "sha256:bbbb").unwrap();
+        let err = lock.check(&spec, "sha256:cccc").unwrap_err().to_string();
+        assert!(err.contains("guest image"), "{}", err);
+
+        let mut changed = spec.clone();
+        changed.runtimes.push(RuntimeProfile::Go);
+        let err = lock.check(&changed, "sha256:bbbb").unwrap_err().to_string();
+        assert!(err.contains("spec changed"), "{}", err);
+
+        let pinned = lock.pin(spec);
+        assert!(
+            matches!(&pinned.rootfs, RootfsSpec::Image(r) if r == "python:3.12@sha256:aaaa"),
+            "{:?}",
+            pinned.rootfs
+        );
+        assert!(
+            matches!(lock.pin(pinned).rootfs, RootfsSpec::Image(r) if r == "python:3.12@sha256:aaaa")
+        );
+    }
+}
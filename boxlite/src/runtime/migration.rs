@@ -0,0 +1,715 @@
+//! Moving stopped boxes between hosts.
+//!
+//! [`export`] writes a box as a zstd-compressed tar bundle: a `box.json`
+//! manifest with its name and options, followed by its disks together with
+//! every image they are backed by (snapshot layers, the image base disk,
+//! the guest rootfs base), so the bundle does not depend on anything else
+//! on the source host. [`import`] unpacks a bundle into a new box directory
+//! and points each disk at its unpacked backing file. The bundle is a plain
+//! stream, so it can be piped over ssh to drain a host, handed to a
+//! teammate, or attached to a bug report.
+//!
+//! With [`ExportOptions`] a bundle can instead hold the box as of one of its
+//! snapshots, and can carry the contents of its host directory volumes,
+//! which are unpacked into the imported box's directory.
+//!
+//! Only disk state travels. A running VM's memory and device state cannot
+//! be saved by the engine, so a box has to be stopped to be moved, and
+//! snapshot history stays behind.
+//!
+//! A bundle may come from anyone, so importing one never reaches past the
+//! new box's directory: [`imported_options`] carries over only options
+//! that stay inside the box and refuses bundles that need host resources,
+//! and every disk must be backed by exactly the bundled file its manifest
+//! names.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use serde::{Deserialize, Serialize};
+
+use crate::disk::Qcow2Helper;
+use crate::runtime::layout::BoxFilesystemLayout;
+use crate::runtime::options::{BoxOptions, RootfsSpec};
+use crate::runtime::types::BoxID;
+
+/// Bumped whenever the bundle layout changes; bundles of any earlier
+/// version can still be imported.
+const BUNDLE_VERSION: u32 = 2;
+
+const MANIFEST: &str = "box.json";
+const DISKS_DIR: &str = "disks";
+const VOLUMES_DIR: &str = "volumes";
+
+/// What goes into a bundle besides the box's options and disks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Bundle the contents of the box's host directory volumes.
+    ///
+    /// The imported box mounts its own copy of each instead of a directory
+    /// at the same host path.
+    pub include_volumes: bool,
+    /// Export the disks as of this snapshot (ID or name) rather than their
+    /// current state.
+    pub snapshot: Option<String>,
+}
+
+/// What a bundle holds, stored first in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    pub version: u32,
+    /// The box on the host it was exported from.
+    pub source_id: BoxID,
+    pub name: Option<String>,
+    pub options: BoxOptions,
+    pub disks: Vec<BundledDisk>,
+    /// Indices into `options.volumes` whose contents are bundled.
+    #[serde(default)]
+    pub volumes: Vec<usize>,
+    /// Name of the snapshot the disks were exported from, if any.
+    #[serde(default)]
+    pub snapshot: Option<String>,
+}
+
+/// A disk file in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct BundledDisk {
+    /// File name in the bundle and in the imported box directory.
+    pub file: String,
+    /// File name of the disk backing this one, if any.
+    pub backing: Option<String>,
+}
+
+/// Write the stopped box `source_id` as a bundle to `writer`.
+///
+/// `roots` are the disks to export with the file name each gets in the
+/// imported box; see [`box_disks`]. `snapshot` names the snapshot they
+/// come from, if any.
+pub(crate) fn export<W: Write>(
+    source_id: &BoxID,
+    name: Option<String>,
+    options: &BoxOptions,
+    roots: Vec<(PathBuf, String)>,
+    include_volumes: bool,
+    snapshot: Option<String>,
+    writer: W,
+) -> BoxliteResult<()> {
+    let sources = collect_disks(roots)?;
+    let volumes: Vec<usize> = if include_volumes {
+        (0..options.volumes.len()).collect()
+    } else {
+        Vec::new()
+    };
+    for &index in &volumes {
+        let path = Path::new(&options.volumes[index].host_path);
+        if !path.is_dir() {
+            return Err(BoxliteError::Storage(format!(
+                "cannot export: volume directory {} not found",
+                path.display()
+            )));
+        }
+    }
+    let manifest = Manifest {
+        version: BUNDLE_VERSION,
+        source_id: source_id.clone(),
+        name,
+        options: options.clone(),
+        disks: sources.iter().map(|(disk, _)| disk.clone()).collect(),
+        volumes,
+        snapshot,
+    };
+
+    let fail = |e: std::io::Error| BoxliteError::Storage(format!("Failed to write bundle: {}", e));
+    let encoder = zstd::Encoder::new(writer, 0).map_err(fail)?;
+    let mut builder = tar::Builder::new(encoder);
+    // Volume contents keep their symlinks as links
+    builder.follow_symlinks(false);
+
+    let content = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to encode bundle manifest: {}", e)))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, MANIFEST, content.as_slice())
+        .map_err(fail)?;
+
+    for (disk, path) in &sources {
+        builder
+            .append_path_with_name(path, Path::new(DISKS_DIR).join(&disk.file))
+            .map_err(|e| {
+                BoxliteError::Storage(format!("Failed to bundle {}: {}", path.display(), e))
+            })?;
+    }
+    for &index in &manifest.volumes {
+        let path = &options.volumes[index].host_path;
+        builder
+            .append_dir_all(Path::new(VOLUMES_DIR).join(index.to_string()), path)
+            .map_err(|e| BoxliteError::Storage(format!("Failed to bundle {}: {}", path, e)))?;
+    }
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut writer| writer.flush())
+        .map_err(fail)
+}
+
+/// The box's current disks, as export roots.
+pub(crate) fn box_disks(layout: &BoxFilesystemLayout) -> BoxliteResult<Vec<(PathBuf, String)>> {
+    let disk = layout.disk_path();
+    if !disk.exists() {
+        return Err(BoxliteError::Storage(format!(
+            "cannot export: container rootfs disk not found at {}",
+            disk.display()
+        )));
+    }
+    let mut roots = vec![(disk.clone(), file_name(&disk)?)];
+    let guest_disk = layout.guest_rootfs_disk_path();
+    if guest_disk.exists() {
+        roots.push((guest_disk.clone(), file_name(&guest_disk)?));
+    }
+    Ok(roots)
+}
+
+/// A snapshot's layers as export roots, each standing in for the live disk
+/// it was frozen from.
+pub(crate) fn snapshot_disks(
+    layout: &BoxFilesystemLayout,
+    disk_layer: PathBuf,
+    guest_layer: Option<PathBuf>,
+) -> BoxliteResult<Vec<(PathBuf, String)>> {
+    let mut roots = vec![(disk_layer, file_name(&layout.disk_path())?)];
+    if let Some(guest_layer) = guest_layer {
+        roots.push((guest_layer, file_name(&layout.guest_rootfs_disk_path())?));
+    }
+    Ok(roots)
+}
+
+/// The root disks and everything they are backed by, each with its
+/// bundle entry.
+fn collect_disks(roots: Vec<(PathBuf, String)>) -> BoxliteResult<Vec<(BundledDisk, PathBuf)>> {
+    let mut disks = Vec::new();
+    let mut files: HashMap<PathBuf, String> = HashMap::new();
+    for (root, name) in roots {
+        let mut file = name;
+        let mut path = root;
+        loop {
+            files.insert(path.clone(), file.clone());
+            let Some(backing) = backing_of(&path) else {
+                disks.push((
+                    BundledDisk {
+                        file,
+                        backing: None,
+                    },
+                    path,
+                ));
+                break;
+            };
+            // Both disks can share a chain; bundle each image once
+            let (backing_file, bundled) = match files.get(&backing) {
+                Some(known) => (known.clone(), true),
+                None => (format!("base-{}.img", files.len()), false),
+            };
+            disks.push((
+                BundledDisk {
+                    file,
+                    backing: Some(backing_file.clone()),
+                },
+                path,
+            ));
+            if bundled {
+                break;
+            }
+            file = backing_file;
+            path = backing;
+        }
+    }
+    Ok(disks)
+}
+
+/// Absolute path of the image backing `path`; base images are raw ext4,
+/// which ends the chain.
+fn backing_of(path: &Path) -> Option<PathBuf> {
+    let backing = Qcow2Helper::backing_file(path).ok()??;
+    Some(match path.parent() {
+        Some(dir) if backing.is_relative() => dir.join(backing),
+        _ => backing,
+    })
+}
+
+fn file_name(path: &Path) -> BoxliteResult<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| BoxliteError::Storage(format!("Invalid disk path {}", path.display())))
+}
+
+/// Unpack a bundle from `reader`.
+///
+/// `prepare` is given the manifest before any disk is unpacked and returns
+/// the box directory to unpack into, so a bundle the host cannot take is
+/// refused before its disks are copied. Bundled volumes are unpacked into
+/// `volumes/{index}` under that directory.
+pub(crate) fn import<R: Read>(
+    reader: R,
+    prepare: impl FnOnce(&Manifest) -> BoxliteResult<PathBuf>,
+) -> BoxliteResult<Manifest> {
+    let fail = |e: std::io::Error| BoxliteError::Storage(format!("Failed to read bundle: {}", e));
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader).map_err(fail)?);
+    let mut entries = archive.entries().map_err(fail)?;
+
+    let mut first = entries
+        .next()
+        .ok_or_else(|| BoxliteError::InvalidArgument("Bundle is empty".to_string()))?
+        .map_err(fail)?;
+    if first.path().map_err(fail)?.as_ref() != Path::new(MANIFEST) {
+        return Err(BoxliteError::InvalidArgument(
+            "Bundle does not start with a manifest".to_string(),
+        ));
+    }
+    let mut content = Vec::new();
+    first.read_to_end(&mut content).map_err(fail)?;
+    let manifest: Manifest = serde_json::from_slice(&content)
+        .map_err(|e| BoxliteError::InvalidArgument(format!("Invalid bundle manifest: {}", e)))?;
+    if manifest.version == 0 || manifest.version > BUNDLE_VERSION {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "Unsupported bundle version {} (this build reads up to {})",
+            manifest.version, BUNDLE_VERSION
+        )));
+    }
+    if let Some(disk) = manifest.disks.iter().find(|disk| {
+        [Some(&disk.file), disk.backing.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|file| file.is_empty() || file.contains(['/', '\\']) || file.starts_with('.'))
+    }) {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "Invalid disk name in bundle manifest: {}",
+            disk.file
+        )));
+    }
+    if let Some(index) = manifest
+        .volumes
+        .iter()
+        .find(|&&index| index >= manifest.options.volumes.len())
+    {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "Bundle manifest lists volume {} but the box has {}",
+            index,
+            manifest.options.volumes.len()
+        )));
+    }
+    let box_dir = prepare(&manifest)?;
+
+    let mut unpacked = Vec::new();
+    for entry in entries {
+        let mut entry = entry.map_err(fail)?;
+        let path = entry.path().map_err(fail)?.into_owned();
+        if let Ok(rest) = path.strip_prefix(VOLUMES_DIR) {
+            let listed = rest
+                .components()
+                .next()
+                .and_then(|index| index.as_os_str().to_str()?.parse::<usize>().ok())
+                .is_some_and(|index| manifest.volumes.contains(&index));
+            // unpack_in refuses entries that would land outside box_dir
+            if !listed || !entry.unpack_in(&box_dir).map_err(fail)? {
+                return Err(BoxliteError::InvalidArgument(format!(
+                    "Unexpected bundle entry {}",
+                    path.display()
+                )));
+            }
+            continue;
+        }
+        // Only files the manifest names, and never outside the box directory
+        let file = path
+            .strip_prefix(DISKS_DIR)
+            .ok()
+            .and_then(|name| name.to_str())
+            .filter(|name| manifest.disks.iter().any(|d| d.file == *name))
+            .ok_or_else(|| {
+                BoxliteError::InvalidArgument(format!("Unexpected bundle entry {}", path.display()))
+            })?
+            .to_string();
+        let dest = box_dir.join(&file);
+        let mut out = std::fs::File::create(&dest).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", dest.display(), e))
+        })?;
+        std::io::copy(&mut entry, &mut out).map_err(fail)?;
+        unpacked.push(file);
+    }
+    for index in &manifest.volumes {
+        let dir = box_dir.join(VOLUMES_DIR).join(index.to_string());
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", dir.display(), e))
+        })?;
+    }
+
+    for disk in &manifest.disks {
+        if !unpacked.contains(&disk.file) {
+            return Err(BoxliteError::InvalidArgument(format!(
+                "Bundle is missing disk {}",
+                disk.file
+            )));
+        }
+        let path = box_dir.join(&disk.file);
+        match &disk.backing {
+            Some(backing) => Qcow2Helper::set_backing_file(&path, &box_dir.join(backing))?,
+            // Raw images have no header to name a backing file in
+            None => {
+                if let Ok(Some(backing)) = Qcow2Helper::backing_file(&path) {
+                    return Err(BoxliteError::InvalidArgument(format!(
+                        "Bundle disk {} is backed by {}, which the bundle does not hold",
+                        disk.file,
+                        backing.display()
+                    )));
+                }
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+/// The options an imported box gets from `manifest`.
+///
+/// Only options that stay inside the box are carried over; everything else
+/// is left at its default, so options added later are dropped until they
+/// are listed here. That leaves out whatever reaches past the box: host
+/// ports and names (port forwards, ingress, mDNS, the DNS zone), host
+/// services (SSH agent, git credential and X11 forwarding, sharing, volume
+/// watching, the lock proxy, the package cache), host scheduling (priority,
+/// batch queues), nested virtualization, network and object mounts, env
+/// files and RPC recordings. Set them again on the imported box if it is
+/// trusted. A firewall is kept, since it can only narrow what the box
+/// reaches. A box that cannot run without host resources, such as volumes
+/// that are not bundled, a host rootfs, host keys and secrets, named
+/// volumes or passed through devices, is refused.
+pub(crate) fn imported_options(manifest: &Manifest) -> BoxliteResult<BoxOptions> {
+    let source = &manifest.options;
+    let refused = [
+        (
+            (0..source.volumes.len()).any(|index| !manifest.volumes.contains(&index)),
+            "volumes that are not bundled",
+        ),
+        (
+            matches!(source.rootfs, RootfsSpec::RootfsPath(_)),
+            "a rootfs path",
+        ),
+        (!source.encrypted_volumes.is_empty(), "encrypted volumes"),
+        (source.home_volume.is_some(), "a home volume"),
+        (source.gpu.is_some(), "a GPU"),
+    ];
+    let refused: Vec<&str> = refused
+        .into_iter()
+        .filter_map(|(uses, what)| uses.then_some(what))
+        .collect();
+    if !refused.is_empty() {
+        return Err(BoxliteError::InvalidArgument(format!(
+            "Bundle needs host resources it cannot bring: {}",
+            refused.join(", ")
+        )));
+    }
+
+    let source = source.clone();
+    Ok(BoxOptions {
+        cpus: source.cpus,
+        memory_mib: source.memory_mib,
+        io_limits: source.io_limits,
+        disk_size_gb: source.disk_size_gb,
+        working_dir: source.working_dir,
+        env: source.env,
+        rootfs: source.rootfs,
+        volumes: source.volumes,
+        network: source.network,
+        firewall: source.firewall,
+        isolate_mounts: source.isolate_mounts,
+        auto_remove: source.auto_remove,
+        idle_timeout_secs: source.idle_timeout_secs,
+        ttl_secs: source.ttl_secs,
+        labels: source.labels,
+        x86_emulation: source.x86_emulation,
+        memory_dedup: source.memory_dedup,
+        low_memory_percent: source.low_memory_percent,
+        pressure_percent: source.pressure_percent,
+        core_dump_max_bytes: source.core_dump_max_bytes,
+        readahead: source.readahead,
+        scheduled_tasks: source.scheduled_tasks,
+        setup_steps: source.setup_steps,
+        readiness_probe: source.readiness_probe,
+        liveness_probe: source.liveness_probe,
+        unhealthy_policy: source.unhealthy_policy,
+        etc_overlay: source.etc_overlay,
+        masked_paths: source.masked_paths,
+        readonly_paths: source.readonly_paths,
+        device_policy: source.device_policy,
+        fuse: source.fuse,
+        nested_containers: source.nested_containers,
+        init_mode: source.init_mode,
+        runtimes: source.runtimes,
+        record_sessions: source.record_sessions,
+        artifact_retention: source.artifact_retention,
+        agent_max_concurrency: source.agent_max_concurrency,
+        output_buffer: source.output_buffer,
+        devices: source.devices,
+        deterministic: source.deterministic,
+        ..BoxOptions::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::BackingFormat;
+    use crate::net::firewall::{FirewallAction, FirewallPolicy};
+    use crate::runtime::options::{
+        NetworkFilesystem, NetworkMountSpec, PortSpec, SharingOptions, VolumeSpec,
+    };
+
+    #[test]
+    fn test_export_import_rewrites_backing() {
+        let source = tempfile::tempdir().unwrap();
+        let images = source.path().join("images");
+        let box_dir = source.path().join("boxes").join("src");
+        std::fs::create_dir_all(&images).unwrap();
+        std::fs::create_dir_all(&box_dir).unwrap();
+
+        // A box disk over a snapshot layer over a raw base image
+        let base = images.join("rootfs.ext4");
+        std::fs::write(&base, vec![7u8; 4096]).unwrap();
+        let layer = images.join("layer.qcow2");
+        let helper = Qcow2Helper::new();
+        helper
+            .create_cow_child_disk(&base, BackingFormat::Raw, &layer, 4096)
+            .unwrap()
+            .leak();
+        let layout = BoxFilesystemLayout::new(box_dir, Default::default(), false);
+        helper
+            .create_cow_child_disk(&layer, BackingFormat::Qcow2, &layout.disk_path(), 4096)
+            .unwrap()
+            .leak();
+
+        let mut bundle = Vec::new();
+        let options = BoxOptions::default();
+        export(
+            &BoxID::new(),
+            Some("build".to_string()),
+            &options,
+            box_disks(&layout).unwrap(),
+            false,
+            None,
+            &mut bundle,
+        )
+        .unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let manifest = import(bundle.as_slice(), |manifest| {
+            assert_eq!(manifest.name.as_deref(), Some("build"));
+            Ok(dest.path().to_path_buf())
+        })
+        .unwrap();
+        assert_eq!(manifest.disks.len(), 3);
+
+        let disk = dest.path().join("disk.qcow2");
+        let layer = Qcow2Helper::backing_file(&disk).unwrap().unwrap();
+        assert_eq!(layer.parent(), Some(dest.path()));
+        let base = Qcow2Helper::backing_file(&layer).unwrap().unwrap();
+        assert_eq!(std::fs::read(base).unwrap(), vec![7u8; 4096]);
+    }
+
+    #[test]
+    fn test_import_refuses_foreign_backing() {
+        let source = tempfile::tempdir().unwrap();
+        let secret = source.path().join("secret.ext4");
+        std::fs::write(&secret, vec![9u8; 4096]).unwrap();
+        let disk = source.path().join("disk.qcow2");
+        Qcow2Helper::new()
+            .create_cow_child_disk(&secret, BackingFormat::Raw, &disk, 4096)
+            .unwrap()
+            .leak();
+
+        // A crafted manifest claiming the disk stands alone
+        let manifest = Manifest {
+            version: BUNDLE_VERSION,
+            source_id: BoxID::new(),
+            name: None,
+            options: BoxOptions::default(),
+            disks: vec![BundledDisk {
+                file: "disk.qcow2".to_string(),
+                backing: None,
+            }],
+            volumes: Vec::new(),
+            snapshot: None,
+        };
+        let mut builder = tar::Builder::new(zstd::Encoder::new(Vec::new(), 0).unwrap());
+        let content = serde_json::to_vec(&manifest).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, MANIFEST, content.as_slice())
+            .unwrap();
+        builder
+            .append_path_with_name(&disk, Path::new(DISKS_DIR).join("disk.qcow2"))
+            .unwrap();
+        let bundle = builder.into_inner().unwrap().finish().unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            import(bundle.as_slice(), |_| Ok(dest.path().to_path_buf())),
+            Err(BoxliteError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_imported_options_drop_host_access() {
+        let mut options = BoxOptions {
+            forward_ssh_agent: true,
+            forward_git_credentials: true,
+            env_files: vec![PathBuf::from("/home/me/.env")],
+            ..Default::default()
+        };
+        options.volumes.push(VolumeSpec {
+            host_path: "/home/me/.ssh".to_string(),
+            guest_path: "/keys".to_string(),
+            ..Default::default()
+        });
+        let mut manifest = Manifest {
+            version: BUNDLE_VERSION,
+            source_id: BoxID::new(),
+            name: None,
+            options,
+            disks: Vec::new(),
+            volumes: Vec::new(),
+            snapshot: None,
+        };
+        assert!(matches!(
+            imported_options(&manifest),
+            Err(BoxliteError::InvalidArgument(_))
+        ));
+
+        // Bundled volumes are mounted from the box's own copy
+        manifest.volumes.push(0);
+        let imported = imported_options(&manifest).unwrap();
+        assert!(!imported.forward_ssh_agent);
+        assert!(!imported.forward_git_credentials);
+        assert!(imported.env_files.is_empty());
+    }
+
+    #[test]
+    fn test_imported_options_keep_only_box_options() {
+        let options = BoxOptions {
+            cpus: Some(2),
+            package_cache: true,
+            package_cache_domain: Some("team".to_string()),
+            ports: vec![PortSpec {
+                host_port: Some(8080),
+                guest_port: 80,
+                ..Default::default()
+            }],
+            ingress_port: Some(80),
+            mdns: true,
+            dns_network: Some("web".to_string()),
+            dns_services: vec!["api".to_string()],
+            sharing: Some(SharingOptions::default()),
+            nested_virt: true,
+            lock_proxy: true,
+            watch_volumes: true,
+            firewall: Some(FirewallPolicy {
+                egress: FirewallAction::Deny,
+                ..Default::default()
+            }),
+            network_mounts: vec![NetworkMountSpec {
+                filesystem: NetworkFilesystem::Nfs,
+                source: "127.0.0.1:/export".to_string(),
+                guest_path: "/data".to_string(),
+                read_only: false,
+                options: None,
+                credentials: None,
+            }],
+            ..Default::default()
+        };
+        let manifest = Manifest {
+            version: BUNDLE_VERSION,
+            source_id: BoxID::new(),
+            name: Some("web".to_string()),
+            options,
+            disks: Vec::new(),
+            volumes: Vec::new(),
+            snapshot: None,
+        };
+        let manifest: Manifest =
+            serde_json::from_slice(&serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+        let imported = imported_options(&manifest).unwrap();
+        assert_eq!(imported.cpus, Some(2));
+        assert!(!imported.package_cache);
+        assert!(imported.package_cache_domain.is_none());
+        assert!(imported.ports.is_empty());
+        assert!(imported.ingress_port.is_none());
+        assert!(!imported.mdns);
+        assert!(imported.dns_network.is_none());
+        assert!(imported.dns_services.is_empty());
+        assert!(imported.sharing.is_none());
+        assert!(!imported.nested_virt);
+        assert!(!imported.lock_proxy);
+        assert!(!imported.watch_volumes);
+        assert!(imported.network_mounts.is_empty());
+        // A firewall only narrows what the box reaches
+        assert_eq!(
+            imported.firewall.map(|policy| policy.egress),
+            Some(FirewallAction::Deny)
+        );
+    }
+
+    #[test]
+    fn test_bundle_carries_volumes_and_renamed_roots() {
+        let source = tempfile::tempdir().unwrap();
+        let base = source.path().join("layer.ext4");
+        std::fs::write(&base, vec![3u8; 4096]).unwrap();
+        let project = source.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::write(project.join("src").join("main.rs"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink("src/main.rs", project.join("entry")).unwrap();
+
+        let mut options = BoxOptions::default();
+        options.volumes.push(VolumeSpec {
+            host_path: project.to_string_lossy().into_owned(),
+            guest_path: "/work".to_string(),
+            ..Default::default()
+        });
+
+        // A snapshot layer exported under the live disk's name
+        let mut bundle = Vec::new();
+        export(
+            &BoxID::new(),
+            None,
+            &options,
+            vec![(base, "disk.qcow2".to_string())],
+            true,
+            Some("before-upgrade".to_string()),
+            &mut bundle,
+        )
+        .unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let manifest = import(bundle.as_slice(), |_| Ok(dest.path().to_path_buf())).unwrap();
+        assert_eq!(manifest.volumes, [0]);
+        assert_eq!(manifest.snapshot.as_deref(), Some("before-upgrade"));
+        assert_eq!(
+            std::fs::read(dest.path().join("disk.qcow2")).unwrap(),
+            vec![3u8; 4096]
+        );
+        let volume = dest.path().join("volumes").join("0");
+        assert_eq!(
+            std::fs::read_to_string(volume.join("src").join("main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert_eq!(
+            std::fs::read_link(volume.join("entry")).unwrap(),
+            Path::new("src/main.rs")
+        );
+    }
+}
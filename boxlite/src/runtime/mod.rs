@@ -1,8 +1,25 @@
+pub mod bake;
+pub(crate) mod batch;
+pub mod config;
 pub mod constants;
+pub(crate) mod diagnostics;
+pub mod disk_usage;
+pub mod doctor;
+pub mod dry_run;
+pub(crate) mod exec_policy;
+pub mod filter;
 pub(crate) mod guest_rootfs;
+pub(crate) mod hooks;
 pub mod layout;
 pub(crate) mod lock;
+pub mod lockfile;
+pub(crate) mod migration;
+pub mod operations;
 pub mod options;
+pub mod overcommit;
+pub(crate) mod policy;
+pub(crate) mod pressure;
+pub(crate) mod quota;
 pub mod types;
 
 mod core;
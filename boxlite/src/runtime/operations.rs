@@ -0,0 +1,499 @@
+//! Progress and cancellation of long operations.
+//!
+//! Image pulls, snapshot restores and copies into or out of boxes run as
+//! operations. Each gets an ID (`op-{ulid}`) when it starts, is listed by
+//! `BoxliteRuntime::operations()` while it runs, and reports its progress to
+//! `BoxliteRuntime::subscribe_progress()` subscribers: when it starts, at most
+//! every [`REPORT_INTERVAL`] while it advances, and once when it ends.
+//!
+//! `BoxliteRuntime::cancel_operation()` stops an operation; the call running
+//! it fails with `BoxliteError::Cancelled`. Dropping that call's future
+//! cancels the operation as well, so work handed to blocking threads stops
+//! too. A snapshot restore runs to the end once started, since stopping it
+//! half way would leave the box's disks on different snapshots.
+//!
+//! Progress reports are not lifecycle events: they are not persisted, and a
+//! subscriber that falls behind skips ahead to the newest ones.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWrite;
+use tokio::sync::{broadcast, watch};
+
+use crate::runtime::types::BoxID;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Minimum time between two progress reports of a running operation.
+pub const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reports a subscriber can fall behind by before it skips ahead.
+const PROGRESS_BUFFER: usize = 256;
+
+/// What an operation does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    /// Downloading an image's layers from its registry (bytes).
+    ImagePull,
+    /// Restoring a stopped box's disks to a snapshot (disks).
+    SnapshotRestore,
+    /// Copying a host file into a box (bytes).
+    CopyIn,
+    /// Copying a file out of a box (bytes).
+    CopyOut,
+}
+
+impl OperationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationKind::ImagePull => "image_pull",
+            OperationKind::SnapshotRestore => "snapshot_restore",
+            OperationKind::CopyIn => "copy_in",
+            OperationKind::CopyOut => "copy_out",
+        }
+    }
+}
+
+/// Where an operation is at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl OperationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationState::Running => "running",
+            OperationState::Succeeded => "succeeded",
+            OperationState::Failed => "failed",
+            OperationState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Progress of one operation at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationProgress {
+    /// Operation ID, for `BoxliteRuntime::cancel_operation()`.
+    pub id: String,
+    pub kind: OperationKind,
+    /// What the operation works on: the image reference, the snapshot name,
+    /// or the path in the box.
+    pub target: String,
+    /// Box the operation works on; None for image pulls, which boxes share.
+    pub box_id: Option<BoxID>,
+    pub state: OperationState,
+    /// Work done so far, in the unit of its kind.
+    pub done: u64,
+    /// Total work, once known.
+    pub total: Option<u64>,
+    /// Why the operation failed.
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl OperationProgress {
+    /// Whether the operation ended.
+    pub fn is_finished(&self) -> bool {
+        self.state != OperationState::Running
+    }
+}
+
+/// Live progress reports of a runtime's operations.
+pub struct ProgressSubscription {
+    receiver: broadcast::Receiver<OperationProgress>,
+}
+
+impl ProgressSubscription {
+    /// Wait for the next report; `None` once the runtime is gone.
+    pub async fn recv(&mut self) -> Option<OperationProgress> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(progress) => return Some(progress),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The next report if one is ready.
+    pub fn try_recv(&mut self) -> Option<OperationProgress> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(progress) => return Some(progress),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// Operations running in a runtime.
+#[derive(Clone)]
+pub(crate) struct OperationRegistry {
+    inner: Arc<Registry>,
+}
+
+struct Registry {
+    active: Mutex<HashMap<String, Arc<Entry>>>,
+    sender: broadcast::Sender<OperationProgress>,
+}
+
+struct Entry {
+    id: String,
+    kind: OperationKind,
+    target: String,
+    box_id: Option<BoxID>,
+    started_at: DateTime<Utc>,
+    done: AtomicU64,
+    /// u64::MAX while unknown
+    total: AtomicU64,
+    cancelled: watch::Sender<bool>,
+    last_report: Mutex<Instant>,
+}
+
+impl Entry {
+    fn progress(&self, state: OperationState, error: Option<String>) -> OperationProgress {
+        let total = self.total.load(Ordering::Relaxed);
+        OperationProgress {
+            id: self.id.clone(),
+            kind: self.kind,
+            target: self.target.clone(),
+            box_id: self.box_id.clone(),
+            state,
+            done: self.done.load(Ordering::Relaxed),
+            total: (total != u64::MAX).then_some(total),
+            error,
+            started_at: self.started_at,
+        }
+    }
+}
+
+impl Registry {
+    fn report(&self, entry: &Entry, state: OperationState, error: Option<String>) {
+        // Nobody listening is fine
+        let _ = self.sender.send(entry.progress(state, error));
+    }
+}
+
+impl Default for OperationRegistry {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(PROGRESS_BUFFER);
+        Self {
+            inner: Arc::new(Registry {
+                active: Mutex::default(),
+                sender,
+            }),
+        }
+    }
+}
+
+impl OperationRegistry {
+    /// Register a running operation on `target`.
+    pub(crate) fn start(
+        &self,
+        kind: OperationKind,
+        target: impl Into<String>,
+        box_id: Option<BoxID>,
+    ) -> Operation {
+        let entry = Arc::new(Entry {
+            id: format!("op-{}", ulid::Ulid::new()),
+            kind,
+            target: target.into(),
+            box_id,
+            started_at: Utc::now(),
+            done: AtomicU64::new(0),
+            total: AtomicU64::new(u64::MAX),
+            cancelled: watch::Sender::new(false),
+            last_report: Mutex::new(Instant::now()),
+        });
+        self.inner
+            .active
+            .lock()
+            .unwrap()
+            .insert(entry.id.clone(), Arc::clone(&entry));
+        self.inner.report(&entry, OperationState::Running, None);
+        tracing::debug!(
+            operation_id = %entry.id,
+            kind = kind.as_str(),
+            target = %entry.target,
+            "Operation started"
+        );
+        Operation {
+            progress: Progress {
+                registry: Arc::clone(&self.inner),
+                entry,
+            },
+            finished: false,
+        }
+    }
+
+    /// Running operations, oldest first.
+    pub(crate) fn list(&self) -> Vec<OperationProgress> {
+        let mut operations: Vec<_> = self
+            .inner
+            .active
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.progress(OperationState::Running, None))
+            .collect();
+        operations.sort_by(|a, b| a.started_at.cmp(&b.started_at).then(a.id.cmp(&b.id)));
+        operations
+    }
+
+    /// Cancel the running operation `id`.
+    pub(crate) fn cancel(&self, id: &str) -> BoxliteResult<()> {
+        let active = self.inner.active.lock().unwrap();
+        let entry = active
+            .get(id)
+            .ok_or_else(|| BoxliteError::NotFound(format!("operation {}", id)))?;
+        entry.cancelled.send_replace(true);
+        Ok(())
+    }
+
+    pub(crate) fn subscribe(&self) -> ProgressSubscription {
+        ProgressSubscription {
+            receiver: self.inner.sender.subscribe(),
+        }
+    }
+}
+
+/// Reports the progress of a running operation; cheap to clone into the
+/// tasks and threads doing its work.
+#[derive(Clone)]
+pub(crate) struct Progress {
+    registry: Arc<Registry>,
+    entry: Arc<Entry>,
+}
+
+impl Progress {
+    /// Set the total work once known.
+    pub(crate) fn set_total(&self, total: u64) {
+        self.entry.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Count `amount` more work done.
+    pub(crate) fn advance(&self, amount: u64) {
+        self.entry.done.fetch_add(amount, Ordering::Relaxed);
+        let mut last_report = self.entry.last_report.lock().unwrap();
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            *last_report = Instant::now();
+            self.registry
+                .report(&self.entry, OperationState::Running, None);
+        }
+    }
+
+    /// Take back `amount` of work counted for an attempt that failed and
+    /// is done over.
+    pub(crate) fn retract(&self, amount: u64) {
+        let _ = self
+            .entry
+            .done
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |done| {
+                Some(done.saturating_sub(amount))
+            });
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        *self.entry.cancelled.borrow()
+    }
+
+    /// Resolves once the operation is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        let mut cancelled = self.entry.cancelled.subscribe();
+        // The sender lives in the entry, so this only returns once cancelled
+        let _ = cancelled.wait_for(|cancelled| *cancelled).await;
+    }
+
+    /// The error a cancelled operation fails with.
+    pub(crate) fn cancelled_error(&self) -> BoxliteError {
+        BoxliteError::Cancelled(format!("operation {}", self.entry.id))
+    }
+
+    /// Wrap `writer` to count what is written through it as done.
+    pub(crate) fn writer<W>(&self, writer: W) -> ProgressWriter<'_, W> {
+        ProgressWriter {
+            inner: writer,
+            progress: self,
+            written: 0,
+        }
+    }
+}
+
+/// A running operation; reported cancelled if dropped before it finished.
+pub(crate) struct Operation {
+    progress: Progress,
+    finished: bool,
+}
+
+impl Operation {
+    pub(crate) fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Run `work` as the operation, until it finishes or the operation is
+    /// cancelled.
+    pub(crate) async fn run<T, F, Fut>(mut self, work: F) -> BoxliteResult<T>
+    where
+        F: FnOnce(Progress) -> Fut,
+        Fut: Future<Output = BoxliteResult<T>>,
+    {
+        let progress = self.progress.clone();
+        let result = tokio::select! {
+            result = work(progress) => result,
+            () = self.progress.cancelled() => Err(self.progress.cancelled_error()),
+        };
+        self.end(&result);
+        result
+    }
+
+    /// Report the operation ended with `result`.
+    pub(crate) fn finish<T>(mut self, result: &BoxliteResult<T>) {
+        self.end(result);
+    }
+
+    fn end<T>(&mut self, result: &BoxliteResult<T>) {
+        let (state, error) = match result {
+            Ok(_) => (OperationState::Succeeded, None),
+            Err(BoxliteError::Cancelled(_)) => (OperationState::Cancelled, None),
+            Err(_) if self.progress.is_cancelled() => (OperationState::Cancelled, None),
+            Err(e) => (OperationState::Failed, Some(e.to_string())),
+        };
+        self.close(state, error);
+    }
+
+    fn close(&mut self, state: OperationState, error: Option<String>) {
+        self.finished = true;
+        let Progress { registry, entry } = &self.progress;
+        registry.active.lock().unwrap().remove(&entry.id);
+        registry.report(entry, state, error);
+        tracing::debug!(operation_id = %entry.id, state = state.as_str(), "Operation ended");
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Stop work still running on other threads
+            self.progress.entry.cancelled.send_replace(true);
+            self.close(OperationState::Cancelled, None);
+        }
+    }
+}
+
+/// Counts the bytes written through it as progress.
+pub(crate) struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: &'a Progress,
+    written: u64,
+}
+
+impl<W> ProgressWriter<'_, W> {
+    /// Bytes written through this writer.
+    pub(crate) fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for ProgressWriter<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.written += n as u64;
+            self.progress.advance(n as u64);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_operation_lifecycle() {
+        let registry = OperationRegistry::default();
+        let mut subscription = registry.subscribe();
+
+        let operation = registry.start(OperationKind::CopyIn, "/data.bin", None);
+        let id = registry.list()[0].id.clone();
+        assert!(id.starts_with("op-"));
+        let started = subscription.try_recv().unwrap();
+        assert_eq!(started.state, OperationState::Running);
+        assert_eq!(started.total, None);
+
+        let result = operation
+            .run(|progress| async move {
+                progress.set_total(10);
+                progress.advance(4);
+                progress.retract(1);
+                progress.advance(7);
+                Ok(())
+            })
+            .await;
+        assert!(result.is_ok());
+        assert!(registry.list().is_empty());
+        let finished = std::iter::from_fn(|| subscription.try_recv())
+            .last()
+            .unwrap();
+        assert_eq!(finished.state, OperationState::Succeeded);
+        assert_eq!((finished.done, finished.total), (10, Some(10)));
+        assert!(registry.cancel(&id).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_and_drop() {
+        let registry = OperationRegistry::default();
+        let mut subscription = registry.subscribe();
+
+        let operation = registry.start(OperationKind::ImagePull, "alpine", None);
+        registry.cancel(&registry.list()[0].id).unwrap();
+        let result: BoxliteResult<()> = operation.run(|_| std::future::pending()).await;
+        assert!(matches!(result, Err(BoxliteError::Cancelled(_))));
+
+        let operation = registry.start(OperationKind::CopyOut, "/out", None);
+        let progress = operation.progress().clone();
+        drop(operation);
+        assert!(progress.is_cancelled());
+        assert!(registry.list().is_empty());
+
+        let states: Vec<_> = std::iter::from_fn(|| subscription.try_recv())
+            .map(|progress| progress.state)
+            .collect();
+        assert_eq!(
+            states,
+            [
+                OperationState::Running,
+                OperationState::Cancelled,
+                OperationState::Running,
+                OperationState::Cancelled
+            ]
+        );
+    }
+}
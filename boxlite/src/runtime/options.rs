@@ -1,37 +1,479 @@
 //! Configuration for Boxlite.
 
+use crate::events::EventKind;
+use crate::net::firewall::FirewallPolicy;
+use crate::runtime::config::{ConfigLoader, ResolvedConfig};
 use crate::runtime::constants::envs as const_envs;
 use crate::runtime::layout::dirs as const_dirs;
 use boxlite_shared::errors::BoxliteResult;
 use dirs::home_dir;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 /// Configuration options for BoxliteRuntime.
 ///
 /// Users can create it with defaults and modify fields as needed.
 #[derive(Clone, Debug)]
 pub struct BoxliteOptions {
     pub home_dir: PathBuf,
+    /// Limits on resources reserved by all boxes in `home_dir`.
+    pub quota: QuotaOptions,
+    /// Buffering of each live event subscriber (see
+    /// `BoxliteRuntime::subscribe_events`).
+    pub event_buffer: StreamBufferOptions,
+    /// A cgroup2 directory delegated to the runtime, e.g. the cgroup of a
+    /// systemd unit with `Delegate=yes`. Each running box's VMM process is
+    /// placed in a child cgroup there weighted by its
+    /// [`BoxPriority`]. The directory must hold no processes itself.
+    /// Without it, priorities only set nice values and I/O priorities.
+    pub cgroup_parent: Option<PathBuf>,
+    /// Endpoints that lifecycle events are POSTed to (see
+    /// [`WebhookOptions`]).
+    pub webhooks: Vec<WebhookOptions>,
+    /// Executables run at box lifecycle points, in order (see
+    /// [`HookOptions`]).
+    pub hooks: Vec<HookOptions>,
+    /// Rules every box spec must satisfy to be created (see
+    /// [`PolicyRule`]).
+    pub policy: Vec<PolicyRule>,
+    /// TOML file of further `[[rule]]` entries, read when the runtime
+    /// starts, so an administrator can set them in the system config.
+    pub policy_file: Option<PathBuf>,
+    /// Rules applied, in order, to every command run in any box (see
+    /// [`ExecRule`]). The policy file may add `[[exec_rule]]` entries.
+    pub exec_rules: Vec<ExecRule>,
+    /// HTTPS proxy serving boxes at `https://<name>.localhost` (see
+    /// [`IngressOptions`]). None leaves it off.
+    pub ingress: Option<IngressOptions>,
+    /// DNS server answering `<name>.box` for running boxes (see
+    /// [`DnsOptions`]). None leaves it off.
+    pub dns: Option<DnsOptions>,
+    /// How far running boxes may oversubscribe the host's CPUs and memory
+    /// (see [`OvercommitOptions`]). None admits every box.
+    pub overcommit: Option<OvercommitOptions>,
+    /// What boxes run in (see [`EngineSelection`]).
+    pub engine: EngineSelection,
+    /// How the runtime responds to boxes under pressure (see
+    /// [`PressureOptions`]).
+    pub pressure: PressureOptions,
 }
 
 impl Default for BoxliteOptions {
     fn default() -> Self {
         let home_dir = std::env::var(const_envs::BOXLITE_HOME)
             .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                let mut path = home_dir().unwrap_or_else(|| PathBuf::from("."));
-                path.push(const_dirs::BOXLITE_DIR);
-                path
-            });
+            .unwrap_or_else(|_| default_home_dir());
+
+        Self {
+            home_dir,
+            quota: QuotaOptions::default(),
+            event_buffer: StreamBufferOptions::default(),
+            cgroup_parent: None,
+            webhooks: Vec::new(),
+            hooks: Vec::new(),
+            policy: Vec::new(),
+            policy_file: None,
+            exec_rules: Vec::new(),
+            ingress: None,
+            dns: None,
+            overcommit: None,
+            engine: EngineSelection::default(),
+            pressure: PressureOptions::default(),
+        }
+    }
+}
+
+impl BoxliteOptions {
+    /// Options resolved from the config files and `BOXLITE_*` environment
+    /// variables on top of the defaults (see [`crate::runtime::config`]).
+    pub fn load() -> BoxliteResult<Self> {
+        ConfigLoader::new()
+            .resolve()
+            .map(ResolvedConfig::into_options)
+    }
+}
+
+/// `~/.boxlite`, ignoring `BOXLITE_HOME`.
+pub(crate) fn default_home_dir() -> PathBuf {
+    let mut path = home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(const_dirs::BOXLITE_DIR);
+    path
+}
+
+/// Which engine runs boxes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineSelection {
+    /// A VM per box; fails to start boxes without a hypervisor.
+    #[default]
+    Vm,
+    /// A VM when the host has a usable hypervisor, else boxless.
+    Auto,
+    /// No VM: the guest agent runs on the host in namespaces under seccomp.
+    ///
+    /// Much weaker isolation than a VM. The box shares the host kernel and
+    /// network, and cannot attach disk volumes. For CI runners and nested
+    /// environments without virtualization; not for untrusted code.
+    Boxless,
+}
+
+impl EngineSelection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineSelection::Vm => "vm",
+            EngineSelection::Auto => "auto",
+            EngineSelection::Boxless => "boxless",
+        }
+    }
+}
+
+/// What a bounded stream buffer does when its consumer falls behind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Evict the oldest buffered item and count it as dropped.
+    #[default]
+    DropOldest,
+    /// Hold back the producer until the consumer catches up.
+    Block,
+}
+
+/// Size and overflow behavior of a stream buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StreamBufferOptions {
+    /// Items (output chunks, events) buffered before `overflow` applies.
+    pub capacity: usize,
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for StreamBufferOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            overflow: OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+/// An HTTP endpoint notified of lifecycle events.
+///
+/// Each event is POSTed as its JSON [`crate::BoxEvent`], with the event
+/// kind in `X-Boxlite-Event` and its sequence number in
+/// `X-Boxlite-Delivery`. With a `secret`, `X-Boxlite-Signature` carries
+/// `sha256=` and the hex HMAC-SHA256 of the body under it, so the receiver
+/// can check the request came from this runtime. Failed deliveries are
+/// retried a few times and then dropped; the event log stays the source of
+/// truth.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WebhookOptions {
+    pub url: String,
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Event kinds to deliver; empty delivers all of them.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+}
+
+impl WebhookOptions {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Whether events of `kind` go to this endpoint.
+    pub fn wants(&self, kind: EventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// The host-side HTTPS proxy for box web services.
+///
+/// Connections to `listen` are TLS-terminated and passed on to the box
+/// named by the SNI host `<name>.localhost`, at the host port forwarded to
+/// its `BoxOptions::ingress_port`, so a dev server in a box gets HTTPS
+/// without configuring one. Certificates are issued on demand by a local CA
+/// created in `<home_dir>/ingress/ca.pem`; trust that file once in the
+/// browser or system store. Only HTTP/1.1 is offered to clients.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IngressOptions {
+    pub listen: std::net::SocketAddr,
+}
+
+impl Default for IngressOptions {
+    fn default() -> Self {
+        Self {
+            listen: std::net::SocketAddr::from(([127, 0, 0, 1], 8443)),
+        }
+    }
+}
+
+/// The host-side DNS server for box names.
+///
+/// Answers `<name>.box` for each running named box and
+/// `<service>.<network>.box` for the services of boxes that joined a
+/// network (`BoxOptions::dns_network`), all resolving to the host's
+/// loopback, where forwarded ports listen. Guests of a runtime with DNS on
+/// resolve the same zone to the host and search it, so boxes reach each
+/// other's forwarded ports by name. The host resolver must be pointed at
+/// `listen` for the `box` zone to use the names outside boxes.
+///
+/// The alias address guests reach the host through only lets connections
+/// through to the host ports forwarded by boxes answered in the zone;
+/// other services on the host's loopback stay out of reach.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsOptions {
+    pub listen: std::net::SocketAddr,
+}
+
+impl Default for DnsOptions {
+    fn default() -> Self {
+        Self {
+            listen: std::net::SocketAddr::from(([127, 0, 0, 1], 5380)),
+        }
+    }
+}
+
+/// How the runtime responds to a box coming under pressure.
+///
+/// Boxes report pressure from their guest (`BoxOptions::pressure_percent`).
+/// The response falls on running boxes of lower [`BoxPriority`] than the
+/// box under pressure. Both responses are off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PressureOptions {
+    /// On memory pressure, have each of them drop its clean page cache and
+    /// compact memory; the balloon's free page reporting hands the freed
+    /// pages back to the host.
+    pub reclaim_memory: bool,
+    /// While any box is under CPU pressure, cap each of their VMMs at a
+    /// tenth of a CPU. Needs `cgroup_parent`.
+    pub throttle_cpu: bool,
+}
+
+/// Lifecycle point at which a hook runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    /// Before a box is created, cloned or imported. The hook may rewrite
+    /// the box options or veto the box.
+    PreCreate,
+    /// After a box's VM booted and the guest is ready. A veto stops the box.
+    PostReady,
+    /// Before a box is removed. A veto keeps the box.
+    PreDestroy,
+}
+
+impl HookStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookStage::PreCreate => "pre_create",
+            HookStage::PostReady => "post_ready",
+            HookStage::PreDestroy => "pre_destroy",
+        }
+    }
+}
+
+/// An executable run on the host at box lifecycle points.
+///
+/// The hook gets the stage in `BOXLITE_HOOK_STAGE` and a JSON document on
+/// stdin with `stage`, `box_id` (absent before creation), `name`,
+/// `options` and, for `pre_destroy`, `reason` (`removed`, `pruned` or
+/// `reaped`). Exiting 0 lets the operation go ahead; at `pre_create`, a
+/// JSON box options document on stdout replaces the box's options. Any
+/// other exit, or running past `timeout_secs`, vetoes the operation with
+/// the hook's stderr as the reason.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HookOptions {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Stages to run at; empty runs at all of them.
+    #[serde(default)]
+    pub stages: Vec<HookStage>,
+    #[serde(default = "default_hook_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_hook_timeout_secs() -> u64 {
+    30
+}
+
+impl HookOptions {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            args: Vec::new(),
+            stages: Vec::new(),
+            timeout_secs: default_hook_timeout_secs(),
+        }
+    }
+
+    /// Whether this hook runs at `stage`.
+    pub fn runs_at(&self, stage: HookStage) -> bool {
+        self.stages.is_empty() || self.stages.contains(&stage)
+    }
+}
+
+/// A named admission rule for box specs.
+///
+/// Rules are checked on every box created, cloned or imported, after
+/// `pre_create` hooks ran, and a spec that breaks one is refused with
+/// [`BoxliteError::PolicyViolation`](boxlite_shared::errors::BoxliteError)
+/// naming the rule. In a policy file:
+///
+/// ```toml
+/// [[rule]]
+/// name = "approved-registries"
+/// check = "allowed_registries"
+/// registries = ["ghcr.io", "registry.internal"]
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PolicyRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub check: PolicyCheck,
+}
+
+/// What a [`PolicyRule`] requires of a box spec.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum PolicyCheck {
+    /// Memory, with the default applied, is at most `limit` MiB.
+    MaxMemoryMib { limit: u32 },
+    /// vCPUs, with the default applied, are at most `limit`.
+    MaxCpus { limit: u8 },
+    /// Disk size, with the default applied, is at most `limit` GB.
+    MaxDiskGb { limit: u64 },
+    /// The rootfs is an image from one of `registries` (`docker.io` for
+    /// unqualified references); prepared rootfs paths are refused.
+    AllowedRegistries { registries: Vec<String> },
+    /// No host device reaches the box: no GPU, device nodes, devtmpfs,
+    /// FUSE or nested virtualization.
+    NoDevices,
+    /// Every volume's host path is under one of `prefixes`.
+    AllowedVolumePaths { prefixes: Vec<PathBuf> },
+}
+
+/// A named rule applied to every command before it runs in a box.
+///
+/// Rules apply in order, so a deny after a wrap sees the wrapped command.
+/// A denied command fails with
+/// [`BoxliteError::PolicyViolation`](boxlite_shared::errors::BoxliteError)
+/// naming the rule, before it reaches the guest. They cover execs,
+/// scheduled tasks, setup steps and command probes, but not SSH sessions.
+///
+/// Rules match the command line, not what it does: `sh -c 'pip ...'` or a
+/// renamed binary gets past a `deny_commands` rule for `pip`. They keep
+/// cooperating clients in line and are not a security boundary. In a
+/// policy file:
+///
+/// ```toml
+/// [[exec_rule]]
+/// name = "no-package-managers"
+/// action = "deny_commands"
+/// commands = ["apt-get", "pip"]
+///
+/// [[exec_rule]]
+/// name = "low-priority"
+/// action = "wrap"
+/// prefix = ["nice", "-n", "10"]
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExecRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub action: ExecAction,
+}
+
+/// What an [`ExecRule`] does to a command.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ExecAction {
+    /// Refuse commands whose program, by file name, is one of `commands`.
+    DenyCommands { commands: Vec<String> },
+    /// Refuse commands whose command line (program and arguments joined by
+    /// spaces) matches the regex `pattern`.
+    DenyPattern { pattern: String },
+    /// Run commands through `prefix`, e.g. `["timeout", "300"]`.
+    Wrap { prefix: Vec<String> },
+    /// Replace matches of the regex `pattern` in arguments with
+    /// `replacement` (`***` by default).
+    ScrubArgs {
+        pattern: String,
+        #[serde(default = "default_scrub_replacement")]
+        replacement: String,
+    },
+}
+
+fn default_scrub_replacement() -> String {
+    "***".to_string()
+}
+
+/// Host resource quotas, checked when a box is created.
+///
+/// Every box that exists in the runtime home (running or stopped) reserves
+/// its configured vCPUs, memory, and virtual disk size. Creating a box that
+/// would push any total past its limit fails with `QuotaExceeded`.
+/// Since the home directory is per user by default, so are the quotas.
+/// `None` means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct QuotaOptions {
+    /// Maximum number of boxes.
+    pub max_boxes: Option<usize>,
+    /// Maximum total vCPUs.
+    pub max_cpus: Option<u32>,
+    /// Maximum total memory in MiB.
+    pub max_memory_mib: Option<u64>,
+    /// Maximum total container disk size in GB (virtual size).
+    pub max_disk_gb: Option<u64>,
+}
 
-        Self { home_dir }
+/// Host admission for starting boxes.
+///
+/// Each running box commits its configured vCPUs and memory. A box may
+/// start only while the committed totals, its own included, stay within the
+/// host's CPUs and memory times these ratios. When memory is overcommitted
+/// (a ratio above 1), the host must also have the box's memory actually
+/// available. A box that does not fit waits up to `queue_timeout_secs` for
+/// others to stop, then fails with `QuotaExceeded`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OvercommitOptions {
+    /// vCPUs committed per host CPU (default 4).
+    pub cpu_ratio: f64,
+    /// Memory committed per byte of host memory (default 1).
+    pub memory_ratio: f64,
+    /// Seconds a box waits for capacity before it is refused; None refuses
+    /// it at once.
+    pub queue_timeout_secs: Option<u64>,
+}
+
+impl Default for OvercommitOptions {
+    fn default() -> Self {
+        Self {
+            cpu_ratio: 4.0,
+            memory_ratio: 1.0,
+            queue_timeout_secs: None,
+        }
     }
 }
 
 /// Options used when constructing a box.
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct BoxOptions {
     pub cpus: Option<u8>,
     pub memory_mib: Option<u32>,
+    /// Share of host CPU and disk I/O the box gets when boxes compete.
+    #[serde(default)]
+    pub priority: BoxPriority,
+    /// Caps on the box's disk bandwidth and IOPS (see [`IoLimits`]).
+    #[serde(default)]
+    pub io_limits: IoLimits,
+    /// Named queue the box waits in before it starts (see [`BatchQueue`]).
+    #[serde(default)]
+    pub queue: Option<BatchQueue>,
     /// Disk size in GB for the container rootfs (sparse, grows as needed).
     ///
     /// The actual disk will be at least as large as the base image.
@@ -39,11 +481,72 @@ pub struct BoxOptions {
     /// the container to write more data than the base image size.
     pub disk_size_gb: Option<u64>,
     pub working_dir: Option<String>,
+    /// Environment variables for the container and every exec in it.
+    ///
+    /// Per-exec variables (`BoxCommand::env`) override these.
     pub env: Vec<(String, String)>,
+    /// Host `.env` files loaded into the environment, in order.
+    ///
+    /// Files are read each time the box starts. Variables in `env` take
+    /// precedence over file contents; later files override earlier ones.
+    #[serde(default)]
+    pub env_files: Vec<PathBuf>,
     pub rootfs: RootfsSpec,
     pub volumes: Vec<VolumeSpec>,
+    /// Remote filesystems (NFSv4, CIFS) mounted into the box.
+    ///
+    /// The guest mounts them once its network is up, before the container
+    /// starts, so a server that cannot be reached fails the start. Needs
+    /// the network device.
+    #[serde(default)]
+    pub network_mounts: Vec<NetworkMountSpec>,
+    /// S3-compatible buckets mounted into the box (Linux hosts only).
+    ///
+    /// Objects are fetched when first opened, so a large dataset is usable
+    /// without copying it in first.
+    #[serde(default)]
+    pub object_volumes: Vec<ObjectVolumeSpec>,
+    /// Named volumes encrypted at rest with a host-held key (Linux hosts
+    /// only).
+    ///
+    /// They are kept under the runtime home, outlive the box, and can be
+    /// attached to one box at a time.
+    #[serde(default)]
+    pub encrypted_volumes: Vec<EncryptedVolumeSpec>,
+    /// Named volume mounted as the workload's home directory.
+    ///
+    /// It outlives the box, so a box recreated with the same name finds
+    /// its dotfiles and shell history where it left them. Defaults to None.
+    #[serde(default)]
+    pub home_volume: Option<HomeVolume>,
     pub network: NetworkSpec,
     pub ports: Vec<PortSpec>,
+    /// Answer mDNS queries for `<name>.local` on the host LAN while the box
+    /// runs, resolving to the host, so forwarded ports can be reached by
+    /// name from other devices. Needs a box name. Defaults to false.
+    #[serde(default)]
+    pub mdns: bool,
+
+    /// Guest port served at `https://<name>.localhost` by the runtime's
+    /// ingress proxy (see [`IngressOptions`]). It must be forwarded in
+    /// `ports` or exposed by the image. Needs a box name. Defaults to None.
+    #[serde(default)]
+    pub ingress_port: Option<u16>,
+    /// Network the box joins in the runtime's DNS zone (see
+    /// [`DnsOptions`]), making its services answerable as
+    /// `<service>.<network>.box`. Defaults to None.
+    #[serde(default)]
+    pub dns_network: Option<String>,
+    /// Service names the box offers in `dns_network`; empty offers the box
+    /// name.
+    #[serde(default)]
+    pub dns_services: Vec<String>,
+    /// Firewall the box's network starts with (see
+    /// [`crate::net::firewall`]); the running box's rules can be changed
+    /// with `LiteBox::set_firewall`. Needs the network device. Defaults to
+    /// None, which allows all traffic.
+    #[serde(default)]
+    pub firewall: Option<FirewallPolicy>,
     /// Enable bind mount isolation for the shared mounts directory.
     ///
     /// When true, creates a read-only bind mount from `mounts/` to `shared/`,
@@ -62,42 +565,1388 @@ pub struct BoxOptions {
     ///
     /// When false, the box is preserved after stop and can be restarted
     /// with `runtime.get(box_id)`.
+    ///
+    /// If the creating process exits without stopping the box, the box is
+    /// removed by the next runtime that reaps the home directory.
     #[serde(default = "default_auto_remove")]
     pub auto_remove: bool,
+
+    /// Suspend the box after this many seconds without exec activity.
+    ///
+    /// A suspended box has its VM torn down while its disks are kept, and is
+    /// reported as stopped. The next `exec()` on any handle transparently
+    /// restarts it. Processes inside the guest do not survive a suspend.
+    ///
+    /// Boxes with port mappings or SSH are never suspended, since inbound
+    /// connections reach the VM's network backend directly and cannot wake it.
+    /// Defaults to None (never suspend).
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+
+    /// Maximum lifetime of the box in seconds, counted from creation.
+    ///
+    /// Once expired, the box is stopped by its own shim process (so the
+    /// limit holds even if the creating process dies): the guest is shut
+    /// down as on a normal stop, then the VM is killed. The box is removed
+    /// by the runtime's reaper. Defaults to None (no limit).
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+
+    /// User-defined key/value labels for filtering and bulk operations.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+
+    /// GPU device exposed to the guest. Defaults to None (no GPU).
+    #[serde(default)]
+    pub gpu: Option<GpuSpec>,
+
+    /// Enable nested virtualization and expose `/dev/kvm` in the box.
+    ///
+    /// Lets workloads inside the box start their own VMs. Requires host
+    /// support (KVM nested virt on Linux, M3 or newer on macOS).
+    /// Defaults to false.
+    #[serde(default)]
+    pub nested_virt: bool,
+
+    /// Run x86_64 binaries in an arm64 box, for x86-only toolchains.
+    ///
+    /// Guest init registers an x86_64 interpreter with binfmt_misc: Rosetta
+    /// on macOS (`softwareupdate --install-rosetta`), a static
+    /// `qemu-x86_64` on Linux (e.g. from `qemu-user-static`). Emulated
+    /// binaries run several times slower than native ones. Has no effect on
+    /// x86_64 hosts. Defaults to false.
+    #[serde(default)]
+    pub x86_emulation: bool,
+
+    /// Let the host kernel deduplicate this box's guest memory (KSM).
+    ///
+    /// Boxes started from the same image hold many identical pages (kernel,
+    /// libraries, page cache), which KSM merges into one copy. Merged pages
+    /// can leak information between boxes through timing side channels, so
+    /// disable this for security-sensitive workloads.
+    ///
+    /// Linux only (kernel 6.4+), and only effective while KSM is running
+    /// (`/sys/kernel/mm/ksm/run`). Defaults to true.
+    #[serde(default = "default_memory_dedup")]
+    pub memory_dedup: bool,
+
+    /// Emit a `low_memory` event when the guest's available memory drops
+    /// under this percent of its total.
+    ///
+    /// The event names the process the OOM killer would pick, so the host
+    /// can resize the box or fail fast before it strikes; OOM kills are
+    /// always reported as `oom_killed` events. 0 disables low memory
+    /// events. Defaults to 10.
+    #[serde(default = "default_low_memory_percent")]
+    pub low_memory_percent: u8,
+
+    /// Emit a `pressure` event when some task in the guest stalls on CPU,
+    /// memory or I/O this percent of the time or more, over 10 seconds,
+    /// and a `pressure_relieved` event once it has dropped back.
+    ///
+    /// Read from the guest kernel's pressure stall information (PSI). The
+    /// runtime may respond by leaning on boxes of lower priority (see
+    /// [`PressureOptions`]). 0 disables pressure events. Defaults to 20.
+    #[serde(default = "default_pressure_percent")]
+    pub pressure_percent: u8,
+
+    /// Collect core dumps of crashing processes, keeping up to this many
+    /// bytes of each.
+    ///
+    /// The guest pipes dumps to its agent, which holds them on a scratch
+    /// volume of this size in guest memory until the host stores them with
+    /// the box's artifacts, named `core-{time}-{pid}` (see
+    /// `LiteBox::artifacts`), and emits a `crash_dump` event. Larger dumps
+    /// are cut short. 0 leaves the guest kernel's core dump settings alone.
+    /// Defaults to 256 MiB.
+    ///
+    /// The scratch volume is a tmpfs, so a dump waiting to be taken off
+    /// occupies guest RAM out of `memory_mib`: up to this many bytes (plus
+    /// 1 MiB of metadata) while a box has dumps pending, nothing otherwise.
+    /// Lower it for boxes with little memory to spare.
+    #[serde(default = "default_core_dump_max_bytes")]
+    pub core_dump_max_bytes: u64,
+
+    /// Read ahead the rootfs pages earlier boots of the same image used.
+    ///
+    /// The first box started from an image records which rootfs file ranges
+    /// are in its page cache once it has settled; later boots from that
+    /// image read them in the background while the workload starts. Only
+    /// disk-based rootfs boxes record hints. Defaults to true.
+    #[serde(default = "default_readahead")]
+    pub readahead: bool,
+
+    /// Mount a package cache and point apt, pip and npm at it.
+    ///
+    /// Downloaded packages are kept on the host and reused when the box
+    /// restarts, or by other boxes in the same `package_cache_domain`.
+    /// Only one running box holds a cache; others start without it.
+    /// Defaults to false.
+    #[serde(default)]
+    pub package_cache: bool,
+
+    /// Share the package cache with other boxes naming the same domain.
+    ///
+    /// Anything a box writes to the cache is installed by the boxes after
+    /// it, so only boxes that trust each other should share a domain.
+    /// When unset, the box's cache is its own.
+    #[serde(default)]
+    pub package_cache_domain: Option<String>,
+
+    /// Commands the guest agent runs periodically once the box is up.
+    ///
+    /// Inspect their last runs with `LiteBox::list_tasks`; tasks can also be
+    /// added and removed at runtime.
+    #[serde(default)]
+    pub scheduled_tasks: Vec<ScheduledTask>,
+
+    /// Commands run in order, each to completion, before the workload.
+    ///
+    /// The image entrypoint waits until every step exits 0, and the box
+    /// fails to start if one does not. Like Kubernetes init containers, but
+    /// in the box's own rootfs.
+    #[serde(default)]
+    pub setup_steps: Vec<SetupStep>,
+
+    /// Check the guest agent runs to tell whether the box is ready to serve.
+    ///
+    /// The box reports `starting` health until it first passes. Defaults
+    /// to None.
+    #[serde(default)]
+    pub readiness_probe: Option<Probe>,
+
+    /// Check the guest agent runs to tell whether the box still works.
+    ///
+    /// The box reports `unhealthy` while it fails, and `unhealthy_policy`
+    /// decides what happens then. Defaults to None.
+    #[serde(default)]
+    pub liveness_probe: Option<Probe>,
+
+    /// What to do when the liveness probe fails. Defaults to nothing.
+    #[serde(default)]
+    pub unhealthy_policy: UnhealthyPolicy,
+
+    /// Start an SSH server in the box for tools that only speak SSH.
+    ///
+    /// The image must ship OpenSSH (`sshd` and `ssh-keygen`). Defaults to None.
+    #[serde(default)]
+    pub ssh: Option<SshOptions>,
+
+    /// Relay the host's SSH agent into the box and set `SSH_AUTH_SOCK`.
+    ///
+    /// Keys stay on the host; the box can only ask the agent to sign.
+    /// Skipped when the host has no agent. Defaults to false.
+    #[serde(default)]
+    pub forward_ssh_agent: bool,
+
+    /// Answer git credential requests in the box from the host's git
+    /// credential helpers (`git credential fill`).
+    ///
+    /// Credentials are never stored back to the host, and are only given
+    /// for [`git_credential_hosts`](Self::git_credential_hosts). Only
+    /// available while the creating process runs. Defaults to false.
+    #[serde(default)]
+    pub forward_git_credentials: bool,
+
+    /// Hosts (with a port if not the default) that forwarded git
+    /// credentials are answered for; requests for any other host get none.
+    ///
+    /// Empty uses the hosts of the remotes configured in the git repository
+    /// the creating process runs in. Defaults to empty.
+    #[serde(default)]
+    pub git_credential_hosts: Vec<String>,
+
+    /// Show GUI apps from the box on the host's X server (`$DISPLAY`).
+    ///
+    /// The box gets `DISPLAY=:0`. Wayland-only apps need XWayland on the
+    /// host. Skipped when the host has no local X display. Defaults to false.
+    #[serde(default)]
+    pub forward_x11: bool,
+
+    /// Clipboard and file drop sharing with the host. Defaults to None.
+    #[serde(default)]
+    pub sharing: Option<SharingOptions>,
+
+    /// Report host-side edits in user volumes to file watchers in the box.
+    ///
+    /// Volumes are polled every second while the box runs and changed
+    /// entries raise inotify attribute events (`IN_ATTRIB`) in the box:
+    /// edits on the file, creations, deletions and renames on its
+    /// directory. Other event types are not forwarded, so watchers must
+    /// react to attribute changes. Defaults to false.
+    #[serde(default)]
+    pub watch_volumes: bool,
+
+    /// Broker file locks on user volumes through the host.
+    ///
+    /// Locks taken in the box on a volume are otherwise invisible to the
+    /// host and other boxes. Commands run under
+    /// `/run/boxlite-lock/boxlite-lock FILE COMMAND...` hold a host-side
+    /// lock on FILE instead. Defaults to false.
+    #[serde(default)]
+    pub lock_proxy: bool,
+
+    /// Give the box its own writable `/etc` over a shared rootfs.
+    ///
+    /// Edits land in a tmpfs overlay in the VM and are discarded when the
+    /// box stops. Defaults to false.
+    #[serde(default)]
+    pub etc_overlay: bool,
+
+    /// Container paths hidden from the workload.
+    ///
+    /// Directories are covered with an empty read-only tmpfs and files with
+    /// `/dev/null` before the workload starts; paths missing from the image
+    /// are skipped. See [`BoxOptions::with_default_path_masks`] for the OCI
+    /// defaults.
+    #[serde(default)]
+    pub masked_paths: Vec<String>,
+
+    /// Container paths remounted read-only before the workload starts.
+    #[serde(default)]
+    pub readonly_paths: Vec<String>,
+
+    /// How the box's `/dev` is populated beyond the standard nodes.
+    #[serde(default)]
+    pub device_policy: DevicePolicy,
+
+    /// Allow FUSE mounts (sshfs, s3fs, AppImages) in the box.
+    ///
+    /// Adds `/dev/fuse` and enables `user_allow_other` in the image's
+    /// `/etc/fuse.conf`; non-root users also need the image's setuid
+    /// `fusermount`. When false, `/dev/fuse` is withheld even with a
+    /// devtmpfs `/dev`. Defaults to false.
+    #[serde(default)]
+    pub fuse: bool,
+
+    /// Prepare the box to run container engines such as dockerd and podman.
+    ///
+    /// The container gets a cgroup namespace with a writable cgroup2
+    /// hierarchy, shared root mount propagation, and the netfilter, bridge
+    /// and overlay modules the guest kernel has. Engine storage must stay
+    /// on the box's disk, so volumes may not cover `/var/lib/docker` or
+    /// `/var/lib/containers`. See [`BoxOptions::with_docker_profile`] for
+    /// the full setup. Defaults to false.
+    #[serde(default)]
+    pub nested_containers: bool,
+
+    /// What runs as the container's init process.
+    #[serde(default)]
+    pub init_mode: InitMode,
+
+    /// Language runtimes to install on the box's first boot.
+    ///
+    /// The first box with a given image and set of runtimes installs them
+    /// with the image's package manager and leaves its disk, as it is right
+    /// after, as a derived image; later boxes start from that and skip the
+    /// install. Needs the network device.
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeProfile>,
+
+    /// Record TTY sessions as asciinema casts in the box directory.
+    ///
+    /// List and read them with `LiteBox::recordings`. Recordings go with
+    /// the box when it is removed. Defaults to false.
+    #[serde(default)]
+    pub record_sessions: bool,
+
+    /// How long artifacts collected from executions are kept (see
+    /// `BoxCommand::artifacts`). Defaults to keeping them all until the box
+    /// is removed.
+    #[serde(default)]
+    pub artifact_retention: ArtifactRetention,
+
+    /// Most RPCs one connection to the guest agent may have in flight.
+    ///
+    /// Calls past it fail instead of queueing. Streaming calls (attached
+    /// output, stdin) count until they finish, so raise this for clients
+    /// that keep many executions attached at once. None uses the agent's
+    /// default of 256.
+    #[serde(default)]
+    pub agent_max_concurrency: Option<usize>,
+
+    /// Buffering of exec stdout and stderr on the host.
+    ///
+    /// With `DropOldest` (the default) a consumer that falls behind loses
+    /// the oldest output, counted in `BoxMetrics::output_chunks_dropped_total`.
+    /// With `Block` nothing is lost: the guest stops reading the process's
+    /// output until the consumer catches up, which stalls a process whose
+    /// output is never read.
+    #[serde(default)]
+    pub output_buffer: StreamBufferOptions,
+
+    /// Optional virtio devices the VM is built with. All are on by
+    /// default; see [`BoxOptions::with_minimal_devices`].
+    #[serde(default)]
+    pub devices: DeviceProfile,
+
+    /// Best-effort repeatable runs; set with
+    /// [`BoxOptions::with_deterministic`]. None by default.
+    #[serde(default)]
+    pub deterministic: Option<Determinism>,
+
+    /// Record every RPC to the guest agent, with its response, to this
+    /// file for replay with `rpc_replay`.
+    ///
+    /// Exchanges are appended as JSON lines, so the file collects every run
+    /// of the box. File copies (`LiteBox::copy_in`, `copy_out`) use a
+    /// separate channel and are not recorded. None by default.
+    #[serde(default)]
+    pub rpc_recording: Option<PathBuf>,
+
+    /// Answer the box's RPCs from a file written with `rpc_recording`
+    /// instead of starting a VM, so code built on the SDK can be tested
+    /// without booting one.
+    ///
+    /// Starting the box pulls no image and runs nothing. Each call gets the
+    /// first unused recorded response of the same method, preferring one
+    /// whose request matches exactly; calls with none left fail with
+    /// `Unimplemented`. None by default.
+    #[serde(default)]
+    pub rpc_replay: Option<PathBuf>,
+}
+
+fn default_auto_remove() -> bool {
+    false
+}
+
+fn default_memory_dedup() -> bool {
+    true
+}
+
+fn default_readahead() -> bool {
+    true
+}
+
+fn default_low_memory_percent() -> u8 {
+    10
+}
+
+fn default_pressure_percent() -> u8 {
+    20
+}
+
+fn default_core_dump_max_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+impl Default for BoxOptions {
+    fn default() -> Self {
+        Self {
+            cpus: None,
+            memory_mib: None,
+            priority: BoxPriority::default(),
+            io_limits: IoLimits::default(),
+            queue: None,
+            disk_size_gb: None,
+            working_dir: None,
+            env: Vec::new(),
+            env_files: Vec::new(),
+            rootfs: RootfsSpec::default(),
+            volumes: Vec::new(),
+            network_mounts: Vec::new(),
+            object_volumes: Vec::new(),
+            encrypted_volumes: Vec::new(),
+            home_volume: None,
+            network: NetworkSpec::default(),
+            ports: Vec::new(),
+            mdns: false,
+            ingress_port: None,
+            dns_network: None,
+            dns_services: Vec::new(),
+            firewall: None,
+            isolate_mounts: false,
+            auto_remove: default_auto_remove(),
+            idle_timeout_secs: None,
+            ttl_secs: None,
+            labels: HashMap::new(),
+            gpu: None,
+            nested_virt: false,
+            x86_emulation: false,
+            memory_dedup: default_memory_dedup(),
+            low_memory_percent: default_low_memory_percent(),
+            pressure_percent: default_pressure_percent(),
+            core_dump_max_bytes: default_core_dump_max_bytes(),
+            readahead: default_readahead(),
+            package_cache: false,
+            package_cache_domain: None,
+            scheduled_tasks: Vec::new(),
+            setup_steps: Vec::new(),
+            readiness_probe: None,
+            liveness_probe: None,
+            unhealthy_policy: UnhealthyPolicy::default(),
+            ssh: None,
+            forward_ssh_agent: false,
+            forward_git_credentials: false,
+            git_credential_hosts: Vec::new(),
+            forward_x11: false,
+            sharing: None,
+            watch_volumes: false,
+            lock_proxy: false,
+            etc_overlay: false,
+            masked_paths: Vec::new(),
+            readonly_paths: Vec::new(),
+            device_policy: DevicePolicy::default(),
+            fuse: false,
+            nested_containers: false,
+            init_mode: InitMode::default(),
+            runtimes: Vec::new(),
+            record_sessions: false,
+            artifact_retention: ArtifactRetention::default(),
+            agent_max_concurrency: None,
+            output_buffer: StreamBufferOptions::default(),
+            devices: DeviceProfile::default(),
+            deterministic: None,
+            rpc_recording: None,
+            rpc_replay: None,
+        }
+    }
+}
+
+/// Longest tag a virtio-fs device accepts.
+const MAX_SHARE_TAG_LEN: usize = 36;
+
+/// Tags of the shares the runtime adds itself.
+const RESERVED_SHARE_TAGS: &[&str] = &[
+    boxlite_shared::constants::mount_tags::ROOTFS,
+    boxlite_shared::constants::mount_tags::LAYERS,
+    boxlite_shared::constants::mount_tags::SHARED,
+    crate::volumes::PACKAGE_CACHE_TAG,
+    HOME_VOLUME_TAG,
+];
+
+/// Where container engines keep images and layers.
+const ENGINE_STORAGE_DIRS: &[&str] = &["/var/lib/docker", "/var/lib/containers"];
+
+/// Disk size the Docker profile grows the box to, for images and layers.
+const DOCKER_PROFILE_DISK_GB: u64 = 20;
+
+/// Kernel interfaces runc masks by default.
+const DEFAULT_MASKED_PATHS: &[&str] = &[
+    "/proc/acpi",
+    "/proc/asound",
+    "/proc/kcore",
+    "/proc/keys",
+    "/proc/latency_stats",
+    "/proc/timer_list",
+    "/proc/timer_stats",
+    "/proc/sched_debug",
+    "/proc/scsi",
+    "/sys/firmware",
+    "/sys/devices/virtual/powercap",
+];
+
+/// Kernel interfaces runc makes read-only by default.
+const DEFAULT_READONLY_PATHS: &[&str] = &[
+    "/proc/bus",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+];
+
+impl BoxOptions {
+    /// Apply the Docker profile: what dockerd or podman needs to build and
+    /// run containers inside the box.
+    ///
+    /// Enables `nested_containers` and `fuse` (for fuse-overlayfs), adds
+    /// `/dev/net/tun` (for rootless networking) and grows the disk to at
+    /// least 20 GB. Other options are kept.
+    pub fn with_docker_profile(mut self) -> Self {
+        self.nested_containers = true;
+        self.fuse = true;
+        if !self
+            .device_policy
+            .nodes
+            .iter()
+            .any(|node| node.path == "/dev/net/tun")
+        {
+            self.device_policy
+                .nodes
+                .push(DeviceNodeSpec::new("/dev/net/tun"));
+        }
+        self.disk_size_gb = Some(
+            self.disk_size_gb
+                .map_or(DOCKER_PROFILE_DISK_GB, |gb| gb.max(DOCKER_PROFILE_DISK_GB)),
+        );
+        self
+    }
+
+    /// Apply the minimal device profile for pure-compute boxes: the VM is
+    /// built without network or console devices, which boots faster and
+    /// leaves the guest less device surface.
+    ///
+    /// The box keeps its disk and the agent channel but has loopback
+    /// networking only, so port mappings and SSH are rejected.
+    pub fn with_minimal_devices(mut self) -> Self {
+        self.devices = DeviceProfile::minimal();
+        self
+    }
+
+    /// Make runs of the box as repeatable as the platform allows, for
+    /// reproducing flaky failures and for graders that rerun the same task.
+    ///
+    /// The box gets a single vCPU and no network device, its clock starts
+    /// at [`Determinism::clock_start_secs`] on every boot, and ASLR is off
+    /// in the guest. `seed` is handed to the container as `BOXLITE_SEED`
+    /// and, unless already set, `PYTHONHASHSEED`; the kernel RNG cannot be
+    /// seeded, so programs reading `/dev/urandom` still see fresh bytes.
+    /// Thread interleaving and timing are not made repeatable either.
+    pub fn with_deterministic(mut self, seed: u64) -> Self {
+        self.deterministic = Some(Determinism {
+            seed,
+            ..Default::default()
+        });
+        self.cpus = Some(1);
+        self.devices.network = false;
+        for (key, value) in [
+            ("BOXLITE_SEED", seed.to_string()),
+            // Python accepts hash seeds up to 2^32 - 1
+            ("PYTHONHASHSEED", (seed % (1 << 32)).to_string()),
+        ] {
+            if !self.env.iter().any(|(k, _)| k == key) {
+                self.env.push((key.to_string(), value));
+            }
+        }
+        self
+    }
+
+    /// Mask and make read-only the kernel interfaces runc hides by default,
+    /// such as `/proc/kcore` and `/proc/sys`, on top of any paths already
+    /// listed.
+    ///
+    /// Not the default because container engines and systemd in the box
+    /// may need to write `/proc/sys`.
+    pub fn with_default_path_masks(mut self) -> Self {
+        for (paths, defaults) in [
+            (&mut self.masked_paths, DEFAULT_MASKED_PATHS),
+            (&mut self.readonly_paths, DEFAULT_READONLY_PATHS),
+        ] {
+            for path in defaults {
+                if !paths.iter().any(|p| p == path) {
+                    paths.push(path.to_string());
+                }
+            }
+        }
+        self
+    }
+
+    /// Environment from `env_files` and `env`, later entries winning.
+    pub fn resolved_env(&self) -> BoxliteResult<Vec<(String, String)>> {
+        let mut env = Vec::new();
+        for path in &self.env_files {
+            env.extend(crate::util::dotenv::load(path)?);
+        }
+        env.extend(self.env.iter().cloned());
+        Ok(env)
+    }
+
+    /// Sanitize and validate options.
+    pub fn sanitize(&self) -> BoxliteResult<()> {
+        if self.idle_timeout_secs == Some(0) {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "idle_timeout_secs must be greater than zero".to_string(),
+            ));
+        }
+        if self.ttl_secs == Some(0) {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "ttl_secs must be greater than zero".to_string(),
+            ));
+        }
+
+        self.io_limits.validate()?;
+        if let Some(queue) = &self.queue {
+            queue.validate()?;
+        }
+        if self.low_memory_percent >= 100 {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "low_memory_percent must be below 100".to_string(),
+            ));
+        }
+        if self.pressure_percent > 100 {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "pressure_percent must be at most 100".to_string(),
+            ));
+        }
+
+        if self.rpc_recording.is_some() && self.rpc_replay.is_some() {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "rpc_recording and rpc_replay cannot both be set".to_string(),
+            ));
+        }
+        if self.deterministic.is_some() && (self.cpus != Some(1) || self.devices.network) {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "deterministic boxes need a single vCPU and no network device".to_string(),
+            ));
+        }
+        if !self.devices.network
+            && (!self.ports.is_empty()
+                || self.ssh.is_some()
+                || !self.runtimes.is_empty()
+                || !self.network_mounts.is_empty())
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "port mappings, ssh, runtimes and network mounts need the network device"
+                    .to_string(),
+            ));
+        }
+        if let Some(domain) = &self.package_cache_domain
+            && !is_volume_name(domain)
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!("invalid package_cache_domain '{}'", domain),
+            ));
+        }
+        for mount in &self.network_mounts {
+            mount.validate()?;
+        }
+        for volume in &self.object_volumes {
+            volume.validate()?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        if !self.object_volumes.is_empty() {
+            return Err(boxlite_shared::errors::BoxliteError::Unsupported(
+                "object_volumes are only supported on Linux".to_string(),
+            ));
+        }
+        if self.encrypted_volumes.len() > MAX_ENCRYPTED_VOLUMES {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "at most {} encrypted volumes can be attached to a box",
+                    MAX_ENCRYPTED_VOLUMES
+                ),
+            ));
+        }
+        let mut names = HashSet::new();
+        for volume in &self.encrypted_volumes {
+            volume.validate()?;
+            if !names.insert(volume.name.as_str()) {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!("encrypted volume '{}' is attached twice", volume.name),
+                ));
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        if !self.encrypted_volumes.is_empty() {
+            return Err(boxlite_shared::errors::BoxliteError::Unsupported(
+                "encrypted_volumes are only supported on Linux".to_string(),
+            ));
+        }
+        if let Some(home) = &self.home_volume {
+            home.validate()?;
+        }
+        if !self.dns_services.is_empty() && self.dns_network.is_none() {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "dns_services need a dns_network".to_string(),
+            ));
+        }
+        if let Some(firewall) = &self.firewall {
+            if !self.devices.network {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    "firewall needs the network device".to_string(),
+                ));
+            }
+            firewall.validate()?;
+        }
+        for label in self.dns_network.iter().chain(&self.dns_services) {
+            if !crate::net::dns::is_label(label) {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!("invalid DNS name '{}'", label),
+                ));
+            }
+        }
+
+        // Surface unreadable or malformed env files at creation
+        self.resolved_env()?;
+
+        let mut task_names = std::collections::HashSet::new();
+        for task in &self.scheduled_tasks {
+            task.validate()?;
+            if !task_names.insert(task.name.as_str()) {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!("duplicate scheduled task name '{}'", task.name),
+                ));
+            }
+        }
+
+        let mut step_names = std::collections::HashSet::new();
+        for step in &self.setup_steps {
+            step.validate()?;
+            if !step_names.insert(step.name.as_str()) {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!("duplicate setup step name '{}'", step.name),
+                ));
+            }
+        }
+
+        if let Some(probe) = &self.readiness_probe {
+            probe.validate("readiness")?;
+        }
+        if let Some(probe) = &self.liveness_probe {
+            probe.validate("liveness")?;
+        }
+
+        if let Some(drop_dir) = self.sharing.as_ref().and_then(|s| s.drop_dir.as_deref())
+            && !drop_dir.starts_with('/')
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "sharing drop_dir must be an absolute path, got '{}'",
+                    drop_dir
+                ),
+            ));
+        }
+
+        if let Some(path) = self
+            .masked_paths
+            .iter()
+            .chain(&self.readonly_paths)
+            .find(|path| !is_container_path(path))
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "masked and read-only paths must be absolute without '..', got '{}'",
+                    path
+                ),
+            ));
+        }
+
+        for node in &self.device_policy.nodes {
+            node.validate()?;
+            if node.path == "/dev/fuse" && !self.fuse {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    "/dev/fuse is controlled by the fuse option".to_string(),
+                ));
+            }
+        }
+
+        // overlayfs storage drivers cannot layer on virtio-fs
+        if self.nested_containers
+            && let Some(volume) = self.volumes.iter().find(|volume| {
+                ENGINE_STORAGE_DIRS
+                    .iter()
+                    .any(|dir| Path::new(dir).starts_with(Path::new(&volume.guest_path)))
+            })
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "volume at {} would hide container engine storage",
+                    volume.guest_path
+                ),
+            ));
+        }
+
+        if let Some(volume) = self
+            .volumes
+            .iter()
+            .find(|volume| volume.quota_bytes.is_some() && volume.read_only)
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "read-only volume at {} cannot have a quota",
+                    volume.guest_path
+                ),
+            ));
+        }
+        for volume in &self.volumes {
+            if let Some(hidden) = volume.hidden.iter().find(|path| !is_volume_subpath(path)) {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!(
+                        "hidden path '{}' of volume at {} must be a relative path inside it",
+                        hidden, volume.guest_path
+                    ),
+                ));
+            }
+        }
+        let mut tags = HashSet::new();
+        for (index, volume) in self.volumes.iter().enumerate() {
+            let tag = volume.share_tag(index);
+            if !is_share_tag(&tag) {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!(
+                        "tag '{}' of volume at {} must be 1 to {} ASCII letters, digits, '-' or '_'",
+                        tag, volume.guest_path, MAX_SHARE_TAG_LEN
+                    ),
+                ));
+            }
+            if RESERVED_SHARE_TAGS.contains(&tag.as_str())
+                || tag.starts_with(NETWORK_VOLUME_PREFIX)
+                || tag.starts_with(OBJECT_VOLUME_PREFIX)
+                || tag.starts_with(ENCRYPTED_VOLUME_PREFIX)
+                || !tags.insert(tag.clone())
+            {
+                return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                    format!(
+                        "tag '{}' of volume at {} is already in use",
+                        tag, volume.guest_path
+                    ),
+                ));
+            }
+        }
+        if let Some(volume) = self
+            .volumes
+            .iter()
+            .find(|volume| volume.quota_bytes == Some(0))
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "quota_bytes for volume at {} must be greater than zero",
+                    volume.guest_path
+                ),
+            ));
+        }
+        #[cfg(not(target_os = "linux"))]
+        if self
+            .volumes
+            .iter()
+            .any(|volume| volume.quota_bytes.is_some())
+        {
+            return Err(boxlite_shared::errors::BoxliteError::Unsupported(
+                "volume quotas are only supported on Linux".to_string(),
+            ));
+        }
+
+        if let Some(ssh) = &self.ssh
+            && ssh.authorized_keys.is_empty()
+        {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "ssh requires at least one authorized key".to_string(),
+            ));
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        if self.isolate_mounts {
+            return Err(boxlite_shared::errors::BoxliteError::Unsupported(
+                "isolate_mounts is only supported on Linux".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// SSH server started in the box.
+///
+/// Host keys are generated in guest memory on each boot, so clients see a
+/// new host key after a restart. Only public key login as root is allowed.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SshOptions {
+    /// Public keys (authorized_keys lines) allowed to log in.
+    pub authorized_keys: Vec<String>,
+    /// Host port forwarded to the box's SSH server.
+    pub host_port: u16,
+}
+
+impl SshOptions {
+    /// Port sshd listens on inside the box.
+    pub const GUEST_PORT: u16 = 22;
+}
+
+/// Device nodes in the box's `/dev`.
+///
+/// By default `/dev` is a small tmpfs with the standard nodes (null, zero,
+/// random, tty, ...) plus devices implied by other options (e.g. `/dev/kvm`
+/// for nested virtualization). Containers in the VM run without cgroups,
+/// so access is governed by which nodes exist and their permissions.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DevicePolicy {
+    /// Mount a devtmpfs at `/dev`, exposing every device the guest kernel
+    /// knows, including the box's own disks. For images that expect a
+    /// udev-populated `/dev`.
+    #[serde(default)]
+    pub devtmpfs: bool,
+    /// Extra character devices, created from sysfs if missing in the guest.
+    #[serde(default)]
+    pub nodes: Vec<DeviceNodeSpec>,
+}
+
+/// A character device node and its permissions in the box.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DeviceNodeSpec {
+    /// Path under `/dev`, e.g. `/dev/net/tun`.
+    pub path: String,
+    /// Permission bits. Defaults to 0o666.
+    #[serde(default = "default_device_mode")]
+    pub mode: u32,
+    #[serde(default)]
+    pub uid: u32,
+    #[serde(default)]
+    pub gid: u32,
+}
+
+fn default_device_mode() -> u32 {
+    0o666
+}
+
+impl DeviceNodeSpec {
+    /// A node readable and writable by everyone, owned by root.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            mode: default_device_mode(),
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let relative = self.path.strip_prefix("/dev/").unwrap_or_default();
+        if relative.is_empty() || relative.split('/').any(|c| c.is_empty() || c == "..") {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!("device path must be a file under /dev, got '{}'", self.path),
+            ));
+        }
+        if self.mode > 0o777 {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!(
+                    "device {} mode {:o} is not a permission mask",
+                    self.path, self.mode
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Optional virtio devices of a box's VM.
+///
+/// The root disk, the agent's vsock channel and the rng and balloon
+/// devices libkrun always attaches are not optional.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DeviceProfile {
+    /// virtio-net to the host network backend. Without it the guest has
+    /// loopback only: no outbound access and no port mappings.
+    pub network: bool,
+    /// virtio-console carrying the kernel and init log.
+    pub console: bool,
+}
+
+impl Default for DeviceProfile {
+    fn default() -> Self {
+        Self {
+            network: true,
+            console: true,
+        }
+    }
+}
+
+impl DeviceProfile {
+    /// Only the devices a box cannot run without.
+    pub fn minimal() -> Self {
+        Self {
+            network: false,
+            console: false,
+        }
+    }
+}
+
+/// Settings of a deterministic box (see [`BoxOptions::with_deterministic`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Determinism {
+    /// Seed handed to the container's programs.
+    pub seed: u64,
+    /// Wall clock the guest starts at, in seconds since the epoch.
+    /// Defaults to 2000-01-01T00:00:00Z.
+    pub clock_start_secs: u64,
+}
+
+impl Default for Determinism {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            clock_start_secs: 946_684_800,
+        }
+    }
+}
+
+/// What a box boots as its container's init process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InitMode {
+    /// The image's entrypoint and command.
+    #[default]
+    Entrypoint,
+    /// The image's systemd, for images whose services need it.
+    ///
+    /// The image entrypoint is ignored; execs and volumes work as usual,
+    /// and systemd sees the volumes as mount units. Fails at start if the
+    /// image has no systemd.
+    Systemd,
+}
+
+/// Scheduling class of a box's VMM process on the host.
+///
+/// Each class maps to a cgroup `cpu.weight` and `io.weight` when the
+/// runtime has a delegated cgroup (`BoxliteOptions::cgroup_parent`), and
+/// always to a nice value and I/O priority. Raising a box above `Normal`
+/// by nice value needs `CAP_SYS_NICE`; without it only the weights apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoxPriority {
+    /// Interactive boxes that must stay responsive under load.
+    Realtime,
+    High,
+    #[default]
+    Normal,
+    /// Boxes that should only use capacity others leave idle.
+    Batch,
+}
+
+/// A language runtime a box can be provisioned with.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeProfile {
+    /// `python3` and pip, with a virtualenv at `/opt/venv` first on `PATH`.
+    Python,
+    /// `node` and `npm`.
+    Node,
+    /// The Go toolchain.
+    Go,
+    /// `rustc` and `cargo`.
+    Rust,
+}
+
+/// Which way the shared clipboard may be used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardPolicy {
+    #[default]
+    Disabled,
+    /// The host may set the clipboard; reading it back is refused.
+    HostToBox,
+    /// The host may read the clipboard; setting it is refused.
+    BoxToHost,
+    Bidirectional,
+}
+
+impl ClipboardPolicy {
+    pub fn allows_host_to_box(self) -> bool {
+        matches!(self, Self::HostToBox | Self::Bidirectional)
+    }
+
+    pub fn allows_box_to_host(self) -> bool {
+        matches!(self, Self::BoxToHost | Self::Bidirectional)
+    }
+}
+
+/// Caps on a box's disk I/O, per host device. `None` means no cap.
+///
+/// Applied as cgroup v2 `io.max` on every host device holding the box's
+/// disks, the images they are backed by, or its volume directories. The
+/// VMM serves both the box's block devices and its virtio-fs shares from
+/// its own process, so the caps cover scratch and shared storage alike.
+/// Needs `BoxliteOptions::cgroup_parent` with the io controller delegated;
+/// without it the caps are not enforced and a warning is logged.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct IoLimits {
+    /// Bytes read per second.
+    #[serde(default)]
+    pub read_bps: Option<u64>,
+    /// Bytes written per second.
+    #[serde(default)]
+    pub write_bps: Option<u64>,
+    /// Read operations per second.
+    #[serde(default)]
+    pub read_iops: Option<u64>,
+    /// Write operations per second.
+    #[serde(default)]
+    pub write_iops: Option<u64>,
+}
+
+impl IoLimits {
+    /// Whether no cap is set.
+    pub fn is_empty(&self) -> bool {
+        self.entries().next().is_none()
+    }
+
+    /// The caps that are set, as `io.max` keys and values.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&'static str, u64)> {
+        [
+            ("rbps", self.read_bps),
+            ("wbps", self.write_bps),
+            ("riops", self.read_iops),
+            ("wiops", self.write_iops),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        if self.entries().any(|(_, value)| value == 0) {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "io_limits must be greater than zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A named queue limiting how many of its boxes run at once.
+///
+/// Boxes in the same queue start in the order they were submitted, at most
+/// `max_parallel` at a time; the rest wait until a running one stops. The
+/// limit is set by the first box to use the queue while it has boxes
+/// running or waiting. Queues are per runtime.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BatchQueue {
+    pub name: String,
+    /// Boxes from the queue that may run at once (default 1).
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+}
+
+fn default_max_parallel() -> usize {
+    1
+}
+
+impl BatchQueue {
+    pub fn new(name: impl Into<String>, max_parallel: usize) -> Self {
+        Self {
+            name: name.into(),
+            max_parallel,
+        }
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        if self.name.is_empty() {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "queue name must not be empty".to_string(),
+            ));
+        }
+        if self.max_parallel == 0 {
+            return Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                "queue max_parallel must be greater than zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Per-box policy for sharing data with a host desktop.
+///
+/// The clipboard is a file at `/run/boxlite-clipboard/content` in the box;
+/// dropped files land in `drop_dir`. Both are in-memory (tmpfs) and
+/// discarded when the box stops.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SharingOptions {
+    #[serde(default)]
+    pub clipboard: ClipboardPolicy,
+    /// Box path where dropped files land. None disables drops.
+    #[serde(default)]
+    pub drop_dir: Option<String>,
+    /// Largest file accepted as a drop. Defaults to no limit.
+    #[serde(default)]
+    pub max_drop_bytes: Option<u64>,
+}
+
+/// Limits on the artifacts a box keeps, applied each time new ones land.
+///
+/// The oldest archives go first, and the newest is always kept. `None`
+/// means no limit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactRetention {
+    /// Most archives kept.
+    #[serde(default)]
+    pub max_count: Option<usize>,
+    /// Archives older than this are deleted.
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Most bytes all archives may take together.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+/// Command run by the guest agent on a fixed interval.
+///
+/// The first run happens one interval after registration. A run that
+/// outlasts the interval delays the next one instead of overlapping it.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledTask {
+    /// Unique name, used to inspect or remove the task.
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    /// Seconds between runs (at least 1).
+    pub interval_secs: u64,
+    /// Kill a run that takes longer than this. Defaults to no limit.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl ScheduledTask {
+    pub fn new(name: impl Into<String>, command: impl Into<String>, interval_secs: u64) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            interval_secs,
+            timeout_secs: None,
+        }
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let invalid = |reason: &str| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!("scheduled task '{}': {}", self.name, reason),
+            ))
+        };
+        if self.name.is_empty() || self.command.is_empty() {
+            return invalid("name and command are required");
+        }
+        if self.interval_secs == 0 {
+            return invalid("interval_secs must be greater than zero");
+        }
+        if self.timeout_secs == Some(0) {
+            return invalid("timeout_secs must be greater than zero");
+        }
+        Ok(())
+    }
+}
+
+/// One-shot command run in the box before its workload starts.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SetupStep {
+    /// Unique name, reported when the step fails.
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+    /// Fail the step if it takes longer than this. Defaults to no limit.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl SetupStep {
+    pub fn new(name: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            timeout_secs: None,
+        }
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let invalid = |reason: &str| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!("setup step '{}': {}", self.name, reason),
+            ))
+        };
+        if self.name.is_empty() || self.command.is_empty() {
+            return invalid("name and command are required");
+        }
+        if self.timeout_secs == Some(0) {
+            return invalid("timeout_secs must be greater than zero");
+        }
+        Ok(())
+    }
+}
+
+/// A health check the guest agent runs against the box.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Probe {
+    pub check: ProbeCheck,
+    /// Seconds between checks. Defaults to 10.
+    pub interval_secs: u64,
+    /// Seconds before a check counts as failed. Defaults to 1.
+    pub timeout_secs: u64,
+    /// Seconds after the box starts before the first check.
+    #[serde(default)]
+    pub initial_delay_secs: u64,
+    /// Consecutive failed checks before the probe fails. Defaults to 3.
+    pub failure_threshold: u32,
+}
+
+/// What a [`Probe`] checks, from inside the guest.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ProbeCheck {
+    /// Run a command in the container; passes when it exits 0.
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Passes when a TCP connection to the port succeeds.
+    Tcp { port: u16 },
+    /// Passes on a 2xx or 3xx response to `GET path`.
+    Http { port: u16, path: String },
+}
+
+impl Probe {
+    pub fn new(check: ProbeCheck) -> Self {
+        Self {
+            check,
+            interval_secs: 10,
+            timeout_secs: 1,
+            initial_delay_secs: 0,
+            failure_threshold: 3,
+        }
+    }
+
+    fn validate(&self, kind: &str) -> BoxliteResult<()> {
+        let invalid = |reason: &str| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                format!("{} probe: {}", kind, reason),
+            ))
+        };
+        match &self.check {
+            ProbeCheck::Command { command, .. } if command.is_empty() => {
+                return invalid("command is required");
+            }
+            ProbeCheck::Tcp { port: 0 } | ProbeCheck::Http { port: 0, .. } => {
+                return invalid("port must be greater than zero");
+            }
+            ProbeCheck::Http { path, .. } if !path.starts_with('/') => {
+                return invalid("HTTP path must start with '/'");
+            }
+            _ => {}
+        }
+        if self.interval_secs == 0 || self.timeout_secs == 0 {
+            return invalid("interval_secs and timeout_secs must be greater than zero");
+        }
+        if self.failure_threshold == 0 {
+            return invalid("failure_threshold must be greater than zero");
+        }
+        Ok(())
+    }
 }
 
-fn default_auto_remove() -> bool {
-    false
+/// What to do with a box whose liveness probe fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum UnhealthyPolicy {
+    /// Only report the box as unhealthy.
+    #[default]
+    Ignore,
+    /// Restart the box, at most `max_restarts` times if set.
+    Restart { max_restarts: Option<u32> },
 }
 
-impl Default for BoxOptions {
-    fn default() -> Self {
-        Self {
-            cpus: None,
-            memory_mib: None,
-            disk_size_gb: None,
-            working_dir: None,
-            env: Vec::new(),
-            rootfs: RootfsSpec::default(),
-            volumes: Vec::new(),
-            network: NetworkSpec::default(),
-            ports: Vec::new(),
-            isolate_mounts: false,
-            auto_remove: default_auto_remove(),
-        }
-    }
+/// GPU device exposed to the guest.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GpuSpec {
+    /// Paravirtualized virtio-gpu rendered on the host GPU.
+    ///
+    /// `venus` selects Vulkan (Venus) instead of OpenGL (virgl), which is
+    /// what compute workloads need.
+    VirtioGpu { venus: bool },
 }
 
-impl BoxOptions {
-    /// Sanitize and validate options.
-    pub fn sanitize(&self) -> BoxliteResult<()> {
-        #[cfg(not(target_os = "linux"))]
-        if self.isolate_mounts {
-            return Err(boxlite_shared::errors::BoxliteError::Unsupported(
-                "isolate_mounts is only supported on Linux".to_string(),
-            ));
+impl GpuSpec {
+    /// Kernel modules the guest loads before the container starts.
+    pub fn driver_modules(&self) -> Vec<String> {
+        match self {
+            GpuSpec::VirtioGpu { .. } => vec!["virtio_gpu".to_string()],
         }
-        Ok(())
     }
 }
 
@@ -122,6 +1971,415 @@ pub struct VolumeSpec {
     pub host_path: String,
     pub guest_path: String,
     pub read_only: bool,
+    /// Cap on the directory's total size in bytes, writes included.
+    ///
+    /// The volume is shared through a host-side FUSE mount that fails
+    /// writes past the cap with EDQUOT until usage drops again, so the cap
+    /// holds whatever runs in the guest. The mount is served by the process
+    /// that started the box. Linux only. The box's own disk is already
+    /// bounded by `disk_size_gb`.
+    #[serde(default)]
+    pub quota_bytes: Option<u64>,
+    /// Box user and group the directory's owner appears as.
+    ///
+    /// Files owned by the owner of `host_path` show up as `uid`/`gid` in the
+    /// box, and files the box creates as that user land on the host owned
+    /// by the directory's owner. Files owned by anyone else show up as
+    /// `nobody`. Needs a guest kernel with idmapped virtio-fs mounts.
+    #[serde(default)]
+    pub owner: Option<VolumeOwner>,
+    /// Paths inside the volume, relative to it, hidden from the box.
+    ///
+    /// Each is covered by an empty read-only directory (or an empty file),
+    /// so secrets such as `.env` or `.git` in a shared project stay on the
+    /// host. Paths that do not exist when the box starts are not hidden.
+    #[serde(default)]
+    pub hidden: Vec<String>,
+    /// Virtio-fs tag the directory is exported under.
+    ///
+    /// Defaults to `uservol<N>`, N being the volume's index in
+    /// `BoxOptions::volumes`. The tag names the share in the guest, so it
+    /// must be unique within the box, at most 36 bytes, and made of ASCII
+    /// letters, digits, `-` and `_`.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Start the box without this volume if it cannot be mounted.
+    ///
+    /// The guest retries a failing virtio-fs mount and then tries 9p; if
+    /// both fail, an optional volume is replaced by an empty tmpfs and the
+    /// box boots degraded instead of failing. `LiteBox::shares()` reports
+    /// which strategy each volume ended up with.
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl VolumeSpec {
+    /// The tag of the volume at `index` in `BoxOptions::volumes`.
+    pub(crate) fn share_tag(&self, index: usize) -> String {
+        self.tag
+            .clone()
+            .unwrap_or_else(|| format!("uservol{}", index))
+    }
+}
+
+/// Remote filesystem protocol of a [`NetworkMountSpec`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkFilesystem {
+    /// NFS version 4
+    Nfs,
+    /// SMB/CIFS
+    Cifs,
+}
+
+/// A remote filesystem the guest mounts into the box.
+///
+/// The box reaches the server through its NAT like any other outbound
+/// connection, so NFS exports must allow unprivileged source ports
+/// (`insecure`).
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct NetworkMountSpec {
+    pub filesystem: NetworkFilesystem,
+    /// `server:/export` for NFS, `//server/share` for CIFS.
+    pub source: String,
+    /// Absolute mount point in the container.
+    pub guest_path: String,
+    #[serde(default)]
+    pub read_only: bool,
+    /// Extra mount options, comma-separated as for `mount -o`
+    /// (e.g. `vers=3.0` for CIFS).
+    #[serde(default)]
+    pub options: Option<String>,
+    /// CIFS login; without one the share is mounted as guest.
+    #[serde(default)]
+    pub credentials: Option<MountCredentials>,
+}
+
+/// Login for a CIFS [`NetworkMountSpec`].
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MountCredentials {
+    pub username: String,
+    /// Host environment variable holding the password.
+    ///
+    /// Read each time the box starts, so the password is never stored
+    /// with the box.
+    pub password_env: String,
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Volume names network mounts get in the guest, followed by their index.
+const NETWORK_VOLUME_PREFIX: &str = "netvol";
+
+impl NetworkMountSpec {
+    /// The guest volume name of the mount at `index` in
+    /// `BoxOptions::network_mounts`.
+    pub(crate) fn volume_name(index: usize) -> String {
+        format!("{}{}", NETWORK_VOLUME_PREFIX, index)
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let invalid = |message: String| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                message,
+            ))
+        };
+        let source_ok = match self.filesystem {
+            // An IPv6 server is written in brackets
+            NetworkFilesystem::Nfs => match self.source.strip_prefix('[') {
+                Some(rest) => rest.split_once("]:"),
+                None => self.source.split_once(':'),
+            }
+            .is_some_and(|(host, path)| !host.is_empty() && path.starts_with('/')),
+            NetworkFilesystem::Cifs => self
+                .source
+                .strip_prefix("//")
+                .and_then(|rest| rest.split_once('/'))
+                .is_some_and(|(host, share)| !host.is_empty() && !share.is_empty()),
+        };
+        if !source_ok {
+            let expected = match self.filesystem {
+                NetworkFilesystem::Nfs => "server:/export",
+                NetworkFilesystem::Cifs => "//server/share",
+            };
+            return invalid(format!(
+                "network mount source '{}' must look like {}",
+                self.source, expected
+            ));
+        }
+        if !is_container_path(&self.guest_path) {
+            return invalid(format!(
+                "network mount path '{}' must be an absolute container path",
+                self.guest_path
+            ));
+        }
+        if let Some(credentials) = &self.credentials {
+            if self.filesystem != NetworkFilesystem::Cifs {
+                return invalid(format!(
+                    "credentials for {} are only supported for CIFS",
+                    self.source
+                ));
+            }
+            if credentials.username.is_empty() || credentials.password_env.is_empty() {
+                return invalid(format!(
+                    "credentials for {} need a username and password_env",
+                    self.source
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An S3-compatible bucket (or a prefix of one) mounted into the box.
+///
+/// The host serves the mount through FUSE from a cache in the box
+/// directory: listing a directory lists its prefix, and opening a file
+/// downloads the object. The cache starts empty each time the box starts.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ObjectVolumeSpec {
+    /// Service URL, e.g. `https://s3.eu-west-1.amazonaws.com` or
+    /// `http://minio.local:9000`. Buckets are addressed path-style.
+    pub endpoint: String,
+    pub bucket: String,
+    /// Key prefix mounted as the volume root; empty for the whole bucket.
+    #[serde(default)]
+    pub prefix: String,
+    /// Region requests are signed for. Defaults to `us-east-1`.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Absolute mount point in the container.
+    pub guest_path: String,
+    /// Upload files written in the box when they are closed, and delete
+    /// objects for removed files. Without it the volume is read-only.
+    ///
+    /// Each upload is a single PUT of the whole file, so files are limited
+    /// to 5 GiB.
+    #[serde(default)]
+    pub write_back: bool,
+    /// Access keys; without them requests are anonymous (public buckets).
+    #[serde(default)]
+    pub credentials: Option<ObjectCredentials>,
+}
+
+/// Keys for an [`ObjectVolumeSpec`], named by host environment variables.
+///
+/// The variables are read each time the box starts, so keys are never
+/// stored with the box.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ObjectCredentials {
+    pub access_key_env: String,
+    pub secret_key_env: String,
+    #[serde(default)]
+    pub session_token_env: Option<String>,
+}
+
+/// Volume tags object volumes get, followed by their index.
+const OBJECT_VOLUME_PREFIX: &str = "objvol";
+
+impl ObjectVolumeSpec {
+    /// The virtio-fs tag of the volume at `index` in
+    /// `BoxOptions::object_volumes`.
+    pub(crate) fn tag(index: usize) -> String {
+        format!("{}{}", OBJECT_VOLUME_PREFIX, index)
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let invalid = |message: String| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                message,
+            ))
+        };
+        let host = self
+            .endpoint
+            .strip_prefix("https://")
+            .or_else(|| self.endpoint.strip_prefix("http://"));
+        if !host.is_some_and(|host| !host.is_empty() && !host.trim_end_matches('/').contains('/')) {
+            return invalid(format!(
+                "object volume endpoint '{}' must be an http(s) URL without a path",
+                self.endpoint
+            ));
+        }
+        if self.bucket.is_empty() || self.bucket.contains('/') {
+            return invalid(format!("invalid object volume bucket '{}'", self.bucket));
+        }
+        if self.prefix.starts_with('/') {
+            return invalid(format!(
+                "object volume prefix '{}' must not start with '/'",
+                self.prefix
+            ));
+        }
+        if !is_container_path(&self.guest_path) {
+            return invalid(format!(
+                "object volume path '{}' must be an absolute container path",
+                self.guest_path
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A named volume encrypted at rest, attached to the box.
+///
+/// The volume is an ext4 filesystem on a disk image under
+/// `~/.boxlite/volumes/{name}`, encrypted in 4 KiB blocks with AES-256-GCM.
+/// The host decrypts it through a FUSE mount while the box runs, so the
+/// key never enters the guest and nothing readable is written to disk.
+/// The first box to attach a name creates the volume with that key.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct EncryptedVolumeSpec {
+    pub name: String,
+    /// Absolute mount point in the container.
+    pub guest_path: String,
+    /// Size of the volume when it is created; an existing volume keeps
+    /// its size.
+    #[serde(default = "default_encrypted_volume_size_gb")]
+    pub size_gb: u64,
+    pub key: VolumeKeySource,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Where the 256-bit key of an [`EncryptedVolumeSpec`] comes from.
+///
+/// Keys are fetched every time the box starts and never stored with it.
+/// Each form yields either 32 raw bytes or 64 hex digits.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum VolumeKeySource {
+    /// A key file, readable only by its owner.
+    File { path: PathBuf },
+    /// A secret in the desktop keyring, looked up with `secret-tool`.
+    Keyring { service: String, account: String },
+    /// An external program, such as a KMS client, that prints the key.
+    /// It runs with `BOXLITE_VOLUME` set to the volume name.
+    Plugin { command: Vec<String> },
+}
+
+/// Volume names encrypted volumes get, followed by their index.
+const ENCRYPTED_VOLUME_PREFIX: &str = "encvol";
+
+/// Block devices left once the rootfs disks are attached.
+const MAX_ENCRYPTED_VOLUMES: usize = 16;
+
+fn default_encrypted_volume_size_gb() -> u64 {
+    10
+}
+
+impl EncryptedVolumeSpec {
+    /// The volume name of the volume at `index` in
+    /// `BoxOptions::encrypted_volumes`.
+    pub(crate) fn volume_name(index: usize) -> String {
+        format!("{}{}", ENCRYPTED_VOLUME_PREFIX, index)
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let invalid = |message: String| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                message,
+            ))
+        };
+        if !is_volume_name(&self.name) {
+            return invalid(format!(
+                "encrypted volume name '{}' must be up to 64 ASCII letters, digits, '-', '_' or '.'",
+                self.name
+            ));
+        }
+        if !is_container_path(&self.guest_path) {
+            return invalid(format!(
+                "encrypted volume path '{}' must be an absolute container path",
+                self.guest_path
+            ));
+        }
+        if self.size_gb == 0 {
+            return invalid(format!(
+                "encrypted volume '{}' must be at least 1 GB",
+                self.name
+            ));
+        }
+        match &self.key {
+            VolumeKeySource::File { path } if !path.is_absolute() => invalid(format!(
+                "key file of encrypted volume '{}' must be an absolute path",
+                self.name
+            )),
+            VolumeKeySource::Plugin { command } if command.is_empty() => invalid(format!(
+                "key plugin of encrypted volume '{}' has no command",
+                self.name
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A named volume holding the workload's home directory.
+///
+/// It is a plain directory under `~/.boxlite/volumes/{name}/home`, shared
+/// into the box and mounted at `path`. The first box to mount it, while
+/// it is still empty, fills it from the image: the image's own home
+/// directory if it has one with files in it, otherwise `/etc/skel`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HomeVolume {
+    pub name: String,
+    /// Absolute mount point in the container. Defaults to `/root`, the
+    /// home of the user boxes run as; set it for images whose workload
+    /// runs as another user.
+    #[serde(default = "default_home_path")]
+    pub path: String,
+}
+
+/// Volume name the home volume gets in the box.
+pub(crate) const HOME_VOLUME_TAG: &str = "homevol";
+
+fn default_home_path() -> String {
+    "/root".to_string()
+}
+
+impl HomeVolume {
+    /// Home volume `name` mounted at `/root`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            path: default_home_path(),
+        }
+    }
+
+    fn validate(&self) -> BoxliteResult<()> {
+        let invalid = |message: String| {
+            Err(boxlite_shared::errors::BoxliteError::InvalidArgument(
+                message,
+            ))
+        };
+        if !is_volume_name(&self.name) {
+            return invalid(format!(
+                "home volume name '{}' must be up to 64 ASCII letters, digits, '-', '_' or '.'",
+                self.name
+            ));
+        }
+        if !is_container_path(&self.path) {
+            return invalid(format!(
+                "home volume path '{}' must be an absolute container path",
+                self.path
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` can name a volume under `~/.boxlite/volumes`.
+fn is_volume_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Box-side owner of a volume; see [`VolumeSpec::owner`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct VolumeOwner {
+    pub uid: u32,
+    pub gid: u32,
 }
 
 /// Network isolation options.
@@ -133,7 +2391,9 @@ pub enum NetworkSpec {
     // Custom(String),
 }
 
-#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub enum PortProtocol {
     #[default]
     Tcp,
@@ -141,6 +2401,28 @@ pub enum PortProtocol {
     // Sctp,
 }
 
+/// A non-empty relative path that stays inside the directory it is joined to.
+fn is_volume_subpath(path: &str) -> bool {
+    let path = Path::new(path);
+    path.components().next().is_some()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// A virtio-fs tag that is also safe as a directory name in the guest.
+fn is_share_tag(tag: &str) -> bool {
+    (1..=MAX_SHARE_TAG_LEN).contains(&tag.len())
+        && tag
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// An absolute path below the container root with no `.` or `..` steps.
+fn is_container_path(path: &str) -> bool {
+    path.strip_prefix('/').is_some_and(is_volume_subpath)
+}
+
 fn default_protocol() -> PortProtocol {
     PortProtocol::Tcp
 }
@@ -154,3 +2436,577 @@ pub struct PortSpec {
     pub protocol: PortProtocol,
     pub host_ip: Option<String>, // Optional bind IP, defaults to 0.0.0.0/:: if None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_dedup_defaults_on() {
+        assert!(BoxOptions::default().memory_dedup);
+
+        // Options persisted before the field existed keep dedup enabled
+        let mut value = serde_json::to_value(BoxOptions::default()).unwrap();
+        value.as_object_mut().unwrap().remove("memory_dedup");
+        let options: BoxOptions = serde_json::from_value(value).unwrap();
+        assert!(options.memory_dedup);
+    }
+
+    #[test]
+    fn test_io_limits_validation() {
+        let mut options = BoxOptions::default();
+        assert!(options.io_limits.is_empty());
+        options.io_limits.write_bps = Some(10 * 1024 * 1024);
+        assert!(options.sanitize().is_ok());
+        options.io_limits.read_iops = Some(0);
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_resolved_env_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.env");
+        let local = dir.path().join("local.env");
+        std::fs::write(&base, "A=base\nB=base\nC=base\n").unwrap();
+        std::fs::write(&local, "B=local\nC=local\n").unwrap();
+
+        let options = BoxOptions {
+            env_files: vec![base, local],
+            env: vec![("C".to_string(), "explicit".to_string())],
+            ..Default::default()
+        };
+        let env: HashMap<_, _> = options.resolved_env().unwrap().into_iter().collect();
+        assert_eq!(env["A"], "base");
+        assert_eq!(env["B"], "local");
+        assert_eq!(env["C"], "explicit");
+
+        let missing = BoxOptions {
+            env_files: vec![dir.path().join("missing.env")],
+            ..Default::default()
+        };
+        assert!(missing.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_ssh_requires_authorized_key() {
+        let mut options = BoxOptions {
+            ssh: Some(SshOptions {
+                authorized_keys: Vec::new(),
+                host_port: 2222,
+            }),
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_err());
+
+        options.ssh.as_mut().unwrap().authorized_keys =
+            vec!["ssh-ed25519 AAAA user@host".to_string()];
+        assert!(options.sanitize().is_ok());
+    }
+
+    #[test]
+    fn test_package_cache_domain_must_be_plain_name() {
+        let mut options = BoxOptions {
+            package_cache: true,
+            package_cache_domain: Some("../packages".to_string()),
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_err());
+
+        options.package_cache_domain = Some("ci-runners".to_string());
+        assert!(options.sanitize().is_ok());
+    }
+
+    #[test]
+    fn test_sharing_drop_dir_must_be_absolute() {
+        let mut options = BoxOptions {
+            sharing: Some(SharingOptions {
+                drop_dir: Some("drops".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_err());
+
+        options.sharing.as_mut().unwrap().drop_dir = Some("/home/user/Drops".to_string());
+        assert!(options.sanitize().is_ok());
+        assert!(!ClipboardPolicy::HostToBox.allows_box_to_host());
+        assert!(ClipboardPolicy::Bidirectional.allows_box_to_host());
+    }
+
+    #[test]
+    fn test_minimal_devices_reject_networking() {
+        let options = BoxOptions::default().with_minimal_devices();
+        assert_eq!(options.devices, DeviceProfile::minimal());
+        assert!(options.sanitize().is_ok());
+
+        let options = BoxOptions {
+            ports: vec![PortSpec {
+                guest_port: 80,
+                ..Default::default()
+            }],
+            ..BoxOptions::default().with_minimal_devices()
+        };
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_docker_profile() {
+        let options = BoxOptions {
+            disk_size_gb: Some(50),
+            ..Default::default()
+        }
+        .with_docker_profile()
+        .with_docker_profile();
+        assert!(options.nested_containers);
+        assert!(options.fuse);
+        assert_eq!(options.disk_size_gb, Some(50));
+        assert_eq!(options.device_policy.nodes.len(), 1);
+        assert!(options.sanitize().is_ok());
+        assert_eq!(
+            BoxOptions::default().with_docker_profile().disk_size_gb,
+            Some(DOCKER_PROFILE_DISK_GB)
+        );
+
+        // Storage drivers need the box's own disk
+        for guest_path in ["/var/lib/docker", "/var", "/"] {
+            let mut options = options.clone();
+            options.volumes.push(VolumeSpec {
+                host_path: "/srv/data".to_string(),
+                guest_path: guest_path.to_string(),
+                read_only: false,
+                quota_bytes: None,
+                ..Default::default()
+            });
+            assert!(options.sanitize().is_err(), "{}", guest_path);
+        }
+        let mut options = options;
+        options.volumes.push(VolumeSpec {
+            host_path: "/srv/data".to_string(),
+            guest_path: "/var/lib/docker-data".to_string(),
+            read_only: false,
+            quota_bytes: None,
+            ..Default::default()
+        });
+        assert!(options.sanitize().is_ok());
+    }
+
+    #[test]
+    fn test_volume_quota_validation() {
+        let volume = VolumeSpec {
+            host_path: "/srv/data".to_string(),
+            guest_path: "/data".to_string(),
+            read_only: false,
+            quota_bytes: Some(1 << 30),
+            ..Default::default()
+        };
+        let mut options = BoxOptions::default();
+        options.volumes.push(volume.clone());
+        assert_eq!(options.sanitize().is_ok(), cfg!(target_os = "linux"));
+
+        options.volumes[0].quota_bytes = Some(0);
+        assert!(options.sanitize().is_err());
+
+        options.volumes[0] = VolumeSpec {
+            read_only: true,
+            ..volume
+        };
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_volume_hidden_paths_stay_inside() {
+        let mut options = BoxOptions::default();
+        options.volumes.push(VolumeSpec {
+            host_path: "/srv/project".to_string(),
+            guest_path: "/project".to_string(),
+            hidden: vec![".env".to_string(), "config/secrets".to_string()],
+            ..Default::default()
+        });
+        assert!(options.sanitize().is_ok());
+
+        for hidden in ["", "/etc", "../outside", "a/../../b"] {
+            options.volumes[0].hidden = vec![hidden.to_string()];
+            assert!(options.sanitize().is_err(), "{} accepted", hidden);
+        }
+    }
+
+    #[test]
+    fn test_volume_tags() {
+        let volume = |tag: Option<&str>| VolumeSpec {
+            host_path: "/srv/data".to_string(),
+            guest_path: "/data".to_string(),
+            tag: tag.map(String::from),
+            ..Default::default()
+        };
+        let mut options = BoxOptions {
+            volumes: vec![volume(Some("build-cache")), volume(None)],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+        assert_eq!(options.volumes[1].share_tag(1), "uservol1");
+
+        // Clashes with the other volume's default tag
+        options.volumes[0].tag = Some("uservol1".to_string());
+        assert!(options.sanitize().is_err());
+
+        let long = "a".repeat(MAX_SHARE_TAG_LEN + 1);
+        for tag in [
+            "",
+            "a/b",
+            "..",
+            "BoxLiteShared",
+            "pkgcache",
+            "objvol0",
+            long.as_str(),
+        ] {
+            options.volumes = vec![volume(Some(tag))];
+            assert!(options.sanitize().is_err(), "{} accepted", tag);
+        }
+    }
+
+    #[test]
+    fn test_network_mount_validation() {
+        let nfs = NetworkMountSpec {
+            filesystem: NetworkFilesystem::Nfs,
+            source: "nas.local:/exports/data".to_string(),
+            guest_path: "/data".to_string(),
+            read_only: false,
+            options: None,
+            credentials: None,
+        };
+        let mut options = BoxOptions {
+            network_mounts: vec![nfs.clone()],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        for source in ["nas.local", "nas.local:exports", ":/exports", "[fd00::2]/x"] {
+            options.network_mounts[0].source = source.to_string();
+            assert!(options.sanitize().is_err(), "{} accepted", source);
+        }
+        options.network_mounts[0] = NetworkMountSpec {
+            guest_path: "data".to_string(),
+            ..nfs.clone()
+        };
+        assert!(options.sanitize().is_err());
+
+        // Logins are CIFS-only
+        let credentials = MountCredentials {
+            username: "ci".to_string(),
+            password_env: "NAS_PASSWORD".to_string(),
+            domain: None,
+        };
+        options.network_mounts[0] = NetworkMountSpec {
+            credentials: Some(credentials.clone()),
+            ..nfs.clone()
+        };
+        assert!(options.sanitize().is_err());
+        options.network_mounts[0] = NetworkMountSpec {
+            filesystem: NetworkFilesystem::Cifs,
+            source: "//nas/team".to_string(),
+            credentials: Some(credentials),
+            ..nfs
+        };
+        assert!(options.sanitize().is_ok());
+
+        options.devices.network = false;
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_with_deterministic() {
+        let options = BoxOptions {
+            env: vec![("PYTHONHASHSEED".to_string(), "0".to_string())],
+            ..Default::default()
+        }
+        .with_deterministic(1 << 40);
+        assert!(options.sanitize().is_ok());
+        assert_eq!(options.cpus, Some(1));
+        assert!(!options.devices.network);
+        assert_eq!(
+            options.env,
+            vec![
+                ("PYTHONHASHSEED".to_string(), "0".to_string()),
+                ("BOXLITE_SEED".to_string(), (1u64 << 40).to_string()),
+            ]
+        );
+
+        let options = BoxOptions {
+            cpus: Some(2),
+            ..options
+        };
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_object_volume_validation() {
+        let bucket = ObjectVolumeSpec {
+            endpoint: "http://minio.local:9000".to_string(),
+            bucket: "datasets".to_string(),
+            prefix: "imagenet/".to_string(),
+            region: None,
+            guest_path: "/data".to_string(),
+            write_back: false,
+            credentials: None,
+        };
+        let mut options = BoxOptions {
+            object_volumes: vec![bucket.clone()],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        for endpoint in ["minio.local:9000", "https://", "https://s3.local/datasets"] {
+            options.object_volumes[0].endpoint = endpoint.to_string();
+            assert!(options.sanitize().is_err(), "{} accepted", endpoint);
+        }
+        for (bucket, prefix) in [("", ""), ("a/b", ""), ("datasets", "/imagenet")] {
+            options.object_volumes[0] = ObjectVolumeSpec {
+                bucket: bucket.to_string(),
+                prefix: prefix.to_string(),
+                ..options.object_volumes[0].clone()
+            };
+            options.object_volumes[0].endpoint = "https://s3.local".to_string();
+            assert!(
+                options.sanitize().is_err(),
+                "{}/{} accepted",
+                bucket,
+                prefix
+            );
+        }
+
+        options.object_volumes[0] = ObjectVolumeSpec {
+            guest_path: "data".to_string(),
+            ..bucket
+        };
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_encrypted_volume_validation() {
+        let notes = EncryptedVolumeSpec {
+            name: "notes".to_string(),
+            guest_path: "/notes".to_string(),
+            size_gb: 1,
+            key: VolumeKeySource::File {
+                path: PathBuf::from("/etc/boxlite/notes.key"),
+            },
+            read_only: false,
+        };
+        let mut options = BoxOptions {
+            encrypted_volumes: vec![notes.clone()],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        let invalid = [
+            EncryptedVolumeSpec {
+                name: "../notes".to_string(),
+                ..notes.clone()
+            },
+            EncryptedVolumeSpec {
+                size_gb: 0,
+                ..notes.clone()
+            },
+            EncryptedVolumeSpec {
+                key: VolumeKeySource::File {
+                    path: PathBuf::from("notes.key"),
+                },
+                ..notes.clone()
+            },
+            EncryptedVolumeSpec {
+                key: VolumeKeySource::Plugin { command: vec![] },
+                ..notes.clone()
+            },
+        ];
+        for volume in invalid {
+            options.encrypted_volumes = vec![volume.clone()];
+            assert!(options.sanitize().is_err(), "{:?} accepted", volume);
+        }
+
+        // One box cannot attach a volume twice
+        options.encrypted_volumes = vec![notes.clone(), notes];
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_home_volume_validation() {
+        let mut options = BoxOptions {
+            home_volume: Some(HomeVolume::new("dev-home")),
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+        assert_eq!(options.home_volume.as_ref().unwrap().path, "/root");
+
+        let parsed: HomeVolume = serde_json::from_str(r#"{"name": "dev-home"}"#).unwrap();
+        assert_eq!(parsed, HomeVolume::new("dev-home"));
+
+        let invalid = [
+            HomeVolume::new("../home"),
+            HomeVolume::new(""),
+            HomeVolume {
+                path: "home/dev".to_string(),
+                ..HomeVolume::new("dev-home")
+            },
+            HomeVolume {
+                path: "/".to_string(),
+                ..HomeVolume::new("dev-home")
+            },
+        ];
+        for home in invalid {
+            options.home_volume = Some(home.clone());
+            assert!(options.sanitize().is_err(), "{:?} accepted", home);
+        }
+    }
+
+    #[test]
+    fn test_dns_network_validation() {
+        let mut options = BoxOptions {
+            dns_network: Some("shop".to_string()),
+            dns_services: vec!["api".to_string(), "www".to_string()],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        options.dns_services.push("api.v2".to_string());
+        assert!(options.sanitize().is_err());
+
+        options.dns_services.pop();
+        options.dns_network = None;
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_default_path_masks() {
+        let options = BoxOptions {
+            masked_paths: vec!["/run/secrets".to_string(), "/proc/kcore".to_string()],
+            ..Default::default()
+        }
+        .with_default_path_masks();
+        assert_eq!(options.masked_paths[0], "/run/secrets");
+        assert_eq!(
+            options
+                .masked_paths
+                .iter()
+                .filter(|p| *p == "/proc/kcore")
+                .count(),
+            1
+        );
+        assert!(options.readonly_paths.iter().any(|p| p == "/proc/sys"));
+        assert!(options.sanitize().is_ok());
+
+        for path in ["/", "proc/kcore", "/proc/../etc"] {
+            let options = BoxOptions {
+                readonly_paths: vec![path.to_string()],
+                ..Default::default()
+            };
+            assert!(options.sanitize().is_err(), "{} accepted", path);
+        }
+    }
+
+    #[test]
+    fn test_device_node_validation() {
+        let mut options = BoxOptions::default();
+        options.device_policy.nodes = vec![DeviceNodeSpec::new("/dev/net/tun")];
+        assert!(options.sanitize().is_ok());
+
+        // FUSE has its own switch
+        options.device_policy.nodes = vec![DeviceNodeSpec::new("/dev/fuse")];
+        assert!(options.sanitize().is_err());
+        options.fuse = true;
+        assert!(options.sanitize().is_ok());
+
+        for path in ["/dev/", "/etc/shadow", "/dev/../etc/shadow", "/dev//fuse"] {
+            options.device_policy.nodes = vec![DeviceNodeSpec::new(path)];
+            assert!(options.sanitize().is_err(), "{} accepted", path);
+        }
+
+        options.device_policy.nodes = vec![DeviceNodeSpec {
+            mode: 0o4755,
+            ..DeviceNodeSpec::new("/dev/fuse")
+        }];
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_scheduled_task_validation() {
+        let mut options = BoxOptions {
+            scheduled_tasks: vec![ScheduledTask::new("backup", "/usr/bin/backup", 60)],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        options
+            .scheduled_tasks
+            .push(ScheduledTask::new("backup", "/bin/true", 5));
+        assert!(options.sanitize().is_err());
+
+        options.scheduled_tasks[1] = ScheduledTask::new("probe", "/bin/true", 0);
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_setup_step_validation() {
+        let mut options = BoxOptions {
+            setup_steps: vec![
+                SetupStep::new("migrate", "/app/migrate"),
+                SetupStep::new("seed", "/app/seed"),
+            ],
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        options
+            .setup_steps
+            .push(SetupStep::new("seed", "/app/seed"));
+        assert!(options.sanitize().is_err());
+
+        options.setup_steps = vec![SetupStep::new("migrate", "")];
+        assert!(options.sanitize().is_err());
+
+        options.setup_steps = vec![SetupStep {
+            timeout_secs: Some(0),
+            ..SetupStep::new("migrate", "/app/migrate")
+        }];
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_probe_validation() {
+        let mut options = BoxOptions {
+            readiness_probe: Some(Probe::new(ProbeCheck::Http {
+                port: 8080,
+                path: "/healthz".to_string(),
+            })),
+            liveness_probe: Some(Probe::new(ProbeCheck::Tcp { port: 8080 })),
+            ..Default::default()
+        };
+        assert!(options.sanitize().is_ok());
+
+        options.liveness_probe = Some(Probe::new(ProbeCheck::Tcp { port: 0 }));
+        assert!(options.sanitize().is_err());
+
+        options.liveness_probe = Some(Probe {
+            failure_threshold: 0,
+            ..Probe::new(ProbeCheck::Command {
+                command: "pg_isready".to_string(),
+                args: Vec::new(),
+            })
+        });
+        assert!(options.sanitize().is_err());
+
+        options.liveness_probe = None;
+        options.readiness_probe = Some(Probe::new(ProbeCheck::Http {
+            port: 80,
+            path: "healthz".to_string(),
+        }));
+        assert!(options.sanitize().is_err());
+    }
+
+    #[test]
+    fn test_gpu_driver_modules() {
+        let virtio = GpuSpec::VirtioGpu { venus: true };
+        assert_eq!(virtio.driver_modules(), vec!["virtio_gpu"]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Host CPU and memory admission for starting boxes.
+//!
+//! The [`Planner`] tracks what every running box has committed and, with
+//! [`OvercommitOptions`] set, admits a box only while the commitments fit
+//! the host at the configured ratios. Admission hands out a [`Reservation`]
+//! that the box holds while it runs; dropping it releases the box's share
+//! and wakes the boxes queued behind it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use sysinfo::System;
+use tokio::sync::Notify;
+
+use crate::runtime::options::OvercommitOptions;
+use crate::runtime::quota::ResourceUsage;
+use crate::runtime::types::BoxID;
+
+/// How often a queued box re-reads the host's available memory when no box
+/// has stopped in the meantime.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Host capacity against what running boxes have committed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapacityReport {
+    /// CPUs on the host.
+    pub host_cpus: u32,
+    /// Memory on the host in MiB.
+    pub host_memory_mib: u64,
+    /// Memory the host can hand out right now in MiB.
+    pub available_memory_mib: u64,
+    /// Boxes running in this runtime.
+    pub running_boxes: usize,
+    /// vCPUs committed by running boxes.
+    pub committed_cpus: u32,
+    /// Memory committed by running boxes in MiB.
+    pub committed_memory_mib: u64,
+    /// Most vCPUs that may be committed; None without overcommit options.
+    pub cpu_limit: Option<u32>,
+    /// Most memory that may be committed in MiB; None without overcommit
+    /// options.
+    pub memory_limit_mib: Option<u64>,
+}
+
+/// What the host has, as admission sees it.
+#[derive(Clone, Copy, Debug)]
+struct HostCapacity {
+    cpus: u32,
+    memory_mib: u64,
+    available_memory_mib: u64,
+}
+
+impl HostCapacity {
+    fn read() -> Self {
+        let mut sys = System::new();
+        sys.refresh_memory();
+        Self {
+            cpus: std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+            memory_mib: sys.total_memory() >> 20,
+            available_memory_mib: sys.available_memory() >> 20,
+        }
+    }
+}
+
+/// Tracks committed resources and admits boxes against host capacity.
+pub(crate) struct Planner {
+    options: Option<OvercommitOptions>,
+    committed: Mutex<HashMap<BoxID, ResourceUsage>>,
+    released: Notify,
+}
+
+impl Planner {
+    pub(crate) fn new(options: Option<OvercommitOptions>) -> Arc<Self> {
+        Arc::new(Self {
+            options,
+            committed: Mutex::new(HashMap::new()),
+            released: Notify::new(),
+        })
+    }
+
+    /// Admit a box about to start, waiting for capacity if so configured.
+    pub(crate) async fn admit(
+        self: &Arc<Self>,
+        box_id: &BoxID,
+        requested: ResourceUsage,
+    ) -> BoxliteResult<Reservation> {
+        let Some(options) = &self.options else {
+            return Ok(self.track(box_id, requested));
+        };
+        let deadline = options
+            .queue_timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        let mut queued = false;
+        loop {
+            // Registered before checking so a release in between is not missed
+            let released = self.released.notified();
+            let reason = {
+                let mut committed = self.committed.lock().unwrap();
+                let total = total(&committed);
+                match check(options, HostCapacity::read(), total, requested) {
+                    Ok(()) => {
+                        committed.insert(box_id.clone(), requested);
+                        None
+                    }
+                    Err(reason) => Some(reason),
+                }
+            };
+            let Some(reason) = reason else {
+                return Ok(Reservation {
+                    planner: Arc::clone(self),
+                    box_id: box_id.clone(),
+                });
+            };
+            let remaining = deadline.and_then(|d| d.checked_duration_since(Instant::now()));
+            let Some(remaining) = remaining.filter(|r| !r.is_zero()) else {
+                return Err(BoxliteError::QuotaExceeded(format!(
+                    "host is oversubscribed: {}",
+                    reason
+                )));
+            };
+            if !queued {
+                queued = true;
+                tracing::info!(box_id = %box_id, %reason, "Box queued for host capacity");
+            }
+            let _ = tokio::time::timeout(remaining.min(RECHECK_INTERVAL), released).await;
+        }
+    }
+
+    /// Record a box that is already running, such as one being reattached.
+    pub(crate) fn track(self: &Arc<Self>, box_id: &BoxID, usage: ResourceUsage) -> Reservation {
+        self.committed.lock().unwrap().insert(box_id.clone(), usage);
+        Reservation {
+            planner: Arc::clone(self),
+            box_id: box_id.clone(),
+        }
+    }
+
+    pub(crate) fn report(&self) -> CapacityReport {
+        let host = HostCapacity::read();
+        let committed = self.committed.lock().unwrap();
+        let total = total(&committed);
+        let limits = self.options.as_ref().map(|options| limits(options, host));
+        CapacityReport {
+            host_cpus: host.cpus,
+            host_memory_mib: host.memory_mib,
+            available_memory_mib: host.available_memory_mib,
+            running_boxes: committed.len(),
+            committed_cpus: total.cpus,
+            committed_memory_mib: total.memory_mib,
+            cpu_limit: limits.map(|(cpus, _)| cpus),
+            memory_limit_mib: limits.map(|(_, memory_mib)| memory_mib),
+        }
+    }
+
+    fn release(&self, box_id: &BoxID) {
+        self.committed.lock().unwrap().remove(box_id);
+        self.released.notify_waiters();
+    }
+}
+
+/// A running box's share of the host, released on drop.
+pub(crate) struct Reservation {
+    planner: Arc<Planner>,
+    box_id: BoxID,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.planner.release(&self.box_id);
+    }
+}
+
+fn total(committed: &HashMap<BoxID, ResourceUsage>) -> ResourceUsage {
+    let mut total = ResourceUsage::default();
+    for usage in committed.values() {
+        total.add(*usage);
+    }
+    total
+}
+
+/// Most vCPUs and MiB of memory that may be committed on `host`.
+fn limits(options: &OvercommitOptions, host: HostCapacity) -> (u32, u64) {
+    (
+        (host.cpus as f64 * options.cpu_ratio) as u32,
+        (host.memory_mib as f64 * options.memory_ratio) as u64,
+    )
+}
+
+/// Why `requested` does not fit on top of `committed`, if it does not.
+fn check(
+    options: &OvercommitOptions,
+    host: HostCapacity,
+    committed: ResourceUsage,
+    requested: ResourceUsage,
+) -> Result<(), String> {
+    let (cpu_limit, memory_limit) = limits(options, host);
+    if committed.cpus + requested.cpus > cpu_limit {
+        return Err(format!(
+            "{} vCPUs committed + {} requested exceeds {} ({} CPUs x {})",
+            committed.cpus, requested.cpus, cpu_limit, host.cpus, options.cpu_ratio
+        ));
+    }
+    if committed.memory_mib + requested.memory_mib > memory_limit {
+        return Err(format!(
+            "{} MiB committed + {} requested exceeds {} MiB ({} MiB x {})",
+            committed.memory_mib,
+            requested.memory_mib,
+            memory_limit,
+            host.memory_mib,
+            options.memory_ratio
+        ));
+    }
+    if options.memory_ratio > 1.0 && requested.memory_mib > host.available_memory_mib {
+        return Err(format!(
+            "{} MiB requested but only {} MiB is available",
+            requested.memory_mib, host.available_memory_mib
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(cpus: u32, memory_mib: u64) -> ResourceUsage {
+        ResourceUsage {
+            boxes: 1,
+            cpus,
+            memory_mib,
+            disk_gb: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_applies_ratios() {
+        let host = HostCapacity {
+            cpus: 4,
+            memory_mib: 8192,
+            available_memory_mib: 1024,
+        };
+        let options = OvercommitOptions::default();
+        assert!(check(&options, host, usage(14, 4096), usage(2, 4096)).is_ok());
+        let err = check(&options, host, usage(15, 0), usage(2, 0)).unwrap_err();
+        assert!(err.contains("vCPUs"), "{}", err);
+        let err = check(&options, host, usage(0, 6144), usage(1, 4096)).unwrap_err();
+        assert!(err.contains("8192 MiB"), "{}", err);
+
+        let options = OvercommitOptions {
+            memory_ratio: 2.0,
+            ..Default::default()
+        };
+        assert!(check(&options, host, usage(0, 12288), usage(1, 1024)).is_ok());
+        let err = check(&options, host, usage(0, 0), usage(1, 2048)).unwrap_err();
+        assert!(err.contains("available"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_reservation_releases_on_drop() {
+        let planner = Planner::new(Some(OvercommitOptions {
+            cpu_ratio: 0.0,
+            queue_timeout_secs: None,
+            ..Default::default()
+        }));
+        let id = BoxID::new();
+        let err = planner.admit(&id, usage(1, 0)).await.err().unwrap();
+        assert!(matches!(err, BoxliteError::QuotaExceeded(_)));
+
+        let reservation = planner.track(&id, usage(2, 512));
+        let report = planner.report();
+        assert_eq!((report.running_boxes, report.committed_cpus), (1, 2));
+        assert_eq!(report.cpu_limit, Some(0));
+        drop(reservation);
+        assert_eq!(planner.report().running_boxes, 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! Admission policy for box specs.
+//!
+//! Administrators describe what boxes may ask for as [`PolicyRule`]s, set
+//! in `BoxliteOptions::policy` or a policy file. Every spec is checked
+//! against all of them before a box is created, and the first rule it
+//! breaks is named in the error, so a refused caller knows what to change.
+
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use oci_client::Reference;
+use serde::Deserialize;
+
+use crate::runtime::options::{BoxOptions, PolicyCheck, PolicyRule, RootfsSpec};
+use crate::runtime::quota::ResourceUsage;
+
+/// The rules a runtime admits boxes by.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Policy {
+    rules: Vec<PolicyRule>,
+}
+
+/// Layout of a policy file.
+#[derive(Deserialize)]
+struct PolicyFile {
+    #[serde(default)]
+    rule: Vec<PolicyRule>,
+}
+
+impl Policy {
+    /// `rules` followed by those in `file`, if any.
+    pub(crate) fn load(mut rules: Vec<PolicyRule>, file: Option<&Path>) -> BoxliteResult<Self> {
+        if let Some(path) = file {
+            let content = std::fs::read_to_string(path).map_err(|e| {
+                BoxliteError::Config(format!(
+                    "Failed to read policy file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let parsed: PolicyFile = toml::from_str(&content).map_err(|e| {
+                BoxliteError::Config(format!("Invalid policy file {}: {}", path.display(), e))
+            })?;
+            rules.extend(parsed.rule);
+        }
+        Ok(Self { rules })
+    }
+
+    /// Fail with the first rule `options` breaks.
+    pub(crate) fn check(&self, options: &BoxOptions) -> BoxliteResult<()> {
+        match self.violations(options).into_iter().next() {
+            Some((rule, reason)) => Err(BoxliteError::PolicyViolation(format!(
+                "rule '{}': {}",
+                rule, reason
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Every rule `options` breaks, by name, with why.
+    pub(crate) fn violations(&self, options: &BoxOptions) -> Vec<(String, String)> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                violation(&rule.check, options).map(|reason| (rule.name.clone(), reason))
+            })
+            .collect()
+    }
+}
+
+fn violation(check: &PolicyCheck, options: &BoxOptions) -> Option<String> {
+    let usage = ResourceUsage::of(options);
+    match check {
+        PolicyCheck::MaxMemoryMib { limit } => (usage.memory_mib > *limit as u64).then(|| {
+            format!(
+                "{} MiB of memory exceeds the limit of {} MiB",
+                usage.memory_mib, limit
+            )
+        }),
+        PolicyCheck::MaxCpus { limit } => (usage.cpus > *limit as u32)
+            .then(|| format!("{} vCPUs exceed the limit of {}", usage.cpus, limit)),
+        PolicyCheck::MaxDiskGb { limit } => (usage.disk_gb > *limit).then(|| {
+            format!(
+                "{} GB of disk exceeds the limit of {} GB",
+                usage.disk_gb, limit
+            )
+        }),
+        PolicyCheck::AllowedRegistries { registries } => match &options.rootfs {
+            RootfsSpec::Image(image) => match image.parse::<Reference>() {
+                Ok(reference) if registries.iter().any(|r| r == reference.registry()) => None,
+                Ok(reference) => Some(format!(
+                    "registry {} of image {} is not approved",
+                    reference.registry(),
+                    image
+                )),
+                Err(e) => Some(format!("invalid image reference {}: {}", image, e)),
+            },
+            RootfsSpec::RootfsPath(path) => Some(format!(
+                "rootfs path {} is not an image from an approved registry",
+                path
+            )),
+        },
+        PolicyCheck::NoDevices => {
+            let devices = [
+                (options.gpu.is_some(), "gpu"),
+                (!options.device_policy.nodes.is_empty(), "device nodes"),
+                (options.device_policy.devtmpfs, "devtmpfs"),
+                (options.fuse, "fuse"),
+                (options.nested_virt, "nested_virt"),
+            ];
+            let requested: Vec<&str> = devices
+                .into_iter()
+                .filter_map(|(on, name)| on.then_some(name))
+                .collect();
+            (!requested.is_empty())
+                .then(|| format!("host devices are not allowed ({})", requested.join(", ")))
+        }
+        PolicyCheck::AllowedVolumePaths { prefixes } => options
+            .volumes
+            .iter()
+            .find(|volume| !under_any(Path::new(&volume.host_path), prefixes))
+            .map(|volume| format!("volume {} is outside the allowed paths", volume.host_path)),
+    }
+}
+
+fn under_any(path: &Path, prefixes: &[PathBuf]) -> bool {
+    // Resolve `..` and symlinks so a path cannot climb out of a prefix
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    path.is_absolute() && prefixes.iter().any(|prefix| path.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::options::GpuSpec;
+
+    #[test]
+    fn test_policy_file_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("policy.toml");
+        std::fs::write(
+            &file,
+            r#"
+[[rule]]
+name = "approved-registries"
+check = "allowed_registries"
+registries = ["ghcr.io"]
+
+[[rule]]
+name = "no-devices"
+check = "no_devices"
+"#,
+        )
+        .unwrap();
+        let memory = PolicyRule {
+            name: "memory-cap".to_string(),
+            check: PolicyCheck::MaxMemoryMib { limit: 2048 },
+        };
+        let policy = Policy::load(vec![memory], Some(&file)).unwrap();
+
+        let allowed = BoxOptions {
+            rootfs: RootfsSpec::Image("ghcr.io/acme/app:1".to_string()),
+            memory_mib: Some(1024),
+            ..Default::default()
+        };
+        policy.check(&allowed).unwrap();
+
+        let err = policy
+            .check(&BoxOptions {
+                memory_mib: Some(4096),
+                ..allowed.clone()
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("rule 'memory-cap'"));
+
+        // Unqualified references come from Docker Hub
+        let hub = BoxOptions {
+            rootfs: RootfsSpec::Image("alpine:latest".to_string()),
+            gpu: Some(GpuSpec::VirtioGpu { venus: false }),
+            ..allowed
+        };
+        let names: Vec<String> = policy.violations(&hub).into_iter().map(|v| v.0).collect();
+        assert_eq!(names, vec!["approved-registries", "no-devices"]);
+    }
+}
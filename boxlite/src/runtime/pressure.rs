@@ -0,0 +1,148 @@
+//! Host response to guest pressure.
+//!
+//! Running boxes report CPU, memory and I/O pressure from their guest (see
+//! `BoxOptions::pressure_percent`). With [`PressureOptions`] turned on, the
+//! runtime answers a box coming under pressure by leaning on the running
+//! boxes of lower priority: memory pressure has each of them reclaim memory
+//! once, and CPU pressure caps their VMM cgroups until no box above them is
+//! under CPU pressure any more. I/O pressure is only reported.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::litebox::config::BoxConfig;
+use crate::litebox::pressure::{PressureChange, PressureResource};
+use crate::runtime::options::PressureOptions;
+use crate::runtime::rt_impl::RuntimeImpl;
+use crate::runtime::types::BoxID;
+use crate::vmm::priority;
+
+/// Leans on low-priority boxes while others are under pressure.
+pub(crate) struct PressureBalancer {
+    options: PressureOptions,
+    cgroup_parent: Option<PathBuf>,
+    cpu: Mutex<CpuBalance>,
+}
+
+/// Boxes under CPU pressure and boxes capped for them, with their
+/// priority weights.
+#[derive(Default)]
+struct CpuBalance {
+    pressured: HashMap<BoxID, u32>,
+    throttled: HashMap<BoxID, u32>,
+}
+
+impl CpuBalance {
+    /// Boxes weighted under this are capped.
+    fn floor(&self) -> u32 {
+        self.pressured.values().copied().max().unwrap_or(0)
+    }
+
+    /// Lift the caps of boxes no longer under the floor.
+    fn release(&mut self, cgroup_parent: &Path) {
+        let floor = self.floor();
+        self.throttled.retain(|box_id, weight| {
+            if *weight < floor {
+                return true;
+            }
+            if let Err(e) = priority::throttle(cgroup_parent, box_id, false) {
+                tracing::debug!(box_id = %box_id, error = %e, "Failed to lift CPU cap");
+            }
+            false
+        });
+    }
+}
+
+impl PressureBalancer {
+    pub(crate) fn new(options: PressureOptions, cgroup_parent: Option<PathBuf>) -> Self {
+        if options.throttle_cpu && cgroup_parent.is_none() {
+            tracing::warn!("Throttling boxes on CPU pressure needs cgroup_parent, not throttling");
+        }
+        Self {
+            options,
+            cgroup_parent,
+            cpu: Mutex::default(),
+        }
+    }
+
+    /// Respond to `change`, reported by the running box `config`.
+    pub(crate) fn update(
+        &self,
+        runtime: &RuntimeImpl,
+        config: &BoxConfig,
+        change: &PressureChange,
+    ) {
+        let weight = config.options.priority.weight();
+        match change.resource {
+            PressureResource::Memory if change.under_pressure && self.options.reclaim_memory => {
+                for other in runtime.running_boxes() {
+                    if other.config.options.priority.weight() >= weight {
+                        continue;
+                    }
+                    tokio::spawn(async move {
+                        match other.reclaim_memory().await {
+                            Ok(freed_bytes) => tracing::info!(
+                                box_id = %other.config.id,
+                                freed_bytes,
+                                "Reclaimed memory for a box under pressure"
+                            ),
+                            Err(e) => tracing::debug!(
+                                box_id = %other.config.id,
+                                error = %e,
+                                "Failed to reclaim memory"
+                            ),
+                        }
+                    });
+                }
+            }
+            PressureResource::Cpu if self.options.throttle_cpu => {
+                let Some(parent) = &self.cgroup_parent else {
+                    return;
+                };
+                let mut cpu = self.cpu.lock().unwrap();
+                if change.under_pressure {
+                    cpu.pressured.insert(config.id.clone(), weight);
+                } else {
+                    cpu.pressured.remove(&config.id);
+                }
+                let floor = cpu.floor();
+                for other in runtime.running_boxes() {
+                    let other_weight = other.config.options.priority.weight();
+                    if other_weight >= floor || cpu.throttled.contains_key(&other.config.id) {
+                        continue;
+                    }
+                    match priority::throttle(parent, &other.config.id, true) {
+                        Ok(()) => {
+                            tracing::info!(
+                                box_id = %other.config.id,
+                                "Capped CPU for a box under pressure"
+                            );
+                            cpu.throttled.insert(other.config.id.clone(), other_weight);
+                        }
+                        Err(e) => tracing::debug!(
+                            box_id = %other.config.id,
+                            error = %e,
+                            "Failed to cap CPU"
+                        ),
+                    }
+                }
+                cpu.release(parent);
+            }
+            _ => {}
+        }
+    }
+
+    /// Forget `box_id` once it stopped, lifting the caps it caused.
+    pub(crate) fn forget(&self, box_id: &BoxID) {
+        let Some(parent) = &self.cgroup_parent else {
+            return;
+        };
+        let mut cpu = self.cpu.lock().unwrap();
+        // Its cgroup goes away with it
+        cpu.throttled.remove(box_id);
+        if cpu.pressured.remove(box_id).is_some() {
+            cpu.release(parent);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Admission checks for host resource quotas.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use crate::runtime::constants::vm_defaults;
+use crate::runtime::options::{BoxOptions, QuotaOptions};
+
+/// Resources reserved by one or more boxes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ResourceUsage {
+    pub(crate) boxes: usize,
+    pub(crate) cpus: u32,
+    pub(crate) memory_mib: u64,
+    pub(crate) disk_gb: u64,
+}
+
+impl ResourceUsage {
+    /// Resources reserved by a single box with the given options.
+    pub(crate) fn of(options: &BoxOptions) -> Self {
+        Self {
+            boxes: 1,
+            cpus: options.cpus.unwrap_or(vm_defaults::DEFAULT_CPUS) as u32,
+            memory_mib: options
+                .memory_mib
+                .unwrap_or(vm_defaults::DEFAULT_MEMORY_MIB) as u64,
+            disk_gb: options
+                .disk_size_gb
+                .unwrap_or(vm_defaults::DEFAULT_DISK_SIZE_GB),
+        }
+    }
+
+    pub(crate) fn add(&mut self, other: ResourceUsage) {
+        self.boxes += other.boxes;
+        self.cpus += other.cpus;
+        self.memory_mib += other.memory_mib;
+        self.disk_gb += other.disk_gb;
+    }
+}
+
+impl QuotaOptions {
+    /// Check that reserving `requested` on top of `used` stays within quota.
+    pub(crate) fn check(&self, used: ResourceUsage, requested: ResourceUsage) -> BoxliteResult<()> {
+        fn check_one<T>(what: &str, limit: Option<T>, used: T, requested: T) -> BoxliteResult<()>
+        where
+            T: Copy + PartialOrd + std::ops::Add<Output = T> + std::fmt::Display,
+        {
+            match limit {
+                Some(limit) if used + requested > limit => {
+                    Err(BoxliteError::QuotaExceeded(format!(
+                        "{}: {} in use + {} requested exceeds limit of {}",
+                        what, used, requested, limit
+                    )))
+                }
+                _ => Ok(()),
+            }
+        }
+
+        check_one("boxes", self.max_boxes, used.boxes, requested.boxes)?;
+        check_one("vCPUs", self.max_cpus, used.cpus, requested.cpus)?;
+        check_one(
+            "memory (MiB)",
+            self.max_memory_mib,
+            used.memory_mib,
+            requested.memory_mib,
+        )?;
+        check_one(
+            "disk (GB)",
+            self.max_disk_gb,
+            used.disk_gb,
+            requested.disk_gb,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(boxes: usize, cpus: u32, memory_mib: u64, disk_gb: u64) -> ResourceUsage {
+        ResourceUsage {
+            boxes,
+            cpus,
+            memory_mib,
+            disk_gb,
+        }
+    }
+
+    #[test]
+    fn test_unlimited_quota_admits_everything() {
+        let quota = QuotaOptions::default();
+        assert!(
+            quota
+                .check(
+                    usage(1000, 1000, 1 << 30, 1 << 20),
+                    usage(1, 64, 1 << 20, 100)
+                )
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_limit_is_inclusive() {
+        let quota = QuotaOptions {
+            max_cpus: Some(8),
+            ..Default::default()
+        };
+        assert!(quota.check(usage(1, 6, 0, 0), usage(1, 2, 0, 0)).is_ok());
+
+        let err = quota
+            .check(usage(1, 6, 0, 0), usage(1, 4, 0, 0))
+            .unwrap_err();
+        assert!(matches!(err, BoxliteError::QuotaExceeded(_)));
+        assert!(err.to_string().contains("vCPUs"));
+    }
+
+    #[test]
+    fn test_usage_of_options_applies_defaults() {
+        let used = ResourceUsage::of(&BoxOptions::default());
+        assert_eq!(
+            used,
+            usage(
+                1,
+                vm_defaults::DEFAULT_CPUS as u32,
+                vm_defaults::DEFAULT_MEMORY_MIB as u64,
+                vm_defaults::DEFAULT_DISK_SIZE_GB
+            )
+        );
+    }
+}
@@ -1,20 +1,51 @@
-use crate::db::{BoxStore, Database};
+use crate::db::{BakedImageStore, BoxStore, Database, UsageStore};
+use crate::events::{BoxEvent, EventBus, EventKind, EventSubscription, webhooks};
 use crate::images::ImageManager;
 use crate::init_logging_for;
 use crate::litebox::config::BoxConfig;
+use crate::litebox::resume::{self, ResumeReason};
 use crate::litebox::{BoxManager, LiteBox, SharedBoxImpl};
-use crate::lock::{FileLockManager, LockManager};
+use crate::lock::{FileLockManager, LockGuard, LockManager, Locker};
 use crate::metrics::{RuntimeMetrics, RuntimeMetricsStorage};
-use crate::runtime::constants::filenames;
+use crate::net::dns::BoxDns;
+use crate::net::ingress::Ingress;
+use crate::net::mdns::MdnsResponder;
+use crate::runtime::batch::BatchQueues;
+use crate::runtime::constants::{filenames, images};
+use crate::runtime::diagnostics::Diagnostics;
+use crate::runtime::disk_usage::{
+    self, BoxDiskUsage, DiskUsage, PruneReport, SystemPruneOptions, UsageEntry, VolumeUsage,
+    image_references,
+};
+use crate::runtime::doctor;
+use crate::runtime::dry_run::{self, DryRunReport, SpecIssue};
+use crate::runtime::exec_policy::ExecPolicy;
+use crate::runtime::filter::BoxFilter;
 use crate::runtime::guest_rootfs::GuestRootfs;
-use crate::runtime::layout::{FilesystemLayout, FsLayoutConfig};
+use crate::runtime::hooks::Hooks;
+use crate::runtime::layout::{BoxFilesystemLayout, FilesystemLayout, FsLayoutConfig};
 use crate::runtime::lock::RuntimeLock;
-use crate::runtime::options::{BoxOptions, BoxliteOptions};
+use crate::runtime::lockfile::{BoxLock, LockedImage};
+use crate::runtime::migration::{self, ExportOptions};
+use crate::runtime::operations::{
+    OperationKind, OperationProgress, OperationRegistry, ProgressSubscription,
+};
+use crate::runtime::options::{
+    BoxOptions, BoxliteOptions, EngineSelection, QuotaOptions, RootfsSpec,
+};
+use crate::runtime::overcommit::{CapacityReport, Planner};
+use crate::runtime::policy::Policy;
+use crate::runtime::pressure::PressureBalancer;
+use crate::runtime::quota::ResourceUsage;
 use crate::runtime::types::{BoxID, BoxInfo, BoxState, BoxStatus, ContainerID};
+use crate::snapshots::{SnapshotInfo, SnapshotManager};
+use crate::usage::ExecUsage;
+use crate::util::allocated_size;
 use crate::vmm::VmmKind;
 use boxlite_shared::{BoxliteError, BoxliteResult, Transport};
-use chrono::Utc;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock, Weak};
 use tokio::sync::OnceCell;
 
@@ -44,6 +75,16 @@ pub struct RuntimeImpl {
     pub(crate) box_manager: BoxManager,
     /// Image management (has internal RwLock via ImageStore)
     pub(crate) image_manager: ImageManager,
+    /// Snapshot records and content-addressed layers
+    pub(crate) snapshot_manager: SnapshotManager,
+    /// Lifecycle event log and live broadcast (internally synchronized)
+    pub(crate) events: EventBus,
+    /// Long operations in progress (internally synchronized)
+    pub(crate) operations: OperationRegistry,
+    /// Per-execution resource usage records
+    pub(crate) usage: UsageStore,
+    /// Images baked under local tags
+    pub(crate) baked_images: BakedImageStore,
 
     // ========================================================================
     // NO COORDINATION NEEDED: Immutable or internally synchronized
@@ -54,6 +95,30 @@ pub struct RuntimeImpl {
     pub(crate) guest_rootfs: Arc<OnceCell<GuestRootfs>>,
     /// Runtime-wide metrics (AtomicU64 based, lock-free)
     pub(crate) runtime_metrics: RuntimeMetricsStorage,
+    /// Host resource quotas checked on box creation (immutable after init)
+    pub(crate) quota: QuotaOptions,
+    /// Delegated cgroup2 directory box VMMs are placed under (immutable after init)
+    pub(crate) cgroup_parent: Option<PathBuf>,
+    /// Host hooks run at box lifecycle points (immutable after init)
+    pub(crate) hooks: Hooks,
+    /// Admission rules for box specs (immutable after init)
+    policy: Policy,
+    /// Rules every exec passes through before it runs (immutable after init)
+    pub(crate) exec_policy: ExecPolicy,
+    /// Answers mDNS queries for boxes with `mdns` set
+    pub(crate) mdns: MdnsResponder,
+    /// HTTPS proxy for boxes with `ingress_port` set, if enabled
+    pub(crate) ingress: Option<Ingress>,
+    /// DNS server for box names, if enabled
+    pub(crate) dns: Option<BoxDns>,
+    /// Admits starting boxes against host CPU and memory
+    pub(crate) planner: Arc<Planner>,
+    /// Named queues boxes wait in before they start
+    pub(crate) queues: Arc<BatchQueues>,
+    /// Leans on low-priority boxes while others are under pressure
+    pub(crate) pressure: PressureBalancer,
+    /// Engine new boxes run in, resolved from `BoxliteOptions::engine`
+    pub(crate) engine_kind: VmmKind,
 
     /// Per-entity lock manager for multiprocess-safe locking.
     ///
@@ -76,6 +141,8 @@ pub struct SynchronizedState {
     active_boxes_by_id: HashMap<BoxID, Weak<crate::litebox::box_impl::BoxImpl>>,
     /// Cache of active BoxImpl instances by name (only for named boxes).
     active_boxes_by_name: HashMap<String, Weak<crate::litebox::box_impl::BoxImpl>>,
+    /// Quota held by boxes being cloned or imported, until they are persisted.
+    quota_reservations: HashMap<BoxID, ResourceUsage>,
 }
 
 impl RuntimeImpl {
@@ -141,14 +208,38 @@ impl RuntimeImpl {
             ))
         })?;
 
-        let image_manager = ImageManager::new(layout.images_dir(), db.clone()).map_err(|e| {
-            BoxliteError::Storage(format!(
-                "Failed to initialize image manager at {}: {}",
-                layout.images_dir().display(),
-                e
-            ))
-        })?;
-
+        let operations = OperationRegistry::default();
+        let image_manager = ImageManager::new(layout.images_dir(), db.clone(), operations.clone())
+            .map_err(|e| {
+                BoxliteError::Storage(format!(
+                    "Failed to initialize image manager at {}: {}",
+                    layout.images_dir().display(),
+                    e
+                ))
+            })?;
+
+        let policy = Policy::load(options.policy, options.policy_file.as_deref())?;
+        let exec_policy = ExecPolicy::load(options.exec_rules, options.policy_file.as_deref())?;
+        let engine_kind = resolve_engine(options.engine);
+        let ingress = options
+            .ingress
+            .as_ref()
+            .map(|ingress| Ingress::start(ingress, &layout.ingress_dir()))
+            .transpose()?;
+        let dns = options.dns.as_ref().map(BoxDns::start).transpose()?;
+        let pressure = PressureBalancer::new(options.pressure, options.cgroup_parent.clone());
+        let snapshot_manager = SnapshotManager::new(db.clone(), layout.snapshots_dir());
+        let runtime_metrics = RuntimeMetricsStorage::new();
+        let events = EventBus::new(
+            db.clone(),
+            options.event_buffer,
+            Arc::clone(&runtime_metrics.events_dropped),
+        );
+        if !options.webhooks.is_empty() {
+            webhooks::start(options.webhooks, events.subscribe());
+        }
+        let usage = UsageStore::new(db.clone());
+        let baked_images = BakedImageStore::new(db.clone());
         let box_store = BoxStore::new(db);
 
         // Initialize lock manager for per-entity multiprocess-safe locking
@@ -170,12 +261,30 @@ impl RuntimeImpl {
             sync_state: RwLock::new(SynchronizedState {
                 active_boxes_by_id: HashMap::new(),
                 active_boxes_by_name: HashMap::new(),
+                quota_reservations: HashMap::new(),
             }),
             box_manager: BoxManager::new(box_store),
             image_manager,
+            snapshot_manager,
+            events,
+            operations,
+            usage,
+            baked_images,
             layout,
             guest_rootfs: Arc::new(OnceCell::new()),
-            runtime_metrics: RuntimeMetricsStorage::new(),
+            runtime_metrics,
+            quota: options.quota,
+            cgroup_parent: options.cgroup_parent,
+            hooks: Hooks::new(options.hooks),
+            policy,
+            exec_policy,
+            mdns: MdnsResponder::default(),
+            ingress,
+            dns,
+            planner: Planner::new(options.overcommit),
+            queues: Arc::default(),
+            pressure,
+            engine_kind,
             lock_manager,
             _runtime_lock: runtime_lock,
         });
@@ -185,6 +294,9 @@ impl RuntimeImpl {
         // Recover boxes from database
         inner.recover_boxes()?;
 
+        // Clean up boxes whose TTL ran out or whose creator died
+        inner.reap()?;
+
         Ok(inner)
     }
 
@@ -212,12 +324,20 @@ impl RuntimeImpl {
             )));
         }
 
+        let options = self.hooks.pre_create(options, name.as_deref())?;
+        self.policy.check(&options)?;
+
         // Initialize box variables with defaults (no lock, not persisted yet)
         let (config, state) = self.init_box_variables(&options, name);
 
         // Create LiteBox handle with shared BoxImpl
-        // This also checks in-memory cache for duplicate names
-        let (box_impl, inserted) = self.get_or_create_box_impl(config, state);
+        // This also checks in-memory cache for duplicate names. The quota is
+        // checked under the same lock, so concurrent creates cannot both fit.
+        let (box_impl, inserted) = {
+            let mut sync = self.sync_state.write().unwrap();
+            self.check_quota(&sync, &options)?;
+            self.cache_box_impl(&mut sync, config, state)
+        };
         if !inserted {
             return Err(BoxliteError::InvalidArgument(
                 "box with this name already exists".into(),
@@ -229,6 +349,8 @@ impl RuntimeImpl {
             .boxes_created
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
+        self.events.emit(EventKind::Created, &box_impl.config, []);
+
         // DB persistence and lock allocation happen on first use (init_live_state)
         Ok(LiteBox::new(box_impl))
     }
@@ -288,6 +410,421 @@ impl RuntimeImpl {
         self.remove_box(&box_id, force)
     }
 
+    /// Lifecycle events at or after `since` matching a filter, oldest first.
+    pub fn events(
+        &self,
+        since: Option<DateTime<Utc>>,
+        filter: &BoxFilter,
+    ) -> BoxliteResult<Vec<BoxEvent>> {
+        let mut events = self.events.history(since)?;
+        events.retain(|event| filter.matches_event(event));
+        Ok(events)
+    }
+
+    /// Usage of executions that finished at or after `since`, in boxes
+    /// matching a filter.
+    pub fn usage(
+        &self,
+        since: Option<DateTime<Utc>>,
+        filter: &BoxFilter,
+    ) -> BoxliteResult<Vec<ExecUsage>> {
+        let mut records = self.usage.list(since)?;
+        records.retain(|usage| filter.matches_usage(usage));
+        Ok(records)
+    }
+
+    /// Subscribe to lifecycle events emitted from now on.
+    pub fn subscribe_events(&self) -> EventSubscription {
+        self.events.subscribe()
+    }
+
+    pub fn operations(&self) -> Vec<OperationProgress> {
+        self.operations.list()
+    }
+
+    pub fn subscribe_progress(&self) -> ProgressSubscription {
+        self.operations.subscribe()
+    }
+
+    pub fn cancel_operation(&self, id: &str) -> BoxliteResult<()> {
+        self.operations.cancel(id)
+    }
+
+    pub fn ingress_ca(&self) -> Option<PathBuf> {
+        self.ingress
+            .as_ref()
+            .map(|ingress| ingress.ca_path().to_path_buf())
+    }
+
+    /// Remove every box matching a filter.
+    ///
+    /// Stops at the first failure (e.g. an active box with `force=false`);
+    /// boxes removed before it stay removed. Returns the removed box IDs.
+    pub fn remove_matching(&self, filter: &BoxFilter, force: bool) -> BoxliteResult<Vec<BoxID>> {
+        let mut removed = Vec::new();
+        for info in self.list_info_filtered(filter)? {
+            self.remove_box(&info.id, force)?;
+            removed.push(info.id);
+        }
+        Ok(removed)
+    }
+
+    /// Remove every stopped box matching a filter.
+    ///
+    /// Active boxes are left alone. Returns the removed box IDs.
+    pub fn prune(&self, filter: &BoxFilter) -> BoxliteResult<Vec<BoxID>> {
+        let mut pruned = Vec::new();
+        for info in self.list_info_filtered(filter)? {
+            if info.status.is_active() {
+                continue;
+            }
+            self.remove_box_as(&info.id, false, EventKind::Pruned)?;
+            pruned.push(info.id);
+        }
+        Ok(pruned)
+    }
+
+    /// Remove expired and orphaned ephemeral boxes.
+    ///
+    /// Reaps every persisted box whose TTL has run out (killing its VM if it
+    /// is somehow still alive), and every stopped `auto_remove` box that no
+    /// handle in this process owns - i.e. its creator died before removing it.
+    /// Runs on startup; call it periodically to clean up while running.
+    ///
+    /// Returns the IDs of the removed boxes.
+    pub fn reap(&self) -> BoxliteResult<Vec<BoxID>> {
+        let mut reaped = Vec::new();
+
+        for (config, state) in self.box_manager.all_boxes(true)? {
+            let expired = config.is_expired();
+            let orphaned = config.options.auto_remove
+                && state.status.is_stopped()
+                && !self.is_box_owned(&config.id);
+            if !expired && !orphaned {
+                continue;
+            }
+
+            tracing::info!(
+                box_id = %config.id,
+                expired = expired,
+                orphaned = orphaned,
+                "Reaping box"
+            );
+            match self.remove_box_as(&config.id, true, EventKind::Reaped) {
+                Ok(()) => reaped.push(config.id),
+                Err(e) => tracing::warn!(box_id = %config.id, error = %e, "Failed to reap box"),
+            }
+        }
+
+        Ok(reaped)
+    }
+
+    /// Clone a box into a new, independent box.
+    ///
+    /// The clone gets copies of the source's COW disks (container rootfs and
+    /// guest rootfs), so it starts with the source's filesystem state while
+    /// sharing the read-only base images. Identity is regenerated: new box ID,
+    /// new container ID (and with it the hostname), new socket paths, and a
+    /// new machine ID written on its first boot. Host port mappings
+    /// (including SSH) are dropped because they would collide with the
+    /// source box.
+    ///
+    /// A running source is copied with its container rootfs frozen and keeps
+    /// running. The libkrun engine cannot snapshot memory, so the clone
+    /// boots from the copied disks rather than resuming the source's
+    /// processes.
+    pub async fn clone_box(
+        self: &Arc<Self>,
+        id_or_name: &str,
+        name: Option<String>,
+    ) -> BoxliteResult<LiteBox> {
+        if let Some(ref name) = name
+            && self.exists(name)?
+        {
+            return Err(BoxliteError::AlreadyExists(format!(
+                "box with name '{}' already exists",
+                name
+            )));
+        }
+
+        let (source_config, source_state) =
+            match self.box_manager.box_by_id(&self.resolve_id(id_or_name)?)? {
+                Some((config, state)) if state.status == BoxStatus::Running => (config, state),
+                _ => self.stopped_box(id_or_name, "clone")?,
+            };
+        let source_id = source_config.id.clone();
+        let running = source_state.status == BoxStatus::Running;
+        // A running box is copied under a frozen rootfs instead: attaching to
+        // it takes this same lock, which is not reentrant
+        let locker = self.box_locker(&source_state)?;
+        let _guard = locker.as_deref().filter(|_| !running).map(LockGuard::new);
+
+        let mut options = source_config.options.clone();
+        options.ports.clear();
+        options.ssh = None;
+        let options = self.hooks.pre_create(options, name.as_deref())?;
+        self.policy.check(&options)?;
+
+        let (config, mut state) = self.init_box_variables(&options, name);
+        state.set_status(BoxStatus::Stopped);
+        let _reservation = self.reserve_quota(&config)?;
+
+        let source_layout = self.stopped_box_layout(&source_config)?;
+        let layout = self.stopped_box_layout(&config)?;
+        layout.prepare()?;
+
+        let copied = if running {
+            let (source_box, _) =
+                self.get_or_create_box_impl(source_config.clone(), source_state.clone());
+            let (from, to) = (source_layout.clone(), layout.clone());
+            source_box
+                .with_frozen_rootfs(move || Self::copy_box_disks(&from, &to))
+                .await
+        } else {
+            Self::copy_box_disks(&source_layout, &layout)
+        };
+        let lock_id = match copied
+            .and_then(|_| resume::mark(&config.box_home, ResumeReason::Clone))
+            .and_then(|_| self.lock_manager.allocate())
+        {
+            Ok(lock_id) => lock_id,
+            Err(e) => {
+                let _ = layout.cleanup();
+                return Err(e);
+            }
+        };
+        state.set_lock_id(lock_id);
+
+        if let Err(e) = self.box_manager.add_box(&config, &state) {
+            let _ = self.lock_manager.free(lock_id);
+            let _ = layout.cleanup();
+            return Err(e);
+        }
+
+        // The copied disks sit on the source's snapshot layers (if any)
+        if let Err(e) = self.snapshot_manager.copy_history(&source_id, &config.id) {
+            let _ = self.remove_box(&config.id, true);
+            return Err(e);
+        }
+
+        tracing::info!(
+            source_id = %source_id,
+            box_id = %config.id,
+            "Cloned box"
+        );
+
+        self.runtime_metrics
+            .boxes_created
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        self.events.emit(
+            EventKind::Created,
+            &config,
+            [("source", source_id.to_string())],
+        );
+
+        let (box_impl, _) = self.get_or_create_box_impl(config, state);
+        Ok(LiteBox::new(box_impl))
+    }
+
+    /// Write a stopped box as a bundle another host can import.
+    pub fn export_box(
+        &self,
+        id_or_name: &str,
+        options: &ExportOptions,
+        writer: impl std::io::Write,
+    ) -> BoxliteResult<()> {
+        let (config, state) = self.stopped_box(id_or_name, "export")?;
+        let locker = self.box_locker(&state)?;
+        let _guard = locker.as_deref().map(LockGuard::new);
+        let layout = self.stopped_box_layout(&config)?;
+        let (roots, snapshot) = match &options.snapshot {
+            None => (migration::box_disks(&layout)?, None),
+            Some(snapshot) => {
+                let snapshot = self.snapshot_manager.get(&config.id, snapshot)?;
+                let (disk_layer, guest_layer) = self.snapshot_manager.layers(&snapshot);
+                let roots = migration::snapshot_disks(&layout, disk_layer, guest_layer)?;
+                (roots, Some(snapshot.name))
+            }
+        };
+        migration::export(
+            &config.id,
+            config.name.clone(),
+            &config.options,
+            roots,
+            options.include_volumes,
+            snapshot,
+            writer,
+        )?;
+
+        tracing::info!(box_id = %config.id, "Exported box");
+        self.events.emit(EventKind::Exported, &config, []);
+        Ok(())
+    }
+
+    /// Create a stopped box from a bundle written by `export_box`.
+    ///
+    /// The box keeps the exported name unless `name` is given, and gets a
+    /// fresh box ID and container ID.
+    pub fn import_box(
+        self: &Arc<Self>,
+        reader: impl std::io::Read,
+        name: Option<String>,
+    ) -> BoxliteResult<LiteBox> {
+        let mut created: Option<(
+            BoxConfig,
+            BoxState,
+            BoxFilesystemLayout,
+            QuotaReservation<'_>,
+        )> = None;
+        let imported = migration::import(reader, |manifest| {
+            let name = name.or_else(|| manifest.name.clone());
+            if let Some(ref name) = name
+                && self.exists(name)?
+            {
+                return Err(BoxliteError::AlreadyExists(format!(
+                    "box with name '{}' already exists",
+                    name
+                )));
+            }
+            let options = self
+                .hooks
+                .pre_create(migration::imported_options(manifest)?, name.as_deref())?;
+
+            let (mut config, mut state) = self.init_box_variables(&options, name);
+            state.set_status(BoxStatus::Stopped);
+            let layout = self.stopped_box_layout(&config)?;
+            // Bundled volumes are mounted from the box's own copy
+            for &index in &manifest.volumes {
+                if let Some(volume) = config.options.volumes.get_mut(index) {
+                    volume.host_path = layout
+                        .imported_volume_dir(index)
+                        .to_string_lossy()
+                        .into_owned();
+                }
+            }
+            self.policy.check(&config.options)?;
+            let reservation = self.reserve_quota(&config)?;
+            layout.prepare()?;
+            let box_dir = config.box_home.clone();
+            created = Some((config, state, layout, reservation));
+            Ok(box_dir)
+        });
+        let manifest = match imported {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                if let Some((_, _, layout, _)) = &created {
+                    let _ = layout.cleanup();
+                }
+                return Err(e);
+            }
+        };
+        let Some((config, mut state, layout, _reservation)) = created else {
+            return Err(BoxliteError::Internal(
+                "bundle imported without a box".into(),
+            ));
+        };
+
+        let lock_id = match resume::mark(&config.box_home, ResumeReason::Import)
+            .and_then(|_| self.lock_manager.allocate())
+        {
+            Ok(lock_id) => lock_id,
+            Err(e) => {
+                let _ = layout.cleanup();
+                return Err(e);
+            }
+        };
+        state.set_lock_id(lock_id);
+
+        if let Err(e) = self.box_manager.add_box(&config, &state) {
+            let _ = self.lock_manager.free(lock_id);
+            let _ = layout.cleanup();
+            return Err(e);
+        }
+
+        tracing::info!(
+            source_id = %manifest.source_id,
+            box_id = %config.id,
+            "Imported box"
+        );
+
+        self.runtime_metrics
+            .boxes_created
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        self.events.emit(
+            EventKind::Created,
+            &config,
+            [("imported_from", manifest.source_id.to_string())],
+        );
+
+        let (box_impl, _) = self.get_or_create_box_impl(config, state);
+        Ok(LiteBox::new(box_impl))
+    }
+
+    // ========================================================================
+    // PUBLIC API - SNAPSHOT OPERATIONS
+    // ========================================================================
+
+    /// Snapshot a stopped box's disks under the given name.
+    pub fn snapshot(&self, id_or_name: &str, name: &str) -> BoxliteResult<SnapshotInfo> {
+        let (config, state) = self.stopped_box(id_or_name, "snapshot")?;
+        let locker = self.box_locker(&state)?;
+        let _guard = locker.as_deref().map(LockGuard::new);
+        let layout = self.stopped_box_layout(&config)?;
+        let snapshot = self.snapshot_manager.create(&config.id, &layout, name)?;
+
+        self.events.emit(
+            EventKind::Snapshotted,
+            &config,
+            [("snapshot", snapshot.name.clone())],
+        );
+        Ok(snapshot)
+    }
+
+    /// List a box's snapshots, oldest first.
+    pub fn list_snapshots(&self, id_or_name: &str) -> BoxliteResult<Vec<SnapshotInfo>> {
+        let box_id = self.resolve_id(id_or_name)?;
+        self.snapshot_manager.list(&box_id)
+    }
+
+    /// Restore a stopped box's disks to one of its snapshots.
+    ///
+    /// Changes made since the box's current snapshot are discarded.
+    pub fn restore_snapshot(&self, id_or_name: &str, snapshot: &str) -> BoxliteResult<()> {
+        let (config, state) = self.stopped_box(id_or_name, "restore")?;
+        let locker = self.box_locker(&state)?;
+        let _guard = locker.as_deref().map(LockGuard::new);
+        let layout = self.stopped_box_layout(&config)?;
+        let snapshot = self.snapshot_manager.get(&config.id, snapshot)?;
+        let operation = self.operations.start(
+            OperationKind::SnapshotRestore,
+            snapshot.name.clone(),
+            Some(config.id.clone()),
+        );
+        let restored = self
+            .snapshot_manager
+            .restore(&layout, &snapshot, operation.progress());
+        operation.finish(&restored);
+        restored?;
+        resume::mark(&config.box_home, ResumeReason::Restore)?;
+
+        self.events
+            .emit(EventKind::Restored, &config, [("snapshot", snapshot.name)]);
+        Ok(())
+    }
+
+    /// Remove one of a stopped box's snapshots and free layers no longer
+    /// referenced.
+    pub fn remove_snapshot(&self, id_or_name: &str, snapshot: &str) -> BoxliteResult<()> {
+        let (config, state) = self.stopped_box(id_or_name, "remove a snapshot of")?;
+        let locker = self.box_locker(&state)?;
+        let _guard = locker.as_deref().map(LockGuard::new);
+        let layout = self.stopped_box_layout(&config)?;
+        let snapshot = self.snapshot_manager.get(&config.id, snapshot)?;
+        self.snapshot_manager.remove(&layout, &snapshot)
+    }
+
     // ========================================================================
     // PUBLIC API - QUERY OPERATIONS
     // ========================================================================
@@ -323,13 +860,21 @@ impl RuntimeImpl {
         Ok(None)
     }
 
+    /// Boxes of this runtime that are running now.
+    pub(crate) fn running_boxes(&self) -> Vec<SharedBoxImpl> {
+        let sync = self.sync_state.read().unwrap();
+        sync.active_boxes_by_id
+            .values()
+            .filter_map(Weak::upgrade)
+            .filter(|box_impl| box_impl.state.read().status.is_running())
+            .collect()
+    }
+
     /// List all boxes, sorted by creation time (newest first).
     ///
     /// Includes both persisted boxes (from database) and in-memory boxes
     /// (created but not yet persisted).
     pub fn list_info(&self) -> BoxliteResult<Vec<BoxInfo>> {
-        use std::collections::HashSet;
-
         // Get boxes from database
         let db_boxes = self.box_manager.all_boxes(true)?;
         let mut seen_ids: HashSet<BoxID> = db_boxes.iter().map(|(c, _)| c.id.clone()).collect();
@@ -352,7 +897,14 @@ impl RuntimeImpl {
         }
 
         // Sort by creation time (newest first)
-        infos.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        infos.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+        Ok(infos)
+    }
+
+    /// List boxes matching a filter, newest first.
+    pub fn list_info_filtered(&self, filter: &BoxFilter) -> BoxliteResult<Vec<BoxInfo>> {
+        let mut infos = self.list_info()?;
+        infos.retain(|info| filter.matches(info));
         Ok(infos)
     }
 
@@ -393,6 +945,246 @@ impl RuntimeImpl {
         RuntimeMetrics::new(self.runtime_metrics.clone())
     }
 
+    pub fn capacity(&self) -> CapacityReport {
+        self.planner.report()
+    }
+
+    /// Write a diagnostics bundle for a box.
+    pub fn diagnose(&self, id_or_name: &str, writer: impl std::io::Write) -> BoxliteResult<()> {
+        let active = {
+            let sync = self.sync_state.read().unwrap();
+            BoxID::parse(id_or_name)
+                .and_then(|box_id| sync.active_boxes_by_id.get(&box_id))
+                .or_else(|| sync.active_boxes_by_name.get(id_or_name))
+                .and_then(Weak::upgrade)
+        };
+        let (config, state) = match active {
+            Some(active) => (active.config.clone(), active.state.read().clone()),
+            None => self
+                .box_manager
+                .lookup_box(id_or_name)?
+                .ok_or_else(|| BoxliteError::NotFound(id_or_name.to_string()))?,
+        };
+        self.diagnostics(&config, &state, None)?.write(writer)
+    }
+
+    /// Save a diagnostics bundle for a box that failed, logging where.
+    pub(crate) fn save_diagnostics(&self, config: &BoxConfig, state: &BoxState, failure: &str) {
+        let saved = self
+            .diagnostics(config, state, Some(failure.to_string()))
+            .and_then(|diagnostics| diagnostics.save());
+        match saved {
+            Ok(path) => tracing::warn!(
+                box_id = %config.id,
+                path = %path.display(),
+                "Saved diagnostics for failed box"
+            ),
+            Err(e) => tracing::warn!(box_id = %config.id, "Failed to save diagnostics: {}", e),
+        }
+    }
+
+    fn diagnostics<'a>(
+        &'a self,
+        config: &'a BoxConfig,
+        state: &'a BoxState,
+        failure: Option<String>,
+    ) -> BoxliteResult<Diagnostics<'a>> {
+        let mut events = self.events.history(None)?;
+        events.retain(|event| event.box_id == config.id);
+        Ok(Diagnostics {
+            layout: &self.layout,
+            config,
+            state,
+            events,
+            failure,
+        })
+    }
+
+    /// Resolve and check a box spec as `create` would, creating nothing.
+    pub async fn dry_run(
+        &self,
+        options: &BoxOptions,
+        name: Option<&str>,
+    ) -> BoxliteResult<DryRunReport> {
+        let mut issues = Vec::new();
+        let spec = dry_run::resolve(options, &mut issues);
+
+        if let Some(name) = name
+            && self.exists(name)?
+        {
+            issues.push(SpecIssue::new(
+                "name",
+                format!("box with name '{}' already exists", name),
+            ));
+        }
+        for (rule, reason) in self.policy.violations(&spec) {
+            issues.push(SpecIssue::new(format!("policy.{}", rule), reason));
+        }
+        if let Err(e) = self.check_quota(&self.sync_state.read().unwrap(), &spec) {
+            issues.push(SpecIssue::new("quota", e));
+        }
+
+        let mut image_digest = None;
+        if let RootfsSpec::Image(image) = &spec.rootfs {
+            match self.image_manager.resolve(image).await {
+                Ok(digest) => image_digest = Some(digest),
+                Err(e) => issues.push(SpecIssue::new("rootfs", e)),
+            }
+        }
+        dry_run::check_host(&spec, &mut issues);
+
+        Ok(DryRunReport {
+            spec,
+            image_digest,
+            issues,
+        })
+    }
+
+    /// Resolve a box spec and pin what it resolved to.
+    pub async fn lock(&self, options: &BoxOptions) -> BoxliteResult<BoxLock> {
+        let spec = self.locked_spec(options)?;
+        let image = match &spec.rootfs {
+            RootfsSpec::Image(reference) => Some(LockedImage {
+                reference: reference.clone(),
+                digest: self.image_manager.resolve(reference).await?,
+            }),
+            _ => None,
+        };
+        let guest_image = LockedImage {
+            reference: images::INIT_ROOTFS.to_string(),
+            digest: self.image_manager.resolve(images::INIT_ROOTFS).await?,
+        };
+        BoxLock::new(&spec, image, guest_image)
+    }
+
+    /// Create a box from a spec that must still match `lock`, pulling the
+    /// locked image digest.
+    pub async fn create_locked(
+        self: &Arc<Self>,
+        options: BoxOptions,
+        lock: &BoxLock,
+        name: Option<String>,
+    ) -> BoxliteResult<LiteBox> {
+        let spec = self.locked_spec(&options)?;
+        let guest_digest = self.image_manager.resolve(images::INIT_ROOTFS).await?;
+        lock.check(&spec, &guest_digest)?;
+        self.create(lock.pin(options), name)
+    }
+
+    /// The spec a lock is computed over; a spec that would not be created
+    /// is not locked.
+    fn locked_spec(&self, options: &BoxOptions) -> BoxliteResult<BoxOptions> {
+        let mut issues = Vec::new();
+        let spec = dry_run::resolve(options, &mut issues);
+        match issues.into_iter().next() {
+            Some(issue) => Err(BoxliteError::InvalidArgument(issue.to_string())),
+            None => Ok(spec),
+        }
+    }
+
+    // ========================================================================
+    // PUBLIC API - DISK USAGE
+    // ========================================================================
+
+    /// Report space used by images, snapshots, the package cache and boxes.
+    pub async fn disk_usage(&self) -> BoxliteResult<DiskUsage> {
+        let records = self.box_records()?;
+        let survivors: Vec<&BoxConfig> = records
+            .iter()
+            .filter(|(_, info)| info.status.is_active())
+            .map(|(config, _)| config)
+            .collect();
+        let kept_disks = self.disks_in_use(&survivors)?;
+        let unused_images = self
+            .image_manager
+            .prune(&self.kept_images(&survivors)?, &kept_disks, true)
+            .await?;
+
+        let boxes = records
+            .iter()
+            .map(|(config, info)| BoxDiskUsage {
+                id: config.id.clone(),
+                name: config.name.clone(),
+                status: info.status,
+                scratch_bytes: allocated_size(&config.box_home),
+                volumes: config
+                    .options
+                    .volumes
+                    .iter()
+                    .map(|volume| VolumeUsage {
+                        host_path: volume.host_path.clone(),
+                        guest_path: volume.guest_path.clone(),
+                        bytes: allocated_size(Path::new(&volume.host_path)),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(DiskUsage {
+            images: UsageEntry {
+                bytes: allocated_size(&self.layout.images_dir()),
+                reclaimable_bytes: unused_images.bytes,
+            },
+            snapshots: UsageEntry {
+                bytes: allocated_size(&self.layout.snapshots_dir()),
+                reclaimable_bytes: self.unused_snapshot_layer_bytes(&kept_disks),
+            },
+            package_cache: UsageEntry {
+                bytes: allocated_size(&self.layout.package_cache_dir()),
+                reclaimable_bytes: 0,
+            },
+            boxes,
+        })
+    }
+
+    /// Remove stopped boxes and, optionally, images nothing uses anymore.
+    pub async fn system_prune(&self, options: &SystemPruneOptions) -> BoxliteResult<PruneReport> {
+        let records = self.box_records()?;
+        let (doomed, survivors): (Vec<_>, Vec<_>) = records
+            .iter()
+            .partition(|(_, info)| !info.status.is_active() && options.filter.matches(info));
+        let survivors: Vec<&BoxConfig> = survivors.into_iter().map(|(config, _)| config).collect();
+        let kept_disks = self.disks_in_use(&survivors)?;
+
+        let mut report = PruneReport {
+            reclaimed_bytes: self.unused_snapshot_layer_bytes(&kept_disks)
+                + doomed
+                    .iter()
+                    .map(|(config, _)| allocated_size(&config.box_home))
+                    .sum::<u64>(),
+            ..Default::default()
+        };
+        for (config, _) in &doomed {
+            if !options.dry_run {
+                self.remove_box_as(&config.id, false, EventKind::Pruned)?;
+            }
+            report.boxes.push(config.id.clone());
+        }
+
+        if options.images {
+            let images = self
+                .image_manager
+                .prune(&self.kept_images(&survivors)?, &kept_disks, options.dry_run)
+                .await?;
+            report.images = images.references;
+            report.reclaimed_bytes += images.bytes;
+        }
+        Ok(report)
+    }
+
+    /// Images pruning keeps: those of `survivors`, and those baked images
+    /// start from.
+    fn kept_images(&self, survivors: &[&BoxConfig]) -> BoxliteResult<HashSet<String>> {
+        let mut kept = image_references(survivors);
+        kept.extend(
+            self.baked_images
+                .list()?
+                .into_iter()
+                .map(|image| image.base_image),
+        );
+        Ok(kept)
+    }
+
     // ========================================================================
     // INTERNAL - BOX OPERATIONS
     // ========================================================================
@@ -427,6 +1219,84 @@ impl RuntimeImpl {
             .ok_or_else(|| BoxliteError::NotFound(id_or_name.to_string()))
     }
 
+    /// Check that a new box with `options` fits in the host quota.
+    ///
+    /// Counts every persisted box plus in-memory boxes not yet persisted and
+    /// reservations. Hold `sync` until the new box is cached or reserved, so
+    /// that a concurrent create sees it.
+    fn check_quota(&self, sync: &SynchronizedState, options: &BoxOptions) -> BoxliteResult<()> {
+        let db_boxes = self.box_manager.all_boxes(true)?;
+        let persisted = |box_id: &BoxID| db_boxes.iter().any(|(c, _)| &c.id == box_id);
+        let mut used = ResourceUsage::default();
+        for (config, _) in &db_boxes {
+            used.add(ResourceUsage::of(&config.options));
+        }
+        for (box_id, weak) in &sync.active_boxes_by_id {
+            if !persisted(box_id)
+                && let Some(strong) = weak.upgrade()
+            {
+                used.add(ResourceUsage::of(&strong.config.options));
+            }
+        }
+        for (box_id, reserved) in &sync.quota_reservations {
+            if !persisted(box_id) {
+                used.add(*reserved);
+            }
+        }
+
+        self.quota.check(used, ResourceUsage::of(options))
+    }
+
+    /// Check the quota for a box that is persisted only once its disks are
+    /// in place, and hold its share until the returned reservation drops.
+    fn reserve_quota(&self, config: &BoxConfig) -> BoxliteResult<QuotaReservation<'_>> {
+        let mut sync = self.sync_state.write().unwrap();
+        self.check_quota(&sync, &config.options)?;
+        sync.quota_reservations
+            .insert(config.id.clone(), ResourceUsage::of(&config.options));
+        Ok(QuotaReservation {
+            runtime: self,
+            box_id: config.id.clone(),
+        })
+    }
+
+    /// Load a persisted box and require it to be stopped.
+    ///
+    /// Disk-level operations (clone, snapshot, restore) need consistent disks,
+    /// which only a stopped box guarantees.
+    fn stopped_box(&self, id_or_name: &str, action: &str) -> BoxliteResult<(BoxConfig, BoxState)> {
+        let box_id = self.resolve_id(id_or_name)?;
+        let (config, state) = self.box_manager.box_by_id(&box_id)?.ok_or_else(|| {
+            BoxliteError::InvalidState(format!(
+                "cannot {} box {}: it has never been started",
+                action, box_id
+            ))
+        })?;
+
+        if state.status != BoxStatus::Stopped {
+            return Err(BoxliteError::InvalidState(format!(
+                "cannot {} box {} (status: {:?}): stop the box first",
+                action, box_id, state.status
+            )));
+        }
+
+        Ok((config, state))
+    }
+
+    /// Retrieve the box's entity lock, held during disk operations so the
+    /// box cannot be started concurrently.
+    fn box_locker(&self, state: &BoxState) -> BoxliteResult<Option<Arc<dyn Locker>>> {
+        state
+            .lock_id
+            .map(|lock_id| self.lock_manager.retrieve(lock_id))
+            .transpose()
+    }
+
+    fn stopped_box_layout(&self, config: &BoxConfig) -> BoxliteResult<BoxFilesystemLayout> {
+        self.layout
+            .box_layout(config.id.as_str(), config.options.isolate_mounts)
+    }
+
     /// Remove a box from the runtime (internal implementation).
     ///
     /// This is the internal implementation called by both `BoxliteRuntime::remove()`
@@ -443,11 +1313,77 @@ impl RuntimeImpl {
     /// - Box not found
     /// - Box is active and force=false
     pub(crate) fn remove_box(&self, id: &BoxID, force: bool) -> BoxliteResult<()> {
+        self.remove_box_as(id, force, EventKind::Removed)
+    }
+
+    /// Every box with its config, including in-memory ones not yet persisted.
+    fn box_records(&self) -> BoxliteResult<Vec<(BoxConfig, BoxInfo)>> {
+        let mut records: Vec<(BoxConfig, BoxInfo)> = self
+            .box_manager
+            .all_boxes(true)?
+            .into_iter()
+            .map(|(config, state)| {
+                let info = BoxInfo::new(&config, &state);
+                (config, info)
+            })
+            .collect();
+
+        {
+            let sync = self.sync_state.read().unwrap();
+            for (box_id, weak) in &sync.active_boxes_by_id {
+                if !records.iter().any(|(config, _)| &config.id == box_id)
+                    && let Some(strong) = weak.upgrade()
+                {
+                    records.push((strong.config.clone(), strong.info()));
+                }
+            }
+        }
+
+        records.sort_by_key(|(config, _)| std::cmp::Reverse(config.created_at));
+        Ok(records)
+    }
+
+    /// Disk files the given boxes depend on: their own disks, their
+    /// snapshot layers, and the backing chains (image base disks) of both.
+    fn disks_in_use(&self, configs: &[&BoxConfig]) -> BoxliteResult<HashSet<PathBuf>> {
+        let mut disks = HashSet::new();
+        for config in configs {
+            if let Ok(entries) = std::fs::read_dir(&config.box_home) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.extension().is_some_and(|ext| ext == "qcow2") {
+                        disk_usage::backing_chain(&path, &mut disks);
+                    }
+                }
+            }
+            for layer in self.snapshot_manager.layer_paths(&config.id)? {
+                disk_usage::backing_chain(&layer, &mut disks);
+            }
+        }
+        Ok(disks)
+    }
+
+    /// Bytes of snapshot layers outside `kept_disks`, which pruning frees.
+    fn unused_snapshot_layer_bytes(&self, kept_disks: &HashSet<PathBuf>) -> u64 {
+        let Ok(entries) = std::fs::read_dir(self.layout.snapshots_dir()) else {
+            return 0;
+        };
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| !kept_disks.contains(path))
+            .map(|path| allocated_size(&path))
+            .sum()
+    }
+
+    /// Remove a box, recording the removal as the given event kind.
+    fn remove_box_as(&self, id: &BoxID, force: bool, kind: EventKind) -> BoxliteResult<()> {
         tracing::debug!(box_id = %id, force = force, "RuntimeInnerImpl::remove_box called");
 
         // Try to get box from database first
         if let Some((config, state)) = self.box_manager.box_by_id(id)? {
             // Box exists in database - handle as before
+            self.hooks.pre_destroy(&config, kind)?;
             let mut state = state;
             if state.status.is_active() {
                 if force {
@@ -456,6 +1392,8 @@ impl RuntimeImpl {
                         tracing::info!(box_id = %id, pid = pid, "Force killing active box");
                         crate::util::kill_process(pid);
                     }
+                    crate::vmm::priority::release(self.cgroup_parent.as_deref(), id);
+                    self.pressure.forget(id);
                     // Update status to stopped and save
                     state.set_status(BoxStatus::Stopped);
                     state.set_pid(None);
@@ -491,9 +1429,9 @@ impl RuntimeImpl {
             }
 
             // Delete box directory
-            let box_home = config.box_home;
+            let box_home = &config.box_home;
             if box_home.exists()
-                && let Err(e) = std::fs::remove_dir_all(&box_home)
+                && let Err(e) = std::fs::remove_dir_all(box_home)
             {
                 tracing::warn!(
                     box_id = %id,
@@ -506,6 +1444,13 @@ impl RuntimeImpl {
             // Invalidate cache
             self.invalidate_box_impl(id, config.name.as_deref());
 
+            // Snapshot records were removed with the box; drop orphaned layers
+            if let Err(e) = self.snapshot_manager.gc_layers() {
+                tracing::warn!(box_id = %id, error = %e, "Failed to collect snapshot layers");
+            }
+
+            self.events.emit(kind, &config, []);
+
             tracing::info!(box_id = %id, "Removed box");
             return Ok(());
         }
@@ -528,6 +1473,7 @@ impl RuntimeImpl {
                 )));
             }
             drop(state);
+            self.hooks.pre_destroy(&box_impl.config, kind)?;
 
             // Invalidate cache (removes from in-memory maps)
             self.invalidate_box_impl(id, box_impl.config.name.as_deref());
@@ -545,6 +1491,8 @@ impl RuntimeImpl {
                 );
             }
 
+            self.events.emit(kind, &box_impl.config, []);
+
             tracing::info!(box_id = %id, "Removed in-memory box");
             return Ok(());
         }
@@ -592,7 +1540,7 @@ impl RuntimeImpl {
             created_at: now,
             container,
             options: options.clone(),
-            engine_kind: VmmKind::Libkrun,
+            engine_kind: self.engine_kind,
             transport: Transport::unix(socket_path),
             box_home,
             ready_socket_path,
@@ -604,6 +1552,43 @@ impl RuntimeImpl {
         (config, state)
     }
 
+    /// Copy the per-box COW disks from one box directory to another.
+    ///
+    /// Only the qcow2 overlays are copied; their backing files are the shared
+    /// base images, which both boxes keep referencing.
+    fn copy_box_disks(
+        source: &BoxFilesystemLayout,
+        target: &BoxFilesystemLayout,
+    ) -> BoxliteResult<()> {
+        let disk = source.disk_path();
+        if !disk.exists() {
+            return Err(BoxliteError::Storage(format!(
+                "cannot clone: container rootfs disk not found at {}",
+                disk.display()
+            )));
+        }
+
+        // Guest rootfs disk only exists for the disk-based guest rootfs strategy
+        let mut disks = vec![(disk, target.disk_path())];
+        let guest_disk = source.guest_rootfs_disk_path();
+        if guest_disk.exists() {
+            disks.push((guest_disk, target.guest_rootfs_disk_path()));
+        }
+
+        for (from, to) in disks {
+            std::fs::copy(&from, &to).map_err(|e| {
+                BoxliteError::Storage(format!(
+                    "failed to copy disk {} to {}: {}",
+                    from.display(),
+                    to.display(),
+                    e
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
     /// Recover boxes from persistent storage on runtime startup.
     fn recover_boxes(&self) -> BoxliteResult<()> {
         use crate::util::{is_process_alive, is_same_process};
@@ -655,6 +1640,13 @@ impl RuntimeImpl {
                     // Process died or PID was reused - mark as Stopped
                     if state.status.is_active() {
                         state.mark_crashed();
+                        self.events
+                            .emit(EventKind::Exited, &config, [("pid", pid.to_string())]);
+                        self.save_diagnostics(
+                            &config,
+                            &state,
+                            &format!("VM process (PID {}) was found dead", pid),
+                        );
                         tracing::warn!(
                             "Box {} marked as Stopped (PID {} not found or different process)",
                             box_id,
@@ -698,14 +1690,23 @@ impl RuntimeImpl {
         self: &Arc<Self>,
         config: BoxConfig,
         state: BoxState,
+    ) -> (SharedBoxImpl, bool) {
+        let mut sync = self.sync_state.write().unwrap();
+        self.cache_box_impl(&mut sync, config, state)
+    }
+
+    /// [`Self::get_or_create_box_impl`] with the coordination lock held.
+    fn cache_box_impl(
+        self: &Arc<Self>,
+        sync: &mut SynchronizedState,
+        config: BoxConfig,
+        state: BoxState,
     ) -> (SharedBoxImpl, bool) {
         use crate::litebox::box_impl::BoxImpl;
 
         let box_id = config.id.clone();
         let box_name = config.name.clone();
 
-        let mut sync = self.sync_state.write().unwrap();
-
         // Check by name first (if provided) - prevents duplicate names
         if let Some(ref name) = box_name
             && let Some(weak) = sync.active_boxes_by_name.get(name)
@@ -743,6 +1744,14 @@ impl RuntimeImpl {
         (box_impl, true)
     }
 
+    /// Check whether a live handle in this process owns the box.
+    fn is_box_owned(&self, box_id: &BoxID) -> bool {
+        let sync = self.sync_state.read().unwrap();
+        sync.active_boxes_by_id
+            .get(box_id)
+            .is_some_and(|weak| weak.strong_count() > 0)
+    }
+
     /// Remove BoxImpl from cache.
     ///
     /// Called when box is stopped or removed. Existing handles become stale;
@@ -769,6 +1778,20 @@ impl RuntimeImpl {
     }
 }
 
+/// A box's share of the quota, held from the check until the box is
+/// persisted and counted from the database.
+struct QuotaReservation<'a> {
+    runtime: &'a RuntimeImpl,
+    box_id: BoxID,
+}
+
+impl Drop for QuotaReservation<'_> {
+    fn drop(&mut self) {
+        let mut sync = self.runtime.sync_state.write().unwrap();
+        sync.quota_reservations.remove(&self.box_id);
+    }
+}
+
 impl std::fmt::Debug for RuntimeImpl {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RuntimeInner")
@@ -776,3 +1799,29 @@ impl std::fmt::Debug for RuntimeImpl {
             .finish()
     }
 }
+
+/// The engine boxes run in for `selection`.
+fn resolve_engine(selection: EngineSelection) -> VmmKind {
+    let kind = match selection {
+        EngineSelection::Vm => vm_engine(),
+        EngineSelection::Boxless => VmmKind::Boxless,
+        EngineSelection::Auto if doctor::hypervisor_available() => vm_engine(),
+        EngineSelection::Auto => VmmKind::Boxless,
+    };
+    if kind == VmmKind::Boxless {
+        tracing::warn!(
+            "Running boxes without a VM (boxless engine): they share the host kernel and \
+             network and are isolated only by namespaces and seccomp. Do not run untrusted \
+             code this way."
+        );
+    }
+    kind
+}
+
+/// The hypervisor engine for this host.
+///
+/// WHPX is never picked on its own: it cannot boot guests yet, so Windows
+/// hosts are unsupported (see the FAQ) rather than failing at every start.
+fn vm_engine() -> VmmKind {
+    VmmKind::Libkrun
+}
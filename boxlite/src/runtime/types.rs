@@ -248,6 +248,12 @@ pub struct BoxInfo {
 
     /// User-defined labels for filtering and organization.
     pub labels: HashMap<String, String>,
+
+    /// Engine the box runs in. `Boxless` means no VM: isolation is weaker.
+    pub engine: crate::vmm::VmmKind,
+
+    /// Probe health while running; None without probes or when stopped.
+    pub health: Option<crate::litebox::BoxHealth>,
 }
 
 impl BoxInfo {
@@ -269,7 +275,9 @@ impl BoxInfo {
             },
             cpus: config.options.cpus.unwrap_or(2),
             memory_mib: config.options.memory_mib.unwrap_or(512),
-            labels: HashMap::new(),
+            labels: config.options.labels.clone(),
+            engine: config.engine_kind,
+            health: state.health.clone().filter(|_| state.status.is_running()),
         }
     }
 }
@@ -284,6 +292,7 @@ impl PartialEq for BoxInfo {
             && self.cpus == other.cpus
             && self.memory_mib == other.memory_mib
             && self.labels == other.labels
+            && self.engine == other.engine
     }
 }
 
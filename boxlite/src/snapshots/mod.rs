@@ -0,0 +1,500 @@
+//! Layered disk snapshots.
+//!
+//! A snapshot freezes a box's current qcow2 overlays into immutable layers and
+//! puts a fresh, empty overlay on top of them. Because every overlay only holds
+//! the blocks written since the previous snapshot, a chain of snapshots is a
+//! base image plus small deltas.
+//!
+//! ```text
+//! image base ← layer A ← layer B ← disk.qcow2 (live writes)
+//!                 ↑          ↑
+//!            snapshot 1  snapshot 2
+//! ```
+//!
+//! Layers are stored content-addressed in `~/.boxlite/snapshots/` and shared by
+//! every snapshot (and cloned box) that references them. Restoring a snapshot
+//! replaces the live overlay with a new empty one on top of that snapshot's
+//! layer, so any point in the chain can be restored.
+//!
+//! A snapshot's record is saved before its layers are moved into the store,
+//! and layers are only moved or collected under one lock, so collection never
+//! sees a layer that is about to be referenced.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db::{Database, SnapshotStore};
+use crate::disk::{BackingFormat, Qcow2Helper};
+use crate::runtime::layout::BoxFilesystemLayout;
+use crate::runtime::operations::Progress;
+use crate::runtime::types::BoxID;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Public metadata about a box snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Unique snapshot identifier (ULID).
+    pub id: String,
+    /// Box this snapshot belongs to.
+    pub box_id: BoxID,
+    /// User-defined name (unique per box).
+    pub name: String,
+    /// Snapshot this one was taken on top of, if any.
+    pub parent_id: Option<String>,
+    /// Content digest of the frozen container rootfs layer.
+    pub disk_layer: String,
+    /// Content digest of the frozen guest rootfs layer, if the box has one.
+    pub guest_disk_layer: Option<String>,
+    /// On-disk size of the layers this snapshot added (its delta), in bytes.
+    pub size_bytes: u64,
+    /// Creation timestamp.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Creates, restores, and garbage-collects snapshot layers.
+#[derive(Clone)]
+pub(crate) struct SnapshotManager {
+    store: SnapshotStore,
+    layers_dir: PathBuf,
+    /// Held while layers are added to or collected from `layers_dir`.
+    layers_lock: Arc<Mutex<()>>,
+}
+
+impl SnapshotManager {
+    pub(crate) fn new(db: Database, layers_dir: PathBuf) -> Self {
+        Self {
+            store: SnapshotStore::new(db),
+            layers_dir,
+            layers_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Find a snapshot of a box by snapshot ID or name.
+    pub(crate) fn get(&self, box_id: &BoxID, id_or_name: &str) -> BoxliteResult<SnapshotInfo> {
+        self.store.get(box_id.as_str(), id_or_name)?.ok_or_else(|| {
+            BoxliteError::NotFound(format!("snapshot '{}' of box {}", id_or_name, box_id))
+        })
+    }
+
+    /// List snapshots of a box, oldest first.
+    pub(crate) fn list(&self, box_id: &BoxID) -> BoxliteResult<Vec<SnapshotInfo>> {
+        self.store.list(box_id.as_str())
+    }
+
+    /// Layer files a box's snapshots reference.
+    pub(crate) fn layer_paths(&self, box_id: &BoxID) -> BoxliteResult<Vec<PathBuf>> {
+        Ok(self
+            .list(box_id)?
+            .iter()
+            .flat_map(|snapshot| {
+                std::iter::once(&snapshot.disk_layer).chain(&snapshot.guest_disk_layer)
+            })
+            .map(|digest| self.layer_path(digest))
+            .collect())
+    }
+
+    /// The frozen container rootfs layer of a snapshot, and its guest
+    /// rootfs layer if it has one.
+    pub(crate) fn layers(&self, snapshot: &SnapshotInfo) -> (PathBuf, Option<PathBuf>) {
+        (
+            self.layer_path(&snapshot.disk_layer),
+            snapshot
+                .guest_disk_layer
+                .as_deref()
+                .map(|digest| self.layer_path(digest)),
+        )
+    }
+
+    /// Snapshot the box's disks.
+    ///
+    /// The box must be stopped so the overlays are consistent.
+    pub(crate) fn create(
+        &self,
+        box_id: &BoxID,
+        layout: &BoxFilesystemLayout,
+        name: &str,
+    ) -> BoxliteResult<SnapshotInfo> {
+        if name.is_empty() {
+            return Err(BoxliteError::InvalidArgument(
+                "snapshot name must not be empty".into(),
+            ));
+        }
+        if self.store.get(box_id.as_str(), name)?.is_some() {
+            return Err(BoxliteError::AlreadyExists(format!(
+                "snapshot '{}' of box {}",
+                name, box_id
+            )));
+        }
+
+        let disk = layout.disk_path();
+        if !disk.exists() {
+            return Err(BoxliteError::Storage(format!(
+                "cannot snapshot: container rootfs disk not found at {}",
+                disk.display()
+            )));
+        }
+
+        let parent_id = self.head(box_id, layout)?.map(|s| s.id);
+
+        let (disk_layer, mut size_bytes) = Self::content(&disk)?;
+        let guest_disk = layout.guest_rootfs_disk_path();
+        let guest_disk_layer = if guest_disk.exists() {
+            let (digest, size) = Self::content(&guest_disk)?;
+            size_bytes += size;
+            Some(digest)
+        } else {
+            None
+        };
+
+        let mut snapshot = SnapshotInfo {
+            id: ulid::Ulid::new().to_string(),
+            box_id: box_id.clone(),
+            name: name.to_string(),
+            parent_id,
+            disk_layer,
+            guest_disk_layer,
+            size_bytes,
+            created_at: Utc::now(),
+        };
+
+        // Referenced before they exist, so collection cannot take them
+        let _layers = self.layers_lock.lock();
+        self.store.save(&snapshot)?;
+        if let Err(e) = self.freeze(&disk, &snapshot.disk_layer) {
+            let _ = self.store.delete(&snapshot.id);
+            return Err(e);
+        }
+        if let Some(layer) = snapshot.guest_disk_layer.take() {
+            if let Err(e) = self.freeze(&guest_disk, &layer) {
+                // The container disk already sits on its layer; keep it
+                // referenced by a snapshot without the guest disk
+                let _ = self
+                    .store
+                    .delete(&snapshot.id)
+                    .and_then(|()| self.store.save(&snapshot));
+                return Err(e);
+            }
+            snapshot.guest_disk_layer = Some(layer);
+        }
+
+        tracing::info!(
+            box_id = %box_id,
+            snapshot_id = %snapshot.id,
+            name = %snapshot.name,
+            size_bytes = snapshot.size_bytes,
+            "Created snapshot"
+        );
+
+        Ok(snapshot)
+    }
+
+    /// Restore the box's disks to a snapshot.
+    ///
+    /// Writes made since the box's current snapshot are discarded. A guest
+    /// rootfs disk the snapshot did not capture is reset to its base image.
+    /// Each disk rebased counts as one step of `progress`.
+    pub(crate) fn restore(
+        &self,
+        layout: &BoxFilesystemLayout,
+        snapshot: &SnapshotInfo,
+        progress: &Progress,
+    ) -> BoxliteResult<()> {
+        let guest_disk = layout.guest_rootfs_disk_path();
+        let guest_disk_exists = guest_disk.exists();
+        progress.set_total(1 + (snapshot.guest_disk_layer.is_some() || guest_disk_exists) as u64);
+        self.rebase(&layout.disk_path(), &self.layer_path(&snapshot.disk_layer))?;
+        progress.advance(1);
+        if let Some(ref layer) = snapshot.guest_disk_layer {
+            self.rebase(&guest_disk, &self.layer_path(layer))?;
+            progress.advance(1);
+        } else if guest_disk_exists {
+            self.reset_to_base(&guest_disk)?;
+            progress.advance(1);
+        }
+
+        tracing::info!(
+            box_id = %snapshot.box_id,
+            snapshot_id = %snapshot.id,
+            name = %snapshot.name,
+            "Restored snapshot"
+        );
+
+        Ok(())
+    }
+
+    /// Remove a snapshot record and any layers no longer referenced.
+    ///
+    /// Only leaf snapshots that the box is not currently running on top of
+    /// can be removed; their layers are still needed otherwise.
+    pub(crate) fn remove(
+        &self,
+        layout: &BoxFilesystemLayout,
+        snapshot: &SnapshotInfo,
+    ) -> BoxliteResult<()> {
+        if self.store.has_children(&snapshot.id)? {
+            return Err(BoxliteError::InvalidState(format!(
+                "snapshot '{}' has child snapshots and cannot be removed",
+                snapshot.name
+            )));
+        }
+        if self
+            .head(&snapshot.box_id, layout)?
+            .is_some_and(|head| head.id == snapshot.id)
+        {
+            return Err(BoxliteError::InvalidState(format!(
+                "snapshot '{}' is the box's current base and cannot be removed",
+                snapshot.name
+            )));
+        }
+
+        let _layers = self.layers_lock.lock();
+        self.store.delete(&snapshot.id)?;
+        self.collect_layers()?;
+
+        tracing::info!(
+            box_id = %snapshot.box_id,
+            snapshot_id = %snapshot.id,
+            "Removed snapshot"
+        );
+
+        Ok(())
+    }
+
+    /// Copy a box's snapshot history to another box.
+    ///
+    /// Used when cloning: the clone's disks sit on top of the same layers, so
+    /// it needs its own records to keep those layers alive.
+    pub(crate) fn copy_history(&self, from: &BoxID, to: &BoxID) -> BoxliteResult<()> {
+        use std::collections::HashMap;
+
+        let mut new_ids: HashMap<String, String> = HashMap::new();
+        for snapshot in self.store.list(from.as_str())? {
+            let id = ulid::Ulid::new().to_string();
+            new_ids.insert(snapshot.id.clone(), id.clone());
+            self.store.save(&SnapshotInfo {
+                id,
+                box_id: to.clone(),
+                parent_id: snapshot
+                    .parent_id
+                    .as_ref()
+                    .and_then(|p| new_ids.get(p).cloned()),
+                ..snapshot
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Delete layer files that no snapshot references anymore.
+    pub(crate) fn gc_layers(&self) -> BoxliteResult<()> {
+        let _layers = self.layers_lock.lock();
+        self.collect_layers()
+    }
+
+    // ========================================================================
+    // INTERNAL
+    // ========================================================================
+
+    /// [`gc_layers`](Self::gc_layers) with the layers lock already held.
+    fn collect_layers(&self) -> BoxliteResult<()> {
+        let referenced = self.store.referenced_layers()?;
+
+        let entries = match std::fs::read_dir(&self.layers_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(digest) = Self::layer_digest(&path) else {
+                continue;
+            };
+            if referenced.contains(&digest) {
+                continue;
+            }
+            match std::fs::remove_file(&path) {
+                Ok(()) => tracing::debug!(layer = %digest, "Removed unreferenced snapshot layer"),
+                Err(e) => tracing::warn!(
+                    layer = %digest,
+                    error = %e,
+                    "Failed to remove unreferenced snapshot layer"
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The snapshot the box's live container disk currently sits on, if any.
+    fn head(
+        &self,
+        box_id: &BoxID,
+        layout: &BoxFilesystemLayout,
+    ) -> BoxliteResult<Option<SnapshotInfo>> {
+        let disk = layout.disk_path();
+        if !disk.exists() {
+            return Ok(None);
+        }
+        let Some(digest) =
+            Qcow2Helper::backing_file(&disk)?.and_then(|backing| Self::layer_digest(&backing))
+        else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .store
+            .list(box_id.as_str())?
+            .into_iter()
+            .rev()
+            .find(|s| s.disk_layer == digest))
+    }
+
+    /// Digest and size of the layer a live overlay would freeze into.
+    fn content(disk: &Path) -> BoxliteResult<(String, u64)> {
+        Ok((Self::file_digest(disk)?, std::fs::metadata(disk)?.len()))
+    }
+
+    /// Freeze a live overlay into the layer `digest` (its content digest)
+    /// and replace it with an empty overlay on top.
+    fn freeze(&self, disk: &Path, digest: &str) -> BoxliteResult<()> {
+        let layer = self.layer_path(digest);
+
+        if layer.exists() {
+            // Identical content already stored - keep the existing layer
+            return self.rebase(disk, &layer);
+        }
+
+        std::fs::create_dir_all(&self.layers_dir)?;
+        std::fs::rename(disk, &layer).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "failed to move {} to {}: {}",
+                disk.display(),
+                layer.display(),
+                e
+            ))
+        })?;
+
+        if let Err(e) = self.rebase(disk, &layer) {
+            // Put the original overlay back so the box stays usable
+            let _ = std::fs::rename(&layer, disk);
+            return Err(e);
+        }
+
+        let mut perms = std::fs::metadata(&layer)?.permissions();
+        perms.set_readonly(true);
+        std::fs::set_permissions(&layer, perms)?;
+        Ok(())
+    }
+
+    /// Atomically replace `disk` with an empty overlay backed by `layer`.
+    fn rebase(&self, disk: &Path, layer: &Path) -> BoxliteResult<()> {
+        if !layer.exists() {
+            return Err(BoxliteError::Storage(format!(
+                "snapshot layer not found at {}",
+                layer.display()
+            )));
+        }
+
+        let virtual_size = Qcow2Helper::qcow2_virtual_size(layer)?;
+        Self::replace_overlay(disk, layer, BackingFormat::Qcow2, virtual_size)
+    }
+
+    /// Replace the overlay `disk` with an empty one on the raw base image
+    /// under its chain of snapshot layers.
+    fn reset_to_base(&self, disk: &Path) -> BoxliteResult<()> {
+        let mut current = disk.to_path_buf();
+        while let Some(backing) = Qcow2Helper::backing_file(&current)? {
+            if Self::layer_digest(&backing).is_none() {
+                let size = std::fs::metadata(&backing)?.len();
+                return Self::replace_overlay(disk, &backing, BackingFormat::Raw, size);
+            }
+            current = backing;
+        }
+        Err(BoxliteError::Storage(format!(
+            "cannot reset {}: no base image under it",
+            disk.display()
+        )))
+    }
+
+    /// Atomically replace `disk` with an empty overlay on `backing`.
+    fn replace_overlay(
+        disk: &Path,
+        backing: &Path,
+        format: BackingFormat,
+        virtual_size: u64,
+    ) -> BoxliteResult<()> {
+        let tmp = disk.with_extension("qcow2.tmp");
+        let _ = std::fs::remove_file(&tmp);
+
+        let child =
+            Qcow2Helper::new().create_cow_child_disk(backing, format, &tmp, virtual_size)?;
+        let tmp = child.leak();
+
+        std::fs::rename(&tmp, disk).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            BoxliteError::Storage(format!(
+                "failed to replace {} with new overlay: {}",
+                disk.display(),
+                e
+            ))
+        })
+    }
+
+    fn layer_path(&self, digest: &str) -> PathBuf {
+        self.layers_dir
+            .join(format!("{}.qcow2", digest.replace(':', "-")))
+    }
+
+    /// Parse `sha256-<hex>.qcow2` back into `sha256:<hex>`.
+    fn layer_digest(path: &Path) -> Option<String> {
+        let stem = path.file_name()?.to_str()?.strip_suffix(".qcow2")?;
+        let hex = stem.strip_prefix("sha256-")?;
+        Some(format!("sha256:{}", hex))
+    }
+
+    fn file_digest(path: &Path) -> BoxliteResult<String> {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(format!("sha256:{}", hex::encode(hasher.finalize())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_layer_digest_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(&dir.path().join("test.db")).unwrap();
+        let manager = SnapshotManager::new(db, PathBuf::from("/tmp/layers"));
+        let digest = "sha256:abcdef";
+        let path = manager.layer_path(digest);
+        assert_eq!(path, PathBuf::from("/tmp/layers/sha256-abcdef.qcow2"));
+        assert_eq!(
+            SnapshotManager::layer_digest(&path).as_deref(),
+            Some(digest)
+        );
+    }
+
+    #[test]
+    fn test_layer_digest_ignores_other_files() {
+        assert!(SnapshotManager::layer_digest(Path::new("/tmp/layers/disk.qcow2")).is_none());
+        assert!(SnapshotManager::layer_digest(Path::new("/tmp/layers/sha256-ab.raw")).is_none());
+    }
+}
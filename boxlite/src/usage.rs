@@ -0,0 +1,173 @@
+//! Per-execution resource accounting.
+//!
+//! When an execution finishes, what it consumed (wall and CPU time, peak
+//! memory, block I/O) is recorded as an [`ExecUsage`] in the runtime
+//! database, tagged with the box's name and labels, so multi-tenant setups
+//! can bill or enforce limits per team after the boxes are gone. Usage is
+//! measured in the guest with `wait4`, so it covers the command and every
+//! descendant it reaped. Replays from the execution cache are not
+//! recorded; they consume nothing.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::litebox::ExecResult;
+use crate::litebox::config::BoxConfig;
+use crate::runtime::types::BoxID;
+
+/// Resources one execution consumed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecUsage {
+    /// Monotonic sequence number.
+    #[serde(skip)]
+    pub seq: i64,
+    pub box_id: BoxID,
+    /// Box name when the execution ran.
+    pub box_name: Option<String>,
+    /// Box labels when the execution ran.
+    pub labels: HashMap<String, String>,
+    pub execution_id: String,
+    /// Program and arguments, space-separated.
+    pub command: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub exit_code: i32,
+    /// "exited", "signaled", "oom_killed", "timed_out" or "unknown".
+    pub exit_kind: String,
+    pub wall_time: Duration,
+    pub user_time: Duration,
+    pub system_time: Duration,
+    pub peak_memory_bytes: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+impl ExecUsage {
+    pub(crate) fn new(
+        config: &BoxConfig,
+        execution_id: String,
+        command: String,
+        started_at: DateTime<Utc>,
+        result: &ExecResult,
+    ) -> Self {
+        Self {
+            seq: 0,
+            box_id: config.id.clone(),
+            box_name: config.name.clone(),
+            labels: config.options.labels.clone(),
+            execution_id,
+            command,
+            started_at,
+            finished_at: Utc::now(),
+            exit_code: result.exit_code,
+            exit_kind: result.exit_kind.as_str().to_string(),
+            wall_time: result.duration,
+            user_time: result.user_time,
+            system_time: result.system_time,
+            peak_memory_bytes: result.peak_memory_bytes,
+            read_bytes: result.read_bytes,
+            write_bytes: result.write_bytes,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "seq,box_id,box_name,execution_id,command,started_at,finished_at,\
+                          exit_code,exit_kind,wall_time_ms,user_time_ms,system_time_ms,\
+                          peak_memory_bytes,read_bytes,write_bytes";
+
+/// Write `records` as CSV with a header row, for spreadsheets and billing
+/// pipelines. Times are RFC 3339; durations are in milliseconds.
+pub fn write_usage_csv<W: Write>(records: &[ExecUsage], mut writer: W) -> std::io::Result<()> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for r in records {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            r.seq,
+            r.box_id,
+            csv_field(r.box_name.as_deref().unwrap_or("")),
+            csv_field(&r.execution_id),
+            csv_field(&r.command),
+            r.started_at.to_rfc3339(),
+            r.finished_at.to_rfc3339(),
+            r.exit_code,
+            r.exit_kind,
+            r.wall_time.as_millis(),
+            r.user_time.as_millis(),
+            r.system_time.as_millis(),
+            r.peak_memory_bytes,
+            r.read_bytes,
+            r.write_bytes,
+        )?;
+    }
+    Ok(())
+}
+
+/// Quote a field if it holds a separator, quote or line break.
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Database, UsageStore};
+
+    fn record(execution_id: &str, command: &str) -> ExecUsage {
+        ExecUsage {
+            seq: 0,
+            box_id: BoxID::new(),
+            box_name: Some("ci".to_string()),
+            labels: HashMap::from([("team".to_string(), "ml".to_string())]),
+            execution_id: execution_id.to_string(),
+            command: command.to_string(),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            exit_code: 0,
+            exit_kind: "exited".to_string(),
+            wall_time: Duration::from_millis(1500),
+            user_time: Duration::from_millis(900),
+            system_time: Duration::from_millis(100),
+            peak_memory_bytes: 64 << 20,
+            read_bytes: 4096,
+            write_bytes: 512,
+        }
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = UsageStore::new(Database::open(&dir.path().join("boxlite.db")).unwrap());
+        let first = store.append(&record("exec-1", "make")).unwrap();
+        store.append(&record("exec-2", "make test")).unwrap();
+
+        let records = store.list(None).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seq, first);
+        assert_eq!(records[1].command, "make test");
+        assert_eq!(records[1].labels["team"], "ml");
+
+        let later = Utc::now() + chrono::Duration::hours(1);
+        assert!(store.list(Some(later)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let mut out = Vec::new();
+        write_usage_csv(&[record("exec-1", "sh -c \"a, b\"")], &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let row = lines.next().unwrap();
+        assert!(row.contains(",\"sh -c \"\"a, b\"\"\","));
+        assert!(row.ends_with(",exited,1500,900,100,67108864,4096,512"));
+    }
+}
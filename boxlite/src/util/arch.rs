@@ -0,0 +1,162 @@
+//! CPU architecture of the host and of the binaries it boots.
+//!
+//! Boxes run natively, so the guest agent, the guest kernel and the image
+//! must all match the host. libkrun builds the machine for each
+//! architecture itself (GIC and its console devices on aarch64); what the
+//! runtime has to get right is picking matching images and refusing a
+//! guest binary built for the other architecture, which would otherwise
+//! fail inside the VM with no output.
+//!
+//! riscv64 is experimental and needs the `riscv64` feature: it builds, but
+//! libkrun's riscv64 machine is young and boxes on it are largely untested.
+
+use std::io::Read;
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Architectures libkrun supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Arch {
+    X86_64,
+    Aarch64,
+    Riscv64,
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) const HOST: Arch = Arch::X86_64;
+#[cfg(target_arch = "aarch64")]
+pub(crate) const HOST: Arch = Arch::Aarch64;
+#[cfg(all(target_arch = "riscv64", feature = "riscv64"))]
+pub(crate) const HOST: Arch = Arch::Riscv64;
+#[cfg(all(target_arch = "riscv64", not(feature = "riscv64")))]
+compile_error!("riscv64 hosts are experimental; enable the `riscv64` feature");
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+)))]
+compile_error!("boxlite supports x86_64 and aarch64 hosts (riscv64 experimentally) only");
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+
+impl Arch {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+            Arch::Riscv64 => "riscv64",
+        }
+    }
+
+    /// Rust target triple guest binaries are built for.
+    pub(crate) fn guest_target(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64-unknown-linux-musl",
+            Arch::Aarch64 => "aarch64-unknown-linux-musl",
+            Arch::Riscv64 => "riscv64gc-unknown-linux-musl",
+        }
+    }
+
+    /// Architecture name in OCI image platforms.
+    pub(crate) fn oci_arch(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "amd64",
+            Arch::Aarch64 => "arm64",
+            Arch::Riscv64 => "riscv64",
+        }
+    }
+
+    /// Platform variant images use for this architecture, if any.
+    pub(crate) fn oci_variant(self) -> Option<&'static str> {
+        match self {
+            Arch::X86_64 | Arch::Riscv64 => None,
+            Arch::Aarch64 => Some("v8"),
+        }
+    }
+
+    fn from_elf_machine(machine: u16) -> Option<Self> {
+        match machine {
+            EM_X86_64 => Some(Arch::X86_64),
+            EM_AARCH64 => Some(Arch::Aarch64),
+            EM_RISCV => Some(Arch::Riscv64),
+            _ => None,
+        }
+    }
+}
+
+/// Check that the binary at `path` is a Linux executable for the host.
+pub(crate) fn check_guest_binary(path: &Path) -> BoxliteResult<()> {
+    let mut header = [0u8; 20];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to read guest binary {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+    // Every supported architecture is little-endian (EI_DATA = 1)
+    if &header[..4] != ELF_MAGIC || header[5] != 1 {
+        return Err(BoxliteError::Storage(format!(
+            "Guest binary {} is not a little-endian ELF executable",
+            path.display()
+        )));
+    }
+    let machine = u16::from_le_bytes([header[18], header[19]]);
+    match Arch::from_elf_machine(machine) {
+        Some(arch) if arch == HOST => Ok(()),
+        found => Err(BoxliteError::Unsupported(format!(
+            "Guest binary {} is built for {}, but this host is {}; \
+             rebuild it for {}",
+            path.display(),
+            found.map_or_else(|| format!("ELF machine {}", machine), |a| a.name().into()),
+            HOST.name(),
+            HOST.guest_target()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elf(machine: u16) -> Vec<u8> {
+        let mut header = vec![0u8; 64];
+        header[..4].copy_from_slice(ELF_MAGIC);
+        header[4] = 2; // 64-bit
+        header[5] = 1; // little-endian
+        header[18..20].copy_from_slice(&machine.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn test_check_guest_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("boxlite-guest");
+        let other = match HOST {
+            Arch::X86_64 => EM_AARCH64,
+            Arch::Aarch64 | Arch::Riscv64 => EM_X86_64,
+        };
+
+        let host_machine = match HOST {
+            Arch::X86_64 => EM_X86_64,
+            Arch::Aarch64 => EM_AARCH64,
+            Arch::Riscv64 => EM_RISCV,
+        };
+        std::fs::write(&path, elf(host_machine)).unwrap();
+        assert!(check_guest_binary(&path).is_ok());
+
+        std::fs::write(&path, elf(other)).unwrap();
+        let err = check_guest_binary(&path).unwrap_err().to_string();
+        assert!(err.contains("rebuild it for"), "{}", err);
+
+        std::fs::write(&path, b"#!/bin/sh\nexit 0\n").unwrap();
+        assert!(check_guest_binary(&path).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! `.env` file parsing.
+//!
+//! Supports the common subset of the format: `KEY=VALUE` lines, an optional
+//! `export ` prefix, `#` comments, and single- or double-quoted values.
+//! Double-quoted values understand `\n`, `\t`, `\"` and `\\` escapes.
+//! Variable interpolation (`${OTHER}`) is not performed.
+
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Read and parse the `.env` file at `path`.
+pub fn load(path: &Path) -> BoxliteResult<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        BoxliteError::InvalidArgument(format!("Failed to read env file {}: {}", path.display(), e))
+    })?;
+    parse(&contents).map_err(|(line, reason)| {
+        BoxliteError::InvalidArgument(format!("{}:{}: {}", path.display(), line, reason))
+    })
+}
+
+/// Parse `.env` contents into ordered key/value pairs.
+///
+/// Errors carry the 1-based line number.
+pub fn parse(contents: &str) -> Result<Vec<(String, String)>, (usize, String)> {
+    let mut vars = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| (index + 1, "expected KEY=VALUE".to_string()))?;
+
+        let key = key.trim();
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err((index + 1, format!("invalid variable name '{}'", key)));
+        }
+
+        let value = parse_value(value.trim()).map_err(|reason| (index + 1, reason))?;
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+fn parse_value(raw: &str) -> Result<String, String> {
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("unterminated single quote")?;
+        return Ok(rest[..end].to_string());
+    }
+
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Ok(value),
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated double quote".to_string());
+    }
+
+    // Unquoted: an inline comment starts at " #"
+    let value = raw.split(" #").next().unwrap_or_default();
+    Ok(value.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let vars = parse(
+            "# comment\n\
+             \n\
+             PLAIN=value # trailing\n\
+             export EXPORTED=1\n\
+             SINGLE='a # $b'\n\
+             DOUBLE=\"line1\\nline2\"\n\
+             EMPTY=\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vars,
+            vec![
+                ("PLAIN".to_string(), "value".to_string()),
+                ("EXPORTED".to_string(), "1".to_string()),
+                ("SINGLE".to_string(), "a # $b".to_string()),
+                ("DOUBLE".to_string(), "line1\nline2".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        assert_eq!(parse("OK=1\nnot a var\n").unwrap_err().0, 2);
+        assert_eq!(parse("1BAD=x").unwrap_err().0, 1);
+        assert_eq!(parse("Q=\"open").unwrap_err().0, 1);
+    }
+}
@@ -1,4 +1,8 @@
+pub(crate) mod arch;
+pub mod dotenv;
 pub mod process;
+pub(crate) mod stream_buffer;
+pub mod usage;
 
 use std::path::PathBuf;
 use std::process::Command;
@@ -11,6 +15,7 @@ use tracing_subscriber::{EnvFilter, fmt};
 
 // Re-export process utilities
 pub use process::{is_process_alive, is_same_process, kill_process};
+pub use usage::allocated_size;
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 unsafe extern "C" {
@@ -186,6 +191,7 @@ pub fn inject_guest_binary(rootfs_path: &std::path::Path) -> BoxliteResult<()> {
     let dest_dir = rootfs_path.join("boxlite/bin");
     let dest_path = dest_dir.join("boxlite-guest");
     let guest_bin = find_binary("boxlite-guest")?;
+    arch::check_guest_binary(&guest_bin)?;
 
     // Check if binary needs update
     if dest_path.exists() {
@@ -0,0 +1,204 @@
+//! Bounded buffers between a stream producer and a slow consumer.
+//!
+//! A [`channel`] holds at most `capacity` items. When it is full the
+//! producer either waits for the consumer ([`OverflowPolicy::Block`]),
+//! which carries the backpressure back to the source, or evicts the oldest
+//! item ([`OverflowPolicy::DropOldest`]), counting it in a shared counter
+//! so the loss shows up in metrics.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::task::AtomicWaker;
+use tokio::sync::Notify;
+
+use crate::runtime::options::{OverflowPolicy, StreamBufferOptions};
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Wakes the receiver when an item arrives or the sender goes away
+    item_ready: AtomicWaker,
+    /// Wakes a blocked sender when space frees up or the receiver goes away
+    space_ready: Notify,
+    options: StreamBufferOptions,
+    dropped: Arc<AtomicU64>,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+/// Create a buffer; items evicted under `DropOldest` are added to `dropped`.
+pub(crate) fn channel<T>(
+    options: StreamBufferOptions,
+    dropped: Arc<AtomicU64>,
+) -> (BufferSender<T>, BufferReceiver<T>) {
+    let options = StreamBufferOptions {
+        capacity: options.capacity.max(1),
+        ..options
+    };
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            items: VecDeque::new(),
+            sender_closed: false,
+            receiver_closed: false,
+        }),
+        item_ready: AtomicWaker::new(),
+        space_ready: Notify::new(),
+        options,
+        dropped,
+    });
+    (
+        BufferSender {
+            shared: Arc::clone(&shared),
+        },
+        BufferReceiver { shared },
+    )
+}
+
+/// Producing half of a [`channel`].
+pub(crate) struct BufferSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> BufferSender<T> {
+    /// Queue an item, applying the overflow policy when full.
+    ///
+    /// Fails, handing the item back, once the receiver is gone.
+    pub(crate) async fn send(&self, item: T) -> Result<(), T> {
+        loop {
+            // Register before checking so a release in between is not missed
+            let space = self.shared.space_ready.notified();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if state.receiver_closed {
+                    return Err(item);
+                }
+                if state.items.len() >= self.shared.options.capacity {
+                    match self.shared.options.overflow {
+                        OverflowPolicy::Block => {}
+                        OverflowPolicy::DropOldest => {
+                            state.items.pop_front();
+                            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                if state.items.len() < self.shared.options.capacity {
+                    state.items.push_back(item);
+                    drop(state);
+                    self.shared.item_ready.wake();
+                    return Ok(());
+                }
+            }
+            space.await;
+        }
+    }
+}
+
+impl<T> Drop for BufferSender<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().sender_closed = true;
+        self.shared.item_ready.wake();
+    }
+}
+
+/// Consuming half of a [`channel`].
+pub(crate) struct BufferReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> BufferReceiver<T> {
+    /// Next item, or `None` once the sender is gone and the buffer drained.
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.shared.item_ready.register(cx.waker());
+        let mut state = self.shared.state.lock().unwrap();
+        match state.items.pop_front() {
+            Some(item) => {
+                drop(state);
+                self.shared.space_ready.notify_one();
+                Poll::Ready(Some(item))
+            }
+            None if state.sender_closed => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+
+    pub(crate) async fn recv(&mut self) -> Option<T> {
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+}
+
+impl<T> Drop for BufferReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receiver_closed = true;
+        state.items.clear();
+        drop(state);
+        self.shared.space_ready.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn options(capacity: usize, overflow: OverflowPolicy) -> StreamBufferOptions {
+        StreamBufferOptions { capacity, overflow }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_latest() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (tx, mut rx) = channel(options(2, OverflowPolicy::DropOldest), dropped.clone());
+        for i in 0..5 {
+            tx.send(i).await.unwrap();
+        }
+        drop(tx);
+
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, Some(4));
+        assert_eq!(rx.recv().await, None);
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_consumer() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (tx, mut rx) = channel(options(1, OverflowPolicy::Block), dropped.clone());
+        tx.send(1).await.unwrap();
+
+        let producer = tokio::spawn(async move {
+            tx.send(2).await.unwrap();
+            tx.send(3).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!producer.is_finished());
+
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, Some(2));
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, None);
+        producer.await.unwrap();
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_without_receiver() {
+        let (tx, rx) = channel(
+            options(1, OverflowPolicy::Block),
+            Arc::new(AtomicU64::new(0)),
+        );
+        tx.send(1).await.unwrap();
+
+        // A sender blocked on a full buffer is released by the drop
+        let blocked = tokio::spawn(async move { tx.send(2).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(rx);
+        assert_eq!(blocked.await.unwrap(), Err(2));
+    }
+}
@@ -0,0 +1,65 @@
+//! Disk usage accounting.
+
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Bytes allocated on disk for `path` and, for a directory, everything under it.
+///
+/// Counts allocated blocks rather than file lengths, so sparse qcow2 disks
+/// count for what they occupy. An inode is counted once however many times
+/// it shows up, which covers hard links and bind mounts of the same tree.
+/// Symlinks are not followed; unreadable entries are skipped and a missing
+/// path is 0.
+pub fn allocated_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    let mut seen = HashSet::from([(metadata.dev(), metadata.ino())]);
+    // st_blocks is always in 512-byte units
+    let mut total = metadata.blocks() * 512;
+    if !metadata.is_dir() {
+        return total;
+    }
+
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(read_dir) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !seen.insert((metadata.dev(), metadata.ino())) {
+                continue;
+            }
+            total += metadata.blocks() * 512;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocated_size_counts_inodes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/data"), vec![1u8; 64 * 1024]).unwrap();
+        let before = allocated_size(dir.path());
+        assert!(before >= 64 * 1024);
+        assert!(allocated_size(&dir.path().join("sub/data")) >= 64 * 1024);
+        assert_eq!(allocated_size(&dir.path().join("missing")), 0);
+
+        std::fs::hard_link(dir.path().join("sub/data"), dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("sub"), dir.path().join("loop")).unwrap();
+        let after = allocated_size(dir.path());
+        assert!(after - before < 64 * 1024, "{} -> {}", before, after);
+    }
+}
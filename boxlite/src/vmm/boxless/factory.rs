@@ -0,0 +1,26 @@
+//! Boxless engine factory implementation.
+
+use crate::vmm::{
+    VmmConfig, VmmKind, boxless::Boxless, factory::VmmFactory, registry::EngineFactoryRegistration,
+};
+use boxlite_shared::errors::BoxliteResult;
+
+pub struct BoxlessFactory;
+
+impl VmmFactory for BoxlessFactory {
+    type Engine = Boxless;
+
+    fn create(options: VmmConfig) -> BoxliteResult<Self::Engine> {
+        Boxless::new(options)
+    }
+}
+
+// Auto-register this factory with the global registry at compile time
+inventory::submit! {
+    EngineFactoryRegistration {
+        kind: VmmKind::Boxless,
+        factory: |options| {
+            Ok(Box::new(BoxlessFactory::create(options)?))
+        }
+    }
+}
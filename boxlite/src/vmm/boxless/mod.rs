@@ -0,0 +1,112 @@
+//! Boxless engine: the guest agent runs on the host, without a VM.
+//!
+//! For hosts with no usable hypervisor (CI runners, WSL without nested
+//! virtualization). The shim starts the agent in new user, mount, PID, IPC
+//! and UTS namespaces under a seccomp filter. The agent serves on the box's
+//! host-side sockets directly, so the runtime drives it exactly as it drives
+//! an agent in a VM.
+//!
+//! Isolation is much weaker than a VM's. The box shares the host kernel and
+//! network, and sees the host filesystem outside its container. Block
+//! devices cannot be attached, so the container rootfs is a directory
+//! merged on the host and disk-backed volumes are refused.
+
+mod factory;
+#[cfg(target_os = "linux")]
+mod sandbox;
+#[cfg(target_os = "linux")]
+mod seccomp;
+
+use crate::vmm::{InstanceSpec, Vmm, VmmConfig, VmmInstance, engine::VmmInstanceImpl};
+use boxlite_shared::Transport;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::path::Path;
+
+pub use factory::BoxlessFactory;
+
+/// Directories the agent mounts tmpfs over at startup. Host paths the
+/// agent must reach cannot live under them.
+const AGENT_TMPFS: &[&str] = &["/tmp", "/var/tmp", "/run"];
+
+/// Runs the guest agent in a namespace sandbox on the host.
+pub struct Boxless {
+    #[allow(dead_code)]
+    options: VmmConfig,
+}
+
+impl Boxless {
+    /// Create the engine; fails on hosts other than Linux.
+    pub fn new(options: VmmConfig) -> BoxliteResult<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(BoxliteError::Unsupported(
+                "The boxless engine needs Linux namespaces".to_string(),
+            ));
+        }
+        Ok(Self { options })
+    }
+}
+
+impl Vmm for Boxless {
+    fn create(&mut self, config: InstanceSpec) -> BoxliteResult<VmmInstance> {
+        if let Some(device) = config.block_devices.devices().first() {
+            return Err(BoxliteError::Unsupported(format!(
+                "Boxless boxes cannot attach block devices ({} from {})",
+                device.block_id,
+                device.disk_path.display()
+            )));
+        }
+        let sockets = [&config.transport, &config.ready_transport]
+            .into_iter()
+            .chain(&config.bulk_transport);
+        for transport in sockets {
+            if let Transport::Unix { socket_path } = transport {
+                check_reachable(socket_path)?;
+            }
+        }
+        for share in config.fs_shares.shares() {
+            check_reachable(&share.host_path)?;
+            if !share.host_path.exists() {
+                return Err(BoxliteError::Engine(format!(
+                    "Filesystem share directory '{}' not found: {}",
+                    share.tag,
+                    share.host_path.display()
+                )));
+            }
+        }
+        tracing::warn!(
+            "Starting box without a VM: it shares the host kernel, network and filesystem, \
+             isolated only by namespaces and seccomp"
+        );
+        Ok(VmmInstance::new(Box::new(BoxlessInstance { spec: config })))
+    }
+}
+
+fn check_reachable(path: &Path) -> BoxliteResult<()> {
+    match AGENT_TMPFS.iter().find(|dir| path.starts_with(dir)) {
+        Some(dir) => Err(BoxliteError::Unsupported(format!(
+            "Boxless boxes cannot use {}: the agent mounts its own {} (move BOXLITE_HOME)",
+            path.display(),
+            dir
+        ))),
+        None => Ok(()),
+    }
+}
+
+struct BoxlessInstance {
+    #[allow(dead_code)]
+    spec: InstanceSpec,
+}
+
+impl VmmInstanceImpl for BoxlessInstance {
+    #[cfg(target_os = "linux")]
+    fn enter(self: Box<Self>) -> BoxliteResult<()> {
+        sandbox::run(&self.spec)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn enter(self: Box<Self>) -> BoxliteResult<()> {
+        Err(BoxliteError::Unsupported(
+            "The boxless engine needs Linux namespaces".to_string(),
+        ))
+    }
+}
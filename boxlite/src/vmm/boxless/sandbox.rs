@@ -0,0 +1,168 @@
+//! Namespace sandbox the boxless agent runs in.
+//!
+//! The shim is multithreaded, and `unshare(CLONE_NEWUSER)` only works in a
+//! single-threaded process, so all setup runs in the forked child before
+//! exec. The child enters new namespaces, maps the caller's uid to root,
+//! then forks again so the agent becomes PID 1 of the new PID namespace.
+//! The middle process only waits and passes on the agent's exit status.
+
+use super::seccomp;
+use crate::vmm::InstanceSpec;
+use boxlite_shared::constants::{boxless, container};
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, Stdio};
+
+/// Run the agent described by `spec` in the sandbox until it exits.
+pub(super) fn run(spec: &InstanceSpec) -> BoxliteResult<()> {
+    let entrypoint = &spec.guest_entrypoint;
+    let shares: HashMap<&str, &std::path::Path> = spec
+        .fs_shares
+        .shares()
+        .iter()
+        .map(|share| (share.tag.as_str(), share.host_path.as_path()))
+        .collect();
+
+    let mut command = Command::new(&entrypoint.executable);
+    command
+        .args(&entrypoint.args)
+        .env_clear()
+        .envs(entrypoint.env.iter().map(|(k, v)| (k, v)))
+        .env(boxless::SHARES_ENV, serde_json::to_string(&shares)?)
+        .stdin(Stdio::null());
+    if let Some(console) = &spec.console_output {
+        let log = File::create(console)?;
+        command.stdout(log.try_clone()?).stderr(log);
+    }
+
+    // Everything the child needs is prepared here: between fork and exec
+    // only async-signal-safe calls are allowed, so no allocation.
+    // SAFETY: getuid/getgid cannot fail
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let uid_map = format!("0 {uid} 1");
+    let gid_map = format!("0 {gid} 1");
+    let filter = seccomp::filter();
+
+    // SAFETY: the closure only makes raw syscalls on data prepared above
+    unsafe {
+        command.pre_exec(move || enter_sandbox(uid_map.as_bytes(), gid_map.as_bytes(), &filter));
+    }
+
+    tracing::info!(
+        executable = %entrypoint.executable,
+        "Starting boxless agent in namespace sandbox"
+    );
+    let status = command
+        .spawn()
+        .map_err(|e| BoxliteError::Engine(format!("Failed to start boxless agent: {e}")))?
+        .wait()?;
+
+    match (status.code(), status.signal()) {
+        (Some(0), _) => Ok(()),
+        (Some(code), _) => Err(BoxliteError::Engine(format!(
+            "Boxless agent exited with status {code}"
+        ))),
+        (None, Some(signal)) => Err(BoxliteError::Engine(format!(
+            "Boxless agent killed by signal {signal}"
+        ))),
+        (None, None) => Err(BoxliteError::Engine(format!(
+            "Boxless agent ended unexpectedly: {status}"
+        ))),
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Write `data` to a /proc file, as uid_map and friends require: in one
+/// write(2) call.
+fn write_proc(path: &std::ffi::CStr, data: &[u8]) -> io::Result<()> {
+    // SAFETY: plain open/write/close on a NUL-terminated path
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        libc::close(fd);
+        if written != data.len() as isize {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Runs in the forked child, before exec.
+fn enter_sandbox(uid_map: &[u8], gid_map: &[u8], filter: &[libc::sock_filter]) -> io::Result<()> {
+    // SAFETY: raw syscalls between fork and exec, none of which allocate
+    unsafe {
+        check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0))?;
+        check(libc::unshare(
+            libc::CLONE_NEWUSER
+                | libc::CLONE_NEWNS
+                | libc::CLONE_NEWPID
+                | libc::CLONE_NEWIPC
+                | libc::CLONE_NEWUTS,
+        ))?;
+    }
+    write_proc(c"/proc/self/setgroups", b"deny")?;
+    write_proc(c"/proc/self/uid_map", uid_map)?;
+    write_proc(c"/proc/self/gid_map", gid_map)?;
+
+    // SAFETY: as above; the parent branch never returns
+    unsafe {
+        let pid = libc::fork();
+        check(pid)?;
+        if pid > 0 {
+            wait_and_exit(pid);
+        }
+
+        // PID 1 of the new namespace from here on
+        check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0))?;
+        check(libc::mount(
+            c"none".as_ptr(),
+            c"/".as_ptr(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            std::ptr::null(),
+        ))?;
+        check(libc::mount(
+            c"proc".as_ptr(),
+            c"/proc".as_ptr(),
+            c"proc".as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+            std::ptr::null(),
+        ))?;
+        let hostname = container::DEFAULT_HOSTNAME;
+        check(libc::sethostname(hostname.as_ptr().cast(), hostname.len()))?;
+    }
+    seccomp::install(filter)
+}
+
+/// The middle process: wait for the agent and exit with its status.
+///
+/// Closes every inherited descriptor first. One of them is the pipe the
+/// spawner reads exec errors from; holding it open would block `spawn()`
+/// until the box exits.
+unsafe fn wait_and_exit(pid: libc::pid_t) -> ! {
+    // SAFETY: async-signal-safe syscalls only; _exit never returns
+    unsafe {
+        libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0);
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) < 0 {
+            if *libc::__errno_location() != libc::EINTR {
+                libc::_exit(1);
+            }
+        }
+        if libc::WIFEXITED(status) {
+            libc::_exit(libc::WEXITSTATUS(status));
+        }
+        libc::_exit(128 + libc::WTERMSIG(status));
+    }
+}
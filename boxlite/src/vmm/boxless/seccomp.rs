@@ -0,0 +1,146 @@
+//! Seccomp filter for boxless boxes.
+//!
+//! A deny list, not an allow list: the agent and the container runtime it
+//! embeds need most of the syscall surface, so the filter only removes what
+//! would reach past the namespaces into the host kernel — module loading,
+//! kexec, eBPF, perf, the host clock and the kernel keyring. Denied calls
+//! fail with EPERM so software that probes for them degrades gracefully.
+
+use std::io;
+
+// Classic BPF opcodes (linux/filter.h, linux/bpf_common.h)
+const BPF_LD: u16 = 0x00;
+const BPF_W: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_JMP: u16 = 0x05;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGE: u16 = 0x30;
+const BPF_K: u16 = 0x00;
+const BPF_RET: u16 = 0x06;
+
+// Offsets into struct seccomp_data
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// x32 syscalls share the x86_64 audit arch; refuse them all.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Syscalls a boxless box may not make.
+const DENIED: &[libc::c_long] = &[
+    libc::SYS_kexec_load,
+    libc::SYS_kexec_file_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_clock_adjtime,
+    libc::SYS_adjtimex,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_userfaultfd,
+    libc::SYS_quotactl,
+];
+
+/// Port I/O exists only on x86.
+#[cfg(target_arch = "x86_64")]
+const ARCH_DENIED: &[libc::c_long] = &[libc::SYS_iopl, libc::SYS_ioperm];
+#[cfg(not(target_arch = "x86_64"))]
+const ARCH_DENIED: &[libc::c_long] = &[];
+
+fn stmt(code: u16, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Build the filter program.
+pub(super) fn filter() -> Vec<libc::sock_filter> {
+    let mut program = vec![
+        stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARCH),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+        stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR),
+    ];
+
+    #[cfg(target_arch = "x86_64")]
+    program.extend([
+        jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+        stmt(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | libc::EPERM as u32),
+    ]);
+
+    for &nr in DENIED.iter().chain(ARCH_DENIED) {
+        program.extend([
+            jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1),
+            stmt(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | libc::EPERM as u32),
+        ]);
+    }
+
+    program.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    program
+}
+
+/// Install `program` on the calling thread and its future children.
+///
+/// Async-signal-safe, so it can run between fork and exec.
+pub(super) fn install(program: &[libc::sock_filter]) -> io::Result<()> {
+    let prog = libc::sock_fprog {
+        len: program.len() as libc::c_ushort,
+        filter: program.as_ptr() as *mut libc::sock_filter,
+    };
+    // SAFETY: prctl with integer arguments, then a pointer to a filter
+    // program that outlives the call
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &prog as *const libc::sock_fprog,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_shape() {
+        let program = filter();
+        let denied = DENIED.len() + ARCH_DENIED.len();
+        let x32 = if cfg!(target_arch = "x86_64") { 2 } else { 0 };
+        assert_eq!(program.len(), 4 + x32 + denied * 2 + 1);
+        assert_eq!(program[1].k, AUDIT_ARCH);
+        assert_eq!(program.last().unwrap().k, SECCOMP_RET_ALLOW);
+    }
+}
@@ -1,10 +1,10 @@
 //! ShimController and ShimHandler - Universal process management for all Box engines.
 
-use std::{path::PathBuf, process::Child, sync::Mutex, time::Instant};
+use std::{os::unix::io::RawFd, path::PathBuf, process::Child, sync::Mutex, time::Instant};
 
 use crate::{
     BoxID,
-    vmm::{InstanceSpec, VmmKind},
+    vmm::{InstanceSpec, VmmKind, priority::Placement},
 };
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 
@@ -133,6 +133,8 @@ pub struct ShimController {
     binary_path: PathBuf,
     engine_type: VmmKind,
     box_id: BoxID,
+    placement: Placement,
+    inherited_fds: Vec<RawFd>,
 }
 
 impl ShimController {
@@ -159,8 +161,23 @@ impl ShimController {
             binary_path,
             engine_type,
             box_id,
+            placement: Placement::default(),
+            inherited_fds: Vec::new(),
         })
     }
+
+    /// Schedule the subprocess according to `placement`.
+    pub(crate) fn with_placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Have the subprocess inherit `fd`, e.g. to hold a lock for the VM's
+    /// lifetime. `fd` must stay open until [`VmmController::start`] returns.
+    pub(crate) fn with_inherited_fd(mut self, fd: RawFd) -> Self {
+        self.inherited_fds.push(fd);
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -192,11 +209,21 @@ impl VmmController for ShimController {
             guest_entrypoint,
             transport: config.transport.clone(),
             ready_transport: config.ready_transport.clone(),
+            bulk_transport: config.bulk_transport.clone(),
+            ssh_agent_socket: config.ssh_agent_socket.clone(),
+            git_credential_socket: config.git_credential_socket.clone(),
+            x11_socket: config.x11_socket.clone(),
+            lock_socket: config.lock_socket.clone(),
             guest_rootfs: config.guest_rootfs.clone(),
             network_config: config.network_config.clone(), // Pass port mappings to subprocess (shim creates gvproxy)
             network_backend_endpoint: None, // Will be populated by shim (not serialized)
             home_dir: config.home_dir.clone(),
             console_output: config.console_output.clone(),
+            expires_at: config.expires_at,
+            gpu: config.gpu.clone(),
+            nested_virt: config.nested_virt,
+            memory_dedup: config.memory_dedup,
+            devices: config.devices,
         };
 
         // Serialize the config for passing to subprocess
@@ -211,6 +238,11 @@ impl VmmController for ShimController {
             tracing::warn!("Removing stale Unix socket: {}", socket_path.display());
             let _ = std::fs::remove_file(socket_path);
         }
+        if let Some(boxlite_shared::Transport::Unix { socket_path }) = &config.bulk_transport
+            && socket_path.exists()
+        {
+            let _ = std::fs::remove_file(socket_path);
+        }
 
         // Spawn Box subprocess with piped stdio
         tracing::info!(
@@ -223,7 +255,13 @@ impl VmmController for ShimController {
 
         // Measure subprocess spawn time
         let shim_spawn_start = Instant::now();
-        let child = spawn_subprocess(&self.binary_path, self.engine_type, &config_json)?;
+        let child = spawn_subprocess(
+            &self.binary_path,
+            self.engine_type,
+            &config_json,
+            &self.placement,
+            &self.inherited_fds,
+        )?;
         // spawn_duration: time to create Box subprocess
         let shim_spawn_duration = shim_spawn_start.elapsed();
 
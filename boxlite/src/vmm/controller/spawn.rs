@@ -1,12 +1,15 @@
 //! Subprocess spawning for boxlite-shim binary.
 
 use std::{
+    os::unix::io::RawFd,
+    os::unix::process::CommandExt,
     path::PathBuf,
     process::{Child, Command, Stdio},
 };
 
 use crate::util::configure_library_env;
 use crate::vmm::VmmKind;
+use crate::vmm::priority::Placement;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use libkrun_sys::krun_create_ctx;
 
@@ -16,6 +19,8 @@ use libkrun_sys::krun_create_ctx;
 /// * `binary_path` - Path to the boxlite-shim binary
 /// * `engine_type` - Type of VM engine to use
 /// * `config_json` - Serialized BoxConfig
+/// * `placement` - Host scheduling priority and cgroup of the subprocess
+/// * `inherited_fds` - Descriptors the subprocess keeps open until it exits
 ///
 /// # Returns
 /// * `Ok(Child)` - Successfully spawned subprocess with piped stdio
@@ -24,6 +29,8 @@ pub(crate) fn spawn_subprocess(
     binary_path: &PathBuf,
     engine_type: VmmKind,
     config_json: &str,
+    placement: &Placement,
+    inherited_fds: &[RawFd],
 ) -> BoxliteResult<Child> {
     let mut cmd = Command::new(binary_path);
     cmd.arg("--engine")
@@ -47,6 +54,23 @@ pub(crate) fn spawn_subprocess(
     cmd.stdout(Stdio::null());
     cmd.stderr(Stdio::null());
 
+    placement.apply_on_spawn(&mut cmd);
+
+    if !inherited_fds.is_empty() {
+        let fds = inherited_fds.to_vec();
+        // SAFETY: fcntl is async-signal-safe and the list is built before fork.
+        unsafe {
+            cmd.pre_exec(move || {
+                for &fd in &fds {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
     cmd.spawn().map_err(|e| {
         let err_msg = format!(
             "Failed to spawn VM subprocess at {}: {}",
@@ -1,3 +1,33 @@
+/// Size of the DAX window attached to virtiofs shares that enable DAX.
+///
+/// This is guest physical address space, not memory: pages are only backed
+/// when the guest maps file ranges into the window.
+pub const VIRTIOFS_DAX_WINDOW_SIZE: u64 = 1 << 30;
+
+/// `prctl` option to make a process's memory mergeable by KSM (Linux 6.4+).
+///
+/// Not yet exported by the libc crate.
+#[cfg(target_os = "linux")]
+pub const PR_SET_MEMORY_MERGE: libc::c_int = 67;
+
+/// virglrenderer flags for `krun_set_gpu_options`
+pub mod gpu_flags {
+    pub const VIRGLRENDERER_USE_EGL: u32 = 1 << 0;
+    pub const VIRGLRENDERER_THREAD_SYNC: u32 = 1 << 1;
+    pub const VIRGLRENDERER_USE_SURFACELESS: u32 = 1 << 3;
+    pub const VIRGLRENDERER_USE_GLES: u32 = 1 << 4;
+    pub const VIRGLRENDERER_VENUS: u32 = 1 << 6;
+    pub const VIRGLRENDERER_NO_VIRGL: u32 = 1 << 7;
+
+    /// Headless OpenGL rendering through virgl
+    pub const VIRGL: u32 = VIRGLRENDERER_USE_EGL
+        | VIRGLRENDERER_THREAD_SYNC
+        | VIRGLRENDERER_USE_SURFACELESS
+        | VIRGLRENDERER_USE_GLES;
+    /// Vulkan through Venus, without virgl
+    pub const VENUS: u32 = VIRGLRENDERER_VENUS | VIRGLRENDERER_NO_VIRGL;
+}
+
 /// Network feature flags (host-specific)
 pub mod network_features {
     // Virtio-net feature flags for libkrun net
@@ -12,10 +12,11 @@ use crate::vmm::krun::check_status;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use libkrun_sys::{
     krun_add_disk2, krun_add_net_unixgram, krun_add_net_unixstream, krun_add_virtiofs,
-    krun_add_vsock_port2, krun_create_ctx, krun_free_ctx, krun_init_log, krun_set_console_output,
-    krun_set_env, krun_set_exec, krun_set_gpu_options, krun_set_kernel, krun_set_nested_virt,
-    krun_set_port_map, krun_set_rlimits, krun_set_root, krun_set_root_disk_remount,
-    krun_set_vm_config, krun_set_workdir, krun_setgid, krun_setuid, krun_split_irqchip,
+    krun_add_virtiofs2, krun_add_vsock, krun_add_vsock_port2, krun_create_ctx,
+    krun_disable_implicit_console, krun_disable_implicit_vsock, krun_free_ctx, krun_init_log,
+    krun_set_console_output, krun_set_env, krun_set_exec, krun_set_gpu_options, krun_set_kernel,
+    krun_set_nested_virt, krun_set_port_map, krun_set_rlimits, krun_set_root,
+    krun_set_root_disk_remount, krun_set_vm_config, krun_set_workdir, krun_setgid, krun_setuid,
     krun_start_enter,
 };
 
@@ -289,10 +290,13 @@ impl KrunContext {
         })
     }
 
+    /// Split the IRQ chip between KVM and userspace (x86 only; aarch64
+    /// uses the GIC libkrun sets up).
+    #[cfg(target_arch = "x86_64")]
     pub unsafe fn split_irqchip(&self, enable: bool) -> BoxliteResult<()> {
         tracing::trace!("Setting split IRQ chip to: {}", enable);
         check_status("krun_split_irqchip", unsafe {
-            krun_split_irqchip(self.ctx_id, enable)
+            libkrun_sys::krun_split_irqchip(self.ctx_id, enable)
         })
     }
 
@@ -433,6 +437,43 @@ impl KrunContext {
         })
     }
 
+    /// Add a virtiofs share with a DAX window.
+    ///
+    /// The guest can map file contents from the window directly instead of
+    /// copying them into its page cache.
+    ///
+    /// # Arguments
+    /// * `mount_tag` - Tag used by guest to mount this share
+    /// * `host_path` - Path to directory on host to share
+    /// * `shm_size` - Size of the DAX window in bytes
+    pub unsafe fn add_virtiofs_dax(
+        &self,
+        mount_tag: &str,
+        host_path: &str,
+        shm_size: u64,
+    ) -> BoxliteResult<()> {
+        tracing::debug!(
+            host_path,
+            mount_tag,
+            shm_size,
+            "Adding virtiofs mount with DAX"
+        );
+
+        let host_path_c = CString::new(host_path)
+            .map_err(|e| BoxliteError::Engine(format!("invalid host path: {e}")))?;
+        let mount_tag_c = CString::new(mount_tag)
+            .map_err(|e| BoxliteError::Engine(format!("invalid mount tag: {e}")))?;
+
+        check_status("krun_add_virtiofs2", unsafe {
+            krun_add_virtiofs2(
+                self.ctx_id,
+                mount_tag_c.as_ptr(),
+                host_path_c.as_ptr(),
+                shm_size,
+            )
+        })
+    }
+
     /// Configure vsock port with Unix socket bridge.
     ///
     /// # Arguments
@@ -535,6 +576,24 @@ impl KrunContext {
         check_status("krun_setgid", unsafe { krun_setgid(self.ctx_id, gid) })
     }
 
+    /// Build the VM without a virtio-console device.
+    pub unsafe fn disable_implicit_console(&self) -> BoxliteResult<()> {
+        tracing::debug!("Disabling implicit console");
+        check_status("krun_disable_implicit_console", unsafe {
+            krun_disable_implicit_console(self.ctx_id)
+        })
+    }
+
+    /// Replace the default vsock device with one that has no TSI, so the
+    /// guest gets no network through it. Vsock ports keep working.
+    pub unsafe fn set_vsock_without_tsi(&self) -> BoxliteResult<()> {
+        tracing::debug!("Configuring vsock without TSI");
+        check_status("krun_disable_implicit_vsock", unsafe {
+            krun_disable_implicit_vsock(self.ctx_id)
+        })?;
+        check_status("krun_add_vsock", unsafe { krun_add_vsock(self.ctx_id, 0) })
+    }
+
     /// Redirect VM console output to a file.
     ///
     /// This allows capturing kernel and init output for debugging.
@@ -43,6 +43,27 @@ impl VmmInstanceImpl for KrunVmmInstance {
     }
 }
 
+/// Make all anonymous memory of this process, including guest RAM mapped
+/// later, eligible for KSM merging.
+///
+/// Best effort: kernels before 6.4 don't support process-wide merging, and
+/// the box runs fine without it.
+#[cfg(target_os = "linux")]
+fn enable_memory_merge() {
+    use crate::vmm::krun::constants::PR_SET_MEMORY_MERGE;
+
+    // SAFETY: prctl with integer arguments only
+    let ret = unsafe { libc::prctl(PR_SET_MEMORY_MERGE, 1, 0, 0, 0) };
+    if ret == 0 {
+        tracing::info!("Enabled KSM memory merging for guest memory");
+    } else {
+        tracing::warn!(
+            "Failed to enable KSM memory merging: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
 /// Krun handles VM execution using the libkrun hypervisor.
 ///
 /// This engine is responsible for creating Box instances with the provided
@@ -63,6 +84,10 @@ impl Krun {
     /// * `Ok(Krun)` - Successfully created engine
     /// * `Err(...)` - Failed to detect libkrun library
     pub fn new(options: VmmConfig) -> BoxliteResult<Self> {
+        #[cfg(target_arch = "riscv64")]
+        tracing::warn!(
+            "riscv64 support is experimental; libkrun's riscv64 machine is largely untested"
+        );
         Ok(Self { options })
     }
 
@@ -164,6 +189,9 @@ impl Krun {
         // Transform --notify unix://... -> --notify vsock://2696
         Self::transform_arg_unix_to_vsock(&mut guest_args, "notify", network::GUEST_READY_PORT);
 
+        // Transform --bulk unix://... -> --bulk vsock://2697
+        Self::transform_arg_unix_to_vsock(&mut guest_args, "bulk", network::GUEST_BULK_PORT);
+
         guest_args
     }
 
@@ -243,8 +271,31 @@ impl Vmm for Krun {
             // Configure VM like chroot_vm example: 4 CPUs and 4096MB memory
             ctx.set_vm_config(config.cpus.unwrap_or(4), config.memory_mib.unwrap_or(4096))?;
 
+            if let Some(crate::runtime::options::GpuSpec::VirtioGpu { venus }) = &config.gpu {
+                use crate::vmm::krun::constants::gpu_flags::{VENUS, VIRGL};
+                let flags = if *venus { VENUS } else { VIRGL };
+                tracing::info!(venus = *venus, "Configuring virtio-gpu");
+                ctx.set_gpu_options(flags)?;
+            }
+
+            if config.nested_virt {
+                tracing::info!("Enabling nested virtualization");
+                ctx.set_nested_virt(true)?;
+            }
+
+            // Guest memory is mapped by libkrun when the VM starts, so mark
+            // the whole process before that happens.
+            #[cfg(target_os = "linux")]
+            if config.memory_dedup {
+                enable_memory_merge();
+            }
+
             // Configure net from connection info passed by parent process
-            if let Some(connection) = &config.network_backend_endpoint {
+            if !config.devices.network {
+                // Keep vsock for the agent channels but drop TSI networking
+                tracing::debug!("Network device disabled by the device profile");
+                ctx.set_vsock_without_tsi()?;
+            } else if let Some(connection) = &config.network_backend_endpoint {
                 tracing::info!(connection = ?connection, "Configuring network connection");
 
                 match connection {
@@ -338,12 +389,21 @@ impl Vmm for Krun {
                 })?;
 
                 tracing::info!(
-                    "  {} → {} ({})",
+                    "  {} → {} ({}{})",
                     share.tag,
                     share.host_path.display(),
-                    if share.read_only { "ro" } else { "rw" }
+                    if share.read_only { "ro" } else { "rw" },
+                    if share.dax { ", dax" } else { "" }
                 );
-                ctx.add_virtiofs(&share.tag, path_str)?;
+                if share.dax {
+                    ctx.add_virtiofs_dax(
+                        &share.tag,
+                        path_str,
+                        crate::vmm::krun::constants::VIRTIOFS_DAX_WINDOW_SIZE,
+                    )?;
+                } else {
+                    ctx.add_virtiofs(&share.tag, path_str)?;
+                }
             }
 
             // Attach disk images via virtio-blk
@@ -443,8 +503,47 @@ impl Vmm for Krun {
             );
             ctx.add_vsock_port(network::GUEST_READY_PORT, ready_socket_path, false)?;
 
+            // Configure bulk transfer channel (same direction as gRPC)
+            if let Some(boxlite_shared::Transport::Unix { socket_path }) = &config.bulk_transport {
+                let bulk_socket_path = socket_path
+                    .to_str()
+                    .ok_or_else(|| BoxliteError::Engine("invalid bulk socket path".into()))?;
+                tracing::debug!(
+                    socket_path = bulk_socket_path,
+                    guest_port = network::GUEST_BULK_PORT,
+                    "Configuring vsock bridge for bulk transfers"
+                );
+                ctx.add_vsock_port(network::GUEST_BULK_PORT, bulk_socket_path, true)?;
+            }
+
+            // Configure credential, X11 and lock forwarding (listen=false: host services listen)
+            for (socket_path, guest_port) in [
+                (&config.ssh_agent_socket, network::GUEST_SSH_AGENT_PORT),
+                (
+                    &config.git_credential_socket,
+                    network::GUEST_GIT_CREDENTIAL_PORT,
+                ),
+                (&config.x11_socket, network::GUEST_X11_PORT),
+                (&config.lock_socket, network::GUEST_LOCK_PORT),
+            ] {
+                if let Some(socket_path) = socket_path {
+                    let socket_path = socket_path.to_str().ok_or_else(|| {
+                        BoxliteError::Engine("invalid forwarded socket path".into())
+                    })?;
+                    tracing::debug!(
+                        socket_path,
+                        guest_port,
+                        "Configuring vsock bridge for host service"
+                    );
+                    ctx.add_vsock_port(guest_port, socket_path, false)?;
+                }
+            }
+
             // Configure console output redirection if specified
-            if let Some(console_path) = &config.console_output {
+            if !config.devices.console {
+                tracing::debug!("Console device disabled by the device profile");
+                ctx.disable_implicit_console()?;
+            } else if let Some(console_path) = &config.console_output {
                 let console_path_str = console_path.to_str().ok_or_else(|| {
                     BoxliteError::Engine(format!(
                         "Invalid console output path: {}",
@@ -5,11 +5,14 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::str::FromStr;
 
+pub mod boxless;
 pub mod controller;
 pub mod engine;
 pub mod factory;
 pub mod krun;
+pub(crate) mod priority;
 pub mod registry;
+pub mod whpx;
 
 use crate::runtime::guest_rootfs::GuestRootfs;
 pub use engine::{Vmm, VmmConfig, VmmInstance};
@@ -21,6 +24,28 @@ pub use registry::create_engine;
 pub enum VmmKind {
     Libkrun,
     Firecracker,
+    /// No VM: the guest agent runs on the host in namespaces (see
+    /// [`boxless`]). Much weaker isolation than the hypervisor engines.
+    Boxless,
+    /// Windows Hypervisor Platform (see [`whpx`]).
+    Whpx,
+}
+
+impl VmmKind {
+    /// Lowercase name, as accepted by `from_str`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VmmKind::Libkrun => "libkrun",
+            VmmKind::Firecracker => "firecracker",
+            VmmKind::Boxless => "boxless",
+            VmmKind::Whpx => "whpx",
+        }
+    }
+
+    /// Whether boxes of this kind are isolated by a hypervisor.
+    pub fn is_vm(&self) -> bool {
+        !matches!(self, VmmKind::Boxless)
+    }
 }
 
 impl FromStr for VmmKind {
@@ -30,8 +55,10 @@ impl FromStr for VmmKind {
         match s.to_lowercase().as_str() {
             "libkrun" => Ok(VmmKind::Libkrun),
             "firecracker" => Ok(VmmKind::Firecracker),
+            "boxless" => Ok(VmmKind::Boxless),
+            "whpx" => Ok(VmmKind::Whpx),
             _ => Err(BoxliteError::Engine(format!(
-                "Unknown engine type: '{}'. Supported: libkrun, firecracker",
+                "Unknown engine type: '{}'. Supported: libkrun, firecracker, boxless, whpx",
                 s
             ))),
         }
@@ -50,8 +77,17 @@ pub struct FsShare {
     pub host_path: PathBuf,
     /// Whether the share is read-only
     pub read_only: bool,
+    /// Whether the share gets a DAX window (see [`VIRTIOFS_DAX_SUPPORTED`])
+    #[serde(default)]
+    pub dax: bool,
 }
 
+/// Whether virtiofs shares can be given a DAX window on this host.
+///
+/// libkrun maps DAX windows through KVM; other hypervisors fall back to
+/// plain virtiofs.
+pub const VIRTIOFS_DAX_SUPPORTED: bool = cfg!(target_os = "linux");
+
 /// Collection of filesystem shares from host to guest.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FsShares {
@@ -63,11 +99,12 @@ impl FsShares {
         Self { shares: Vec::new() }
     }
 
-    pub fn add(&mut self, tag: impl Into<String>, path: PathBuf, read_only: bool) {
+    pub fn add(&mut self, tag: impl Into<String>, path: PathBuf, read_only: bool, dax: bool) {
         self.shares.push(FsShare {
             tag: tag.into(),
             host_path: path,
             read_only,
+            dax,
         });
     }
 
@@ -151,6 +188,21 @@ pub struct InstanceSpec {
     pub transport: boxlite_shared::Transport,
     /// Host-side transport for ready notification (host listens, guest connects when ready)
     pub ready_transport: boxlite_shared::Transport,
+    /// Host-side transport for bulk file transfers (libkrun listens, host connects)
+    #[serde(default)]
+    pub bulk_transport: Option<boxlite_shared::Transport>,
+    /// Host SSH agent socket the guest connects to (guest connects, agent listens)
+    #[serde(default)]
+    pub ssh_agent_socket: Option<PathBuf>,
+    /// Host git credential socket the guest connects to (guest connects, host listens)
+    #[serde(default)]
+    pub git_credential_socket: Option<PathBuf>,
+    /// Host X server socket the guest connects to
+    #[serde(default)]
+    pub x11_socket: Option<PathBuf>,
+    /// Host lock broker socket the guest connects to (guest connects, host listens)
+    #[serde(default)]
+    pub lock_socket: Option<PathBuf>,
     /// Resolved guest rootfs path and assembly strategy
     pub guest_rootfs: GuestRootfs,
     /// Network configuration (port mappings) passed to shim subprocess.
@@ -165,6 +217,21 @@ pub struct InstanceSpec {
     pub home_dir: PathBuf,
     /// Optional file path to redirect console output (kernel/init messages)
     pub console_output: Option<PathBuf>,
+    /// Box TTL deadline - the shim shuts the guest down and exits (killing the VM) when it passes
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// GPU device to attach
+    #[serde(default)]
+    pub gpu: Option<crate::runtime::options::GpuSpec>,
+    /// Enable nested virtualization on the vCPUs
+    #[serde(default)]
+    pub nested_virt: bool,
+    /// Mark guest memory as mergeable by KSM
+    #[serde(default)]
+    pub memory_dedup: bool,
+    /// Optional virtio devices to build the VM with
+    #[serde(default)]
+    pub devices: crate::runtime::options::DeviceProfile,
 }
 
 /// Entrypoint configuration that the guest should run.
@@ -265,4 +332,15 @@ mod tests {
         assert!(deserialized.read_only);
         assert_eq!(deserialized.format, DiskFormat::Qcow2);
     }
+
+    #[test]
+    fn test_fs_share_dax_defaults_to_off() {
+        let json = r#"{"tag":"shared","host_path":"/tmp/shared","read_only":false}"#;
+        let share: FsShare = serde_json::from_str(json).unwrap();
+        assert!(!share.dax);
+
+        let mut shares = FsShares::new();
+        shares.add("shared", PathBuf::from("/tmp/shared"), false, true);
+        assert!(shares.shares()[0].dax);
+    }
 }
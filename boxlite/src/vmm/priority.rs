@@ -0,0 +1,346 @@
+//! Host scheduling priority of box VMM processes.
+//!
+//! A box's [`BoxPriority`] decides how the host splits CPU time and disk
+//! bandwidth between its VMM process and those of other boxes. The process
+//! always gets the class's nice value and, on Linux, its I/O priority. When
+//! the runtime has a delegated cgroup2 directory, each box also gets a child
+//! cgroup `boxlite-{id}` there carrying the class's `cpu.weight` and
+//! `io.weight`, which is what keeps the split proportional under full load.
+//!
+//! The same cgroup carries the box's [`IoLimits`] as `io.max`, on every
+//! host device holding files the VMM reads and writes for the box.
+//!
+//! Everything here is best effort: a host that refuses a setting logs a
+//! warning and the box runs with whatever was applied.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::runtime::options::{BoxPriority, IoLimits};
+use crate::runtime::types::BoxID;
+
+/// `cpu.max` of a throttled box: 10ms of CPU time every 100ms.
+const THROTTLED_CPU_MAX: &str = "10000 100000";
+
+impl BoxPriority {
+    /// Relative share under cgroup v2 `cpu.weight` and `io.weight`
+    /// (range 1..=10000, default 100).
+    pub(crate) fn weight(self) -> u32 {
+        match self {
+            BoxPriority::Realtime => 1000,
+            BoxPriority::High => 300,
+            BoxPriority::Normal => 100,
+            BoxPriority::Batch => 1,
+        }
+    }
+
+    /// Nice value of the VMM process.
+    pub(crate) fn nice(self) -> i32 {
+        match self {
+            BoxPriority::Realtime => -10,
+            BoxPriority::High => -5,
+            BoxPriority::Normal => 0,
+            BoxPriority::Batch => 10,
+        }
+    }
+
+    /// `ioprio_set` value, or `None` to inherit the runtime's.
+    #[cfg(target_os = "linux")]
+    fn ioprio(self) -> Option<libc::c_int> {
+        const CLASS_SHIFT: libc::c_int = 13;
+        const CLASS_BE: libc::c_int = 2;
+        const CLASS_IDLE: libc::c_int = 3;
+        match self {
+            // The real-time class needs CAP_SYS_ADMIN; best-effort level 0
+            // is the highest an unprivileged runtime can ask for
+            BoxPriority::Realtime => Some(CLASS_BE << CLASS_SHIFT),
+            BoxPriority::High => Some((CLASS_BE << CLASS_SHIFT) | 2),
+            BoxPriority::Normal => None,
+            BoxPriority::Batch => Some(CLASS_IDLE << CLASS_SHIFT),
+        }
+    }
+}
+
+/// Where and how a box's VMM process is scheduled, prepared before spawn.
+#[derive(Default)]
+pub(crate) struct Placement {
+    priority: BoxPriority,
+    /// `cgroup.procs` of the box's cgroup, written by the child itself so
+    /// the VMM never runs outside it.
+    #[cfg(target_os = "linux")]
+    procs: Option<std::fs::File>,
+}
+
+impl Placement {
+    /// Prepare placement for `box_id`, creating its cgroup under
+    /// `cgroup_parent` if one is given. `io_limits` apply to the devices
+    /// holding `io_paths`.
+    pub(crate) fn prepare(
+        box_id: &BoxID,
+        priority: BoxPriority,
+        io_limits: &IoLimits,
+        io_paths: &[PathBuf],
+        cgroup_parent: Option<&Path>,
+    ) -> Self {
+        if !io_limits.is_empty() && cgroup_parent.is_none() {
+            tracing::warn!(box_id = %box_id, "io_limits need cgroup_parent, not enforced");
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let io_max = io_max_lines(io_limits, &block_devices(io_paths));
+            let procs = cgroup_parent.and_then(|parent| {
+                create_cgroup(parent, box_id, priority, &io_max)
+                    .inspect_err(|e| {
+                        tracing::warn!(
+                            box_id = %box_id,
+                            cgroup_parent = %parent.display(),
+                            error = %e,
+                            "Failed to set up box cgroup, using nice values only"
+                        )
+                    })
+                    .ok()
+            });
+            Self { priority, procs }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = io_paths;
+            if cgroup_parent.is_some() {
+                tracing::warn!(box_id = %box_id, "cgroup_parent is only supported on Linux");
+            }
+            Self { priority }
+        }
+    }
+
+    /// Have `cmd`'s child apply this placement to itself before exec.
+    pub(crate) fn apply_on_spawn(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let nice = self.priority.nice();
+        #[cfg(target_os = "linux")]
+        let ioprio = self.priority.ioprio();
+        #[cfg(target_os = "linux")]
+        let procs = self.procs.as_ref().map(std::os::fd::AsRawFd::as_raw_fd);
+
+        // SAFETY: the hook only makes async-signal-safe calls (setpriority,
+        // ioprio_set, write) on values prepared before fork. Failures are
+        // ignored so a refused setting never stops the box from starting.
+        unsafe {
+            cmd.pre_exec(move || {
+                if nice != 0 {
+                    libc::setpriority(libc::PRIO_PROCESS, 0, nice);
+                }
+                #[cfg(target_os = "linux")]
+                {
+                    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+                    if let Some(ioprio) = ioprio {
+                        libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio);
+                    }
+                    if let Some(fd) = procs {
+                        libc::write(fd, b"0".as_ptr().cast(), 1);
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Remove the cgroup of `box_id` once its VMM process is gone.
+pub(crate) fn release(cgroup_parent: Option<&Path>, box_id: &BoxID) {
+    let Some(parent) = cgroup_parent else {
+        return;
+    };
+    let dir = cgroup_dir(parent, box_id);
+    if let Err(e) = std::fs::remove_dir(&dir)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!(box_id = %box_id, cgroup = %dir.display(), error = %e, "Failed to remove box cgroup");
+    }
+}
+
+/// Cap the VMM of `box_id` at a tenth of a CPU, or lift the cap.
+pub(crate) fn throttle(
+    cgroup_parent: &Path,
+    box_id: &BoxID,
+    throttled: bool,
+) -> std::io::Result<()> {
+    let cpu_max = if throttled { THROTTLED_CPU_MAX } else { "max" };
+    std::fs::write(cgroup_dir(cgroup_parent, box_id).join("cpu.max"), cpu_max)
+}
+
+fn cgroup_dir(parent: &Path, box_id: &BoxID) -> PathBuf {
+    parent.join(format!("boxlite-{}", box_id))
+}
+
+/// Whole-disk block devices (major, minor) holding `paths`, each once.
+///
+/// `io.max` only takes whole disks, so a partition is replaced by its
+/// disk. Paths on filesystems without a block device (tmpfs, FUSE, btrfs
+/// subvolumes) are skipped.
+#[cfg(target_os = "linux")]
+fn block_devices(paths: &[PathBuf]) -> Vec<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut devices = Vec::new();
+    for path in paths {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            continue;
+        };
+        let (major, minor) = (libc::major(metadata.dev()), libc::minor(metadata.dev()));
+        if major == 0 {
+            tracing::debug!(path = %path.display(), "Not on a block device, io_limits do not apply");
+            continue;
+        }
+        let device = whole_disk(major, minor).unwrap_or((major, minor));
+        if !devices.contains(&device) {
+            devices.push(device);
+        }
+    }
+    devices
+}
+
+/// The disk a partition is on; `None` for a whole disk.
+#[cfg(target_os = "linux")]
+fn whole_disk(major: u32, minor: u32) -> Option<(u32, u32)> {
+    let sys = std::fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+    if !sys.join("partition").exists() {
+        return None;
+    }
+    let dev = std::fs::read_to_string(sys.parent()?.join("dev")).ok()?;
+    let (major, minor) = dev.trim().split_once(':')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// One `io.max` line per device; none without caps.
+#[cfg(target_os = "linux")]
+fn io_max_lines(limits: &IoLimits, devices: &[(u32, u32)]) -> Vec<String> {
+    if limits.is_empty() {
+        return Vec::new();
+    }
+    let caps: Vec<String> = limits
+        .entries()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    devices
+        .iter()
+        .map(|(major, minor)| format!("{}:{} {}", major, minor, caps.join(" ")))
+        .collect()
+}
+
+/// Create the cgroup of `box_id` with `priority`'s weights and the
+/// `io_max` lines, and open its `cgroup.procs` for writing.
+#[cfg(target_os = "linux")]
+fn create_cgroup(
+    parent: &Path,
+    box_id: &BoxID,
+    priority: BoxPriority,
+    io_max: &[String],
+) -> std::io::Result<std::fs::File> {
+    let available = std::fs::read_to_string(parent.join("cgroup.controllers"))?;
+    let enabled: Vec<&str> = ["cpu", "io"]
+        .into_iter()
+        .filter(|controller| available.split_whitespace().any(|c| c == *controller))
+        .collect();
+    if enabled.is_empty() {
+        return Err(std::io::Error::other(
+            "neither the cpu nor the io controller is delegated",
+        ));
+    }
+    let control: Vec<String> = enabled.iter().map(|c| format!("+{}", c)).collect();
+    std::fs::write(parent.join("cgroup.subtree_control"), control.join(" "))?;
+
+    let dir = cgroup_dir(parent, box_id);
+    match std::fs::create_dir(&dir) {
+        Ok(()) => {}
+        // Left behind by a VMM that was killed; reuse it
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+    let weight = priority.weight();
+    if enabled.contains(&"cpu") {
+        std::fs::write(dir.join("cpu.weight"), weight.to_string())?;
+    }
+    if enabled.contains(&"io") {
+        std::fs::write(dir.join("io.weight"), format!("default {}", weight))?;
+        // The kernel takes one device per write
+        for line in io_max {
+            std::fs::write(dir.join("io.max"), line)?;
+        }
+    } else if !io_max.is_empty() {
+        tracing::warn!(box_id = %box_id, "io controller is not delegated, io_limits not enforced");
+    }
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("cgroup.procs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classes_are_ordered() {
+        let classes = [
+            BoxPriority::Batch,
+            BoxPriority::Normal,
+            BoxPriority::High,
+            BoxPriority::Realtime,
+        ];
+        for pair in classes.windows(2) {
+            assert!(pair[0].weight() < pair[1].weight());
+            assert!(pair[0].nice() > pair[1].nice());
+        }
+        assert_eq!(BoxPriority::default().weight(), 100);
+        assert_eq!(BoxPriority::default().nice(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_create_cgroup_sets_weights() {
+        let parent = tempfile::tempdir().unwrap();
+        std::fs::write(
+            parent.path().join("cgroup.controllers"),
+            "cpu memory pids\n",
+        )
+        .unwrap();
+        let box_id = BoxID::new();
+        // The kernel creates a cgroup's interface files; fake the one opened
+        let dir = format!("boxlite-{}", box_id);
+        std::fs::create_dir(parent.path().join(&dir)).unwrap();
+        std::fs::write(parent.path().join(&dir).join("cgroup.procs"), "").unwrap();
+
+        create_cgroup(parent.path(), &box_id, BoxPriority::Batch, &[]).unwrap();
+        let read = |name: &str| std::fs::read_to_string(parent.path().join(name)).unwrap();
+        assert_eq!(read("cgroup.subtree_control"), "+cpu");
+        assert_eq!(read(&format!("{}/cpu.weight", dir)), "1");
+        assert!(!parent.path().join(&dir).join("io.weight").exists());
+
+        create_cgroup(parent.path(), &box_id, BoxPriority::High, &[]).unwrap();
+        assert_eq!(read(&format!("{}/cpu.weight", dir)), "300");
+
+        let bare = tempfile::tempdir().unwrap();
+        std::fs::write(bare.path().join("cgroup.controllers"), "memory\n").unwrap();
+        assert!(create_cgroup(bare.path(), &box_id, BoxPriority::Normal, &[]).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_io_max_lines() {
+        let devices = [(8, 0), (259, 0)];
+        assert!(io_max_lines(&IoLimits::default(), &devices).is_empty());
+
+        let limits = IoLimits {
+            write_bps: Some(50 * 1024 * 1024),
+            read_iops: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(
+            io_max_lines(&limits, &devices),
+            [
+                "8:0 wbps=52428800 riops=2000",
+                "259:0 wbps=52428800 riops=2000"
+            ]
+        );
+    }
+}
@@ -36,11 +36,11 @@ inventory::collect!(EngineFactoryRegistration);
 ///
 /// # Example
 /// ```rust,no_run
-/// use boxlite_runtime::vmm::{self, VmmKind, VmmConfig};
+/// use boxlite::vmm::{self, VmmConfig, VmmKind};
 ///
 /// let options = VmmConfig::default();
 /// let engine = vmm::create_engine(VmmKind::Libkrun, options)?;
-/// # Ok::<(), boxlite_runtime::errors::BoxliteError>(())
+/// # Ok::<(), boxlite_shared::errors::BoxliteError>(())
 /// ```
 pub fn create_engine(kind: VmmKind, options: VmmConfig) -> BoxliteResult<Box<dyn Vmm>> {
     // Iterate over all registered factories
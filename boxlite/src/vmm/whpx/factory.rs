@@ -0,0 +1,26 @@
+//! WHPX engine factory implementation.
+
+use crate::vmm::{
+    VmmConfig, VmmKind, factory::VmmFactory, registry::EngineFactoryRegistration, whpx::Whpx,
+};
+use boxlite_shared::errors::BoxliteResult;
+
+pub struct WhpxFactory;
+
+impl VmmFactory for WhpxFactory {
+    type Engine = Whpx;
+
+    fn create(options: VmmConfig) -> BoxliteResult<Self::Engine> {
+        Whpx::new(options)
+    }
+}
+
+// Auto-register this factory with the global registry at compile time
+inventory::submit! {
+    EngineFactoryRegistration {
+        kind: VmmKind::Whpx,
+        factory: |options| {
+            Ok(Box::new(WhpxFactory::create(options)?))
+        }
+    }
+}
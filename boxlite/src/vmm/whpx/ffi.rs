@@ -0,0 +1,32 @@
+//! Bindings to the few WinHvPlatform.dll calls the backend makes.
+
+use std::ffi::c_void;
+
+/// `WHvCapabilityCodeHypervisorPresent`
+const CAPABILITY_HYPERVISOR_PRESENT: u32 = 0x0000_0000;
+
+#[link(name = "WinHvPlatform")]
+unsafe extern "system" {
+    fn WHvGetCapability(
+        capability_code: u32,
+        capability_buffer: *mut c_void,
+        capability_buffer_size: u32,
+        written_size: *mut u32,
+    ) -> i32;
+}
+
+/// Whether the Windows Hypervisor Platform is enabled and usable.
+pub(super) fn hypervisor_present() -> bool {
+    let mut present: i32 = 0;
+    let mut written: u32 = 0;
+    // SAFETY: the buffer is a BOOL-sized local, and its size is passed
+    let hr = unsafe {
+        WHvGetCapability(
+            CAPABILITY_HYPERVISOR_PRESENT,
+            (&mut present as *mut i32).cast(),
+            std::mem::size_of::<i32>() as u32,
+            &mut written,
+        )
+    };
+    hr >= 0 && present != 0
+}
@@ -0,0 +1,94 @@
+//! WHPX engine: Linux boxes on Windows hosts.
+//!
+//! Uses the Windows Hypervisor Platform API (WinHvPlatform.dll), which is
+//! available once the "Windows Hypervisor Platform" optional feature is
+//! enabled. The backend currently probes the hypervisor and validates the
+//! box's exports through the path and permission shims in [`paths`].
+//!
+//! Booting a guest needs the virtio devices libkrun provides on the other
+//! hosts (virtio-fs, virtio-blk, vsock, console), which this backend does
+//! not emulate yet. Until it does, `enter` fails with `Unsupported`, and the
+//! runtime never selects it by default: only an explicit `whpx` engine
+//! reaches it. Windows hosts are unsupported (see the FAQ).
+
+mod factory;
+#[cfg(windows)]
+mod ffi;
+pub(crate) mod paths;
+
+use crate::vmm::{InstanceSpec, Vmm, VmmConfig, VmmInstance, engine::VmmInstanceImpl};
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+pub use factory::WhpxFactory;
+
+/// Whether the Windows Hypervisor Platform is enabled on this host.
+pub fn hypervisor_present() -> bool {
+    #[cfg(windows)]
+    {
+        ffi::hypervisor_present()
+    }
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Runs boxes under the Windows Hypervisor Platform.
+pub struct Whpx;
+
+impl Whpx {
+    /// Create the engine; fails unless WHPX is enabled.
+    pub fn new(_options: VmmConfig) -> BoxliteResult<Self> {
+        if !cfg!(windows) {
+            return Err(BoxliteError::Unsupported(
+                "The WHPX engine needs a Windows host".to_string(),
+            ));
+        }
+        if !hypervisor_present() {
+            return Err(BoxliteError::Engine(
+                "Windows Hypervisor Platform is not available: enable the \
+                 HypervisorPlatform optional feature and reboot"
+                    .to_string(),
+            ));
+        }
+        Ok(Self)
+    }
+}
+
+impl Vmm for Whpx {
+    fn create(&mut self, config: InstanceSpec) -> BoxliteResult<VmmInstance> {
+        // Refuse what the guest could never use before failing to boot it
+        for share in config.fs_shares.shares() {
+            paths::export_root(&share.host_path)?;
+            if !share.host_path.is_dir() {
+                return Err(BoxliteError::Engine(format!(
+                    "Filesystem share directory '{}' not found: {}",
+                    share.tag,
+                    share.host_path.display()
+                )));
+            }
+        }
+        for device in config.block_devices.devices() {
+            if !device.disk_path.exists() {
+                return Err(BoxliteError::Storage(format!(
+                    "Disk image not found: {}",
+                    device.disk_path.display()
+                )));
+            }
+        }
+
+        Ok(VmmInstance::new(Box::new(WhpxInstance)))
+    }
+}
+
+struct WhpxInstance;
+
+impl VmmInstanceImpl for WhpxInstance {
+    fn enter(self: Box<Self>) -> BoxliteResult<()> {
+        tracing::error!("WHPX backend cannot boot guests yet");
+        Err(BoxliteError::Unsupported(
+            "The WHPX engine cannot boot guests yet: its virtio devices are not implemented"
+                .to_string(),
+        ))
+    }
+}
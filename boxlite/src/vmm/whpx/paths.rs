@@ -0,0 +1,161 @@
+//! Host-side shims for exporting Windows directories over virtio-fs.
+//!
+//! The guest expects POSIX semantics; NTFS has drive-letter paths, file
+//! attributes instead of mode bits, and names it refuses to store. These
+//! helpers translate between the two at the export boundary. They work on
+//! the path text rather than `std::path` so they behave the same on every
+//! host, and are tested off Windows.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::path::{Path, PathBuf};
+
+/// `FILE_ATTRIBUTE_READONLY`
+const ATTRIBUTE_READONLY: u32 = 0x1;
+/// `FILE_ATTRIBUTE_DIRECTORY`
+const ATTRIBUTE_DIRECTORY: u32 = 0x10;
+/// `FILE_ATTRIBUTE_REPARSE_POINT` (symlinks and junctions)
+const ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Names Windows reserves in every directory, with or without extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters NTFS does not allow in a file name.
+const RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+/// The root a share is exported from: an absolute drive path such as
+/// `C:\Users\dev\project`, with the drive letter upper-cased.
+///
+/// Verbatim (`\\?\C:\...`) paths are accepted and unwrapped. Network
+/// (`\\server\share`) and device (`\\.\`) paths are refused: their
+/// permissions and locking cannot be mapped onto the guest.
+pub(crate) fn export_root(path: &Path) -> BoxliteResult<PathBuf> {
+    let text = path.to_string_lossy();
+    let unsupported = |reason: &str| {
+        BoxliteError::Unsupported(format!("Cannot share {} with the box: {}", text, reason))
+    };
+
+    let text = text.replace('/', "\\");
+    if text.starts_with("\\\\?\\UNC\\") || text.starts_with("\\\\.\\") {
+        return Err(unsupported("network and device paths cannot be shared"));
+    }
+    let local = text.strip_prefix("\\\\?\\").unwrap_or(&text);
+    if local.starts_with("\\\\") {
+        return Err(unsupported("network and device paths cannot be shared"));
+    }
+
+    let mut chars = local.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(drive), Some(':'), Some('\\')) if drive.is_ascii_alphabetic() => Ok(PathBuf::from(
+            format!("{}{}", drive.to_ascii_uppercase(), &local[1..]),
+        )),
+        _ => Err(unsupported("expected an absolute path with a drive letter")),
+    }
+}
+
+/// POSIX mode bits the guest sees for a file with these attributes.
+///
+/// NTFS ACLs do not map onto owner/group/other, so every exported file is
+/// owned by the box user and only the read-only attribute is reflected.
+#[allow(dead_code)] // Used by the virtio-fs device once WHPX boots guests
+pub(crate) fn posix_mode(attributes: u32) -> u32 {
+    let read_only = attributes & ATTRIBUTE_READONLY != 0;
+    if attributes & ATTRIBUTE_REPARSE_POINT != 0 {
+        S_IFLNK | 0o777
+    } else if attributes & ATTRIBUTE_DIRECTORY != 0 {
+        S_IFDIR | if read_only { 0o555 } else { 0o755 }
+    } else {
+        S_IFREG | if read_only { 0o444 } else { 0o644 }
+    }
+}
+
+/// Check a name the guest creates in an export; NTFS would refuse it or
+/// silently map it onto a device.
+#[allow(dead_code)] // Used by the virtio-fs device once WHPX boots guests
+pub(crate) fn check_guest_name(name: &str) -> BoxliteResult<()> {
+    let invalid =
+        |reason: &str| BoxliteError::InvalidArgument(format!("File name '{}' {}", name, reason));
+
+    if let Some(c) = name
+        .chars()
+        .find(|c| RESERVED_CHARS.contains(c) || c.is_control())
+    {
+        return Err(invalid(&format!("contains {:?}, which Windows forbids", c)));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err(invalid("ends with a dot or space, which Windows strips"));
+    }
+    let stem = name.split('.').next().unwrap_or(name);
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        return Err(invalid("is a reserved device name on Windows"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_root() {
+        assert_eq!(
+            export_root(Path::new(r"c:\Users\dev\project")).unwrap(),
+            PathBuf::from(r"C:\Users\dev\project")
+        );
+        assert_eq!(
+            export_root(Path::new(r"\\?\D:\work")).unwrap(),
+            PathBuf::from(r"D:\work")
+        );
+        assert_eq!(
+            export_root(Path::new("E:/data")).unwrap(),
+            PathBuf::from(r"E:\data")
+        );
+        for path in [
+            r"\\server\share",
+            r"\\?\UNC\server\share",
+            r"\\.\PhysicalDrive0",
+            r"relative\dir",
+            r"C:relative",
+        ] {
+            assert!(export_root(Path::new(path)).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_posix_mode() {
+        assert_eq!(posix_mode(0), S_IFREG | 0o644);
+        assert_eq!(posix_mode(ATTRIBUTE_READONLY), S_IFREG | 0o444);
+        assert_eq!(posix_mode(ATTRIBUTE_DIRECTORY), S_IFDIR | 0o755);
+        assert_eq!(
+            posix_mode(ATTRIBUTE_DIRECTORY | ATTRIBUTE_REPARSE_POINT),
+            S_IFLNK | 0o777
+        );
+    }
+
+    #[test]
+    fn test_check_guest_name() {
+        assert!(check_guest_name("main.rs").is_ok());
+        assert!(check_guest_name(".gitignore").is_ok());
+        assert!(check_guest_name("console.log").is_ok());
+        for name in [
+            "a:b",
+            "what?",
+            "trailing.",
+            "trailing ",
+            "nul",
+            "COM1.txt",
+            "tab\t",
+        ] {
+            assert!(check_guest_name(name).is_err(), "{}", name);
+        }
+    }
+}
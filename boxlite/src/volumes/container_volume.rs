@@ -7,9 +7,13 @@
 //! - Host: Only tracks volume_name, doesn't know guest paths
 //! - Guest: Constructs paths from `/run/boxlite/shared/containers/{container_id}/volumes/{volume_name}`
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use super::guest_volume::GuestVolumeManager;
+use boxlite_shared::layout::{GUEST_BASE, SharedGuestLayout};
+
+use super::guest_volume::{GuestVolumeManager, NetworkMountEntry};
+use crate::disk::DiskFormat;
+use crate::portal::interfaces::IdMapping;
 
 /// Container bind mount entry.
 ///
@@ -23,6 +27,10 @@ pub struct ContainerMount {
     pub destination: String,
     /// Read-only mount
     pub read_only: bool,
+    /// Paths inside the volume masked in the container
+    pub hidden: Vec<String>,
+    /// Fill the volume from the image if it is empty
+    pub populate: bool,
 }
 
 /// Manages container-level volume configuration.
@@ -85,18 +93,100 @@ impl<'a> ContainerVolumeManager<'a> {
             volume_name: volume_name.to_string(),
             destination: container_path.to_string(),
             read_only,
+            hidden: Vec::new(),
+            populate: false,
         });
     }
 
+    /// Add a network filesystem, mounted by the guest at the convention
+    /// path for `entry.name` and bound to `container_path`.
+    pub fn add_network_volume(&mut self, entry: NetworkMountEntry, container_path: &str) {
+        self.container_mounts.push(ContainerMount {
+            volume_name: entry.name.clone(),
+            destination: container_path.to_string(),
+            read_only: entry.read_only,
+            hidden: Vec::new(),
+            populate: false,
+        });
+        self.guest.add_network_mount(entry);
+    }
+
+    /// Add a block device, mounted by the guest at the convention path for
+    /// `volume_name` and bound to `container_path`.
+    pub fn add_block_volume(
+        &mut self,
+        container_id: &str,
+        volume_name: &str,
+        disk_path: &Path,
+        container_path: &str,
+        read_only: bool,
+        need_format: bool,
+    ) {
+        let guest_path = SharedGuestLayout::new(Path::new(GUEST_BASE).join("shared"))
+            .container(container_id)
+            .volume_dir(volume_name);
+        // Read-only at the bind: ext4 cannot be mounted read-write on a
+        // read-only device, and a new disk must still be formatted
+        self.guest.add_block_device(
+            disk_path,
+            DiskFormat::Ext4,
+            false,
+            Some(&guest_path.to_string_lossy()),
+            need_format,
+            false,
+        );
+        self.add_bind(volume_name, container_path, read_only);
+    }
+
+    /// Restrict how a volume added with [`add_volume`](Self::add_volume)
+    /// appears: its host owner shown as other ids, and subpaths masked.
+    pub fn restrict_volume(
+        &mut self,
+        volume_name: &str,
+        tag: &str,
+        id_mapping: Option<IdMapping>,
+        hidden: &[String],
+    ) {
+        if let Some(mapping) = id_mapping {
+            self.guest.set_id_mapping(tag, mapping);
+        }
+        if let Some(mount) = self
+            .container_mounts
+            .iter_mut()
+            .find(|m| m.volume_name == volume_name)
+        {
+            mount.hidden = hidden.to_vec();
+        }
+    }
+
+    /// Let the box boot without the volume shared as `tag`, degraded, if
+    /// the guest cannot mount it.
+    pub fn make_optional(&mut self, tag: &str) {
+        self.guest.set_optional(tag);
+    }
+
+    /// Have the guest fill `volume_name` from the image's files at its
+    /// destination (or `/etc/skel`) when it mounts the volume empty.
+    pub fn populate_volume(&mut self, volume_name: &str) {
+        if let Some(mount) = self
+            .container_mounts
+            .iter_mut()
+            .find(|m| m.volume_name == volume_name)
+        {
+            mount.populate = true;
+        }
+    }
+
     /// Add a container bind mount directly.
     ///
     /// Use when guest path already exists (e.g., from block device mount).
-    #[allow(dead_code)]
     pub fn add_bind(&mut self, volume_name: &str, container_path: &str, read_only: bool) {
         self.container_mounts.push(ContainerMount {
             volume_name: volume_name.to_string(),
             destination: container_path.to_string(),
             read_only,
+            hidden: Vec::new(),
+            populate: false,
         });
     }
 
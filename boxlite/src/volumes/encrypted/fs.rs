@@ -0,0 +1,241 @@
+//! FUSE filesystem exposing a decrypted [`EncryptedImage`] as one file.
+//!
+//! The mount holds a single regular file, `disk.img`, which the VMM
+//! attaches as a raw block device. Reads and writes go straight to the
+//! image, uncached, so the host page cache never holds plaintext.
+
+use std::ffi::CStr;
+use std::io;
+use std::time::Duration;
+
+use fuse_backend_rs::abi::fuse_abi::stat64;
+use fuse_backend_rs::api::filesystem::{
+    Context, DirEntry, Entry, FileSystem, FsOptions, OpenOptions, ROOT_ID, ZeroCopyReader,
+    ZeroCopyWriter,
+};
+
+use super::image::{BLOCK_SIZE, EncryptedImage};
+
+pub(crate) const DISK_FILE: &str = "disk.img";
+
+const DISK_ID: u64 = ROOT_ID + 1;
+const TIMEOUT: Duration = Duration::from_secs(3600);
+
+pub(crate) struct ImageFs {
+    image: EncryptedImage,
+    uid: u32,
+    gid: u32,
+}
+
+impl ImageFs {
+    pub(crate) fn new(image: EncryptedImage) -> Self {
+        // SAFETY: getuid/getgid have no preconditions
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self { image, uid, gid }
+    }
+
+    fn attr(&self, inode: u64) -> io::Result<stat64> {
+        // SAFETY: stat64 is plain data; all-zero is a valid value
+        let mut st: stat64 = unsafe { std::mem::zeroed() };
+        st.st_ino = inode;
+        st.st_uid = self.uid;
+        st.st_gid = self.gid;
+        st.st_blksize = BLOCK_SIZE as _;
+        match inode {
+            ROOT_ID => {
+                st.st_mode = libc::S_IFDIR | 0o700;
+                st.st_nlink = 2;
+            }
+            DISK_ID => {
+                st.st_mode = libc::S_IFREG | 0o600;
+                st.st_nlink = 1;
+                st.st_size = self.image.size() as _;
+                st.st_blocks = (self.image.size() / 512) as _;
+            }
+            _ => return Err(io::Error::from_raw_os_error(libc::ENOENT)),
+        }
+        Ok(st)
+    }
+}
+
+impl FileSystem for ImageFs {
+    type Inode = u64;
+    type Handle = u64;
+
+    fn init(&self, _capable: FsOptions) -> io::Result<FsOptions> {
+        Ok(FsOptions::empty())
+    }
+
+    fn lookup(&self, _ctx: &Context, parent: u64, name: &CStr) -> io::Result<Entry> {
+        if parent != ROOT_ID || name.to_bytes() != DISK_FILE.as_bytes() {
+            return Err(io::Error::from_raw_os_error(libc::ENOENT));
+        }
+        Ok(Entry {
+            inode: DISK_ID,
+            generation: 0,
+            attr: self.attr(DISK_ID)?,
+            attr_flags: 0,
+            attr_timeout: TIMEOUT,
+            entry_timeout: TIMEOUT,
+        })
+    }
+
+    fn getattr(
+        &self,
+        _ctx: &Context,
+        inode: u64,
+        _handle: Option<u64>,
+    ) -> io::Result<(stat64, Duration)> {
+        Ok((self.attr(inode)?, TIMEOUT))
+    }
+
+    fn open(
+        &self,
+        _ctx: &Context,
+        inode: u64,
+        _flags: u32,
+        _fuse_flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions, Option<u32>)> {
+        if inode != DISK_ID {
+            return Err(io::Error::from_raw_os_error(libc::EISDIR));
+        }
+        Ok((None, OpenOptions::DIRECT_IO, None))
+    }
+
+    fn read(
+        &self,
+        _ctx: &Context,
+        inode: u64,
+        _handle: u64,
+        w: &mut dyn ZeroCopyWriter,
+        size: u32,
+        offset: u64,
+        _lock_owner: Option<u64>,
+        _flags: u32,
+    ) -> io::Result<usize> {
+        if inode != DISK_ID {
+            return Err(io::Error::from_raw_os_error(libc::EISDIR));
+        }
+        let mut buf = vec![0u8; size as usize];
+        let len = self.image.read_at(&mut buf, offset)?;
+        w.write_all(&buf[..len])?;
+        Ok(len)
+    }
+
+    fn write(
+        &self,
+        _ctx: &Context,
+        inode: u64,
+        _handle: u64,
+        r: &mut dyn ZeroCopyReader,
+        size: u32,
+        offset: u64,
+        _lock_owner: Option<u64>,
+        _delayed_write: bool,
+        _flags: u32,
+        _fuse_flags: u32,
+    ) -> io::Result<usize> {
+        if inode != DISK_ID {
+            return Err(io::Error::from_raw_os_error(libc::EISDIR));
+        }
+        let mut buf = vec![0u8; size as usize];
+        r.read_exact(&mut buf)?;
+        self.image.write_at(&buf, offset)
+    }
+
+    fn flush(&self, _ctx: &Context, _inode: u64, _handle: u64, _lock_owner: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn fsync(&self, _ctx: &Context, _inode: u64, _datasync: bool, _handle: u64) -> io::Result<()> {
+        self.image.sync()
+    }
+
+    fn release(
+        &self,
+        _ctx: &Context,
+        _inode: u64,
+        _flags: u32,
+        _handle: u64,
+        _flush: bool,
+        _flock_release: bool,
+        _lock_owner: Option<u64>,
+    ) -> io::Result<()> {
+        self.image.sync()
+    }
+
+    fn opendir(
+        &self,
+        _ctx: &Context,
+        inode: u64,
+        _flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions)> {
+        if inode != ROOT_ID {
+            return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+        }
+        Ok((None, OpenOptions::empty()))
+    }
+
+    fn readdir(
+        &self,
+        _ctx: &Context,
+        inode: u64,
+        _handle: u64,
+        _size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        if inode != ROOT_ID {
+            return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+        }
+        let entries = [
+            (ROOT_ID, libc::DT_DIR, "."),
+            (ROOT_ID, libc::DT_DIR, ".."),
+            (DISK_ID, libc::DT_REG, DISK_FILE),
+        ];
+        for (index, (ino, type_, name)) in entries.iter().enumerate().skip(offset as usize) {
+            let added = add_entry(DirEntry {
+                ino: *ino,
+                offset: index as u64 + 1,
+                type_: *type_ as u32,
+                name: name.as_bytes(),
+            })?;
+            if added == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn releasedir(&self, _ctx: &Context, _inode: u64, _flags: u32, _handle: u64) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposes_one_disk_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let image =
+            EncryptedImage::create(&dir.path().join("data.img"), &[1u8; 32], 8 * BLOCK_SIZE)
+                .unwrap();
+        let fs = ImageFs::new(image);
+        let ctx = Context::default();
+
+        let entry = fs.lookup(&ctx, ROOT_ID, c"disk.img").unwrap();
+        assert_eq!(entry.inode, DISK_ID);
+        assert_eq!(entry.attr.st_size as u64, 8 * BLOCK_SIZE);
+        assert!(fs.lookup(&ctx, ROOT_ID, c"other").is_err());
+
+        let mut names = Vec::new();
+        fs.readdir(&ctx, ROOT_ID, 0, 4096, 0, &mut |entry| {
+            names.push(String::from_utf8_lossy(entry.name).into_owned());
+            Ok(1)
+        })
+        .unwrap();
+        assert_eq!(names, [".", "..", "disk.img"]);
+    }
+}
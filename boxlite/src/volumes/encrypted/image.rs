@@ -0,0 +1,643 @@
+//! Encrypted disk image format.
+//!
+//! A 4 KiB header is followed by two copies of a generation table and then
+//! one slot per 4 KiB block of the disk: a nonce, the block's generation,
+//! the AES-256-GCM ciphertext and its tag.
+//!
+//! Every write of a block bumps its generation, and the tag covers the
+//! block index and generation, so a block can neither be moved to another
+//! index nor replaced by an older copy of itself. The table records each
+//! block's latest generation and is authenticated by an HMAC in the
+//! header. It is written on every sync, alternating between its two
+//! copies so a crash mid-sync leaves the previous one intact. A block the
+//! table has at generation 0 was never written and reads as zeros, which
+//! keeps a new image sparse; a zeroed slot for any other block fails to
+//! authenticate. What the table cannot catch is a rollback of the whole
+//! image, header included, or of blocks written since the last sync to
+//! their state at that sync.
+//!
+//! Every block write draws a fresh 96-bit nonce from the system RNG and
+//! stores it in the slot. Nothing read from the file goes into a nonce, so
+//! restoring an old image or header cannot make one repeat; two writes
+//! share a nonce with probability under 2^-32 until about 2^32 blocks
+//! (16 TiB) have been written under one key.
+//!
+//! The data key is derived from the volume key with HKDF and a per-image
+//! salt, so one key can protect several volumes. A sealed check value in
+//! the header rejects a wrong key before anything is read.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use parking_lot::{Mutex, RwLock};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::digest::{Digest, SHA256, digest};
+use ring::hkdf::{HKDF_SHA256, Salt};
+use ring::hmac::{self, HMAC_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+pub(crate) const KEY_LEN: usize = 32;
+pub(crate) const BLOCK_SIZE: u64 = 4096;
+
+const MAGIC: &[u8; 8] = b"BXLTEVOL";
+const VERSION: u32 = 2;
+const HEADER_SIZE: u64 = 4096;
+const TAG_LEN: usize = 16;
+const GENERATION_LEN: usize = 8;
+const SLOT_SIZE: u64 = (NONCE_LEN + GENERATION_LEN + TAG_LEN) as u64 + BLOCK_SIZE;
+const SALT_LEN: usize = 32;
+const KEY_INFO: &[u8] = b"boxlite encrypted volume v1";
+const TABLE_KEY_INFO: &[u8] = b"boxlite encrypted volume generations";
+const CHECK_AAD: &[u8] = b"key check";
+/// Table entries per chunk, the unit it is hashed and written in.
+const CHUNK_ENTRIES: usize = BLOCK_SIZE as usize / GENERATION_LEN;
+
+// Header layout
+const SIZE_OFFSET: usize = 16;
+const SALT_OFFSET: usize = 24;
+const CHECK_OFFSET: usize = SALT_OFFSET + SALT_LEN;
+const CHECK_LEN: usize = NONCE_LEN + 32 + TAG_LEN;
+/// One root per table copy: its sync counter and HMAC.
+const ROOTS_OFFSET: usize = 128;
+const ROOT_LEN: usize = 8 + 32;
+
+pub(crate) struct EncryptedImage {
+    file: File,
+    key: LessSafeKey,
+    table_key: hmac::Key,
+    size: u64,
+    /// Source of block nonces.
+    rng: SystemRandom,
+    /// Shared by reads; a write holds it across its read-modify-write.
+    io: RwLock<()>,
+    generations: Mutex<Generations>,
+}
+
+/// The generation table, as last written and as changed since.
+struct Generations {
+    table: Vec<u64>,
+    /// Per chunk and table copy: changed since that copy was written.
+    dirty: [Vec<bool>; 2],
+    /// Per chunk: digest of its entries, `None` once it changes.
+    digests: Vec<Option<Digest>>,
+    /// Whether anything changed since the last sync.
+    changed: bool,
+    /// Sync counter and copy of the newest table on disk.
+    counter: u64,
+    active: usize,
+}
+
+impl Generations {
+    fn new(table: Vec<u64>, counter: u64, active: usize) -> Self {
+        let chunks = table.len().div_ceil(CHUNK_ENTRIES);
+        let mut generations = Self {
+            table,
+            dirty: [vec![false; chunks], vec![false; chunks]],
+            digests: vec![None; chunks],
+            changed: false,
+            counter,
+            active,
+        };
+        generations.digest_all();
+        generations
+    }
+
+    fn record(&mut self, index: u64, generation: u64) {
+        let entry = &mut self.table[index as usize];
+        if generation <= *entry {
+            return;
+        }
+        *entry = generation;
+        let chunk = index as usize / CHUNK_ENTRIES;
+        self.dirty[0][chunk] = true;
+        self.dirty[1][chunk] = true;
+        self.digests[chunk] = None;
+        self.changed = true;
+    }
+
+    fn chunk_bytes(&self, chunk: usize) -> Vec<u8> {
+        let start = chunk * CHUNK_ENTRIES;
+        let end = (start + CHUNK_ENTRIES).min(self.table.len());
+        let mut bytes = vec![0u8; BLOCK_SIZE as usize];
+        for (i, generation) in self.table[start..end].iter().enumerate() {
+            bytes[i * GENERATION_LEN..(i + 1) * GENERATION_LEN]
+                .copy_from_slice(&generation.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn digest_all(&mut self) {
+        for chunk in 0..self.digests.len() {
+            if self.digests[chunk].is_none() {
+                self.digests[chunk] = Some(digest(&SHA256, &self.chunk_bytes(chunk)));
+            }
+        }
+    }
+
+    /// What the root HMAC of `counter` covers.
+    fn root_input(&self, counter: u64) -> Vec<u8> {
+        let mut input = counter.to_le_bytes().to_vec();
+        for d in self.digests.iter().flatten() {
+            input.extend_from_slice(d.as_ref());
+        }
+        input
+    }
+}
+
+impl EncryptedImage {
+    /// Create an image of `size` bytes (rounded up to whole blocks).
+    pub(crate) fn create(path: &Path, key: &[u8; KEY_LEN], size: u64) -> BoxliteResult<Self> {
+        let rng = SystemRandom::new();
+        let size = size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill(&mut salt)
+            .map_err(|_| BoxliteError::Internal("system RNG failed".to_string()))?;
+        let (key, table_key) = derive_keys(key, &salt)?;
+
+        let mut header = vec![0u8; HEADER_SIZE as usize];
+        header[..MAGIC.len()].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        header[SIZE_OFFSET..SIZE_OFFSET + 8].copy_from_slice(&size.to_le_bytes());
+        header[SALT_OFFSET..CHECK_OFFSET].copy_from_slice(&salt);
+        let check = &mut header[CHECK_OFFSET..CHECK_OFFSET + CHECK_LEN];
+        let (nonce, sealed) = check.split_at_mut(NONCE_LEN);
+        rng.fill(nonce)
+            .map_err(|_| BoxliteError::Internal("system RNG failed".to_string()))?;
+        seal(&key, nonce, sealed, CHECK_AAD)
+            .map_err(|e| BoxliteError::Internal(format!("Failed to seal key check: {}", e)))?;
+
+        // Both copies of the all-zero table are valid; copy 0 is newest
+        let blocks = size / BLOCK_SIZE;
+        let generations = Generations::new(vec![0; blocks as usize], 1, 0);
+        for (copy, counter) in [(0, 1u64), (1, 0)] {
+            let mac = hmac::sign(&table_key, &generations.root_input(counter));
+            let root = &mut header[root_offset(copy)..root_offset(copy) + ROOT_LEN];
+            root[..8].copy_from_slice(&counter.to_le_bytes());
+            root[8..].copy_from_slice(mac.as_ref());
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| storage_error("create", path, e))?;
+        file.write_all_at(&header, 0)
+            .and_then(|()| file.set_len(slot_offset(blocks, 0)))
+            .and_then(|()| file.sync_all())
+            .map_err(|e| storage_error("initialize", path, e))?;
+
+        Ok(Self {
+            file,
+            key,
+            table_key,
+            size,
+            rng,
+            io: RwLock::new(()),
+            generations: Mutex::new(generations),
+        })
+    }
+
+    /// Open an existing image, failing if `key` is not its key.
+    pub(crate) fn open(path: &Path, key: &[u8; KEY_LEN]) -> BoxliteResult<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| storage_error("open", path, e))?;
+        let mut header = vec![0u8; HEADER_SIZE as usize];
+        file.read_exact_at(&mut header, 0)
+            .map_err(|e| storage_error("read header of", path, e))?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(BoxliteError::Storage(format!(
+                "{} is not an encrypted volume image",
+                path.display()
+            )));
+        }
+        if header[8..12] != VERSION.to_le_bytes() {
+            return Err(BoxliteError::Storage(format!(
+                "Encrypted volume {} uses an unsupported format version; copy its data to a new volume",
+                path.display()
+            )));
+        }
+        let size = u64::from_le_bytes(header[SIZE_OFFSET..SIZE_OFFSET + 8].try_into().unwrap());
+        let (key, table_key) = derive_keys(key, &header[SALT_OFFSET..CHECK_OFFSET])?;
+
+        let check = &mut header[CHECK_OFFSET..CHECK_OFFSET + CHECK_LEN];
+        let (nonce, sealed) = check.split_at_mut(NONCE_LEN);
+        if open(&key, nonce, sealed, CHECK_AAD).is_err() {
+            return Err(BoxliteError::Config(format!(
+                "Wrong key for encrypted volume {}",
+                path.display()
+            )));
+        }
+
+        let blocks = size / BLOCK_SIZE;
+        let generations = load_generations(&file, &header, &table_key, blocks)
+            .map_err(|e| storage_error("read generation table of", path, e))?
+            .ok_or_else(|| {
+                BoxliteError::Storage(format!(
+                    "Generation table of encrypted volume {} failed to authenticate",
+                    path.display()
+                ))
+            })?;
+
+        Ok(Self {
+            file,
+            key,
+            table_key,
+            size,
+            rng: SystemRandom::new(),
+            io: RwLock::new(()),
+            generations: Mutex::new(generations),
+        })
+    }
+
+    /// Size of the decrypted disk in bytes.
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Read up to `buf.len()` bytes at `offset`; short only at the end.
+    pub(crate) fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let _io = self.io.read();
+        let len = (buf.len() as u64).min(self.size.saturating_sub(offset)) as usize;
+        let mut block = [0u8; BLOCK_SIZE as usize];
+        let mut done = 0;
+        while done < len {
+            let position = offset + done as u64;
+            let index = position / BLOCK_SIZE;
+            let start = (position % BLOCK_SIZE) as usize;
+            let count = (BLOCK_SIZE as usize - start).min(len - done);
+            self.read_block(index, &mut block)?;
+            buf[done..done + count].copy_from_slice(&block[start..start + count]);
+            done += count;
+        }
+        Ok(len)
+    }
+
+    /// Write all of `buf` at `offset`, which must lie within the disk.
+    pub(crate) fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        if offset + buf.len() as u64 > self.size {
+            return Err(io::Error::from_raw_os_error(libc::ENOSPC));
+        }
+        let _io = self.io.write();
+        let mut block = [0u8; BLOCK_SIZE as usize];
+        let mut done = 0;
+        while done < buf.len() {
+            let position = offset + done as u64;
+            let index = position / BLOCK_SIZE;
+            let start = (position % BLOCK_SIZE) as usize;
+            let count = (BLOCK_SIZE as usize - start).min(buf.len() - done);
+            // Partial blocks keep the rest of their contents
+            if count < BLOCK_SIZE as usize {
+                self.read_block(index, &mut block)?;
+            }
+            block[start..start + count].copy_from_slice(&buf[done..done + count]);
+            self.write_block(index, &block)?;
+            done += count;
+        }
+        Ok(buf.len())
+    }
+
+    /// Make written blocks durable and record their generations.
+    pub(crate) fn sync(&self) -> io::Result<()> {
+        // Blocks first, so the table never names a generation not on disk
+        self.file.sync_data()?;
+        let mut generations = self.generations.lock();
+        if !generations.changed {
+            return Ok(());
+        }
+
+        let copy = 1 - generations.active;
+        let base = table_offset(self.blocks(), copy);
+        for chunk in 0..generations.digests.len() {
+            if generations.dirty[copy][chunk] {
+                self.file.write_all_at(
+                    &generations.chunk_bytes(chunk),
+                    base + chunk as u64 * BLOCK_SIZE,
+                )?;
+            }
+        }
+        generations.digest_all();
+        let counter = generations.counter + 1;
+        let mac = hmac::sign(&self.table_key, &generations.root_input(counter));
+        self.file.sync_data()?;
+
+        let mut root = [0u8; ROOT_LEN];
+        root[..8].copy_from_slice(&counter.to_le_bytes());
+        root[8..].copy_from_slice(mac.as_ref());
+        self.file.write_all_at(&root, root_offset(copy) as u64)?;
+        self.file.sync_data()?;
+
+        generations.dirty[copy].fill(false);
+        generations.changed = false;
+        generations.counter = counter;
+        generations.active = copy;
+        Ok(())
+    }
+
+    fn read_block(&self, index: u64, out: &mut [u8; BLOCK_SIZE as usize]) -> io::Result<()> {
+        let mut slot = [0u8; SLOT_SIZE as usize];
+        self.file
+            .read_exact_at(&mut slot, slot_offset(self.blocks(), index))?;
+        let (nonce, rest) = slot.split_at_mut(NONCE_LEN);
+        let (generation, sealed) = rest.split_at_mut(GENERATION_LEN);
+        let generation = u64::from_le_bytes((&*generation).try_into().unwrap());
+        let recorded = self.generations.lock().table[index as usize];
+        let failed = |why: &str| {
+            tracing::error!(block = index, "Encrypted volume block {}", why);
+            io::Error::from_raw_os_error(libc::EIO)
+        };
+        if generation < recorded {
+            return Err(failed("was rolled back"));
+        }
+        // Never written
+        if generation == 0 {
+            out.fill(0);
+            return Ok(());
+        }
+        let plain = open(&self.key, nonce, sealed, &block_aad(index, generation))
+            .map_err(|_| failed("failed to authenticate"))?;
+        out.copy_from_slice(plain);
+        // Written after the last sync, before the image was last closed
+        if generation > recorded {
+            self.generations.lock().record(index, generation);
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, index: u64, data: &[u8; BLOCK_SIZE as usize]) -> io::Result<()> {
+        let offset = slot_offset(self.blocks(), index);
+        // The slot may hold a generation written after the last sync
+        let mut stored = [0u8; GENERATION_LEN];
+        self.file
+            .read_exact_at(&mut stored, offset + NONCE_LEN as u64)?;
+        let recorded = self.generations.lock().table[index as usize];
+        let generation = recorded
+            .max(u64::from_le_bytes(stored))
+            .checked_add(1)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EIO))?;
+
+        let mut slot = [0u8; SLOT_SIZE as usize];
+        let (nonce, rest) = slot.split_at_mut(NONCE_LEN);
+        self.rng
+            .fill(nonce)
+            .map_err(|_| io::Error::other("system RNG failed"))?;
+        let (stored, sealed) = rest.split_at_mut(GENERATION_LEN);
+        stored.copy_from_slice(&generation.to_le_bytes());
+        sealed[..BLOCK_SIZE as usize].copy_from_slice(data);
+        seal(&self.key, nonce, sealed, &block_aad(index, generation))?;
+        self.file.write_all_at(&slot, offset)?;
+        self.generations.lock().record(index, generation);
+        Ok(())
+    }
+
+    fn blocks(&self) -> u64 {
+        self.size / BLOCK_SIZE
+    }
+}
+
+impl Drop for EncryptedImage {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync() {
+            tracing::warn!("Failed to sync encrypted volume on close: {}", e);
+        }
+    }
+}
+
+/// The newest table copy whose root authenticates, or `None`.
+fn load_generations(
+    file: &File,
+    header: &[u8],
+    table_key: &hmac::Key,
+    blocks: u64,
+) -> io::Result<Option<Generations>> {
+    let mut roots: Vec<(usize, u64, &[u8])> = (0..2)
+        .map(|copy| {
+            let root = &header[root_offset(copy)..root_offset(copy) + ROOT_LEN];
+            let counter = u64::from_le_bytes(root[..8].try_into().unwrap());
+            (copy, counter, &root[8..])
+        })
+        .collect();
+    roots.sort_by_key(|&(_, counter, _)| std::cmp::Reverse(counter));
+
+    let mut bytes = vec![0u8; table_len(blocks) as usize];
+    for (copy, counter, mac) in roots {
+        file.read_exact_at(&mut bytes, table_offset(blocks, copy))?;
+        let table = bytes
+            .chunks_exact(GENERATION_LEN)
+            .take(blocks as usize)
+            .map(|entry| u64::from_le_bytes(entry.try_into().unwrap()))
+            .collect();
+        let mut generations = Generations::new(table, counter, copy);
+        if hmac::verify(table_key, &generations.root_input(counter), mac).is_ok() {
+            // The other copy is behind by an unknown set of chunks
+            generations.dirty[1 - copy].fill(true);
+            return Ok(Some(generations));
+        }
+    }
+    Ok(None)
+}
+
+fn table_len(blocks: u64) -> u64 {
+    (blocks * GENERATION_LEN as u64).div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+fn table_offset(blocks: u64, copy: usize) -> u64 {
+    HEADER_SIZE + copy as u64 * table_len(blocks)
+}
+
+fn root_offset(copy: usize) -> usize {
+    ROOTS_OFFSET + copy * ROOT_LEN
+}
+
+fn slot_offset(blocks: u64, index: u64) -> u64 {
+    HEADER_SIZE + 2 * table_len(blocks) + index * SLOT_SIZE
+}
+
+/// Associated data of a block: where it is and which write of it.
+fn block_aad(index: u64, generation: u64) -> [u8; 16] {
+    let mut aad = [0u8; 16];
+    aad[..8].copy_from_slice(&index.to_le_bytes());
+    aad[8..].copy_from_slice(&generation.to_le_bytes());
+    aad
+}
+
+/// The data key and the key authenticating the generation table.
+fn derive_keys(key: &[u8; KEY_LEN], salt: &[u8]) -> BoxliteResult<(LessSafeKey, hmac::Key)> {
+    let failed = |_| BoxliteError::Internal("Failed to derive volume key".to_string());
+    let prk = Salt::new(HKDF_SHA256, salt).extract(key);
+    let data = prk.expand(&[KEY_INFO], &AES_256_GCM).map_err(failed)?;
+    let data = LessSafeKey::new(UnboundKey::from(data));
+    let table = prk.expand(&[TABLE_KEY_INFO], HMAC_SHA256).map_err(failed)?;
+    Ok((data, hmac::Key::from(table)))
+}
+
+/// Seal `sealed` in place under `nonce`: the plaintext before the last
+/// `TAG_LEN` bytes, which receive the tag.
+fn seal(key: &LessSafeKey, nonce: &[u8], sealed: &mut [u8], aad: &[u8]) -> io::Result<()> {
+    let failed = || io::Error::other("encryption failed");
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| failed())?;
+    let (plain, tag) = sealed.split_at_mut(sealed.len() - TAG_LEN);
+    let computed = key
+        .seal_in_place_separate_tag(nonce, Aad::from(aad), plain)
+        .map_err(|_| failed())?;
+    tag.copy_from_slice(computed.as_ref());
+    Ok(())
+}
+
+/// Open `sealed` (ciphertext then tag) in place, returning the plaintext.
+fn open<'a>(
+    key: &LessSafeKey,
+    nonce: &[u8],
+    sealed: &'a mut [u8],
+    aad: &[u8],
+) -> Result<&'a mut [u8], ring::error::Unspecified> {
+    let nonce = Nonce::try_assume_unique_for_key(nonce)?;
+    key.open_in_place(nonce, Aad::from(aad), sealed)
+}
+
+fn storage_error(action: &str, path: &Path, e: io::Error) -> BoxliteError {
+    BoxliteError::Storage(format!(
+        "Failed to {} encrypted volume {}: {}",
+        action,
+        path.display(),
+        e
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_wrong_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.img");
+        let key = [7u8; KEY_LEN];
+        let image = EncryptedImage::create(&path, &key, 5 * BLOCK_SIZE - 100).unwrap();
+        assert_eq!(image.size(), 5 * BLOCK_SIZE);
+
+        // Unaligned, across a block boundary
+        let data = b"secret project notes".repeat(300);
+        image.write_at(&data, BLOCK_SIZE - 10).unwrap();
+        assert!(image.write_at(b"x", 5 * BLOCK_SIZE).is_err());
+        drop(image);
+
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(14).any(|w| w == b"secret project"));
+
+        let image = EncryptedImage::open(&path, &key).unwrap();
+        let mut buf = vec![0u8; data.len() + 20];
+        image.read_at(&mut buf, BLOCK_SIZE - 20).unwrap();
+        assert_eq!(&buf[..10], &[0u8; 10]);
+        assert_eq!(&buf[10..10 + data.len()], &data[..]);
+        assert_eq!(&buf[10 + data.len()..], &[0u8; 10]);
+        let mut tail = [1u8; 200];
+        assert_eq!(image.read_at(&mut tail, 5 * BLOCK_SIZE - 50).unwrap(), 50);
+        assert_eq!(&tail[..50], &[0u8; 50]);
+
+        assert!(matches!(
+            EncryptedImage::open(&path, &[8u8; KEY_LEN]),
+            Err(BoxliteError::Config(_))
+        ));
+
+        // A tampered block fails to authenticate
+        let mut raw = raw;
+        let slot = slot_offset(5, 0) as usize;
+        raw[slot + NONCE_LEN + GENERATION_LEN + 5] ^= 1;
+        std::fs::write(&path, &raw).unwrap();
+        let image = EncryptedImage::open(&path, &key).unwrap();
+        assert!(image.read_at(&mut buf[..16], 0).is_err());
+    }
+
+    #[test]
+    fn test_zeroed_and_rolled_back_blocks_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.img");
+        let key = [7u8; KEY_LEN];
+        let image = EncryptedImage::create(&path, &key, 2 * BLOCK_SIZE).unwrap();
+        let slot = slot_offset(2, 0) as usize..slot_offset(2, 1) as usize;
+
+        image.write_at(b"old", 0).unwrap();
+        image.sync().unwrap();
+        let old = std::fs::read(&path).unwrap()[slot.clone()].to_vec();
+        image.write_at(b"new", 0).unwrap();
+        drop(image);
+
+        // An older copy of the block
+        let mut raw = std::fs::read(&path).unwrap();
+        let current = raw[slot.clone()].to_vec();
+        raw[slot.clone()].copy_from_slice(&old);
+        std::fs::write(&path, &raw).unwrap();
+        let image = EncryptedImage::open(&path, &key).unwrap();
+        let mut buf = [0u8; 3];
+        assert!(image.read_at(&mut buf, 0).is_err());
+        // Untouched blocks still read as zeros
+        image.read_at(&mut buf, BLOCK_SIZE).unwrap();
+        assert_eq!(buf, [0u8; 3]);
+        drop(image);
+
+        // A zeroed slot is not an empty block once written
+        let mut raw = std::fs::read(&path).unwrap();
+        raw[slot.clone()].fill(0);
+        std::fs::write(&path, &raw).unwrap();
+        let image = EncryptedImage::open(&path, &key).unwrap();
+        assert!(image.read_at(&mut buf, 0).is_err());
+        drop(image);
+
+        let mut raw = std::fs::read(&path).unwrap();
+        raw[slot].copy_from_slice(&current);
+        std::fs::write(&path, &raw).unwrap();
+        let image = EncryptedImage::open(&path, &key).unwrap();
+        image.read_at(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"new");
+    }
+
+    #[test]
+    fn test_restored_image_does_not_reuse_nonces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.img");
+        let key = [7u8; KEY_LEN];
+        let nonce = slot_offset(1, 0) as usize..slot_offset(1, 0) as usize + NONCE_LEN;
+        drop(EncryptedImage::create(&path, &key, BLOCK_SIZE).unwrap());
+        let backup = std::fs::read(&path).unwrap();
+
+        // The same write after restoring the same image each time
+        let mut nonces = Vec::new();
+        for _ in 0..2 {
+            std::fs::write(&path, &backup).unwrap();
+            let image = EncryptedImage::open(&path, &key).unwrap();
+            image.write_at(b"same data", 0).unwrap();
+            drop(image);
+            nonces.push(std::fs::read(&path).unwrap()[nonce.clone()].to_vec());
+        }
+        assert_ne!(nonces[0], nonces[1]);
+    }
+
+    #[test]
+    fn test_concurrent_partial_writes_keep_both() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = [7u8; KEY_LEN];
+        let image = EncryptedImage::create(&dir.path().join("data.img"), &key, BLOCK_SIZE).unwrap();
+        let half = BLOCK_SIZE as usize / 2;
+        std::thread::scope(|s| {
+            for (offset, byte) in [(0, 1u8), (half, 2u8)] {
+                let image = &image;
+                s.spawn(move || {
+                    for _ in 0..200 {
+                        image.write_at(&vec![byte; half], offset as u64).unwrap();
+                    }
+                });
+            }
+        });
+        let mut buf = vec![0u8; BLOCK_SIZE as usize];
+        image.read_at(&mut buf, 0).unwrap();
+        assert!(buf[..half].iter().all(|&b| b == 1));
+        assert!(buf[half..].iter().all(|&b| b == 2));
+    }
+}
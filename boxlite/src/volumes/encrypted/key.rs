@@ -0,0 +1,94 @@
+//! Fetching volume keys from their [`VolumeKeySource`].
+
+use std::os::unix::fs::MetadataExt;
+use std::process::{Command, Stdio};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+use super::image::KEY_LEN;
+use crate::runtime::options::VolumeKeySource;
+
+/// The key of the volume `name`.
+pub(crate) fn fetch(source: &VolumeKeySource, name: &str) -> BoxliteResult<[u8; KEY_LEN]> {
+    let material = match source {
+        VolumeKeySource::File { path } => {
+            let metadata = std::fs::metadata(path).map_err(|e| {
+                BoxliteError::Config(format!("Failed to read key file {}: {}", path.display(), e))
+            })?;
+            if metadata.mode() & 0o077 != 0 {
+                return Err(BoxliteError::Config(format!(
+                    "Key file {} must not be accessible to group or others",
+                    path.display()
+                )));
+            }
+            std::fs::read(path).map_err(|e| {
+                BoxliteError::Config(format!("Failed to read key file {}: {}", path.display(), e))
+            })?
+        }
+        VolumeKeySource::Keyring { service, account } => run(
+            Command::new("secret-tool").args(["lookup", "service", service, "account", account]),
+            "secret-tool",
+        )?,
+        VolumeKeySource::Plugin { command } => run(
+            Command::new(&command[0])
+                .args(&command[1..])
+                .env("BOXLITE_VOLUME", name),
+            &command[0],
+        )?,
+    };
+    parse(&material).ok_or_else(|| {
+        BoxliteError::Config(format!(
+            "Key of encrypted volume '{}' is not 32 bytes or 64 hex digits",
+            name
+        ))
+    })
+}
+
+fn run(command: &mut Command, program: &str) -> BoxliteResult<Vec<u8>> {
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| BoxliteError::Config(format!("Failed to run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(BoxliteError::Config(format!(
+            "{} failed to provide a volume key: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn parse(material: &[u8]) -> Option<[u8; KEY_LEN]> {
+    if let Ok(raw) = <[u8; KEY_LEN]>::try_from(material) {
+        return Some(raw);
+    }
+    let decoded = hex::decode(material.trim_ascii()).ok()?;
+    decoded.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_formats() {
+        let hex = "00".repeat(31) + "ff";
+        let mut expected = [0u8; KEY_LEN];
+        expected[31] = 0xff;
+        assert_eq!(parse(format!("{}\n", hex).as_bytes()), Some(expected));
+        assert_eq!(parse(&[9u8; KEY_LEN]), Some([9u8; KEY_LEN]));
+        assert_eq!(parse(b"too short"), None);
+
+        let plugin = VolumeKeySource::Plugin {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("[ \"$BOXLITE_VOLUME\" = notes ] && echo {}", hex),
+            ],
+        };
+        assert_eq!(fetch(&plugin, "notes").unwrap(), expected);
+        assert!(fetch(&plugin, "other").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Named volumes encrypted at rest.
+//!
+//! Each volume is an encrypted disk image under the runtime home. While a
+//! box is attached, a host-side FUSE mount presents the decrypted disk as
+//! a single file, which the VMM attaches as a block device and the guest
+//! mounts as ext4. The key stays on the host.
+
+mod fs;
+mod image;
+mod key;
+
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use fuse_backend_rs::transport::FuseSession;
+
+use crate::runtime::options::EncryptedVolumeSpec;
+use fs::{DISK_FILE, ImageFs};
+use image::EncryptedImage;
+
+const IMAGE_FILE: &str = "data.img";
+
+/// Offset and value of the ext4 superblock magic.
+const EXT4_MAGIC_OFFSET: u64 = 1080;
+const EXT4_MAGIC: [u8; 2] = [0x53, 0xef];
+
+/// An encrypted volume attached to a box; detached on drop.
+pub struct EncryptedVolume {
+    mount_point: PathBuf,
+    needs_format: bool,
+    session: Option<FuseSession>,
+    server_thread: Option<JoinHandle<()>>,
+    // Held while attached, so a volume is used by one box at a time
+    _lock: File,
+}
+
+impl EncryptedVolume {
+    /// Unlock the volume kept in `volume_dir`, creating it on first use,
+    /// and serve its decrypted disk at `mount_point`.
+    pub fn attach(
+        spec: &EncryptedVolumeSpec,
+        volume_dir: &Path,
+        mount_point: &Path,
+    ) -> BoxliteResult<Self> {
+        std::fs::create_dir_all(volume_dir).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", volume_dir.display(), e))
+        })?;
+        let lock = lock(volume_dir, &spec.name)?;
+        let key = key::fetch(&spec.key, &spec.name)?;
+
+        let image_path = volume_dir.join(IMAGE_FILE);
+        let image = if image_path.exists() {
+            EncryptedImage::open(&image_path, &key)?
+        } else {
+            tracing::info!(volume = %spec.name, size_gb = spec.size_gb, "Creating encrypted volume");
+            EncryptedImage::create(&image_path, &key, spec.size_gb * 1024 * 1024 * 1024)?
+        };
+        // Formatted by the guest on first attach; checked rather than
+        // recorded so a box that died before formatting is not a problem
+        let mut magic = [0u8; 2];
+        image
+            .read_at(&mut magic, EXT4_MAGIC_OFFSET)
+            .map_err(|e| BoxliteError::Storage(format!("Failed to read {}: {}", spec.name, e)))?;
+        let needs_format = magic != EXT4_MAGIC;
+
+        if mount_point.exists() {
+            // A mount left behind by a crashed run
+            let _ = nix::mount::umount2(mount_point, nix::mount::MntFlags::MNT_DETACH);
+        }
+        std::fs::create_dir_all(mount_point).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", mount_point.display(), e))
+        })?;
+        let (session, server_thread) =
+            crate::fs::serve_fuse(Arc::new(ImageFs::new(image)), mount_point, "boxlite-encfs")?;
+
+        tracing::info!(
+            volume = %spec.name,
+            mount_point = %mount_point.display(),
+            needs_format,
+            "Attached encrypted volume"
+        );
+
+        Ok(Self {
+            mount_point: mount_point.to_path_buf(),
+            needs_format,
+            session: Some(session),
+            server_thread: Some(server_thread),
+            _lock: lock,
+        })
+    }
+
+    /// Decrypted disk for the VMM.
+    pub fn disk_path(&self) -> PathBuf {
+        self.mount_point.join(DISK_FILE)
+    }
+
+    /// Whether the disk has no filesystem yet.
+    pub fn needs_format(&self) -> bool {
+        self.needs_format
+    }
+}
+
+impl Drop for EncryptedVolume {
+    fn drop(&mut self) {
+        if let Some(mut session) = self.session.take() {
+            let _ = session.wake();
+            if let Err(e) = session.umount() {
+                tracing::warn!(
+                    "Failed to unmount encrypted volume {}: {}",
+                    self.mount_point.display(),
+                    e
+                );
+            }
+        }
+        if let Some(thread) = self.server_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn lock(volume_dir: &Path, name: &str) -> BoxliteResult<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(volume_dir.join(".lock"))
+        .map_err(|e| BoxliteError::Storage(format!("Failed to open volume lock: {}", e)))?;
+    // SAFETY: plain syscall on an open fd
+    let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if result != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            return Err(BoxliteError::InvalidState(format!(
+                "Encrypted volume '{}' is attached to another box",
+                name
+            )));
+        }
+        return Err(BoxliteError::Storage(format!(
+            "Failed to lock encrypted volume '{}': {}",
+            name, err
+        )));
+    }
+    Ok(file)
+}
@@ -3,14 +3,16 @@
 //! Manages volumes visible to the guest VM:
 //! - Virtiofs shares (host directory → guest mount point)
 //! - Block devices (disk image → /dev/vdX)
+//! - Network filesystems (NFS/CIFS server → guest mount point)
 //!
 //! Generates configuration for both VMM layer and guest mount instructions.
 
 use std::path::{Path, PathBuf};
 
 use crate::disk::DiskFormat;
-use crate::portal::interfaces::VolumeConfig;
+use crate::portal::interfaces::{IdMapping, NetworkCredentials, VolumeConfig};
 use crate::vmm::{BlockDevice, BlockDevices, FsShares};
+use boxlite_shared::NetworkFilesystem;
 
 /// Tracked virtiofs share entry.
 #[allow(dead_code)]
@@ -23,6 +25,12 @@ pub struct FsShareEntry {
     pub read_only: bool,
     /// Optional container_id for convention-based paths.
     pub container_id: Option<String>,
+    /// Attach a DAX window so the guest maps file contents directly.
+    pub dax: bool,
+    /// Guest ids the share's host owner appears as.
+    pub id_mapping: Option<IdMapping>,
+    /// The guest may boot without the share if it cannot mount it.
+    pub optional: bool,
 }
 
 /// Tracked block device entry.
@@ -41,6 +49,19 @@ pub struct BlockDeviceEntry {
     pub need_resize: bool,
 }
 
+/// Tracked network filesystem entry.
+#[derive(Debug, Clone)]
+pub struct NetworkMountEntry {
+    /// Volume name; the guest mounts at the convention path for it
+    pub name: String,
+    pub container_id: String,
+    pub filesystem: NetworkFilesystem,
+    pub source: String,
+    pub read_only: bool,
+    pub options: String,
+    pub credentials: Option<NetworkCredentials>,
+}
+
 /// VMM layer mount configuration.
 #[allow(dead_code)]
 pub struct VmmMountConfig {
@@ -57,6 +78,7 @@ pub struct VmmMountConfig {
 pub struct GuestVolumeManager {
     fs_shares: Vec<FsShareEntry>,
     block_devices: Vec<BlockDeviceEntry>,
+    network_mounts: Vec<NetworkMountEntry>,
     next_block_index: u8,
     next_auto_tag_index: u32,
 }
@@ -68,6 +90,7 @@ impl GuestVolumeManager {
         Self {
             fs_shares: Vec::new(),
             block_devices: Vec::new(),
+            network_mounts: Vec::new(),
             next_block_index: 0,
             next_auto_tag_index: 0,
         }
@@ -91,9 +114,47 @@ impl GuestVolumeManager {
             guest_path: guest_path.map(String::from),
             read_only,
             container_id,
+            dax: false,
+            id_mapping: None,
+            optional: false,
         });
     }
 
+    /// Map the owner of a previously added share to other guest ids.
+    pub fn set_id_mapping(&mut self, tag: &str, mapping: IdMapping) {
+        if let Some(entry) = self.fs_shares.iter_mut().find(|e| e.tag == tag) {
+            entry.id_mapping = Some(mapping);
+        }
+    }
+
+    /// Let the guest boot without a previously added share, degraded, if
+    /// every way of mounting it fails.
+    pub fn set_optional(&mut self, tag: &str) {
+        if let Some(entry) = self.fs_shares.iter_mut().find(|e| e.tag == tag) {
+            entry.optional = true;
+        }
+    }
+
+    /// Enable DAX for a previously added share, if the host supports it.
+    ///
+    /// Reads through a DAX window are served from host page cache pages
+    /// mapped into the guest, so data shared by many boxes is not duplicated
+    /// in each guest's page cache.
+    pub fn enable_dax(&mut self, tag: &str) {
+        if !crate::vmm::VIRTIOFS_DAX_SUPPORTED {
+            return;
+        }
+        if let Some(entry) = self.fs_shares.iter_mut().find(|e| e.tag == tag) {
+            entry.dax = true;
+        }
+    }
+
+    /// Add a network filesystem, mounted by the guest once its network is
+    /// up. Needs no VMM device.
+    pub fn add_network_mount(&mut self, entry: NetworkMountEntry) {
+        self.network_mounts.push(entry);
+    }
+
     /// Add a block device.
     ///
     /// Returns the device path in guest (e.g., "/dev/vda").
@@ -154,7 +215,12 @@ impl GuestVolumeManager {
     pub fn build_vmm_config(&self) -> VmmMountConfig {
         let mut fs_shares = FsShares::new();
         for entry in &self.fs_shares {
-            fs_shares.add(&entry.tag, entry.host_path.clone(), entry.read_only);
+            fs_shares.add(
+                &entry.tag,
+                entry.host_path.clone(),
+                entry.read_only,
+                entry.dax,
+            );
         }
 
         let mut block_devices = BlockDevices::new();
@@ -187,12 +253,17 @@ impl GuestVolumeManager {
         for entry in &self.fs_shares {
             // Empty mount_point = guest determines from tag
             let mount_point = entry.guest_path.as_deref().unwrap_or("");
-            volumes.push(VolumeConfig::virtiofs(
-                &entry.tag,
-                mount_point,
-                entry.read_only,
-                entry.container_id.clone(),
-            ));
+            volumes.push(
+                VolumeConfig::virtiofs(
+                    &entry.tag,
+                    mount_point,
+                    entry.read_only,
+                    entry.dax,
+                    entry.container_id.clone(),
+                )
+                .with_id_mapping(entry.id_mapping)
+                .with_optional(entry.optional),
+            );
         }
 
         for entry in &self.block_devices {
@@ -207,6 +278,18 @@ impl GuestVolumeManager {
             }
         }
 
+        for entry in &self.network_mounts {
+            volumes.push(VolumeConfig::Network {
+                filesystem: entry.filesystem,
+                source: entry.source.clone(),
+                name: entry.name.clone(),
+                container_id: entry.container_id.clone(),
+                read_only: entry.read_only,
+                options: entry.options.clone(),
+                credentials: entry.credentials.clone(),
+            });
+        }
+
         volumes
     }
 
@@ -3,9 +3,31 @@
 //! Provides:
 //! - `GuestVolumeManager` for virtiofs shares and block devices
 //! - `ContainerVolumeManager` for container bind mounts
+//! - `PackageCacheLease` for the shared package manager cache
+//! - `VolumeWatcher` for forwarding host-side changes to the guest
+//! - `QuotaMount` and `QuotaEnforcer` for capping how much a box writes to
+//!   its volumes (Linux)
+//! - `ObjectMount` for serving S3-compatible buckets as volumes (Linux)
+//! - `EncryptedVolume` for named volumes encrypted at rest (Linux)
 
 mod container_volume;
+#[cfg(target_os = "linux")]
+mod encrypted;
 mod guest_volume;
+#[cfg(target_os = "linux")]
+mod object;
+mod package_cache;
+mod quota;
+mod watch;
 
 pub use container_volume::{ContainerMount, ContainerVolumeManager};
-pub use guest_volume::GuestVolumeManager;
+#[cfg(target_os = "linux")]
+pub use encrypted::EncryptedVolume;
+pub use guest_volume::{GuestVolumeManager, NetworkMountEntry};
+#[cfg(target_os = "linux")]
+pub use object::ObjectMount;
+pub use package_cache::{PACKAGE_CACHE_PATH, PACKAGE_CACHE_TAG, PackageCacheLease};
+pub use quota::QuotaEnforcer;
+#[cfg(target_os = "linux")]
+pub use quota::{QuotaMount, QuotaVolume};
+pub use watch::{VolumeWatcher, WatchedVolume};
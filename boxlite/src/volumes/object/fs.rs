@@ -0,0 +1,500 @@
+//! FUSE filesystem presenting a bucket prefix as a directory tree.
+//!
+//! A passthrough filesystem over the cache directory does the file work;
+//! this layer fills the cache in as the tree is walked. Listing a
+//! directory for the first time lists its prefix and creates a sparse
+//! placeholder of the right size for each object, and opening a
+//! placeholder downloads the object into it. With write-back, a file
+//! written in the box is uploaded when it is closed, and removing a file
+//! deletes its object. Directories exist only as key prefixes, so an empty
+//! one made in the box is not kept in the bucket.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, OsStr};
+use std::fs::OpenOptions as FileOptions;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use fuse_backend_rs::abi::fuse_abi::{CreateIn, stat64, statvfs64};
+use fuse_backend_rs::api::filesystem::{
+    Context, DirEntry, Entry, FileSystem, FsOptions, OpenOptions, ROOT_ID, SetattrValid,
+    ZeroCopyReader, ZeroCopyWriter,
+};
+use fuse_backend_rs::passthrough::PassthroughFs;
+
+use super::client::ObjectClient;
+
+pub(crate) struct ObjectFs {
+    inner: PassthroughFs,
+    client: ObjectClient,
+    runtime: tokio::runtime::Handle,
+    cache: PathBuf,
+    /// Key prefix of the root, empty or ending in `/`
+    prefix: String,
+    write_back: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Path below the root of each inode handed out
+    paths: HashMap<u64, PathBuf>,
+    /// Directories whose prefix has been listed
+    listed: HashSet<PathBuf>,
+    /// Placeholders whose object has not been downloaded
+    pending: HashSet<PathBuf>,
+    /// Files written since they were last uploaded
+    dirty: HashSet<u64>,
+}
+
+impl ObjectFs {
+    pub(crate) fn new(
+        inner: PassthroughFs,
+        client: ObjectClient,
+        runtime: tokio::runtime::Handle,
+        cache: PathBuf,
+        prefix: &str,
+        write_back: bool,
+    ) -> Self {
+        let prefix = match prefix.trim_end_matches('/') {
+            "" => String::new(),
+            prefix => format!("{}/", prefix),
+        };
+        let mut state = State::default();
+        state.paths.insert(ROOT_ID, PathBuf::new());
+        Self {
+            inner,
+            client,
+            runtime,
+            cache,
+            prefix,
+            write_back,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn key(&self, path: &Path) -> String {
+        format!("{}{}", self.prefix, path.to_string_lossy())
+    }
+
+    fn path(&self, inode: u64) -> io::Result<PathBuf> {
+        self.state
+            .lock()
+            .unwrap()
+            .paths
+            .get(&inode)
+            .cloned()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ESTALE))
+    }
+
+    fn child(&self, parent: u64, name: &CStr) -> io::Result<PathBuf> {
+        Ok(self.path(parent)?.join(OsStr::from_bytes(name.to_bytes())))
+    }
+
+    fn remember(&self, entry: &Entry, path: PathBuf) {
+        if entry.inode != 0 {
+            self.state.lock().unwrap().paths.insert(entry.inode, path);
+        }
+    }
+
+    /// Fill the cache directory for `dir` from its prefix, once.
+    fn list(&self, dir: &Path) -> io::Result<()> {
+        if self.state.lock().unwrap().listed.contains(dir) {
+            return Ok(());
+        }
+        let prefix = if dir.as_os_str().is_empty() {
+            self.prefix.clone()
+        } else {
+            format!("{}/", self.key(dir))
+        };
+        let listing = self
+            .runtime
+            .block_on(self.client.list(&prefix))
+            .map_err(|e| {
+                tracing::warn!("Failed to list {}: {}", prefix, e);
+                io::Error::from_raw_os_error(libc::EIO)
+            })?;
+
+        let local = self.cache.join(dir);
+        let mut state = self.state.lock().unwrap();
+        for name in &listing.prefixes {
+            if is_plain_name(name) {
+                std::fs::create_dir_all(local.join(name))?;
+            }
+        }
+        for (name, size) in &listing.objects {
+            let path = local.join(name);
+            // Files already here were written in the box, or a
+            // directory of the same name won
+            if !is_plain_name(name) || path.symlink_metadata().is_ok() {
+                continue;
+            }
+            FileOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?
+                .set_len(*size)?;
+            state.pending.insert(dir.join(name));
+        }
+        state.listed.insert(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Download the object behind the placeholder at `path`, if it is one.
+    fn fetch(&self, path: &Path) -> io::Result<()> {
+        if !self.state.lock().unwrap().pending.contains(path) {
+            return Ok(());
+        }
+        let key = self.key(path);
+        // Written in place: the passthrough layer holds the file's inode
+        let mut file = FileOptions::new().write(true).open(self.cache.join(path))?;
+        let size = self
+            .runtime
+            .block_on(self.client.get(&key, &mut file))
+            .map_err(|e| {
+                tracing::warn!("Failed to download {}: {}", key, e);
+                io::Error::from_raw_os_error(libc::EIO)
+            })?;
+        file.set_len(size)?;
+        self.state.lock().unwrap().pending.remove(path);
+        tracing::debug!(key = %key, size, "Downloaded object");
+        Ok(())
+    }
+
+    fn upload(&self, path: &Path) -> io::Result<()> {
+        let key = self.key(path);
+        let body = std::fs::read(self.cache.join(path))?;
+        self.runtime
+            .block_on(self.client.put(&key, body))
+            .map_err(|e| {
+                tracing::warn!("Failed to upload {}: {}", key, e);
+                io::Error::from_raw_os_error(libc::EIO)
+            })
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let key = self.key(path);
+        match self.runtime.block_on(self.client.delete(&key)) {
+            Ok(()) | Err(boxlite_shared::errors::BoxliteError::NotFound(_)) => Ok(()),
+            Err(e) => {
+                tracing::warn!("Failed to delete {}: {}", key, e);
+                Err(io::Error::from_raw_os_error(libc::EIO))
+            }
+        }
+    }
+
+    fn writable(&self) -> io::Result<()> {
+        if self.write_back {
+            Ok(())
+        } else {
+            Err(io::Error::from_raw_os_error(libc::EROFS))
+        }
+    }
+}
+
+/// A single path component that is safe to create in the cache.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('\0')
+}
+
+fn opens_for_write(flags: u32) -> bool {
+    let flags = flags as i32;
+    flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC != 0
+}
+
+impl FileSystem for ObjectFs {
+    type Inode = u64;
+    type Handle = u64;
+
+    fn init(&self, capable: FsOptions) -> io::Result<FsOptions> {
+        self.inner.init(capable)
+    }
+
+    fn destroy(&self) {
+        self.inner.destroy()
+    }
+
+    fn lookup(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<Entry> {
+        self.list(&self.path(parent)?)?;
+        let path = self.child(parent, name)?;
+        let entry = self.inner.lookup(ctx, parent, name)?;
+        self.remember(&entry, path);
+        Ok(entry)
+    }
+
+    fn forget(&self, ctx: &Context, inode: u64, count: u64) {
+        self.inner.forget(ctx, inode, count)
+    }
+
+    fn batch_forget(&self, ctx: &Context, requests: Vec<(u64, u64)>) {
+        self.inner.batch_forget(ctx, requests)
+    }
+
+    fn getattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: Option<u64>,
+    ) -> io::Result<(stat64, Duration)> {
+        self.inner.getattr(ctx, inode, handle)
+    }
+
+    fn setattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        attr: stat64,
+        handle: Option<u64>,
+        valid: SetattrValid,
+    ) -> io::Result<(stat64, Duration)> {
+        if valid.contains(SetattrValid::SIZE) {
+            self.writable()?;
+            // Truncating keeps the start of the object
+            self.fetch(&self.path(inode)?)?;
+            self.state.lock().unwrap().dirty.insert(inode);
+        }
+        self.inner.setattr(ctx, inode, attr, handle, valid)
+    }
+
+    fn readlink(&self, ctx: &Context, inode: u64) -> io::Result<Vec<u8>> {
+        self.inner.readlink(ctx, inode)
+    }
+
+    fn mkdir(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        mode: u32,
+        umask: u32,
+    ) -> io::Result<Entry> {
+        self.writable()?;
+        let path = self.child(parent, name)?;
+        let entry = self.inner.mkdir(ctx, parent, name, mode, umask)?;
+        let mut state = self.state.lock().unwrap();
+        state.listed.insert(path.clone());
+        if entry.inode != 0 {
+            state.paths.insert(entry.inode, path);
+        }
+        Ok(entry)
+    }
+
+    fn unlink(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<()> {
+        self.writable()?;
+        let path = self.child(parent, name)?;
+        self.delete(&path)?;
+        self.state.lock().unwrap().pending.remove(&path);
+        self.inner.unlink(ctx, parent, name)
+    }
+
+    fn rmdir(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<()> {
+        self.writable()?;
+        self.inner.rmdir(ctx, parent, name)
+    }
+
+    fn rename(
+        &self,
+        ctx: &Context,
+        olddir: u64,
+        oldname: &CStr,
+        newdir: u64,
+        newname: &CStr,
+        flags: u32,
+    ) -> io::Result<()> {
+        self.writable()?;
+        let from = self.child(olddir, oldname)?;
+        let to = self.child(newdir, newname)?;
+        // Renaming a prefix means copying every object under it; EXDEV
+        // makes mv do that itself
+        if self.cache.join(&from).is_dir() {
+            return Err(io::Error::from_raw_os_error(libc::EXDEV));
+        }
+        self.fetch(&from)?;
+        self.inner
+            .rename(ctx, olddir, oldname, newdir, newname, flags)?;
+        self.upload(&to)?;
+        self.delete(&from)?;
+        let mut state = self.state.lock().unwrap();
+        state.pending.remove(&to);
+        for path in state.paths.values_mut().filter(|path| **path == from) {
+            *path = to.clone();
+        }
+        Ok(())
+    }
+
+    fn open(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+        fuse_flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions, Option<u32>)> {
+        if opens_for_write(flags) {
+            self.writable()?;
+        }
+        // A file opened with O_TRUNC is reset, so its object is not needed
+        let path = self.path(inode)?;
+        if flags as i32 & libc::O_TRUNC != 0 {
+            self.state.lock().unwrap().pending.remove(&path);
+        } else {
+            self.fetch(&path)?;
+        }
+        if opens_for_write(flags) {
+            self.state.lock().unwrap().dirty.insert(inode);
+        }
+        self.inner.open(ctx, inode, flags, fuse_flags)
+    }
+
+    fn create(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        args: CreateIn,
+    ) -> io::Result<(Entry, Option<u64>, OpenOptions, Option<u32>)> {
+        self.writable()?;
+        self.list(&self.path(parent)?)?;
+        let path = self.child(parent, name)?;
+        let created = self.inner.create(ctx, parent, name, args)?;
+        let mut state = self.state.lock().unwrap();
+        state.pending.remove(&path);
+        state.dirty.insert(created.0.inode);
+        state.paths.insert(created.0.inode, path);
+        Ok(created)
+    }
+
+    fn read(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        w: &mut dyn ZeroCopyWriter,
+        size: u32,
+        offset: u64,
+        lock_owner: Option<u64>,
+        flags: u32,
+    ) -> io::Result<usize> {
+        self.inner
+            .read(ctx, inode, handle, w, size, offset, lock_owner, flags)
+    }
+
+    fn write(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        r: &mut dyn ZeroCopyReader,
+        size: u32,
+        offset: u64,
+        lock_owner: Option<u64>,
+        delayed_write: bool,
+        flags: u32,
+        fuse_flags: u32,
+    ) -> io::Result<usize> {
+        self.inner.write(
+            ctx,
+            inode,
+            handle,
+            r,
+            size,
+            offset,
+            lock_owner,
+            delayed_write,
+            flags,
+            fuse_flags,
+        )
+    }
+
+    fn flush(&self, ctx: &Context, inode: u64, handle: u64, lock_owner: u64) -> io::Result<()> {
+        self.inner.flush(ctx, inode, handle, lock_owner)?;
+        // Uploaded on close, so a failed upload fails close()
+        if self.state.lock().unwrap().dirty.remove(&inode) {
+            let path = self.path(inode)?;
+            if let Err(e) = self.upload(&path) {
+                self.state.lock().unwrap().dirty.insert(inode);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn fsync(&self, ctx: &Context, inode: u64, datasync: bool, handle: u64) -> io::Result<()> {
+        self.inner.fsync(ctx, inode, datasync, handle)
+    }
+
+    fn release(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+        handle: u64,
+        flush: bool,
+        flock_release: bool,
+        lock_owner: Option<u64>,
+    ) -> io::Result<()> {
+        self.inner
+            .release(ctx, inode, flags, handle, flush, flock_release, lock_owner)
+    }
+
+    fn statfs(&self, ctx: &Context, inode: u64) -> io::Result<statvfs64> {
+        self.inner.statfs(ctx, inode)
+    }
+
+    fn opendir(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions)> {
+        self.list(&self.path(inode)?)?;
+        self.inner.opendir(ctx, inode, flags)
+    }
+
+    fn readdir(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        self.inner
+            .readdir(ctx, inode, handle, size, offset, add_entry)
+    }
+
+    fn readdirplus(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry, Entry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        let dir = self.path(inode)?;
+        self.inner
+            .readdirplus(ctx, inode, handle, size, offset, &mut |dir_entry, entry| {
+                let name = OsStr::from_bytes(dir_entry.name);
+                if name != "." && name != ".." {
+                    self.remember(&entry, dir.join(name));
+                }
+                add_entry(dir_entry, entry)
+            })
+    }
+
+    fn releasedir(&self, ctx: &Context, inode: u64, flags: u32, handle: u64) -> io::Result<()> {
+        self.inner.releasedir(ctx, inode, flags, handle)
+    }
+
+    fn fsyncdir(&self, ctx: &Context, inode: u64, datasync: bool, handle: u64) -> io::Result<()> {
+        self.inner.fsyncdir(ctx, inode, datasync, handle)
+    }
+
+    fn access(&self, ctx: &Context, inode: u64, mask: u32) -> io::Result<()> {
+        self.inner.access(ctx, inode, mask)
+    }
+}
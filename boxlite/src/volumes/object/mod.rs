@@ -0,0 +1,163 @@
+//! S3-compatible buckets mounted as volumes.
+//!
+//! Each bucket is served by a host-side FUSE mount over a cache directory
+//! in the box directory, and the mount is shared into the guest over
+//! virtio-fs like any other volume. Objects are only downloaded when a
+//! file is opened, so listing a large bucket is cheap.
+
+mod client;
+mod fs;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use fuse_backend_rs::passthrough::{Config, PassthroughFs};
+use fuse_backend_rs::transport::FuseSession;
+
+use crate::runtime::options::{ObjectCredentials, ObjectVolumeSpec};
+use client::{Credentials, ObjectClient};
+use fs::ObjectFs;
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// A mounted object volume; unmounted on drop.
+pub struct ObjectMount {
+    mount_point: PathBuf,
+    session: Option<FuseSession>,
+    server_thread: Option<JoinHandle<()>>,
+}
+
+impl ObjectMount {
+    /// Mount `spec` under `dir`, replacing anything cached there by a
+    /// previous run.
+    ///
+    /// Must be called from within the tokio runtime, which serves the
+    /// object store requests.
+    pub fn start(spec: &ObjectVolumeSpec, dir: &Path) -> BoxliteResult<Self> {
+        let credentials = spec
+            .credentials
+            .as_ref()
+            .map(read_credentials)
+            .transpose()?;
+        let client = ObjectClient::new(
+            &spec.endpoint,
+            &spec.bucket,
+            spec.region.as_deref().unwrap_or(DEFAULT_REGION),
+            credentials,
+        )?;
+
+        let cache = dir.join("cache");
+        let mount_point = Self::mount_point(dir);
+        if dir.exists() {
+            // A mount left behind by a crashed run keeps the directory busy
+            let _ = nix::mount::umount2(&mount_point, nix::mount::MntFlags::MNT_DETACH);
+            std::fs::remove_dir_all(dir).map_err(|e| {
+                BoxliteError::Storage(format!(
+                    "Failed to clear object volume cache {}: {}",
+                    dir.display(),
+                    e
+                ))
+            })?;
+        }
+        for path in [&cache, &mount_point] {
+            std::fs::create_dir_all(path).map_err(|e| {
+                BoxliteError::Storage(format!("Failed to create {}: {}", path.display(), e))
+            })?;
+        }
+
+        let config = Config {
+            root_dir: cache.to_string_lossy().to_string(),
+            do_import: false,
+            // Opens must reach ObjectFs so placeholders are filled
+            no_open: false,
+            no_opendir: false,
+            writeback: false,
+            killpriv_v2: false,
+            ..Default::default()
+        };
+        let inner = PassthroughFs::new(config).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to create object volume filesystem for {}: {}",
+                cache.display(),
+                e
+            ))
+        })?;
+        inner.import().map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to initialize object volume filesystem for {}: {}",
+                cache.display(),
+                e
+            ))
+        })?;
+        let fs = ObjectFs::new(
+            inner,
+            client,
+            tokio::runtime::Handle::current(),
+            cache,
+            &spec.prefix,
+            spec.write_back,
+        );
+
+        let (session, server_thread) =
+            crate::fs::serve_fuse(Arc::new(fs), &mount_point, "boxlite-objfs")?;
+
+        tracing::info!(
+            bucket = %spec.bucket,
+            prefix = %spec.prefix,
+            mount_point = %mount_point.display(),
+            write_back = spec.write_back,
+            "Mounted object volume"
+        );
+
+        Ok(Self {
+            mount_point,
+            session: Some(session),
+            server_thread: Some(server_thread),
+        })
+    }
+
+    /// Host path shared into the guest for the volume started under `dir`.
+    pub fn mount_point(dir: &Path) -> PathBuf {
+        dir.join("mnt")
+    }
+}
+
+impl Drop for ObjectMount {
+    fn drop(&mut self) {
+        if let Some(mut session) = self.session.take() {
+            let _ = session.wake();
+            if let Err(e) = session.umount() {
+                tracing::warn!(
+                    "Failed to unmount object volume {}: {}",
+                    self.mount_point.display(),
+                    e
+                );
+            }
+        }
+        if let Some(thread) = self.server_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn read_credentials(credentials: &ObjectCredentials) -> BoxliteResult<Credentials> {
+    let read = |name: &str| {
+        std::env::var(name).map_err(|_| {
+            BoxliteError::Config(format!(
+                "Object volume credential variable {} is not set",
+                name
+            ))
+        })
+    };
+    Ok(Credentials {
+        access_key: read(&credentials.access_key_env)?,
+        secret_key: read(&credentials.secret_key_env)?,
+        session_token: credentials
+            .session_token_env
+            .as_deref()
+            .map(read)
+            .transpose()?,
+    })
+}
@@ -0,0 +1,129 @@
+//! Package manager cache.
+//!
+//! A host directory holds apt archives and pip/npm download caches for boxes
+//! created with `package_cache`. Whatever one box writes there is installed
+//! by the next, so boxes only share a cache when they name the same
+//! `package_cache_domain`; a box without one gets a cache of its own.
+//! Package managers don't expect another writer on the same cache, so the
+//! directory is mounted read-write into at most one running box at a time,
+//! enforced with an exclusive flock. The VMM process inherits the lock, so
+//! it is held until the VM exits, even when the runtime detaches.
+
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Mount point of the cache inside the container.
+pub const PACKAGE_CACHE_PATH: &str = "/var/cache/boxlite";
+
+/// Volume tag of the cache share.
+pub const PACKAGE_CACHE_TAG: &str = "pkgcache";
+
+/// Per-manager subdirectories created before the cache is mounted.
+const CACHE_SUBDIRS: &[&str] = &["apt/archives/partial", "pip", "npm"];
+
+/// Exclusive use of the package cache, released on drop.
+#[derive(Debug)]
+pub struct PackageCacheLease {
+    _file: File,
+    dir: PathBuf,
+}
+
+impl PackageCacheLease {
+    /// Take the cache for one box.
+    ///
+    /// Returns `Ok(None)` when another box is using it.
+    pub fn try_acquire(dir: &Path) -> BoxliteResult<Option<Self>> {
+        for subdir in CACHE_SUBDIRS {
+            std::fs::create_dir_all(dir.join(subdir)).map_err(|e| {
+                BoxliteError::Storage(format!("failed to create package cache dir: {}", e))
+            })?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(dir.join(".lock"))
+            .map_err(|e| {
+                BoxliteError::Storage(format!("failed to open package cache lock: {}", e))
+            })?;
+
+        let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if result != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(BoxliteError::Storage(format!(
+                "failed to lock package cache: {}",
+                err
+            )));
+        }
+
+        Ok(Some(Self {
+            _file: file,
+            dir: dir.to_path_buf(),
+        }))
+    }
+
+    /// Host directory of the cache.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Descriptor holding the lock, for the VMM process to inherit.
+    pub fn lock_fd(&self) -> RawFd {
+        self._file.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_is_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let lease = PackageCacheLease::try_acquire(dir.path()).unwrap().unwrap();
+        assert!(dir.path().join("apt/archives/partial").is_dir());
+        assert!(
+            PackageCacheLease::try_acquire(dir.path())
+                .unwrap()
+                .is_none()
+        );
+
+        drop(lease);
+        assert!(
+            PackageCacheLease::try_acquire(dir.path())
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_inherited_lock_outlives_lease() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // What the VMM process holds once the runtime has gone
+        let lease = PackageCacheLease::try_acquire(dir.path()).unwrap().unwrap();
+        let inherited = unsafe { libc::dup(lease.lock_fd()) };
+        assert!(inherited >= 0);
+        drop(lease);
+        assert!(
+            PackageCacheLease::try_acquire(dir.path())
+                .unwrap()
+                .is_none()
+        );
+
+        unsafe { libc::close(inherited) };
+        assert!(
+            PackageCacheLease::try_acquire(dir.path())
+                .unwrap()
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! FUSE filesystem holding a volume to its quota.
+//!
+//! A passthrough filesystem over the volume's directory does the file
+//! work; this layer reserves room under the quota before each write and
+//! cuts a write that would cross it short, so the box gets EDQUOT once the
+//! volume is full. Creating entries in a full volume fails the same way.
+//! Space freed by deletes is picked up when the enforcer next measures the
+//! volume.
+
+use std::ffi::CStr;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use fuse_backend_rs::abi::fuse_abi::{CreateIn, stat64, statvfs64};
+use fuse_backend_rs::api::filesystem::{
+    Context, DirEntry, Entry, FileSystem, FsOptions, OpenOptions, SetattrValid, ZeroCopyReader,
+    ZeroCopyWriter,
+};
+use fuse_backend_rs::passthrough::PassthroughFs;
+
+/// Bytes allocated in a quota volume, shared by its filesystem and the enforcer.
+pub(crate) struct Usage {
+    allocated: AtomicU64,
+    quota: u64,
+}
+
+impl Usage {
+    pub(crate) fn new(allocated: u64, quota: u64) -> Self {
+        Self {
+            allocated: AtomicU64::new(allocated),
+            quota,
+        }
+    }
+
+    pub(crate) fn get(&self) -> u64 {
+        self.allocated.load(Ordering::SeqCst)
+    }
+
+    /// Replace the count with a fresh measurement.
+    pub(crate) fn set(&self, allocated: u64) {
+        self.allocated.store(allocated, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.get() >= self.quota
+    }
+
+    /// Reserve up to `bytes` under the quota, returning how many were granted.
+    ///
+    /// Fails with EDQUOT if nothing is left.
+    fn reserve(&self, bytes: u64) -> io::Result<u64> {
+        let mut granted = 0;
+        let _ = self
+            .allocated
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |allocated| {
+                granted = bytes.min(self.quota.saturating_sub(allocated));
+                Some(allocated + granted)
+            });
+        if granted == 0 && bytes > 0 {
+            return Err(io::Error::from_raw_os_error(libc::EDQUOT));
+        }
+        Ok(granted)
+    }
+
+    /// Swap a reservation for what the operation actually allocated.
+    fn settle(&self, reserved: u64, allocated: u64) {
+        let _ = self
+            .allocated
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+                Some(current.saturating_sub(reserved).saturating_add(allocated))
+            });
+    }
+
+    /// Fail with EDQUOT if the volume is full.
+    fn check(&self) -> io::Result<()> {
+        if self.is_full() {
+            Err(io::Error::from_raw_os_error(libc::EDQUOT))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub(crate) struct QuotaFs {
+    inner: PassthroughFs,
+    usage: Arc<Usage>,
+}
+
+impl QuotaFs {
+    pub(crate) fn new(inner: PassthroughFs, usage: Arc<Usage>) -> Self {
+        Self { inner, usage }
+    }
+
+    /// Bytes allocated to `inode`, or 0 if it cannot be read.
+    fn allocated(&self, ctx: &Context, inode: u64) -> u64 {
+        self.inner
+            .getattr(ctx, inode, None)
+            .map_or(0, |(attr, _)| attr.st_blocks as u64 * 512)
+    }
+
+    /// Run `op` on `inode`, then settle its reservation with what it allocated.
+    fn reserved<T>(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        reserved: u64,
+        op: impl FnOnce() -> io::Result<T>,
+    ) -> io::Result<T> {
+        let before = self.allocated(ctx, inode);
+        let result = op();
+        let grown = self.allocated(ctx, inode).saturating_sub(before);
+        self.usage.settle(reserved, grown);
+        result
+    }
+}
+
+impl FileSystem for QuotaFs {
+    type Inode = u64;
+    type Handle = u64;
+
+    fn init(&self, capable: FsOptions) -> io::Result<FsOptions> {
+        self.inner.init(capable)
+    }
+
+    fn destroy(&self) {
+        self.inner.destroy()
+    }
+
+    fn lookup(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<Entry> {
+        self.inner.lookup(ctx, parent, name)
+    }
+
+    fn forget(&self, ctx: &Context, inode: u64, count: u64) {
+        self.inner.forget(ctx, inode, count)
+    }
+
+    fn batch_forget(&self, ctx: &Context, requests: Vec<(u64, u64)>) {
+        self.inner.batch_forget(ctx, requests)
+    }
+
+    fn getattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: Option<u64>,
+    ) -> io::Result<(stat64, Duration)> {
+        self.inner.getattr(ctx, inode, handle)
+    }
+
+    fn setattr(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        attr: stat64,
+        handle: Option<u64>,
+        valid: SetattrValid,
+    ) -> io::Result<(stat64, Duration)> {
+        // Growing a file leaves a hole, which takes no space
+        self.inner.setattr(ctx, inode, attr, handle, valid)
+    }
+
+    fn readlink(&self, ctx: &Context, inode: u64) -> io::Result<Vec<u8>> {
+        self.inner.readlink(ctx, inode)
+    }
+
+    fn symlink(
+        &self,
+        ctx: &Context,
+        linkname: &CStr,
+        parent: u64,
+        name: &CStr,
+    ) -> io::Result<Entry> {
+        self.usage.check()?;
+        self.inner.symlink(ctx, linkname, parent, name)
+    }
+
+    fn mknod(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        name: &CStr,
+        mode: u32,
+        rdev: u32,
+        umask: u32,
+    ) -> io::Result<Entry> {
+        self.usage.check()?;
+        self.inner.mknod(ctx, inode, name, mode, rdev, umask)
+    }
+
+    fn mkdir(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        mode: u32,
+        umask: u32,
+    ) -> io::Result<Entry> {
+        self.usage.check()?;
+        self.inner.mkdir(ctx, parent, name, mode, umask)
+    }
+
+    fn unlink(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<()> {
+        self.inner.unlink(ctx, parent, name)
+    }
+
+    fn rmdir(&self, ctx: &Context, parent: u64, name: &CStr) -> io::Result<()> {
+        self.inner.rmdir(ctx, parent, name)
+    }
+
+    fn rename(
+        &self,
+        ctx: &Context,
+        olddir: u64,
+        oldname: &CStr,
+        newdir: u64,
+        newname: &CStr,
+        flags: u32,
+    ) -> io::Result<()> {
+        self.inner
+            .rename(ctx, olddir, oldname, newdir, newname, flags)
+    }
+
+    fn link(&self, ctx: &Context, inode: u64, newparent: u64, newname: &CStr) -> io::Result<Entry> {
+        self.usage.check()?;
+        self.inner.link(ctx, inode, newparent, newname)
+    }
+
+    fn open(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+        fuse_flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions, Option<u32>)> {
+        self.inner.open(ctx, inode, flags, fuse_flags)
+    }
+
+    fn create(
+        &self,
+        ctx: &Context,
+        parent: u64,
+        name: &CStr,
+        args: CreateIn,
+    ) -> io::Result<(Entry, Option<u64>, OpenOptions, Option<u32>)> {
+        self.usage.check()?;
+        self.inner.create(ctx, parent, name, args)
+    }
+
+    fn read(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        w: &mut dyn ZeroCopyWriter,
+        size: u32,
+        offset: u64,
+        lock_owner: Option<u64>,
+        flags: u32,
+    ) -> io::Result<usize> {
+        self.inner
+            .read(ctx, inode, handle, w, size, offset, lock_owner, flags)
+    }
+
+    fn write(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        r: &mut dyn ZeroCopyReader,
+        size: u32,
+        offset: u64,
+        lock_owner: Option<u64>,
+        delayed_write: bool,
+        flags: u32,
+        fuse_flags: u32,
+    ) -> io::Result<usize> {
+        // A write that would cross the quota is cut short at it
+        let reserved = self.usage.reserve(size.into())?;
+        self.reserved(ctx, inode, reserved, || {
+            self.inner.write(
+                ctx,
+                inode,
+                handle,
+                r,
+                reserved as u32,
+                offset,
+                lock_owner,
+                delayed_write,
+                flags,
+                fuse_flags,
+            )
+        })
+    }
+
+    fn flush(&self, ctx: &Context, inode: u64, handle: u64, lock_owner: u64) -> io::Result<()> {
+        self.inner.flush(ctx, inode, handle, lock_owner)
+    }
+
+    fn fsync(&self, ctx: &Context, inode: u64, datasync: bool, handle: u64) -> io::Result<()> {
+        self.inner.fsync(ctx, inode, datasync, handle)
+    }
+
+    fn fallocate(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        mode: u32,
+        offset: u64,
+        length: u64,
+    ) -> io::Result<()> {
+        // Cannot be cut short, so the whole range has to fit
+        let reserved = self.usage.reserve(length)?;
+        if reserved < length {
+            self.usage.settle(reserved, 0);
+            return Err(io::Error::from_raw_os_error(libc::EDQUOT));
+        }
+        self.reserved(ctx, inode, reserved, || {
+            self.inner
+                .fallocate(ctx, inode, handle, mode, offset, length)
+        })
+    }
+
+    fn release(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+        handle: u64,
+        flush: bool,
+        flock_release: bool,
+        lock_owner: Option<u64>,
+    ) -> io::Result<()> {
+        self.inner
+            .release(ctx, inode, flags, handle, flush, flock_release, lock_owner)
+    }
+
+    fn statfs(&self, ctx: &Context, inode: u64) -> io::Result<statvfs64> {
+        self.inner.statfs(ctx, inode)
+    }
+
+    fn opendir(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        flags: u32,
+    ) -> io::Result<(Option<u64>, OpenOptions)> {
+        self.inner.opendir(ctx, inode, flags)
+    }
+
+    fn readdir(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        self.inner
+            .readdir(ctx, inode, handle, size, offset, add_entry)
+    }
+
+    fn readdirplus(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(DirEntry, Entry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        self.inner
+            .readdirplus(ctx, inode, handle, size, offset, add_entry)
+    }
+
+    fn releasedir(&self, ctx: &Context, inode: u64, flags: u32, handle: u64) -> io::Result<()> {
+        self.inner.releasedir(ctx, inode, flags, handle)
+    }
+
+    fn fsyncdir(&self, ctx: &Context, inode: u64, datasync: bool, handle: u64) -> io::Result<()> {
+        self.inner.fsyncdir(ctx, inode, datasync, handle)
+    }
+
+    fn access(&self, ctx: &Context, inode: u64, mask: u32) -> io::Result<()> {
+        self.inner.access(ctx, inode, mask)
+    }
+
+    fn lseek(
+        &self,
+        ctx: &Context,
+        inode: u64,
+        handle: u64,
+        offset: u64,
+        whence: u32,
+    ) -> io::Result<u64> {
+        self.inner.lseek(ctx, inode, handle, offset, whence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errno(result: io::Result<u64>) -> Option<i32> {
+        result.err().and_then(|e| e.raw_os_error())
+    }
+
+    #[test]
+    fn test_reserve_cuts_short_at_quota() {
+        let usage = Usage::new(90, 100);
+        assert_eq!(usage.reserve(50).unwrap(), 10);
+        assert!(usage.is_full());
+        assert_eq!(errno(usage.reserve(1)), Some(libc::EDQUOT));
+        assert_eq!(usage.reserve(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_settle_counts_what_was_allocated() {
+        let usage = Usage::new(0, 100);
+        let reserved = usage.reserve(40).unwrap();
+        // An overwrite allocates nothing new
+        usage.settle(reserved, 0);
+        assert_eq!(usage.get(), 0);
+
+        let reserved = usage.reserve(40).unwrap();
+        // Allocation is in whole blocks
+        usage.settle(reserved, 64);
+        assert_eq!(usage.get(), 64);
+    }
+
+    #[test]
+    fn test_check_fails_once_full() {
+        let usage = Usage::new(99, 100);
+        assert!(usage.check().is_ok());
+        usage.set(100);
+        assert_eq!(
+            usage.check().unwrap_err().raw_os_error(),
+            Some(libc::EDQUOT)
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Write quotas for user volumes.
+//!
+//! A virtio-fs volume writes straight into a host directory, so nothing in
+//! the guest stops a box from filling the host disk through it. A volume
+//! with `quota_bytes` is instead shared through a host-side FUSE mount
+//! over its directory (Linux only), which counts what the box allocates
+//! and fails writes past the quota with EDQUOT, whatever the guest does.
+//! Once a volume is full, a `quota_exceeded` event is emitted and host-side
+//! copies into it fail with [`BoxliteError::QuotaExceeded`] until usage
+//! drops back under the quota.
+//!
+//! Usage is allocated size (see [`allocated_size`]). It is measured when
+//! the box starts and every few seconds after, which picks up deletes and
+//! changes made on the host; between measurements the mount keeps count.
+
+#[cfg(target_os = "linux")]
+mod fs;
+
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+#[cfg(target_os = "linux")]
+use fuse_backend_rs::passthrough::{Config, PassthroughFs};
+#[cfg(target_os = "linux")]
+use fuse_backend_rs::transport::FuseSession;
+use tokio::task::JoinHandle;
+
+#[cfg(target_os = "linux")]
+use crate::util::allocated_size;
+#[cfg(target_os = "linux")]
+use fs::{QuotaFs, Usage};
+
+#[cfg(target_os = "linux")]
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A user volume with a quota.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Debug)]
+pub struct QuotaVolume {
+    pub host_path: PathBuf,
+    /// Mount point inside the container.
+    pub guest_path: String,
+    pub quota_bytes: u64,
+}
+
+/// A volume served through its quota mount; unmounted on drop.
+#[cfg(target_os = "linux")]
+pub struct QuotaMount {
+    volume: QuotaVolume,
+    usage: Arc<Usage>,
+    mount_point: PathBuf,
+    session: Option<FuseSession>,
+    server_thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(target_os = "linux")]
+impl QuotaMount {
+    /// Serve `volume` at `mount_point`, which the VMM shares with the guest
+    /// in place of the volume's directory.
+    pub fn start(volume: QuotaVolume, mount_point: &Path) -> BoxliteResult<Self> {
+        if mount_point.exists() {
+            // A mount left behind by a crashed run
+            let _ = nix::mount::umount2(mount_point, nix::mount::MntFlags::MNT_DETACH);
+        }
+        std::fs::create_dir_all(mount_point).map_err(|e| {
+            BoxliteError::Storage(format!("Failed to create {}: {}", mount_point.display(), e))
+        })?;
+
+        let config = Config {
+            root_dir: volume.host_path.to_string_lossy().to_string(),
+            do_import: false,
+            // Writes must reach QuotaFs as they happen, not when flushed
+            writeback: false,
+            no_open: true,
+            no_opendir: true,
+            killpriv_v2: false,
+            ..Default::default()
+        };
+        let inner = PassthroughFs::new(config).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to create quota filesystem for {}: {}",
+                volume.host_path.display(),
+                e
+            ))
+        })?;
+        inner.import().map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to initialize quota filesystem for {}: {}",
+                volume.host_path.display(),
+                e
+            ))
+        })?;
+
+        let usage = Arc::new(Usage::new(
+            allocated_size(&volume.host_path),
+            volume.quota_bytes,
+        ));
+        let fs = QuotaFs::new(inner, Arc::clone(&usage));
+        let (session, server_thread) =
+            crate::fs::serve_fuse(Arc::new(fs), mount_point, "boxlite-quotafs")?;
+
+        tracing::info!(
+            path = %volume.guest_path,
+            usage = usage.get(),
+            quota = volume.quota_bytes,
+            mount_point = %mount_point.display(),
+            "Mounted quota volume"
+        );
+
+        Ok(Self {
+            volume,
+            usage,
+            mount_point: mount_point.to_path_buf(),
+            session: Some(session),
+            server_thread: Some(server_thread),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for QuotaMount {
+    fn drop(&mut self) {
+        if let Some(mut session) = self.session.take() {
+            let _ = session.wake();
+            if let Err(e) = session.umount() {
+                tracing::warn!(
+                    "Failed to unmount quota volume {}: {}",
+                    self.mount_point.display(),
+                    e
+                );
+            }
+        }
+        if let Some(thread) = self.server_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Measures quota volumes and reports the ones that fill up until dropped.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct QuotaEnforcer {
+    task: JoinHandle<()>,
+    /// Volumes currently full, by guest path.
+    exceeded: Arc<Mutex<HashMap<String, u64>>>,
+    #[cfg(target_os = "linux")]
+    _mounts: Vec<QuotaMount>,
+}
+
+impl QuotaEnforcer {
+    /// Start measuring the volumes served by `mounts`; `on_exceeded` is
+    /// called with the volume and its usage each time one fills up.
+    #[cfg(target_os = "linux")]
+    pub fn start<F>(mounts: Vec<QuotaMount>, on_exceeded: F) -> Self
+    where
+        F: Fn(&QuotaVolume, u64) + Send + 'static,
+    {
+        let exceeded = Arc::new(Mutex::new(HashMap::new()));
+        let shared = Arc::clone(&exceeded);
+        let volumes: Vec<_> = mounts
+            .iter()
+            .map(|mount| (mount.volume.clone(), Arc::clone(&mount.usage)))
+            .collect();
+        let task = tokio::spawn(async move {
+            loop {
+                let paths = volumes.iter().map(|(v, _)| v.host_path.clone()).collect();
+                let usages = measure(paths).await;
+                for ((volume, usage), allocated) in volumes.iter().zip(usages) {
+                    usage.set(allocated);
+                    let full = usage.is_full();
+                    let newly_full = {
+                        let mut exceeded = shared.lock().unwrap();
+                        if full {
+                            exceeded
+                                .insert(volume.guest_path.clone(), volume.quota_bytes)
+                                .is_none()
+                        } else {
+                            exceeded.remove(&volume.guest_path);
+                            false
+                        }
+                    };
+                    if newly_full {
+                        tracing::warn!(
+                            path = %volume.guest_path,
+                            usage = allocated,
+                            quota = volume.quota_bytes,
+                            "Volume reached its quota"
+                        );
+                        on_exceeded(volume, allocated);
+                    }
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+        Self {
+            task,
+            exceeded,
+            _mounts: mounts,
+        }
+    }
+
+    /// Fail if `box_path` is on a volume that is at its quota.
+    pub fn check_writable(&self, box_path: &str) -> BoxliteResult<()> {
+        check_writable(&self.exceeded.lock().unwrap(), box_path)
+    }
+}
+
+fn check_writable(exceeded: &HashMap<String, u64>, box_path: &str) -> BoxliteResult<()> {
+    match exceeded
+        .iter()
+        .find(|(guest_path, _)| Path::new(box_path).starts_with(guest_path))
+    {
+        Some((guest_path, quota)) => Err(BoxliteError::QuotaExceeded(format!(
+            "volume at {} is at its {} byte quota",
+            guest_path, quota
+        ))),
+        None => Ok(()),
+    }
+}
+
+impl Drop for QuotaEnforcer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn measure(paths: Vec<PathBuf>) -> Vec<u64> {
+    tokio::task::spawn_blocking(move || paths.iter().map(|path| allocated_size(path)).collect())
+        .await
+        // A failed measurement leaves the counts as they were
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_writable() {
+        let exceeded = HashMap::from([("/data".to_string(), 100)]);
+        assert!(matches!(
+            check_writable(&exceeded, "/data/out.bin"),
+            Err(BoxliteError::QuotaExceeded(_))
+        ));
+        assert!(check_writable(&exceeded, "/data2/out.bin").is_ok());
+        assert!(check_writable(&exceeded, "/tmp").is_ok());
+    }
+}
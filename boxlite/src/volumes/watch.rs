@@ -0,0 +1,190 @@
+//! Change notification for user volumes.
+//!
+//! virtio-fs shows host edits to the guest, but no inotify event fires in
+//! the box, so watch-mode tools never rebuild. While a box runs with
+//! `watch_volumes`, its volume trees are polled on the host and every entry
+//! whose mtime moved is reported to the guest (Container.NotifyChanges),
+//! which replays it as a real inotify `IN_ATTRIB` event. Creations,
+//! deletions and renames show up as a change to the parent directory; no
+//! create, delete or move event is raised in the box, since that would
+//! mean changing the entry itself from the guest under the host's feet.
+//!
+//! Polling keeps this portable across Linux and macOS hosts at the cost of
+//! a tree walk per interval; `.git` is skipped and large trees are capped.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use boxlite_shared::FileChange;
+use tokio::task::JoinHandle;
+
+use crate::portal::GuestSession;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Entries walked per volume before the rest of the tree is ignored.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Directories never walked.
+const SKIPPED_DIRS: &[&str] = &[".git"];
+
+/// A user volume by its virtio-fs tag and host directory.
+#[derive(Clone, Debug)]
+pub struct WatchedVolume {
+    pub name: String,
+    pub host_path: PathBuf,
+}
+
+/// Polls volumes and forwards changes to the guest until dropped.
+pub struct VolumeWatcher {
+    task: JoinHandle<()>,
+}
+
+impl VolumeWatcher {
+    pub fn start(session: GuestSession, container_id: String, volumes: Vec<WatchedVolume>) -> Self {
+        let task = tokio::spawn(async move {
+            let mut snapshots = take_snapshots(volumes.clone()).await;
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let current = take_snapshots(volumes.clone()).await;
+                let changes: Vec<FileChange> = volumes
+                    .iter()
+                    .zip(snapshots.iter().zip(&current))
+                    .flat_map(|(volume, (old, new))| {
+                        diff(old, new)
+                            .into_iter()
+                            .filter_map(|(path, mtime)| file_change(&volume.name, path, mtime))
+                    })
+                    .collect();
+                snapshots = current;
+                if changes.is_empty() {
+                    continue;
+                }
+
+                let count = changes.len();
+                let result = match session.container().await {
+                    Ok(mut container) => container.notify_changes(&container_id, changes).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(replayed) => {
+                        tracing::trace!(count, replayed, "Forwarded volume changes")
+                    }
+                    Err(e) => tracing::debug!("Failed to forward {} volume changes: {}", count, e),
+                }
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for VolumeWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type Snapshot = HashMap<String, SystemTime>;
+
+async fn take_snapshots(volumes: Vec<WatchedVolume>) -> Vec<Snapshot> {
+    tokio::task::spawn_blocking(move || {
+        volumes
+            .iter()
+            .map(|volume| snapshot(&volume.host_path))
+            .collect()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// mtime of every entry under `root`, keyed by relative path ("" is the root).
+///
+/// Symlinks are recorded but not followed; unreadable entries are skipped.
+fn snapshot(root: &Path) -> Snapshot {
+    let mut entries = Snapshot::new();
+    if let Ok(mtime) = std::fs::metadata(root).and_then(|m| m.modified()) {
+        entries.insert(String::new(), mtime);
+    }
+
+    let mut pending = vec![(root.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = pending.pop() {
+        let Ok(read_dir) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            if entries.len() >= MAX_ENTRIES {
+                tracing::warn!(
+                    root = %root.display(),
+                    "Volume has more than {} entries; not watching the rest",
+                    MAX_ENTRIES
+                );
+                return entries;
+            }
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            if let Ok(mtime) = metadata.modified() {
+                entries.insert(path.clone(), mtime);
+            }
+            if metadata.is_dir() && !SKIPPED_DIRS.contains(&name.as_str()) {
+                pending.push((entry.path(), path));
+            }
+        }
+    }
+    entries
+}
+
+/// Entries created or modified between two snapshots.
+fn diff<'a>(old: &Snapshot, new: &'a Snapshot) -> Vec<(&'a str, SystemTime)> {
+    new.iter()
+        .filter(|(path, mtime)| old.get(*path) != Some(mtime))
+        .map(|(path, mtime)| (path.as_str(), *mtime))
+        .collect()
+}
+
+fn file_change(volume_name: &str, path: &str, mtime: SystemTime) -> Option<FileChange> {
+    let since_epoch = mtime.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    Some(FileChange {
+        volume_name: volume_name.to_string(),
+        path: path.to_string(),
+        mtime_secs: i64::try_from(since_epoch.as_secs()).ok()?,
+        mtime_nanos: since_epoch.subsec_nanos(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(dir.path().join(".git/HEAD"), b"ref").unwrap();
+        let before = snapshot(dir.path());
+        assert!(before.contains_key("src/main.rs"));
+        assert!(before.contains_key(".git"));
+        assert!(!before.contains_key(".git/HEAD"));
+
+        let mut after = before.clone();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        after.insert("src/main.rs".to_string(), later);
+        after.insert("src/lib.rs".to_string(), later);
+
+        let mut changed: Vec<&str> = diff(&before, &after).into_iter().map(|(p, _)| p).collect();
+        changed.sort();
+        assert_eq!(changed, ["src/lib.rs", "src/main.rs"]);
+        assert!(diff(&after, &after).is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+//! Integration tests for running container engines inside a box.
+
+use boxlite::BoxCommand;
+use boxlite::BoxliteRuntime;
+use boxlite::runtime::options::{BoxOptions, BoxliteOptions, RootfsSpec};
+use futures::StreamExt;
+use tempfile::TempDir;
+
+/// Start dockerd, wait for it, then build and run a nested image.
+const NESTED_BUILD_SCRIPT: &str = r#"
+set -e
+dockerd-entrypoint.sh dockerd >/tmp/dockerd.log 2>&1 &
+for _ in $(seq 60); do
+    docker info >/dev/null 2>&1 && break
+    sleep 1
+done
+docker info --format '{{.Driver}} {{.CgroupVersion}}'
+mkdir -p /tmp/nested
+printf 'FROM alpine:latest\nRUN echo built > /built\n' > /tmp/nested/Dockerfile
+docker build -q -t boxlite-nested /tmp/nested >/dev/null
+docker run --rm boxlite-nested cat /built
+"#;
+
+#[tokio::test]
+#[ignore] // Requires a VM and registry access
+async fn docker_profile_runs_nested_builds() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = BoxliteRuntime::new(BoxliteOptions {
+        home_dir: temp_dir.path().to_path_buf(),
+        ..Default::default()
+    })
+    .expect("Failed to create runtime");
+
+    let litebox = runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("docker:dind".into()),
+                memory_mib: Some(2048),
+                ..Default::default()
+            }
+            .with_docker_profile(),
+            None,
+        )
+        .unwrap();
+
+    let mut execution = litebox
+        .exec(BoxCommand::new("sh").args(["-c", NESTED_BUILD_SCRIPT]))
+        .await
+        .unwrap();
+    let mut stdout = execution.stdout().unwrap();
+    let mut output = String::new();
+    while let Some(line) = stdout.next().await {
+        output.push_str(&line);
+        output.push('\n');
+    }
+    let result = execution.wait().await.unwrap();
+    assert_eq!(result.exit_code, 0, "output: {}", output);
+
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("overlay2 2"));
+    assert_eq!(lines.next(), Some("built"));
+
+    litebox.stop().await.unwrap();
+}
@@ -1,9 +1,10 @@
 //! Integration tests for box lifecycle (create, list, get, remove, stop).
 
-use boxlite::BoxliteRuntime;
-use boxlite::runtime::options::{BoxOptions, BoxliteOptions, RootfsSpec};
+use boxlite::runtime::options::{BoxOptions, BoxliteOptions, QuotaOptions, RootfsSpec};
 use boxlite::runtime::types::{BoxID, BoxStatus};
-use boxlite_shared::Transport;
+use boxlite::{BoxCommand, BoxFilter, BoxliteRuntime, EventKind, LiteBox};
+use boxlite_shared::{BoxliteError, Transport};
+use futures::StreamExt;
 use tempfile::TempDir;
 
 // ============================================================================
@@ -21,6 +22,7 @@ impl TestContext {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let options = BoxliteOptions {
             home_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
         };
         let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime");
         Self {
@@ -220,6 +222,49 @@ async fn list_info_sorted_by_creation_time_newest_first() {
     ctx.runtime.remove(box3_id.as_str(), false).await.unwrap();
 }
 
+#[tokio::test]
+async fn labels_filter_listing_and_bulk_removal() {
+    let ctx = TestContext::new();
+    let labeled = |team: &str| BoxOptions {
+        rootfs: RootfsSpec::Image("alpine:latest".into()),
+        labels: [("team".to_string(), team.to_string())].into(),
+        ..Default::default()
+    };
+
+    let ml = ctx
+        .runtime
+        .create(labeled("ml"), Some("ml".into()))
+        .unwrap();
+    let web = ctx
+        .runtime
+        .create(labeled("web"), Some("web".into()))
+        .unwrap();
+    ml.stop().await.unwrap();
+    web.stop().await.unwrap();
+
+    let filter = BoxFilter::parse(["label=team=ml"]).unwrap();
+    let matched = ctx.runtime.list_info_filtered(&filter).unwrap();
+    assert_eq!(matched.len(), 1);
+    assert_eq!(matched[0].id, *ml.id());
+    assert_eq!(
+        matched[0].labels.get("team").map(String::as_str),
+        Some("ml")
+    );
+
+    let pruned = ctx.runtime.prune(&filter).await.unwrap();
+    assert_eq!(pruned, vec![ml.id().clone()]);
+    assert!(!ctx.runtime.exists("ml").unwrap());
+    assert!(ctx.runtime.exists("web").unwrap());
+
+    let removed = ctx
+        .runtime
+        .remove_matching(&BoxFilter::new().label_exists("team"), false)
+        .await
+        .unwrap();
+    assert_eq!(removed, vec![web.id().clone()]);
+    assert!(ctx.runtime.list_info().unwrap().is_empty());
+}
+
 // ============================================================================
 // GET / EXISTS TESTS
 // ============================================================================
@@ -446,6 +491,83 @@ async fn stop_marks_box_as_stopped() {
     ctx.runtime.remove(box_id.as_str(), false).await.unwrap();
 }
 
+// ============================================================================
+// IDLE SUSPEND TESTS
+// ============================================================================
+
+#[tokio::test]
+async fn metrics_does_not_start_box() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+    let box_id = handle.id().clone();
+
+    let err = handle.metrics().await.unwrap_err();
+    assert!(matches!(err, BoxliteError::InvalidState(_)), "{err}");
+
+    let info = ctx.runtime.get_info(box_id.as_str()).unwrap().unwrap();
+    assert_eq!(info.status, BoxStatus::Starting);
+
+    ctx.runtime.remove(box_id.as_str(), true).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a VM and registry access
+async fn idle_box_suspends_and_resumes_on_exec() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                idle_timeout_secs: Some(1),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+    let box_id = handle.id().clone();
+    let status = || {
+        ctx.runtime
+            .get_info(box_id.as_str())
+            .unwrap()
+            .unwrap()
+            .status
+    };
+
+    let mut execution = handle.exec(BoxCommand::new("true")).await.unwrap();
+    assert_eq!(execution.wait().await.unwrap().exit_code, 0);
+    assert_eq!(status(), BoxStatus::Running);
+    handle.metrics().await.unwrap();
+
+    for _ in 0..50 {
+        if status() == BoxStatus::Stopped {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    }
+    assert_eq!(status(), BoxStatus::Stopped);
+
+    // Reading metrics leaves a suspended box suspended
+    assert!(handle.metrics().await.is_err());
+    assert_eq!(status(), BoxStatus::Stopped);
+
+    let mut execution = handle.exec(BoxCommand::new("true")).await.unwrap();
+    assert_eq!(execution.wait().await.unwrap().exit_code, 0);
+    assert_eq!(status(), BoxStatus::Running);
+
+    handle.stop().await.unwrap();
+    ctx.runtime.remove(box_id.as_str(), false).await.unwrap();
+}
+
 // ============================================================================
 // LITEBOX INFO TESTS
 // ============================================================================
@@ -522,6 +644,418 @@ async fn multiple_runtimes_are_isolated() {
     ctx2.runtime.remove(box2.id().as_str(), true).await.unwrap();
 }
 
+// ============================================================================
+// QUOTA TESTS
+// ============================================================================
+
+#[tokio::test]
+async fn create_rejects_boxes_over_quota() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = BoxliteRuntime::new(BoxliteOptions {
+        home_dir: temp_dir.path().to_path_buf(),
+        quota: QuotaOptions {
+            max_boxes: Some(2),
+            max_cpus: Some(4),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .unwrap();
+    let options = |cpus| BoxOptions {
+        rootfs: RootfsSpec::Image("alpine:latest".into()),
+        cpus: Some(cpus),
+        ..Default::default()
+    };
+
+    let first = runtime.create(options(3), None).unwrap();
+
+    // 3 + 2 vCPUs exceeds the limit of 4
+    let err = runtime
+        .create(options(2), None)
+        .err()
+        .expect("over cpu quota");
+    assert!(matches!(err, BoxliteError::QuotaExceeded(_)));
+
+    let second = runtime.create(options(1), None).unwrap();
+
+    // Third box exceeds the box count limit
+    let err = runtime
+        .create(options(1), None)
+        .err()
+        .expect("over box quota");
+    assert!(err.to_string().contains("boxes"));
+
+    // Removing a box frees its reservation
+    runtime.remove(second.id().as_str(), true).await.unwrap();
+    let third = runtime.create(options(1), None).unwrap();
+
+    runtime.remove(first.id().as_str(), true).await.unwrap();
+    runtime.remove(third.id().as_str(), true).await.unwrap();
+}
+
+#[tokio::test]
+async fn concurrent_creates_cannot_exceed_quota() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = BoxliteRuntime::new(BoxliteOptions {
+        home_dir: temp_dir.path().to_path_buf(),
+        quota: QuotaOptions {
+            max_boxes: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .unwrap();
+
+    let created: Vec<_> = std::thread::scope(|scope| {
+        let creates: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    runtime.create(
+                        BoxOptions {
+                            rootfs: RootfsSpec::Image("alpine:latest".into()),
+                            ..Default::default()
+                        },
+                        None,
+                    )
+                })
+            })
+            .collect();
+        creates
+            .into_iter()
+            .filter_map(|create| create.join().unwrap().ok())
+            .collect()
+    });
+    assert_eq!(created.len(), 1);
+
+    runtime
+        .remove(created[0].id().as_str(), true)
+        .await
+        .unwrap();
+}
+
+// ============================================================================
+// CLONE TESTS
+// ============================================================================
+
+#[tokio::test]
+async fn clone_never_started_box_fails() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+
+    let result = ctx.runtime.clone_box(handle.id().as_str(), None).await;
+    let err = result
+        .err()
+        .expect("clone of a box never started should fail");
+    assert!(
+        err.to_string().contains("cannot clone box"),
+        "Expected invalid state error, got: {}",
+        err
+    );
+
+    ctx.runtime
+        .remove(handle.id().as_str(), true)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn clone_stopped_box_copies_disks_with_new_identity() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ports: vec![boxlite::runtime::options::PortSpec {
+                    host_port: Some(18080),
+                    guest_port: 80,
+                    protocol: Default::default(),
+                    host_ip: None,
+                }],
+                ..Default::default()
+            },
+            Some("source".into()),
+        )
+        .unwrap();
+    let source_id = handle.id().clone();
+    handle.stop().await.unwrap();
+
+    // Simulate the disks a previous run would have left behind
+    let source_home = ctx._temp_dir.path().join("boxes").join(source_id.as_str());
+    std::fs::create_dir_all(&source_home).unwrap();
+    std::fs::write(source_home.join("disk.qcow2"), b"rootfs").unwrap();
+
+    let clone = ctx
+        .runtime
+        .clone_box("source", Some("copy".into()))
+        .await
+        .unwrap();
+    assert_ne!(clone.id(), &source_id);
+    assert_eq!(clone.name(), Some("copy"));
+
+    let info = ctx.runtime.get_info("copy").unwrap().unwrap();
+    assert_eq!(info.status, BoxStatus::Stopped);
+
+    let clone_home = ctx._temp_dir.path().join("boxes").join(clone.id().as_str());
+    assert_eq!(
+        std::fs::read(clone_home.join("disk.qcow2")).unwrap(),
+        b"rootfs"
+    );
+
+    // Duplicate names are rejected
+    assert!(
+        ctx.runtime
+            .clone_box("source", Some("copy".into()))
+            .await
+            .is_err()
+    );
+
+    ctx.runtime.remove("copy", false).await.unwrap();
+    ctx.runtime.remove("source", false).await.unwrap();
+}
+
+/// Run `hostname` in a box and return what it printed.
+async fn box_hostname(litebox: &LiteBox) -> String {
+    let mut execution = litebox.exec(BoxCommand::new("hostname")).await.unwrap();
+    let mut stdout = execution.stdout().unwrap();
+    let mut output = String::new();
+    while let Some(line) = stdout.next().await {
+        output.push_str(&line);
+    }
+    assert_eq!(execution.wait().await.unwrap().exit_code, 0);
+    output.trim().to_string()
+}
+
+#[tokio::test]
+#[ignore] // Requires a VM and registry access
+async fn clone_running_box_keeps_source_running() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ..Default::default()
+            },
+            Some("source".into()),
+        )
+        .unwrap();
+    let mut execution = handle
+        .exec(BoxCommand::new("sh").args(["-c", "echo cloned > /marker"]))
+        .await
+        .unwrap();
+    assert_eq!(execution.wait().await.unwrap().exit_code, 0);
+
+    let clone = ctx
+        .runtime
+        .clone_box("source", Some("copy".into()))
+        .await
+        .unwrap();
+    let info = ctx.runtime.get_info("source").unwrap().unwrap();
+    assert_eq!(info.status, BoxStatus::Running);
+
+    // The clone has the source's files under its own hostname
+    let mut execution = clone
+        .exec(BoxCommand::new("cat").args(["/marker"]))
+        .await
+        .unwrap();
+    assert_eq!(execution.wait().await.unwrap().exit_code, 0);
+    assert_ne!(box_hostname(&handle).await, box_hostname(&clone).await);
+
+    clone.stop().await.unwrap();
+    handle.stop().await.unwrap();
+    ctx.runtime.remove("copy", false).await.unwrap();
+    ctx.runtime.remove("source", false).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a VM and registry access
+async fn clone_running_box_through_fresh_handle() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ..Default::default()
+            },
+            Some("source".into()),
+        )
+        .unwrap();
+    let mut execution = handle.exec(BoxCommand::new("true")).await.unwrap();
+    assert_eq!(execution.wait().await.unwrap().exit_code, 0);
+    // The box keeps running, but no live state is cached for it any more
+    drop(execution);
+    drop(handle);
+
+    let clone = tokio::time::timeout(
+        tokio::time::Duration::from_secs(60),
+        ctx.runtime.clone_box("source", Some("copy".into())),
+    )
+    .await
+    .expect("clone of a running box should not deadlock")
+    .unwrap();
+    let info = ctx.runtime.get_info("source").unwrap().unwrap();
+    assert_eq!(info.status, BoxStatus::Running);
+
+    let source = ctx.runtime.get("source").unwrap().unwrap();
+    source.stop().await.unwrap();
+    ctx.runtime
+        .remove(clone.id().as_str(), false)
+        .await
+        .unwrap();
+    ctx.runtime.remove("source", false).await.unwrap();
+}
+
+// ============================================================================
+// SNAPSHOT TESTS
+// ============================================================================
+
+/// Write a minimal qcow2 v3 header (no backing file) with a content marker.
+fn write_fake_qcow2(path: &std::path::Path, marker: &[u8]) {
+    let mut header = vec![0u8; 512];
+    header[0..4].copy_from_slice(&0x514649fbu32.to_be_bytes());
+    header[4..8].copy_from_slice(&3u32.to_be_bytes());
+    header[20..24].copy_from_slice(&16u32.to_be_bytes());
+    header[24..32].copy_from_slice(&(64u64 * 1024 * 1024).to_be_bytes());
+    header[100..104].copy_from_slice(&104u32.to_be_bytes());
+    header[256..256 + marker.len()].copy_from_slice(marker);
+    std::fs::write(path, header).unwrap();
+}
+
+#[tokio::test]
+async fn snapshots_form_a_chain_and_restore_any_point() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ..Default::default()
+            },
+            Some("dev".into()),
+        )
+        .unwrap();
+    let box_id = handle.id().clone();
+    handle.stop().await.unwrap();
+
+    let box_home = ctx._temp_dir.path().join("boxes").join(box_id.as_str());
+    std::fs::create_dir_all(&box_home).unwrap();
+    write_fake_qcow2(&box_home.join("disk.qcow2"), b"first");
+
+    let s1 = ctx.runtime.snapshot("dev", "s1").unwrap();
+    assert_eq!(s1.parent_id, None);
+    assert!(s1.disk_layer.starts_with("sha256:"));
+
+    let s2 = ctx.runtime.snapshot("dev", "s2").unwrap();
+    assert_eq!(s2.parent_id.as_deref(), Some(s1.id.as_str()));
+    assert!(ctx.runtime.snapshot("dev", "s2").is_err());
+
+    let names: Vec<_> = ctx
+        .runtime
+        .list_snapshots("dev")
+        .unwrap()
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert_eq!(names, vec!["s1", "s2"]);
+
+    // s1 has a child and cannot be removed
+    assert!(ctx.runtime.remove_snapshot("dev", "s1").is_err());
+
+    // Go back to s1, then s2 is a removable leaf
+    ctx.runtime.restore_snapshot("dev", "s1").unwrap();
+    ctx.runtime.remove_snapshot("dev", "s2").unwrap();
+
+    // s1 is now the box's base and cannot be removed
+    assert!(ctx.runtime.remove_snapshot("dev", &s1.id).is_err());
+
+    // Removing the box collects its layers
+    ctx.runtime.remove("dev", false).await.unwrap();
+    let layers = std::fs::read_dir(ctx._temp_dir.path().join("snapshots"))
+        .unwrap()
+        .count();
+    assert_eq!(layers, 0);
+}
+
+#[tokio::test]
+async fn snapshot_active_box_fails() {
+    let ctx = TestContext::new();
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+
+    assert!(ctx.runtime.snapshot(handle.id().as_str(), "s1").is_err());
+
+    ctx.runtime
+        .remove(handle.id().as_str(), true)
+        .await
+        .unwrap();
+}
+
+// ============================================================================
+// EVENT TESTS
+// ============================================================================
+
+#[tokio::test]
+async fn lifecycle_events_are_recorded_and_broadcast() {
+    let ctx = TestContext::new();
+    let mut live = ctx.runtime.subscribe_events();
+    let since = chrono::Utc::now();
+
+    let handle = ctx
+        .runtime
+        .create(
+            BoxOptions {
+                rootfs: RootfsSpec::Image("alpine:latest".into()),
+                labels: [("team".to_string(), "ml".to_string())].into(),
+                ..Default::default()
+            },
+            Some("evt".into()),
+        )
+        .unwrap();
+    let box_id = handle.id().clone();
+    handle.stop().await.unwrap();
+    ctx.runtime.remove("evt", false).await.unwrap();
+
+    let kinds: Vec<_> = ctx
+        .runtime
+        .events(Some(since), &BoxFilter::parse(["label=team=ml"]).unwrap())
+        .unwrap()
+        .into_iter()
+        .map(|e| {
+            assert_eq!(e.box_id, box_id);
+            e.kind
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![EventKind::Created, EventKind::Stopped, EventKind::Removed]
+    );
+
+    assert_eq!(live.try_recv().unwrap().kind, EventKind::Created);
+
+    // Filters that don't match the box's labels exclude its events
+    let other = BoxFilter::parse(["label=team=web"]).unwrap();
+    assert!(ctx.runtime.events(Some(since), &other).unwrap().is_empty());
+}
+
 // ============================================================================
 // PERSISTENCE TESTS
 // ============================================================================
@@ -537,6 +1071,7 @@ async fn boxes_persist_across_runtime_restart() {
     {
         let options = BoxliteOptions {
             home_dir: home_dir.clone(),
+            ..Default::default()
         };
         let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime");
         let litebox = runtime
@@ -560,7 +1095,10 @@ async fn boxes_persist_across_runtime_restart() {
 
     // Create new runtime with same home directory (simulates restart)
     {
-        let options = BoxliteOptions { home_dir };
+        let options = BoxliteOptions {
+            home_dir,
+            ..Default::default()
+        };
         let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime");
 
         // Box should be recovered from database
@@ -589,6 +1127,7 @@ async fn multiple_boxes_persist_and_recover_without_lock_errors() {
     {
         let options = BoxliteOptions {
             home_dir: home_dir.clone(),
+            ..Default::default()
         };
         let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime");
 
@@ -638,7 +1177,10 @@ async fn multiple_boxes_persist_and_recover_without_lock_errors() {
     // Create new runtime with same home directory (simulates restart)
     // This should successfully recover all boxes without lock allocation errors
     {
-        let options = BoxliteOptions { home_dir };
+        let options = BoxliteOptions {
+            home_dir,
+            ..Default::default()
+        };
         let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime after restart");
 
         // All boxes should be recovered from database
@@ -670,3 +1212,61 @@ async fn multiple_boxes_persist_and_recover_without_lock_errors() {
         }
     }
 }
+
+#[tokio::test]
+async fn expired_boxes_are_reaped_on_runtime_restart() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let home_dir = temp_dir.path().to_path_buf();
+
+    let (expiring_id, kept_id): (BoxID, BoxID);
+
+    {
+        let options = BoxliteOptions {
+            home_dir: home_dir.clone(),
+            ..Default::default()
+        };
+        let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime");
+        let expiring = runtime
+            .create(
+                BoxOptions {
+                    rootfs: RootfsSpec::Image("alpine:latest".into()),
+                    ttl_secs: Some(1),
+                    ..Default::default()
+                },
+                None,
+            )
+            .unwrap();
+        let kept = runtime
+            .create(
+                BoxOptions {
+                    rootfs: RootfsSpec::Image("alpine:latest".into()),
+                    ttl_secs: Some(3600),
+                    ..Default::default()
+                },
+                None,
+            )
+            .unwrap();
+        expiring_id = expiring.id().clone();
+        kept_id = kept.id().clone();
+        expiring.stop().await.unwrap();
+        kept.stop().await.unwrap();
+
+        // Not expired yet
+        assert!(runtime.reap().unwrap().is_empty());
+    }
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(1100)).await;
+
+    {
+        let options = BoxliteOptions {
+            home_dir,
+            ..Default::default()
+        };
+        let runtime = BoxliteRuntime::new(options).expect("Failed to create runtime");
+
+        assert!(!runtime.exists(expiring_id.as_str()).unwrap());
+        assert!(runtime.exists(kept_id.as_str()).unwrap());
+
+        runtime.remove(kept_id.as_str(), false).await.unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! x86_64 binary emulation through binfmt_misc
+//!
+//! The host shares an x86_64 interpreter (Rosetta or a static qemu-user)
+//! with the guest. Registering it with binfmt_misc makes the kernel run
+//! every x86_64 ELF through it, inside the container too: with the `F`
+//! flag the kernel opens the interpreter once, at registration, so it need
+//! not be visible in the container's mount namespace.
+
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use nix::mount::{mount, MsFlags};
+
+const BINFMT_MISC: &str = "/proc/sys/fs/binfmt_misc";
+
+/// Name of the binfmt_misc entry.
+const ENTRY: &str = "x86_64";
+
+/// ELF header of an x86_64 executable: class 64, little endian, machine
+/// 0x3e. The kernel decodes the `\x` escapes.
+const ELF_X86_64_MAGIC: &str =
+    r"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x3e\x00";
+
+/// Ignores the OS ABI byte and accepts both ET_EXEC and ET_DYN.
+const ELF_X86_64_MASK: &str =
+    r"\xff\xff\xff\xff\xff\xfe\xfe\x00\xff\xff\xff\xff\xff\xff\xff\xff\xfe\xff\xff\xff";
+
+/// Register `interpreter` for x86_64 ELF binaries.
+pub fn register_x86_64(interpreter: &str, flags: &str) -> BoxliteResult<()> {
+    if !Path::new(interpreter).is_file() {
+        return Err(BoxliteError::Internal(format!(
+            "x86_64 interpreter {} not found",
+            interpreter
+        )));
+    }
+
+    let register = Path::new(BINFMT_MISC).join("register");
+    if !register.exists() {
+        mount(
+            Some("binfmt_misc"),
+            BINFMT_MISC,
+            Some("binfmt_misc"),
+            MsFlags::empty(),
+            None::<&str>,
+        )
+        .map_err(|e| BoxliteError::Internal(format!("Failed to mount binfmt_misc: {}", e)))?;
+    }
+    if Path::new(BINFMT_MISC).join(ENTRY).exists() {
+        tracing::debug!("binfmt_misc entry {} already registered", ENTRY);
+        return Ok(());
+    }
+
+    std::fs::write(&register, registration(interpreter, flags)).map_err(|e| {
+        BoxliteError::Internal(format!(
+            "Failed to register {} with binfmt_misc: {}",
+            interpreter, e
+        ))
+    })?;
+    tracing::info!(interpreter, flags, "Registered x86_64 emulation");
+    Ok(())
+}
+
+/// The `:name:type:offset:magic:mask:interpreter:flags` line.
+fn registration(interpreter: &str, flags: &str) -> String {
+    format!(
+        ":{}:M::{}:{}:{}:{}",
+        ENTRY, ELF_X86_64_MAGIC, ELF_X86_64_MASK, interpreter, flags
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration() {
+        let line = registration("/run/boxlite/x86/rosetta", "CF");
+        assert!(line.starts_with(r":x86_64:M::\x7fELF\x02"));
+        assert!(line.ends_with(":/run/boxlite/x86/rosetta:CF"));
+        assert_eq!(line.split(':').count(), 8);
+    }
+}
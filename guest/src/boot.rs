@@ -0,0 +1,220 @@
+//! Concurrent boot stages
+//!
+//! Guest startup and Guest.Init are split into named stages with explicit
+//! dependencies. Each stage starts as soon as everything it depends on has
+//! finished, so independent work (mounting volumes, bringing up the network,
+//! loading drivers) overlaps instead of running back to back. Every stage is
+//! timed, and the timings are reported to the host.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+type StageFuture = Pin<Box<dyn Future<Output = BoxliteResult<()>> + Send>>;
+
+struct Stage {
+    name: &'static str,
+    deps: &'static [&'static str],
+    run: StageFuture,
+}
+
+/// Time taken by a completed stage.
+#[derive(Clone, Debug)]
+pub struct StageTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+/// A stage that failed, aborting the remaining stages.
+#[derive(Debug)]
+pub struct StageFailure {
+    pub stage: &'static str,
+    pub error: BoxliteError,
+}
+
+impl fmt::Display for StageFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.stage, self.error)
+    }
+}
+
+/// Dependency graph of boot stages.
+#[derive(Default)]
+pub struct BootGraph {
+    stages: Vec<Stage>,
+}
+
+impl BootGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an async stage that runs after `deps` complete.
+    pub fn stage<F>(mut self, name: &'static str, deps: &'static [&'static str], run: F) -> Self
+    where
+        F: Future<Output = BoxliteResult<()>> + Send + 'static,
+    {
+        self.stages.push(Stage {
+            name,
+            deps,
+            run: Box::pin(run),
+        });
+        self
+    }
+
+    /// Add a blocking stage (syscall-heavy work) run on the blocking pool.
+    pub fn blocking_stage<F>(
+        self,
+        name: &'static str,
+        deps: &'static [&'static str],
+        run: F,
+    ) -> Self
+    where
+        F: FnOnce() -> BoxliteResult<()> + Send + 'static,
+    {
+        self.stage(name, deps, async move {
+            tokio::task::spawn_blocking(run)
+                .await
+                .map_err(|e| BoxliteError::Internal(format!("Stage panicked: {}", e)))?
+        })
+    }
+
+    /// Run all stages, returning their timings in completion order.
+    ///
+    /// The first failure aborts stages still running.
+    pub async fn run(self) -> Result<Vec<StageTiming>, StageFailure> {
+        let names: HashSet<&str> = self.stages.iter().map(|s| s.name).collect();
+        for stage in &self.stages {
+            if let Some(dep) = stage.deps.iter().find(|d| !names.contains(*d)) {
+                return Err(StageFailure {
+                    stage: stage.name,
+                    error: BoxliteError::Internal(format!("Unknown dependency '{}'", dep)),
+                });
+            }
+        }
+
+        let mut pending = self.stages;
+        let mut done: HashSet<&'static str> = HashSet::new();
+        let mut running = JoinSet::new();
+        let mut timings = Vec::with_capacity(pending.len());
+
+        loop {
+            let (ready, blocked): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|s| s.deps.iter().all(|d| done.contains(d)));
+            pending = blocked;
+
+            for stage in ready {
+                running.spawn(async move {
+                    let start = Instant::now();
+                    let result = stage.run.await;
+                    (stage.name, start.elapsed(), result)
+                });
+            }
+
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let (name, duration, result) = joined.map_err(|e| StageFailure {
+                stage: "unknown",
+                error: BoxliteError::Internal(format!("Stage task failed: {}", e)),
+            })?;
+            result.map_err(|error| StageFailure { stage: name, error })?;
+
+            tracing::debug!(
+                stage = name,
+                duration_ms = duration.as_millis(),
+                "Boot stage done"
+            );
+            done.insert(name);
+            timings.push(StageTiming { name, duration });
+        }
+
+        if let Some(stage) = pending.first() {
+            return Err(StageFailure {
+                stage: stage.name,
+                error: BoxliteError::Internal("Dependency cycle".to_string()),
+            });
+        }
+
+        Ok(timings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_stages_respect_dependencies() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let order = order.clone();
+            async move {
+                order.lock().unwrap().push(name);
+                Ok(())
+            }
+        };
+
+        let timings = BootGraph::new()
+            .stage("c", &["a", "b"], record("c"))
+            .stage("a", &[], record("a"))
+            .stage("b", &["a"], record("b"))
+            .run()
+            .await
+            .unwrap();
+
+        assert_eq!(*order.lock().unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(timings.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_independent_stages_overlap() {
+        let sleep = || async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(())
+        };
+
+        let start = Instant::now();
+        BootGraph::new()
+            .stage("a", &[], sleep())
+            .stage("b", &[], sleep())
+            .stage("c", &[], sleep())
+            .run()
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(550));
+    }
+
+    #[tokio::test]
+    async fn test_failure_and_invalid_graphs() {
+        let failure = BootGraph::new()
+            .blocking_stage("a", &[], || Err(BoxliteError::Internal("boom".into())))
+            .stage("b", &["a"], async { Ok(()) })
+            .run()
+            .await
+            .unwrap_err();
+        assert_eq!(failure.stage, "a");
+
+        let failure = BootGraph::new()
+            .stage("a", &["missing"], async { Ok(()) })
+            .run()
+            .await
+            .unwrap_err();
+        assert_eq!(failure.stage, "a");
+
+        let failure = BootGraph::new()
+            .stage("a", &["b"], async { Ok(()) })
+            .stage("b", &["a"], async { Ok(()) })
+            .run()
+            .await
+            .unwrap_err();
+        assert!(failure.error.to_string().contains("cycle"));
+    }
+}
@@ -0,0 +1,705 @@
+//! Bulk transfer server
+//!
+//! Serves the raw file transfer channel described in `boxlite_shared::bulk`.
+//! Each connection carries one transfer and is handled on the blocking pool,
+//! where payloads move between the socket and the file with `std::io::copy`
+//! (splice/sendfile on Linux) rather than through gRPC messages.
+//!
+//! Payloads move a chunk at a time, and each chunk waits for one of
+//! [`TRANSFER_SLOTS`] slots. The slots are handed out first come first
+//! served, so concurrent transfers take turns and a large one cannot
+//! starve the rest or tie up every blocking thread with its I/O.
+
+use crate::coredump;
+use crate::layout::GuestLayout;
+use crate::upgrade;
+use boxlite_shared::bulk::{read_header, write_header, BulkRequest, BulkResponse};
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::Transport;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, openat2, FcntlArg, OFlag, OpenHow, ResolveFlag};
+use nix::sys::stat::{mkdirat, Mode};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+/// Transfers moving data at the same time.
+const TRANSFER_SLOTS: usize = 4;
+
+/// Bytes a transfer moves per turn.
+const TRANSFER_CHUNK: u64 = 1024 * 1024;
+
+/// Accept bulk transfer connections until the listener fails.
+pub async fn serve(listen_uri: String, layout: GuestLayout) -> BoxliteResult<()> {
+    let transport = Transport::from_uri(&listen_uri)
+        .map_err(|e| BoxliteError::Internal(format!("Invalid bulk URI '{}': {}", listen_uri, e)))?;
+    let slots = Arc::new(Semaphore::new(TRANSFER_SLOTS));
+
+    match transport {
+        Transport::Vsock { port } => {
+            use tokio_vsock::{VsockAddr, VsockListener, VMADDR_CID_ANY};
+
+            let listener = VsockListener::bind(VsockAddr::new(VMADDR_CID_ANY, port))
+                .map_err(|e| BoxliteError::Internal(format!("Failed to bind vsock: {}", e)))?;
+            info!("Bulk transfers on vsock://{}:{}", VMADDR_CID_ANY, port);
+            loop {
+                let (stream, _) = listener.accept().await?;
+                // SAFETY: into_raw_fd hands over ownership of the socket
+                let fd = unsafe { OwnedFd::from_raw_fd(stream.into_raw_fd()) };
+                spawn_transfer(fd, layout.clone(), Arc::clone(&slots));
+            }
+        }
+        Transport::Unix { socket_path } => {
+            if socket_path.exists() {
+                std::fs::remove_file(&socket_path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(&socket_path)?;
+            info!("Bulk transfers on unix://{}", socket_path.display());
+            loop {
+                let (stream, _) = listener.accept().await?;
+                spawn_transfer(
+                    OwnedFd::from(stream.into_std()?),
+                    layout.clone(),
+                    Arc::clone(&slots),
+                );
+            }
+        }
+        Transport::Tcp { .. } => Err(BoxliteError::Unsupported(
+            "Bulk transfers over TCP are not supported".to_string(),
+        )),
+    }
+}
+
+fn spawn_transfer(fd: OwnedFd, layout: GuestLayout, slots: Arc<Semaphore>) {
+    tokio::task::spawn_blocking(move || {
+        // The socket came from tokio in non-blocking mode
+        let blocking = fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)
+            .map(OFlag::from_bits_truncate)
+            .and_then(|flags| {
+                fcntl(
+                    fd.as_raw_fd(),
+                    FcntlArg::F_SETFL(flags & !OFlag::O_NONBLOCK),
+                )
+            });
+        if let Err(e) = blocking {
+            warn!("Failed to make bulk socket blocking: {}", e);
+            return;
+        }
+        if let Err(e) = handle(File::from(fd), &layout, &slots) {
+            warn!("Bulk transfer failed: {}", e);
+        }
+    });
+}
+
+fn handle(mut conn: File, layout: &GuestLayout, slots: &Semaphore) -> io::Result<()> {
+    match read_header::<BulkRequest>(&mut conn)? {
+        BulkRequest::Write {
+            container_id,
+            path,
+            mode,
+            size,
+        } => {
+            let target = resolve(layout, &container_id, &path);
+            let response =
+                match target.and_then(|t| receive_file(&mut conn, &t.path(), mode, size, slots)) {
+                    Ok(written) => BulkResponse::Ok { size: written },
+                    Err(e) => BulkResponse::Error {
+                        reason: format!("Failed to write {}: {}", path, e),
+                    },
+                };
+            write_header(&mut conn, &response)
+        }
+        BulkRequest::Drop {
+            container_id,
+            name,
+            mode,
+            size,
+        } => {
+            let target = resolve_drop(layout, &container_id, &name);
+            let response = match target.and_then(|t| receive_file(&mut conn, &t, mode, size, slots))
+            {
+                Ok(written) => BulkResponse::Ok { size: written },
+                Err(e) => BulkResponse::Error {
+                    reason: format!("Failed to drop {}: {}", name, e),
+                },
+            };
+            write_header(&mut conn, &response)
+        }
+        BulkRequest::Read { container_id, path } => {
+            let opened = resolve(layout, &container_id, &path).and_then(|p| {
+                let file = p.open()?;
+                let size = file.metadata()?.len();
+                Ok((file, size))
+            });
+            match opened {
+                Ok((mut file, size)) => {
+                    write_header(&mut conn, &BulkResponse::Ok { size })?;
+                    let sent = fair_copy(&mut file, &mut conn, size, slots)?;
+                    if sent < size {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!("{} shrank while sending", path),
+                        ));
+                    }
+                    Ok(())
+                }
+                Err(e) => write_header(
+                    &mut conn,
+                    &BulkResponse::Error {
+                        reason: format!("Failed to read {}: {}", path, e),
+                    },
+                ),
+            }
+        }
+        BulkRequest::Remove { container_id, path } => {
+            let response =
+                match resolve(layout, &container_id, &path).and_then(|t| remove(&t.path())) {
+                    Ok(()) => BulkResponse::Ok { size: 0 },
+                    Err(e) => BulkResponse::Error {
+                        reason: format!("Failed to remove {}: {}", path, e),
+                    },
+                };
+            write_header(&mut conn, &response)
+        }
+        BulkRequest::MakeDir {
+            container_id,
+            path,
+            mode,
+        } => {
+            let response = match make_dirs(layout, &container_id, &path, mode) {
+                Ok(()) => BulkResponse::Ok { size: 0 },
+                Err(e) => BulkResponse::Error {
+                    reason: format!("Failed to create {}: {}", path, e),
+                },
+            };
+            write_header(&mut conn, &response)
+        }
+        BulkRequest::StageAgent { size } => {
+            let response = match stage_agent(&mut conn, layout, size, slots) {
+                Ok(written) => BulkResponse::Ok { size: written },
+                Err(e) => BulkResponse::Error {
+                    reason: format!("Failed to stage agent: {}", e),
+                },
+            };
+            write_header(&mut conn, &response)
+        }
+        BulkRequest::Archive {
+            container_id,
+            paths,
+        } => match pack(layout, &container_id, &paths) {
+            Ok((mut file, size)) => {
+                write_header(&mut conn, &BulkResponse::Ok { size })?;
+                fair_copy(&mut file, &mut conn, size, slots)?;
+                Ok(())
+            }
+            Err(e) => write_header(
+                &mut conn,
+                &BulkResponse::Error {
+                    reason: format!("Failed to archive {}: {}", paths.join(", "), e),
+                },
+            ),
+        },
+        BulkRequest::CrashDump { name } => match coredump::pack(layout, &name) {
+            Ok((mut file, size)) => {
+                write_header(&mut conn, &BulkResponse::Ok { size })?;
+                if fair_copy(&mut file, &mut conn, size, slots)? == size {
+                    coredump::remove(layout, &name);
+                }
+                Ok(())
+            }
+            Err(e) => write_header(
+                &mut conn,
+                &BulkResponse::Error {
+                    reason: format!("Failed to pack crash dump {}: {}", name, e),
+                },
+            ),
+        },
+    }
+}
+
+/// Pack container `paths` into a zstd-compressed tar, returning the
+/// archive rewound and its size.
+///
+/// The archive is staged in the container's /tmp, which is on its disk
+/// rather than in guest memory, and unlinked right away. /tmp is opened
+/// inside the rootfs like any container path, so a symlink there cannot
+/// send the archive out of it. Symlinks are stored as links, never followed
+/// out of the rootfs.
+fn pack(layout: &GuestLayout, container_id: &str, paths: &[String]) -> io::Result<(File, u64)> {
+    let container_tmp = container_tmp(layout, container_id);
+    let staging_dir = match &container_tmp {
+        Some(tmp) => PathBuf::from(format!("/proc/self/fd/{}", tmp.as_raw_fd())),
+        None => std::env::temp_dir(),
+    };
+    stage_archive(&staging_dir, |builder| {
+        for path in paths {
+            let resolved = resolve(layout, container_id, path)?;
+            let source = resolved.path();
+            let name = path.trim_start_matches('/');
+            match std::fs::symlink_metadata(&source) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+                Ok(metadata) if metadata.is_dir() => builder.append_dir_all(name, &source)?,
+                Ok(_) => builder.append_path_with_name(&source, name)?,
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The container's /tmp, opened inside its rootfs; None if it has none.
+fn container_tmp(layout: &GuestLayout, container_id: &str) -> Option<OwnedFd> {
+    let root = open_rootfs(layout, container_id).ok()?;
+    open_in_root(&root, Path::new("tmp"), OFlag::O_PATH | OFlag::O_DIRECTORY).ok()
+}
+
+/// Build a zstd-compressed tar in an unlinked file in `staging_dir`,
+/// returning it rewound and its size. Symlinks are stored as links.
+pub(crate) fn stage_archive(
+    staging_dir: &Path,
+    fill: impl FnOnce(&mut tar::Builder<zstd::Encoder<'static, File>>) -> io::Result<()>,
+) -> io::Result<(File, u64)> {
+    let staging = staging_dir.join(format!(".boxlite-archive-{}", uuid::Uuid::new_v4()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&staging)?;
+    std::fs::remove_file(&staging)?;
+
+    let mut builder = tar::Builder::new(zstd::Encoder::new(file, 0)?);
+    builder.follow_symlinks(false);
+    fill(&mut builder)?;
+    let mut file = builder.into_inner()?.finish()?;
+    let size = file.stream_position()?;
+    file.rewind()?;
+    Ok((file, size))
+}
+
+/// Remove `target`, without following symlinks; directories only when
+/// empty, so nothing the caller has not seen is lost.
+fn remove(target: &Path) -> io::Result<()> {
+    let removed = match std::fs::symlink_metadata(target) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir(target),
+        Ok(_) => std::fs::remove_file(target),
+        Err(e) => Err(e),
+    };
+    match removed {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Receive a new agent binary where Guest.UpgradeAgent looks for it.
+fn stage_agent(
+    conn: &mut File,
+    layout: &GuestLayout,
+    size: u64,
+    slots: &Semaphore,
+) -> io::Result<u64> {
+    let dir = layout.upgrade_dir();
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)?;
+    receive_file(conn, &dir.join(upgrade::STAGED_NAME), 0o755, size, slots)
+}
+
+/// Receive `size` bytes into `target`.
+///
+/// The payload goes to a temporary file next to the target, which replaces
+/// it only once complete: a transfer the host cancels or drops partway
+/// leaves the target as it was rather than truncated.
+fn receive_file(
+    conn: &mut File,
+    target: &Path,
+    mode: u32,
+    size: u64,
+    slots: &Semaphore,
+) -> io::Result<u64> {
+    let name = target
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "target has no file name"))?;
+    let partial = target.with_file_name(format!(
+        ".{}.boxlite-{}",
+        name.to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(&partial)?;
+
+    let received = fair_copy(conn, &mut file, size, slots).and_then(|written| {
+        if written < size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("connection closed after {} of {} bytes", written, size),
+            ));
+        }
+        std::fs::rename(&partial, target)?;
+        Ok(written)
+    });
+    if received.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    received
+}
+
+/// Copy up to `size` bytes, taking a transfer slot for each chunk.
+///
+/// Runs on the blocking pool, which is inside the runtime, so waiting for
+/// a slot can block on the async semaphore.
+fn fair_copy<R: Read, W: io::Write>(
+    reader: &mut R,
+    writer: &mut W,
+    size: u64,
+    slots: &Semaphore,
+) -> io::Result<u64> {
+    let runtime = tokio::runtime::Handle::current();
+    let mut copied = 0;
+    while copied < size {
+        let _slot = runtime
+            .block_on(slots.acquire())
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let chunk = (size - copied).min(TRANSFER_CHUNK);
+        let n = io::copy(&mut reader.take(chunk), writer)?;
+        copied += n;
+        if n < chunk {
+            break;
+        }
+    }
+    Ok(copied)
+}
+
+/// A container path resolved without leaving the container's rootfs.
+///
+/// The directories leading to it are opened with `openat2`
+/// `RESOLVE_IN_ROOT`, so symlinks the container planted in them resolve
+/// as if its rootfs were `/`, never to the agent's own files or another
+/// container's.
+pub(crate) struct Resolved {
+    root: OwnedFd,
+    relative: PathBuf,
+    parent: OwnedFd,
+}
+
+impl Resolved {
+    /// The path through its opened parent directory.
+    ///
+    /// The last component is not resolved yet, so this is only for calls
+    /// that do not follow a symlink there; [`Resolved::open`] reads.
+    pub(crate) fn path(&self) -> PathBuf {
+        let name = self.relative.file_name().unwrap_or_default();
+        PathBuf::from(format!("/proc/self/fd/{}", self.parent.as_raw_fd())).join(name)
+    }
+
+    /// Open for reading, resolving a symlink in the last component inside
+    /// the rootfs too.
+    fn open(&self) -> io::Result<File> {
+        open_in_root(&self.root, &self.relative, OFlag::O_RDONLY).map(File::from)
+    }
+}
+
+/// Resolve an absolute container path inside the container's rootfs.
+pub(crate) fn resolve(
+    layout: &GuestLayout,
+    container_id: &str,
+    path: &str,
+) -> io::Result<Resolved> {
+    let root = open_rootfs(layout, container_id)?;
+    let relative = container_relative(path)?.to_path_buf();
+    let parent = match relative.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent = open_in_root(&root, parent, OFlag::O_PATH | OFlag::O_DIRECTORY)?;
+    Ok(Resolved {
+        root,
+        relative,
+        parent,
+    })
+}
+
+/// Create a container directory and any missing parents, each resolved
+/// inside the rootfs.
+fn make_dirs(layout: &GuestLayout, container_id: &str, path: &str, mode: u32) -> io::Result<()> {
+    let root = open_rootfs(layout, container_id)?;
+    let relative = container_relative(path)?;
+    let mut created = PathBuf::new();
+    for component in relative.components() {
+        let parent = if created.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            created.as_path()
+        };
+        let parent = open_in_root(&root, parent, OFlag::O_PATH | OFlag::O_DIRECTORY)?;
+        match mkdirat(
+            Some(parent.as_raw_fd()),
+            component.as_os_str(),
+            Mode::from_bits_truncate(mode),
+        ) {
+            Ok(()) | Err(Errno::EEXIST) => {}
+            Err(e) => return Err(e.into()),
+        }
+        created.push(component);
+    }
+    // What already existed has to be a directory
+    open_in_root(&root, relative, OFlag::O_PATH | OFlag::O_DIRECTORY).map(drop)
+}
+
+fn open_rootfs(layout: &GuestLayout, container_id: &str) -> io::Result<OwnedFd> {
+    let mut components = Path::new(container_id).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid container ID",
+        ));
+    }
+    let rootfs = layout.container(container_id).rootfs_dir();
+    File::open(&rootfs)
+        .map(OwnedFd::from)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", rootfs.display(), e)))
+}
+
+/// `path` relative to the container root, refusing the root itself and
+/// `..` components.
+fn container_relative(path: &str) -> io::Result<&Path> {
+    let relative = Path::new(path)
+        .strip_prefix("/")
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path must be absolute"))?;
+    if relative.as_os_str().is_empty()
+        || relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path must name a file without '..' components",
+        ));
+    }
+    Ok(relative)
+}
+
+/// Open `path` under `root` as if `root` were `/`.
+fn open_in_root(root: &OwnedFd, path: &Path, flags: OFlag) -> io::Result<OwnedFd> {
+    let how = OpenHow::new()
+        .flags(flags | OFlag::O_CLOEXEC)
+        .resolve(ResolveFlag::RESOLVE_IN_ROOT | ResolveFlag::RESOLVE_NO_MAGICLINKS);
+    let fd = openat2(root.as_raw_fd(), path, how)?;
+    // SAFETY: openat2 returned a new descriptor nothing else owns
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Map a dropped file name to its location in the drop directory.
+fn resolve_drop(layout: &GuestLayout, container_id: &str, name: &str) -> io::Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "name must be a plain file name",
+        ));
+    }
+    let drops_dir = layout.container(container_id).drops_dir();
+    if !drops_dir.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "drops are not enabled for this box",
+        ));
+    }
+    Ok(drops_dir.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fair_copy_shares_slots() {
+        let slots = Arc::new(Semaphore::new(1));
+        let data: Vec<u8> = (0..3 * TRANSFER_CHUNK + 7).map(|i| i as u8).collect();
+
+        let copies: Vec<_> = (0..3)
+            .map(|_| {
+                let slots = Arc::clone(&slots);
+                let data = data.clone();
+                tokio::task::spawn_blocking(move || {
+                    let mut out = Vec::new();
+                    let copied =
+                        fair_copy(&mut data.as_slice(), &mut out, u64::MAX, &slots).unwrap();
+                    (copied, out)
+                })
+            })
+            .collect();
+        for copy in copies {
+            let (copied, out) = copy.await.unwrap();
+            assert_eq!(copied, data.len() as u64);
+            assert_eq!(out, data);
+        }
+        assert_eq!(slots.available_permits(), 1);
+
+        // Stops at the requested size
+        let mut out = Vec::new();
+        let slots2 = Arc::clone(&slots);
+        let copied = tokio::task::spawn_blocking(move || {
+            fair_copy(&mut &b"abcdef"[..], &mut out, 4, &slots2).map(|n| (n, out))
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(copied, (4, b"abcd".to_vec()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_receive_file_keeps_target_on_short_transfer() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("data.bin");
+        std::fs::write(&target, b"original").unwrap();
+        let payload = dir.path().join("payload");
+        std::fs::write(&payload, b"new contents").unwrap();
+
+        let slots = Arc::new(Semaphore::new(1));
+        let (target2, payload2, slots2) = (target.clone(), payload.clone(), Arc::clone(&slots));
+        let short = tokio::task::spawn_blocking(move || {
+            receive_file(&mut File::open(&payload2)?, &target2, 0o644, 100, &slots2)
+        })
+        .await
+        .unwrap();
+        assert_eq!(short.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(std::fs::read(&target).unwrap(), b"original");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        let (target2, payload2) = (target.clone(), payload.clone());
+        let received = tokio::task::spawn_blocking(move || {
+            receive_file(&mut File::open(&payload2)?, &target2, 0o644, 12, &slots)
+        })
+        .await
+        .unwrap();
+        assert_eq!(received.unwrap(), 12);
+        assert_eq!(std::fs::read(&target).unwrap(), b"new contents");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_resolve_rejects_escapes() {
+        let base = tempfile::tempdir().unwrap();
+        let layout = GuestLayout::with_base(base.path());
+        let rootfs = layout.container("c1").rootfs_dir();
+        std::fs::create_dir_all(rootfs.join("etc")).unwrap();
+        std::fs::write(rootfs.join("etc/hosts"), "127.0.0.1 box").unwrap();
+        std::fs::write(base.path().join("secret"), "agent only").unwrap();
+        // Links the container planted to reach the agent's files
+        std::os::unix::fs::symlink("/", rootfs.join("up")).unwrap();
+        std::os::unix::fs::symlink("../../../../secret", rootfs.join("leak")).unwrap();
+
+        let hosts = resolve(&layout, "c1", "/etc/hosts").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(hosts.path()).unwrap(),
+            "127.0.0.1 box"
+        );
+        let mut content = String::new();
+        hosts.open().unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "127.0.0.1 box");
+
+        // Links resolve inside the rootfs
+        let through = resolve(&layout, "c1", "/up/etc/hosts").unwrap();
+        assert!(through.open().is_ok());
+        assert!(resolve(&layout, "c1", "/up/secret")
+            .unwrap()
+            .open()
+            .is_err());
+        assert!(resolve(&layout, "c1", "/leak").unwrap().open().is_err());
+        make_dirs(&layout, "c1", "/up/new/dir", 0o755).unwrap();
+        assert!(rootfs.join("new/dir").is_dir());
+        assert!(!base.path().join("new").exists());
+
+        assert!(resolve(&layout, "c1", "etc/hosts").is_err());
+        assert!(resolve(&layout, "c1", "/").is_err());
+        assert!(resolve(&layout, "c1", "/tmp/../../../etc/shadow").is_err());
+        assert!(resolve(&layout, "../c1", "/etc/hosts").is_err());
+    }
+
+    #[test]
+    fn test_pack_archives_existing_paths() {
+        let base = tempfile::tempdir().unwrap();
+        let layout = GuestLayout::with_base(base.path());
+        let rootfs = layout.container("c1").rootfs_dir();
+        std::fs::create_dir_all(rootfs.join("out/logs")).unwrap();
+        std::fs::write(rootfs.join("out/report.xml"), b"<ok/>").unwrap();
+        std::fs::write(rootfs.join("out/logs/run.log"), b"done").unwrap();
+
+        let paths = vec!["/out".to_string(), "/missing.txt".to_string()];
+        let (file, size) = pack(&layout, "c1", &paths).unwrap();
+        assert!(size > 0);
+
+        let mut archive = tar::Archive::new(zstd::Decoder::new(file).unwrap());
+        let mut files: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|e| e.header().entry_type().is_file())
+            .map(|e| e.path().unwrap().display().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["out/logs/run.log", "out/report.xml"]);
+    }
+
+    #[test]
+    fn test_pack_stages_in_container_tmp() {
+        use std::os::unix::fs::MetadataExt;
+
+        let base = tempfile::tempdir().unwrap();
+        let layout = GuestLayout::with_base(base.path());
+        let rootfs = layout.container("c1").rootfs_dir();
+        std::fs::create_dir_all(rootfs.join("scratch")).unwrap();
+        let staged_in = |tmp: OwnedFd| {
+            std::fs::metadata(format!("/proc/self/fd/{}", tmp.as_raw_fd()))
+                .unwrap()
+                .ino()
+        };
+        let ino = |path: &Path| std::fs::metadata(path).unwrap().ino();
+
+        assert!(container_tmp(&layout, "c1").is_none());
+
+        // A /tmp link the container planted resolves inside its rootfs
+        std::os::unix::fs::symlink("/scratch", rootfs.join("tmp")).unwrap();
+        let tmp = container_tmp(&layout, "c1").unwrap();
+        assert_eq!(staged_in(tmp), ino(&rootfs.join("scratch")));
+
+        std::fs::remove_file(rootfs.join("tmp")).unwrap();
+        std::os::unix::fs::symlink("../../../..", rootfs.join("tmp")).unwrap();
+        let tmp = container_tmp(&layout, "c1").unwrap();
+        assert_eq!(staged_in(tmp), ino(&rootfs));
+        assert!(pack(&layout, "c1", &["/scratch".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_drop() {
+        let base = tempfile::tempdir().unwrap();
+        let layout = GuestLayout::with_base(base.path());
+
+        // Disabled until the drop directory exists
+        assert!(resolve_drop(&layout, "c1", "report.pdf").is_err());
+
+        std::fs::create_dir_all(layout.container("c1").drops_dir()).unwrap();
+        assert_eq!(
+            resolve_drop(&layout, "c1", "report.pdf").unwrap(),
+            layout.container("c1").drops_dir().join("report.pdf")
+        );
+        assert!(resolve_drop(&layout, "c1", "../escape").is_err());
+        assert!(resolve_drop(&layout, "c1", "a/b").is_err());
+    }
+}
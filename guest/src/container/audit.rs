@@ -0,0 +1,700 @@
+//! Syscall auditing for execs
+//!
+//! An audited exec starts as `boxlite-audit` (the agent binary, mounted
+//! read-only into every container next to `boxlite-netns`), which installs
+//! a seccomp filter that hands the interesting syscalls to a user-space
+//! listener and then execs the command:
+//!
+//! ```text
+//! /run/boxlite-audit PROGRAM [ARG...]
+//! ```
+//!
+//! The filter is inherited across fork and exec, so it covers everything
+//! the command starts. The helper's own execve is the first call it
+//! stops, and stays stopped until the agent has copied the listener out of
+//! the helper with pidfd_getfd; the listener is close-on-exec, so the
+//! command never holds it. From then on an agent thread records each
+//! stopped call (the program run, the path opened, the address connected
+//! to) and lets it continue unchanged. It exits once the last process
+//! under the filter is gone.
+//!
+//! The report is advisory, not a security boundary: arguments are read
+//! from the process's memory while the call is stopped, so another thread
+//! of the same program can change them before the kernel reads them. Calls
+//! through the 32-bit compat ABI are not traced. Every traced call costs a
+//! round trip to the agent, which slows programs that open many files.
+//!
+//! Run as a non-root user, the helper has to set no_new_privs to install
+//! the filter, so setuid programs run without their privileges.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::AuditReport;
+use nix::unistd::Pid;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where the helper is mounted in the container.
+pub const CONTAINER_AUDIT_HELPER: &str = "/run/boxlite-audit";
+
+/// Name the agent binary answers to as the audit helper.
+pub const AUDIT_HELPER_NAME: &str = "boxlite-audit";
+
+const USAGE: &str = "usage: boxlite-audit COMMAND [ARG...]";
+
+/// Longest wait for the helper to install its filter.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Distinct entries kept per list; later ones are only counted.
+const MAX_ENTRIES: usize = 512;
+
+/// Longest path read from a process.
+const PATH_MAX: usize = 4096;
+
+/// How `/proc/<pid>/fd` shows a seccomp listener.
+const LISTENER_LINK: &str = "anon_inode:seccomp notify";
+
+// Classic BPF opcodes (linux/filter.h, linux/bpf_common.h)
+const BPF_LD: u16 = 0x00;
+const BPF_W: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_JMP: u16 = 0x05;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGE: u16 = 0x30;
+const BPF_K: u16 = 0x00;
+const BPF_RET: u16 = 0x06;
+
+// Offsets into struct seccomp_data
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// x32 syscalls share the x86_64 audit arch.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+// Listener ioctls (linux/seccomp.h), _IOWR('!', n, size) and _IOW('!', 2, u64)
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xC050_2100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xC018_2101;
+const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 0x4008_2102;
+
+/// Syscalls the filter stops, by name.
+const TRACED: &[(libc::c_long, &str)] = &[
+    (libc::SYS_execve, "execve"),
+    (libc::SYS_execveat, "execveat"),
+    (libc::SYS_openat, "openat"),
+    (libc::SYS_openat2, "openat2"),
+    (libc::SYS_unlinkat, "unlinkat"),
+    (libc::SYS_renameat, "renameat"),
+    (libc::SYS_renameat2, "renameat2"),
+    (libc::SYS_connect, "connect"),
+    (libc::SYS_bind, "bind"),
+    (libc::SYS_ptrace, "ptrace"),
+    (libc::SYS_mount, "mount"),
+    (libc::SYS_unshare, "unshare"),
+    (libc::SYS_setns, "setns"),
+    (libc::SYS_bpf, "bpf"),
+    (libc::SYS_init_module, "init_module"),
+    (libc::SYS_finit_module, "finit_module"),
+];
+
+/// Legacy path calls only x86_64 still has.
+#[cfg(target_arch = "x86_64")]
+const ARCH_TRACED: &[(libc::c_long, &str)] = &[
+    (libc::SYS_open, "open"),
+    (libc::SYS_creat, "creat"),
+    (libc::SYS_unlink, "unlink"),
+    (libc::SYS_rmdir, "rmdir"),
+    (libc::SYS_rename, "rename"),
+];
+#[cfg(not(target_arch = "x86_64"))]
+const ARCH_TRACED: &[(libc::c_long, &str)] = &[];
+
+/// Program and arguments that run `program` through the helper.
+pub fn wrap(program: &str, args: &[String]) -> (String, Vec<String>) {
+    let mut wrapped = vec![program.to_string()];
+    wrapped.extend_from_slice(args);
+    (CONTAINER_AUDIT_HELPER.to_string(), wrapped)
+}
+
+/// Helper entry point (`boxlite-audit` arguments after the program name).
+///
+/// Only returns on failure: 2 for bad usage, 1 if the filter could not be
+/// installed, 127 if the command could not be executed.
+pub fn run_audit_helper(args: &[String]) -> i32 {
+    let [program, program_args @ ..] = args else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    if let Err(e) = install(&filter()) {
+        eprintln!("boxlite-audit: failed to install the audit filter: {}", e);
+        return 1;
+    }
+    let e = std::process::Command::new(program)
+        .args(program_args)
+        .exec();
+    eprintln!("boxlite-audit: {}: {}", program, e);
+    127
+}
+
+fn stmt(code: u16, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Filter that stops the traced syscalls and allows everything else.
+fn filter() -> Vec<libc::sock_filter> {
+    let allow = stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW);
+    let mut program = vec![
+        stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARCH),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+        allow,
+        stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR),
+    ];
+
+    #[cfg(target_arch = "x86_64")]
+    program.extend([
+        jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+        allow,
+    ]);
+
+    for &(nr, _) in TRACED.iter().chain(ARCH_TRACED) {
+        program.extend([
+            jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1),
+            stmt(BPF_RET | BPF_K, libc::SECCOMP_RET_USER_NOTIF),
+        ]);
+    }
+
+    program.push(allow);
+    program
+}
+
+/// Install `program` with a listener, setting no_new_privs if the caller
+/// lacks CAP_SYS_ADMIN. The listener is left open, close-on-exec.
+fn install(program: &[libc::sock_filter]) -> io::Result<()> {
+    let prog = libc::sock_fprog {
+        len: program.len() as libc::c_ushort,
+        filter: program.as_ptr() as *mut libc::sock_filter,
+    };
+    let seccomp = || {
+        // SAFETY: seccomp with a pointer to a filter program that outlives
+        // the call
+        unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &prog as *const libc::sock_fprog,
+            )
+        }
+    };
+
+    let mut ret = seccomp();
+    if ret < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EACCES) {
+        // SAFETY: prctl with integer arguments
+        if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        ret = seccomp();
+    }
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Activity recorded for one audited exec.
+#[derive(Clone)]
+pub struct Audit {
+    summary: Arc<Mutex<Summary>>,
+}
+
+impl Audit {
+    /// What the exec has done so far.
+    pub fn report(&self) -> AuditReport {
+        self.summary.lock().unwrap().report()
+    }
+}
+
+/// Take over the listener of the helper running as `pid` and start
+/// recording what it and its descendants do.
+pub async fn attach(pid: Pid) -> BoxliteResult<Audit> {
+    let fd = wait_for_listener(pid).await?;
+    let listener = take_fd(pid, fd)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to take audit listener: {}", e)))?;
+
+    let summary = Arc::new(Mutex::new(Summary::default()));
+    let recorded = Arc::clone(&summary);
+    std::thread::Builder::new()
+        .name(format!("audit-{}", pid))
+        .spawn(move || supervise(listener, recorded))
+        .map_err(|e| BoxliteError::Internal(format!("Failed to start audit thread: {}", e)))?;
+    Ok(Audit { summary })
+}
+
+/// Number of the helper's listener fd, once it has one.
+async fn wait_for_listener(pid: Pid) -> BoxliteResult<i32> {
+    let fd_dir = PathBuf::from(format!("/proc/{}/fd", pid));
+    let deadline = Instant::now() + ATTACH_TIMEOUT;
+    loop {
+        let entries = std::fs::read_dir(&fd_dir).map_err(|e| {
+            BoxliteError::Internal(format!("Process exited before auditing started: {}", e))
+        })?;
+        let listener = entries.flatten().find_map(|entry| {
+            let target = std::fs::read_link(entry.path()).ok()?;
+            (target.as_os_str() == LISTENER_LINK)
+                .then(|| entry.file_name().to_str()?.parse().ok())
+                .flatten()
+        });
+        if let Some(fd) = listener {
+            return Ok(fd);
+        }
+        if Instant::now() > deadline {
+            return Err(BoxliteError::Internal(
+                "timed out waiting for the audit filter".to_string(),
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Duplicate `fd` out of process `pid`.
+fn take_fd(pid: Pid, fd: i32) -> io::Result<OwnedFd> {
+    // SAFETY: plain syscalls; each returned fd is owned exactly once
+    unsafe {
+        let pidfd = libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0);
+        if pidfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let pidfd = OwnedFd::from_raw_fd(pidfd as i32);
+        let taken = libc::syscall(libc::SYS_pidfd_getfd, pidfd.as_raw_fd(), fd, 0);
+        if taken < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(OwnedFd::from_raw_fd(taken as i32))
+    }
+}
+
+/// Record and continue stopped calls until no process uses the filter.
+fn supervise(listener: OwnedFd, summary: Arc<Mutex<Summary>>) {
+    let fd = listener.as_raw_fd();
+    loop {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: one pollfd, valid for the call
+        if unsafe { libc::poll(&mut pollfd, 1, -1) } < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            break;
+        }
+        if pollfd.revents & libc::POLLIN == 0 {
+            // POLLHUP: the last process under the filter has exited
+            break;
+        }
+
+        // SAFETY: seccomp_notif is plain data the kernel fills in; RECV
+        // requires it zeroed
+        let mut notif: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+        if unsafe {
+            libc::ioctl(
+                fd,
+                SECCOMP_IOCTL_NOTIF_RECV as _,
+                &mut notif as *mut libc::seccomp_notif,
+            )
+        } < 0
+        {
+            // ENOENT: the caller died before we got to it
+            continue;
+        }
+
+        let entries = describe(notif.pid, &notif.data);
+        // SAFETY: the id is a u64 the ioctl only reads
+        let valid = unsafe {
+            libc::ioctl(
+                fd,
+                SECCOMP_IOCTL_NOTIF_ID_VALID as _,
+                &notif.id as *const u64,
+            )
+        } == 0;
+        {
+            let mut summary = summary.lock().unwrap();
+            summary.count(notif.data.nr as libc::c_long);
+            // Arguments read from a pid that has since been reused are junk
+            if valid {
+                for (list, entry) in entries {
+                    summary.add(list, entry);
+                }
+            }
+        }
+
+        let mut resp = libc::seccomp_notif_resp {
+            id: notif.id,
+            val: 0,
+            error: 0,
+            flags: libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32,
+        };
+        // SAFETY: seccomp_notif_resp is plain data the ioctl only reads; a
+        // failure means the caller is gone
+        unsafe {
+            libc::ioctl(
+                fd,
+                SECCOMP_IOCTL_NOTIF_SEND as _,
+                &mut resp as *mut libc::seccomp_notif_resp,
+            )
+        };
+    }
+}
+
+/// Report list an entry goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum List {
+    Executed,
+    Read,
+    Written,
+    Removed,
+    Connections,
+    Listeners,
+}
+
+/// Entries for a stopped call, from the caller's memory.
+fn describe(pid: u32, data: &libc::seccomp_data) -> Vec<(List, String)> {
+    let Ok(mem) = File::open(format!("/proc/{}/mem", pid)) else {
+        return vec![];
+    };
+    let process = Process { pid, mem };
+    let args = data.args;
+    let at_fdcwd = libc::AT_FDCWD as u64;
+    let path = |dirfd: u64, addr: u64| process.path(dirfd as i32, addr);
+    let mut entries = vec![];
+
+    match data.nr as libc::c_long {
+        libc::SYS_execve => entries.extend(path(at_fdcwd, args[0]).map(|p| (List::Executed, p))),
+        libc::SYS_execveat => entries.extend(path(args[0], args[1]).map(|p| (List::Executed, p))),
+        libc::SYS_openat => {
+            entries.extend(path(args[0], args[1]).map(|p| (open_list(args[2]), p)));
+        }
+        libc::SYS_openat2 => {
+            // struct open_how starts with the u64 flags
+            let flags = process
+                .read(args[2], 8)
+                .map(|b| u64::from_ne_bytes(b.try_into().unwrap()));
+            if let Some(flags) = flags {
+                entries.extend(path(args[0], args[1]).map(|p| (open_list(flags), p)));
+            }
+        }
+        libc::SYS_unlinkat => entries.extend(path(args[0], args[1]).map(|p| (List::Removed, p))),
+        libc::SYS_renameat | libc::SYS_renameat2 => {
+            entries.extend(path(args[0], args[1]).map(|p| (List::Removed, p)));
+            entries.extend(path(args[2], args[3]).map(|p| (List::Written, p)));
+        }
+        libc::SYS_connect => entries.extend(
+            process
+                .read(args[1], args[2] as usize)
+                .and_then(|addr| format_sockaddr(&addr))
+                .map(|a| (List::Connections, a)),
+        ),
+        libc::SYS_bind => entries.extend(
+            process
+                .read(args[1], args[2] as usize)
+                .and_then(|addr| format_sockaddr(&addr))
+                .map(|a| (List::Listeners, a)),
+        ),
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_open => entries.extend(path(at_fdcwd, args[0]).map(|p| (open_list(args[1]), p))),
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_creat => entries.extend(path(at_fdcwd, args[0]).map(|p| (List::Written, p))),
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_unlink | libc::SYS_rmdir => {
+            entries.extend(path(at_fdcwd, args[0]).map(|p| (List::Removed, p)));
+        }
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_rename => {
+            entries.extend(path(at_fdcwd, args[0]).map(|p| (List::Removed, p)));
+            entries.extend(path(at_fdcwd, args[1]).map(|p| (List::Written, p)));
+        }
+        _ => {}
+    }
+    entries
+}
+
+/// Whether an open with `flags` reads or writes.
+fn open_list(flags: u64) -> List {
+    let flags = flags as libc::c_int;
+    if flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0 {
+        List::Written
+    } else {
+        List::Read
+    }
+}
+
+/// A stopped caller.
+struct Process {
+    pid: u32,
+    mem: File,
+}
+
+impl Process {
+    /// `len` bytes at `addr`.
+    fn read(&self, addr: u64, len: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0; len.min(PATH_MAX)];
+        self.mem.read_exact_at(&mut buf, addr).ok()?;
+        Some(buf)
+    }
+
+    /// NUL-terminated string at `addr`, read a page at a time so it may
+    /// end just before unmapped memory.
+    fn read_string(&self, addr: u64) -> Option<String> {
+        let mut bytes = Vec::new();
+        let mut at = addr;
+        while bytes.len() < PATH_MAX {
+            let mut page = [0u8; 4096];
+            let len = 4096 - (at % 4096) as usize;
+            let read = self.mem.read_at(&mut page[..len], at).ok()?;
+            if read == 0 {
+                return None;
+            }
+            if let Some(end) = page[..read].iter().position(|&b| b == 0) {
+                bytes.extend_from_slice(&page[..end]);
+                return Some(String::from_utf8_lossy(&bytes).into_owned());
+            }
+            bytes.extend_from_slice(&page[..read]);
+            at += read as u64;
+        }
+        None
+    }
+
+    /// Path argument at `addr`, made absolute against `dirfd` and shown
+    /// as the process sees it.
+    fn path(&self, dirfd: i32, addr: u64) -> Option<String> {
+        let path = self.read_string(addr)?;
+        if path.starts_with('/') {
+            return Some(path);
+        }
+        let base = if dirfd == libc::AT_FDCWD {
+            self.link("cwd")
+        } else {
+            self.link(&format!("fd/{}", dirfd))
+        };
+        let Some(base) = base else {
+            return Some(path);
+        };
+        let joined = if path.is_empty() {
+            base
+        } else {
+            base.join(path)
+        };
+        Some(self.in_root(&joined))
+    }
+
+    fn link(&self, name: &str) -> Option<PathBuf> {
+        std::fs::read_link(format!("/proc/{}/{}", self.pid, name)).ok()
+    }
+
+    /// `path` (as the agent sees it) relative to the process's root.
+    fn in_root(&self, path: &Path) -> String {
+        let root = self.link("root").unwrap_or_else(|| PathBuf::from("/"));
+        match path.strip_prefix(&root) {
+            Ok(rest) => Path::new("/").join(rest).to_string_lossy().into_owned(),
+            Err(_) => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// `host:port` for inet sockets, the path for Unix sockets (`@name` for
+/// abstract ones).
+fn format_sockaddr(addr: &[u8]) -> Option<String> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+    let family = u16::from_ne_bytes(addr.get(..2)?.try_into().ok()?) as libc::c_int;
+    match family {
+        libc::AF_INET => {
+            let port = u16::from_be_bytes(addr.get(2..4)?.try_into().ok()?);
+            let ip: [u8; 4] = addr.get(4..8)?.try_into().ok()?;
+            Some(SocketAddrV4::new(Ipv4Addr::from(ip), port).to_string())
+        }
+        libc::AF_INET6 => {
+            let port = u16::from_be_bytes(addr.get(2..4)?.try_into().ok()?);
+            let ip: [u8; 16] = addr.get(8..24)?.try_into().ok()?;
+            Some(SocketAddrV6::new(Ipv6Addr::from(ip), port, 0, 0).to_string())
+        }
+        libc::AF_UNIX => {
+            let path = addr.get(2..)?;
+            match path.split_first() {
+                Some((&0, name)) => {
+                    let name = name.split(|&b| b == 0).next().unwrap_or(name);
+                    Some(format!("@{}", String::from_utf8_lossy(name)))
+                }
+                Some(_) => {
+                    let path = path.split(|&b| b == 0).next().unwrap_or(path);
+                    Some(String::from_utf8_lossy(path).into_owned())
+                }
+                // Unnamed socket
+                None => None,
+            }
+        }
+        other => Some(format!("family {}", other)),
+    }
+}
+
+/// Running totals for one audited exec.
+#[derive(Default)]
+struct Summary {
+    syscalls: BTreeMap<&'static str, u64>,
+    executed: BTreeSet<String>,
+    files_read: BTreeSet<String>,
+    files_written: BTreeSet<String>,
+    files_removed: BTreeSet<String>,
+    connections: BTreeSet<String>,
+    listeners: BTreeSet<String>,
+    dropped: u64,
+}
+
+impl Summary {
+    fn count(&mut self, nr: libc::c_long) {
+        if let Some(&(_, name)) = TRACED.iter().chain(ARCH_TRACED).find(|(n, _)| *n == nr) {
+            *self.syscalls.entry(name).or_default() += 1;
+        }
+    }
+
+    fn add(&mut self, list: List, entry: String) {
+        let set = match list {
+            List::Executed => &mut self.executed,
+            List::Read => &mut self.files_read,
+            List::Written => &mut self.files_written,
+            List::Removed => &mut self.files_removed,
+            List::Connections => &mut self.connections,
+            List::Listeners => &mut self.listeners,
+        };
+        if set.contains(&entry) {
+            return;
+        }
+        if set.len() >= MAX_ENTRIES {
+            self.dropped += 1;
+            return;
+        }
+        set.insert(entry);
+    }
+
+    fn report(&self) -> AuditReport {
+        let list = |set: &BTreeSet<String>| set.iter().cloned().collect();
+        AuditReport {
+            syscalls: self
+                .syscalls
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect(),
+            executed: list(&self.executed),
+            files_read: list(&self.files_read),
+            files_written: list(&self.files_written),
+            files_removed: list(&self.files_removed),
+            connections: list(&self.connections),
+            listeners: list(&self.listeners),
+            dropped: self.dropped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_shape() {
+        let program = filter();
+        let traced = TRACED.len() + ARCH_TRACED.len();
+        let x32 = if cfg!(target_arch = "x86_64") { 2 } else { 0 };
+        assert_eq!(program.len(), 4 + x32 + traced * 2 + 1);
+        assert_eq!(program[1].k, AUDIT_ARCH);
+        assert_eq!(program.last().unwrap().k, SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn test_format_sockaddr() {
+        let mut inet = vec![0u8; 16];
+        inet[..2].copy_from_slice(&(libc::AF_INET as u16).to_ne_bytes());
+        inet[2..4].copy_from_slice(&443u16.to_be_bytes());
+        inet[4..8].copy_from_slice(&[10, 0, 2, 2]);
+        assert_eq!(format_sockaddr(&inet).unwrap(), "10.0.2.2:443");
+
+        let mut inet6 = vec![0u8; 28];
+        inet6[..2].copy_from_slice(&(libc::AF_INET6 as u16).to_ne_bytes());
+        inet6[2..4].copy_from_slice(&80u16.to_be_bytes());
+        inet6[23] = 1;
+        assert_eq!(format_sockaddr(&inet6).unwrap(), "[::1]:80");
+
+        let mut unix = (libc::AF_UNIX as u16).to_ne_bytes().to_vec();
+        unix.extend_from_slice(b"/run/app.sock\0\0\0");
+        assert_eq!(format_sockaddr(&unix).unwrap(), "/run/app.sock");
+        let mut abstract_unix = (libc::AF_UNIX as u16).to_ne_bytes().to_vec();
+        abstract_unix.extend_from_slice(b"\0bus");
+        assert_eq!(format_sockaddr(&abstract_unix).unwrap(), "@bus");
+    }
+
+    #[test]
+    fn test_summary_caps_lists() {
+        let mut summary = Summary::default();
+        summary.count(libc::SYS_openat);
+        summary.count(libc::SYS_openat);
+        summary.count(libc::SYS_getpid);
+        for i in 0..MAX_ENTRIES + 2 {
+            summary.add(List::Read, format!("/data/{}", i));
+        }
+        summary.add(List::Read, "/data/0".to_string());
+        assert_eq!(open_list(libc::O_RDONLY as u64), List::Read);
+        assert_eq!(
+            open_list((libc::O_WRONLY | libc::O_CREAT) as u64),
+            List::Written
+        );
+
+        let report = summary.report();
+        assert_eq!(report.syscalls.get("openat"), Some(&2));
+        assert_eq!(report.syscalls.len(), 1);
+        assert_eq!(report.files_read.len(), MAX_ENTRIES);
+        assert_eq!(report.dropped, 2);
+    }
+
+    #[test]
+    fn test_read_own_memory() {
+        let process = Process {
+            pid: std::process::id(),
+            mem: File::open("/proc/self/mem").unwrap(),
+        };
+        let path = std::ffi::CString::new("/etc/hostname").unwrap();
+        let addr = path.as_ptr() as u64;
+        assert_eq!(process.read_string(addr).unwrap(), "/etc/hostname");
+        assert_eq!(process.path(libc::AT_FDCWD, addr).unwrap(), "/etc/hostname");
+
+        let relative = std::ffi::CString::new("Cargo.toml").unwrap();
+        let resolved = process
+            .path(libc::AT_FDCWD, relative.as_ptr() as u64)
+            .unwrap();
+        let expected = std::env::current_dir().unwrap().join("Cargo.toml");
+        assert_eq!(resolved, expected.to_string_lossy());
+    }
+}
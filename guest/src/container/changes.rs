@@ -0,0 +1,83 @@
+//! Replay of host-side file changes on shared volumes
+//!
+//! Edits made on the host reach the guest through virtio-fs, but the guest
+//! kernel never sees them happen, so inotify watchers in the box (webpack,
+//! cargo-watch) stay silent. The host polls its volume trees and reports
+//! each changed path with the mtime it observed; setting that same mtime
+//! again from the guest is a no-op for the data but raises a real
+//! `IN_ATTRIB` event on the guest inode. That is the only event replayed:
+//! host-side creations, deletions and renames arrive as a change to the
+//! parent directory, and raising `IN_CREATE`, `IN_DELETE` or `IN_MOVED_*`
+//! would mean renaming or recreating the entry on the shared volume.
+
+use std::fs::{File, FileTimes};
+use std::io;
+use std::path::{Component, Path};
+use std::time::{Duration, SystemTime};
+
+/// Re-apply the host-observed mtime to `path` under `volume_root`.
+///
+/// Returns false when the entry is gone, is a symlink, or no longer has
+/// the reported mtime (it changed again and a newer report will follow).
+pub fn replay(
+    volume_root: &Path,
+    path: &str,
+    mtime_secs: i64,
+    mtime_nanos: u32,
+) -> io::Result<bool> {
+    if Path::new(path)
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not a relative path inside the volume", path),
+        ));
+    }
+    let target = volume_root.join(path);
+
+    let metadata = match std::fs::symlink_metadata(&target) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        result => result?,
+    };
+    let reported = u64::try_from(mtime_secs)
+        .ok()
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::new(secs, mtime_nanos));
+    if metadata.file_type().is_symlink() || Some(metadata.modified()?) != reported {
+        return Ok(false);
+    }
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    File::open(&target)?.set_times(times)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_checks_reported_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let file = root.join("src/main.rs");
+        std::fs::write(&file, b"fn main() {}").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 250);
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        assert!(replay(root, "src/main.rs", 1_700_000_000, 250).unwrap());
+        assert_eq!(std::fs::metadata(&file).unwrap().modified().unwrap(), mtime);
+        // Changed again since the report
+        assert!(!replay(root, "src/main.rs", 1_600_000_000, 0).unwrap());
+        assert!(!replay(root, "src/gone.rs", 1_700_000_000, 0).unwrap());
+        assert!(replay(root, "../escape", 0, 0).is_err());
+    }
+}
@@ -57,6 +57,9 @@ pub struct ContainerCommand {
 
     /// PTY configuration (set via with_pty())
     pty_config: Option<PtyConfig>,
+
+    /// User and group to run as (None = root)
+    user: Option<(u32, u32)>,
 }
 
 impl ContainerCommand {
@@ -72,6 +75,7 @@ impl ContainerCommand {
             cwd: None,
             console_socket: None,
             pty_config: None,
+            user: None,
             id,
             state_root,
         }
@@ -87,6 +91,12 @@ impl ContainerCommand {
         self
     }
 
+    /// Run as `uid` with primary group `gid` instead of root
+    pub fn user(mut self, uid: u32, gid: u32) -> Self {
+        self.user = Some((uid, gid));
+        self
+    }
+
     /// Set the program to execute
     ///
     /// # Example
@@ -258,6 +268,7 @@ impl ContainerCommand {
     }
 
     /// Spawn process with PTY (interactive mode).
+    #[cfg(feature = "pty")]
     async fn spawn_with_pty(mut self, config: PtyConfig) -> BoxliteResult<ExecHandle> {
         use super::console_socket::ConsoleSocket;
 
@@ -282,6 +293,13 @@ impl ContainerCommand {
         create_pty_child(pid, pty_master, config)
     }
 
+    #[cfg(not(feature = "pty"))]
+    async fn spawn_with_pty(self, _config: PtyConfig) -> BoxliteResult<ExecHandle> {
+        Err(crate::features::missing(
+            boxlite_shared::constants::agent_features::PTY,
+        ))
+    }
+
     /// Build and spawn process using libcontainer.
     async fn build_and_spawn(
         &self,
@@ -345,6 +363,8 @@ impl ContainerCommand {
             .with_detach(false)
             .with_cwd(self.cwd.clone().or(Some("/".parse().unwrap())))
             .with_env(self.env.clone())
+            .with_user(self.user.map(|(uid, _)| uid))
+            .with_group(self.user.map(|(_, gid)| gid))
             .with_container_args(container_args.clone())
             .build()
             .map_err(|e| {
@@ -389,6 +409,7 @@ impl ContainerCommand {
 ///
 /// Sets terminal window size, reconciles PTY master FD as stdin/stdout/stderr,
 /// and stores PTY controller for later resizing.
+#[cfg(feature = "pty")]
 fn create_pty_child(pid: Pid, pty_master: OwnedFd, config: PtyConfig) -> BoxliteResult<ExecHandle> {
     set_pty_window_size(&pty_master, &config)?;
     let (stdin, stdout, stderr) = reconcile_pty_fds(&pty_master)?;
@@ -401,6 +422,7 @@ fn create_pty_child(pid: Pid, pty_master: OwnedFd, config: PtyConfig) -> Boxlite
 }
 
 /// Set PTY terminal window size via ioctl.
+#[cfg(feature = "pty")]
 fn set_pty_window_size(pty_master: &OwnedFd, config: &PtyConfig) -> BoxliteResult<()> {
     use nix::pty::Winsize;
     use std::os::fd::AsRawFd;
@@ -435,6 +457,7 @@ fn set_pty_window_size(pty_master: &OwnedFd, config: &PtyConfig) -> BoxliteResul
 /// Duplicates the PTY master FD three times so it can be used as separate
 /// stdin, stdout, and stderr streams. This allows reusing existing pipe-based
 /// I/O forwarding code.
+#[cfg(feature = "pty")]
 fn reconcile_pty_fds(pty_master: &OwnedFd) -> BoxliteResult<(OwnedFd, OwnedFd, OwnedFd)> {
     use nix::unistd::dup;
     use std::os::fd::{AsRawFd, FromRawFd};
@@ -456,6 +479,7 @@ fn reconcile_pty_fds(pty_master: &OwnedFd) -> BoxliteResult<(OwnedFd, OwnedFd, O
 /// Convert OwnedFd to File for PTY controller.
 ///
 /// The PTY controller is kept for later resizing operations.
+#[cfg(feature = "pty")]
 fn pty_master_to_file(pty_master: OwnedFd) -> std::fs::File {
     use std::os::fd::{AsRawFd, FromRawFd};
 
@@ -0,0 +1,130 @@
+//! Credential forwarding
+//!
+//! Lets builds in the container use the host's SSH agent and git
+//! credentials without copying keys into the VM. For each forwarded
+//! service the agent listens on a Unix socket in a guest tmpfs directory
+//! bind-mounted at [`CONTAINER_AGENT_DIR`], and relays every connection to
+//! a vsock port that libkrun bridges to the host service:
+//!
+//! - `ssh-agent.sock` → host `$SSH_AUTH_SOCK` (agent protocol, relayed as-is)
+//! - `git-credential.sock` → host git credential server
+//!
+//! Git reaches its socket through `git-credential-boxlite`, a copy of the
+//! agent binary that acts as a credential helper when invoked under that
+//! name (see [`run_git_helper`]). Sockets are only accessible to root.
+//! Relaying is done by [`super::relay`].
+
+use boxlite_shared::constants::network::{GUEST_GIT_CREDENTIAL_PORT, GUEST_SSH_AGENT_PORT};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::Path;
+use tracing::warn;
+
+use super::relay;
+
+/// Where the forwarding directory is mounted in the container.
+pub const CONTAINER_AGENT_DIR: &str = "/run/boxlite-agent";
+
+/// Name the agent binary answers to as a git credential helper.
+pub const GIT_HELPER_NAME: &str = "git-credential-boxlite";
+
+const SSH_AGENT_SOCKET: &str = "ssh-agent.sock";
+const GIT_CREDENTIAL_SOCKET: &str = "git-credential.sock";
+
+/// Set up forwarding in `dir` and add the matching variables to `env`.
+///
+/// Must run inside the tokio runtime; relays live as long as the agent.
+pub fn prepare(
+    dir: &Path,
+    ssh_agent: bool,
+    git_credentials: bool,
+    env: &mut Vec<String>,
+) -> io::Result<()> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o755)
+        .create(dir)?;
+
+    if ssh_agent {
+        relay::listen(&dir.join(SSH_AGENT_SOCKET), 0o600, GUEST_SSH_AGENT_PORT)?;
+        set_default(
+            env,
+            "SSH_AUTH_SOCK",
+            &format!("{}/{}", CONTAINER_AGENT_DIR, SSH_AGENT_SOCKET),
+        );
+    }
+
+    if git_credentials {
+        relay::listen(
+            &dir.join(GIT_CREDENTIAL_SOCKET),
+            0o600,
+            GUEST_GIT_CREDENTIAL_PORT,
+        )?;
+        let helper = dir.join(GIT_HELPER_NAME);
+        std::fs::copy(std::env::current_exe()?, &helper)?;
+        std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755))?;
+
+        // GIT_CONFIG_COUNT adds config without touching files in the rootfs
+        if env.iter().any(|e| e.starts_with("GIT_CONFIG_COUNT=")) {
+            warn!("GIT_CONFIG_COUNT already set; not configuring the git credential helper");
+        } else {
+            env.push("GIT_CONFIG_COUNT=1".to_string());
+            env.push("GIT_CONFIG_KEY_0=credential.helper".to_string());
+            env.push(format!(
+                "GIT_CONFIG_VALUE_0={}/{}",
+                CONTAINER_AGENT_DIR, GIT_HELPER_NAME
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn set_default(env: &mut Vec<String>, key: &str, value: &str) {
+    let prefix = format!("{}=", key);
+    if !env.iter().any(|e| e.starts_with(&prefix)) {
+        env.push(format!("{}{}", prefix, value));
+    }
+}
+
+/// Git credential helper entry point (`git-credential-boxlite <op>`).
+///
+/// Sends the operation and git's attributes to the host and prints the
+/// reply. Only `get` is answered by the host; `store` and `erase` are
+/// accepted and ignored, so the container cannot modify host credentials.
+pub fn run_git_helper(operation: &str) -> io::Result<()> {
+    let mut request = format!("{}\n", operation).into_bytes();
+    io::stdin().read_to_end(&mut request)?;
+
+    let socket = Path::new(CONTAINER_AGENT_DIR).join(GIT_CREDENTIAL_SOCKET);
+    let mut conn = std::os::unix::net::UnixStream::connect(socket)?;
+    conn.write_all(&request)?;
+    conn.shutdown(std::net::Shutdown::Write)?;
+
+    let mut reply = Vec::new();
+    conn.read_to_end(&mut reply)?;
+    io::stdout().write_all(&reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prepare_sets_env() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("agent");
+        let mut env = vec!["SSH_AUTH_SOCK=/custom.sock".to_string()];
+
+        prepare(&dir, true, true, &mut env).unwrap();
+
+        // User-provided values win
+        assert!(env.contains(&"SSH_AUTH_SOCK=/custom.sock".to_string()));
+        assert!(env.contains(&format!(
+            "GIT_CONFIG_VALUE_0={}/{}",
+            CONTAINER_AGENT_DIR, GIT_HELPER_NAME
+        )));
+        assert!(dir.join(SSH_AGENT_SOCKET).exists());
+        assert!(dir.join(GIT_HELPER_NAME).exists());
+    }
+}
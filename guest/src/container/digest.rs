@@ -0,0 +1,95 @@
+//! Content digests of paths in the container rootfs
+//!
+//! A digest covers what a command could observe through the path: the
+//! entry's type and permission bits, a file's bytes, a symlink's target,
+//! and for a directory, the names and digests of everything under it in
+//! sorted order. Timestamps and ownership are left out, so rewriting a file
+//! with the same content keeps its digest.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Hex SHA-256 of `path`, or `None` if it does not exist.
+pub fn digest(path: &Path) -> io::Result<Option<String>> {
+    if let Err(e) = fs::symlink_metadata(path) {
+        return match e.kind() {
+            io::ErrorKind::NotFound => Ok(None),
+            _ => Err(e),
+        };
+    }
+    let mut hasher = Sha256::new();
+    hash_entry(&mut hasher, path)?;
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+fn hash_entry(hasher: &mut Sha256, path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        hasher.update(b"l");
+        field(hasher, fs::read_link(path)?.as_os_str().as_encoded_bytes());
+    } else if file_type.is_dir() {
+        hasher.update(b"d");
+        hasher.update((metadata.permissions().mode() & 0o7777).to_le_bytes());
+        let mut names: Vec<_> = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect::<io::Result<_>>()?;
+        names.sort();
+        hasher.update((names.len() as u64).to_le_bytes());
+        for name in names {
+            field(hasher, name.as_encoded_bytes());
+            hash_entry(hasher, &path.join(name))?;
+        }
+    } else if file_type.is_file() {
+        hasher.update(b"f");
+        hasher.update((metadata.permissions().mode() & 0o7777).to_le_bytes());
+        hasher.update(metadata.size().to_le_bytes());
+        let mut file = File::open(path)?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+    } else {
+        // Devices, sockets and FIFOs: only their kind and identity
+        hasher.update(b"o");
+        hasher.update(metadata.rdev().to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Length-prefixed, so neighbouring fields cannot run into each other.
+fn field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_tracks_content_not_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        fs::write(src.join("main.py"), b"print(1)\n").unwrap();
+        fs::write(src.join("util.py"), b"").unwrap();
+
+        let before = digest(&src).unwrap().unwrap();
+        fs::write(src.join("main.py"), b"print(1)\n").unwrap();
+        assert_eq!(digest(&src).unwrap().unwrap(), before);
+
+        fs::write(src.join("main.py"), b"print(2)\n").unwrap();
+        assert_ne!(digest(&src).unwrap().unwrap(), before);
+
+        assert_eq!(digest(&dir.path().join("missing")).unwrap(), None);
+    }
+}
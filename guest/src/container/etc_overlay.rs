@@ -0,0 +1,75 @@
+//! Writable /etc over a shared rootfs
+//!
+//! A rootfs shared between boxes (or read-only) cannot take per-box edits to
+//! `/etc/passwd`, `/etc/hosts` or `/etc/alternatives`. With `etc_overlay`
+//! the container's `/etc` becomes an overlayfs: the rootfs `/etc` is the
+//! lower layer and the upper layer lives on the guest tmpfs, so each box
+//! sees its own writable `/etc` without copying the rootfs. Changes are
+//! discarded when the box stops.
+
+use nix::mount::{mount as nix_mount, MsFlags};
+use std::io;
+use std::path::Path;
+
+/// Mount an overlay on `etc`, keeping its changes under `dir`.
+pub fn mount(etc: &Path, dir: &Path) -> io::Result<()> {
+    let upper = dir.join("upper");
+    let work = dir.join("work");
+    std::fs::create_dir_all(&upper)?;
+    std::fs::create_dir_all(&work)?;
+
+    let options = mount_options(etc, &upper, &work)?;
+    nix_mount(
+        Some("overlay"),
+        etc,
+        Some("overlay"),
+        MsFlags::empty(),
+        Some(options.as_str()),
+    )
+    .map_err(io::Error::from)
+}
+
+/// overlayfs options for the given layers.
+///
+/// overlayfs splits its options on ',' and lower layers on ':', so paths
+/// containing either are refused rather than misparsed.
+fn mount_options(lower: &Path, upper: &Path, work: &Path) -> io::Result<String> {
+    let mut paths = Vec::new();
+    for path in [lower, upper, work] {
+        let path = path
+            .to_str()
+            .filter(|p| !p.contains([',', ':']))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unsupported overlay path {}", path.display()),
+                )
+            })?;
+        paths.push(path);
+    }
+    Ok(format!(
+        "lowerdir={},upperdir={},workdir={}",
+        paths[0], paths[1], paths[2]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_options() {
+        assert_eq!(
+            mount_options(
+                Path::new("/rootfs/etc"),
+                Path::new("/run/etc/upper"),
+                Path::new("/run/etc/work")
+            )
+            .unwrap(),
+            "lowerdir=/rootfs/etc,upperdir=/run/etc/upper,workdir=/run/etc/work"
+        );
+        assert!(
+            mount_options(Path::new("/rootfs:x/etc"), Path::new("/u"), Path::new("/w")).is_err()
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! Fault injection for Container.InjectFault
+//!
+//! Test-only: compiled with the `fault-injection` feature, so embedders can
+//! exercise their error handling against a real box. Each fault mimics a
+//! failure seen in the field rather than a clean error.
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use nix::mount::{umount2, MntFlags};
+use nix::sched::{setns, unshare, CloneFlags};
+
+/// Lazily detach the mount at `path` in the mount namespace of `init_pid`.
+///
+/// Open files keep working; new lookups see the directory underneath, as
+/// after a mount that failed.
+pub fn detach_mount(init_pid: i32, path: &str) -> io::Result<()> {
+    let ns_path = format!("/proc/{}/ns/mnt", init_pid);
+    let path = path.to_string();
+    // setns sticks to the thread, and a thread sharing its filesystem
+    // context cannot change mount namespace, so use a throwaway one.
+    std::thread::spawn(move || -> io::Result<()> {
+        let ns = std::fs::File::open(&ns_path)?;
+        unshare(CloneFlags::CLONE_FS)?;
+        setns(&ns, CloneFlags::CLONE_NEWNS)?;
+        umount2(path.as_str(), MntFlags::MNT_DETACH)?;
+        Ok(())
+    })
+    .join()
+    .map_err(|_| io::Error::other("mount namespace thread panicked"))?
+}
+
+/// Step the wall clock by `seconds`, back if negative.
+pub fn jump_clock(seconds: i64) -> io::Result<()> {
+    let step = Duration::from_secs(seconds.unsigned_abs());
+    let now = SystemTime::now();
+    let target = if seconds >= 0 {
+        now.checked_add(step)
+    } else {
+        now.checked_sub(step)
+    }
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "clock jump out of range"))?;
+    crate::resume::step_clock(target).map(|_| ())
+}
@@ -0,0 +1,61 @@
+//! Freezing the container rootfs for a consistent copy
+//!
+//! FIFREEZE flushes the filesystem and holds new writes until FITHAW, so
+//! the host can copy the disk under the rootfs while the box runs. Writers
+//! in the container stall meanwhile, so a freeze the host never lifts is
+//! lifted here after [`AUTO_THAW`].
+
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use nix::libc;
+
+/// `_IOWR('X', 119, int)`
+const FIFREEZE: u32 = 0xC004_5877;
+/// `_IOWR('X', 120, int)`
+const FITHAW: u32 = 0xC004_5878;
+
+/// How long a rootfs stays frozen if the host does not thaw it.
+pub const AUTO_THAW: Duration = Duration::from_secs(60);
+
+/// Bumped by every freeze, so an earlier freeze's timer leaves a later one be.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Freeze the filesystem mounted at `rootfs`, thawing it after [`AUTO_THAW`].
+pub fn freeze(rootfs: &Path) -> io::Result<()> {
+    ioctl(rootfs, FIFREEZE)?;
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    let rootfs: PathBuf = rootfs.to_path_buf();
+    tokio::spawn(async move {
+        tokio::time::sleep(AUTO_THAW).await;
+        if GENERATION.load(Ordering::SeqCst) == generation {
+            tracing::warn!(rootfs = %rootfs.display(), "Rootfs still frozen, thawing it");
+            let _ = thaw(&rootfs);
+        }
+    });
+    Ok(())
+}
+
+/// Thaw the filesystem mounted at `rootfs`; thawing one not frozen is a no-op.
+pub fn thaw(rootfs: &Path) -> io::Result<()> {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    match ioctl(rootfs, FITHAW) {
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Ok(()),
+        result => result,
+    }
+}
+
+fn ioctl(rootfs: &Path, request: u32) -> io::Result<()> {
+    let dir = File::open(rootfs)?;
+    let mut unused: libc::c_int = 0;
+    // SAFETY: FIFREEZE and FITHAW take an int argument they ignore
+    if unsafe { libc::ioctl(dir.as_raw_fd(), request as _, &mut unused) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
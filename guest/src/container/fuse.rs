@@ -0,0 +1,62 @@
+//! FUSE inside the container
+//!
+//! The guest kernel already has FUSE (virtio-fs is built on it), so
+//! enabling it only takes `/dev/fuse` in the container, requested by the
+//! host like any other device node. Non-root users mount through the
+//! image's setuid `fusermount`; `user_allow_other` is turned on so their
+//! mounts can be shared with other users in the box, as FUSE tools like
+//! AppImage and s3fs commonly expect.
+//!
+//! With FUSE disabled and a devtmpfs `/dev`, `/dev/fuse` is masked with
+//! `/dev/null` so the box's switch holds even though devtmpfs shows every
+//! guest device.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const FUSE_DEVICE: &str = "/dev/fuse";
+
+const FUSE_CONF: &str = "etc/fuse.conf";
+
+const ALLOW_OTHER: &str = "user_allow_other";
+
+/// Enable `user_allow_other` in the rootfs's fuse.conf, creating it if needed.
+pub fn configure(rootfs: &Path) -> io::Result<()> {
+    let path = rootfs.join(FUSE_CONF);
+    let current = match std::fs::read_to_string(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        result => result?,
+    };
+    if current.lines().any(|line| line.trim() == ALLOW_OTHER) {
+        return Ok(());
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    if !current.is_empty() && !current.ends_with('\n') {
+        file.write_all(b"\n")?;
+    }
+    writeln!(file, "{}", ALLOW_OTHER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configure_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let rootfs = dir.path();
+        std::fs::create_dir_all(rootfs.join("etc")).unwrap();
+        std::fs::write(rootfs.join(FUSE_CONF), "#user_allow_other\nmount_max = 100").unwrap();
+
+        configure(rootfs).unwrap();
+        configure(rootfs).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(rootfs.join(FUSE_CONF)).unwrap(),
+            "#user_allow_other\nmount_max = 100\nuser_allow_other\n"
+        );
+    }
+}
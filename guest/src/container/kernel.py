@@ -0,0 +1,114 @@
+# Driver for boxlite kernel sessions, run with `python3 -c`.
+#
+# Reads one JSON request ({"code": ...}) per line from stdin and writes one
+# JSON reply per line to the original stdout. Cells run in one namespace, so
+# state carries over. Output from child processes goes to stderr, and
+# SIGINT interrupts the running cell.
+import ast
+import base64
+import io
+import json
+import linecache
+import os
+import sys
+import traceback
+
+_requests = os.fdopen(os.dup(0), "r", encoding="utf-8")
+_replies = os.fdopen(os.dup(1), "w", encoding="utf-8")
+os.dup2(os.open(os.devnull, os.O_RDONLY), 0)
+os.dup2(2, 1)
+
+_MIME_METHODS = [
+    ("_repr_html_", "text/html"),
+    ("_repr_markdown_", "text/markdown"),
+    ("_repr_svg_", "image/svg+xml"),
+    ("_repr_png_", "image/png"),
+    ("_repr_jpeg_", "image/jpeg"),
+    ("_repr_json_", "application/json"),
+    ("_repr_latex_", "text/latex"),
+]
+
+_displays = []
+
+
+def _bundle(obj):
+    data = {"text/plain": repr(obj)}
+    for method, mime in _MIME_METHODS:
+        fn = getattr(obj, method, None)
+        if not callable(fn):
+            continue
+        try:
+            value = fn()
+        except Exception:
+            continue
+        if isinstance(value, tuple):
+            value = value[0]
+        if value is None:
+            continue
+        if isinstance(value, bytes):
+            value = base64.b64encode(value).decode("ascii")
+        elif not isinstance(value, str):
+            value = json.dumps(value)
+        data[mime] = value
+    return data
+
+
+def display(*objs):
+    for obj in objs:
+        _displays.append(_bundle(obj))
+
+
+_namespace = {"__name__": "__main__", "__builtins__": __builtins__, "display": display}
+
+
+def _run(code, count):
+    name = "<cell-%d>" % count
+    linecache.cache[name] = (len(code), None, code.splitlines(True), name)
+    stdout, stderr = io.StringIO(), io.StringIO()
+    del _displays[:]
+    error = None
+    sys.stdout, sys.stderr = stdout, stderr
+    try:
+        tree = ast.parse(code, name)
+        last = None
+        if tree.body and isinstance(tree.body[-1], ast.Expr):
+            last = ast.Expression(tree.body.pop().value)
+        exec(compile(tree, name, "exec"), _namespace)
+        if last is not None:
+            value = eval(compile(last, name, "eval"), _namespace)
+            if value is not None:
+                _namespace["_"] = value
+                display(value)
+    except BaseException as e:
+        error = {
+            "ename": type(e).__name__,
+            "evalue": str(e),
+            "traceback": traceback.format_exception(type(e), e, e.__traceback__.tb_next),
+        }
+    finally:
+        sys.stdout, sys.stderr = sys.__stdout__, sys.__stderr__
+    return {
+        "stdout": stdout.getvalue(),
+        "stderr": stderr.getvalue(),
+        "display": list(_displays),
+        "error": error,
+    }
+
+
+def _main():
+    count = 0
+    while True:
+        try:
+            line = _requests.readline()
+        except KeyboardInterrupt:
+            continue
+        if not line:
+            return
+        count += 1
+        reply = _run(json.loads(line)["code"], count)
+        reply["execution_count"] = count
+        _replies.write(json.dumps(reply) + "\n")
+        _replies.flush()
+
+
+_main()
@@ -0,0 +1,265 @@
+//! Persistent interpreter sessions for Container.ExecuteCell
+//!
+//! A kernel is a Python process in the container running `kernel.py`. It
+//! takes one JSON request per line on stdin and answers each with one JSON
+//! line on stdout; what child processes print arrives on stderr and is
+//! handed back with the next reply. A cell that runs past its timeout is
+//! interrupted with SIGINT, which leaves the session's state intact.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use boxlite_shared::{CellError, DisplayData, ExecuteCellResponse};
+use futures::StreamExt;
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::waitpid;
+use nix::unistd::Pid;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::Container;
+use crate::service::exec::exec_handle::{ExecHandle, ExecStdin, ExecStdout};
+
+const DRIVER: &str = include_str!("kernel.py");
+
+/// How long an interrupted cell gets to unwind before the kernel is killed.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+struct Reply {
+    execution_count: u32,
+    stdout: String,
+    stderr: String,
+    display: Vec<HashMap<String, String>>,
+    error: Option<ReplyError>,
+}
+
+#[derive(Deserialize)]
+struct ReplyError {
+    ename: String,
+    evalue: String,
+    traceback: Vec<String>,
+}
+
+impl From<Reply> for ExecuteCellResponse {
+    fn from(reply: Reply) -> Self {
+        Self {
+            execution_count: reply.execution_count,
+            stdout: reply.stdout,
+            stderr: reply.stderr,
+            display: reply
+                .display
+                .into_iter()
+                .map(|data| DisplayData { data })
+                .collect(),
+            error: reply.error.map(|e| CellError {
+                ename: e.ename,
+                evalue: e.evalue,
+                traceback: e.traceback,
+            }),
+        }
+    }
+}
+
+/// A running interpreter session.
+pub struct Kernel {
+    handle: ExecHandle,
+    stdin: ExecStdin,
+    stdout: ExecStdout,
+    buffered: Vec<u8>,
+    stderr: Arc<std::sync::Mutex<Vec<u8>>>,
+}
+
+impl Kernel {
+    /// Start a kernel for `language` in `container`.
+    pub async fn start(container: &Mutex<Container>, language: &str) -> Result<Self, String> {
+        if !language.is_empty() && language != "python" {
+            return Err(format!("Unsupported kernel language: {}", language));
+        }
+        let mut handle = container
+            .lock()
+            .await
+            .cmd()
+            .program("python3")
+            .args(["-u", "-c", DRIVER])
+            .env("PYTHONDONTWRITEBYTECODE", "1")
+            .spawn()
+            .await
+            .map_err(|e| format!("Failed to start python3: {}", e))?;
+
+        let (Some(stdin), Some(stdout), Some(mut stderr)) =
+            (handle.stdin(), handle.stdout(), handle.stderr())
+        else {
+            return Err("Kernel process has no stdio".to_string());
+        };
+        let captured = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&captured);
+        tokio::spawn(async move {
+            while let Some(chunk) = stderr.next().await {
+                sink.lock().unwrap().extend_from_slice(&chunk);
+            }
+        });
+
+        Ok(Self {
+            handle,
+            stdin,
+            stdout,
+            buffered: Vec::new(),
+            stderr: captured,
+        })
+    }
+
+    /// Run `code`, interrupting it once `timeout` has passed.
+    ///
+    /// An error means the kernel is gone and has to be started again.
+    pub async fn execute(
+        &mut self,
+        code: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecuteCellResponse, String> {
+        let mut request = serde_json::json!({ "code": code }).to_string();
+        request.push('\n');
+        self.stdin
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("Kernel stopped: {}", e))?;
+
+        let line = match timeout {
+            None => self.read_line().await?,
+            Some(timeout) => match tokio::time::timeout(timeout, self.read_line()).await {
+                Ok(line) => line?,
+                Err(_) => {
+                    let _ = self.handle.kill(Signal::SIGINT);
+                    tokio::time::timeout(INTERRUPT_GRACE, self.read_line())
+                        .await
+                        .map_err(|_| "Kernel did not respond to an interrupt".to_string())??
+                }
+            },
+        };
+        let reply: Reply =
+            serde_json::from_slice(&line).map_err(|e| format!("Malformed kernel reply: {}", e))?;
+
+        let mut response = ExecuteCellResponse::from(reply);
+        let stderr = std::mem::take(&mut *self.stderr.lock().unwrap());
+        response.stderr.push_str(&String::from_utf8_lossy(&stderr));
+        Ok(response)
+    }
+
+    async fn read_line(&mut self) -> Result<Vec<u8>, String> {
+        loop {
+            if let Some(end) = self.buffered.iter().position(|&b| b == b'\n') {
+                let mut line: Vec<u8> = self.buffered.drain(..=end).collect();
+                line.pop();
+                return Ok(line);
+            }
+            match self.stdout.next().await {
+                Some(chunk) => self.buffered.extend_from_slice(&chunk),
+                None => return Err("Kernel exited".to_string()),
+            }
+        }
+    }
+
+    fn pid(&self) -> Pid {
+        self.handle.pid()
+    }
+}
+
+struct Slot {
+    pid: Pid,
+    kernel: Mutex<Kernel>,
+}
+
+/// Kernels by container and kernel id.
+#[derive(Default)]
+pub struct Kernels {
+    kernels: Mutex<HashMap<(String, String), Arc<Slot>>>,
+}
+
+impl Kernels {
+    /// Run `code` in a kernel, starting it first if it is not running.
+    ///
+    /// Cells for one kernel run one at a time; a kernel that died is
+    /// dropped, so the next cell starts a fresh one.
+    pub async fn execute(
+        &self,
+        container_id: &str,
+        container: &Mutex<Container>,
+        kernel_id: &str,
+        language: &str,
+        code: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecuteCellResponse, String> {
+        let key = (container_id.to_string(), kernel_id.to_string());
+        let slot = {
+            let mut kernels = self.kernels.lock().await;
+            match kernels.get(&key) {
+                Some(slot) => Arc::clone(slot),
+                None => {
+                    let kernel = Kernel::start(container, language).await?;
+                    let slot = Arc::new(Slot {
+                        pid: kernel.pid(),
+                        kernel: Mutex::new(kernel),
+                    });
+                    kernels.insert(key.clone(), Arc::clone(&slot));
+                    slot
+                }
+            }
+        };
+
+        let result = slot.kernel.lock().await.execute(code, timeout).await;
+        if result.is_err() {
+            let mut kernels = self.kernels.lock().await;
+            if kernels.get(&key).is_some_and(|s| Arc::ptr_eq(s, &slot)) {
+                kernels.remove(&key);
+                drop(kernels);
+                stop(slot.pid);
+            }
+        }
+        result
+    }
+
+    /// Whether no kernel is running.
+    pub async fn is_empty(&self) -> bool {
+        self.kernels.lock().await.is_empty()
+    }
+
+    /// Stop a kernel; returns whether it was running.
+    ///
+    /// A cell still running in it fails.
+    pub async fn shutdown(&self, container_id: &str, kernel_id: &str) -> bool {
+        let key = (container_id.to_string(), kernel_id.to_string());
+        match self.kernels.lock().await.remove(&key) {
+            Some(slot) => {
+                stop(slot.pid);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Kill a kernel process and reap it in the background.
+fn stop(pid: Pid) {
+    let _ = kill(pid, Signal::SIGKILL);
+    tokio::task::spawn_blocking(move || waitpid(pid, None));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_maps_to_response() {
+        let reply: Reply = serde_json::from_str(
+            r#"{"execution_count": 2, "stdout": "hi\n", "stderr": "",
+                "display": [{"text/plain": "42"}],
+                "error": {"ename": "ValueError", "evalue": "bad", "traceback": ["tb"]}}"#,
+        )
+        .unwrap();
+        let response = ExecuteCellResponse::from(reply);
+        assert_eq!(response.execution_count, 2);
+        assert_eq!(response.display[0].data["text/plain"], "42");
+        assert_eq!(response.error.unwrap().ename, "ValueError");
+    }
+}
@@ -4,11 +4,13 @@
 //! Follows the OCI Runtime Specification.
 
 use super::command::ContainerCommand;
-use super::spec::UserMount;
+use super::spec::{SpecFeatures, UserMount};
 use super::stdio::ContainerStdio;
 use super::{kill, start};
+use crate::devices::DeviceSetup;
 use crate::layout::GuestLayout;
 use boxlite_shared::errors::BoxliteResult;
+use boxlite_shared::ContainerHandoff;
 use libcontainer::container::Container as LibContainer;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -46,7 +48,6 @@ pub struct Container {
     env: HashMap<String, String>,
     /// Stdio pipes that keep init process alive.
     /// Dropping this closes pipes → init gets EOF → init exits.
-    #[allow(dead_code)]
     stdio: ContainerStdio,
 }
 
@@ -69,6 +70,8 @@ impl Container {
     /// - `env`: Environment variables in "KEY=VALUE" format
     /// - `workdir`: Working directory inside container
     /// - `user_mounts`: Bind mounts from guest VM paths into container
+    /// - `devices`: Guest device nodes to expose in the container's /dev
+    /// - `features`: Optional setups such as container engines or systemd
     ///
     /// # Errors
     ///
@@ -76,6 +79,7 @@ impl Container {
     /// - Failed to create container directory
     /// - Failed to create or start container
     /// - Init process exited immediately
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         container_id: &str,
         rootfs: impl AsRef<Path>,
@@ -83,6 +87,8 @@ impl Container {
         env: Vec<String>,
         workdir: impl AsRef<Path>,
         user_mounts: Vec<UserMount>,
+        devices: DeviceSetup,
+        features: SpecFeatures,
     ) -> BoxliteResult<Self> {
         let rootfs = rootfs.as_ref();
         let workdir = workdir.as_ref();
@@ -116,6 +122,8 @@ impl Container {
             workdir,
             &layout.containers_dir(),
             &user_mounts,
+            &devices,
+            features,
         )?;
 
         // Create stdio pipes before container creation.
@@ -135,6 +143,39 @@ impl Container {
         })
     }
 
+    /// What the agent replacing this one needs to take the container over.
+    ///
+    /// The stdio descriptors must be kept open across the exec.
+    pub fn handoff(&self) -> ContainerHandoff {
+        let [stdin_fd, stdout_fd, stderr_fd] = self.stdio.raw_fds();
+        ContainerHandoff {
+            id: self.id.clone(),
+            env: self.env.clone(),
+            stdin_fd,
+            stdout_fd,
+            stderr_fd,
+        }
+    }
+
+    /// Take over a container started by the agent this one replaced.
+    ///
+    /// # Safety
+    ///
+    /// The handoff's descriptors must be the container's stdio pipes,
+    /// inherited across the exec and owned by nothing else.
+    pub unsafe fn adopt(handoff: ContainerHandoff) -> BoxliteResult<Self> {
+        let layout = GuestLayout::new();
+        let stdio =
+            ContainerStdio::from_raw_fds([handoff.stdin_fd, handoff.stdout_fd, handoff.stderr_fd])?;
+        Ok(Self {
+            state_root: layout.container_state_dir(&handoff.id),
+            bundle_path: layout.container_bundle_dir(&handoff.id),
+            id: handoff.id,
+            env: handoff.env,
+            stdio,
+        })
+    }
+
     /// Check if container init process is running
     ///
     /// Returns `true` if the container is in Running state, `false` otherwise.
@@ -291,6 +332,14 @@ impl Container {
         }
     }
 
+    /// PID of the container's init process, if it is running
+    pub fn init_pid(&self) -> Option<i32> {
+        LibContainer::load(self.container_state_path())
+            .ok()
+            .and_then(|c| c.pid())
+            .map(|pid| pid.as_raw())
+    }
+
     fn container_state_path(&self) -> PathBuf {
         self.state_root.join(&self.id)
     }
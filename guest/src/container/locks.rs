@@ -0,0 +1,112 @@
+//! Brokered file locks for shared volumes
+//!
+//! Locks taken in the box on a virtio-fs volume are local to the guest
+//! kernel: the host and other boxes never see them. With `lock_proxy`,
+//! `boxlite-lock` (a copy of the agent binary, like `flock(1)`) asks the
+//! host to lock the real file instead, then runs a command while holding
+//! it:
+//!
+//! ```text
+//! /run/boxlite-lock/boxlite-lock [--shared] /data/app.db sqlite3 /data/app.db
+//! ```
+//!
+//! The request travels over `lock.sock`, relayed to the host's lock broker.
+//! The first line is `exclusive <path>` or `shared <path>`; the host answers
+//! `ok` once locked (or `error <reason>`) and holds the lock until the
+//! connection closes, so a crashed box never leaves a file locked.
+
+use boxlite_shared::constants::network::GUEST_LOCK_PORT;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use super::relay;
+
+/// Where the lock directory is mounted in the container.
+pub const CONTAINER_LOCK_DIR: &str = "/run/boxlite-lock";
+
+/// Name the agent binary answers to as the lock helper.
+pub const LOCK_HELPER_NAME: &str = "boxlite-lock";
+
+const LOCK_SOCKET: &str = "lock.sock";
+
+const USAGE: &str = "usage: boxlite-lock [-s|--shared] FILE COMMAND [ARG...]";
+
+/// Set up the lock socket and helper in `dir`.
+///
+/// Must run inside the tokio runtime; the relay lives as long as the agent.
+pub fn prepare(dir: &Path) -> io::Result<()> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o755)
+        .create(dir)?;
+
+    // Any user may lock; the host only locks files on shared volumes
+    relay::listen(&dir.join(LOCK_SOCKET), 0o666, GUEST_LOCK_PORT)?;
+    let helper = dir.join(LOCK_HELPER_NAME);
+    std::fs::copy(std::env::current_exe()?, &helper)?;
+    std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755))
+}
+
+/// Lock helper entry point (`boxlite-lock` arguments after the program name).
+///
+/// Returns the exit code: the command's, 1 if locking failed, or 2 for
+/// bad usage.
+pub fn run_lock_helper(args: &[String]) -> i32 {
+    let (exclusive, args) = match args.first().map(String::as_str) {
+        Some("-s" | "--shared") => (false, &args[1..]),
+        _ => (true, args),
+    };
+    let [file, program, program_args @ ..] = args else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let _lock = match acquire(file, exclusive) {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("boxlite-lock: {}: {}", file, e);
+            return 1;
+        }
+    };
+    match std::process::Command::new(program)
+        .args(program_args)
+        .status()
+    {
+        // 128 + signal, as a shell reports it
+        Ok(status) => status.code().unwrap_or_else(|| {
+            128 + std::os::unix::process::ExitStatusExt::signal(&status).unwrap_or(0)
+        }),
+        Err(e) => {
+            eprintln!("boxlite-lock: {}: {}", program, e);
+            1
+        }
+    }
+}
+
+/// Lock `file` on the host; the lock is held until the stream is dropped.
+fn acquire(file: &str, exclusive: bool) -> io::Result<UnixStream> {
+    let path = std::env::current_dir()?.join(file);
+    let path = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not UTF-8"))?;
+
+    let mut conn = UnixStream::connect(Path::new(CONTAINER_LOCK_DIR).join(LOCK_SOCKET))?;
+    let mode = if exclusive { "exclusive" } else { "shared" };
+    conn.write_all(format!("{} {}\n", mode, path).as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(&conn).read_line(&mut reply)?;
+    let reply = reply.trim_end();
+    if reply == "ok" {
+        return Ok(conn);
+    }
+    Err(match reply.strip_prefix("error ") {
+        Some(reason) => io::Error::other(reason.to_string()),
+        None => io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "lock broker closed the connection",
+        ),
+    })
+}
@@ -0,0 +1,91 @@
+//! Hidden volume paths
+//!
+//! A volume can list paths inside it that the container must not see. Each
+//! one is covered with a read-only bind mount of an empty directory or an
+//! empty file, matching what it covers. The host files stay untouched and
+//! are still visible through the host; only the container's view changes.
+
+use super::UserMount;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+use tracing::warn;
+
+/// Mounts hiding `hidden` paths of the volume at `source`, which the
+/// container sees at `destination`.
+///
+/// The empty directory and file used as covers are created in `masks_dir`.
+/// Paths that do not exist, or are symlinks, are skipped with a warning.
+pub fn mask_mounts(
+    masks_dir: &Path,
+    source: &Path,
+    destination: &str,
+    hidden: &[String],
+) -> io::Result<Vec<UserMount>> {
+    if hidden.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let empty_dir = masks_dir.join("dir");
+    let empty_file = masks_dir.join("file");
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o555)
+        .create(&empty_dir)?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o444)
+        .open(&empty_file)?;
+
+    let mut mounts = Vec::new();
+    for path in hidden {
+        let cover = match std::fs::symlink_metadata(source.join(path)) {
+            Ok(meta) if meta.is_dir() => &empty_dir,
+            Ok(meta) if meta.file_type().is_symlink() => {
+                warn!(path = %path, "Not hiding symlink in volume {}", destination);
+                continue;
+            }
+            Ok(_) => &empty_file,
+            Err(e) => {
+                warn!(path = %path, "Not hiding path in volume {}: {}", destination, e);
+                continue;
+            }
+        };
+        mounts.push(UserMount {
+            source: cover.to_string_lossy().to_string(),
+            destination: format!("{}/{}", destination.trim_end_matches('/'), path),
+            read_only: true,
+        });
+    }
+    Ok(mounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_mounts_match_file_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let volume = dir.path().join("volume");
+        std::fs::create_dir_all(volume.join(".git")).unwrap();
+        std::fs::write(volume.join(".env"), "TOKEN=x").unwrap();
+        std::os::unix::fs::symlink(".env", volume.join("link")).unwrap();
+
+        let hidden = [".git", ".env", "link", "missing"].map(String::from);
+        let mounts = mask_mounts(&dir.path().join("masks"), &volume, "/work/", &hidden).unwrap();
+
+        let covers: Vec<(&str, &str)> = mounts
+            .iter()
+            .map(|m| (m.destination.as_str(), m.source.rsplit('/').next().unwrap()))
+            .collect();
+        assert_eq!(covers, [("/work/.git", "dir"), ("/work/.env", "file")]);
+        assert!(mounts.iter().all(|m| m.read_only));
+        assert_eq!(
+            std::fs::read_to_string(volume.join(".env")).unwrap(),
+            "TOKEN=x"
+        );
+    }
+}
@@ -61,24 +61,80 @@
 //! # }
 //! ```
 
+#[cfg(target_os = "linux")]
+pub mod audit;
 #[cfg(target_os = "linux")]
 mod capabilities;
 #[cfg(target_os = "linux")]
-mod command;
+pub mod cgroups;
 #[cfg(target_os = "linux")]
+pub mod changes;
+#[cfg(target_os = "linux")]
+mod command;
+#[cfg(all(target_os = "linux", feature = "pty"))]
 mod console_socket;
 #[cfg(target_os = "linux")]
+pub mod credentials;
+#[cfg(target_os = "linux")]
+pub mod digest;
+#[cfg(target_os = "linux")]
+pub mod etc_overlay;
+#[cfg(all(target_os = "linux", feature = "fault-injection"))]
+pub mod faults;
+#[cfg(target_os = "linux")]
+pub mod freeze;
+#[cfg(target_os = "linux")]
+pub mod fuse;
+#[cfg(target_os = "linux")]
+pub mod kernel;
+#[cfg(target_os = "linux")]
 mod kill;
 #[cfg(target_os = "linux")]
 mod lifecycle;
 #[cfg(target_os = "linux")]
+pub mod locks;
+#[cfg(target_os = "linux")]
+pub mod masks;
+#[cfg(target_os = "linux")]
+pub mod nested;
+#[cfg(all(target_os = "linux", feature = "net"))]
+pub mod netns;
+#[cfg(target_os = "linux")]
+pub mod package_cache;
+#[cfg(target_os = "linux")]
+pub mod packages;
+#[cfg(target_os = "linux")]
+pub mod populate;
+#[cfg(target_os = "linux")]
+pub mod private_fs;
+#[cfg(target_os = "linux")]
+pub mod processes;
+#[cfg(target_os = "linux")]
+pub mod readahead;
+#[cfg(target_os = "linux")]
+mod relay;
+#[cfg(target_os = "linux")]
+pub mod setup;
+#[cfg(target_os = "linux")]
+pub mod sharing;
+#[cfg(target_os = "linux")]
 mod spec;
 #[cfg(target_os = "linux")]
+pub mod ssh;
+#[cfg(target_os = "linux")]
 mod start;
 #[cfg(target_os = "linux")]
 mod stdio;
+#[cfg(target_os = "linux")]
+pub mod systemd;
+#[cfg(target_os = "linux")]
+pub mod users;
+#[cfg(target_os = "linux")]
+pub mod watch;
+#[cfg(target_os = "linux")]
+pub mod x11;
 
 #[cfg(target_os = "linux")]
 pub use lifecycle::Container;
 #[cfg(target_os = "linux")]
-pub use spec::UserMount;
+pub use spec::{SpecFeatures, UserMount};
@@ -0,0 +1,49 @@
+//! Container engines inside the container
+//!
+//! dockerd and podman need three things the default spec leaves out: a
+//! writable cgroup hierarchy to put their containers in, shared mount
+//! propagation so their mounts reach the containers they start, and the
+//! netfilter, bridge and overlay kernel modules. With `nested_containers`
+//! the guest mounts cgroup2 (see [`super::cgroups`]), the spec adds a
+//! cgroup namespace with a writable `/sys/fs/cgroup` and shared root
+//! propagation, and the modules below are loaded where the kernel has them.
+//!
+//! The runtime enables every controller down to the container's cgroup.
+//! Engines then do what they do under any cgroup2 host: move their own
+//! processes out of the namespace root before delegating further (the
+//! docker:dind entrypoint does this).
+//!
+//! The container rootfs is an ext4 disk, so overlayfs storage drivers work
+//! on `/var/lib/docker` and `/var/lib/containers` as long as those are not
+//! virtio-fs volumes.
+
+use std::io;
+
+/// Modules engines use for bridge networking, NAT and image storage.
+///
+/// Missing ones are skipped: kernels often build these in, and engines
+/// report what they still lack.
+const ENGINE_MODULES: &[&str] = &[
+    "overlay",
+    "bridge",
+    "br_netfilter",
+    "veth",
+    "nf_nat",
+    "ip_tables",
+    "iptable_filter",
+    "iptable_nat",
+    "xt_MASQUERADE",
+    "xt_conntrack",
+    "xt_addrtype",
+];
+
+/// Prepare the guest before a container that runs container engines.
+pub fn prepare() -> io::Result<()> {
+    super::cgroups::mount_root()?;
+    for module in ENGINE_MODULES {
+        if let Err(e) = crate::modules::load_modules(&[module.to_string()]) {
+            tracing::debug!(module = %module, "Skipping kernel module: {}", e);
+        }
+    }
+    Ok(())
+}
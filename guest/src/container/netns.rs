@@ -0,0 +1,339 @@
+//! Per-exec network namespaces
+//!
+//! The container shares the guest's network namespace, so by default every
+//! exec can reach every other exec's local ports. An isolated exec instead
+//! starts as `boxlite-netns` (the agent binary, bind-mounted read-only into
+//! every container), which unshares a fresh network namespace as root, sets
+//! it up and then drops to the requested user and execs the command:
+//!
+//! ```text
+//! /run/boxlite-netns MODE UID GID PROGRAM [ARG...]
+//! ```
+//!
+//! `MODE` is `none` (no interfaces up), `loopback` or `veth`; `UID`/`GID`
+//! are `-` to stay root. For `veth` the agent notices the new namespace,
+//! creates a veth pair and moves one end in as `eth0` with a /30 from
+//! 10.254.0.0/16; the helper waits for its default route before running
+//! the command. The kernel deletes the pair once the namespace's last
+//! process exits. With IP forwarding off in the guest (the default), veth
+//! execs reach the box's main namespace but not each other or the outside.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use futures::stream::TryStreamExt;
+use nix::sched::{setns, unshare, CloneFlags};
+use nix::unistd::{setgid, setgroups, setuid, Gid, Pid, Uid};
+use std::net::Ipv4Addr;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::process::CommandExt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+/// Where the helper is mounted in the container.
+pub const CONTAINER_NETNS_HELPER: &str = "/run/boxlite-netns";
+
+/// Name the agent binary answers to as the namespace helper.
+pub const NETNS_HELPER_NAME: &str = "boxlite-netns";
+
+const USAGE: &str = "usage: boxlite-netns none|loopback|veth UID|- GID|- COMMAND [ARG...]";
+
+/// Longest wait for the namespace or its veth to appear.
+const SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// /30 subnets handed out in 10.254.0.0/16.
+const VETH_SUBNETS: u32 = 1 << 14;
+
+static NEXT_SUBNET: AtomicU32 = AtomicU32::new(0);
+
+/// Namespace setup for an isolated exec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    None,
+    Loopback,
+    Veth,
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::None => "none",
+            Mode::Loopback => "loopback",
+            Mode::Veth => "veth",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(Mode::None),
+            "loopback" => Some(Mode::Loopback),
+            "veth" => Some(Mode::Veth),
+            _ => None,
+        }
+    }
+}
+
+/// Program and arguments that run `program` through the helper.
+pub fn wrap(
+    mode: Mode,
+    user: Option<(u32, u32)>,
+    program: &str,
+    args: &[String],
+) -> (String, Vec<String>) {
+    let (uid, gid) = match user {
+        Some((uid, gid)) => (uid.to_string(), gid.to_string()),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    let mut wrapped = vec![mode.as_str().to_string(), uid, gid, program.to_string()];
+    wrapped.extend_from_slice(args);
+    (CONTAINER_NETNS_HELPER.to_string(), wrapped)
+}
+
+/// Helper entry point (`boxlite-netns` arguments after the program name).
+///
+/// Only returns on failure: 2 for bad usage, 1 if setup failed, 127 if
+/// the command could not be executed.
+pub async fn run_netns_helper(args: &[String]) -> i32 {
+    let [mode, uid, gid, program, program_args @ ..] = args else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let (Some(mode), Ok(user)) = (Mode::parse(mode), parse_user(uid, gid)) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    if let Err(e) = enter_namespace(mode).await {
+        eprintln!("boxlite-netns: {}", e);
+        return 1;
+    }
+    if let Some((uid, gid)) = user {
+        let dropped = setgroups(&[Gid::from_raw(gid)])
+            .and_then(|()| setgid(Gid::from_raw(gid)))
+            .and_then(|()| setuid(Uid::from_raw(uid)));
+        if let Err(e) = dropped {
+            eprintln!("boxlite-netns: failed to switch to {}:{}: {}", uid, gid, e);
+            return 1;
+        }
+    }
+
+    let e = std::process::Command::new(program)
+        .args(program_args)
+        .exec();
+    eprintln!("boxlite-netns: {}: {}", program, e);
+    127
+}
+
+fn parse_user(uid: &str, gid: &str) -> Result<Option<(u32, u32)>, ()> {
+    match (uid, gid) {
+        ("-", "-") => Ok(None),
+        (uid, gid) => Ok(Some((
+            uid.parse().map_err(|_| ())?,
+            gid.parse().map_err(|_| ())?,
+        ))),
+    }
+}
+
+/// Unshare a network namespace and wait until it is usable.
+async fn enter_namespace(mode: Mode) -> BoxliteResult<()> {
+    unshare(CloneFlags::CLONE_NEWNET).map_err(|e| {
+        BoxliteError::Internal(format!("failed to create network namespace: {}", e))
+    })?;
+    if mode == Mode::None {
+        return Ok(());
+    }
+
+    let (connection, handle, _) = rtnetlink::new_connection()
+        .map_err(|e| BoxliteError::Internal(format!("failed to open netlink: {}", e)))?;
+    tokio::spawn(connection);
+    set_up(&handle, "lo").await?;
+
+    if mode == Mode::Veth {
+        let deadline = Instant::now() + SETUP_TIMEOUT;
+        while !has_default_route() {
+            if Instant::now() > deadline {
+                return Err(BoxliteError::Internal(
+                    "timed out waiting for the veth link".to_string(),
+                ));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the current namespace has an IPv4 default route.
+fn has_default_route() -> bool {
+    std::fs::read_to_string("/proc/net/route").is_ok_and(|routes| {
+        routes
+            .lines()
+            .skip(1)
+            .any(|line| line.split_whitespace().nth(1) == Some("00000000"))
+    })
+}
+
+/// Link an exec started in [`Mode::Veth`] to the guest's namespace.
+pub async fn attach_veth(pid: Pid) -> BoxliteResult<()> {
+    let netns = wait_for_namespace(pid).await?;
+
+    let subnet = NEXT_SUBNET.fetch_add(1, Ordering::Relaxed) % VETH_SUBNETS;
+    let (host_addr, exec_addr) = veth_addresses(subnet);
+    let host_name = format!("bxe{}", subnet);
+    let peer_name = format!("bxp{}", subnet);
+
+    let (connection, handle, _) = rtnetlink::new_connection()
+        .map_err(|e| BoxliteError::Internal(format!("Failed to open netlink: {}", e)))?;
+    tokio::spawn(connection);
+    handle
+        .link()
+        .add()
+        .veth(host_name.clone(), peer_name.clone())
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to create veth: {}", e)))?;
+    let host_index = link_index(&handle, &host_name).await?;
+    let peer_index = link_index(&handle, &peer_name).await?;
+    handle
+        .address()
+        .add(host_index, host_addr.into(), 30)
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to address {}: {}", host_name, e)))?;
+    set_up(&handle, &host_name).await?;
+    handle
+        .link()
+        .set(peer_index)
+        .setns_by_fd(std::os::fd::AsRawFd::as_raw_fd(&netns))
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to move veth: {}", e)))?;
+
+    // Configure the exec's end from a netlink socket opened in its namespace.
+    // setns sticks to the thread, so use a throwaway one rather than the pool.
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let opened = setns(&netns, CloneFlags::CLONE_NEWNET)
+            .map_err(std::io::Error::from)
+            .and_then(|()| rtnetlink::new_connection());
+        let _ = tx.send(opened);
+    });
+    let (connection, handle, _) = rx
+        .await
+        .map_err(|_| BoxliteError::Internal("Netlink thread failed".to_string()))?
+        .map_err(|e| BoxliteError::Internal(format!("Failed to open netlink in exec: {}", e)))?;
+    tokio::spawn(connection);
+
+    let index = link_index(&handle, &peer_name).await?;
+    handle
+        .link()
+        .set(index)
+        .name("eth0".to_string())
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to rename veth: {}", e)))?;
+    handle
+        .address()
+        .add(index, exec_addr.into(), 30)
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to address eth0: {}", e)))?;
+    set_up(&handle, "eth0").await?;
+    handle
+        .route()
+        .add()
+        .v4()
+        .gateway(host_addr)
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to add default route: {}", e)))?;
+    Ok(())
+}
+
+/// Open the exec's network namespace once it has left the guest's.
+async fn wait_for_namespace(pid: Pid) -> BoxliteResult<std::fs::File> {
+    let own = std::fs::metadata("/proc/self/ns/net")?.ino();
+    let path = format!("/proc/{}/ns/net", pid);
+    let deadline = Instant::now() + SETUP_TIMEOUT;
+    loop {
+        match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.ino() != own => return Ok(std::fs::File::open(&path)?),
+            Ok(_) => {}
+            Err(e) => {
+                return Err(BoxliteError::Internal(format!(
+                    "Exec exited before its network was set up: {}",
+                    e
+                )))
+            }
+        }
+        if Instant::now() > deadline {
+            return Err(BoxliteError::Internal(
+                "Timed out waiting for the exec's network namespace".to_string(),
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Guest-side and exec-side addresses of the n-th /30.
+fn veth_addresses(subnet: u32) -> (Ipv4Addr, Ipv4Addr) {
+    let base = u32::from(Ipv4Addr::new(10, 254, 0, 0)) + subnet * 4;
+    (Ipv4Addr::from(base + 1), Ipv4Addr::from(base + 2))
+}
+
+async fn link_index(handle: &rtnetlink::Handle, name: &str) -> BoxliteResult<u32> {
+    handle
+        .link()
+        .get()
+        .match_name(name.to_string())
+        .execute()
+        .try_next()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to get {}: {}", name, e)))?
+        .map(|link| link.header.index)
+        .ok_or_else(|| BoxliteError::Internal(format!("{} not found", name)))
+}
+
+async fn set_up(handle: &rtnetlink::Handle, name: &str) -> BoxliteResult<()> {
+    let index = link_index(handle, name).await?;
+    handle
+        .link()
+        .set(index)
+        .up()
+        .execute()
+        .await
+        .map_err(|e| BoxliteError::Internal(format!("Failed to bring up {}: {}", name, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let args = vec!["-c".to_string(), "true".to_string()];
+        let (program, wrapped) = wrap(Mode::Veth, Some((1000, 100)), "sh", &args);
+        assert_eq!(program, CONTAINER_NETNS_HELPER);
+        assert_eq!(wrapped, ["veth", "1000", "100", "sh", "-c", "true"]);
+
+        let (_, wrapped) = wrap(Mode::None, None, "sh", &[]);
+        assert_eq!(wrapped, ["none", "-", "-", "sh"]);
+        assert_eq!(parse_user("-", "-"), Ok(None));
+        assert_eq!(parse_user("1000", "100"), Ok(Some((1000, 100))));
+        assert!(parse_user("root", "-").is_err());
+    }
+
+    #[test]
+    fn test_veth_addresses() {
+        assert_eq!(
+            veth_addresses(0),
+            (Ipv4Addr::new(10, 254, 0, 1), Ipv4Addr::new(10, 254, 0, 2))
+        );
+        assert_eq!(
+            veth_addresses(VETH_SUBNETS - 1),
+            (
+                Ipv4Addr::new(10, 254, 255, 253),
+                Ipv4Addr::new(10, 254, 255, 254)
+            )
+        );
+    }
+}
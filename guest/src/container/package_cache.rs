@@ -0,0 +1,64 @@
+//! Package manager cache configuration
+//!
+//! When the host mounts its shared package cache into the container, apt,
+//! pip and npm are pointed at per-manager subdirectories of it: apt through
+//! a config snippet in the rootfs, pip and npm through environment variables.
+
+use std::io;
+use std::path::Path;
+
+/// apt config snippet name (sorts after distro defaults such as docker-clean).
+const APT_CONF: &str = "etc/apt/apt.conf.d/99boxlite-package-cache";
+
+/// Configure package managers in `rootfs` to use the cache at `cache_dir`
+/// (a container path), adding their variables to `env`.
+///
+/// Variables already set by the image or the user are left alone.
+pub fn configure(rootfs: &Path, cache_dir: &str, env: &mut Vec<String>) -> io::Result<()> {
+    for (key, subdir) in [("PIP_CACHE_DIR", "pip"), ("npm_config_cache", "npm")] {
+        let prefix = format!("{}=", key);
+        if !env.iter().any(|e| e.starts_with(&prefix)) {
+            env.push(format!("{}{}/{}", prefix, cache_dir, subdir));
+        }
+    }
+
+    // Only images that ship apt get the snippet
+    if rootfs.join("etc/apt/apt.conf.d").is_dir() {
+        std::fs::write(
+            rootfs.join(APT_CONF),
+            format!(
+                "Dir::Cache::Archives \"{}/apt/archives\";\n\
+                 APT::Keep-Downloaded-Packages \"true\";\n\
+                 Binary::apt::APT::Keep-Downloaded-Packages \"true\";\n",
+                cache_dir
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configure_env_and_apt() {
+        let dir = tempfile::tempdir().unwrap();
+        let rootfs = dir.path();
+        std::fs::create_dir_all(rootfs.join("etc/apt/apt.conf.d")).unwrap();
+
+        let mut env = vec!["PIP_CACHE_DIR=/custom".to_string()];
+        configure(rootfs, "/var/cache/boxlite", &mut env).unwrap();
+
+        assert_eq!(
+            env,
+            vec![
+                "PIP_CACHE_DIR=/custom".to_string(),
+                "npm_config_cache=/var/cache/boxlite/npm".to_string(),
+            ]
+        );
+        let apt = std::fs::read_to_string(rootfs.join(APT_CONF)).unwrap();
+        assert!(apt.contains("Dir::Cache::Archives \"/var/cache/boxlite/apt/archives\";"));
+    }
+}
@@ -0,0 +1,385 @@
+//! Package installation with the image's package manager
+//!
+//! Finds which of apt, apk and dnf the image ships and installs packages
+//! with it non-interactively: an optional index refresh, the install
+//! itself, then a query for the versions that ended up installed. Output is
+//! streamed to the host as it comes and the run ends with a result the host
+//! can act on. The container's /tmp is a tmpfs, which is what apt and the
+//! others need for their temporary files, so no further setup is done.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use boxlite_shared::{
+    install_packages_event, InstallPackagesEvent, InstallPackagesResult, InstalledPackage,
+};
+use futures::StreamExt;
+use nix::sys::wait::{waitpid, WaitStatus};
+use tokio::sync::{mpsc, Mutex};
+use tonic::Status;
+
+use super::Container;
+
+/// Characters package specs may contain, besides ASCII alphanumerics.
+const PACKAGE_PUNCTUATION: &str = "._+-:=<>~/@";
+
+/// A package manager the agent knows how to drive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackageManager {
+    Apt,
+    Apk,
+    Dnf,
+}
+
+/// One command to run in the container.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Step {
+    pub program: &'static str,
+    pub args: Vec<String>,
+}
+
+impl PackageManager {
+    /// The package manager installed in `rootfs`, preferring apt, then apk.
+    pub fn detect(rootfs: &Path) -> Option<Self> {
+        [
+            ("usr/bin/apt-get", PackageManager::Apt),
+            ("sbin/apk", PackageManager::Apk),
+            ("usr/bin/dnf", PackageManager::Dnf),
+        ]
+        .into_iter()
+        // Not followed: absolute symlinks would resolve outside the rootfs
+        .find(|(path, _)| rootfs.join(path).symlink_metadata().is_ok())
+        .map(|(_, manager)| manager)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt",
+            PackageManager::Apk => "apk",
+            PackageManager::Dnf => "dnf",
+        }
+    }
+
+    /// Commands that install `packages`, in order.
+    pub fn install_steps(self, packages: &[String], update_index: bool) -> Vec<Step> {
+        let with = |program, args: &[&str]| Step {
+            program,
+            args: args
+                .iter()
+                .map(|a| a.to_string())
+                .chain(packages.iter().cloned())
+                .collect(),
+        };
+        match self {
+            PackageManager::Apt => {
+                let mut steps = Vec::new();
+                if update_index {
+                    steps.push(Step {
+                        program: "apt-get",
+                        args: vec!["update".to_string(), "-q".to_string()],
+                    });
+                }
+                steps.push(with(
+                    "apt-get",
+                    &["install", "-y", "-q", "--no-install-recommends"],
+                ));
+                steps
+            }
+            PackageManager::Apk if update_index => {
+                vec![with("apk", &["add", "--no-progress", "--update-cache"])]
+            }
+            PackageManager::Apk => vec![with("apk", &["add", "--no-progress"])],
+            PackageManager::Dnf if update_index => {
+                vec![with("dnf", &["install", "-y", "--refresh"])]
+            }
+            PackageManager::Dnf => vec![with("dnf", &["install", "-y"])],
+        }
+    }
+
+    /// Command that lists the installed versions of `packages`.
+    pub fn query_step(self, packages: &[String]) -> Step {
+        let names = packages.iter().map(|p| self.base_name(p).to_string());
+        match self {
+            PackageManager::Apt => Step {
+                program: "dpkg-query",
+                args: std::iter::once("-W".to_string())
+                    .chain(std::iter::once("-f=${Package}\t${Version}\n".to_string()))
+                    .chain(names)
+                    .collect(),
+            },
+            PackageManager::Apk => Step {
+                program: "apk",
+                args: ["list", "--installed"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(names)
+                    .collect(),
+            },
+            PackageManager::Dnf => Step {
+                program: "rpm",
+                args: ["-q", "--qf", "%{NAME}\t%{VERSION}-%{RELEASE}\n"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(names)
+                    .collect(),
+            },
+        }
+    }
+
+    /// Installed packages in the output of [`Self::query_step`].
+    pub fn parse_query(self, output: &str, packages: &[String]) -> Vec<InstalledPackage> {
+        let names: Vec<&str> = packages.iter().map(|p| self.base_name(p)).collect();
+        output
+            .lines()
+            .filter_map(|line| match self {
+                PackageManager::Apt | PackageManager::Dnf => {
+                    let (name, version) = line.split_once('\t')?;
+                    Some((name.to_string(), version.trim().to_string()))
+                }
+                // curl-8.5.0-r0 x86_64 {curl} (curl) [installed]
+                PackageManager::Apk => {
+                    let full = line.split_whitespace().next()?;
+                    names.iter().find_map(|name| {
+                        let version = full.strip_prefix(name)?.strip_prefix('-')?;
+                        // A digit starts the version, so curl does not match curl-dev
+                        version
+                            .starts_with(|c: char| c.is_ascii_digit())
+                            .then(|| (name.to_string(), version.to_string()))
+                    })
+                }
+            })
+            .filter(|(name, version)| !version.is_empty() && names.contains(&name.as_str()))
+            .map(|(name, version)| InstalledPackage { name, version })
+            .collect()
+    }
+
+    /// Package name without the version or release it was requested with.
+    fn base_name(self, spec: &str) -> &str {
+        match self {
+            // dnf takes name-version, which cannot be split reliably
+            PackageManager::Dnf => spec,
+            PackageManager::Apt | PackageManager::Apk => {
+                spec.split(['=', '<', '>', '~', '/']).next().unwrap_or(spec)
+            }
+        }
+    }
+}
+
+/// Check that `spec` names a package and cannot be taken for an option.
+pub fn validate_package(spec: &str) -> Result<(), String> {
+    let valid = spec
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && spec
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || PACKAGE_PUNCTUATION.contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid package name: '{}'", spec))
+    }
+}
+
+/// Install `packages` in `container`, sending output and then the result.
+pub async fn install(
+    container: Arc<Mutex<Container>>,
+    packages: Vec<String>,
+    update_index: bool,
+    events: mpsc::Sender<Result<InstallPackagesEvent, Status>>,
+) {
+    let result = run(&container, &packages, update_index, &events).await;
+    tracing::info!(
+        package_manager = %result.package_manager,
+        exit_code = result.exit_code,
+        installed = result.installed.len(),
+        error = %result.error,
+        "Package installation finished"
+    );
+    let _ = events
+        .send(Ok(InstallPackagesEvent {
+            event: Some(install_packages_event::Event::Result(result)),
+        }))
+        .await;
+}
+
+async fn run(
+    container: &Mutex<Container>,
+    packages: &[String],
+    update_index: bool,
+    events: &mpsc::Sender<Result<InstallPackagesEvent, Status>>,
+) -> InstallPackagesResult {
+    let failed = |package_manager: &str, exit_code, error| InstallPackagesResult {
+        package_manager: package_manager.to_string(),
+        exit_code,
+        installed: Vec::new(),
+        error,
+    };
+
+    let Some(pid) = container.lock().await.init_pid() else {
+        return failed("", -1, "Container is not running".to_string());
+    };
+    let rootfs = PathBuf::from(format!("/proc/{}/root", pid));
+    let Some(manager) = PackageManager::detect(&rootfs) else {
+        return failed("", -1, "Image has no apt, apk or dnf".to_string());
+    };
+
+    for step in manager.install_steps(packages, update_index) {
+        match run_step(container, &step, Some(events)).await {
+            Ok((0, _)) => {}
+            Ok((code, _)) => {
+                let error = format!("{} exited with code {}", step.program, code);
+                return failed(manager.name(), code, error);
+            }
+            Err(e) => return failed(manager.name(), -1, e),
+        }
+    }
+
+    // A failed query leaves the list empty; the install itself succeeded
+    let installed = match run_step(container, &manager.query_step(packages), None).await {
+        Ok((_, output)) => manager.parse_query(&output, packages),
+        Err(e) => {
+            tracing::warn!("Failed to query installed packages: {}", e);
+            Vec::new()
+        }
+    };
+    InstallPackagesResult {
+        package_manager: manager.name().to_string(),
+        exit_code: 0,
+        installed,
+        error: String::new(),
+    }
+}
+
+/// Run `step` to completion, returning its exit code and, unless the output
+/// is forwarded to `events`, what it printed.
+async fn run_step(
+    container: &Mutex<Container>,
+    step: &Step,
+    events: Option<&mpsc::Sender<Result<InstallPackagesEvent, Status>>>,
+) -> Result<(i32, String), String> {
+    let mut handle = container
+        .lock()
+        .await
+        .cmd()
+        .program(step.program)
+        .args(&step.args)
+        .env("DEBIAN_FRONTEND", "noninteractive")
+        .env("TMPDIR", "/tmp")
+        .spawn()
+        .await
+        .map_err(|e| format!("Failed to start {}: {}", step.program, e))?;
+
+    let mut output = futures::stream::select(
+        futures::stream::iter(handle.stdout()).flatten().boxed(),
+        futures::stream::iter(handle.stderr()).flatten().boxed(),
+    );
+    let mut captured = Vec::new();
+    while let Some(chunk) = output.next().await {
+        match events {
+            Some(events) => {
+                let event = InstallPackagesEvent {
+                    event: Some(install_packages_event::Event::Output(
+                        String::from_utf8_lossy(&chunk).into_owned(),
+                    )),
+                };
+                // The host going away does not stop the install
+                let _ = events.send(Ok(event)).await;
+            }
+            None => captured.extend_from_slice(&chunk),
+        }
+    }
+
+    let pid = handle.pid();
+    let status = tokio::task::spawn_blocking(move || waitpid(pid, None))
+        .await
+        .map_err(|e| format!("spawn_blocking failed: {}", e))?
+        .map_err(|e| format!("waitpid failed: {}", e))?;
+    let exit_code = match status {
+        WaitStatus::Exited(_, code) => code,
+        WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+        other => return Err(format!("Unexpected wait status: {:?}", other)),
+    };
+    Ok((exit_code, String::from_utf8_lossy(&captured).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect_and_steps() {
+        let rootfs = tempfile::tempdir().unwrap();
+        assert_eq!(PackageManager::detect(rootfs.path()), None);
+        std::fs::create_dir_all(rootfs.path().join("sbin")).unwrap();
+        std::os::unix::fs::symlink("/bin/busybox", rootfs.path().join("sbin/apk")).unwrap();
+        assert_eq!(
+            PackageManager::detect(rootfs.path()),
+            Some(PackageManager::Apk)
+        );
+
+        let packages = strings(&["curl", "git=2.43.0-1"]);
+        let steps = PackageManager::Apt.install_steps(&packages, true);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].args, ["update", "-q"]);
+        assert_eq!(
+            steps[1].args,
+            [
+                "install",
+                "-y",
+                "-q",
+                "--no-install-recommends",
+                "curl",
+                "git=2.43.0-1"
+            ]
+        );
+        let query = PackageManager::Apt.query_step(&packages);
+        assert_eq!(query.args[2..], ["curl", "git"]);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let packages = strings(&["curl", "git"]);
+        let apt = "curl\t7.88.1-10+deb12u5\ngit\t\nlibc6\t2.36-9\n";
+        let installed = PackageManager::Apt.parse_query(apt, &packages);
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].version, "7.88.1-10+deb12u5");
+
+        let apk = "curl-dev-8.5.0-r0 x86_64 {curl} (curl) [installed]\n\
+                   curl-8.5.0-r0 x86_64 {curl} (curl) [installed]\n";
+        let installed = PackageManager::Apk.parse_query(apk, &packages);
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].name, "curl");
+        assert_eq!(installed[0].version, "8.5.0-r0");
+
+        let rpm = "curl\t8.2.1-4.fc39\npackage git is not installed\n";
+        assert_eq!(PackageManager::Dnf.parse_query(rpm, &packages).len(), 1);
+    }
+
+    #[test]
+    fn test_validate_package() {
+        for spec in [
+            "curl",
+            "python3.11",
+            "libstdc++6",
+            "git=1:2.43.0-1",
+            "pkg/bookworm",
+        ] {
+            assert!(validate_package(spec).is_ok(), "{} rejected", spec);
+        }
+        for spec in [
+            "",
+            "-y",
+            "--allow-unauthenticated",
+            "a b",
+            "curl;reboot",
+            "$(id)",
+        ] {
+            assert!(validate_package(spec).is_err(), "{} accepted", spec);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! First-mount population of volumes
+//!
+//! A volume mounted with `populate` (the home volume) that is still empty
+//! is filled before the container sees it, so a new home directory starts
+//! out like the image's: with the files the image has at the mount point,
+//! or `/etc/skel` if it has none there. A volume with anything in it is
+//! left alone, which keeps later boxes from overwriting what earlier ones
+//! wrote.
+
+use std::io;
+use std::os::unix::fs::{lchown, symlink, MetadataExt, PermissionsExt};
+use std::path::Path;
+use tracing::{debug, warn};
+
+const SKELETON: &str = "etc/skel";
+
+/// Fill `volume` from `rootfs` if it is empty; returns whether it was.
+///
+/// `destination` is where the volume is mounted in the container.
+pub fn populate(volume: &Path, rootfs: &Path, destination: &str) -> io::Result<bool> {
+    if std::fs::read_dir(volume)?.next().is_some() {
+        return Ok(false);
+    }
+
+    let image_dir = rootfs.join(destination.trim_start_matches('/'));
+    let image_dir = std::fs::symlink_metadata(&image_dir)
+        .is_ok_and(|m| m.is_dir())
+        .then_some(image_dir);
+    let source = match &image_dir {
+        Some(dir) if std::fs::read_dir(dir)?.next().is_some() => dir.clone(),
+        _ => rootfs.join(SKELETON),
+    };
+    if source.is_dir() {
+        debug!("Populating {} from {}", volume.display(), source.display());
+        copy_tree(&source, volume)?;
+    }
+
+    // The volume root takes the place of the image's directory
+    if let Some(dir) = &image_dir {
+        let metadata = std::fs::metadata(dir)?;
+        std::fs::set_permissions(
+            volume,
+            std::fs::Permissions::from_mode(metadata.mode() & 0o7777),
+        )?;
+        chown_tree(volume, metadata.uid(), metadata.gid());
+    }
+    Ok(true)
+}
+
+/// Copy the contents of `src` into `dst`, keeping modes, owners and
+/// symlinks.
+fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        let metadata = entry.metadata()?;
+        let file_type = metadata.file_type();
+
+        if file_type.is_symlink() {
+            symlink(std::fs::read_link(&from)?, &to)?;
+        } else if file_type.is_dir() {
+            std::fs::create_dir(&to)?;
+            copy_tree(&from, &to)?;
+            std::fs::set_permissions(&to, metadata.permissions())?;
+        } else if file_type.is_file() {
+            std::fs::copy(&from, &to)?;
+        } else {
+            // Sockets and device nodes have no place in a home directory
+            continue;
+        }
+        set_owner(&to, metadata.uid(), metadata.gid());
+    }
+    Ok(())
+}
+
+/// Give everything under `dir`, and `dir` itself, to `uid`:`gid`.
+fn chown_tree(dir: &Path, uid: u32, gid: u32) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                chown_tree(&path, uid, gid);
+            } else {
+                set_owner(&path, uid, gid);
+            }
+        }
+    }
+    set_owner(dir, uid, gid);
+}
+
+/// Best effort: a host share may not let the guest change owners, and
+/// the files are usable by the workload either way.
+fn set_owner(path: &Path, uid: u32, gid: u32) {
+    if let Err(e) = lchown(path, Some(uid), Some(gid)) {
+        warn!("Failed to chown {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_populate() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let skel = rootfs.path().join(SKELETON);
+        std::fs::create_dir_all(skel.join(".config")).unwrap();
+        std::fs::write(skel.join(".bashrc"), "PS1='$ '\n").unwrap();
+        symlink(".bashrc", skel.join(".profile")).unwrap();
+
+        // No home directory in the image: /etc/skel
+        let volume = tempfile::tempdir().unwrap();
+        assert!(populate(volume.path(), rootfs.path(), "/root").unwrap());
+        assert_eq!(
+            std::fs::read_to_string(volume.path().join(".bashrc")).unwrap(),
+            "PS1='$ '\n"
+        );
+        assert!(volume.path().join(".config").is_dir());
+        assert_eq!(
+            std::fs::read_link(volume.path().join(".profile")).unwrap(),
+            Path::new(".bashrc")
+        );
+
+        // Left alone once it has files
+        std::fs::write(volume.path().join(".bashrc"), "edited\n").unwrap();
+        assert!(!populate(volume.path(), rootfs.path(), "/root").unwrap());
+        assert_eq!(
+            std::fs::read_to_string(volume.path().join(".bashrc")).unwrap(),
+            "edited\n"
+        );
+
+        // The image's own home directory wins over /etc/skel
+        std::fs::create_dir(rootfs.path().join("root")).unwrap();
+        std::fs::write(rootfs.path().join("root/.npmrc"), "cache=/tmp\n").unwrap();
+        let volume = tempfile::tempdir().unwrap();
+        assert!(populate(volume.path(), rootfs.path(), "/root").unwrap());
+        assert!(volume.path().join(".npmrc").is_file());
+        assert!(!volume.path().join(".bashrc").exists());
+    }
+}
@@ -0,0 +1,199 @@
+//! Per-exec private filesystem views
+//!
+//! Execs in one container share its mounts, so concurrent jobs trip over
+//! each other's files in /tmp and in whatever scratch directories they
+//! write to. An exec with a private filesystem starts as
+//! `boxlite-private-fs` (the agent binary, bind-mounted read-only into
+//! every container next to `boxlite-netns`), which unshares a mount
+//! namespace as root, mounts its view and then drops to the requested user
+//! and execs the command:
+//!
+//! ```text
+//! /run/boxlite-private-fs UID|- GID|- COUNT [PATH...] PROGRAM [ARG...]
+//! ```
+//!
+//! The view is a fresh tmpfs at /tmp, plus an overlay over each of the
+//! COUNT directories given, with its upper layer in memory: the job sees
+//! the directory's contents and writes to it freely, other execs never see
+//! the writes. The overlays' layers live on a tmpfs that the final /tmp
+//! mount hides from the job. Nothing propagates back to the container, and
+//! the kernel discards it all once the last process in the namespace exits.
+
+use nix::mount::{mount, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::unistd::{setgid, setgroups, setuid, Gid, Uid};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+
+use super::etc_overlay;
+
+/// Where the helper is mounted in the container.
+pub const CONTAINER_PRIVATE_FS_HELPER: &str = "/run/boxlite-private-fs";
+
+/// Name the agent binary answers to as the private filesystem helper.
+pub const PRIVATE_FS_HELPER_NAME: &str = "boxlite-private-fs";
+
+const USAGE: &str = "usage: boxlite-private-fs UID|- GID|- COUNT [PATH...] COMMAND [ARG...]";
+
+/// Program and arguments that run `program` in a private view with
+/// overlays over `overlay_paths`.
+pub fn wrap(
+    overlay_paths: &[String],
+    user: Option<(u32, u32)>,
+    program: &str,
+    args: &[String],
+) -> (String, Vec<String>) {
+    let (uid, gid) = match user {
+        Some((uid, gid)) => (uid.to_string(), gid.to_string()),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    let mut wrapped = vec![uid, gid, overlay_paths.len().to_string()];
+    wrapped.extend_from_slice(overlay_paths);
+    wrapped.push(program.to_string());
+    wrapped.extend_from_slice(args);
+    (CONTAINER_PRIVATE_FS_HELPER.to_string(), wrapped)
+}
+
+/// Helper entry point (`boxlite-private-fs` arguments after the program
+/// name).
+///
+/// Only returns on failure: 2 for bad usage, 1 if the view could not be
+/// set up, 127 if the command could not be executed.
+pub fn run_private_fs_helper(args: &[String]) -> i32 {
+    let Some((user, overlay_paths, program, program_args)) = parse(args) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    if let Err(e) = enter_view(overlay_paths) {
+        eprintln!("boxlite-private-fs: {}", e);
+        return 1;
+    }
+    if let Some((uid, gid)) = user {
+        let dropped = setgroups(&[Gid::from_raw(gid)])
+            .and_then(|()| setgid(Gid::from_raw(gid)))
+            .and_then(|()| setuid(Uid::from_raw(uid)));
+        if let Err(e) = dropped {
+            eprintln!(
+                "boxlite-private-fs: failed to switch to {}:{}: {}",
+                uid, gid, e
+            );
+            return 1;
+        }
+    }
+
+    let e = std::process::Command::new(program)
+        .args(program_args)
+        .exec();
+    eprintln!("boxlite-private-fs: {}: {}", program, e);
+    127
+}
+
+type Parsed<'a> = (Option<(u32, u32)>, &'a [String], &'a str, &'a [String]);
+
+fn parse(args: &[String]) -> Option<Parsed<'_>> {
+    let [uid, gid, count, rest @ ..] = args else {
+        return None;
+    };
+    let user = match (uid.as_str(), gid.as_str()) {
+        ("-", "-") => None,
+        (uid, gid) => Some((uid.parse().ok()?, gid.parse().ok()?)),
+    };
+    let count: usize = count.parse().ok()?;
+    let (overlay_paths, command) = (rest.get(..count)?, rest.get(count..)?);
+    let [program, program_args @ ..] = command else {
+        return None;
+    };
+    Some((user, overlay_paths, program.as_str(), program_args))
+}
+
+/// Unshare a mount namespace and mount the view in it.
+fn enter_view(overlay_paths: &[String]) -> io::Result<()> {
+    for path in overlay_paths {
+        if !Path::new(path).is_absolute() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("overlay path {} is not absolute", path),
+            ));
+        }
+    }
+
+    unshare(CloneFlags::CLONE_NEWNS).map_err(io::Error::from)?;
+    // Keep the view's mounts from propagating back to the container
+    mount(
+        None::<&str>,
+        "/",
+        None::<&str>,
+        MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+        None::<&str>,
+    )
+    .map_err(io::Error::from)?;
+
+    let tmp = Path::new("/tmp");
+    std::fs::create_dir_all(tmp)?;
+    if !overlay_paths.is_empty() {
+        // The layers go on a tmpfs of their own, hidden by the /tmp below
+        mount_tmpfs(tmp, "mode=0700")?;
+        for (i, path) in overlay_paths.iter().enumerate() {
+            etc_overlay::mount(Path::new(path), &tmp.join(i.to_string())).map_err(|e| {
+                io::Error::new(e.kind(), format!("failed to overlay {}: {}", path, e))
+            })?;
+        }
+    }
+    mount_tmpfs(tmp, "mode=1777")
+}
+
+fn mount_tmpfs(target: &Path, options: &str) -> io::Result<()> {
+    mount(
+        Some("tmpfs"),
+        target,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(options),
+    )
+    .map_err(|e| {
+        io::Error::other(format!(
+            "failed to mount tmpfs on {}: {}",
+            target.display(),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_parse_roundtrip() {
+        let overlay_paths = vec!["/work".to_string(), "/var/cache".to_string()];
+        let (program, args) = wrap(
+            &overlay_paths,
+            Some((1000, 100)),
+            "make",
+            &["-j4".to_string()],
+        );
+        assert_eq!(program, CONTAINER_PRIVATE_FS_HELPER);
+        assert_eq!(
+            parse(&args),
+            Some((
+                Some((1000, 100)),
+                overlay_paths.as_slice(),
+                "make",
+                ["-j4".to_string()].as_slice()
+            ))
+        );
+
+        let (_, args) = wrap(&[], None, "sh", &[]);
+        assert_eq!(args, ["-", "-", "0", "sh"]);
+        assert_eq!(
+            parse(&args),
+            Some((None, [].as_slice(), "sh", [].as_slice()))
+        );
+
+        // Fewer paths than counted leaves no command
+        let args: Vec<String> = ["-", "-", "2", "/work"].map(String::from).to_vec();
+        assert_eq!(parse(&args), None);
+    }
+}
@@ -0,0 +1,167 @@
+//! Process listing for Container.ListProcesses
+//!
+//! A process belongs to the container when it shares the pid namespace of
+//! the container's init, which covers both init's descendants and commands
+//! the agent executes into the container. Pids are reported as the
+//! container sees them, from the last `NSpid` entry of each process.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use boxlite_shared::ProcessInfo;
+
+/// Fields of `/proc/<pid>/stat` used here.
+#[derive(Debug, PartialEq, Eq)]
+struct Stat {
+    name: String,
+    state: char,
+    ppid: u32,
+    /// utime + stime, in clock ticks
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Processes sharing the pid namespace of `init_pid`, by pid.
+pub fn list(init_pid: i32) -> io::Result<Vec<ProcessInfo>> {
+    let proc = Path::new("/proc");
+    let namespace = fs::read_link(proc.join(init_pid.to_string()).join("ns/pid"))?;
+    // SAFETY: sysconf has no preconditions
+    let (ticks, page_size) = unsafe {
+        (
+            nix::libc::sysconf(nix::libc::_SC_CLK_TCK).max(1) as u64,
+            nix::libc::sysconf(nix::libc::_SC_PAGESIZE).max(0) as u64,
+        )
+    };
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(proc)?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let dir = entry.path();
+        // Processes exit while we look; skip whatever vanished
+        if fs::read_link(dir.join("ns/pid")).ok().as_ref() != Some(&namespace) {
+            continue;
+        }
+        let (Ok(stat), Ok(status)) = (
+            fs::read_to_string(dir.join("stat")),
+            fs::read_to_string(dir.join("status")),
+        ) else {
+            continue;
+        };
+        let Some(stat) = parse_stat(&stat) else {
+            continue;
+        };
+        let (uid, ns_pid) = parse_status(&status);
+        let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+        found.push((pid, ns_pid.unwrap_or(pid), uid, stat, cmdline));
+    }
+
+    // Parents are read in the agent's namespace; translate them too
+    let ns_pids: HashMap<u32, u32> = found.iter().map(|p| (p.0, p.1)).collect();
+    let mut processes: Vec<ProcessInfo> = found
+        .into_iter()
+        .map(|(_, pid, uid, stat, cmdline)| ProcessInfo {
+            pid,
+            ppid: ns_pids.get(&stat.ppid).copied().unwrap_or(0),
+            uid,
+            state: stat.state.to_string(),
+            command: command_line(&cmdline, &stat.name),
+            rss_bytes: stat.rss_pages * page_size,
+            cpu_time_ms: stat.cpu_ticks * 1000 / ticks,
+        })
+        .collect();
+    processes.sort_by_key(|p| p.pid);
+    Ok(processes)
+}
+
+fn parse_stat(content: &str) -> Option<Stat> {
+    // The name is parenthesized and may itself hold spaces and parentheses
+    let open = content.find('(')?;
+    let close = content.rfind(')')?;
+    let name = content.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = content.get(close + 1..)?.split_whitespace().collect();
+    let field = |index: usize| fields.get(index)?.parse::<u64>().ok();
+    Some(Stat {
+        name,
+        state: fields.first()?.chars().next()?,
+        ppid: field(1)? as u32,
+        cpu_ticks: field(11)? + field(12)?,
+        rss_pages: field(21)?,
+    })
+}
+
+/// Real uid and innermost namespace pid from `/proc/<pid>/status`.
+fn parse_status(content: &str) -> (u32, Option<u32>) {
+    let mut uid = 0;
+    let mut ns_pid = None;
+    for line in content.lines() {
+        if let Some(rest) = line.strip_prefix("Uid:") {
+            uid = rest
+                .split_whitespace()
+                .next()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+        } else if let Some(rest) = line.strip_prefix("NSpid:") {
+            ns_pid = rest.split_whitespace().last().and_then(|s| s.parse().ok());
+        }
+    }
+    (uid, ns_pid)
+}
+
+fn command_line(cmdline: &[u8], name: &str) -> String {
+    let args: Vec<String> = cmdline
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if args.is_empty() {
+        format!("[{}]", name)
+    } else {
+        args.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let stat = "42 (my (odd) cmd) S 7 42 42 0 -1 4194560 100 0 0 0 \
+                    15 5 0 0 20 0 1 0 1000 10485760 256 18446744073709551615";
+        assert_eq!(
+            parse_stat(stat),
+            Some(Stat {
+                name: "my (odd) cmd".to_string(),
+                state: 'S',
+                ppid: 7,
+                cpu_ticks: 20,
+                rss_pages: 256,
+            })
+        );
+        assert_eq!(parse_stat("42 (truncated"), None);
+
+        let status = "Name:\tsh\nUid:\t1000\t1000\t1000\t1000\nNSpid:\t312\t5\n";
+        assert_eq!(parse_status(status), (1000, Some(5)));
+
+        assert_eq!(
+            command_line(b"python3\0-m\0http.server\0", "python3"),
+            "python3 -m http.server"
+        );
+        assert_eq!(command_line(b"", "kworker"), "[kworker]");
+    }
+
+    #[test]
+    fn test_list_includes_self() {
+        let me = std::process::id();
+        let processes = list(me as i32).unwrap();
+        assert!(processes.iter().any(|p| p.command.contains("guest")));
+    }
+}
@@ -0,0 +1,245 @@
+//! Page-cache readahead hints for the container rootfs
+//!
+//! A cold boot reads the same binaries and libraries every time, a page
+//! fault at a time. Once the container has been up for a while the host
+//! asks which ranges of rootfs files are resident in the page cache
+//! (mincore on a read-only mapping of each file) and keeps them with the
+//! base disk. Later boots from that disk hand the ranges back, and they are
+//! read ahead in the background while the workload starts.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use boxlite_shared::ReadaheadHint;
+use tracing::{debug, info};
+
+/// Hints recorded per rootfs; the walk stops once this many are found.
+pub const MAX_HINTS: usize = 20_000;
+
+/// Larger files are never recorded, whatever of them is resident.
+const MAX_FILE_SIZE: u64 = 1 << 30;
+
+/// Resident runs this many pages apart or closer are one hint.
+const MERGE_GAP_PAGES: u64 = 8;
+
+/// The resident ranges of every file on the rootfs filesystem.
+pub fn record(rootfs: &Path) -> io::Result<Vec<ReadaheadHint>> {
+    let dev = std::fs::metadata(rootfs)?.dev();
+    let page = page_size();
+    let mut hints = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(rootfs.join(&dir)) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let relative = dir.join(entry.file_name());
+            // Not followed, so symlinks are neither files nor directories
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            // Volumes and other filesystems mounted into the rootfs
+            if metadata.dev() != dev {
+                continue;
+            }
+            if metadata.is_dir() {
+                dirs.push(relative);
+                continue;
+            }
+            if !metadata.is_file() || metadata.len() == 0 || metadata.len() > MAX_FILE_SIZE {
+                continue;
+            }
+            let Ok(pages) = resident_pages(&entry.path(), metadata.len(), page) else {
+                continue;
+            };
+            let path = relative.to_string_lossy().into_owned();
+            for (offset, length) in runs(&pages, page, metadata.len()) {
+                hints.push(ReadaheadHint {
+                    path: path.clone(),
+                    offset,
+                    length,
+                });
+                if hints.len() >= MAX_HINTS {
+                    return Ok(hints);
+                }
+            }
+        }
+    }
+    Ok(hints)
+}
+
+/// Read `hints` ahead on a background thread.
+pub fn apply(rootfs: PathBuf, hints: Vec<ReadaheadHint>) {
+    let spawned = std::thread::Builder::new()
+        .name("readahead".to_string())
+        .spawn(move || {
+            let started = Instant::now();
+            let mut issued = 0usize;
+            let mut open: Option<(String, File)> = None;
+            for hint in &hints {
+                // Hints come from the host; nothing outside the rootfs
+                let confined = Path::new(&hint.path)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)));
+                if !confined {
+                    continue;
+                }
+                if open.as_ref().is_none_or(|(path, _)| path != &hint.path) {
+                    open = OpenOptions::new()
+                        .read(true)
+                        .custom_flags(libc::O_NOFOLLOW | libc::O_NOATIME)
+                        .open(rootfs.join(&hint.path))
+                        .ok()
+                        .map(|file| (hint.path.clone(), file));
+                }
+                let Some((_, file)) = &open else {
+                    continue;
+                };
+                // SAFETY: plain syscall on an open fd
+                let rc = unsafe {
+                    libc::posix_fadvise(
+                        file.as_raw_fd(),
+                        hint.offset as libc::off_t,
+                        hint.length as libc::off_t,
+                        libc::POSIX_FADV_WILLNEED,
+                    )
+                };
+                if rc == 0 {
+                    issued += 1;
+                }
+            }
+            info!(
+                hints = hints.len(),
+                issued,
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "Rootfs readahead issued"
+            );
+        });
+    if let Err(e) = spawned {
+        debug!("Failed to start readahead: {}", e);
+    }
+}
+
+/// mincore of `path`: one byte per page, bit 0 set when resident.
+fn resident_pages(path: &Path, len: u64, page: u64) -> io::Result<Vec<u8>> {
+    // Looking must not count as a read
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOATIME)
+        .open(path)
+        .or_else(|_| File::open(path))?;
+    let len = len as usize;
+    let mut pages = vec![0u8; (len as u64).div_ceil(page) as usize];
+    // SAFETY: the mapping is only passed to mincore and unmapped before
+    // returning; mincore writes one byte per page into `pages`
+    unsafe {
+        let addr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        );
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let rc = libc::mincore(addr, len, pages.as_mut_ptr());
+        let error = io::Error::last_os_error();
+        libc::munmap(addr, len);
+        if rc != 0 {
+            return Err(error);
+        }
+    }
+    Ok(pages)
+}
+
+/// Resident runs as (offset, length) byte ranges within a `len`-byte file.
+fn runs(pages: &[u8], page: u64, len: u64) -> Vec<(u64, u64)> {
+    let mut runs: Vec<(u64, u64)> = Vec::new();
+    for (index, _) in pages.iter().enumerate().filter(|(_, p)| *p & 1 != 0) {
+        let start = index as u64 * page;
+        let end = (start + page).min(len);
+        match runs.last_mut() {
+            Some((offset, length)) if start <= *offset + *length + MERGE_GAP_PAGES * page => {
+                *length = end - *offset;
+            }
+            _ => runs.push((start, end - start)),
+        }
+    }
+    runs
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as u64
+    } else {
+        4096
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_merge_small_gaps() {
+        let page = 4096;
+        let mut pages = vec![0u8; 40];
+        pages[0] = 1;
+        pages[1] = 1;
+        // Close enough to the first run to join it
+        pages[6] = 1;
+        pages[30] = 1;
+        pages[39] = 1;
+        let len = 39 * page + 100;
+
+        assert_eq!(
+            runs(&pages, page, len),
+            vec![(0, 7 * page), (30 * page, 9 * page + 100)]
+        );
+        assert!(runs(&[0, 0], page, 2 * page).is_empty());
+    }
+
+    #[test]
+    fn test_record_finds_cached_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("bin")).unwrap();
+        std::fs::write(dir.path().join("bin/sh"), vec![7u8; 10_000]).unwrap();
+        std::fs::write(dir.path().join("empty"), "").unwrap();
+        std::os::unix::fs::symlink("bin/sh", dir.path().join("link")).unwrap();
+
+        let hints = record(dir.path()).unwrap();
+        assert_eq!(
+            hints,
+            vec![ReadaheadHint {
+                path: "bin/sh".to_string(),
+                offset: 0,
+                length: 10_000,
+            }]
+        );
+
+        // Hints escaping the rootfs or naming missing files are skipped
+        let outside = ReadaheadHint {
+            path: "../etc/passwd".to_string(),
+            offset: 0,
+            length: 1,
+        };
+        let missing = ReadaheadHint {
+            path: "gone".to_string(),
+            ..outside.clone()
+        };
+        apply(
+            dir.path().to_path_buf(),
+            vec![outside, missing, hints[0].clone()],
+        );
+    }
+}
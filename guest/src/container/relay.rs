@@ -0,0 +1,50 @@
+//! Unix socket to host vsock relays
+//!
+//! Host services forwarded into the container (SSH agent, git credentials,
+//! X11) are reached through Unix sockets in the container. Each accepted
+//! connection is relayed byte-for-byte to a vsock port that libkrun bridges
+//! to the host service. File descriptors (SCM_RIGHTS) cannot cross vsock,
+//! so only byte-stream protocols work through a relay.
+
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tokio::net::UnixListener;
+use tokio_vsock::{VsockAddr, VsockStream, VMADDR_CID_HOST};
+use tracing::{debug, warn};
+
+/// Relay connections on a new Unix socket at `path` (with `mode`) to host
+/// vsock `port`, for as long as the agent runs.
+///
+/// Must be called inside the tokio runtime.
+pub fn listen(path: &Path, mode: u32, port: u32) -> io::Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+
+    tokio::spawn(async move {
+        loop {
+            let mut local = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!(port, "Relay stopped: {}", e);
+                    return;
+                }
+            };
+            tokio::spawn(async move {
+                let result = async {
+                    let mut host =
+                        VsockStream::connect(VsockAddr::new(VMADDR_CID_HOST, port)).await?;
+                    tokio::io::copy_bidirectional(&mut local, &mut host).await
+                }
+                .await;
+                if let Err(e) = result {
+                    debug!(port, "Relay connection ended: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Setup steps run before the container's workload
+//!
+//! A container with setup steps starts as `boxlite-setup-gate` (a copy of
+//! the agent binary, like the lock helper) instead of its entrypoint. The
+//! gate blocks reading a FIFO while the agent runs each step in the
+//! container; once all of them exit 0 the agent writes `go` and the gate
+//! execs the real entrypoint in its place, keeping PID 1. If a step fails
+//! the agent writes `stop`, the gate exits, and Container.Init fails.
+//!
+//! ```text
+//! /run/boxlite-setup/boxlite-setup-gate ENTRYPOINT [ARG...]
+//! ```
+
+use boxlite_shared::{PeriodicTask, SetupStep};
+use nix::sys::stat::Mode;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::Container;
+use crate::scheduler;
+
+/// Where the setup directory is mounted in the container.
+pub const CONTAINER_SETUP_DIR: &str = "/run/boxlite-setup";
+
+/// Name the agent binary answers to as the gate.
+pub const GATE_HELPER_NAME: &str = "boxlite-setup-gate";
+
+const GATE: &str = "gate";
+
+const USAGE: &str = "usage: boxlite-setup-gate ENTRYPOINT [ARG...]";
+
+/// Longest wait for the gate to open its end of the FIFO.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Set up the gate FIFO and helper in `dir`.
+pub fn prepare(dir: &Path) -> io::Result<()> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o755)
+        .create(dir)?;
+
+    let gate = dir.join(GATE);
+    if gate.exists() {
+        std::fs::remove_file(&gate)?;
+    }
+    // Readable by any user the image runs as; only the agent writes
+    nix::unistd::mkfifo(&gate, Mode::from_bits_truncate(0o644)).map_err(io::Error::from)?;
+    let helper = dir.join(GATE_HELPER_NAME);
+    std::fs::copy(std::env::current_exe()?, &helper)?;
+    std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755))
+}
+
+/// Entrypoint that waits at the gate before running `entrypoint`.
+pub fn wrap(entrypoint: Vec<String>) -> Vec<String> {
+    let mut wrapped = vec![format!("{}/{}", CONTAINER_SETUP_DIR, GATE_HELPER_NAME)];
+    wrapped.extend(entrypoint);
+    wrapped
+}
+
+/// Run `steps` in order in `container`, stopping at the first failure.
+pub async fn run(container: &Mutex<Container>, steps: &[SetupStep]) -> Result<(), String> {
+    for step in steps {
+        let task = PeriodicTask {
+            name: step.name.clone(),
+            program: step.program.clone(),
+            args: step.args.clone(),
+            env: step.env.clone(),
+            timeout_ms: step.timeout_ms,
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let run = scheduler::run_once(container, &task)
+            .await
+            .map_err(|e| format!("Setup step '{}' failed to start: {}", step.name, e))?;
+        if let Some(error) = run.error {
+            return Err(format!("Setup step '{}' failed: {}", step.name, error));
+        }
+        if run.exit_code != 0 {
+            return Err(format!(
+                "Setup step '{}' exited with {}: {}",
+                step.name,
+                run.exit_code,
+                run.output.trim()
+            ));
+        }
+        tracing::info!(
+            step = %step.name,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Setup step completed"
+        );
+    }
+    Ok(())
+}
+
+/// Let the gate in `dir` run the entrypoint (`proceed`) or exit.
+pub async fn release(dir: &Path, proceed: bool) -> io::Result<()> {
+    let gate = dir.join(GATE);
+    let message: &[u8] = if proceed { b"go" } else { b"stop" };
+    let deadline = Instant::now() + RELEASE_TIMEOUT;
+    loop {
+        // Non-blocking so a gate that never opened the FIFO cannot hang Init
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&gate)
+        {
+            Ok(mut fifo) => return fifo.write_all(message),
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) && Instant::now() < deadline => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Gate entry point (`boxlite-setup-gate` arguments after the program name).
+///
+/// Only returns on failure: 2 for bad usage, 1 if setup failed, 127 if the
+/// entrypoint could not be executed.
+pub fn run_gate_helper(args: &[String]) -> i32 {
+    let [program, program_args @ ..] = args else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let mut verdict = String::new();
+    let gate = Path::new(CONTAINER_SETUP_DIR).join(GATE);
+    if let Err(e) = std::fs::File::open(&gate).and_then(|mut f| f.read_to_string(&mut verdict)) {
+        eprintln!("boxlite-setup-gate: {}: {}", gate.display(), e);
+        return 1;
+    }
+    if verdict != "go" {
+        eprintln!("boxlite-setup-gate: setup failed, not starting {}", program);
+        return 1;
+    }
+
+    let e = std::process::Command::new(program)
+        .args(program_args)
+        .exec();
+    eprintln!("boxlite-setup-gate: {}: {}", program, e);
+    127
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap(vec!["nginx".to_string(), "-g".to_string()]),
+            ["/run/boxlite-setup/boxlite-setup-gate", "nginx", "-g"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let gate = dir.path().join(GATE);
+        nix::unistd::mkfifo(&gate, Mode::from_bits_truncate(0o644)).unwrap();
+
+        let reader = std::thread::spawn(move || {
+            let mut verdict = String::new();
+            std::fs::File::open(&gate)
+                .unwrap()
+                .read_to_string(&mut verdict)
+                .unwrap();
+            verdict
+        });
+        release(dir.path(), true).await.unwrap();
+        assert_eq!(reader.join().unwrap(), "go");
+    }
+}
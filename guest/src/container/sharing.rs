@@ -0,0 +1,63 @@
+//! Clipboard and drop directory sharing
+//!
+//! Both live on the guest's /run tmpfs and are bind-mounted into the
+//! container, so shared data never reaches the box's disk:
+//!
+//! - The clipboard is a file at `/run/boxlite-clipboard/content`. The host
+//!   sets and reads it over gRPC; apps in the box read and write the file
+//!   (e.g. from an `xclip` wrapper).
+//! - Dropped files arrive over the bulk channel into the drop directory,
+//!   mounted at the path chosen in the box options.
+//!
+//! The host enforces the box's sharing policy; the guest only serves what
+//! was enabled at Container.Init.
+
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+
+/// Where the clipboard directory is mounted in the container.
+pub const CONTAINER_CLIPBOARD_DIR: &str = "/run/boxlite-clipboard";
+
+const CLIPBOARD_FILE: &str = "content";
+
+/// Create a shared directory, writable by every user in the container.
+pub fn prepare_dir(dir: &Path) -> io::Result<()> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o1777)
+        .create(dir)
+}
+
+/// Replace the clipboard contents in `dir`.
+///
+/// Written through a temporary file so readers never see a partial update.
+pub fn write_clipboard(dir: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp = dir.join(format!(".{}.tmp", CLIPBOARD_FILE));
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, dir.join(CLIPBOARD_FILE))
+}
+
+/// Current clipboard contents in `dir` (empty if never set).
+pub fn read_clipboard(dir: &Path) -> io::Result<Vec<u8>> {
+    match std::fs::read(dir.join(CLIPBOARD_FILE)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipboard_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("clipboard");
+        prepare_dir(&dir).unwrap();
+
+        assert!(read_clipboard(&dir).unwrap().is_empty());
+        write_clipboard(&dir, b"copied text").unwrap();
+        assert_eq!(read_clipboard(&dir).unwrap(), b"copied text");
+    }
+}
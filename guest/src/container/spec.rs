@@ -3,15 +3,27 @@
 //! Creates OCI-compliant runtime specifications following the runtime-spec standard.
 
 use super::capabilities::all_capabilities;
+use crate::devices::{DeviceNode, DeviceSetup};
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use std::path::Path;
 
 use oci_spec::runtime::{
-    LinuxBuilder, LinuxCapabilitiesBuilder, LinuxIdMappingBuilder, LinuxNamespaceBuilder,
-    LinuxNamespaceType, Mount, MountBuilder, PosixRlimitBuilder, PosixRlimitType, ProcessBuilder,
-    RootBuilder, Spec, SpecBuilder, UserBuilder,
+    LinuxBuilder, LinuxCapabilitiesBuilder, LinuxDeviceBuilder, LinuxDeviceType,
+    LinuxIdMappingBuilder, LinuxNamespaceBuilder, LinuxNamespaceType, Mount, MountBuilder,
+    PosixRlimitBuilder, PosixRlimitType, ProcessBuilder, RootBuilder, Spec, SpecBuilder,
+    UserBuilder,
 };
 
+/// Characters of the container ID used as its hostname.
+const HOSTNAME_LEN: usize = 12;
+
+/// Container hostname: the short container ID, as Docker does.
+///
+/// Each box has its own, and a clone gets a new one with its container ID.
+pub fn hostname(container_id: &str) -> &str {
+    container_id.get(..HOSTNAME_LEN).unwrap_or(container_id)
+}
+
 /// User-specified bind mount for container
 #[derive(Debug, Clone)]
 pub struct UserMount {
@@ -23,6 +35,28 @@ pub struct UserMount {
     pub read_only: bool,
 }
 
+/// Optional container setups requested by the host
+#[derive(Debug, Clone, Default)]
+pub struct SpecFeatures {
+    /// Run container engines (see [`super::nested`])
+    pub nested_containers: bool,
+    /// Boot systemd as the container's init (see [`super::systemd`])
+    pub systemd: bool,
+    /// Paths covered with /dev/null or an empty read-only tmpfs
+    pub masked_paths: Vec<String>,
+    /// Paths remounted read-only
+    pub readonly_paths: Vec<String>,
+    /// Search domains added to /etc/resolv.conf
+    pub dns_search: Vec<String>,
+}
+
+impl SpecFeatures {
+    /// Whether the container gets its own writable cgroup hierarchy
+    pub fn cgroups(&self) -> bool {
+        self.nested_containers || self.systemd
+    }
+}
+
 /// Create OCI runtime specification with default configuration
 ///
 /// Builds an OCI spec with:
@@ -33,12 +67,17 @@ pub struct UserMount {
 /// - UID/GID mappings for user namespace
 /// - Root user (uid=0, gid=0)
 /// - Resource limits (rlimits)
+/// - Requested guest device nodes (e.g. /dev/kvm)
+/// - Masked and read-only paths requested for the box
+/// - For engines and systemd, a cgroup namespace, writable cgroup hierarchy
+///   and shared root propagation; for systemd, tmpfs /run and /run/lock
 /// - No new privileges disabled (allows sudo)
 ///
 /// NOTE: Cgroups are disabled for performance (~105ms savings on container startup).
 /// Since we're inside a VM with single-tenant isolation, cgroup resource limits
 /// provide minimal benefit. See comments in build_default_namespaces() and
 /// build_standard_mounts() to re-enable if needed.
+#[allow(clippy::too_many_arguments)]
 pub fn create_oci_spec(
     container_id: &str,
     rootfs: &str,
@@ -47,10 +86,19 @@ pub fn create_oci_spec(
     workdir: &str,
     bundle_path: &Path,
     user_mounts: &[UserMount],
+    devices: &DeviceSetup,
+    features: SpecFeatures,
 ) -> BoxliteResult<Spec> {
     let caps = build_default_capabilities()?;
-    let namespaces = build_default_namespaces()?;
-    let mut mounts = build_standard_mounts(bundle_path)?;
+    let mut namespaces = build_default_namespaces()?;
+    let mut mounts = build_standard_mounts(bundle_path, devices.devtmpfs)?;
+    if features.cgroups() {
+        namespaces.push(build_namespace(LinuxNamespaceType::Cgroup)?);
+        mounts.push(build_cgroup_mount()?);
+    }
+    if features.systemd {
+        mounts.extend(build_systemd_mounts()?);
+    }
 
     // Add user-specified bind mounts
     for user_mount in user_mounts {
@@ -85,11 +133,11 @@ pub fn create_oci_spec(
 
     let process = build_process_spec(entrypoint, env, workdir, caps)?;
     let root = build_root_spec(rootfs)?;
-    let linux = build_linux_spec(container_id, namespaces)?;
+    let linux = build_linux_spec(container_id, namespaces, &devices.nodes, &features)?;
 
     SpecBuilder::default()
         .version("1.0.2")
-        .hostname("boxlite")
+        .hostname(hostname(container_id))
         .root(root)
         .mounts(mounts)
         .process(process)
@@ -193,6 +241,8 @@ fn build_root_spec(rootfs: &str) -> BoxliteResult<oci_spec::runtime::Root> {
 fn build_linux_spec(
     container_id: &str,
     namespaces: Vec<oci_spec::runtime::LinuxNamespace>,
+    devices: &[DeviceNode],
+    features: &SpecFeatures,
 ) -> BoxliteResult<oci_spec::runtime::Linux> {
     // UID/GID mappings for user namespace
     // Map full range of UIDs/GIDs to allow non-root users (nginx=33, etc.)
@@ -210,49 +260,96 @@ fn build_linux_spec(
         .build()
         .map_err(|e| BoxliteError::Internal(format!("Failed to build GID mapping: {}", e)))?];
 
-    // Masked paths for security (hide sensitive /proc and /sys entries)
-    #[allow(unused)]
-    let masked_paths = vec![
-        "/proc/acpi".to_string(),
-        "/proc/asound".to_string(),
-        "/proc/kcore".to_string(),
-        "/proc/keys".to_string(),
-        "/proc/latency_stats".to_string(),
-        "/proc/timer_list".to_string(),
-        "/proc/timer_stats".to_string(),
-        "/proc/sched_debug".to_string(),
-        "/sys/firmware".to_string(),
-        "/sys/devices/virtual/powercap".to_string(),
-    ];
+    // NOTE: Cgroup path disabled for performance (see cgroup mount comment above)
+    // Only engine containers get one, with the cgroup namespace and mount.
 
-    // Readonly paths
-    #[allow(unused)]
-    let readonly_paths = [
-        "/proc/bus".to_string(),
-        "/proc/fs".to_string(),
-        "/proc/irq".to_string(),
-        "/proc/sys".to_string(),
-        "/proc/sysrq-trigger".to_string(),
-    ];
+    let devices = devices
+        .iter()
+        .map(|device| {
+            LinuxDeviceBuilder::default()
+                .path(&device.path)
+                .typ(LinuxDeviceType::C)
+                .major(device.major as i64)
+                .minor(device.minor as i64)
+                .file_mode(device.file_mode)
+                .uid(device.uid)
+                .gid(device.gid)
+                .build()
+                .map_err(|e| {
+                    BoxliteError::Internal(format!("Failed to build device {}: {}", device.path, e))
+                })
+        })
+        .collect::<BoxliteResult<Vec<_>>>()?;
 
-    // NOTE: Cgroup path disabled for performance (see cgroup mount comment above)
-    // Re-enable together with cgroup namespace and mount if resource limits are needed.
-    // let cgroups_path = format!("/boxlite/{}", container_id);
-    let _ = container_id; // Suppress unused warning
+    let mut linux = LinuxBuilder::default();
+    if features.cgroups() {
+        // Engines' and systemd's mounts must propagate to what they start
+        linux = linux
+            .rootfs_propagation("shared")
+            .cgroups_path(format!("/boxlite/{}", container_id));
+    }
 
-    LinuxBuilder::default()
+    linux
         .namespaces(namespaces)
+        .devices(devices)
         .uid_mappings(uid_mappings)
         .gid_mappings(gid_mappings)
-        // .masked_paths(masked_paths)
-        // .readonly_paths(readonly_paths)
-        // .cgroups_path(cgroups_path)
+        .masked_paths(features.masked_paths.clone())
+        .readonly_paths(features.readonly_paths.clone())
         .build()
         .map_err(|e| BoxliteError::Internal(format!("Failed to build linux spec: {}", e)))
 }
 
+/// Writable cgroup hierarchy for container engines and systemd
+fn build_cgroup_mount() -> BoxliteResult<Mount> {
+    MountBuilder::default()
+        .destination("/sys/fs/cgroup")
+        .typ("cgroup")
+        .source("cgroup")
+        .options(vec![
+            "nosuid".to_string(),
+            "noexec".to_string(),
+            "nodev".to_string(),
+            "relatime".to_string(),
+            "rw".to_string(),
+        ])
+        .build()
+        .map_err(|e| BoxliteError::Internal(format!("Failed to build /sys/fs/cgroup mount: {}", e)))
+}
+
+/// tmpfs mounts systemd expects to own
+fn build_systemd_mounts() -> BoxliteResult<Vec<Mount>> {
+    [("/run", "mode=755"), ("/run/lock", "mode=1777")]
+        .into_iter()
+        .map(|(destination, mode)| {
+            MountBuilder::default()
+                .destination(destination)
+                .typ("tmpfs")
+                .source("tmpfs")
+                .options(vec![
+                    "nosuid".to_string(),
+                    "nodev".to_string(),
+                    mode.to_string(),
+                ])
+                .build()
+                .map_err(|e| {
+                    BoxliteError::Internal(format!("Failed to build {} mount: {}", destination, e))
+                })
+        })
+        .collect()
+}
+
 /// Build standard mounts for container filesystem
-fn build_standard_mounts(bundle_path: &Path) -> BoxliteResult<Vec<Mount>> {
+fn build_standard_mounts(bundle_path: &Path, devtmpfs: bool) -> BoxliteResult<Vec<Mount>> {
+    // devtmpfs shows every guest device; the default tmpfs only gets the
+    // nodes the runtime creates
+    let dev_fs = if devtmpfs { "devtmpfs" } else { "tmpfs" };
+    let mut dev_options = vec!["nosuid".to_string(), "mode=755".to_string()];
+    if !devtmpfs {
+        dev_options.push("strictatime".to_string());
+        dev_options.push("size=65536k".to_string());
+    }
+
     let mut mounts = vec![
         // /proc - Process information
         MountBuilder::default()
@@ -264,14 +361,9 @@ fn build_standard_mounts(bundle_path: &Path) -> BoxliteResult<Vec<Mount>> {
         // /dev - Device filesystem
         MountBuilder::default()
             .destination("/dev")
-            .typ("tmpfs")
-            .source("tmpfs")
-            .options(vec![
-                "nosuid".to_string(),
-                "strictatime".to_string(),
-                "mode=755".to_string(),
-                "size=65536k".to_string(),
-            ])
+            .typ(dev_fs)
+            .source(dev_fs)
+            .options(dev_options)
             .build()
             .map_err(|e| BoxliteError::Internal(format!("Failed to build /dev mount: {}", e)))?,
         // /dev/pts - Pseudo-terminals
@@ -0,0 +1,121 @@
+//! SSH server bring-up
+//!
+//! Starts the image's OpenSSH server inside the container for tools that
+//! only speak SSH (rsync, ansible, IDE remotes). Host keys are generated on
+//! first start into a guest tmpfs directory bind-mounted into the container,
+//! alongside the authorized_keys sent by the host; only key authentication
+//! is accepted.
+//!
+//! The image must provide `sshd` and `ssh-keygen` (e.g. `openssh-server`).
+
+use super::Container;
+use boxlite_shared::errors::BoxliteResult;
+use futures::StreamExt;
+use nix::sys::wait::waitpid;
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+/// Where the SSH directory is mounted in the container.
+pub const CONTAINER_SSH_DIR: &str = "/run/boxlite-ssh";
+
+/// Create `dir` (mode 0700) holding `authorized_keys` (mode 0600).
+///
+/// sshd's StrictModes rejects keys in group- or world-accessible paths.
+pub fn prepare(dir: &Path, authorized_keys: &[String]) -> io::Result<()> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(dir.join("authorized_keys"))?;
+    for key in authorized_keys {
+        writeln!(file, "{}", key.trim())?;
+    }
+    Ok(())
+}
+
+/// Start sshd in `container`, listening on `port`.
+///
+/// Returns once the server is spawned; it runs until the container stops,
+/// with its log forwarded to the agent's log.
+pub async fn start(container: &Container, port: u32) -> BoxliteResult<()> {
+    let mut handle = container
+        .cmd()
+        .program("/bin/sh")
+        .args(["-c", &server_script(port)])
+        .spawn()
+        .await?;
+    info!(port, pid = %handle.pid(), "Started SSH server");
+
+    for stream in [
+        handle.stdout().map(|s| s.boxed()),
+        handle.stderr().map(|s| s.boxed()),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        tokio::spawn(async move {
+            let mut stream = stream;
+            while let Some(chunk) = stream.next().await {
+                debug!(target: "sshd", "{}", String::from_utf8_lossy(&chunk).trim_end());
+            }
+        });
+    }
+
+    let pid = handle.pid();
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || waitpid(pid, None)).await {
+            Ok(Ok(status)) => warn!("SSH server exited: {:?}", status),
+            Ok(Err(e)) => warn!("Failed to wait for SSH server: {}", e),
+            Err(e) => warn!("Failed to wait for SSH server: {}", e),
+        }
+    });
+    Ok(())
+}
+
+fn server_script(port: u32) -> String {
+    format!(
+        "set -e\n\
+         key={dir}/ssh_host_ed25519_key\n\
+         [ -f \"$key\" ] || ssh-keygen -q -t ed25519 -N '' -f \"$key\"\n\
+         mkdir -p /run/sshd\n\
+         exec \"$(command -v sshd || echo /usr/sbin/sshd)\" -D -e -p {port} -h \"$key\" \
+         -o AuthorizedKeysFile={dir}/authorized_keys \
+         -o PasswordAuthentication=no \
+         -o KbdInteractiveAuthentication=no \
+         -o PermitRootLogin=prohibit-password\n",
+        dir = CONTAINER_SSH_DIR,
+        port = port
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_prepare_writes_private_keys_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("ssh");
+        let keys = vec!["ssh-ed25519 AAAA user@host\n".to_string()];
+
+        prepare(&dir, &keys).unwrap();
+
+        let file = dir.join("authorized_keys");
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "ssh-ed25519 AAAA user@host\n"
+        );
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&file), 0o600);
+    }
+}
@@ -4,6 +4,7 @@
 //! Separated from container.rs to group by lifecycle phase (Prepare → Execute).
 
 use super::spec;
+use crate::devices::DeviceSetup;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
 use libcontainer::container::builder::ContainerBuilder;
 use libcontainer::container::Container as LibContainer;
@@ -41,15 +42,18 @@ pub(crate) fn validate_container_inputs(
 }
 
 /// Create /etc/hosts, /etc/hostname and /etc/resolv.conf files for the container
+///
+/// `dns_search` domains are searched after the default one.
 pub(crate) fn create_container_etc_files(
     bundle_path: &Path,
-    _container_id: &str,
+    container_id: &str,
+    dns_search: &[String],
 ) -> BoxliteResult<()> {
-    const DEFAULT_HOSTNAME: &str = "boxlite";
+    let hostname = spec::hostname(container_id);
 
     // Create /etc/hostname
     let hostname_path = bundle_path.join("hostname");
-    fs::write(&hostname_path, format!("{}\n", DEFAULT_HOSTNAME))
+    fs::write(&hostname_path, format!("{}\n", hostname))
         .map_err(|e| BoxliteError::Internal(format!("Failed to create hostname file: {}", e)))?;
 
     // Create /etc/hosts with localhost and hostname entries
@@ -62,7 +66,7 @@ pub(crate) fn create_container_etc_files(
          ff02::1\t\tip6-allnodes\n\
          ff02::2\t\tip6-allrouters\n\
          127.0.1.1\t{}\n",
-        DEFAULT_HOSTNAME
+        hostname
     );
     fs::write(&hosts_path, hosts_content)
         .map_err(|e| BoxliteError::Internal(format!("Failed to create hosts file: {}", e)))?;
@@ -70,8 +74,9 @@ pub(crate) fn create_container_etc_files(
     // Create /etc/resolv.conf with gateway as DNS server
     let resolv_conf_path = bundle_path.join("resolv.conf");
     let resolv_conf_content = format!(
-        "# Generated by BoxLite Guest\n# DNS queries forwarded to gateway\nnameserver {}\nsearch localdomain\n",
-        "192.168.127.1" // TODO: Use constant when guest can access boxlite constants
+        "# Generated by BoxLite Guest\n# DNS queries forwarded to gateway\nnameserver {}\nsearch {}\n",
+        "192.168.127.1", // TODO: Use constant when guest can access boxlite constants
+        search_domains(dns_search)
     );
     fs::write(&resolv_conf_path, resolv_conf_content)
         .map_err(|e| BoxliteError::Internal(format!("Failed to create resolv.conf file: {}", e)))?;
@@ -86,7 +91,16 @@ pub(crate) fn create_container_etc_files(
     Ok(())
 }
 
+/// The resolv.conf search line: the default domain, then `extra` ones.
+fn search_domains(extra: &[String]) -> String {
+    std::iter::once("localdomain")
+        .chain(extra.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// Create OCI bundle (config.json + rootfs reference)
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn create_oci_bundle(
     container_id: &str,
     rootfs: &Path,
@@ -95,6 +109,8 @@ pub(crate) fn create_oci_bundle(
     workdir: &Path,
     bundle_root: &Path,
     user_mounts: &[spec::UserMount],
+    devices: &DeviceSetup,
+    features: spec::SpecFeatures,
 ) -> BoxliteResult<PathBuf> {
     let bundle_path = bundle_root.join(container_id);
 
@@ -108,7 +124,7 @@ pub(crate) fn create_oci_bundle(
 
     // Create /etc/hosts, /etc/hostname and /etc/resolv.conf files
     // These will be bind-mounted into the container to provide hostname and DNS resolution
-    create_container_etc_files(&bundle_path, container_id)?;
+    create_container_etc_files(&bundle_path, container_id, &features.dns_search)?;
 
     let spec = spec::create_oci_spec(
         container_id,
@@ -122,6 +138,8 @@ pub(crate) fn create_oci_bundle(
             .ok_or_else(|| BoxliteError::Internal("Invalid workdir path".to_string()))?,
         &bundle_path,
         user_mounts,
+        devices,
+        features,
     )?;
     let config_path = bundle_path.join("config.json");
 
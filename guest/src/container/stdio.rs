@@ -33,8 +33,9 @@
 //! ```
 
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
 use nix::unistd::pipe;
-use std::os::unix::io::OwnedFd;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 
 /// Stdio configuration for container init process.
 ///
@@ -52,15 +53,12 @@ use std::os::unix::io::OwnedFd;
 #[derive(Debug)]
 pub struct ContainerStdio {
     /// Write-end of stdin pipe (held open, never written to)
-    #[allow(dead_code)]
     stdin_tx: OwnedFd,
 
     /// Read-end of stdout pipe (for optional log capture)
-    #[allow(dead_code)]
     stdout_rx: OwnedFd,
 
     /// Read-end of stderr pipe (for optional log capture)
-    #[allow(dead_code)]
     stderr_rx: OwnedFd,
 }
 
@@ -124,12 +122,45 @@ impl ContainerStdio {
 
         Ok((container_stdio, init_fds))
     }
+
+    /// Descriptors of the held pipe ends: stdin, stdout, stderr.
+    ///
+    /// An agent upgrading in place keeps them open across its exec, so the
+    /// init process does not see EOF.
+    pub fn raw_fds(&self) -> [RawFd; 3] {
+        [
+            self.stdin_tx.as_raw_fd(),
+            self.stdout_rx.as_raw_fd(),
+            self.stderr_rx.as_raw_fd(),
+        ]
+    }
+
+    /// Take back pipe ends kept open across an exec of the agent.
+    ///
+    /// They are marked close-on-exec again, so processes the agent starts
+    /// do not inherit them.
+    ///
+    /// # Safety
+    ///
+    /// The descriptors must be open and owned by nothing else.
+    pub unsafe fn from_raw_fds(fds: [RawFd; 3]) -> BoxliteResult<Self> {
+        let [stdin_tx, stdout_rx, stderr_rx] = fds.map(|fd| OwnedFd::from_raw_fd(fd));
+        for fd in [&stdin_tx, &stdout_rx, &stderr_rx] {
+            fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map_err(|e| {
+                BoxliteError::Internal(format!("Failed to adopt container stdio: {}", e))
+            })?;
+        }
+        Ok(Self {
+            stdin_tx,
+            stdout_rx,
+            stderr_rx,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::os::unix::io::AsRawFd;
 
     #[test]
     fn test_stdio_creation() {
@@ -161,4 +192,17 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_stdio_adopted_after_exec() {
+        let (stdio, _init_fds) = ContainerStdio::new().unwrap();
+        let fds = stdio.raw_fds();
+        // As if the agent exec'd: the descriptors outlive their owner
+        std::mem::forget(stdio);
+
+        let adopted = unsafe { ContainerStdio::from_raw_fds(fds) }.unwrap();
+        assert_eq!(adopted.raw_fds(), fds);
+        let flags = FdFlag::from_bits_truncate(fcntl(fds[0], FcntlArg::F_GETFD).unwrap());
+        assert!(flags.contains(FdFlag::FD_CLOEXEC));
+    }
 }
@@ -0,0 +1,72 @@
+//! systemd as the container's init
+//!
+//! Some images only work with their services running under systemd. In
+//! systemd mode the image's own systemd replaces its entrypoint, with a
+//! writable cgroup hierarchy (see [`super::cgroups`]), tmpfs `/run` and
+//! `/run/lock`, and `$container` set so systemd skips hardware setup.
+//!
+//! The agent keeps running in the guest and enters the container for
+//! execs, so it needs no unit of its own. Volumes and other boxlite mounts
+//! are made by the runtime before systemd starts; systemd adopts them as
+//! mount units from the mount table.
+
+use std::io;
+use std::path::Path;
+
+/// Where images keep the systemd binary, in lookup order.
+const SYSTEMD_PATHS: &[&str] = &[
+    "/usr/lib/systemd/systemd",
+    "/lib/systemd/systemd",
+    "/usr/sbin/init",
+    "/sbin/init",
+];
+
+/// `$container` value systemd reports as the virtualization type.
+pub const CONTAINER_ENV: &str = "container=boxlite";
+
+/// Entrypoint that boots the rootfs's systemd.
+///
+/// `init` paths only count when they lead to systemd, since many images
+/// ship a different init there.
+pub fn entrypoint(rootfs: &Path) -> io::Result<Vec<String>> {
+    SYSTEMD_PATHS
+        .iter()
+        .find(|path| {
+            let candidate = rootfs.join(path.trim_start_matches('/'));
+            if path.ends_with("/systemd") {
+                return candidate.is_file();
+            }
+            std::fs::read_link(&candidate)
+                .is_ok_and(|target| target.file_name().is_some_and(|name| name == "systemd"))
+        })
+        .map(|path| vec![path.to_string()])
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "image has no systemd (looked for /usr/lib/systemd/systemd and /sbin/init)",
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entrypoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let rootfs = dir.path();
+        std::fs::create_dir_all(rootfs.join("sbin")).unwrap();
+        // busybox init is not systemd
+        std::os::unix::fs::symlink("/bin/busybox", rootfs.join("sbin/init")).unwrap();
+        assert!(entrypoint(rootfs).is_err());
+
+        std::fs::remove_file(rootfs.join("sbin/init")).unwrap();
+        std::os::unix::fs::symlink("/lib/systemd/systemd", rootfs.join("sbin/init")).unwrap();
+        assert_eq!(entrypoint(rootfs).unwrap(), ["/sbin/init"]);
+
+        std::fs::create_dir_all(rootfs.join("usr/lib/systemd")).unwrap();
+        std::fs::write(rootfs.join("usr/lib/systemd/systemd"), b"").unwrap();
+        assert_eq!(entrypoint(rootfs).unwrap(), ["/usr/lib/systemd/systemd"]);
+    }
+}
@@ -0,0 +1,565 @@
+//! Container user accounts
+//!
+//! Adds and removes users in the container's `/etc/passwd`, `/etc/group`
+//! and `/etc/shadow`, and manages their home directories. All file and mount
+//! operations run in the container's mount namespace, so paths (including
+//! symlinks in the rootfs) resolve the way they do for the workload.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use nix::sched::{setns, unshare, CloneFlags};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::fs::{chown, fchown, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+const PASSWD: &str = "/etc/passwd";
+const GROUP: &str = "/etc/group";
+const SHADOW: &str = "/etc/shadow";
+
+/// Lowest UID of a regular user, as `UID_MIN` in `login.defs`; lower ones
+/// belong to the system, and their homes are never removed.
+const FIRST_USER_UID: u32 = 1000;
+
+/// Only homes below this directory are removed with their user.
+const HOMES_DIR: &str = "/home";
+
+/// Account to create.
+#[derive(Clone, Debug)]
+pub struct UserSpec {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub shell: String,
+    pub home: String,
+    /// Mount a tmpfs at `home` instead of creating a directory in the rootfs
+    pub tmpfs_home: bool,
+}
+
+/// Create a user in the container whose init process is `init_pid`.
+pub fn create(init_pid: i32, spec: UserSpec) -> BoxliteResult<()> {
+    in_mount_namespace(init_pid, move || {
+        validate_name(&spec.name)?;
+        if !spec.home.starts_with('/') || spec.home == "/" {
+            return Err(BoxliteError::InvalidArgument(format!(
+                "Home must be an absolute path below /: {}",
+                spec.home
+            )));
+        }
+
+        let (passwd, group, shadow) = read_account_files()?;
+        let (passwd, group, shadow) =
+            add_account(&passwd, &group, shadow.as_deref(), &spec).map_err(invalid)?;
+
+        create_home(&spec)?;
+        write_account_files(&passwd, &group, shadow.as_deref())
+    })
+}
+
+/// Delete a user from the container whose init process is `init_pid`.
+pub fn delete(init_pid: i32, name: String, remove_home: bool) -> BoxliteResult<()> {
+    in_mount_namespace(init_pid, move || {
+        validate_name(&name)?;
+        let (passwd, group, shadow) = read_account_files()?;
+        let removed = remove_account(&passwd, &group, shadow.as_deref(), &name).map_err(invalid)?;
+        if remove_home {
+            check_removable_home(removed.uid, Path::new(&removed.home))?;
+        }
+        write_account_files(&removed.passwd, &removed.group, removed.shadow.as_deref())?;
+
+        if remove_home {
+            // A tmpfs home is a mount point; a rootfs home just a directory
+            let _ = umount2(removed.home.as_str(), MntFlags::MNT_DETACH);
+            match std::fs::remove_dir_all(&removed.home) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(BoxliteError::Storage(format!(
+                        "Failed to remove home {}: {}",
+                        removed.home, e
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Run `f` on a thread that has joined the mount namespace of `pid`.
+fn in_mount_namespace<F>(pid: i32, f: F) -> BoxliteResult<()>
+where
+    F: FnOnce() -> BoxliteResult<()> + Send + 'static,
+{
+    std::thread::spawn(move || {
+        // Threads share filesystem attributes, which setns(CLONE_NEWNS) refuses
+        unshare(CloneFlags::CLONE_FS)
+            .map_err(|e| BoxliteError::Internal(format!("unshare(CLONE_FS) failed: {}", e)))?;
+        let ns = File::open(format!("/proc/{}/ns/mnt", pid)).map_err(|e| {
+            BoxliteError::Internal(format!("Failed to open mount namespace: {}", e))
+        })?;
+        setns(ns, CloneFlags::CLONE_NEWNS).map_err(|e| {
+            BoxliteError::Internal(format!("Failed to enter mount namespace: {}", e))
+        })?;
+        f()
+    })
+    .join()
+    .map_err(|_| BoxliteError::Internal("User management thread panicked".to_string()))?
+}
+
+fn read_account_files() -> BoxliteResult<(String, String, Option<String>)> {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .map_err(|e| BoxliteError::Storage(format!("Failed to read {}: {}", path, e)))
+    };
+    let shadow = match std::fs::read_to_string(SHADOW) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(BoxliteError::Storage(format!(
+                "Failed to read {}: {}",
+                SHADOW, e
+            )))
+        }
+    };
+    Ok((read(PASSWD)?, read(GROUP)?, shadow))
+}
+
+fn write_account_files(passwd: &str, group: &str, shadow: Option<&str>) -> BoxliteResult<()> {
+    let files = [
+        (GROUP, Some(group)),
+        (PASSWD, Some(passwd)),
+        (SHADOW, shadow),
+    ];
+    for (path, content) in files {
+        if let Some(content) = content {
+            replace_file(Path::new(path), content)
+                .map_err(|e| BoxliteError::Storage(format!("Failed to write {}: {}", path, e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace `path` with `content` through a renamed temporary file, keeping
+/// its mode and owner, so readers never see it truncated or half written.
+fn replace_file(path: &Path, content: &str) -> io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push("+");
+    let temp = Path::new(&temp_name);
+
+    let written = (|| {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(temp)?;
+        fchown(&file, Some(metadata.uid()), Some(metadata.gid()))?;
+        file.set_permissions(std::fs::Permissions::from_mode(metadata.mode() & 0o7777))?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(temp, path)
+    })();
+    if written.is_err() {
+        let _ = std::fs::remove_file(temp);
+    }
+    written
+}
+
+fn create_home(spec: &UserSpec) -> BoxliteResult<()> {
+    let home = Path::new(&spec.home);
+    check_new_home(home, spec.uid)?;
+    std::fs::create_dir_all(home)
+        .map_err(|e| BoxliteError::Storage(format!("Failed to create {}: {}", spec.home, e)))?;
+
+    if spec.tmpfs_home {
+        let options = format!("mode=0700,uid={},gid={}", spec.uid, spec.gid);
+        mount(
+            Some("tmpfs"),
+            home,
+            Some("tmpfs"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+            Some(options.as_str()),
+        )
+        .map_err(|e| {
+            BoxliteError::Storage(format!("Failed to mount tmpfs at {}: {}", spec.home, e))
+        })?;
+        return Ok(());
+    }
+
+    chown(home, Some(spec.uid), Some(spec.gid))
+        .map_err(|e| BoxliteError::Storage(format!("Failed to chown {}: {}", spec.home, e)))?;
+    std::fs::set_permissions(home, std::fs::Permissions::from_mode(0o700))
+        .map_err(|e| BoxliteError::Storage(format!("Failed to chmod {}: {}", spec.home, e)))
+}
+
+/// Refuse to hand `home` to `uid` when it already holds someone else's
+/// files: it is chowned and locked down to the new user, or hidden under a
+/// tmpfs. A symlink is refused too, since it would redirect both.
+fn check_new_home(home: &Path, uid: u32) -> BoxliteResult<()> {
+    let metadata = match std::fs::symlink_metadata(home) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(BoxliteError::Storage(format!(
+                "Failed to inspect {}: {}",
+                home.display(),
+                e
+            )))
+        }
+    };
+    if !metadata.is_dir() {
+        return Err(invalid(format!(
+            "Home {} exists and is not a directory",
+            home.display()
+        )));
+    }
+    let empty = std::fs::read_dir(home)
+        .map_err(|e| BoxliteError::Storage(format!("Failed to read {}: {}", home.display(), e)))?
+        .next()
+        .is_none();
+    if !empty && metadata.uid() != uid {
+        return Err(invalid(format!(
+            "Home {} already holds files of UID {}",
+            home.display(),
+            metadata.uid()
+        )));
+    }
+    Ok(())
+}
+
+/// Refuse to remove the home of a system user, a home outside
+/// [`HOMES_DIR`], or one the user does not own.
+fn check_removable_home(uid: u32, home: &Path) -> BoxliteResult<()> {
+    if uid < FIRST_USER_UID {
+        return Err(invalid(format!(
+            "Not removing the home of system user UID {}",
+            uid
+        )));
+    }
+    let below_homes = home.strip_prefix(HOMES_DIR).is_ok_and(|rest| {
+        rest.components().next().is_some()
+            && rest
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+    });
+    if !below_homes {
+        return Err(invalid(format!(
+            "Not removing home {} outside {}",
+            home.display(),
+            HOMES_DIR
+        )));
+    }
+    match std::fs::symlink_metadata(home) {
+        Ok(metadata) if metadata.is_symlink() || metadata.uid() != uid => Err(invalid(format!(
+            "Not removing home {}, which UID {} does not own",
+            home.display(),
+            uid
+        ))),
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(BoxliteError::Storage(format!(
+            "Failed to inspect {}: {}",
+            home.display(),
+            e
+        ))),
+    }
+}
+
+fn invalid(reason: String) -> BoxliteError {
+    BoxliteError::InvalidArgument(reason)
+}
+
+/// Accept portable user names (`[a-z_][a-z0-9_-]*`, at most 32 characters).
+fn validate_name(name: &str) -> BoxliteResult<()> {
+    let mut chars = name.chars();
+    let valid = name.len() <= 32
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid(format!("Invalid user name: {:?}", name)))
+    }
+}
+
+/// Refuse a value that would break out of its account file field.
+fn validate_field(what: &str, value: &str) -> Result<(), String> {
+    if value.contains([':', '\n', '\r', '\0']) {
+        return Err(format!("Invalid {}: {:?}", what, value));
+    }
+    Ok(())
+}
+
+/// Fields of a colon-separated account file line.
+fn fields(line: &str) -> Vec<&str> {
+    line.split(':').collect()
+}
+
+fn with_line(content: &str, line: &str) -> String {
+    let mut content = content.to_string();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(line);
+    content.push('\n');
+    content
+}
+
+/// Add `spec` to the account files, creating a personal group when `gid`
+/// has none.
+fn add_account(
+    passwd: &str,
+    group: &str,
+    shadow: Option<&str>,
+    spec: &UserSpec,
+) -> Result<(String, String, Option<String>), String> {
+    validate_field("user name", &spec.name)?;
+    validate_field("home", &spec.home)?;
+    validate_field("shell", &spec.shell)?;
+    for line in passwd.lines() {
+        let f = fields(line);
+        if f.first() == Some(&spec.name.as_str()) {
+            return Err(format!("User {} already exists", spec.name));
+        }
+        if f.get(2) == Some(&spec.uid.to_string().as_str()) {
+            return Err(format!("UID {} is already used by {}", spec.uid, f[0]));
+        }
+    }
+
+    let gid = spec.gid.to_string();
+    let group_exists = group
+        .lines()
+        .any(|line| fields(line).get(2) == Some(&gid.as_str()));
+    let group = if group_exists {
+        group.to_string()
+    } else if group
+        .lines()
+        .any(|line| fields(line).first() == Some(&spec.name.as_str()))
+    {
+        return Err(format!(
+            "Group {} already exists with a different GID",
+            spec.name
+        ));
+    } else {
+        with_line(group, &format!("{}:x:{}:", spec.name, gid))
+    };
+
+    let passwd = with_line(
+        passwd,
+        &format!(
+            "{}:x:{}:{}::{}:{}",
+            spec.name, spec.uid, gid, spec.home, spec.shell
+        ),
+    );
+    // Locked password: the account is only entered through exec
+    let shadow = shadow.map(|s| with_line(s, &format!("{}:!::0:99999:7:::", spec.name)));
+
+    Ok((passwd, group, shadow))
+}
+
+struct RemovedAccount {
+    passwd: String,
+    group: String,
+    shadow: Option<String>,
+    uid: u32,
+    home: String,
+}
+
+/// Remove `name` from the account files, along with its personal group if
+/// no other account uses it.
+fn remove_account(
+    passwd: &str,
+    group: &str,
+    shadow: Option<&str>,
+    name: &str,
+) -> Result<RemovedAccount, String> {
+    let entry = passwd
+        .lines()
+        .map(fields)
+        .find(|f| f.first() == Some(&name) && f.len() >= 7)
+        .ok_or_else(|| format!("User {} does not exist", name))?;
+    let uid = entry[2]
+        .parse()
+        .map_err(|_| format!("User {} has an invalid UID: {}", name, entry[2]))?;
+    let gid = entry[3];
+    let home = entry[5].to_string();
+
+    let keep = |content: &str| -> String {
+        content
+            .lines()
+            .filter(|line| fields(line).first() != Some(&name))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    };
+    let passwd = keep(passwd);
+
+    let gid_in_use = passwd.lines().any(|line| fields(line).get(3) == Some(&gid));
+    let group = group
+        .lines()
+        .filter(|line| {
+            let f = fields(line);
+            gid_in_use || !(f.first() == Some(&name) && f.get(2) == Some(&gid))
+        })
+        .map(|line| match fields(line).as_slice() {
+            // Drop the user from supplementary group member lists
+            [group_name, password, gid, members] => {
+                let members: Vec<&str> = members
+                    .split(',')
+                    .filter(|m| !m.is_empty() && *m != name)
+                    .collect();
+                format!(
+                    "{}:{}:{}:{}\n",
+                    group_name,
+                    password,
+                    gid,
+                    members.join(",")
+                )
+            }
+            _ => format!("{}\n", line),
+        })
+        .collect();
+
+    Ok(RemovedAccount {
+        passwd,
+        group,
+        shadow: shadow.map(keep),
+        uid,
+        home,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD_FILE: &str = "root:x:0:0:root:/root:/bin/sh\n";
+    const GROUP_FILE: &str = "root:x:0:\nusers:x:100:root\n";
+
+    fn spec(name: &str, uid: u32, gid: u32) -> UserSpec {
+        UserSpec {
+            name: name.to_string(),
+            uid,
+            gid,
+            shell: "/bin/sh".to_string(),
+            home: format!("/home/{}", name),
+            tmpfs_home: false,
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove_account() {
+        let (passwd, group, shadow) = add_account(
+            PASSWD_FILE,
+            GROUP_FILE,
+            Some("root:*::0:::::\n"),
+            &spec("alice", 1000, 1000),
+        )
+        .unwrap();
+        assert!(passwd.ends_with("alice:x:1000:1000::/home/alice:/bin/sh\n"));
+        assert!(group.ends_with("alice:x:1000:\n"));
+        assert!(shadow
+            .as_deref()
+            .unwrap()
+            .ends_with("alice:!::0:99999:7:::\n"));
+
+        // Existing group is reused rather than duplicated
+        let (_, group_reused, _) =
+            add_account(&passwd, &group, None, &spec("bob", 1001, 100)).unwrap();
+        assert_eq!(group_reused, group);
+
+        let group = group.replace("users:x:100:root", "users:x:100:root,alice");
+        let removed = remove_account(&passwd, &group, shadow.as_deref(), "alice").unwrap();
+        assert_eq!(removed.passwd, PASSWD_FILE);
+        assert_eq!(removed.group, GROUP_FILE);
+        assert_eq!(removed.shadow.as_deref(), Some("root:*::0:::::\n"));
+        assert_eq!(removed.uid, 1000);
+        assert_eq!(removed.home, "/home/alice");
+    }
+
+    #[test]
+    fn test_new_home_must_not_hold_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let owner = std::fs::metadata(dir.path()).unwrap().uid();
+        let other = owner + 1;
+
+        // Missing and empty directories can be handed to anyone
+        assert!(check_new_home(&dir.path().join("missing"), other).is_ok());
+        assert!(check_new_home(dir.path(), other).is_ok());
+
+        std::fs::write(dir.path().join("data"), "x").unwrap();
+        assert!(check_new_home(dir.path(), owner).is_ok());
+        assert!(matches!(
+            check_new_home(dir.path(), other),
+            Err(BoxliteError::InvalidArgument(_))
+        ));
+
+        // Nor through a symlink, or over a file
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink("/etc", &link).unwrap();
+        assert!(check_new_home(&link, owner).is_err());
+        assert!(check_new_home(&dir.path().join("data"), owner).is_err());
+    }
+
+    #[test]
+    fn test_removable_home() {
+        assert!(check_removable_home(1000, Path::new("/home/boxlite-test-missing")).is_ok());
+
+        for (uid, home) in [
+            (0, "/root"),
+            (0, "/home/root"),
+            (999, "/home/daemon"),
+            (1000, "/"),
+            (1000, "/home"),
+            (1000, "/etc"),
+            (1000, "/srv/alice"),
+            (1000, "/home/../etc"),
+            (1000, "/homes/alice"),
+        ] {
+            assert!(
+                matches!(
+                    check_removable_home(uid, Path::new(home)),
+                    Err(BoxliteError::InvalidArgument(_))
+                ),
+                "{} {}",
+                uid,
+                home
+            );
+        }
+    }
+
+    #[test]
+    fn test_conflicts_and_invalid_names() {
+        assert!(add_account(PASSWD_FILE, GROUP_FILE, None, &spec("root", 1000, 1000)).is_err());
+        assert!(add_account(PASSWD_FILE, GROUP_FILE, None, &spec("alice", 0, 1000)).is_err());
+        assert!(remove_account(PASSWD_FILE, GROUP_FILE, None, "alice").is_err());
+
+        assert!(validate_name("dev_user-1").is_ok());
+        for name in ["", "1abc", "Alice", "a:b", "a/b", &"a".repeat(33)] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+
+        // Home and shell cannot add fields or lines
+        let mut bad = spec("alice", 1000, 1000);
+        bad.home = "/home/alice:0:0::/root:/bin/sh".to_string();
+        assert!(add_account(PASSWD_FILE, GROUP_FILE, None, &bad).is_err());
+        let mut bad = spec("alice", 1000, 1000);
+        bad.shell = "/bin/sh\nevil::0:0::/:/bin/sh".to_string();
+        assert!(add_account(PASSWD_FILE, GROUP_FILE, None, &bad).is_err());
+    }
+
+    #[test]
+    fn test_replace_file_keeps_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shadow");
+        std::fs::write(&path, "old\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        replace_file(&path, "new\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert!(!dir.path().join("shadow+").exists());
+    }
+}
@@ -0,0 +1,314 @@
+//! Path watching for Container.WatchPath
+//!
+//! Changes are read from inotify and held until the path has been quiet
+//! for the debounce period, so an editor's save (write to a temp file,
+//! rename over, chmod) or a build that touches hundreds of files arrives
+//! on the host as one batch. Events for the same path in a batch collapse
+//! into their net effect: a file created and then deleted in one batch is
+//! not reported at all.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use boxlite_shared::{PathChange, PathChangeKind, WatchPathEvent};
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use tokio::sync::mpsc;
+use tonic::Status;
+
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(100);
+/// How often a quiet watcher checks whether the caller hung up.
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+/// A path that never goes quiet still gets a batch out this many debounce
+/// periods after its first change.
+const MAX_DELAY_FACTOR: u32 = 10;
+
+/// An open watch on `root`.
+pub struct Watch {
+    inotify: Inotify,
+    root: PathBuf,
+    recursive: bool,
+    /// The directory (relative to `root`) each descriptor watches
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    pending: Pending,
+}
+
+impl Watch {
+    /// Start watching `root`, and with `recursive` every directory below.
+    pub fn new(root: &Path, recursive: bool) -> io::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        let mut watch = Self {
+            inotify,
+            root: root.to_path_buf(),
+            recursive,
+            dirs: HashMap::new(),
+            pending: Pending::default(),
+        };
+        watch.add(Path::new(""))?;
+        if recursive && root.is_dir() {
+            watch.add_tree(Path::new(""), false);
+        }
+        Ok(watch)
+    }
+
+    /// Run until `tx` is closed or the watched path goes away, sending a
+    /// batch each time changes have been quiet for `debounce`.
+    pub fn run(mut self, debounce: Duration, tx: mpsc::Sender<Result<WatchPathEvent, Status>>) {
+        let mut first_event: Option<Instant> = None;
+        let mut last_event = Instant::now();
+        loop {
+            if tx.is_closed() {
+                return;
+            }
+            let gone = match self.inotify.read_events() {
+                Ok(events) => {
+                    last_event = Instant::now();
+                    first_event.get_or_insert(last_event);
+                    events.into_iter().any(|event| self.handle(event))
+                }
+                Err(Errno::EAGAIN) => false,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(Status::internal(format!(
+                        "Failed to read inotify events: {}",
+                        e
+                    ))));
+                    return;
+                }
+            };
+            let due = first_event.is_some_and(|first| {
+                last_event.elapsed() >= debounce || first.elapsed() >= debounce * MAX_DELAY_FACTOR
+            });
+            if due || gone {
+                first_event = None;
+                if !self.pending.is_empty() {
+                    let changes = self.pending.take();
+                    if tx.blocking_send(Ok(WatchPathEvent { changes })).is_err() {
+                        return;
+                    }
+                }
+            }
+            if gone {
+                return;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Record one event; true once the watched path itself is gone.
+    fn handle(&mut self, event: InotifyEvent) -> bool {
+        let mask = event.mask;
+        if mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+            self.pending.overflow();
+            return false;
+        }
+        let Some(dir) = self.dirs.get(&event.wd).cloned() else {
+            return false;
+        };
+        if mask.intersects(AddWatchFlags::IN_DELETE_SELF | AddWatchFlags::IN_MOVE_SELF) {
+            if dir.as_os_str().is_empty() {
+                self.pending
+                    .record(PathBuf::new(), PathChangeKind::PathChangeDeleted, true);
+                return true;
+            }
+            return false;
+        }
+        if mask.contains(AddWatchFlags::IN_IGNORED) {
+            self.dirs.remove(&event.wd);
+            return false;
+        }
+
+        let path = match &event.name {
+            Some(name) => dir.join(name),
+            None => dir,
+        };
+        let kind = if mask.intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO) {
+            PathChangeKind::PathChangeCreated
+        } else if mask.intersects(AddWatchFlags::IN_DELETE | AddWatchFlags::IN_MOVED_FROM) {
+            PathChangeKind::PathChangeDeleted
+        } else {
+            PathChangeKind::PathChangeModified
+        };
+        let is_dir = mask.contains(AddWatchFlags::IN_ISDIR);
+        if kind == PathChangeKind::PathChangeCreated && self.recursive && is_dir {
+            // Files may land in the directory before its watch is added, so
+            // what is already there is reported as created too
+            if self.add(&path).is_ok() {
+                self.add_tree(&path, true);
+            }
+        }
+        self.pending.record(path, kind, is_dir);
+        false
+    }
+
+    /// Watch the directory (or file) at `relative`.
+    fn add(&mut self, relative: &Path) -> io::Result<()> {
+        let flags = AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_DELETE_SELF
+            | AddWatchFlags::IN_MOVE_SELF
+            | AddWatchFlags::IN_DONT_FOLLOW;
+        let wd = self.inotify.add_watch(&self.root.join(relative), flags)?;
+        self.dirs.insert(wd, relative.to_path_buf());
+        Ok(())
+    }
+
+    /// Watch every directory below `relative`, recording what is found as
+    /// created when `report` is set.
+    fn add_tree(&mut self, relative: &Path, report: bool) {
+        let Ok(entries) = std::fs::read_dir(self.root.join(relative)) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = relative.join(entry.file_name());
+            // Symlinked directories are not descended into
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if report {
+                self.pending
+                    .record(path.clone(), PathChangeKind::PathChangeCreated, is_dir);
+            }
+            if is_dir && self.add(&path).is_ok() {
+                self.add_tree(&path, report);
+            }
+        }
+    }
+}
+
+/// Changes since the last batch, by path.
+#[derive(Default)]
+struct Pending {
+    /// Net change of each path, and whether it is a directory
+    changes: HashMap<PathBuf, (PathChangeKind, bool)>,
+    /// Order paths were first seen in, so batches read chronologically
+    order: Vec<PathBuf>,
+    overflowed: bool,
+}
+
+impl Pending {
+    fn is_empty(&self) -> bool {
+        self.changes.is_empty() && !self.overflowed
+    }
+
+    fn overflow(&mut self) {
+        self.overflowed = true;
+    }
+
+    fn record(&mut self, path: PathBuf, kind: PathChangeKind, is_dir: bool) {
+        use PathChangeKind::*;
+
+        let Some(&(previous, _)) = self.changes.get(&path) else {
+            self.order.push(path.clone());
+            self.changes.insert(path, (kind, is_dir));
+            return;
+        };
+        let net = match (previous, kind) {
+            // Never existed as far as the host is concerned
+            (PathChangeCreated, PathChangeDeleted) => None,
+            (PathChangeCreated, _) => Some(PathChangeCreated),
+            // Replaced: the path is there, with new content
+            (PathChangeDeleted, PathChangeCreated) => Some(PathChangeModified),
+            (_, kind) => Some(kind),
+        };
+        match net {
+            Some(net) => {
+                self.changes.insert(path, (net, is_dir));
+            }
+            None => {
+                self.changes.remove(&path);
+                self.order.retain(|p| p != &path);
+            }
+        }
+    }
+
+    fn take(&mut self) -> Vec<PathChange> {
+        let mut changes = std::mem::take(&mut self.changes);
+        let mut batch: Vec<PathChange> = std::mem::take(&mut self.order)
+            .into_iter()
+            .filter_map(|path| {
+                let (kind, is_dir) = changes.remove(&path)?;
+                Some(PathChange {
+                    path: path.to_string_lossy().into_owned(),
+                    kind: kind as i32,
+                    is_dir,
+                })
+            })
+            .collect();
+        if std::mem::take(&mut self.overflowed) {
+            batch.push(PathChange {
+                path: String::new(),
+                kind: PathChangeKind::PathChangeOverflow as i32,
+                is_dir: false,
+            });
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(batch: Vec<PathChange>) -> Vec<(String, PathChangeKind)> {
+        batch
+            .into_iter()
+            .map(|c| (c.path, PathChangeKind::try_from(c.kind).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_pending_collapses_to_net_effect() {
+        use PathChangeKind::*;
+
+        let mut pending = Pending::default();
+        pending.record("a.txt".into(), PathChangeCreated, false);
+        pending.record("a.txt".into(), PathChangeModified, false);
+        pending.record("tmp".into(), PathChangeCreated, false);
+        pending.record("b.txt".into(), PathChangeDeleted, false);
+        pending.record("tmp".into(), PathChangeDeleted, false);
+        pending.record("b.txt".into(), PathChangeCreated, false);
+        pending.overflow();
+
+        assert_eq!(
+            changes(pending.take()),
+            vec![
+                ("a.txt".to_string(), PathChangeCreated),
+                ("b.txt".to_string(), PathChangeModified),
+                (String::new(), PathChangeOverflow),
+            ]
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_watch_reports_new_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let watch = Watch::new(dir.path(), true).unwrap();
+        let (tx, mut rx) = mpsc::channel(8);
+        let handle = std::thread::spawn(move || watch.run(Duration::from_millis(50), tx));
+
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        let first = changes(rx.blocking_recv().unwrap().unwrap().changes);
+        assert!(first.contains(&("src/main.rs".to_string(), PathChangeKind::PathChangeCreated)));
+
+        std::fs::create_dir(dir.path().join("src/bin")).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        std::fs::write(dir.path().join("src/bin/tool.rs"), "").unwrap();
+        let mut seen = Vec::new();
+        while !seen.contains(&(
+            "src/bin/tool.rs".to_string(),
+            PathChangeKind::PathChangeCreated,
+        )) {
+            seen.extend(changes(rx.blocking_recv().unwrap().unwrap().changes));
+        }
+
+        drop(rx);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,32 @@
+//! X11 application forwarding
+//!
+//! GUI apps in the container render on the host's X server. The agent
+//! serves display `:0` from a guest tmpfs directory mounted at
+//! `/tmp/.X11-unix` in the container and relays connections to the host.
+//!
+//! X11 is a plain byte stream, so it survives the relay; clients fall back
+//! from MIT-SHM to the socket when shared memory is unavailable. Wayland
+//! passes buffers as file descriptors and cannot be relayed this way, so
+//! Wayland hosts are reached through XWayland.
+
+use super::credentials::set_default;
+use super::relay;
+use boxlite_shared::constants::network::GUEST_X11_PORT;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+
+/// Where the X11 socket directory is mounted in the container.
+pub const CONTAINER_X11_DIR: &str = "/tmp/.X11-unix";
+
+/// Serve display `:0` from `dir` and point `DISPLAY` at it.
+pub fn prepare(dir: &Path, env: &mut Vec<String>) -> io::Result<()> {
+    // Sticky and world-writable like the real /tmp/.X11-unix
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o1777)
+        .create(dir)?;
+    relay::listen(&dir.join("X0"), 0o777, GUEST_X11_PORT)?;
+    set_default(env, "DISPLAY", ":0");
+    Ok(())
+}
@@ -0,0 +1,358 @@
+//! Core dump collection for Guest.WatchCrashes
+//!
+//! Watching turns collection on: a tmpfs scratch volume, sized to the
+//! host's limit, is mounted at `/run/boxlite/crashes`, and the kernel's
+//! `core_pattern` pipes every dump to the agent binary running as
+//! `boxlite-core` (a symlink next to the scratch volume):
+//!
+//! ```text
+//! |/run/boxlite/boxlite-core MAX_BYTES %P %s %t %e
+//! ```
+//!
+//! The handler keeps the first MAX_BYTES of the dump as `NAME.core`, reads
+//! the rest so the crashed process can go away, and writes what it learned
+//! to `NAME.json` last. The watch reports each dump once its metadata is
+//! there; the host then takes it over the bulk channel, which frees the
+//! space. Dumps that do not fit the volume are cut short rather than lost.
+//! The volume lives in guest RAM, so pending dumps take up to MAX_BYTES plus
+//! `METADATA_ROOM` of the box's memory until the host takes them.
+//!
+//! Pipe handlers are not bound by RLIMIT_CORE, so processes dump whatever
+//! their limit, except those that set it to 1. The kernel keeps the crashed
+//! process's `/proc` entry until the handler exits (`core_pipe_limit`), so
+//! its command line can be read.
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Component, Path};
+use std::time::Duration;
+
+use boxlite_shared::CrashDump;
+use nix::mount::{mount, MsFlags};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tonic::Status;
+
+use crate::layout::GuestLayout;
+
+/// Name the agent binary answers to as the core dump handler.
+pub const CORE_HELPER_NAME: &str = "boxlite-core";
+
+const USAGE: &str = "usage: boxlite-core MAX_BYTES PID SIGNAL TIME COMMAND";
+
+const CORE_SUFFIX: &str = ".core";
+const INFO_SUFFIX: &str = ".json";
+
+/// Room on the scratch volume for metadata, on top of the dump limit.
+const METADATA_ROOM: u64 = 1024 * 1024;
+
+/// Dumps the kernel pipes to handlers at the same time.
+const CORE_PIPE_LIMIT: &str = "4";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What the handler learned about a dump, stored next to it.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct DumpInfo {
+    pid: u32,
+    signal: u32,
+    command: String,
+    command_line: String,
+    time: i64,
+    size_bytes: u64,
+    stored_bytes: u64,
+}
+
+/// Mount the scratch volume and point the kernel's `core_pattern` at the
+/// handler, keeping up to `max_bytes` of each dump.
+pub fn enable(layout: &GuestLayout, max_bytes: u64) -> io::Result<()> {
+    let dir = layout.crashes_dir();
+    std::fs::create_dir_all(&dir)?;
+    let options = format!("size={},mode=0700", max_bytes.saturating_add(METADATA_ROOM));
+    // A replaced agent mounted it already; keep the dumps on it
+    let flags = if is_mount_point(&dir) {
+        MsFlags::MS_REMOUNT
+    } else {
+        MsFlags::empty()
+    };
+    mount(
+        Some("tmpfs"),
+        &dir,
+        Some("tmpfs"),
+        flags,
+        Some(options.as_str()),
+    )
+    .map_err(io::Error::from)?;
+
+    // The agent binary may have been replaced since the last watch
+    let helper = layout.base().join(CORE_HELPER_NAME);
+    match std::fs::remove_file(&helper) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    std::os::unix::fs::symlink(std::env::current_exe()?, &helper)?;
+
+    std::fs::write("/proc/sys/kernel/core_pipe_limit", CORE_PIPE_LIMIT)?;
+    std::fs::write(
+        "/proc/sys/kernel/core_pattern",
+        format!("|{} {} %P %s %t %e", helper.display(), max_bytes),
+    )
+}
+
+fn is_mount_point(dir: &Path) -> bool {
+    let parent = dir.parent().unwrap_or(dir);
+    match (std::fs::metadata(dir), std::fs::metadata(parent)) {
+        (Ok(dir), Ok(parent)) => dir.dev() != parent.dev(),
+        _ => false,
+    }
+}
+
+/// Report each dump on the scratch volume until `tx` is closed, starting
+/// with those stored before the watch.
+pub fn run(layout: &GuestLayout, tx: mpsc::Sender<Result<CrashDump, Status>>) {
+    let dir = layout.crashes_dir();
+    let mut reported = HashSet::new();
+    loop {
+        if tx.is_closed() {
+            return;
+        }
+        let mut present = HashSet::new();
+        for name in dump_names(&dir) {
+            present.insert(name.clone());
+            if reported.contains(&name) {
+                continue;
+            }
+            let info = match read_info(&dir, &name) {
+                Ok(info) => info,
+                Err(e) => {
+                    tracing::warn!(name = %name, "Failed to read crash dump metadata: {}", e);
+                    continue;
+                }
+            };
+            tracing::info!(
+                name = %name,
+                pid = info.pid,
+                signal = info.signal,
+                command = %info.command,
+                "Core dump stored"
+            );
+            let dump = CrashDump {
+                name: name.clone(),
+                pid: info.pid,
+                signal: info.signal,
+                command: info.command,
+                command_line: info.command_line,
+                time: info.time,
+                size_bytes: info.size_bytes,
+                stored_bytes: info.stored_bytes,
+            };
+            if tx.blocking_send(Ok(dump)).is_err() {
+                return;
+            }
+            reported.insert(name);
+        }
+        // Dumps the host took are forgotten
+        reported.retain(|name| present.contains(name));
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Names of the dumps whose metadata is written, oldest first.
+fn dump_names(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let name = file_name.to_str()?.strip_suffix(INFO_SUFFIX)?;
+            Some(name.to_string())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn read_info(dir: &Path, name: &str) -> io::Result<DumpInfo> {
+    let json = std::fs::read(dir.join(format!("{}{}", name, INFO_SUFFIX)))?;
+    serde_json::from_slice(&json).map_err(io::Error::from)
+}
+
+/// Pack the dump `name` and its metadata into a zstd-compressed tar, for
+/// the bulk channel.
+pub fn pack(layout: &GuestLayout, name: &str) -> io::Result<(File, u64)> {
+    if !is_dump_name(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a crash dump name",
+        ));
+    }
+    let dir = layout.crashes_dir();
+    let info = dir.join(format!("{}{}", name, INFO_SUFFIX));
+    let core = dir.join(format!("{}{}", name, CORE_SUFFIX));
+    if !info.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no such crash dump",
+        ));
+    }
+    // Staged off the scratch volume, which may be full
+    crate::bulk::stage_archive(&std::env::temp_dir(), |builder| {
+        builder.append_path_with_name(&info, format!("{}{}", name, INFO_SUFFIX))?;
+        builder.append_path_with_name(&core, format!("{}{}", name, CORE_SUFFIX))
+    })
+}
+
+/// Remove the dump `name` from the scratch volume.
+pub fn remove(layout: &GuestLayout, name: &str) {
+    if !is_dump_name(name) {
+        return;
+    }
+    let dir = layout.crashes_dir();
+    // Metadata first, so a half-removed dump is never reported
+    for suffix in [INFO_SUFFIX, CORE_SUFFIX] {
+        let path = dir.join(format!("{}{}", name, suffix));
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::debug!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn is_dump_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !name.starts_with('.')
+}
+
+/// Entry point of the agent binary run as `boxlite-core` by the kernel,
+/// with the dump on stdin. Returns the exit code.
+pub fn run_core_helper(args: &[String]) -> i32 {
+    let parsed = (|| {
+        let max_bytes = args.first()?.parse::<u64>().ok()?;
+        let pid = args.get(1)?.parse::<u32>().ok()?;
+        let signal = args.get(2)?.parse::<u32>().ok()?;
+        let time = args.get(3)?.parse::<i64>().ok()?;
+        // Spaces in the executable name split it into several arguments
+        let command = args.get(4..)?.join(" ");
+        Some((max_bytes, pid, signal, time, command))
+    })();
+    let Some((max_bytes, pid, signal, time, command)) = parsed else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let command_line = std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|raw| {
+            String::from_utf8_lossy(&raw)
+                .split('\0')
+                .filter(|arg| !arg.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+    let info = DumpInfo {
+        pid,
+        signal,
+        command,
+        command_line,
+        time,
+        size_bytes: 0,
+        stored_bytes: 0,
+    };
+    match store(
+        &GuestLayout::new().crashes_dir(),
+        info,
+        max_bytes,
+        &mut io::stdin().lock(),
+    ) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("boxlite-core: {}", e);
+            1
+        }
+    }
+}
+
+/// Keep up to `max_bytes` of the dump read from `input` in `dir`, then its
+/// metadata.
+fn store(dir: &Path, mut info: DumpInfo, max_bytes: u64, input: &mut impl Read) -> io::Result<()> {
+    let name = format!("core-{}-{}", info.time, info.pid);
+    let core = dir.join(format!("{}{}", name, CORE_SUFFIX));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&core)?;
+
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut full = false;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        info.size_bytes += n as u64;
+        let keep = (n as u64).min(max_bytes.saturating_sub(info.stored_bytes)) as usize;
+        if full || keep == 0 {
+            continue;
+        }
+        match file.write_all(&buf[..keep]) {
+            Ok(()) => info.stored_bytes += keep as u64,
+            // Other dumps filled the volume; keep what fit
+            Err(e) if e.raw_os_error() == Some(libc::ENOSPC) => full = true,
+            Err(e) => return Err(e),
+        }
+    }
+    info.stored_bytes = file.metadata()?.len();
+
+    let staging = dir.join(format!("{}{}.tmp", name, INFO_SUFFIX));
+    let written = serde_json::to_vec(&info)
+        .map_err(io::Error::from)
+        .and_then(|json| std::fs::write(&staging, json))
+        .and_then(|()| std::fs::rename(&staging, dir.join(format!("{}{}", name, INFO_SUFFIX))));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&staging);
+        let _ = std::fs::remove_file(&core);
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_caps_dump() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = DumpInfo {
+            pid: 42,
+            signal: 11,
+            command: "worker".to_string(),
+            command_line: "worker --fast".to_string(),
+            time: 1_700_000_000,
+            size_bytes: 0,
+            stored_bytes: 0,
+        };
+        let dump = vec![7u8; 200 * 1024];
+        store(dir.path(), info, 100 * 1024, &mut dump.as_slice()).unwrap();
+
+        assert_eq!(dump_names(dir.path()), ["core-1700000000-42"]);
+        let info = read_info(dir.path(), "core-1700000000-42").unwrap();
+        assert_eq!(info.size_bytes, 200 * 1024);
+        assert_eq!(info.stored_bytes, 100 * 1024);
+        assert_eq!(info.command_line, "worker --fast");
+        let core = std::fs::metadata(dir.path().join("core-1700000000-42.core")).unwrap();
+        assert_eq!(core.len(), 100 * 1024);
+
+        assert!(is_dump_name("core-1700000000-42"));
+        assert!(!is_dump_name("../core"));
+        assert!(!is_dump_name(".hidden"));
+    }
+}
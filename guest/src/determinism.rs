@@ -0,0 +1,23 @@
+//! Best-effort deterministic runs
+//!
+//! A deterministic box boots with its wall clock set to a fixed start and
+//! address-space randomization off, so reruns of the same program see the
+//! same time and the same memory layout.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+
+/// Step the clock to `clock_start_secs` and turn ASLR off.
+pub fn apply(clock_start_secs: u64) -> BoxliteResult<()> {
+    let start = UNIX_EPOCH + Duration::from_secs(clock_start_secs);
+    crate::resume::step_clock(start)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to set clock: {}", e)))?;
+    std::fs::write("/proc/sys/kernel/randomize_va_space", "0")
+        .map_err(|e| BoxliteError::Internal(format!("Failed to disable ASLR: {}", e)))?;
+    tracing::info!(
+        clock_start_secs,
+        "Deterministic mode: clock set, ASLR disabled"
+    );
+    Ok(())
+}
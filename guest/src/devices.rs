@@ -0,0 +1,144 @@
+//! Guest device nodes exposed to the container
+//!
+//! The host requests device nodes by guest path (e.g. `/dev/kvm` for nested
+//! virtualization). A node missing from `/dev` is created from its sysfs
+//! `dev` entry, so the device only needs to be known to the kernel.
+//!
+//! The box's device policy can also set each node's permissions and swap
+//! the container's minimal `/dev` tmpfs for a devtmpfs ([`DeviceSetup`]).
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::DevicePolicy;
+use nix::sys::stat::{major, makedev, minor, mknod, stat, Mode, SFlag};
+use std::fs;
+use std::path::Path;
+
+/// A character device node to create in the container's `/dev`.
+#[derive(Debug, Clone)]
+pub struct DeviceNode {
+    pub path: String,
+    pub major: u64,
+    pub minor: u64,
+    pub file_mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// How the container's `/dev` is populated.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSetup {
+    /// Mount devtmpfs at `/dev` instead of a minimal tmpfs
+    pub devtmpfs: bool,
+    pub nodes: Vec<DeviceNode>,
+}
+
+impl DeviceSetup {
+    /// Resolve the requested device paths and the box's device policy.
+    ///
+    /// A path listed in both uses the policy's permissions.
+    pub fn prepare(paths: &[String], policy: Option<&DevicePolicy>) -> BoxliteResult<Self> {
+        let mut nodes = paths
+            .iter()
+            .map(|path| ensure_device_node(path))
+            .collect::<BoxliteResult<Vec<_>>>()?;
+        let Some(policy) = policy else {
+            return Ok(Self {
+                devtmpfs: false,
+                nodes,
+            });
+        };
+
+        for rule in &policy.nodes {
+            let mut node = ensure_device_node(&rule.path)?;
+            node.file_mode = rule.mode & 0o777;
+            node.uid = rule.uid;
+            node.gid = rule.gid;
+            nodes.retain(|n| n.path != node.path);
+            nodes.push(node);
+        }
+        Ok(Self {
+            devtmpfs: policy.devtmpfs,
+            nodes,
+        })
+    }
+}
+
+/// Make sure a character device node exists in the guest and describe it.
+pub fn ensure_device_node(path: &str) -> BoxliteResult<DeviceNode> {
+    if !Path::new(path).exists() {
+        create_from_sysfs(path)?;
+    }
+
+    let st = stat(path)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to stat {}: {}", path, e)))?;
+    if SFlag::from_bits_truncate(st.st_mode) & SFlag::S_IFMT != SFlag::S_IFCHR {
+        return Err(BoxliteError::Internal(format!(
+            "{} is not a character device",
+            path
+        )));
+    }
+
+    Ok(DeviceNode {
+        path: path.to_string(),
+        major: major(st.st_rdev),
+        minor: minor(st.st_rdev),
+        file_mode: 0o666,
+        uid: 0,
+        gid: 0,
+    })
+}
+
+/// Create a device node from `/sys/class/<class>/<name>/dev`.
+fn create_from_sysfs(path: &str) -> BoxliteResult<()> {
+    let name = Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| BoxliteError::Internal(format!("Invalid device path: {}", path)))?;
+
+    let classes = fs::read_dir("/sys/class")
+        .map_err(|e| BoxliteError::Internal(format!("Failed to read /sys/class: {}", e)))?;
+    let numbers = classes
+        .flatten()
+        .filter_map(|class| fs::read_to_string(class.path().join(name).join("dev")).ok())
+        .find_map(|dev| parse_dev_numbers(&dev))
+        .ok_or_else(|| {
+            BoxliteError::Internal(format!("Device {} is not available in the guest", path))
+        })?;
+
+    tracing::info!(
+        path,
+        major = numbers.0,
+        minor = numbers.1,
+        "Creating device node"
+    );
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            BoxliteError::Internal(format!("Failed to create {}: {}", parent.display(), e))
+        })?;
+    }
+    mknod(
+        path,
+        SFlag::S_IFCHR,
+        Mode::from_bits_truncate(0o666),
+        makedev(numbers.0, numbers.1),
+    )
+    .map_err(|e| BoxliteError::Internal(format!("Failed to create {}: {}", path, e)))
+}
+
+/// Parse a sysfs `dev` entry (`major:minor`).
+fn parse_dev_numbers(dev: &str) -> Option<(u64, u64)> {
+    let (major, minor) = dev.trim().split_once(':')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dev_numbers() {
+        assert_eq!(parse_dev_numbers("10:232\n"), Some((10, 232)));
+        assert_eq!(parse_dev_numbers("10"), None);
+        assert_eq!(parse_dev_numbers("a:b"), None);
+    }
+}
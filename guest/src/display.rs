@@ -0,0 +1,145 @@
+//! Display capture for boxes with a virtio-gpu device
+//!
+//! The virtio-gpu driver exposes its scanout through fbdev emulation at
+//! `/dev/fb0`. Whatever renders to the display (a Wayland compositor or X
+//! server in the container using `/dev/dri`, or the console) is captured by
+//! reading the framebuffer and encoding it as PNG.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+
+const FB_DEVICE: &str = "/dev/fb0";
+const FB_SYSFS: &str = "/sys/class/graphics/fb0";
+
+/// A captured display frame.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+}
+
+/// Capture the current framebuffer contents.
+pub fn capture() -> BoxliteResult<Frame> {
+    let (width, height) = read_sysfs(FB_SYSFS, "virtual_size")?
+        .split_once(',')
+        .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)))
+        .ok_or_else(|| BoxliteError::Internal("Invalid framebuffer size".to_string()))?;
+    let bpp: u32 = parse_sysfs(FB_SYSFS, "bits_per_pixel")?;
+    let stride: usize = parse_sysfs(FB_SYSFS, "stride")?;
+
+    if bpp != 32 {
+        return Err(BoxliteError::Unsupported(format!(
+            "Unsupported framebuffer depth: {} bits per pixel",
+            bpp
+        )));
+    }
+
+    let raw = fs::read(FB_DEVICE)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to read {}: {}", FB_DEVICE, e)))?;
+    let rgb = xrgb_to_rgb(&raw, width as usize, height as usize, stride)?;
+
+    Ok(Frame {
+        width,
+        height,
+        png: encode_png(width, height, &rgb)?,
+    })
+}
+
+fn read_sysfs(dir: &str, attr: &str) -> BoxliteResult<String> {
+    fs::read_to_string(format!("{}/{}", dir, attr))
+        .map(|s| s.trim().to_string())
+        .map_err(|e| {
+            BoxliteError::Unsupported(format!("No display available ({}/{}: {})", dir, attr, e))
+        })
+}
+
+fn parse_sysfs<T: std::str::FromStr>(dir: &str, attr: &str) -> BoxliteResult<T> {
+    read_sysfs(dir, attr)?
+        .parse()
+        .map_err(|_| BoxliteError::Internal(format!("Invalid framebuffer {}", attr)))
+}
+
+/// Convert little-endian XRGB8888 rows (B, G, R, X bytes) to packed RGB.
+fn xrgb_to_rgb(raw: &[u8], width: usize, height: usize, stride: usize) -> BoxliteResult<Vec<u8>> {
+    if stride < width * 4 || raw.len() < stride * height {
+        return Err(BoxliteError::Internal(format!(
+            "Framebuffer too small: {} bytes for {}x{} (stride {})",
+            raw.len(),
+            width,
+            height,
+            stride
+        )));
+    }
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for row in raw.chunks(stride).take(height) {
+        for px in row[..width * 4].chunks_exact(4) {
+            rgb.extend_from_slice(&[px[2], px[1], px[0]]);
+        }
+    }
+    Ok(rgb)
+}
+
+/// Encode packed 8-bit RGB pixels as a PNG image.
+fn encode_png(width: u32, height: u32, rgb: &[u8]) -> BoxliteResult<Vec<u8>> {
+    let encode_err = |e: std::io::Error| BoxliteError::Internal(format!("PNG encode: {}", e));
+
+    // Each scanline is prefixed with filter type 0 (none)
+    let row_len = width as usize * 3;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    for row in rgb.chunks(row_len) {
+        encoder.write_all(&[0]).map_err(encode_err)?;
+        encoder.write_all(row).map_err(encode_err)?;
+    }
+    let data = encoder.finish().map_err(encode_err)?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8-bit depth, color type 2 (RGB), default compression/filter, no interlace
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &data);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xrgb_to_rgb_skips_stride_padding() {
+        // 1x2 image, stride 8 (4 bytes of padding per row)
+        let raw = [3, 2, 1, 0, 9, 9, 9, 9, 6, 5, 4, 0, 9, 9, 9, 9];
+        assert_eq!(xrgb_to_rgb(&raw, 1, 2, 8).unwrap(), vec![1, 2, 3, 4, 5, 6]);
+        assert!(xrgb_to_rgb(&raw, 1, 3, 8).is_err());
+    }
+
+    #[test]
+    fn test_encode_png_structure() {
+        let png = encode_png(2, 1, &[255, 0, 0, 0, 255, 0]).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..20], &2u32.to_be_bytes());
+        assert_eq!(&png[20..24], &1u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+}
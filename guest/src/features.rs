@@ -0,0 +1,57 @@
+//! Optional agent features
+//!
+//! Networking and PTY support are cargo features, on by default. An agent
+//! built without them (`--no-default-features`) leaves out rtnetlink and
+//! the terminal code, and starts faster for boxes that neither configure a
+//! network nor run interactive execs. Guest.Capabilities reports what the
+//! agent was built with; requests that need a missing feature fail as
+//! unsupported.
+
+#[cfg(not(all(feature = "net", feature = "pty")))]
+use boxlite_shared::errors::BoxliteError;
+
+use boxlite_shared::constants::agent_features;
+
+/// Optional features this agent was built with.
+pub fn enabled() -> Vec<String> {
+    [
+        (agent_features::NET, cfg!(feature = "net")),
+        (agent_features::PTY, cfg!(feature = "pty")),
+        (
+            agent_features::FAULT_INJECTION,
+            cfg!(feature = "fault-injection"),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, on)| *on)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+/// Error for a request that needs `feature`, which this agent was built
+/// without.
+#[cfg(not(all(feature = "net", feature = "pty")))]
+pub fn missing(feature: &str) -> BoxliteError {
+    BoxliteError::Unsupported(format!(
+        "guest agent was built without the {} feature",
+        feature
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled_matches_build() {
+        let features = enabled();
+        assert_eq!(
+            features.iter().any(|f| f == agent_features::NET),
+            cfg!(feature = "net")
+        );
+        assert_eq!(
+            features.iter().any(|f| f == agent_features::PTY),
+            cfg!(feature = "pty")
+        );
+    }
+}
@@ -6,6 +6,12 @@
 use boxlite_shared::layout::{dirs, SharedGuestLayout, GUEST_BASE};
 use std::path::{Path, PathBuf};
 
+/// Agent upgrade directory name, under the guest base.
+const UPGRADE_DIR: &str = "upgrade";
+
+/// Crash scratch volume directory name, under the guest base.
+const CRASHES_DIR: &str = "crashes";
+
 // ============================================================================
 // CONTAINER LAYOUT (per-container runtime directory)
 // ============================================================================
@@ -42,6 +48,70 @@ impl ContainerLayout {
         self.root.join(dirs::ROOTFS)
     }
 
+    /// SSH directory: /run/boxlite/containers/{cid}/ssh
+    ///
+    /// Host keys and authorized_keys for the container's SSH server. Lives on
+    /// the guest's /run tmpfs, so keys never reach the box's disk.
+    pub fn ssh_dir(&self) -> PathBuf {
+        self.root.join("ssh")
+    }
+
+    /// Credential forwarding directory: /run/boxlite/containers/{cid}/agent
+    ///
+    /// Relay sockets and the git credential helper, on the guest's /run tmpfs.
+    pub fn agent_dir(&self) -> PathBuf {
+        self.root.join("agent")
+    }
+
+    /// X11 socket directory: /run/boxlite/containers/{cid}/x11
+    ///
+    /// Mounted at /tmp/.X11-unix in the container when X11 is forwarded.
+    pub fn x11_dir(&self) -> PathBuf {
+        self.root.join("x11")
+    }
+
+    /// Clipboard directory: /run/boxlite/containers/{cid}/clipboard
+    ///
+    /// Holds the shared clipboard file when clipboard sharing is enabled.
+    pub fn clipboard_dir(&self) -> PathBuf {
+        self.root.join("clipboard")
+    }
+
+    /// Drop directory: /run/boxlite/containers/{cid}/drops
+    ///
+    /// Files dropped from the host land here; mounted at the box's drop path.
+    pub fn drops_dir(&self) -> PathBuf {
+        self.root.join("drops")
+    }
+
+    /// Lock directory: /run/boxlite/containers/{cid}/lock
+    ///
+    /// Holds the lock broker socket and the `boxlite-lock` helper.
+    pub fn lock_dir(&self) -> PathBuf {
+        self.root.join("lock")
+    }
+
+    /// /etc overlay directory: /run/boxlite/containers/{cid}/etc-overlay
+    ///
+    /// Upper and work layers of the container's /etc when it is overlaid.
+    pub fn etc_overlay_dir(&self) -> PathBuf {
+        self.root.join("etc-overlay")
+    }
+
+    /// Mask directory: /run/boxlite/containers/{cid}/masks
+    ///
+    /// Empty directory and file mounted over hidden volume paths.
+    pub fn masks_dir(&self) -> PathBuf {
+        self.root.join("masks")
+    }
+
+    /// Setup directory: /run/boxlite/containers/{cid}/setup
+    ///
+    /// Holds the setup gate FIFO and the `boxlite-setup-gate` helper.
+    pub fn setup_dir(&self) -> PathBuf {
+        self.root.join("setup")
+    }
+
     /// Prepare container directory.
     pub fn prepare(&self) -> std::io::Result<()> {
         std::fs::create_dir_all(self.rootfs_dir())
@@ -155,6 +225,21 @@ impl GuestLayout {
         ContainerLayout::new(self.container_bundle_dir(container_id))
     }
 
+    /// Agent upgrade directory: /run/boxlite/upgrade
+    ///
+    /// Holds the agent binary staged by the host and the state the running
+    /// agent hands to it.
+    pub fn upgrade_dir(&self) -> PathBuf {
+        self.base.join(UPGRADE_DIR)
+    }
+
+    /// Crash scratch volume: /run/boxlite/crashes
+    ///
+    /// Holds core dumps and their metadata until the host takes them.
+    pub fn crashes_dir(&self) -> PathBuf {
+        self.base.join(CRASHES_DIR)
+    }
+
     // ========================================================================
     // PREPARATION
     // ========================================================================
@@ -3,20 +3,52 @@
 #[cfg(not(target_os = "linux"))]
 compile_error!("BoxLite guest is Linux-only; build with a Linux target");
 
+#[cfg(target_os = "linux")]
+mod binfmt;
+#[cfg(target_os = "linux")]
+mod boot;
+#[cfg(target_os = "linux")]
+mod bulk;
 #[cfg(target_os = "linux")]
 mod container;
 #[cfg(target_os = "linux")]
+mod coredump;
+#[cfg(target_os = "linux")]
+mod determinism;
+#[cfg(target_os = "linux")]
+mod devices;
+#[cfg(target_os = "linux")]
+mod display;
+#[cfg(target_os = "linux")]
+mod features;
+#[cfg(target_os = "linux")]
 mod layout;
 #[cfg(target_os = "linux")]
-mod mounts;
+mod memory;
+#[cfg(target_os = "linux")]
+mod modules;
 #[cfg(target_os = "linux")]
+mod mounts;
+#[cfg(all(target_os = "linux", feature = "net"))]
 mod network;
 #[cfg(target_os = "linux")]
 mod overlayfs;
 #[cfg(target_os = "linux")]
+mod pressure;
+#[cfg(target_os = "linux")]
+mod probes;
+#[cfg(target_os = "linux")]
+mod resume;
+#[cfg(target_os = "linux")]
+mod scheduler;
+#[cfg(target_os = "linux")]
 mod service;
 #[cfg(target_os = "linux")]
 mod storage;
+#[cfg(target_os = "linux")]
+mod upgrade;
+#[cfg(target_os = "linux")]
+mod uring;
 
 #[cfg(target_os = "linux")]
 use boxlite_shared::errors::BoxliteResult;
@@ -34,26 +66,83 @@ use tracing::info;
 struct GuestArgs {
     /// Listen URI for host communication
     ///
+    /// Defaults to `boxlite.listen=` on the kernel command line, then to
+    /// vsock port 2695.
     /// Examples:
     ///   --listen vsock://2695
     ///   --listen unix:///var/run/boxlite.sock
-    ///   --listen tcp://127.0.0.1:8080
+    ///   --listen tcp://0.0.0.0:8080
     #[arg(short, long)]
-    listen: String,
+    listen: Option<String>,
 
     /// Notify URI to signal host when ready
     ///
     /// Guest connects to this URI after gRPC server is ready to serve.
+    /// Defaults to `boxlite.notify=` on the kernel command line.
     /// Examples:
     ///   --notify vsock://2696
     ///   --notify unix:///var/run/boxlite-ready.sock
     #[arg(short, long)]
     notify: Option<String>,
+
+    /// Listen URI for bulk file transfers (raw channel beside gRPC)
+    ///
+    /// Examples:
+    ///   --bulk vsock://2697
+    #[arg(long)]
+    bulk: Option<String>,
+
+    /// Most RPCs one client connection may have in flight; calls past it
+    /// are rejected with RESOURCE_EXHAUSTED
+    #[arg(long, default_value_t = service::sessions::DEFAULT_MAX_CONCURRENCY)]
+    max_concurrency: usize,
+
+    /// State handed over by the agent this one replaced (set by
+    /// Guest.UpgradeAgent); the guest is already booted and initialized
+    #[arg(long)]
+    handoff: Option<std::path::PathBuf>,
 }
 
 #[cfg(target_os = "linux")]
 #[tokio::main]
 async fn main() -> BoxliteResult<()> {
+    let started = std::time::Instant::now();
+
+    // Copies of this binary in containers act as the git credential, lock,
+    // network namespace, private filesystem, audit and setup gate helpers,
+    // and the kernel runs it as the core dump handler
+    let mut argv = std::env::args();
+    let program = argv.next().unwrap_or_default();
+    if program.rsplit('/').next() == Some(container::credentials::GIT_HELPER_NAME) {
+        let operation = argv.next().unwrap_or_default();
+        return container::credentials::run_git_helper(&operation).map_err(Into::into);
+    }
+    if program.rsplit('/').next() == Some(container::locks::LOCK_HELPER_NAME) {
+        let args: Vec<String> = argv.collect();
+        std::process::exit(container::locks::run_lock_helper(&args));
+    }
+    #[cfg(feature = "net")]
+    if program.rsplit('/').next() == Some(container::netns::NETNS_HELPER_NAME) {
+        let args: Vec<String> = argv.collect();
+        std::process::exit(container::netns::run_netns_helper(&args).await);
+    }
+    if program.rsplit('/').next() == Some(container::private_fs::PRIVATE_FS_HELPER_NAME) {
+        let args: Vec<String> = argv.collect();
+        std::process::exit(container::private_fs::run_private_fs_helper(&args));
+    }
+    if program.rsplit('/').next() == Some(container::audit::AUDIT_HELPER_NAME) {
+        let args: Vec<String> = argv.collect();
+        std::process::exit(container::audit::run_audit_helper(&args));
+    }
+    if program.rsplit('/').next() == Some(container::setup::GATE_HELPER_NAME) {
+        let args: Vec<String> = argv.collect();
+        std::process::exit(container::setup::run_gate_helper(&args));
+    }
+    if program.rsplit('/').next() == Some(coredump::CORE_HELPER_NAME) {
+        let args: Vec<String> = argv.collect();
+        std::process::exit(coredump::run_core_helper(&args));
+    }
+
     // Set panic hook to ensure we see panics
     std::panic::set_hook(Box::new(|panic_info| {
         eprintln!("[PANIC] Guest agent panicked: {}", panic_info);
@@ -77,24 +166,70 @@ async fn main() -> BoxliteResult<()> {
 
     info!("🚀 BoxLite Guest Agent starting");
 
-    // Mount essential tmpfs directories early
-    // Needed because virtio-fs doesn't support open-unlink-fstat pattern
-    mounts::mount_essential_tmpfs()?;
-
     // Parse command-line arguments with clap
     let args = GuestArgs::parse();
     info!("✅ Arguments parsed successfully");
 
-    // Prepare guest layout directories
     let layout = layout::GuestLayout::new();
-    info!("Preparing guest layout at {}", layout.base().display());
-    layout.prepare_base()?;
+    let handoff = args
+        .handoff
+        .as_deref()
+        .map(upgrade::read_handoff)
+        .transpose()?;
+    let boot_stages = if handoff.is_some() {
+        // The replaced agent booted the guest already
+        Vec::new()
+    } else {
+        boot(&layout).await?
+    };
+
+    if let Some(bulk_uri) = args.bulk {
+        let bulk_layout = layout.clone();
+        tokio::spawn(async move {
+            if let Err(e) = bulk::serve(bulk_uri, bulk_layout).await {
+                tracing::error!("Bulk transfer server stopped: {}", e);
+            }
+        });
+    }
 
-    // Start server in uninitialized state
+    // Start server in uninitialized state, unless it takes over from the
+    // agent it replaced
     // All initialization (mounts, rootfs, network) will happen via Guest.Init RPC
-    info!("🌐 Starting guest server on: {}", args.listen);
-    let server = GuestServer::new(layout);
-    server.run(args.listen, args.notify).await
+    let listen = service::listener::listen_uri(args.listen);
+    info!("🌐 Starting guest server on: {}", listen);
+    let mut server = GuestServer::new(layout, boot_stages, started);
+    let notify = match handoff {
+        Some(handoff) => {
+            // The host is not waiting for an upgraded agent to come up
+            server.restore(handoff).await;
+            None
+        }
+        None => service::listener::notify_uri(args.notify),
+    };
+    server.run(listen, notify, args.max_concurrency).await
+}
+
+/// Run the startup stages that come before the server.
+#[cfg(target_os = "linux")]
+async fn boot(layout: &layout::GuestLayout) -> BoxliteResult<Vec<boot::StageTiming>> {
+    info!("Preparing guest layout at {}", layout.base().display());
+    let startup_layout = layout.clone();
+    boot::BootGraph::new()
+        // Mount essential tmpfs directories early
+        // Needed because virtio-fs doesn't support open-unlink-fstat pattern
+        .blocking_stage("tmpfs", &[], mounts::mount_essential_tmpfs)
+        // Guest layout lives under /run, so it waits for the tmpfs mounts
+        .blocking_stage("layout", &["tmpfs"], move || {
+            startup_layout.prepare_base().map_err(|e| {
+                boxlite_shared::errors::BoxliteError::Internal(format!(
+                    "Failed to prepare guest layout: {}",
+                    e
+                ))
+            })
+        })
+        .run()
+        .await
+        .map_err(|failure| failure.error)
 }
 
 #[cfg(all(test, target_os = "linux"))]
@@ -105,10 +240,13 @@ mod tests {
     fn test_args_structure() {
         // Test that the args structure compiles
         let args = GuestArgs {
-            listen: "vsock://2695".to_string(),
+            listen: Some("vsock://2695".to_string()),
             notify: Some("vsock://2696".to_string()),
+            bulk: None,
+            max_concurrency: 8,
+            handoff: None,
         };
-        assert_eq!(args.listen, "vsock://2695");
+        assert_eq!(args.listen.as_deref(), Some("vsock://2695"));
         assert_eq!(args.notify, Some("vsock://2696".to_string()));
     }
 }
@@ -0,0 +1,208 @@
+//! Memory watchdog for Guest.WatchMemory
+//!
+//! Available memory is polled from `/proc/meminfo`, early-OOM style: when
+//! it drops under the requested share of the total a low memory event goes
+//! out, naming the process with the highest OOM score, so the host can
+//! resize the box or fail the job before the kernel has to pick a victim.
+//! Another is sent only after memory has recovered past the threshold plus
+//! a margin. OOM kills are read from the kernel log (`/dev/kmsg`) as they
+//! happen, with the victim the kernel named.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::Duration;
+
+use boxlite_shared::{memory_event, LowMemory, MemoryEvent, MemoryProcess, OomKill};
+use tokio::sync::mpsc;
+use tonic::Status;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Points above the threshold available memory has to climb back to
+/// before another low memory event is sent.
+const RECOVERY_MARGIN: u64 = 5;
+
+/// Watch until `tx` is closed, reporting memory running low under
+/// `low_percent` of the total (never with 0) and every OOM kill.
+pub fn run(low_percent: u32, tx: mpsc::Sender<Result<MemoryEvent, Status>>) {
+    let mut kmsg = match open_kmsg() {
+        Ok(kmsg) => Some(kmsg),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to open /dev/kmsg, OOM kills are not reported: {}",
+                e
+            );
+            None
+        }
+    };
+    let low_percent = u64::from(low_percent);
+    let mut low = false;
+    let mut record = vec![0u8; 8192];
+    loop {
+        if tx.is_closed() {
+            return;
+        }
+        let mut events = Vec::new();
+        if let Some(file) = kmsg.as_mut() {
+            // One record per read; EAGAIN once caught up, EPIPE if records
+            // were overwritten before they were read
+            loop {
+                match file.read(&mut record) {
+                    Ok(0) => break,
+                    Ok(len) => {
+                        if let Some(victim) =
+                            parse_oom_kill(&String::from_utf8_lossy(&record[..len]))
+                        {
+                            tracing::warn!(
+                                pid = victim.pid,
+                                command = %victim.command,
+                                "OOM killer ended a process"
+                            );
+                            events.push(memory_event::Event::OomKill(OomKill {
+                                victim: Some(victim),
+                            }));
+                        }
+                    }
+                    Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+                    Err(_) => break,
+                }
+            }
+        }
+        if low_percent > 0 {
+            if let Some((total, available)) = std::fs::read_to_string("/proc/meminfo")
+                .ok()
+                .as_deref()
+                .and_then(parse_meminfo)
+            {
+                if !low && available * 100 < total * low_percent {
+                    low = true;
+                    tracing::warn!(available, total, "Guest memory is low");
+                    events.push(memory_event::Event::LowMemory(LowMemory {
+                        available_bytes: available,
+                        total_bytes: total,
+                        top: top_process(),
+                    }));
+                } else if low && available * 100 >= total * (low_percent + RECOVERY_MARGIN) {
+                    low = false;
+                }
+            }
+        }
+        for event in events {
+            let event = MemoryEvent { event: Some(event) };
+            if tx.blocking_send(Ok(event)).is_err() {
+                return;
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The kernel log, positioned after the records already in it.
+fn open_kmsg() -> io::Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/dev/kmsg")?;
+    file.seek(SeekFrom::End(0))?;
+    Ok(file)
+}
+
+/// Total and available memory in bytes.
+pub fn parse_meminfo(content: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        content.lines().find_map(|line| {
+            let kib = line.strip_prefix(name)?.strip_prefix(':')?;
+            kib.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()
+        })
+    };
+    Some((field("MemTotal")? * 1024, field("MemAvailable")? * 1024))
+}
+
+/// The victim of a kmsg record such as `3,812,4417,-;Out of memory:
+/// Killed process 321 (python3) total-vm:..., anon-rss:524288kB, ...`.
+fn parse_oom_kill(record: &str) -> Option<MemoryProcess> {
+    let message = record
+        .split_once(';')
+        .map_or(record, |(_, message)| message);
+    let rest = message.split_once("Killed process ")?.1;
+    let (pid, rest) = rest.split_once(' ')?;
+    let (command, rest) = rest.strip_prefix('(')?.split_once(')')?;
+    let rss_kib = ["anon-rss:", "file-rss:", "shmem-rss:"]
+        .iter()
+        .filter_map(|key| {
+            let value = rest.split_once(key)?.1;
+            let end = value.find("kB")?;
+            value[..end].parse::<u64>().ok()
+        })
+        .sum::<u64>();
+    Some(MemoryProcess {
+        pid: pid.parse().ok()?,
+        command: command.to_string(),
+        rss_bytes: rss_kib * 1024,
+    })
+}
+
+/// The process the OOM killer would pick now.
+fn top_process() -> Option<MemoryProcess> {
+    // SAFETY: sysconf has no preconditions
+    let page_size = u64::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(4096);
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let dir = entry.path();
+            let score: u64 = std::fs::read_to_string(dir.join("oom_score"))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()?;
+            let rss_pages: u64 = std::fs::read_to_string(dir.join("statm"))
+                .ok()?
+                .split_whitespace()
+                .nth(1)?
+                .parse()
+                .ok()?;
+            let command = std::fs::read_to_string(dir.join("comm")).ok()?;
+            Some((
+                score,
+                MemoryProcess {
+                    pid,
+                    command: command.trim_end().to_string(),
+                    rss_bytes: rss_pages * page_size,
+                },
+            ))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, process)| process)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_meminfo() {
+        let content = "MemTotal:        2014560 kB\nMemFree:          100000 kB\nMemAvailable:     512000 kB\n";
+        assert_eq!(
+            parse_meminfo(content),
+            Some((2014560 * 1024, 512000 * 1024))
+        );
+        assert_eq!(parse_meminfo("MemTotal: 10 kB\n"), None);
+    }
+
+    #[test]
+    fn test_parse_oom_kill() {
+        let record = "3,812,44172,-;Out of memory: Killed process 321 (python3) total-vm:1048576kB, anon-rss:524288kB, file-rss:1024kB, shmem-rss:0kB, UID:0 pgtables:1200kB oom_score_adj:0";
+        let victim = parse_oom_kill(record).unwrap();
+        assert_eq!(victim.pid, 321);
+        assert_eq!(victim.command, "python3");
+        assert_eq!(victim.rss_bytes, (524288 + 1024) * 1024);
+
+        let memcg = "3,900,1,-;Memory cgroup out of memory: Killed process 77 (node worker) total-vm:10kB, anon-rss:8kB, file-rss:0kB, shmem-rss:0kB";
+        let victim = parse_oom_kill(memcg).unwrap();
+        assert_eq!((victim.pid, victim.command.as_str()), (77, "node worker"));
+
+        assert!(parse_oom_kill("6,1,1,-;eth0: link up").is_none());
+    }
+}
@@ -0,0 +1,82 @@
+//! Kernel module loading for guest devices
+//!
+//! Device drivers (e.g. GPU drivers) may be built as loadable modules in the
+//! guest kernel. Modules that are already present - built in or loaded - are
+//! skipped, so the same request works with either kernel configuration.
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use std::path::Path;
+use std::process::Command;
+
+/// Load kernel modules in order, skipping ones already present.
+pub fn load_modules(modules: &[String]) -> BoxliteResult<()> {
+    for module in modules {
+        if !is_module_name(module) {
+            return Err(BoxliteError::InvalidArgument(format!(
+                "invalid kernel module name '{}'",
+                module
+            )));
+        }
+        if is_loaded(module) {
+            tracing::debug!(module = %module, "Kernel module already present");
+            continue;
+        }
+
+        tracing::info!(module = %module, "Loading kernel module");
+        let output = Command::new("modprobe")
+            .arg("--")
+            .arg(module)
+            .output()
+            .map_err(|e| {
+                BoxliteError::Internal(format!("Failed to run modprobe {}: {}", module, e))
+            })?;
+        if !output.status.success() {
+            return Err(BoxliteError::Internal(format!(
+                "modprobe {} failed: {}",
+                module,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// A bare module name, so the host can't pass modprobe an option or a path.
+fn is_module_name(module: &str) -> bool {
+    !module.is_empty()
+        && !module.starts_with('-')
+        && module
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Check `/sys/module`, which lists both loaded and built-in modules.
+fn is_loaded(module: &str) -> bool {
+    Path::new("/sys/module").join(sysfs_name(module)).exists()
+}
+
+/// Module names use `_` in sysfs, while `modprobe` accepts either.
+fn sysfs_name(module: &str) -> String {
+    module.replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sysfs_name() {
+        assert_eq!(sysfs_name("virtio-gpu"), "virtio_gpu");
+        assert_eq!(sysfs_name("nvidia_uvm"), "nvidia_uvm");
+    }
+
+    #[test]
+    fn test_is_module_name() {
+        assert!(is_module_name("virtio_gpu"));
+        assert!(is_module_name("virtio-gpu"));
+        assert!(!is_module_name(""));
+        assert!(!is_module_name("--help"));
+        assert!(!is_module_name("-r"));
+        assert!(!is_module_name("../evil"));
+    }
+}
@@ -279,6 +279,119 @@ pub async fn configure_network_from_config(
     Ok(())
 }
 
+/// Re-apply the network configuration after the host slept, and announce
+/// the guest address.
+///
+/// Address and route are re-added if the host side lost them, and a
+/// gratuitous ARP makes the network backend relearn where the guest is,
+/// so forwarded ports reach it again without waiting for its neighbor
+/// entry to expire.
+pub async fn refresh_network(
+    interface: &str,
+    ip: Option<&str>,
+    gateway: Option<&str>,
+) -> BoxliteResult<()> {
+    configure_network_from_config(interface, ip, gateway).await?;
+    if let Some(ip_str) = ip {
+        let (ip_addr, _) = parse_ip_prefix(ip_str)?;
+        announce(interface, ip_addr).map_err(|e| {
+            BoxliteError::Internal(format!(
+                "Failed to announce {} on {}: {}",
+                ip_addr, interface, e
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+/// Broadcast a gratuitous ARP request for `ip` from `interface`.
+fn announce(interface: &str, ip: Ipv4Addr) -> std::io::Result<()> {
+    use std::io::{Error, ErrorKind};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let mac = read_mac(interface)?;
+    let name = std::ffi::CString::new(interface)
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "interface name has a NUL"))?;
+    // SAFETY: name is a valid C string
+    let if_index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if if_index == 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let protocol = (libc::ETH_P_ARP as u16).to_be();
+    // SAFETY: plain socket(2) call
+    let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, protocol as i32) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    // SAFETY: fd was just opened and is owned here
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: sockaddr_ll is plain data; all-zero is a valid value
+    let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = protocol;
+    addr.sll_ifindex = if_index as i32;
+    addr.sll_halen = 6;
+    addr.sll_addr[..6].copy_from_slice(&[0xff; 6]);
+
+    let frame = arp_announcement(mac, ip);
+    // SAFETY: frame and addr outlive the call, and the lengths match them
+    let sent = unsafe {
+        libc::sendto(
+            socket.as_raw_fd(),
+            frame.as_ptr().cast(),
+            frame.len(),
+            0,
+            (&addr as *const libc::sockaddr_ll).cast(),
+            std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Hardware address of `interface`, from sysfs.
+fn read_mac(interface: &str) -> std::io::Result<[u8; 6]> {
+    let text = std::fs::read_to_string(format!("/sys/class/net/{}/address", interface))?;
+    let mut mac = [0u8; 6];
+    let mut octets = text.trim().split(':');
+    for byte in &mut mac {
+        *byte = octets
+            .next()
+            .and_then(|octet| u8::from_str_radix(octet, 16).ok())
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid address {:?}", text.trim()),
+                )
+            })?;
+    }
+    Ok(mac)
+}
+
+/// Ethernet frame of an ARP request asking for `ip` from `ip` itself.
+fn arp_announcement(mac: [u8; 6], ip: Ipv4Addr) -> [u8; 42] {
+    let mut frame = [0u8; 42];
+    // Ethernet: broadcast destination, our source, ARP ethertype
+    frame[0..6].copy_from_slice(&[0xff; 6]);
+    frame[6..12].copy_from_slice(&mac);
+    frame[12..14].copy_from_slice(&(libc::ETH_P_ARP as u16).to_be_bytes());
+    // ARP: Ethernet/IPv4, request
+    frame[14..16].copy_from_slice(&1u16.to_be_bytes());
+    frame[16..18].copy_from_slice(&(libc::ETH_P_IP as u16).to_be_bytes());
+    frame[18] = 6;
+    frame[19] = 4;
+    frame[20..22].copy_from_slice(&1u16.to_be_bytes());
+    frame[22..28].copy_from_slice(&mac);
+    frame[28..32].copy_from_slice(&ip.octets());
+    // Target hardware address stays zero; target protocol address is ours
+    frame[38..42].copy_from_slice(&ip.octets());
+    frame
+}
+
 /// Parse IP address with optional prefix (e.g., "192.168.127.2/24" or "192.168.127.2")
 fn parse_ip_prefix(ip_str: &str) -> BoxliteResult<(Ipv4Addr, u8)> {
     if let Some((ip_part, prefix_part)) = ip_str.split_once('/') {
@@ -297,3 +410,23 @@ fn parse_ip_prefix(ip_str: &str) -> BoxliteResult<(Ipv4Addr, u8)> {
         Ok((ip_addr, 24))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arp_announcement() {
+        let mac = [0x5a, 0x94, 0xef, 0xe4, 0x0c, 0xee];
+        let ip = Ipv4Addr::new(192, 168, 127, 2);
+        let frame = arp_announcement(mac, ip);
+        assert_eq!(frame[0..6], [0xff; 6]);
+        assert_eq!(frame[6..12], mac);
+        assert_eq!(frame[12..14], [0x08, 0x06]);
+        assert_eq!(frame[20..22], [0x00, 0x01]);
+        assert_eq!(frame[22..28], mac);
+        assert_eq!(frame[28..32], [192, 168, 127, 2]);
+        assert_eq!(frame[32..38], [0; 6]);
+        assert_eq!(frame[38..42], [192, 168, 127, 2]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Pressure watch for Guest.WatchPressure, and memory reclaim
+//!
+//! CPU, memory and I/O pressure are polled from the kernel's PSI files
+//! (`/proc/pressure/*`). A resource comes under pressure when the share of
+//! the last 10 seconds some task stalled on it reaches the threshold, and
+//! is relieved once it has dropped back under the threshold minus a
+//! margin; each change goes out as one event. Kernels built without PSI, or
+//! booted with `psi=0`, have no files to read, and the watch fails.
+
+use std::path::Path;
+use std::time::Duration;
+
+use boxlite_shared::PressureEvent;
+use tokio::sync::mpsc;
+use tonic::Status;
+
+const PRESSURE_DIR: &str = "/proc/pressure";
+const RESOURCES: [&str; 3] = ["cpu", "memory", "io"];
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Points under the threshold `some avg10` has to drop to before the
+/// resource is relieved.
+const RELIEF_MARGIN: f32 = 5.0;
+
+/// One PSI file's averages, in percent.
+#[derive(Debug, Default, PartialEq)]
+struct Psi {
+    some_avg10: f32,
+    some_avg60: f32,
+    full_avg10: f32,
+}
+
+/// Whether the kernel reports pressure.
+pub fn available() -> bool {
+    Path::new(PRESSURE_DIR).join("memory").exists()
+}
+
+/// Watch until `tx` is closed, reporting each resource coming under
+/// `threshold_percent` of pressure and being relieved.
+pub fn run(threshold_percent: u32, tx: mpsc::Sender<Result<PressureEvent, Status>>) {
+    let threshold = threshold_percent as f32;
+    let mut under = [false; RESOURCES.len()];
+    loop {
+        if tx.is_closed() {
+            return;
+        }
+        for (resource, under) in RESOURCES.iter().zip(under.iter_mut()) {
+            let Some(psi) = std::fs::read_to_string(Path::new(PRESSURE_DIR).join(resource))
+                .ok()
+                .as_deref()
+                .and_then(parse_psi)
+            else {
+                continue;
+            };
+            let changed = if *under {
+                psi.some_avg10 < threshold - RELIEF_MARGIN
+            } else {
+                psi.some_avg10 >= threshold
+            };
+            if !changed {
+                continue;
+            }
+            *under = !*under;
+            tracing::info!(
+                resource,
+                under_pressure = *under,
+                some_avg10 = psi.some_avg10,
+                "Guest pressure changed"
+            );
+            let event = PressureEvent {
+                resource: resource.to_string(),
+                under_pressure: *under,
+                some_avg10: psi.some_avg10,
+                some_avg60: psi.some_avg60,
+                full_avg10: psi.full_avg10,
+            };
+            if tx.blocking_send(Ok(event)).is_err() {
+                return;
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Drop clean page cache and compact memory; returns how many bytes more
+/// are available afterwards.
+pub fn reclaim() -> u64 {
+    let available = || {
+        std::fs::read_to_string("/proc/meminfo")
+            .ok()
+            .as_deref()
+            .and_then(crate::memory::parse_meminfo)
+            .map_or(0, |(_, available)| available)
+    };
+    let before = available();
+    // SAFETY: sync has no preconditions
+    unsafe { libc::sync() };
+    for (knob, value) in [("drop_caches", "1"), ("compact_memory", "1")] {
+        if let Err(e) = std::fs::write(Path::new("/proc/sys/vm").join(knob), value) {
+            tracing::debug!("Failed to write vm.{}: {}", knob, e);
+        }
+    }
+    available().saturating_sub(before)
+}
+
+/// The averages of a PSI file such as
+/// `some avg10=1.50 avg60=0.80 avg300=0.20 total=12345`, with a `full`
+/// line after it except for the CPU of older kernels.
+fn parse_psi(content: &str) -> Option<Psi> {
+    let averages = |kind: &str| {
+        let line = content
+            .lines()
+            .find_map(|line| line.strip_prefix(kind)?.strip_prefix(' '))?;
+        let field = |name: &str| {
+            line.split_whitespace().find_map(|pair| {
+                pair.strip_prefix(name)?
+                    .strip_prefix('=')?
+                    .parse::<f32>()
+                    .ok()
+            })
+        };
+        Some((field("avg10")?, field("avg60")?))
+    };
+    let (some_avg10, some_avg60) = averages("some")?;
+    let full_avg10 = averages("full").map_or(0.0, |(avg10, _)| avg10);
+    Some(Psi {
+        some_avg10,
+        some_avg60,
+        full_avg10,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_psi() {
+        let memory = "some avg10=31.25 avg60=12.00 avg300=3.10 total=987654\nfull avg10=8.50 avg60=2.00 avg300=0.50 total=12345\n";
+        assert_eq!(
+            parse_psi(memory),
+            Some(Psi {
+                some_avg10: 31.25,
+                some_avg60: 12.0,
+                full_avg10: 8.5,
+            })
+        );
+
+        let cpu = "some avg10=0.00 avg60=0.10 avg300=0.00 total=42\n";
+        assert_eq!(parse_psi(cpu).unwrap().full_avg10, 0.0);
+
+        assert_eq!(parse_psi("avg10=1.00\n"), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Health probes for Container.WatchHealth
+//!
+//! Each probe runs on its own interval against the container: a command run
+//! in it, a TCP connect, or an HTTP GET to a port on the guest. A probe turns
+//! unhealthy after `failure_threshold` consecutive failures and healthy again
+//! on its first success; only those changes are sent to the host.
+
+use crate::container::Container;
+use crate::scheduler;
+use boxlite_shared::{health_probe, HealthEvent, HealthProbe, PeriodicTask, ProbeKind};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::MissedTickBehavior;
+use tonic::Status;
+use tracing::debug;
+
+/// Used when a probe sets no interval.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Used when a probe sets no timeout.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Shortest accepted interval.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bytes of an HTTP response read to find its status line.
+const STATUS_LINE_MAX: usize = 1024;
+
+type Events = mpsc::Sender<Result<HealthEvent, Status>>;
+
+/// Start running `probes` in `container`; their changes arrive on the
+/// returned channel, and they stop once it is dropped.
+pub fn watch(
+    container: Arc<Mutex<Container>>,
+    probes: Vec<HealthProbe>,
+) -> Result<mpsc::Receiver<Result<HealthEvent, Status>>, String> {
+    if probes.is_empty() {
+        return Err("At least one probe is required".to_string());
+    }
+    for probe in &probes {
+        if probe.check.is_none() {
+            return Err("Every probe needs a check".to_string());
+        }
+        if probe.interval_ms != 0 && Duration::from_millis(probe.interval_ms) < MIN_INTERVAL {
+            return Err(format!(
+                "Probe interval must be at least {}ms",
+                MIN_INTERVAL.as_millis()
+            ));
+        }
+    }
+
+    let (tx, rx) = mpsc::channel(16);
+    for probe in probes {
+        tokio::spawn(run_probe(container.clone(), probe, tx.clone()));
+    }
+    Ok(rx)
+}
+
+async fn run_probe(container: Arc<Mutex<Container>>, probe: HealthProbe, tx: Events) {
+    let kind = ProbeKind::try_from(probe.kind).unwrap_or(ProbeKind::ProbeReadiness);
+    let interval = match probe.interval_ms {
+        0 => DEFAULT_INTERVAL,
+        ms => Duration::from_millis(ms),
+    };
+    let mut ticker = tokio::time::interval_at(
+        tokio::time::Instant::now() + Duration::from_millis(probe.initial_delay_ms),
+        interval,
+    );
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut tracker = Tracker::new(probe.failure_threshold);
+    loop {
+        tokio::select! {
+            _ = tx.closed() => return,
+            _ = ticker.tick() => {}
+        }
+        let result = check(&container, &probe).await;
+        if let Err(reason) = &result {
+            debug!(probe = ?kind, "Health check failed: {}", reason);
+        }
+        if let Some(healthy) = tracker.record(result.is_ok()) {
+            let event = HealthEvent {
+                kind: kind as i32,
+                healthy,
+                message: result.err().unwrap_or_default(),
+            };
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Turns check results into health changes.
+struct Tracker {
+    threshold: u32,
+    failures: u32,
+    healthy: Option<bool>,
+}
+
+impl Tracker {
+    fn new(threshold: u32) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            failures: 0,
+            healthy: None,
+        }
+    }
+
+    /// The new health, if this result changed it.
+    fn record(&mut self, passed: bool) -> Option<bool> {
+        let healthy = if passed {
+            self.failures = 0;
+            true
+        } else {
+            self.failures = self.failures.saturating_add(1);
+            if self.failures < self.threshold {
+                return None;
+            }
+            false
+        };
+        (self.healthy.replace(healthy) != Some(healthy)).then_some(healthy)
+    }
+}
+
+async fn check(container: &Mutex<Container>, probe: &HealthProbe) -> Result<(), String> {
+    let timeout = match probe.timeout_ms {
+        0 => DEFAULT_TIMEOUT,
+        ms => Duration::from_millis(ms),
+    };
+    match probe.check.as_ref() {
+        Some(health_probe::Check::Command(command)) => {
+            let task = PeriodicTask {
+                name: "health-probe".to_string(),
+                program: command.program.clone(),
+                args: command.args.clone(),
+                timeout_ms: timeout.as_millis() as u64,
+                ..Default::default()
+            };
+            let run = scheduler::run_once(container, &task).await?;
+            if let Some(error) = run.error {
+                return Err(error);
+            }
+            match run.exit_code {
+                0 => Ok(()),
+                code => Err(format!("Exited with {}: {}", code, run.output.trim())),
+            }
+        }
+        Some(health_probe::Check::Tcp(tcp)) => tokio::time::timeout(timeout, connect(tcp.port))
+            .await
+            .map_err(|_| format!("Timed out after {}ms", timeout.as_millis()))?
+            .map(drop),
+        Some(health_probe::Check::Http(http)) => {
+            let path = if http.path.is_empty() {
+                "/"
+            } else {
+                &http.path
+            };
+            tokio::time::timeout(timeout, get(http.port, path))
+                .await
+                .map_err(|_| format!("Timed out after {}ms", timeout.as_millis()))?
+        }
+        None => Err("Probe has no check".to_string()),
+    }
+}
+
+async fn connect(port: u32) -> Result<TcpStream, String> {
+    let port = u16::try_from(port).map_err(|_| format!("Invalid port {}", port))?;
+    TcpStream::connect(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to connect to port {}: {}", port, e))
+}
+
+async fn get(port: u32, path: &str) -> Result<(), String> {
+    let mut stream = connect(port).await?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: localhost:{}\r\nUser-Agent: boxlite-probe\r\nConnection: close\r\n\r\n",
+        path, port
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 256];
+    while !response.windows(2).any(|w| w == b"\r\n") && response.len() < STATUS_LINE_MAX {
+        let n = stream
+            .read(&mut buf)
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+    match status_code(&response) {
+        Some(code) if (200..400).contains(&code) => Ok(()),
+        Some(code) => Err(format!("GET {} returned {}", path, code)),
+        None => Err(format!("GET {} returned no HTTP status", path)),
+    }
+}
+
+/// Status code of an HTTP response starting with `response`.
+fn status_code(response: &[u8]) -> Option<u16> {
+    let line = response.split(|&b| b == b'\r').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split(' ');
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = Tracker::new(3);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(true), Some(true));
+        assert_eq!(tracker.record(true), None);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(false), Some(false));
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(true), Some(true));
+
+        let mut tracker = Tracker::new(0);
+        assert_eq!(tracker.record(false), Some(false));
+    }
+
+    #[test]
+    fn test_status_code() {
+        assert_eq!(status_code(b"HTTP/1.1 204 No Content\r\n"), Some(204));
+        assert_eq!(status_code(b"HTTP/1.0 503 Service Unavailable"), Some(503));
+        assert_eq!(status_code(b"SSH-2.0-OpenSSH\r\n"), None);
+        assert_eq!(status_code(b""), None);
+    }
+}
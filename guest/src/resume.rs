@@ -0,0 +1,129 @@
+//! Fix-ups for a guest booted from cloned or restored disks
+//!
+//! Such a guest starts with state that belongs to another run: the disks
+//! carry the random seed and anything else the image saved on the way
+//! down, and several clones of one box would otherwise boot from identical
+//! state. When the host sends Guest.Resume the agent steps the clock to the
+//! host's, mixes host entropy into the kernel pool and forces the CRNG to
+//! reseed from it, and rotates its session token.
+//!
+//! A clone also gets a machine ID of its own before its container starts;
+//! its hostname, the short container ID, is new with the container ID.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `_IO('R', 0x07)`: reseed the CRNG from the input pool (Linux 4.17+).
+const RNDRESEEDCRNG: u32 = 0x5207;
+
+/// Set the wall clock to `host_time`, returning how far it moved in ms.
+pub fn step_clock(host_time: SystemTime) -> io::Result<i64> {
+    let now = SystemTime::now();
+    let since_epoch = host_time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "host time before epoch"))?;
+    let ts = nix::libc::timespec {
+        tv_sec: since_epoch.as_secs() as _,
+        tv_nsec: since_epoch.subsec_nanos() as _,
+    };
+    // SAFETY: ts is a valid timespec
+    if unsafe { nix::libc::clock_settime(nix::libc::CLOCK_REALTIME, &ts) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(step_millis(now, host_time))
+}
+
+/// Signed step in milliseconds from `before` to `host_time`.
+fn step_millis(before: SystemTime, host_time: SystemTime) -> i64 {
+    match host_time.duration_since(before) {
+        Ok(forward) => forward.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+/// Mix `entropy` into the kernel pool and reseed the CRNG from it.
+pub fn reseed(entropy: &[u8]) -> io::Result<()> {
+    let mut random = OpenOptions::new().write(true).open("/dev/urandom")?;
+    random.write_all(entropy)?;
+    // SAFETY: RNDRESEEDCRNG takes no argument
+    if unsafe { nix::libc::ioctl(random.as_raw_fd(), RNDRESEEDCRNG as _) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Where images keep their machine ID, relative to the rootfs.
+const MACHINE_ID_PATHS: &[&str] = &["etc/machine-id", "var/lib/dbus/machine-id"];
+
+/// Replace the machine ID in `rootfs` with a random one; returns whether
+/// there was one to replace.
+///
+/// A missing, empty or `uninitialized` ID is left for systemd to set on
+/// boot. Symlinks are left alone: D-Bus's usually points at /etc's.
+pub fn new_machine_id(rootfs: &Path) -> io::Result<bool> {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let mut replaced = false;
+    for path in MACHINE_ID_PATHS {
+        let path = rootfs.join(path);
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let current = std::fs::read_to_string(&path)?;
+        if matches!(current.trim(), "" | "uninitialized") {
+            continue;
+        }
+        std::fs::write(&path, format!("{}\n", id))?;
+        replaced = true;
+    }
+    Ok(replaced)
+}
+
+/// A fresh session token.
+pub fn new_session_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_step_millis_is_signed() {
+        let t = UNIX_EPOCH + Duration::from_secs(1_000);
+        assert_eq!(step_millis(t, t + Duration::from_millis(1500)), 1500);
+        assert_eq!(step_millis(t, t - Duration::from_secs(2)), -2000);
+        assert_ne!(new_session_token(), new_session_token());
+    }
+
+    #[test]
+    fn test_new_machine_id() {
+        let rootfs = tempfile::tempdir().unwrap();
+        assert!(!new_machine_id(rootfs.path()).unwrap());
+
+        let etc = rootfs.path().join("etc/machine-id");
+        std::fs::create_dir_all(etc.parent().unwrap()).unwrap();
+        std::fs::write(&etc, "uninitialized\n").unwrap();
+        assert!(!new_machine_id(rootfs.path()).unwrap());
+
+        let old = "0123456789abcdef0123456789abcdef\n";
+        std::fs::write(&etc, old).unwrap();
+        let dbus = rootfs.path().join("var/lib/dbus/machine-id");
+        std::fs::create_dir_all(dbus.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink("/etc/machine-id", &dbus).unwrap();
+
+        assert!(new_machine_id(rootfs.path()).unwrap());
+        let new = std::fs::read_to_string(&etc).unwrap();
+        assert_ne!(new, old);
+        assert_eq!(new.trim().len(), 32);
+        assert!(std::fs::symlink_metadata(&dbus).unwrap().is_symlink());
+    }
+}
@@ -0,0 +1,283 @@
+//! Periodic task scheduler
+//!
+//! Runs registered commands in the container on a fixed interval and keeps
+//! the outcome of each task's last run for the host to query. A task never
+//! overlaps itself: if a run takes longer than the interval, the missed
+//! ticks are skipped.
+
+use crate::container::Container;
+use boxlite_shared::{PeriodicTask, TaskHandoff, TaskStatus};
+use futures::{Stream, StreamExt};
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitStatus};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+/// Shortest accepted interval.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bytes of output kept from each run.
+const OUTPUT_TAIL: usize = 4096;
+
+struct ScheduledTask {
+    container_id: String,
+    task: PeriodicTask,
+    status: Arc<Mutex<TaskStatus>>,
+    runner: JoinHandle<()>,
+}
+
+/// Registry of periodic tasks.
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Mutex<HashMap<String, ScheduledTask>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start running `task` in `container`, replacing any task with its name.
+    pub async fn register(
+        &self,
+        container_id: &str,
+        container: Arc<Mutex<Container>>,
+        task: PeriodicTask,
+    ) -> Result<(), String> {
+        if task.name.is_empty() || task.program.is_empty() {
+            return Err("Task name and program are required".to_string());
+        }
+        let interval = Duration::from_millis(task.interval_ms);
+        if interval < MIN_INTERVAL {
+            return Err(format!(
+                "Task interval must be at least {}s",
+                MIN_INTERVAL.as_secs()
+            ));
+        }
+
+        let status = Arc::new(Mutex::new(TaskStatus {
+            name: task.name.clone(),
+            interval_ms: task.interval_ms,
+            ..Default::default()
+        }));
+        let name = task.name.clone();
+        let runner = tokio::spawn(run_periodically(
+            container,
+            task.clone(),
+            interval,
+            status.clone(),
+        ));
+
+        info!(task = %name, interval_ms = interval.as_millis(), "Registered periodic task");
+        let scheduled = ScheduledTask {
+            container_id: container_id.to_string(),
+            task,
+            status,
+            runner,
+        };
+        if let Some(old) = self.tasks.lock().await.insert(name, scheduled) {
+            old.runner.abort();
+        }
+        Ok(())
+    }
+
+    /// Stop a task. A run in progress is left to finish.
+    pub async fn unregister(&self, name: &str) -> Result<(), String> {
+        let task = self
+            .tasks
+            .lock()
+            .await
+            .remove(name)
+            .ok_or_else(|| format!("Task not found: {}", name))?;
+        task.runner.abort();
+        info!(task = %name, "Unregistered periodic task");
+        Ok(())
+    }
+
+    /// Status of every task, sorted by name.
+    pub async fn list(&self) -> Vec<TaskStatus> {
+        let tasks = self.tasks.lock().await;
+        let mut statuses = Vec::with_capacity(tasks.len());
+        for task in tasks.values() {
+            statuses.push(task.status.lock().await.clone());
+        }
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Every task and its container, for the agent replacing this one to
+    /// register again.
+    pub async fn handoff(&self) -> Vec<TaskHandoff> {
+        self.tasks
+            .lock()
+            .await
+            .values()
+            .map(|scheduled| TaskHandoff {
+                container_id: scheduled.container_id.clone(),
+                task: Some(scheduled.task.clone()),
+            })
+            .collect()
+    }
+}
+
+async fn run_periodically(
+    container: Arc<Mutex<Container>>,
+    task: PeriodicTask,
+    interval: Duration,
+    status: Arc<Mutex<TaskStatus>>,
+) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        ticker.tick().await;
+
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let start = Instant::now();
+        let outcome = run_once(&container, &task).await;
+        let duration = start.elapsed();
+
+        let mut status = status.lock().await;
+        status.runs += 1;
+        status.last_run_unix_ms = started_at.as_millis() as u64;
+        status.last_duration_ms = duration.as_millis() as u64;
+        match outcome {
+            Ok(run) => {
+                if run.exit_code != 0 || run.error.is_some() {
+                    status.failures += 1;
+                }
+                status.last_exit_code = Some(run.exit_code);
+                status.last_output = run.output;
+                status.last_error = run.error.unwrap_or_default();
+            }
+            Err(e) => {
+                warn!(task = %task.name, "Periodic task failed to start: {}", e);
+                status.failures += 1;
+                status.last_exit_code = None;
+                status.last_output.clear();
+                status.last_error = e;
+            }
+        }
+    }
+}
+
+pub(crate) struct RunOutcome {
+    pub(crate) exit_code: i32,
+    /// Tail of the combined stdout and stderr
+    pub(crate) output: String,
+    /// Why the run was killed
+    pub(crate) error: Option<String>,
+}
+
+/// Run `task` once in `container`, killing it after its timeout.
+pub(crate) async fn run_once(
+    container: &Mutex<Container>,
+    task: &PeriodicTask,
+) -> Result<RunOutcome, String> {
+    let mut handle = {
+        let container = container.lock().await;
+        container
+            .cmd()
+            .program(&task.program)
+            .args(&task.args)
+            .envs(task.env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .spawn()
+            .await
+            .map_err(|e| e.to_string())?
+    };
+
+    let output = Arc::new(std::sync::Mutex::new(OutputTail::default()));
+    let drains = [
+        handle.stdout().map(|s| drain(s, output.clone())),
+        handle.stderr().map(|s| drain(s, output.clone())),
+    ];
+
+    let pid = handle.pid();
+    let mut wait = tokio::task::spawn_blocking(move || waitpid(pid, None));
+    let mut error = None;
+    if task.timeout_ms > 0 {
+        let timeout = Duration::from_millis(task.timeout_ms);
+        if tokio::time::timeout(timeout, &mut wait).await.is_err() {
+            error = Some(format!("Timed out after {}ms", task.timeout_ms));
+            let _ = handle.kill(Signal::SIGKILL);
+        }
+    }
+    let status = wait
+        .await
+        .map_err(|e| format!("spawn_blocking failed: {}", e))?
+        .map_err(|e| format!("waitpid failed: {}", e))?;
+    for drain in drains.into_iter().flatten() {
+        let _ = drain.await;
+    }
+
+    // Exit code, or 128 + signal number like a shell reports it
+    let exit_code = match status {
+        WaitStatus::Exited(_, code) => code,
+        WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+        other => return Err(format!("Unexpected wait status: {:?}", other)),
+    };
+    let output = output.lock().unwrap().to_string();
+    Ok(RunOutcome {
+        exit_code,
+        output,
+        error,
+    })
+}
+
+fn drain<S>(mut stream: S, output: Arc<std::sync::Mutex<OutputTail>>) -> JoinHandle<()>
+where
+    S: Stream<Item = Vec<u8>> + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        while let Some(chunk) = stream.next().await {
+            output.lock().unwrap().push(&chunk);
+        }
+    })
+}
+
+/// Last [`OUTPUT_TAIL`] bytes written to a run's stdout and stderr.
+#[derive(Default)]
+struct OutputTail {
+    bytes: Vec<u8>,
+}
+
+impl OutputTail {
+    fn push(&mut self, chunk: &[u8]) {
+        self.bytes.extend_from_slice(chunk);
+        if self.bytes.len() > OUTPUT_TAIL {
+            let excess = self.bytes.len() - OUTPUT_TAIL;
+            self.bytes.drain(..excess);
+        }
+    }
+}
+
+impl std::fmt::Display for OutputTail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_tail_keeps_last_bytes() {
+        let mut tail = OutputTail::default();
+        tail.push(b"head");
+        tail.push(&vec![b'x'; OUTPUT_TAIL]);
+        tail.push(b"end");
+
+        let text = tail.to_string();
+        assert_eq!(text.len(), OUTPUT_TAIL);
+        assert!(text.ends_with("xend"));
+        assert!(!text.contains("head"));
+    }
+}
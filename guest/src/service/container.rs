@@ -4,17 +4,32 @@
 //! Handles OCI container lifecycle (Init RPC).
 
 use std::path::Path;
+use std::pin::Pin;
 
 use crate::service::server::GuestServer;
 use boxlite_shared::{
-    container_init_response, rootfs_init, Container as ContainerService, ContainerInitError,
-    ContainerInitRequest, ContainerInitResponse, ContainerInitSuccess, Filesystem, RootfsInit,
+    clipboard_response, container_init_response, rootfs_init, task_response, user_response,
+    ClipboardError, ClipboardResponse, Container as ContainerService, ContainerInitError,
+    ContainerInitRequest, ContainerInitResponse, ContainerInitSuccess, CreateUserRequest,
+    DeleteUserRequest, DigestPathsRequest, DigestPathsResponse, ExecuteCellRequest,
+    ExecuteCellResponse, Filesystem, FreezeRootfsRequest, FreezeRootfsResponse,
+    GetClipboardRequest, HealthEvent, HomeStorage, InjectFaultRequest, InjectFaultResponse,
+    InstallPackagesEvent, InstallPackagesRequest, ListProcessesRequest, ListProcessesResponse,
+    ListTasksRequest, ListTasksResponse, NotifyChangesRequest, NotifyChangesResponse, PathDigest,
+    RecordReadaheadRequest, RecordReadaheadResponse, RegisterTaskRequest, RootfsInit,
+    SetClipboardRequest, ShutdownKernelRequest, ShutdownKernelResponse, TaskError, TaskResponse,
+    TaskSuccess, UnregisterTaskRequest, UserError, UserResponse, UserSuccess, WatchHealthRequest,
+    WatchPathEvent, WatchPathRequest,
 };
 use nix::mount::{mount, MsFlags};
 use tonic::{Request, Response, Status};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::container::{Container, UserMount};
+use crate::container::{
+    audit, cgroups, changes, credentials, digest, etc_overlay, freeze, fuse, locks, masks, nested,
+    packages, populate, private_fs, processes, readahead, setup, sharing, ssh, systemd, users,
+    watch, x11, Container, SpecFeatures, UserMount,
+};
 use crate::layout::GuestLayout;
 use crate::storage::block_device::BlockDeviceMount;
 
@@ -79,6 +94,9 @@ fn prepare_rootfs(
             )
             .map_err(|e| format!("Failed to mount rootfs disk: {}", e))?;
 
+            if !disk.readahead.is_empty() {
+                readahead::apply(shared_rootfs.to_path_buf(), disk.readahead.clone());
+            }
             Ok(())
         }
         None => Err("Missing rootfs strategy in Container.Init request".to_string()),
@@ -120,7 +138,7 @@ impl ContainerService for GuestServer {
         }
 
         // Extract container config
-        let config = init_req
+        let mut config = init_req
             .container_config
             .ok_or_else(|| Status::invalid_argument("Missing container_config in Init request"))?;
 
@@ -187,12 +205,61 @@ impl ContainerService for GuestServer {
             }));
         }
 
+        // A clone must not share its source's machine ID; written to the
+        // disk, below any /etc overlay, so it sticks
+        if init_req.new_machine_id {
+            match crate::resume::new_machine_id(&bundle_rootfs) {
+                Ok(true) => info!("Gave the cloned rootfs a new machine ID"),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to replace machine ID: {}", e),
+            }
+        }
+
+        // Per-box writable /etc; the runtime's /etc/hosts etc. mount over it
+        if init_req.etc_overlay {
+            let overlay_dir = self.layout.container(&container_id).etc_overlay_dir();
+            if let Err(e) = etc_overlay::mount(&bundle_rootfs.join("etc"), &overlay_dir) {
+                error!("Failed to overlay /etc: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to overlay /etc: {}", e),
+                    })),
+                }));
+            }
+        }
+
+        // Point package managers at the shared cache; without it they just
+        // download as usual, so a failure here is not fatal
+        let mut env = config.env;
+        if !init_req.package_cache.is_empty() {
+            if let Err(e) = crate::container::package_cache::configure(
+                &bundle_rootfs,
+                &init_req.package_cache,
+                &mut env,
+            ) {
+                warn!("Failed to configure package cache: {}", e);
+            }
+        }
+
         // Convert proto BindMount to UserMount for OCI spec
         // Construct full source path from convention: /run/boxlite/shared/containers/{id}/volumes/{name}
         let guest_layout = boxlite_shared::layout::SharedGuestLayout::new("/run/boxlite/shared");
         let container_layout = guest_layout.container(&container_id);
 
-        let user_mounts: Vec<UserMount> = init_req
+        // A new home volume starts out as the image's home directory
+        for m in init_req.mounts.iter().filter(|m| m.populate) {
+            let source = container_layout.volume_dir(&m.volume_name);
+            if let Err(e) = populate::populate(&source, &bundle_rootfs, &m.destination) {
+                error!("Failed to populate {}: {}", m.destination, e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to populate {}: {}", m.destination, e),
+                    })),
+                }));
+            }
+        }
+
+        let mut user_mounts: Vec<UserMount> = init_req
             .mounts
             .iter()
             .map(|m| {
@@ -205,10 +272,243 @@ impl ContainerService for GuestServer {
             })
             .collect();
 
+        // Hidden paths are covered once their volumes are mounted
+        let masks_dir = self.layout.container(&container_id).masks_dir();
+        for m in &init_req.mounts {
+            let source = container_layout.volume_dir(&m.volume_name);
+            match masks::mask_mounts(&masks_dir, &source, &m.destination, &m.hidden) {
+                Ok(masks) => user_mounts.extend(masks),
+                Err(e) => {
+                    error!("Failed to prepare hidden paths of {}: {}", m.destination, e);
+                    return Ok(Response::new(ContainerInitResponse {
+                        result: Some(container_init_response::Result::Error(ContainerInitError {
+                            reason: format!(
+                                "Failed to prepare hidden paths of {}: {}",
+                                m.destination, e
+                            ),
+                        })),
+                    }));
+                }
+            }
+        }
+
+        // SSH keys live on the guest tmpfs and are mounted into the container
+        if let Some(ssh_config) = &init_req.ssh {
+            let ssh_dir = self.layout.container(&container_id).ssh_dir();
+            if let Err(e) = ssh::prepare(&ssh_dir, &ssh_config.authorized_keys) {
+                error!("Failed to prepare SSH directory: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to prepare SSH directory: {}", e),
+                    })),
+                }));
+            }
+            user_mounts.push(UserMount {
+                source: ssh_dir.to_string_lossy().to_string(),
+                destination: ssh::CONTAINER_SSH_DIR.to_string(),
+                read_only: false,
+            });
+        }
+
+        // Relay sockets for host credentials, also on the guest tmpfs
+        if let Some(credentials) = &init_req.credentials {
+            if credentials.ssh_agent || credentials.git_credentials {
+                let agent_dir = self.layout.container(&container_id).agent_dir();
+                if let Err(e) = credentials::prepare(
+                    &agent_dir,
+                    credentials.ssh_agent,
+                    credentials.git_credentials,
+                    &mut env,
+                ) {
+                    error!("Failed to set up credential forwarding: {}", e);
+                    return Ok(Response::new(ContainerInitResponse {
+                        result: Some(container_init_response::Result::Error(ContainerInitError {
+                            reason: format!("Failed to set up credential forwarding: {}", e),
+                        })),
+                    }));
+                }
+                user_mounts.push(UserMount {
+                    source: agent_dir.to_string_lossy().to_string(),
+                    destination: credentials::CONTAINER_AGENT_DIR.to_string(),
+                    read_only: true,
+                });
+            }
+        }
+
+        if init_req.x11 {
+            let x11_dir = self.layout.container(&container_id).x11_dir();
+            if let Err(e) = x11::prepare(&x11_dir, &mut env) {
+                error!("Failed to set up X11 forwarding: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to set up X11 forwarding: {}", e),
+                    })),
+                }));
+            }
+            user_mounts.push(UserMount {
+                source: x11_dir.to_string_lossy().to_string(),
+                destination: x11::CONTAINER_X11_DIR.to_string(),
+                read_only: false,
+            });
+        }
+
+        if let Some(sharing_config) = &init_req.sharing {
+            let container_layout = self.layout.container(&container_id);
+            let mut shares = Vec::new();
+            if sharing_config.clipboard {
+                shares.push((
+                    container_layout.clipboard_dir(),
+                    sharing::CONTAINER_CLIPBOARD_DIR.to_string(),
+                ));
+            }
+            if !sharing_config.drop_dir.is_empty() {
+                shares.push((
+                    container_layout.drops_dir(),
+                    sharing_config.drop_dir.clone(),
+                ));
+            }
+            for (dir, destination) in shares {
+                if let Err(e) = sharing::prepare_dir(&dir) {
+                    error!("Failed to prepare shared directory: {}", e);
+                    return Ok(Response::new(ContainerInitResponse {
+                        result: Some(container_init_response::Result::Error(ContainerInitError {
+                            reason: format!("Failed to prepare shared directory: {}", e),
+                        })),
+                    }));
+                }
+                user_mounts.push(UserMount {
+                    source: dir.to_string_lossy().to_string(),
+                    destination,
+                    read_only: false,
+                });
+            }
+        }
+
+        if init_req.lock_proxy {
+            let lock_dir = self.layout.container(&container_id).lock_dir();
+            if let Err(e) = locks::prepare(&lock_dir) {
+                error!("Failed to set up the lock proxy: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to set up the lock proxy: {}", e),
+                    })),
+                }));
+            }
+            user_mounts.push(UserMount {
+                source: lock_dir.to_string_lossy().to_string(),
+                destination: locks::CONTAINER_LOCK_DIR.to_string(),
+                read_only: true,
+            });
+        }
+
+        // Any exec may ask for its own network namespace or filesystem
+        // view, or to be audited
+        match std::env::current_exe() {
+            Ok(exe) => {
+                for helper in [
+                    #[cfg(feature = "net")]
+                    crate::container::netns::CONTAINER_NETNS_HELPER,
+                    private_fs::CONTAINER_PRIVATE_FS_HELPER,
+                    audit::CONTAINER_AUDIT_HELPER,
+                ] {
+                    user_mounts.push(UserMount {
+                        source: exe.to_string_lossy().to_string(),
+                        destination: helper.to_string(),
+                        read_only: true,
+                    });
+                }
+            }
+            Err(e) => warn!(
+                "Network-isolated, private filesystem and audited execs unavailable: {}",
+                e
+            ),
+        }
+
+        // FUSE config is a convenience for non-root mounts; not fatal
+        if init_req.fuse {
+            if let Err(e) = fuse::configure(&bundle_rootfs) {
+                warn!("Failed to configure fuse.conf: {}", e);
+            }
+        } else if init_req
+            .device_policy
+            .as_ref()
+            .is_some_and(|policy| policy.devtmpfs)
+        {
+            user_mounts.push(UserMount {
+                source: "/dev/null".to_string(),
+                destination: fuse::FUSE_DEVICE.to_string(),
+                read_only: true,
+            });
+        }
+
+        if init_req.nested_containers {
+            if let Err(e) = nested::prepare() {
+                error!("Failed to prepare for container engines: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to prepare for container engines: {}", e),
+                    })),
+                }));
+            }
+        }
+
+        if init_req.systemd {
+            let prepared = cgroups::mount_root().and_then(|()| systemd::entrypoint(&bundle_rootfs));
+            match prepared {
+                Ok(entrypoint) => {
+                    config.entrypoint = entrypoint;
+                    env.push(systemd::CONTAINER_ENV.to_string());
+                }
+                Err(e) => {
+                    error!("Failed to prepare systemd boot: {}", e);
+                    return Ok(Response::new(ContainerInitResponse {
+                        result: Some(container_init_response::Result::Error(ContainerInitError {
+                            reason: format!("Failed to prepare systemd boot: {}", e),
+                        })),
+                    }));
+                }
+            }
+        }
+
+        // With setup steps, the entrypoint waits at the gate until they pass
+        let setup_dir = self.layout.container(&container_id).setup_dir();
+        if !init_req.setup_steps.is_empty() {
+            if let Err(e) = setup::prepare(&setup_dir) {
+                error!("Failed to set up the setup gate: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to set up the setup gate: {}", e),
+                    })),
+                }));
+            }
+            user_mounts.push(UserMount {
+                source: setup_dir.to_string_lossy().to_string(),
+                destination: setup::CONTAINER_SETUP_DIR.to_string(),
+                read_only: true,
+            });
+            config.entrypoint = setup::wrap(config.entrypoint);
+        }
+
+        // Make sure requested device nodes exist before the runtime looks for them
+        let devices = match crate::devices::DeviceSetup::prepare(
+            &init_req.devices,
+            init_req.device_policy.as_ref(),
+        ) {
+            Ok(devices) => devices,
+            Err(e) => {
+                error!("Failed to prepare devices: {}", e);
+                return Ok(Response::new(ContainerInitResponse {
+                    result: Some(container_init_response::Result::Error(ContainerInitError {
+                        reason: format!("Failed to prepare devices: {}", e),
+                    })),
+                }));
+            }
+        };
+
         debug!(
             entrypoint = ?config.entrypoint,
             workdir = %config.workdir,
-            env_count = config.env.len(),
+            env_count = env.len(),
             shared_rootfs = %shared_rootfs.display(),
             bundle_rootfs = %bundle_rootfs.display(),
             container_id = %container_id,
@@ -228,9 +528,17 @@ impl ContainerService for GuestServer {
             &container_id,
             &bundle_rootfs,
             config.entrypoint,
-            config.env,
+            env,
             &config.workdir,
             user_mounts,
+            devices,
+            SpecFeatures {
+                nested_containers: init_req.nested_containers,
+                systemd: init_req.systemd,
+                masked_paths: init_req.masked_paths.clone(),
+                readonly_paths: init_req.readonly_paths.clone(),
+                dns_search: init_req.dns_search.clone(),
+            },
         ) {
             Ok(container) => {
                 debug!(container_id = %container_id, "Container started, checking if init process is running");
@@ -259,11 +567,39 @@ impl ContainerService for GuestServer {
                     "✅ Container started successfully and ready for exec"
                 );
 
+                let container = std::sync::Arc::new(tokio::sync::Mutex::new(container));
+                if !init_req.setup_steps.is_empty() {
+                    let result = setup::run(&container, &init_req.setup_steps).await;
+                    let released = setup::release(&setup_dir, result.is_ok()).await;
+                    let result = result.and_then(|()| {
+                        released.map_err(|e| format!("Failed to start the entrypoint: {}", e))
+                    });
+                    if let Err(reason) = result {
+                        error!("{}", reason);
+                        return Ok(Response::new(ContainerInitResponse {
+                            result: Some(container_init_response::Result::Error(
+                                ContainerInitError { reason },
+                            )),
+                        }));
+                    }
+                    info!(
+                        container_id = %container_id,
+                        steps = init_req.setup_steps.len(),
+                        "Setup steps completed, entrypoint started"
+                    );
+                }
+
+                if let Some(ssh_config) = &init_req.ssh {
+                    if let Err(e) = ssh::start(&*container.lock().await, ssh_config.port).await {
+                        warn!("Failed to start SSH server: {}", e);
+                    }
+                }
+
                 // Store container in registry
-                self.containers.lock().await.insert(
-                    container_id.clone(),
-                    std::sync::Arc::new(tokio::sync::Mutex::new(container)),
-                );
+                self.containers
+                    .lock()
+                    .await
+                    .insert(container_id.clone(), container);
 
                 Ok(Response::new(ContainerInitResponse {
                     result: Some(container_init_response::Result::Success(
@@ -281,4 +617,531 @@ impl ContainerService for GuestServer {
             }
         }
     }
+
+    async fn create_user(
+        &self,
+        request: Request<CreateUserRequest>,
+    ) -> Result<Response<UserResponse>, Status> {
+        let req = request.into_inner();
+        info!(container_id = %req.container_id, user = %req.name, uid = req.uid, "Creating user");
+
+        let tmpfs_home = req.home_storage() == HomeStorage::Tmpfs;
+        let spec = users::UserSpec {
+            name: req.name,
+            uid: req.uid,
+            gid: req.gid,
+            shell: req.shell,
+            home: req.home,
+            tmpfs_home,
+        };
+        let result = match self.container_init_pid(&req.container_id).await {
+            Ok(pid) => run_user_op(move || users::create(pid, spec)).await,
+            Err(reason) => Err(reason),
+        };
+        Ok(Response::new(user_response(result)))
+    }
+
+    async fn delete_user(
+        &self,
+        request: Request<DeleteUserRequest>,
+    ) -> Result<Response<UserResponse>, Status> {
+        let req = request.into_inner();
+        info!(container_id = %req.container_id, user = %req.name, "Deleting user");
+
+        let result = match self.container_init_pid(&req.container_id).await {
+            Ok(pid) => run_user_op(move || users::delete(pid, req.name, req.remove_home)).await,
+            Err(reason) => Err(reason),
+        };
+        Ok(Response::new(user_response(result)))
+    }
+
+    async fn register_task(
+        &self,
+        request: Request<RegisterTaskRequest>,
+    ) -> Result<Response<TaskResponse>, Status> {
+        let req = request.into_inner();
+        let result = match req.task {
+            Some(task) => match self.containers.lock().await.get(&req.container_id).cloned() {
+                Some(container) => {
+                    self.scheduler
+                        .register(&req.container_id, container, task)
+                        .await
+                }
+                None => Err(format!("Container not found: {}", req.container_id)),
+            },
+            None => Err("Task is required".to_string()),
+        };
+        Ok(Response::new(task_response(result)))
+    }
+
+    async fn unregister_task(
+        &self,
+        request: Request<UnregisterTaskRequest>,
+    ) -> Result<Response<TaskResponse>, Status> {
+        let req = request.into_inner();
+        let result = self.scheduler.unregister(&req.name).await;
+        Ok(Response::new(task_response(result)))
+    }
+
+    async fn set_clipboard(
+        &self,
+        request: Request<SetClipboardRequest>,
+    ) -> Result<Response<ClipboardResponse>, Status> {
+        let req = request.into_inner();
+        let dir = self.layout.container(&req.container_id).clipboard_dir();
+        let result = if dir.is_dir() {
+            sharing::write_clipboard(&dir, &req.content)
+                .map(|_| Vec::new())
+                .map_err(|e| format!("Failed to write clipboard: {}", e))
+        } else {
+            Err("Clipboard sharing is not enabled for this box".to_string())
+        };
+        Ok(Response::new(clipboard_response(result)))
+    }
+
+    async fn get_clipboard(
+        &self,
+        request: Request<GetClipboardRequest>,
+    ) -> Result<Response<ClipboardResponse>, Status> {
+        let req = request.into_inner();
+        let dir = self.layout.container(&req.container_id).clipboard_dir();
+        let result = if dir.is_dir() {
+            sharing::read_clipboard(&dir).map_err(|e| format!("Failed to read clipboard: {}", e))
+        } else {
+            Err("Clipboard sharing is not enabled for this box".to_string())
+        };
+        Ok(Response::new(clipboard_response(result)))
+    }
+
+    async fn notify_changes(
+        &self,
+        request: Request<NotifyChangesRequest>,
+    ) -> Result<Response<NotifyChangesResponse>, Status> {
+        let req = request.into_inner();
+        let replayed = tokio::task::spawn_blocking(move || {
+            let layout = boxlite_shared::layout::SharedGuestLayout::new("/run/boxlite/shared");
+            let container_layout = layout.container(&req.container_id);
+            let mut replayed = 0;
+            for change in &req.changes {
+                let root = container_layout.volume_dir(&change.volume_name);
+                match changes::replay(&root, &change.path, change.mtime_secs, change.mtime_nanos) {
+                    Ok(true) => replayed += 1,
+                    Ok(false) => {}
+                    Err(e) => debug!(
+                        volume = %change.volume_name,
+                        path = %change.path,
+                        "Failed to replay change: {}",
+                        e
+                    ),
+                }
+            }
+            replayed
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Change replay failed: {}", e)))?;
+
+        Ok(Response::new(NotifyChangesResponse { replayed }))
+    }
+
+    async fn list_tasks(
+        &self,
+        _request: Request<ListTasksRequest>,
+    ) -> Result<Response<ListTasksResponse>, Status> {
+        Ok(Response::new(ListTasksResponse {
+            tasks: self.scheduler.list().await,
+        }))
+    }
+
+    async fn freeze_rootfs(
+        &self,
+        request: Request<FreezeRootfsRequest>,
+    ) -> Result<Response<FreezeRootfsResponse>, Status> {
+        let req = request.into_inner();
+        if !self.containers.lock().await.contains_key(&req.container_id) {
+            return Err(Status::not_found(format!(
+                "Container not found: {}",
+                req.container_id
+            )));
+        }
+        let rootfs = self
+            .layout
+            .shared()
+            .container(&req.container_id)
+            .rootfs_dir();
+        let result = if req.frozen {
+            freeze::freeze(&rootfs)
+        } else {
+            freeze::thaw(&rootfs)
+        };
+        result.map_err(|e| {
+            Status::unavailable(format!(
+                "Failed to {} {}: {}",
+                if req.frozen { "freeze" } else { "thaw" },
+                rootfs.display(),
+                e
+            ))
+        })?;
+
+        info!(container_id = %req.container_id, frozen = req.frozen, "Changed rootfs freeze");
+        Ok(Response::new(FreezeRootfsResponse {}))
+    }
+
+    type InstallPackagesStream =
+        Pin<Box<dyn futures::Stream<Item = Result<InstallPackagesEvent, Status>> + Send + 'static>>;
+
+    async fn install_packages(
+        &self,
+        request: Request<InstallPackagesRequest>,
+    ) -> Result<Response<Self::InstallPackagesStream>, Status> {
+        let req = request.into_inner();
+        if req.packages.is_empty() {
+            return Err(Status::invalid_argument("No packages to install"));
+        }
+        for package in &req.packages {
+            packages::validate_package(package).map_err(Status::invalid_argument)?;
+        }
+        let container = self
+            .containers
+            .lock()
+            .await
+            .get(&req.container_id)
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!("Container not found: {}", req.container_id))
+            })?;
+
+        info!(container_id = %req.container_id, packages = ?req.packages, "Installing packages");
+        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        tokio::spawn(packages::install(
+            container,
+            req.packages,
+            req.update_index,
+            tx,
+        ));
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    async fn execute_cell(
+        &self,
+        request: Request<ExecuteCellRequest>,
+    ) -> Result<Response<ExecuteCellResponse>, Status> {
+        let req = request.into_inner();
+        let container = self
+            .containers
+            .lock()
+            .await
+            .get(&req.container_id)
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!("Container not found: {}", req.container_id))
+            })?;
+
+        debug!(container_id = %req.container_id, kernel_id = %req.kernel_id, "Executing cell");
+        let timeout =
+            (req.timeout_ms > 0).then(|| std::time::Duration::from_millis(req.timeout_ms));
+        let response = self
+            .kernels
+            .execute(
+                &req.container_id,
+                &container,
+                &req.kernel_id,
+                &req.language,
+                &req.code,
+                timeout,
+            )
+            .await
+            .map_err(Status::failed_precondition)?;
+        Ok(Response::new(response))
+    }
+
+    async fn shutdown_kernel(
+        &self,
+        request: Request<ShutdownKernelRequest>,
+    ) -> Result<Response<ShutdownKernelResponse>, Status> {
+        let req = request.into_inner();
+        if self
+            .kernels
+            .shutdown(&req.container_id, &req.kernel_id)
+            .await
+        {
+            info!(container_id = %req.container_id, kernel_id = %req.kernel_id, "Kernel stopped");
+        }
+        Ok(Response::new(ShutdownKernelResponse {}))
+    }
+
+    async fn digest_paths(
+        &self,
+        request: Request<DigestPathsRequest>,
+    ) -> Result<Response<DigestPathsResponse>, Status> {
+        let req = request.into_inner();
+        if !self.containers.lock().await.contains_key(&req.container_id) {
+            return Err(Status::not_found(format!(
+                "Container not found: {}",
+                req.container_id
+            )));
+        }
+        let mut resolved = Vec::with_capacity(req.paths.len());
+        for path in &req.paths {
+            resolved.push(
+                crate::bulk::resolve(&self.layout, &req.container_id, path)
+                    .map_err(|e| Status::invalid_argument(format!("{}: {}", path, e)))?,
+            );
+        }
+        let digests = tokio::task::spawn_blocking(move || {
+            req.paths
+                .into_iter()
+                .zip(resolved)
+                .map(|(path, resolved)| match digest::digest(&resolved.path()) {
+                    Ok(digest) => Ok(PathDigest {
+                        path,
+                        digest: digest.unwrap_or_default(),
+                    }),
+                    Err(e) => Err(format!("Failed to hash {}: {}", path, e)),
+                })
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Digest task failed: {}", e)))?
+        .map_err(Status::internal)?;
+
+        Ok(Response::new(DigestPathsResponse { digests }))
+    }
+
+    async fn list_processes(
+        &self,
+        request: Request<ListProcessesRequest>,
+    ) -> Result<Response<ListProcessesResponse>, Status> {
+        let req = request.into_inner();
+        let pid = self
+            .container_init_pid(&req.container_id)
+            .await
+            .map_err(Status::failed_precondition)?;
+        let processes = tokio::task::spawn_blocking(move || processes::list(pid))
+            .await
+            .map_err(|e| Status::internal(format!("Process listing failed: {}", e)))?
+            .map_err(|e| Status::internal(format!("Failed to list processes: {}", e)))?;
+
+        Ok(Response::new(ListProcessesResponse { processes }))
+    }
+
+    type WatchPathStream =
+        Pin<Box<dyn futures::Stream<Item = Result<WatchPathEvent, Status>> + Send + 'static>>;
+
+    async fn watch_path(
+        &self,
+        request: Request<WatchPathRequest>,
+    ) -> Result<Response<Self::WatchPathStream>, Status> {
+        let req = request.into_inner();
+        if !self.containers.lock().await.contains_key(&req.container_id) {
+            return Err(Status::not_found(format!(
+                "Container not found: {}",
+                req.container_id
+            )));
+        }
+        let root = crate::bulk::resolve(&self.layout, &req.container_id, &req.path)
+            .map_err(|e| Status::invalid_argument(format!("{}: {}", req.path, e)))?;
+        let debounce = match req.debounce_ms {
+            0 => watch::DEFAULT_DEBOUNCE,
+            ms => std::time::Duration::from_millis(ms),
+        };
+        let watcher = watch::Watch::new(&root.path(), req.recursive).map_err(|e| {
+            Status::failed_precondition(format!("Failed to watch {}: {}", req.path, e))
+        })?;
+
+        debug!(container_id = %req.container_id, path = %req.path, recursive = req.recursive, "Watching path");
+        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        // The watch reaches the path through the resolved parent directory
+        tokio::task::spawn_blocking(move || {
+            let _root = root;
+            watcher.run(debounce, tx)
+        });
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    type WatchHealthStream =
+        Pin<Box<dyn futures::Stream<Item = Result<HealthEvent, Status>> + Send + 'static>>;
+
+    async fn watch_health(
+        &self,
+        request: Request<WatchHealthRequest>,
+    ) -> Result<Response<Self::WatchHealthStream>, Status> {
+        let req = request.into_inner();
+        let container = self
+            .containers
+            .lock()
+            .await
+            .get(&req.container_id)
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!("Container not found: {}", req.container_id))
+            })?;
+
+        debug!(container_id = %req.container_id, probes = req.probes.len(), "Watching health");
+        let rx = crate::probes::watch(container, req.probes).map_err(Status::invalid_argument)?;
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    async fn record_readahead(
+        &self,
+        request: Request<RecordReadaheadRequest>,
+    ) -> Result<Response<RecordReadaheadResponse>, Status> {
+        let req = request.into_inner();
+        if !self.containers.lock().await.contains_key(&req.container_id) {
+            return Err(Status::not_found(format!(
+                "Container not found: {}",
+                req.container_id
+            )));
+        }
+        let rootfs = self
+            .layout
+            .shared()
+            .container(&req.container_id)
+            .rootfs_dir();
+        let hints = tokio::task::spawn_blocking(move || readahead::record(&rootfs))
+            .await
+            .map_err(|e| Status::internal(format!("Readahead task failed: {}", e)))?
+            .map_err(|e| Status::internal(format!("Failed to record readahead: {}", e)))?;
+
+        debug!(container_id = %req.container_id, hints = hints.len(), "Recorded readahead hints");
+        Ok(Response::new(RecordReadaheadResponse { hints }))
+    }
+
+    #[cfg(not(feature = "fault-injection"))]
+    async fn inject_fault(
+        &self,
+        _request: Request<InjectFaultRequest>,
+    ) -> Result<Response<InjectFaultResponse>, Status> {
+        Err(Status::unimplemented(
+            "guest agent was built without the fault-injection feature",
+        ))
+    }
+
+    #[cfg(feature = "fault-injection")]
+    async fn inject_fault(
+        &self,
+        request: Request<InjectFaultRequest>,
+    ) -> Result<Response<InjectFaultResponse>, Status> {
+        use crate::container::faults;
+        use boxlite_shared::inject_fault_request::Fault;
+
+        let req = request.into_inner();
+        let fault = req
+            .fault
+            .ok_or_else(|| Status::invalid_argument("no fault given"))?;
+        warn!(container_id = %req.container_id, ?fault, "Injecting fault");
+        match fault {
+            Fault::MountError(mount) => {
+                let pid = self
+                    .container_init_pid(&req.container_id)
+                    .await
+                    .map_err(Status::failed_precondition)?;
+                let path = mount.path;
+                let target = path.clone();
+                tokio::task::spawn_blocking(move || faults::detach_mount(pid, &target))
+                    .await
+                    .map_err(|e| Status::internal(format!("Detach failed: {}", e)))?
+                    .map_err(|e| {
+                        Status::unavailable(format!("Failed to detach {}: {}", path, e))
+                    })?;
+            }
+            Fault::IoStall(stall) => {
+                let duration = std::time::Duration::from_millis(stall.duration_ms);
+                if duration > freeze::AUTO_THAW {
+                    return Err(Status::invalid_argument(format!(
+                        "IO stalls are capped at {}s",
+                        freeze::AUTO_THAW.as_secs()
+                    )));
+                }
+                self.container_init_pid(&req.container_id)
+                    .await
+                    .map_err(Status::failed_precondition)?;
+                let rootfs = self
+                    .layout
+                    .shared()
+                    .container(&req.container_id)
+                    .rootfs_dir();
+                freeze::freeze(&rootfs)
+                    .map_err(|e| Status::unavailable(format!("Failed to stall IO: {}", e)))?;
+                tokio::spawn(async move {
+                    tokio::time::sleep(duration).await;
+                    if let Err(e) = freeze::thaw(&rootfs) {
+                        error!("Failed to end IO stall: {}", e);
+                    }
+                });
+            }
+            Fault::ClockJump(jump) => faults::jump_clock(jump.seconds)
+                .map_err(|e| Status::unavailable(format!("Failed to step clock: {}", e)))?,
+        }
+        Ok(Response::new(InjectFaultResponse {}))
+    }
+}
+
+impl GuestServer {
+    /// PID of a running container's init process.
+    async fn container_init_pid(&self, container_id: &str) -> Result<i32, String> {
+        let container = self
+            .containers
+            .lock()
+            .await
+            .get(container_id)
+            .cloned()
+            .ok_or_else(|| format!("Container not found: {}", container_id))?;
+        let pid = container.lock().await.init_pid();
+        pid.ok_or_else(|| format!("Container {} is not running", container_id))
+    }
+}
+
+async fn run_user_op<F>(op: F) -> Result<(), String>
+where
+    F: FnOnce() -> boxlite_shared::errors::BoxliteResult<()> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(op).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(e) => Err(format!("User operation failed: {}", e)),
+    }
+}
+
+fn user_response(result: Result<(), String>) -> UserResponse {
+    let result = match result {
+        Ok(()) => user_response::Result::Success(UserSuccess {}),
+        Err(reason) => {
+            error!("User operation failed: {}", reason);
+            user_response::Result::Error(UserError { reason })
+        }
+    };
+    UserResponse {
+        result: Some(result),
+    }
+}
+
+fn task_response(result: Result<(), String>) -> TaskResponse {
+    let result = match result {
+        Ok(()) => task_response::Result::Success(TaskSuccess {}),
+        Err(reason) => {
+            warn!("Task operation failed: {}", reason);
+            task_response::Result::Error(TaskError { reason })
+        }
+    };
+    TaskResponse {
+        result: Some(result),
+    }
+}
+
+fn clipboard_response(result: Result<Vec<u8>, String>) -> ClipboardResponse {
+    let result = match result {
+        Ok(content) => clipboard_response::Result::Content(content),
+        Err(reason) => {
+            warn!("Clipboard operation failed: {}", reason);
+            clipboard_response::Result::Error(ClipboardError { reason })
+        }
+    };
+    ClipboardResponse {
+        result: Some(result),
+    }
 }
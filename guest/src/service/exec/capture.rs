@@ -0,0 +1,309 @@
+//! Output capture to files.
+//!
+//! A captured stream is drained into a file instead of being forwarded
+//! over Attach, so long-running output stays in the box until the host
+//! asks for a range of it. Files rotate logrotate-style: once `path`
+//! reaches `max_file_bytes` it becomes `path.1` (shifting older ones up)
+//! and anything past `max_files` is deleted.
+//!
+//! Each file holds exactly `max_file_bytes`, so a stream offset maps to a
+//! file and a position in it without an index. Paths are resolved in the
+//! process's filesystem through `/proc/<pid>/root`, and the parent
+//! directory is held open so rotation keeps working after it exits.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Largest range returned by one fetch, well under the gRPC message limit.
+pub const MAX_FETCH_BYTES: u64 = 1024 * 1024;
+
+/// How much of a captured stream to keep.
+#[derive(Debug, Clone, Copy)]
+pub struct RotationPolicy {
+    /// Rotate at this size; 0 = never.
+    pub max_file_bytes: u64,
+    /// Files kept, including the current one; 0 = all.
+    pub max_files: u32,
+}
+
+/// A range of captured output.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OutputRange {
+    pub data: Vec<u8>,
+    pub offset: u64,
+    pub total_bytes: u64,
+    pub complete: bool,
+}
+
+/// One stream being written to (rotating) files.
+pub struct CapturedStream {
+    /// Parent directory of the output file, held open.
+    dir: File,
+    name: String,
+    policy: RotationPolicy,
+    file: File,
+    /// Index of the file being written, counting from the first.
+    segment: u64,
+    written: u64,
+    complete: bool,
+}
+
+impl CapturedStream {
+    /// Create (truncating) `path` as seen by process `pid`.
+    pub fn create(pid: i32, path: &str, policy: RotationPolicy) -> io::Result<Self> {
+        let path = Path::new(path);
+        if !path.is_absolute() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("capture path must be absolute: {}", path.display()),
+            ));
+        }
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("capture path has no file name: {}", path.display()),
+                )
+            })?
+            .to_string();
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        let dir_path = PathBuf::from(format!("/proc/{}/root", pid))
+            .join(parent.strip_prefix("/").unwrap_or(parent));
+        std::fs::create_dir_all(&dir_path)?;
+        let dir = File::open(&dir_path)?;
+        Self::in_dir(dir, name, policy)
+    }
+
+    fn in_dir(dir: File, name: String, policy: RotationPolicy) -> io::Result<Self> {
+        let file = create(&entry(&dir, &name))?;
+        Ok(Self {
+            dir,
+            name,
+            policy,
+            file,
+            segment: 0,
+            written: 0,
+            complete: false,
+        })
+    }
+
+    /// Append output, rotating as files fill up.
+    pub fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let max = self.policy.max_file_bytes;
+            let len = if max == 0 {
+                data.len()
+            } else {
+                let used = self.written - self.segment * max;
+                if used == max {
+                    self.rotate()?;
+                    continue;
+                }
+                data.len().min((max - used) as usize)
+            };
+            self.file.write_all(&data[..len])?;
+            self.written += len as u64;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    /// Mark the stream closed; no more output will come.
+    pub fn finish(&mut self) {
+        self.complete = true;
+    }
+
+    /// Read up to `length` bytes from stream offset `offset`.
+    ///
+    /// Reads never cross a file boundary, so a short result does not mean
+    /// the end; ask again from `offset + data.len()`.
+    pub fn read(&self, offset: u64, length: u64) -> io::Result<OutputRange> {
+        let max = self.policy.max_file_bytes;
+        let first = match (max, self.policy.max_files) {
+            (0, _) | (_, 0) => 0,
+            (_, kept) => self.segment.saturating_sub(kept as u64 - 1) * max,
+        };
+        let offset = offset.clamp(first, self.written);
+        let length = match length {
+            0 => MAX_FETCH_BYTES,
+            n => n.min(MAX_FETCH_BYTES),
+        };
+
+        let (segment, position, available) = match offset.checked_div(max) {
+            None => (0, offset, self.written - offset),
+            Some(segment) => {
+                let end = ((segment + 1) * max).min(self.written);
+                (segment, offset - segment * max, end - offset)
+            }
+        };
+        let mut data = vec![0; length.min(available) as usize];
+        if !data.is_empty() {
+            let mut file = File::open(self.path(&self.file_name(segment)))?;
+            file.seek(SeekFrom::Start(position))?;
+            file.read_exact(&mut data)?;
+        }
+
+        Ok(OutputRange {
+            data,
+            offset,
+            total_bytes: self.written,
+            complete: self.complete,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // Rotated files kept besides the current one
+        let kept = match self.policy.max_files {
+            0 => u64::MAX,
+            n => n as u64 - 1,
+        };
+        // Oldest first so nothing is overwritten
+        for age in (1..=self.segment.min(kept)).rev() {
+            let from = self.path(&self.aged_name(age));
+            if age == kept {
+                std::fs::remove_file(&from).or_else(ignore_missing)?;
+            } else {
+                std::fs::rename(&from, self.path(&self.aged_name(age + 1)))?;
+            }
+        }
+        if kept > 0 {
+            std::fs::rename(self.path(&self.name), self.path(&self.aged_name(1)))?;
+        }
+        self.file = create(&self.path(&self.name))?;
+        self.segment += 1;
+        Ok(())
+    }
+
+    /// Name of the file holding `segment`.
+    fn file_name(&self, segment: u64) -> String {
+        self.aged_name(self.segment - segment)
+    }
+
+    /// `name` for the current file, `name.N` for the one N rotations old.
+    fn aged_name(&self, age: u64) -> String {
+        match age {
+            0 => self.name.clone(),
+            n => format!("{}.{}", self.name, n),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        entry(&self.dir, name)
+    }
+}
+
+/// Path of `name` in an open directory, valid whatever mount namespace or
+/// chroot the directory was opened through.
+fn entry(dir: &File, name: &str) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", dir.as_raw_fd())).join(name)
+}
+
+fn create(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+}
+
+fn ignore_missing(e: io::Error) -> io::Result<()> {
+    if e.kind() == io::ErrorKind::NotFound {
+        Ok(())
+    } else {
+        Err(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(dir: &Path, max_file_bytes: u64, max_files: u32) -> CapturedStream {
+        let policy = RotationPolicy {
+            max_file_bytes,
+            max_files,
+        };
+        CapturedStream::in_dir(File::open(dir).unwrap(), "out.log".to_string(), policy).unwrap()
+    }
+
+    fn read_all(stream: &CapturedStream, mut offset: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let range = stream.read(offset, 0).unwrap();
+            if range.data.is_empty() {
+                return out;
+            }
+            offset = range.offset + range.data.len() as u64;
+            out.extend(range.data);
+        }
+    }
+
+    #[test]
+    fn test_unlimited_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = stream(dir.path(), 0, 0);
+        out.write(b"hello ").unwrap();
+        out.write(b"world").unwrap();
+        out.finish();
+
+        let range = out.read(6, 3).unwrap();
+        assert_eq!(range.data, b"wor");
+        assert_eq!(range.total_bytes, 11);
+        assert!(range.complete);
+        assert_eq!(read_all(&out, 0), b"hello world");
+        assert_eq!(
+            std::fs::read(dir.path().join("out.log")).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn test_rotation_drops_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = stream(dir.path(), 4, 3);
+        out.write(b"0123456789").unwrap();
+        out.write(b"abcdefgh").unwrap();
+
+        // 18 bytes in files of 4: "0123" and "4567" rotated away
+        assert_eq!(std::fs::read(dir.path().join("out.log")).unwrap(), b"gh");
+        assert_eq!(
+            std::fs::read(dir.path().join("out.log.1")).unwrap(),
+            b"cdef"
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("out.log.2")).unwrap(),
+            b"89ab"
+        );
+        assert!(!dir.path().join("out.log.3").exists());
+
+        let range = out.read(0, 0).unwrap();
+        assert_eq!(range.offset, 8);
+        assert_eq!(range.data, b"89ab");
+        assert!(!range.complete);
+        assert_eq!(read_all(&out, 0), b"89abcdefgh");
+        assert_eq!(read_all(&out, 15), b"fgh");
+        assert!(out.read(18, 0).unwrap().data.is_empty());
+    }
+
+    #[test]
+    fn test_single_file_keeps_latest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = stream(dir.path(), 4, 1);
+        out.write(b"012345").unwrap();
+        assert_eq!(std::fs::read(dir.path().join("out.log")).unwrap(), b"45");
+        assert_eq!(read_all(&out, 0), b"45");
+    }
+
+    #[test]
+    fn test_keep_all_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut out = stream(dir.path(), 2, 0);
+        out.write(b"abcdefg").unwrap();
+        assert_eq!(std::fs::read(dir.path().join("out.log.3")).unwrap(), b"ab");
+        assert_eq!(read_all(&out, 0), b"abcdefg");
+    }
+}
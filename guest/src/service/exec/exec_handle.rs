@@ -40,8 +40,22 @@ impl ExecStdin {
             .await
             .map_err(|e| BoxliteError::Internal(format!("Failed to write to stdin: {}", e)))
     }
+
+    /// Close stdin (signals EOF to process)
+    ///
+    /// Writes complete in the background, so flush first to make sure the
+    /// last of the input reached the process and to surface its errors.
+    pub async fn close(mut self) -> BoxliteResult<()> {
+        self.inner
+            .flush()
+            .await
+            .map_err(|e| BoxliteError::Internal(format!("Failed to write to stdin: {}", e)))
+    }
 }
 
+/// Output chunks buffered before the reader thread applies backpressure.
+const OUTPUT_CHANNEL_CAPACITY: usize = 16;
+
 // Shared output stream implementation
 struct OutputStream {
     inner: Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>,
@@ -50,23 +64,17 @@ struct OutputStream {
 impl OutputStream {
     fn new(fd: OwnedFd) -> Self {
         use async_stream::stream;
-        use std::os::fd::{FromRawFd, IntoRawFd};
-        use tokio::io::AsyncReadExt;
 
-        // Convert OwnedFd to tokio file
-        let std_file = unsafe { std::fs::File::from_raw_fd(fd.into_raw_fd()) };
-        let file = tokio::fs::File::from_std(std_file);
-        let mut reader = tokio::io::BufReader::new(file);
+        // Drain the fd on a blocking thread (io_uring when available) and
+        // hand chunks over as they arrive (works for both PTY and pipes)
+        let (tx, mut rx) = tokio::sync::mpsc::channel(OUTPUT_CHANNEL_CAPACITY);
+        tokio::task::spawn_blocking(move || {
+            crate::uring::drain_pipe(fd, |chunk| tx.blocking_send(chunk).is_ok());
+        });
 
-        // Read chunks as they arrive (works for both PTY and pipes)
         let stream = stream! {
-            let mut buf = [0u8; 1024];
-            loop {
-                match reader.read(&mut buf).await {
-                    Ok(0) => break,  // EOF
-                    Ok(n) => yield buf[..n].to_vec(),
-                    Err(_) => break,
-                }
+            while let Some(chunk) = rx.recv().await {
+                yield chunk;
             }
         };
 
@@ -141,7 +149,6 @@ pub enum ExitStatus {
     Code(i32),
 
     /// Process was terminated by signal
-    #[allow(dead_code)] // API completeness for future signal handling
     Signal(Signal),
 }
 
@@ -256,6 +263,7 @@ impl ExecHandle {
     /// Set PTY controller and config
     ///
     /// Called when process is spawned with console socket (PTY mode).
+    #[cfg_attr(not(feature = "pty"), allow(dead_code))]
     pub fn set_pty(&mut self, controller: std::fs::File, config: PtyConfig) {
         self.pty_controller = Some(controller);
         self.pty_config = Some(config);
@@ -4,11 +4,15 @@
 //! - ContainerExecutor: runs commands inside OCI container
 //! - GuestExecutor: runs commands directly on guest
 
+use crate::container::audit;
+#[cfg(feature = "net")]
+use crate::container::netns::{self, Mode};
+use crate::container::private_fs;
 use crate::container::Container;
 use crate::service::exec::exec_handle::{ExecHandle, PtyConfig};
 use async_trait::async_trait;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
-use boxlite_shared::ExecRequest;
+use boxlite_shared::{ExecNetwork, ExecRequest};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -34,13 +38,48 @@ impl ContainerExecutor {
 impl Executor for ContainerExecutor {
     async fn spawn(&self, req: &ExecRequest) -> BoxliteResult<ExecHandle> {
         let container = self.container.lock().await;
+        let user = req.uid.map(|uid| (uid, req.gid.unwrap_or(0)));
+        #[cfg(feature = "net")]
+        let mode = network_mode(req.network());
+        #[cfg(not(feature = "net"))]
+        if req.network() != ExecNetwork::Shared {
+            return Err(crate::features::missing(
+                boxlite_shared::constants::agent_features::NET,
+            ));
+        }
 
         let mut cmd = container
             .cmd()
-            .program(&req.program)
-            .args(&req.args)
             .envs(req.env.iter().map(|(k, v)| (k.as_str(), v.as_str())));
 
+        // The audit filter goes on last, so it covers only the command
+        let (program, args) = if req.audit {
+            audit::wrap(&req.program, &req.args)
+        } else {
+            (req.program.clone(), req.args.clone())
+        };
+
+        // The helpers switch to the user after setting up their namespace
+        #[cfg(feature = "net")]
+        let (program, args, user) = match mode {
+            Some(mode) => {
+                let (program, args) = netns::wrap(mode, user, &program, &args);
+                (program, args, None)
+            }
+            None => (program, args, user),
+        };
+        let (program, args, user) = match &req.private_fs {
+            Some(view) => {
+                let (program, args) = private_fs::wrap(&view.overlay_paths, user, &program, &args);
+                (program, args, None)
+            }
+            None => (program, args, user),
+        };
+        cmd = cmd.program(program).args(args);
+        if let Some((uid, gid)) = user {
+            cmd = cmd.user(uid, gid);
+        }
+
         if !req.workdir.is_empty() {
             cmd = cmd.current_dir(&req.workdir);
         }
@@ -54,7 +93,26 @@ impl Executor for ContainerExecutor {
             });
         }
 
-        cmd.spawn().await
+        let handle = cmd.spawn().await?;
+        #[cfg(feature = "net")]
+        if mode == Some(Mode::Veth) {
+            if let Err(e) = netns::attach_veth(handle.pid()).await {
+                let _ = nix::sys::signal::kill(handle.pid(), nix::sys::signal::Signal::SIGKILL);
+                return Err(e);
+            }
+        }
+        Ok(handle)
+    }
+}
+
+/// Namespace helper mode, or None to share the guest's network.
+#[cfg(feature = "net")]
+fn network_mode(network: ExecNetwork) -> Option<Mode> {
+    match network {
+        ExecNetwork::Shared => None,
+        ExecNetwork::None => Some(Mode::None),
+        ExecNetwork::Loopback => Some(Mode::Loopback),
+        ExecNetwork::Veth => Some(Mode::Veth),
     }
 }
 
@@ -64,6 +122,16 @@ pub struct GuestExecutor;
 #[async_trait]
 impl Executor for GuestExecutor {
     async fn spawn(&self, req: &ExecRequest) -> BoxliteResult<ExecHandle> {
+        if req.network() != ExecNetwork::Shared {
+            return Err(BoxliteError::Unsupported(
+                "network isolation is only available for container execs".to_string(),
+            ));
+        }
+        if req.private_fs.is_some() {
+            return Err(BoxliteError::Unsupported(
+                "private filesystem views are only available for container execs".to_string(),
+            ));
+        }
         if let Some(tty) = &req.tty {
             let config = PtyConfig {
                 rows: tty.rows as u16,
@@ -78,13 +146,25 @@ impl Executor for GuestExecutor {
     }
 }
 
+/// This binary, which runs audited commands as the audit helper.
+fn agent_exe() -> BoxliteResult<std::path::PathBuf> {
+    std::env::current_exe()
+        .map_err(|e| BoxliteError::Internal(format!("Cannot locate the audit helper: {}", e)))
+}
+
 /// Spawn process with pipes (standard mode).
 fn spawn_with_pipes(req: &ExecRequest) -> BoxliteResult<ExecHandle> {
     use nix::unistd::Pid;
     use std::os::unix::io::{AsRawFd, FromRawFd};
     use tokio::process::Command;
 
-    let mut cmd = Command::new(&req.program);
+    let mut cmd = if req.audit {
+        let mut cmd = Command::new(agent_exe()?);
+        cmd.arg0(audit::AUDIT_HELPER_NAME).arg(&req.program);
+        cmd
+    } else {
+        Command::new(&req.program)
+    };
     cmd.args(&req.args);
 
     for (k, v) in &req.env {
@@ -95,6 +175,10 @@ fn spawn_with_pipes(req: &ExecRequest) -> BoxliteResult<ExecHandle> {
         cmd.current_dir(&req.workdir);
     }
 
+    if let Some(uid) = req.uid {
+        cmd.uid(uid).gid(req.gid.unwrap_or(0));
+    }
+
     // Create pipes for stdin/stdout/stderr
     let (stdin_read, stdin_write) = nix::unistd::pipe()
         .map_err(|e| BoxliteError::Internal(format!("Failed to create stdin pipe: {}", e)))?;
@@ -132,6 +216,7 @@ fn spawn_with_pipes(req: &ExecRequest) -> BoxliteResult<ExecHandle> {
 }
 
 /// Spawn process with PTY (interactive mode).
+#[cfg(feature = "pty")]
 fn spawn_with_pty(req: &ExecRequest, config: PtyConfig) -> BoxliteResult<ExecHandle> {
     use nix::pty::{openpty, OpenptyResult, Winsize};
     use nix::unistd::{dup, Pid};
@@ -162,7 +247,13 @@ fn spawn_with_pty(req: &ExecRequest, config: PtyConfig) -> BoxliteResult<ExecHan
         .map_err(|e| BoxliteError::Internal(format!("Failed to dup slave for stderr: {}", e)))?;
 
     // Build command
-    let mut cmd = Command::new(&req.program);
+    let mut cmd = if req.audit {
+        let mut cmd = Command::new(agent_exe()?);
+        cmd.arg0(audit::AUDIT_HELPER_NAME).arg(&req.program);
+        cmd
+    } else {
+        Command::new(&req.program)
+    };
     cmd.args(&req.args);
 
     for (k, v) in &req.env {
@@ -173,6 +264,10 @@ fn spawn_with_pty(req: &ExecRequest, config: PtyConfig) -> BoxliteResult<ExecHan
         cmd.current_dir(&req.workdir);
     }
 
+    if let Some(uid) = req.uid {
+        cmd.uid(uid).gid(req.gid.unwrap_or(0));
+    }
+
     // Configure child to use PTY slave as stdin/stdout/stderr
     // Each Stdio takes ownership of its dup'd FD
     unsafe {
@@ -229,3 +324,10 @@ fn spawn_with_pty(req: &ExecRequest, config: PtyConfig) -> BoxliteResult<ExecHan
 
     Ok(handle)
 }
+
+#[cfg(not(feature = "pty"))]
+fn spawn_with_pty(_req: &ExecRequest, _config: PtyConfig) -> BoxliteResult<ExecHandle> {
+    Err(crate::features::missing(
+        boxlite_shared::constants::agent_features::PTY,
+    ))
+}
@@ -16,19 +16,23 @@
 //!
 //! Each file has a single, clear responsibility.
 
+mod capture;
 #[cfg(target_os = "linux")]
 pub mod exec_handle;
 pub(in crate::service) mod executor;
 pub(in crate::service) mod registry;
 mod state;
 mod timeout;
+mod usage;
 
 use crate::service::exec::executor::{ContainerExecutor, GuestExecutor};
 use crate::service::server::GuestServer;
+use crate::service::sessions;
 use boxlite_shared::{
     constants::executor as executor_const, AttachRequest, ExecError, ExecOutput, ExecRequest,
-    ExecResponse, ExecStdin, Execution, KillRequest, KillResponse, ResizeTtyRequest,
-    ResizeTtyResponse, SendInputAck, WaitRequest, WaitResponse,
+    ExecResponse, ExecStdin, Execution, FetchOutputRequest, FetchOutputResponse, KillRequest,
+    KillResponse, OutputStream, ResizeTtyRequest, ResizeTtyResponse, SendInputAck, WaitRequest,
+    WaitResponse,
 };
 use futures::stream::Stream;
 use std::pin::Pin;
@@ -73,6 +77,7 @@ impl Execution for GuestServer {
         &self,
         request: Request<AttachRequest>,
     ) -> Result<Response<Self::AttachStream>, Status> {
+        let cancel = sessions::cancellation(&request);
         let exec_id = request.into_inner().execution_id;
         info!(execution_id = %exec_id, "attach request");
 
@@ -84,7 +89,7 @@ impl Execution for GuestServer {
             .ok_or_else(|| Status::not_found(format!("Execution not found: {}", exec_id)))?;
 
         // Call state directly
-        let rx = state.attach(&exec_id).await?;
+        let rx = state.attach(&exec_id, cancel).await?;
 
         Ok(Response::new(
             Box::pin(ReceiverStream::new(rx)) as Self::AttachStream
@@ -95,6 +100,7 @@ impl Execution for GuestServer {
         &self,
         request: Request<Streaming<ExecStdin>>,
     ) -> Result<Response<SendInputAck>, Status> {
+        let cancel = sessions::cancellation(&request);
         let mut stream = request.into_inner();
 
         // First message must carry execution_id
@@ -116,7 +122,7 @@ impl Execution for GuestServer {
             .ok_or_else(|| Status::not_found(format!("Execution not found: {}", exec_id)))?;
 
         // Call state directly
-        let task = state.send_input(first, stream).await?;
+        let task = state.send_input(first, stream, cancel).await?;
 
         // Wait for task to complete
         match task.await {
@@ -129,7 +135,8 @@ impl Execution for GuestServer {
     async fn wait(&self, request: Request<WaitRequest>) -> Result<Response<WaitResponse>, Status> {
         use exec_handle::ExitStatus;
 
-        let exec_id = request.into_inner().execution_id;
+        let req = request.into_inner();
+        let exec_id = req.execution_id;
         debug!(execution_id = %exec_id, "wait request");
 
         // Get state from registry
@@ -139,10 +146,18 @@ impl Execution for GuestServer {
             .await
             .ok_or_else(|| Status::not_found(format!("Execution not found: {}", exec_id)))?;
 
-        // Wait for process to exit
-        let exit_status = state.wait_process().await?;
+        // Wait for process to exit. Tonic drops this future if the caller
+        // cancels the call, which kills the process if asked to.
+        let mut guard = KillOnCancel {
+            exec_id: exec_id.clone(),
+            state: state.clone(),
+            armed: req.kill_on_cancel,
+        };
+        let report = state.wait_process().await;
+        guard.armed = false;
+        let report = report?;
 
-        let (exit_code, signal) = match exit_status {
+        let (exit_code, signal) = match report.status {
             ExitStatus::Code(code) => {
                 debug!(
                     execution_id = %exec_id,
@@ -160,12 +175,25 @@ impl Execution for GuestServer {
                 (0, sig as i32)
             }
         };
+        let signal_name = match report.status {
+            ExitStatus::Code(_) => String::new(),
+            ExitStatus::Signal(sig) => sig.as_str().to_string(),
+        };
+        let audit = state.audit_report().await;
 
         Ok(Response::new(WaitResponse {
             exit_code,
             signal,
-            timed_out: false,
-            duration_ms: 0,
+            timed_out: report.timed_out,
+            duration_ms: report.duration.as_millis() as u64,
+            oom_killed: report.oom_killed,
+            signal_name,
+            peak_memory_bytes: report.usage.peak_memory_bytes,
+            user_time_us: report.usage.user_time.as_micros() as u64,
+            system_time_us: report.usage.system_time.as_micros() as u64,
+            read_bytes: report.usage.read_bytes,
+            write_bytes: report.usage.write_bytes,
+            audit,
         }))
     }
 
@@ -268,9 +296,52 @@ impl Execution for GuestServer {
             }
         }
     }
+
+    async fn fetch_output(
+        &self,
+        request: Request<FetchOutputRequest>,
+    ) -> Result<Response<FetchOutputResponse>, Status> {
+        let req = request.into_inner();
+        let stream = OutputStream::try_from(req.stream)
+            .map_err(|_| Status::invalid_argument(format!("Unknown stream: {}", req.stream)))?;
+
+        let state = self.registry.get(&req.execution_id).await.ok_or_else(|| {
+            Status::not_found(format!("Execution not found: {}", req.execution_id))
+        })?;
+
+        let range = state.fetch_output(stream, req.offset, req.length).await?;
+        Ok(Response::new(FetchOutputResponse {
+            data: range.data,
+            offset: range.offset,
+            total_bytes: range.total_bytes,
+            complete: range.complete,
+        }))
+    }
 }
 
 /// Spawn execution (orchestrates full lifecycle).
+/// Kills an execution whose wait call was dropped before it exited.
+struct KillOnCancel {
+    exec_id: String,
+    state: state::ExecutionState,
+    armed: bool,
+}
+
+impl Drop for KillOnCancel {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let exec_id = std::mem::take(&mut self.exec_id);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            if state.kill(nix::sys::signal::Signal::SIGKILL).await {
+                info!(execution_id = %exec_id, "wait cancelled, killed execution");
+            }
+        });
+    }
+}
+
 async fn spawn_execution(
     server: &GuestServer,
     execution_id: String,
@@ -283,8 +354,27 @@ async fn spawn_execution(
 
     let pid = child.pid().as_raw() as u32;
 
-    // Step 2: Create execution state and register
+    // Step 2: Create execution state, capture output, and register
     let state = state::ExecutionState::new(child);
+    if let Some(capture) = &req.capture {
+        if let Err(e) = state.start_capture(capture).await {
+            state.kill(nix::sys::signal::Signal::SIGKILL).await;
+            // Reap it; nobody will wait on an execution that failed to start
+            tokio::spawn(async move {
+                let _ = state.wait_process().await;
+            });
+            return Err(error_response(execution_id, "capture_failed", e.message()));
+        }
+    }
+    if req.audit {
+        if let Err(e) = state.start_audit().await {
+            state.kill(nix::sys::signal::Signal::SIGKILL).await;
+            tokio::spawn(async move {
+                let _ = state.wait_process().await;
+            });
+            return Err(error_response(execution_id, "audit_failed", e.message()));
+        }
+    }
     server
         .registry
         .register(execution_id.clone(), state.clone())
@@ -39,4 +39,17 @@ impl ExecutionRegistry {
     pub async fn register(&self, exec_id: String, state: ExecutionState) {
         self.executions.lock().await.insert(exec_id, state);
     }
+
+    /// IDs of executions whose process has not exited, sorted.
+    pub async fn running(&self) -> Vec<String> {
+        let executions = self.executions.lock().await.clone();
+        let mut running = Vec::new();
+        for (exec_id, state) in executions {
+            if state.is_running().await {
+                running.push(exec_id);
+            }
+        }
+        running.sort();
+        running
+    }
 }
@@ -1,9 +1,15 @@
-use crate::service::exec::exec_handle::ExecHandle;
-use boxlite_shared::ExecOutput;
+use crate::container::audit::{self, Audit};
+use crate::service::exec::capture::{CapturedStream, OutputRange, RotationPolicy};
+use crate::service::exec::exec_handle::{ExecHandle, ExecStdin, ExitStatus};
+use crate::service::exec::usage::{self, OomCounter, ResourceUsage};
+use boxlite_shared::{AuditReport, ExecOutput, OutputCapture, OutputStream};
+use futures::{Stream, StreamExt};
 use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tonic::Status;
 use tracing::info;
 
@@ -13,9 +19,102 @@ struct Inner {
     handle: Option<ExecHandle>,
     /// Stdout/stderr forwarding tasks (set on attach)
     output_tasks: Vec<JoinHandle<()>>,
-    /// Timeout flag
-    #[allow(dead_code)] // Will be used for timeout handling
+    /// Set when the timeout watcher killed the process
     timed_out: bool,
+    started_at: Instant,
+    oom_kills: OomCounter,
+    /// Streams written to files instead of attach
+    stdout_capture: Option<Arc<std::sync::Mutex<CapturedStream>>>,
+    stderr_capture: Option<Arc<std::sync::Mutex<CapturedStream>>>,
+    /// Syscall activity, for audited executions
+    audit: Option<Audit>,
+}
+
+/// How a process ended and what it used.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExitReport {
+    pub status: ExitStatus,
+    pub timed_out: bool,
+    pub oom_killed: bool,
+    pub duration: Duration,
+    pub usage: ResourceUsage,
+}
+
+/// Flush what is still in flight to the process, then close its stdin.
+async fn close_stdin(stdin: ExecStdin) -> Result<(), Status> {
+    stdin
+        .close()
+        .await
+        .map_err(|e| Status::internal(format!("Stdin write failed: {}", e)))
+}
+
+/// Drain `output` into `file` until the process closes it.
+///
+/// A failed write (full disk, say) drops the rest of the output rather
+/// than stopping the drain, which would block the process on a full pipe.
+fn spawn_capture<S>(mut output: S, file: Arc<std::sync::Mutex<CapturedStream>>)
+where
+    S: Stream<Item = Vec<u8>> + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut failed = false;
+        while let Some(chunk) = output.next().await {
+            if failed {
+                continue;
+            }
+            let file = Arc::clone(&file);
+            let result =
+                tokio::task::spawn_blocking(move || file.lock().unwrap().write(&chunk)).await;
+            if let Ok(Err(e)) = result {
+                tracing::warn!("Dropping captured output after write failure: {}", e);
+                failed = true;
+            }
+        }
+        file.lock().unwrap().finish();
+    });
+}
+
+/// Write the stdin stream to the process until it closes.
+async fn forward_input(
+    first: boxlite_shared::ExecStdin,
+    mut stream: tonic::Streaming<boxlite_shared::ExecStdin>,
+    mut stdin: ExecStdin,
+) -> Result<(), Status> {
+    // Write first message data
+    if !first.data.is_empty() {
+        stdin
+            .write_all(&first.data)
+            .await
+            .map_err(|e| Status::internal(format!("Stdin write failed: {}", e)))?;
+    }
+    if first.close {
+        return close_stdin(stdin).await;
+    }
+
+    // Forward remaining messages
+    while let Some(msg) = stream.message().await? {
+        if !msg.data.is_empty() {
+            stdin
+                .write_all(&msg.data)
+                .await
+                .map_err(|e| Status::internal(format!("Stdin write failed: {}", e)))?;
+        }
+        if msg.close {
+            break;
+        }
+    }
+    close_stdin(stdin).await
+}
+
+/// Next chunk of `output`, or `None` once it ends or `cancel` fires.
+async fn next_output<S>(output: &mut S, cancel: &CancellationToken) -> Option<Vec<u8>>
+where
+    S: Stream<Item = Vec<u8>> + Unpin,
+{
+    tokio::select! {
+        chunk = output.next() => chunk,
+        _ = cancel.cancelled() => None,
+    }
 }
 
 /// Execution state.
@@ -30,10 +129,16 @@ pub(crate) struct ExecutionState {
 impl ExecutionState {
     /// Create new execution state.
     pub(super) fn new(handle: ExecHandle) -> Self {
+        let oom_kills = OomCounter::for_process(handle.pid());
         let inner = Inner {
             handle: Some(handle),
             output_tasks: Vec::new(),
             timed_out: false,
+            started_at: Instant::now(),
+            oom_kills,
+            stdout_capture: None,
+            stderr_capture: None,
+            audit: None,
         };
 
         Self {
@@ -48,17 +153,32 @@ impl ExecutionState {
         inner.handle.as_ref().map(|h| h.pid().as_raw() as u32)
     }
 
+    /// Whether the process has not exited yet (an exited one that is not
+    /// reaped is a zombie).
+    pub async fn is_running(&self) -> bool {
+        let inner = self.inner.lock().await;
+        inner.handle.as_ref().is_some_and(|h| {
+            procfs::process::Process::new(h.pid().as_raw())
+                .and_then(|process| process.stat())
+                .is_ok_and(|stat| stat.state != 'Z')
+        })
+    }
+
     /// Send input to execution stdin.
     ///
     /// Takes stdin from handle, spawns forwarding task, returns task handle.
     /// Note: First message has already been read to extract execution_id.
+    ///
+    /// The task stops, closing stdin, once `cancel` fires, so a process
+    /// that is not reading cannot hold it after the client has gone.
     pub async fn send_input(
         &self,
         first: boxlite_shared::ExecStdin,
-        mut stream: tonic::Streaming<boxlite_shared::ExecStdin>,
+        stream: tonic::Streaming<boxlite_shared::ExecStdin>,
+        cancel: CancellationToken,
     ) -> Result<JoinHandle<Result<(), Status>>, Status> {
         // Take stdin from handle
-        let mut stdin = {
+        let stdin = {
             let mut inner = self.inner.lock().await;
             let handle = inner
                 .handle
@@ -72,30 +192,10 @@ impl ExecutionState {
 
         // Spawn forwarding task
         let task = tokio::spawn(async move {
-            // Write first message data
-            if !first.data.is_empty() {
-                stdin
-                    .write_all(&first.data)
-                    .await
-                    .map_err(|e| Status::internal(format!("Stdin write failed: {}", e)))?;
-            }
-            if first.close {
-                return Ok(());
+            tokio::select! {
+                result = forward_input(first, stream, stdin) => result,
+                _ = cancel.cancelled() => Err(Status::cancelled("Client went away")),
             }
-
-            // Forward remaining messages
-            while let Some(msg) = stream.message().await? {
-                if !msg.data.is_empty() {
-                    stdin
-                        .write_all(&msg.data)
-                        .await
-                        .map_err(|e| Status::internal(format!("Stdin write failed: {}", e)))?;
-                }
-                if msg.close {
-                    break;
-                }
-            }
-            Ok(())
         });
 
         Ok(task)
@@ -103,12 +203,10 @@ impl ExecutionState {
 
     /// Wait for process to exit.
     ///
-    /// Gets pid from handle and waits using waitpid.
-    pub async fn wait_process(
-        &self,
-    ) -> Result<crate::service::exec::exec_handle::ExitStatus, Status> {
-        use crate::service::exec::exec_handle::ExitStatus;
-        use nix::sys::wait::{waitpid, WaitStatus};
+    /// Gets pid from handle and reaps it with wait4 to collect its usage.
+    pub async fn wait_process(&self) -> Result<ExitReport, Status> {
+        use nix::sys::signal::Signal;
+        use nix::sys::wait::WaitStatus;
 
         // Get pid from handle
         let pid = {
@@ -121,31 +219,44 @@ impl ExecutionState {
         };
 
         // Wait for process (blocking call in spawn_blocking)
-        let result = tokio::task::spawn_blocking(move || waitpid(pid, None))
+        let (result, usage) = tokio::task::spawn_blocking(move || usage::wait(pid))
             .await
             .map_err(|e| Status::internal(format!("spawn_blocking failed: {}", e)))?
-            .map_err(|e| Status::internal(format!("waitpid failed: {}", e)))?;
-
-        match result {
-            WaitStatus::Exited(_, code) => Ok(ExitStatus::Code(code)),
-            WaitStatus::Signaled(_, sig, _) => Ok(ExitStatus::Signal(sig)),
-            other => Err(Status::internal(format!(
-                "Unexpected wait status: {:?}",
-                other
-            ))),
-        }
+            .map_err(|e| Status::internal(format!("wait4 failed: {}", e)))?;
+
+        let status = match result {
+            WaitStatus::Exited(_, code) => ExitStatus::Code(code),
+            WaitStatus::Signaled(_, sig, _) => ExitStatus::Signal(sig),
+            other => {
+                return Err(Status::internal(format!(
+                    "Unexpected wait status: {:?}",
+                    other
+                )))
+            }
+        };
+
+        let inner = self.inner.lock().await;
+        Ok(ExitReport {
+            status,
+            timed_out: inner.timed_out,
+            // The OOM killer always uses SIGKILL
+            oom_killed: matches!(status, ExitStatus::Signal(Signal::SIGKILL))
+                && inner.oom_kills.fired(),
+            duration: inner.started_at.elapsed(),
+            usage,
+        })
     }
 
     /// Attach to execution output.
     ///
-    /// Takes stdout/stderr from handle and starts forwarding tasks.
-    /// Returns stream of output chunks.
+    /// Takes stdout/stderr from handle and starts forwarding tasks, which
+    /// stop when `cancel` fires. Returns stream of output chunks.
     pub async fn attach(
         &self,
         exec_id: &str,
+        cancel: CancellationToken,
     ) -> Result<mpsc::Receiver<Result<ExecOutput, Status>>, Status> {
         use boxlite_shared::{exec_output, Stderr, Stdout};
-        use futures::StreamExt;
 
         let (tx, rx) = mpsc::channel(100);
 
@@ -175,8 +286,9 @@ impl ExecutionState {
         let exec_id_string = exec_id.to_string();
         if let Some(mut stdout) = stdout {
             let tx = tx.clone();
+            let cancel = cancel.clone();
             let handle = tokio::spawn(async move {
-                while let Some(chunk) = stdout.next().await {
+                while let Some(chunk) = next_output(&mut stdout, &cancel).await {
                     let msg = ExecOutput {
                         event: Some(exec_output::Event::Stdout(Stdout { data: chunk })),
                     };
@@ -193,8 +305,9 @@ impl ExecutionState {
         let exec_id_string = exec_id.to_string();
         if let Some(mut stderr) = stderr {
             let tx = tx.clone();
+            let cancel = cancel.clone();
             let handle = tokio::spawn(async move {
-                while let Some(chunk) = stderr.next().await {
+                while let Some(chunk) = next_output(&mut stderr, &cancel).await {
                     let msg = ExecOutput {
                         event: Some(exec_output::Event::Stderr(Stderr { data: chunk })),
                     };
@@ -216,6 +329,87 @@ impl ExecutionState {
         Ok(rx)
     }
 
+    /// Write stdout and/or stderr to files instead of leaving them for attach.
+    pub async fn start_capture(&self, capture: &OutputCapture) -> Result<(), Status> {
+        let policy = RotationPolicy {
+            max_file_bytes: capture.max_file_bytes,
+            max_files: capture.max_files,
+        };
+        let mut inner = self.inner.lock().await;
+        let handle = inner
+            .handle
+            .as_mut()
+            .ok_or_else(|| Status::failed_precondition("Handle not available"))?;
+        let pid = handle.pid().as_raw();
+
+        let open = |path: &str| match path {
+            "" => Ok(None),
+            path => CapturedStream::create(pid, path, policy)
+                .map(|stream| Some(Arc::new(std::sync::Mutex::new(stream))))
+                .map_err(|e| format!("Failed to create {}: {}", path, e)),
+        };
+        let stdout = open(&capture.stdout_path).map_err(Status::internal)?;
+        let stderr = open(&capture.stderr_path).map_err(Status::internal)?;
+
+        if let (Some(file), Some(output)) = (&stdout, handle.stdout()) {
+            spawn_capture(output, Arc::clone(file));
+        }
+        if let (Some(file), Some(output)) = (&stderr, handle.stderr()) {
+            spawn_capture(output, Arc::clone(file));
+        }
+        inner.stdout_capture = stdout;
+        inner.stderr_capture = stderr;
+        Ok(())
+    }
+
+    /// Start recording the syscalls of a process spawned through the audit
+    /// helper, which stays stopped until this is done.
+    pub async fn start_audit(&self) -> Result<(), Status> {
+        let pid = {
+            let inner = self.inner.lock().await;
+            inner
+                .handle
+                .as_ref()
+                .ok_or_else(|| Status::failed_precondition("Handle not available"))?
+                .pid()
+        };
+        let audit = audit::attach(pid)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        self.inner.lock().await.audit = Some(audit);
+        Ok(())
+    }
+
+    /// What an audited execution has done so far.
+    pub async fn audit_report(&self) -> Option<AuditReport> {
+        let inner = self.inner.lock().await;
+        inner.audit.as_ref().map(Audit::report)
+    }
+
+    /// Read a range of captured output.
+    pub async fn fetch_output(
+        &self,
+        stream: OutputStream,
+        offset: u64,
+        length: u64,
+    ) -> Result<OutputRange, Status> {
+        let capture = {
+            let inner = self.inner.lock().await;
+            match stream {
+                OutputStream::Stdout => inner.stdout_capture.clone(),
+                OutputStream::Stderr => inner.stderr_capture.clone(),
+            }
+        }
+        .ok_or_else(|| {
+            Status::failed_precondition(format!("{} is not captured", stream.as_str_name()))
+        })?;
+
+        tokio::task::spawn_blocking(move || capture.lock().unwrap().read(offset, length))
+            .await
+            .map_err(|e| Status::internal(format!("spawn_blocking failed: {}", e)))?
+            .map_err(|e| Status::internal(format!("Failed to read captured output: {}", e)))
+    }
+
     /// Kill process with signal.
     ///
     /// Returns true if signal was sent, false if already exited.
@@ -229,6 +423,20 @@ impl ExecutionState {
         }
     }
 
+    /// Kill process because it ran past its timeout.
+    ///
+    /// Returns true if signal was sent, false if already exited.
+    pub async fn kill_on_timeout(&self, signal: nix::sys::signal::Signal) -> bool {
+        let mut inner = self.inner.lock().await;
+
+        let killed = match inner.handle {
+            Some(ref handle) => handle.kill(signal).is_ok(),
+            None => false,
+        };
+        inner.timed_out |= killed;
+        killed
+    }
+
     /// Resize PTY window.
     pub async fn resize_pty(
         &self,
@@ -0,0 +1,143 @@
+//! Exit accounting.
+//!
+//! Reaps an execution with wait4 so its resource usage comes back with its
+//! status, and tells OOM kills apart from other SIGKILLs by the kernel's
+//! oom_kill counter: the one in the process's cgroup `memory.events` when
+//! cgroupfs is mounted, otherwise the VM-wide one in `/proc/vmstat` (the
+//! guest is single-tenant, so that is still tied to the box).
+
+use nix::sys::wait::WaitStatus;
+use nix::unistd::Pid;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const VMSTAT: &str = "/proc/vmstat";
+
+/// Resources a reaped process (and the descendants it reaped) consumed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceUsage {
+    /// Largest resident set size of any process in the tree.
+    pub peak_memory_bytes: u64,
+    pub user_time: Duration,
+    pub system_time: Duration,
+    /// Bytes the tree read from and wrote to block devices.
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+/// Block until `pid` exits and reap it.
+pub fn wait(pid: Pid) -> io::Result<(WaitStatus, ResourceUsage)> {
+    let mut status = 0;
+    // SAFETY: rusage is plain data that wait4 fills in
+    let mut rusage: nix::libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let ret = unsafe { nix::libc::wait4(pid.as_raw(), &mut status, 0, &mut rusage) };
+        if ret >= 0 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    let status = WaitStatus::from_raw(pid, status).map_err(io::Error::from)?;
+    let usage = ResourceUsage {
+        // ru_maxrss is in KiB on Linux
+        peak_memory_bytes: rusage.ru_maxrss.max(0) as u64 * 1024,
+        user_time: timeval(rusage.ru_utime),
+        system_time: timeval(rusage.ru_stime),
+        // Block I/O counts are in 512-byte units
+        read_bytes: rusage.ru_inblock.max(0) as u64 * 512,
+        write_bytes: rusage.ru_oublock.max(0) as u64 * 512,
+    };
+    Ok((status, usage))
+}
+
+fn timeval(tv: nix::libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec.max(0) as u64) + Duration::from_micros(tv.tv_usec.max(0) as u64)
+}
+
+/// OOM kill count covering a process, taken when it started.
+#[derive(Debug, Clone)]
+pub struct OomCounter {
+    path: PathBuf,
+    start: Option<u64>,
+}
+
+impl OomCounter {
+    /// Snapshot the counter for `pid`'s cgroup, or the VM's.
+    pub fn for_process(pid: Pid) -> Self {
+        let cgroup_events = std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+            .ok()
+            .and_then(|content| cgroup_path(&content).map(str::to_string))
+            .map(|path| PathBuf::from(CGROUP_ROOT).join(path.trim_start_matches('/')))
+            .map(|dir| dir.join("memory.events"))
+            .filter(|events| events.exists());
+
+        let path = cgroup_events.unwrap_or_else(|| PathBuf::from(VMSTAT));
+        let start = read_counter(&path);
+        Self { path, start }
+    }
+
+    /// Whether the OOM killer has fired since the snapshot.
+    pub fn fired(&self) -> bool {
+        match (self.start, read_counter(&self.path)) {
+            (Some(start), Some(now)) => now > start,
+            _ => false,
+        }
+    }
+}
+
+fn read_counter(path: &std::path::Path) -> Option<u64> {
+    parse_counter(&std::fs::read_to_string(path).ok()?, "oom_kill")
+}
+
+/// Value of a `key value` line, the format of both `memory.events` and
+/// `/proc/vmstat`.
+fn parse_counter(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (name, value) = line.split_once(' ')?;
+        (name == key).then(|| value.trim().parse().ok())?
+    })
+}
+
+/// The cgroup2 path in `/proc/<pid>/cgroup` content.
+fn cgroup_path(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_counter() {
+        let events = "low 0\nhigh 0\nmax 3\noom 2\noom_kill 1\noom_group_kill 0\n";
+        assert_eq!(parse_counter(events, "oom_kill"), Some(1));
+        assert_eq!(parse_counter(events, "oom"), Some(2));
+        assert_eq!(parse_counter(events, "missing"), None);
+        assert_eq!(parse_counter("pgfault 10\noom_kill 7", "oom_kill"), Some(7));
+    }
+
+    #[test]
+    fn test_cgroup_path() {
+        assert_eq!(cgroup_path("0::/boxlite/abc\n"), Some("/boxlite/abc"));
+        assert_eq!(cgroup_path("1:name=systemd:/init.scope\n0::/\n"), Some("/"));
+        assert_eq!(cgroup_path(""), None);
+    }
+
+    #[test]
+    #[allow(clippy::zombie_processes)] // reaped by wait() under test
+    fn test_wait_reports_exit_and_usage() {
+        let child = std::process::Command::new("sh")
+            .args(["-c", "exit 3"])
+            .spawn()
+            .unwrap();
+        let pid = Pid::from_raw(child.id() as i32);
+        let (status, usage) = wait(pid).unwrap();
+        assert!(matches!(status, WaitStatus::Exited(_, 3)));
+        assert!(usage.peak_memory_bytes > 0);
+    }
+}
@@ -1,23 +1,42 @@
 //! Guest service implementation.
 //!
-//! Handles guest initialization and management (Init, Ping, Shutdown RPCs).
+//! Handles guest initialization and management (Init, Ping, Shutdown,
+//! Resume, Wake, UpgradeAgent, WatchMemory, WatchPressure, ReclaimMemory,
+//! WatchCrashes, Capabilities RPCs).
 
+use crate::boot::BootGraph;
 use crate::service::server::GuestServer;
+use boxlite_shared::constants::agent_features;
+use boxlite_shared::errors::BoxliteResult;
 use boxlite_shared::{
-    guest_init_response, Guest as GuestService, GuestInitError, GuestInitRequest,
-    GuestInitResponse, GuestInitSuccess, PingRequest, PingResponse, ShutdownRequest,
-    ShutdownResponse,
+    guest_init_response, screenshot_response, upgrade_agent_response, BootStageTiming,
+    CapabilitiesRequest, CapabilitiesResponse, CrashDump, Guest as GuestService, GuestInitError,
+    GuestInitRequest, GuestInitResponse, GuestInitSuccess, MemoryEvent, NetworkInit, PingRequest,
+    PingResponse, PressureEvent, ReclaimMemoryRequest, ReclaimMemoryResponse, ResumeRequest,
+    ResumeResponse, ScreenshotError, ScreenshotImage, ScreenshotRequest, ScreenshotResponse,
+    ShutdownRequest, ShutdownResponse, UpgradeAgentError, UpgradeAgentRequest,
+    UpgradeAgentResponse, UpgradeAgentSuccess, WakeRequest, WakeResponse, WatchCrashesRequest,
+    WatchMemoryRequest, WatchPressureRequest,
 };
+use std::pin::Pin;
+use std::time::{Duration, UNIX_EPOCH};
 use tonic::{Request, Response, Status};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+
+/// How long an upgrading agent waits after replying before it execs the
+/// new one, so the reply reaches the host.
+const UPGRADE_EXEC_DELAY: Duration = Duration::from_millis(200);
 
 #[tonic::async_trait]
 impl GuestService for GuestServer {
     /// Initialize guest environment.
     ///
-    /// This must be called first after connection. It:
-    /// 1. Mounts all volumes (virtiofs + block devices)
-    /// 2. Configures network (if specified)
+    /// This must be called first after connection. Concurrently, it:
+    /// - Mounts all volumes (virtiofs + block devices)
+    /// - Configures network (if specified), then mounts network filesystems
+    /// - Loads requested kernel modules (e.g. GPU drivers)
+    ///
+    /// Responds with per-stage timings for the host's boot report.
     ///
     /// Note: Rootfs setup is handled by Container.Init.
     async fn init(
@@ -38,43 +57,91 @@ impl GuestService for GuestServer {
             }));
         }
 
-        // Step 1: Mount all volumes (virtiofs + block devices)
-        // Empty mount_point = guest determines path from tag
-        info!("Mounting {} volumes", req.volumes.len());
-        if let Err(e) = crate::storage::mount_volumes(&req.volumes) {
-            error!("Failed to mount volumes: {}", e);
-            return Ok(Response::new(GuestInitResponse {
-                result: Some(guest_init_response::Result::Error(GuestInitError {
-                    reason: format!("Failed to mount volumes: {}", e),
-                })),
-            }));
-        }
+        let GuestInitRequest {
+            volumes,
+            network,
+            kernel_modules,
+            deterministic,
+            x86_emulation,
+        } = req;
+        let (network_volumes, volumes): (Vec<_>, Vec<_>) = volumes
+            .into_iter()
+            .partition(crate::storage::is_network_volume);
+        let shares = self.shares.clone();
+        let applied_network = network.clone();
 
-        // Step 2: Configure network (if specified)
-        if let Some(network) = req.network {
-            info!("Configuring network interface: {}", network.interface);
-            if let Err(e) = crate::network::configure_network_from_config(
-                &network.interface,
-                network.ip.as_deref(),
-                network.gateway.as_deref(),
-            )
-            .await
-            {
-                error!("Failed to configure network: {}", e);
+        // Init stages are independent of each other and run concurrently
+        let graph = BootGraph::new()
+            // Mount all volumes (virtiofs + block devices)
+            // Empty mount_point = guest determines path from tag
+            .blocking_stage("volumes", &[], move || {
+                info!("Mounting {} volumes", volumes.len());
+                let mounted = crate::storage::mount_volumes(&volumes)?;
+                *shares.lock().unwrap() = mounted;
+                Ok(())
+            })
+            // Configure network (if specified)
+            .stage("network", &[], async move {
+                let Some(network) = network else {
+                    return Ok(());
+                };
+                info!("Configuring network interface: {}", network.interface);
+                configure_network(&network).await
+            })
+            // Remote filesystems need the network, and may land under the
+            // shared mount
+            .blocking_stage("network_mounts", &["volumes", "network"], move || {
+                crate::storage::mount_volumes(&network_volumes).map(drop)
+            })
+            // Load kernel modules (device drivers)
+            .blocking_stage("kernel_modules", &[], move || {
+                crate::modules::load_modules(&kernel_modules)
+            })
+            // Fixed clock start and no ASLR for deterministic boxes
+            .blocking_stage("deterministic", &[], move || match deterministic {
+                Some(deterministic) => crate::determinism::apply(deterministic.clock_start_secs),
+                None => Ok(()),
+            })
+            // The interpreter lives on a volume
+            .blocking_stage("x86_emulation", &["volumes"], move || {
+                match x86_emulation {
+                    Some(emulation) => {
+                        crate::binfmt::register_x86_64(&emulation.interpreter, &emulation.flags)
+                    }
+                    None => Ok(()),
+                }
+            });
+
+        let init_stages = match graph.run().await {
+            Ok(timings) => timings,
+            Err(failure) => {
+                error!("Guest init {}", failure);
                 return Ok(Response::new(GuestInitResponse {
                     result: Some(guest_init_response::Result::Error(GuestInitError {
-                        reason: format!("Failed to configure network: {}", e),
+                        reason: format!("Guest init {}", failure),
                     })),
                 }));
             }
-        }
+        };
 
         // Mark as initialized
         init_state.initialized = true;
+        init_state.network = applied_network;
 
         info!("✅ Guest initialized successfully");
+        let stages = self
+            .startup_stages
+            .iter()
+            .chain(&init_stages)
+            .map(|timing| BootStageTiming {
+                name: timing.name.to_string(),
+                duration_ms: timing.duration.as_millis() as u64,
+            })
+            .collect();
         Ok(Response::new(GuestInitResponse {
-            result: Some(guest_init_response::Result::Success(GuestInitSuccess {})),
+            result: Some(guest_init_response::Result::Success(GuestInitSuccess {
+                stages,
+            })),
         }))
     }
 
@@ -82,6 +149,8 @@ impl GuestService for GuestServer {
         debug!("Received ping request");
         Ok(Response::new(PingResponse {
             version: env!("CARGO_PKG_VERSION").to_string(),
+            shares: self.shares.lock().unwrap().clone(),
+            upgrades: self.upgrades,
         }))
     }
 
@@ -90,6 +159,268 @@ impl GuestService for GuestServer {
         _request: Request<ShutdownRequest>,
     ) -> Result<Response<ShutdownResponse>, Status> {
         info!("Received shutdown request");
+        // The host kills the VM next; flush what the box wrote first
+        // SAFETY: sync has no preconditions
+        if let Err(e) = tokio::task::spawn_blocking(|| unsafe { libc::sync() }).await {
+            warn!("Failed to sync filesystems: {}", e);
+        }
         Ok(Response::new(ShutdownResponse {}))
     }
+
+    async fn screenshot(
+        &self,
+        _request: Request<ScreenshotRequest>,
+    ) -> Result<Response<ScreenshotResponse>, Status> {
+        debug!("Received screenshot request");
+        let result = match tokio::task::spawn_blocking(crate::display::capture).await {
+            Ok(Ok(frame)) => screenshot_response::Result::Image(ScreenshotImage {
+                width: frame.width,
+                height: frame.height,
+                png: frame.png,
+            }),
+            Ok(Err(e)) => {
+                error!("Failed to capture screenshot: {}", e);
+                screenshot_response::Result::Error(ScreenshotError {
+                    reason: e.to_string(),
+                })
+            }
+            Err(e) => return Err(Status::internal(format!("screenshot task failed: {}", e))),
+        };
+        Ok(Response::new(ScreenshotResponse {
+            result: Some(result),
+        }))
+    }
+
+    /// Fix up the guest after it booted from cloned or restored disks.
+    ///
+    /// Each step is attempted; one that fails is logged and skipped so the
+    /// box keeps running.
+    async fn resume(
+        &self,
+        request: Request<ResumeRequest>,
+    ) -> Result<Response<ResumeResponse>, Status> {
+        let req = request.into_inner();
+        info!(reason = %req.reason, "Received resume request");
+
+        let host_time = UNIX_EPOCH + Duration::from_nanos(req.host_time_unix_nanos.max(0) as u64);
+        let clock_step_ms = match crate::resume::step_clock(host_time) {
+            Ok(step) => {
+                info!(step_ms = step, "Stepped clock to host time");
+                step
+            }
+            Err(e) => {
+                warn!("Failed to step clock: {}", e);
+                0
+            }
+        };
+
+        if req.entropy.is_empty() {
+            warn!("Resume request carried no entropy");
+        } else if let Err(e) = crate::resume::reseed(&req.entropy) {
+            warn!("Failed to reseed entropy: {}", e);
+        }
+
+        let session_token = crate::resume::new_session_token();
+        *self.session_token.lock().unwrap() = Some(session_token.clone());
+
+        Ok(Response::new(ResumeResponse {
+            clock_step_ms,
+            session_token,
+        }))
+    }
+
+    async fn wake(&self, request: Request<WakeRequest>) -> Result<Response<WakeResponse>, Status> {
+        let req = request.into_inner();
+        info!(jump_ms = req.jump_ms, "Received wake request");
+
+        let host_time = UNIX_EPOCH + Duration::from_nanos(req.host_time_unix_nanos.max(0) as u64);
+        let clock_step_ms = match crate::resume::step_clock(host_time) {
+            Ok(step) => {
+                info!(step_ms = step, "Stepped clock to host time");
+                step
+            }
+            Err(e) => {
+                warn!("Failed to step clock: {}", e);
+                0
+            }
+        };
+
+        let network = self.init_state.lock().await.network.clone();
+        let network_refreshed = match network {
+            Some(network) => match refresh_network(&network).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Failed to refresh network: {}", e);
+                    false
+                }
+            },
+            None => false,
+        };
+
+        Ok(Response::new(WakeResponse {
+            clock_step_ms,
+            network_refreshed,
+        }))
+    }
+
+    /// Replace this agent with the binary the host staged.
+    ///
+    /// The reply goes out before the exec; the new agent takes over the
+    /// guest's state and serves on the same port.
+    async fn upgrade_agent(
+        &self,
+        request: Request<UpgradeAgentRequest>,
+    ) -> Result<Response<UpgradeAgentResponse>, Status> {
+        let req = request.into_inner();
+        info!("Received agent upgrade request");
+
+        let result = match self.prepare_upgrade(&req.signature).await {
+            Ok((binary, handoff, keep)) => {
+                tokio::spawn(async move {
+                    tokio::time::sleep(UPGRADE_EXEC_DELAY).await;
+                    info!("Replacing agent with {}", binary.display());
+                    let e = crate::upgrade::exec(&binary, &handoff, &keep);
+                    error!("Agent upgrade failed: {}", e);
+                    let _ = std::fs::remove_file(&handoff);
+                });
+                upgrade_agent_response::Result::Success(UpgradeAgentSuccess {
+                    previous_version: env!("CARGO_PKG_VERSION").to_string(),
+                })
+            }
+            Err(e) => {
+                warn!("Refused agent upgrade: {}", e);
+                upgrade_agent_response::Result::Error(UpgradeAgentError {
+                    reason: e.to_string(),
+                })
+            }
+        };
+        Ok(Response::new(UpgradeAgentResponse {
+            result: Some(result),
+        }))
+    }
+
+    type WatchMemoryStream =
+        Pin<Box<dyn futures::Stream<Item = Result<MemoryEvent, Status>> + Send + 'static>>;
+
+    /// Stream low memory events and OOM kills until the host hangs up.
+    async fn watch_memory(
+        &self,
+        request: Request<WatchMemoryRequest>,
+    ) -> Result<Response<Self::WatchMemoryStream>, Status> {
+        let req = request.into_inner();
+        debug!(low_percent = req.low_percent, "Watching memory");
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::task::spawn_blocking(move || crate::memory::run(req.low_percent, tx));
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    type WatchPressureStream =
+        Pin<Box<dyn futures::Stream<Item = Result<PressureEvent, Status>> + Send + 'static>>;
+
+    /// Stream pressure changes until the host hangs up.
+    async fn watch_pressure(
+        &self,
+        request: Request<WatchPressureRequest>,
+    ) -> Result<Response<Self::WatchPressureStream>, Status> {
+        let req = request.into_inner();
+        if !crate::pressure::available() {
+            return Err(Status::unimplemented(
+                "Guest kernel does not report pressure (PSI)",
+            ));
+        }
+        debug!(
+            threshold_percent = req.threshold_percent,
+            "Watching pressure"
+        );
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::task::spawn_blocking(move || crate::pressure::run(req.threshold_percent, tx));
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    async fn reclaim_memory(
+        &self,
+        _request: Request<ReclaimMemoryRequest>,
+    ) -> Result<Response<ReclaimMemoryResponse>, Status> {
+        let freed_bytes = tokio::task::spawn_blocking(crate::pressure::reclaim)
+            .await
+            .map_err(|e| Status::internal(format!("Reclaim task failed: {}", e)))?;
+        info!(freed_bytes, "Reclaimed guest memory");
+        Ok(Response::new(ReclaimMemoryResponse { freed_bytes }))
+    }
+
+    type WatchCrashesStream =
+        Pin<Box<dyn futures::Stream<Item = Result<CrashDump, Status>> + Send + 'static>>;
+
+    /// Collect core dumps and stream each one stored until the host hangs
+    /// up.
+    async fn watch_crashes(
+        &self,
+        request: Request<WatchCrashesRequest>,
+    ) -> Result<Response<Self::WatchCrashesStream>, Status> {
+        let req = request.into_inner();
+        if let Err(e) = crate::coredump::enable(&self.layout, req.max_bytes) {
+            return Err(Status::internal(format!(
+                "Failed to enable core dump collection: {}",
+                e
+            )));
+        }
+        debug!(max_bytes = req.max_bytes, "Watching crashes");
+        let layout = self.layout.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::task::spawn_blocking(move || crate::coredump::run(&layout, tx));
+        Ok(Response::new(Box::pin(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        )))
+    }
+
+    async fn capabilities(
+        &self,
+        _request: Request<CapabilitiesRequest>,
+    ) -> Result<Response<CapabilitiesResponse>, Status> {
+        debug!("Received capabilities request");
+        let readiness = self.readiness;
+        Ok(Response::new(CapabilitiesResponse {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features: crate::features::enabled(),
+            startup_ms: readiness.startup.as_millis() as u64,
+            ready_ms: readiness.since_boot.map_or(0, |d| d.as_millis() as u64),
+            budget_ms: agent_features::READY_BUDGET_MS,
+        }))
+    }
+}
+
+/// Bring up the interface Guest.Init was given.
+#[cfg(feature = "net")]
+async fn configure_network(network: &NetworkInit) -> BoxliteResult<()> {
+    crate::network::configure_network_from_config(
+        &network.interface,
+        network.ip.as_deref(),
+        network.gateway.as_deref(),
+    )
+    .await
+}
+
+#[cfg(not(feature = "net"))]
+async fn configure_network(_network: &NetworkInit) -> BoxliteResult<()> {
+    Err(crate::features::missing(agent_features::NET))
+}
+
+/// Re-apply the interface configuration after the guest was paused.
+#[cfg(feature = "net")]
+async fn refresh_network(network: &NetworkInit) -> BoxliteResult<()> {
+    crate::network::refresh_network(
+        &network.interface,
+        network.ip.as_deref(),
+        network.gateway.as_deref(),
+    )
+    .await
+}
+
+#[cfg(not(feature = "net"))]
+async fn refresh_network(_network: &NetworkInit) -> BoxliteResult<()> {
+    Err(crate::features::missing(agent_features::NET))
 }
@@ -0,0 +1,198 @@
+//! The transport the agent serves gRPC on.
+//!
+//! The agent listens on vsock by default. It can also listen on a Unix
+//! socket, when it runs in a container beside the host instead of in a VM,
+//! or on TCP, to reach it from a debugger in nested environments without
+//! vsock. All three accept the same [`AgentStream`], so the server runs one
+//! code path whatever the transport.
+//!
+//! The listen and notify URIs come from `--listen` and `--notify`, else
+//! from `boxlite.listen=` and `boxlite.notify=` on the kernel command line.
+//! Without either, the agent listens on the default vsock port.
+
+use boxlite_shared::constants::network;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::Transport;
+use futures::Stream;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio_vsock::{VsockAddr, VsockListener, VsockStream, VMADDR_CID_ANY};
+use tracing::info;
+
+/// Kernel command line key for the listen URI.
+pub(crate) const CMDLINE_LISTEN: &str = "boxlite.listen";
+
+/// Kernel command line key for the notify URI.
+pub(crate) const CMDLINE_NOTIFY: &str = "boxlite.notify";
+
+/// Pick the listen URI: the flag, then the kernel command line, then vsock.
+pub(crate) fn listen_uri(flag: Option<String>) -> String {
+    flag.or_else(|| kernel_param(CMDLINE_LISTEN))
+        .unwrap_or_else(|| Transport::vsock(network::GUEST_AGENT_PORT).to_uri())
+}
+
+/// Pick the notify URI: the flag, then the kernel command line.
+pub(crate) fn notify_uri(flag: Option<String>) -> Option<String> {
+    flag.or_else(|| kernel_param(CMDLINE_NOTIFY))
+}
+
+fn kernel_param(key: &str) -> Option<String> {
+    let cmdline = std::fs::read_to_string("/proc/cmdline").ok()?;
+    cmdline_value(&cmdline, key)
+}
+
+/// The value of `key=value` on a kernel command line; the last one wins,
+/// as with the kernel's own parameters.
+fn cmdline_value(cmdline: &str, key: &str) -> Option<String> {
+    cmdline
+        .split_whitespace()
+        .filter_map(|param| param.strip_prefix(key)?.strip_prefix('='))
+        .next_back()
+        .map(str::to_string)
+}
+
+/// A bound listener for one of the agent's transports.
+pub(crate) enum AgentListener {
+    Vsock(VsockListener),
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+impl AgentListener {
+    /// Bind to `transport`, replacing a stale Unix socket.
+    pub(crate) async fn bind(transport: &Transport) -> BoxliteResult<Self> {
+        match transport {
+            Transport::Vsock { port } => {
+                let listener = VsockListener::bind(VsockAddr::new(VMADDR_CID_ANY, *port))
+                    .map_err(|e| BoxliteError::Internal(format!("Failed to bind vsock: {}", e)))?;
+                info!("Listening on vsock://{}:{}", VMADDR_CID_ANY, port);
+                Ok(Self::Vsock(listener))
+            }
+            Transport::Unix { socket_path } => {
+                if socket_path.exists() {
+                    std::fs::remove_file(socket_path)?;
+                }
+                if let Some(parent) = socket_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let listener = UnixListener::bind(socket_path)?;
+                info!("Listening on unix://{}", socket_path.display());
+                Ok(Self::Unix(listener))
+            }
+            Transport::Tcp { host, port } => {
+                let listener = TcpListener::bind((host.as_str(), *port)).await?;
+                info!("Listening on tcp://{}:{}", host, port);
+                Ok(Self::Tcp(listener))
+            }
+        }
+    }
+
+    async fn accept(&self) -> io::Result<AgentStream> {
+        Ok(match self {
+            Self::Vsock(listener) => AgentStream::Vsock(listener.accept().await?.0),
+            Self::Unix(listener) => AgentStream::Unix(listener.accept().await?.0),
+            Self::Tcp(listener) => AgentStream::Tcp(listener.accept().await?.0),
+        })
+    }
+
+    /// Accepted connections, until accepting fails.
+    pub(crate) fn incoming(self) -> impl Stream<Item = io::Result<AgentStream>> {
+        futures::stream::try_unfold(self, |listener| async move {
+            let stream = listener.accept().await?;
+            Ok(Some((stream, listener)))
+        })
+    }
+}
+
+/// A connection accepted on any of the agent's transports.
+pub(crate) enum AgentStream {
+    Vsock(VsockStream),
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl AsyncRead for AgentStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Vsock(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for AgentStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Vsock(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Vsock(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Vsock(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_cmdline_value() {
+        let cmdline = "console=hvc0 boxlite.listen=tcp://0.0.0.0:2695 quiet";
+        assert_eq!(
+            cmdline_value(cmdline, CMDLINE_LISTEN).as_deref(),
+            Some("tcp://0.0.0.0:2695")
+        );
+        assert_eq!(cmdline_value(cmdline, CMDLINE_NOTIFY), None);
+        assert_eq!(
+            cmdline_value("boxlite.listen=a boxlite.listen=b", CMDLINE_LISTEN).as_deref(),
+            Some("b")
+        );
+        assert_eq!(cmdline_value("boxlite.listener=a", CMDLINE_LISTEN), None);
+    }
+
+    #[tokio::test]
+    async fn test_unix_listener_accepts() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("agent.sock");
+        let listener = AgentListener::bind(&Transport::unix(socket_path.clone()))
+            .await
+            .unwrap();
+        let mut incoming = Box::pin(listener.incoming());
+
+        let mut client = UnixStream::connect(&socket_path).await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let mut server = incoming.try_next().await.unwrap().unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+}
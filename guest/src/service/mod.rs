@@ -4,8 +4,12 @@
 //! - `guest`: Guest initialization and management (Init, Ping, Shutdown RPCs)
 //! - `container`: Container lifecycle (Init RPC)
 //! - `execution`: Command execution (Exec, Wait, Kill RPCs)
+//! - `listener`: The vsock, Unix or TCP transport the server listens on
+//! - `sessions`: Per-connection accounting and admission control
 
 mod container;
 pub(crate) mod exec;
 mod guest;
+pub(crate) mod listener;
 pub(crate) mod server;
+pub(crate) mod sessions;
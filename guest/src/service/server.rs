@@ -1,9 +1,21 @@
+use crate::boot::StageTiming;
+use crate::container::kernel::Kernels;
 use crate::container::Container;
 use crate::layout::GuestLayout;
+use crate::scheduler::Scheduler;
 use crate::service::exec::registry::ExecutionRegistry;
-use boxlite_shared::{BoxliteResult, Transport};
+use crate::service::listener::AgentListener;
+use crate::service::sessions::{AdmissionLayer, SessionIo};
+use boxlite_shared::constants::agent_features;
+use boxlite_shared::{
+    AgentHandoff, BoxliteError, BoxliteResult, NetworkInit, ShareMount, Transport,
+};
+use futures::TryStreamExt;
 use std::collections::HashMap;
+use std::os::fd::RawFd;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tonic::transport::Server;
 use tracing::{info, warn};
@@ -15,6 +27,41 @@ use tracing::{info, warn};
 pub(crate) struct GuestInitState {
     /// Whether guest has been initialized
     pub initialized: bool,
+
+    /// Network configuration applied by Guest.Init, re-applied by Guest.Wake
+    pub network: Option<NetworkInit>,
+}
+
+/// How long the agent took to start serving.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Readiness {
+    /// From the agent's process starting
+    pub startup: Duration,
+    /// From the guest kernel booting, if the boot clock could be read
+    pub since_boot: Option<Duration>,
+}
+
+impl Readiness {
+    /// Readiness now, for an agent that started at `started`.
+    fn now(started: Instant) -> Self {
+        Self {
+            startup: started.elapsed(),
+            since_boot: boot_time(),
+        }
+    }
+}
+
+/// Time since the kernel booted, including any time suspended.
+fn boot_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid timespec for the kernel to fill in
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) } != 0 {
+        return None;
+    }
+    Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
 }
 
 /// Guest agent server.
@@ -27,6 +74,9 @@ pub(crate) struct GuestServer {
     /// Guest filesystem layout
     pub layout: GuestLayout,
 
+    /// Timings of the startup stages run before the server started
+    pub startup_stages: Vec<StageTiming>,
+
     /// Guest initialization state (set by Guest.Init)
     pub init_state: Arc<Mutex<GuestInitState>>,
 
@@ -35,6 +85,29 @@ pub(crate) struct GuestServer {
 
     /// Execution registry for tracking running executions
     pub registry: ExecutionRegistry,
+
+    /// Periodic tasks registered through Container.RegisterTask
+    pub scheduler: Arc<Scheduler>,
+
+    /// Interpreter sessions for Container.ExecuteCell
+    pub kernels: Kernels,
+
+    /// Identifies this run of the guest; rotated by Guest.Resume so clones
+    /// of one box never share it. Generated on first use: early in boot the
+    /// kernel's entropy pool may not be ready, and startup would block on it
+    pub session_token: std::sync::Mutex<Option<String>>,
+
+    /// How Guest.Init mounted each virtiofs share, reported by Guest.Ping
+    pub shares: Arc<std::sync::Mutex<Vec<ShareMount>>>,
+
+    /// Times the agent was replaced through Guest.UpgradeAgent
+    pub upgrades: u32,
+
+    /// When the agent's process started
+    pub started: Instant,
+
+    /// How long the agent took to start serving, set once it listens
+    pub readiness: Readiness,
 }
 
 impl GuestServer {
@@ -42,23 +115,146 @@ impl GuestServer {
     ///
     /// Server starts uninitialized. Guest.Init must be called first to setup
     /// the environment, then Container.Init to start the container.
-    pub fn new(layout: GuestLayout) -> Self {
+    /// `started` is when the agent's process started, for the readiness
+    /// Guest.Capabilities reports.
+    pub fn new(layout: GuestLayout, startup_stages: Vec<StageTiming>, started: Instant) -> Self {
         Self {
             layout,
+            startup_stages,
             init_state: Arc::new(Mutex::new(GuestInitState::default())),
             containers: Arc::new(Mutex::new(HashMap::new())),
             registry: ExecutionRegistry::new(),
+            scheduler: Arc::new(Scheduler::new()),
+            kernels: Kernels::default(),
+            session_token: std::sync::Mutex::new(None),
+            shares: Arc::default(),
+            upgrades: 0,
+            started,
+            readiness: Readiness::default(),
         }
     }
 
+    /// Take over the state handed over by the agent this one replaced.
+    ///
+    /// A container whose stdio cannot be adopted is left out, along with
+    /// its tasks.
+    pub async fn restore(&mut self, handoff: AgentHandoff) {
+        {
+            let mut init_state = self.init_state.lock().await;
+            init_state.initialized = handoff.initialized;
+            init_state.network = handoff.network;
+        }
+        *self.shares.lock().unwrap() = handoff.shares;
+        *self.session_token.lock().unwrap() = Some(handoff.session_token);
+        self.upgrades = handoff.upgrades;
+
+        let mut containers = self.containers.lock().await;
+        for container in handoff.containers {
+            let id = container.id.clone();
+            // SAFETY: the replaced agent kept these descriptors open across
+            // the exec, and nothing here has taken them
+            match unsafe { Container::adopt(container) } {
+                Ok(container) => {
+                    containers.insert(id, Arc::new(Mutex::new(container)));
+                }
+                Err(e) => warn!(container_id = %id, "Failed to adopt container: {}", e),
+            }
+        }
+        for task in handoff.tasks {
+            let (Some(container), Some(definition)) =
+                (containers.get(&task.container_id).cloned(), task.task)
+            else {
+                continue;
+            };
+            if let Err(e) = self
+                .scheduler
+                .register(&task.container_id, container, definition)
+                .await
+            {
+                warn!("Failed to register handed over task: {}", e);
+            }
+        }
+        info!(
+            containers = containers.len(),
+            upgrades = self.upgrades,
+            "Took over from the replaced agent"
+        );
+    }
+
+    /// Check this agent can be replaced, install the staged binary and
+    /// write the handoff for it.
+    ///
+    /// Returns the binary, the handoff file and the descriptors to keep
+    /// open across the exec. Executions and interpreter sessions hold pipes
+    /// the handoff cannot carry, so none may be running.
+    pub async fn prepare_upgrade(
+        &self,
+        signature: &[u8],
+    ) -> BoxliteResult<(PathBuf, PathBuf, Vec<RawFd>)> {
+        let running = self.registry.running().await;
+        if !running.is_empty() {
+            return Err(BoxliteError::InvalidState(format!(
+                "Executions still running: {}",
+                running.join(", ")
+            )));
+        }
+        if !self.kernels.is_empty().await {
+            return Err(BoxliteError::InvalidState(
+                "Interpreter sessions still running".to_string(),
+            ));
+        }
+
+        let binary = crate::upgrade::install(&self.layout, signature)?;
+        let (handoff, keep) = self.handoff().await;
+        let handoff = crate::upgrade::write_handoff(&self.layout, &handoff)?;
+        Ok((binary, handoff, keep))
+    }
+
+    /// State for the agent replacing this one, and the descriptors it
+    /// refers to.
+    async fn handoff(&self) -> (AgentHandoff, Vec<RawFd>) {
+        let init_state = self.init_state.lock().await;
+        let mut containers = Vec::new();
+        for container in self.containers.lock().await.values() {
+            containers.push(container.lock().await.handoff());
+        }
+        let keep = containers
+            .iter()
+            .flat_map(|c| [c.stdin_fd, c.stdout_fd, c.stderr_fd])
+            .collect();
+        let handoff = AgentHandoff {
+            initialized: init_state.initialized,
+            network: init_state.network.clone(),
+            shares: self.shares.lock().unwrap().clone(),
+            session_token: self
+                .session_token
+                .lock()
+                .unwrap()
+                .get_or_insert_with(crate::resume::new_session_token)
+                .clone(),
+            containers,
+            tasks: self.scheduler.handoff().await,
+            upgrades: self.upgrades + 1,
+        };
+        (handoff, keep)
+    }
+
     /// Run the tonic server listening on the specified transport.
     ///
-    /// Binds to the specified transport (Unix, TCP, or Vsock) and serves
-    /// all three gRPC services on a single port.
+    /// Binds to the specified transport (Unix, TCP, or Vsock; see
+    /// [`listener`](super::listener)) and serves all three gRPC services on
+    /// a single port.
     ///
     /// If `notify_uri` is provided, connects to that URI after the server
     /// is ready to serve, signaling readiness to the host.
-    pub async fn run(self, listen_uri: String, notify_uri: Option<String>) -> BoxliteResult<()> {
+    ///
+    /// Each connection may have at most `max_concurrency` RPCs in flight.
+    pub async fn run(
+        mut self,
+        listen_uri: String,
+        notify_uri: Option<String>,
+        max_concurrency: usize,
+    ) -> BoxliteResult<()> {
         info!("Starting tonic gRPC server");
 
         // Parse the listen URI to determine transport type
@@ -71,110 +267,45 @@ impl GuestServer {
 
         info!("Parsed transport from URI: {:?}", transport);
 
+        let incoming = AgentListener::bind(&transport)
+            .await?
+            .incoming()
+            .map_ok(SessionIo::new);
+
+        // Ready once listening: connections queue until the server takes them
+        self.readiness = Readiness::now(self.started);
+        let startup_ms = self.readiness.startup.as_millis() as u64;
+        if startup_ms > agent_features::READY_BUDGET_MS {
+            warn!(
+                startup_ms,
+                budget_ms = agent_features::READY_BUDGET_MS,
+                "Agent started slower than its budget"
+            );
+        } else {
+            info!(startup_ms, "Agent ready");
+        }
+
         // Wrap self in Arc for sharing across services
         let server = Arc::new(self);
 
         let server_builder = Server::builder()
+            .layer(AdmissionLayer::new(max_concurrency))
             .add_service(boxlite_shared::ContainerServer::from_arc(server.clone()))
             .add_service(boxlite_shared::GuestServer::from_arc(server.clone()))
             .add_service(boxlite_shared::ExecutionServer::from_arc(server.clone()));
 
-        match transport {
-            Transport::Vsock { port } => {
-                use tokio_vsock::{VsockAddr, VsockListener, VMADDR_CID_ANY};
-
-                info!("Binding to vsock port {}", port);
-                let addr = VsockAddr::new(VMADDR_CID_ANY, port);
-                let listener = VsockListener::bind(addr).map_err(|e| {
-                    boxlite_shared::errors::BoxliteError::Internal(format!(
-                        "Failed to bind vsock: {}",
-                        e
-                    ))
-                })?;
-                info!("Listening on vsock://{}:{}", VMADDR_CID_ANY, port);
-
-                let incoming = listener.incoming();
-
-                tokio::spawn(async move {
-                    if let Err(e) = notify_host_ready(notify_uri).await {
-                        warn!("Failed to notify host: {}", e);
-                    }
-                });
-
-                server_builder
-                    .serve_with_incoming(incoming)
-                    .await
-                    .map_err(|e| {
-                        boxlite_shared::errors::BoxliteError::Internal(format!(
-                            "Server error: {}",
-                            e
-                        ))
-                    })?;
-            }
-
-            Transport::Unix { socket_path } => {
-                use tokio_stream::wrappers::UnixListenerStream;
-
-                // Remove socket if it exists
-                if socket_path.exists() {
-                    std::fs::remove_file(&socket_path)?;
-                }
-
-                // Ensure parent directory exists
-                if let Some(parent) = socket_path.parent() {
-                    std::fs::create_dir_all(parent)?;
-                }
-
-                info!("Binding to Unix socket: {}", socket_path.display());
-                let listener = tokio::net::UnixListener::bind(&socket_path)?;
-                info!("Listening on unix://{}", socket_path.display());
-
-                let incoming = UnixListenerStream::new(listener);
-
-                tokio::spawn(async move {
-                    if let Err(e) = notify_host_ready(notify_uri).await {
-                        warn!("Failed to notify host: {}", e);
-                    }
-                });
-
-                server_builder
-                    .serve_with_incoming(incoming)
-                    .await
-                    .map_err(|e| {
-                        boxlite_shared::errors::BoxliteError::Internal(format!(
-                            "Server error: {}",
-                            e
-                        ))
-                    })?;
+        tokio::spawn(async move {
+            if let Err(e) = notify_host_ready(notify_uri).await {
+                warn!("Failed to notify host: {}", e);
             }
+        });
 
-            Transport::Tcp { port } => {
-                use tokio_stream::wrappers::TcpListenerStream;
-
-                let addr = format!("127.0.0.1:{}", port);
-                info!("Binding to TCP address: {}", addr);
-                let listener = tokio::net::TcpListener::bind(&addr).await?;
-                info!("Listening on tcp://{}", addr);
-
-                let incoming = TcpListenerStream::new(listener);
-
-                tokio::spawn(async move {
-                    if let Err(e) = notify_host_ready(notify_uri).await {
-                        warn!("Failed to notify host: {}", e);
-                    }
-                });
-
-                server_builder
-                    .serve_with_incoming(incoming)
-                    .await
-                    .map_err(|e| {
-                        boxlite_shared::errors::BoxliteError::Internal(format!(
-                            "Server error: {}",
-                            e
-                        ))
-                    })?;
-            }
-        }
+        server_builder
+            .serve_with_incoming(incoming)
+            .await
+            .map_err(|e| {
+                boxlite_shared::errors::BoxliteError::Internal(format!("Server error: {}", e))
+            })?;
 
         Ok(())
     }
@@ -226,9 +357,9 @@ async fn notify_host_ready(notify_uri: Option<String>) -> BoxliteResult<()> {
                 })?;
             info!("Host notified successfully");
         }
-        Transport::Tcp { port } => {
-            info!("Notifying host via tcp:127.0.0.1:{}", port);
-            let _stream = tokio::net::TcpStream::connect(format!("127.0.0.1:{}", port))
+        Transport::Tcp { host, port } => {
+            info!("Notifying host via tcp:{}:{}", host, port);
+            let _stream = tokio::net::TcpStream::connect((host.as_str(), port))
                 .await
                 .map_err(|e| {
                     boxlite_shared::errors::BoxliteError::Internal(format!(
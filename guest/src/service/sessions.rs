@@ -0,0 +1,364 @@
+//! Client sessions and admission control.
+//!
+//! Every connection to the agent is a session. [`SessionIo`] wraps the
+//! connection to count its traffic and hands the session to each request
+//! through its connect info, which tonic stores in the request extensions.
+//! [`AdmissionLayer`] then admits each RPC against the session's limit on
+//! calls in flight, rejecting the excess with RESOURCE_EXHAUSTED instead of
+//! queueing it, so a client that floods the agent gets errors back rather
+//! than wedging it for everyone.
+//!
+//! An admitted RPC carries a cancellation token that fires when the call
+//! ends, which includes its response being dropped because the client
+//! reset the stream or went away. Tasks a handler spawns watch it (see
+//! [`cancellation`]) so they do not outlive their client.
+
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::sync::CancellationToken;
+use tonic::body::BoxBody;
+use tonic::transport::server::Connected;
+use tonic::Status;
+use tracing::{debug, info, warn};
+
+/// RPCs one session may have in flight when no limit is given.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 256;
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// One client connection and what it has used.
+#[derive(Debug)]
+pub(crate) struct Session {
+    id: u64,
+    started_at: Instant,
+    in_flight: AtomicUsize,
+    rpcs: AtomicU64,
+    rejected: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    /// Fires when the connection closes; parent of every RPC's token
+    closed: CancellationToken,
+}
+
+impl Session {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            started_at: Instant::now(),
+            in_flight: AtomicUsize::new(0),
+            rpcs: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            closed: CancellationToken::new(),
+        })
+    }
+
+    /// Admit an RPC if fewer than `max_concurrency` are in flight.
+    fn admit(self: &Arc<Self>, max_concurrency: usize) -> Option<RpcGuard> {
+        let admitted = self
+            .in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max_concurrency).then_some(n + 1)
+            })
+            .is_ok();
+        if !admitted {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        self.rpcs.fetch_add(1, Ordering::Relaxed);
+        Some(RpcGuard {
+            session: Arc::clone(self),
+            cancel: self.closed.child_token(),
+        })
+    }
+
+    fn close(&self) {
+        self.closed.cancel();
+        info!(
+            session = self.id,
+            duration_ms = self.started_at.elapsed().as_millis() as u64,
+            rpcs = self.rpcs.load(Ordering::Relaxed),
+            rejected = self.rejected.load(Ordering::Relaxed),
+            bytes_in = self.bytes_in.load(Ordering::Relaxed),
+            bytes_out = self.bytes_out.load(Ordering::Relaxed),
+            "Session closed"
+        );
+    }
+}
+
+/// A connection counted as a session.
+pub(crate) struct SessionIo<T> {
+    inner: T,
+    session: Arc<Session>,
+}
+
+impl<T> SessionIo<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        let session = Session::new();
+        debug!(session = session.id, "Session opened");
+        Self { inner, session }
+    }
+}
+
+impl<T> Drop for SessionIo<T> {
+    fn drop(&mut self) {
+        self.session.close();
+    }
+}
+
+impl<T> Connected for SessionIo<T> {
+    type ConnectInfo = Arc<Session>;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        Arc::clone(&self.session)
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for SessionIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        self.session.bytes_in.fetch_add(read, Ordering::Relaxed);
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for SessionIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.count_written(&result);
+        result
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.count_written(&result);
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+impl<T> SessionIo<T> {
+    fn count_written(&self, result: &Poll<io::Result<usize>>) {
+        if let Poll::Ready(Ok(n)) = result {
+            self.session
+                .bytes_out
+                .fetch_add(*n as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+/// An admitted RPC; releases its slot and cancels its tasks when dropped.
+struct RpcGuard {
+    session: Arc<Session>,
+    cancel: CancellationToken,
+}
+
+impl Drop for RpcGuard {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        self.session.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Cancellation of an RPC, stored in its request extensions.
+#[derive(Clone)]
+struct RpcCancellation(CancellationToken);
+
+/// Token that fires when the RPC behind `request` ends or its client goes
+/// away. Requests that did not come through [`AdmissionLayer`] get one
+/// that never fires.
+pub(crate) fn cancellation<T>(request: &tonic::Request<T>) -> CancellationToken {
+    request
+        .extensions()
+        .get::<RpcCancellation>()
+        .map(|c| c.0.clone())
+        .unwrap_or_default()
+}
+
+/// Applies the per-session limit on RPCs in flight.
+#[derive(Clone, Copy)]
+pub(crate) struct AdmissionLayer {
+    max_concurrency: usize,
+}
+
+impl AdmissionLayer {
+    pub(crate) fn new(max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency: max_concurrency.max(1),
+        }
+    }
+}
+
+impl<S> tower_layer::Layer<S> for AdmissionLayer {
+    type Service = Admission<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Admission {
+            inner,
+            max_concurrency: self.max_concurrency,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Admission<S> {
+    inner: S,
+    max_concurrency: usize,
+}
+
+impl<S> tower_service::Service<http::Request<BoxBody>> for Admission<S>
+where
+    S: tower_service::Service<http::Request<BoxBody>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        let guard = match request.extensions().get::<Arc<Session>>() {
+            Some(session) => match session.admit(self.max_concurrency) {
+                Some(guard) => Some(guard),
+                None => {
+                    warn!(
+                        session = session.id,
+                        path = request.uri().path(),
+                        "Rejecting RPC over the concurrency limit"
+                    );
+                    let status = Status::resource_exhausted(format!(
+                        "more than {} RPCs in flight on this connection",
+                        self.max_concurrency
+                    ));
+                    return Box::pin(std::future::ready(Ok(status.into_http())));
+                }
+            },
+            None => None,
+        };
+        if let Some(guard) = &guard {
+            request
+                .extensions_mut()
+                .insert(RpcCancellation(guard.cancel.clone()));
+        }
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            // Streaming responses stay admitted until the body is done
+            Ok(response.map(|body| {
+                tonic::body::boxed(GuardedBody {
+                    body,
+                    _guard: guard,
+                })
+            }))
+        })
+    }
+}
+
+/// A response body that holds its RPC's admission.
+struct GuardedBody {
+    body: BoxBody,
+    _guard: Option<RpcGuard>,
+}
+
+impl Body for GuardedBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_admit_up_to_limit() {
+        let session = Session::new();
+        let first = session.admit(2).unwrap();
+        let second = session.admit(2).unwrap();
+        assert!(session.admit(2).is_none());
+        assert_eq!(session.rejected.load(Ordering::Relaxed), 1);
+
+        let cancel = first.cancel.clone();
+        drop(first);
+        assert!(cancel.is_cancelled());
+        assert!(!second.cancel.is_cancelled());
+        assert!(session.admit(2).is_some());
+        assert_eq!(session.rpcs.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_closing_session_cancels_rpcs() {
+        let (client, _server) = tokio::io::duplex(64);
+        let io = SessionIo::new(client);
+        let guard = io.session.admit(1).unwrap();
+        drop(io);
+        assert!(guard.cancel.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_counts_traffic() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut io = SessionIo::new(client);
+        io.write_all(b"hello").await.unwrap();
+        server.write_all(b"hi").await.unwrap();
+        let mut buf = [0; 2];
+        io.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(io.session.bytes_out.load(Ordering::Relaxed), 5);
+        assert_eq!(io.session.bytes_in.load(Ordering::Relaxed), 2);
+    }
+}
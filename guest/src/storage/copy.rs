@@ -224,7 +224,7 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
                 copy_dir_recursive(&src_path, &dst_path)?;
             } else if file_type.is_file() {
                 // Copy file (this is I/O bound, benefits from parallelization)
-                std::fs::copy(&src_path, &dst_path).map_err(|e| {
+                crate::uring::copy_file(&src_path, &dst_path).map_err(|e| {
                     tracing::error!(
                         "Failed to copy file {} -> {}: {}",
                         src_path.display(),
@@ -0,0 +1,181 @@
+//! Idmapped mounts for volumes with a box-side owner.
+//!
+//! Virtio-fs reports host ids, so files a host user shares appear owned by
+//! their host uid. An idmapped mount shows the share's owner as another
+//! user without changing anything on disk, and maps files created through
+//! it back to the host owner. The mapping lives in a user namespace made
+//! only to be attached to the mount.
+//!
+//! Needs Linux 5.12+ and, for virtio-fs, a kernel and server that allow
+//! idmapped FUSE mounts.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::VolumeIdMapping;
+use nix::libc;
+use nix::mount::{umount2, MntFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::sys::wait::waitpid;
+use nix::unistd::{fork, pipe, ForkResult};
+
+const OPEN_TREE_CLONE: libc::c_uint = 1;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x4;
+const MOUNT_ATTR_IDMAP: u64 = 0x0010_0000;
+
+/// `struct mount_attr` from linux/mount.h.
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+/// Replace the mount at `path` with an idmapped copy of it.
+pub fn remount_idmapped(path: &Path, mapping: &VolumeIdMapping) -> BoxliteResult<()> {
+    let fail = |what: &str, e: io::Error| {
+        BoxliteError::Storage(format!(
+            "Failed to idmap {} ({}): {}",
+            path.display(),
+            what,
+            e
+        ))
+    };
+    let c_path = std::ffi::CString::new(path.as_os_str().as_encoded_bytes())
+        .map_err(|e| BoxliteError::Storage(format!("Invalid mount path: {}", e)))?;
+    let userns = user_namespace(mapping).map_err(|e| fail("user namespace", e))?;
+
+    // SAFETY: c_path is NUL-terminated; the returned fd is owned below
+    let tree = unsafe {
+        libc::syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint,
+        )
+    };
+    if tree < 0 {
+        return Err(fail("open_tree", io::Error::last_os_error()));
+    }
+    // SAFETY: open_tree returned a new fd
+    let tree = unsafe { OwnedFd::from_raw_fd(tree as i32) };
+
+    let attr = MountAttr {
+        attr_set: MOUNT_ATTR_IDMAP,
+        attr_clr: 0,
+        propagation: 0,
+        userns_fd: userns.as_raw_fd() as u64,
+    };
+    // SAFETY: attr outlives the call and its size is passed along
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            tree.as_raw_fd(),
+            c"".as_ptr(),
+            libc::AT_EMPTY_PATH,
+            &attr as *const MountAttr,
+            std::mem::size_of::<MountAttr>(),
+        )
+    };
+    if ret < 0 {
+        return Err(fail("mount_setattr", io::Error::last_os_error()));
+    }
+
+    umount2(path, MntFlags::MNT_DETACH).map_err(|e| fail("umount", e.into()))?;
+    // SAFETY: both paths are NUL-terminated
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            tree.as_raw_fd(),
+            c"".as_ptr(),
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    };
+    if ret < 0 {
+        return Err(fail("move_mount", io::Error::last_os_error()));
+    }
+
+    tracing::info!(
+        "Idmapped {}: {}:{} → {}:{}",
+        path.display(),
+        mapping.host_uid,
+        mapping.host_gid,
+        mapping.uid,
+        mapping.gid
+    );
+    Ok(())
+}
+
+/// `/proc/<pid>/{uid,gid}_map` line showing on-disk `host` as `guest`.
+fn map_line(host: u32, guest: u32) -> String {
+    format!("{} {} 1\n", host, guest)
+}
+
+/// A user namespace mapping the host owner to the box ids.
+///
+/// A forked child unshares the namespace and waits while it is set up and
+/// opened; the namespace outlives the child through the returned fd.
+fn user_namespace(mapping: &VolumeIdMapping) -> io::Result<OwnedFd> {
+    let (ready_rx, ready_tx) = pipe()?;
+    let (done_rx, done_tx) = pipe()?;
+
+    // SAFETY: the child only makes async-signal-safe calls before exiting
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            drop(ready_rx);
+            drop(done_tx);
+            let status = match unshare(CloneFlags::CLONE_NEWUSER) {
+                Ok(()) => 0,
+                Err(_) => 1,
+            };
+            let mut ready = File::from(ready_tx);
+            let _ = ready.write_all(&[status]);
+            let _ = File::from(done_rx).read(&mut [0u8]);
+            // SAFETY: exit without running the parent's atexit handlers
+            unsafe { libc::_exit(0) }
+        }
+        ForkResult::Parent { child } => {
+            drop(ready_tx);
+            drop(done_rx);
+            let mut status = [1u8];
+            let result = File::from(ready_rx)
+                .read_exact(&mut status)
+                .and_then(|_| match status[0] {
+                    0 => Ok(()),
+                    _ => Err(io::Error::other("child failed to unshare")),
+                })
+                .and_then(|_| {
+                    let proc = format!("/proc/{}", child);
+                    std::fs::write(
+                        format!("{}/uid_map", proc),
+                        map_line(mapping.host_uid, mapping.uid),
+                    )?;
+                    std::fs::write(
+                        format!("{}/gid_map", proc),
+                        map_line(mapping.host_gid, mapping.gid),
+                    )?;
+                    File::open(format!("{}/ns/user", proc)).map(OwnedFd::from)
+                });
+            drop(done_tx);
+            let _ = waitpid(child, None);
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_line_maps_disk_owner_to_box_id() {
+        // Inside id is what the filesystem reports, outside what is shown
+        assert_eq!(map_line(501, 0), "501 0 1\n");
+    }
+}
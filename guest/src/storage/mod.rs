@@ -3,12 +3,15 @@
 //! Provides unified abstraction for mounting different volume types:
 //! - Virtiofs: Host-shared directories via virtio-fs
 //! - Block devices: Disk images attached via virtio-blk
+//! - Network: Remote NFSv4 and CIFS filesystems
 
 pub mod block_device;
 #[allow(dead_code)]
 mod copy;
+mod idmap;
+mod network;
 mod perms;
 mod virtiofs;
 mod volume;
 
-pub use volume::mount_volumes;
+pub use volume::{is_network_volume, mount_volumes};
@@ -0,0 +1,189 @@
+//! Remote filesystem mount helper (NFSv4, CIFS).
+//!
+//! The kernel clients are mounted with mount(2) directly, without the
+//! mount.nfs or mount.cifs helpers, so the server name is resolved here and
+//! passed as `addr=` (NFS) or `ip=` (CIFS).
+
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::Path;
+
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::{NetworkFilesystem, NetworkSource};
+use nix::mount::{mount, MsFlags};
+
+const NFS_PORT: u16 = 2049;
+const CIFS_PORT: u16 = 445;
+
+pub struct NetworkMount;
+
+impl NetworkMount {
+    /// Mount the remote filesystem described by `source` at `mount_point`.
+    pub fn mount(source: &NetworkSource, mount_point: &Path) -> BoxliteResult<()> {
+        let filesystem = NetworkFilesystem::try_from(source.filesystem)
+            .unwrap_or(NetworkFilesystem::Unspecified);
+        let invalid = || {
+            BoxliteError::InvalidArgument(format!(
+                "Invalid {:?} source '{}'",
+                filesystem, source.source
+            ))
+        };
+        let (fstype, host, port) = match filesystem {
+            NetworkFilesystem::Nfs => {
+                let (host, _) = split_nfs_source(&source.source).ok_or_else(invalid)?;
+                ("nfs4", host, NFS_PORT)
+            }
+            NetworkFilesystem::Cifs => {
+                let (host, _) = split_cifs_source(&source.source).ok_or_else(invalid)?;
+                ("cifs", host, CIFS_PORT)
+            }
+            NetworkFilesystem::Unspecified => return Err(invalid()),
+        };
+
+        tracing::info!(
+            "Mounting {}: {} → {} ({})",
+            fstype,
+            source.source,
+            mount_point.display(),
+            if source.read_only { "ro" } else { "rw" }
+        );
+
+        let addr = resolve(host, port)?;
+        let data = mount_data(filesystem, source, addr);
+
+        std::fs::create_dir_all(mount_point).map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to create mount point {}: {}",
+                mount_point.display(),
+                e
+            ))
+        })?;
+
+        let mut flags = MsFlags::empty();
+        if source.read_only {
+            flags |= MsFlags::MS_RDONLY;
+        }
+        mount(
+            Some(source.source.as_str()),
+            mount_point,
+            Some(fstype),
+            flags,
+            Some(data.as_str()),
+        )
+        .map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to mount {} {} to {}: {}",
+                fstype,
+                source.source,
+                mount_point.display(),
+                e
+            ))
+        })?;
+
+        tracing::info!(
+            "Mounted {}: {} → {}",
+            fstype,
+            source.source,
+            mount_point.display()
+        );
+        Ok(())
+    }
+}
+
+/// `server:/export` (or `[v6addr]:/export`) as (server, export).
+fn split_nfs_source(source: &str) -> Option<(&str, &str)> {
+    let (host, path) = match source.strip_prefix('[') {
+        Some(rest) => rest.split_once("]:")?,
+        None => source.split_once(':')?,
+    };
+    (!host.is_empty() && path.starts_with('/')).then_some((host, path))
+}
+
+/// `//server/share[/path]` as (server, share and path).
+fn split_cifs_source(source: &str) -> Option<(&str, &str)> {
+    let (host, share) = source.strip_prefix("//")?.split_once('/')?;
+    (!host.is_empty() && !share.is_empty()).then_some((host, share))
+}
+
+fn resolve(host: &str, port: u16) -> BoxliteResult<IpAddr> {
+    (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .map(|addr| addr.ip())
+        .ok_or_else(|| BoxliteError::Network(format!("Failed to resolve {}", host)))
+}
+
+/// Mount options for the kernel client. Passwords go in here, so this is
+/// never logged.
+fn mount_data(filesystem: NetworkFilesystem, source: &NetworkSource, addr: IpAddr) -> String {
+    let mut data = Vec::new();
+    match filesystem {
+        NetworkFilesystem::Cifs => {
+            data.push(format!("ip={}", addr));
+            if source.username.is_empty() {
+                data.push("guest".to_string());
+            } else {
+                data.push(format!("username={}", source.username));
+                data.push(format!("password={}", source.password));
+                if !source.domain.is_empty() {
+                    data.push(format!("domain={}", source.domain));
+                }
+            }
+        }
+        _ => data.push(format!("addr={}", addr)),
+    }
+    if !source.options.is_empty() {
+        data.push(source.options.clone());
+    }
+    data.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_and_mount_data() {
+        assert_eq!(
+            split_nfs_source("nas.local:/exports/data"),
+            Some(("nas.local", "/exports/data"))
+        );
+        assert_eq!(
+            split_nfs_source("[fd00::2]:/srv"),
+            Some(("fd00::2", "/srv"))
+        );
+        assert_eq!(split_nfs_source("nas.local:exports"), None);
+        assert_eq!(
+            split_cifs_source("//nas/team/shared"),
+            Some(("nas", "team/shared"))
+        );
+        assert_eq!(split_cifs_source("//nas"), None);
+
+        let addr: IpAddr = "10.0.0.5".parse().unwrap();
+        let mut cifs = NetworkSource {
+            filesystem: NetworkFilesystem::Cifs as i32,
+            source: "//nas/team".to_string(),
+            options: "vers=3.0".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            mount_data(NetworkFilesystem::Cifs, &cifs, addr),
+            "ip=10.0.0.5,guest,vers=3.0"
+        );
+        cifs.username = "ci".to_string();
+        cifs.password = "s3cret".to_string();
+        assert_eq!(
+            mount_data(NetworkFilesystem::Cifs, &cifs, addr),
+            "ip=10.0.0.5,username=ci,password=s3cret,vers=3.0"
+        );
+        let nfs = NetworkSource {
+            filesystem: NetworkFilesystem::Nfs as i32,
+            source: "nas:/data".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            mount_data(NetworkFilesystem::Nfs, &nfs, addr),
+            "addr=10.0.0.5"
+        );
+    }
+}
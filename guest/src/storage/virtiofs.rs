@@ -1,20 +1,50 @@
 //! Virtiofs mount helper.
 
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use boxlite_shared::constants::boxless;
 use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::{ShareMount, ShareStrategy};
 use nix::mount::{mount, MsFlags};
 
 pub struct VirtiofsMount;
 
+/// Mount attempts per strategy before falling back to the next one.
+const ATTEMPTS: u32 = 3;
+
+/// Wait before the second attempt, doubled before each later one.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
 impl VirtiofsMount {
-    /// Mount virtiofs tag to mount point.
-    pub fn mount(tag: &str, mount_point: &Path, read_only: bool) -> BoxliteResult<()> {
+    /// Mount virtiofs tag to mount point, returning how it was mounted.
+    ///
+    /// With `dax`, file contents are mapped from the device's DAX window.
+    /// If the guest kernel can't use DAX the share is mounted without it.
+    /// A failing mount is retried with backoff, then tried as 9p over
+    /// virtio; if that fails too, an `optional` share is replaced by an
+    /// empty tmpfs so the box still boots, degraded.
+    /// Under the boxless engine there is no virtiofs device; the share's
+    /// host directory is bind-mounted instead.
+    pub fn mount(
+        tag: &str,
+        mount_point: &Path,
+        read_only: bool,
+        dax: bool,
+        optional: bool,
+    ) -> BoxliteResult<ShareMount> {
+        if let Some(source) = boxless_source(tag)? {
+            bind_mount(tag, &source, mount_point, read_only)?;
+            return Ok(share_mount(tag, ShareStrategy::ShareBind, 1, String::new()));
+        }
+
         tracing::info!(
-            "Mounting virtiofs: {} → {} ({})",
+            "Mounting virtiofs: {} → {} ({}{})",
             tag,
             mount_point.display(),
-            if read_only { "ro" } else { "rw" }
+            if read_only { "ro" } else { "rw" },
+            if dax { ", dax" } else { "" }
         );
 
         // Create mount point
@@ -31,28 +61,190 @@ impl VirtiofsMount {
             flags |= MsFlags::MS_RDONLY;
         }
 
+        let mount_with = |fstype: &str, data: Option<&str>| {
+            mount(Some(tag), mount_point, Some(fstype), flags, data)
+        };
+        let mut attempts = 0;
+        let virtiofs = with_retries(&mut attempts, || {
+            if !dax {
+                return mount_with("virtiofs", None).map(|()| ShareStrategy::ShareVirtiofs);
+            }
+            match mount_with("virtiofs", Some("dax=always")) {
+                Ok(()) => Ok(ShareStrategy::ShareVirtiofs),
+                Err(e) => {
+                    tracing::warn!("DAX mount of {} failed ({}), mounting without DAX", tag, e);
+                    mount_with("virtiofs", None).map(|()| ShareStrategy::ShareVirtiofsNoDax)
+                }
+            }
+        });
+        let virtiofs_error = match virtiofs {
+            Ok(strategy) => {
+                tracing::info!(
+                    "Mounted virtiofs: {} → {} ({})",
+                    tag,
+                    mount_point.display(),
+                    if read_only { "ro" } else { "rw" }
+                );
+                return Ok(share_mount(tag, strategy, attempts, String::new()));
+            }
+            Err(e) => e.to_string(),
+        };
+
+        tracing::warn!(
+            "Mounting virtiofs {} failed after {} attempts ({}), trying 9p",
+            tag,
+            attempts,
+            virtiofs_error
+        );
+        attempts += 1;
+        let p9_error = match mount_with("9p", Some("trans=virtio,version=9p2000.L")) {
+            Ok(()) => {
+                tracing::info!("Mounted {} → {} over 9p", tag, mount_point.display());
+                return Ok(share_mount(
+                    tag,
+                    ShareStrategy::Share9p,
+                    attempts,
+                    virtiofs_error,
+                ));
+            }
+            Err(e) => e,
+        };
+
+        if !optional {
+            return Err(BoxliteError::Storage(format!(
+                "Failed to mount virtiofs {} to {} after {} attempts: {} (9p: {})",
+                tag,
+                mount_point.display(),
+                attempts,
+                virtiofs_error,
+                p9_error
+            )));
+        }
+        tracing::warn!(
+            "Share {} is unavailable (virtiofs: {}, 9p: {}); booting without it",
+            tag,
+            virtiofs_error,
+            p9_error
+        );
         mount(
-            Some(tag),
+            Some("tmpfs"),
             mount_point,
-            Some("virtiofs"),
+            Some("tmpfs"),
             flags,
             None::<&str>,
         )
         .map_err(|e| {
             BoxliteError::Storage(format!(
-                "Failed to mount virtiofs {} to {}: {}",
+                "Failed to mount tmpfs in place of share {} at {}: {}",
                 tag,
                 mount_point.display(),
                 e
             ))
         })?;
-
-        tracing::info!(
-            "Mounted virtiofs: {} → {} ({})",
+        Ok(share_mount(
             tag,
-            mount_point.display(),
-            if read_only { "ro" } else { "rw" }
-        );
-        Ok(())
+            ShareStrategy::ShareDegraded,
+            attempts,
+            virtiofs_error,
+        ))
+    }
+}
+
+/// Run `op` up to [`ATTEMPTS`] times, backing off between attempts, and
+/// count the attempts made in `attempts`.
+fn with_retries<T, E: std::fmt::Display>(
+    attempts: &mut u32,
+    mut op: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        *attempts += 1;
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if *attempts < ATTEMPTS => {
+                tracing::debug!("Attempt {} failed ({}), retrying", attempts, e);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn share_mount(tag: &str, strategy: ShareStrategy, attempts: u32, error: String) -> ShareMount {
+    ShareMount {
+        tag: tag.to_string(),
+        strategy: strategy.into(),
+        attempts,
+        error,
+    }
+}
+
+/// The host directory for `tag` when running boxless.
+fn boxless_source(tag: &str) -> BoxliteResult<Option<PathBuf>> {
+    let Ok(shares) = std::env::var(boxless::SHARES_ENV) else {
+        return Ok(None);
+    };
+    let mut shares: HashMap<String, PathBuf> = serde_json::from_str(&shares)?;
+    Ok(shares.remove(tag))
+}
+
+fn bind_mount(tag: &str, source: &Path, mount_point: &Path, read_only: bool) -> BoxliteResult<()> {
+    tracing::info!(
+        "Bind-mounting share {}: {} → {} ({})",
+        tag,
+        source.display(),
+        mount_point.display(),
+        if read_only { "ro" } else { "rw" }
+    );
+    std::fs::create_dir_all(mount_point)?;
+
+    let bind = MsFlags::MS_BIND | MsFlags::MS_REC;
+    mount(Some(source), mount_point, None::<&str>, bind, None::<&str>)
+        .and_then(|()| {
+            if !read_only {
+                return Ok(());
+            }
+            // A bind mount ignores MS_RDONLY; it takes a remount
+            mount(
+                None::<&str>,
+                mount_point,
+                None::<&str>,
+                bind | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+                None::<&str>,
+            )
+        })
+        .map_err(|e| {
+            BoxliteError::Storage(format!(
+                "Failed to bind-mount share {} to {}: {}",
+                tag,
+                mount_point.display(),
+                e
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_retries() {
+        let mut attempts = 0;
+        let mut failures = 2;
+        let result = with_retries(&mut attempts, || {
+            if failures == 0 {
+                return Ok("mounted");
+            }
+            failures -= 1;
+            Err("ENODEV")
+        });
+        assert_eq!(result, Ok("mounted"));
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result: Result<(), _> = with_retries(&mut attempts, || Err("ENODEV"));
+        assert_eq!(result, Err("ENODEV"));
+        assert_eq!(attempts, ATTEMPTS);
     }
 }
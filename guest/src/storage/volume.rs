@@ -7,9 +7,11 @@ use std::path::{Path, PathBuf};
 use boxlite_shared::constants::mount_tags;
 use boxlite_shared::errors::BoxliteResult;
 use boxlite_shared::layout::GUEST_BASE;
-use boxlite_shared::{volume, Filesystem, Volume};
+use boxlite_shared::{volume, Filesystem, ShareMount, ShareStrategy, Volume};
 
 use super::block_device::BlockDeviceMount;
+use super::idmap;
+use super::network::NetworkMount;
 use super::virtiofs::VirtiofsMount;
 
 /// Resolve mount point from tag when mount_point is empty.
@@ -60,12 +62,26 @@ fn resolve_mount_point(tag: &str, mount_point: &str, container_id: &str) -> Path
 /// Mount a single volume in guest.
 ///
 /// Empty mount_point: guest determines path from tag and container_id.
-pub fn mount_volume(vol: &Volume) -> BoxliteResult<()> {
+/// Returns how a virtiofs share was mounted.
+pub fn mount_volume(vol: &Volume) -> BoxliteResult<Option<ShareMount>> {
     match &vol.source {
         Some(volume::Source::Virtiofs(virtiofs)) => {
             let mount_point =
                 resolve_mount_point(&virtiofs.tag, &vol.mount_point, &vol.container_id);
-            VirtiofsMount::mount(&virtiofs.tag, &mount_point, virtiofs.read_only)
+            let share = VirtiofsMount::mount(
+                &virtiofs.tag,
+                &mount_point,
+                virtiofs.read_only,
+                virtiofs.dax,
+                virtiofs.optional,
+            )?;
+            // A stand-in tmpfs has no host owner to map
+            if let Some(mapping) = &virtiofs.id_mapping {
+                if share.strategy() != ShareStrategy::ShareDegraded {
+                    idmap::remount_idmapped(&mount_point, mapping)?;
+                }
+            }
+            Ok(Some(share))
         }
         Some(volume::Source::BlockDevice(block)) => {
             let mount_point = Path::new(&vol.mount_point);
@@ -76,19 +92,32 @@ pub fn mount_volume(vol: &Volume) -> BoxliteResult<()> {
                 filesystem,
                 block.need_format,
                 block.need_resize,
-            )
+            )?;
+            Ok(None)
+        }
+        Some(volume::Source::Network(network)) => {
+            let mount_point =
+                resolve_mount_point(&network.name, &vol.mount_point, &vol.container_id);
+            NetworkMount::mount(network, &mount_point)?;
+            Ok(None)
         }
         None => {
             tracing::warn!("Volume {} has no source, skipping", vol.mount_point);
-            Ok(())
+            Ok(None)
         }
     }
 }
 
-/// Mount all volumes.
-pub fn mount_volumes(volumes: &[Volume]) -> BoxliteResult<()> {
+/// Whether `vol` is a remote filesystem, which needs the network up.
+pub fn is_network_volume(vol: &Volume) -> bool {
+    matches!(vol.source, Some(volume::Source::Network(_)))
+}
+
+/// Mount all volumes, returning how each virtiofs share was mounted.
+pub fn mount_volumes(volumes: &[Volume]) -> BoxliteResult<Vec<ShareMount>> {
+    let mut shares = Vec::new();
     for vol in volumes {
-        mount_volume(vol)?;
+        shares.extend(mount_volume(vol)?);
     }
-    Ok(())
+    Ok(shares)
 }
@@ -0,0 +1,192 @@
+//! In-place agent upgrades
+//!
+//! The host stages a new agent binary over the bulk channel, then asks for
+//! it with Guest.UpgradeAgent. The agent checks the binary's Ed25519
+//! signature against the key it was built with (`BOXLITE_AGENT_PUBLIC_KEY`,
+//! base64), writes its state to a handoff file and execs the binary in
+//! place. The process keeps its PID, its children and the guest's mounts;
+//! the stdio pipes of running containers are kept open across the exec.
+//! The new agent reads the handoff (`--handoff`) instead of booting, and
+//! serves on the same ports.
+//!
+//! Agents built without a key refuse upgrades.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use boxlite_shared::errors::{BoxliteError, BoxliteResult};
+use boxlite_shared::AgentHandoff;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use prost::Message;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use std::os::fd::RawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+
+use crate::layout::GuestLayout;
+
+/// Name of the binary staged by the host.
+pub const STAGED_NAME: &str = "boxlite-guest.staged";
+
+/// Name of a verified binary, which the agent execs.
+const ACTIVE_NAME: &str = "boxlite-guest";
+
+const HANDOFF_NAME: &str = "handoff.pb";
+
+/// Argument passing the handoff file to the new agent.
+const HANDOFF_ARG: &str = "--handoff";
+
+/// Public key agent binaries are signed with.
+const PUBLIC_KEY: Option<&str> = option_env!("BOXLITE_AGENT_PUBLIC_KEY");
+
+/// Verify the staged binary and move it to where it will run from.
+///
+/// The binary is moved before it is read, so a binary staged meanwhile is
+/// not the one that runs.
+pub fn install(layout: &GuestLayout, signature: &[u8]) -> BoxliteResult<PathBuf> {
+    let key = PUBLIC_KEY.ok_or_else(|| {
+        BoxliteError::Unsupported("This agent was built without an upgrade key".to_string())
+    })?;
+    let key = STANDARD
+        .decode(key.trim())
+        .map_err(|e| BoxliteError::Internal(format!("Invalid built-in upgrade key: {}", e)))?;
+
+    let dir = layout.upgrade_dir();
+    let active = dir.join(ACTIVE_NAME);
+    std::fs::rename(dir.join(STAGED_NAME), &active)
+        .map_err(|e| BoxliteError::InvalidState(format!("No agent binary staged: {}", e)))?;
+    let binary = std::fs::read(&active).map_err(|e| {
+        BoxliteError::Internal(format!("Failed to read {}: {}", active.display(), e))
+    })?;
+    if let Err(e) = verify(&key, &binary, signature) {
+        let _ = std::fs::remove_file(&active);
+        return Err(e);
+    }
+    Ok(active)
+}
+
+fn verify(key: &[u8], binary: &[u8], signature: &[u8]) -> BoxliteResult<()> {
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(binary, signature)
+        .map_err(|_| {
+            BoxliteError::InvalidArgument("Agent binary signature does not verify".to_string())
+        })
+}
+
+/// Write `handoff` where the new agent will find it.
+pub fn write_handoff(layout: &GuestLayout, handoff: &AgentHandoff) -> BoxliteResult<PathBuf> {
+    let path = layout.upgrade_dir().join(HANDOFF_NAME);
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, &handoff.encode_to_vec()));
+    written.map_err(|e| {
+        BoxliteError::Internal(format!("Failed to write {}: {}", path.display(), e))
+    })?;
+    Ok(path)
+}
+
+/// Read the handoff left by the agent this one replaced, and remove it.
+pub fn read_handoff(path: &Path) -> BoxliteResult<AgentHandoff> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| BoxliteError::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+    let _ = std::fs::remove_file(path);
+    AgentHandoff::decode(bytes.as_slice())
+        .map_err(|e| BoxliteError::Internal(format!("Invalid agent handoff: {}", e)))
+}
+
+/// Replace this process with `binary`, run with this agent's arguments and
+/// the handoff file; `keep` are descriptors the handoff refers to.
+///
+/// Only returns if the exec failed, with `keep` closed on exec again.
+pub fn exec(binary: &Path, handoff: &Path, keep: &[RawFd]) -> BoxliteError {
+    let mut args = std::env::args();
+    let program = args.next().unwrap_or_else(|| ACTIVE_NAME.to_string());
+    let args = handoff_args(args.collect(), handoff);
+
+    let error = match set_cloexec(keep, false) {
+        Ok(()) => {
+            let e = std::process::Command::new(binary)
+                .arg0(program)
+                .args(args)
+                .exec();
+            BoxliteError::Internal(format!("Failed to exec {}: {}", binary.display(), e))
+        }
+        Err(e) => BoxliteError::Internal(format!("Failed to keep descriptors open: {}", e)),
+    };
+    let _ = set_cloexec(keep, true);
+    error
+}
+
+fn set_cloexec(fds: &[RawFd], cloexec: bool) -> nix::Result<()> {
+    let flags = if cloexec {
+        FdFlag::FD_CLOEXEC
+    } else {
+        FdFlag::empty()
+    };
+    for &fd in fds {
+        fcntl(fd, FcntlArg::F_SETFD(flags))?;
+    }
+    Ok(())
+}
+
+/// `args` with the handoff argument, replacing one from an earlier upgrade.
+fn handoff_args(args: Vec<String>, handoff: &Path) -> Vec<String> {
+    let mut kept = Vec::with_capacity(args.len() + 2);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == HANDOFF_ARG {
+            args.next();
+        } else if !arg.starts_with(&format!("{}=", HANDOFF_ARG)) {
+            kept.push(arg);
+        }
+    }
+    kept.push(HANDOFF_ARG.to_string());
+    kept.push(handoff.display().to_string());
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_verify() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key = pair.public_key().as_ref();
+
+        let binary = b"\x7fELF new agent";
+        let signature = pair.sign(binary);
+        assert!(verify(key, binary, signature.as_ref()).is_ok());
+        assert!(verify(key, b"\x7fELF other agent", signature.as_ref()).is_err());
+        assert!(verify(key, binary, &[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn test_handoff_args() {
+        let args = vec!["--listen".to_string(), "vsock://2695".to_string()];
+        let args = handoff_args(args, Path::new("/run/boxlite/upgrade/handoff.pb"));
+        assert_eq!(
+            args,
+            [
+                "--listen",
+                "vsock://2695",
+                "--handoff",
+                "/run/boxlite/upgrade/handoff.pb"
+            ]
+        );
+
+        // A second upgrade replaces the first one's handoff
+        let args = handoff_args(args, Path::new("/tmp/handoff.pb"));
+        assert_eq!(
+            args,
+            ["--listen", "vsock://2695", "--handoff", "/tmp/handoff.pb"]
+        );
+    }
+}
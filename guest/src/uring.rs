@@ -0,0 +1,305 @@
+//! io_uring-backed file and pipe IO
+//!
+//! Used for the guest's bulk data paths: copying files (rootfs layers) and
+//! draining command output pipes. io_uring lets a file copy keep several
+//! reads and writes in flight with one syscall per batch, and lets a pipe be
+//! drained with large reads without a thread-pool round trip per chunk.
+//!
+//! io_uring can be missing (old kernel) or disabled (`kernel.io_uring_disabled`),
+//! so every entry point falls back to plain blocking IO when a ring cannot
+//! be created.
+
+use io_uring::{opcode, types, IoUring};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Chunk size for file copies and pipe reads.
+const CHUNK_SIZE: usize = 128 * 1024;
+
+/// Number of chunks kept in flight during a file copy.
+const QUEUE_DEPTH: usize = 8;
+
+/// Whether io_uring is usable in this guest (probed once).
+pub fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match IoUring::new(2) {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::info!("io_uring unavailable, using blocking IO: {}", e);
+            false
+        }
+    })
+}
+
+/// Copy a regular file, preserving permissions like `std::fs::copy`.
+pub fn copy_file(src: &Path, dst: &Path) -> io::Result<u64> {
+    if !available() {
+        return std::fs::copy(src, dst);
+    }
+
+    match copy_file_uring(src, dst) {
+        Ok(copied) => Ok(copied),
+        // Short reads mean the file changed under us or the filesystem
+        // doesn't honor offsets; redo the copy the simple way.
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => std::fs::copy(src, dst),
+        Err(e) => Err(e),
+    }
+}
+
+fn copy_file_uring(src: &Path, dst: &Path) -> io::Result<u64> {
+    let input = File::open(src)?;
+    let metadata = input.metadata()?;
+    let len = metadata.len();
+    let output = File::create(dst)?;
+    output.set_permissions(metadata.permissions())?;
+
+    let mut ring = IoUring::new((QUEUE_DEPTH * 2) as u32)?;
+    let buffers = vec![vec![0u8; CHUNK_SIZE]; QUEUE_DEPTH];
+    match copy_batches(&mut ring, &input, &output, len, buffers) {
+        Ok(()) => Ok(len),
+        Err(failed) => Err(failed.into_error()),
+    }
+}
+
+fn copy_batches(
+    ring: &mut IoUring,
+    input: &File,
+    output: &File,
+    len: u64,
+    mut buffers: Vec<Vec<u8>>,
+) -> Result<(), Failed<Vec<Vec<u8>>>> {
+    let mut offset = 0u64;
+
+    while offset < len {
+        // Read a batch of chunks at consecutive offsets
+        let mut lengths = Vec::with_capacity(QUEUE_DEPTH);
+        for (i, buf) in buffers.iter_mut().enumerate() {
+            let start = offset + (i * CHUNK_SIZE) as u64;
+            if start >= len {
+                break;
+            }
+            let chunk = (len - start).min(CHUNK_SIZE as u64) as u32;
+            let read = opcode::Read::new(types::Fd(input.as_raw_fd()), buf.as_mut_ptr(), chunk)
+                .offset64(start as _)
+                .build()
+                .user_data(i as u64);
+            push(ring, &read).map_err(Failed::Done)?;
+            lengths.push(chunk);
+        }
+        if let Err(failed) = complete(ring, &lengths) {
+            return Err(failed.holding(buffers));
+        }
+
+        // Write them back at the same offsets
+        for (i, chunk) in lengths.iter().enumerate() {
+            let start = offset + (i * CHUNK_SIZE) as u64;
+            let write =
+                opcode::Write::new(types::Fd(output.as_raw_fd()), buffers[i].as_ptr(), *chunk)
+                    .offset64(start as _)
+                    .build()
+                    .user_data(i as u64);
+            push(ring, &write).map_err(Failed::Done)?;
+        }
+        if let Err(failed) = complete(ring, &lengths) {
+            return Err(failed.holding(buffers));
+        }
+
+        offset += lengths.iter().map(|&n| n as u64).sum::<u64>();
+    }
+
+    Ok(())
+}
+
+/// Why a batch failed, and whether its requests may still be in flight.
+enum Failed<T> {
+    /// Every request completed (or none was submitted).
+    Done(io::Error),
+    /// The kernel would not let us wait; requests may still read or write
+    /// the memory `T` owns, so it must never be freed.
+    InFlight(io::Error, T),
+}
+
+impl Failed<()> {
+    /// Attach the memory the batch's requests use.
+    fn holding<T>(self, memory: T) -> Failed<T> {
+        match self {
+            Failed::Done(e) => Failed::Done(e),
+            Failed::InFlight(e, ()) => Failed::InFlight(e, memory),
+        }
+    }
+}
+
+impl<T> Failed<T> {
+    /// The error, leaking the memory in-flight requests may still use.
+    fn into_error(self) -> io::Error {
+        match self {
+            Failed::Done(e) => e,
+            Failed::InFlight(e, memory) => {
+                tracing::warn!("io_uring requests abandoned in flight: {}", e);
+                std::mem::forget(memory);
+                e
+            }
+        }
+    }
+}
+
+fn push(ring: &mut IoUring, entry: &io_uring::squeue::Entry) -> io::Result<()> {
+    // SAFETY: buffers and fds outlive the request; pushed entries are
+    // either collected by `complete` before their buffers are reused or
+    // dropped, or their buffers are leaked (`Failed::InFlight`).
+    unsafe { ring.submission().push(entry) }
+        .map_err(|_| io::Error::other("io_uring submission queue full"))
+}
+
+/// Submit pending entries and wait for `want` completions, retrying when
+/// a signal or a full completion queue interrupts the wait.
+fn submit_and_wait(ring: &mut IoUring, want: usize) -> io::Result<()> {
+    loop {
+        match ring.submit_and_wait(want) {
+            Ok(_) => return Ok(()),
+            Err(e)
+                if matches!(
+                    e.raw_os_error(),
+                    Some(nix::libc::EINTR | nix::libc::EAGAIN | nix::libc::EBUSY)
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Wait for one completion per entry in `lengths`, each transferring exactly
+/// the expected number of bytes.
+///
+/// Collects every completion before returning, even after one fails.
+fn complete(ring: &mut IoUring, lengths: &[u32]) -> Result<(), Failed<()>> {
+    let mut first_error = None;
+    let mut seen = 0;
+    while seen < lengths.len() {
+        if let Err(e) = submit_and_wait(ring, lengths.len() - seen) {
+            return Err(Failed::InFlight(e, ()));
+        }
+        for cqe in ring.completion() {
+            seen += 1;
+            let expected = lengths[cqe.user_data() as usize];
+            let result = cqe.result();
+            if result < 0 {
+                first_error.get_or_insert(io::Error::from_raw_os_error(-result));
+            } else if result as u32 != expected {
+                first_error.get_or_insert(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+        }
+    }
+
+    first_error.map_or(Ok(()), |e| Err(Failed::Done(e)))
+}
+
+/// Drain a pipe on a dedicated thread, calling `on_chunk` for each read.
+///
+/// Returns when the write end is closed, a read fails, or `on_chunk`
+/// returns false (e.g. the receiver went away).
+pub fn drain_pipe(fd: OwnedFd, mut on_chunk: impl FnMut(Vec<u8>) -> bool) {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+
+    let ring = if available() {
+        IoUring::new(1).ok()
+    } else {
+        None
+    };
+
+    if let Some(mut ring) = ring {
+        loop {
+            let read = opcode::Read::new(
+                types::Fd(fd.as_raw_fd()),
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+            )
+            .build();
+            if push(&mut ring, &read).is_err() {
+                return;
+            }
+            // The read stays pending until its completion is seen
+            let cqe = match submit_and_wait(&mut ring, 1) {
+                Ok(()) => ring.completion().next(),
+                Err(e) => {
+                    Failed::InFlight(e, buf).into_error();
+                    return;
+                }
+            };
+            let Some(cqe) = cqe else {
+                Failed::InFlight(io::Error::other("missing completion"), buf).into_error();
+                return;
+            };
+            match cqe.result() {
+                n if n > 0 => {
+                    if !on_chunk(buf[..n as usize].to_vec()) {
+                        return;
+                    }
+                }
+                // EINTR: a signal interrupted the read, retry
+                n if n == -nix::libc::EINTR => continue,
+                // EOF or error
+                _ => return,
+            }
+        }
+    }
+
+    let mut file = File::from(fd);
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => {
+                if !on_chunk(buf[..n].to_vec()) {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_copy_file_multiple_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        // Spans several batches and ends with a partial chunk
+        let data: Vec<u8> = (0..CHUNK_SIZE * QUEUE_DEPTH * 2 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&src, &data).unwrap();
+
+        assert_eq!(copy_file(&src, &dst).unwrap(), data.len() as u64);
+        assert_eq!(std::fs::read(&dst).unwrap(), data);
+    }
+
+    #[test]
+    fn test_drain_pipe_until_eof() {
+        let (read_end, write_end) = nix::unistd::pipe().unwrap();
+        let writer = std::thread::spawn(move || {
+            let mut file = File::from(write_end);
+            file.write_all(b"hello ").unwrap();
+            file.write_all(b"world").unwrap();
+        });
+
+        let mut output = Vec::new();
+        drain_pipe(read_end, |chunk| {
+            output.extend_from_slice(&chunk);
+            true
+        });
+        writer.join().unwrap();
+
+        assert_eq!(output, b"hello world");
+    }
+}
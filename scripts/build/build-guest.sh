@@ -7,11 +7,16 @@
 #   - musllinux: scripts/setup/setup-musllinux.sh
 #
 # Usage:
-#   ./build-guest.sh [--dest-dir DIR] [--profile PROFILE]
+#   ./build-guest.sh [--dest-dir DIR] [--profile PROFILE] [--features LIST]
 #
 # Options:
 #   --dest-dir DIR      Directory to copy the guest binary to
 #   --profile PROFILE   Build profile: release or debug (default: release)
+#   --features LIST     Optional agent features, comma-separated
+#                       (default: net,pty; "" for a minimal agent)
+#
+# Release builds use the size-tuned `guest` cargo profile and land in
+# target/$GUEST_TARGET/guest.
 
 set -e
 
@@ -29,6 +34,8 @@ ORIG_DIR="$(pwd)"
 parse_args() {
     DEST_DIR_ARG=""
     PROFILE="release"
+    FEATURES_ARG=""
+    FEATURES_SET=false
 
     while [[ $# -gt 0 ]]; do
         case $1 in
@@ -40,9 +47,14 @@ parse_args() {
                 PROFILE="$2"
                 shift 2
                 ;;
+            --features)
+                FEATURES_ARG="$2"
+                FEATURES_SET=true
+                shift 2
+                ;;
             *)
                 echo "Unknown option: $1"
-                echo "Usage: $0 [--dest-dir DIR] [--profile PROFILE]"
+                echo "Usage: $0 [--dest-dir DIR] [--profile PROFILE] [--features LIST]"
                 exit 1
                 ;;
         esac
@@ -55,6 +67,13 @@ parse_args() {
         exit 1
     fi
 
+    # Where cargo puts the binary for this profile
+    if [ "$PROFILE" = "release" ]; then
+        OUT_DIR="guest"
+    else
+        OUT_DIR="debug"
+    fi
+
     # Resolve destination path to absolute path
     if [ -n "$DEST_DIR_ARG" ]; then
         # If relative, make it absolute relative to original working directory
@@ -100,18 +119,22 @@ setup_rust_target() {
 build_guest_binary() {
     cd "$PROJECT_ROOT"
     echo "🔨 Building guest binary for $GUEST_TARGET $PROFILE..."
-    local build_flag=""
+    local build_flags=()
     if [ "$PROFILE" = "release" ]; then
-        build_flag="--release"
+        build_flags+=(--profile guest)
+    fi
+    if [ "$FEATURES_SET" = true ]; then
+        echo "   Features: ${FEATURES_ARG:-none}"
+        build_flags+=(--no-default-features --features "$FEATURES_ARG")
     fi
-    cargo build $build_flag --target "$GUEST_TARGET" -p boxlite-guest
+    cargo build "${build_flags[@]}" --target "$GUEST_TARGET" -p boxlite-guest
 }
 
 # Copy binary to destination
 copy_to_destination() {
     if [ -z "$DEST_DIR" ]; then
         echo "✅ Guest binary built successfully (no destination specified)"
-        echo "Binary location: $PROJECT_ROOT/target/$GUEST_TARGET/$PROFILE/boxlite-guest"
+        echo "Binary location: $PROJECT_ROOT/target/$GUEST_TARGET/$OUT_DIR/boxlite-guest"
         return 0
     fi
 
@@ -119,7 +142,7 @@ copy_to_destination() {
     # Absolute paths are used as-is
     echo "📦 Copying to destination: $DEST_DIR"
     mkdir -p "$DEST_DIR"
-    cp "$PROJECT_ROOT/target/$GUEST_TARGET/$PROFILE/boxlite-guest" "$DEST_DIR/"
+    cp "$PROJECT_ROOT/target/$GUEST_TARGET/$OUT_DIR/boxlite-guest" "$DEST_DIR/"
 
     echo "✅ Guest binary built and copied to $DEST_DIR"
     echo "Binary info:"
@@ -191,7 +191,12 @@ build_guest() {
 
     # Detect guest target
     source "$SCRIPT_DIR/util.sh"
-    local guest_path="$PROJECT_ROOT/target/$GUEST_TARGET/$PROFILE/boxlite-guest"
+    # Release guests are built with the `guest` cargo profile
+    local guest_dir="$PROFILE"
+    if [ "$PROFILE" = "release" ]; then
+        guest_dir="guest"
+    fi
+    local guest_path="$PROJECT_ROOT/target/$GUEST_TARGET/$guest_dir/boxlite-guest"
 
     if [ -f "$guest_path" ]; then
         GUEST_BINARY="$guest_path"
@@ -106,7 +106,7 @@ clean_runtime() {
            target/release/boxlite-shim \
            target/debug/boxlite-shim
     if [ -z "${KEEP_GUEST_BIN:-}" ]; then
-        rm -rf target/$GUEST_TARGET/release/boxlite-guest \
+        rm -rf target/$GUEST_TARGET/guest/boxlite-guest \
                target/$GUEST_TARGET/debug/boxlite-guest
     else
         print_info "Keeping guest binary as requested"
@@ -143,7 +143,7 @@ install_dylibbundler() {
     echo ""
 }
 
-# Install protobuf (for boxlite-shared gRPC/protobuf compilation)
+# Install protobuf (for boxlite-proto gRPC/protobuf compilation)
 install_protobuf() {
     print_step "Checking for protobuf... "
     if brew_installed "protobuf"; then
@@ -89,7 +89,7 @@ install_system_deps() {
         libelf-dev         # ELF library (kernel objtool)
         python3-pyelftools # ELF parsing (bin2cbundle.py)
 
-        # gRPC/protobuf (boxlite-shared)
+        # gRPC/protobuf (boxlite-proto)
         protobuf-compiler  # protoc compiler
     )
 
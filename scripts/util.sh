@@ -29,9 +29,13 @@ map_arch_to_target() {
         x86_64|amd64)
             echo "x86_64-unknown-linux-musl"
             ;;
+        riscv64)
+            # Experimental
+            echo "riscv64gc-unknown-linux-musl"
+            ;;
         *)
             echo "ERROR: Unsupported architecture: $arch" >&2
-            echo "Supported: arm64, aarch64, x86_64, amd64" >&2
+            echo "Supported: arm64, aarch64, x86_64, amd64, riscv64 (experimental)" >&2
             return 1
             ;;
     esac
@@ -48,6 +52,9 @@ normalize_arch() {
         x86_64|amd64)
             echo "x86_64"
             ;;
+        riscv64)
+            echo "riscv64"
+            ;;
         *)
             echo "ERROR: Unsupported architecture: $arch" >&2
             return 1
@@ -18,7 +18,23 @@ try:
         Execution,
         ExecStdout,
         ExecStderr,
+        OutputChunk,
+        PackageInstallation,
+        PackageInstallResult,
+        CellOutput,
         BoxInfo,
+        SnapshotInfo,
+        DiskUsage,
+        BoxDiskUsage,
+        PruneReport,
+        DryRunReport,
+        RecordingInfo,
+        ArtifactInfo,
+        BoxEvent,
+        ExecUsage,
+        OperationProgress,
+        AgentCapabilities,
+        ProcessInfo,
         RuntimeMetrics,
         BoxMetrics,
     )
@@ -32,7 +48,23 @@ try:
         "Execution",
         "ExecStdout",
         "ExecStderr",
+        "OutputChunk",
+        "PackageInstallation",
+        "PackageInstallResult",
+        "CellOutput",
         "BoxInfo",
+        "SnapshotInfo",
+        "DiskUsage",
+        "BoxDiskUsage",
+        "PruneReport",
+        "DryRunReport",
+        "RecordingInfo",
+        "ArtifactInfo",
+        "BoxEvent",
+        "ExecUsage",
+        "OperationProgress",
+        "AgentCapabilities",
+        "ProcessInfo",
         "RuntimeMetrics",
         "BoxMetrics",
     ]
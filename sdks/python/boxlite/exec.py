@@ -5,6 +5,7 @@ Provides Docker-like API for executing commands in boxes.
 """
 
 from dataclasses import dataclass
+from typing import Optional
 
 __all__ = [
     'ExecResult',
@@ -20,7 +21,22 @@ class ExecResult:
         exit_code: Exit code from the command (negative if terminated by signal)
         stdout: Standard output as string
         stderr: Standard error as string
+        exit_kind: Why the command ended: "exited", "signaled",
+            "oom_killed", "timed_out" or "unknown"
+        signal_name: Signal that terminated it, for "signaled"
+        duration_secs: Wall-clock run time
+        peak_memory_bytes: Peak resident set size
+        cpu_time_secs: User plus kernel CPU time
+        read_bytes: Bytes read from block devices
+        write_bytes: Bytes written to block devices
     """
     exit_code: int
     stdout: str
     stderr: str
+    exit_kind: str = "exited"
+    signal_name: Optional[str] = None
+    duration_secs: float = 0.0
+    peak_memory_bytes: int = 0
+    cpu_time_secs: float = 0.0
+    read_bytes: int = 0
+    write_bytes: int = 0
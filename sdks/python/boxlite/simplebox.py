@@ -4,9 +4,10 @@ SimpleBox - Foundation for specialized container types.
 Provides common functionality for all specialized boxes (CodeBox, BrowserBox, etc.)
 """
 
+import asyncio
 import logging
 from enum import IntEnum
-from typing import Optional
+from typing import Optional, Union
 
 from .exec import ExecResult
 
@@ -113,6 +114,7 @@ class SimpleBox:
             cmd: str,
             *args: str,
             env: Optional[dict[str, str]] = None,
+            stdin: Optional[Union[str, bytes]] = None,
     ) -> ExecResult:
         """
         Execute a command in the box and return the result.
@@ -121,6 +123,8 @@ class SimpleBox:
             cmd: Command to execute (e.g., 'ls', 'python')
             *args: Arguments to the command (e.g., '-l', '-a')
             env: Environment variables (default: guest's default environment)
+            stdin: Input fed to the command, then closed, like a here-doc
+                (default: stdin stays open and unused)
 
         Returns:
             ExecResult with exit_code and output
@@ -137,6 +141,11 @@ class SimpleBox:
 
                 result = await box.exec('env', env={'FOO': 'bar'})
                 print(result.stdout)
+
+            With input::
+
+                result = await box.exec('wc', '-l', stdin='one\ntwo\n')
+                print(result.stdout)
         """
 
         arg_list = list(args) if args else None
@@ -146,6 +155,13 @@ class SimpleBox:
         # Execute via Rust (returns PyExecution)
         execution = await self._box.exec(cmd, arg_list, env_list)
 
+        # Feed stdin alongside reading output, so a command that writes
+        # while it reads cannot stall on a full output pipe
+        feed = None
+        if stdin is not None:
+            data = stdin.encode('utf-8') if isinstance(stdin, str) else stdin
+            feed = asyncio.ensure_future(self._feed_stdin(execution.stdin(), data))
+
         # Get streams from Rust execution
         try:
             stdout = execution.stdout()
@@ -193,16 +209,43 @@ class SimpleBox:
         stdout = ''.join(stdout_lines)
         stderr = ''.join(stderr_lines)
 
+        if feed is not None:
+            try:
+                await feed
+            except Exception as e:
+                # The command may exit without reading all of its input
+                logger.debug(f"feeding stdin err: {e}")
+
         try:
             exec_result = await execution.wait()
-            exit_code = exec_result.exit_code
         except Exception as e:
             logger.error(f"failed to wait execution: {e}")
-            exit_code = -1
-
-        logger.debug(f"exec finish, exit_code: {exit_code}")
+            return ExecResult(exit_code=-1, stdout=stdout, stderr=stderr, exit_kind="unknown")
+
+        logger.debug(f"exec finish, exit_code: {exec_result.exit_code}")
+
+        return ExecResult(
+            exit_code=exec_result.exit_code,
+            stdout=stdout,
+            stderr=stderr,
+            exit_kind=exec_result.exit_kind,
+            signal_name=exec_result.signal_name,
+            duration_secs=exec_result.duration_secs,
+            peak_memory_bytes=exec_result.peak_memory_bytes,
+            cpu_time_secs=exec_result.user_time_secs + exec_result.system_time_secs,
+            read_bytes=exec_result.read_bytes,
+            write_bytes=exec_result.write_bytes,
+        )
 
-        return ExecResult(exit_code=exit_code, stdout=stdout, stderr=stderr)
+    @staticmethod
+    async def _feed_stdin(stdin, data: bytes):
+        """Write data to stdin in chunks, then close it."""
+        chunk_size = 64 * 1024
+        try:
+            for start in range(0, len(data), chunk_size):
+                await stdin.send_input(data[start:start + chunk_size])
+        finally:
+            await stdin.close()
 
     def shutdown(self):
         """
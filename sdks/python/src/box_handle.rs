@@ -1,11 +1,21 @@
+use std::path::Path;
 use std::sync::Arc;
 
-use crate::exec::PyExecution;
-use crate::info::PyBoxInfo;
+use crate::exec::{
+    PyCellOutput, PyExecution, PyNetworkCapture, PyPackageInstallation, PyPathWatch, PySyncSession,
+};
+use crate::info::{
+    PyAgentCapabilities, PyArtifactInfo, PyBoxInfo, PyProcessInfo, PyRecordingInfo, PyTaskStatus,
+};
 use crate::metrics::PyBoxMetrics;
 use crate::util::map_err;
-use boxlite::{BoxCommand, LiteBox};
+use boxlite::{
+    BoxCommand, CaptureSpec, ConflictPolicy, ExecNetwork, FirewallAction, FirewallRule,
+    HomeStorage, LiteBox, OutputCapture, ScheduledTask, SyncSpec, TrafficDirection, UserSpec,
+};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 
 #[pyclass(name = "Box")]
 pub(crate) struct PyBox {
@@ -28,7 +38,8 @@ impl PyBox {
         PyBoxInfo::from(self.handle.info())
     }
 
-    #[pyo3(signature = (command, args=None, env=None, tty=false))]
+    #[pyo3(signature = (command, args=None, env=None, tty=false, user=None, network=None, stdout_file=None, stderr_file=None, max_file_bytes=0, max_files=0, artifacts=None, cache=None, audit=false, private_fs=None))]
+    #[allow(clippy::too_many_arguments)]
     fn exec<'a>(
         &self,
         py: Python<'a>,
@@ -36,10 +47,32 @@ impl PyBox {
         args: Option<Vec<String>>,
         env: Option<Vec<(String, String)>>,
         tty: bool,
+        user: Option<(u32, u32)>,
+        network: Option<String>,
+        stdout_file: Option<String>,
+        stderr_file: Option<String>,
+        max_file_bytes: u64,
+        max_files: u32,
+        artifacts: Option<Vec<String>>,
+        cache: Option<Vec<String>>,
+        audit: bool,
+        private_fs: Option<Vec<String>>,
     ) -> PyResult<Bound<'a, PyAny>> {
         let handle = Arc::clone(&self.handle);
 
         let args = args.unwrap_or_default();
+        let network = match network.as_deref() {
+            None | Some("shared") => ExecNetwork::Shared,
+            Some("none") => ExecNetwork::None,
+            Some("loopback") => ExecNetwork::Loopback,
+            Some("veth") => ExecNetwork::Veth,
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "network must be 'shared', 'none', 'loopback' or 'veth', got '{}'",
+                    other
+                )));
+            }
+        };
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let mut cmd = BoxCommand::new(command);
@@ -49,6 +82,28 @@ impl PyBox {
                     cmd = cmd.env(k, v);
                 }
             }
+            if let Some((uid, gid)) = user {
+                cmd = cmd.user(uid, gid);
+            }
+            cmd = cmd.network(network);
+            if stdout_file.is_some() || stderr_file.is_some() {
+                cmd = cmd.capture_output(OutputCapture {
+                    stdout_path: stdout_file,
+                    stderr_path: stderr_file,
+                    max_file_bytes,
+                    max_files,
+                });
+            }
+            if let Some(paths) = artifacts {
+                cmd = cmd.artifacts(paths);
+            }
+            if let Some(inputs) = cache {
+                cmd = cmd.cache(inputs);
+            }
+            cmd = cmd.audit(audit);
+            if let Some(overlay_paths) = private_fs {
+                cmd = cmd.private_fs(overlay_paths);
+            }
             if tty {
                 // Auto-detect terminal size like Docker (done inside .tty())
                 cmd = cmd.tty(true);
@@ -81,6 +136,513 @@ impl PyBox {
         })
     }
 
+    /// Capture the box's display as PNG bytes (requires gpu="virgl" or "venus").
+    fn screenshot<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let screenshot = handle.screenshot().await.map_err(map_err)?;
+            Ok(screenshot.png)
+        })
+    }
+
+    /// How each shared directory is mounted, as (tag, strategy) tuples;
+    /// strategy is "virtiofs", "virtiofs_no_dax", "9p", "degraded" or "bind".
+    fn shares<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let shares = handle.shares().await.map_err(map_err)?;
+            Ok(shares
+                .into_iter()
+                .map(|share| (share.tag, share.strategy.as_str()))
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Optional features the guest agent was built with ("net", "pty", ...)
+    /// and how fast it started.
+    fn agent_capabilities<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let capabilities = handle.agent_capabilities().await.map_err(map_err)?;
+            Ok(PyAgentCapabilities::from(capabilities))
+        })
+    }
+
+    /// Replace the box's guest agent with a signed binary without a reboot.
+    /// Returns the new agent's version.
+    fn upgrade_agent<'a>(
+        &self,
+        py: Python<'a>,
+        binary: String,
+        signature: Vec<u8>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle
+                .upgrade_agent(Path::new(&binary), &signature)
+                .await
+                .map_err(map_err)
+        })
+    }
+
+    /// Copy a host file into the box. Returns the number of bytes copied.
+    fn copy_in<'a>(
+        &self,
+        py: Python<'a>,
+        host_path: String,
+        box_path: String,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle
+                .copy_in(Path::new(&host_path), &box_path)
+                .await
+                .map_err(map_err)
+        })
+    }
+
+    /// Replace the box's shared clipboard.
+    fn set_clipboard<'a>(&self, py: Python<'a>, content: Vec<u8>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.set_clipboard(&content).await.map_err(map_err)
+        })
+    }
+
+    /// Read the box's shared clipboard as bytes.
+    fn clipboard<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let content = handle.clipboard().await.map_err(map_err)?;
+            Ok(Python::attach(|py| PyBytes::new(py, &content).unbind()))
+        })
+    }
+
+    /// Drop a host file into the box. Returns its path in the box.
+    fn drop_file<'a>(&self, py: Python<'a>, host_path: String) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle
+                .drop_file(Path::new(&host_path))
+                .await
+                .map_err(map_err)
+        })
+    }
+
+    /// Copy a file out of the box. Returns the number of bytes copied.
+    fn copy_out<'a>(
+        &self,
+        py: Python<'a>,
+        box_path: String,
+        host_path: String,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle
+                .copy_out(&box_path, Path::new(&host_path))
+                .await
+                .map_err(map_err)
+        })
+    }
+
+    /// Create a user account in the box. `home_storage` is "rootfs" or "tmpfs".
+    #[pyo3(signature = (name, uid, gid=None, shell=None, home=None, home_storage="rootfs"))]
+    #[allow(clippy::too_many_arguments)]
+    fn create_user<'a>(
+        &self,
+        py: Python<'a>,
+        name: String,
+        uid: u32,
+        gid: Option<u32>,
+        shell: Option<String>,
+        home: Option<String>,
+        home_storage: &str,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        let mut user = UserSpec::new(name, uid);
+        if let Some(gid) = gid {
+            user = user.gid(gid);
+        }
+        if let Some(shell) = shell {
+            user = user.shell(shell);
+        }
+        if let Some(home) = home {
+            user = user.home(home);
+        }
+        user = user.home_storage(match home_storage {
+            "rootfs" => HomeStorage::Rootfs,
+            "tmpfs" => HomeStorage::Tmpfs,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "home_storage must be 'rootfs' or 'tmpfs', got '{}'",
+                    other
+                )));
+            }
+        });
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.create_user(user).await.map_err(map_err)
+        })
+    }
+
+    /// Delete a user account, and its home directory if `remove_home`.
+    #[pyo3(signature = (name, remove_home=false))]
+    fn delete_user<'a>(
+        &self,
+        py: Python<'a>,
+        name: String,
+        remove_home: bool,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle
+                .delete_user(&name, remove_home)
+                .await
+                .map_err(map_err)
+        })
+    }
+
+    /// Run a command every `interval_secs`, replacing any task named `name`.
+    #[pyo3(signature = (name, command, interval_secs, args=None, env=None, timeout_secs=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn register_task<'a>(
+        &self,
+        py: Python<'a>,
+        name: String,
+        command: String,
+        interval_secs: u64,
+        args: Option<Vec<String>>,
+        env: Option<Vec<(String, String)>>,
+        timeout_secs: Option<u64>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        let mut task = ScheduledTask::new(name, command, interval_secs);
+        task.args = args.unwrap_or_default();
+        task.env = env.unwrap_or_default();
+        task.timeout_secs = timeout_secs;
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.register_task(task).await.map_err(map_err)
+        })
+    }
+
+    fn unregister_task<'a>(&self, py: Python<'a>, name: String) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.unregister_task(&name).await.map_err(map_err)
+        })
+    }
+
+    /// Install packages with the image's apt, apk or dnf.
+    #[pyo3(signature = (packages, update_index=false))]
+    fn install_packages<'a>(
+        &self,
+        py: Python<'a>,
+        packages: Vec<String>,
+        update_index: bool,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let installation = handle
+                .install_packages(&packages, update_index)
+                .await
+                .map_err(map_err)?;
+            Ok(PyPackageInstallation {
+                installation: Arc::new(tokio::sync::Mutex::new(Some(installation))),
+            })
+        })
+    }
+
+    /// Run Python code in a persistent interpreter session.
+    #[pyo3(signature = (code, kernel="default".to_string(), timeout_secs=None))]
+    fn execute_cell<'a>(
+        &self,
+        py: Python<'a>,
+        code: String,
+        kernel: String,
+        timeout_secs: Option<u64>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+        let timeout = timeout_secs.map(std::time::Duration::from_secs);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let output = handle
+                .execute_cell(&kernel, &code, timeout)
+                .await
+                .map_err(map_err)?;
+            Ok(PyCellOutput::from(output))
+        })
+    }
+
+    /// Stop an interpreter session, discarding its state.
+    #[pyo3(signature = (kernel="default".to_string()))]
+    fn shutdown_kernel<'a>(&self, py: Python<'a>, kernel: String) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.shutdown_kernel(&kernel).await.map_err(map_err)
+        })
+    }
+
+    fn list_tasks<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let tasks = handle.list_tasks().await.map_err(map_err)?;
+            Ok(tasks
+                .into_iter()
+                .map(PyTaskStatus::from)
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Processes running in the box's container, by pid.
+    fn processes<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let processes = handle.processes().await.map_err(map_err)?;
+            Ok(processes
+                .into_iter()
+                .map(PyProcessInfo::from)
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Watch a path in the box; iterate the result for batches of changes.
+    #[pyo3(signature = (path, recursive=false, debounce_ms=None))]
+    fn watch<'a>(
+        &self,
+        py: Python<'a>,
+        path: String,
+        recursive: bool,
+        debounce_ms: Option<u64>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let watch = handle
+                .watch(
+                    &path,
+                    recursive,
+                    debounce_ms.map(std::time::Duration::from_millis),
+                )
+                .await
+                .map_err(map_err)?;
+            Ok(PyPathWatch {
+                watch: Arc::new(tokio::sync::Mutex::new(watch)),
+            })
+        })
+    }
+
+    /// Copy a host directory into the box, then keep both in step both ways.
+    ///
+    /// `conflict` settles files changed on both sides: "keep_both" leaves
+    /// the box's version next to the host's as `<name>.conflict-box`.
+    #[pyo3(signature = (host_path, box_path, conflict="keep_both", ignore=None))]
+    fn sync<'a>(
+        &self,
+        py: Python<'a>,
+        host_path: String,
+        box_path: String,
+        conflict: &str,
+        ignore: Option<Vec<String>>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+        let mut spec = SyncSpec::new(host_path, box_path);
+        spec.conflict = match conflict {
+            "keep_both" => ConflictPolicy::KeepBoth,
+            "host_wins" => ConflictPolicy::HostWins,
+            "box_wins" => ConflictPolicy::BoxWins,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "conflict must be 'keep_both', 'host_wins' or 'box_wins', got '{}'",
+                    other
+                )));
+            }
+        };
+        spec.ignore = ignore.unwrap_or_default();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let session = handle.sync(spec).await.map_err(map_err)?;
+            Ok(PySyncSession {
+                session: std::sync::Mutex::new(Some(session)),
+            })
+        })
+    }
+
+    /// Capture the box's network traffic to a pcap file until stopped.
+    ///
+    /// `filter` is a pcap-filter expression such as "tcp port 80".
+    #[pyo3(signature = (output, filter=None, snaplen=65535))]
+    fn capture_network<'a>(
+        &self,
+        py: Python<'a>,
+        output: String,
+        filter: Option<String>,
+        snaplen: u32,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+        let mut spec = CaptureSpec::new(output);
+        spec.filter = filter;
+        spec.snaplen = snaplen;
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let capture = handle.capture_network(spec).await.map_err(map_err)?;
+            Ok(PyNetworkCapture {
+                capture: Arc::new(std::sync::Mutex::new(Some(capture))),
+            })
+        })
+    }
+
+    /// Have the running box drop its page cache and compact memory,
+    /// handing freed pages back to the host. Returns the bytes freed.
+    fn reclaim_memory<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.reclaim_memory().await.map_err(map_err)
+        })
+    }
+
+    /// The running box's firewall, as (egress default, ingress default,
+    /// rules such as "allow egress tcp:443").
+    fn firewall(&self) -> PyResult<(String, String, Vec<String>)> {
+        let policy = self.handle.firewall().map_err(map_err)?;
+        Ok((
+            policy.egress.as_str().to_string(),
+            policy.ingress.as_str().to_string(),
+            policy.rules.iter().map(ToString::to_string).collect(),
+        ))
+    }
+
+    /// Add firewall rules to the running box, after its current ones.
+    ///
+    /// Rules are `PROTO[:PORT[-PORT]][@CIDR]`, such as "tcp:443" or
+    /// "any@10.0.0.0/8"; `ingress` makes them match traffic to the box.
+    /// Returns the box's rules. The change lasts until the box stops.
+    #[pyo3(signature = (allow=None, deny=None, ingress=false))]
+    fn firewall_add<'a>(
+        &self,
+        py: Python<'a>,
+        allow: Option<Vec<String>>,
+        deny: Option<Vec<String>>,
+        ingress: bool,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+        let direction = traffic_direction(ingress);
+        let allow = allow
+            .into_iter()
+            .flatten()
+            .map(|spec| (FirewallAction::Allow, spec));
+        let deny = deny
+            .into_iter()
+            .flatten()
+            .map(|spec| (FirewallAction::Deny, spec));
+        let rules = allow
+            .chain(deny)
+            .map(|(action, spec)| FirewallRule::parse(direction, action, &spec))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(map_err)?;
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let policy = handle.add_firewall_rules(rules).await.map_err(map_err)?;
+            Ok(policy
+                .rules
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Set what the running box's firewall does with traffic no rule
+    /// matches: "allow" or "deny", for egress or, with `ingress`, ingress.
+    #[pyo3(signature = (action, ingress=false))]
+    fn firewall_default<'a>(
+        &self,
+        py: Python<'a>,
+        action: &str,
+        ingress: bool,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+        let action = match action {
+            "allow" => FirewallAction::Allow,
+            "deny" => FirewallAction::Deny,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "action must be 'allow' or 'deny', got '{}'",
+                    other
+                )));
+            }
+        };
+        let mut policy = self.handle.firewall().map_err(map_err)?;
+        match traffic_direction(ingress) {
+            TrafficDirection::Egress => policy.egress = action,
+            TrafficDirection::Ingress => policy.ingress = action,
+        }
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle.set_firewall(policy).await.map_err(map_err)
+        })
+    }
+
+    /// Host ports forwarded into the running box, as (host_port, guest_port).
+    fn ports(&self) -> Vec<(u16, u16)> {
+        self.handle
+            .ports()
+            .into_iter()
+            .map(|port| (port.host_port, port.guest_port))
+            .collect()
+    }
+
+    /// Recorded TTY sessions, oldest first.
+    fn recordings(&self) -> PyResult<Vec<PyRecordingInfo>> {
+        let recordings = self.handle.recordings().map_err(map_err)?;
+        Ok(recordings.into_iter().map(PyRecordingInfo::from).collect())
+    }
+
+    /// Artifacts collected from executions run with `artifacts=[...]`.
+    fn artifacts(&self) -> PyResult<Vec<PyArtifactInfo>> {
+        let artifacts = self.handle.artifacts().map_err(map_err)?;
+        Ok(artifacts.into_iter().map(PyArtifactInfo::from).collect())
+    }
+
+    /// Unpack an execution's artifacts into a host directory.
+    fn download_artifacts<'a>(
+        &self,
+        py: Python<'a>,
+        execution_id: String,
+        dest: String,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let handle = Arc::clone(&self.handle);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            handle
+                .download_artifacts(&execution_id, Path::new(&dest))
+                .await
+                .map_err(map_err)
+        })
+    }
+
+    /// The asciinema cast of a recorded session.
+    fn read_recording(&self, execution_id: &str) -> PyResult<String> {
+        self.handle.read_recording(execution_id).map_err(map_err)
+    }
+
     fn __aenter__<'a>(slf: PyRefMut<'_, Self>, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
         let handle = Arc::clone(&slf.handle);
 
@@ -107,3 +669,11 @@ impl PyBox {
         format!("Box(id={:?})", self.handle.id().to_string())
     }
 }
+
+fn traffic_direction(ingress: bool) -> TrafficDirection {
+    if ingress {
+        TrafficDirection::Ingress
+    } else {
+        TrafficDirection::Egress
+    }
+}
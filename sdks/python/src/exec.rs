@@ -1,6 +1,11 @@
 use crate::util::map_err;
-use boxlite::Execution;
+use boxlite::{
+    AuditReport, CellOutput, ConflictPolicy, ExecResult, Execution, ExitKind, NetworkCapture,
+    OutputChunk, OutputStream, PackageInstallResult, PackageInstallation, PathChangeKind,
+    PathWatch, SyncSession,
+};
 use pyo3::{Bound, PyAny, PyRef, PyResult, Python, pyclass, pymethods};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -37,18 +42,36 @@ impl PyExecStdout {
 
 #[pyclass(name = "ExecStdin")]
 pub(crate) struct PyExecStdin {
-    pub(crate) stream: Arc<Mutex<boxlite::ExecStdin>>,
+    /// None once closed.
+    pub(crate) stream: Arc<Mutex<Option<boxlite::ExecStdin>>>,
 }
 
 #[pymethods]
 impl PyExecStdin {
     /// Send data to stdin.
+    ///
+    /// Waits while the process is behind on reading.
     fn send_input<'a>(&self, py: Python<'a>, data: Vec<u8>) -> PyResult<Bound<'a, PyAny>> {
         let stream = Arc::clone(&self.stream);
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let mut guard = stream.lock().await;
-            guard.write_all(&data).await.map_err(map_err)?;
+            let stdin = guard.as_mut().ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err("stdin stream is closed")
+            })?;
+            stdin.write_all(&data).await.map_err(map_err)?;
+            Ok(())
+        })
+    }
+
+    /// Close stdin, so the process reads EOF.
+    fn close<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let stream = Arc::clone(&self.stream);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            if let Some(stdin) = stream.lock().await.take() {
+                stdin.close();
+            }
             Ok(())
         })
     }
@@ -93,6 +116,139 @@ impl PyExecStderr {
 pub(crate) struct PyExecResult {
     #[pyo3(get, set)]
     pub(crate) exit_code: i32,
+    /// "exited", "signaled", "oom_killed", "timed_out" or "unknown".
+    #[pyo3(get)]
+    pub(crate) exit_kind: String,
+    #[pyo3(get)]
+    pub(crate) signal: Option<i32>,
+    #[pyo3(get)]
+    pub(crate) signal_name: Option<String>,
+    #[pyo3(get)]
+    pub(crate) duration_secs: f64,
+    #[pyo3(get)]
+    pub(crate) peak_memory_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) user_time_secs: f64,
+    #[pyo3(get)]
+    pub(crate) system_time_secs: f64,
+    #[pyo3(get)]
+    pub(crate) read_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) write_bytes: u64,
+    /// Set when the command ran with audit=True.
+    #[pyo3(get)]
+    pub(crate) audit: Option<PyAuditReport>,
+}
+
+impl From<ExecResult> for PyExecResult {
+    fn from(result: ExecResult) -> Self {
+        let (exit_kind, signal, signal_name) = match result.exit_kind {
+            ExitKind::Exited => ("exited", None, None),
+            ExitKind::Signaled { signal, name } => ("signaled", Some(signal), Some(name)),
+            ExitKind::OomKilled => ("oom_killed", None, None),
+            ExitKind::TimedOut => ("timed_out", None, None),
+            ExitKind::Unknown => ("unknown", None, None),
+        };
+        Self {
+            exit_code: result.exit_code,
+            exit_kind: exit_kind.to_string(),
+            signal,
+            signal_name,
+            duration_secs: result.duration.as_secs_f64(),
+            peak_memory_bytes: result.peak_memory_bytes,
+            user_time_secs: result.user_time.as_secs_f64(),
+            system_time_secs: result.system_time.as_secs_f64(),
+            read_bytes: result.read_bytes,
+            write_bytes: result.write_bytes,
+            audit: result.audit.map(PyAuditReport::from),
+        }
+    }
+}
+
+#[pyclass(name = "AuditReport")]
+#[derive(Clone)]
+pub(crate) struct PyAuditReport {
+    /// Calls per traced syscall name.
+    #[pyo3(get)]
+    pub(crate) syscalls: HashMap<String, u64>,
+    #[pyo3(get)]
+    pub(crate) executed: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) files_read: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) files_written: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) files_removed: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) connections: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) listeners: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) dropped: u64,
+}
+
+impl From<AuditReport> for PyAuditReport {
+    fn from(report: AuditReport) -> Self {
+        Self {
+            syscalls: report.syscalls.into_iter().collect(),
+            executed: report.executed,
+            files_read: report.files_read,
+            files_written: report.files_written,
+            files_removed: report.files_removed,
+            connections: report.connections,
+            listeners: report.listeners,
+            dropped: report.dropped,
+        }
+    }
+}
+
+#[pymethods]
+impl PyAuditReport {
+    fn __repr__(&self) -> String {
+        format!(
+            "AuditReport(executed={}, files_read={}, files_written={}, connections={})",
+            self.executed.len(),
+            self.files_read.len(),
+            self.files_written.len(),
+            self.connections.len()
+        )
+    }
+}
+
+#[pyclass(name = "OutputChunk")]
+pub(crate) struct PyOutputChunk {
+    #[pyo3(get)]
+    pub(crate) data: Vec<u8>,
+    #[pyo3(get)]
+    pub(crate) offset: u64,
+    #[pyo3(get)]
+    pub(crate) total_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) complete: bool,
+}
+
+impl From<OutputChunk> for PyOutputChunk {
+    fn from(chunk: OutputChunk) -> Self {
+        Self {
+            data: chunk.data,
+            offset: chunk.offset,
+            total_bytes: chunk.total_bytes,
+            complete: chunk.complete,
+        }
+    }
+}
+
+#[pymethods]
+impl PyOutputChunk {
+    fn __repr__(&self) -> String {
+        format!(
+            "OutputChunk(offset={}, len={}, total_bytes={}, complete={})",
+            self.offset,
+            self.data.len(),
+            self.total_bytes,
+            self.complete
+        )
+    }
 }
 
 #[pyclass(name = "Execution")]
@@ -110,7 +266,7 @@ impl PyExecution {
         let execution = unsafe { &mut *(Arc::as_ptr(&self.execution) as *mut Execution) };
         match execution.stdin() {
             Some(stream) => Ok(PyExecStdin {
-                stream: Arc::new(Mutex::new(stream)),
+                stream: Arc::new(Mutex::new(Some(stream))),
             }),
             None => Err(pyo3::exceptions::PyRuntimeError::new_err(
                 "stdin stream not available",
@@ -148,9 +304,37 @@ impl PyExecution {
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let execution_mut = unsafe { &mut *(Arc::as_ptr(&execution) as *mut Execution) };
             let exec_result = execution_mut.wait().await.map_err(map_err)?;
-            Ok(PyExecResult {
-                exit_code: exec_result.exit_code,
-            })
+            Ok(PyExecResult::from(exec_result))
+        })
+    }
+
+    /// Read captured output ("stdout" or "stderr") from `offset`.
+    #[pyo3(signature = (stream, offset=0, length=0))]
+    fn fetch_output<'a>(
+        &self,
+        py: Python<'a>,
+        stream: String,
+        offset: u64,
+        length: u64,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let stream = match stream.as_str() {
+            "stdout" => OutputStream::Stdout,
+            "stderr" => OutputStream::Stderr,
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "stream must be 'stdout' or 'stderr', got '{}'",
+                    other
+                )));
+            }
+        };
+        let execution = Arc::clone(&self.execution);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let chunk = execution
+                .fetch_output(stream, offset, length)
+                .await
+                .map_err(map_err)?;
+            Ok(PyOutputChunk::from(chunk))
         })
     }
 
@@ -168,3 +352,273 @@ impl PyExecution {
         "Execution(...)".to_string()
     }
 }
+
+#[pyclass(name = "PackageInstallResult")]
+pub(crate) struct PyPackageInstallResult {
+    /// "apt", "apk" or "dnf"; None if the image has none of them.
+    #[pyo3(get)]
+    pub(crate) package_manager: Option<String>,
+    #[pyo3(get)]
+    pub(crate) exit_code: i32,
+    /// (name, version) of each requested package now installed.
+    #[pyo3(get)]
+    pub(crate) installed: Vec<(String, String)>,
+    #[pyo3(get)]
+    pub(crate) error: Option<String>,
+    #[pyo3(get)]
+    pub(crate) success: bool,
+}
+
+impl From<PackageInstallResult> for PyPackageInstallResult {
+    fn from(result: PackageInstallResult) -> Self {
+        Self {
+            success: result.success(),
+            package_manager: result.package_manager,
+            exit_code: result.exit_code,
+            installed: result
+                .installed
+                .into_iter()
+                .map(|p| (p.name, p.version))
+                .collect(),
+            error: result.error,
+        }
+    }
+}
+
+#[pyclass(name = "CellOutput")]
+pub(crate) struct PyCellOutput {
+    #[pyo3(get)]
+    pub(crate) execution_count: u32,
+    #[pyo3(get)]
+    pub(crate) stdout: String,
+    #[pyo3(get)]
+    pub(crate) stderr: String,
+    /// MIME type -> data for each display() call, then the trailing expression.
+    #[pyo3(get)]
+    pub(crate) display: Vec<HashMap<String, String>>,
+    /// Exception class name if the cell raised.
+    #[pyo3(get)]
+    pub(crate) error_name: Option<String>,
+    #[pyo3(get)]
+    pub(crate) error_value: Option<String>,
+    #[pyo3(get)]
+    pub(crate) traceback: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) success: bool,
+}
+
+impl From<CellOutput> for PyCellOutput {
+    fn from(output: CellOutput) -> Self {
+        let success = output.success();
+        let (error_name, error_value, traceback) = match output.error {
+            Some(e) => (Some(e.ename), Some(e.evalue), e.traceback),
+            None => (None, None, Vec::new()),
+        };
+        Self {
+            execution_count: output.execution_count,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            display: output.display,
+            error_name,
+            error_value,
+            traceback,
+            success,
+        }
+    }
+}
+
+#[pymethods]
+impl PyCellOutput {
+    fn __repr__(&self) -> String {
+        format!(
+            "CellOutput(execution_count={}, success={})",
+            self.execution_count, self.success
+        )
+    }
+}
+
+/// Async iterator over package manager output, then `wait()` for the result.
+#[pyclass(name = "PackageInstallation")]
+pub(crate) struct PyPackageInstallation {
+    /// None once waited on.
+    pub(crate) installation: Arc<Mutex<Option<PackageInstallation>>>,
+}
+
+#[pymethods]
+impl PyPackageInstallation {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'a>(&self, py: Python<'a>) -> PyResult<Option<Bound<'a, PyAny>>> {
+        let installation = Arc::clone(&self.installation);
+
+        let future = pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut guard = installation.lock().await;
+            let output = match guard.as_mut() {
+                Some(installation) => installation.next_output().await.map_err(map_err)?,
+                None => None,
+            };
+            output.ok_or_else(|| pyo3::exceptions::PyStopAsyncIteration::new_err(""))
+        })?;
+
+        Ok(Some(future))
+    }
+
+    fn wait<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let installation = Arc::clone(&self.installation);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let installation = installation.lock().await.take().ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err("installation already waited on")
+            })?;
+            let result = installation.wait().await.map_err(map_err)?;
+            Ok(PyPackageInstallResult::from(result))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        "PackageInstallation(...)".to_string()
+    }
+}
+
+/// Async iterator over batches of (path, kind) changes under a watched path;
+/// kind is "created", "modified", "deleted" or "overflow".
+#[pyclass(name = "PathWatch")]
+pub(crate) struct PyPathWatch {
+    pub(crate) watch: Arc<Mutex<PathWatch>>,
+}
+
+#[pymethods]
+impl PyPathWatch {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'a>(&self, py: Python<'a>) -> PyResult<Option<Bound<'a, PyAny>>> {
+        let watch = Arc::clone(&self.watch);
+
+        let future = pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let batch = watch.lock().await.next().await.map_err(map_err)?;
+            let batch = batch.ok_or_else(|| pyo3::exceptions::PyStopAsyncIteration::new_err(""))?;
+            Ok(batch
+                .into_iter()
+                .map(|change| {
+                    let kind = match change.kind {
+                        PathChangeKind::Created => "created",
+                        PathChangeKind::Modified => "modified",
+                        PathChangeKind::Deleted => "deleted",
+                        PathChangeKind::Overflow => "overflow",
+                    };
+                    (change.path, kind)
+                })
+                .collect::<Vec<_>>())
+        })?;
+
+        Ok(Some(future))
+    }
+
+    fn __repr__(&self) -> String {
+        "PathWatch(...)".to_string()
+    }
+}
+
+/// A running two-way sync; it stops when stopped or garbage collected.
+#[pyclass(name = "SyncSession")]
+pub(crate) struct PySyncSession {
+    pub(crate) session: std::sync::Mutex<Option<SyncSession>>,
+}
+
+#[pymethods]
+impl PySyncSession {
+    /// Conflicts settled so far, as (path, resolution).
+    fn conflicts(&self) -> Vec<(String, &'static str)> {
+        let session = self.session.lock().unwrap();
+        let Some(session) = session.as_ref() else {
+            return Vec::new();
+        };
+        session
+            .conflicts()
+            .into_iter()
+            .map(|conflict| {
+                let resolution = match conflict.resolution {
+                    ConflictPolicy::KeepBoth => "keep_both",
+                    ConflictPolicy::HostWins => "host_wins",
+                    ConflictPolicy::BoxWins => "box_wins",
+                };
+                (conflict.path, resolution)
+            })
+            .collect()
+    }
+
+    /// Why the sync stopped on its own, if it has.
+    fn error(&self) -> Option<String> {
+        self.session.lock().unwrap().as_ref()?.error()
+    }
+
+    #[getter]
+    fn running(&self) -> bool {
+        self.session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(SyncSession::is_running)
+    }
+
+    fn stop(&self) {
+        self.session.lock().unwrap().take();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SyncSession(running={})", self.running())
+    }
+}
+
+/// A running packet capture; it stops when stopped or garbage collected.
+#[pyclass(name = "NetworkCapture")]
+pub(crate) struct PyNetworkCapture {
+    pub(crate) capture: Arc<std::sync::Mutex<Option<NetworkCapture>>>,
+}
+
+#[pymethods]
+impl PyNetworkCapture {
+    /// Packets written so far.
+    #[getter]
+    fn packets(&self) -> u64 {
+        self.capture
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |capture| capture.stats().packets)
+    }
+
+    #[getter]
+    fn running(&self) -> bool {
+        self.capture
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(NetworkCapture::is_running)
+    }
+
+    /// Stop capturing and flush the file; returns (packets, bytes).
+    fn stop<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let capture = self.capture.lock().unwrap().take();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let Some(capture) = capture else {
+                return Ok((0u64, 0u64));
+            };
+            let stats = capture.stop().await.map_err(map_err)?;
+            Ok((stats.packets, stats.bytes))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "NetworkCapture(running={}, packets={})",
+            self.running(),
+            self.packets()
+        )
+    }
+}
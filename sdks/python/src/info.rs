@@ -1,6 +1,14 @@
-use boxlite::{BoxInfo, BoxStatus};
+use std::collections::HashMap;
+
+use boxlite::{
+    AgentCapabilities, ArtifactInfo, BoxDiskUsage, BoxEvent, BoxInfo, BoxStatus, DiskUsage,
+    DryRunReport, ExecUsage, OperationProgress, ProcessInfo, PruneReport, RecordingInfo,
+    SnapshotInfo, TaskStatus,
+};
 use pyo3::prelude::*;
 
+use crate::util::map_err;
+
 #[pyclass(name = "BoxInfo")]
 #[derive(Clone)]
 pub(crate) struct PyBoxInfo {
@@ -22,28 +30,507 @@ pub(crate) struct PyBoxInfo {
     pub(crate) cpus: u8,
     #[pyo3(get)]
     pub(crate) memory_mib: u32,
+    #[pyo3(get)]
+    pub(crate) labels: HashMap<String, String>,
+    #[pyo3(get)]
+    pub(crate) engine: String,
+    /// "starting", "healthy" or "unhealthy" while a box with probes runs
+    #[pyo3(get)]
+    pub(crate) health: Option<String>,
+    /// Times the box was restarted for failing its liveness probe
+    #[pyo3(get)]
+    pub(crate) restarts: u32,
 }
 
 impl From<BoxInfo> for PyBoxInfo {
     fn from(info: BoxInfo) -> Self {
-        let state_str = match info.status {
-            BoxStatus::Unknown => "unknown",
-            BoxStatus::Starting => "starting",
-            BoxStatus::Running => "running",
-            BoxStatus::Stopping => "stopping",
-            BoxStatus::Stopped => "stopped",
-        };
-
         PyBoxInfo {
             id: info.id.to_string(),
             name: info.name,
-            state: state_str.to_string(),
+            state: status_str(info.status).to_string(),
             created_at: info.created_at.to_rfc3339(),
             pid: info.pid,
             transport: info.transport.to_string(),
             image: info.image,
             cpus: info.cpus,
             memory_mib: info.memory_mib,
+            labels: info.labels,
+            engine: info.engine.as_str().to_string(),
+            health: info.health.as_ref().map(|h| h.status.to_string()),
+            restarts: info.health.map_or(0, |h| h.restarts),
+        }
+    }
+}
+
+fn status_str(status: BoxStatus) -> &'static str {
+    match status {
+        BoxStatus::Unknown => "unknown",
+        BoxStatus::Starting => "starting",
+        BoxStatus::Running => "running",
+        BoxStatus::Stopping => "stopping",
+        BoxStatus::Stopped => "stopped",
+    }
+}
+
+#[pyclass(name = "DiskUsage")]
+#[derive(Clone)]
+pub(crate) struct PyDiskUsage {
+    #[pyo3(get)]
+    pub(crate) images_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) images_reclaimable_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) snapshots_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) snapshots_reclaimable_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) package_cache_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) total_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) reclaimable_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) boxes: Vec<PyBoxDiskUsage>,
+}
+
+impl From<DiskUsage> for PyDiskUsage {
+    fn from(usage: DiskUsage) -> Self {
+        PyDiskUsage {
+            images_bytes: usage.images.bytes,
+            images_reclaimable_bytes: usage.images.reclaimable_bytes,
+            snapshots_bytes: usage.snapshots.bytes,
+            snapshots_reclaimable_bytes: usage.snapshots.reclaimable_bytes,
+            package_cache_bytes: usage.package_cache.bytes,
+            total_bytes: usage.total_bytes(),
+            reclaimable_bytes: usage.reclaimable_bytes(),
+            boxes: usage.boxes.into_iter().map(PyBoxDiskUsage::from).collect(),
+        }
+    }
+}
+
+#[pyclass(name = "BoxDiskUsage")]
+#[derive(Clone)]
+pub(crate) struct PyBoxDiskUsage {
+    #[pyo3(get)]
+    pub(crate) id: String,
+    #[pyo3(get)]
+    pub(crate) name: Option<String>,
+    #[pyo3(get)]
+    pub(crate) state: String,
+    #[pyo3(get)]
+    pub(crate) scratch_bytes: u64,
+    /// Volume host directory sizes, by guest path.
+    #[pyo3(get)]
+    pub(crate) volumes: HashMap<String, u64>,
+}
+
+impl From<BoxDiskUsage> for PyBoxDiskUsage {
+    fn from(usage: BoxDiskUsage) -> Self {
+        PyBoxDiskUsage {
+            id: usage.id.to_string(),
+            name: usage.name,
+            state: status_str(usage.status).to_string(),
+            scratch_bytes: usage.scratch_bytes,
+            volumes: usage
+                .volumes
+                .into_iter()
+                .map(|volume| (volume.guest_path, volume.bytes))
+                .collect(),
+        }
+    }
+}
+
+#[pyclass(name = "PruneReport")]
+#[derive(Clone)]
+pub(crate) struct PyPruneReport {
+    #[pyo3(get)]
+    pub(crate) boxes: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) images: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) reclaimed_bytes: u64,
+}
+
+impl From<PruneReport> for PyPruneReport {
+    fn from(report: PruneReport) -> Self {
+        PyPruneReport {
+            boxes: report.boxes.into_iter().map(|id| id.to_string()).collect(),
+            images: report.images,
+            reclaimed_bytes: report.reclaimed_bytes,
+        }
+    }
+}
+
+#[pyclass(name = "DryRunReport")]
+#[derive(Clone)]
+pub(crate) struct PyDryRunReport {
+    /// Whether the box would be created and started.
+    #[pyo3(get)]
+    pub(crate) valid: bool,
+    /// The resolved spec as TOML.
+    #[pyo3(get)]
+    pub(crate) spec: String,
+    #[pyo3(get)]
+    pub(crate) image_digest: Option<String>,
+    /// Problems found, as "field: message".
+    #[pyo3(get)]
+    pub(crate) issues: Vec<String>,
+    text: String,
+}
+
+#[pymethods]
+impl PyDryRunReport {
+    fn __str__(&self) -> String {
+        self.text.clone()
+    }
+}
+
+impl TryFrom<DryRunReport> for PyDryRunReport {
+    type Error = PyErr;
+
+    fn try_from(report: DryRunReport) -> PyResult<Self> {
+        Ok(PyDryRunReport {
+            valid: report.is_valid(),
+            spec: report.spec_toml().map_err(map_err)?,
+            image_digest: report.image_digest.clone(),
+            issues: report.issues.iter().map(ToString::to_string).collect(),
+            text: report.to_string(),
+        })
+    }
+}
+
+#[pyclass(name = "SnapshotInfo")]
+#[derive(Clone)]
+pub(crate) struct PySnapshotInfo {
+    #[pyo3(get)]
+    pub(crate) id: String,
+    #[pyo3(get)]
+    pub(crate) box_id: String,
+    #[pyo3(get)]
+    pub(crate) name: String,
+    #[pyo3(get)]
+    pub(crate) parent_id: Option<String>,
+    #[pyo3(get)]
+    pub(crate) size_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) created_at: String,
+}
+
+impl From<SnapshotInfo> for PySnapshotInfo {
+    fn from(info: SnapshotInfo) -> Self {
+        PySnapshotInfo {
+            id: info.id,
+            box_id: info.box_id.to_string(),
+            name: info.name,
+            parent_id: info.parent_id,
+            size_bytes: info.size_bytes,
+            created_at: info.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[pyclass(name = "TaskStatus")]
+#[derive(Clone)]
+pub(crate) struct PyTaskStatus {
+    #[pyo3(get)]
+    pub(crate) name: String,
+    #[pyo3(get)]
+    pub(crate) interval_secs: f64,
+    #[pyo3(get)]
+    pub(crate) runs: u64,
+    #[pyo3(get)]
+    pub(crate) failures: u64,
+    #[pyo3(get)]
+    pub(crate) last_exit_code: Option<i32>,
+    #[pyo3(get)]
+    pub(crate) last_run_at: Option<String>,
+    #[pyo3(get)]
+    pub(crate) last_duration_secs: f64,
+    #[pyo3(get)]
+    pub(crate) last_output: String,
+    #[pyo3(get)]
+    pub(crate) last_error: Option<String>,
+}
+
+impl From<TaskStatus> for PyTaskStatus {
+    fn from(status: TaskStatus) -> Self {
+        PyTaskStatus {
+            name: status.name,
+            interval_secs: status.interval.as_secs_f64(),
+            runs: status.runs,
+            failures: status.failures,
+            last_exit_code: status.last_exit_code,
+            last_run_at: status
+                .last_run
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()),
+            last_duration_secs: status.last_duration.as_secs_f64(),
+            last_output: status.last_output,
+            last_error: status.last_error,
+        }
+    }
+}
+
+#[pyclass(name = "ProcessInfo")]
+#[derive(Clone)]
+pub(crate) struct PyProcessInfo {
+    /// Pid inside the container.
+    #[pyo3(get)]
+    pub(crate) pid: u32,
+    #[pyo3(get)]
+    pub(crate) ppid: u32,
+    #[pyo3(get)]
+    pub(crate) uid: u32,
+    #[pyo3(get)]
+    pub(crate) state: String,
+    #[pyo3(get)]
+    pub(crate) command: String,
+    #[pyo3(get)]
+    pub(crate) rss_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) cpu_time_secs: f64,
+}
+
+impl From<ProcessInfo> for PyProcessInfo {
+    fn from(process: ProcessInfo) -> Self {
+        PyProcessInfo {
+            pid: process.pid,
+            ppid: process.ppid,
+            uid: process.uid,
+            state: process.state,
+            command: process.command,
+            rss_bytes: process.rss_bytes,
+            cpu_time_secs: process.cpu_time.as_secs_f64(),
+        }
+    }
+}
+
+#[pyclass(name = "ArtifactInfo")]
+#[derive(Clone)]
+pub(crate) struct PyArtifactInfo {
+    #[pyo3(get)]
+    pub(crate) execution_id: String,
+    /// The zstd-compressed tar.
+    #[pyo3(get)]
+    pub(crate) path: String,
+    #[pyo3(get)]
+    pub(crate) collected_at: String,
+    #[pyo3(get)]
+    pub(crate) size_bytes: u64,
+}
+
+impl From<ArtifactInfo> for PyArtifactInfo {
+    fn from(info: ArtifactInfo) -> Self {
+        PyArtifactInfo {
+            execution_id: info.execution_id,
+            path: info.path.to_string_lossy().into_owned(),
+            collected_at: chrono::DateTime::<chrono::Utc>::from(info.collected_at).to_rfc3339(),
+            size_bytes: info.size_bytes,
+        }
+    }
+}
+
+#[pymethods]
+impl PyArtifactInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "ArtifactInfo(execution_id='{}', size_bytes={}, collected_at='{}')",
+            self.execution_id, self.size_bytes, self.collected_at
+        )
+    }
+}
+
+#[pyclass(name = "RecordingInfo")]
+#[derive(Clone)]
+pub(crate) struct PyRecordingInfo {
+    #[pyo3(get)]
+    pub(crate) execution_id: String,
+    #[pyo3(get)]
+    pub(crate) path: String,
+    #[pyo3(get)]
+    pub(crate) command: String,
+    #[pyo3(get)]
+    pub(crate) started_at: String,
+    #[pyo3(get)]
+    pub(crate) size_bytes: u64,
+}
+
+impl From<RecordingInfo> for PyRecordingInfo {
+    fn from(info: RecordingInfo) -> Self {
+        PyRecordingInfo {
+            execution_id: info.execution_id,
+            path: info.path.to_string_lossy().into_owned(),
+            command: info.command,
+            started_at: chrono::DateTime::<chrono::Utc>::from(info.started_at).to_rfc3339(),
+            size_bytes: info.size_bytes,
+        }
+    }
+}
+
+#[pymethods]
+impl PyRecordingInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "RecordingInfo(execution_id='{}', command='{}', started_at='{}')",
+            self.execution_id, self.command, self.started_at
+        )
+    }
+}
+
+#[pyclass(name = "BoxEvent")]
+#[derive(Clone)]
+pub(crate) struct PyBoxEvent {
+    #[pyo3(get)]
+    pub(crate) seq: i64,
+    #[pyo3(get)]
+    pub(crate) kind: String,
+    #[pyo3(get)]
+    pub(crate) timestamp: String,
+    #[pyo3(get)]
+    pub(crate) box_id: String,
+    #[pyo3(get)]
+    pub(crate) box_name: Option<String>,
+    #[pyo3(get)]
+    pub(crate) labels: HashMap<String, String>,
+    #[pyo3(get)]
+    pub(crate) attributes: HashMap<String, String>,
+}
+
+impl From<BoxEvent> for PyBoxEvent {
+    fn from(event: BoxEvent) -> Self {
+        PyBoxEvent {
+            seq: event.seq,
+            kind: event.kind.to_string(),
+            timestamp: event.timestamp.to_rfc3339(),
+            box_id: event.box_id.to_string(),
+            box_name: event.box_name,
+            labels: event.labels,
+            attributes: event.attributes,
+        }
+    }
+}
+
+#[pyclass(name = "OperationProgress")]
+#[derive(Clone)]
+pub(crate) struct PyOperationProgress {
+    #[pyo3(get)]
+    pub(crate) id: String,
+    #[pyo3(get)]
+    pub(crate) kind: String,
+    #[pyo3(get)]
+    pub(crate) target: String,
+    #[pyo3(get)]
+    pub(crate) box_id: Option<String>,
+    #[pyo3(get)]
+    pub(crate) state: String,
+    #[pyo3(get)]
+    pub(crate) done: u64,
+    #[pyo3(get)]
+    pub(crate) total: Option<u64>,
+    #[pyo3(get)]
+    pub(crate) error: Option<String>,
+    #[pyo3(get)]
+    pub(crate) started_at: String,
+}
+
+impl From<OperationProgress> for PyOperationProgress {
+    fn from(progress: OperationProgress) -> Self {
+        PyOperationProgress {
+            id: progress.id,
+            kind: progress.kind.as_str().to_string(),
+            target: progress.target,
+            box_id: progress.box_id.map(|id| id.to_string()),
+            state: progress.state.as_str().to_string(),
+            done: progress.done,
+            total: progress.total,
+            error: progress.error,
+            started_at: progress.started_at.to_rfc3339(),
+        }
+    }
+}
+
+#[pyclass(name = "AgentCapabilities")]
+#[derive(Clone)]
+pub(crate) struct PyAgentCapabilities {
+    #[pyo3(get)]
+    pub(crate) version: String,
+    #[pyo3(get)]
+    pub(crate) features: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) startup_ms: u64,
+    #[pyo3(get)]
+    pub(crate) ready_ms: Option<u64>,
+    #[pyo3(get)]
+    pub(crate) budget_ms: u64,
+}
+
+impl From<AgentCapabilities> for PyAgentCapabilities {
+    fn from(capabilities: AgentCapabilities) -> Self {
+        PyAgentCapabilities {
+            version: capabilities.version,
+            features: capabilities.features,
+            startup_ms: capabilities.startup.as_millis() as u64,
+            ready_ms: capabilities.ready.map(|ready| ready.as_millis() as u64),
+            budget_ms: capabilities.budget.as_millis() as u64,
+        }
+    }
+}
+
+#[pyclass(name = "ExecUsage")]
+#[derive(Clone)]
+pub(crate) struct PyExecUsage {
+    #[pyo3(get)]
+    pub(crate) seq: i64,
+    #[pyo3(get)]
+    pub(crate) box_id: String,
+    #[pyo3(get)]
+    pub(crate) box_name: Option<String>,
+    #[pyo3(get)]
+    pub(crate) labels: HashMap<String, String>,
+    #[pyo3(get)]
+    pub(crate) execution_id: String,
+    #[pyo3(get)]
+    pub(crate) command: String,
+    #[pyo3(get)]
+    pub(crate) started_at: String,
+    #[pyo3(get)]
+    pub(crate) finished_at: String,
+    #[pyo3(get)]
+    pub(crate) exit_code: i32,
+    #[pyo3(get)]
+    pub(crate) exit_kind: String,
+    #[pyo3(get)]
+    pub(crate) wall_time_secs: f64,
+    #[pyo3(get)]
+    pub(crate) user_time_secs: f64,
+    #[pyo3(get)]
+    pub(crate) system_time_secs: f64,
+    #[pyo3(get)]
+    pub(crate) peak_memory_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) read_bytes: u64,
+    #[pyo3(get)]
+    pub(crate) write_bytes: u64,
+}
+
+impl From<ExecUsage> for PyExecUsage {
+    fn from(usage: ExecUsage) -> Self {
+        PyExecUsage {
+            seq: usage.seq,
+            box_id: usage.box_id.to_string(),
+            box_name: usage.box_name,
+            labels: usage.labels,
+            execution_id: usage.execution_id,
+            command: usage.command,
+            started_at: usage.started_at.to_rfc3339(),
+            finished_at: usage.finished_at.to_rfc3339(),
+            exit_code: usage.exit_code,
+            exit_kind: usage.exit_kind,
+            wall_time_secs: usage.wall_time.as_secs_f64(),
+            user_time_secs: usage.user_time.as_secs_f64(),
+            system_time_secs: usage.system_time.as_secs_f64(),
+            peak_memory_bytes: usage.peak_memory_bytes,
+            read_bytes: usage.read_bytes,
+            write_bytes: usage.write_bytes,
         }
     }
 }
@@ -9,9 +9,17 @@ mod runtime;
 mod util;
 
 use crate::box_handle::PyBox;
-use crate::exec::{PyExecStderr, PyExecStdin, PyExecStdout, PyExecution};
-use crate::info::PyBoxInfo;
-use crate::metrics::{PyBoxMetrics, PyRuntimeMetrics};
+use crate::exec::{
+    PyAuditReport, PyCellOutput, PyExecStderr, PyExecStdin, PyExecStdout, PyExecution,
+    PyNetworkCapture, PyOutputChunk, PyPackageInstallResult, PyPackageInstallation, PyPathWatch,
+    PySyncSession,
+};
+use crate::info::{
+    PyAgentCapabilities, PyArtifactInfo, PyBoxDiskUsage, PyBoxEvent, PyBoxInfo, PyDiskUsage,
+    PyDryRunReport, PyExecUsage, PyOperationProgress, PyProcessInfo, PyPruneReport,
+    PyRecordingInfo, PySnapshotInfo, PyTaskStatus,
+};
+use crate::metrics::{PyBoxMetrics, PyCapacityReport, PyRuntimeMetrics};
 use crate::options::{PyBoxOptions, PyOptions};
 use crate::runtime::PyBoxlite;
 use pyo3::prelude::*;
@@ -26,8 +34,30 @@ fn boxlite_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyExecStdin>()?;
     m.add_class::<PyExecStdout>()?;
     m.add_class::<PyExecStderr>()?;
+    m.add_class::<PyOutputChunk>()?;
+    m.add_class::<PyAuditReport>()?;
+    m.add_class::<PyPackageInstallation>()?;
+    m.add_class::<PyPathWatch>()?;
+    m.add_class::<PySyncSession>()?;
+    m.add_class::<PyNetworkCapture>()?;
+    m.add_class::<PyCellOutput>()?;
+    m.add_class::<PyPackageInstallResult>()?;
     m.add_class::<PyBoxInfo>()?;
+    m.add_class::<PySnapshotInfo>()?;
+    m.add_class::<PyDiskUsage>()?;
+    m.add_class::<PyBoxDiskUsage>()?;
+    m.add_class::<PyPruneReport>()?;
+    m.add_class::<PyDryRunReport>()?;
+    m.add_class::<PyRecordingInfo>()?;
+    m.add_class::<PyArtifactInfo>()?;
+    m.add_class::<PyBoxEvent>()?;
+    m.add_class::<PyExecUsage>()?;
+    m.add_class::<PyOperationProgress>()?;
+    m.add_class::<PyAgentCapabilities>()?;
+    m.add_class::<PyProcessInfo>()?;
+    m.add_class::<PyTaskStatus>()?;
     m.add_class::<PyRuntimeMetrics>()?;
+    m.add_class::<PyCapacityReport>()?;
     m.add_class::<PyBoxMetrics>()?;
 
     Ok(())
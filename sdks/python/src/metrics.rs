@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use boxlite::CapacityReport;
 use boxlite::metrics::{BoxMetrics, RuntimeMetrics};
 use pyo3::prelude::*;
 
@@ -14,6 +17,8 @@ pub(crate) struct PyRuntimeMetrics {
     pub(crate) total_commands_executed: u64,
     #[pyo3(get)]
     pub(crate) total_exec_errors: u64,
+    #[pyo3(get)]
+    pub(crate) events_dropped_total: u64,
 }
 
 #[pymethods]
@@ -38,6 +43,57 @@ impl From<RuntimeMetrics> for PyRuntimeMetrics {
             num_running_boxes: metrics.num_running_boxes(),
             total_commands_executed: metrics.total_commands_executed(),
             total_exec_errors: metrics.total_exec_errors(),
+            events_dropped_total: metrics.events_dropped_total(),
+        }
+    }
+}
+
+#[pyclass(name = "CapacityReport")]
+#[derive(Clone)]
+pub(crate) struct PyCapacityReport {
+    #[pyo3(get)]
+    pub(crate) host_cpus: u32,
+    #[pyo3(get)]
+    pub(crate) host_memory_mib: u64,
+    #[pyo3(get)]
+    pub(crate) available_memory_mib: u64,
+    #[pyo3(get)]
+    pub(crate) running_boxes: usize,
+    #[pyo3(get)]
+    pub(crate) committed_cpus: u32,
+    #[pyo3(get)]
+    pub(crate) committed_memory_mib: u64,
+    #[pyo3(get)]
+    pub(crate) cpu_limit: Option<u32>,
+    #[pyo3(get)]
+    pub(crate) memory_limit_mib: Option<u64>,
+}
+
+#[pymethods]
+impl PyCapacityReport {
+    fn __repr__(&self) -> String {
+        format!(
+            "CapacityReport(cpus={}/{:?}, memory_mib={}/{:?}, running={})",
+            self.committed_cpus,
+            self.cpu_limit,
+            self.committed_memory_mib,
+            self.memory_limit_mib,
+            self.running_boxes
+        )
+    }
+}
+
+impl From<CapacityReport> for PyCapacityReport {
+    fn from(report: CapacityReport) -> Self {
+        PyCapacityReport {
+            host_cpus: report.host_cpus,
+            host_memory_mib: report.host_memory_mib,
+            available_memory_mib: report.available_memory_mib,
+            running_boxes: report.running_boxes,
+            committed_cpus: report.committed_cpus,
+            committed_memory_mib: report.committed_memory_mib,
+            cpu_limit: report.cpu_limit,
+            memory_limit_mib: report.memory_limit_mib,
         }
     }
 }
@@ -54,6 +110,8 @@ pub(crate) struct PyBoxMetrics {
     #[pyo3(get)]
     pub(crate) bytes_received_total: u64,
     #[pyo3(get)]
+    pub(crate) output_chunks_dropped_total: u64,
+    #[pyo3(get)]
     pub(crate) total_create_duration_ms: Option<u128>,
     #[pyo3(get)]
     pub(crate) guest_boot_duration_ms: Option<u128>,
@@ -82,6 +140,8 @@ pub(crate) struct PyBoxMetrics {
     pub(crate) stage_box_spawn_ms: Option<u128>,
     #[pyo3(get)]
     pub(crate) stage_container_init_ms: Option<u128>,
+    #[pyo3(get)]
+    pub(crate) guest_stages_ms: HashMap<String, u128>,
 }
 
 #[pymethods]
@@ -106,6 +166,7 @@ impl From<BoxMetrics> for PyBoxMetrics {
             exec_errors_total: metrics.exec_errors_total(),
             bytes_sent_total: metrics.bytes_sent_total(),
             bytes_received_total: metrics.bytes_received_total(),
+            output_chunks_dropped_total: metrics.output_chunks_dropped_total(),
             total_create_duration_ms: metrics.total_create_duration_ms(),
             guest_boot_duration_ms: metrics.guest_boot_duration_ms(),
             cpu_percent: metrics.cpu_percent(),
@@ -120,6 +181,11 @@ impl From<BoxMetrics> for PyBoxMetrics {
             stage_box_config_ms: metrics.stage_box_config_ms(),
             stage_box_spawn_ms: metrics.stage_box_spawn_ms(),
             stage_container_init_ms: metrics.stage_container_init_ms(),
+            guest_stages_ms: metrics
+                .guest_stages()
+                .iter()
+                .map(|stage| (stage.name.clone(), stage.duration_ms))
+                .collect(),
         }
     }
 }
@@ -1,8 +1,15 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use boxlite::runtime::constants::images;
 use boxlite::runtime::options::{
-    BoxOptions, BoxliteOptions, NetworkSpec, PortProtocol, PortSpec, RootfsSpec, VolumeSpec,
+    ArtifactRetention, BatchQueue, BoxOptions, BoxPriority, BoxliteOptions, ClipboardPolicy,
+    DeviceNodeSpec, DevicePolicy, DnsOptions, EncryptedVolumeSpec, EngineSelection, GpuSpec,
+    HomeVolume, HookOptions, IngressOptions, InitMode, IoLimits, MountCredentials,
+    NetworkFilesystem, NetworkMountSpec, NetworkSpec, ObjectCredentials, ObjectVolumeSpec,
+    OvercommitOptions, OverflowPolicy, PortProtocol, PortSpec, PressureOptions, Probe, ProbeCheck,
+    QuotaOptions, RootfsSpec, RuntimeProfile, SetupStep, SharingOptions, SshOptions,
+    StreamBufferOptions, UnhealthyPolicy, VolumeKeySource, VolumeOwner, VolumeSpec, WebhookOptions,
 };
 use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
@@ -13,14 +20,131 @@ use pyo3::types::{PyAnyMethods, PyDict, PyTuple};
 pub(crate) struct PyOptions {
     #[pyo3(get, set)]
     pub(crate) home_dir: Option<String>,
+    #[pyo3(get, set)]
+    pub(crate) max_boxes: Option<usize>,
+    #[pyo3(get, set)]
+    pub(crate) max_cpus: Option<u32>,
+    #[pyo3(get, set)]
+    pub(crate) max_memory_mib: Option<u64>,
+    #[pyo3(get, set)]
+    pub(crate) max_disk_gb: Option<u64>,
+    /// Events buffered per live subscriber (default 1024)
+    #[pyo3(get, set)]
+    pub(crate) event_buffer_size: Option<usize>,
+    /// "drop_oldest" (default) or "block" to replay missed events from the log
+    #[pyo3(get, set)]
+    pub(crate) event_overflow: Option<String>,
+    /// Delegated cgroup2 directory box VMMs are placed under, for priorities
+    #[pyo3(get, set)]
+    pub(crate) cgroup_parent: Option<String>,
+    /// Endpoints lifecycle events are POSTed to
+    #[pyo3(get, set)]
+    pub(crate) webhook_urls: Vec<String>,
+    /// Key for the X-Boxlite-Signature HMAC of each delivery
+    #[pyo3(get, set)]
+    pub(crate) webhook_secret: Option<String>,
+    /// Executables run at pre_create, post_ready and pre_destroy
+    #[pyo3(get, set)]
+    pub(crate) hooks: Vec<String>,
+    /// TOML file of admission rules every box spec must satisfy
+    #[pyo3(get, set)]
+    pub(crate) policy_file: Option<String>,
+    /// Serve boxes with ingress_port at https://<name>.localhost:8443
+    #[pyo3(get, set)]
+    pub(crate) ingress: bool,
+    /// Answer <name>.box for running boxes on 127.0.0.1:5380
+    #[pyo3(get, set)]
+    pub(crate) dns: bool,
+    /// vCPUs running boxes may commit per host CPU; enables host admission
+    #[pyo3(get, set)]
+    pub(crate) cpu_overcommit: Option<f64>,
+    /// Memory running boxes may commit per byte of host memory
+    #[pyo3(get, set)]
+    pub(crate) memory_overcommit: Option<f64>,
+    /// Seconds a box waits for host capacity before it is refused
+    #[pyo3(get, set)]
+    pub(crate) admission_timeout_secs: Option<u64>,
+    /// "vm" (default), "auto", or "boxless" for no VM (weaker isolation)
+    #[pyo3(get, set)]
+    pub(crate) engine: Option<String>,
+    /// Have lower-priority boxes reclaim memory when a box is under memory pressure
+    #[pyo3(get, set)]
+    pub(crate) reclaim_on_pressure: bool,
+    /// Cap lower-priority boxes' CPU while a box is under CPU pressure (needs cgroup_parent)
+    #[pyo3(get, set)]
+    pub(crate) throttle_on_pressure: bool,
 }
 
 #[pymethods]
 impl PyOptions {
     #[new]
-    #[pyo3(signature = (home_dir=None))]
-    fn new(home_dir: Option<String>) -> Self {
-        Self { home_dir }
+    #[pyo3(signature = (
+        home_dir=None,
+        max_boxes=None,
+        max_cpus=None,
+        max_memory_mib=None,
+        max_disk_gb=None,
+        event_buffer_size=None,
+        event_overflow=None,
+        cgroup_parent=None,
+        webhook_urls=Vec::new(),
+        webhook_secret=None,
+        hooks=Vec::new(),
+        policy_file=None,
+        ingress=false,
+        dns=false,
+        cpu_overcommit=None,
+        memory_overcommit=None,
+        admission_timeout_secs=None,
+        engine=None,
+        reclaim_on_pressure=false,
+        throttle_on_pressure=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        home_dir: Option<String>,
+        max_boxes: Option<usize>,
+        max_cpus: Option<u32>,
+        max_memory_mib: Option<u64>,
+        max_disk_gb: Option<u64>,
+        event_buffer_size: Option<usize>,
+        event_overflow: Option<String>,
+        cgroup_parent: Option<String>,
+        webhook_urls: Vec<String>,
+        webhook_secret: Option<String>,
+        hooks: Vec<String>,
+        policy_file: Option<String>,
+        ingress: bool,
+        dns: bool,
+        cpu_overcommit: Option<f64>,
+        memory_overcommit: Option<f64>,
+        admission_timeout_secs: Option<u64>,
+        engine: Option<String>,
+        reclaim_on_pressure: bool,
+        throttle_on_pressure: bool,
+    ) -> Self {
+        Self {
+            home_dir,
+            max_boxes,
+            max_cpus,
+            max_memory_mib,
+            max_disk_gb,
+            event_buffer_size,
+            event_overflow,
+            cgroup_parent,
+            webhook_urls,
+            webhook_secret,
+            hooks,
+            policy_file,
+            ingress,
+            dns,
+            cpu_overcommit,
+            memory_overcommit,
+            admission_timeout_secs,
+            engine,
+            reclaim_on_pressure,
+            throttle_on_pressure,
+        }
     }
 
     fn __repr__(&self) -> String {
@@ -36,10 +160,95 @@ impl From<PyOptions> for BoxliteOptions {
             config.home_dir = PathBuf::from(home_dir);
         }
 
+        config.quota = QuotaOptions {
+            max_boxes: py_opts.max_boxes,
+            max_cpus: py_opts.max_cpus,
+            max_memory_mib: py_opts.max_memory_mib,
+            max_disk_gb: py_opts.max_disk_gb,
+        };
+        config.event_buffer =
+            stream_buffer(py_opts.event_buffer_size, py_opts.event_overflow.as_deref());
+        config.cgroup_parent = py_opts.cgroup_parent.map(PathBuf::from);
+        config.webhooks = py_opts
+            .webhook_urls
+            .into_iter()
+            .map(|url| WebhookOptions {
+                secret: py_opts.webhook_secret.clone(),
+                ..WebhookOptions::new(url)
+            })
+            .collect();
+        config.hooks = py_opts.hooks.into_iter().map(HookOptions::new).collect();
+        if let Some(policy_file) = py_opts.policy_file {
+            config.policy_file = Some(PathBuf::from(policy_file));
+        }
+        if py_opts.ingress {
+            config.ingress = Some(IngressOptions::default());
+        }
+        if py_opts.dns {
+            config.dns = Some(DnsOptions::default());
+        }
+        if py_opts.cpu_overcommit.is_some()
+            || py_opts.memory_overcommit.is_some()
+            || py_opts.admission_timeout_secs.is_some()
+        {
+            let defaults = OvercommitOptions::default();
+            config.overcommit = Some(OvercommitOptions {
+                cpu_ratio: py_opts.cpu_overcommit.unwrap_or(defaults.cpu_ratio),
+                memory_ratio: py_opts.memory_overcommit.unwrap_or(defaults.memory_ratio),
+                queue_timeout_secs: py_opts.admission_timeout_secs,
+            });
+        }
+
+        config.engine = match py_opts.engine.as_deref() {
+            Some("auto") => EngineSelection::Auto,
+            Some("boxless") => EngineSelection::Boxless,
+            _ => EngineSelection::Vm,
+        };
+        config.pressure = PressureOptions {
+            reclaim_memory: py_opts.reclaim_on_pressure,
+            throttle_cpu: py_opts.throttle_on_pressure,
+        };
+
         config
     }
 }
 
+/// Buffer options from a size and an overflow policy name.
+fn stream_buffer(size: Option<usize>, overflow: Option<&str>) -> StreamBufferOptions {
+    let defaults = StreamBufferOptions::default();
+    StreamBufferOptions {
+        capacity: size.unwrap_or(defaults.capacity),
+        overflow: match overflow {
+            Some("block") => OverflowPolicy::Block,
+            _ => OverflowPolicy::DropOldest,
+        },
+    }
+}
+
+/// Probe from "tcp:PORT", "http:PORT/PATH" or a command line.
+///
+/// Malformed ports parse as 0, which box creation rejects.
+fn probe(spec: &str) -> Probe {
+    let check = if let Some(port) = spec.strip_prefix("tcp:") {
+        ProbeCheck::Tcp {
+            port: port.parse().unwrap_or(0),
+        }
+    } else if let Some(rest) = spec.strip_prefix("http:") {
+        let (port, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        ProbeCheck::Http {
+            port: port.parse().unwrap_or(0),
+            path: if path.is_empty() { "/" } else { path }.to_string(),
+        }
+    } else {
+        let mut words = spec.split_whitespace().map(str::to_string);
+        ProbeCheck::Command {
+            command: words.next().unwrap_or_default(),
+            args: words.collect(),
+        }
+    };
+    Probe::new(check)
+}
+
 #[pyclass(name = "BoxOptions")]
 #[derive(Clone, Debug)]
 pub(crate) struct PyBoxOptions {
@@ -57,12 +266,197 @@ pub(crate) struct PyBoxOptions {
     pub(crate) working_dir: Option<String>,
     #[pyo3(get, set)]
     pub(crate) env: Vec<(String, String)>,
+    #[pyo3(get, set)]
+    pub(crate) env_files: Vec<String>,
     pub(crate) volumes: Vec<PyVolumeSpec>,
+    /// NFS/CIFS mounts, as dicts (see `PyNetworkMountSpec`)
+    pub(crate) network_mounts: Vec<PyNetworkMountSpec>,
+    /// S3-compatible buckets, as dicts (see `PyObjectVolumeSpec`)
+    pub(crate) object_volumes: Vec<PyObjectVolumeSpec>,
+    /// Encrypted named volumes, as dicts (see `PyEncryptedVolumeSpec`)
+    pub(crate) encrypted_volumes: Vec<PyEncryptedVolumeSpec>,
     #[pyo3(get, set)]
     pub(crate) network: Option<String>,
     pub(crate) ports: Vec<PyPortSpec>,
     #[pyo3(get, set)]
     pub(crate) auto_remove: Option<bool>,
+    #[pyo3(get, set)]
+    pub(crate) idle_timeout_secs: Option<u64>,
+    #[pyo3(get, set)]
+    pub(crate) ttl_secs: Option<u64>,
+    #[pyo3(get, set)]
+    pub(crate) labels: HashMap<String, String>,
+    /// "virgl" (OpenGL) or "venus" (Vulkan) virtio-gpu rendering
+    #[pyo3(get, set)]
+    pub(crate) gpu: Option<String>,
+    #[pyo3(get, set)]
+    pub(crate) nested_virt: bool,
+    /// Run x86_64 binaries in arm64 boxes (Rosetta or qemu-user)
+    #[pyo3(get, set)]
+    pub(crate) x86_emulation: bool,
+    #[pyo3(get, set)]
+    pub(crate) memory_dedup: bool,
+    /// Available memory percent under which a low_memory event is emitted (0 = never)
+    #[pyo3(get, set)]
+    pub(crate) low_memory_percent: u8,
+    /// Percent of time stalled on CPU, memory or I/O that emits a pressure event (0 = never)
+    #[pyo3(get, set)]
+    pub(crate) pressure_percent: u8,
+    /// Bytes kept of each core dump, stored with the box's artifacts (0 = no collection)
+    #[pyo3(get, set)]
+    pub(crate) core_dump_max_bytes: u64,
+    #[pyo3(get, set)]
+    pub(crate) readahead: bool,
+    #[pyo3(get, set)]
+    pub(crate) package_cache: bool,
+    /// Boxes naming the same domain share a package cache (None = own cache)
+    #[pyo3(get, set)]
+    pub(crate) package_cache_domain: Option<String>,
+    /// Host port forwarded to an SSH server in the box (None = no SSH)
+    #[pyo3(get, set)]
+    pub(crate) ssh_port: Option<u16>,
+    #[pyo3(get, set)]
+    pub(crate) ssh_authorized_keys: Vec<String>,
+    #[pyo3(get, set)]
+    pub(crate) forward_ssh_agent: bool,
+    #[pyo3(get, set)]
+    pub(crate) forward_git_credentials: bool,
+    /// Hosts git credentials are answered for (empty = the remotes of the
+    /// repository the process runs in)
+    #[pyo3(get, set)]
+    pub(crate) git_credential_hosts: Vec<String>,
+    #[pyo3(get, set)]
+    pub(crate) forward_x11: bool,
+    /// Clipboard sharing: "disabled", "host_to_box", "box_to_host" or "bidirectional"
+    #[pyo3(get, set)]
+    pub(crate) clipboard: String,
+    /// Box directory for files dropped with `drop_file` (None = drops disabled)
+    #[pyo3(get, set)]
+    pub(crate) drop_dir: Option<String>,
+    #[pyo3(get, set)]
+    pub(crate) max_drop_bytes: Option<u64>,
+    /// Report host-side volume edits to file watchers in the box, as
+    /// attribute (`IN_ATTRIB`) events only
+    #[pyo3(get, set)]
+    pub(crate) watch_volumes: bool,
+    /// Broker file locks on volumes through the host (see `boxlite-lock`)
+    #[pyo3(get, set)]
+    pub(crate) lock_proxy: bool,
+    /// Per-box writable /etc overlay (changes are lost when the box stops)
+    #[pyo3(get, set)]
+    pub(crate) etc_overlay: bool,
+    /// Container paths hidden from the workload (/dev/null or empty tmpfs)
+    #[pyo3(get, set)]
+    pub(crate) masked_paths: Vec<String>,
+    /// Container paths remounted read-only
+    #[pyo3(get, set)]
+    pub(crate) readonly_paths: Vec<String>,
+    /// Also mask the kernel interfaces runc hides by default (/proc/kcore, ...)
+    #[pyo3(get, set)]
+    pub(crate) default_path_masks: bool,
+    /// Runtimes installed on first boot and cached: "python", "node", "go", "rust"
+    #[pyo3(get, set)]
+    pub(crate) runtimes: Vec<String>,
+    /// Mount devtmpfs at /dev (exposes every guest device)
+    #[pyo3(get, set)]
+    pub(crate) devtmpfs: bool,
+    /// Extra character devices under /dev, mode 0666 owned by root
+    #[pyo3(get, set)]
+    pub(crate) device_nodes: Vec<String>,
+    /// Allow FUSE mounts in the box
+    #[pyo3(get, set)]
+    pub(crate) fuse: bool,
+    /// Answer for `<name>.local` on the host LAN while running
+    #[pyo3(get, set)]
+    pub(crate) mdns: bool,
+    /// Guest port served at https://<name>.localhost by the runtime ingress
+    #[pyo3(get, set)]
+    pub(crate) ingress_port: Option<u16>,
+    /// Network joined in the runtime DNS, answering <service>.<network>.box
+    #[pyo3(get, set)]
+    pub(crate) dns_network: Option<String>,
+    /// Service names offered in dns_network (default: the box name)
+    #[pyo3(get, set)]
+    pub(crate) dns_services: Vec<String>,
+    /// Prepare the box to run container engines (dockerd, podman)
+    #[pyo3(get, set)]
+    pub(crate) nested_containers: bool,
+    /// Apply the Docker profile (nested containers, FUSE, /dev/net/tun, 20 GB disk)
+    #[pyo3(get, set)]
+    pub(crate) docker_profile: bool,
+    /// Container init: "entrypoint" (default) or "systemd" to boot the image's systemd
+    #[pyo3(get, set)]
+    pub(crate) init_mode: Option<String>,
+    /// Host CPU and I/O priority: "realtime", "high", "normal" (default) or "batch"
+    #[pyo3(get, set)]
+    pub(crate) priority: Option<String>,
+    /// Caps on the box's disk I/O, per host device (need a delegated cgroup)
+    #[pyo3(get, set)]
+    pub(crate) io_read_bps: Option<u64>,
+    #[pyo3(get, set)]
+    pub(crate) io_write_bps: Option<u64>,
+    #[pyo3(get, set)]
+    pub(crate) io_read_iops: Option<u64>,
+    #[pyo3(get, set)]
+    pub(crate) io_write_iops: Option<u64>,
+    /// Named queue the box waits in, running queue_max_parallel at a time
+    #[pyo3(get, set)]
+    pub(crate) queue: Option<String>,
+    #[pyo3(get, set)]
+    pub(crate) queue_max_parallel: usize,
+    /// Record TTY sessions as asciinema casts (see Box.recordings())
+    #[pyo3(get, set)]
+    pub(crate) record_sessions: bool,
+    /// Most execution artifact archives kept (see Box.artifacts())
+    #[pyo3(get, set)]
+    pub(crate) max_artifacts: Option<usize>,
+    /// Artifact archives older than this are deleted
+    #[pyo3(get, set)]
+    pub(crate) artifact_max_age_secs: Option<u64>,
+    /// Most bytes all artifact archives may take together
+    #[pyo3(get, set)]
+    pub(crate) artifact_max_bytes: Option<u64>,
+    /// Most RPCs one connection to the guest agent may have in flight (default 256)
+    #[pyo3(get, set)]
+    pub(crate) agent_max_concurrency: Option<usize>,
+    /// Exec output chunks buffered per stream (default 1024)
+    #[pyo3(get, set)]
+    pub(crate) output_buffer_size: Option<usize>,
+    /// "drop_oldest" (default) or "block" to hold back the process when output is not read
+    #[pyo3(get, set)]
+    pub(crate) output_overflow: Option<String>,
+    /// Build the VM without network and console devices (pure-compute boxes)
+    #[pyo3(get, set)]
+    pub(crate) minimal_devices: bool,
+    /// Seed for a best-effort deterministic box: one vCPU, no network, fixed clock start, no ASLR
+    #[pyo3(get, set)]
+    pub(crate) deterministic_seed: Option<u64>,
+    /// Record every RPC to the guest agent to this file, for rpc_replay
+    #[pyo3(get, set)]
+    pub(crate) rpc_recording: Option<String>,
+    /// Answer RPCs from a file written with rpc_recording instead of starting a VM
+    #[pyo3(get, set)]
+    pub(crate) rpc_replay: Option<String>,
+    /// Shell commands run in order before the workload; the box fails to start if one fails
+    #[pyo3(get, set)]
+    pub(crate) setup_steps: Vec<String>,
+    /// Probe run in the box until it is ready: "tcp:PORT", "http:PORT/PATH" or a command
+    #[pyo3(get, set)]
+    pub(crate) readiness_probe: Option<String>,
+    /// Probe run in the box to check it still works, in the same forms
+    #[pyo3(get, set)]
+    pub(crate) liveness_probe: Option<String>,
+    /// Restart the box when its liveness probe fails, at most max_restarts times
+    #[pyo3(get, set)]
+    pub(crate) restart_unhealthy: bool,
+    #[pyo3(get, set)]
+    pub(crate) max_restarts: Option<u32>,
+    /// Named volume kept across boxes and mounted as the home directory
+    #[pyo3(get, set)]
+    pub(crate) home: Option<String>,
+    /// Where the home volume is mounted (default "/root")
+    #[pyo3(get, set)]
+    pub(crate) home_path: Option<String>,
 }
 
 #[pymethods]
@@ -76,10 +470,78 @@ impl PyBoxOptions {
         disk_size_gb=None,
         working_dir=None,
         env=vec![],
+        env_files=vec![],
         volumes=vec![],
+        network_mounts=vec![],
+        object_volumes=vec![],
+        encrypted_volumes=vec![],
         network=None,
         ports=vec![],
         auto_remove=None,
+        idle_timeout_secs=None,
+        ttl_secs=None,
+        labels=HashMap::new(),
+        gpu=None,
+        nested_virt=false,
+        x86_emulation=false,
+        memory_dedup=true,
+        low_memory_percent=10,
+        pressure_percent=20,
+        core_dump_max_bytes=268435456,
+        readahead=true,
+        package_cache=false,
+        package_cache_domain=None,
+        ssh_port=None,
+        ssh_authorized_keys=vec![],
+        forward_ssh_agent=false,
+        forward_git_credentials=false,
+        git_credential_hosts=vec![],
+        forward_x11=false,
+        clipboard="disabled".to_string(),
+        drop_dir=None,
+        max_drop_bytes=None,
+        watch_volumes=false,
+        lock_proxy=false,
+        etc_overlay=false,
+        masked_paths=vec![],
+        readonly_paths=vec![],
+        default_path_masks=false,
+        runtimes=vec![],
+        devtmpfs=false,
+        device_nodes=vec![],
+        fuse=false,
+        mdns=false,
+        ingress_port=None,
+        dns_network=None,
+        dns_services=vec![],
+        nested_containers=false,
+        docker_profile=false,
+        init_mode=None,
+        priority=None,
+        io_read_bps=None,
+        io_write_bps=None,
+        io_read_iops=None,
+        io_write_iops=None,
+        queue=None,
+        queue_max_parallel=1,
+        record_sessions=false,
+        max_artifacts=None,
+        artifact_max_age_secs=None,
+        artifact_max_bytes=None,
+        agent_max_concurrency=None,
+        output_buffer_size=None,
+        output_overflow=None,
+        minimal_devices=false,
+        deterministic_seed=None,
+        rpc_recording=None,
+        rpc_replay=None,
+        setup_steps=vec![],
+        readiness_probe=None,
+        liveness_probe=None,
+        restart_unhealthy=false,
+        max_restarts=None,
+        home=None,
+        home_path=None,
     ))]
     #[allow(clippy::too_many_arguments)]
     fn new(
@@ -90,10 +552,78 @@ impl PyBoxOptions {
         disk_size_gb: Option<u64>,
         working_dir: Option<String>,
         env: Vec<(String, String)>,
+        env_files: Vec<String>,
         volumes: Vec<PyVolumeSpec>,
+        network_mounts: Vec<PyNetworkMountSpec>,
+        object_volumes: Vec<PyObjectVolumeSpec>,
+        encrypted_volumes: Vec<PyEncryptedVolumeSpec>,
         network: Option<String>,
         ports: Vec<PyPortSpec>,
         auto_remove: Option<bool>,
+        idle_timeout_secs: Option<u64>,
+        ttl_secs: Option<u64>,
+        labels: HashMap<String, String>,
+        gpu: Option<String>,
+        nested_virt: bool,
+        x86_emulation: bool,
+        memory_dedup: bool,
+        low_memory_percent: u8,
+        pressure_percent: u8,
+        core_dump_max_bytes: u64,
+        readahead: bool,
+        package_cache: bool,
+        package_cache_domain: Option<String>,
+        ssh_port: Option<u16>,
+        ssh_authorized_keys: Vec<String>,
+        forward_ssh_agent: bool,
+        forward_git_credentials: bool,
+        git_credential_hosts: Vec<String>,
+        forward_x11: bool,
+        clipboard: String,
+        drop_dir: Option<String>,
+        max_drop_bytes: Option<u64>,
+        watch_volumes: bool,
+        lock_proxy: bool,
+        etc_overlay: bool,
+        masked_paths: Vec<String>,
+        readonly_paths: Vec<String>,
+        default_path_masks: bool,
+        runtimes: Vec<String>,
+        devtmpfs: bool,
+        device_nodes: Vec<String>,
+        fuse: bool,
+        mdns: bool,
+        ingress_port: Option<u16>,
+        dns_network: Option<String>,
+        dns_services: Vec<String>,
+        nested_containers: bool,
+        docker_profile: bool,
+        init_mode: Option<String>,
+        priority: Option<String>,
+        io_read_bps: Option<u64>,
+        io_write_bps: Option<u64>,
+        io_read_iops: Option<u64>,
+        io_write_iops: Option<u64>,
+        queue: Option<String>,
+        queue_max_parallel: usize,
+        record_sessions: bool,
+        max_artifacts: Option<usize>,
+        artifact_max_age_secs: Option<u64>,
+        artifact_max_bytes: Option<u64>,
+        agent_max_concurrency: Option<usize>,
+        output_buffer_size: Option<usize>,
+        output_overflow: Option<String>,
+        minimal_devices: bool,
+        deterministic_seed: Option<u64>,
+        rpc_recording: Option<String>,
+        rpc_replay: Option<String>,
+        setup_steps: Vec<String>,
+        readiness_probe: Option<String>,
+        liveness_probe: Option<String>,
+        restart_unhealthy: bool,
+        max_restarts: Option<u32>,
+        home: Option<String>,
+        home_path: Option<String>,
     ) -> Self {
         Self {
             image,
@@ -103,10 +633,78 @@ impl PyBoxOptions {
             disk_size_gb,
             working_dir,
             env,
+            env_files,
             volumes,
+            network_mounts,
+            object_volumes,
+            encrypted_volumes,
             network,
             ports,
             auto_remove,
+            idle_timeout_secs,
+            ttl_secs,
+            labels,
+            gpu,
+            nested_virt,
+            x86_emulation,
+            memory_dedup,
+            low_memory_percent,
+            pressure_percent,
+            core_dump_max_bytes,
+            readahead,
+            package_cache,
+            package_cache_domain,
+            ssh_port,
+            ssh_authorized_keys,
+            forward_ssh_agent,
+            forward_git_credentials,
+            git_credential_hosts,
+            forward_x11,
+            clipboard,
+            drop_dir,
+            max_drop_bytes,
+            watch_volumes,
+            lock_proxy,
+            etc_overlay,
+            masked_paths,
+            readonly_paths,
+            default_path_masks,
+            runtimes,
+            devtmpfs,
+            device_nodes,
+            fuse,
+            mdns,
+            ingress_port,
+            dns_network,
+            dns_services,
+            nested_containers,
+            docker_profile,
+            init_mode,
+            priority,
+            io_read_bps,
+            io_write_bps,
+            io_read_iops,
+            io_write_iops,
+            queue,
+            queue_max_parallel,
+            record_sessions,
+            max_artifacts,
+            artifact_max_age_secs,
+            artifact_max_bytes,
+            agent_max_concurrency,
+            output_buffer_size,
+            output_overflow,
+            minimal_devices,
+            deterministic_seed,
+            rpc_recording,
+            rpc_replay,
+            setup_steps,
+            readiness_probe,
+            liveness_probe,
+            restart_unhealthy,
+            max_restarts,
+            home,
+            home_path,
         }
     }
 
@@ -130,6 +728,22 @@ impl From<PyBoxOptions> for BoxOptions {
         };
 
         let ports = py_opts.ports.into_iter().map(PortSpec::from).collect();
+        let network_mounts = py_opts.network_mounts.into_iter().map(|m| m.0).collect();
+        let object_volumes = py_opts.object_volumes.into_iter().map(|v| v.0).collect();
+        let encrypted_volumes = py_opts.encrypted_volumes.into_iter().map(|v| v.0).collect();
+
+        let clipboard = match py_opts.clipboard.to_ascii_lowercase().as_str() {
+            "host_to_box" => ClipboardPolicy::HostToBox,
+            "box_to_host" => ClipboardPolicy::BoxToHost,
+            "bidirectional" => ClipboardPolicy::Bidirectional,
+            _ => ClipboardPolicy::Disabled,
+        };
+        let sharing = (clipboard != ClipboardPolicy::Disabled || py_opts.drop_dir.is_some())
+            .then_some(SharingOptions {
+                clipboard,
+                drop_dir: py_opts.drop_dir,
+                max_drop_bytes: py_opts.max_drop_bytes,
+            });
 
         // Convert image/rootfs_path to RootfsSpec
         let rootfs = match &py_opts.rootfs_path {
@@ -143,16 +757,134 @@ impl From<PyBoxOptions> for BoxOptions {
             }
         };
 
+        let gpu = py_opts.gpu.map(|gpu| GpuSpec::VirtioGpu {
+            venus: gpu.eq_ignore_ascii_case("venus"),
+        });
+
         let mut opts = BoxOptions {
             cpus: py_opts.cpus,
             memory_mib: py_opts.memory_mib,
+            priority: match py_opts.priority.as_deref() {
+                Some("realtime") => BoxPriority::Realtime,
+                Some("high") => BoxPriority::High,
+                Some("batch") => BoxPriority::Batch,
+                _ => BoxPriority::Normal,
+            },
+            io_limits: IoLimits {
+                read_bps: py_opts.io_read_bps,
+                write_bps: py_opts.io_write_bps,
+                read_iops: py_opts.io_read_iops,
+                write_iops: py_opts.io_write_iops,
+            },
+            queue: py_opts
+                .queue
+                .map(|name| BatchQueue::new(name, py_opts.queue_max_parallel)),
             disk_size_gb: py_opts.disk_size_gb,
             working_dir: py_opts.working_dir,
             env: py_opts.env,
+            env_files: py_opts.env_files.into_iter().map(Into::into).collect(),
             rootfs,
             volumes,
+            network_mounts,
+            object_volumes,
+            encrypted_volumes,
             network,
             ports,
+            idle_timeout_secs: py_opts.idle_timeout_secs,
+            ttl_secs: py_opts.ttl_secs,
+            labels: py_opts.labels,
+            gpu,
+            nested_virt: py_opts.nested_virt,
+            x86_emulation: py_opts.x86_emulation,
+            memory_dedup: py_opts.memory_dedup,
+            low_memory_percent: py_opts.low_memory_percent,
+            pressure_percent: py_opts.pressure_percent,
+            core_dump_max_bytes: py_opts.core_dump_max_bytes,
+            readahead: py_opts.readahead,
+            package_cache: py_opts.package_cache,
+            package_cache_domain: py_opts.package_cache_domain,
+            ssh: py_opts.ssh_port.map(|host_port| SshOptions {
+                authorized_keys: py_opts.ssh_authorized_keys,
+                host_port,
+            }),
+            forward_ssh_agent: py_opts.forward_ssh_agent,
+            forward_git_credentials: py_opts.forward_git_credentials,
+            git_credential_hosts: py_opts.git_credential_hosts,
+            forward_x11: py_opts.forward_x11,
+            sharing,
+            watch_volumes: py_opts.watch_volumes,
+            lock_proxy: py_opts.lock_proxy,
+            etc_overlay: py_opts.etc_overlay,
+            masked_paths: py_opts.masked_paths,
+            readonly_paths: py_opts.readonly_paths,
+            runtimes: py_opts
+                .runtimes
+                .iter()
+                .filter_map(|r| match r.to_ascii_lowercase().as_str() {
+                    "python" => Some(RuntimeProfile::Python),
+                    "node" => Some(RuntimeProfile::Node),
+                    "go" => Some(RuntimeProfile::Go),
+                    "rust" => Some(RuntimeProfile::Rust),
+                    _ => None,
+                })
+                .collect(),
+            fuse: py_opts.fuse,
+            mdns: py_opts.mdns,
+            ingress_port: py_opts.ingress_port,
+            dns_network: py_opts.dns_network,
+            dns_services: py_opts.dns_services,
+            nested_containers: py_opts.nested_containers,
+            init_mode: match py_opts.init_mode.as_deref() {
+                Some("systemd") => InitMode::Systemd,
+                _ => InitMode::Entrypoint,
+            },
+            record_sessions: py_opts.record_sessions,
+            artifact_retention: ArtifactRetention {
+                max_count: py_opts.max_artifacts,
+                max_age_secs: py_opts.artifact_max_age_secs,
+                max_bytes: py_opts.artifact_max_bytes,
+            },
+            agent_max_concurrency: py_opts.agent_max_concurrency,
+            rpc_recording: py_opts.rpc_recording.map(Into::into),
+            rpc_replay: py_opts.rpc_replay.map(Into::into),
+            output_buffer: stream_buffer(
+                py_opts.output_buffer_size,
+                py_opts.output_overflow.as_deref(),
+            ),
+            device_policy: DevicePolicy {
+                devtmpfs: py_opts.devtmpfs,
+                nodes: py_opts
+                    .device_nodes
+                    .into_iter()
+                    .map(DeviceNodeSpec::new)
+                    .collect(),
+            },
+            setup_steps: py_opts
+                .setup_steps
+                .iter()
+                .enumerate()
+                .map(|(i, script)| {
+                    let mut step = SetupStep::new(format!("setup-{}", i + 1), "/bin/sh");
+                    step.args = vec!["-c".to_string(), script.clone()];
+                    step
+                })
+                .collect(),
+            readiness_probe: py_opts.readiness_probe.as_deref().map(probe),
+            liveness_probe: py_opts.liveness_probe.as_deref().map(probe),
+            unhealthy_policy: if py_opts.restart_unhealthy {
+                UnhealthyPolicy::Restart {
+                    max_restarts: py_opts.max_restarts,
+                }
+            } else {
+                UnhealthyPolicy::Ignore
+            },
+            home_volume: py_opts.home.map(|name| {
+                let mut home = HomeVolume::new(name);
+                if let Some(path) = py_opts.home_path {
+                    home.path = path;
+                }
+                home
+            }),
             ..Default::default()
         };
 
@@ -160,6 +892,18 @@ impl From<PyBoxOptions> for BoxOptions {
             opts.auto_remove = auto_remove;
         }
 
+        if py_opts.docker_profile {
+            opts = opts.with_docker_profile();
+        }
+        if py_opts.minimal_devices {
+            opts = opts.with_minimal_devices();
+        }
+        if py_opts.default_path_masks {
+            opts = opts.with_default_path_masks();
+        }
+        if let Some(seed) = py_opts.deterministic_seed {
+            opts = opts.with_deterministic(seed);
+        }
         opts
     }
 }
@@ -169,6 +913,11 @@ pub(crate) struct PyVolumeSpec {
     host: String,
     guest: String,
     read_only: bool,
+    quota_bytes: Option<u64>,
+    owner: Option<(u32, u32)>,
+    hidden: Vec<String>,
+    tag: Option<String>,
+    optional: bool,
 }
 
 impl From<PyVolumeSpec> for VolumeSpec {
@@ -177,6 +926,11 @@ impl From<PyVolumeSpec> for VolumeSpec {
             host_path: v.host,
             guest_path: v.guest,
             read_only: v.read_only,
+            quota_bytes: v.quota_bytes,
+            owner: v.owner.map(|(uid, gid)| VolumeOwner { uid, gid }),
+            hidden: v.hidden,
+            tag: v.tag,
+            optional: v.optional,
         }
     }
 }
@@ -213,6 +967,11 @@ impl<'a, 'py> pyo3::FromPyObject<'a, 'py> for PyVolumeSpec {
                 host,
                 guest,
                 read_only,
+                quota_bytes: None,
+                owner: None,
+                hidden: Vec::new(),
+                tag: None,
+                optional: false,
             });
         }
 
@@ -245,10 +1004,42 @@ impl<'a, 'py> pyo3::FromPyObject<'a, 'py> for PyVolumeSpec {
                 false
             };
 
+            let quota_bytes: Option<u64> = match d.get_item("quota_bytes") {
+                Ok(Some(v)) => v.extract()?,
+                _ => None,
+            };
+
+            // (uid, gid) the host directory's owner appears as in the box
+            let owner: Option<(u32, u32)> = match d.get_item("owner") {
+                Ok(Some(v)) => v.extract()?,
+                _ => None,
+            };
+
+            let hidden: Vec<String> = match d.get_item("hidden") {
+                Ok(Some(v)) => v.extract()?,
+                _ => Vec::new(),
+            };
+
+            let tag: Option<String> = match d.get_item("tag") {
+                Ok(Some(v)) => v.extract()?,
+                _ => None,
+            };
+
+            // Boot without the volume if the guest cannot mount it
+            let optional: bool = match d.get_item("optional") {
+                Ok(Some(v)) => v.extract()?,
+                _ => false,
+            };
+
             return Ok(PyVolumeSpec {
                 host,
                 guest,
                 read_only,
+                quota_bytes,
+                owner,
+                hidden,
+                tag,
+                optional,
             });
         }
 
@@ -258,6 +1049,184 @@ impl<'a, 'py> pyo3::FromPyObject<'a, 'py> for PyVolumeSpec {
     }
 }
 
+/// A network mount dict: `type` ("nfs" or "cifs"), `source`, `guest`,
+/// and optionally `read_only`, `options`, `username`, `password_env` and
+/// `domain`.
+#[derive(Clone, Debug)]
+pub(crate) struct PyNetworkMountSpec(NetworkMountSpec);
+
+impl<'a, 'py> pyo3::FromPyObject<'a, 'py> for PyNetworkMountSpec {
+    type Error = PyErr;
+
+    fn extract(ob: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        let obj = ob.to_owned();
+        let d = obj
+            .cast::<PyDict>()
+            .map_err(|_| PyRuntimeError::new_err("network_mounts entries must be dicts"))?;
+        let required = |key: &str| -> PyResult<String> {
+            match d.get_item(key) {
+                Ok(Some(v)) => v.extract(),
+                _ => Err(PyRuntimeError::new_err(format!(
+                    "network mount dict missing {}",
+                    key
+                ))),
+            }
+        };
+        let optional = |key: &str| -> PyResult<Option<String>> {
+            match d.get_item(key) {
+                Ok(Some(v)) => v.extract(),
+                _ => Ok(None),
+            }
+        };
+
+        let filesystem = match required("type")?.to_ascii_lowercase().as_str() {
+            "nfs" => NetworkFilesystem::Nfs,
+            "cifs" | "smb" => NetworkFilesystem::Cifs,
+            other => {
+                return Err(PyRuntimeError::new_err(format!(
+                    "unknown network mount type '{}' (expected nfs or cifs)",
+                    other
+                )));
+            }
+        };
+        let read_only: bool = match d.get_item("read_only") {
+            Ok(Some(v)) => v.extract()?,
+            _ => false,
+        };
+        let credentials = match optional("username")? {
+            Some(username) => Some(MountCredentials {
+                username,
+                password_env: required("password_env")?,
+                domain: optional("domain")?,
+            }),
+            None => None,
+        };
+
+        Ok(PyNetworkMountSpec(NetworkMountSpec {
+            filesystem,
+            source: required("source")?,
+            guest_path: required("guest")?,
+            read_only,
+            options: optional("options")?,
+            credentials,
+        }))
+    }
+}
+
+/// An object volume dict: `endpoint`, `bucket`, `guest`, and optionally
+/// `prefix`, `region`, `write_back`, `access_key_env`, `secret_key_env`
+/// and `session_token_env`.
+#[derive(Clone, Debug)]
+pub(crate) struct PyObjectVolumeSpec(ObjectVolumeSpec);
+
+impl<'a, 'py> pyo3::FromPyObject<'a, 'py> for PyObjectVolumeSpec {
+    type Error = PyErr;
+
+    fn extract(ob: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        let obj = ob.to_owned();
+        let d = obj
+            .cast::<PyDict>()
+            .map_err(|_| PyRuntimeError::new_err("object_volumes entries must be dicts"))?;
+        let required = |key: &str| -> PyResult<String> {
+            match d.get_item(key) {
+                Ok(Some(v)) => v.extract(),
+                _ => Err(PyRuntimeError::new_err(format!(
+                    "object volume dict missing {}",
+                    key
+                ))),
+            }
+        };
+        let optional = |key: &str| -> PyResult<Option<String>> {
+            match d.get_item(key) {
+                Ok(Some(v)) => v.extract(),
+                _ => Ok(None),
+            }
+        };
+
+        let write_back: bool = match d.get_item("write_back") {
+            Ok(Some(v)) => v.extract()?,
+            _ => false,
+        };
+        let credentials = match optional("access_key_env")? {
+            Some(access_key_env) => Some(ObjectCredentials {
+                access_key_env,
+                secret_key_env: required("secret_key_env")?,
+                session_token_env: optional("session_token_env")?,
+            }),
+            None => None,
+        };
+
+        Ok(PyObjectVolumeSpec(ObjectVolumeSpec {
+            endpoint: required("endpoint")?,
+            bucket: required("bucket")?,
+            prefix: optional("prefix")?.unwrap_or_default(),
+            region: optional("region")?,
+            guest_path: required("guest")?,
+            write_back,
+            credentials,
+        }))
+    }
+}
+
+/// An encrypted volume dict: `name`, `guest`, one of `key_file`,
+/// `keyring` (a `(service, account)` pair) or `key_command` (a list), and
+/// optionally `size_gb` and `read_only`.
+#[derive(Clone, Debug)]
+pub(crate) struct PyEncryptedVolumeSpec(EncryptedVolumeSpec);
+
+impl<'a, 'py> pyo3::FromPyObject<'a, 'py> for PyEncryptedVolumeSpec {
+    type Error = PyErr;
+
+    fn extract(ob: Borrowed<'a, 'py, PyAny>) -> PyResult<Self> {
+        let obj = ob.to_owned();
+        let d = obj
+            .cast::<PyDict>()
+            .map_err(|_| PyRuntimeError::new_err("encrypted_volumes entries must be dicts"))?;
+        let required = |key: &str| -> PyResult<String> {
+            match d.get_item(key) {
+                Ok(Some(v)) => v.extract(),
+                _ => Err(PyRuntimeError::new_err(format!(
+                    "encrypted volume dict missing {}",
+                    key
+                ))),
+            }
+        };
+
+        let key = if let Ok(Some(path)) = d.get_item("key_file") {
+            VolumeKeySource::File {
+                path: path.extract::<String>()?.into(),
+            }
+        } else if let Ok(Some(keyring)) = d.get_item("keyring") {
+            let (service, account): (String, String) = keyring.extract()?;
+            VolumeKeySource::Keyring { service, account }
+        } else if let Ok(Some(command)) = d.get_item("key_command") {
+            VolumeKeySource::Plugin {
+                command: command.extract()?,
+            }
+        } else {
+            return Err(PyRuntimeError::new_err(
+                "encrypted volume dict needs key_file, keyring or key_command",
+            ));
+        };
+        let size_gb: u64 = match d.get_item("size_gb") {
+            Ok(Some(v)) => v.extract()?,
+            _ => 10,
+        };
+        let read_only: bool = match d.get_item("read_only") {
+            Ok(Some(v)) => v.extract()?,
+            _ => false,
+        };
+
+        Ok(PyEncryptedVolumeSpec(EncryptedVolumeSpec {
+            name: required("name")?,
+            guest_path: required("guest")?,
+            size_gb,
+            key,
+            read_only,
+        }))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct PyPortSpec {
     host: Option<u16>,
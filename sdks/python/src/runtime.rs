@@ -1,11 +1,18 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use boxlite::BoxliteRuntime;
+use boxlite::{
+    BakeSpec, BoxFilter, BoxLock, BoxOptions, BoxliteRuntime, ConfigLoader, ExportOptions,
+    SystemPruneOptions,
+};
 use pyo3::prelude::*;
 
 use crate::box_handle::PyBox;
-use crate::info::PyBoxInfo;
-use crate::metrics::PyRuntimeMetrics;
+use crate::info::{
+    PyBoxEvent, PyBoxInfo, PyDiskUsage, PyDryRunReport, PyExecUsage, PyOperationProgress,
+    PyPruneReport, PySnapshotInfo,
+};
+use crate::metrics::{PyCapacityReport, PyRuntimeMetrics};
 use crate::options::{PyBoxOptions, PyOptions};
 use crate::util::map_err;
 
@@ -38,6 +45,18 @@ impl PyBoxlite {
         BoxliteRuntime::init_default_runtime(options.into()).map_err(map_err)
     }
 
+    /// Effective runtime configuration, one `key = value  # source` line
+    /// per key. `overrides` are `key=value` pairs applied last.
+    #[staticmethod]
+    #[pyo3(signature = (overrides=None))]
+    fn resolved_config(overrides: Option<Vec<String>>) -> PyResult<String> {
+        let mut loader = ConfigLoader::new();
+        for pair in overrides.unwrap_or_default() {
+            loader = loader.set_pair(&pair).map_err(map_err)?;
+        }
+        Ok(loader.resolve().map_err(map_err)?.to_string())
+    }
+
     #[pyo3(signature = (options, name=None))]
     fn create(&self, options: PyBoxOptions, name: Option<String>) -> PyResult<PyBox> {
         let handle = self.runtime.create(options.into(), name).map_err(map_err)?;
@@ -47,9 +66,18 @@ impl PyBoxlite {
         })
     }
 
-    #[pyo3(signature = (_state=None))]
-    fn list_info(&self, _state: Option<String>) -> PyResult<Vec<PyBoxInfo>> {
-        let infos = self.runtime.list_info().map_err(map_err)?;
+    /// List boxes, newest first.
+    ///
+    /// Args:
+    ///     filters: Optional filter expressions, e.g. ["label=team=ml", "status=running"]
+    #[pyo3(signature = (_state=None, filters=None))]
+    fn list_info(
+        &self,
+        _state: Option<String>,
+        filters: Option<Vec<String>>,
+    ) -> PyResult<Vec<PyBoxInfo>> {
+        let filter = BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?;
+        let infos = self.runtime.list_info_filtered(&filter).map_err(map_err)?;
 
         Ok(infos.into_iter().map(PyBoxInfo::from).collect())
     }
@@ -88,11 +116,74 @@ impl PyBoxlite {
         Ok(py_box)
     }
 
+    /// The ingress CA certificate to trust, if ingress is enabled.
+    fn ingress_ca(&self) -> Option<String> {
+        self.runtime
+            .ingress_ca()
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
     fn metrics(&self) -> PyResult<PyRuntimeMetrics> {
         let metrics = self.runtime.metrics();
         Ok(PyRuntimeMetrics::from(metrics))
     }
 
+    /// Check the host can run boxes; returns the report as text.
+    fn doctor(&self) -> String {
+        self.runtime.doctor().to_string()
+    }
+
+    /// Boot a throwaway box from `image` and time boot, exec round trips
+    /// and virtio-fs throughput; returns the report as text.
+    #[pyo3(signature = (image="alpine:latest".to_string()))]
+    fn bench<'py>(&self, py: Python<'py>, image: String) -> PyResult<Bound<'py, PyAny>> {
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let report = runtime.bench(&image).await.map_err(map_err)?;
+            Ok(report.to_string())
+        })
+    }
+
+    /// Boot a box from the spec file's image, run its steps, and save the
+    /// result as an image tagged `tag` that boxes can be created from.
+    fn bake<'py>(
+        &self,
+        py: Python<'py>,
+        spec: PathBuf,
+        tag: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let spec = BakeSpec::from_file(&spec).map_err(map_err)?;
+            let baked = runtime.bake(&spec, &tag).await.map_err(map_err)?;
+            Ok(baked.tag)
+        })
+    }
+
+    /// Tags of the baked images matching the filters.
+    ///
+    /// Args:
+    ///     filters: Optional filter expressions, e.g. ["label=team=ml"]
+    #[pyo3(signature = (filters=None))]
+    fn baked_images(&self, filters: Option<Vec<String>>) -> PyResult<Vec<String>> {
+        let filter = BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?;
+        let images = self
+            .runtime
+            .baked_images_filtered(&filter)
+            .map_err(map_err)?;
+        Ok(images.into_iter().map(|image| image.tag).collect())
+    }
+
+    /// Forget a baked image; returns whether there was one.
+    fn remove_baked_image(&self, tag: &str) -> PyResult<bool> {
+        self.runtime.remove_baked_image(tag).map_err(map_err)
+    }
+
+    /// Host CPUs and memory against what running boxes have committed.
+    fn capacity(&self) -> PyCapacityReport {
+        PyCapacityReport::from(self.runtime.capacity())
+    }
+
     /// Remove a box by ID or name.
     ///
     /// Args:
@@ -112,6 +203,395 @@ impl PyBoxlite {
         })
     }
 
+    /// Lifecycle events for boxes matching the filters, oldest first.
+    ///
+    /// Args:
+    ///     since: Optional RFC 3339 timestamp; only events at or after it are returned
+    ///     filters: Optional filter expressions, e.g. ["label=team=ml"]
+    #[pyo3(signature = (since=None, filters=None))]
+    fn events(
+        &self,
+        since: Option<String>,
+        filters: Option<Vec<String>>,
+    ) -> PyResult<Vec<PyBoxEvent>> {
+        let since = parse_since(since)?;
+        let filter = BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?;
+        let events = self.runtime.events(since, &filter).map_err(map_err)?;
+        Ok(events.into_iter().map(PyBoxEvent::from).collect())
+    }
+
+    /// Image pulls, snapshot restores and copies in progress, oldest first.
+    ///
+    /// Poll it to draw progress bars: `done` of `total` (None until known)
+    /// is bytes, or disks for snapshot restores.
+    fn operations(&self) -> Vec<PyOperationProgress> {
+        self.runtime
+            .operations()
+            .into_iter()
+            .map(PyOperationProgress::from)
+            .collect()
+    }
+
+    /// Cancel a running operation by ID; the call running it raises.
+    fn cancel_operation(&self, id: String) -> PyResult<()> {
+        self.runtime.cancel_operation(&id).map_err(map_err)
+    }
+
+    /// Resource usage of executions in boxes matching the filters, oldest first.
+    ///
+    /// Args:
+    ///     since: Optional RFC 3339 timestamp; only executions that finished at or after it
+    ///     filters: Optional filter expressions, e.g. ["label=team=ml"]
+    #[pyo3(signature = (since=None, filters=None))]
+    fn usage(
+        &self,
+        since: Option<String>,
+        filters: Option<Vec<String>>,
+    ) -> PyResult<Vec<PyExecUsage>> {
+        let since = parse_since(since)?;
+        let filter = BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?;
+        let records = self.runtime.usage(since, &filter).map_err(map_err)?;
+        Ok(records.into_iter().map(PyExecUsage::from).collect())
+    }
+
+    /// The same records as `usage()`, as CSV with a header row.
+    #[pyo3(signature = (since=None, filters=None))]
+    fn usage_csv(&self, since: Option<String>, filters: Option<Vec<String>>) -> PyResult<String> {
+        let since = parse_since(since)?;
+        let filter = BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?;
+        let records = self.runtime.usage(since, &filter).map_err(map_err)?;
+        let mut csv = Vec::new();
+        boxlite::write_usage_csv(&records, &mut csv).map_err(map_err)?;
+        String::from_utf8(csv).map_err(map_err)
+    }
+
+    /// Remove every box matching the filters.
+    ///
+    /// Args:
+    ///     filters: Filter expressions, e.g. ["label=team=ml"]
+    ///     force: If True, stop matching boxes first if running (default: False)
+    ///
+    /// Returns:
+    ///     IDs of the removed boxes
+    #[pyo3(signature = (filters, force=false))]
+    fn remove_matching<'py>(
+        &self,
+        py: Python<'py>,
+        filters: Vec<String>,
+        force: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let filter = BoxFilter::parse(filters).map_err(map_err)?;
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let removed = runtime
+                .remove_matching(&filter, force)
+                .await
+                .map_err(map_err)?;
+            Ok(removed
+                .into_iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Remove every stopped box matching the filters (all stopped boxes by default).
+    ///
+    /// Returns:
+    ///     IDs of the removed boxes
+    #[pyo3(signature = (filters=None))]
+    fn prune<'py>(
+        &self,
+        py: Python<'py>,
+        filters: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let filter = BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?;
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let pruned = runtime.prune(&filter).await.map_err(map_err)?;
+            Ok(pruned
+                .into_iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Report disk space used by images, snapshots, the package cache and each box.
+    ///
+    /// Returns:
+    ///     DiskUsage with per-category and per-box sizes and what a prune could reclaim
+    fn disk_usage<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let usage = runtime.disk_usage().await.map_err(map_err)?;
+            Ok(PyDiskUsage::from(usage))
+        })
+    }
+
+    /// Remove stopped boxes matching the filters and, with images=True, unused images.
+    ///
+    /// Args:
+    ///     filters: Box filters (all stopped boxes by default)
+    ///     images: Also remove cached images no remaining box uses
+    ///     dry_run: Report what would be removed without removing it
+    ///
+    /// Returns:
+    ///     PruneReport with the removed boxes, images and reclaimed bytes
+    #[pyo3(signature = (filters=None, images=false, dry_run=false))]
+    fn system_prune<'py>(
+        &self,
+        py: Python<'py>,
+        filters: Option<Vec<String>>,
+        images: bool,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = SystemPruneOptions {
+            filter: BoxFilter::parse(filters.unwrap_or_default()).map_err(map_err)?,
+            images,
+            dry_run,
+        };
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let report = runtime.system_prune(&options).await.map_err(map_err)?;
+            Ok(PyPruneReport::from(report))
+        })
+    }
+
+    /// Resolve and validate box options without creating or booting anything.
+    ///
+    /// Args:
+    ///     options: Box options to check
+    ///     name: Name the box would get
+    ///
+    /// Returns:
+    ///     DryRunReport with the resolved spec and every problem found
+    #[pyo3(signature = (options, name=None))]
+    fn dry_run<'py>(
+        &self,
+        py: Python<'py>,
+        options: PyBoxOptions,
+        name: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options: BoxOptions = options.into();
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let report = runtime
+                .dry_run(&options, name.as_deref())
+                .await
+                .map_err(map_err)?;
+            PyDryRunReport::try_from(report)
+        })
+    }
+
+    /// Validate a TOML box spec file, as dry_run does for options.
+    ///
+    /// Args:
+    ///     path: Spec file; keys are BoxOptions fields, missing ones default
+    ///     name: Name the box would get
+    ///
+    /// Returns:
+    ///     DryRunReport with the resolved spec and every problem found
+    #[pyo3(signature = (path, name=None))]
+    fn validate_spec<'py>(
+        &self,
+        py: Python<'py>,
+        path: PathBuf,
+        name: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = BoxOptions::from_spec_file(&path).map_err(map_err)?;
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let report = runtime
+                .dry_run(&options, name.as_deref())
+                .await
+                .map_err(map_err)?;
+            PyDryRunReport::try_from(report)
+        })
+    }
+
+    /// Lock a TOML box spec file, pinning its images to digests.
+    ///
+    /// Args:
+    ///     path: Spec file; keys are BoxOptions fields, missing ones default
+    ///     lock_path: Lockfile to write (default: the spec path with a .lock extension)
+    ///
+    /// Returns:
+    ///     Path of the written lockfile
+    #[pyo3(signature = (path, lock_path=None))]
+    fn lock_spec<'py>(
+        &self,
+        py: Python<'py>,
+        path: PathBuf,
+        lock_path: Option<PathBuf>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = BoxOptions::from_spec_file(&path).map_err(map_err)?;
+        let lock_path = lock_path.unwrap_or_else(|| BoxLock::path_for(&path));
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let lock = runtime.lock(&options).await.map_err(map_err)?;
+            lock.write(&lock_path).map_err(map_err)?;
+            Ok(lock_path)
+        })
+    }
+
+    /// Create a box from a TOML spec file, refusing it if it drifted from its lock.
+    ///
+    /// Args:
+    ///     path: Spec file locked with lock_spec()
+    ///     lock_path: Lockfile (default: the spec path with a .lock extension)
+    ///     name: Optional unique name for the box
+    ///
+    /// Returns:
+    ///     Box handle, created from the locked image digest
+    #[pyo3(signature = (path, lock_path=None, name=None))]
+    fn create_locked<'py>(
+        &self,
+        py: Python<'py>,
+        path: PathBuf,
+        lock_path: Option<PathBuf>,
+        name: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = BoxOptions::from_spec_file(&path).map_err(map_err)?;
+        let lock = BoxLock::read(&lock_path.unwrap_or_else(|| BoxLock::path_for(&path)))
+            .map_err(map_err)?;
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let handle = runtime
+                .create_locked(options, &lock, name)
+                .await
+                .map_err(map_err)?;
+            Ok(PyBox {
+                handle: Arc::new(handle),
+            })
+        })
+    }
+
+    /// Remove boxes whose TTL expired or whose creator exited without removing them.
+    ///
+    /// Returns:
+    ///     IDs of the removed boxes
+    fn reap(&self) -> PyResult<Vec<String>> {
+        let reaped = self.runtime.reap().map_err(map_err)?;
+        Ok(reaped.into_iter().map(|id| id.to_string()).collect())
+    }
+
+    /// Clone a box into a new, independent box.
+    ///
+    /// A running source keeps running; its disks are copied with the rootfs
+    /// frozen. The clone gets a new hostname and machine ID.
+    ///
+    /// Args:
+    ///     id_or_name: Either a box ID (ULID) or user-defined name of the source box
+    ///     name: Optional name for the new box
+    ///
+    /// Returns:
+    ///     Handle to the cloned box (stopped; starts on first use)
+    #[pyo3(signature = (id_or_name, name=None))]
+    fn clone_box<'py>(
+        &self,
+        py: Python<'py>,
+        id_or_name: String,
+        name: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let runtime = Arc::clone(&self.runtime);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let handle = runtime
+                .clone_box(&id_or_name, name)
+                .await
+                .map_err(map_err)?;
+            Ok(PyBox {
+                handle: Arc::new(handle),
+            })
+        })
+    }
+
+    /// Export a stopped box as a bundle file another host can import.
+    ///
+    /// Args:
+    ///     id_or_name: Either a box ID (ULID) or user-defined name
+    ///     path: File to write the bundle (zstd-compressed tar) to
+    ///     include_volumes: Also bundle the contents of the box's volume directories
+    ///     snapshot: Export the box as of this snapshot (ID or name)
+    #[pyo3(signature = (id_or_name, path, include_volumes=false, snapshot=None))]
+    fn export_box(
+        &self,
+        id_or_name: String,
+        path: String,
+        include_volumes: bool,
+        snapshot: Option<String>,
+    ) -> PyResult<()> {
+        let options = ExportOptions {
+            include_volumes,
+            snapshot,
+        };
+        let file = std::fs::File::create(&path)?;
+        self.runtime
+            .export_box(&id_or_name, &options, std::io::BufWriter::new(file))
+            .map_err(map_err)
+    }
+
+    /// Write a diagnostics bundle for a box, to attach to a bug report.
+    ///
+    /// Args:
+    ///     id_or_name: Either a box ID (ULID) or user-defined name
+    ///     path: File to write the bundle (gzipped tar) to
+    fn diagnose(&self, id_or_name: String, path: String) -> PyResult<()> {
+        let file = std::fs::File::create(&path)?;
+        self.runtime
+            .diagnose(&id_or_name, std::io::BufWriter::new(file))
+            .map_err(map_err)
+    }
+
+    /// Create a stopped box from a bundle written by export_box().
+    ///
+    /// Args:
+    ///     path: Bundle file
+    ///     name: Name for the box (default: the exported box's name)
+    ///
+    /// Returns:
+    ///     Handle to the imported box (stopped; starts on first use)
+    #[pyo3(signature = (path, name=None))]
+    fn import_box(&self, path: String, name: Option<String>) -> PyResult<PyBox> {
+        let file = std::fs::File::open(&path)?;
+        let handle = self
+            .runtime
+            .import_box(std::io::BufReader::new(file), name)
+            .map_err(map_err)?;
+
+        Ok(PyBox {
+            handle: Arc::new(handle),
+        })
+    }
+
+    /// Snapshot a stopped box's disks.
+    ///
+    /// Args:
+    ///     id_or_name: Either a box ID (ULID) or user-defined name
+    ///     name: Snapshot name (unique per box)
+    fn snapshot(&self, id_or_name: String, name: String) -> PyResult<PySnapshotInfo> {
+        let info = self.runtime.snapshot(&id_or_name, &name).map_err(map_err)?;
+        Ok(PySnapshotInfo::from(info))
+    }
+
+    /// List a box's snapshots, oldest first.
+    fn list_snapshots(&self, id_or_name: String) -> PyResult<Vec<PySnapshotInfo>> {
+        let infos = self.runtime.list_snapshots(&id_or_name).map_err(map_err)?;
+        Ok(infos.into_iter().map(PySnapshotInfo::from).collect())
+    }
+
+    /// Restore a stopped box to a snapshot (by snapshot ID or name).
+    fn restore_snapshot(&self, id_or_name: String, snapshot: String) -> PyResult<()> {
+        self.runtime
+            .restore_snapshot(&id_or_name, &snapshot)
+            .map_err(map_err)
+    }
+
+    /// Remove a snapshot (by snapshot ID or name).
+    fn remove_snapshot(&self, id_or_name: String, snapshot: String) -> PyResult<()> {
+        self.runtime
+            .remove_snapshot(&id_or_name, &snapshot)
+            .map_err(map_err)
+    }
+
     fn close(&self) -> PyResult<()> {
         Ok(())
     }
@@ -133,3 +613,13 @@ impl PyBoxlite {
         "Boxlite(open=true)".to_string()
     }
 }
+
+fn parse_since(since: Option<String>) -> PyResult<Option<chrono::DateTime<chrono::Utc>>> {
+    since
+        .map(|s| {
+            chrono::DateTime::parse_from_rfc3339(&s)
+                .map(|t| t.with_timezone(&chrono::Utc))
+                .map_err(map_err)
+        })
+        .transpose()
+}